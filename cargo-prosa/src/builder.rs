@@ -3,15 +3,19 @@
 //! Builder contain the strucure of the ProSA.toml file useful to build a ProSA.
 
 use std::{
+    collections::HashSet,
     fmt, fs,
     io::{self, Write},
     path::Path,
 };
 
 use serde::{Deserialize, Serialize};
-use toml_edit::{Item, Table, Value};
+use toml_edit::{DocumentMut, InlineTable, Item, Table, Value};
 
-use crate::cargo::{CargoMetadata, ComponentVersion};
+use crate::{
+    cargo::{CargoMetadata, ComponentKind, ComponentVersion},
+    upgrade::SKELETON_VERSION,
+};
 
 /// Descriptor of ProSA main configuration
 ///
@@ -24,6 +28,15 @@ pub struct MainDesc {
     pub main: String,
     /// Name of the TVF use for ProSA (`prosa_utils::msg::simple_string_tvf::SimpleStringTvf` by default)
     pub tvf: String,
+    /// Reject unknown configuration keys of the ProSA settings, and report them with suggestions (`false` by default)
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub strict: bool,
+    /// Duration in seconds to wait for the dependencies of a processor to be ready before starting it (`30` by default)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub startup_timeout: Option<u64>,
+    /// Version of the skeleton generated by cargo-prosa, used to upgrade the project (`0` if not recorded)
+    #[serde(default)]
+    pub skeleton: u32,
 }
 
 impl Default for MainDesc {
@@ -31,6 +44,109 @@ impl Default for MainDesc {
         MainDesc {
             main: String::from("prosa::core::main::MainProc"),
             tvf: String::from("prosa_utils::msg::simple_string_tvf::SimpleStringTvf"),
+            strict: false,
+            startup_timeout: None,
+            skeleton: SKELETON_VERSION,
+        }
+    }
+}
+
+/// Version of a ProSA component recorded in the ProSA.toml file when it was added
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ComponentLock {
+    /// Name of the component's crate
+    #[serde(rename = "crate")]
+    pub crate_name: String,
+    /// Version of the component's crate
+    pub version: String,
+}
+
+impl From<&ComponentVersion<'_>> for ComponentLock {
+    fn from(component_version: &ComponentVersion<'_>) -> Self {
+        ComponentLock {
+            crate_name: component_version.crate_name.clone(),
+            version: component_version.version.clone(),
+        }
+    }
+}
+
+impl fmt::Display for ComponentLock {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}[{}]", self.crate_name, self.version)
+    }
+}
+
+impl TryFrom<&Item> for ComponentLock {
+    type Error = &'static str;
+
+    fn try_from(item: &Item) -> Result<Self, Self::Error> {
+        let (crate_name, version) = match item {
+            Item::Value(Value::InlineTable(table)) => (
+                table.get("crate").and_then(|v| v.as_str()),
+                table.get("version").and_then(|v| v.as_str()),
+            ),
+            Item::Table(table) => (
+                table.get("crate").and_then(|v| v.as_str()),
+                table.get("version").and_then(|v| v.as_str()),
+            ),
+            _ => return Err("The item type is not correct for ComponentLock"),
+        };
+
+        Ok(ComponentLock {
+            crate_name: crate_name
+                .ok_or("No `crate` key in toml component version")?
+                .into(),
+            version: version
+                .ok_or("No `version` key in toml component version")?
+                .into(),
+        })
+    }
+}
+
+impl From<ComponentLock> for Item {
+    fn from(component_lock: ComponentLock) -> Item {
+        let mut table = InlineTable::new();
+        table.insert("crate", component_lock.crate_name.into());
+        table.insert("version", component_lock.version.into());
+        Item::Value(Value::InlineTable(table))
+    }
+}
+
+/// Drift between the component version recorded in the ProSA.toml file and the one of the current dependencies
+#[derive(Debug, PartialEq)]
+pub struct VersionDrift {
+    /// Name of the processor inside the ProSA
+    pub name: String,
+    /// Kind of the drifting component (processor or adaptor)
+    pub kind: ComponentKind,
+    /// Path of the drifting component
+    pub component: String,
+    /// Version recorded in the ProSA.toml file
+    pub recorded: ComponentLock,
+    /// Version of the current dependencies, `None` if the component can't be found anymore
+    pub current: Option<ComponentLock>,
+}
+
+impl fmt::Display for VersionDrift {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let kind = if self.kind == ComponentKind::Adaptor {
+            "adaptor"
+        } else {
+            "processor"
+        };
+
+        if let Some(current) = &self.current {
+            write!(
+                f,
+                "{}: {} {} recorded at {} but {} is used",
+                self.name, kind, self.component, self.recorded, current
+            )
+        } else {
+            write!(
+                f,
+                "{}: {} {} recorded at {} can't be found in the dependencies",
+                self.name, kind, self.component, self.recorded
+            )
         }
     }
 }
@@ -50,6 +166,18 @@ pub struct ProcDesc {
     pub proc: String,
     /// Adaptor to use
     pub adaptor: String,
+    /// Optional group of the processor, use to stop/restart processors together
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+    /// Optional names of the processor instances to start (and wait to be ready) before this processor
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub depends_on: Option<Vec<String>>,
+    /// Version of the processor crate recorded when the processor was added
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub proc_version: Option<ComponentLock>,
+    /// Version of the adaptor crate recorded when the processor was added
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub adaptor_version: Option<ComponentLock>,
 }
 
 impl ProcDesc {
@@ -60,6 +188,10 @@ impl ProcDesc {
             proc_name,
             proc,
             adaptor,
+            group: None,
+            depends_on: None,
+            proc_version: None,
+            adaptor_version: None,
         }
     }
 
@@ -72,6 +204,45 @@ impl ProcDesc {
         }
     }
 
+    /// Get the name of the settings field of the processor instance in the generated settings.
+    /// The section of the instance in the configuration file stay its name
+    ///
+    /// ```
+    /// use cargo_prosa::builder::ProcDesc;
+    ///
+    /// let mut proc_desc = ProcDesc::new("stub".into(), "prosa::stub::proc::StubProc".into(), "prosa::stub::adaptor::StubParotAdaptor".into());
+    /// assert_eq!("stub", proc_desc.get_settings_field());
+    /// proc_desc.name = Some(String::from("partner-a.stub"));
+    /// assert_eq!("partner_a_stub", proc_desc.get_settings_field());
+    /// ```
+    pub fn get_settings_field(&self) -> String {
+        let field: String = self
+            .get_name()
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect();
+        if field.starts_with(|c: char| c.is_ascii_digit()) {
+            format!("_{}", field)
+        } else {
+            field
+        }
+    }
+
+    /// Get the sections of the previous configuration format that can hold the settings of the processor instance.
+    ///
+    /// Sections used to be keyed by the settings field name, or by the processor name when there was a single instance of it.
+    /// They are still read (with a deprecation warning) when the configuration doesn't have a section for the instance name.
+    pub fn get_legacy_sections(&self) -> Vec<String> {
+        let name = self.get_name();
+        let mut sections = Vec::new();
+        for section in [self.get_settings_field(), self.proc_name.clone()] {
+            if section != name && !sections.contains(&section) {
+                sections.push(section);
+            }
+        }
+        sections
+    }
+
     /// Getter of the (processor, adaptor) version from the processor description
     pub fn get_versions<'a>(
         &self,
@@ -79,6 +250,59 @@ impl ProcDesc {
     ) -> (Option<ComponentVersion<'a>>, Option<ComponentVersion<'a>>) {
         cargo_metadata.get_versions(&self.proc, &self.adaptor)
     }
+
+    /// Method to record the current (processor, adaptor) versions. Return `true` if a recorded version changed
+    pub fn lock_versions(&mut self, cargo_metadata: &CargoMetadata) -> bool {
+        let (proc_version, adaptor_version) = self.get_versions(cargo_metadata);
+        let proc_version = proc_version.as_ref().map(ComponentLock::from);
+        let adaptor_version = adaptor_version.as_ref().map(ComponentLock::from);
+        let changed = (proc_version.is_some() && proc_version != self.proc_version)
+            || (adaptor_version.is_some() && adaptor_version != self.adaptor_version);
+        if proc_version.is_some() {
+            self.proc_version = proc_version;
+        }
+        if adaptor_version.is_some() {
+            self.adaptor_version = adaptor_version;
+        }
+
+        changed
+    }
+
+    /// Getter of the drifts between the recorded versions and the current ones
+    pub fn version_drifts(&self, cargo_metadata: &CargoMetadata) -> Vec<VersionDrift> {
+        let (proc_version, adaptor_version) = self.get_versions(cargo_metadata);
+        [
+            (
+                ComponentKind::Proc,
+                &self.proc,
+                &self.proc_version,
+                proc_version,
+            ),
+            (
+                ComponentKind::Adaptor,
+                &self.adaptor,
+                &self.adaptor_version,
+                adaptor_version,
+            ),
+        ]
+        .into_iter()
+        .filter_map(|(kind, component, recorded, current)| {
+            let recorded = recorded.as_ref()?;
+            let current = current.as_ref().map(ComponentLock::from);
+            if current.as_ref() != Some(recorded) {
+                Some(VersionDrift {
+                    name: self.get_name(),
+                    kind,
+                    component: component.clone(),
+                    recorded: recorded.clone(),
+                    current,
+                })
+            } else {
+                None
+            }
+        })
+        .collect()
+    }
 }
 
 impl fmt::Display for ProcDesc {
@@ -89,8 +313,22 @@ impl fmt::Display for ProcDesc {
             self.name.as_ref().unwrap_or(&self.proc),
             self.proc_name,
         )?;
-        writeln!(f, "  Processor {}", self.proc)?;
-        writeln!(f, "  Adaptor {}", self.adaptor)
+        write!(f, "  Processor {}", self.proc)?;
+        if let Some(proc_version) = &self.proc_version {
+            write!(f, " ({})", proc_version)?;
+        }
+        write!(f, "\n  Adaptor {}", self.adaptor)?;
+        if let Some(adaptor_version) = &self.adaptor_version {
+            write!(f, " ({})", adaptor_version)?;
+        }
+        if let Some(group) = &self.group {
+            write!(f, "\n  Group {}", group)?;
+        }
+        if let Some(depends_on) = &self.depends_on {
+            write!(f, "\n  Depends on {}", depends_on.join(", "))?;
+        }
+
+        writeln!(f)
     }
 }
 
@@ -103,6 +341,10 @@ impl TryFrom<&Item> for ProcDesc {
             let mut proc_name = None;
             let mut proc = None;
             let mut adaptor = None;
+            let mut group = None;
+            let mut depends_on = None;
+            let mut proc_version = None;
+            let mut adaptor_version = None;
             for array in array_tables {
                 if let Some(Item::Value(Value::String(item_name))) = array.get("name") {
                     name = Some(item_name.value().clone());
@@ -112,6 +354,20 @@ impl TryFrom<&Item> for ProcDesc {
                     proc = Some(item_name.value().clone());
                 } else if let Some(Item::Value(Value::String(item_name))) = array.get("adaptor") {
                     adaptor = Some(item_name.value().clone());
+                } else if let Some(Item::Value(Value::String(item_name))) = array.get("group") {
+                    group = Some(item_name.value().clone());
+                } else if let Some(Item::Value(Value::Array(item_names))) = array.get("depends_on")
+                {
+                    depends_on = Some(
+                        item_names
+                            .iter()
+                            .filter_map(|n| n.as_str().map(String::from))
+                            .collect(),
+                    );
+                } else if let Some(item) = array.get("proc_version") {
+                    proc_version = Some(ComponentLock::try_from(item)?);
+                } else if let Some(item) = array.get("adaptor_version") {
+                    adaptor_version = Some(ComponentLock::try_from(item)?);
                 }
             }
 
@@ -123,6 +379,10 @@ impl TryFrom<&Item> for ProcDesc {
                             proc_name,
                             proc,
                             adaptor,
+                            group,
+                            depends_on,
+                            proc_version,
+                            adaptor_version,
                         })
                     } else {
                         Err("No `adaptor` key in toml ProSA description")
@@ -166,6 +426,24 @@ impl From<ProcDesc> for Table {
                 proc_desc.adaptor,
             ))),
         );
+        if let Some(group) = proc_desc.group {
+            table.insert(
+                "group",
+                Item::Value(toml_edit::Value::String(toml_edit::Formatted::new(group))),
+            );
+        }
+        if let Some(depends_on) = proc_desc.depends_on {
+            table.insert(
+                "depends_on",
+                Item::Value(toml_edit::Value::Array(depends_on.into_iter().collect())),
+            );
+        }
+        if let Some(proc_version) = proc_desc.proc_version {
+            table.insert("proc_version", proc_version.into());
+        }
+        if let Some(adaptor_version) = proc_desc.adaptor_version {
+            table.insert("adaptor_version", adaptor_version.into());
+        }
 
         table
     }
@@ -213,6 +491,144 @@ impl Desc {
             .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
     }
 
+    /// Getter of a processor instance from its name
+    pub fn get_proc(&self, name: &str) -> Option<&ProcDesc> {
+        self.proc.iter().flatten().find(|p| p.get_name() == name)
+    }
+
+    /// Method to check that every processor instance have its own name and settings field
+    pub fn check_instances(&self) -> Result<(), io::Error> {
+        let mut names = HashSet::new();
+        let mut fields = HashSet::new();
+        for proc in self.proc.iter().flatten() {
+            if !names.insert(proc.get_name()) {
+                return Err(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    format!(
+                        "The processor instance `{}` is declared several times, use a distinct `name` for each instance",
+                        proc.get_name()
+                    ),
+                ));
+            }
+
+            if !fields.insert(proc.get_settings_field()) {
+                return Err(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    format!(
+                        "The processor instance `{}` have the same settings field `{}` as another instance",
+                        proc.get_name(),
+                        proc.get_settings_field()
+                    ),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Getter of the processor instances with their id, in their startup order: after their dependencies, in declaration order otherwise.
+    /// Fail if a dependency is unknown, or if the dependencies have a cycle
+    pub fn startup_order(&self) -> Result<Vec<(u32, &ProcDesc)>, io::Error> {
+        let procs: Vec<(u32, &ProcDesc)> = (1..).zip(self.proc.iter().flatten()).collect();
+        let names: HashSet<String> = procs.iter().map(|(_, p)| p.get_name()).collect();
+        for (_, proc) in &procs {
+            if let Some(dependency) = proc
+                .depends_on
+                .iter()
+                .flatten()
+                .find(|d| !names.contains(*d))
+            {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!(
+                        "The processor instance `{}` depends on the unknown processor instance `{}`",
+                        proc.get_name(),
+                        dependency
+                    ),
+                ));
+            }
+        }
+
+        let mut order: Vec<(u32, &ProcDesc)> = Vec::with_capacity(procs.len());
+        let mut started = HashSet::with_capacity(procs.len());
+        while order.len() < procs.len() {
+            let next = procs.iter().find(|(id, proc)| {
+                !order.iter().any(|(o, _)| o == id)
+                    && proc
+                        .depends_on
+                        .iter()
+                        .flatten()
+                        .all(|d| started.contains(d))
+            });
+
+            if let Some((id, proc)) = next {
+                started.insert(proc.get_name());
+                order.push((*id, proc));
+            } else {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "The dependencies of the processor instances `{}` have a cycle",
+                        procs
+                            .iter()
+                            .map(|(_, p)| p.get_name())
+                            .filter(|n| !started.contains(n))
+                            .collect::<Vec<String>>()
+                            .join("`, `")
+                    ),
+                ));
+            }
+        }
+
+        Ok(order)
+    }
+
+    /// Getter of all the drifts between the recorded component versions and the current dependencies
+    pub fn version_drifts(&self, cargo_metadata: &CargoMetadata) -> Vec<VersionDrift> {
+        self.proc
+            .iter()
+            .flatten()
+            .flat_map(|proc| proc.version_drifts(cargo_metadata))
+            .collect()
+    }
+
+    /// Method to record the current component versions into a ProSA toml document (keeping its format).
+    /// Return the number of processors for which a recorded version changed
+    pub fn lock_versions(prosa_doc: &mut DocumentMut, cargo_metadata: &CargoMetadata) -> usize {
+        let mut updated = 0;
+        if let Some(Item::ArrayOfTables(array_tables)) = prosa_doc.get_mut("proc") {
+            for table in array_tables.iter_mut() {
+                if let (Some(proc), Some(adaptor)) = (
+                    table.get("proc").and_then(|p| p.as_str()),
+                    table.get("adaptor").and_then(|a| a.as_str()),
+                ) {
+                    let (proc_version, adaptor_version) =
+                        cargo_metadata.get_versions(proc, adaptor);
+                    let mut changed = false;
+                    for (key, version) in [
+                        ("proc_version", proc_version),
+                        ("adaptor_version", adaptor_version),
+                    ] {
+                        if let Some(version) = version.as_ref().map(ComponentLock::from) {
+                            let recorded =
+                                table.get(key).and_then(|i| ComponentLock::try_from(i).ok());
+                            if recorded.as_ref() != Some(&version) {
+                                table.insert(key, version.into());
+                                changed = true;
+                            }
+                        }
+                    }
+
+                    if changed {
+                        updated += 1;
+                    }
+                }
+            }
+        }
+
+        updated
+    }
+
     /// Method to add a processor to the list
     #[cfg(test)]
     pub fn add_proc(&mut self, proc_desc: ProcDesc) {
@@ -240,6 +656,7 @@ mod tests {
         let prosa_toml = "[prosa]
 main = \"prosa::core::main::MainProc\"
 tvf = \"prosa_utils::msg::simple_string_tvf::SimpleStringTvf\"
+skeleton = 4
 
 [[proc]]
 proc_name = \"proc\"
@@ -256,4 +673,272 @@ adaptor = \"crate::adaptor\"
         let prosa_desc_from_file = Desc::read(toml_path_file).unwrap();
         assert_eq!(prosa_desc, prosa_desc_from_file);
     }
+
+    #[test]
+    fn prosa_desc_instances() {
+        let mut prosa_desc = Desc::default();
+        for name in ["partner-a", "partner_b"] {
+            let mut proc_desc = ProcDesc::new(
+                "stub".into(),
+                "prosa::stub::proc::StubProc".into(),
+                "prosa::stub::adaptor::StubParotAdaptor".into(),
+            );
+            proc_desc.name = Some(name.into());
+            prosa_desc.add_proc(proc_desc);
+        }
+
+        let prosa_toml = toml::to_string(&prosa_desc).unwrap();
+        assert!(
+            prosa_toml.contains("[[proc]]\nname = \"partner-a\"\nproc_name = \"stub\"\n"),
+            "{}",
+            prosa_toml
+        );
+        assert!(
+            prosa_toml.contains("[[proc]]\nname = \"partner_b\"\nproc_name = \"stub\"\n"),
+            "{}",
+            prosa_toml
+        );
+
+        // Both instances of the processor are read with their own settings
+        let prosa_desc = toml::from_str::<Desc>(&prosa_toml).unwrap();
+        assert!(prosa_desc.check_instances().is_ok());
+        let partner_a = prosa_desc.get_proc("partner-a").unwrap();
+        assert_eq!("partner_a", partner_a.get_settings_field());
+        assert_eq!(
+            vec![String::from("partner_a"), String::from("stub")],
+            partner_a.get_legacy_sections()
+        );
+        let partner_b = prosa_desc.get_proc("partner_b").unwrap();
+        assert_eq!("partner_b", partner_b.get_settings_field());
+        assert_eq!(vec![String::from("stub")], partner_b.get_legacy_sections());
+        assert!(prosa_desc.get_proc("stub").is_none());
+
+        // Instances must have distinct names and settings fields
+        let duplicated_toml = format!(
+            "{}\n[[proc]]\nname = \"partner-b\"\nproc_name = \"stub\"\nproc = \"prosa::stub::proc::StubProc\"\nadaptor = \"prosa::stub::adaptor::StubParotAdaptor\"\n",
+            prosa_toml
+        );
+        let duplicated_desc = toml::from_str::<Desc>(&duplicated_toml).unwrap();
+        assert_eq!(
+            io::ErrorKind::AlreadyExists,
+            duplicated_desc.check_instances().unwrap_err().kind()
+        );
+    }
+
+    #[test]
+    fn prosa_desc_dependencies() {
+        let prosa_toml = "[prosa]
+main = \"prosa::core::main::MainProc\"
+tvf = \"prosa_utils::msg::simple_string_tvf::SimpleStringTvf\"
+
+[[proc]]
+name = \"connector\"
+proc_name = \"stub\"
+proc = \"prosa::stub::proc::StubProc\"
+adaptor = \"prosa::stub::adaptor::StubParotAdaptor\"
+depends_on = [\"router\"]
+
+[[proc]]
+name = \"router\"
+proc_name = \"stub\"
+proc = \"prosa::stub::proc::StubProc\"
+adaptor = \"prosa::stub::adaptor::StubParotAdaptor\"
+depends_on = [\"backend\"]
+
+[[proc]]
+name = \"backend\"
+proc_name = \"stub\"
+proc = \"prosa::stub::proc::StubProc\"
+adaptor = \"prosa::stub::adaptor::StubParotAdaptor\"
+";
+
+        // Processors start after their dependencies, with their declaration id
+        let prosa_desc = toml::from_str::<Desc>(prosa_toml).unwrap();
+        assert_eq!(
+            vec![
+                (3, String::from("backend")),
+                (2, String::from("router")),
+                (1, String::from("connector"))
+            ],
+            prosa_desc
+                .startup_order()
+                .unwrap()
+                .into_iter()
+                .map(|(id, p)| (id, p.get_name()))
+                .collect::<Vec<(u32, String)>>()
+        );
+        let mut proc_desc = ProcDesc::new(
+            "stub".into(),
+            "prosa::stub::proc::StubProc".into(),
+            "prosa::stub::adaptor::StubParotAdaptor".into(),
+        );
+        proc_desc.depends_on = Some(vec![String::from("backend")]);
+        let table = Table::from(proc_desc);
+        assert_eq!(
+            Some("backend"),
+            table
+                .get("depends_on")
+                .and_then(|d| d.as_array())
+                .and_then(|d| d.get(0))
+                .and_then(|d| d.as_str())
+        );
+
+        // A cycle fails the validation
+        let cycle_desc =
+            toml::from_str::<Desc>(&format!("{}depends_on = [\"connector\"]\n", prosa_toml))
+                .unwrap();
+        let error = cycle_desc.startup_order().unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidData, error.kind());
+        assert_eq!(
+            "The dependencies of the processor instances `connector`, `router`, `backend` have a cycle",
+            error.to_string()
+        );
+
+        // As an unknown dependency
+        let unknown_desc =
+            toml::from_str::<Desc>(&format!("{}depends_on = [\"partner\"]\n", prosa_toml)).unwrap();
+        assert_eq!(
+            io::ErrorKind::NotFound,
+            unknown_desc.startup_order().unwrap_err().kind()
+        );
+    }
+
+    fn test_cargo_metadata(prosa_version: &str) -> CargoMetadata {
+        serde_json::from_value(serde_json::json!({
+            "packages": [{
+                "name": "prosa",
+                "version": prosa_version,
+                "authors": [],
+                "metadata": {
+                    "prosa": {
+                        "stub": {
+                            "proc": "stub::proc::StubProc",
+                            "settings": "stub::proc::StubSettings",
+                            "adaptor": ["stub::adaptor::StubParotAdaptor"],
+                        },
+                    },
+                },
+            }],
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn prosa_desc_versions_toml() {
+        let mut proc_desc = ProcDesc::new(
+            "stub".into(),
+            "prosa::stub::proc::StubProc".into(),
+            "prosa::stub::adaptor::StubParotAdaptor".into(),
+        );
+        assert!(proc_desc.lock_versions(&test_cargo_metadata("0.1.2")));
+        assert!(!proc_desc.lock_versions(&test_cargo_metadata("0.1.2")));
+        assert_eq!(
+            Some(ComponentLock {
+                crate_name: "prosa".into(),
+                version: "0.1.2".into()
+            }),
+            proc_desc.proc_version
+        );
+
+        let mut prosa_desc = Desc::default();
+        prosa_desc.add_proc(proc_desc);
+        let prosa_toml = toml::to_string(&prosa_desc).unwrap();
+        assert!(
+            prosa_toml.contains("[proc.proc_version]\ncrate = \"prosa\"\nversion = \"0.1.2\"\n"),
+            "{}",
+            prosa_toml
+        );
+        assert_eq!(prosa_desc, toml::from_str::<Desc>(&prosa_toml).unwrap());
+
+        // Inline tables are written by the `add` command
+        let mut array_tables = toml_edit::ArrayOfTables::new();
+        array_tables.push(prosa_desc.proc.unwrap().pop().unwrap().into());
+        let mut prosa_doc = DocumentMut::new();
+        prosa_doc.insert("proc", Item::ArrayOfTables(array_tables));
+        let prosa_toml = prosa_doc.to_string();
+        assert!(
+            prosa_toml.contains("proc_version = { crate = \"prosa\", version = \"0.1.2\" }\n"),
+            "{}",
+            prosa_toml
+        );
+        assert!(
+            prosa_toml.contains("adaptor_version = { crate = \"prosa\", version = \"0.1.2\" }\n"),
+            "{}",
+            prosa_toml
+        );
+        let proc_desc = &toml::from_str::<Desc>(&format!(
+            "[prosa]\nmain = \"main\"\ntvf = \"tvf\"\n{}",
+            prosa_toml
+        ))
+        .unwrap()
+        .proc
+        .unwrap()[0];
+        assert_eq!("0.1.2", proc_desc.adaptor_version.as_ref().unwrap().version);
+    }
+
+    #[test]
+    fn prosa_desc_versions_drift() {
+        let mut proc_desc = ProcDesc::new(
+            "stub".into(),
+            "prosa::stub::proc::StubProc".into(),
+            "prosa::stub::adaptor::StubParotAdaptor".into(),
+        );
+
+        // Nothing recorded, so no drift
+        assert!(proc_desc
+            .version_drifts(&test_cargo_metadata("0.1.2"))
+            .is_empty());
+
+        proc_desc.lock_versions(&test_cargo_metadata("0.1.2"));
+        assert!(proc_desc
+            .version_drifts(&test_cargo_metadata("0.1.2"))
+            .is_empty());
+
+        let drifts = proc_desc.version_drifts(&test_cargo_metadata("0.2.0"));
+        assert_eq!(2, drifts.len());
+        assert_eq!(ComponentKind::Proc, drifts[0].kind);
+        assert_eq!(ComponentKind::Adaptor, drifts[1].kind);
+        assert_eq!(
+            "stub: processor prosa::stub::proc::StubProc recorded at prosa[0.1.2] but prosa[0.2.0] is used",
+            drifts[0].to_string()
+        );
+
+        let missing_metadata: CargoMetadata =
+            serde_json::from_value(serde_json::json!({ "packages": [] })).unwrap();
+        let drifts = proc_desc.version_drifts(&missing_metadata);
+        assert_eq!(None, drifts[1].current);
+        assert_eq!(
+            "stub: adaptor prosa::stub::adaptor::StubParotAdaptor recorded at prosa[0.1.2] can't be found in the dependencies",
+            drifts[1].to_string()
+        );
+
+        // Sync the recorded versions in a ProSA toml document
+        let mut prosa_doc = "# ProSA definition\n[prosa]\nmain = \"main\"\ntvf = \"tvf\"\n\n[[proc]]\nproc_name = \"stub\"\nproc = \"prosa::stub::proc::StubProc\"\nadaptor = \"prosa::stub::adaptor::StubParotAdaptor\"\n"
+            .parse::<DocumentMut>()
+            .unwrap();
+        assert_eq!(
+            1,
+            Desc::lock_versions(&mut prosa_doc, &test_cargo_metadata("0.2.0"))
+        );
+        assert_eq!(
+            0,
+            Desc::lock_versions(&mut prosa_doc, &test_cargo_metadata("0.2.0"))
+        );
+        let prosa_toml = prosa_doc.to_string();
+        assert!(
+            prosa_toml.starts_with("# ProSA definition\n"),
+            "{}",
+            prosa_toml
+        );
+        let prosa_desc = toml::from_str::<Desc>(&prosa_toml).unwrap();
+        assert!(prosa_desc
+            .version_drifts(&test_cargo_metadata("0.2.0"))
+            .is_empty());
+        assert_eq!(
+            2,
+            prosa_desc
+                .version_drifts(&test_cargo_metadata("0.1.2"))
+                .len()
+        );
+    }
 }
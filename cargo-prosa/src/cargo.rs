@@ -27,18 +27,46 @@
 //! adaptor = ["MyCustomAdaptor"]
 //! ```
 
-use std::{collections::HashMap, fmt, io};
+use std::{collections::HashMap, fmt, io, str::FromStr};
 
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 use crate::builder::ProcDesc;
 
+/// Kind of ProSA component
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ComponentKind {
+    /// ProSA processor
+    Proc,
+    /// ProSA adaptor
+    Adaptor,
+    /// ProSA main task
+    Main,
+    /// ProSA TVF format
+    Tvf,
+}
+
+impl FromStr for ComponentKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "proc" => Ok(ComponentKind::Proc),
+            "adaptor" => Ok(ComponentKind::Adaptor),
+            "main" => Ok(ComponentKind::Main),
+            "tvf" => Ok(ComponentKind::Tvf),
+            _ => Err(format!("Unknown ProSA component kind `{}`", s)),
+        }
+    }
+}
+
 /// Structure to define ProSA component (processor/adaptor) version
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Serialize)]
 pub struct ComponentVersion<'a> {
     /// Name of the component
     pub name: String,
     /// Name of the component's crate
+    #[serde(rename = "crate")]
     pub crate_name: &'a String,
     /// Version of the component
     pub version: &'a String,
@@ -69,6 +97,12 @@ pub struct Metadata {
     /// Description of the ProSA package (not included in the metadata)
     #[serde(skip_deserializing)]
     pub description: Option<String>,
+    /// Crate of the ProSA processor (not included in the metadata)
+    #[serde(skip_deserializing)]
+    pub crate_name: Option<String>,
+    /// Version of the ProSA processor crate (not included in the metadata)
+    #[serde(skip_deserializing)]
+    pub version: Option<String>,
     /// Struct name of the ProSA processor
     pub proc: Option<String>,
     /// Struct name of the ProSA settings
@@ -78,9 +112,11 @@ pub struct Metadata {
 }
 
 impl Metadata {
-    /// Method to add the crate name, and description to the metadata
-    fn specify(&mut self, crate_name: &str, description: Option<String>) {
+    /// Method to add the crate name, version, and description to the metadata
+    fn specify(&mut self, crate_name: &str, version: &str, description: Option<String>) {
         self.description = description;
+        self.crate_name = Some(crate_name.into());
+        self.version = Some(version.into());
         let crate_prefix = format!("{}::", crate_name);
 
         if let Some(proc) = &mut self.proc {
@@ -101,6 +137,13 @@ impl Metadata {
     /// Method to merge 2 metadata from the same processor
     pub fn merge(&mut self, prosa_metadata: Metadata) {
         if self.proc.is_none() {
+            if prosa_metadata.proc.is_some() {
+                // The processor crate define the component
+                self.crate_name = prosa_metadata.crate_name;
+                self.version = prosa_metadata.version;
+                self.description = prosa_metadata.description;
+            }
+
             self.proc = prosa_metadata.proc;
         }
 
@@ -188,6 +231,10 @@ impl Metadata {
                 .map(|p| p.replace('-', "_"))
                 .ok_or(format!("Missing ProSA `proc` metadata for {}", name))?,
             adaptor: adaptor.replace('-', "_"),
+            group: None,
+            depends_on: None,
+            proc_version: None,
+            adaptor_version: None,
         })
     }
 }
@@ -307,7 +354,7 @@ impl PackageMetadata {
     }
 
     /// Method to get a component version from its name if it exist
-    fn get_version(&self, name: &str, ty: &str) -> Option<ComponentVersion> {
+    fn get_version(&self, name: &str, ty: &str) -> Option<ComponentVersion<'_>> {
         if let Some(metadata) = self
             .metadata
             .as_ref()
@@ -361,22 +408,22 @@ impl PackageMetadata {
     }
 
     /// Method to get the processor version from its name if it exist
-    pub fn get_main_version(&self, name: &str) -> Option<ComponentVersion> {
+    pub fn get_main_version(&self, name: &str) -> Option<ComponentVersion<'_>> {
         self.get_version(name, "main")
     }
 
     /// Method to get the processor version from its name if it exist
-    pub fn get_tvf_version(&self, name: &str) -> Option<ComponentVersion> {
+    pub fn get_tvf_version(&self, name: &str) -> Option<ComponentVersion<'_>> {
         self.get_version(name, "tvf")
     }
 
     /// Method to get the processor version from its name if it exist
-    pub fn get_proc_version(&self, name: &str) -> Option<ComponentVersion> {
+    pub fn get_proc_version(&self, name: &str) -> Option<ComponentVersion<'_>> {
         self.get_version(name, "proc")
     }
 
     /// Method to get the adaptor version from its name if it exist
-    pub fn get_adaptor_version(&self, name: &str) -> Option<ComponentVersion> {
+    pub fn get_adaptor_version(&self, name: &str) -> Option<ComponentVersion<'_>> {
         self.get_version(name, "adaptor")
     }
 
@@ -449,10 +496,18 @@ pub struct CargoMetadata {
 impl CargoMetadata {
     /// Method to load metadata for the ProSA package
     pub fn load_metadata() -> Result<CargoMetadata, io::Error> {
+        Self::load_metadata_with(false)
+    }
+
+    /// Method to load metadata for the ProSA package, without accessing the network if `offline`
+    pub fn load_metadata_with(offline: bool) -> Result<CargoMetadata, io::Error> {
+        let mut args = vec!["metadata", "-q"];
+        if offline {
+            args.push("--offline");
+        }
+
         // Get packges metadata
-        let cargo_metadata = std::process::Command::new("cargo")
-            .args(vec!["metadata", "-q"])
-            .output()?;
+        let cargo_metadata = std::process::Command::new("cargo").args(args).output()?;
 
         Ok(serde_json::from_slice(cargo_metadata.stdout.as_slice())?)
     }
@@ -475,8 +530,7 @@ impl CargoMetadata {
                 ))
             }
         } else {
-            Err(io::Error::new(
-                io::ErrorKind::Other,
+            Err(io::Error::other(
                 std::str::from_utf8(cargo_metadata.stderr.as_slice()).unwrap_or(
                     format!(
                         "Can't retrieve package metadata {:?}",
@@ -494,7 +548,11 @@ impl CargoMetadata {
         for package in &self.packages {
             if let Some(prosa_metadata) = package.get_prosa_proc_metadata() {
                 for (prosa_proc_name, mut prosa_proc_metadata) in prosa_metadata {
-                    prosa_proc_metadata.specify(&package.name, package.description.clone());
+                    prosa_proc_metadata.specify(
+                        &package.name,
+                        &package.version,
+                        package.description.clone(),
+                    );
                     if let Some(prosa_existing_metadata) = prosa_list.get_mut(&prosa_proc_name) {
                         prosa_existing_metadata.merge(prosa_proc_metadata);
                     } else {
@@ -527,8 +585,63 @@ impl CargoMetadata {
         tvf
     }
 
+    /// Method to get all ProSA components (optionally of a single kind) with their version infos
+    pub fn components(&self, kind: Option<ComponentKind>) -> ProsaComponents<'_> {
+        let mut components = ProsaComponents::default();
+        if matches!(
+            kind,
+            None | Some(ComponentKind::Proc) | Some(ComponentKind::Adaptor)
+        ) {
+            components.proc = self
+                .prosa_proc_metadata()
+                .into_iter()
+                .map(|(name, metadata)| ProcComponent::new(name, metadata))
+                .filter(|c| match kind {
+                    Some(ComponentKind::Proc) => c.proc.is_some(),
+                    Some(ComponentKind::Adaptor) => !c.adaptors.is_empty(),
+                    _ => true,
+                })
+                .collect();
+            components.proc.sort_by(|a, b| a.name.cmp(&b.name));
+        }
+
+        for package in &self.packages {
+            if matches!(kind, None | Some(ComponentKind::Main)) {
+                components
+                    .main
+                    .extend(
+                        package
+                            .get_prosa_main()
+                            .into_iter()
+                            .map(|name| ComponentVersion {
+                                name,
+                                crate_name: &package.name,
+                                version: &package.version,
+                            }),
+                    );
+            }
+
+            if matches!(kind, None | Some(ComponentKind::Tvf)) {
+                components
+                    .tvf
+                    .extend(
+                        package
+                            .get_prosa_tvf()
+                            .into_iter()
+                            .map(|name| ComponentVersion {
+                                name,
+                                crate_name: &package.name,
+                                version: &package.version,
+                            }),
+                    );
+            }
+        }
+
+        components
+    }
+
     /// Getter of the main version from its name if it exist
-    pub fn get_main_version(&self, main_name: &str) -> Option<ComponentVersion> {
+    pub fn get_main_version(&self, main_name: &str) -> Option<ComponentVersion<'_>> {
         for package in &self.packages {
             if let Some(main) = package.get_main_version(main_name) {
                 return Some(main);
@@ -539,7 +652,7 @@ impl CargoMetadata {
     }
 
     /// Getter of the TVF version from its name if it exist
-    pub fn get_tvf_version(&self, main_name: &str) -> Option<ComponentVersion> {
+    pub fn get_tvf_version(&self, main_name: &str) -> Option<ComponentVersion<'_>> {
         for package in &self.packages {
             if let Some(main) = package.get_tvf_version(main_name) {
                 return Some(main);
@@ -554,7 +667,7 @@ impl CargoMetadata {
         &self,
         proc_name: &str,
         adaptor_name: &str,
-    ) -> (Option<ComponentVersion>, Option<ComponentVersion>) {
+    ) -> (Option<ComponentVersion<'_>>, Option<ComponentVersion<'_>>) {
         let mut processor_version = None;
         let mut adaptor_version = None;
         for package in &self.packages {
@@ -594,3 +707,204 @@ impl fmt::Display for CargoMetadata {
         Ok(())
     }
 }
+
+/// Merged ProSA processor component, with its adaptors
+#[derive(Debug, PartialEq, Serialize)]
+pub struct ProcComponent {
+    /// Name of the component
+    pub name: String,
+    /// Name of the processor's crate
+    #[serde(rename = "crate")]
+    pub crate_name: Option<String>,
+    /// Version of the processor's crate
+    pub version: Option<String>,
+    /// Struct name of the ProSA processor
+    pub proc: Option<String>,
+    /// Struct name of the ProSA settings
+    pub settings: Option<String>,
+    /// Struct names of ProSA adaptors
+    pub adaptors: Vec<String>,
+    /// Description of the processor's package
+    pub description: Option<String>,
+}
+
+impl ProcComponent {
+    /// Method to create a processor component from its merged metadata
+    fn new(name: String, metadata: Metadata) -> ProcComponent {
+        ProcComponent {
+            name,
+            crate_name: metadata.crate_name,
+            version: metadata.version,
+            proc: metadata.proc,
+            settings: metadata.settings,
+            adaptors: metadata.adaptor.unwrap_or_default(),
+            description: metadata.description,
+        }
+    }
+}
+
+impl fmt::Display for ProcComponent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (&self.crate_name, &self.version) {
+            (Some(crate_name), Some(version)) => {
+                writeln!(f, "  - {} ({}[{}])", self.name, crate_name, version)?
+            }
+            _ => writeln!(f, "  - {}", self.name)?,
+        }
+
+        if let Some(proc) = &self.proc {
+            writeln!(f, "    Processor {}", proc)?;
+        }
+
+        if let Some(settings) = &self.settings {
+            writeln!(f, "    Settings {}", settings)?;
+        }
+
+        if !self.adaptors.is_empty() {
+            writeln!(f, "    Adaptor:")?;
+            for adaptor in &self.adaptors {
+                writeln!(f, "     - {}", adaptor)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// All ProSA components, serializable to be consumed by scripts
+#[derive(Debug, Default, Serialize)]
+pub struct ProsaComponents<'a> {
+    /// Processors (and adaptors) components
+    pub proc: Vec<ProcComponent>,
+    /// Main task components
+    pub main: Vec<ComponentVersion<'a>>,
+    /// TVF components
+    pub tvf: Vec<ComponentVersion<'a>>,
+}
+
+impl fmt::Display for ProsaComponents<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if !self.proc.is_empty() {
+            writeln!(f, "Processors:")?;
+            for proc in &self.proc {
+                write!(f, "{}", proc)?;
+            }
+        }
+
+        for (title, components) in [("Main:", &self.main), ("TVF:", &self.tvf)] {
+            if !components.is_empty() {
+                writeln!(f, "{}", title)?;
+                for component in components {
+                    writeln!(
+                        f,
+                        "  - {} ({}[{}])",
+                        component.name, component.crate_name, component.version
+                    )?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn test_cargo_metadata() -> CargoMetadata {
+        let prosa_package = PackageMetadata {
+            name: String::from("prosa"),
+            version: String::from("0.1.2"),
+            license: None,
+            description: Some(String::from("ProSA core")),
+            documentation: None,
+            authors: Vec::new(),
+            metadata: serde_json::from_value(json!({
+                "prosa": {
+                    "main": ["core::main::MainProc"],
+                    "stub": {
+                        "proc": "stub::proc::StubProc",
+                        "settings": "stub::proc::StubSettings",
+                        "adaptor": ["stub::adaptor::StubParotAdaptor"],
+                    },
+                },
+            }))
+            .unwrap(),
+        };
+        let adaptor_package = PackageMetadata {
+            name: String::from("my-adaptor"),
+            version: String::from("1.0.0"),
+            license: None,
+            description: None,
+            documentation: None,
+            authors: Vec::new(),
+            metadata: serde_json::from_value(json!({
+                "prosa": {
+                    "stub": { "adaptor": ["MyStubAdaptor"] },
+                    "tvf": ["MyTvf"],
+                },
+            }))
+            .unwrap(),
+        };
+
+        CargoMetadata {
+            packages: vec![adaptor_package, prosa_package],
+        }
+    }
+
+    #[test]
+    fn components_json() {
+        let cargo_metadata = test_cargo_metadata();
+        let components = serde_json::to_value(cargo_metadata.components(None)).unwrap();
+        assert_eq!(
+            json!({
+                "proc": [{
+                    "name": "stub",
+                    "crate": "prosa",
+                    "version": "0.1.2",
+                    "proc": "prosa::stub::proc::StubProc",
+                    "settings": "prosa::stub::proc::StubSettings",
+                    "adaptors": ["my-adaptor::MyStubAdaptor", "prosa::stub::adaptor::StubParotAdaptor"],
+                    "description": "ProSA core",
+                }],
+                "main": [{ "name": "prosa::core::main::MainProc", "crate": "prosa", "version": "0.1.2" }],
+                "tvf": [{ "name": "my_adaptor::MyTvf", "crate": "my-adaptor", "version": "1.0.0" }],
+            }),
+            components
+        );
+    }
+
+    #[test]
+    fn components_kind_filter() {
+        let cargo_metadata = test_cargo_metadata();
+
+        let components = cargo_metadata.components(Some(ComponentKind::Tvf));
+        assert!(components.proc.is_empty());
+        assert!(components.main.is_empty());
+        assert_eq!(1, components.tvf.len());
+
+        let components = cargo_metadata.components(Some(ComponentKind::Adaptor));
+        assert_eq!(1, components.proc.len());
+        assert!(components.main.is_empty() && components.tvf.is_empty());
+        assert_eq!(
+            "Processors:\n  - stub (prosa[0.1.2])\n    Processor prosa::stub::proc::StubProc\n    Settings prosa::stub::proc::StubSettings\n    Adaptor:\n     - my-adaptor::MyStubAdaptor\n     - prosa::stub::adaptor::StubParotAdaptor\n",
+            components.to_string()
+        );
+
+        let components = cargo_metadata.components(Some(ComponentKind::Main));
+        assert!(components.proc.is_empty() && components.tvf.is_empty());
+        let toml = toml::to_string(&components).unwrap();
+        assert!(
+            toml.contains("name = \"prosa::core::main::MainProc\""),
+            "{}",
+            toml
+        );
+        assert!(toml.contains("crate = \"prosa\""), "{}", toml);
+
+        assert_eq!(Ok(ComponentKind::Proc), ComponentKind::from_str("proc"));
+        assert!(ComponentKind::from_str("unknown").is_err());
+    }
+}
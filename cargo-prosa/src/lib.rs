@@ -8,11 +8,134 @@
 #![warn(missing_docs)]
 #![deny(unreachable_pub)]
 
+use clap::{arg, Command};
+
 /// Configuration file name for ProSA. Define all processor list
 pub const CONFIGURATION_FILENAME: &str = "ProSA.toml";
 
+/// Jinja template of the build.rs file of a ProSA
+pub const BUILD_RS_TEMPLATE: &str =
+    include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/assets/build.rs.j2"));
+
+/// Jinja template of the main.rs file of a ProSA
+pub const MAIN_RS_TEMPLATE: &str =
+    include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/assets/main.rs.j2"));
+
+/// Command line definition of `cargo prosa`
+pub fn cli() -> Command {
+    Command::new("cargo")
+        .bin_name("cargo")
+        .subcommand_required(true)
+        .arg_required_else_help(true)
+        .subcommand(Command::new("prosa")
+            .about("ProSA builder")
+            .subcommand_required(true)
+            .arg_required_else_help(true)
+            .subcommand(
+                Command::new("new")
+                    .about("Create a new ProSA package")
+                    .arg(arg!(-n --name <NAME> "Set the package name. Defaults to the directory name"))
+                    .arg(arg!(--deb "Configure the ProSA to generate a deb package").action(clap::ArgAction::SetTrue))
+                    .arg(arg!(--"no-verify" "Don't check that the generated ProSA compiles").action(clap::ArgAction::SetTrue))
+                    .arg(arg!(<PATH> "Name of the new ProSA"))
+                    .arg_required_else_help(true),
+            )
+            .subcommand(
+                Command::new("new-proc")
+                    .about("Create a new ProSA processor crate")
+                    .arg(arg!(-k --kind <KIND> "Kind of processor: a client of a remote, a server for remote clients, or a worker that only use the bus").value_parser(["client", "server", "worker"]).default_value("worker"))
+                    .arg(arg!(<NAME> "Name of the new processor crate, also used as processor name"))
+                    .arg_required_else_help(true),
+            )
+            .subcommand(
+                Command::new("init")
+                    .about("Create a new ProSA package in an existing directory")
+                    .arg(arg!(--deb "Configure the ProSA to generate a deb package").action(clap::ArgAction::SetTrue))
+                    .arg(arg!(--"no-verify" "Don't check that the generated ProSA compiles").action(clap::ArgAction::SetTrue))
+                    .arg(arg!(-n --name <NAME> "Set the package name. Defaults to the directory name"))
+            )
+            .subcommand(
+                Command::new("update")
+                    .about("Update ProSA files to the latest skeleton")
+                    .arg(arg!(--deb "Configure the ProSA to generate a deb package").action(clap::ArgAction::SetTrue))
+                    .arg(arg!(--"no-verify" "Don't check that the generated ProSA compiles").action(clap::ArgAction::SetTrue))
+                    .arg(arg!(--sync "Record the current processors/adaptors versions in the ProSA.toml file").action(clap::ArgAction::SetTrue))
+            )
+            .subcommand(
+                Command::new("upgrade")
+                    .about("Upgrade the ProSA project across breaking ProSA versions, with the migrations newer than its skeleton version")
+                    .arg(arg!(--dry_run "Displays the diff of the migrations, but doesn't actually write the ProSA files").action(clap::ArgAction::SetTrue))
+                    .arg(arg!(-c --config <CONFIG> "Configuration file to migrate (the YAML/TOML files of the project directory and its subdirectories by default)").action(clap::ArgAction::Append))
+            )
+            .subcommand(
+                Command::new("check")
+                    .about("Check the processors instances and their dependencies, and the recorded processors/adaptors versions against the current dependencies")
+                    .arg(arg!(--sync "Record the current processors/adaptors versions in the ProSA.toml file").action(clap::ArgAction::SetTrue))
+                    .arg(arg!(--offline "Run the underlying cargo metadata without accessing the network").action(clap::ArgAction::SetTrue))
+            )
+            .subcommand(
+                Command::new("add")
+                    .about("Add a ProSA processor")
+                    .arg(arg!(--dry_run "Displays what would be updated, but doesn't actually write the ProSA files").action(clap::ArgAction::SetTrue))
+                    .arg(arg!(-n --name <NAME> "Name of the processor instance inside the ProSA, also used as its settings section (use the processor name by default). Give distinct names to add several instances of a processor"))
+                    .arg(arg!(-a --adaptor <ADAPTOR> "Adaptor name to use for the processor"))
+                    .arg(arg!(<PROCESSOR> "Processor to add"))
+                    .arg_required_else_help(true),
+            )
+            .subcommand(
+                Command::new("remove")
+                    .about("Remove one or more ProSA processor")
+                    .arg(arg!(--dry_run "Displays what would be removed, but doesn't actually write the ProSA files").action(clap::ArgAction::SetTrue))
+                    .arg(arg!(<PROCESSORS> ... "Processors to remove"))
+                    .arg_required_else_help(true),
+            )
+            .subcommand(
+                Command::new("main")
+                    .about("Change the ProSA main processor")
+                    .arg(arg!(--dry_run "Displays what would be removed, but doesn't actually write the ProSA files").action(clap::ArgAction::SetTrue))
+                    .arg(arg!(<MAIN> "Name of the main processor"))
+                    .arg_required_else_help(true),
+            )
+            .subcommand(
+                Command::new("tvf")
+                    .about("Change the ProSA TVF internal messaging")
+                    .arg(arg!(--dry_run "Displays what would be removed, but doesn't actually write the ProSA files").action(clap::ArgAction::SetTrue))
+                    .arg(arg!(<TVF> "Name of the TVF"))
+                    .arg_required_else_help(true),
+            )
+            .subcommand(
+                Command::new("list")
+                    .about("List all available ProSA component")
+                    .arg(arg!(-f --format <FORMAT> "Output format of the list").value_parser(["text", "json", "toml"]).default_value("text"))
+                    .arg(arg!(-k --kind <KIND> "Only list a kind of component").value_parser(["proc", "adaptor", "main", "tvf"]))
+                    .arg(arg!(--offline "Run the underlying cargo metadata without accessing the network").action(clap::ArgAction::SetTrue))
+            )
+            .subcommand(
+                Command::new("container")
+                    .about("Create a container file to containerize ProSA")
+                    .arg(arg!(--docker "Generate Dockerfile container format").action(clap::ArgAction::SetTrue))
+                    .arg(arg!(-i --image <IMG> "Base image to use for ProSA container image").default_value("debian:stable-slim"))
+                    .arg(arg!(-b --builder <BUILDER_IMG> "Builder to use to compile the ProSA"))
+                    .arg(arg!(-p --package_manager <PKG_MANAGER> "Indicate which package manager to use with the Docker image to install pre-requisite").default_value("apt"))
+                    .arg(arg!(--chef "Use cargo-chef in the builder to cache the dependencies layer").action(clap::ArgAction::SetTrue).requires("builder"))
+                    .arg(arg!(-u --user <USER> "Non-root user that run ProSA in the container (`root` to disable)").default_value("prosa"))
+                    .arg(arg!(--"target-arch" <TARGET> "Rust target triple to cross compile ProSA (ex: aarch64-unknown-linux-gnu)"))
+                    .arg(arg!(--health_endpoint <URL> "Health/metrics endpoint URL to declare a container HEALTHCHECK (ex: http://localhost:9100/metrics)"))
+                    .arg(arg!([PATH] "Path of the output container file to generate an image"))
+            )
+            .subcommand(
+                Command::new("completion")
+                    .about("Output shell completion code for the specified shell (Bash, Elvish, Fish, PowerShell, or Zsh)")
+                    .arg(arg!(<SHELL>))
+                    .arg_required_else_help(true),
+            )
+        )
+}
+
 pub mod package;
 
 pub mod builder;
 
 pub mod cargo;
+
+pub mod upgrade;
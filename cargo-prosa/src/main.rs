@@ -10,17 +10,18 @@ use std::{
     collections::HashSet,
     env, fs,
     io::{self, Write},
-    path::Path,
+    path::{Path, PathBuf},
     str::FromStr,
 };
 
 use cargo_prosa::{
     builder::Desc,
-    cargo::CargoMetadata,
+    cargo::{CargoMetadata, ComponentKind},
+    cli,
     package::{container::ContainerFile, deb::DebPkg},
-    CONFIGURATION_FILENAME,
+    upgrade::{self, Project, MIGRATIONS},
+    BUILD_RS_TEMPLATE, CONFIGURATION_FILENAME, MAIN_RS_TEMPLATE,
 };
-use clap::{arg, Command};
 use tera::Tera;
 use toml_edit::DocumentMut;
 
@@ -64,10 +65,7 @@ where
 {
     const RENDER_FILENAME: &str = "build.rs";
     let mut tera_build = Tera::default();
-    tera_build.add_raw_template(
-        RENDER_FILENAME,
-        include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/assets/build.rs.j2")),
-    )?;
+    tera_build.add_raw_template(RENDER_FILENAME, BUILD_RS_TEMPLATE)?;
 
     let build_file = fs::File::create(&path).map_err(tera::Error::io_error)?;
     tera_build.render_to(RENDER_FILENAME, ctx, build_file)
@@ -80,17 +78,121 @@ where
 {
     const RENDER_FILENAME: &str = "main.rs";
     let mut tera_build = Tera::default();
-    tera_build.add_raw_template(
-        RENDER_FILENAME,
-        include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/assets/main.rs.j2")),
-    )?;
+    tera_build.add_raw_template(RENDER_FILENAME, MAIN_RS_TEMPLATE)?;
 
     let main_file = fs::File::create(&path).map_err(tera::Error::io_error)?;
     tera_build.render_to(RENDER_FILENAME, ctx, main_file)
 }
 
+/// Files of a ProSA project that are overwritten by the generation, relative to the project path
+const GENERATED_FILES: [&str; 4] = [
+    "build.rs",
+    "src/main.rs",
+    "Cargo.toml",
+    CONFIGURATION_FILENAME,
+];
+
+/// Backup of the ProSA project files, taken before they are overwritten
+struct ProsaBackup {
+    /// Generated files with their backup path if the file existed before the generation
+    files: Vec<(PathBuf, Option<PathBuf>)>,
+}
+
+impl ProsaBackup {
+    /// Keep a `.bak` copy of every existing generated file of the project
+    fn save<P>(prosa_path: P) -> io::Result<ProsaBackup>
+    where
+        P: AsRef<Path>,
+    {
+        let mut files = Vec::with_capacity(GENERATED_FILES.len());
+        for file in GENERATED_FILES {
+            let file_path = prosa_path.as_ref().join(file);
+            if file_path.exists() {
+                let mut backup_path = file_path.clone().into_os_string();
+                backup_path.push(".bak");
+                let backup_path = PathBuf::from(backup_path);
+                fs::copy(&file_path, &backup_path)?;
+                files.push((file_path, Some(backup_path)));
+            } else {
+                files.push((file_path, None));
+            }
+        }
+
+        Ok(ProsaBackup { files })
+    }
+
+    /// Restore the files of the project as they were before the generation
+    fn restore(self) -> io::Result<()> {
+        for (file_path, backup_path) in self.files {
+            if let Some(backup_path) = backup_path {
+                fs::rename(backup_path, file_path)?;
+            } else if file_path.exists() {
+                fs::remove_file(file_path)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Remove the backup files once the generation is validated
+    fn clean(self) -> io::Result<()> {
+        for backup_path in self.files.into_iter().filter_map(|(_, b)| b) {
+            fs::remove_file(backup_path)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Function to check that the ProSA project compiles
+fn check_prosa(path: &str) -> io::Result<()> {
+    let cargo_check = cargo!("check", Some(path),);
+    if cargo_check.status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "the generated ProSA project doesn't compile",
+        ))
+    }
+}
+
+/// Function to generate ProSA project files, check them and restore the previous files if they don't compile
+fn generate_prosa<F>(path: &str, verify: bool, generate: F) -> io::Result<()>
+where
+    F: FnOnce() -> io::Result<bool>,
+{
+    let backup = ProsaBackup::save(path)?;
+    let result = generate().and_then(|generated| {
+        if generated && verify {
+            check_prosa(path)
+        } else {
+            Ok(())
+        }
+    });
+
+    match result {
+        Ok(()) => backup.clean(),
+        Err(e) => {
+            backup.restore()?;
+            eprintln!(
+                "error: {e}, the previous ProSA files have been restored\nhint: the generated files may not match your ProSA dependencies version (cargo-prosa {}), update them or use `--no-verify` to keep the generated files",
+                env!("CARGO_PKG_VERSION")
+            );
+            Err(e)
+        }
+    }
+}
+
 /// Function to initiate ProSA project file (or update them if existing)
-fn init_prosa(path: &str, context: &tera::Context) -> io::Result<()> {
+///
+/// Generated files are checked with `cargo check` if `verify` is set, and restored on failure.
+fn init_prosa(path: &str, context: &tera::Context, verify: bool) -> io::Result<()> {
+    generate_prosa(path, verify, || write_prosa(path, context))
+}
+
+/// Function to write ProSA project files. Return `false` if the dependencies can't be added (nothing is generated)
+fn write_prosa(path: &str, context: &tera::Context) -> io::Result<bool> {
     let prosa_path = Path::new(&path);
 
     // Add dependencies
@@ -170,89 +272,211 @@ fn init_prosa(path: &str, context: &tera::Context) -> io::Result<()> {
             let mut cargo_toml_file = fs::File::create(prosa_path.join("Cargo.toml"))?;
             cargo_toml_file.write_all(cargo_doc.to_string().as_bytes())?;
         }
+
+        Ok(true)
+    } else {
+        Ok(false)
+    }
+}
+
+/// Templates of the files of a processor crate generated by `cargo prosa new-proc`, relative to the crate path
+const PROC_TEMPLATES: [(&str, &str); 3] = [
+    (
+        "src/lib.rs",
+        include_str!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/assets/proc/lib.rs.j2"
+        )),
+    ),
+    (
+        "src/proc.rs",
+        include_str!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/assets/proc/proc.rs.j2"
+        )),
+    ),
+    (
+        "src/adaptor.rs",
+        include_str!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/assets/proc/adaptor.rs.j2"
+        )),
+    ),
+];
+
+/// Function to render jinja processor files into a processor crate
+fn render_proc<P>(path: P, ctx: &tera::Context) -> Result<(), tera::Error>
+where
+    P: AsRef<Path>,
+{
+    let mut tera_proc = Tera::default();
+    tera_proc.add_raw_templates(PROC_TEMPLATES)?;
+    for (render_filename, _) in PROC_TEMPLATES {
+        let proc_file =
+            fs::File::create(path.as_ref().join(render_filename)).map_err(tera::Error::io_error)?;
+        tera_proc.render_to(render_filename, ctx, proc_file)?;
     }
 
     Ok(())
 }
 
-fn cli() -> Command {
-    Command::new("cargo")
-        .bin_name("cargo")
-        .subcommand_required(true)
-        .arg_required_else_help(true)
-        .subcommand(Command::new("prosa")
-            .about("ProSA builder")
-            .subcommand_required(true)
-            .arg_required_else_help(true)
-            .subcommand(
-                Command::new("new")
-                    .about("Create a new ProSA package")
-                    .arg(arg!(-n --name <NAME> "Set the package name. Defaults to the directory name"))
-                    .arg(arg!(--deb "Configure the ProSA to generate a deb package").action(clap::ArgAction::SetTrue))
-                    .arg(arg!(<PATH> "Name of the new ProSA"))
-                    .arg_required_else_help(true),
-            )
-            .subcommand(
-                Command::new("init")
-                    .about("Create a new ProSA package in an existing directory")
-                    .arg(arg!(--deb "Configure the ProSA to generate a deb package").action(clap::ArgAction::SetTrue))
-                    .arg(arg!(-n --name <NAME> "Set the package name. Defaults to the directory name"))
-            )
-            .subcommand(
-                Command::new("update")
-                    .about("Update ProSA files to the latest skeleton")
-                    .arg(arg!(--deb "Configure the ProSA to generate a deb package").action(clap::ArgAction::SetTrue))
-            )
-            .subcommand(
-                Command::new("add")
-                    .about("Add a ProSA processor")
-                    .arg(arg!(--dry_run "Displays what would be updated, but doesn't actually write the ProSA files").action(clap::ArgAction::SetTrue))
-                    .arg(arg!(-n --name <NAME> "Name of the processor schedule inside the ProSA (use the processor name by default)"))
-                    .arg(arg!(-a --adaptor <ADAPTOR> "Adaptor name to use for the processor"))
-                    .arg(arg!(<PROCESSOR> "Processor to add"))
-                    .arg_required_else_help(true),
-            )
-            .subcommand(
-                Command::new("remove")
-                    .about("Remove one or more ProSA processor")
-                    .arg(arg!(--dry_run "Displays what would be removed, but doesn't actually write the ProSA files").action(clap::ArgAction::SetTrue))
-                    .arg(arg!(<PROCESSORS> ... "Processors to remove"))
-                    .arg_required_else_help(true),
-            )
-            .subcommand(
-                Command::new("main")
-                    .about("Change the ProSA main processor")
-                    .arg(arg!(--dry_run "Displays what would be removed, but doesn't actually write the ProSA files").action(clap::ArgAction::SetTrue))
-                    .arg(arg!(<MAIN> "Name of the main processor"))
-                    .arg_required_else_help(true),
-            )
-            .subcommand(
-                Command::new("tvf")
-                    .about("Change the ProSA TVF internal messaging")
-                    .arg(arg!(--dry_run "Displays what would be removed, but doesn't actually write the ProSA files").action(clap::ArgAction::SetTrue))
-                    .arg(arg!(<TVF> "Name of the TVF"))
-                    .arg_required_else_help(true),
-            )
-            .subcommand(
-                Command::new("list")
-                    .about("List all available ProSA component")
-            )
-            .subcommand(
-                Command::new("container")
-                    .about("Create a container file to containerize ProSA")
-                    .arg(arg!(--docker "Generate Dockerfile container format").action(clap::ArgAction::SetTrue))
-                    .arg(arg!(-i --image <IMG> "Base image to use for ProSA container image").default_value("debian:stable-slim"))
-                    .arg(arg!(-b --builder <BUILDER_IMG> "Builder to use to compile the ProSA"))
-                    .arg(arg!(-p --package_manager <PKG_MANAGER> "Indicate which package manager to use with the Docker image to install pre-requisite").default_value("apt"))
-                    .arg(arg!([PATH] "Path of the output container file to generate an image"))
-            )
-            .subcommand(
-                Command::new("completion")
-                    .about("Output shell completion code for the specified shell (Bash, Elvish, Fish, PowerShell, or Zsh)")
-                    .arg(arg!(<SHELL>))
-                    .arg_required_else_help(true),
-            )
-        )
+/// Function to add the names of a processor to a Jinja context, from its crate name.
+/// The structures are prefixed by the camel case name, and the default service is the upper case name
+fn proc_j2_context(name: &str, kind: &str) -> tera::Context {
+    let mut j2_context = tera::Context::new();
+    let prefix: String = name
+        .split(['-', '_'])
+        .filter(|word| !word.is_empty())
+        .map(|word| {
+            let mut chars = word.chars();
+            chars
+                .next()
+                .map(|first| first.to_uppercase().chain(chars).collect::<String>())
+                .unwrap_or_default()
+        })
+        .collect();
+
+    j2_context.insert("name", name);
+    j2_context.insert("kind", kind);
+    j2_context.insert("description", &format!("ProSA {} processor", name));
+    j2_context.insert("prefix", &prefix);
+    j2_context.insert("fn_name", &name.replace('-', "_").to_lowercase());
+    j2_context.insert("service", &name.replace('-', "_").to_uppercase());
+    j2_context
+}
+
+/// Function to write the files of a processor crate, and declare the processor in its ProSA metadata.
+/// Return `false` if the dependencies can't be added (nothing is generated)
+fn write_proc(path: &str, context: &tera::Context) -> io::Result<bool> {
+    let proc_path = Path::new(&path);
+    let kind = context
+        .get("kind")
+        .and_then(|v| v.as_str())
+        .unwrap_or_default();
+
+    // Add dependencies
+    let mut cargo_add = vec![
+        cargo!("add", Some(path), "prosa"),
+        cargo!("add", Some(path), "prosa-utils"),
+        cargo!("add", Some(path), "serde", "--features", "derive"),
+        cargo!("add", Some(path), "tokio", "--features", "macros"),
+        cargo!("add", Some(path), "tracing"),
+    ];
+    if kind == "client" || kind == "server" {
+        cargo_add.push(cargo!("add", Some(path), "bytes"));
+        cargo_add.push(cargo!("add", Some(path), "url"));
+    }
+
+    if cargo_add.iter().all(|cargo| cargo.status.success()) {
+        // Create (or replace) processor files
+        render_proc(proc_path, context)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        // Declare the processor in the ProSA metadata
+        let cargo_toml = fs::read_to_string(proc_path.join("Cargo.toml"))?;
+        let mut cargo_doc = cargo_toml
+            .parse::<DocumentMut>()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        if let (Some(toml_edit::Item::Table(package_table)), Some(name), Some(prefix)) = (
+            cargo_doc.get_mut("package"),
+            context.get("name").and_then(|v| v.as_str()),
+            context.get("prefix").and_then(|v| v.as_str()),
+        ) {
+            if !package_table.contains_key("description") {
+                if let Some(description) = context.get("description").and_then(|v| v.as_str()) {
+                    package_table.insert("description", toml_edit::value(description));
+                }
+            }
+
+            let mut proc_table = toml_edit::Table::new();
+            proc_table.insert("proc", toml_edit::value(format!("proc::{}Proc", prefix)));
+            proc_table.insert(
+                "settings",
+                toml_edit::value(format!("proc::{}Settings", prefix)),
+            );
+            let mut adaptors = toml_edit::Array::new();
+            adaptors.push(format!("adaptor::{}DefaultAdaptor", prefix));
+            proc_table.insert("adaptor", toml_edit::value(adaptors));
+
+            let mut prosa_table = toml_edit::Table::new();
+            prosa_table.set_implicit(true);
+            prosa_table.insert(name, toml_edit::Item::Table(proc_table));
+
+            let metadata_table = package_table.entry("metadata").or_insert_with(|| {
+                let mut metadata_table = toml_edit::Table::new();
+                metadata_table.set_implicit(true);
+                toml_edit::Item::Table(metadata_table)
+            });
+            if let Some(metadata_table) = metadata_table.as_table_mut() {
+                metadata_table.insert("prosa", toml_edit::Item::Table(prosa_table));
+            }
+        }
+
+        // The test kit used by the processor unit tests need the `testing` feature of the same ProSA dependency
+        let prosa_dev_dep = match cargo_doc.get("dependencies").and_then(|d| d.get("prosa")) {
+            Some(toml_edit::Item::Value(toml_edit::Value::String(version))) => {
+                let mut prosa_dev_dep = toml_edit::InlineTable::new();
+                prosa_dev_dep.insert("version", version.value().into());
+                Some(prosa_dev_dep)
+            }
+            Some(toml_edit::Item::Value(toml_edit::Value::InlineTable(prosa_dep))) => {
+                Some(prosa_dep.clone())
+            }
+            _ => None,
+        };
+        if let Some(mut prosa_dev_dep) = prosa_dev_dep {
+            prosa_dev_dep.insert("features", toml_edit::Array::from_iter(["testing"]).into());
+            if let Some(dev_dependencies) = cargo_doc
+                .entry("dev-dependencies")
+                .or_insert(toml_edit::table())
+                .as_table_like_mut()
+            {
+                dev_dependencies.insert("prosa", toml_edit::value(prosa_dev_dep));
+            }
+        }
+
+        let mut cargo_toml_file = fs::File::create(proc_path.join("Cargo.toml"))?;
+        cargo_toml_file.write_all(cargo_doc.to_string().as_bytes())?;
+
+        // Run fmt to reformat code
+        let _ = cargo!("fmt", Some(path), "-q");
+
+        Ok(true)
+    } else {
+        Ok(false)
+    }
+}
+
+/// Function to print the drift between the recorded component versions and the current dependencies.
+/// If `sync` is set, the recorded versions are updated in the ProSA.toml file
+fn check_versions(cargo_metadata: &CargoMetadata, sync: bool) -> io::Result<()> {
+    let desc = Desc::read(CONFIGURATION_FILENAME)?;
+    let drifts = desc.version_drifts(cargo_metadata);
+    if drifts.is_empty() {
+        println!("All ProSA components match their recorded version");
+    } else {
+        eprintln!("warning: ProSA components drifted from their recorded version:");
+        for drift in &drifts {
+            eprintln!("  - {}", drift);
+        }
+    }
+
+    if sync {
+        let prosa_toml = fs::read_to_string(CONFIGURATION_FILENAME)?;
+        let mut prosa_doc = prosa_toml
+            .parse::<DocumentMut>()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let updated = Desc::lock_versions(&mut prosa_doc, cargo_metadata);
+        if updated > 0 {
+            let mut prosa_toml_file = fs::File::create(CONFIGURATION_FILENAME)?;
+            prosa_toml_file.write_all(prosa_doc.to_string().as_bytes())?;
+            println!("Recorded versions synced for {} processor(s)", updated);
+        }
+    } else if !drifts.is_empty() {
+        eprintln!("hint: use `--sync` to record the current versions");
+    }
+
+    Ok(())
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -283,7 +507,29 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 io::stderr().write_all(&cargo_new.stderr).unwrap();
 
                 if cargo_new.status.success() {
-                    init_prosa(path, &j2_context)?;
+                    init_prosa(path, &j2_context, !matches.get_flag("no-verify"))?;
+                }
+            }
+            Some(("new-proc", matches)) => {
+                let name = matches
+                    .get_one::<String>("NAME")
+                    .expect("required processor name");
+                let kind = matches
+                    .get_one::<String>("kind")
+                    .expect("default processor kind");
+
+                // Create the new Rust library
+                let cargo_new = std::process::Command::new("cargo")
+                    .args(["new", "--lib", name])
+                    .output()?;
+
+                io::stdout().write_all(&cargo_new.stdout).unwrap();
+                io::stderr().write_all(&cargo_new.stderr).unwrap();
+
+                if cargo_new.status.success() && !write_proc(name, &proc_j2_context(name, kind))? {
+                    return Err(Box::new(io::Error::other(
+                        "Can't add the dependencies of the processor crate",
+                    )));
                 }
             }
             Some(("init", matches)) => {
@@ -311,7 +557,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                     io::stderr().write_all(&cargo_init.stderr).unwrap();
 
                     if cargo_init.status.success() {
-                        init_prosa(path_name, &j2_context)?;
+                        init_prosa(path_name, &j2_context, !matches.get_flag("no-verify"))?;
                     }
                 } else {
                     return Err(Box::new(io::Error::new(
@@ -330,7 +576,10 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
                 if let Some(path_name) = env::current_dir()?.as_path().to_str() {
                     j2_context.insert("path", path_name);
-                    init_prosa(path_name, &j2_context)?;
+                    if Path::new(CONFIGURATION_FILENAME).exists() {
+                        check_versions(&CargoMetadata::load_metadata()?, matches.get_flag("sync"))?;
+                    }
+                    init_prosa(path_name, &j2_context, !matches.get_flag("no-verify"))?;
                 } else {
                     return Err(Box::new(io::Error::new(
                         io::ErrorKind::InvalidInput,
@@ -338,14 +587,68 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                     )));
                 }
             }
+            Some(("upgrade", matches)) => {
+                let package_metadata = CargoMetadata::load_package_metadata()?;
+                let mut j2_context = tera::Context::new();
+                package_metadata.j2_context(&mut j2_context);
+                if !j2_context.contains_key("deb_pkg") {
+                    j2_context.insert("deb_pkg", &false);
+                }
+
+                let path = env::current_dir()?;
+                if let Some(path_name) = path.to_str() {
+                    j2_context.insert("path", path_name);
+                }
+
+                let mut project = Project::new(path, j2_context);
+                if let Some(config_files) = matches.get_many::<String>("config") {
+                    config_files.for_each(|config_file| project.add_config_file(config_file));
+                } else {
+                    project.find_config_files()?;
+                }
+
+                println!(
+                    "Upgrade {} (prosa {}) from skeleton {} to {}",
+                    package_metadata.name,
+                    project.prosa_version()?.as_deref().unwrap_or("?"),
+                    project.skeleton_version()?,
+                    upgrade::SKELETON_VERSION
+                );
+                let migrations = upgrade::upgrade(&mut project, MIGRATIONS)?;
+                if migrations.is_empty() {
+                    println!("The ProSA project is up to date");
+                }
+                for migration in migrations {
+                    println!("  - {}", migration);
+                }
+                if !project.skipped_config_files().is_empty() {
+                    eprintln!("warning: configuration files not migrated:");
+                    for (config_file, reason) in project.skipped_config_files() {
+                        eprintln!("  - {}: {}", config_file.display(), reason);
+                    }
+                }
+
+                if matches.get_flag("dry_run") {
+                    print!("{}", project.diff());
+                } else {
+                    project.commit()?;
+                }
+            }
+            Some(("check", matches)) => {
+                let desc = Desc::read(CONFIGURATION_FILENAME)?;
+                desc.check_instances()?;
+                desc.startup_order()?;
+                let cargo_metadata =
+                    CargoMetadata::load_metadata_with(matches.get_flag("offline"))?;
+                check_versions(&cargo_metadata, matches.get_flag("sync"))?;
+            }
             Some(("add", matches)) => {
                 let dry_run = matches.get_flag("dry_run");
                 let prosa_toml = fs::read_to_string(CONFIGURATION_FILENAME)?;
                 let mut prosa_doc = prosa_toml.parse::<DocumentMut>()?;
                 if let Some(processor) = matches.get_one::<String>("PROCESSOR") {
-                    if let Some(proc_metadata) = CargoMetadata::load_metadata()?
-                        .prosa_proc_metadata()
-                        .get(processor)
+                    let cargo_metadata = CargoMetadata::load_metadata()?;
+                    if let Some(proc_metadata) = cargo_metadata.prosa_proc_metadata().get(processor)
                     {
                         let mut proc_desc = proc_metadata.get_proc_desc(
                             matches.get_one::<String>("adaptor").map(|x| x.as_str()),
@@ -360,6 +663,23 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                         // Use the processor name instead of the crate name
                         proc_desc.proc_name = processor.clone();
 
+                        // Each instance of a processor is keyed by its name, so it can't be reused
+                        let prosa_desc = toml::from_str::<Desc>(&prosa_toml)?;
+                        if prosa_desc.get_proc(&proc_desc.get_name()).is_some() {
+                            return Err(io::Error::new(
+                                io::ErrorKind::AlreadyExists,
+                                format!(
+                                    "The processor instance `{}` already exists, use `--name` to add another instance of `{}`",
+                                    proc_desc.get_name(),
+                                    processor
+                                ),
+                            )
+                            .into());
+                        }
+
+                        // Record the processor and adaptor versions to detect future drifts
+                        proc_desc.lock_versions(&cargo_metadata);
+
                         if !dry_run {
                             if let Some(toml_edit::Item::ArrayOfTables(array_tables)) =
                                 prosa_doc.get_mut("proc")
@@ -474,9 +794,29 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                     }
                 }
             }
-            Some(("list", _matches)) => {
-                let cargo_metadata = CargoMetadata::load_metadata()?;
-                print!("{}", cargo_metadata);
+            Some(("list", matches)) => {
+                let cargo_metadata =
+                    CargoMetadata::load_metadata_with(matches.get_flag("offline"))?;
+                let kind = matches
+                    .get_one::<String>("kind")
+                    .map(|k| ComponentKind::from_str(k))
+                    .transpose()?;
+                match matches.get_one::<String>("format").map(|f| f.as_str()) {
+                    Some("json") => println!(
+                        "{}",
+                        serde_json::to_string_pretty(&cargo_metadata.components(kind))?
+                    ),
+                    Some("toml") => {
+                        print!("{}", toml::to_string(&cargo_metadata.components(kind))?)
+                    }
+                    _ => {
+                        if kind.is_some() {
+                            print!("{}", cargo_metadata.components(kind));
+                        } else {
+                            print!("{}", cargo_metadata);
+                        }
+                    }
+                }
             }
             Some(("container", matches)) => {
                 let container = ContainerFile::new(matches)?;
@@ -503,3 +843,69 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generate_rollback() {
+        let prosa_path = env::temp_dir().join("cargo-prosa-rollback-test");
+        let _ = fs::remove_dir_all(&prosa_path);
+        fs::create_dir_all(prosa_path.join("src")).unwrap();
+        let cargo_toml =
+            "[package]\nname = \"rollback\"\nversion = \"0.1.0\"\nedition = \"2021\"\n";
+        let main_rs = "fn main() {}\n";
+        let prosa_toml = "[prosa]\nmain = \"prosa::core::main::MainProc\"\n";
+        fs::write(prosa_path.join("Cargo.toml"), cargo_toml).unwrap();
+        fs::write(prosa_path.join("src").join("main.rs"), main_rs).unwrap();
+        fs::write(prosa_path.join(CONFIGURATION_FILENAME), prosa_toml).unwrap();
+        let path = prosa_path.to_str().unwrap();
+
+        // Render invalid code through the template context
+        let mut j2_context = tera::Context::new();
+        j2_context.insert("name", "\"); compile_error!(\"invalid");
+        j2_context.insert("deb_pkg", &false);
+        let render = || {
+            render_build_rs(prosa_path.join("build.rs"), &j2_context)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            render_main_rs(prosa_path.join("src").join("main.rs"), &j2_context)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            fs::write(prosa_path.join(CONFIGURATION_FILENAME), "")?;
+            Ok(true)
+        };
+
+        // Without verification, the generated files are kept
+        generate_prosa(path, false, render).unwrap();
+        assert!(prosa_path.join("build.rs").exists());
+        assert!(fs::read_to_string(prosa_path.join("src").join("main.rs"))
+            .unwrap()
+            .contains("compile_error!"));
+        fs::write(prosa_path.join("src").join("main.rs"), main_rs).unwrap();
+        fs::write(prosa_path.join(CONFIGURATION_FILENAME), prosa_toml).unwrap();
+        fs::remove_file(prosa_path.join("build.rs")).unwrap();
+
+        // With verification, the project doesn't compile so files are restored
+        assert!(generate_prosa(path, true, render).is_err());
+        assert!(!prosa_path.join("build.rs").exists());
+        assert_eq!(
+            main_rs,
+            fs::read_to_string(prosa_path.join("src").join("main.rs")).unwrap()
+        );
+        assert_eq!(
+            cargo_toml,
+            fs::read_to_string(prosa_path.join("Cargo.toml")).unwrap()
+        );
+        assert_eq!(
+            prosa_toml,
+            fs::read_to_string(prosa_path.join(CONFIGURATION_FILENAME)).unwrap()
+        );
+        for file in GENERATED_FILES {
+            let mut backup_path = prosa_path.join(file).into_os_string();
+            backup_path.push(".bak");
+            assert!(!Path::new(&backup_path).exists());
+        }
+
+        let _ = fs::remove_dir_all(&prosa_path);
+    }
+}
@@ -19,9 +19,14 @@ impl ContainerFile {
     /// Create a container file builder from `cargo-prosa` command arguments
     pub fn new(args: &ArgMatches) -> io::Result<ContainerFile> {
         let package_metadata = CargoMetadata::load_package_metadata()?;
-        let is_docker = args.get_flag("docker");
         let mut ctx = tera::Context::new();
         package_metadata.j2_context(&mut ctx);
+        Ok(Self::with_context(ctx, args))
+    }
+
+    /// Create a container file builder from a package context and `cargo-prosa` command arguments
+    fn with_context(mut ctx: tera::Context, args: &ArgMatches) -> ContainerFile {
+        let is_docker = args.get_flag("docker");
         ctx.insert("docker", &is_docker);
         ctx.insert(
             "image",
@@ -37,12 +42,24 @@ impl ContainerFile {
         if let Some(img) = builder_img {
             ctx.insert("builder_image", img);
         }
+        ctx.insert("chef", &args.get_flag("chef"));
+        ctx.insert(
+            "user",
+            args.get_one::<String>("user")
+                .expect("required container user"),
+        );
+        if let Some(target_arch) = args.get_one::<String>("target-arch") {
+            ctx.insert("target_arch", target_arch);
+        }
+        if let Some(health_endpoint) = args.get_one::<String>("health_endpoint") {
+            ctx.insert("health_endpoint", health_endpoint);
+        }
 
-        Ok(ContainerFile {
+        ContainerFile {
             is_docker,
             ctx,
             path: args.get_one::<String>("PATH").cloned(),
-        })
+        }
     }
 
     /// Method to get the path of the Dockerfile/Containerfile
@@ -65,8 +82,7 @@ impl ContainerFile {
         }
     }
 
-    /// Method to create a container file
-    pub fn create_container_file(&self) -> tera::Result<()> {
+    fn get_template(&self) -> tera::Result<(Tera, &'static str)> {
         let template_name = if self.is_docker {
             "Dockerfile"
         } else {
@@ -79,6 +95,18 @@ impl ContainerFile {
             include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/assets/container.j2")),
         )?;
 
+        Ok((tera_build, template_name))
+    }
+
+    /// Method to render the container file content
+    pub fn render(&self) -> tera::Result<String> {
+        let (tera_build, template_name) = self.get_template()?;
+        tera_build.render(template_name, &self.ctx)
+    }
+
+    /// Method to create a container file
+    pub fn create_container_file(&self) -> tera::Result<()> {
+        let (tera_build, template_name) = self.get_template()?;
         let build_file = fs::File::create(self.get_path()).map_err(tera::Error::io_error)?;
         tera_build.render_to(template_name, &self.ctx, build_file)
     }
@@ -113,15 +141,148 @@ impl fmt::Display for ContainerFile {
             } else {
                 Ok(())
             }
-        } else if self.path.is_some() {
-            writeln!(
-                f,
-                "  `podman build -f {} -t {} .`",
-                self.get_path().display(),
-                img_name
-            )
         } else {
-            writeln!(f, "  `podman build -t {} .`", img_name)
+            // HEALTHCHECK is not part of the OCI image format, so it need the docker format to be kept
+            write!(f, "  `podman build")?;
+            if self.ctx.contains_key("health_endpoint") {
+                write!(f, " --format docker")?;
+            }
+            if self.path.is_some() {
+                write!(f, " -f {}", self.get_path().display())?;
+            }
+            writeln!(f, " -t {} .`", img_name)
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use clap::ArgMatches;
+
+    use super::*;
+    use crate::cli;
+
+    fn container_matches(args: &[&str]) -> clap::error::Result<ArgMatches> {
+        let matches =
+            cli().try_get_matches_from(["cargo", "prosa", "container"].iter().chain(args))?;
+        let (_, prosa_matches) = matches.subcommand().unwrap();
+        let (_, container_matches) = prosa_matches.subcommand().unwrap();
+        Ok(container_matches.clone())
+    }
+
+    fn container_file(args: &[&str]) -> ContainerFile {
+        let mut ctx = tera::Context::new();
+        ctx.insert("name", "prosa-test");
+        ctx.insert("version", "0.1.0");
+        ContainerFile::with_context(ctx, &container_matches(args).unwrap())
+    }
+
+    #[test]
+    fn container_file_default() {
+        let container = container_file(&[]);
+        assert_eq!(Path::new("Containerfile"), container.get_path());
+
+        let content = container.render().unwrap();
+        assert!(content.starts_with("FROM debian:stable-slim\n"));
+        assert!(!content.contains("AS builder"));
+        assert!(content
+            .contains("COPY --chmod=755 target/release/prosa-test /usr/local/bin/prosa-test\n"));
+        assert!(content.contains("useradd --system --no-create-home --gid prosa"));
+        assert!(content.contains("USER prosa\n"));
+        assert!(!content.contains("HEALTHCHECK"));
+        assert!(content.ends_with(
+            "ENTRYPOINT [\"/usr/local/bin/prosa-test\", \"-c\", \"/etc/prosa-test.yml\"]\n"
+        ));
+        assert!(container
+            .to_string()
+            .contains("`podman build -t prosa-test:0.1.0 .`"));
+    }
+
+    #[test]
+    fn container_file_builder() {
+        let container = container_file(&["-b", "rust:latest", "-u", "root"]);
+        let content = container.render().unwrap();
+        assert!(content.starts_with("FROM rust:latest AS builder\nWORKDIR /opt\nCOPY . .\n"));
+        assert!(content.contains("\nRUN cargo build -r\n"));
+        assert!(!content.contains("--mount=type=ssh"));
+        assert!(!content.contains("cargo chef"));
+        assert!(content.contains(
+            "COPY --from=builder --chmod=755 /opt/target/release/prosa-test /usr/local/bin/prosa-test\n"
+        ));
+        assert!(!content.contains("USER"));
+    }
+
+    #[test]
+    fn dockerfile_chef_builder() {
+        let container = container_file(&[
+            "--docker",
+            "-b",
+            "rust:latest",
+            "--chef",
+            "--target-arch",
+            "aarch64-unknown-linux-gnu",
+            "--health_endpoint",
+            "http://localhost:9100/metrics",
+        ]);
+        assert_eq!(Path::new("Dockerfile"), container.get_path());
+
+        let content = container.render().unwrap();
+        assert!(content.starts_with(
+            "FROM rust:latest AS chef\nRUN cargo install cargo-chef --locked\nWORKDIR /opt\n"
+        ));
+        assert!(content.contains(
+            "FROM chef AS planner\nCOPY . .\nRUN cargo chef prepare --recipe-path recipe.json\n"
+        ));
+        assert!(content.contains("FROM chef AS builder\n"));
+        assert!(content.contains("RUN rustup target add aarch64-unknown-linux-gnu\n"));
+        assert!(content.contains("COPY --from=planner /opt/recipe.json recipe.json\n"));
+        assert!(content.contains(
+            " && cargo chef cook -r --target aarch64-unknown-linux-gnu --recipe-path recipe.json\n"
+        ));
+        assert!(content.contains(" && cargo build -r --target aarch64-unknown-linux-gnu\n"));
+
+        // Dependencies must be cooked before the sources are copied
+        let cook_pos = content.find("cargo chef cook").unwrap();
+        let copy_pos = content.rfind("COPY . .").unwrap();
+        assert!(cook_pos < copy_pos);
+
+        assert!(content.contains("COPY --from=builder --chmod=755 /opt/target/aarch64-unknown-linux-gnu/release/prosa-test /usr/local/bin/prosa-test\n"));
+        assert!(content.contains("      curl \\\n"));
+        assert!(content.contains("USER prosa\n"));
+        assert!(content.contains("CMD curl -fs http://localhost:9100/metrics || exit 1\n"));
+
+        let help = container.to_string();
+        assert!(help.contains("`docker build -t prosa-test:0.1.0 .`"));
+        assert!(help.contains("--ssh default=$SSH_AUTH_SOCK"));
+    }
+
+    #[test]
+    fn containerfile_oci() {
+        let container = container_file(&[
+            "-i",
+            "alpine:latest",
+            "-p",
+            "apk",
+            "--health_endpoint",
+            "http://localhost:9100/metrics",
+            "/tmp/Containerfile.prosa",
+        ]);
+        let content = container.render().unwrap();
+        assert!(content.starts_with("FROM alpine:latest\n"));
+        assert!(!content.contains("apt-get"));
+        assert!(
+            content.contains("RUN /usr/local/bin/prosa-test -c /etc/prosa-test.yml --dry_run\n")
+        );
+        assert!(content.contains("RUN apk add --no-cache curl\n"));
+        assert!(content.contains("adduser -S -D -H -G prosa prosa\n"));
+        assert!(content.contains("CMD curl -fs http://localhost:9100/metrics || exit 1\n"));
+        assert!(container.to_string().contains(
+            "`podman build --format docker -f /tmp/Containerfile.prosa -t prosa-test:0.1.0 .`"
+        ));
+    }
+
+    #[test]
+    fn container_chef_requires_builder() {
+        assert!(container_matches(&["--chef"]).is_err());
+    }
+}
@@ -0,0 +1,923 @@
+//! Tool to upgrade a ProSA project across breaking ProSA versions
+//!
+//! The version of the skeleton (the files generated by cargo-prosa) is recorded in the `skeleton` key of the ProSA.toml file.
+//! An upgrade apply in order the [`MIGRATIONS`] newer than this version, then record the version of the last one.
+//!
+//! Migrations don't write the project files directly. Their changes are kept in the [`Project`] to be displayed as a diff, or written once all migrations succeeded.
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt, fs, io,
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use toml_edit::{DocumentMut, InlineTable, Item, Key, Table, Value};
+
+use crate::{builder::Desc, BUILD_RS_TEMPLATE, CONFIGURATION_FILENAME, MAIN_RS_TEMPLATE};
+
+/// Version of the skeleton generated by this cargo-prosa, the version of its last migration
+pub const SKELETON_VERSION: u32 = 4;
+
+/// Migration of a ProSA project to a skeleton version
+#[derive(Debug)]
+pub struct Migration {
+    /// Skeleton version of the project once migrated
+    pub version: u32,
+    /// Description of the migration, displayed during the upgrade
+    pub description: &'static str,
+    /// Function that apply the migration to the project
+    pub apply: fn(&mut Project) -> io::Result<()>,
+}
+
+impl fmt::Display for Migration {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[{}] {}", self.version, self.description)
+    }
+}
+
+/// Migrations of the ProSA projects, ordered by skeleton version
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "Render the build.rs and main.rs files with the current templates",
+        apply: render_templates,
+    },
+    Migration {
+        version: 2,
+        description: "Rename the legacy processor sections of the configuration files to the processor instance names",
+        apply: rename_legacy_sections,
+    },
+    Migration {
+        version: 3,
+        description: "Require the ProSA dependencies of this cargo-prosa version",
+        apply: bump_prosa_dependencies,
+    },
+    Migration {
+        version: 4,
+        description: "Render the build.rs and main.rs files to start the processors in their dependencies order",
+        apply: render_templates,
+    },
+];
+
+fn render_templates(project: &mut Project) -> io::Result<()> {
+    project.render("build.rs", BUILD_RS_TEMPLATE)?;
+    project.render("src/main.rs", MAIN_RS_TEMPLATE)
+}
+
+fn rename_legacy_sections(project: &mut Project) -> io::Result<()> {
+    let prosa_toml = project.read(CONFIGURATION_FILENAME)?.unwrap_or_default();
+    let desc = toml::from_str::<Desc>(&prosa_toml)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+    // A legacy section shared by several instances can't be given to only one of them
+    let mut legacy_sections: HashMap<String, Vec<String>> = HashMap::new();
+    for proc in desc.proc.iter().flatten() {
+        for section in proc.get_legacy_sections() {
+            legacy_sections
+                .entry(section)
+                .or_default()
+                .push(proc.get_name());
+        }
+    }
+
+    let renames: Vec<(String, String)> = desc
+        .proc
+        .iter()
+        .flatten()
+        .flat_map(|proc| {
+            proc.get_legacy_sections()
+                .into_iter()
+                .map(move |section| (section, proc.get_name()))
+        })
+        .filter(|(section, _)| legacy_sections.get(section).is_some_and(|n| n.len() == 1))
+        .collect();
+    let renames: Vec<(&str, &str)> = renames
+        .iter()
+        .map(|(section, name)| (section.as_str(), name.as_str()))
+        .collect();
+    project.rename_settings_keys(&renames)
+}
+
+fn bump_prosa_dependencies(project: &mut Project) -> io::Result<()> {
+    let version = env!("CARGO_PKG_VERSION");
+    project.bump_dependency("dependencies", "prosa", version)?;
+    project.bump_dependency("dependencies", "prosa-utils", version)?;
+    project.bump_dependency("build-dependencies", "cargo-prosa", version)?;
+    Ok(())
+}
+
+/// Method to upgrade a project with the migrations newer than its skeleton version, and record the version of the last applied one.
+/// Return the applied migrations
+///
+/// Migrations are kept in the project, so nothing is written until [`Project::commit`] is called.
+pub fn upgrade<'a>(
+    project: &mut Project,
+    migrations: &'a [Migration],
+) -> io::Result<Vec<&'a Migration>> {
+    let skeleton = project.skeleton_version()?;
+    if let Some(last) = migrations.last() {
+        if skeleton > last.version {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "The project skeleton version {} is newer than the cargo-prosa one ({}), update cargo-prosa",
+                    skeleton, last.version
+                ),
+            ));
+        }
+    }
+
+    let mut applied = Vec::new();
+    for migration in migrations.iter().filter(|m| m.version > skeleton) {
+        (migration.apply)(project).map_err(|e| {
+            io::Error::new(e.kind(), format!("migration {} failed: {}", migration, e))
+        })?;
+        applied.push(migration);
+    }
+
+    if let Some(migration) = applied.last() {
+        project.set_skeleton_version(migration.version)?;
+    }
+
+    Ok(applied)
+}
+
+/// TOML files of a project that are not configuration files
+const NON_CONFIG_TOML_FILES: &[&str] = &[
+    "Cargo.toml",
+    CONFIGURATION_FILENAME,
+    "rust-toolchain.toml",
+    "rustfmt.toml",
+    "clippy.toml",
+    "deny.toml",
+    "Cross.toml",
+];
+
+/// Content of a project file changed by the migrations
+#[derive(Debug)]
+struct FileChange {
+    /// Content of the file on disk, `None` if the file doesn't exist
+    original: Option<String>,
+    /// Content of the file after the migrations
+    content: String,
+}
+
+/// ProSA project to upgrade, with the changes of its files
+#[derive(Debug)]
+pub struct Project {
+    path: PathBuf,
+    context: tera::Context,
+    config_files: Vec<PathBuf>,
+    skipped_config_files: Vec<(PathBuf, String)>,
+    files: BTreeMap<PathBuf, FileChange>,
+}
+
+impl Project {
+    /// Method to create a project from its path, with the Jinja context used to render its templates
+    pub fn new<P>(path: P, context: tera::Context) -> Project
+    where
+        P: Into<PathBuf>,
+    {
+        Project {
+            path: path.into(),
+            context,
+            config_files: Vec::new(),
+            skipped_config_files: Vec::new(),
+            files: BTreeMap::new(),
+        }
+    }
+
+    /// Method to add a configuration file (relative to the project path) to migrate
+    pub fn add_config_file<P>(&mut self, file: P)
+    where
+        P: Into<PathBuf>,
+    {
+        self.config_files.push(file.into());
+    }
+
+    /// Method to add the YAML and TOML files of the project directory and its subdirectories as configuration files to migrate
+    ///
+    /// Hidden entries, the `target` directory, and the TOML files of the tools ([`NON_CONFIG_TOML_FILES`]) are ignored.
+    pub fn find_config_files(&mut self) -> io::Result<()> {
+        let mut config_files = Vec::new();
+        let mut dirs = vec![PathBuf::new()];
+        while let Some(dir) = dirs.pop() {
+            for entry in fs::read_dir(self.path.join(&dir))? {
+                let entry = entry?;
+                let file_name = entry.file_name();
+                let path = dir.join(&file_name);
+                if file_name.to_string_lossy().starts_with('.') {
+                    continue;
+                }
+
+                if entry.file_type()?.is_dir() {
+                    if path != Path::new("target") {
+                        dirs.push(path);
+                    }
+                } else if path
+                    .extension()
+                    .is_some_and(|ext| ext == "yml" || ext == "yaml")
+                    || (path.extension().is_some_and(|ext| ext == "toml")
+                        && !NON_CONFIG_TOML_FILES.iter().any(|name| file_name == *name))
+                {
+                    config_files.push(path);
+                }
+            }
+        }
+
+        config_files.sort();
+        self.config_files.append(&mut config_files);
+        Ok(())
+    }
+
+    /// Getter of the configuration files that couldn't be migrated, with the reason
+    pub fn skipped_config_files(&self) -> &[(PathBuf, String)] {
+        &self.skipped_config_files
+    }
+
+    fn skip_config_file(&mut self, file: PathBuf, reason: String) {
+        if !self.skipped_config_files.iter().any(|(f, _)| *f == file) {
+            self.skipped_config_files.push((file, reason));
+        }
+    }
+
+    /// Getter of a project file content (with the changes of the migrations), `None` if the file doesn't exist
+    pub fn read<P>(&self, file: P) -> io::Result<Option<String>>
+    where
+        P: AsRef<Path>,
+    {
+        if let Some(change) = self.files.get(file.as_ref()) {
+            Ok(Some(change.content.clone()))
+        } else {
+            match fs::read_to_string(self.path.join(file)) {
+                Ok(content) => Ok(Some(content)),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+                Err(e) => Err(e),
+            }
+        }
+    }
+
+    /// Method to change the content of a project file
+    pub fn write<P>(&mut self, file: P, content: String) -> io::Result<()>
+    where
+        P: Into<PathBuf>,
+    {
+        let file = file.into();
+        if let Some(change) = self.files.get_mut(&file) {
+            change.content = content;
+        } else {
+            let original = self.read(&file)?;
+            if original.as_ref() != Some(&content) {
+                self.files.insert(file, FileChange { original, content });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Method to render a Jinja template into a project file
+    pub fn render<P>(&mut self, file: P, template: &str) -> io::Result<()>
+    where
+        P: Into<PathBuf>,
+    {
+        let content = tera::Tera::one_off(template, &self.context, false)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        self.write(file, content)
+    }
+
+    /// Method to rename settings keys in the configuration files of the project, from a table of `(path, new key)`
+    ///
+    /// Paths are the keys of the settings sections separated by dots, where `*` match any key.
+    /// Only the last key of a path is renamed, and only if the new key doesn't already exist in its section.
+    ///
+    /// Files with a `.toml` extension are TOML, the others YAML (like the ProSA settings).
+    /// Configuration files that are missing or can't be parsed are kept in the [`Project::skipped_config_files`].
+    pub fn rename_settings_keys(&mut self, renames: &[(&str, &str)]) -> io::Result<()> {
+        for file in self.config_files.clone() {
+            match self.read(&file)? {
+                Some(content) if file.extension().is_some_and(|ext| ext == "toml") => {
+                    match rename_toml_keys(&content, renames) {
+                        Ok(content) => self.write(file, content)?,
+                        Err(e) => self.skip_config_file(file, e.to_string().trim().to_string()),
+                    }
+                }
+                Some(content) => self.write(file, rename_yaml_keys(&content, renames))?,
+                None => self.skip_config_file(file, String::from("file not found")),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Method to raise the version requirement of a dependency of the Cargo.toml file (in the `table` dependencies table).
+    /// Return `true` if the requirement was bumped
+    ///
+    /// Dependencies without version, with a requirement that is not a simple version, or already at this version are left unchanged.
+    pub fn bump_dependency(&mut self, table: &str, name: &str, version: &str) -> io::Result<bool> {
+        let mut cargo_doc = self.cargo_doc()?;
+        let requirement = match cargo_doc.get_mut(table).and_then(|t| t.get_mut(name)) {
+            Some(Item::Value(Value::String(requirement))) => Some(requirement),
+            Some(Item::Value(Value::InlineTable(dep))) => match dep.get_mut("version") {
+                Some(Value::String(requirement)) => Some(requirement),
+                _ => None,
+            },
+            Some(Item::Table(dep)) => match dep.get_mut("version") {
+                Some(Item::Value(Value::String(requirement))) => Some(requirement),
+                _ => None,
+            },
+            _ => None,
+        };
+
+        if let Some(requirement) = requirement {
+            if let (Some(current), Some(required)) =
+                (parse_version(requirement.value()), parse_version(version))
+            {
+                if current < required {
+                    let decor = requirement.decor().clone();
+                    *requirement = toml_edit::Formatted::new(version.to_string());
+                    *requirement.decor_mut() = decor;
+                    self.write("Cargo.toml", cargo_doc.to_string())?;
+                    return Ok(true);
+                }
+            }
+        }
+
+        Ok(false)
+    }
+
+    /// Getter of the prosa dependency version requirement from the Cargo.toml file
+    pub fn prosa_version(&self) -> io::Result<Option<String>> {
+        let cargo_doc = self.cargo_doc()?;
+        Ok(
+            match cargo_doc.get("dependencies").and_then(|d| d.get("prosa")) {
+                Some(Item::Value(Value::String(requirement))) => Some(requirement.value().clone()),
+                Some(dep) => dep
+                    .get("version")
+                    .and_then(|v| v.as_str())
+                    .map(String::from),
+                None => None,
+            },
+        )
+    }
+
+    /// Getter of the skeleton version recorded in the ProSA.toml file (0 if not recorded)
+    pub fn skeleton_version(&self) -> io::Result<u32> {
+        let prosa_doc = self.prosa_doc()?;
+        Ok(prosa_doc
+            .get("prosa")
+            .and_then(|p| p.get("skeleton"))
+            .and_then(|s| s.as_integer())
+            .and_then(|s| u32::try_from(s).ok())
+            .unwrap_or_default())
+    }
+
+    /// Method to record the skeleton version in the ProSA.toml file
+    pub fn set_skeleton_version(&mut self, version: u32) -> io::Result<()> {
+        let mut prosa_doc = self.prosa_doc()?;
+        if let Some(prosa_table) = prosa_doc
+            .entry("prosa")
+            .or_insert(toml_edit::table())
+            .as_table_like_mut()
+        {
+            prosa_table.insert("skeleton", toml_edit::value(version as i64));
+        }
+
+        self.write(CONFIGURATION_FILENAME, prosa_doc.to_string())
+    }
+
+    /// Getter of the unified diff of the changed files
+    pub fn diff(&self) -> String {
+        let mut diff = String::new();
+        for (file, change) in &self.files {
+            if change.original.as_ref() != Some(&change.content) {
+                if change.original.is_some() {
+                    diff.push_str(&format!("--- a/{}\n", file.display()));
+                } else {
+                    diff.push_str("--- /dev/null\n");
+                }
+                diff.push_str(&format!("+++ b/{}\n", file.display()));
+                diff_lines(
+                    change.original.as_deref().unwrap_or_default(),
+                    &change.content,
+                    &mut diff,
+                );
+            }
+        }
+
+        diff
+    }
+
+    /// Method to write the changed files of the project
+    pub fn commit(self) -> io::Result<()> {
+        for (file, change) in self.files {
+            if change.original.as_ref() != Some(&change.content) {
+                let file_path = self.path.join(file);
+                if let Some(parent) = file_path.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                fs::write(file_path, change.content)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn cargo_doc(&self) -> io::Result<DocumentMut> {
+        self.read("Cargo.toml")?
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "No Cargo.toml file"))?
+            .parse::<DocumentMut>()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    fn prosa_doc(&self) -> io::Result<DocumentMut> {
+        self.read(CONFIGURATION_FILENAME)?
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!(
+                        "No {} file, it's not a ProSA project",
+                        CONFIGURATION_FILENAME
+                    ),
+                )
+            })?
+            .parse::<DocumentMut>()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+/// Function to parse a simple version requirement (`1.2`, `^1.2.3`, `=1.2.3`) into its numbers
+fn parse_version(requirement: &str) -> Option<Vec<u64>> {
+    let mut version: Vec<u64> = requirement
+        .trim()
+        .trim_start_matches(['^', '='])
+        .split('.')
+        .map(|n| n.parse().ok())
+        .collect::<Option<_>>()?;
+    version.resize(3, 0);
+    Some(version)
+}
+
+/// Function to get the keys of a YAML document, with their line, their path, and their position in the line
+fn yaml_keys(content: &str) -> Vec<(usize, Vec<String>, Range<usize>)> {
+    let mut keys = Vec::new();
+    let mut stack: Vec<(usize, String)> = Vec::new();
+    for (index, line) in content.lines().enumerate() {
+        let mut column = line.len() - line.trim_start_matches(' ').len();
+        let mut rest = &line[column..];
+        if let Some(item) = rest.strip_prefix("- ") {
+            column += 2 + item.len() - item.trim_start_matches(' ').len();
+            rest = &line[column..];
+        }
+
+        if rest.starts_with('#') {
+            continue;
+        }
+
+        let Some(end) = rest
+            .find(": ")
+            .or_else(|| rest.strip_suffix(':').map(str::len))
+        else {
+            continue;
+        };
+        let key = rest[..end].trim_matches(['"', '\'']);
+        if key.is_empty() || key.contains(['#', ' ', '{', '[']) {
+            continue;
+        }
+
+        while stack.last().is_some_and(|(c, _)| *c >= column) {
+            stack.pop();
+        }
+        stack.push((column, key.to_string()));
+        keys.push((
+            index,
+            stack.iter().map(|(_, k)| k.clone()).collect(),
+            column..column + end,
+        ));
+    }
+
+    keys
+}
+
+/// Function to get the new key of a settings path from a table of `(path, new key)`, if it's renamed
+fn renamed_key<'a>(path: &[String], renames: &[(&str, &'a str)]) -> Option<&'a str> {
+    renames
+        .iter()
+        .find(|(from, _)| {
+            let from: Vec<&str> = from.split('.').collect();
+            from.len() == path.len() && from.iter().zip(path).all(|(f, k)| *f == "*" || f == k)
+        })
+        .map(|(_, to)| *to)
+}
+
+/// Function to rename keys of a YAML document (keeping its comments and format) from a table of `(path, new key)`
+fn rename_yaml_keys(content: &str, renames: &[(&str, &str)]) -> String {
+    let keys = yaml_keys(content);
+    let mut paths: HashSet<Vec<String>> = keys.iter().map(|(_, path, _)| path.clone()).collect();
+    let mut lines: Vec<String> = content.split_inclusive('\n').map(String::from).collect();
+    for (index, path, range) in keys {
+        if let Some(to) = renamed_key(&path, renames) {
+            let mut renamed = path.clone();
+            if let Some(key) = renamed.last_mut() {
+                *key = to.to_string();
+            }
+
+            if !paths.contains(&renamed) {
+                lines[index].replace_range(range, to);
+                paths.insert(renamed);
+            }
+        }
+    }
+
+    lines.concat()
+}
+
+/// Function to rename keys of a TOML document (keeping its comments and format) from a table of `(path, new key)`
+fn rename_toml_keys(
+    content: &str,
+    renames: &[(&str, &str)],
+) -> Result<String, toml_edit::TomlError> {
+    let mut doc = content.parse::<DocumentMut>()?;
+    rename_toml_table(doc.as_table_mut(), &mut Vec::new(), renames);
+    Ok(doc.to_string())
+}
+
+/// Function to get the key of a TOML entry once renamed (if the new key is not in the `keys` of its table), keeping its format
+fn rename_toml_key(
+    key: Key,
+    path: &[String],
+    renames: &[(&str, &str)],
+    keys: &mut Vec<String>,
+) -> Key {
+    match renamed_key(path, renames) {
+        Some(to) if !keys.iter().any(|k| k == to) => {
+            keys.push(to.to_string());
+            Key::new(to)
+                .with_leaf_decor(key.leaf_decor().clone())
+                .with_dotted_decor(key.dotted_decor().clone())
+        }
+        _ => key,
+    }
+}
+
+fn rename_toml_table(table: &mut Table, path: &mut Vec<String>, renames: &[(&str, &str)]) {
+    // Entries are inserted back in their order to keep the document layout
+    let keys: Vec<String> = table.iter().map(|(k, _)| k.to_string()).collect();
+    let mut table_keys = keys.clone();
+    for k in &keys {
+        if let Some((key, mut item)) = table.remove_entry(k) {
+            path.push(k.clone());
+            match &mut item {
+                Item::Table(sub_table) => rename_toml_table(sub_table, path, renames),
+                Item::ArrayOfTables(tables) => tables
+                    .iter_mut()
+                    .for_each(|sub_table| rename_toml_table(sub_table, path, renames)),
+                Item::Value(value) => rename_toml_value(value, path, renames),
+                Item::None => {}
+            }
+
+            let key = rename_toml_key(key, path, renames, &mut table_keys);
+            path.pop();
+            table.insert_formatted(&key, item);
+        }
+    }
+}
+
+fn rename_toml_inline_table(
+    table: &mut InlineTable,
+    path: &mut Vec<String>,
+    renames: &[(&str, &str)],
+) {
+    let keys: Vec<String> = table.iter().map(|(k, _)| k.to_string()).collect();
+    let mut table_keys = keys.clone();
+    for k in &keys {
+        if let Some((key, mut value)) = table.remove_entry(k) {
+            path.push(k.clone());
+            rename_toml_value(&mut value, path, renames);
+            let key = rename_toml_key(key, path, renames, &mut table_keys);
+            path.pop();
+            table.insert_formatted(&key, value);
+        }
+    }
+}
+
+fn rename_toml_value(value: &mut Value, path: &mut Vec<String>, renames: &[(&str, &str)]) {
+    match value {
+        Value::InlineTable(table) => rename_toml_inline_table(table, path, renames),
+        Value::Array(array) => array
+            .iter_mut()
+            .for_each(|value| rename_toml_value(value, path, renames)),
+        _ => {}
+    }
+}
+
+/// Function to write the unified diff of two contents, with 3 lines of context around the changes
+fn diff_lines(original: &str, content: &str, diff: &mut String) {
+    const CONTEXT: usize = 3;
+    let old: Vec<&str> = original.lines().collect();
+    let new: Vec<&str> = content.lines().collect();
+
+    // Longest common subsequence of the lines, from the end
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    // Operations with the position of their lines in the old and new contents
+    let mut ops = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            ops.push((' ', old[i], i, j));
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            ops.push(('-', old[i], i, j));
+            i += 1;
+        } else {
+            ops.push(('+', new[j], i, j));
+            j += 1;
+        }
+    }
+
+    let changes: Vec<usize> = (0..ops.len()).filter(|&k| ops[k].0 != ' ').collect();
+    let mut change = 0;
+    while change < changes.len() {
+        let start = changes[change].saturating_sub(CONTEXT);
+        while change + 1 < changes.len() && changes[change + 1] - changes[change] <= 2 * CONTEXT + 1
+        {
+            change += 1;
+        }
+        let end = (changes[change] + CONTEXT + 1).min(ops.len());
+        change += 1;
+
+        let hunk = &ops[start..end];
+        let old_len = hunk.iter().filter(|op| op.0 != '+').count();
+        let new_len = hunk.iter().filter(|op| op.0 != '-').count();
+        let (_, _, old_start, new_start) = hunk[0];
+        diff.push_str(&format!(
+            "@@ -{},{} +{},{} @@\n",
+            old_start + usize::from(old_len > 0),
+            old_len,
+            new_start + usize::from(new_len > 0),
+            new_len
+        ));
+        for (op, line, _, _) in hunk {
+            diff.push(*op);
+            diff.push_str(line);
+            diff.push('\n');
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+
+    fn rename_fake_keys(project: &mut Project) -> io::Result<()> {
+        project.rename_settings_keys(&[
+            ("observability.level", "log_level"),
+            ("*.service_name", "service"),
+        ])
+    }
+
+    fn bump_fake_prosa(project: &mut Project) -> io::Result<()> {
+        project.bump_dependency("dependencies", "prosa", "0.2.0")?;
+        project.bump_dependency("build-dependencies", "cargo-prosa", "0.2.0")?;
+        Ok(())
+    }
+
+    fn render_fake_main(project: &mut Project) -> io::Result<()> {
+        project.render(
+            "src/main.rs",
+            "fn main() {\n    println!(\"{{ name }}\");\n}\n",
+        )
+    }
+
+    const FAKE_MIGRATIONS: &[Migration] = &[
+        Migration {
+            version: 1,
+            description: "Rename the fake settings keys",
+            apply: rename_fake_keys,
+        },
+        Migration {
+            version: 2,
+            description: "Bump the fake ProSA version",
+            apply: bump_fake_prosa,
+        },
+        Migration {
+            version: 3,
+            description: "Render the fake main",
+            apply: render_fake_main,
+        },
+    ];
+
+    #[test]
+    fn migrations_order() {
+        assert!(MIGRATIONS.windows(2).all(|m| m[0].version < m[1].version));
+        assert_eq!(Some(SKELETON_VERSION), MIGRATIONS.last().map(|m| m.version));
+    }
+
+    #[test]
+    fn yaml_rename() {
+        let yaml = "# ProSA settings
+name: test
+observability:
+  level: debug # verbose
+  service_name: prosa
+stub:
+  service_name: STUB
+  service: ALREADY
+  list:
+    - service_name: item
+      other: value
+";
+        assert_eq!(
+            "# ProSA settings
+name: test
+observability:
+  log_level: debug # verbose
+  service: prosa
+stub:
+  service_name: STUB
+  service: ALREADY
+  list:
+    - service_name: item
+      other: value
+",
+            rename_yaml_keys(
+                yaml,
+                &[
+                    ("observability.level", "log_level"),
+                    ("*.service_name", "service")
+                ]
+            )
+        );
+    }
+
+    #[test]
+    fn toml_rename() {
+        let toml = "# ProSA settings
+name = \"test\"
+
+[observability]
+level = \"debug\" # verbose
+service_name = \"prosa\"
+
+[stub]
+service_name = \"STUB\"
+service = \"ALREADY\"
+list = [{ service_name = \"item\", other = \"value\" }]
+";
+        assert_eq!(
+            "# ProSA settings
+name = \"test\"
+
+[observability]
+log_level = \"debug\" # verbose
+service = \"prosa\"
+
+[stub]
+service_name = \"STUB\"
+service = \"ALREADY\"
+list = [{ service_name = \"item\", other = \"value\" }]
+",
+            rename_toml_keys(
+                toml,
+                &[
+                    ("observability.level", "log_level"),
+                    ("*.service_name", "service")
+                ]
+            )
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn upgrade_fake_project() {
+        let prosa_path = env::temp_dir().join("cargo-prosa-upgrade-test");
+        let _ = fs::remove_dir_all(&prosa_path);
+        fs::create_dir_all(prosa_path.join("src")).unwrap();
+        let cargo_toml = "[package]
+name = \"upgrade\"
+version = \"0.1.0\"
+
+[dependencies]
+prosa = { version = \"0.1.0\", features = [\"testing\"] }
+prosa-utils = \"0.1\"
+
+[build-dependencies]
+cargo-prosa = \"0.1\" # generator
+";
+        let prosa_toml = "# ProSA definition
+[prosa]
+main = \"prosa::core::main::MainProc\"
+tvf = \"prosa_utils::msg::simple_string_tvf::SimpleStringTvf\"
+";
+        let prosa_yml = "observability:\n  level: debug\nstub:\n  service_name: STUB\n";
+        fs::write(prosa_path.join("Cargo.toml"), cargo_toml).unwrap();
+        fs::write(prosa_path.join(CONFIGURATION_FILENAME), prosa_toml).unwrap();
+        fs::write(prosa_path.join("prosa.yml"), prosa_yml).unwrap();
+        fs::write(prosa_path.join("src").join("main.rs"), "fn main() {}\n").unwrap();
+        fs::create_dir_all(prosa_path.join("config")).unwrap();
+        let stub_toml = "[stub]\nservice_name = \"STUB\"\n";
+        fs::write(prosa_path.join("config").join("stub.toml"), stub_toml).unwrap();
+        fs::write(prosa_path.join("config").join("broken.toml"), "[stub\n").unwrap();
+
+        let mut j2_context = tera::Context::new();
+        j2_context.insert("name", "upgrade");
+        let new_project = || {
+            let mut project = Project::new(&prosa_path, j2_context.clone());
+            project.find_config_files().unwrap();
+            project
+        };
+
+        // Dry run: the migrations are only in the diff
+        let mut project = new_project();
+        assert_eq!(
+            Some(String::from("0.1.0")),
+            project.prosa_version().unwrap()
+        );
+        assert_eq!(0, project.skeleton_version().unwrap());
+        assert_eq!(3, upgrade(&mut project, FAKE_MIGRATIONS).unwrap().len());
+        let diff = project.diff();
+        assert!(diff.contains(concat!(
+            "--- a/Cargo.toml\n",
+            "+++ b/Cargo.toml\n",
+            "@@ -3,8 +3,8 @@\n",
+            " version = \"0.1.0\"\n",
+            " \n",
+            " [dependencies]\n",
+            "-prosa = { version = \"0.1.0\", features = [\"testing\"] }\n",
+            "+prosa = { version = \"0.2.0\", features = [\"testing\"] }\n",
+            " prosa-utils = \"0.1\"\n",
+            " \n",
+            " [build-dependencies]\n",
+            "-cargo-prosa = \"0.1\" # generator\n",
+            "+cargo-prosa = \"0.2.0\" # generator\n",
+        )));
+        assert!(diff.contains("+skeleton = 3\n"));
+        assert!(diff.contains("-  level: debug\n+  log_level: debug\n"));
+        assert!(diff.contains(concat!(
+            "--- a/config/stub.toml\n",
+            "+++ b/config/stub.toml\n",
+            "@@ -1,2 +1,2 @@\n",
+            " [stub]\n",
+            "-service_name = \"STUB\"\n",
+            "+service = \"STUB\"\n",
+        )));
+        assert_eq!(
+            vec![PathBuf::from("config").join("broken.toml")],
+            project
+                .skipped_config_files()
+                .iter()
+                .map(|(file, _)| file.clone())
+                .collect::<Vec<PathBuf>>()
+        );
+        assert!(diff.contains("-fn main() {}\n+fn main() {\n+    println!(\"upgrade\");\n+}\n"));
+        assert_eq!(
+            prosa_yml,
+            fs::read_to_string(prosa_path.join("prosa.yml")).unwrap()
+        );
+
+        // Upgrade the project
+        project.commit().unwrap();
+        let mut project = new_project();
+        assert_eq!(
+            Some(String::from("0.2.0")),
+            project.prosa_version().unwrap()
+        );
+        assert_eq!(3, project.skeleton_version().unwrap());
+        assert_eq!(
+            "observability:\n  log_level: debug\nstub:\n  service: STUB\n",
+            fs::read_to_string(prosa_path.join("prosa.yml")).unwrap()
+        );
+        assert!(fs::read_to_string(prosa_path.join(CONFIGURATION_FILENAME))
+            .unwrap()
+            .starts_with(prosa_toml));
+
+        // An upgraded project doesn't have any migration to apply
+        assert!(upgrade(&mut project, FAKE_MIGRATIONS).unwrap().is_empty());
+        assert_eq!("", project.diff());
+
+        // Migrations are idempotent, applying them again doesn't change anything
+        let mut project = new_project();
+        for migration in FAKE_MIGRATIONS {
+            (migration.apply)(&mut project).unwrap();
+        }
+        assert_eq!("", project.diff());
+
+        // A skeleton newer than the known migrations can't be upgraded
+        assert!(upgrade(&mut project, &FAKE_MIGRATIONS[..2]).is_err());
+
+        let _ = fs::remove_dir_all(&prosa_path);
+    }
+}
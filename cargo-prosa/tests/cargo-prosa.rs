@@ -32,6 +32,12 @@ fn replace_prosa_dependencies(prosa_path: &PathBuf) {
         cmd.args(["--path", test_prosa_dep.to_str().unwrap(), prosa_dep]);
         cmd.assert().success();
     }
+
+    // The generated main give its configuration to ProSA, so it must use the same config crate
+    let mut cmd = Command::new("cargo");
+    cmd.current_dir(prosa_path);
+    cmd.args(["add", "config@0.13"]);
+    cmd.assert().success();
 }
 
 #[test]
@@ -54,10 +60,10 @@ fn project() -> Result<(), Box<dyn std::error::Error>> {
     // Clean test files
     let _ = fs::remove_dir_all(&prosa_path);
 
-    // Generate a dummy project
+    // Generate a dummy project, checked once its dependencies are the local ones
     let mut cmd = cargo_prosa_command()?;
     cmd.current_dir(&temp_dir);
-    cmd.args(["new", "--deb", PROSA_NAME]);
+    cmd.args(["new", "--deb", "--no-verify", PROSA_NAME]);
     cmd.assert()
         .success()
         .stderr(predicate::str::contains(format!(
@@ -72,6 +78,11 @@ fn project() -> Result<(), Box<dyn std::error::Error>> {
     cmd.arg("list");
     cmd.assert().success().stdout(predicate::str::is_match(
         r"Package prosa\[[0-9].[0-9].[0-9]\] \(ProSA core\)
+  - bridge
+    Processor io::bridge::proc::BridgeProc
+    Settings io::bridge::proc::BridgeSettings
+    Adaptor:
+     - io::bridge::adaptor::BridgeDefaultAdaptor
   - inj
     Processor inj::proc::InjProc
     Settings inj::proc::InjSettings
@@ -101,7 +112,7 @@ Package prosa-utils\[[0-9].[0-9].[0-9]\] \(ProSA utils\)
         "StubParotAdaptor",
         "stub",
     ]);
-    cmd.assert().success().stdout("Will add ProSA processor stub-1 (stub)\n  Processor prosa::stub::proc::StubProc\n  Adaptor prosa::stub::adaptor::StubParotAdaptor\n\n");
+    cmd.assert().success().stdout(predicate::str::is_match(r"^Will add ProSA processor stub-1 \(stub\)\n  Processor prosa::stub::proc::StubProc \(prosa\[[0-9].[0-9].[0-9]\]\)\n  Adaptor prosa::stub::adaptor::StubParotAdaptor \(prosa\[[0-9].[0-9].[0-9]\]\)\n\n$")?);
 
     let predicate_stub_proc = predicate::str::contains("[[proc]]\nname = \"stub-1\"\nproc_name = \"stub\"\nproc = \"prosa::stub::proc::StubProc\"\nadaptor = \"prosa::stub::adaptor::StubParotAdaptor\"");
     assert!(!predicate_stub_proc.eval(fs::read_to_string(&prosa_toml_path)?.as_str()));
@@ -154,6 +165,14 @@ Package prosa-utils\[[0-9].[0-9].[0-9]\] \(ProSA utils\)
         .join("service")
         .exists());
 
+    // Dump the effective configuration of the ProSA
+    let mut cmd = Command::new("cargo");
+    cmd.args(["run", "--", "-c", "target/config.yml", "--dump-config"]);
+    cmd.current_dir(&prosa_path);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::is_match(r"(?s)name: .*observability:.*stub-1:").unwrap());
+
     // Try to update the ProSA
     let build_path = prosa_path.join("build.rs");
     let _ = fs::remove_file(&build_path);
@@ -216,7 +235,7 @@ If you have an external git dependency, specify your ssh agent with:
     assert!(!build_path.exists());
     let mut cmd = cargo_prosa_command()?;
     cmd.current_dir(&prosa_path);
-    cmd.arg("init");
+    cmd.args(["init", "--no-verify"]);
     cmd.assert().success();
     assert!(build_path.exists());
     replace_prosa_dependencies(&prosa_path);
@@ -227,7 +246,7 @@ If you have an external git dependency, specify your ssh agent with:
     cmd.args(["completion", "bash"]);
     cmd.assert()
         .success()
-        .stdout(predicate::str::contains("cargo__prosa"));
+        .stdout(predicate::str::is_match(r"cargo__(subcmd__)?prosa")?);
 
     // Get Zsh command completion
     let mut cmd = cargo_prosa_command()?;
@@ -235,7 +254,75 @@ If you have an external git dependency, specify your ssh agent with:
     cmd.args(["completion", "zsh"]);
     cmd.assert()
         .success()
-        .stdout(predicate::str::contains("cargo__prosa"));
+        .stdout(predicate::str::is_match(r"cargo__(subcmd__)?prosa")?);
+
+    Ok(())
+}
+
+#[test]
+fn new_proc() -> Result<(), Box<dyn std::error::Error>> {
+    const PROC_NAME: &str = "dummy-test-proc";
+    let temp_dir = env::temp_dir();
+    let proc_path = temp_dir.join(PROC_NAME);
+
+    // Clean test files
+    let _ = fs::remove_dir_all(&proc_path);
+
+    // Generate a dummy processor
+    let mut cmd = cargo_prosa_command()?;
+    cmd.current_dir(&temp_dir);
+    cmd.args(["new-proc", "--kind", "worker", PROC_NAME]);
+    cmd.assert()
+        .success()
+        .stderr(predicate::str::contains(format!(
+            "library `{}` package",
+            PROC_NAME
+        )));
+
+    // Take the local ProSA for the processor and its tests (edited directly, the dev dependency need the `testing` feature)
+    let mut test_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    test_path.pop();
+    let cargo_toml_path = proc_path.join("Cargo.toml");
+    let mut cargo_toml = fs::read_to_string(&cargo_toml_path)?.parse::<toml_edit::DocumentMut>()?;
+    for (deps, prosa_dep, prosa_dep_path) in [
+        ("dependencies", "prosa-utils", "prosa_utils"),
+        ("dependencies", "prosa", "prosa"),
+        ("dev-dependencies", "prosa", "prosa"),
+    ] {
+        let mut dep = toml_edit::InlineTable::new();
+        dep.insert(
+            "path",
+            test_path.join(prosa_dep_path).to_str().unwrap().into(),
+        );
+        if let Some(features) = cargo_toml[deps][prosa_dep].get("features") {
+            dep.insert("features", features.as_value().unwrap().clone());
+        }
+        cargo_toml[deps][prosa_dep] = toml_edit::value(dep);
+    }
+    fs::write(&cargo_toml_path, cargo_toml.to_string())?;
+    let _ = fs::remove_file(proc_path.join("Cargo.lock"));
+
+    // The generated processor must pass its own tests
+    let mut cmd = Command::new("cargo");
+    cmd.current_dir(&proc_path);
+    cmd.arg("test");
+    cmd.assert().success();
+
+    // The processor is declared in the crate metadata
+    let mut cmd = cargo_prosa_command()?;
+    cmd.current_dir(&proc_path);
+    cmd.arg("list");
+    cmd.assert().success().stdout(predicate::str::contains(
+        "Package dummy-test-proc[0.1.0] (ProSA dummy-test-proc processor)
+  - dummy-test-proc
+    Processor proc::DummyTestProcProc
+    Settings proc::DummyTestProcSettings
+    Adaptor:
+     - adaptor::DummyTestProcDefaultAdaptor",
+    ));
+
+    // Clean test files
+    let _ = fs::remove_dir_all(&proc_path);
 
     Ok(())
 }
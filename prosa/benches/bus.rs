@@ -0,0 +1,308 @@
+//! Benchmarks of the ProSA main bus
+//!
+//! Run them with `cargo bench -p prosa --features bench-api` (and the `lockfree` feature to compare the bus transports)
+
+use std::hint::black_box;
+use std::sync::Arc;
+use std::thread::JoinHandle;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use prosa::core::main::{Main, MainProc, MainRunnable as _};
+use prosa::core::proc::{Proc, ProcConfig as _, ProcParam};
+use prosa::core::service::{BatchSettings, ProcService, ServiceTable};
+use prosa::core::settings::settings;
+use prosa::core::transport::TransportSettings;
+use prosa::inj::bench::BenchInjProc;
+use prosa::stub::adaptor::StubParotAdaptor;
+use prosa::stub::proc::{StubProc, StubSettings};
+use prosa_utils::msg::simple_string_tvf::SimpleStringTvf;
+use prosa_utils::msg::tvf::Tvf as _;
+use serde::Serialize;
+use tokio::runtime::Runtime;
+use tokio::sync::mpsc;
+
+/// Service called by the injector
+const SERVICE_NAME: &str = "BENCH";
+/// Maximum number of transactions in flight
+const INJECTION_WINDOW: usize = 64;
+/// Maximum number of transactions in flight for the batching comparison (several batches in flight)
+const BATCH_WINDOW: usize = 256;
+
+#[settings]
+#[derive(Default, Debug, Serialize)]
+struct BenchSettings {}
+
+/// ProSA with N stub processors and a deterministic injector
+struct BenchProSA {
+    rt: Runtime,
+    _bus: Main<SimpleStringTvf>,
+    _main_task: JoinHandle<()>,
+    injector: BenchInjProc<SimpleStringTvf>,
+}
+
+impl BenchProSA {
+    fn start(stub_count: u32) -> BenchProSA {
+        Self::start_with(stub_count, false, TransportSettings::Mpsc)
+    }
+
+    /// Start the ProSA with stub processors that consume the batches of requests or not, through a transport
+    fn start_with(stub_count: u32, batch: bool, transport: TransportSettings) -> BenchProSA {
+        let rt = Runtime::new().unwrap();
+        let (bus, main) = MainProc::<SimpleStringTvf>::create(&BenchSettings::default());
+        let main_task = main.run();
+
+        for proc_id in 1..=stub_count {
+            let mut stub_settings = StubSettings::new(vec![String::from(SERVICE_NAME)]);
+            stub_settings.set_batch(batch);
+            stub_settings.set_transport(transport.clone());
+            let stub_proc =
+                StubProc::<SimpleStringTvf>::create(proc_id, bus.clone(), stub_settings);
+            Proc::<StubParotAdaptor>::run(stub_proc, format!("STUB_PROC_{}", proc_id));
+        }
+
+        let mut injector = BenchInjProc::<SimpleStringTvf>::create_raw(stub_count + 1, bus.clone());
+        rt.block_on(injector.wait_service(SERVICE_NAME, stub_count as usize))
+            .unwrap();
+
+        BenchProSA {
+            rt,
+            _bus: bus,
+            _main_task: main_task,
+            injector,
+        }
+    }
+
+    /// Remove the injector from the bus.
+    /// The ProSA is kept idle until the end of the benchmark process (stub processors can't deregister from a stopped main task)
+    fn stop(self) {
+        self.rt.block_on(self.injector.stop()).unwrap();
+    }
+}
+
+fn bus_throughput(c: &mut Criterion) {
+    let mut transaction = SimpleStringTvf::default();
+    transaction.put_string(1, "bench");
+    transaction.put_unsigned(2, 42);
+
+    let mut group = c.benchmark_group("bus_throughput");
+    group.throughput(Throughput::Elements(1));
+    for stub_count in [1u32, 4, 16] {
+        let mut prosa = BenchProSA::start(stub_count);
+        group.bench_with_input(
+            BenchmarkId::from_parameter(stub_count),
+            &stub_count,
+            |b, _| {
+                b.iter_custom(|iters| {
+                    let report = prosa
+                        .rt
+                        .block_on(prosa.injector.inject(
+                            SERVICE_NAME,
+                            iters,
+                            INJECTION_WINDOW,
+                            &transaction,
+                        ))
+                        .unwrap();
+                    assert_eq!(iters, report.responses);
+                    report.elapsed
+                })
+            },
+        );
+        prosa.stop();
+    }
+    group.finish();
+}
+
+fn bus_batching(c: &mut Criterion) {
+    let mut transaction = SimpleStringTvf::default();
+    transaction.put_string(1, "bench");
+    transaction.put_unsigned(2, 42);
+
+    // Unbatched transactions, against batches of 64 requests flushed after 1ms
+    let mut group = c.benchmark_group("bus_batching");
+    group.throughput(Throughput::Elements(1));
+    for batched in [false, true] {
+        let mut prosa = BenchProSA::start_with(1, batched, TransportSettings::Mpsc);
+        let id = if batched { "batched" } else { "unbatched" };
+        group.bench_function(id, |b| {
+            b.iter_custom(|iters| {
+                let report = if batched {
+                    prosa.rt.block_on(prosa.injector.inject_batched(
+                        SERVICE_NAME,
+                        iters,
+                        BATCH_WINDOW,
+                        BatchSettings::default(),
+                        &transaction,
+                    ))
+                } else {
+                    prosa.rt.block_on(prosa.injector.inject(
+                        SERVICE_NAME,
+                        iters,
+                        BATCH_WINDOW,
+                        &transaction,
+                    ))
+                }
+                .unwrap();
+                assert_eq!(iters, report.responses);
+                report.elapsed
+            })
+        });
+        prosa.stop();
+    }
+    group.finish();
+}
+
+fn bus_transport(c: &mut Criterion) {
+    let mut transaction = SimpleStringTvf::default();
+    transaction.put_string(1, "bench");
+    transaction.put_unsigned(2, 42);
+
+    // Transport of the requests to the stub processor (the lock-free queue needs the `lockfree` feature)
+    let mut transports = vec![("mpsc", TransportSettings::Mpsc)];
+    if cfg!(feature = "lockfree") {
+        transports.push((
+            "lock_free",
+            TransportSettings::LockFree {
+                capacity: INJECTION_WINDOW * 4,
+            },
+        ));
+    }
+
+    let mut group = c.benchmark_group("bus_transport");
+    group.throughput(Throughput::Elements(1));
+    for (id, transport) in transports {
+        let mut prosa = BenchProSA::start_with(1, false, transport);
+        group.bench_function(id, |b| {
+            b.iter_custom(|iters| {
+                let report = prosa
+                    .rt
+                    .block_on(prosa.injector.inject(
+                        SERVICE_NAME,
+                        iters,
+                        INJECTION_WINDOW,
+                        &transaction,
+                    ))
+                    .unwrap();
+                assert_eq!(iters, report.responses);
+                report.elapsed
+            })
+        });
+        prosa.stop();
+    }
+    group.finish();
+}
+
+fn service_table(c: &mut Criterion) {
+    let (bus_queue, _bus_rx) = mpsc::channel(1);
+    let main =
+        Main::<SimpleStringTvf>::new(bus_queue.clone(), bus_queue, &BenchSettings::default())
+            .unwrap();
+    let proc_services: Vec<ProcService<SimpleStringTvf>> = (1..=16)
+        .map(|proc_id| {
+            let (proc_queue, _) = mpsc::channel(1);
+            ProcService::new_proc(&ProcParam::new(proc_id, proc_queue, main.clone()), 0)
+        })
+        .collect();
+
+    let mut group = c.benchmark_group("service_table");
+    for service_count in [1_000usize, 10_000] {
+        let names: Vec<String> = (0..service_count)
+            .map(|i| format!("SERVICE_{:06}", i))
+            .collect();
+        group.throughput(Throughput::Elements(service_count as u64));
+
+        group.bench_with_input(
+            BenchmarkId::new("add_service", service_count),
+            &names,
+            |b, names| {
+                b.iter(|| {
+                    let mut table = ServiceTable::default();
+                    for (i, name) in names.iter().enumerate() {
+                        table.add_service(name, proc_services[i % proc_services.len()].clone());
+                    }
+                    table
+                })
+            },
+        );
+
+        let mut table = ServiceTable::default();
+        for (i, name) in names.iter().enumerate() {
+            for proc_service in proc_services.iter().skip(i % 4).step_by(4) {
+                table.add_service(name, proc_service.clone());
+            }
+        }
+        group.bench_with_input(
+            BenchmarkId::new("lookup", service_count),
+            &names,
+            |b, names| {
+                b.iter(|| {
+                    for (msg_id, name) in names.iter().enumerate() {
+                        black_box(table.get_proc_service(name, msg_id as u64));
+                    }
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+fn service_churn(c: &mut Criterion) {
+    let (bus_queue, _bus_rx) = mpsc::channel(1);
+    let main =
+        Main::<SimpleStringTvf>::new(bus_queue.clone(), bus_queue, &BenchSettings::default())
+            .unwrap();
+    let (proc_queue, _) = mpsc::channel(1);
+    let proc_service =
+        ProcService::<SimpleStringTvf>::new_proc(&ProcParam::new(1, proc_queue, main), 0);
+
+    let mut table = ServiceTable::default();
+    for i in 0..10_000 {
+        table.add_service(&format!("SERVICE_{:06}", i), proc_service.clone());
+    }
+    let table = Arc::new(table);
+    let churn: Vec<String> = (0..64).map(|i| format!("CHURN_{:02}", i)).collect();
+
+    // Reconnection of a connector: its services are removed then added again
+    let mut group = c.benchmark_group("service_churn");
+    group.throughput(Throughput::Elements(churn.len() as u64 * 2));
+    group.bench_function("clone_per_update", |b| {
+        b.iter(|| {
+            let mut snapshot = table.clone();
+            for name in &churn {
+                let mut new_table = (*snapshot).clone();
+                new_table.add_service(name, proc_service.clone());
+                snapshot = Arc::new(new_table);
+            }
+            for name in &churn {
+                let mut new_table = (*snapshot).clone();
+                new_table.remove_service(name, 1, 0);
+                snapshot = Arc::new(new_table);
+            }
+            snapshot
+        })
+    });
+    group.bench_function("batched", |b| {
+        b.iter(|| {
+            // Processors hold the previous snapshot, so the table is cloned once for the whole batch
+            let mut snapshot = table.clone();
+            let new_table = Arc::make_mut(&mut snapshot);
+            for name in &churn {
+                new_table.add_service(name, proc_service.clone());
+            }
+            for name in &churn {
+                new_table.remove_service(name, 1, 0);
+            }
+            snapshot
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bus_throughput,
+    bus_batching,
+    bus_transport,
+    service_table,
+    service_churn
+);
+criterion_main!(benches);
@@ -60,15 +60,19 @@ where
                             info!("Proc {} receive an error: {:?}", self.get_proc_id(), err);
                         },
                         InternalMsg::Command(_) => todo!(),
-                        InternalMsg::Config => todo!(),
+                        // Batches are split by the processor queue
+                        InternalMsg::RequestBatch(_) | InternalMsg::ResponseBatch(_) => {}
+                        InternalMsg::Config(_) => todo!(),
                         InternalMsg::Service(table) => {
                             debug!("New service table received:\n{}\n", table);
                             self.service = table;
                         },
                         InternalMsg::Shutdown => {
-                            adaptor.terminate();
+                            adaptor.async_terminate().await;
                             warn!("The processor will shut down");
                         },
+                        // Heartbeats are answered by the processor queue
+                        _ => {}
                     }
                 },
                 _ = interval.tick() => {
@@ -24,15 +24,39 @@
 
 /// Adaptor module to adapt processor object and internal messages
 pub mod adaptor;
+/// Audit log of the main bus routing
+pub mod audit;
+/// Builder to assemble and run a ProSA programmatically, the entry point to embed ProSA in an existing binary
+pub mod builder;
+/// Control socket to query and control a running ProSA
+#[cfg(unix)]
+pub mod ctl;
+pub mod dead_letter;
+/// Errors of the ProSA internal exchanges (queues and bus)
+pub mod error;
 /// The module define ProSA main processing to bring asynchronous handler for all processors
 pub mod main;
 /// Module to define ProSA messages
 /// Messages are implement to be handle in an asynchronous context. Every data in message will be TVF formatted
 pub mod msg;
+/// Pending transactions map that expire the entries never completed, to detect leaks
+pub mod pending;
 /// A processor in ProSA is an element that process transactions and can contact external component. It's similar to a micro service.
 /// It can answer to a service request or ask something to a service.
 pub mod proc;
+/// Delayed sending of requests on the service bus
+pub mod schedule;
 /// Service defined for a ProSA
 pub mod service;
 /// Settings module of a ProSA
 pub mod settings;
+/// Load shedding of the processors requests
+pub mod shed;
+/// Embedded key-value state store of the processors
+pub mod state;
+/// Moving window of the transactions per second and latency of the bus
+pub mod tps;
+/// Transports of the internal messages between the bus and the processors
+pub mod transport;
+/// Watchdog of the processors adaptor invocations
+pub mod watchdog;
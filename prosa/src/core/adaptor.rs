@@ -13,10 +13,136 @@
 //! So if several task in multiple thread are running, there will be concurency.
 //!
 //! An adaptor should be seen as a routine call to know what to do with a protocol message. How to convert it in internal message, and have an attach configuration to have routing rule.
+//!
+//! ## Pipeline
+//!
+//! Cross-cutting processing (field normalization, enrichment, validation) can be shared between adaptors with a [`Pipeline`] of [`Transform`].
+//! The [`PipelineAdaptor`] wrap a stub or an inj adaptor to pass its requests and responses through the pipeline configured in the processor settings:
+//!
+//! ```yaml
+//! stub:
+//!   service_names: ["PAYMENT"]
+//!   pipeline:
+//!     - name: tag_rename
+//!       params:
+//!         1: 10
+//!     - name: mandatory_fields
+//!       params:
+//!         dictionary: /etc/prosa/payment.csv
+//!         fields: ["amount", "card.pan"]
+//! ```
+//!
+//! ## Configuration reload
+//!
+//! An adaptor can reload its configuration (from the `adaptor_config_path` of its processor settings) without restarting its processor.
+//! It implements [`AdaptorConfigReload`] and registers its configuration type with `#[adaptor(reload = MyConfig)]`.
+//! Processors call [`Adaptor::reload_config`] when they receive a new configuration (`InternalMsg::Config`), through the `reload_adaptor()` method generated by the `#[proc]` macro.
+
+use std::{collections::BTreeMap, error::Error, fmt, path::Path, sync::Arc};
+
+use config::ConfigError;
+use opentelemetry::KeyValue;
+use prosa_utils::{
+    dict::{Dictionary, EntryType},
+    msg::tvf::{Tvf, TvfExt as _, TvfType},
+};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tracing::{info, warn, Span};
+use tracing_opentelemetry::OpenTelemetrySpanExt as _;
+
+use super::{
+    proc::{ProcBusParam, ProcSettings},
+    service::ServiceError,
+};
+
+/// Maximum number of attributes an adaptor can put on a transaction span (see `span_attributes` of the stub and inj adaptors)
+pub const MAX_SPAN_ATTRIBUTES: usize = 16;
+
+/// Method to put the attributes given by an adaptor on a transaction span.
+///
+/// The attributes are only built if the span is recorded by a subscriber, so adaptors don't pay their cost when the traces are disabled.
+/// Only the first [`MAX_SPAN_ATTRIBUTES`] attributes are kept, the others are dropped.
+pub fn set_span_attributes<F>(span: &Span, attributes: F)
+where
+    F: FnOnce() -> Vec<KeyValue>,
+{
+    if !span.is_disabled() {
+        for attribute in attributes().into_iter().take(MAX_SPAN_ATTRIBUTES) {
+            span.set_attribute(attribute.key, attribute.value);
+        }
+    }
+}
 
 /// Implement the trait [`Adaptor`].
 pub use prosa_macros::Adaptor;
 
+/// Error define for the adaptors
+#[derive(Debug, Error)]
+pub enum AdaptorError {
+    /// Error when the adaptor configuration can't be loaded
+    #[error("Can't load the adaptor configuration: {0}")]
+    Config(#[from] ConfigError),
+    /// Error when the adaptor reject its configuration
+    #[error("Invalid adaptor configuration: {0}")]
+    InvalidConfig(String),
+}
+
+/// Trait of the adaptors that can reload their configuration without restarting their processor
+///
+/// The configuration type must be registered on the adaptor derive with `#[adaptor(reload = MyConfig)]`:
+/// ```
+/// use prosa::core::adaptor::{Adaptor, AdaptorConfigReload, AdaptorError};
+/// use serde::Deserialize;
+///
+/// #[derive(Deserialize)]
+/// struct MyConfig {
+///     endpoints: Vec<String>,
+/// }
+///
+/// #[derive(Adaptor)]
+/// #[adaptor(reload = MyConfig)]
+/// struct MyAdaptor {
+///     endpoints: Vec<String>,
+/// }
+///
+/// impl AdaptorConfigReload<MyConfig> for MyAdaptor {
+///     fn reload(&mut self, config: MyConfig) -> Result<(), AdaptorError> {
+///         if config.endpoints.is_empty() {
+///             return Err(AdaptorError::InvalidConfig("no endpoint".into()));
+///         }
+///
+///         self.endpoints = config.endpoints;
+///         Ok(())
+///     }
+/// }
+/// ```
+pub trait AdaptorConfigReload<C> {
+    /// Method call with the reloaded adaptor configuration.
+    /// If an error is returned, the adaptor should keep its previous configuration
+    fn reload(&mut self, config: C) -> Result<(), AdaptorError>;
+}
+
+/// Method to reload the configuration of an adaptor from the processor settings, and log the outcome
+///
+/// Adaptors that don't implement [`AdaptorConfigReload`] are left unchanged.
+/// Use the `reload_adaptor()` method generated by the `#[proc]` macro rather than calling it directly.
+pub fn reload_adaptor_config<A, S>(adaptor: &mut A, settings: &S, proc_name: &str)
+where
+    A: Adaptor,
+    S: ProcSettings,
+{
+    match adaptor.reload_config(settings) {
+        Ok(true) => {
+            info!(name: "adaptor_reload", target: "prosa::core::adaptor", proc_name = proc_name, "Adaptor configuration reloaded")
+        }
+        Ok(false) => {}
+        Err(e) => {
+            warn!(name: "adaptor_reload", target: "prosa::core::adaptor", proc_name = proc_name, "Can't reload the adaptor configuration: {}", e)
+        }
+    }
+}
+
 #[cfg_attr(doc, aquamarine::aquamarine)]
 /// Generic ProSA Adaptor.
 /// Define generic function call that are use by every processor.
@@ -39,8 +165,933 @@ pub use prosa_macros::Adaptor;
 /// #[derive(Adaptor)]
 /// struct MyAdaptor {}
 /// ```
+///
+/// If your adaptor need an asynchronous setup or teardown (connections, caches, ...), ask the derive to call your async hooks:
+/// ```
+/// use std::error::Error;
+/// use prosa::core::adaptor::Adaptor;
+/// use prosa::core::proc::ProcBusParam;
+///
+/// #[derive(Adaptor)]
+/// #[adaptor(async_init, async_terminate)]
+/// struct MyAdaptor {
+///     connected: bool,
+/// }
+///
+/// impl MyAdaptor {
+///     async fn on_init(&mut self, _proc: &(dyn ProcBusParam + Sync)) -> Result<(), Box<dyn Error>> {
+///         // Open your connections
+///         self.connected = true;
+///         Ok(())
+///     }
+///
+///     async fn on_terminate(&mut self) {
+///         // Close your connections
+///         self.connected = false;
+///     }
+/// }
+/// ```
 pub trait Adaptor {
     /// Method call when the ProSA need to shut down.
     /// This method is call only once so the processing will be thread safe.
     fn terminate(&mut self);
+
+    /// Method call by the processor right after the adaptor creation to do asynchronous initialization.
+    /// Do nothing by default.
+    fn async_init(
+        &mut self,
+        _proc: &(dyn ProcBusParam + Sync),
+    ) -> impl std::future::Future<Output = Result<(), Box<dyn Error>>> + Send {
+        async { Ok(()) }
+    }
+
+    /// Method call by the processor when it shuts down, before it's removed from the main bus.
+    /// Call [`Adaptor::terminate`] by default.
+    fn async_terminate(&mut self) -> impl std::future::Future<Output = ()> + Send
+    where
+        Self: Send,
+    {
+        async { self.terminate() }
+    }
+
+    /// Method call by the processor when the configuration is reloaded, to reload the adaptor configuration from the processor settings.
+    /// Return `true` if the configuration was reloaded, and `false` if the adaptor doesn't support the reload.
+    /// Do nothing by default; derive the adaptor with `#[adaptor(reload = MyConfig)]` to call [`AdaptorConfigReload::reload`].
+    fn reload_config<S>(&mut self, _settings: &S) -> Result<bool, AdaptorError>
+    where
+        S: ProcSettings,
+    {
+        Ok(false)
+    }
+}
+
+/// Transformation applied by a [`Pipeline`] on the requests and the responses of an adaptor
+///
+/// ```
+/// use prosa::core::adaptor::Transform;
+/// use prosa::core::service::ServiceError;
+/// use prosa_utils::msg::tvf::Tvf;
+///
+/// /// Transform that add the currency of the requests
+/// struct DefaultCurrency;
+///
+/// impl<M> Transform<M> for DefaultCurrency
+/// where
+///     M: Tvf,
+/// {
+///     fn on_request(&self, mut request: M) -> Result<M, ServiceError> {
+///         if !request.contains(4) {
+///             request.put_string(4, "EUR");
+///         }
+///         Ok(request)
+///     }
+/// }
+/// ```
+pub trait Transform<M>: Send + Sync {
+    /// Method to transform a request before it's processed by the adaptor
+    fn on_request(&self, request: M) -> Result<M, ServiceError>;
+
+    /// Method to transform a response after it's produced by the adaptor.
+    /// Responses are given back unchanged by default
+    fn on_response(&self, response: M) -> Result<M, ServiceError> {
+        Ok(response)
+    }
+}
+
+/// Chain of [`Transform`] applied around an adaptor
+///
+/// Requests go through the transforms in their order, and responses in the reverse order (the first transform see the request first and the response last).
+pub struct Pipeline<M> {
+    transforms: Vec<Box<dyn Transform<M>>>,
+}
+
+impl<M> Pipeline<M> {
+    /// Method to create an empty pipeline
+    pub fn new() -> Pipeline<M> {
+        Pipeline {
+            transforms: Vec::new(),
+        }
+    }
+
+    /// Method to add a transform at the end of the pipeline
+    pub fn push<T>(&mut self, transform: T)
+    where
+        T: Transform<M> + 'static,
+    {
+        self.transforms.push(Box::new(transform));
+    }
+
+    /// Method to add a transform at the end of the pipeline (builder style)
+    pub fn with<T>(mut self, transform: T) -> Pipeline<M>
+    where
+        T: Transform<M> + 'static,
+    {
+        self.push(transform);
+        self
+    }
+
+    /// Getter of the number of transforms
+    pub fn len(&self) -> usize {
+        self.transforms.len()
+    }
+
+    /// Getter to know if the pipeline don't have any transform
+    pub fn is_empty(&self) -> bool {
+        self.transforms.is_empty()
+    }
+
+    /// Method to pass a request through all the transforms
+    pub fn on_request(&self, request: M) -> Result<M, ServiceError> {
+        self.transforms
+            .iter()
+            .try_fold(request, |request, transform| transform.on_request(request))
+    }
+
+    /// Method to pass a response through all the transforms, in the reverse order
+    pub fn on_response(&self, response: M) -> Result<M, ServiceError> {
+        self.transforms
+            .iter()
+            .rev()
+            .try_fold(response, |response, transform| {
+                transform.on_response(response)
+            })
+    }
+}
+
+impl<M> Default for Pipeline<M> {
+    fn default() -> Self {
+        Pipeline::new()
+    }
+}
+
+impl<M> fmt::Debug for Pipeline<M> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Pipeline")
+            .field("transforms", &self.transforms.len())
+            .finish()
+    }
+}
+
+/// Parameters of a transform, specific to each transform
+///
+/// ```
+/// use std::collections::BTreeMap;
+/// use prosa::core::adaptor::TransformParams;
+///
+/// let params = TransformParams::new(&BTreeMap::from([(1, 10)])).unwrap();
+/// assert_eq!(BTreeMap::from([(1, 10)]), params.deserialize::<BTreeMap<usize, usize>>().unwrap());
+/// ```
+#[derive(Debug, Default, Deserialize, Serialize, Clone, PartialEq)]
+#[serde(transparent)]
+pub struct TransformParams(serde_yaml::Value);
+
+impl TransformParams {
+    /// Method to create the parameters of a transform from any serializable value
+    pub fn new<T>(params: &T) -> Result<TransformParams, Box<dyn Error>>
+    where
+        T: Serialize,
+    {
+        Ok(TransformParams(serde_yaml::to_value(params)?))
+    }
+
+    /// Method to deserialize the parameters into the type expected by the transform
+    pub fn deserialize<T>(&self) -> Result<T, Box<dyn Error>>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        Ok(serde_yaml::from_value(self.0.clone())?)
+    }
+}
+
+/// Settings of a transform of a pipeline, with the name of the transform in the [`TransformRegistry`] and its parameters
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct TransformSettings {
+    /// Name of the transform
+    pub name: String,
+    /// Parameters of the transform, specific to each transform
+    #[serde(default)]
+    pub params: TransformParams,
+}
+
+impl TransformSettings {
+    /// Method to create the settings of a transform
+    pub fn new<T>(name: T, params: TransformParams) -> TransformSettings
+    where
+        T: Into<String>,
+    {
+        TransformSettings {
+            name: name.into(),
+            params,
+        }
+    }
+}
+
+/// Constructor of a transform out of its parameters
+pub type TransformBuilder<M> =
+    fn(&TransformParams) -> Result<Box<dyn Transform<M>>, Box<dyn Error>>;
+
+/// Registry of named transforms, to build pipelines from settings
+///
+/// The default registry contains the built-in transforms:
+/// - `tag_rename`: [`TagRename`]
+/// - `mandatory_fields`: [`MandatoryFields`]
+///
+/// ```
+/// use prosa::core::adaptor::{Pipeline, TransformRegistry, TransformSettings};
+/// use prosa_utils::msg::simple_string_tvf::SimpleStringTvf;
+///
+/// let settings: Vec<TransformSettings> = serde_yaml::from_str("- name: tag_rename\n  params:\n    1: 10\n").unwrap();
+/// let pipeline: Pipeline<SimpleStringTvf> = TransformRegistry::default().build(&settings).unwrap();
+/// assert_eq!(1, pipeline.len());
+/// ```
+pub struct TransformRegistry<M> {
+    builders: BTreeMap<String, TransformBuilder<M>>,
+}
+
+impl<M> TransformRegistry<M>
+where
+    M: Tvf + Default + fmt::Debug + Clone + 'static,
+{
+    /// Method to create a registry without any transform
+    pub fn empty() -> TransformRegistry<M> {
+        TransformRegistry {
+            builders: BTreeMap::new(),
+        }
+    }
+
+    /// Method to register a transform constructor with its name. Replace the transform that had the same name
+    pub fn register<T>(&mut self, name: T, builder: TransformBuilder<M>)
+    where
+        T: Into<String>,
+    {
+        self.builders.insert(name.into(), builder);
+    }
+
+    /// Getter to know if a transform is registered
+    pub fn contains(&self, name: &str) -> bool {
+        self.builders.contains_key(name)
+    }
+
+    /// Method to build a pipeline from the settings of its transforms
+    pub fn build(&self, settings: &[TransformSettings]) -> Result<Pipeline<M>, Box<dyn Error>> {
+        let mut pipeline = Pipeline::new();
+        for transform_settings in settings {
+            let builder = self.builders.get(&transform_settings.name).ok_or_else(|| {
+                format!("unknown pipeline transform `{}`", transform_settings.name)
+            })?;
+            pipeline
+                .transforms
+                .push(builder(&transform_settings.params).map_err(|e| {
+                    format!(
+                        "invalid parameters for the transform `{}`: {}",
+                        transform_settings.name, e
+                    )
+                })?);
+        }
+
+        Ok(pipeline)
+    }
+}
+
+impl<M> Default for TransformRegistry<M>
+where
+    M: Tvf + Default + fmt::Debug + Clone + 'static,
+{
+    fn default() -> Self {
+        let mut registry = TransformRegistry::empty();
+        registry.register("tag_rename", |params| {
+            Ok(Box::new(TagRename::from_params(params)?))
+        });
+        registry.register("mandatory_fields", |params| {
+            Ok(Box::new(MandatoryFields::from_params(params)?))
+        });
+        registry
+    }
+}
+
+/// Transform that rename the tags of the requests, and rename them back on the responses
+///
+/// Parameters are the map of the request tags to rename with their new tag:
+/// ```yaml
+/// name: tag_rename
+/// params:
+///   1: 10
+///   2: 20
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TagRename {
+    tags: BTreeMap<usize, usize>,
+}
+
+impl TagRename {
+    /// Method to create a tag rename transform from the map of tags to rename
+    pub fn new(tags: BTreeMap<usize, usize>) -> TagRename {
+        TagRename { tags }
+    }
+
+    /// Method to create a tag rename transform from its settings parameters
+    pub fn from_params(params: &TransformParams) -> Result<TagRename, Box<dyn Error>> {
+        let serde_yaml::Value::Mapping(params) = &params.0 else {
+            return Err("the tags to rename must be a map".into());
+        };
+
+        let mut tags = BTreeMap::new();
+        for (from, to) in params {
+            tags.insert(Self::parse_tag(from)?, Self::parse_tag(to)?);
+        }
+
+        Ok(TagRename { tags })
+    }
+
+    /// Method to parse a tag that can be a number or a string (keys of configuration files are strings)
+    fn parse_tag(tag: &serde_yaml::Value) -> Result<usize, Box<dyn Error>> {
+        match tag {
+            serde_yaml::Value::Number(number) => number
+                .as_u64()
+                .map(|tag| tag as usize)
+                .ok_or_else(|| format!("invalid tag `{}`", number).into()),
+            serde_yaml::Value::String(string) => string
+                .parse()
+                .map_err(|e| format!("invalid tag `{}`: {}", string, e).into()),
+            _ => Err(format!("invalid tag `{:?}`", tag).into()),
+        }
+    }
+
+    /// Method to move a field of a TVF to another tag
+    fn rename<M>(tvf: &mut M, from: usize, to: usize) -> Result<(), ServiceError>
+    where
+        M: Tvf + Default + fmt::Debug + Clone,
+    {
+        if !tvf.contains(from) {
+            return Ok(());
+        }
+
+        match tvf.get_type(from)? {
+            TvfType::Buffer => {
+                let buffer = tvf.get_buffer(from)?.into_owned();
+                tvf.put_buffer(to, buffer);
+            }
+            TvfType::Unsigned => tvf.put_unsigned(to, tvf.get_unsigned(from)?),
+            TvfType::Signed => tvf.put_signed(to, tvf.get_signed(from)?),
+            TvfType::Byte => tvf.put_byte(to, tvf.get_byte(from)?),
+            TvfType::Float => tvf.put_float(to, tvf.get_float(from)?),
+            TvfType::String => {
+                let string = tvf.get_string(from)?.into_owned();
+                tvf.put_string(to, string);
+            }
+            TvfType::Bytes => {
+                let bytes = tvf.get_bytes(from)?.into_owned();
+                tvf.put_bytes(to, bytes);
+            }
+            TvfType::Date => tvf.put_date(to, tvf.get_date(from)?),
+            TvfType::DateTime => tvf.put_datetime(to, tvf.get_datetime(from)?),
+        }
+
+        tvf.remove(from);
+        Ok(())
+    }
+}
+
+impl<M> Transform<M> for TagRename
+where
+    M: Tvf + Default + fmt::Debug + Clone,
+{
+    fn on_request(&self, mut request: M) -> Result<M, ServiceError> {
+        for (from, to) in &self.tags {
+            Self::rename(&mut request, *from, *to)?;
+        }
+
+        Ok(request)
+    }
+
+    fn on_response(&self, mut response: M) -> Result<M, ServiceError> {
+        for (from, to) in &self.tags {
+            Self::rename(&mut response, *to, *from)?;
+        }
+
+        Ok(response)
+    }
+}
+
+/// Parameters of the [`MandatoryFields`] transform
+#[derive(Debug, Deserialize)]
+struct MandatoryFieldsParams {
+    /// Path of the dictionary (XML if the file have a `.xml` extension, CSV otherwise)
+    dictionary: String,
+    /// Labels of the mandatory fields. Fields of sub buffers are separated by dots
+    fields: Vec<String>,
+}
+
+/// Transform that check that the requests contain mandatory fields, described by their labels in a dictionary
+///
+/// A request without a mandatory field is rejected with a protocol error.
+/// Fields of sub buffers are separated by dots:
+/// ```yaml
+/// name: mandatory_fields
+/// params:
+///   dictionary: /etc/prosa/payment.csv
+///   fields: ["amount", "card.pan"]
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct MandatoryFields {
+    fields: Vec<(String, Vec<usize>)>,
+}
+
+impl MandatoryFields {
+    /// Method to create a mandatory fields transform from the labels of the fields in the dictionary
+    pub fn new<T>(dictionary: &Dictionary, labels: &[T]) -> Result<MandatoryFields, Box<dyn Error>>
+    where
+        T: AsRef<str>,
+    {
+        let mut fields = Vec::with_capacity(labels.len());
+        for label in labels {
+            let label = label.as_ref();
+            let mut path = Vec::new();
+            let mut current = dictionary;
+            let mut segments = label.split('.').peekable();
+            while let Some(segment) = segments.next() {
+                let entry = current.get_by_label(segment).ok_or_else(|| {
+                    format!(
+                        "unknown field `{}` in the dictionary `{}`",
+                        label,
+                        dictionary.get_name()
+                    )
+                })?;
+                path.push(entry.tag);
+
+                if segments.peek().is_some() {
+                    match &entry.entry_type {
+                        EntryType::Node(node) if !entry.repeatable => current = node,
+                        _ => {
+                            return Err(format!(
+                                "the field `{}` is not a sub buffer in `{}`",
+                                segment, label
+                            )
+                            .into())
+                        }
+                    }
+                }
+            }
+
+            fields.push((label.to_string(), path));
+        }
+
+        Ok(MandatoryFields { fields })
+    }
+
+    /// Method to create a mandatory fields transform from its settings parameters
+    pub fn from_params(params: &TransformParams) -> Result<MandatoryFields, Box<dyn Error>> {
+        let params: MandatoryFieldsParams = params.deserialize()?;
+        let dictionary: Arc<Dictionary> = if Path::new(&params.dictionary)
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("xml"))
+        {
+            Dictionary::from_xml_file(&params.dictionary)?
+        } else {
+            Dictionary::from_csv_file(&params.dictionary)?
+        };
+
+        Self::new(&dictionary, &params.fields)
+    }
+}
+
+impl<M> Transform<M> for MandatoryFields
+where
+    M: Tvf + Default + fmt::Debug + Clone,
+{
+    fn on_request(&self, request: M) -> Result<M, ServiceError> {
+        if let Some((label, _)) = self
+            .fields
+            .iter()
+            .find(|(_, path)| !request.contains_path(path))
+        {
+            Err(ServiceError::ProtocolError {
+                code: 0,
+                reason: format!("missing mandatory field `{}`", label),
+            })
+        } else {
+            Ok(request)
+        }
+    }
+}
+
+/// Adaptor that pass the requests and the responses of an inner adaptor through a [`Pipeline`]
+///
+/// It implement the stub ([`crate::stub::adaptor::StubAdaptor`]) and the inj ([`crate::inj::adaptor::InjAdaptor`]) adaptor traits when the inner adaptor does.
+/// When created by the processor, the pipeline is built with the default [`TransformRegistry`] from the `pipeline` of the processor settings.
+///
+/// ```
+/// use prosa::core::adaptor::PipelineAdaptor;
+/// use prosa::stub::adaptor::StubParotAdaptor;
+/// use prosa::stub::proc::StubProc;
+/// use prosa_utils::msg::simple_string_tvf::SimpleStringTvf;
+///
+/// // Adaptor type to give to the stub processor
+/// type ParotPipelineAdaptor = PipelineAdaptor<StubParotAdaptor, SimpleStringTvf>;
+/// ```
+pub struct PipelineAdaptor<A, M> {
+    pub(crate) adaptor: A,
+    pub(crate) pipeline: Pipeline<M>,
+}
+
+impl<A, M> PipelineAdaptor<A, M> {
+    /// Method to wrap an adaptor with a pipeline
+    pub fn with_pipeline(adaptor: A, pipeline: Pipeline<M>) -> PipelineAdaptor<A, M> {
+        PipelineAdaptor { adaptor, pipeline }
+    }
+
+    /// Getter of the inner adaptor
+    pub fn get_adaptor(&self) -> &A {
+        &self.adaptor
+    }
+
+    /// Getter of the pipeline
+    pub fn get_pipeline(&self) -> &Pipeline<M> {
+        &self.pipeline
+    }
+
+    /// Method to retrieve the inner adaptor
+    pub fn into_adaptor(self) -> A {
+        self.adaptor
+    }
+}
+
+impl<A, M> AdaptorConfigReload<&[TransformSettings]> for PipelineAdaptor<A, M>
+where
+    M: Tvf + Default + fmt::Debug + Clone + 'static,
+{
+    fn reload(&mut self, config: &[TransformSettings]) -> Result<(), AdaptorError> {
+        self.pipeline = TransformRegistry::default()
+            .build(config)
+            .map_err(|e| AdaptorError::InvalidConfig(e.to_string()))?;
+        Ok(())
+    }
+}
+
+impl<A, M> Adaptor for PipelineAdaptor<A, M>
+where
+    A: Adaptor + Send,
+    M: Tvf + Default + fmt::Debug + Clone + 'static,
+{
+    fn terminate(&mut self) {
+        self.adaptor.terminate();
+    }
+
+    fn async_init(
+        &mut self,
+        proc: &(dyn ProcBusParam + Sync),
+    ) -> impl std::future::Future<Output = Result<(), Box<dyn Error>>> + Send {
+        self.adaptor.async_init(proc)
+    }
+
+    fn async_terminate(&mut self) -> impl std::future::Future<Output = ()> + Send
+    where
+        Self: Send,
+    {
+        self.adaptor.async_terminate()
+    }
+
+    /// Rebuild the pipeline from the `pipeline` of the processor settings, then reload the inner adaptor
+    fn reload_config<S>(&mut self, settings: &S) -> Result<bool, AdaptorError>
+    where
+        S: ProcSettings,
+    {
+        AdaptorConfigReload::reload(self, settings.get_pipeline())?;
+        self.adaptor.reload_config(settings)?;
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate self as prosa;
+
+    use std::{collections::BTreeMap, env, error::Error, fs, sync::Mutex};
+
+    use prosa_macros::{proc_settings, settings};
+    use prosa_utils::{
+        dict::{DictEntry, Dictionary, EntryType},
+        msg::{simple_string_tvf::SimpleStringTvf, tvf::Tvf as _},
+    };
+    use serde::{Deserialize, Serialize};
+    use tokio::sync::mpsc;
+
+    use super::{
+        Adaptor, AdaptorConfigReload, AdaptorError, MandatoryFields, Pipeline, PipelineAdaptor,
+        TagRename, TransformParams, TransformRegistry, TransformSettings,
+    };
+    use crate::core::{
+        main::Main,
+        msg::{InternalMainMsg, InternalMsg},
+        proc::{Proc, ProcBusParam, ProcConfig as _},
+        service::ServiceError,
+    };
+    use crate::stub::{
+        adaptor::{StubAdaptor, StubParotAdaptor},
+        proc::StubProc,
+        proc::StubSettings,
+    };
+
+    static EVENTS: Mutex<Vec<&str>> = Mutex::new(Vec::new());
+
+    /// Dummy settings
+    #[settings]
+    #[derive(Default, Debug, Serialize)]
+    struct TestSettings {}
+
+    #[derive(Adaptor)]
+    struct TestDefaultAdaptor {
+        terminated: bool,
+    }
+
+    #[derive(Adaptor)]
+    #[adaptor(async_init, async_terminate)]
+    struct TestHookAdaptor {
+        initialized: bool,
+    }
+
+    impl TestHookAdaptor {
+        async fn on_init(
+            &mut self,
+            proc: &(dyn ProcBusParam + Sync),
+        ) -> Result<(), Box<dyn Error>> {
+            assert_eq!(1, proc.get_proc_id());
+            self.initialized = true;
+            EVENTS.lock().unwrap().push("init");
+            Ok(())
+        }
+
+        async fn on_terminate(&mut self) {
+            assert!(self.initialized);
+            EVENTS.lock().unwrap().push("terminate");
+        }
+    }
+
+    #[proc_settings]
+    #[derive(Default, Debug, Deserialize, Serialize)]
+    struct TestReloadSettings {}
+
+    #[derive(Deserialize)]
+    struct TestReloadConfig {
+        endpoints: Vec<String>,
+    }
+
+    #[derive(Adaptor)]
+    #[adaptor(reload = TestReloadConfig)]
+    struct TestReloadAdaptor {
+        endpoints: Vec<String>,
+    }
+
+    impl AdaptorConfigReload<TestReloadConfig> for TestReloadAdaptor {
+        fn reload(&mut self, config: TestReloadConfig) -> Result<(), AdaptorError> {
+            if config.endpoints.is_empty() {
+                return Err(AdaptorError::InvalidConfig("no endpoint".into()));
+            }
+
+            self.endpoints = config.endpoints;
+            Ok(())
+        }
+    }
+
+    impl StubAdaptor<SimpleStringTvf> for TestHookAdaptor {
+        fn new(_proc: &StubProc<SimpleStringTvf>) -> Result<Self, Box<dyn Error>> {
+            Ok(Self { initialized: false })
+        }
+
+        fn process_request(
+            &mut self,
+            _service_name: &str,
+            request: &SimpleStringTvf,
+        ) -> Result<SimpleStringTvf, ServiceError> {
+            Ok(request.clone())
+        }
+    }
+
+    #[tokio::test]
+    async fn adaptor_default_hooks() {
+        let (internal_tx_queue, _internal_rx_queue) = mpsc::channel(16);
+        let bus = Main::<SimpleStringTvf>::new(
+            internal_tx_queue.clone(),
+            internal_tx_queue,
+            &TestSettings::default(),
+        )
+        .unwrap();
+
+        let mut adaptor = TestDefaultAdaptor { terminated: false };
+        adaptor.async_init(&bus).await.unwrap();
+        adaptor.async_terminate().await;
+        assert!(!adaptor.terminated);
+    }
+
+    #[test]
+    fn adaptor_config_reload() {
+        let config_path = env::temp_dir().join("prosa_adaptor_config_reload.yml");
+        fs::write(
+            &config_path,
+            "endpoints: [\"10.0.0.1:443\", \"10.0.0.2:443\"]\n",
+        )
+        .unwrap();
+        let settings = TestReloadSettings {
+            adaptor_config_path: Some(config_path.to_str().unwrap().into()),
+            ..Default::default()
+        };
+
+        let mut adaptor = TestReloadAdaptor {
+            endpoints: vec![String::from("10.0.0.1:443")],
+        };
+        assert!(adaptor.reload_config(&settings).unwrap());
+        assert_eq!(vec!["10.0.0.1:443", "10.0.0.2:443"], adaptor.endpoints);
+
+        // A rejected configuration keep the previous one
+        fs::write(&config_path, "endpoints: []\n").unwrap();
+        assert!(matches!(
+            adaptor.reload_config(&settings),
+            Err(AdaptorError::InvalidConfig(_))
+        ));
+        assert_eq!(vec!["10.0.0.1:443", "10.0.0.2:443"], adaptor.endpoints);
+
+        fs::write(&config_path, "endpoints: 443\n").unwrap();
+        assert!(matches!(
+            adaptor.reload_config(&settings),
+            Err(AdaptorError::Config(_))
+        ));
+        assert_eq!(2, adaptor.endpoints.len());
+
+        // Adaptors without reload are left unchanged
+        let mut adaptor = TestDefaultAdaptor { terminated: false };
+        assert!(!adaptor.reload_config(&settings).unwrap());
+
+        fs::remove_file(config_path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn adaptor_terminate_before_deregister() {
+        let (internal_tx_queue, mut internal_rx_queue) = mpsc::channel(16);
+        let bus = Main::<SimpleStringTvf>::new(
+            internal_tx_queue.clone(),
+            internal_tx_queue,
+            &TestSettings::default(),
+        )
+        .unwrap();
+
+        let stub_proc = StubProc::<SimpleStringTvf>::create(
+            1,
+            bus,
+            StubSettings::new(vec![String::from("TEST")]),
+        );
+        Proc::<TestHookAdaptor>::run(stub_proc, String::from("STUB_PROC"));
+
+        // Act as the main task to shut down the processor
+        while let Some(msg) = internal_rx_queue.recv().await {
+            match msg {
+                InternalMainMsg::NewProcQueue(proc_service) => proc_service
+                    .proc_queue
+                    .send(InternalMsg::Shutdown)
+                    .await
+                    .unwrap(),
+                InternalMainMsg::DeleteProc(proc_id) => {
+                    assert_eq!(1, proc_id);
+                    EVENTS.lock().unwrap().push("deregister");
+                    break;
+                }
+                _ => {}
+            }
+        }
+
+        assert_eq!(
+            vec!["init", "terminate", "deregister"],
+            *EVENTS.lock().unwrap()
+        );
+    }
+
+    #[test]
+    fn pipeline_transforms() {
+        let mut card = Dictionary::new("card");
+        card.add_entry(DictEntry::new(1, "pan", EntryType::String, false))
+            .unwrap();
+        let mut dictionary = Dictionary::new("message");
+        dictionary
+            .add_entry(DictEntry::new(1, "amount", EntryType::Unsigned, false))
+            .unwrap();
+        dictionary
+            .add_entry(DictEntry::new(
+                2,
+                "card",
+                EntryType::Node(std::sync::Arc::new(card)),
+                false,
+            ))
+            .unwrap();
+        assert!(MandatoryFields::new(&dictionary, &["card.unknown"]).is_err());
+        assert!(MandatoryFields::new(&dictionary, &["amount.pan"]).is_err());
+
+        // Check the fields of the adaptor request, then rename them for the processing
+        let pipeline = Pipeline::<SimpleStringTvf>::new()
+            .with(MandatoryFields::new(&dictionary, &["amount", "card.pan"]).unwrap())
+            .with(TagRename::new(BTreeMap::from([(1, 10), (2, 20)])));
+        assert_eq!(2, pipeline.len());
+
+        let mut request = SimpleStringTvf::default();
+        request.put_unsigned(1, 42);
+        let mut card = SimpleStringTvf::default();
+        card.put_string(1, "4000000000000002");
+        request.put_buffer(2, card);
+        request.put_string(3, "unchanged");
+
+        let transformed = pipeline.on_request(request.clone()).unwrap();
+        assert!(!transformed.contains(1) && !transformed.contains(2));
+        assert_eq!(42, transformed.get_unsigned(10).unwrap());
+        assert_eq!(
+            "4000000000000002",
+            transformed
+                .get_buffer(20)
+                .unwrap()
+                .get_string(1)
+                .unwrap()
+                .as_str()
+        );
+        assert_eq!("unchanged", transformed.get_string(3).unwrap().as_str());
+
+        // Responses are renamed back
+        let response = pipeline.on_response(transformed).unwrap();
+        assert_eq!(42, response.get_unsigned(1).unwrap());
+        assert_eq!(
+            "4000000000000002",
+            response
+                .get_buffer(2)
+                .unwrap()
+                .get_string(1)
+                .unwrap()
+                .as_str()
+        );
+
+        request.remove(2);
+        assert_eq!(
+            "Protocol error 0: missing mandatory field `card.pan`",
+            pipeline.on_request(request).unwrap_err().to_string()
+        );
+    }
+
+    #[tokio::test]
+    async fn pipeline_stub_adaptor() {
+        let dictionary_path = env::temp_dir().join("prosa_pipeline_stub_adaptor.csv");
+        fs::write(
+            &dictionary_path,
+            "dictionary,tag,label,type,repeatable,reference\nmessage,1,mti,string,,\nmessage,2,amount,unsigned,,\n",
+        )
+        .unwrap();
+
+        let pipeline: Vec<TransformSettings> = serde_yaml::from_str(&format!(
+            "- name: mandatory_fields\n  params:\n    dictionary: {}\n    fields: [mti]\n- name: tag_rename\n  params:\n    2: 12\n",
+            dictionary_path.display()
+        ))
+        .unwrap();
+        assert!(TransformRegistry::<SimpleStringTvf>::default()
+            .build(&[TransformSettings::new(
+                "unknown",
+                TransformParams::default()
+            )])
+            .is_err());
+
+        let (internal_tx_queue, _internal_rx_queue) = mpsc::channel(16);
+        let bus = Main::<SimpleStringTvf>::new(
+            internal_tx_queue.clone(),
+            internal_tx_queue,
+            &TestSettings::default(),
+        )
+        .unwrap();
+        let mut settings = StubSettings::new(vec![String::from("TEST")]);
+        settings.set_pipeline(pipeline);
+        let stub_proc = StubProc::<SimpleStringTvf>::create(1, bus, settings);
+
+        // The parot adaptor respond with the renamed request, that is renamed back
+        let mut adaptor =
+            PipelineAdaptor::<StubParotAdaptor, SimpleStringTvf>::new(&stub_proc).unwrap();
+        assert_eq!(2, adaptor.get_pipeline().len());
+        let mut request = SimpleStringTvf::default();
+        request.put_string(1, "0100");
+        request.put_unsigned(2, 1000);
+        let response = adaptor.process_request("TEST", &request).unwrap();
+        assert_eq!("0100", response.get_string(1).unwrap().as_str());
+        assert_eq!(1000, response.get_unsigned(2).unwrap());
+        assert!(!response.contains(12));
+
+        request.remove(1);
+        assert!(adaptor.process_request("TEST", &request).is_err());
+
+        // The pipeline is rebuilt with the reloaded settings, and kept if they are invalid
+        let mut settings = StubSettings::new(vec![String::from("TEST")]);
+        settings.set_pipeline(vec![TransformSettings::new(
+            "unknown",
+            TransformParams::default(),
+        )]);
+        assert!(adaptor.reload_config(&settings).is_err());
+        assert_eq!(2, adaptor.get_pipeline().len());
+        settings.set_pipeline(Vec::new());
+        assert!(adaptor.reload_config(&settings).unwrap());
+        assert!(adaptor.get_pipeline().is_empty());
+        assert!(adaptor.process_request("TEST", &request).is_ok());
+        adaptor.async_terminate().await;
+
+        fs::remove_file(dictionary_path).unwrap();
+    }
 }
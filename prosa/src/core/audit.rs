@@ -0,0 +1,263 @@
+//! Audit log of the main bus, to know what the bus did with the processors, services and transactions
+//!
+//! The audit log is enabled with the `audit` setting (see [`AuditSettings`]).
+//! It keeps the last routed control events (and optionally the service resolutions of the transactions) in a ring buffer, retrieved with [`Main::audit_dump`](crate::core::main::Main::audit_dump) or with the `audit` command of the control socket.
+//!
+//! ```yaml
+//! audit:
+//!   capacity: 1024
+//!   resolutions: true
+//! ```
+
+use std::{collections::VecDeque, fmt::Debug, sync::Mutex, time::SystemTime};
+
+use prosa_utils::msg::tvf::Tvf;
+use serde::{Deserialize, Serialize};
+
+use super::msg::InternalMainMsg;
+
+/// Settings of the main bus audit log
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct AuditSettings {
+    /// Number of records kept by the audit log (the oldest records are dropped first)
+    #[serde(default = "AuditSettings::default_capacity")]
+    pub capacity: usize,
+    /// Record the service resolution of every transaction (processor and queue that serve it)
+    #[serde(default)]
+    pub resolutions: bool,
+}
+
+impl AuditSettings {
+    fn default_capacity() -> usize {
+        1024
+    }
+
+    /// Method to create audit settings with the number of records kept
+    pub fn new(capacity: usize) -> AuditSettings {
+        AuditSettings {
+            capacity,
+            resolutions: false,
+        }
+    }
+}
+
+impl Default for AuditSettings {
+    fn default() -> Self {
+        AuditSettings::new(Self::default_capacity())
+    }
+}
+
+/// Kind of event recorded by the audit log
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditKind {
+    /// Registration of a processor queue
+    NewProcQueue,
+    /// Deregistration of a processor
+    DeleteProc,
+    /// Change of the lifecycle state of a processor
+    ProcState,
+    /// Deregistration of a processor queue
+    DeleteProcQueue,
+    /// Registration of a service (for a whole processor if there is no queue id)
+    NewService,
+    /// Deregistration of a service (for a whole processor if there is no queue id)
+    DeleteService,
+    /// Command sent to the main task
+    Command,
+    /// Shutdown of the ProSA
+    Shutdown,
+    /// Stop of a processors group
+    StopGroup,
+    /// Restart of a processors group
+    RestartGroup,
+    /// Reload of the configuration
+    ReloadConfig,
+    /// Eviction of the services of an unresponsive processor queue
+    Evicted,
+    /// Restoration of the services of a processor queue responsive again
+    Restored,
+    /// Resolution of a service for a transaction
+    Resolution,
+}
+
+/// Record of the audit log
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AuditRecord {
+    /// Time of the event
+    pub timestamp: SystemTime,
+    /// Kind of the event
+    pub kind: AuditKind,
+    /// Processor id concerned by the event
+    pub proc_id: Option<u32>,
+    /// Processor queue id concerned by the event
+    pub queue_id: Option<u32>,
+    /// Service name concerned by the event
+    pub service: Option<String>,
+    /// Message id of the transaction concerned by the event
+    pub correlation_id: Option<u64>,
+    /// Detail of the event (command, shutdown reason, group name, processor state)
+    pub detail: Option<String>,
+}
+
+impl AuditRecord {
+    /// Method to create a record of an event that happen now
+    pub fn new(kind: AuditKind) -> AuditRecord {
+        AuditRecord {
+            timestamp: SystemTime::now(),
+            kind,
+            proc_id: None,
+            queue_id: None,
+            service: None,
+            correlation_id: None,
+            detail: None,
+        }
+    }
+
+    /// Setter of the processor (and processor queue) concerned by the event
+    pub fn proc(mut self, proc_id: u32, queue_id: Option<u32>) -> Self {
+        self.proc_id = Some(proc_id);
+        self.queue_id = queue_id;
+        self
+    }
+
+    /// Setter of the service concerned by the event
+    pub fn service<S>(mut self, service: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.service = Some(service.into());
+        self
+    }
+
+    /// Setter of the message id of the transaction concerned by the event
+    pub fn correlation(mut self, correlation_id: u64) -> Self {
+        self.correlation_id = Some(correlation_id);
+        self
+    }
+
+    /// Setter of the detail of the event
+    pub fn detail<S>(mut self, detail: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.detail = Some(detail.into());
+        self
+    }
+}
+
+/// Ring buffer of the audit records
+#[derive(Debug)]
+pub struct AuditLog {
+    capacity: usize,
+    resolutions: bool,
+    records: Mutex<VecDeque<AuditRecord>>,
+}
+
+impl AuditLog {
+    /// Method to create an audit log from its settings
+    pub fn new(settings: &AuditSettings) -> AuditLog {
+        AuditLog {
+            capacity: settings.capacity.max(1),
+            resolutions: settings.resolutions,
+            records: Mutex::new(VecDeque::with_capacity(settings.capacity.max(1))),
+        }
+    }
+
+    /// Method to know if the service resolutions are recorded
+    pub fn is_recording_resolutions(&self) -> bool {
+        self.resolutions
+    }
+
+    /// Method to add a record, the oldest record is dropped if the log is full
+    pub fn record(&self, record: AuditRecord) {
+        let mut records = self.records.lock().unwrap();
+        if records.len() >= self.capacity {
+            records.pop_front();
+        }
+        records.push_back(record);
+    }
+
+    /// Method to record a message received by the main task (queries and heartbeats are not recorded)
+    pub(crate) fn record_msg<M>(&self, msg: &InternalMainMsg<M>)
+    where
+        M: Sized + Clone + Debug + Tvf + Default + 'static + std::marker::Send + std::marker::Sync,
+    {
+        match msg {
+            InternalMainMsg::NewProcQueue(proc) => self.record(
+                AuditRecord::new(AuditKind::NewProcQueue)
+                    .proc(proc.get_proc_id(), Some(proc.get_queue_id())),
+            ),
+            InternalMainMsg::DeleteProc(proc_id) => {
+                self.record(AuditRecord::new(AuditKind::DeleteProc).proc(*proc_id, None))
+            }
+            InternalMainMsg::ProcState(proc_id, state) => self.record(
+                AuditRecord::new(AuditKind::ProcState)
+                    .proc(*proc_id, None)
+                    .detail(state.to_string()),
+            ),
+            InternalMainMsg::DeleteProcQueue(proc_id, queue_id) => self.record(
+                AuditRecord::new(AuditKind::DeleteProcQueue).proc(*proc_id, Some(*queue_id)),
+            ),
+            InternalMainMsg::NewProcService(names, proc_id) => {
+                for name in names {
+                    self.record(
+                        AuditRecord::new(AuditKind::NewService)
+                            .proc(*proc_id, None)
+                            .service(name),
+                    );
+                }
+            }
+            InternalMainMsg::NewService(names, proc_id, queue_id) => {
+                for name in names {
+                    self.record(
+                        AuditRecord::new(AuditKind::NewService)
+                            .proc(*proc_id, Some(*queue_id))
+                            .service(name),
+                    );
+                }
+            }
+            InternalMainMsg::DeleteProcService(names, proc_id) => {
+                for name in names {
+                    self.record(
+                        AuditRecord::new(AuditKind::DeleteService)
+                            .proc(*proc_id, None)
+                            .service(name),
+                    );
+                }
+            }
+            InternalMainMsg::DeleteService(names, proc_id, queue_id) => {
+                for name in names {
+                    self.record(
+                        AuditRecord::new(AuditKind::DeleteService)
+                            .proc(*proc_id, Some(*queue_id))
+                            .service(name),
+                    );
+                }
+            }
+            InternalMainMsg::Command(cmd) => {
+                self.record(AuditRecord::new(AuditKind::Command).detail(cmd.as_str()))
+            }
+            InternalMainMsg::Shutdown(reason) => {
+                self.record(AuditRecord::new(AuditKind::Shutdown).detail(reason.as_str()))
+            }
+            InternalMainMsg::StopGroup(group) => {
+                self.record(AuditRecord::new(AuditKind::StopGroup).detail(group.as_str()))
+            }
+            InternalMainMsg::RestartGroup(group) => {
+                self.record(AuditRecord::new(AuditKind::RestartGroup).detail(group.as_str()))
+            }
+            InternalMainMsg::ReloadConfig(_) => {
+                self.record(AuditRecord::new(AuditKind::ReloadConfig))
+            }
+            InternalMainMsg::Pong(_, _, _)
+            | InternalMainMsg::Topology(_)
+            | InternalMainMsg::AuditDump(_) => {}
+        }
+    }
+
+    /// Getter of the records, from the oldest to the newest
+    pub fn records(&self) -> Vec<AuditRecord> {
+        self.records.lock().unwrap().iter().cloned().collect()
+    }
+}
@@ -0,0 +1,333 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Debug,
+    thread::JoinHandle,
+    time::Duration,
+};
+
+use prosa_utils::msg::tvf::Tvf;
+use thiserror::Error;
+
+use super::{
+    adaptor::Adaptor,
+    error::BusError,
+    main::{Main, MainProc, MainRunnable as _},
+    proc::{Proc, ProcConfig},
+    settings::Settings,
+};
+
+/// Error define for the ProSA builder
+#[derive(Debug, Eq, Error, PartialEq)]
+pub enum BuilderError {
+    /// Error when the ProSA settings are not set
+    #[error("The ProSA settings are missing")]
+    MissingSettings,
+    /// Error when several processors have the same name
+    #[error("The processor name `{0}` is used several times")]
+    DuplicateProcName(String),
+    /// Error when dependencies are declared for a processor that is not declared
+    #[error("The processor `{0}` is not declared")]
+    UnknownProcName(String),
+    /// Error when a processor depends on a processor that is not declared, with the processor and the dependency names
+    #[error("The processor `{0}` depends on the unknown processor `{1}`")]
+    UnknownDependency(String, String),
+    /// Error when the processors dependencies have a cycle, with the names of the processors in it
+    #[error("The dependencies of the processors `{}` have a cycle", .0.join("`, `"))]
+    DependencyCycle(Vec<String>),
+    /// Error when a dependency is not ready within the startup timeout, with the dependency and the processor waiting for it
+    #[error("The processor `{0}` is not ready after {2:?}, the processor `{1}` that depends on it can't start")]
+    DependencyNotReady(String, String, Duration),
+    /// Error of the main bus during the startup
+    #[error(transparent)]
+    Bus(#[from] BusError),
+}
+
+/// Function that create the ProSA main bus and its main task from the settings
+type MainCreator<M> = Box<dyn FnOnce() -> (Main<M>, MainProc<M>)>;
+
+/// Function that run a processor on the ProSA bus with its processor id
+type ProcLauncher<M> = Box<dyn FnOnce(&Main<M>, u32) + Send>;
+
+/// Builder to assemble and run a ProSA programmatically.
+///
+/// It's the entry point to embed ProSA in an existing binary (for tests or hybrid applications) without the main generated by cargo-prosa.
+/// Processors ids are assigned in declaration order starting from 1, and processors names must be unique.
+/// Processors are run with [`Main::run_proc`], so they can be restarted within their group.
+///
+/// Processors can depend on others with [`ProsaBuilder::depends_on`].
+/// They're launched in dependency order, and [`ProsaBuilder::start`] waits for the dependencies of a processor to be ready before launching it.
+///
+/// ```
+/// use prosa::core::builder::ProsaBuilder;
+/// use prosa::core::settings::settings;
+/// use prosa::stub::adaptor::StubParotAdaptor;
+/// use prosa::stub::proc::{StubProc, StubSettings};
+/// use prosa_utils::msg::simple_string_tvf::SimpleStringTvf;
+/// use serde::Serialize;
+///
+/// #[settings]
+/// #[derive(Default, Debug, Serialize)]
+/// struct Settings {}
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let prosa = ProsaBuilder::<SimpleStringTvf>::new()
+///     .settings(Settings::default())
+///     .with_proc::<StubProc<SimpleStringTvf>, StubParotAdaptor>(
+///         "STUB_PROC",
+///         StubSettings::new(vec![String::from("STUB_TEST")]),
+///     )
+///     .build_and_run()
+///     .unwrap();
+/// assert_eq!(Some(1), prosa.get_proc_id("STUB_PROC"));
+///
+/// prosa.stop(String::from("End of the example")).await.unwrap();
+/// prosa.join().unwrap();
+/// # }
+/// ```
+pub struct ProsaBuilder<M>
+where
+    M: Sized + Clone + Tvf,
+{
+    create_main: Option<MainCreator<M>>,
+    procs: Vec<(String, ProcLauncher<M>)>,
+    dependencies: HashMap<String, Vec<String>>,
+    startup_timeout: Duration,
+}
+
+impl<M> ProsaBuilder<M>
+where
+    M: Sized + Clone + Debug + Tvf + Default + 'static + std::marker::Send + std::marker::Sync,
+{
+    /// Method to create an empty ProSA builder
+    pub fn new() -> ProsaBuilder<M> {
+        ProsaBuilder {
+            create_main: None,
+            procs: Vec::new(),
+            dependencies: HashMap::new(),
+            startup_timeout: Duration::from_secs(30),
+        }
+    }
+
+    /// Setter of the ProSA settings
+    pub fn settings<S>(mut self, settings: S) -> Self
+    where
+        S: Settings + 'static,
+    {
+        self.create_main = Some(Box::new(move || MainProc::create(&settings)));
+        self
+    }
+
+    /// Method to add a processor with its adaptor to the ProSA
+    pub fn with_proc<P, A>(mut self, name: impl Into<String>, settings: P::Settings) -> Self
+    where
+        P: ProcConfig<M> + Proc<A> + std::marker::Send + 'static,
+        P::Settings: Clone + std::marker::Send + std::marker::Sync + 'static,
+        A: Adaptor + 'static,
+    {
+        let name = name.into();
+        let proc_name = name.clone();
+        self.procs.push((
+            name,
+            Box::new(move |main: &Main<M>, proc_id: u32| {
+                main.run_proc::<P, A>(proc_id, settings, None, proc_name);
+            }),
+        ));
+        self
+    }
+
+    /// Method to declare the processors a processor depends on, to launch it once they're ready
+    ///
+    /// The dependencies must report they're ready with [`ProcParam::set_ready`](super::proc::ProcParam::set_ready), otherwise the start times out.
+    pub fn depends_on<I, S>(mut self, name: impl Into<String>, dependencies: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.dependencies
+            .entry(name.into())
+            .or_default()
+            .extend(dependencies.into_iter().map(Into::into));
+        self
+    }
+
+    /// Setter of the duration to wait for a dependency to be ready with [`ProsaBuilder::start`] (30 seconds by default)
+    pub fn startup_timeout(mut self, timeout: Duration) -> Self {
+        self.startup_timeout = timeout;
+        self
+    }
+
+    /// Method to check the processors, and get their launch order: the processors after their dependencies, in declaration order otherwise
+    fn startup_order(&self) -> Result<Vec<usize>, BuilderError> {
+        let mut names = HashSet::with_capacity(self.procs.len());
+        for (name, _) in &self.procs {
+            if !names.insert(name.as_str()) {
+                return Err(BuilderError::DuplicateProcName(name.clone()));
+            }
+        }
+
+        for (name, dependencies) in &self.dependencies {
+            if !names.contains(name.as_str()) {
+                return Err(BuilderError::UnknownProcName(name.clone()));
+            }
+
+            if let Some(dependency) = dependencies.iter().find(|d| !names.contains(d.as_str())) {
+                return Err(BuilderError::UnknownDependency(
+                    name.clone(),
+                    dependency.clone(),
+                ));
+            }
+        }
+
+        let mut order = Vec::with_capacity(self.procs.len());
+        let mut started = HashSet::with_capacity(self.procs.len());
+        while order.len() < self.procs.len() {
+            let next = self.procs.iter().enumerate().position(|(i, (name, _))| {
+                !order.contains(&i)
+                    && self
+                        .dependencies
+                        .get(name)
+                        .is_none_or(|d| d.iter().all(|d| started.contains(d.as_str())))
+            });
+
+            if let Some(next) = next {
+                started.insert(self.procs[next].0.as_str());
+                order.push(next);
+            } else {
+                return Err(BuilderError::DependencyCycle(
+                    self.procs
+                        .iter()
+                        .filter(|(name, _)| !started.contains(name.as_str()))
+                        .map(|(name, _)| name.clone())
+                        .collect(),
+                ));
+            }
+        }
+
+        Ok(order)
+    }
+
+    /// Method to run the ProSA main task, and all its processors in dependency order (without waiting for their dependencies to be ready)
+    pub fn build_and_run(self) -> Result<ProsaHandle<M>, BuilderError> {
+        let order = self.startup_order()?;
+        let create_main = self.create_main.ok_or(BuilderError::MissingSettings)?;
+
+        let (bus, main) = create_main();
+        let main_task = main.run();
+
+        let proc_names = self.procs.iter().map(|(name, _)| name.clone()).collect();
+        let mut launchers: Vec<Option<ProcLauncher<M>>> =
+            self.procs.into_iter().map(|(_, l)| Some(l)).collect();
+        for i in order {
+            if let Some(launcher) = launchers[i].take() {
+                launcher(&bus, i as u32 + 1);
+            }
+        }
+
+        Ok(ProsaHandle {
+            main: bus,
+            main_task,
+            proc_names,
+        })
+    }
+
+    /// Method to run the ProSA main task, and all its processors in dependency order.
+    /// A processor is launched once all its dependencies reported they're [`Ready`](super::proc::ProcLifecycle::Ready).
+    ///
+    /// If a dependency is not ready within the startup timeout, the ProSA is stopped and the stuck processor is given in the error.
+    pub async fn start(self) -> Result<ProsaHandle<M>, BuilderError> {
+        let order = self.startup_order()?;
+        let create_main = self.create_main.ok_or(BuilderError::MissingSettings)?;
+
+        let (bus, main) = create_main();
+        let main_task = main.run();
+
+        let proc_names: Vec<String> = self.procs.iter().map(|(name, _)| name.clone()).collect();
+        let mut launchers: Vec<Option<ProcLauncher<M>>> =
+            self.procs.into_iter().map(|(_, l)| Some(l)).collect();
+        for i in order {
+            for dependency in self.dependencies.get(&proc_names[i]).into_iter().flatten() {
+                let dependency_id = proc_names
+                    .iter()
+                    .position(|n| n == dependency)
+                    .map(|d| d as u32 + 1)
+                    .unwrap_or_default();
+                if !bus
+                    .wait_proc_ready(dependency_id, self.startup_timeout)
+                    .await?
+                {
+                    bus.stop(format!("The processor `{}` is not ready", dependency))
+                        .await?;
+                    return Err(BuilderError::DependencyNotReady(
+                        dependency.clone(),
+                        proc_names[i].clone(),
+                        self.startup_timeout,
+                    ));
+                }
+            }
+
+            if let Some(launcher) = launchers[i].take() {
+                launcher(&bus, i as u32 + 1);
+            }
+        }
+
+        Ok(ProsaHandle {
+            main: bus,
+            main_task,
+            proc_names,
+        })
+    }
+}
+
+impl<M> Default for ProsaBuilder<M>
+where
+    M: Sized + Clone + Debug + Tvf + Default + 'static + std::marker::Send + std::marker::Sync,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Handle of a ProSA run by the [`ProsaBuilder`]
+#[derive(Debug)]
+pub struct ProsaHandle<M>
+where
+    M: Sized + Clone + Tvf,
+{
+    main: Main<M>,
+    main_task: JoinHandle<()>,
+    proc_names: Vec<String>,
+}
+
+impl<M> ProsaHandle<M>
+where
+    M: Sized + Clone + Debug + Tvf + Default + 'static + std::marker::Send + std::marker::Sync,
+{
+    /// Getter of the ProSA main bus (to get metrics, or to run other processors)
+    pub fn main(&self) -> &Main<M> {
+        &self.main
+    }
+
+    /// Getter of the id assigned to a processor
+    pub fn get_proc_id(&self, name: &str) -> Option<u32> {
+        self.proc_names
+            .iter()
+            .position(|n| n == name)
+            .map(|i| i as u32 + 1)
+    }
+
+    /// Getter of the next processor id not assigned by the builder
+    pub fn next_proc_id(&self) -> u32 {
+        self.proc_names.len() as u32 + 1
+    }
+
+    /// Method to stop the ProSA
+    pub async fn stop(&self, reason: String) -> Result<(), BusError> {
+        self.main.stop(reason).await
+    }
+
+    /// Method to wait the end of the ProSA main task
+    pub fn join(self) -> std::thread::Result<()> {
+        self.main_task.join()
+    }
+}
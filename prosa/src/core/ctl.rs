@@ -0,0 +1,355 @@
+//! Control socket to query and control a running ProSA
+//!
+//! When [`Settings::get_ctl_socket`](crate::core::settings::Settings::get_ctl_socket) is set, the main task listens on a unix socket.
+//! Only the owner of the ProSA process can use it: the socket file is created with `0600` permissions.
+//!
+//! The protocol is line based. Every command line gets a JSON line response, `{"status":"ok","result":...}` or `{"status":"error","error":"..."}`:
+//!
+//! | Command          | Action                                                                  | Result                       |
+//! |------------------|-------------------------------------------------------------------------|------------------------------|
+//! | `status`         | Get the processors and services registered on the main bus              | [`TopologySnapshot`] in JSON |
+//! | `health`         | Get the lifecycle state of the processors registered on the main bus    | [`HealthSnapshot`] in JSON   |
+//! | `stop [REASON]`  | Stop the ProSA                                                          | `"stopping"`                 |
+//! | `reload`         | Reload the configuration files (from `config_watch`) for all processors | `null`                       |
+//! | `level <LEVEL>`  | Change the level of the log records                                     | The new level                |
+//! | `audit`          | Get the records of the main bus audit log (see [`audit`](super::audit)) | [`AuditRecord`] list in JSON |
+//! | `tps`            | Get the current TPS and latency of the bus (see [`tps`](super::tps))    | [`TpsSnapshot`] in JSON      |
+//!
+//! ```
+//! use prosa::core::ctl::{self, CtlError};
+//!
+//! async fn status() -> Result<(), CtlError> {
+//!     let topology = ctl::send_command("/run/my-prosa/ctl.sock", "status").await?;
+//!     println!("{}", topology);
+//!     Ok(())
+//! }
+//! ```
+
+use std::{
+    fmt::Debug,
+    io,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+};
+
+use prosa_utils::{config::tracing::TelemetryLevel, msg::tvf::Tvf};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::{UnixListener, UnixStream},
+    sync::oneshot,
+    task::JoinHandle,
+};
+use tracing::{debug, info, warn};
+
+use super::{error::BusError, main::Main, msg::InternalMainMsg};
+
+#[cfg(doc)]
+use super::{
+    audit::AuditRecord,
+    main::{HealthSnapshot, TopologySnapshot},
+    tps::TpsSnapshot,
+};
+
+/// Error of the control socket
+#[derive(Debug, Error)]
+pub enum CtlError {
+    /// Error on the control socket
+    #[error("Control socket error: {0}")]
+    Io(#[from] io::Error),
+    /// Error on the main bus when executing the command
+    #[error("Control bus error: {0}")]
+    Bus(#[from] BusError),
+    /// The control message is not a valid JSON
+    #[error("Control protocol error: {0}")]
+    Protocol(#[from] serde_json::Error),
+    /// The command failed, with its reason
+    #[error("Control command failed: {0}")]
+    Command(String),
+}
+
+/// Response of the control socket to a command
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "lowercase")]
+pub enum CtlResponse {
+    /// The command succeeded, with its result
+    Ok {
+        /// Result of the command
+        result: serde_json::Value,
+    },
+    /// The command failed
+    Error {
+        /// Reason of the failure
+        error: String,
+    },
+}
+
+impl<E> From<Result<serde_json::Value, E>> for CtlResponse
+where
+    E: ToString,
+{
+    fn from(result: Result<serde_json::Value, E>) -> Self {
+        match result {
+            Ok(result) => CtlResponse::Ok { result },
+            Err(e) => CtlResponse::Error {
+                error: e.to_string(),
+            },
+        }
+    }
+}
+
+/// Control socket server, listening as long as the object lives. The socket file is removed on drop
+#[derive(Debug)]
+pub struct CtlServer {
+    path: PathBuf,
+    task: JoinHandle<()>,
+}
+
+impl CtlServer {
+    /// Method to listen on the control socket (must be called within a Tokio runtime).
+    /// A stale socket file is replaced, but an error is returned if another ProSA is listening on it
+    pub fn bind<M, P>(main: Main<M>, path: P) -> io::Result<CtlServer>
+    where
+        M: Sized + Clone + Debug + Tvf + Default + 'static + std::marker::Send + std::marker::Sync,
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref().to_path_buf();
+        if path.exists() {
+            if std::os::unix::net::UnixStream::connect(&path).is_ok() {
+                return Err(io::Error::new(
+                    io::ErrorKind::AddrInUse,
+                    format!("the control socket {:?} is already in use", path),
+                ));
+            }
+
+            std::fs::remove_file(&path)?;
+        }
+
+        let listener = UnixListener::bind(&path)?;
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600))?;
+        info!("Listening on the control socket {:?}", path);
+
+        let task = tokio::spawn(async move {
+            loop {
+                match listener.accept().await {
+                    Ok((stream, _)) => {
+                        let main = main.clone();
+                        tokio::spawn(async move {
+                            if let Err(e) = Self::handle(main, stream).await {
+                                debug!("Control socket connection error: {}", e);
+                            }
+                        });
+                    }
+                    Err(e) => warn!("Can't accept a control socket connection: {}", e),
+                }
+            }
+        });
+
+        Ok(CtlServer { path, task })
+    }
+
+    /// Getter of the control socket path
+    pub fn get_path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Method to handle the commands of a control connection
+    async fn handle<M>(main: Main<M>, stream: UnixStream) -> Result<(), CtlError>
+    where
+        M: Sized + Clone + Debug + Tvf + Default + 'static + std::marker::Send + std::marker::Sync,
+    {
+        let (reader, mut writer) = stream.into_split();
+        let mut lines = BufReader::new(reader).lines();
+        while let Some(line) = lines.next_line().await? {
+            let mut args = line.split_whitespace();
+            let Some(command) = args.next() else {
+                continue;
+            };
+
+            let mut stop_reason = None;
+            let response: CtlResponse = match command {
+                "status" => match main.topology().await {
+                    Ok(topology) => serde_json::to_value(topology).into(),
+                    Err(e) => Err::<serde_json::Value, _>(e).into(),
+                },
+                "health" => match main.health().await {
+                    Ok(health) => serde_json::to_value(health).into(),
+                    Err(e) => Err::<serde_json::Value, _>(e).into(),
+                },
+                "audit" => match main.audit_dump().await {
+                    Ok(records) => serde_json::to_value(records).into(),
+                    Err(e) => Err::<serde_json::Value, _>(e).into(),
+                },
+                "tps" => match main.tps() {
+                    Some(tps) => serde_json::to_value(tps).into(),
+                    None => Err::<serde_json::Value, _>("the `tps` setting is not enabled").into(),
+                },
+                "stop" => {
+                    let reason = args.collect::<Vec<_>>().join(" ");
+                    stop_reason = Some(if reason.is_empty() {
+                        String::from("stop requested on the control socket")
+                    } else {
+                        reason
+                    });
+                    Ok::<_, String>(serde_json::Value::from("stopping")).into()
+                }
+                "reload" => {
+                    let (tx, rx) = oneshot::channel();
+                    match main.send(InternalMainMsg::ReloadConfig(tx)).await {
+                        Ok(()) => match rx.await {
+                            Ok(result) => result.map(|_| serde_json::Value::Null).into(),
+                            Err(e) => Err::<serde_json::Value, _>(e).into(),
+                        },
+                        Err(e) => Err::<serde_json::Value, _>(e).into(),
+                    }
+                }
+                "level" => match args.next().map(TelemetryLevel::try_from) {
+                    Some(Ok(level)) => {
+                        log::set_max_level(level.into());
+                        info!("Log level changed to {:?} on the control socket", level);
+                        Ok::<_, String>(serde_json::Value::from(<&str>::from(level))).into()
+                    }
+                    Some(Err(e)) => Err::<serde_json::Value, _>(e).into(),
+                    None => Err::<serde_json::Value, _>("missing level argument").into(),
+                },
+                command => {
+                    Err::<serde_json::Value, _>(format!("unknown command `{}`", command)).into()
+                }
+            };
+
+            let mut response = serde_json::to_vec(&response)?;
+            response.push(b'\n');
+            writer.write_all(&response).await?;
+
+            if let Some(reason) = stop_reason {
+                main.stop(reason).await?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for CtlServer {
+    fn drop(&mut self) {
+        self.task.abort();
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Method to send a command to a running ProSA through its control socket, and get the result
+pub async fn send_command<P>(path: P, command: &str) -> Result<serde_json::Value, CtlError>
+where
+    P: AsRef<Path>,
+{
+    let stream = UnixStream::connect(path).await?;
+    let (reader, mut writer) = stream.into_split();
+    writer
+        .write_all(format!("{}\n", command).as_bytes())
+        .await?;
+
+    let response = BufReader::new(reader)
+        .lines()
+        .next_line()
+        .await?
+        .ok_or_else(|| io::Error::from(io::ErrorKind::UnexpectedEof))?;
+    match serde_json::from_str(&response)? {
+        CtlResponse::Ok { result } => Ok(result),
+        CtlResponse::Error { error } => Err(CtlError::Command(error)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate self as prosa;
+
+    use std::time::Duration;
+
+    use prosa_macros::settings;
+    use prosa_utils::msg::simple_string_tvf::SimpleStringTvf;
+    use serde::Serialize;
+
+    use crate::core::main::{MainProc, MainRunnable as _};
+    use crate::stub::{adaptor::StubParotAdaptor, proc::StubSettings};
+
+    use super::*;
+
+    /// Dummy settings
+    #[settings]
+    #[derive(Default, Debug, Serialize)]
+    struct TestSettings {}
+
+    #[tokio::test]
+    async fn ctl_status_stop() {
+        let ctl_socket =
+            std::env::temp_dir().join(format!("prosa-ctl-{}.sock", std::process::id()));
+        let settings = TestSettings {
+            ctl_socket: Some(ctl_socket.clone()),
+            ..Default::default()
+        };
+
+        let (bus, main) = MainProc::<SimpleStringTvf>::create(&settings);
+        let main_task = main.run();
+        bus.run_proc::<crate::stub::proc::StubProc<SimpleStringTvf>, StubParotAdaptor>(
+            1,
+            StubSettings::new(vec![String::from("CTL_SRV")]),
+            None,
+            String::from("STUB_PROC"),
+        );
+
+        // Wait the stub service to be registered
+        let status = tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                if let Ok(status) = send_command(&ctl_socket, "status").await {
+                    if status["services"]["CTL_SRV"].is_array() {
+                        return status;
+                    }
+                }
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+        })
+        .await
+        .expect("Timeout waiting for the stub service");
+        assert_eq!(serde_json::json!([[1, 0]]), status["services"]["CTL_SRV"]);
+        assert_eq!(serde_json::json!([0]), status["processors"]["1"]);
+        assert_eq!(
+            0o600,
+            std::fs::metadata(&ctl_socket).unwrap().permissions().mode() & 0o777
+        );
+
+        // Failing commands
+        assert!(matches!(
+            send_command(&ctl_socket, "dummy").await,
+            Err(CtlError::Command(e)) if e == "unknown command `dummy`"
+        ));
+        assert!(matches!(
+            send_command(&ctl_socket, "reload").await,
+            Err(CtlError::Command(_))
+        ));
+        assert!(matches!(
+            send_command(&ctl_socket, "level verbose").await,
+            Err(CtlError::Command(_))
+        ));
+        assert_eq!(
+            serde_json::Value::from("warn"),
+            send_command(&ctl_socket, "level warn").await.unwrap()
+        );
+
+        // Another ProSA can't listen on the same socket
+        assert_eq!(
+            io::ErrorKind::AddrInUse,
+            CtlServer::bind(bus.clone(), &ctl_socket)
+                .unwrap_err()
+                .kind()
+        );
+
+        // Stop the ProSA, the control socket is removed
+        assert_eq!(
+            serde_json::Value::from("stopping"),
+            send_command(&ctl_socket, "stop test end").await.unwrap()
+        );
+        main_task.join().unwrap();
+        assert!(!ctl_socket.exists());
+    }
+}
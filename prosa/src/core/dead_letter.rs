@@ -0,0 +1,321 @@
+//! Dead letters, to keep the messages that can't be delivered or processed
+//!
+//! With the `dead_letter_service` setting, the messages that the bus can't route (unknown service of a scheduled request) and the requests that fail irrecoverably in the stub and inj processors (or that a recording stub can't forward) are forwarded to the dead letter service, wrapped in a [`DeadLetter`] message.
+//! Any processor can serve the dead letter service, like a stub with the [`DeadLetterFileAdaptor`](crate::stub::adaptor::DeadLetterFileAdaptor) that write them to disk.
+//! Dead letters are counted with the `prosa_dead_letters` counter (with the `reason` and `service` attributes).
+//! They are sent without waiting on the queue of the dead letter processor: the dead letters that can't be sent are dropped and counted with the `prosa_dead_letters_dropped` counter (with the `cause` and `service` attributes).
+//!
+//! ```yaml
+//! dead_letter_service: DEAD_LETTER
+//! stub:
+//!   service_names: ["DEAD_LETTER"]
+//!   adaptor_config_path: "dead_letter.yml"
+//! ```
+
+use std::{
+    fmt::Debug,
+    sync::{
+        atomic::{self, AtomicU64},
+        Arc,
+    },
+};
+
+use chrono::{NaiveDateTime, Utc};
+use opentelemetry::{
+    metrics::{Counter, Meter},
+    KeyValue,
+};
+use prosa_utils::{
+    dict::{DictEntry, Dictionary, EntryType},
+    msg::tvf::{Tvf, TvfError},
+};
+use tokio::sync::mpsc;
+use tracing::warn;
+
+use super::{
+    msg::{InternalMsg, RequestMsg},
+    service::{ServiceError, ServiceTable},
+};
+
+/// TVF tag of the original payload in a dead letter message
+pub const PAYLOAD_TAG: usize = 1;
+/// TVF tag of the failure reason in a dead letter message
+pub const REASON_TAG: usize = 2;
+/// TVF tag of the error message in a dead letter message
+pub const ERROR_TAG: usize = 3;
+/// TVF tag of the service of the original message in a dead letter message
+pub const SERVICE_TAG: usize = 4;
+/// TVF tag of the originating processor id in a dead letter message
+pub const PROC_ID_TAG: usize = 5;
+/// TVF tag of the originating processor name in a dead letter message
+pub const PROC_NAME_TAG: usize = 6;
+/// TVF tag of the failure timestamp in a dead letter message
+pub const TIMESTAMP_TAG: usize = 7;
+
+/// Method to get the dictionary of the dead letter messages, with the dictionary of the original payloads (if any)
+///
+/// Without payload dictionary, the payload is labeled as an empty node.
+pub fn dictionary(payload: Option<Arc<Dictionary>>) -> Dictionary {
+    let mut dictionary = Dictionary::new("dead_letter");
+    for entry in [
+        DictEntry::new(TIMESTAMP_TAG, "timestamp", EntryType::DateTime, false),
+        DictEntry::new(PROC_ID_TAG, "proc_id", EntryType::Unsigned, false),
+        DictEntry::new(PROC_NAME_TAG, "proc_name", EntryType::String, false),
+        DictEntry::new(SERVICE_TAG, "service", EntryType::String, false),
+        DictEntry::new(REASON_TAG, "reason", EntryType::String, false),
+        DictEntry::new(ERROR_TAG, "error", EntryType::String, false),
+        DictEntry::new(
+            PAYLOAD_TAG,
+            "payload",
+            EntryType::Node(payload.unwrap_or_else(|| Arc::new(Dictionary::new("payload")))),
+            false,
+        ),
+    ] {
+        dictionary
+            .add_entry(entry)
+            .expect("the dead letter dictionary entries are unique");
+    }
+
+    dictionary
+}
+
+/// Message that couldn't be delivered or processed, with the reason of its failure
+///
+/// It's sent to the dead letter service as a TVF message with the fields:
+///
+/// | Tag    | Type     | Description                                                        |
+/// |--------|----------|--------------------------------------------------------------------|
+/// | 1      | buffer   | Original payload                                                   |
+/// | 2      | string   | Reason of the failure (see [`ServiceError::kind`])                 |
+/// | 3      | string   | Error message                                                      |
+/// | 4      | string   | Service of the original message                                    |
+/// | 5      | unsigned | Id of the originating processor                                    |
+/// | 6      | string   | Name of the originating processor (if known)                       |
+/// | 7      | datetime | Timestamp of the failure (UTC)                                     |
+/// | 0xE770 | buffer   | Encoded service error (see [`ServiceError::encode`])               |
+///
+/// ```
+/// use prosa::core::dead_letter::{self, DeadLetter};
+/// use prosa::core::service::ServiceError;
+/// use prosa_utils::msg::simple_string_tvf::SimpleStringTvf;
+/// use prosa_utils::msg::tvf::Tvf;
+///
+/// let mut payload = SimpleStringTvf::default();
+/// payload.put_string(1, "value");
+/// let letter = DeadLetter::new(1, "SRV", payload.clone(), ServiceError::UnknownService(String::from("SRV")))
+///     .with_proc_name("stub");
+///
+/// let msg = letter.to_msg();
+/// assert_eq!("unknown_service", msg.get_string(dead_letter::REASON_TAG).unwrap().as_str());
+/// let read = DeadLetter::from_msg(&msg).unwrap();
+/// assert_eq!(&payload, read.get_payload());
+/// assert_eq!(&ServiceError::UnknownService(String::from("SRV")), read.get_error());
+/// assert_eq!(Some("stub"), read.get_proc_name());
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeadLetter<M>
+where
+    M: Sized + Clone + Tvf,
+{
+    payload: M,
+    error: ServiceError,
+    service: String,
+    proc_id: u32,
+    proc_name: Option<String>,
+    timestamp: NaiveDateTime,
+}
+
+impl<M> DeadLetter<M>
+where
+    M: Sized + Clone + Debug + Tvf + Default,
+{
+    /// Method to create a dead letter for a message of a service, that failed now
+    pub fn new<S>(proc_id: u32, service: S, payload: M, error: ServiceError) -> DeadLetter<M>
+    where
+        S: Into<String>,
+    {
+        DeadLetter {
+            payload,
+            error,
+            service: service.into(),
+            proc_id,
+            proc_name: None,
+            timestamp: Utc::now().naive_utc(),
+        }
+    }
+
+    /// Setter of the originating processor name
+    pub fn with_proc_name<S>(mut self, proc_name: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.proc_name = Some(proc_name.into());
+        self
+    }
+
+    /// Getter of the original payload
+    pub fn get_payload(&self) -> &M {
+        &self.payload
+    }
+
+    /// Getter of the error that made the message a dead letter
+    pub fn get_error(&self) -> &ServiceError {
+        &self.error
+    }
+
+    /// Getter of the service of the original message
+    pub fn get_service(&self) -> &str {
+        &self.service
+    }
+
+    /// Getter of the originating processor id
+    pub fn get_proc_id(&self) -> u32 {
+        self.proc_id
+    }
+
+    /// Getter of the originating processor name (if known)
+    pub fn get_proc_name(&self) -> Option<&str> {
+        self.proc_name.as_deref()
+    }
+
+    /// Getter of the failure timestamp (UTC)
+    pub fn get_timestamp(&self) -> NaiveDateTime {
+        self.timestamp
+    }
+
+    /// Method to wrap the dead letter into a message
+    pub fn to_msg(&self) -> M {
+        let mut msg = M::default();
+        msg.put_buffer(PAYLOAD_TAG, self.payload.clone());
+        msg.put_string(REASON_TAG, self.error.kind());
+        msg.put_string(ERROR_TAG, self.error.to_string());
+        msg.put_string(SERVICE_TAG, self.service.clone());
+        msg.put_unsigned(PROC_ID_TAG, self.proc_id as u64);
+        if let Some(proc_name) = &self.proc_name {
+            msg.put_string(PROC_NAME_TAG, proc_name.clone());
+        }
+        msg.put_datetime(TIMESTAMP_TAG, self.timestamp);
+        self.error.encode(&mut msg);
+        msg
+    }
+
+    /// Method to read a dead letter from its message
+    pub fn from_msg(msg: &M) -> Result<DeadLetter<M>, TvfError> {
+        Ok(DeadLetter {
+            payload: msg.get_buffer(PAYLOAD_TAG)?.into_owned(),
+            error: ServiceError::decode(msg)?
+                .ok_or(TvfError::FieldNotFound(ServiceError::SERVICE_ERROR_TAG))?,
+            service: msg.get_string(SERVICE_TAG)?.into_owned(),
+            proc_id: msg.get_unsigned(PROC_ID_TAG)? as u32,
+            proc_name: if msg.contains(PROC_NAME_TAG) {
+                Some(msg.get_string(PROC_NAME_TAG)?.into_owned())
+            } else {
+                None
+            },
+            timestamp: msg.get_datetime(TIMESTAMP_TAG)?,
+        })
+    }
+}
+
+/// Forwarder of the dead letters to the dead letter service (set with the `dead_letter_service` setting)
+#[derive(Debug, Clone)]
+pub(crate) struct DeadLetterQueue {
+    service: String,
+    next_id: Arc<AtomicU64>,
+    counter: Counter<u64>,
+    dropped: Counter<u64>,
+}
+
+impl DeadLetterQueue {
+    /// Method to create a dead letter forwarder for a service, with the meter of its counter
+    pub(crate) fn new(service: String, meter: &Meter) -> DeadLetterQueue {
+        DeadLetterQueue {
+            service,
+            next_id: Arc::new(AtomicU64::new(0)),
+            counter: meter
+                .u64_counter("prosa_dead_letters")
+                .with_description("messages forwarded to the dead letter service")
+                .init(),
+            dropped: meter
+                .u64_counter("prosa_dead_letters_dropped")
+                .with_description(
+                    "dead letters dropped because the dead letter service can't receive them",
+                )
+                .init(),
+        }
+    }
+
+    /// Method to count a dead letter that can't be sent to the dead letter service
+    fn drop_letter<M>(&self, letter: &DeadLetter<M>, cause: String)
+    where
+        M: Sized + Clone + Tvf,
+    {
+        warn!(
+            "The dead letter service `{}` can't receive the message of the service `{}` ({}), it is lost: {}",
+            self.service, letter.service, cause, letter.error
+        );
+        self.dropped.add(
+            1,
+            &[
+                KeyValue::new("cause", cause),
+                KeyValue::new("service", letter.service.clone()),
+            ],
+        );
+    }
+
+    /// Method to forward a dead letter to the processor that serve the dead letter service
+    ///
+    /// The dead letters of the dead letter service itself are dropped, to not loop on a failing dead letter processor.
+    /// The dead letter is dropped if the queue of the dead letter processor is full, so a processor that serve the dead letter service never wait on its own queue.
+    /// The answer of the dead letter processor is only checked for errors, without blocking the caller.
+    pub(crate) fn forward<M>(&self, table: &ServiceTable<M>, letter: DeadLetter<M>)
+    where
+        M: Sized + Clone + Debug + Tvf + Default + 'static + std::marker::Send + std::marker::Sync,
+    {
+        if letter.service == self.service {
+            warn!(
+                "Drop the dead letter of the dead letter service `{}`: {}",
+                self.service, letter.error
+            );
+            return;
+        }
+
+        self.counter.add(
+            1,
+            &[
+                KeyValue::new("reason", letter.error.kind()),
+                KeyValue::new("service", letter.service.clone()),
+            ],
+        );
+
+        let msg_id = self.next_id.fetch_add(1, atomic::Ordering::Relaxed);
+        let Some(proc_service) = table.get_proc_service(&self.service, msg_id) else {
+            self.drop_letter(&letter, String::from("unavailable"));
+            return;
+        };
+
+        let (response_queue, mut response_rx) = mpsc::channel(1);
+        let request = RequestMsg::new(
+            msg_id,
+            self.service.clone(),
+            letter.to_msg(),
+            response_queue,
+        );
+        if let Err(e) = proc_service.try_send(InternalMsg::Request(request)) {
+            self.drop_letter(&letter, e.kind().to_string());
+            return;
+        }
+
+        let service = self.service.clone();
+        tokio::spawn(async move {
+            if let Some(InternalMsg::Error(err)) = response_rx.recv().await {
+                warn!(
+                    "The dead letter service `{}` failed to keep the message of the service `{}`: {}",
+                    service,
+                    letter.service,
+                    err.get_err()
+                );
+            }
+        });
+    }
+}
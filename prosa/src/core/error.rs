@@ -0,0 +1,203 @@
+use std::fmt;
+
+use prosa_utils::msg::tvf::TvfError;
+use thiserror::Error;
+use tokio::sync::mpsc;
+
+use super::proc::ProcError;
+
+/// Kind of failure of a message sent on an internal queue
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum QueueErrorKind {
+    /// The queue is full, the message can be sent later
+    Full,
+    /// The receiver of the queue is closed, the message will never be received
+    Closed,
+}
+
+impl fmt::Display for QueueErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QueueErrorKind::Full => write!(f, "full"),
+            QueueErrorKind::Closed => write!(f, "closed"),
+        }
+    }
+}
+
+/// Error returned when a message can't be sent on an internal queue. The message is given back
+///
+/// ```
+/// use prosa::core::error::{BusError, QueueErrorKind, SendError};
+/// use tokio::sync::mpsc;
+///
+/// let (tx, rx) = mpsc::channel::<u32>(1);
+/// drop(rx);
+///
+/// let err = SendError::from(tx.try_send(42).unwrap_err());
+/// assert_eq!(QueueErrorKind::Closed, err.kind());
+/// assert_eq!("The queue is closed", err.to_string());
+///
+/// // The kind is kept by the bus error
+/// assert_eq!(Some(QueueErrorKind::Closed), BusError::from(err).kind());
+/// ```
+#[derive(Error)]
+#[error("The queue is {kind}")]
+pub struct SendError<T> {
+    kind: QueueErrorKind,
+    msg: T,
+}
+
+impl<T> SendError<T> {
+    /// Create a send error with the message that can't be sent
+    pub fn new(kind: QueueErrorKind, msg: T) -> SendError<T> {
+        SendError { kind, msg }
+    }
+
+    /// Getter of the kind of failure
+    pub fn kind(&self) -> QueueErrorKind {
+        self.kind
+    }
+
+    /// Method to get back the message that can't be sent
+    pub fn into_inner(self) -> T {
+        self.msg
+    }
+}
+
+impl<T> fmt::Debug for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SendError")
+            .field("kind", &self.kind)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T> From<mpsc::error::SendError<T>> for SendError<T> {
+    fn from(error: mpsc::error::SendError<T>) -> Self {
+        SendError::new(QueueErrorKind::Closed, error.0)
+    }
+}
+
+impl<T> From<mpsc::error::TrySendError<T>> for SendError<T> {
+    fn from(error: mpsc::error::TrySendError<T>) -> Self {
+        match error {
+            mpsc::error::TrySendError::Full(msg) => SendError::new(QueueErrorKind::Full, msg),
+            mpsc::error::TrySendError::Closed(msg) => SendError::new(QueueErrorKind::Closed, msg),
+        }
+    }
+}
+
+/// Error define for ProSA bus error (for message exchange)
+#[derive(Debug, Eq, Error, PartialEq)]
+pub enum BusError {
+    /// Error that indicate the main bus can't forward the internal main message, with the message name and the processor id
+    #[error(
+        "The main bus can't send the internal main message {0}, proc_id={1}: the queue is {2}"
+    )]
+    InternalMainQueueError(String, u32, QueueErrorKind),
+    /// Error that indicate the queue can't forward the internal message
+    #[error("The Queue can't send the internal message: {0}")]
+    InternalQueueError(String),
+    /// Error that indicate the processor doesn't respect the exchange protocol
+    #[error("The Processor {0}/{1} can't be contacted: {2}")]
+    ProcCommError(u32, u32, String),
+    /// Error on the internal TVF message use for internal exchange
+    #[error("The internal message is not correct: {0}")]
+    InternalTvfError(#[from] TvfError),
+    /// Error that indicate an internal queue can't take the message
+    #[error("The internal queue is {0}")]
+    QueueError(QueueErrorKind),
+    /// Error that indicate the queue of a processor can't take the message, with the processor id and the queue id
+    #[error("The Processor {0}/{1} queue is {2}")]
+    ProcQueueError(u32, u32, QueueErrorKind),
+    /// Error that indicate a processor queue is already registered on the main bus, with the processor id and the queue id
+    #[error("The Processor {0} queue {1} is already registered")]
+    DuplicateQueue(u32, u32),
+}
+
+impl BusError {
+    /// Getter of the kind of queue failure, if the error comes from a queue
+    pub fn kind(&self) -> Option<QueueErrorKind> {
+        match self {
+            BusError::InternalMainQueueError(_, _, kind)
+            | BusError::QueueError(kind)
+            | BusError::ProcQueueError(_, _, kind) => Some(*kind),
+            _ => None,
+        }
+    }
+}
+
+impl ProcError for BusError {
+    fn recoverable(&self) -> bool {
+        match self {
+            BusError::InternalQueueError(_) | BusError::ProcCommError(_, _, _) => true,
+            BusError::QueueError(kind) | BusError::ProcQueueError(_, _, kind) => {
+                *kind == QueueErrorKind::Full
+            }
+            _ => false,
+        }
+    }
+
+    fn error_code(&self) -> u32 {
+        match self {
+            BusError::InternalMainQueueError(_, _, _) => 100,
+            BusError::InternalQueueError(_) => 101,
+            BusError::ProcCommError(_, _, _) => 102,
+            BusError::InternalTvfError(_) => 103,
+            BusError::QueueError(_) => 104,
+            BusError::ProcQueueError(_, _, _) => 105,
+            BusError::DuplicateQueue(_, _) => 106,
+        }
+    }
+}
+
+impl<T> From<SendError<T>> for BusError {
+    fn from(error: SendError<T>) -> Self {
+        BusError::QueueError(error.kind())
+    }
+}
+
+impl<T> From<mpsc::error::SendError<T>> for BusError {
+    fn from(_error: mpsc::error::SendError<T>) -> Self {
+        BusError::QueueError(QueueErrorKind::Closed)
+    }
+}
+
+impl<T> From<mpsc::error::TrySendError<T>> for BusError {
+    fn from(error: mpsc::error::TrySendError<T>) -> Self {
+        SendError::from(error).into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn send_error_kind() {
+        let (tx, mut rx) = mpsc::channel::<u32>(1);
+        tx.send(1).await.unwrap();
+
+        let err = SendError::from(tx.try_send(2).unwrap_err());
+        assert_eq!(QueueErrorKind::Full, err.kind());
+        assert_eq!("The queue is full", err.to_string());
+        let err = BusError::from(err);
+        assert_eq!(Some(QueueErrorKind::Full), err.kind());
+        assert!(err.recoverable());
+
+        assert_eq!(Some(1), rx.recv().await);
+        drop(rx);
+        let err = SendError::from(tx.send(3).await.unwrap_err());
+        assert_eq!(QueueErrorKind::Closed, err.kind());
+        assert_eq!(3, err.into_inner());
+
+        let err = BusError::from(tx.send(4).await.unwrap_err());
+        assert_eq!(Some(QueueErrorKind::Closed), err.kind());
+        assert!(!err.recoverable());
+
+        let err = BusError::ProcQueueError(1, 2, QueueErrorKind::Closed);
+        assert_eq!(Some(QueueErrorKind::Closed), err.kind());
+        assert_eq!("The Processor 1/2 queue is closed", err.to_string());
+        assert_eq!(None, BusError::InternalQueueError("test".into()).kind());
+    }
+}
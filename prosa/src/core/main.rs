@@ -7,24 +7,38 @@
 //!
 //! Main can be consider as a service bus that routing processor messages.
 
+use super::adaptor::Adaptor;
+use super::audit::{AuditKind, AuditLog, AuditRecord};
+#[cfg(unix)]
+use super::ctl::CtlServer;
+use super::dead_letter::{DeadLetter, DeadLetterQueue};
+use super::error::{BusError, QueueErrorKind, SendError};
 use super::msg::{InternalMainMsg, InternalMsg};
-use super::proc::ProcBusParam;
-use super::service::{ProcService, ServiceTable};
-use super::settings::Settings;
+use super::proc::{Proc, ProcBusParam, ProcConfig, ProcLifecycle, PRIMARY_QUEUE_ID};
+use super::service::{MessageSizeLimit, ProcService, ServiceTable};
+use super::settings::{
+    ConfigWatch, ConfigWatcher, Heartbeat, Identity, ReloadedConfig, Settings, Shutdown,
+};
+use super::state::{FileStateStore, StateError, StateStore};
+use super::tps::{TpsSnapshot, TpsWindow};
 use opentelemetry::logs::LoggerProvider as _;
 use opentelemetry::metrics::{Meter, MeterProvider};
 use opentelemetry::trace::TracerProvider as _;
 use opentelemetry::KeyValue;
 use opentelemetry_appender_log::OpenTelemetryLogBridge;
-use prosa_utils::msg::tvf::{Tvf, TvfError};
+use prosa_utils::msg::tvf::Tvf;
+use serde::Serialize;
 use std::borrow::Cow;
-use std::sync::Arc;
-use std::{collections::HashMap, fmt::Debug};
-use thiserror::Error;
-use tokio::sync::mpsc;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet, VecDeque},
+    fmt::{self, Debug},
+};
+use tokio::sync::{mpsc, oneshot, watch};
 use tokio::{
     runtime::{Builder, Runtime},
-    signal,
+    signal, time,
 };
 use tracing::{debug, info, warn};
 
@@ -36,33 +50,46 @@ where
     /// Method to create and run the main task (must be called before processor creation)
     fn create<S: Settings>(settings: &S) -> (Main<M>, Self);
 
+    /// Method to create the main task, returning an error if it can't be created from the settings (must be called before processor creation)
+    fn try_create<S: Settings>(settings: &S) -> Result<(Main<M>, Self), StateError>
+    where
+        Self: Sized,
+    {
+        Ok(Self::create(settings))
+    }
+
     /// Method call to run the main task (must be called before processor creation)
     fn run(self) -> std::thread::JoinHandle<()>;
 }
 
-/// Error define for ProSA bus error (for message exchange)
-#[derive(Debug, Eq, Error, PartialEq)]
-pub enum BusError {
-    /// Error that indicate the queue can forward the internal main message
-    #[error("The Queue can't send the internal main message {0}, proc_id={1}, reason={2}")]
-    InternalMainQueueError(String, u32, String),
-    /// Error that indicate the queue can forward the internal message
-    #[error("The Queue can't send the internal message: {0}")]
-    InternalQueueError(String),
-    /// Error that indicate the queue can forward the internal message
-    #[error("The Processor {0}/{1} can't be contacted: {2}")]
-    ProcCommError(u32, u32, String),
-    /// Error on the internal TVF message use for internal exchange
-    #[error("The internal message is not correct: {0}")]
-    InternalTvfError(#[from] TvfError),
+/// Function that create and run a processor
+type ProcRunFn<M> = Box<dyn Fn(&Main<M>) + Send + Sync>;
+
+/// Factory of a processor, with its group, use to (re)create and run it
+struct ProcFactory<M>
+where
+    M: Sized + Clone + Tvf,
+{
+    group: Option<String>,
+    run: ProcRunFn<M>,
 }
 
-impl<M> From<mpsc::error::SendError<InternalMsg<M>>> for BusError
+/// Registry of the processor factories, use to restart processors
+struct ProcFactories<M>(Mutex<HashMap<u32, ProcFactory<M>>>)
+where
+    M: Sized + Clone + Tvf;
+
+impl<M> Debug for ProcFactories<M>
 where
     M: Sized + Clone + Tvf,
 {
-    fn from(error: mpsc::error::SendError<InternalMsg<M>>) -> Self {
-        BusError::InternalQueueError(error.to_string())
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut debug = f.debug_map();
+        if let Ok(factories) = self.0.lock() {
+            debug.entries(factories.iter().map(|(id, factory)| (id, &factory.group)));
+        }
+
+        debug.finish()
     }
 }
 
@@ -100,12 +127,57 @@ where
     M: Sized + Clone + Tvf,
 {
     internal_tx_queue: mpsc::Sender<InternalMainMsg<M>>,
+    internal_ctrl_tx_queue: mpsc::Sender<InternalMainMsg<M>>,
     name: String,
+    factories: Arc<ProcFactories<M>>,
+    /// Processor queues registered on the main bus, with their registration description
+    queues: Arc<Mutex<HashMap<(u32, u32), String>>>,
+    /// Registrations sent through the data lane and not yet handled by the main task, by processor id.
+    /// The count can be negative while the main task handles a registration before its sender counts it
+    pending_registrations: Arc<Mutex<HashMap<u32, i64>>>,
+    /// Last service table sent to the processors
+    services: watch::Sender<Arc<ServiceTable<M>>>,
+    /// Lifecycle states reported by the registered processors, published by the main task
+    proc_states: watch::Sender<HashMap<u32, ProcLifecycle>>,
+    message_size_limit: MessageSizeLimit,
+    tps: Option<Arc<TpsWindow>>,
+    state_store: Option<Arc<dyn StateStore>>,
+    dead_letter: Option<DeadLetterQueue>,
     meter_provider: opentelemetry_sdk::metrics::SdkMeterProvider,
+    prometheus_registry: Option<prometheus::Registry>,
     logger_provider: opentelemetry_sdk::logs::LoggerProvider,
     tracer_provider: opentelemetry_sdk::trace::TracerProvider,
 }
 
+/// Guard of a running processor, that forgets the registration of its queues on the main bus if the processor panics
+///
+/// A panicked processor never removes itself from the main bus, so its queues would be reported as duplicated when it's run again.
+/// The guard does nothing when the processor ends normally, because it removes itself (see [`Main::remove_proc`]).
+#[derive(Debug)]
+pub struct ProcPanicGuard {
+    proc_id: u32,
+    queues: Arc<Mutex<HashMap<(u32, u32), String>>>,
+}
+
+impl ProcPanicGuard {
+    /// Method to forget the registration of the processor queues when the processor stopped on an error, like a panicked processor
+    pub fn crashed(self) {
+        if let Ok(mut queues) = self.queues.lock() {
+            queues.retain(|(id, _), _| *id != self.proc_id);
+        }
+    }
+}
+
+impl Drop for ProcPanicGuard {
+    fn drop(&mut self) {
+        if std::thread::panicking() {
+            if let Ok(mut queues) = self.queues.lock() {
+                queues.retain(|(id, _), _| *id != self.proc_id);
+            }
+        }
+    }
+}
+
 impl<M> ProcBusParam for Main<M>
 where
     M: Sized + Clone + Tvf,
@@ -119,72 +191,193 @@ impl<M> Main<M>
 where
     M: Sized + Clone + Debug + Tvf + Default + 'static + std::marker::Send + std::marker::Sync,
 {
-    /// Method to instanciate a ProSA main task
+    /// Method to instanciate a ProSA main task with its data and control queues
     /// Must be called only one time
+    ///
+    /// Return a [`StateError`] if the state store (`state` setting) can't be opened
     pub fn new<S: Settings>(
         internal_tx_queue: mpsc::Sender<InternalMainMsg<M>>,
+        internal_ctrl_tx_queue: mpsc::Sender<InternalMainMsg<M>>,
         settings: &S,
-    ) -> Main<M> {
-        let logger_provider = settings.get_observability().build_logger_provider();
+    ) -> Result<Main<M>, StateError> {
+        // All the telemetry carry the identity of the ProSA instance
+        let name = settings.get_prosa_name();
+        let mut observability = settings.get_observability().clone();
+        observability.set_resource(match settings.get_identity() {
+            Some(identity) => identity.resource(&name),
+            None => Identity::default().resource(&name),
+        });
+
+        let logger_provider = observability.build_logger_provider();
         let otel_log_appender = OpenTelemetryLogBridge::new(&logger_provider);
         let _ = log::set_boxed_logger(Box::new(otel_log_appender));
-        log::set_max_level(settings.get_observability().get_logger_level().into());
+        log::set_max_level(observability.get_logger_level().into());
+        let (meter_provider, prometheus_registry) =
+            observability.build_meter_provider_with_registry();
+        let bus_meter = meter_provider.meter("prosa_bus_meter");
+        let mut message_size_limit =
+            MessageSizeLimit::new(settings.get_max_message_size()).meter(&bus_meter);
+        let dead_letter = settings
+            .get_dead_letter_service()
+            .map(|service| DeadLetterQueue::new(service.clone(), &bus_meter));
+        let tps = settings.get_tps().map(|tps| Arc::new(TpsWindow::new(tps)));
+        if let Some(tps) = &tps {
+            message_size_limit = message_size_limit.transactions(tps.clone());
+        }
+        let state_store = match settings.get_state() {
+            Some(state) => Some(Arc::new(FileStateStore::open(state)?) as Arc<dyn StateStore>),
+            None => None,
+        };
 
-        Main {
+        Ok(Main {
             internal_tx_queue,
-            name: settings.get_prosa_name(),
-            meter_provider: settings.get_observability().build_meter_provider(),
+            internal_ctrl_tx_queue,
+            name,
+            factories: Arc::new(ProcFactories(Mutex::new(HashMap::new()))),
+            queues: Arc::new(Mutex::new(HashMap::new())),
+            pending_registrations: Arc::new(Mutex::new(HashMap::new())),
+            services: watch::Sender::new(Arc::new(ServiceTable::default())),
+            proc_states: watch::Sender::new(HashMap::new()),
+            message_size_limit,
+            tps,
+            state_store,
+            dead_letter,
+            meter_provider,
+            prometheus_registry,
             logger_provider,
-            tracer_provider: settings.get_observability().build_tracer_provider(),
-        }
+            tracer_provider: observability.build_tracer_provider(),
+        })
     }
 
-    /// Getter of the main bus
+    /// Getter of the main bus (data lane).
+    /// Control messages sent through this queue are handled in order with the data messages, use [`Main::send`] to prioritize them
     pub fn get_bus_queue(&self) -> mpsc::Sender<InternalMainMsg<M>> {
         self.internal_tx_queue.clone()
     }
 
+    /// Method to send a message to the main task.
+    /// Control messages ([`InternalMainMsg::is_control`]) are sent through the high-priority lane.
+    /// The removals of a processor are handled by the main task after the registrations of the processor previously sent with this method
+    pub async fn send(&self, msg: InternalMainMsg<M>) -> Result<(), SendError<InternalMainMsg<M>>> {
+        if msg.is_control() {
+            self.internal_ctrl_tx_queue.send(msg).await?;
+        } else {
+            let registration_proc_id = msg.get_registration_proc_id();
+            self.internal_tx_queue.send(msg).await?;
+
+            // Counted once sent, so a cancelled send is never counted
+            if let Some(proc_id) = registration_proc_id {
+                let mut pending_registrations = self.pending_registrations.lock().unwrap();
+                let pending = pending_registrations.entry(proc_id).or_default();
+                *pending += 1;
+                if *pending == 0 {
+                    pending_registrations.remove(&proc_id);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Method to know if registrations of a processor are still in the data lane, so its removals must wait for them
+    fn has_pending_registrations(&self, proc_id: u32) -> bool {
+        self.pending_registrations
+            .lock()
+            .unwrap()
+            .get(&proc_id)
+            .is_some_and(|pending| *pending > 0)
+    }
+
+    /// Method to count a registration handled by the main task, return `true` if registrations of the processor are still in the data lane
+    fn registration_handled(&self, proc_id: u32) -> bool {
+        let mut pending_registrations = self.pending_registrations.lock().unwrap();
+        let pending = pending_registrations.entry(proc_id).or_default();
+        *pending -= 1;
+        if *pending == 0 {
+            pending_registrations.remove(&proc_id);
+            false
+        } else {
+            *pending > 0
+        }
+    }
+
     /// Method to declare a new processor on the main bus
+    ///
+    /// Return a [`BusError::DuplicateQueue`] if the processor queue is already registered
     pub async fn add_proc_queue(&self, proc: ProcService<M>) -> Result<(), BusError> {
-        self.internal_tx_queue
-            .send(InternalMainMsg::NewProcQueue(proc.clone()))
+        let (proc_id, queue_id) = (proc.get_proc_id(), proc.get_queue_id());
+        {
+            let mut queues = self.queues.lock().unwrap();
+            if let Some(registered) = queues.get(&(proc_id, queue_id)) {
+                warn!(
+                    "The processor queue {}/{} is already registered: {}, the new registration is rejected: {}",
+                    proc_id, queue_id, registered, proc
+                );
+                return Err(BusError::DuplicateQueue(proc_id, queue_id));
+            }
+
+            queues.insert((proc_id, queue_id), proc.to_string());
+        }
+
+        self.send(InternalMainMsg::NewProcQueue(proc))
             .await
             .map_err(|e| {
-                BusError::InternalMainQueueError(
-                    "NewProcQueue".into(),
-                    proc.get_proc_id(),
-                    e.to_string(),
-                )
+                self.unregister_queues(proc_id, Some(queue_id));
+                BusError::InternalMainQueueError("NewProcQueue".into(), proc_id, e.kind())
             })
     }
 
+    /// Method to know if a processor queue is registered on the main bus
+    pub fn is_queue_registered(&self, proc_id: u32, queue_id: u32) -> bool {
+        self.queues
+            .lock()
+            .unwrap()
+            .contains_key(&(proc_id, queue_id))
+    }
+
+    /// Method to get a guard that forgets the registration of all the processor queues if the processor panics (see [`ProcPanicGuard`])
+    pub fn panic_guard(&self, proc_id: u32) -> ProcPanicGuard {
+        ProcPanicGuard {
+            proc_id,
+            queues: self.queues.clone(),
+        }
+    }
+
+    /// Method to forget the registration of a processor queue, or of all the processor queues
+    fn unregister_queues(&self, proc_id: u32, queue_id: Option<u32>) {
+        let mut queues = self.queues.lock().unwrap();
+        match queue_id {
+            Some(queue_id) => {
+                queues.remove(&(proc_id, queue_id));
+            }
+            None => queues.retain(|(id, _), _| *id != proc_id),
+        }
+    }
+
     /// Method to remove an entire processor from the main bus
     pub async fn remove_proc(&self, proc_id: u32) -> Result<(), BusError> {
-        self.internal_tx_queue
-            .send(InternalMainMsg::DeleteProc(proc_id))
+        self.unregister_queues(proc_id, None);
+        self.send(InternalMainMsg::DeleteProc(proc_id))
             .await
-            .map_err(|e| {
-                BusError::InternalMainQueueError("DeleteProc".into(), proc_id, e.to_string())
-            })
+            .map_err(|e| BusError::InternalMainQueueError("DeleteProc".into(), proc_id, e.kind()))
     }
 
     /// Method to declare a new processor on the main bus
     pub async fn remove_proc_queue(&self, proc_id: u32, queue_id: u32) -> Result<(), BusError> {
-        self.internal_tx_queue
-            .send(InternalMainMsg::DeleteProcQueue(proc_id, queue_id))
+        self.unregister_queues(proc_id, Some(queue_id));
+        self.send(InternalMainMsg::DeleteProcQueue(proc_id, queue_id))
             .await
             .map_err(|e| {
-                BusError::InternalMainQueueError("DeleteProcQueue".into(), proc_id, e.to_string())
+                BusError::InternalMainQueueError("DeleteProcQueue".into(), proc_id, e.kind())
             })
     }
 
     /// Method to declare a new service for a whole processor on the main bus
     pub async fn add_service_proc(&self, names: Vec<String>, proc_id: u32) -> Result<(), BusError> {
-        self.internal_tx_queue
-            .send(InternalMainMsg::NewProcService(names, proc_id))
+        self.send(InternalMainMsg::NewProcService(names, proc_id))
             .await
             .map_err(|e| {
-                BusError::InternalMainQueueError("NewProcService".into(), proc_id, e.to_string())
+                BusError::InternalMainQueueError("NewProcService".into(), proc_id, e.kind())
             })
     }
 
@@ -195,12 +388,9 @@ where
         proc_id: u32,
         queue_id: u32,
     ) -> Result<(), BusError> {
-        self.internal_tx_queue
-            .send(InternalMainMsg::NewService(names, proc_id, queue_id))
+        self.send(InternalMainMsg::NewService(names, proc_id, queue_id))
             .await
-            .map_err(|e| {
-                BusError::InternalMainQueueError("NewService".into(), proc_id, e.to_string())
-            })
+            .map_err(|e| BusError::InternalMainQueueError("NewService".into(), proc_id, e.kind()))
     }
 
     /// Method to remove a service for a whole processor from the main bus
@@ -209,11 +399,10 @@ where
         names: Vec<String>,
         proc_id: u32,
     ) -> Result<(), BusError> {
-        self.internal_tx_queue
-            .send(InternalMainMsg::DeleteProcService(names, proc_id))
+        self.send(InternalMainMsg::DeleteProcService(names, proc_id))
             .await
             .map_err(|e| {
-                BusError::InternalMainQueueError("DeleteProcService".into(), proc_id, e.to_string())
+                BusError::InternalMainQueueError("DeleteProcService".into(), proc_id, e.kind())
             })
     }
 
@@ -224,20 +413,190 @@ where
         proc_id: u32,
         queue_id: u32,
     ) -> Result<(), BusError> {
-        self.internal_tx_queue
-            .send(InternalMainMsg::DeleteService(names, proc_id, queue_id))
+        self.send(InternalMainMsg::DeleteService(names, proc_id, queue_id))
             .await
             .map_err(|e| {
-                BusError::InternalMainQueueError("DeleteService".into(), proc_id, e.to_string())
+                BusError::InternalMainQueueError("DeleteService".into(), proc_id, e.kind())
             })
     }
 
+    /// Method to answer a heartbeat ping of the main task for a processor queue
+    pub async fn pong(&self, proc_id: u32, queue_id: u32, seq: u64) -> Result<(), BusError> {
+        self.send(InternalMainMsg::Pong(proc_id, queue_id, seq))
+            .await
+            .map_err(|e| BusError::InternalMainQueueError("Pong".into(), proc_id, e.kind()))
+    }
+
+    /// Method to report the lifecycle state of a processor
+    pub async fn set_proc_state(&self, proc_id: u32, state: ProcLifecycle) -> Result<(), BusError> {
+        self.send(InternalMainMsg::ProcState(proc_id, state))
+            .await
+            .map_err(|e| BusError::InternalMainQueueError("ProcState".into(), proc_id, e.kind()))
+    }
+
+    /// Method to get the health of the processors registered on the main bus
+    pub async fn health(&self) -> Result<HealthSnapshot, BusError> {
+        Ok(self.topology().await?.health())
+    }
+
+    /// Method to get a snapshot of the processors and services registered on the main bus
+    pub async fn topology(&self) -> Result<TopologySnapshot, BusError> {
+        let (tx, rx) = oneshot::channel();
+        self.send(InternalMainMsg::Topology(tx))
+            .await
+            .map_err(|e| BusError::InternalMainQueueError("Topology".into(), 0, e.kind()))?;
+        rx.await.map_err(|_| {
+            BusError::InternalMainQueueError("Topology".into(), 0, QueueErrorKind::Closed)
+        })
+    }
+
+    /// Method to wait until a processor reports it's [`ProcLifecycle::Ready`], return `false` if it's not ready within the timeout
+    ///
+    /// The processor must report it with [`ProcParam::set_ready`](super::proc::ProcParam::set_ready) once it can serve.
+    /// A processor that never does it is never ready, so its dependents time out (and the ProSA is stopped by the [`ProsaBuilder`](super::builder::ProsaBuilder)).
+    pub async fn wait_proc_ready(
+        &self,
+        proc_id: u32,
+        timeout: time::Duration,
+    ) -> Result<bool, BusError> {
+        let mut proc_states = self.proc_states.subscribe();
+        let ready = time::timeout(timeout, async {
+            proc_states
+                .wait_for(|states| states.get(&proc_id).is_some_and(ProcLifecycle::is_ready))
+                .await
+                .is_ok()
+        })
+        .await;
+        match ready {
+            Ok(true) => Ok(true),
+            Ok(false) => Err(BusError::InternalMainQueueError(
+                "ProcState".into(),
+                proc_id,
+                QueueErrorKind::Closed,
+            )),
+            Err(_) => Ok(false),
+        }
+    }
+
+    /// Method to get the records of the main bus audit log, from the oldest to the newest (empty if the audit log is disabled)
+    pub async fn audit_dump(&self) -> Result<Vec<AuditRecord>, BusError> {
+        let (tx, rx) = oneshot::channel();
+        self.send(InternalMainMsg::AuditDump(tx))
+            .await
+            .map_err(|e| BusError::InternalMainQueueError("AuditDump".into(), 0, e.kind()))?;
+        rx.await.map_err(|_| {
+            BusError::InternalMainQueueError("AuditDump".into(), 0, QueueErrorKind::Closed)
+        })
+    }
+
     /// Method to stop all processors
     pub async fn stop(&self, reason: String) -> Result<(), BusError> {
-        self.internal_tx_queue
-            .send(InternalMainMsg::Shutdown(reason))
+        self.send(InternalMainMsg::Shutdown(reason))
             .await
-            .map_err(|e| BusError::InternalMainQueueError("Shutdown".into(), 0, e.to_string()))
+            .map_err(|e| BusError::InternalMainQueueError("Shutdown".into(), 0, e.kind()))
+    }
+
+    /// Method to stop all processors of a group
+    pub async fn stop_group(&self, group: String) -> Result<(), BusError> {
+        self.send(InternalMainMsg::StopGroup(group))
+            .await
+            .map_err(|e| BusError::InternalMainQueueError("StopGroup".into(), 0, e.kind()))
+    }
+
+    /// Method to restart all processors of a group.
+    /// Only processors launched with [`Main::run_proc`] can be restarted
+    pub async fn restart_group(&self, group: String) -> Result<(), BusError> {
+        self.send(InternalMainMsg::RestartGroup(group))
+            .await
+            .map_err(|e| BusError::InternalMainQueueError("RestartGroup".into(), 0, e.kind()))
+    }
+
+    /// Method to create and run a processor (in an optional group).
+    /// The processor factory is kept by the main task to restart the processor with its group.
+    ///
+    /// ```
+    /// use prosa::core::main::{MainProc, MainRunnable};
+    /// use prosa::stub::adaptor::StubParotAdaptor;
+    /// use prosa::stub::proc::{StubProc, StubSettings};
+    /// use prosa_utils::msg::simple_string_tvf::SimpleStringTvf;
+    /// use prosa::core::settings::settings;
+    /// use serde::Serialize;
+    ///
+    /// #[settings]
+    /// #[derive(Default, Debug, Serialize)]
+    /// struct Settings {}
+    ///
+    /// let (bus, main) = MainProc::<SimpleStringTvf>::create(&Settings::default());
+    /// let main_task = main.run();
+    ///
+    /// let stub_settings = StubSettings::new(vec![String::from("STUB_TEST")]);
+    /// bus.run_proc::<StubProc<SimpleStringTvf>, StubParotAdaptor>(
+    ///     1,
+    ///     stub_settings,
+    ///     Some(String::from("stub_group")),
+    ///     String::from("STUB_PROC"),
+    /// );
+    /// ```
+    pub fn run_proc<P, A>(
+        &self,
+        proc_id: u32,
+        settings: P::Settings,
+        group: Option<String>,
+        proc_name: String,
+    ) where
+        P: ProcConfig<M> + Proc<A> + std::marker::Send + 'static,
+        P::Settings: Clone + std::marker::Send + std::marker::Sync + 'static,
+        A: Adaptor,
+    {
+        let proc_group = group.clone();
+        let run = move |main: &Main<M>| {
+            let proc =
+                P::create_with_group(proc_id, main.clone(), settings.clone(), proc_group.clone());
+            Proc::<A>::run(proc, proc_name.clone());
+        };
+
+        run(self);
+        if let Ok(mut factories) = self.factories.0.lock() {
+            factories.insert(
+                proc_id,
+                ProcFactory {
+                    group,
+                    run: Box::new(run),
+                },
+            );
+        }
+    }
+
+    /// Method to run again a processor from its registered factory (return `false` if there is no factory for the processor)
+    fn rerun_proc(&self, proc_id: u32) -> bool {
+        if let Some(factory) = self
+            .factories
+            .0
+            .lock()
+            .ok()
+            .as_ref()
+            .and_then(|f| f.get(&proc_id))
+        {
+            (factory.run)(self);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Getter of all processors id registered with a factory for a group
+    fn get_group_factories(&self, group: &str) -> Vec<u32> {
+        self.factories
+            .0
+            .lock()
+            .map(|factories| {
+                factories
+                    .iter()
+                    .filter(|(_, f)| f.group.as_deref() == Some(group))
+                    .map(|(id, _)| *id)
+                    .collect()
+            })
+            .unwrap_or_default()
     }
 
     /// Provide the ProSA name based on ProSA settings
@@ -250,6 +609,46 @@ where
         self.meter_provider.meter(name)
     }
 
+    /// Getter of the state store (if configured)
+    pub fn get_state_store(&self) -> Option<&Arc<dyn StateStore>> {
+        self.state_store.as_ref()
+    }
+
+    /// Getter of the registry of the prometheus exporter (if configured), to register custom collectors
+    ///
+    /// The registry is thread safe, collectors can be registered from any processor runtime.
+    pub fn get_prometheus_registry(&self) -> Option<&prometheus::Registry> {
+        self.prometheus_registry.as_ref()
+    }
+
+    /// Getter of the last service table sent to the processors
+    pub fn get_service_table(&self) -> Arc<ServiceTable<M>> {
+        self.services.borrow().clone()
+    }
+
+    /// Getter of a receiver of the service table, that always see its latest version
+    pub(crate) fn watch_service_table(&self) -> watch::Receiver<Arc<ServiceTable<M>>> {
+        self.services.subscribe()
+    }
+
+    /// Method to forward a message that can't be delivered or processed to the dead letter service (see [`dead_letter`](crate::core::dead_letter)).
+    /// Does nothing without the `dead_letter_service` setting
+    pub async fn dead_letter(&self, letter: DeadLetter<M>) {
+        if let Some(dead_letter) = &self.dead_letter {
+            dead_letter.forward(&self.get_service_table(), letter);
+        }
+    }
+
+    /// Getter of the maximum size of the messages exchanged on the bus, with its metrics
+    pub fn get_message_size_limit(&self) -> &MessageSizeLimit {
+        &self.message_size_limit
+    }
+
+    /// Method to get the current TPS and latency of the bus (if the `tps` setting is enabled)
+    pub fn tps(&self) -> Option<TpsSnapshot> {
+        self.tps.as_ref().map(|tps| tps.snapshot())
+    }
+
     /// Provide the opentelemetry Logger based on ProSA settings
     pub fn logger(&self, name: impl Into<Cow<'static, str>>) -> opentelemetry_sdk::logs::Logger {
         self.logger_provider.logger(name)
@@ -260,20 +659,121 @@ where
         self.tracer_provider
             .tracer_builder(name)
             .with_version(env!("CARGO_PKG_VERSION"))
-            .with_attributes([KeyValue::new("prosa_name", self.name.clone())])
             .build()
     }
 }
 
+/// Heartbeat state of a processor queue
+#[derive(Debug, Default)]
+struct QueueHeartbeat {
+    /// Sequence of the last ping not answered yet
+    pending: Option<u64>,
+    /// Number of consecutive pings without answer
+    missed: u32,
+    /// Services removed from the service table while the queue is unresponsive
+    evicted: Option<Vec<String>>,
+}
+
+/// Snapshot of the processors and services registered on the main bus
+///
+/// ```
+/// use prosa::core::main::TopologySnapshot;
+///
+/// let topology = TopologySnapshot::default();
+/// assert_eq!(
+///     r#"{"name":"","processors":{},"services":{},"states":{}}"#,
+///     serde_json::to_string(&topology).unwrap()
+/// );
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct TopologySnapshot {
+    /// Name of the ProSA
+    pub name: String,
+    /// Processor ids with their registered queue ids
+    pub processors: BTreeMap<u32, Vec<u32>>,
+    /// Service names with the processor queues (processor id, queue id) that serve them
+    pub services: BTreeMap<String, Vec<(u32, u32)>>,
+    /// Processor ids with their lifecycle state
+    pub states: BTreeMap<u32, ProcLifecycle>,
+    /// Current TPS and latency of the bus (with the `tps` setting)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tps: Option<TpsSnapshot>,
+}
+
+impl TopologySnapshot {
+    /// Method to get the health of the processors from the snapshot
+    pub fn health(&self) -> HealthSnapshot {
+        HealthSnapshot {
+            ready: !self.states.is_empty() && self.states.values().all(ProcLifecycle::is_ready),
+            processors: self.states.clone(),
+            tps: self.tps.clone(),
+        }
+    }
+}
+
+/// Health of the processors registered on the main bus
+///
+/// The ProSA is ready once processors are registered and all of them reported they're [`ProcLifecycle::Ready`].
+///
+/// ```
+/// use prosa::core::main::TopologySnapshot;
+/// use prosa::core::proc::ProcLifecycle;
+///
+/// let mut topology = TopologySnapshot::default();
+/// assert!(!topology.health().ready);
+/// topology.states.insert(1, ProcLifecycle::Ready);
+/// topology.states.insert(2, ProcLifecycle::Starting);
+/// assert!(!topology.health().ready);
+/// topology.states.insert(2, ProcLifecycle::Ready);
+/// assert_eq!(
+///     r#"{"ready":true,"processors":{"1":{"state":"ready"},"2":{"state":"ready"}}}"#,
+///     serde_json::to_string(&topology.health()).unwrap()
+/// );
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct HealthSnapshot {
+    /// All the registered processors are ready
+    pub ready: bool,
+    /// Processor ids with their lifecycle state
+    pub processors: BTreeMap<u32, ProcLifecycle>,
+    /// Current TPS and latency of the bus (with the `tps` setting)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tps: Option<TpsSnapshot>,
+}
+
+/// Window during which the service table updates are batched, so the processors get a single service table notification for all of them
+pub const SERVICE_UPDATE_WINDOW: time::Duration = time::Duration::from_millis(5);
+
 /// Main ProSA task processor
+///
+/// With the `ready_routing` setting, the services of a processor are withheld from the service table until the processor reports it's [`ProcLifecycle::Ready`], and withheld again if it reports another state.
+///
+/// The service table is shared with the processors as an immutable snapshot (`Arc<ServiceTable>`).
+/// Updates are applied copy-on-write: the table is cloned at most once per [`SERVICE_UPDATE_WINDOW`], and the new snapshot is sent to the processors at the end of the window.
 pub struct MainProc<M>
 where
     M: Sized + Clone + Tvf,
 {
-    name: String,
+    main: Main<M>,
     processors: HashMap<u32, HashMap<u32, ProcService<M>>>,
+    restarting_processors: HashSet<u32>,
     services: Arc<ServiceTable<M>>,
     internal_rx_queue: mpsc::Receiver<InternalMainMsg<M>>,
+    internal_ctrl_rx_queue: mpsc::Receiver<InternalMainMsg<M>>,
+    /// Removals of processors waiting for the registrations of the processor still in the data lane
+    deferred_removals: HashMap<u32, Vec<InternalMainMsg<M>>>,
+    config_watch: Option<ConfigWatch>,
+    #[cfg_attr(not(unix), allow(dead_code))]
+    ctl_socket: Option<PathBuf>,
+    heartbeat: Option<Heartbeat>,
+    heartbeat_seq: u64,
+    heartbeats: HashMap<(u32, u32), QueueHeartbeat>,
+    proc_states: HashMap<u32, ProcLifecycle>,
+    ready_routing: bool,
+    /// Services of the processor queues withheld from the service table until their processor is ready (with `ready_routing`)
+    withheld_services: HashMap<(u32, u32), Vec<String>>,
+    shutdown: Shutdown,
+    audit: Option<Arc<AuditLog>>,
     meter: Meter,
 }
 
@@ -290,6 +790,11 @@ impl<M> MainProc<M>
 where
     M: Sized + Clone + Debug + Tvf + Default + 'static + std::marker::Send + std::marker::Sync,
 {
+    /// Method to run the main task on the current runtime, instead of a dedicated thread (see [`MainRunnable::run`])
+    pub fn spawn(mut self) -> tokio::task::JoinHandle<Result<(), BusError>> {
+        tokio::spawn(async move { self.internal_run().await })
+    }
+
     /// Getter of the number of processors' queues
     fn get_proc_queue_len(&self) -> usize {
         let mut proc_queue_len = 0;
@@ -301,10 +806,15 @@ where
     }
 
     async fn remove_proc(&mut self, proc_id: u32) -> Option<HashMap<u32, ProcService<M>>> {
+        self.heartbeats.retain(|(id, _), _| *id != proc_id);
+        self.withheld_services.retain(|(id, _), _| *id != proc_id);
+        if self.proc_states.remove(&proc_id).is_some() {
+            self.publish_proc_states();
+        }
         if let Some(proc) = self.processors.remove(&proc_id) {
-            let mut new_services = (*self.services).clone();
+            let new_services = Arc::make_mut(&mut self.services);
             new_services.remove_proc_services(proc_id);
-            self.services = Arc::new(new_services);
+            new_services.remove_proc_queues(proc_id);
             Some(proc)
         } else {
             None
@@ -312,14 +822,16 @@ where
     }
 
     async fn remove_proc_queue(&mut self, proc_id: u32, queue_id: u32) -> Option<ProcService<M>> {
+        self.heartbeats.remove(&(proc_id, queue_id));
+        self.withheld_services.remove(&(proc_id, queue_id));
         if let Some(proc_service) = self.processors.get_mut(&proc_id) {
             if let Some(proc_queue) = proc_service.remove(&queue_id) {
-                let mut new_services = (*self.services).clone();
+                let new_services = Arc::make_mut(&mut self.services);
                 new_services.remove_proc_queue_services(
                     proc_queue.get_proc_id(),
                     proc_queue.get_queue_id(),
                 );
-                self.services = Arc::new(new_services);
+                new_services.remove_proc_queue(proc_queue.get_proc_id(), proc_queue.get_queue_id());
                 Some(proc_queue)
             } else {
                 None
@@ -331,18 +843,26 @@ where
 
     /// Method to notify all processor that the service table have changed
     async fn notify_srv_proc_queue(&self) -> Result<(), BusError> {
+        self.main.services.send_replace(self.services.clone());
         for proc in self.processors.values() {
             for proc_service in proc.values() {
+                // Unresponsive queues will get the service table once they are restored
+                if self
+                    .heartbeats
+                    .get(&(proc_service.get_proc_id(), proc_service.get_queue_id()))
+                    .is_some_and(|state| state.evicted.is_some())
+                {
+                    continue;
+                }
+
                 if let Err(e) = proc_service
-                    .proc_queue
                     .send(InternalMsg::Service(self.services.clone()))
                     .await
                 {
-                    // FIXME match the error. If it's a capacity error, don't drop the processor do something else
-                    return Err(BusError::ProcCommError(
+                    return Err(BusError::ProcQueueError(
                         proc_service.get_proc_id(),
                         proc_service.get_queue_id(),
-                        e.to_string(),
+                        e.kind(),
                     ));
                 }
             }
@@ -353,13 +873,15 @@ where
 
     /// Method to notify all processor that the service table have changed
     async fn notify_srv_proc(&mut self) -> bool {
-        if let Err(BusError::ProcCommError(proc_id, queue_id, _)) =
+        if let Err(BusError::ProcQueueError(proc_id, queue_id, _)) =
             self.notify_srv_proc_queue().await
         {
             // The processor doesn't exist anymore so remove it
-            if queue_id > 0 {
+            if queue_id != PRIMARY_QUEUE_ID {
+                self.main.unregister_queues(proc_id, Some(queue_id));
                 self.remove_proc_queue(proc_id, queue_id).await;
             } else {
+                self.main.unregister_queues(proc_id, None);
                 self.remove_proc(proc_id).await;
             }
 
@@ -369,25 +891,390 @@ where
         }
     }
 
-    /// Method to shutdown all processors (return `true` if all processor are off, `false` otherwise)
-    async fn stop(&mut self) -> bool {
-        let mut is_stopped = true;
+    /// Getter of the audit log records (empty if the audit log is disabled)
+    fn audit_records(&self) -> Vec<AuditRecord> {
+        self.audit
+            .as_ref()
+            .map(|audit| audit.records())
+            .unwrap_or_default()
+    }
+
+    /// Method to build a snapshot of the processors and services
+    fn topology(&self) -> TopologySnapshot {
+        let mut processors = BTreeMap::new();
+        for (proc_id, queues) in &self.processors {
+            let mut queue_ids: Vec<u32> = queues.keys().cloned().collect();
+            queue_ids.sort_unstable();
+            processors.insert(*proc_id, queue_ids);
+        }
+
+        let services = self
+            .services
+            .iter()
+            .map(|(name, proc_services)| {
+                (
+                    name.clone(),
+                    proc_services
+                        .iter()
+                        .map(|s| (s.get_proc_id(), s.get_queue_id()))
+                        .collect(),
+                )
+            })
+            .collect();
+
+        TopologySnapshot {
+            name: self.main.name().clone(),
+            processors,
+            services,
+            states: self
+                .proc_states
+                .iter()
+                .map(|(proc_id, state)| (*proc_id, state.clone()))
+                .collect(),
+            tps: self.main.tps(),
+        }
+    }
+
+    /// Method to know if the services of a processor can be routed (always without `ready_routing`)
+    fn is_routable(&self, proc_id: u32) -> bool {
+        !self.ready_routing
+            || self
+                .proc_states
+                .get(&proc_id)
+                .is_some_and(ProcLifecycle::is_ready)
+    }
+
+    /// Method to add services of a processor queue to the service table, or to withhold them if the processor is not ready
+    fn add_queue_services(&mut self, names: &[String], proc_service: &ProcService<M>) {
+        if self.is_routable(proc_service.get_proc_id()) {
+            let new_services = Arc::make_mut(&mut self.services);
+            for name in names {
+                new_services.add_service(name, proc_service.clone());
+            }
+        } else {
+            let withheld = self
+                .withheld_services
+                .entry((proc_service.get_proc_id(), proc_service.get_queue_id()))
+                .or_default();
+            for name in names {
+                if !withheld.contains(name) {
+                    withheld.push(name.clone());
+                }
+            }
+        }
+    }
+
+    /// Method to publish the lifecycle states of the processors to their watchers (see [`Main::wait_proc_ready`])
+    fn publish_proc_states(&self) {
+        self.main.proc_states.send_replace(self.proc_states.clone());
+    }
+
+    /// Method to update the lifecycle state of a processor (return `true` if the service table changed)
+    fn set_proc_state(&mut self, proc_id: u32, state: ProcLifecycle) -> bool {
+        let Some(proc) = self.processors.get(&proc_id) else {
+            debug!(
+                "The state {} of the unregistered processor {} is ignored",
+                state, proc_id
+            );
+            return false;
+        };
+
+        let was_routable = self.is_routable(proc_id);
+        info!("The processor {} is {}", proc_id, state);
+        self.proc_states.insert(proc_id, state);
+        self.publish_proc_states();
+        match (was_routable, self.is_routable(proc_id)) {
+            (false, true) => {
+                // Route the withheld services of the processor
+                let new_services = Arc::make_mut(&mut self.services);
+                for (queue_id, proc_service) in proc {
+                    for name in self
+                        .withheld_services
+                        .remove(&(proc_id, *queue_id))
+                        .unwrap_or_default()
+                    {
+                        new_services.add_service(&name, proc_service.clone());
+                    }
+                }
+                true
+            }
+            (true, false) => {
+                // Withhold the services of the processor until it's ready again
+                let new_services = Arc::make_mut(&mut self.services);
+                for queue_id in proc.keys() {
+                    let names = new_services.take_proc_queue_services(proc_id, *queue_id);
+                    self.withheld_services
+                        .entry((proc_id, *queue_id))
+                        .or_default()
+                        .extend(names);
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Method to reload the configuration files and send it to all processors
+    async fn reload_config(&self) -> Result<(), String> {
+        let config_watch = self
+            .config_watch
+            .as_ref()
+            .ok_or("no configuration files to reload, `config_watch` is not set")?;
+        let config = config_watch.load_config().map_err(|e| e.to_string())?;
+        info!("ProSA configuration reloaded on request");
+        self.notify_config_proc(ReloadedConfig::new(config)).await;
+        Ok(())
+    }
+
+    /// Method to send the reloaded configuration to all processors
+    async fn notify_config_proc(&self, config: ReloadedConfig) {
         for proc in self.processors.values() {
             for proc_service in proc.values() {
-                if let Err(e) = proc_service.proc_queue.send(InternalMsg::Shutdown).await {
-                    debug!("The {:?} seems already stopped: {}", proc_service, e);
-                } else {
-                    is_stopped = false;
+                if let Err(e) = proc_service.send(InternalMsg::Config(config.clone())).await {
+                    debug!(
+                        "The {:?} can't reload its configuration: {}",
+                        proc_service, e
+                    );
+                }
+            }
+        }
+    }
+
+    /// Method to ping all processor queues, and evict the ones that missed too many pings (return `true` if the service table changed)
+    fn heartbeat(&mut self, miss_threshold: u32) -> bool {
+        self.heartbeat_seq += 1;
+        let mut unresponsive_queues = Vec::new();
+        for (proc_id, proc) in &self.processors {
+            for (queue_id, proc_service) in proc {
+                let state = self.heartbeats.entry((*proc_id, *queue_id)).or_default();
+                if state.pending.is_some() {
+                    state.missed += 1;
+                    if state.missed >= miss_threshold && state.evicted.is_none() {
+                        unresponsive_queues.push((*proc_id, *queue_id));
+                    }
                 }
+
+                // Never wait for a processor: a full queue is also a missed ping
+                let _ = proc_service.try_send(InternalMsg::Ping(self.heartbeat_seq));
+                state.pending = Some(self.heartbeat_seq);
             }
         }
 
-        is_stopped
+        if unresponsive_queues.is_empty() {
+            return false;
+        }
+
+        let new_services = Arc::make_mut(&mut self.services);
+        for (proc_id, queue_id) in unresponsive_queues {
+            let names = new_services.take_proc_queue_services(proc_id, queue_id);
+            warn!(
+                "The processor {}/{} is unresponsive, its services {:?} are evicted",
+                proc_id, queue_id, names
+            );
+            if let Some(audit) = &self.audit {
+                for name in &names {
+                    audit.record(
+                        AuditRecord::new(AuditKind::Evicted)
+                            .proc(proc_id, Some(queue_id))
+                            .service(name.as_str()),
+                    );
+                }
+            }
+            if let Some(state) = self.heartbeats.get_mut(&(proc_id, queue_id)) {
+                state.evicted = Some(names);
+            }
+        }
+
+        true
+    }
+
+    /// Method to handle a pong of a processor queue, and restore its services if it was evicted (return `true` if the service table changed)
+    fn pong(&mut self, proc_id: u32, queue_id: u32, seq: u64) -> bool {
+        let Some(state) = self.heartbeats.get_mut(&(proc_id, queue_id)) else {
+            return false;
+        };
+
+        if state.pending.is_some_and(|pending| pending <= seq) {
+            state.pending = None;
+        }
+        state.missed = 0;
+
+        if let (Some(names), Some(proc_service)) = (
+            state.evicted.take(),
+            self.processors
+                .get(&proc_id)
+                .and_then(|proc| proc.get(&queue_id)),
+        ) {
+            info!(
+                "The processor {}/{} is responsive again, its services {:?} are restored",
+                proc_id, queue_id, names
+            );
+            if let Some(audit) = &self.audit {
+                for name in &names {
+                    audit.record(
+                        AuditRecord::new(AuditKind::Restored)
+                            .proc(proc_id, Some(queue_id))
+                            .service(name.as_str()),
+                    );
+                }
+            }
+            let proc_service = proc_service.clone();
+            self.add_queue_services(&names, &proc_service);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Method to shutdown all processors rank by rank (lowest shutdown rank first)
+    ///
+    /// The processors of a rank are asked to stop, then the main task waits for their deregistration (bounded by the rank timeout) before stopping the next rank.
+    /// The whole sequence is bounded by the global shutdown timeout.
+    /// Return the id of the processors in their deregistration order
+    async fn stop(&mut self) -> Vec<u32> {
+        let deadline = time::Instant::now() + self.shutdown.get_timeout();
+
+        // Registrations are dropped during the shutdown, so the removals waiting for them are applied now
+        for removal in std::mem::take(&mut self.deferred_removals)
+            .into_values()
+            .flatten()
+        {
+            match removal {
+                InternalMainMsg::DeleteProc(proc_id) => {
+                    self.remove_proc(proc_id).await;
+                }
+                InternalMainMsg::DeleteProcQueue(proc_id, queue_id) => {
+                    self.remove_proc_queue(proc_id, queue_id).await;
+                    if self.processors.get(&proc_id).is_some_and(|p| p.is_empty()) {
+                        self.remove_proc(proc_id).await;
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let mut ranks: BTreeMap<u32, HashSet<u32>> = BTreeMap::new();
+        for (proc_id, proc) in &self.processors {
+            let rank = proc
+                .values()
+                .map(|p| p.get_shutdown_rank())
+                .min()
+                .unwrap_or_default();
+            ranks.entry(rank).or_default().insert(*proc_id);
+        }
+
+        let mut stopped_processors = Vec::with_capacity(self.processors.len());
+        for (rank, mut rank_processors) in ranks {
+            debug!("Stop the processors {:?} of rank {}", rank_processors, rank);
+            for proc_id in rank_processors.clone() {
+                let mut is_stopped = true;
+                for proc_service in self
+                    .processors
+                    .get(&proc_id)
+                    .into_iter()
+                    .flat_map(|p| p.values())
+                {
+                    if let Err(e) = proc_service.send(InternalMsg::Shutdown).await {
+                        debug!("The {:?} seems already stopped: {}", proc_service, e);
+                    } else {
+                        is_stopped = false;
+                    }
+                }
+
+                if is_stopped {
+                    self.remove_proc(proc_id).await;
+                    rank_processors.remove(&proc_id);
+                    stopped_processors.push(proc_id);
+                }
+            }
+
+            // Wait for the processors of the rank to deregister
+            let rank_deadline =
+                deadline.min(time::Instant::now() + self.shutdown.get_rank_timeout());
+            while !rank_processors.is_empty() {
+                let msg = tokio::select! {
+                    biased;
+                    Some(msg) = self.internal_ctrl_rx_queue.recv() => msg,
+                    Some(msg) = self.internal_rx_queue.recv() => msg,
+                    _ = time::sleep_until(rank_deadline) => {
+                        warn!("The processors {:?} of rank {} didn't stop in time", rank_processors, rank);
+                        break;
+                    },
+                    else => break,
+                };
+
+                if let Some(audit) = &self.audit {
+                    audit.record_msg(&msg);
+                }
+                let proc_id = match msg {
+                    InternalMainMsg::DeleteProc(proc_id) => {
+                        self.remove_proc(proc_id).await.map(|_| proc_id)
+                    }
+                    InternalMainMsg::DeleteProcQueue(proc_id, queue_id) => {
+                        self.remove_proc_queue(proc_id, queue_id).await;
+                        if self.processors.get(&proc_id).is_some_and(|p| p.is_empty()) {
+                            self.remove_proc(proc_id).await.map(|_| proc_id)
+                        } else {
+                            None
+                        }
+                    }
+                    InternalMainMsg::Topology(reply) => {
+                        let _ = reply.send(self.topology());
+                        None
+                    }
+                    InternalMainMsg::AuditDump(reply) => {
+                        let _ = reply.send(self.audit_records());
+                        None
+                    }
+                    // Other messages are dropped during the shutdown
+                    _ => None,
+                };
+
+                if let Some(proc_id) = proc_id {
+                    rank_processors.remove(&proc_id);
+                    stopped_processors.push(proc_id);
+                    self.notify_srv_proc().await;
+                }
+            }
+
+            if time::Instant::now() >= deadline {
+                warn!("The ProSA shutdown timeout is reached, remaining processors are not waited");
+                break;
+            }
+        }
+
+        stopped_processors
+    }
+
+    /// Method to shutdown all processors of a group, return the id of the processors that are stopping
+    async fn stop_group(&self, group: &str) -> Vec<u32> {
+        let mut stopping_processors = Vec::new();
+        for (proc_id, proc) in &self.processors {
+            if proc
+                .values()
+                .any(|p| p.get_group().is_some_and(|g| g == group))
+            {
+                let mut stopping = false;
+                for proc_service in proc.values() {
+                    if let Err(e) = proc_service.send(InternalMsg::Shutdown).await {
+                        debug!("The {:?} seems already stopped: {}", proc_service, e);
+                    } else {
+                        stopping = true;
+                    }
+                }
+
+                // A processor that can't receive the shutdown is already stopped (it may have panicked)
+                if stopping {
+                    stopping_processors.push(*proc_id);
+                }
+            }
+        }
+
+        stopping_processors
     }
 
     async fn internal_run(&mut self) -> Result<(), BusError> {
-        // Monitor RAM usage
-        let prosa_name = self.name.clone();
+        // Monitor RAM usage (the ProSA identity is given by the meter resource)
         self.meter
             .u64_observable_gauge("prosa_main_ram")
             .with_description("RAM consumed by ProSA")
@@ -396,17 +1283,11 @@ where
                 if let Some(usage) = memory_stats::memory_stats() {
                     observer.observe(
                         usage.physical_mem as u64,
-                        &[
-                            KeyValue::new("prosa_name", prosa_name.clone()),
-                            KeyValue::new("type", "physical"),
-                        ],
+                        &[KeyValue::new("type", "physical")],
                     );
                     observer.observe(
                         usage.virtual_mem as u64,
-                        &[
-                            KeyValue::new("prosa_name", prosa_name.clone()),
-                            KeyValue::new("type", "virtual"),
-                        ],
+                        &[KeyValue::new("type", "virtual")],
                     );
                 }
             })
@@ -425,24 +1306,45 @@ where
             .with_description("Processors declared to the main task")
             .init();
 
-        let prosa_name = self.name.clone();
+        // Watch configuration files if needed
+        let mut config_watcher = self.config_watch.clone().map(ConfigWatcher::new);
+
+        // Listen on the control socket if needed (removed when the main task stops)
+        #[cfg(unix)]
+        let _ctl_server = self.ctl_socket.as_ref().and_then(|path| {
+            CtlServer::bind(self.main.clone(), path)
+                .inspect_err(|e| warn!("Can't listen on the control socket {:?}: {}", path, e))
+                .ok()
+        });
+
+        // Ping processors if needed
+        let mut heartbeat_interval = self.heartbeat.as_ref().map(|heartbeat| {
+            let mut interval = time::interval(heartbeat.get_interval());
+            interval.set_missed_tick_behavior(time::MissedTickBehavior::Delay);
+            (interval, heartbeat.get_miss_threshold())
+        });
 
-        /// Macro to notify processors for a change about service list
+        // Close the buckets of the transactions window
+        let mut tps_interval = self.main.tps.as_ref().map(|tps| {
+            let mut interval = time::interval(tps.get_bucket());
+            interval.set_missed_tick_behavior(time::MissedTickBehavior::Delay);
+            (interval, tps.clone())
+        });
+
+        // Instant of the next service table notification, if the table changed
+        let mut service_update: Option<time::Instant> = None;
+
+        /// Macro to notify processors for a change about service list (batched with the next changes of the window)
         macro_rules! prosa_main_update_srv {
             ( ) => {
-                if !self.notify_srv_proc().await {
-                    self.notify_srv_proc().await;
-                }
+                service_update.get_or_insert_with(|| time::Instant::now() + SERVICE_UPDATE_WINDOW);
             };
         }
 
         /// Macro to record a change to the services
         macro_rules! prosa_main_record_services {
             ( ) => {
-                services_meter.record(
-                    self.services.len() as u64,
-                    &[KeyValue::new("prosa_name", prosa_name.clone())],
-                );
+                services_meter.record(self.services.len() as u64, &[]);
             };
         }
 
@@ -451,125 +1353,268 @@ where
             ( ) => {
                 processors_meter.record(
                     self.processors.len() as u64,
-                    &[
-                        KeyValue::new("prosa_name", prosa_name.clone()),
-                        KeyValue::new("type", "tasks"),
-                    ],
+                    &[KeyValue::new("type", "tasks")],
                 );
                 processors_meter.record(
                     self.get_proc_queue_len() as u64,
-                    &[
-                        KeyValue::new("prosa_name", prosa_name.clone()),
-                        KeyValue::new("type", "queues"),
-                    ],
+                    &[KeyValue::new("type", "queues")],
                 );
             };
         }
 
+        // Removals released once the registrations of their processor are handled
+        let mut released_removals: VecDeque<InternalMainMsg<M>> = VecDeque::new();
+
         loop {
-            tokio::select! {
-                Some(msg) = self.internal_rx_queue.recv() => {
-                    match msg {
-                        InternalMainMsg::NewProcQueue(proc) => {
-                            let proc_id = proc.get_proc_id();
-                            let queue_id = proc.get_queue_id();
-                            let proc_queue = proc.proc_queue.clone();
-                            if let Some(proc_service) = self.processors.get_mut(&proc_id) {
-                                proc_service.insert(queue_id, proc);
-                            } else {
-                                self.processors.insert(proc_id, HashMap::from([
-                                    (queue_id, proc),
-                                ]));
-                            }
-
-                            // Ask to the processor to load the service table
-                            if proc_queue.send(InternalMsg::Service(self.services.clone())).await.is_err() {
-                                if let Some(proc_service) = self.processors.get_mut(&proc_id) {
-                                    let _ = proc_service.remove(&queue_id);
-                                } else {
-                                    let _ = self.processors.remove(&proc_id);
-                                }
-                            }
-
-                            prosa_main_record_proc!();
-                        },
-                        InternalMainMsg::DeleteProc(proc_id) => {
-                            if self.remove_proc(proc_id).await.is_some() {
-                                prosa_main_update_srv!();
-                            }
+            // Control messages are always handled before data messages
+            let msg = if let Some(removal) = released_removals.pop_front() {
+                removal
+            } else {
+                tokio::select! {
+                biased;
+                Some(msg) = self.internal_ctrl_rx_queue.recv() => msg,
+                _ = time::sleep_until(service_update.unwrap_or_else(time::Instant::now)), if service_update.is_some() => {
+                    service_update = None;
+                    if !self.notify_srv_proc().await {
+                        self.notify_srv_proc().await;
+                    }
 
-                            prosa_main_record_proc!();
+                    continue;
+                },
+                Some(msg) = self.internal_rx_queue.recv() => msg,
+                Some(config) = async {
+                    match config_watcher.as_mut() {
+                        Some(watcher) => Some(watcher.changed().await),
+                        None => None,
+                    }
+                } => {
+                    match config {
+                        Ok(config) => {
+                            info!("ProSA configuration reloaded");
+                            self.notify_config_proc(ReloadedConfig::new(config)).await;
                         },
-                        InternalMainMsg::DeleteProcQueue(proc_id, queue_id) => {
-                            if self.remove_proc_queue(proc_id, queue_id).await.is_some() {
-                                prosa_main_update_srv!();
-                            }
+                        Err(e) => warn!("Can't reload the ProSA configuration: {}", e),
+                    }
 
-                            prosa_main_record_proc!();
-                        },
-                        InternalMainMsg::NewProcService(names, proc_id) => {
-                            if let Some(proc_service) = self.processors.get(&proc_id) {
-                                let mut new_services = (*self.services).clone();
-                                for proc_queue in proc_service.values() {
-                                    for name in &names {
-                                        new_services.add_service(name, proc_queue.clone());
-                                    }
-                                }
-                                self.services = Arc::new(new_services);
-                                prosa_main_record_services!();
-                                prosa_main_update_srv!();
-                            }
-                        },
-                        InternalMainMsg::NewService(names, proc_id, queue_id) => {
-                            if let Some(proc) = self.processors.get(&proc_id) {
-                                if let Some(proc_queue) = proc.get(&queue_id) {
-                                    let mut new_services = (*self.services).clone();
-                                    for name in names {
-                                        new_services.add_service(&name, proc_queue.clone());
-                                    }
-                                    self.services = Arc::new(new_services);
-                                    prosa_main_record_services!();
-                                    prosa_main_update_srv!();
-                                }
-                            }
-                        },
-                        InternalMainMsg::DeleteProcService(names, proc_id) => {
-                            let mut new_services = (*self.services).clone();
-                            for name in names {
-                                new_services.remove_service_proc(&name, proc_id);
-                            }
-                            self.services = Arc::new(new_services);
-                            prosa_main_record_services!();
-                            prosa_main_update_srv!();
-                        },
-                        InternalMainMsg::DeleteService(names, proc_id, queue_id) => {
-                            let mut new_services = (*self.services).clone();
-                            for name in names {
-                                new_services.remove_service(&name, proc_id, queue_id);
-                            }
-                            self.services = Arc::new(new_services);
-                            prosa_main_record_services!();
-                            prosa_main_update_srv!();
-                        },
-                        InternalMainMsg::Command(cmd)=> {
-                            info!("Wan't to execute the command {}", cmd);
+                    continue;
+                },
+                Some(miss_threshold) = async {
+                    match heartbeat_interval.as_mut() {
+                        Some((interval, miss_threshold)) => {
+                            interval.tick().await;
+                            Some(*miss_threshold)
                         },
-                        InternalMainMsg::Shutdown(reason) => {
-                            warn!("ProSA need to stop: {}", reason);
-                            self.stop().await;
+                        None => None,
+                    }
+                } => {
+                    if self.heartbeat(miss_threshold) {
+                        prosa_main_record_services!();
+                        prosa_main_update_srv!();
+                    }
 
-                            // The shutdown mecanism will be implemented later
-                            return Ok(())
+                    continue;
+                },
+                Some(tps) = async {
+                    match tps_interval.as_mut() {
+                        Some((interval, tps)) => {
+                            interval.tick().await;
+                            Some(tps)
                         },
+                        None => None,
                     }
+                } => {
+                    tps.tick();
+                    continue;
                 },
                 _ = signal::ctrl_c() => {
                     warn!("ProSA need to stop");
-                    self.stop().await;
-
-                    // The shutdown mecanism will be implemented later
+                    if let Some(audit) = &self.audit {
+                        audit.record(AuditRecord::new(AuditKind::Shutdown).detail("signal"));
+                    }
+                    let stopped_processors = self.stop().await;
+                    info!("ProSA stopped (processors {:?} deregistered)", stopped_processors);
                     return Ok(())
                 },
+                }
+            };
+
+            // A removal never overtakes the registrations of its processor still in the data lane
+            if let InternalMainMsg::DeleteProc(proc_id)
+            | InternalMainMsg::DeleteProcQueue(proc_id, _) = msg
+            {
+                if self.main.has_pending_registrations(proc_id) {
+                    self.deferred_removals.entry(proc_id).or_default().push(msg);
+                    continue;
+                }
+            }
+
+            if let Some(audit) = &self.audit {
+                audit.record_msg(&msg);
+            }
+            if let Some(proc_id) = msg.get_registration_proc_id() {
+                if !self.main.registration_handled(proc_id) {
+                    if let Some(removals) = self.deferred_removals.remove(&proc_id) {
+                        released_removals.extend(removals);
+                    }
+                }
+            }
+            match msg {
+                InternalMainMsg::NewProcQueue(proc) => {
+                    let proc_id = proc.get_proc_id();
+                    let queue_id = proc.get_queue_id();
+                    let proc_queue = proc.clone();
+                    Arc::make_mut(&mut self.services).add_proc_queue(proc.clone());
+                    if let Some(proc_service) = self.processors.get_mut(&proc_id) {
+                        if let Some(registered) = proc_service.get(&queue_id) {
+                            warn!(
+                                "The processor queue {}/{} is registered again: {} replaced by {}",
+                                proc_id, queue_id, registered, proc
+                            );
+                        }
+                        proc_service.insert(queue_id, proc);
+                    } else {
+                        self.processors
+                            .insert(proc_id, HashMap::from([(queue_id, proc)]));
+                    }
+                    self.proc_states.entry(proc_id).or_default();
+
+                    // Ask to the processor to load the service table
+                    if proc_queue
+                        .send(InternalMsg::Service(self.services.clone()))
+                        .await
+                        .is_err()
+                    {
+                        self.main.unregister_queues(proc_id, Some(queue_id));
+                        Arc::make_mut(&mut self.services).remove_proc_queue(proc_id, queue_id);
+                        if let Some(proc_service) = self.processors.get_mut(&proc_id) {
+                            let _ = proc_service.remove(&queue_id);
+                        } else {
+                            let _ = self.processors.remove(&proc_id);
+                        }
+                    }
+
+                    prosa_main_record_proc!();
+                }
+                InternalMainMsg::DeleteProc(proc_id) => {
+                    if self.remove_proc(proc_id).await.is_some() {
+                        prosa_main_update_srv!();
+                    }
+
+                    // Run the processor again if it's restarting
+                    if self.restarting_processors.remove(&proc_id) {
+                        self.main.rerun_proc(proc_id);
+                    }
+
+                    prosa_main_record_proc!();
+                }
+                InternalMainMsg::DeleteProcQueue(proc_id, queue_id) => {
+                    if self.remove_proc_queue(proc_id, queue_id).await.is_some() {
+                        prosa_main_update_srv!();
+                    }
+
+                    prosa_main_record_proc!();
+                }
+                InternalMainMsg::ProcState(proc_id, state) => {
+                    if self.set_proc_state(proc_id, state) {
+                        prosa_main_record_services!();
+                        prosa_main_update_srv!();
+                    }
+                }
+                InternalMainMsg::NewProcService(names, proc_id) => {
+                    if let Some(proc_service) = self.processors.get(&proc_id) {
+                        for proc_queue in proc_service.values().cloned().collect::<Vec<_>>() {
+                            self.add_queue_services(&names, &proc_queue);
+                        }
+                        prosa_main_record_services!();
+                        prosa_main_update_srv!();
+                    }
+                }
+                InternalMainMsg::NewService(names, proc_id, queue_id) => {
+                    if let Some(proc_queue) = self
+                        .processors
+                        .get(&proc_id)
+                        .and_then(|proc| proc.get(&queue_id))
+                        .cloned()
+                    {
+                        self.add_queue_services(&names, &proc_queue);
+                        prosa_main_record_services!();
+                        prosa_main_update_srv!();
+                    }
+                }
+                InternalMainMsg::DeleteProcService(names, proc_id) => {
+                    for ((id, _), withheld) in self.withheld_services.iter_mut() {
+                        if *id == proc_id {
+                            withheld.retain(|name| !names.contains(name));
+                        }
+                    }
+                    let new_services = Arc::make_mut(&mut self.services);
+                    for name in names {
+                        new_services.remove_service_proc(&name, proc_id);
+                    }
+                    prosa_main_record_services!();
+                    prosa_main_update_srv!();
+                }
+                InternalMainMsg::DeleteService(names, proc_id, queue_id) => {
+                    if let Some(withheld) = self.withheld_services.get_mut(&(proc_id, queue_id)) {
+                        withheld.retain(|name| !names.contains(name));
+                    }
+                    let new_services = Arc::make_mut(&mut self.services);
+                    for name in names {
+                        new_services.remove_service(&name, proc_id, queue_id);
+                    }
+                    prosa_main_record_services!();
+                    prosa_main_update_srv!();
+                }
+                InternalMainMsg::Command(cmd) => {
+                    info!("Wan't to execute the command {}", cmd);
+                }
+                InternalMainMsg::Topology(reply) => {
+                    let _ = reply.send(self.topology());
+                }
+                InternalMainMsg::AuditDump(reply) => {
+                    let _ = reply.send(self.audit_records());
+                }
+                InternalMainMsg::ReloadConfig(reply) => {
+                    let result = self.reload_config().await;
+                    if let Err(e) = &result {
+                        warn!("Can't reload the ProSA configuration: {}", e);
+                    }
+                    let _ = reply.send(result);
+                }
+                InternalMainMsg::StopGroup(group) => {
+                    warn!("ProSA processors group {} need to stop", group);
+                    self.stop_group(&group).await;
+                }
+                InternalMainMsg::RestartGroup(group) => {
+                    info!("ProSA processors group {} need to restart", group);
+                    let stopping_processors = self.stop_group(&group).await;
+
+                    // Run directly processors of the group that are already stopped
+                    for proc_id in self.main.get_group_factories(&group) {
+                        if !stopping_processors.contains(&proc_id)
+                            && !self.restarting_processors.contains(&proc_id)
+                        {
+                            self.main.rerun_proc(proc_id);
+                        }
+                    }
+
+                    self.restarting_processors.extend(stopping_processors);
+                }
+                InternalMainMsg::Pong(proc_id, queue_id, seq) => {
+                    if self.pong(proc_id, queue_id, seq) {
+                        prosa_main_record_services!();
+                        prosa_main_update_srv!();
+                    }
+                }
+                InternalMainMsg::Shutdown(reason) => {
+                    warn!("ProSA need to stop: {}", reason);
+                    let stopped_processors = self.stop().await;
+                    info!(
+                        "ProSA stopped (processors {:?} deregistered)",
+                        stopped_processors
+                    );
+                    return Ok(());
+                }
             }
         }
     }
@@ -582,21 +1627,49 @@ impl<M> MainRunnable<M> for MainProc<M>
 where
     M: Sized + Clone + Debug + Tvf + Default + 'static + std::marker::Send + std::marker::Sync,
 {
+    /// # Panics
+    ///
+    /// Panics if the main task can't be created from the settings, use [`MainRunnable::try_create`] to handle the error
     fn create<S: Settings>(settings: &S) -> (Main<M>, MainProc<M>) {
+        Self::try_create(settings)
+            .unwrap_or_else(|e| panic!("Can't create the ProSA main task: {}", e))
+    }
+
+    fn try_create<S: Settings>(settings: &S) -> Result<(Main<M>, MainProc<M>), StateError> {
         let (internal_tx_queue, internal_rx_queue) = mpsc::channel(2048);
-        let main = Main::new(internal_tx_queue, settings);
-        let name = main.name().clone();
+        let (internal_ctrl_tx_queue, internal_ctrl_rx_queue) = mpsc::channel(64);
+        let main = Main::new(internal_tx_queue, internal_ctrl_tx_queue, settings)?;
         let meter = main.meter("prosa_main_task_meter");
-        (
-            main,
+        let audit = settings
+            .get_audit()
+            .map(|audit_settings| Arc::new(AuditLog::new(audit_settings)));
+        let mut services = ServiceTable::default();
+        if let Some(audit) = audit.as_ref().filter(|a| a.is_recording_resolutions()) {
+            services.set_audit(audit.clone());
+        }
+        Ok((
+            main.clone(),
             MainProc {
-                name,
+                main,
                 processors: Default::default(),
-                services: Arc::new(ServiceTable::default()),
+                restarting_processors: Default::default(),
+                services: Arc::new(services),
                 internal_rx_queue,
+                internal_ctrl_rx_queue,
+                deferred_removals: Default::default(),
+                config_watch: settings.get_config_watch().cloned(),
+                ctl_socket: settings.get_ctl_socket().cloned(),
+                heartbeat: settings.get_heartbeat().cloned(),
+                heartbeat_seq: 0,
+                heartbeats: Default::default(),
+                proc_states: Default::default(),
+                ready_routing: settings.get_ready_routing(),
+                withheld_services: Default::default(),
+                shutdown: settings.get_shutdown().cloned().unwrap_or_default(),
+                audit,
                 meter,
             },
-        )
+        ))
     }
 
     fn run(mut self) -> std::thread::JoinHandle<()> {
@@ -613,3 +1686,812 @@ where
             .unwrap()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    extern crate self as prosa;
+
+    use std::{
+        error::Error,
+        sync::atomic::{AtomicUsize, Ordering},
+        time::Duration,
+    };
+
+    use prosa_macros::{proc, settings, Adaptor};
+    use prosa_utils::msg::simple_string_tvf::SimpleStringTvf;
+    use serde::Serialize;
+
+    use crate::core::{
+        audit::AuditSettings,
+        error::QueueErrorKind,
+        msg::{InternalMsg, Msg as _, RequestMsg},
+        proc::{ProcConfig as _, ProcParam, ProcRxQueue},
+        service::{ServiceError, ServiceTable},
+    };
+    use crate::inj::{
+        adaptor::InjDummyAdaptor,
+        proc::{InjProc, InjSettings},
+    };
+    use crate::stub::{
+        adaptor::{StubAdaptor, StubParotAdaptor},
+        proc::{StubProc, StubSettings},
+    };
+
+    use super::*;
+
+    const WAIT_TIMEOUT: Duration = Duration::from_secs(5);
+    static ADAPTOR_INSTANCES: AtomicUsize = AtomicUsize::new(0);
+
+    /// Dummy settings
+    #[settings]
+    #[derive(Default, Debug, Serialize)]
+    struct TestSettings {}
+
+    /// Adaptor that count its instances
+    #[derive(Adaptor)]
+    struct TestCountAdaptor {}
+
+    impl StubAdaptor<SimpleStringTvf> for TestCountAdaptor {
+        fn new(_proc: &StubProc<SimpleStringTvf>) -> Result<Self, Box<dyn Error>> {
+            ADAPTOR_INSTANCES.fetch_add(1, Ordering::Relaxed);
+            Ok(Self {})
+        }
+
+        fn process_request(
+            &mut self,
+            _service_name: &str,
+            request: &SimpleStringTvf,
+        ) -> Result<SimpleStringTvf, ServiceError> {
+            Ok(request.clone())
+        }
+    }
+
+    #[proc]
+    struct TestClientProc {}
+
+    #[proc]
+    impl TestClientProc<SimpleStringTvf> {
+        /// Wait until the service table match the condition
+        async fn wait_services<F>(&mut self, condition: F)
+        where
+            F: Fn(&ServiceTable<SimpleStringTvf>) -> bool,
+        {
+            tokio::time::timeout(WAIT_TIMEOUT, async {
+                while !condition(&self.service) {
+                    if let Some(InternalMsg::Service(table)) = self.internal_rx_queue.recv().await {
+                        self.service = table;
+                    }
+                }
+            })
+            .await
+            .expect("Timeout waiting for the service table");
+        }
+
+        /// Send a request to the service and wait for its response
+        async fn request(&mut self, service_name: &str) -> bool {
+            let service_name = service_name.to_string();
+            self.service
+                .get_proc_service(&service_name, 0)
+                .unwrap()
+                .proc_queue
+                .send(InternalMsg::Request(RequestMsg::new(
+                    0,
+                    service_name.clone(),
+                    SimpleStringTvf::default(),
+                    self.proc.get_service_queue(),
+                )))
+                .await
+                .unwrap();
+
+            loop {
+                match self.internal_rx_queue.recv().await {
+                    Some(InternalMsg::Response(resp)) => {
+                        return resp.get_service() == &service_name;
+                    }
+                    Some(InternalMsg::Error(_)) => return false,
+                    Some(InternalMsg::Service(table)) => self.service = table,
+                    msg => panic!("Unexpected message {:?}", msg),
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn proc_groups() {
+        let (bus, main) = MainProc::<SimpleStringTvf>::create(&TestSettings::default());
+        let main_task = main.run();
+
+        bus.run_proc::<StubProc<SimpleStringTvf>, TestCountAdaptor>(
+            1,
+            StubSettings::new(vec![String::from("SRV_A")]),
+            Some(String::from("A")),
+            String::from("STUB_A"),
+        );
+        bus.run_proc::<StubProc<SimpleStringTvf>, TestCountAdaptor>(
+            2,
+            StubSettings::new(vec![String::from("SRV_B")]),
+            Some(String::from("B")),
+            String::from("STUB_B"),
+        );
+
+        let srv_a = String::from("SRV_A");
+        let srv_b = String::from("SRV_B");
+        let mut client = TestClientProc::<SimpleStringTvf>::create_raw(3, bus.clone());
+        client.proc.add_proc().await.unwrap();
+        client
+            .wait_services(|s| s.exist_proc_service(&srv_a) && s.exist_proc_service(&srv_b))
+            .await;
+        assert_eq!(2, ADAPTOR_INSTANCES.load(Ordering::Relaxed));
+
+        // Stopping the group A must keep the group B serving
+        bus.stop_group(String::from("A")).await.unwrap();
+        client
+            .wait_services(|s| !s.exist_proc_service(&srv_a) && s.exist_proc_service(&srv_b))
+            .await;
+        assert!(client.request("SRV_B").await);
+
+        // Restart the stopped group A
+        bus.restart_group(String::from("A")).await.unwrap();
+        client.wait_services(|s| s.exist_proc_service(&srv_a)).await;
+        assert_eq!(3, ADAPTOR_INSTANCES.load(Ordering::Relaxed));
+        assert!(client.request("SRV_A").await);
+
+        // Restart the running group B (its removal can be batched with its registration in the service table)
+        bus.restart_group(String::from("B")).await.unwrap();
+        tokio::time::timeout(WAIT_TIMEOUT, async {
+            while ADAPTOR_INSTANCES.load(Ordering::Relaxed) < 4 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("Timeout waiting for the group B restart");
+        client
+            .wait_services(|s| {
+                s.get_proc_service(&srv_b, 0)
+                    .is_some_and(|p| !p.proc_queue.is_closed())
+            })
+            .await;
+        assert_eq!(4, ADAPTOR_INSTANCES.load(Ordering::Relaxed));
+        assert!(client.request("SRV_B").await);
+
+        client.proc.remove_proc().await.unwrap();
+        bus.stop("ProSA unit test end".into()).await.unwrap();
+        main_task.join().unwrap();
+    }
+
+    #[tokio::test]
+    async fn main_shutdown_ranks() {
+        let settings = TestSettings {
+            shutdown: Some(Shutdown::new(WAIT_TIMEOUT, Duration::from_millis(200))),
+            ..Default::default()
+        };
+        let (bus, mut main) = MainProc::<SimpleStringTvf>::create(&settings);
+        for (proc_id, shutdown_rank) in [(1, 2), (2, 0), (3, 1)] {
+            let stub_settings: StubSettings = serde_json::from_value(serde_json::json!({
+                "service_names": [format!("SRV_{}", proc_id)],
+                "shutdown_rank": shutdown_rank,
+            }))
+            .unwrap();
+            bus.run_proc::<StubProc<SimpleStringTvf>, StubParotAdaptor>(
+                proc_id,
+                stub_settings,
+                None,
+                format!("STUB_{}", proc_id),
+            );
+        }
+
+        // Processor of rank 0 that never handles its shutdown, the rank timeout must not block the sequence
+        let client = TestClientProc::<SimpleStringTvf>::create_raw(4, bus.clone());
+        client.proc.add_proc().await.unwrap();
+
+        tokio::select! {
+            _ = main.internal_run() => panic!("The main task must not stop"),
+            _ = async {
+                while bus.topology().await.unwrap().processors.len() < 4 {
+                    time::sleep(Duration::from_millis(10)).await;
+                }
+            } => {},
+            _ = time::sleep(WAIT_TIMEOUT) => panic!("Timeout waiting for the processors registration"),
+        }
+
+        let start = time::Instant::now();
+        assert_eq!(vec![2, 3, 1], main.stop().await);
+        assert!(start.elapsed() >= Duration::from_millis(200));
+        assert!(start.elapsed() < WAIT_TIMEOUT);
+    }
+
+    #[tokio::test]
+    async fn main_heartbeat() {
+        let settings = TestSettings {
+            heartbeat: Some(Heartbeat::new(Duration::from_millis(20), 3)),
+            // The unresponsive processor never deregisters
+            shutdown: Some(Shutdown::new(WAIT_TIMEOUT, Duration::from_millis(100))),
+            ..Default::default()
+        };
+        let (bus, main) = MainProc::<SimpleStringTvf>::create(&settings);
+        let main_task = main.run();
+
+        bus.run_proc::<StubProc<SimpleStringTvf>, StubParotAdaptor>(
+            1,
+            StubSettings::new(vec![String::from("SRV_ALIVE")]),
+            None,
+            String::from("STUB_ALIVE"),
+        );
+
+        // Processor that doesn't read its queue
+        let mut stuck = TestClientProc::<SimpleStringTvf>::create_raw(2, bus.clone());
+        stuck.proc.add_proc().await.unwrap();
+        stuck
+            .proc
+            .add_service_proc(vec![String::from("SRV_STUCK")])
+            .await
+            .unwrap();
+
+        let srv_alive = String::from("SRV_ALIVE");
+        let srv_stuck = String::from("SRV_STUCK");
+        let mut client = TestClientProc::<SimpleStringTvf>::create_raw(3, bus.clone());
+        client.proc.add_proc().await.unwrap();
+
+        // Additional queue of the client, that answer the heartbeats with its own queue id
+        let (queue, rx_queue) = mpsc::channel(8);
+        let queue_id = client.proc.next_queue_id();
+        client.proc.add_proc_queue(queue, queue_id).await.unwrap();
+        client
+            .proc
+            .add_service(vec![String::from("SRV_QUEUE")], queue_id)
+            .await
+            .unwrap();
+        let mut rx_queue = ProcRxQueue::new_proc_queue(rx_queue, &client.proc, queue_id);
+        let queue_task = tokio::spawn(async move {
+            while let Some(msg) = rx_queue.recv().await {
+                assert!(!matches!(msg, InternalMsg::Ping(_)));
+            }
+        });
+
+        client
+            .wait_services(|s| s.exist_proc_service(&srv_alive) && s.exist_proc_service(&srv_stuck))
+            .await;
+
+        // The stuck processor is evicted, other processors stay available
+        client
+            .wait_services(|s| !s.exist_proc_service(&srv_stuck))
+            .await;
+        assert!(client.request("SRV_ALIVE").await);
+        assert!(bus.get_service_table().exist_proc_service("SRV_QUEUE"));
+        assert!(!queue_task.is_finished());
+        queue_task.abort();
+
+        // The processor read its queue again, so its services are restored
+        let stuck_task = tokio::spawn(async move {
+            while let Some(msg) = stuck.internal_rx_queue.recv().await {
+                if let InternalMsg::Shutdown = msg {
+                    break;
+                }
+            }
+        });
+        client
+            .wait_services(|s| s.exist_proc_service(&srv_stuck))
+            .await;
+        assert!(client.request("SRV_ALIVE").await);
+
+        client.proc.remove_proc().await.unwrap();
+        bus.stop("ProSA unit test end".into()).await.unwrap();
+        main_task.join().unwrap();
+        stuck_task.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn main_bus_error() {
+        let (bus, main) = MainProc::<SimpleStringTvf>::create(&TestSettings::default());
+        drop(main);
+
+        // The kind of the queue error is kept with the message context
+        assert_eq!(
+            Err(BusError::InternalMainQueueError(
+                String::from("Shutdown"),
+                0,
+                QueueErrorKind::Closed
+            )),
+            bus.stop("ProSA unit test end".into()).await
+        );
+        let err = bus.remove_proc(1).await.unwrap_err();
+        assert_eq!(Some(QueueErrorKind::Closed), err.kind());
+        assert_eq!(
+            QueueErrorKind::Closed,
+            bus.send(InternalMainMsg::DeleteProc(1))
+                .await
+                .unwrap_err()
+                .kind()
+        );
+    }
+
+    #[tokio::test]
+    async fn main_duplicate_queue() {
+        let (bus, main) = MainProc::<SimpleStringTvf>::create(&TestSettings::default());
+        let main_task = main.run();
+
+        let (proc_tx_queue, _proc_rx_queue) = mpsc::channel(8);
+        let proc_param = ProcParam::new(1, proc_tx_queue, bus.clone());
+        proc_param.add_proc().await.unwrap();
+        assert_eq!(
+            Err(BusError::DuplicateQueue(1, PRIMARY_QUEUE_ID)),
+            proc_param.add_proc().await
+        );
+
+        // The primary queue id is reserved
+        let (queue, _rx_queue) = mpsc::channel(8);
+        assert_eq!(
+            Err(BusError::DuplicateQueue(1, PRIMARY_QUEUE_ID)),
+            proc_param
+                .add_proc_queue(queue.clone(), PRIMARY_QUEUE_ID)
+                .await
+        );
+
+        let queue_id = proc_param.next_queue_id();
+        assert_eq!(1, queue_id);
+        proc_param
+            .add_proc_queue(queue.clone(), queue_id)
+            .await
+            .unwrap();
+        assert!(bus.is_queue_registered(1, queue_id));
+        assert_eq!(
+            Err(BusError::DuplicateQueue(1, queue_id)),
+            proc_param.add_proc_queue(queue.clone(), queue_id).await
+        );
+
+        // Allocated ids skip the manually registered ones
+        proc_param.add_proc_queue(queue.clone(), 3).await.unwrap();
+        assert_eq!(2, proc_param.next_queue_id());
+        assert_eq!(4, proc_param.next_queue_id());
+
+        // A removed queue can be registered again
+        proc_param.remove_proc_queue(queue_id).await.unwrap();
+        assert!(!bus.is_queue_registered(1, queue_id));
+        proc_param.add_proc_queue(queue, queue_id).await.unwrap();
+
+        proc_param.remove_proc().await.unwrap();
+        assert!(!bus.is_queue_registered(1, PRIMARY_QUEUE_ID));
+
+        // The queues of a panicked processor can be registered again
+        proc_param.add_proc().await.unwrap();
+        let panic_guard = proc_param.panic_guard();
+        assert!(std::thread::spawn(move || {
+            let _panic_guard = panic_guard;
+            panic!("processor panic");
+        })
+        .join()
+        .is_err());
+        assert!(!bus.is_queue_registered(1, PRIMARY_QUEUE_ID));
+        proc_param.add_proc().await.unwrap();
+
+        // Without a panic, the guard keep the registration
+        drop(proc_param.panic_guard());
+        assert!(bus.is_queue_registered(1, PRIMARY_QUEUE_ID));
+
+        proc_param.remove_proc().await.unwrap();
+        bus.stop("ProSA unit test end".into()).await.unwrap();
+        main_task.join().unwrap();
+    }
+
+    #[tokio::test]
+    async fn main_response_routing() {
+        /// Wait for the next response of a processor queue, and return its id
+        async fn recv_response(queue: &mut mpsc::Receiver<InternalMsg<SimpleStringTvf>>) -> u64 {
+            time::timeout(WAIT_TIMEOUT, async {
+                loop {
+                    match queue.recv().await {
+                        Some(InternalMsg::Response(response)) => return response.get_id(),
+                        Some(_) => continue,
+                        None => panic!("The processor queue is closed"),
+                    }
+                }
+            })
+            .await
+            .expect("Timeout waiting for the response")
+        }
+
+        let (bus, main) = MainProc::<SimpleStringTvf>::create(&TestSettings::default());
+        let main_task = main.run();
+        bus.run_proc::<StubProc<SimpleStringTvf>, StubParotAdaptor>(
+            1,
+            StubSettings::new(vec![String::from("ROUTE_SRV")]),
+            None,
+            String::from("STUB_PROC"),
+        );
+
+        // Processor with two queues
+        let (proc_tx_queue, mut proc_rx_queue) = mpsc::channel(64);
+        let proc_param = ProcParam::new(2, proc_tx_queue, bus.clone());
+        proc_param.add_proc().await.unwrap();
+        let (queue, mut rx_queue) = mpsc::channel(64);
+        let queue_id = proc_param.next_queue_id();
+        proc_param
+            .add_proc_queue(queue.clone(), queue_id)
+            .await
+            .unwrap();
+
+        // Wait for the stub service on the second queue
+        let table = time::timeout(WAIT_TIMEOUT, async {
+            loop {
+                if let Some(InternalMsg::Service(table)) = rx_queue.recv().await {
+                    if table.exist_proc_service("ROUTE_SRV") {
+                        return table;
+                    }
+                }
+            }
+        })
+        .await
+        .expect("Timeout waiting for the stub service");
+        assert!(table.get_proc_queue(2, queue_id).is_some());
+
+        // Send a request from each queue, the responses must come back to their queue
+        let request =
+            proc_param.new_request(1, String::from("ROUTE_SRV"), SimpleStringTvf::default());
+        assert_eq!(Some((2, PRIMARY_QUEUE_ID)), request.get_origin());
+        let send_request = |request: RequestMsg<SimpleStringTvf>| {
+            let proc_service = table.get_proc_service("ROUTE_SRV", 0).unwrap().clone();
+            async move { proc_service.send_request(request).await.unwrap() }
+        };
+        send_request(request).await;
+        send_request(
+            RequestMsg::new(
+                2,
+                String::from("ROUTE_SRV"),
+                SimpleStringTvf::default(),
+                queue.clone(),
+            )
+            .with_origin(2, queue_id),
+        )
+        .await;
+        assert_eq!(1, recv_response(&mut proc_rx_queue).await);
+        assert_eq!(2, recv_response(&mut rx_queue).await);
+
+        // If the response queue is closed, the response is delivered to the originating queue
+        let (closed_queue, _) = mpsc::channel(1);
+        send_request(
+            RequestMsg::new(
+                3,
+                String::from("ROUTE_SRV"),
+                SimpleStringTvf::default(),
+                closed_queue,
+            )
+            .with_origin(2, queue_id),
+        )
+        .await;
+        assert_eq!(3, recv_response(&mut rx_queue).await);
+
+        // If the originating queue disappeared, the response is delivered to another queue of the processor
+        drop(rx_queue);
+        send_request(
+            RequestMsg::new(
+                4,
+                String::from("ROUTE_SRV"),
+                SimpleStringTvf::default(),
+                queue,
+            )
+            .with_origin(2, queue_id),
+        )
+        .await;
+        assert_eq!(4, recv_response(&mut proc_rx_queue).await);
+
+        proc_param.remove_proc().await.unwrap();
+        bus.stop("ProSA unit test end".into()).await.unwrap();
+        main_task.join().unwrap();
+    }
+
+    #[tokio::test]
+    async fn main_audit() {
+        let settings = TestSettings {
+            audit: Some(AuditSettings {
+                capacity: 4096,
+                resolutions: true,
+            }),
+            ..Default::default()
+        };
+        let (bus, mut main) = MainProc::<SimpleStringTvf>::create(&settings);
+        bus.run_proc::<StubProc<SimpleStringTvf>, StubParotAdaptor>(
+            1,
+            StubSettings::new(vec![String::from("AUDIT_SRV")]),
+            None,
+            String::from("STUB_PROC"),
+        );
+        bus.run_proc::<InjProc<SimpleStringTvf>, InjDummyAdaptor>(
+            2,
+            InjSettings::new(String::from("AUDIT_SRV")),
+            None,
+            String::from("INJ_PROC"),
+        );
+
+        // Wait for the injector transactions to be routed to the stub
+        tokio::select! {
+            _ = main.internal_run() => panic!("The main task must not stop"),
+            _ = async {
+                while !bus.audit_dump().await.unwrap().iter().any(|record| {
+                    record.kind == AuditKind::Resolution
+                        && record.service.as_deref() == Some("AUDIT_SRV")
+                        && record.proc_id == Some(1)
+                        && record.correlation_id.is_some()
+                }) {
+                    time::sleep(Duration::from_millis(10)).await;
+                }
+            } => {},
+            _ = time::sleep(WAIT_TIMEOUT) => panic!("Timeout waiting for the transactions"),
+        }
+
+        bus.stop(String::from("audit test end")).await.unwrap();
+        main.internal_run().await.unwrap();
+
+        let records = main.audit_records();
+        assert!(records
+            .iter()
+            .any(|record| { record.kind == AuditKind::NewProcQueue && record.proc_id == Some(2) }));
+        assert!(records.iter().any(|record| {
+            record.kind == AuditKind::NewService
+                && record.service.as_deref() == Some("AUDIT_SRV")
+                && record.proc_id == Some(1)
+        }));
+        assert!(records.iter().any(|record| {
+            record.kind == AuditKind::Shutdown && record.detail.as_deref() == Some("audit test end")
+        }));
+        assert!(records
+            .windows(2)
+            .all(|records| records[0].timestamp <= records[1].timestamp));
+
+        // Without audit settings, the audit log is empty
+        let (bus, main) = MainProc::<SimpleStringTvf>::create(&TestSettings::default());
+        let main_task = main.run();
+        assert!(bus.audit_dump().await.unwrap().is_empty());
+        bus.stop("ProSA unit test end".into()).await.unwrap();
+        main_task.join().unwrap();
+    }
+
+    #[tokio::test]
+    async fn main_control_lane() {
+        let (bus, main) = MainProc::<SimpleStringTvf>::create(&TestSettings::default());
+
+        // Fill the data lane with service additions before running the main task
+        let (proc_tx_queue, mut proc_rx_queue) = mpsc::channel(8);
+        let proc_param = ProcParam::new(1, proc_tx_queue, bus.clone());
+        bus.add_proc_queue(ProcService::new_proc(&proc_param, 0))
+            .await
+            .unwrap();
+        let filling_bus = bus.clone();
+        let filler = tokio::spawn(async move {
+            while filling_bus
+                .add_service(vec![String::from("SRV_TEST")], 1, 0)
+                .await
+                .is_ok()
+            {}
+        });
+        tokio::time::timeout(WAIT_TIMEOUT, async {
+            while bus.get_bus_queue().capacity() > 0 {
+                tokio::task::yield_now().await;
+            }
+        })
+        .await
+        .expect("The data lane is not filled");
+
+        // The stop must not wait for the data lane
+        tokio::time::timeout(
+            Duration::from_millis(100),
+            bus.stop("ProSA unit test end".into()),
+        )
+        .await
+        .expect("The stop is stuck behind data messages")
+        .unwrap();
+
+        tokio::time::timeout(Duration::from_millis(500), main.spawn())
+            .await
+            .expect("The main task didn't stop promptly")
+            .unwrap()
+            .unwrap();
+        filler.await.unwrap();
+
+        // The main task stopped before processing the data lane
+        assert!(proc_rx_queue.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn main_removal_sequencing() {
+        let (bus, main) = MainProc::<SimpleStringTvf>::create(&TestSettings::default());
+
+        // The removal is sent through the control lane after the registrations queued in the data lane
+        let (proc_tx_queue, _proc_rx_queue) = mpsc::channel(8);
+        let proc_param = ProcParam::new(1, proc_tx_queue, bus.clone());
+        bus.add_proc_queue(ProcService::new_proc(&proc_param, 0))
+            .await
+            .unwrap();
+        bus.add_service(vec![String::from("SRV_TEST")], 1, 0)
+            .await
+            .unwrap();
+        bus.remove_proc(1).await.unwrap();
+        let main_task = main.run();
+
+        // The removal didn't overtake the registrations, so the processor is not left registered
+        let topology = bus.topology().await.unwrap();
+        assert!(topology.processors.is_empty(), "{:?}", topology);
+        assert!(!is_served(&topology, "SRV_TEST"), "{:?}", topology);
+
+        bus.stop("ProSA unit test end".into()).await.unwrap();
+        main_task.join().unwrap();
+    }
+
+    #[tokio::test]
+    async fn main_service_batching() {
+        let (bus, main) = MainProc::<SimpleStringTvf>::create(&TestSettings::default());
+
+        // Queue a burst of service updates before running the main task
+        let (proc_tx_queue, mut proc_rx_queue) = mpsc::channel(2048);
+        let proc_param = ProcParam::new(1, proc_tx_queue, bus.clone());
+        let bus_queue = bus.get_bus_queue();
+        bus_queue
+            .try_send(InternalMainMsg::NewProcQueue(ProcService::new_proc(
+                &proc_param,
+                0,
+            )))
+            .unwrap();
+        let mut updates = 0;
+        for i in 0..200 {
+            bus_queue
+                .try_send(InternalMainMsg::NewService(
+                    vec![format!("SRV_{}", i)],
+                    1,
+                    0,
+                ))
+                .unwrap();
+            updates += 1;
+            if i % 2 == 0 {
+                bus_queue
+                    .try_send(InternalMainMsg::DeleteService(
+                        vec![format!("SRV_{}", i)],
+                        1,
+                        0,
+                    ))
+                    .unwrap();
+                updates += 1;
+            }
+        }
+
+        let main_task = main.run();
+
+        // The batched updates converge to the final service table
+        let mut notifications = 0;
+        let table = tokio::time::timeout(WAIT_TIMEOUT, async {
+            loop {
+                if let Some(InternalMsg::Service(table)) = proc_rx_queue.recv().await {
+                    notifications += 1;
+                    if table.exist_proc_service("SRV_199") {
+                        return table;
+                    }
+                }
+            }
+        })
+        .await
+        .expect("Timeout waiting for the service table");
+        for i in 0..200 {
+            assert_eq!(
+                i % 2 == 1,
+                table.exist_proc_service(&format!("SRV_{}", i)),
+                "SRV_{}",
+                i
+            );
+        }
+        assert!(
+            notifications < updates / 10,
+            "{} notifications for {} updates",
+            notifications,
+            updates
+        );
+
+        proc_param.remove_proc().await.unwrap();
+        bus.stop("ProSA unit test end".into()).await.unwrap();
+        main_task.join().unwrap();
+    }
+
+    /// Wait until the topology match the condition
+    async fn wait_topology<F>(bus: &Main<SimpleStringTvf>, condition: F) -> TopologySnapshot
+    where
+        F: Fn(&TopologySnapshot) -> bool,
+    {
+        tokio::time::timeout(WAIT_TIMEOUT, async {
+            loop {
+                let topology = bus.topology().await.unwrap();
+                if condition(&topology) {
+                    return topology;
+                }
+                time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("Timeout waiting for the topology")
+    }
+
+    /// Check if a service is served by a processor in the topology
+    fn is_served(topology: &TopologySnapshot, name: &str) -> bool {
+        topology
+            .services
+            .get(name)
+            .is_some_and(|queues| !queues.is_empty())
+    }
+
+    #[tokio::test]
+    async fn main_proc_lifecycle() {
+        let settings = TestSettings {
+            ready_routing: Some(true),
+            ..Default::default()
+        };
+        let (bus, main) = MainProc::<SimpleStringTvf>::create(&settings);
+        let main_task = main.run();
+
+        // The stub report it's ready once its adaptor is initialized
+        bus.run_proc::<StubProc<SimpleStringTvf>, StubParotAdaptor>(
+            1,
+            StubSettings::new(vec![String::from("READY_SRV")]),
+            None,
+            String::from("STUB_PROC"),
+        );
+
+        // A processor that didn't report it's ready yet
+        let (proc_tx_queue, _proc_rx_queue) = mpsc::channel(64);
+        let proc_param = ProcParam::new(2, proc_tx_queue, bus.clone());
+        proc_param.add_proc().await.unwrap();
+        proc_param
+            .add_service_proc(vec![String::from("GATED_SRV")])
+            .await
+            .unwrap();
+
+        let topology = wait_topology(&bus, |topology| {
+            topology.states.get(&1) == Some(&ProcLifecycle::Ready)
+                && topology.states.contains_key(&2)
+        })
+        .await;
+        assert_eq!(Some(&ProcLifecycle::Starting), topology.states.get(&2));
+        assert!(!bus.health().await.unwrap().ready);
+        assert!(is_served(&topology, "READY_SRV"));
+        assert!(!is_served(&topology, "GATED_SRV"));
+        assert!(bus
+            .wait_proc_ready(1, time::Duration::from_millis(10))
+            .await
+            .unwrap());
+        assert!(!bus
+            .wait_proc_ready(2, time::Duration::from_millis(10))
+            .await
+            .unwrap());
+
+        // Once both processors are ready, the ProSA is healthy and the services are routed
+        let (proc_ready, set_ready) = tokio::join!(
+            bus.wait_proc_ready(2, time::Duration::from_secs(5)),
+            proc_param.set_ready()
+        );
+        assert!(proc_ready.unwrap());
+        set_ready.unwrap();
+        let topology = wait_topology(&bus, |topology| topology.health().ready).await;
+        assert_eq!(Some(&vec![(2, 0)]), topology.services.get("GATED_SRV"));
+        assert_eq!(
+            serde_json::json!({"ready": true, "processors": {"1": {"state": "ready"}, "2": {"state": "ready"}}}),
+            serde_json::to_value(bus.health().await.unwrap()).unwrap()
+        );
+
+        // A degraded processor is withheld from the routing until it's ready again
+        proc_param.set_degraded("lost connection").await.unwrap();
+        let topology = wait_topology(&bus, |topology| !topology.health().ready).await;
+        assert_eq!(
+            Some(&ProcLifecycle::Degraded {
+                reason: String::from("lost connection")
+            }),
+            topology.states.get(&2)
+        );
+        assert!(!is_served(&topology, "GATED_SRV"));
+        proc_param.set_ready().await.unwrap();
+        wait_topology(&bus, |topology| is_served(topology, "GATED_SRV")).await;
+
+        // The state of a removed processor is forgotten
+        proc_param.remove_proc().await.unwrap();
+        let topology = wait_topology(&bus, |topology| !topology.states.contains_key(&2)).await;
+        assert!(!is_served(&topology, "GATED_SRV"));
+        assert!(topology.health().ready);
+
+        bus.stop("ProSA unit test end".into()).await.unwrap();
+        main_task.join().unwrap();
+    }
+}
@@ -4,11 +4,16 @@ use std::{
 };
 
 use prosa_utils::msg::tvf::Tvf;
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, oneshot, watch};
 use tracing::span;
 use tracing::{event, Level, Span};
 
-use super::service::{ProcService, ServiceError, ServiceTable};
+use super::audit::AuditRecord;
+use super::error::{QueueErrorKind, SendError};
+use super::main::TopologySnapshot;
+use super::proc::{ProcBusParam, ProcLifecycle};
+use super::service::{MessageSizeLimit, ProcService, ServiceError, ServiceTable};
+use super::settings::ReloadedConfig;
 
 /// Internal ProSA message that define all message type that can be received by the main ProSA processor
 #[derive(Debug)]
@@ -20,6 +25,8 @@ where
     NewProcQueue(ProcService<M>),
     /// Message to indicate that a the processor stopped, delete all processor queues
     DeleteProc(u32),
+    /// Message to report the lifecycle state of a processor, with the processor id
+    ProcState(u32, ProcLifecycle),
     /// Message to indicate that a the processor queue stopped, delete the processor queue
     DeleteProcQueue(u32, u32),
     /// Message to declare new service(s) with their service name and the processor id (the processor should have been declared). Declare service(s) for the whole processor
@@ -34,30 +41,115 @@ where
     Command(String),
     /// Internal call for shutdown (with a reason)
     Shutdown(String),
+    /// Message to stop all processors of a group
+    StopGroup(String),
+    /// Message to restart all processors of a group (with their registered factories)
+    RestartGroup(String),
+    /// Answer of a processor queue to a heartbeat ping, with the processor id, the queue id, and the ping sequence
+    Pong(u32, u32, u64),
+    /// Message to ask a snapshot of the processors and services registered on the main bus
+    Topology(oneshot::Sender<TopologySnapshot>),
+    /// Message to reload the configuration files and send it to all processors, with the result of the reload
+    ReloadConfig(oneshot::Sender<Result<(), String>>),
+    /// Message to ask the records of the audit log (empty if the audit log is disabled)
+    AuditDump(oneshot::Sender<Vec<AuditRecord>>),
+}
+
+impl<M> InternalMainMsg<M>
+where
+    M: Sized + Clone + Tvf,
+{
+    /// Method to know if the message is a control message, that should be handled before data messages by the main task
+    pub fn is_control(&self) -> bool {
+        matches!(
+            self,
+            InternalMainMsg::Shutdown(_)
+                | InternalMainMsg::DeleteProc(_)
+                | InternalMainMsg::DeleteProcQueue(_, _)
+                | InternalMainMsg::Command(_)
+                | InternalMainMsg::StopGroup(_)
+                | InternalMainMsg::RestartGroup(_)
+        )
+    }
+
+    /// Getter of the processor id of a registration message (processor queue or service).
+    /// The removals of the processor (control messages) are sequenced behind its registrations sent through the data lane
+    pub fn get_registration_proc_id(&self) -> Option<u32> {
+        match self {
+            InternalMainMsg::NewProcQueue(proc) => Some(proc.get_proc_id()),
+            InternalMainMsg::NewProcService(_, proc_id)
+            | InternalMainMsg::NewService(_, proc_id, _) => Some(*proc_id),
+            _ => None,
+        }
+    }
 }
 
 /// Internal ProSA message that define all message type that can be received by a processor
+///
+/// New message types can be added, so processors must ignore the messages they don't handle.
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum InternalMsg<M>
 where
     M: Sized + Clone + Tvf,
 {
     /// Request Data message to process
     Request(RequestMsg<M>),
+    /// Batch of requests to process (sent with a [`RequestBatcher`](crate::core::service::RequestBatcher)).
+    /// Batches are split by the [`ProcRxQueue`](crate::core::proc::ProcRxQueue) unless the processor consumes them (see [`ProcRxQueue::set_batch_consumer`](crate::core::proc::ProcRxQueue::set_batch_consumer))
+    RequestBatch(Vec<RequestMsg<M>>),
     /// Response of a data request message
     Response(ResponseMsg<M>),
+    /// Batch of responses for the same processor queue (see [`RequestMsg::return_batch_to_senders`])
+    ResponseBatch(Vec<ResponseMsg<M>>),
     /// Response of a data request message by an error
     Error(ErrorMsg<M>),
     /// Command to ask an actiion or a status to the processor
     Command(String),
-    /// Message to ask the processor to reload its configuration
-    Config,
+    /// Message to ask the processor to reload its configuration (with the reloaded configuration)
+    Config(ReloadedConfig),
     /// Message to ask the processor to reload its service table
     Service(Arc<ServiceTable<M>>),
     /// Message to ask the processor to shutdown
     Shutdown,
+    /// Heartbeat of the main task with its sequence, to answer with [`InternalMainMsg::Pong`].
+    /// Pings are answered by the [`ProcRxQueue`](crate::core::proc::ProcRxQueue) of the processor queue, and never returned to the processor
+    Ping(u64),
+}
+
+impl<M> InternalMsg<M>
+where
+    M: Sized + Clone + Tvf,
+{
+    /// Method to know if the message is a control message, that should be handled before data messages by the processor
+    pub fn is_control(&self) -> bool {
+        matches!(
+            self,
+            InternalMsg::Command(_)
+                | InternalMsg::Config(_)
+                | InternalMsg::Service(_)
+                | InternalMsg::Shutdown
+                | InternalMsg::Ping(_)
+        )
+    }
 }
 
+/// Macro to drop the result of a message returned to its requester, logging (with the caller target) the message that can't be returned because its requester went away (it may have timed out before the response)
+///
+/// `drop_unreturned!(proc_name, msg.return_to_sender(data).await)`
+macro_rules! drop_unreturned {
+    ( $proc_name:expr, $result:expr ) => {
+        if let Err(e) = $result {
+            tracing::warn!(
+                proc_name = $proc_name,
+                "Drop a response that can't be returned to its requester: {}",
+                e
+            );
+        }
+    };
+}
+pub(crate) use drop_unreturned;
+
 #[cfg_attr(doc, aquamarine::aquamarine)]
 /// Trait that define a ProSAMsg use to send transactions
 ///
@@ -83,7 +175,7 @@ where
     /// Getter of the mutable span of the message (use to add informations for metrics)
     fn get_span_mut(&mut self) -> &mut Span;
     /// Enter the span and push metadata in it
-    fn enter_span(&self) -> span::Entered;
+    fn enter_span(&self) -> span::Entered<'_>;
     /// Return the elapsed time corresponding to the processing time (duration since the request creation)
     fn elapsed(&self) -> Duration;
     /// Getter of the message content
@@ -103,7 +195,52 @@ where
     span: Span,
     data: M,
     begin_time: SystemTime,
+    expires_at: Option<SystemTime>,
+    size_limit: MessageSizeLimit,
     response_queue: mpsc::Sender<InternalMsg<M>>,
+    origin: Option<RequestOrigin<M>>,
+}
+
+/// Processor queue that sent a request, with the live service table of the responder to find another queue of the processor if it disappeared
+#[derive(Debug)]
+struct RequestOrigin<M>
+where
+    M: Sized + Clone + Tvf,
+{
+    proc_id: u32,
+    queue_id: u32,
+    routes: Option<watch::Receiver<Arc<ServiceTable<M>>>>,
+}
+
+impl<M> RequestOrigin<M>
+where
+    M: Sized + Clone + Tvf,
+{
+    /// Method to send a message to the response queue, or to another queue of the originating processor if the response queue is closed
+    async fn send(
+        origin: Option<&RequestOrigin<M>>,
+        response_queue: &mpsc::Sender<InternalMsg<M>>,
+        msg: InternalMsg<M>,
+    ) -> Result<(), SendError<InternalMsg<M>>> {
+        match response_queue.send(msg).await {
+            Ok(()) => Ok(()),
+            Err(mpsc::error::SendError(msg)) => {
+                if let Some(proc_queue) = origin.and_then(|origin| {
+                    origin
+                        .routes
+                        .as_ref()?
+                        .borrow()
+                        .get_proc_queue(origin.proc_id, origin.queue_id)
+                        .map(|proc_service| proc_service.proc_queue.clone())
+                }) {
+                    proc_queue.send(msg).await?;
+                    Ok(())
+                } else {
+                    Err(SendError::new(QueueErrorKind::Closed, msg))
+                }
+            }
+        }
+    }
 }
 
 impl<M> Msg<M> for RequestMsg<M>
@@ -126,7 +263,7 @@ where
         &mut self.span
     }
 
-    fn enter_span(&self) -> span::Entered {
+    fn enter_span(&self) -> span::Entered<'_> {
         self.span.enter()
     }
 
@@ -161,25 +298,190 @@ where
             service,
             data,
             begin_time,
+            expires_at: None,
+            size_limit: MessageSizeLimit::default(),
             span,
             response_queue,
+            origin: None,
+        }
+    }
+
+    /// Setter of the processor queue that sends the request (set by [`ProcParam::new_request`](crate::core::proc::ProcParam::new_request)).
+    /// If the response queue is closed when the response is returned, the response is delivered to the originating queue (or another queue of the processor) found in the service table of the responder.
+    pub fn with_origin(mut self, proc_id: u32, queue_id: u32) -> Self {
+        self.origin = Some(RequestOrigin {
+            proc_id,
+            queue_id,
+            routes: None,
+        });
+        self
+    }
+
+    /// Setter of the live service table of the processor that received the request, to route its response if the response queue is closed
+    pub(crate) fn set_routes(&mut self, routes: &watch::Receiver<Arc<ServiceTable<M>>>) {
+        if let Some(origin) = &mut self.origin {
+            origin.routes = Some(routes.clone());
         }
     }
 
+    /// Getter of the processor id and queue id that sent the request (if set with [`RequestMsg::with_origin`])
+    pub fn get_origin(&self) -> Option<(u32, u32)> {
+        self.origin
+            .as_ref()
+            .map(|origin| (origin.proc_id, origin.queue_id))
+    }
+
+    /// Setter of the end-to-end deadline of the request.
+    /// Once expired, processors should stop working on the request and return a [`ServiceError::Timeout`] instead.
+    ///
+    /// ```
+    /// use std::time::{Duration, SystemTime};
+    /// use prosa::core::msg::RequestMsg;
+    /// use prosa_utils::msg::simple_string_tvf::SimpleStringTvf;
+    /// use tokio::sync::mpsc;
+    ///
+    /// let (response_queue, _) = mpsc::channel(1);
+    /// let request = RequestMsg::new(1, String::from("SERVICE"), SimpleStringTvf::default(), response_queue)
+    ///     .with_deadline(SystemTime::now() - Duration::from_secs(1));
+    /// assert!(request.is_expired());
+    /// ```
+    pub fn with_deadline(mut self, deadline: SystemTime) -> Self {
+        self.expires_at = Some(deadline);
+        self
+    }
+
+    /// Getter of the end-to-end deadline of the request (if any)
+    pub fn get_deadline(&self) -> Option<SystemTime> {
+        self.expires_at
+    }
+
+    /// Getter of the remaining time before the deadline of the request (`None` if the request don't have deadline)
+    pub fn get_remaining_time(&self) -> Option<Duration> {
+        self.expires_at.map(|expires_at| {
+            expires_at
+                .duration_since(SystemTime::now())
+                .unwrap_or_default()
+        })
+    }
+
+    /// Method to know if the deadline of the request is exceeded
+    pub fn is_expired(&self) -> bool {
+        self.expires_at
+            .is_some_and(|expires_at| expires_at <= SystemTime::now())
+    }
+
+    /// Getter of the timeout error to return if the request is expired
+    pub fn get_expiration_error(&self) -> Option<ServiceError> {
+        if self.is_expired() {
+            let timeout = self
+                .expires_at
+                .and_then(|expires_at| expires_at.duration_since(self.begin_time).ok())
+                .unwrap_or_default();
+            Some(ServiceError::Timeout(
+                self.service.clone(),
+                timeout.as_millis() as u64,
+            ))
+        } else {
+            None
+        }
+    }
+
+    /// Setter of the size limit of the bus the request is sent on, to check the response size
+    pub(crate) fn set_size_limit(&mut self, size_limit: MessageSizeLimit) {
+        self.size_limit = size_limit;
+    }
+
     /// Method to return the response to the called processor
-    pub async fn return_to_sender(
-        self,
-        resp: M,
-    ) -> Result<(), tokio::sync::mpsc::error::SendError<InternalMsg<M>>> {
-        self.response_queue
-            .send(InternalMsg::Response(ResponseMsg {
+    ///
+    /// If the response exceeds the maximum message size of the bus, a [`ServiceError::MessageTooLarge`] is returned instead
+    pub async fn return_to_sender(self, resp: M) -> Result<(), SendError<InternalMsg<M>>> {
+        if let Err(err) = self
+            .span
+            .in_scope(|| self.size_limit.check(&self.service, &resp, "response"))
+        {
+            return self.return_error_to_sender(None, err).await;
+        }
+
+        self.size_limit.record_transaction(self.elapsed());
+        RequestOrigin::send(
+            self.origin.as_ref(),
+            &self.response_queue,
+            InternalMsg::Response(ResponseMsg {
                 id: self.id,
                 service: self.service,
                 span: self.span,
                 response_time: self.begin_time,
                 data: resp,
-            }))
-            .await
+            }),
+        )
+        .await
+    }
+
+    /// Method to return the responses of a batch of requests.
+    /// Responses for the same processor queue are sent together in an [`InternalMsg::ResponseBatch`], and oversized responses are returned as [`ServiceError::MessageTooLarge`] errors
+    ///
+    /// ```
+    /// use prosa::core::msg::{InternalMsg, RequestMsg};
+    /// use prosa_utils::msg::simple_string_tvf::SimpleStringTvf;
+    /// use tokio::sync::mpsc;
+    ///
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// let (response_queue, mut rx) = mpsc::channel(8);
+    /// let responses = (0..3)
+    ///     .map(|id| (RequestMsg::new(id, String::from("SERVICE"), SimpleStringTvf::default(), response_queue.clone()), SimpleStringTvf::default()))
+    ///     .collect();
+    /// RequestMsg::return_batch_to_senders(responses).await.unwrap();
+    /// assert!(matches!(rx.recv().await, Some(InternalMsg::ResponseBatch(batch)) if batch.len() == 3));
+    /// # }
+    /// ```
+    pub async fn return_batch_to_senders(
+        responses: Vec<(RequestMsg<M>, M)>,
+    ) -> Result<(), SendError<InternalMsg<M>>> {
+        // Responses grouped by response queue (with the origin of the queue)
+        let mut queues: Vec<mpsc::Sender<InternalMsg<M>>> = Vec::new();
+        let mut origins: Vec<Option<RequestOrigin<M>>> = Vec::new();
+        let mut batches: Vec<Vec<ResponseMsg<M>>> = Vec::new();
+        for (request, resp) in responses {
+            if let Err(err) = request.span.in_scope(|| {
+                request
+                    .size_limit
+                    .check(&request.service, &resp, "response")
+            }) {
+                request.return_error_to_sender(None, err).await?;
+                continue;
+            }
+
+            request.size_limit.record_transaction(request.elapsed());
+            let response = ResponseMsg {
+                id: request.id,
+                service: request.service,
+                span: request.span,
+                response_time: request.begin_time,
+                data: resp,
+            };
+            if let Some(index) = queues
+                .iter()
+                .position(|queue| queue.same_channel(&request.response_queue))
+            {
+                batches[index].push(response);
+            } else {
+                queues.push(request.response_queue);
+                origins.push(request.origin);
+                batches.push(vec![response]);
+            }
+        }
+
+        for ((queue, origin), mut batch) in queues.into_iter().zip(origins).zip(batches) {
+            let msg = if batch.len() == 1 {
+                InternalMsg::Response(batch.remove(0))
+            } else {
+                InternalMsg::ResponseBatch(batch)
+            };
+            RequestOrigin::send(origin.as_ref(), &queue, msg).await?;
+        }
+
+        Ok(())
     }
 
     /// Method to return an error to the called processor
@@ -188,17 +490,21 @@ where
         self,
         data: Option<M>,
         err: ServiceError,
-    ) -> Result<(), tokio::sync::mpsc::error::SendError<InternalMsg<M>>> {
-        self.response_queue
-            .send(InternalMsg::Error(ErrorMsg {
+    ) -> Result<(), SendError<InternalMsg<M>>> {
+        self.size_limit.record_transaction(self.elapsed());
+        RequestOrigin::send(
+            self.origin.as_ref(),
+            &self.response_queue,
+            InternalMsg::Error(ErrorMsg {
                 id: self.id,
                 service: self.service,
                 span: self.span,
                 error_time: self.begin_time,
                 data: data.unwrap_or(self.data),
                 err,
-            }))
-            .await
+            }),
+        )
+        .await
     }
 }
 
@@ -235,7 +541,7 @@ where
         &mut self.span
     }
 
-    fn enter_span(&self) -> span::Entered {
+    fn enter_span(&self) -> span::Entered<'_> {
         let enter = self.span.enter();
         enter
     }
@@ -287,7 +593,7 @@ where
         &mut self.span
     }
 
-    fn enter_span(&self) -> span::Entered {
+    fn enter_span(&self) -> span::Entered<'_> {
         let enter = self.span.enter();
         event!(Level::ERROR, "{}", self.err);
         enter
@@ -0,0 +1,433 @@
+use std::{
+    backtrace::Backtrace,
+    collections::HashMap,
+    fmt,
+    hash::{DefaultHasher, Hash, Hasher},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use opentelemetry::{
+    metrics::{Counter, Meter},
+    KeyValue,
+};
+use tokio::task::JoinHandle;
+use tracing::warn;
+
+/// Default number of shards of a [`PendingMap`]
+const DEFAULT_SHARDS: usize = 16;
+
+/// Pending entry, with its deadline
+struct PendingEntry<V> {
+    value: V,
+    deadline: Instant,
+    backtrace: Option<Backtrace>,
+}
+
+type PendingShard<K, V> = Mutex<HashMap<K, PendingEntry<V>>>;
+
+type ExpireCallback<K, V> = Box<dyn Fn(K, V) + Send + Sync>;
+
+/// Map of the pending transactions (requests waiting for their responses), that expire the entries never completed
+///
+/// The map is sharded to limit the lock contention, and every entry is inserted with a deadline.
+/// An entry is either completed (removed with [`PendingMap::complete`]) or expired by a sweep, never both:
+/// the expired entries are given to the expire callback and counted as leaks (`prosa_pending_leaks` counter if a meter is set).
+///
+/// With [`PendingMap::with_backtraces`], the creation backtrace of every entry is kept and logged when it leaks.
+/// It's costly, so meant to track leaks in tests.
+///
+/// Every method that depend on the time have an `_at` variant that takes the current instant, to drive the map with your own clock.
+///
+/// ```
+/// use std::sync::{Arc, Mutex};
+/// use std::time::{Duration, Instant};
+/// use prosa::core::pending::PendingMap;
+///
+/// let expired = Arc::new(Mutex::new(Vec::new()));
+/// let expired_cb = expired.clone();
+/// let pending = PendingMap::<u64, String>::new()
+///     .on_expire(move |id, _request| expired_cb.lock().unwrap().push(id));
+///
+/// let now = Instant::now();
+/// pending.insert_at(1, String::from("request 1"), Duration::from_secs(1), now);
+/// pending.insert_at(2, String::from("request 2"), Duration::from_secs(1), now);
+/// assert_eq!(Some(String::from("request 1")), pending.complete(&1));
+///
+/// assert_eq!(1, pending.sweep_at(now + Duration::from_secs(2)));
+/// assert_eq!(vec![2], *expired.lock().unwrap());
+/// assert_eq!(1, pending.leaked());
+/// assert!(pending.is_empty());
+/// ```
+pub struct PendingMap<K, V> {
+    shards: Vec<PendingShard<K, V>>,
+    on_expire: Option<ExpireCallback<K, V>>,
+    backtraces: bool,
+    leaked: AtomicU64,
+    leak_counter: Option<(Counter<u64>, Vec<KeyValue>)>,
+}
+
+impl<K, V> PendingMap<K, V>
+where
+    K: Hash + Eq,
+{
+    /// Method to create a pending map with the default number of shards
+    pub fn new() -> PendingMap<K, V> {
+        Self::with_shards(DEFAULT_SHARDS)
+    }
+
+    /// Method to create a pending map with a number of shards
+    pub fn with_shards(shards: usize) -> PendingMap<K, V> {
+        PendingMap {
+            shards: (0..shards.max(1))
+                .map(|_| Mutex::new(HashMap::new()))
+                .collect(),
+            on_expire: None,
+            backtraces: false,
+            leaked: AtomicU64::new(0),
+            leak_counter: None,
+        }
+    }
+
+    /// Setter of the callback invoked with every expired entry
+    pub fn on_expire<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(K, V) + Send + Sync + 'static,
+    {
+        self.on_expire = Some(Box::new(callback));
+        self
+    }
+
+    /// Keep the creation backtrace of the entries, to log it when they leak
+    pub fn with_backtraces(mut self) -> Self {
+        self.backtraces = true;
+        self
+    }
+
+    /// Setter of the meter used to count the leaked entries (`prosa_pending_leaks` counter)
+    pub fn meter(mut self, meter: &Meter, attributes: Vec<KeyValue>) -> Self {
+        self.leak_counter = Some((
+            meter
+                .u64_counter("prosa_pending_leaks")
+                .with_description("Pending entries expired without completion")
+                .init(),
+            attributes,
+        ));
+        self
+    }
+
+    /// Method to get the shard of a key
+    fn shard(&self, key: &K) -> &PendingShard<K, V> {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        &self.shards[hasher.finish() as usize % self.shards.len()]
+    }
+
+    /// Getter of the number of pending entries (including the expired ones that are not swept yet)
+    pub fn len(&self) -> usize {
+        self.shards
+            .iter()
+            .map(|shard| shard.lock().unwrap().len())
+            .sum()
+    }
+
+    /// Method to know if the map don't have any pending entry
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Getter of the number of entries that expired without completion
+    pub fn leaked(&self) -> u64 {
+        self.leaked.load(Ordering::Relaxed)
+    }
+
+    /// Method to know if an entry is pending
+    pub fn contains(&self, key: &K) -> bool {
+        self.shard(key).lock().unwrap().contains_key(key)
+    }
+
+    /// Method to insert a pending entry that expire after a TTL. Return the replaced entry value if the key was already pending
+    pub fn insert(&self, key: K, value: V, ttl: Duration) -> Option<V> {
+        self.insert_at(key, value, ttl, Instant::now())
+    }
+
+    /// Same as [`PendingMap::insert`] at a given instant
+    pub fn insert_at(&self, key: K, value: V, ttl: Duration, now: Instant) -> Option<V> {
+        self.insert_with_deadline(key, value, now + ttl)
+    }
+
+    /// Method to insert a pending entry that expire at a deadline. Return the replaced entry value if the key was already pending
+    pub fn insert_with_deadline(&self, key: K, value: V, deadline: Instant) -> Option<V> {
+        let entry = PendingEntry {
+            value,
+            deadline,
+            backtrace: self.backtraces.then(Backtrace::force_capture),
+        };
+        self.shard(&key)
+            .lock()
+            .unwrap()
+            .insert(key, entry)
+            .map(|entry| entry.value)
+    }
+
+    /// Method to complete a pending entry. Return its value if it was still pending (not completed nor expired)
+    pub fn complete(&self, key: &K) -> Option<V> {
+        self.shard(key)
+            .lock()
+            .unwrap()
+            .remove(key)
+            .map(|entry| entry.value)
+    }
+
+    /// Method to expire the entries whose deadline is reached. Return the number of expired entries
+    pub fn sweep(&self) -> usize
+    where
+        K: fmt::Debug,
+    {
+        self.sweep_at(Instant::now())
+    }
+
+    /// Same as [`PendingMap::sweep`] at a given instant
+    pub fn sweep_at(&self, now: Instant) -> usize
+    where
+        K: fmt::Debug,
+    {
+        let mut expired_count = 0;
+        for shard in &self.shards {
+            // The callback is invoked out of the shard lock, so it can use the map
+            let expired: Vec<(K, PendingEntry<V>)> = shard
+                .lock()
+                .unwrap()
+                .extract_if(|_, entry| entry.deadline <= now)
+                .collect();
+            expired_count += expired.len();
+
+            for (key, entry) in expired {
+                if let Some(backtrace) = entry.backtrace {
+                    warn!("Pending entry {:?} leaked, created at:\n{}", key, backtrace);
+                }
+
+                if let Some(on_expire) = &self.on_expire {
+                    on_expire(key, entry.value);
+                }
+            }
+        }
+
+        if expired_count > 0 {
+            self.leaked
+                .fetch_add(expired_count as u64, Ordering::Relaxed);
+            if let Some((counter, attributes)) = &self.leak_counter {
+                counter.add(expired_count as u64, attributes);
+            }
+        }
+
+        expired_count
+    }
+
+    /// Method to spawn a task that sweep the map periodically. The task stop once the map is dropped
+    pub fn spawn_sweeper(self: &Arc<Self>, interval: Duration) -> JoinHandle<()>
+    where
+        K: fmt::Debug + Send + 'static,
+        V: Send + 'static,
+    {
+        let pending = Arc::downgrade(self);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                ticker.tick().await;
+                match pending.upgrade() {
+                    Some(pending) => {
+                        pending.sweep();
+                    }
+                    None => break,
+                }
+            }
+        })
+    }
+}
+
+impl<K, V> Default for PendingMap<K, V>
+where
+    K: Hash + Eq,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V> fmt::Debug for PendingMap<K, V>
+where
+    K: Hash + Eq,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PendingMap")
+            .field("shards", &self.shards.len())
+            .field("len", &self.len())
+            .field("leaked", &self.leaked())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{Arc, Mutex},
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use super::PendingMap;
+
+    #[test]
+    fn test_pending_expiry() {
+        let expired = Arc::new(Mutex::new(Vec::new()));
+        let expired_cb = expired.clone();
+        let pending = PendingMap::<u64, String>::with_shards(4)
+            .on_expire(move |key, value| expired_cb.lock().unwrap().push((key, value)))
+            .with_backtraces();
+
+        let now = Instant::now();
+        assert_eq!(
+            None,
+            pending.insert_at(1, "a".into(), Duration::from_secs(1), now)
+        );
+        assert_eq!(
+            None,
+            pending.insert_at(2, "b".into(), Duration::from_secs(2), now)
+        );
+        assert_eq!(
+            None,
+            pending.insert_at(3, "c".into(), Duration::from_secs(3), now)
+        );
+        assert_eq!(3, pending.len());
+
+        // Nothing is expired before the deadlines
+        assert_eq!(0, pending.sweep_at(now));
+        assert_eq!(0, pending.leaked());
+
+        assert_eq!(1, pending.sweep_at(now + Duration::from_secs(1)));
+        assert_eq!(vec![(1, String::from("a"))], *expired.lock().unwrap());
+        assert!(!pending.contains(&1));
+        assert_eq!(None, pending.complete(&1));
+
+        // A completed entry never expire
+        assert_eq!(Some(String::from("b")), pending.complete(&2));
+        assert_eq!(1, pending.sweep_at(now + Duration::from_secs(10)));
+        assert_eq!(
+            vec![(1, String::from("a")), (3, String::from("c"))],
+            *expired.lock().unwrap()
+        );
+        assert_eq!(2, pending.leaked());
+        assert!(pending.is_empty());
+
+        // A replaced entry keep the last deadline
+        pending.insert_at(4, "d".into(), Duration::from_secs(1), now);
+        assert_eq!(
+            Some(String::from("d")),
+            pending.insert_at(4, "e".into(), Duration::from_secs(5), now)
+        );
+        assert_eq!(0, pending.sweep_at(now + Duration::from_secs(2)));
+        assert_eq!(Some(String::from("e")), pending.complete(&4));
+    }
+
+    #[test]
+    fn test_pending_completion_race() {
+        const ENTRIES: u64 = 10_000;
+        let expired = Arc::new(Mutex::new(Vec::new()));
+        let expired_cb = expired.clone();
+        let pending = Arc::new(
+            PendingMap::<u64, u64>::new()
+                .on_expire(move |key, _| expired_cb.lock().unwrap().push(key)),
+        );
+
+        let now = Instant::now();
+        for key in 0..ENTRIES {
+            pending.insert_at(key, key, Duration::ZERO, now);
+        }
+
+        // Complete all the entries while they are swept
+        let completer = {
+            let pending = pending.clone();
+            thread::spawn(move || {
+                (0..ENTRIES)
+                    .filter_map(|key| pending.complete(&key))
+                    .collect::<Vec<u64>>()
+            })
+        };
+        let sweeper = {
+            let pending = pending.clone();
+            thread::spawn(move || {
+                let mut swept = 0;
+                while swept < ENTRIES as usize && !pending.is_empty() {
+                    swept += pending.sweep_at(now);
+                }
+                swept
+            })
+        };
+        let mut completed = completer.join().unwrap();
+        let swept = sweeper.join().unwrap();
+        pending.sweep_at(now);
+
+        // Every entry is either completed or expired, exactly once
+        let mut expired = expired.lock().unwrap().clone();
+        assert_eq!(expired.len() as u64, pending.leaked());
+        assert!(swept <= expired.len());
+        completed.append(&mut expired);
+        completed.sort_unstable();
+        assert_eq!((0..ENTRIES).collect::<Vec<u64>>(), completed);
+        assert!(pending.is_empty());
+    }
+
+    #[test]
+    fn test_pending_sweep_performance() {
+        const ENTRIES: u64 = 100_000;
+        let pending = PendingMap::<u64, u64>::new();
+        let now = Instant::now();
+        for key in 0..ENTRIES {
+            pending.insert_at(key, key, Duration::from_secs(key % 2 + 1), now);
+        }
+
+        let start = Instant::now();
+        assert_eq!(0, pending.sweep_at(now));
+        assert_eq!(
+            ENTRIES as usize / 2,
+            pending.sweep_at(now + Duration::from_secs(1))
+        );
+        assert_eq!(
+            ENTRIES as usize / 2,
+            pending.sweep_at(now + Duration::from_secs(2))
+        );
+        let elapsed = start.elapsed();
+        assert!(
+            elapsed < Duration::from_secs(2),
+            "sweeping {} entries took {:?}",
+            ENTRIES,
+            elapsed
+        );
+        assert_eq!(ENTRIES, pending.leaked());
+    }
+
+    #[tokio::test]
+    async fn test_pending_sweeper() {
+        let pending = Arc::new(PendingMap::<u64, ()>::new());
+        let sweeper = pending.spawn_sweeper(Duration::from_millis(10));
+        pending.insert(1, (), Duration::from_millis(20));
+        pending.insert(2, (), Duration::from_secs(60));
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(!pending.contains(&1));
+        assert!(pending.contains(&2));
+        assert_eq!(1, pending.leaked());
+
+        // The sweeper stop with the map
+        drop(pending);
+        tokio::time::timeout(Duration::from_secs(1), sweeper)
+            .await
+            .unwrap()
+            .unwrap();
+    }
+}
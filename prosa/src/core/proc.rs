@@ -8,7 +8,7 @@
 //! To create a ProSA processor:
 //! ```
 //! use std::error::Error;
-//! use serde::Serialize;
+//! use serde::{Deserialize, Serialize};
 //! use prosa_utils::msg::tvf::Tvf;
 //! use prosa::core::proc::{proc_settings, proc, Proc, ProcBusParam};
 //! use prosa::core::adaptor::Adaptor;
@@ -60,7 +60,7 @@
 //! }
 //!
 //! #[proc_settings]
-//! #[derive(Default, Debug, Serialize)]
+//! #[derive(Default, Debug, Deserialize, Serialize)]
 //! pub struct MyProcSettings {
 //!     param: String,
 //!     // ...
@@ -103,6 +103,7 @@
 //!     async fn internal_run(&mut self, name: String) -> Result<(), Box<dyn std::error::Error>> {
 //!         // Initiate an adaptor for the stub processor
 //!         let mut adaptor = A::new(self)?;
+//!         adaptor.async_init(&self.proc).await?;
 //!
 //!         // Declare the processor
 //!         self.proc.add_proc().await?;
@@ -131,13 +132,21 @@
 //!                         err
 //!                     ),
 //!                     InternalMsg::Command(_) => todo!(),
-//!                     InternalMsg::Config => todo!(),
+//!                     InternalMsg::Config(config) => {
+//!                         // Reload the processor settings, then the adaptor configuration, from the new configuration
+//!                         self.reload_settings(&config, &name)?;
+//!                         self.reload_adaptor(&mut adaptor, &name);
+//!                     }
 //!                     InternalMsg::Service(table) => self.service = table,
 //!                     InternalMsg::Shutdown => {
-//!                         adaptor.terminate();
+//!                         adaptor.async_terminate().await;
 //!                         self.proc.remove_proc().await?;
 //!                         return Ok(());
 //!                     }
+//!                     // Batches are split by the processor queue
+//!                     InternalMsg::RequestBatch(_) | InternalMsg::ResponseBatch(_) => {}
+//!                     // Heartbeats are answered by the processor queue
+//!                     _ => {}
 //!                 }
 //!             }
 //!         }
@@ -145,17 +154,38 @@
 //! }
 //! ```
 
-use super::adaptor::Adaptor;
-use super::main::BusError;
-use super::{main::Main, msg::InternalMsg, service::ProcService};
+use super::adaptor::{Adaptor, TransformSettings};
+use super::error::BusError;
+use super::{
+    dead_letter::DeadLetter,
+    main::{Main, ProcPanicGuard},
+    msg::{InternalMsg, Msg as _, RequestMsg},
+    schedule::{ScheduleHandle, Scheduler},
+    service::{MessageSizeLimit, ProcService, ServiceError, ServiceTable},
+    shed::ShedPolicy,
+    state::{StateError, StateHandle},
+    transport::{TransportReceiver, TransportSender, TransportSettings},
+    watchdog::{Watchdog, WatchdogSettings},
+};
 use config::File;
 use config::{Config, ConfigError};
 use glob::glob;
+use opentelemetry::metrics::{Counter, Histogram, ObservableGauge};
+use opentelemetry::KeyValue;
 use prosa_utils::msg::tvf::Tvf;
+use serde::Serialize;
 use std::borrow::Cow;
-use std::fmt::Debug;
+use std::collections::VecDeque;
+use std::fmt::{self, Debug};
+use std::ops::{Deref, DerefMut};
+use std::sync::{
+    atomic::{AtomicU32, Ordering},
+    Arc, OnceLock,
+};
+use std::time::{Duration, Instant};
 use tokio::runtime;
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, watch};
+use tracing::{debug, error, warn};
 
 // Export proc macro
 pub use prosa_macros::proc;
@@ -165,6 +195,15 @@ pub use prosa_macros::proc_settings;
 
 /// Trait to define ProSA processor settings
 ///
+/// The macro add common fields to the processor settings:
+/// - `adaptor_config_path`: path of the adaptor configuration
+/// - `threads`: number of threads of the processor runtime (in the range 0..=512, `0` to use the processor default)
+/// - `shutdown_rank`: rank of the processor in the ProSA shutdown sequence (`0` by default). Processors with the lowest rank are stopped first
+/// - `watchdog`: detection of the adaptor invocations that block the processor (see [`WatchdogSettings`](crate::core::watchdog::WatchdogSettings))
+/// - `shed_policy`: load shedding of the requests when the processor falls behind (see [`ShedPolicy`](crate::core::shed::ShedPolicy))
+/// - `runtime_metrics_interval`: sampling interval of the processor runtime metrics (only exported with the `runtime-metrics` feature)
+/// - `transport`: transport of the processor data messages (see [`TransportSettings`](crate::core::transport::TransportSettings))
+///
 /// ```
 /// use prosa::core::proc::proc_settings;
 ///
@@ -187,21 +226,52 @@ pub trait ProcSettings {
     /// Getter of the processor's adaptor configuration path
     fn get_adaptor_config_path(&self) -> Option<&String>;
 
+    /// Getter of the processor's runtime threads number (`threads` setting), if configured
+    fn get_proc_threads(&self) -> Option<usize>;
+
+    /// Getter of the processor's rank in the shutdown sequence (`shutdown_rank` setting), if configured
+    fn get_shutdown_rank(&self) -> Option<u32>;
+
+    /// Getter of the processor's watchdog settings (`watchdog` setting), if configured
+    fn get_watchdog(&self) -> Option<&WatchdogSettings>;
+
+    /// Getter of the processor's load shedding policy (`shed_policy` setting), if configured
+    fn get_shed_policy(&self) -> Option<&ShedPolicy>;
+
+    /// Getter of the processor's runtime metrics sampling interval (`runtime_metrics_interval` setting), if configured
+    fn get_runtime_metrics_interval(&self) -> Option<Duration>;
+
+    /// Getter of the processor's transport settings (`transport` setting), if configured
+    fn get_transport(&self) -> Option<&TransportSettings>;
+
+    /// Getter of the transforms of the processor's adaptor pipeline (`pipeline` setting)
+    ///
+    /// Empty for the processors that don't declare a `pipeline` field in their settings
+    fn get_pipeline(&self) -> &[TransformSettings] {
+        &[]
+    }
+
     /// Getter of the processor's adaptor configuration
+    ///
+    /// A deserialization error give the key path and the file that define it
     fn get_adaptor_config<C>(&self) -> Result<C, ::config::ConfigError>
     where
         C: serde::de::Deserialize<'static>,
     {
         if let Some(config_path) = &self.get_adaptor_config_path() {
-            Config::builder()
+            let files: Vec<_> = glob(config_path)
+                .unwrap()
+                .map(|path| path.unwrap())
+                .collect();
+            let config = Config::builder()
                 .add_source(
-                    glob(config_path)
-                        .unwrap()
-                        .map(|path| File::from(path.unwrap()))
+                    files
+                        .iter()
+                        .map(|path| File::from(path.as_path()))
                         .collect::<Vec<_>>(),
                 )
-                .build()?
-                .try_deserialize()
+                .build()?;
+            crate::core::settings::deserialize_located(config, &files)
         } else {
             Err(ConfigError::NotFound(
                 "No configuration set for processor's adaptor".to_string(),
@@ -210,6 +280,192 @@ pub trait ProcSettings {
     }
 }
 
+/// Trait to define ProSA processor errors
+///
+/// Give information on how a processor should behave when the error occurs, and observability data (code and causes chain)
+///
+/// ```
+/// use std::time::Duration;
+/// use prosa::core::proc::{ProcError, ProcErrorExt as _};
+///
+/// #[derive(Debug, thiserror::Error)]
+/// #[error("connection lost")]
+/// struct MyError;
+///
+/// impl ProcError for MyError {
+///     fn recoverable(&self) -> bool {
+///         true
+///     }
+///
+///     fn error_code(&self) -> u32 {
+///         42
+///     }
+/// }
+///
+/// let err = MyError.with_context("Can't reach the partner");
+/// assert_eq!(42, err.error_code());
+/// assert!(err.recoverable());
+/// assert_eq!("Can't reach the partner: connection lost", err.chain().to_string());
+/// ```
+pub trait ProcError: std::error::Error {
+    /// Method to know if the processor can recover from the error (restart)
+    fn recoverable(&self) -> bool;
+
+    /// Duration to wait before trying to recover from the error
+    fn recovery_duration(&self) -> Duration {
+        Duration::ZERO
+    }
+
+    /// Machine readable code of the error (0 if undefined)
+    fn error_code(&self) -> u32 {
+        0
+    }
+
+    /// Error that caused this one, if any
+    fn context(&self) -> Option<&dyn ProcError> {
+        None
+    }
+
+    /// Getter of the formatter that display the whole chain of errors
+    fn chain(&self) -> ProcErrorChain<'_>
+    where
+        Self: Sized,
+    {
+        ProcErrorChain(self)
+    }
+}
+
+/// Formatter of a [`ProcError`] with all its causes (`error: cause: root cause`)
+pub struct ProcErrorChain<'a>(&'a dyn ProcError);
+
+impl<'a> ProcErrorChain<'a> {
+    /// Method to create a chain formatter from a dynamic processor error
+    pub fn new(err: &'a dyn ProcError) -> ProcErrorChain<'a> {
+        ProcErrorChain(err)
+    }
+}
+
+impl fmt::Display for ProcErrorChain<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)?;
+        let mut cause = self.0.context();
+        while let Some(err) = cause {
+            write!(f, ": {}", err)?;
+            cause = err.context();
+        }
+
+        Ok(())
+    }
+}
+
+/// Processor error wrapper that add a context message to an error
+#[derive(Debug)]
+pub struct ProcErrorContext<E>
+where
+    E: ProcError,
+{
+    msg: String,
+    err: E,
+}
+
+impl<E> ProcErrorContext<E>
+where
+    E: ProcError,
+{
+    /// Getter of the wrapped error
+    pub fn inner(&self) -> &E {
+        &self.err
+    }
+
+    /// Consume the context to return the wrapped error
+    pub fn into_inner(self) -> E {
+        self.err
+    }
+}
+
+impl<E> fmt::Display for ProcErrorContext<E>
+where
+    E: ProcError,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.msg)
+    }
+}
+
+impl<E> std::error::Error for ProcErrorContext<E>
+where
+    E: ProcError + 'static,
+{
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.err)
+    }
+}
+
+impl<E> ProcError for ProcErrorContext<E>
+where
+    E: ProcError + 'static,
+{
+    fn recoverable(&self) -> bool {
+        self.err.recoverable()
+    }
+
+    fn recovery_duration(&self) -> Duration {
+        self.err.recovery_duration()
+    }
+
+    fn error_code(&self) -> u32 {
+        self.err.error_code()
+    }
+
+    fn context(&self) -> Option<&dyn ProcError> {
+        Some(&self.err)
+    }
+}
+
+/// Extension trait to add context to processor errors
+pub trait ProcErrorExt: ProcError + Sized {
+    /// Method to wrap the error with a context message
+    fn with_context<S>(self, msg: S) -> ProcErrorContext<Self>
+    where
+        S: Into<String>,
+    {
+        ProcErrorContext {
+            msg: msg.into(),
+            err: self,
+        }
+    }
+}
+
+impl<E> ProcErrorExt for E where E: ProcError {}
+
+/// Counter of the processors stopped on an error, with their error code (see [`Proc::run`])
+pub type ProcCrashCounter = Counter<u64>;
+
+/// Method to get the code and the chain of causes (`error: cause: root cause`) of an error that stopped a processor.
+/// The code is the one of the first ProSA error ([`BusError`], [`ServiceError`]) of the chain (0 if there is none)
+fn crash_report(err: &(dyn std::error::Error + 'static)) -> (u32, String) {
+    let mut chain = Vec::new();
+    let mut cause = Some(err);
+    while let Some(err) = cause {
+        let proc_err = err
+            .downcast_ref::<BusError>()
+            .map(|e| e as &dyn ProcError)
+            .or_else(|| {
+                err.downcast_ref::<ServiceError>()
+                    .map(|e| e as &dyn ProcError)
+            });
+        if let Some(proc_err) = proc_err {
+            chain.push(ProcErrorChain::new(proc_err).to_string());
+            return (proc_err.error_code(), chain.join(": "));
+        }
+
+        chain.push(err.to_string());
+        cause = err.source();
+    }
+
+    (0, chain.join(": "))
+}
+
 /// Global parameter for a processor (main or specific)
 pub trait ProcBusParam {
     /// Getter of the processor id
@@ -222,6 +478,60 @@ impl Debug for dyn ProcBusParam {
     }
 }
 
+/// Lifecycle state of a processor, reported to the main task
+///
+/// A registered processor is [`ProcLifecycle::Starting`] until it reports it's [`ProcLifecycle::Ready`] to serve (once its adaptor is initialized or its outbound connection is established for example).
+///
+/// ```
+/// use prosa::core::proc::ProcLifecycle;
+///
+/// let degraded = ProcLifecycle::Degraded { reason: String::from("no connection") };
+/// assert_eq!("degraded: no connection", degraded.to_string());
+/// assert_eq!(
+///     r#"{"state":"degraded","reason":"no connection"}"#,
+///     serde_json::to_string(&degraded).unwrap()
+/// );
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum ProcLifecycle {
+    /// The processor is registered, but not ready to serve yet
+    #[default]
+    Starting,
+    /// The processor is fully operational
+    Ready,
+    /// The processor is running, but can't serve normally
+    Degraded {
+        /// Reason of the degradation
+        reason: String,
+    },
+    /// The processor is stopping
+    Stopping,
+}
+
+impl ProcLifecycle {
+    /// Method to know if the processor is ready to serve
+    pub fn is_ready(&self) -> bool {
+        matches!(self, ProcLifecycle::Ready)
+    }
+}
+
+impl fmt::Display for ProcLifecycle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProcLifecycle::Starting => write!(f, "starting"),
+            ProcLifecycle::Ready => write!(f, "ready"),
+            ProcLifecycle::Degraded { reason } => write!(f, "degraded: {}", reason),
+            ProcLifecycle::Stopping => write!(f, "stopping"),
+        }
+    }
+}
+
+/// Queue id of the primary queue of a processor, declared with [`ProcParam::add_proc`]
+///
+/// Additional queues declared with [`ProcParam::add_proc_queue`] can't use it.
+pub const PRIMARY_QUEUE_ID: u32 = 0;
+
 #[derive(Debug, Clone)]
 /// Parameters embeded in a ProSA processor
 pub struct ProcParam<M>
@@ -229,8 +539,18 @@ where
     M: Sized + Clone + Tvf,
 {
     id: u32,
+    group: OnceLock<String>,
+    threads: Option<usize>,
+    shutdown_rank: u32,
+    watchdog: Option<WatchdogSettings>,
+    shed_policy: Option<ShedPolicy>,
+    runtime_metrics_interval: Option<Duration>,
     queue: mpsc::Sender<InternalMsg<M>>,
+    ctrl_queue: Option<mpsc::Sender<InternalMsg<M>>>,
+    transport_queue: Option<TransportSender<InternalMsg<M>>>,
     main: Main<M>,
+    next_queue_id: Arc<AtomicU32>,
+    scheduler: Arc<OnceLock<Scheduler<M>>>,
 }
 
 impl<M> ProcBusParam for ProcParam<M>
@@ -248,7 +568,135 @@ where
 {
     /// Method to create a processor parameter
     pub fn new(id: u32, queue: mpsc::Sender<InternalMsg<M>>, main: Main<M>) -> ProcParam<M> {
-        ProcParam { id, queue, main }
+        ProcParam {
+            id,
+            group: OnceLock::new(),
+            threads: None,
+            shutdown_rank: 0,
+            watchdog: None,
+            shed_policy: None,
+            runtime_metrics_interval: None,
+            queue,
+            ctrl_queue: None,
+            transport_queue: None,
+            main,
+            next_queue_id: Arc::new(AtomicU32::new(PRIMARY_QUEUE_ID + 1)),
+            scheduler: Arc::new(OnceLock::new()),
+        }
+    }
+
+    /// Getter of the processor group (if the processor belong to one)
+    pub fn get_group(&self) -> Option<&String> {
+        self.group.get()
+    }
+
+    /// Setter of the processor group. Processors of a group can be stopped/restarted together
+    ///
+    /// The group is set once, when the processor is created (see [`ProcConfig::create_with_group`])
+    pub fn set_group(&self, group: Option<String>) {
+        if let Some(group) = group {
+            if let Err(group) = self.group.set(group) {
+                warn!(
+                    "The processor {} already belong to the group {:?}, it can't join the group {}",
+                    self.id,
+                    self.group.get(),
+                    group
+                );
+            }
+        }
+    }
+
+    /// Getter of the processor runtime threads number from its settings (if configured)
+    pub fn get_threads(&self) -> Option<usize> {
+        self.threads
+    }
+
+    /// Setter of the processor runtime threads number from its settings. Override [`Proc::get_proc_threads`] if set
+    pub fn set_threads(&mut self, threads: Option<usize>) {
+        self.threads = threads;
+    }
+
+    /// Getter of the processor rank in the ProSA shutdown sequence
+    pub fn get_shutdown_rank(&self) -> u32 {
+        self.shutdown_rank
+    }
+
+    /// Setter of the processor rank in the ProSA shutdown sequence.
+    /// Processors are stopped by ascending rank, a rank being stopped once all the processors of the previous ones are
+    pub fn set_shutdown_rank(&mut self, shutdown_rank: u32) {
+        self.shutdown_rank = shutdown_rank;
+    }
+
+    /// Getter of the processor watchdog settings
+    pub fn get_watchdog(&self) -> Option<&WatchdogSettings> {
+        self.watchdog.as_ref()
+    }
+
+    /// Setter of the processor watchdog settings
+    pub fn set_watchdog(&mut self, watchdog: Option<WatchdogSettings>) {
+        self.watchdog = watchdog;
+    }
+
+    /// Getter of the processor load shedding policy
+    pub fn get_shed_policy(&self) -> Option<&ShedPolicy> {
+        self.shed_policy.as_ref()
+    }
+
+    /// Setter of the processor load shedding policy, applied by its receiver queue created with [`ProcRxQueue::new_proc`]
+    pub fn set_shed_policy(&mut self, shed_policy: Option<ShedPolicy>) {
+        self.shed_policy = shed_policy;
+    }
+
+    /// Getter of the processor runtime metrics sampling interval (if configured)
+    pub fn get_runtime_metrics_interval(&self) -> Option<Duration> {
+        self.runtime_metrics_interval
+    }
+
+    /// Setter of the processor runtime metrics sampling interval.
+    /// The runtime metrics are only sampled with the `runtime-metrics` feature
+    pub fn set_runtime_metrics_interval(&mut self, interval: Option<Duration>) {
+        self.runtime_metrics_interval = interval.filter(|i| !i.is_zero());
+    }
+
+    /// Method to get a guard that forgets the processor queues registered on the main bus if the processor panics
+    pub fn panic_guard(&self) -> ProcPanicGuard {
+        self.main.panic_guard(self.id)
+    }
+
+    /// Method to get the counter of the processors stopped on an error (`prosa_processors{type="crashed"}`)
+    pub fn crash_counter(&self, name: &str) -> ProcCrashCounter {
+        self.meter(name.to_string())
+            .u64_counter("prosa_processors")
+            .with_description("Processors stopped on an error")
+            .init()
+    }
+
+    /// Method to get the sampler of the processor runtime metrics if it's configured
+    pub fn runtime_metrics_sampler(&self, name: &str) -> Option<RuntimeMetricsSampler> {
+        self.runtime_metrics_interval
+            .map(|interval| RuntimeMetricsSampler {
+                meter: self.meter(name.to_string()),
+                interval,
+                proc_name: name.to_string(),
+            })
+    }
+
+    /// Method to start the processor watchdog if it's configured.
+    /// The processor group is restarted on a violation if the watchdog `restart` setting is set and the processor belong to a group
+    pub fn start_watchdog(&self, name: &str) -> Option<Watchdog> {
+        let settings = self.watchdog.clone()?;
+        let restart = settings.restart;
+        let mut watchdog =
+            Watchdog::new(name.to_string(), settings).meter(&self.meter(name.to_string()));
+        if restart {
+            if let Some(group) = self.group.get() {
+                watchdog = watchdog.restart(self.main.clone(), group.clone());
+            } else {
+                warn!(name: "proc_watchdog", target: "prosa::core::proc", proc_name = name, "The processor can't be restarted by its watchdog without group");
+            }
+        }
+
+        Some(watchdog.start())
     }
 
     /// Getter of the processor service queue to send internal messages
@@ -256,13 +704,47 @@ where
         self.queue.clone()
     }
 
+    /// Method to create a request to a service, with its response returned to the processor service queue.
+    /// The request carries the processor as origin, so its response still reach the processor if its queue is recreated (see [`RequestMsg::with_origin`])
+    pub fn new_request(&self, msg_id: u64, service_name: String, data: M) -> RequestMsg<M> {
+        RequestMsg::new(msg_id, service_name, data, self.queue.clone())
+            .with_origin(self.id, PRIMARY_QUEUE_ID)
+    }
+
+    /// Getter of the processor control queue, if the processor split its inbox
+    pub fn get_ctrl_queue(&self) -> Option<mpsc::Sender<InternalMsg<M>>> {
+        self.ctrl_queue.clone()
+    }
+
+    /// Setter of the processor control queue (done by the macro `proc` with its `ctrl_queue_size` argument).
+    /// Once set, control messages ([`InternalMsg::is_control`]) are sent to the processor through it (see [`ProcService::send`]), and must be received with [`ProcRxQueue::set_ctrl_queue`].
+    /// Must be set before the processor declaration with [`ProcParam::add_proc`]
+    pub fn set_ctrl_queue(&mut self, ctrl_queue: Option<mpsc::Sender<InternalMsg<M>>>) {
+        self.ctrl_queue = ctrl_queue;
+    }
+
+    /// Getter of the processor transport queue, if its data messages don't go through its service queue
+    pub fn get_transport_queue(&self) -> Option<TransportSender<InternalMsg<M>>> {
+        self.transport_queue.clone()
+    }
+
+    /// Setter of the processor transport queue (done by the macro `proc` with the `transport` processor setting).
+    /// Once set, data messages are sent to the processor through it (see [`ProcService::send`]), and must be received with [`ProcRxQueue::set_transport_queue`].
+    /// Must be set before the processor declaration with [`ProcParam::add_proc`]
+    pub fn set_transport_queue(
+        &mut self,
+        transport_queue: Option<TransportSender<InternalMsg<M>>>,
+    ) {
+        self.transport_queue = transport_queue;
+    }
+
     /// Method to declare the processor with a signal queue to the main task
     ///
     /// Should be called only once at the processor start
     /// After the declaration, the main task will send the service table to the processor
     pub async fn add_proc(&self) -> Result<(), BusError> {
         self.main
-            .add_proc_queue(ProcService::new_proc(self, 0))
+            .add_proc_queue(ProcService::new_proc(self, PRIMARY_QUEUE_ID))
             .await?;
         Ok(())
     }
@@ -275,15 +757,80 @@ where
         Ok(())
     }
 
+    /// Method to report the lifecycle state of the processor to the main task
+    pub async fn set_state(&self, state: ProcLifecycle) -> Result<(), BusError> {
+        self.main.set_proc_state(self.id, state).await
+    }
+
+    /// Method to report to the main task that the processor is ready to serve (once declared with [`ProcParam::add_proc`])
+    ///
+    /// Every processor must call it, otherwise the processors that depend on it are never launched: they time out waiting for it, and the ProSA is stopped (see [`Main::wait_proc_ready`](super::main::Main::wait_proc_ready)).
+    pub async fn set_ready(&self) -> Result<(), BusError> {
+        self.set_state(ProcLifecycle::Ready).await
+    }
+
+    /// Method to report to the main task that the processor can't serve normally
+    pub async fn set_degraded<S>(&self, reason: S) -> Result<(), BusError>
+    where
+        S: Into<String>,
+    {
+        self.set_state(ProcLifecycle::Degraded {
+            reason: reason.into(),
+        })
+        .await
+    }
+
+    /// Method to report to the main task that the processor is stopping
+    pub async fn set_stopping(&self) -> Result<(), BusError> {
+        self.set_state(ProcLifecycle::Stopping).await
+    }
+
+    /// Method to allocate a queue id for an additional queue of the processor
+    ///
+    /// The allocated id is never the [`PRIMARY_QUEUE_ID`], and is not registered by the processor yet.
+    pub fn next_queue_id(&self) -> u32 {
+        loop {
+            let queue_id = self.next_queue_id.fetch_add(1, Ordering::Relaxed);
+            if queue_id != PRIMARY_QUEUE_ID && !self.main.is_queue_registered(self.id, queue_id) {
+                return queue_id;
+            }
+        }
+    }
+
     /// Method to declare the processor with multiple queue identify with a queue id to the main task
     ///
-    /// Should be called as many as queue but queue id must be unique per processor
+    /// Should be called as many as queue but queue id must be unique per processor (use [`ProcParam::next_queue_id`] to allocate one).
+    /// The id [`PRIMARY_QUEUE_ID`] is reserved for the queue declared by [`ProcParam::add_proc`].
+    /// If the queue id is already registered, a [`BusError::DuplicateQueue`] is returned and the queue is not declared.
     /// After the declaration, the main task will send the service table to the processor
+    ///
+    /// The queue should be received through a [`ProcRxQueue::new_proc_queue`], to answer the heartbeats of the main task (otherwise the queue is evicted as unresponsive).
+    ///
+    /// ```
+    /// use prosa::core::error::BusError;
+    /// use prosa::core::proc::{ProcParam, ProcRxQueue};
+    /// use prosa_utils::msg::tvf::Tvf;
+    /// use tokio::sync::mpsc;
+    ///
+    /// async fn add_queue<M>(proc: &ProcParam<M>) -> Result<ProcRxQueue<M>, BusError>
+    /// where
+    ///     M: Sized + Clone + std::fmt::Debug + Tvf + Default + 'static + Send + Sync,
+    /// {
+    ///     let (queue, rx) = mpsc::channel(2048);
+    ///     let queue_id = proc.next_queue_id();
+    ///     proc.add_proc_queue(queue, queue_id).await?;
+    ///     Ok(ProcRxQueue::new_proc_queue(rx, proc, queue_id))
+    /// }
+    /// ```
     pub async fn add_proc_queue(
         &self,
         queue: mpsc::Sender<InternalMsg<M>>,
         queue_id: u32,
     ) -> Result<(), BusError> {
+        if queue_id == PRIMARY_QUEUE_ID {
+            return Err(BusError::DuplicateQueue(self.id, queue_id));
+        }
+
         self.main
             .add_proc_queue(ProcService::new(self, queue, queue_id))
             .await?;
@@ -330,6 +877,67 @@ where
         Ok(())
     }
 
+    /// Method to answer a heartbeat ping of the main task received on a processor queue
+    pub async fn pong(&self, queue_id: u32, seq: u64) -> Result<(), BusError> {
+        self.main.pong(self.id, queue_id, seq).await
+    }
+
+    /// Method to send a request to a service after a delay, without holding a task per request
+    ///
+    /// The request is sent from the processor timer queue, with a message id allocated by the processor like for its other requests.
+    /// The processor serving the service is resolved when the request is sent, and its response (or error) is returned to the processor queue.
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use prosa::core::proc::ProcParam;
+    /// use prosa_utils::msg::tvf::Tvf;
+    ///
+    /// fn retry_later<M>(proc: &ProcParam<M>, msg_id: u64, request: M)
+    /// where
+    ///     M: Sized + Clone + std::fmt::Debug + Tvf + Default + 'static + Send + Sync,
+    /// {
+    ///     let handle = proc.send_after(Duration::from_secs(30), msg_id, String::from("SERVICE"), request);
+    ///     assert!(handle.is_pending());
+    /// }
+    /// ```
+    pub fn send_after(
+        &self,
+        delay: Duration,
+        msg_id: u64,
+        service_name: String,
+        msg: M,
+    ) -> ScheduleHandle {
+        self.send_at(
+            tokio::time::Instant::now() + delay,
+            msg_id,
+            service_name,
+            msg,
+        )
+    }
+
+    /// Method to send a request to a service at a given instant (see [`ProcParam::send_after`])
+    pub fn send_at(
+        &self,
+        instant: tokio::time::Instant,
+        msg_id: u64,
+        service_name: String,
+        msg: M,
+    ) -> ScheduleHandle {
+        self.scheduler
+            .get_or_init(|| Scheduler::new(self.id, self.main.clone(), self.queue.clone()))
+            .schedule(instant, msg_id, service_name, msg)
+    }
+
+    /// Method to stop the whole ProSA (all its processors) with a reason
+    pub async fn stop_prosa(&self, reason: String) -> Result<(), BusError> {
+        self.main.stop(reason).await
+    }
+
+    /// Method to forward a message that can't be delivered or processed to the dead letter service (see [`Main::dead_letter`])
+    pub async fn dead_letter(&self, letter: DeadLetter<M>) {
+        self.main.dead_letter(letter).await
+    }
+
     /// Provide the ProSA name based on ProSA settings
     pub fn name(&self) -> &String {
         self.main.name()
@@ -340,6 +948,48 @@ where
         self.main.meter(name)
     }
 
+    /// Provide a handle on the registry of the prometheus exporter (if configured)
+    ///
+    /// Processors can register their own prometheus collectors (cache statistics for example) to expose them with the ProSA metrics.
+    /// The registry is thread safe and shared by all the processors, so collector names must be unique in the ProSA.
+    ///
+    /// ```
+    /// use prosa::core::proc::ProcParam;
+    /// use prosa_utils::msg::tvf::Tvf;
+    ///
+    /// fn register_cache_size<M>(proc: &ProcParam<M>) -> Result<(), prometheus::Error>
+    /// where
+    ///     M: Sized + Clone + std::fmt::Debug + Tvf + Default + 'static + Send + Sync,
+    /// {
+    ///     if let Some(registry) = proc.prometheus_registry() {
+    ///         let cache_size = prometheus::IntGauge::new("my_cache_size", "Size of my cache")?;
+    ///         registry.register(Box::new(cache_size.clone()))?;
+    ///         cache_size.set(42);
+    ///     }
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn prometheus_registry(&self) -> Option<prometheus::Registry> {
+        self.main.get_prometheus_registry().cloned()
+    }
+
+    /// Getter of the maximum size of the messages exchanged on the bus (from the ProSA settings)
+    pub fn message_size_limit(&self) -> &MessageSizeLimit {
+        self.main.get_message_size_limit()
+    }
+
+    /// Provide a handle on a namespace of the state store, if the state store is configured (`state` setting)
+    ///
+    /// Use a namespace specific to the processor (its name for example), so its keys don't collide with other processors.
+    /// Return a [`StateError`] if the namespace is not valid (see [`StateHandle::new`])
+    pub fn state(&self, namespace: &str) -> Result<Option<StateHandle>, StateError> {
+        self.main
+            .get_state_store()
+            .map(|store| StateHandle::new(store.clone(), namespace))
+            .transpose()
+    }
+
     /// Provide the opentelemetry Logger based on ProSA settings
     pub fn logger(&self, name: impl Into<Cow<'static, str>>) -> opentelemetry_sdk::logs::Logger {
         self.main.logger(name)
@@ -351,6 +1001,394 @@ where
     }
 }
 
+/// Standard metrics of a processor internal queue
+struct ProcQueueMetrics {
+    attributes: Vec<KeyValue>,
+    messages: Counter<u64>,
+    handling_duration: Histogram<f64>,
+    expired_requests: Counter<u64>,
+    shed_requests: Counter<u64>,
+    batch_size: Histogram<u64>,
+    _queue_depth: ObservableGauge<u64>,
+}
+
+/// Method to receive from an optional queue, pending forever without queue
+async fn recv_opt<T>(queue: Option<&mut mpsc::Receiver<T>>) -> Option<T> {
+    match queue {
+        Some(queue) => queue.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Method to receive from an optional transport queue, pending forever without queue
+async fn recv_transport<T>(queue: Option<&mut TransportReceiver<T>>) -> Option<T>
+where
+    T: Send + Debug,
+{
+    match queue {
+        Some(queue) => queue.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Receiver queue of the processor internal messages
+///
+/// Once its metrics are initialized (done by the macro `proc` at the start of [`Proc::internal_run`]), it measures for the processor:
+/// - `prosa_proc_queue_depth`: the number of messages pending in the internal queue
+/// - `prosa_proc_messages`: the number of internal messages processed, by type
+/// - `prosa_proc_handling_duration`: the handling duration of internal messages, by type.
+///   The handling of a message is considered done when the next message is requested.
+/// - `prosa_proc_expired_requests`: the number of expired requests dropped by the processor, by service (see [`ProcRxQueue::record_expired`])
+/// - `prosa_proc_shed_requests`: the number of requests shed by the queue, by service (see [`ShedPolicy`])
+/// - `prosa_proc_batch_size`: the number of messages of the received batches, by type
+///
+/// Heartbeat pings of the main task are never returned to the processor.
+/// When the queue is created with [`ProcRxQueue::new_proc`] (done by the macro `proc`) or [`ProcRxQueue::new_proc_queue`] for additional queues, they are answered transparently for the queue.
+///
+/// Batches of messages ([`InternalMsg::RequestBatch`] and [`InternalMsg::ResponseBatch`]) are split and returned one message at a time, unless the processor consumes them as a whole (see [`ProcRxQueue::set_batch_consumer`]).
+///
+/// The requests are shed according to the processor load shedding policy (see [`ShedPolicy`]): they are answered with a [`ServiceError::Unavailable`] and never returned to the processor.
+/// Batches consumed as a whole are never shed.
+///
+/// If the processor split its inbox (see [`ProcRxQueue::set_ctrl_queue`]), control messages ([`InternalMsg::is_control`]) are always returned before the pending data messages.
+///
+/// If the processor has a transport queue (see [`ProcRxQueue::set_transport_queue`]), its messages are merged with the ones of the processor queue.
+pub struct ProcRxQueue<M>
+where
+    M: Sized + Clone + Tvf,
+{
+    queue: mpsc::Receiver<InternalMsg<M>>,
+    ctrl_queue: Option<mpsc::Receiver<InternalMsg<M>>>,
+    transport_queue: Option<TransportReceiver<InternalMsg<M>>>,
+    proc: Option<ProcParam<M>>,
+    queue_id: u32,
+    routes: Option<watch::Receiver<Arc<ServiceTable<M>>>>,
+    metrics: Option<ProcQueueMetrics>,
+    handling_msg: Option<(&'static str, Instant)>,
+    batch_consumer: bool,
+    pending: VecDeque<InternalMsg<M>>,
+    shed_policy: ShedPolicy,
+}
+
+impl<M> ProcRxQueue<M>
+where
+    M: Sized + Clone + Debug + Tvf + Default + 'static + std::marker::Send + std::marker::Sync,
+{
+    /// Method to create a processor receiver queue from a tokio receiver
+    pub fn new(queue: mpsc::Receiver<InternalMsg<M>>) -> ProcRxQueue<M> {
+        ProcRxQueue {
+            queue,
+            ctrl_queue: None,
+            transport_queue: None,
+            proc: None,
+            queue_id: PRIMARY_QUEUE_ID,
+            routes: None,
+            metrics: None,
+            handling_msg: None,
+            batch_consumer: false,
+            pending: VecDeque::new(),
+            shed_policy: ShedPolicy::None,
+        }
+    }
+
+    /// Method to create the receiver queue of a processor, that answer the main task heartbeats for the processor primary queue
+    pub fn new_proc(queue: mpsc::Receiver<InternalMsg<M>>, proc: &ProcParam<M>) -> ProcRxQueue<M> {
+        Self::new_proc_queue(queue, proc, PRIMARY_QUEUE_ID)
+    }
+
+    /// Method to create the receiver queue of an additional processor queue (declared with [`ProcParam::add_proc_queue`]), that answer the main task heartbeats for this queue
+    pub fn new_proc_queue(
+        queue: mpsc::Receiver<InternalMsg<M>>,
+        proc: &ProcParam<M>,
+        queue_id: u32,
+    ) -> ProcRxQueue<M> {
+        ProcRxQueue {
+            queue,
+            ctrl_queue: None,
+            transport_queue: None,
+            proc: Some(proc.clone()),
+            queue_id,
+            routes: Some(proc.main.watch_service_table()),
+            metrics: None,
+            handling_msg: None,
+            batch_consumer: false,
+            pending: VecDeque::new(),
+            shed_policy: proc.get_shed_policy().cloned().unwrap_or_default(),
+        }
+    }
+
+    /// Method to register the standard metrics of the processor queue (with the processor name as attribute)
+    pub fn init_metrics(&mut self, proc: &ProcParam<M>, name: &str) {
+        let meter = proc.meter("prosa_proc_meter");
+        let attributes = vec![
+            KeyValue::new("proc", name.to_string()),
+            KeyValue::new("proc_id", proc.get_proc_id() as i64),
+        ];
+
+        let weak_queue = proc.get_service_queue().downgrade();
+        let gauge_attributes = attributes.clone();
+        let queue_depth = meter
+            .u64_observable_gauge("prosa_proc_queue_depth")
+            .with_description("Number of messages pending in the processor internal queue")
+            .with_callback(move |observer| {
+                if let Some(queue) = weak_queue.upgrade() {
+                    observer.observe(
+                        (queue.max_capacity() - queue.capacity()) as u64,
+                        &gauge_attributes,
+                    );
+                }
+            })
+            .init();
+
+        self.metrics = Some(ProcQueueMetrics {
+            attributes,
+            messages: meter
+                .u64_counter("prosa_proc_messages")
+                .with_description("Internal messages processed by the processor")
+                .init(),
+            handling_duration: meter
+                .f64_histogram("prosa_proc_handling_duration")
+                .with_description("Handling duration of the processor internal messages")
+                .with_unit("s")
+                .init(),
+            expired_requests: meter
+                .u64_counter("prosa_proc_expired_requests")
+                .with_description("Expired requests dropped by the processor")
+                .init(),
+            shed_requests: meter
+                .u64_counter("prosa_proc_shed_requests")
+                .with_description("Requests shed by the processor queue")
+                .init(),
+            batch_size: meter
+                .u64_histogram("prosa_proc_batch_size")
+                .with_description("Number of messages of the batches received by the processor")
+                .init(),
+            _queue_depth: queue_depth,
+        });
+    }
+
+    /// Method to count an expired request dropped by the processor
+    pub fn record_expired(&self, service: &str) {
+        if let Some(metrics) = &self.metrics {
+            let mut attributes = metrics.attributes.clone();
+            attributes.push(KeyValue::new("service", service.to_string()));
+            metrics.expired_requests.add(1, &attributes);
+        }
+    }
+
+    /// Method to count a request shed by the queue
+    fn record_shed(&self, service: &str) {
+        if let Some(metrics) = &self.metrics {
+            let mut attributes = metrics.attributes.clone();
+            attributes.push(KeyValue::new("service", service.to_string()));
+            metrics.shed_requests.add(1, &attributes);
+        }
+    }
+
+    /// Setter of the load shedding policy of the queue (the processor policy for queues created with [`ProcRxQueue::new_proc`])
+    pub fn set_shed_policy(&mut self, shed_policy: ShedPolicy) {
+        self.shed_policy = shed_policy;
+    }
+
+    /// Setter of the control queue, to receive the control messages before the data messages.
+    /// Its sender must be given to the processor with [`ProcParam::set_ctrl_queue`] (done by the macro `proc` with its `ctrl_queue_size` argument)
+    pub fn set_ctrl_queue(&mut self, ctrl_queue: mpsc::Receiver<InternalMsg<M>>) {
+        self.ctrl_queue = Some(ctrl_queue);
+    }
+
+    /// Setter of the transport queue, to receive the data messages of the processor transport (see [`TransportSettings`]).
+    /// Its sender must be given to the processor with [`ProcParam::set_transport_queue`] (done by the macro `proc` with the `transport` processor setting)
+    pub fn set_transport_queue(&mut self, transport_queue: TransportReceiver<InternalMsg<M>>) {
+        self.transport_queue = Some(transport_queue);
+    }
+
+    /// Getter of the number of data messages pending in the queue
+    pub fn depth(&self) -> usize {
+        self.queue.len() + self.pending.len() + self.transport_queue.as_ref().map_or(0, |t| t.len())
+    }
+
+    /// Setter to consume the batches of messages as a whole.
+    /// By default batches are split, so processors that don't handle them receive their messages one by one
+    pub fn set_batch_consumer(&mut self, batch_consumer: bool) {
+        self.batch_consumer = batch_consumer;
+    }
+
+    /// Method to know if the batches of messages are consumed as a whole by the processor
+    pub fn is_batch_consumer(&self) -> bool {
+        self.batch_consumer
+    }
+
+    /// Method to record the size of a received batch
+    fn record_batch(&self, msg_type: &'static str, size: usize) {
+        if let Some(metrics) = &self.metrics {
+            let mut attributes = metrics.attributes.clone();
+            attributes.push(KeyValue::new("type", msg_type));
+            metrics.batch_size.record(size as u64, &attributes);
+        }
+    }
+
+    /// Receives the next internal message of the processor (see [`mpsc::Receiver::recv`])
+    pub async fn recv(&mut self) -> Option<InternalMsg<M>> {
+        if let Some(metrics) = &self.metrics {
+            if let Some((msg_type, begin)) = self.handling_msg.take() {
+                let mut attributes = metrics.attributes.clone();
+                attributes.push(KeyValue::new("type", msg_type));
+                metrics
+                    .handling_duration
+                    .record(begin.elapsed().as_secs_f64(), &attributes);
+            }
+        }
+
+        let msg = loop {
+            let mut msg = if let Some(msg) = self
+                .ctrl_queue
+                .as_mut()
+                .and_then(|ctrl_queue| ctrl_queue.try_recv().ok())
+            {
+                msg
+            } else if let Some(msg) = self.pending.pop_front() {
+                msg
+            } else if self.ctrl_queue.is_some() || self.transport_queue.is_some() {
+                // The processor queue is polled before the transport one, to never starve its responses and control messages
+                tokio::select! {
+                    biased;
+                    Some(msg) = recv_opt(self.ctrl_queue.as_mut()) => msg,
+                    msg = self.queue.recv() => match msg {
+                        Some(msg) => msg,
+                        None => break None,
+                    },
+                    Some(msg) = recv_transport(self.transport_queue.as_mut()) => msg,
+                }
+            } else {
+                match self.queue.recv().await {
+                    Some(msg) => msg,
+                    None => break None,
+                }
+            };
+
+            // Received requests route their responses with the live service table if their response queue is closed
+            if let Some(routes) = &self.routes {
+                match &mut msg {
+                    InternalMsg::Request(request) => request.set_routes(routes),
+                    InternalMsg::RequestBatch(batch) => batch
+                        .iter_mut()
+                        .for_each(|request| request.set_routes(routes)),
+                    _ => {}
+                }
+            }
+
+            match msg {
+                InternalMsg::Ping(seq) => {
+                    if let Some(proc) = &self.proc {
+                        if let Err(e) = proc.pong(self.queue_id, seq).await {
+                            debug!(
+                                "The processor {}/{} can't answer the heartbeat: {}",
+                                proc.get_proc_id(),
+                                self.queue_id,
+                                e
+                            );
+                        }
+                    }
+                }
+                InternalMsg::Request(msg)
+                    if self.shed_policy.should_shed(self.depth(), msg.get_data()) =>
+                {
+                    self.record_shed(msg.get_service());
+                    let service = msg.get_service().clone();
+                    if let Err(e) = msg
+                        .return_error_to_sender(None, ServiceError::Unavailable(service, None))
+                        .await
+                    {
+                        debug!("The shed request can't be answered: {}", e);
+                    }
+                }
+                InternalMsg::RequestBatch(batch) if !self.batch_consumer => {
+                    self.record_batch("request_batch", batch.len());
+                    self.pending
+                        .extend(batch.into_iter().map(InternalMsg::Request));
+                }
+                InternalMsg::ResponseBatch(batch) if !self.batch_consumer => {
+                    self.record_batch("response_batch", batch.len());
+                    self.pending
+                        .extend(batch.into_iter().map(InternalMsg::Response));
+                }
+                InternalMsg::RequestBatch(batch) => {
+                    self.record_batch("request_batch", batch.len());
+                    break Some(InternalMsg::RequestBatch(batch));
+                }
+                InternalMsg::ResponseBatch(batch) => {
+                    self.record_batch("response_batch", batch.len());
+                    break Some(InternalMsg::ResponseBatch(batch));
+                }
+                msg => break Some(msg),
+            }
+        };
+
+        if let (Some(metrics), Some(msg)) = (&self.metrics, &msg) {
+            let msg_type = internal_msg_type(msg);
+            let mut attributes = metrics.attributes.clone();
+            attributes.push(KeyValue::new("type", msg_type));
+            metrics.messages.add(1, &attributes);
+            self.handling_msg = Some((msg_type, Instant::now()));
+        }
+
+        msg
+    }
+}
+
+/// Getter of the type name of an internal message (use as metric attribute)
+fn internal_msg_type<M>(msg: &InternalMsg<M>) -> &'static str
+where
+    M: Sized + Clone + Tvf,
+{
+    match msg {
+        InternalMsg::Request(_) => "request",
+        InternalMsg::RequestBatch(_) => "request_batch",
+        InternalMsg::Response(_) => "response",
+        InternalMsg::ResponseBatch(_) => "response_batch",
+        InternalMsg::Error(_) => "error",
+        InternalMsg::Command(_) => "command",
+        InternalMsg::Config(_) => "config",
+        InternalMsg::Service(_) => "service",
+        InternalMsg::Shutdown => "shutdown",
+        InternalMsg::Ping(_) => "ping",
+    }
+}
+
+impl<M> Deref for ProcRxQueue<M>
+where
+    M: Sized + Clone + Tvf,
+{
+    type Target = mpsc::Receiver<InternalMsg<M>>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.queue
+    }
+}
+
+impl<M> DerefMut for ProcRxQueue<M>
+where
+    M: Sized + Clone + Tvf,
+{
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.queue
+    }
+}
+
+impl<M> Debug for ProcRxQueue<M>
+where
+    M: Sized + Clone + Tvf,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProcRxQueue")
+            .field("queue", &self.queue)
+            .field("ctrl_queue", &self.ctrl_queue)
+            .field("transport_queue", &self.transport_queue)
+            .field("metrics", &self.metrics.is_some())
+            .finish()
+    }
+}
+
 /// Trait to define ProSA processor configuration
 ///
 /// Define by the macro `proc`
@@ -371,6 +1409,21 @@ where
     /// Method to create a processor out of it's configuration
     fn create(proc_id: u32, main: Main<M>, settings: Self::Settings) -> Self;
 
+    /// Method to create a processor out of it's configuration, as a member of a processor group
+    fn create_with_group(
+        proc_id: u32,
+        main: Main<M>,
+        settings: Self::Settings,
+        group: Option<String>,
+    ) -> Self
+    where
+        Self: Sized,
+    {
+        let proc = Self::create(proc_id, main, settings);
+        proc.get_proc_param().set_group(group);
+        proc
+    }
+
     /// Method to create a processor with not specific configuration
     fn create_raw(proc_id: u32, main: Main<M>) -> Self
     where
@@ -406,14 +1459,54 @@ pub trait Proc<A>
 where
     A: Adaptor,
 {
+    /// Maximum number of threads of a processor runtime
+    const MAX_THREADS: usize = 512;
+
     /// Main loop of the processor
     fn internal_run(
         &mut self,
         name: String,
     ) -> impl std::future::Future<Output = Result<(), Box<dyn std::error::Error>>> + Send;
 
+    /// Number of threads of the processor runtime (1 for a current thread runtime)
+    ///
+    /// Can be overridden by the processor, but the `threads` processor setting take precedence.
+    fn get_proc_threads(&self) -> usize {
+        1
+    }
+
+    /// Number of threads of the processor runtime given by its settings (`0` to use [`Proc::get_proc_threads`])
+    ///
+    /// Define by the macro `proc`
+    fn get_settings_threads(&self) -> Option<usize> {
+        None
+    }
+
+    /// Sampler of the processor runtime metrics, if configured by its settings
+    ///
+    /// Define by the macro `proc`
+    fn get_runtime_metrics_sampler(&self, _name: &str) -> Option<RuntimeMetricsSampler> {
+        None
+    }
+
+    /// Guard that forgets the processor queues registered on the main bus if the processor panics, kept while the processor runs
+    ///
+    /// Define by the macro `proc`
+    fn get_panic_guard(&self) -> Option<ProcPanicGuard> {
+        None
+    }
+
+    /// Counter of the processors stopped on an error (`prosa_processors{type="crashed"}`), with the error code
+    ///
+    /// Define by the macro `proc`
+    fn get_crash_counter(&self, _name: &str) -> Option<ProcCrashCounter> {
+        None
+    }
+
     /// Method to run the processor
     ///
+    /// If the processor stops on an error, the error is logged with its code and its chain of causes, and counted by the crash counter ([`Proc::get_crash_counter`])
+    ///
     /// ```
     /// use prosa::core::proc::Proc;
     /// use prosa::core::adaptor::Adaptor;
@@ -430,23 +1523,118 @@ where
     where
         Self: Sized + 'static + std::marker::Send,
     {
+        let threads = match self.get_settings_threads() {
+            Some(0) | None => self.get_proc_threads(),
+            Some(threads) if threads <= Self::MAX_THREADS => threads,
+            Some(threads) => panic!(
+                "The processor {} `threads` setting ({}) must be in the range 0..={}",
+                proc_name,
+                threads,
+                Self::MAX_THREADS
+            ),
+        };
+        #[cfg(feature = "runtime-metrics")]
+        let runtime_metrics_sampler = self.get_runtime_metrics_sampler(&proc_name);
+        let panic_guard = self.get_panic_guard();
+        let crash_counter = self.get_crash_counter(&proc_name);
+
         std::thread::Builder::new()
             .name(proc_name.clone())
             .spawn(move || {
-                let rt: runtime::Runtime = runtime::Builder::new_current_thread()
+                let mut rt_builder = if threads > 1 {
+                    let mut rt_builder = runtime::Builder::new_multi_thread();
+                    rt_builder.worker_threads(threads);
+                    rt_builder
+                } else {
+                    runtime::Builder::new_current_thread()
+                };
+                let rt: runtime::Runtime = rt_builder
                     .enable_all()
                     .thread_name(proc_name.clone())
                     .build()
                     .unwrap();
-                rt.block_on(self.internal_run(proc_name)).unwrap();
+                #[cfg(feature = "runtime-metrics")]
+                if let Some(sampler) = runtime_metrics_sampler {
+                    rt.spawn(sampler.run(rt.metrics()));
+                }
+                if let Err(e) = rt.block_on(self.internal_run(proc_name.clone())) {
+                    let (error_code, chain) = crash_report(e.as_ref());
+                    error!(name: "proc_crash", target: "prosa::core::proc", proc_name = proc_name, error_code = error_code, "The processor stopped on an error: {}", chain);
+                    if let Some(crash_counter) = crash_counter {
+                        crash_counter.add(
+                            1,
+                            &[
+                                KeyValue::new("type", "crashed"),
+                                KeyValue::new("proc_name", proc_name),
+                                KeyValue::new("error_code", error_code as i64),
+                            ],
+                        );
+                    }
+                    if let Some(panic_guard) = panic_guard {
+                        panic_guard.crashed();
+                    }
+                }
             })
             .unwrap();
     }
 }
 
+/// Sampler of a processor runtime metrics (workers, alive tasks, injection queue depth)
+///
+/// The metrics are only exported with the `runtime-metrics` feature, labeled with the processor name
+#[cfg_attr(not(feature = "runtime-metrics"), allow(dead_code))]
+pub struct RuntimeMetricsSampler {
+    meter: opentelemetry::metrics::Meter,
+    interval: Duration,
+    proc_name: String,
+}
+
+#[cfg(feature = "runtime-metrics")]
+impl RuntimeMetricsSampler {
+    /// Periodically export the metrics of a processor runtime, until the runtime is shut down
+    async fn run(self, metrics: runtime::RuntimeMetrics) {
+        let workers = self
+            .meter
+            .u64_gauge("prosa_proc_runtime_workers")
+            .with_description("Worker threads of the processor runtime")
+            .init();
+        let alive_tasks = self
+            .meter
+            .u64_gauge("prosa_proc_runtime_alive_tasks")
+            .with_description("Tasks alive in the processor runtime")
+            .init();
+        let injection_queue_depth = self
+            .meter
+            .u64_gauge("prosa_proc_runtime_injection_queue_depth")
+            .with_description("Tasks pending in the injection queue of the processor runtime")
+            .init();
+        #[cfg(all(tokio_unstable, target_has_atomic = "64"))]
+        let budget_forced_yields = self
+            .meter
+            .u64_gauge("prosa_proc_runtime_budget_forced_yields")
+            .with_description(
+                "Tasks forced to yield after exhausting their budget (tokio_unstable only)",
+            )
+            .init();
+
+        let attributes = [KeyValue::new("proc", self.proc_name)];
+        let mut ticker = tokio::time::interval(self.interval);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+        loop {
+            ticker.tick().await;
+            workers.record(metrics.num_workers() as u64, &attributes);
+            alive_tasks.record(metrics.num_alive_tasks() as u64, &attributes);
+            injection_queue_depth.record(metrics.global_queue_depth() as u64, &attributes);
+            #[cfg(all(tokio_unstable, target_has_atomic = "64"))]
+            budget_forced_yields.record(metrics.budget_forced_yield_count(), &attributes);
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::core::service::ServiceError;
     use prosa_macros::proc_settings;
     use serde::Serialize;
 
@@ -476,4 +1664,332 @@ mod tests {
         let test_proc_settings = TestProcSettings::default();
         assert_eq!("test", test_proc_settings.name);
     }
+
+    #[test]
+    fn test_proc_error_context() {
+        let err = BusError::ProcCommError(1, 0, String::from("queue closed"));
+        let err_code = err.error_code();
+        assert!(err_code > 0);
+
+        let err = err
+            .with_context("Can't notify the processor")
+            .with_context("Service table update failed");
+        assert_eq!(err_code, err.error_code());
+        assert_eq!(err.recoverable(), err.inner().recoverable());
+        assert_eq!(
+            "Service table update failed: Can't notify the processor: The Processor 1/0 can't be contacted: queue closed",
+            err.chain().to_string()
+        );
+        assert_eq!(
+            "Can't notify the processor",
+            err.context().unwrap().to_string()
+        );
+
+        let err = ServiceError::Timeout(String::from("SRV"), 200);
+        assert!(err.recoverable());
+        assert_ne!(
+            ServiceError::Unavailable(String::from("SRV"), None).error_code(),
+            err.error_code()
+        );
+        assert_eq!(
+            "The service `SRV` didn't respond before 200 ms",
+            ProcErrorChain::new(&err).to_string()
+        );
+    }
+
+    #[test]
+    fn test_proc_crash_report() {
+        let err = BusError::ProcCommError(1, 0, String::from("queue closed"));
+        let err_code = err.error_code();
+        let err: Box<dyn std::error::Error> = Box::new(
+            err.with_context("Can't notify the processor")
+                .with_context("Service table update failed"),
+        );
+        assert_eq!(
+            (
+                err_code,
+                String::from(
+                    "Service table update failed: Can't notify the processor: The Processor 1/0 can't be contacted: queue closed"
+                )
+            ),
+            crash_report(err.as_ref())
+        );
+
+        let err: Box<dyn std::error::Error> = Box::new(std::io::Error::other("disk full"));
+        assert_eq!((0, String::from("disk full")), crash_report(err.as_ref()));
+    }
+
+    #[tokio::test]
+    async fn test_proc_rx_queue_batch() {
+        use crate::core::msg::{Msg as _, RequestMsg};
+        use prosa_utils::msg::simple_string_tvf::SimpleStringTvf;
+
+        let (tx, rx) = mpsc::channel(8);
+        let mut queue = ProcRxQueue::<SimpleStringTvf>::new(rx);
+        let batch = |ids: std::ops::Range<u64>| {
+            InternalMsg::RequestBatch(
+                ids.map(|id| {
+                    RequestMsg::new(
+                        id,
+                        String::from("SRV"),
+                        SimpleStringTvf::default(),
+                        tx.clone(),
+                    )
+                })
+                .collect(),
+            )
+        };
+
+        // Batches are split by default, empty batches are skipped
+        tx.send(batch(0..0)).await.unwrap();
+        tx.send(batch(0..3)).await.unwrap();
+        tx.send(InternalMsg::Shutdown).await.unwrap();
+        for id in 0..3 {
+            assert!(
+                matches!(queue.recv().await, Some(InternalMsg::Request(msg)) if msg.get_id() == id)
+            );
+        }
+        assert!(matches!(queue.recv().await, Some(InternalMsg::Shutdown)));
+
+        // Batches are kept for batch consumers
+        queue.set_batch_consumer(true);
+        assert!(queue.is_batch_consumer());
+        tx.send(batch(3..5)).await.unwrap();
+        assert!(
+            matches!(queue.recv().await, Some(InternalMsg::RequestBatch(batch)) if batch.len() == 2)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_proc_rx_queue_shed() {
+        use crate::core::msg::RequestMsg;
+        use prosa_utils::msg::simple_string_tvf::SimpleStringTvf;
+
+        const WATERMARK: usize = 4;
+        let (tx, rx) = mpsc::channel(64);
+        let (resp_tx, mut resp_rx) = mpsc::channel(64);
+        let mut queue = ProcRxQueue::<SimpleStringTvf>::new(rx);
+        let request = |id: u64, priority: Option<u64>| {
+            let mut data = SimpleStringTvf::default();
+            if let Some(priority) = priority {
+                data.put_unsigned(1, priority);
+            }
+            InternalMsg::Request(RequestMsg::new(
+                id,
+                String::from("SRV"),
+                data,
+                resp_tx.clone(),
+            ))
+        };
+
+        // Without policy, nothing is shed
+        for id in 0..8 {
+            tx.send(request(id, None)).await.unwrap();
+        }
+        for id in 0..8 {
+            assert!(
+                matches!(queue.recv().await, Some(InternalMsg::Request(msg)) if msg.get_id() == id)
+            );
+        }
+
+        // The oldest requests are shed until the queue depth is below the watermark
+        queue.set_shed_policy(ShedPolicy::DropOldest {
+            watermark: WATERMARK,
+        });
+        for id in 0..20 {
+            tx.send(request(id, None)).await.unwrap();
+        }
+        let mut processed = Vec::new();
+        while !queue.is_empty() {
+            match queue.recv().await {
+                Some(InternalMsg::Request(msg)) => {
+                    assert!(queue.depth() < WATERMARK);
+                    processed.push(msg.get_id());
+                }
+                msg => panic!("Unexpected message {:?}", msg),
+            }
+        }
+        assert_eq!(vec![16, 17, 18, 19], processed);
+        for id in 0..16 {
+            assert!(matches!(
+                resp_rx.try_recv(),
+                Ok(InternalMsg::Error(err)) if err.get_id() == id && matches!(err.get_err(), ServiceError::Unavailable(service, None) if service == "SRV")
+            ));
+        }
+        assert!(resp_rx.try_recv().is_err());
+
+        // Only the low priority requests are shed
+        queue.set_shed_policy(ShedPolicy::DropLowPriority {
+            watermark: WATERMARK,
+            priority_tag: 1,
+            min_priority: 5,
+        });
+        for id in 0..10 {
+            tx.send(request(id, Some(id))).await.unwrap();
+        }
+        let mut processed = Vec::new();
+        while !queue.is_empty() {
+            if let Some(InternalMsg::Request(msg)) = queue.recv().await {
+                processed.push(msg.get_id());
+            }
+        }
+        assert_eq!(vec![5, 6, 7, 8, 9], processed);
+        for id in 0..5 {
+            assert!(
+                matches!(resp_rx.try_recv(), Ok(InternalMsg::Error(err)) if err.get_id() == id)
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_proc_rx_queue_ctrl() {
+        use crate::core::main::{MainProc, MainRunnable as _};
+        use crate::core::msg::{Msg as _, RequestMsg};
+        use prosa_macros::{proc, settings};
+        use prosa_utils::msg::simple_string_tvf::SimpleStringTvf;
+
+        /// Dummy settings
+        #[settings]
+        #[derive(Default, Debug, Serialize)]
+        struct TestSettings {}
+
+        /// Processor with a control queue
+        #[proc(queue_size = 8, ctrl_queue_size = 4)]
+        struct TestCtrlProc {}
+
+        let (bus, _main) = MainProc::<SimpleStringTvf>::create(&TestSettings::default());
+        let mut proc = TestCtrlProc::<SimpleStringTvf>::create_raw(1, bus);
+        let service = ProcService::new_proc(&proc.proc, PRIMARY_QUEUE_ID);
+
+        // Saturate the data queue
+        let (resp_tx, _resp_rx) = mpsc::channel(8);
+        let mut id = 0;
+        while service
+            .try_send(InternalMsg::Request(RequestMsg::new(
+                id,
+                String::from("SRV"),
+                SimpleStringTvf::default(),
+                resp_tx.clone(),
+            )))
+            .is_ok()
+        {
+            id += 1;
+        }
+        assert_eq!(8, id);
+
+        // The shutdown must not wait for the data queue, and is received first
+        tokio::time::timeout(
+            Duration::from_millis(10),
+            service.send(InternalMsg::Shutdown),
+        )
+        .await
+        .expect("The shutdown is stuck behind data messages")
+        .unwrap();
+        assert!(matches!(
+            tokio::time::timeout(Duration::from_millis(10), proc.internal_rx_queue.recv()).await,
+            Ok(Some(InternalMsg::Shutdown))
+        ));
+        assert_eq!(8, proc.internal_rx_queue.depth());
+        for id in 0..8 {
+            assert!(
+                matches!(proc.internal_rx_queue.recv().await, Some(InternalMsg::Request(msg)) if msg.get_id() == id)
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_proc_config_group() {
+        use crate::core::main::{MainProc, MainRunnable as _};
+        use prosa_macros::settings;
+        use prosa_utils::msg::simple_string_tvf::SimpleStringTvf;
+
+        /// Dummy settings
+        #[settings]
+        #[derive(Default, Debug, Serialize)]
+        struct TestSettings {}
+
+        /// Processor configuration written without the `proc` macro
+        struct TestManualProc {
+            proc: ProcParam<SimpleStringTvf>,
+        }
+
+        impl ProcConfig<SimpleStringTvf> for TestManualProc {
+            type Settings = ();
+
+            fn create(proc_id: u32, main: Main<SimpleStringTvf>, _settings: ()) -> Self {
+                let (queue, _) = mpsc::channel(1);
+                TestManualProc {
+                    proc: ProcParam::new(proc_id, queue, main),
+                }
+            }
+
+            fn get_proc_param(&self) -> &ProcParam<SimpleStringTvf> {
+                &self.proc
+            }
+        }
+
+        let (bus, _main) = MainProc::<SimpleStringTvf>::create(&TestSettings::default());
+        let proc = TestManualProc::create_raw(1, bus.clone());
+        assert_eq!(None, proc.get_proc_param().get_group());
+        let proc = TestManualProc::create_with_group(2, bus, (), Some(String::from("test_group")));
+        assert_eq!(
+            Some(&String::from("test_group")),
+            proc.get_proc_param().get_group()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_proc_rx_queue_transport() {
+        use crate::core::msg::{Msg as _, RequestMsg};
+        use prosa_utils::msg::simple_string_tvf::SimpleStringTvf;
+
+        let (tx, rx) = mpsc::channel(8);
+        let mut queue = ProcRxQueue::<SimpleStringTvf>::new(rx);
+        let (transport_tx, transport_rx) = TransportSettings::LockFree { capacity: 8 }
+            .channel()
+            .unwrap_or_else(|| {
+                let (transport_tx, transport_rx) = mpsc::channel(8);
+                (
+                    TransportSender::Mpsc(transport_tx),
+                    TransportReceiver::Mpsc(transport_rx),
+                )
+            });
+        queue.set_transport_queue(transport_rx);
+
+        // Keep the transport queue full
+        let (resp_tx, _resp_rx) = mpsc::channel(8);
+        let producer = tokio::spawn(async move {
+            for id in 0.. {
+                let request = RequestMsg::new(
+                    id,
+                    String::from("SRV"),
+                    SimpleStringTvf::default(),
+                    resp_tx.clone(),
+                );
+                if transport_tx
+                    .send(InternalMsg::Request(request))
+                    .await
+                    .is_err()
+                {
+                    break;
+                }
+            }
+        });
+        for id in 0..16 {
+            assert!(
+                matches!(queue.recv().await, Some(InternalMsg::Request(msg)) if msg.get_id() == id)
+            );
+        }
+
+        // The shutdown of the processor queue is delivered despite the transport load
+        tx.send(InternalMsg::Shutdown).await.unwrap();
+        assert!(matches!(queue.recv().await, Some(InternalMsg::Shutdown)));
+        assert!(matches!(
+            queue.recv().await,
+            Some(InternalMsg::Request(msg)) if msg.get_id() == 16
+        ));
+
+        drop(queue);
+        producer.await.unwrap();
+    }
 }
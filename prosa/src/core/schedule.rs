@@ -0,0 +1,487 @@
+//! Module that define the scheduled sending of requests on the service bus
+//!
+//! Every processor have a single timer queue (started on its first scheduled request) that send the requests once they're due.
+//! The target processor of a scheduled request is resolved from the service table when the request is sent, not when it's scheduled.
+use std::{
+    cmp::Ordering,
+    collections::BinaryHeap,
+    fmt::Debug,
+    sync::{
+        atomic::{self, AtomicU64, AtomicU8},
+        Arc,
+    },
+};
+
+use prosa_utils::msg::tvf::Tvf;
+use tokio::{
+    sync::mpsc,
+    time::{sleep_until, Instant},
+};
+
+use super::{
+    dead_letter::DeadLetter,
+    main::Main,
+    msg::{InternalMsg, Msg as _, RequestMsg},
+    proc::PRIMARY_QUEUE_ID,
+    service::ServiceError,
+};
+
+const SCHEDULE_PENDING: u8 = 0;
+const SCHEDULE_SENT: u8 = 1;
+const SCHEDULE_CANCELLED: u8 = 2;
+
+/// Handle of a scheduled request, to cancel it before it's sent
+///
+/// The scheduled request is sent with the message id given by the processor, to correlate its response (or error) with it.
+#[derive(Debug, Clone)]
+pub struct ScheduleHandle {
+    id: u64,
+    seq: u64,
+    at: Instant,
+    state: Arc<AtomicU8>,
+    cancel_queue: mpsc::UnboundedSender<u64>,
+}
+
+impl ScheduleHandle {
+    /// Getter of the message id of the scheduled request
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Getter of the instant the request is scheduled for
+    pub fn instant(&self) -> Instant {
+        self.at
+    }
+
+    /// Method to know if the request is still waiting to be sent
+    pub fn is_pending(&self) -> bool {
+        self.state.load(atomic::Ordering::Acquire) == SCHEDULE_PENDING
+    }
+
+    /// Method to cancel the scheduled request, and remove it from the timer queue.
+    /// Return `false` if the request was already sent (or cancelled)
+    pub fn cancel(&self) -> bool {
+        let cancelled = self
+            .state
+            .compare_exchange(
+                SCHEDULE_PENDING,
+                SCHEDULE_CANCELLED,
+                atomic::Ordering::AcqRel,
+                atomic::Ordering::Acquire,
+            )
+            .is_ok();
+        if cancelled {
+            // If the timer queue is stopped, there is nothing to remove
+            let _ = self.cancel_queue.send(self.seq);
+        }
+
+        cancelled
+    }
+}
+
+/// Request waiting in the timer queue
+#[derive(Debug)]
+struct ScheduledRequest<M> {
+    handle: ScheduleHandle,
+    service: String,
+    data: M,
+}
+
+impl<M> PartialEq for ScheduledRequest<M> {
+    fn eq(&self, other: &Self) -> bool {
+        self.handle.seq == other.handle.seq
+    }
+}
+
+impl<M> Eq for ScheduledRequest<M> {}
+
+impl<M> PartialOrd for ScheduledRequest<M> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<M> Ord for ScheduledRequest<M> {
+    /// Reversed order to pop the earliest request first (and the first scheduled one for the same instant)
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .handle
+            .at
+            .cmp(&self.handle.at)
+            .then_with(|| other.handle.seq.cmp(&self.handle.seq))
+    }
+}
+
+/// Timer queue of a processor, that send its scheduled requests once they're due
+///
+/// Failures of the scheduled requests (unknown service, unavailable processor) are returned to the processor queue as [`InternalMsg::Error`].
+/// Requests to an unknown service are also forwarded to the dead letter service (see [`dead_letter`](crate::core::dead_letter)).
+#[derive(Debug)]
+pub(crate) struct Scheduler<M> {
+    queue: mpsc::UnboundedSender<ScheduledRequest<M>>,
+    cancel_queue: mpsc::UnboundedSender<u64>,
+    next_seq: AtomicU64,
+}
+
+impl<M> Scheduler<M>
+where
+    M: Sized + Clone + Debug + Tvf + Default + 'static + std::marker::Send + std::marker::Sync,
+{
+    /// Method to start the timer queue of a processor, with the queue where the responses are returned
+    pub(crate) fn new(
+        proc_id: u32,
+        main: Main<M>,
+        response_queue: mpsc::Sender<InternalMsg<M>>,
+    ) -> Scheduler<M> {
+        let (queue, queue_rx) = mpsc::unbounded_channel();
+        let (cancel_queue, cancel_queue_rx) = mpsc::unbounded_channel();
+        tokio::spawn(Self::run(
+            queue_rx,
+            cancel_queue_rx,
+            proc_id,
+            main,
+            response_queue,
+        ));
+        Scheduler {
+            queue,
+            cancel_queue,
+            next_seq: AtomicU64::new(0),
+        }
+    }
+
+    /// Method to schedule a request to a service, with the message id allocated by the processor
+    pub(crate) fn schedule(
+        &self,
+        at: Instant,
+        msg_id: u64,
+        service: String,
+        data: M,
+    ) -> ScheduleHandle {
+        let handle = ScheduleHandle {
+            id: msg_id,
+            seq: self.next_seq.fetch_add(1, atomic::Ordering::Relaxed),
+            at,
+            state: Arc::new(AtomicU8::new(SCHEDULE_PENDING)),
+            cancel_queue: self.cancel_queue.clone(),
+        };
+
+        if let Err(e) = self.queue.send(ScheduledRequest {
+            handle: handle.clone(),
+            service,
+            data,
+        }) {
+            // The timer queue is stopped, so the request will never be sent
+            e.0.handle.cancel();
+        }
+
+        handle
+    }
+
+    async fn run(
+        mut queue: mpsc::UnboundedReceiver<ScheduledRequest<M>>,
+        mut cancel_queue: mpsc::UnboundedReceiver<u64>,
+        proc_id: u32,
+        main: Main<M>,
+        response_queue: mpsc::Sender<InternalMsg<M>>,
+    ) {
+        let mut scheduled = BinaryHeap::new();
+        loop {
+            let next = scheduled
+                .peek()
+                .map(|request: &ScheduledRequest<M>| request.handle.at);
+            // Requests are read first, so a request is always scheduled before its cancellation is handled
+            tokio::select! {
+                biased;
+                request = queue.recv() => match request {
+                    Some(request) => scheduled.push(request),
+                    None => return,
+                },
+                Some(seq) = cancel_queue.recv() => {
+                    scheduled.retain(|request: &ScheduledRequest<M>| request.handle.seq != seq);
+                }
+                _ = sleep_until(next.unwrap_or_else(Instant::now)), if next.is_some() => {
+                    let now = Instant::now();
+                    while scheduled.peek().is_some_and(|request| request.handle.at <= now) {
+                        if let Some(request) = scheduled.pop() {
+                            Self::send(request, proc_id, &main, &response_queue).await;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Method to send a due request to the processor that serve its service now
+    async fn send(
+        request: ScheduledRequest<M>,
+        proc_id: u32,
+        main: &Main<M>,
+        response_queue: &mpsc::Sender<InternalMsg<M>>,
+    ) {
+        if request
+            .handle
+            .state
+            .compare_exchange(
+                SCHEDULE_PENDING,
+                SCHEDULE_SENT,
+                atomic::Ordering::AcqRel,
+                atomic::Ordering::Acquire,
+            )
+            .is_err()
+        {
+            return;
+        }
+
+        let msg_id = request.handle.id;
+        let service = request.service;
+        let request_msg = RequestMsg::new(
+            msg_id,
+            service.clone(),
+            request.data,
+            response_queue.clone(),
+        )
+        .with_origin(proc_id, PRIMARY_QUEUE_ID);
+        let service_table = main.get_service_table();
+        let _ = if let Some(proc_service) = service_table.get_proc_service(&service, msg_id) {
+            match proc_service.send_request(request_msg).await {
+                Ok(()) => Ok(()),
+                Err(e) => match e.into_inner() {
+                    InternalMsg::Request(request_msg) => {
+                        request_msg
+                            .return_error_to_sender(None, ServiceError::Unavailable(service, None))
+                            .await
+                    }
+                    _ => Ok(()),
+                },
+            }
+        } else {
+            let err = ServiceError::UnknownService(service.clone());
+            main.dead_letter(DeadLetter::new(
+                proc_id,
+                service,
+                request_msg.get_data().clone(),
+                err.clone(),
+            ))
+            .await;
+            request_msg.return_error_to_sender(None, err).await
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate self as prosa;
+
+    use std::time::Duration;
+
+    use prosa_macros::settings;
+    use prosa_utils::msg::simple_string_tvf::SimpleStringTvf;
+    use serde::Serialize;
+
+    use crate::core::{
+        main::{MainProc, MainRunnable as _},
+        proc::ProcParam,
+    };
+
+    use super::*;
+
+    const WAIT_TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// Dummy settings
+    #[settings]
+    #[derive(Default, Debug, Serialize)]
+    struct TestSettings {}
+
+    fn test_msg(value: &str) -> SimpleStringTvf {
+        let mut msg = SimpleStringTvf::default();
+        msg.put_string(1, value);
+        msg
+    }
+
+    /// Wait the next request (or error) of a processor queue, ignoring the service table updates
+    async fn next_msg(
+        queue: &mut mpsc::Receiver<InternalMsg<SimpleStringTvf>>,
+    ) -> InternalMsg<SimpleStringTvf> {
+        tokio::time::timeout(WAIT_TIMEOUT, async {
+            loop {
+                match queue.recv().await {
+                    Some(InternalMsg::Service(_)) => continue,
+                    Some(msg) => return msg,
+                    None => panic!("The processor queue is closed"),
+                }
+            }
+        })
+        .await
+        .expect("Timeout waiting for a scheduled message")
+    }
+
+    async fn wait_service_queue(main: &Main<SimpleStringTvf>, service: &str, queue_id: u32) {
+        tokio::time::timeout(WAIT_TIMEOUT, async {
+            while main
+                .get_service_table()
+                .get_proc_service(service, 0)
+                .is_none_or(|proc_service| proc_service.get_queue_id() != queue_id)
+            {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        })
+        .await
+        .expect("Timeout waiting for the service table");
+    }
+
+    #[tokio::test]
+    async fn scheduled_cancel() {
+        let (bus, main) = MainProc::<SimpleStringTvf>::create(&TestSettings::default());
+        let main_task = main.run();
+
+        let (queue, mut rx_queue) = mpsc::channel(2048);
+        let scheduler = Scheduler::new(1, bus.clone(), queue);
+
+        // Cancelled requests are removed from the timer queue, so only the last one remains
+        let handles = (0..10)
+            .map(|id| {
+                scheduler.schedule(
+                    Instant::now() + Duration::from_secs(3600),
+                    id,
+                    String::from("UNKNOWN"),
+                    test_msg("cancelled"),
+                )
+            })
+            .collect::<Vec<_>>();
+        for handle in &handles {
+            assert!(handle.cancel());
+        }
+        let due = scheduler.schedule(Instant::now(), 42, String::from("UNKNOWN"), test_msg("due"));
+        match next_msg(&mut rx_queue).await {
+            InternalMsg::Error(error) => assert_eq!(due.id(), error.get_id()),
+            msg => panic!("Unexpected message {:?}", msg),
+        }
+        assert!(rx_queue.try_recv().is_err());
+
+        // The timer queue doesn't hold the cancelled requests anymore
+        tokio::time::timeout(WAIT_TIMEOUT, async {
+            while handles
+                .iter()
+                .any(|handle| Arc::strong_count(&handle.state) > 1)
+            {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        })
+        .await
+        .expect("Cancelled requests still in the timer queue");
+
+        bus.stop("ProSA unit test end".into()).await.unwrap();
+        main_task.join().unwrap();
+    }
+
+    #[tokio::test]
+    async fn scheduled_requests() {
+        let (bus, main) = MainProc::<SimpleStringTvf>::create(&TestSettings::default());
+        let main_task = main.run();
+
+        let (client_queue, mut client_rx_queue) = mpsc::channel(2048);
+        let client = ProcParam::new(1, client_queue, bus.clone());
+        client.add_proc().await.unwrap();
+        let (server_queue, mut server_rx_queue) = mpsc::channel(2048);
+        let server = ProcParam::new(2, server_queue, bus.clone());
+        server.add_proc().await.unwrap();
+        server
+            .add_service_proc(vec![String::from("SRV")])
+            .await
+            .unwrap();
+        wait_service_queue(&bus, "SRV", 0).await;
+
+        // Requests are sent by scheduled instant, then by scheduling order
+        let now = Instant::now();
+        let late = client.send_at(
+            now + Duration::from_millis(150),
+            1,
+            String::from("SRV"),
+            test_msg("late"),
+        );
+        let first = client.send_at(
+            now + Duration::from_millis(50),
+            2,
+            String::from("SRV"),
+            test_msg("first"),
+        );
+        let second = client.send_at(
+            now + Duration::from_millis(50),
+            3,
+            String::from("SRV"),
+            test_msg("second"),
+        );
+        let cancelled = client.send_after(
+            Duration::from_millis(100),
+            4,
+            String::from("SRV"),
+            test_msg("cancelled"),
+        );
+        assert!(cancelled.cancel());
+        assert!(!cancelled.is_pending());
+        assert!(!cancelled.cancel());
+
+        for (handle, value) in [(&first, "first"), (&second, "second"), (&late, "late")] {
+            match next_msg(&mut server_rx_queue).await {
+                InternalMsg::Request(request) => {
+                    assert_eq!(handle.id(), request.get_id());
+                    assert_eq!("SRV", request.get_service());
+                    assert_eq!(value, request.get_data().get_string(1).unwrap().as_str());
+                    assert!(Instant::now() >= handle.instant());
+                }
+                msg => panic!("Unexpected message {:?}", msg),
+            }
+        }
+        assert!(!late.is_pending());
+        assert!(!late.cancel());
+
+        // The target is resolved when the request is sent
+        let moved = client.send_after(
+            Duration::from_millis(200),
+            5,
+            String::from("SRV"),
+            test_msg("moved"),
+        );
+        let (server_queue_1, mut server_rx_queue_1) = mpsc::channel(2048);
+        server.add_proc_queue(server_queue_1, 1).await.unwrap();
+        server
+            .remove_service(vec![String::from("SRV")], 0)
+            .await
+            .unwrap();
+        server
+            .add_service(vec![String::from("SRV")], 1)
+            .await
+            .unwrap();
+        wait_service_queue(&bus, "SRV", 1).await;
+        match next_msg(&mut server_rx_queue_1).await {
+            InternalMsg::Request(request) => assert_eq!(moved.id(), request.get_id()),
+            msg => panic!("Unexpected message {:?}", msg),
+        }
+        while let Ok(msg) = server_rx_queue.try_recv() {
+            assert!(matches!(msg, InternalMsg::Service(_)), "{:?}", msg);
+        }
+
+        // Failures are returned to the processor queue
+        let unknown = client.send_after(
+            Duration::from_millis(10),
+            6,
+            String::from("UNKNOWN"),
+            test_msg("unknown"),
+        );
+        match next_msg(&mut client_rx_queue).await {
+            InternalMsg::Error(error) => {
+                assert_eq!(unknown.id(), error.get_id());
+                assert_eq!(
+                    &ServiceError::UnknownService(String::from("UNKNOWN")),
+                    error.get_err()
+                );
+            }
+            msg => panic!("Unexpected message {:?}", msg),
+        }
+
+        server.remove_proc().await.unwrap();
+        client.remove_proc().await.unwrap();
+        bus.stop("ProSA unit test end".into()).await.unwrap();
+        main_task.join().unwrap();
+    }
+}
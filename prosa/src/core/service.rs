@@ -1,14 +1,214 @@
 use super::{
-    msg::InternalMsg,
-    proc::{ProcBusParam, ProcParam},
+    audit::{AuditKind, AuditLog, AuditRecord},
+    error::SendError,
+    msg::{InternalMsg, Msg as _, RequestMsg},
+    proc::{ProcBusParam, ProcError, ProcParam},
+    tps::TpsWindow,
+    transport::TransportSender,
 };
-use prosa_utils::msg::tvf::{Tvf, TvfError};
+use opentelemetry::{
+    metrics::{Counter, Gauge, Meter},
+    KeyValue,
+};
+use prosa_utils::config::telemetry::ExemplarHistogram;
+use prosa_utils::msg::tvf::{Tvf, TvfError, TvfExt as _, TvfFieldError};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap, VecDeque},
     fmt::{self, Debug},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, SystemTime},
 };
 use thiserror::Error;
-use tokio::sync::mpsc;
+use tokio::{sync::mpsc, time::Instant};
+use tracing::{info, warn};
+
+/// Maximum length of a service name
+pub const SERVICE_NAME_MAX_LEN: usize = 255;
+
+/// Error of a service name that doesn't respect the naming rules
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ServiceNameError {
+    /// The service name is empty
+    #[error("The service name is empty")]
+    Empty,
+    /// The service name is longer than [`SERVICE_NAME_MAX_LEN`]
+    #[error("The service name `{0}` is too long ({1} > {SERVICE_NAME_MAX_LEN})")]
+    TooLong(String, usize),
+    /// The service name contain a character that is not alphanumeric, `_` or `-`
+    #[error("The service name `{0}` contain the invalid character `{1}`")]
+    InvalidChar(String, char),
+    /// The service name contain an empty namespace (`PAY..AUTH`, `.PAY`)
+    #[error("The service name `{0}` contain an empty namespace")]
+    EmptySegment(String),
+    /// The service name contain a wildcard that is not its whole last segment (`PAY.*.AUTH`, `PAY*`)
+    #[error("The service name `{0}` contain a misplaced wildcard")]
+    MisplacedWildcard(String),
+}
+
+/// Name of a service, with dotted namespaces (`PAY.AUTH`)
+///
+/// A name ending with the `*` segment is a wildcard that match every service of its namespace (`PAY.*` match `PAY.AUTH` and `PAY.AUTH.V2`, `*` match every service).
+/// A processor can register a wildcard to serve a whole namespace. When a service is requested, the [`ServiceTable`] prefer the exact service, then the most specific wildcard.
+///
+/// For backward compatibility, a service name can be converted from any string without validation.
+/// The names deserialized from the settings are validated (charset, length, namespaces).
+///
+/// ```
+/// use prosa::core::service::ServiceName;
+///
+/// let name = ServiceName::new("PAY.*").unwrap();
+/// assert!(name.is_wildcard());
+/// assert!(name.matches("PAY.AUTH"));
+/// assert!(!name.matches("PAYMENT"));
+///
+/// assert!(ServiceName::new("PAY..AUTH").is_err());
+/// assert_eq!("PAY.AUTH", ServiceName::from("PAY.AUTH").as_str());
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ServiceName(String);
+
+impl ServiceName {
+    /// Separator of the namespaces
+    pub const SEPARATOR: char = '.';
+    /// Segment that match every service of a namespace
+    pub const WILDCARD: &'static str = "*";
+
+    /// Method to create a validated service name
+    pub fn new<S>(name: S) -> Result<ServiceName, ServiceNameError>
+    where
+        S: Into<String>,
+    {
+        let name = name.into();
+        Self::validate(&name)?;
+        Ok(ServiceName(name))
+    }
+
+    /// Method to check that a service name respect the naming rules
+    pub fn validate(name: &str) -> Result<(), ServiceNameError> {
+        if name.is_empty() {
+            return Err(ServiceNameError::Empty);
+        } else if name.len() > SERVICE_NAME_MAX_LEN {
+            return Err(ServiceNameError::TooLong(name.to_string(), name.len()));
+        }
+
+        let mut segments = name.split(Self::SEPARATOR).peekable();
+        while let Some(segment) = segments.next() {
+            if segment.is_empty() {
+                return Err(ServiceNameError::EmptySegment(name.to_string()));
+            } else if segment == Self::WILDCARD {
+                if segments.peek().is_some() {
+                    return Err(ServiceNameError::MisplacedWildcard(name.to_string()));
+                }
+            } else if segment.contains('*') {
+                return Err(ServiceNameError::MisplacedWildcard(name.to_string()));
+            } else if let Some(c) = segment
+                .chars()
+                .find(|c| !c.is_ascii_alphanumeric() && *c != '_' && *c != '-')
+            {
+                return Err(ServiceNameError::InvalidChar(name.to_string(), c));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Getter of the service name as a string
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Method to know if the service name is a wildcard
+    pub fn is_wildcard(&self) -> bool {
+        Self::is_wildcard_name(&self.0)
+    }
+
+    fn is_wildcard_name(name: &str) -> bool {
+        name == Self::WILDCARD || name.ends_with(".*")
+    }
+
+    /// Getter of the namespace of the service (`PAY` for `PAY.AUTH`). Return `None` if the service doesn't have a namespace
+    pub fn namespace(&self) -> Option<&str> {
+        self.0
+            .rsplit_once(Self::SEPARATOR)
+            .map(|(namespace, _)| namespace)
+    }
+
+    /// Method to know if a requested service is served by this name (equal, or in the namespace of the wildcard)
+    pub fn matches(&self, service: &str) -> bool {
+        if self.is_wildcard() {
+            let prefix = &self.0[..self.0.len() - Self::WILDCARD.len()];
+            service.len() > prefix.len() && service.starts_with(prefix)
+        } else {
+            self.0 == service
+        }
+    }
+
+    /// Iterator over the wildcards that match a requested service, from the most specific to the least (`PAY.AUTH.*`, `PAY.*`, `*` for `PAY.AUTH.V2`)
+    pub fn wildcards(service: &str) -> impl Iterator<Item = String> + '_ {
+        service
+            .rmatch_indices(Self::SEPARATOR)
+            .map(|(pos, _)| format!("{}*", &service[..=pos]))
+            .chain(std::iter::once(String::from(Self::WILDCARD)))
+    }
+}
+
+impl fmt::Display for ServiceName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl AsRef<str> for ServiceName {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl PartialEq<str> for ServiceName {
+    fn eq(&self, other: &str) -> bool {
+        self.0 == other
+    }
+}
+
+impl From<&str> for ServiceName {
+    fn from(name: &str) -> Self {
+        ServiceName(name.to_string())
+    }
+}
+
+impl From<String> for ServiceName {
+    fn from(name: String) -> Self {
+        ServiceName(name)
+    }
+}
+
+impl From<ServiceName> for String {
+    fn from(name: ServiceName) -> Self {
+        name.0
+    }
+}
+
+impl Serialize for ServiceName {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for ServiceName {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        ServiceName::new(String::deserialize(deserializer)?).map_err(serde::de::Error::custom)
+    }
+}
 
 /// Strucure that define the service table which contain information to how contact a processor for a given service name
 #[derive(Debug, Default, Clone)]
@@ -17,12 +217,21 @@ where
     M: Sized + Clone + Tvf,
 {
     table: HashMap<String, Vec<ProcService<M>>>,
+    /// Queues of every processor, by processor id and queue id (use to route responses to their originating queue)
+    proc_queues: HashMap<u32, BTreeMap<u32, ProcService<M>>>,
+    wildcards: bool,
+    audit: Option<Arc<AuditLog>>,
 }
 
 impl<M> ServiceTable<M>
 where
     M: Sized + Clone + Tvf,
 {
+    /// Iterator over the services with the processor services that serve them
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &Vec<ProcService<M>>)> {
+        self.table.iter()
+    }
+
     /// Getter to know if the service table is empty
     pub fn is_empty(&self) -> bool {
         self.table.is_empty()
@@ -33,22 +242,36 @@ where
         self.table.len()
     }
 
+    /// Method to resolve the processors that serve a requested service: the exact service if served, otherwise the most specific wildcard (see [`ServiceName`])
+    fn resolve(&self, name: &str) -> Option<&Vec<ProcService<M>>> {
+        if let Some(services) = self.table.get(name).filter(|s| !s.is_empty()) {
+            Some(services)
+        } else if self.wildcards && !ServiceName::is_wildcard_name(name) {
+            ServiceName::wildcards(name)
+                .find_map(|wildcard| self.table.get(&wildcard).filter(|s| !s.is_empty()))
+        } else {
+            None
+        }
+    }
+
     /// Method to know if the service is available from a processor
     ///
     /// Call be the processor to know if a service is available (service test)
-    pub fn exist_proc_service(&self, name: &String) -> bool {
-        if let Some(services) = self.table.get(name) {
-            !services.is_empty()
-        } else {
-            false
-        }
+    pub fn exist_proc_service(&self, name: &str) -> bool {
+        self.resolve(name).is_some()
+    }
+
+    /// Method to know how many processors respond to the service
+    pub fn count_proc_service(&self, name: &str) -> usize {
+        self.resolve(name).map_or(0, |services| services.len())
     }
 
     /// Method to get a processor that respond to the service
     ///
-    /// Call by the processor to send a transaction to a processor that give the corresponding service
-    pub fn get_proc_service(&self, name: &String, msg_id: u64) -> Option<&ProcService<M>> {
-        if let Some(services) = self.table.get(name) {
+    /// Call by the processor to send a transaction to a processor that give the corresponding service.
+    /// The transaction keep the requested service name, even if it's served by a wildcard.
+    pub fn get_proc_service(&self, name: &str, msg_id: u64) -> Option<&ProcService<M>> {
+        let proc_service = if let Some(services) = self.resolve(name) {
             match services.len() {
                 2.. => services.get(msg_id as usize % services.len()),
                 1 => services.first(),
@@ -56,26 +279,114 @@ where
             }
         } else {
             None
+        };
+
+        self.audit_resolution(name, msg_id, proc_service);
+        proc_service
+    }
+
+    /// Method to get a processor that respond to the service, preferably not one of the excluded processor services
+    ///
+    /// Call by the processor to failover a transaction on an other processor that give the corresponding service.
+    /// If only excluded processors respond to the service, one of them is returned.
+    pub fn get_proc_service_except(
+        &self,
+        name: &str,
+        msg_id: u64,
+        excluded: &[ProcService<M>],
+    ) -> Option<&ProcService<M>> {
+        let services: Vec<&ProcService<M>> = self
+            .resolve(name)?
+            .iter()
+            .filter(|s| !excluded.contains(s))
+            .collect();
+        if services.is_empty() {
+            self.get_proc_service(name, msg_id)
+        } else {
+            let proc_service = services.get(msg_id as usize % services.len()).copied();
+            self.audit_resolution(name, msg_id, proc_service);
+            proc_service
+        }
+    }
+
+    /// Getter of a processor queue to deliver a message to it: the queue itself if it's still open, otherwise any open queue of the processor
+    pub fn get_proc_queue(&self, proc_id: u32, queue_id: u32) -> Option<&ProcService<M>> {
+        let proc_queues = self.proc_queues.get(&proc_id)?;
+        proc_queues
+            .get(&queue_id)
+            .filter(|proc_queue| !proc_queue.proc_queue.is_closed())
+            .or_else(|| {
+                proc_queues
+                    .values()
+                    .find(|proc_queue| !proc_queue.proc_queue.is_closed())
+            })
+    }
+
+    /// Method to register a processor queue in the table
+    ///
+    /// Can be call only by the main task to modify the service table
+    pub fn add_proc_queue(&mut self, proc_service: ProcService<M>) {
+        self.proc_queues
+            .entry(proc_service.proc_id)
+            .or_default()
+            .insert(proc_service.queue_id, proc_service);
+    }
+
+    /// Method to unregister a processor queue from the table
+    ///
+    /// Can be call only by the main task to modify the service table
+    pub fn remove_proc_queue(&mut self, proc_id: u32, queue_id: u32) {
+        if let Some(proc_queues) = self.proc_queues.get_mut(&proc_id) {
+            proc_queues.remove(&queue_id);
+            if proc_queues.is_empty() {
+                self.proc_queues.remove(&proc_id);
+            }
+        }
+    }
+
+    /// Method to unregister all queues of a processor from the table
+    ///
+    /// Can be call only by the main task to modify the service table
+    pub fn remove_proc_queues(&mut self, proc_id: u32) {
+        self.proc_queues.remove(&proc_id);
+    }
+
+    /// Setter of the audit log that record the service resolutions
+    pub(crate) fn set_audit(&mut self, audit: Arc<AuditLog>) {
+        self.audit = Some(audit);
+    }
+
+    /// Method to record a service resolution in the audit log (if set)
+    fn audit_resolution(&self, name: &str, msg_id: u64, proc_service: Option<&ProcService<M>>) {
+        if let Some(audit) = &self.audit {
+            let mut record = AuditRecord::new(AuditKind::Resolution)
+                .service(name)
+                .correlation(msg_id);
+            if let Some(proc_service) = proc_service {
+                record = record.proc(proc_service.proc_id, Some(proc_service.queue_id));
+            }
+            audit.record(record);
         }
     }
 
     /// Method to add a service to the table
     ///
     /// Can be call only by the main task to modify the service table
-    pub fn add_service(&mut self, name: &String, proc_service: ProcService<M>) {
+    pub fn add_service(&mut self, name: &str, proc_service: ProcService<M>) {
         if let Some(services) = self.table.get_mut(name) {
             if !services.iter().any(|s| s.proc_id == proc_service.proc_id) {
                 services.push(proc_service);
             }
         } else {
-            self.table.insert(name.clone(), vec![proc_service]);
+            self.wildcards |= ServiceName::is_wildcard_name(name);
+            self.table.insert(name.to_string(), vec![proc_service]);
         }
     }
 
     /// Method to remove whole processor service from the table
     ///
     /// Can be call only by the main task to modify the service table
-    pub fn remove_service_proc(&mut self, name: &String, proc_id: u32) {
+    pub fn remove_service_proc(&mut self, name: &str, proc_id: u32) {
         if let Some(services) = self.table.get_mut(name) {
             services.retain(|s| s.proc_id != proc_id);
         }
@@ -84,9 +395,9 @@ where
     /// Method to remove a service from the table
     ///
     /// Can be call only by the main task to modify the service table
-    pub fn remove_service(&mut self, name: &String, proc_id: u32, queue_id: u32) {
+    pub fn remove_service(&mut self, name: &str, proc_id: u32, queue_id: u32) {
         if let Some(services) = self.table.get_mut(name) {
-            services.retain(|s| s.proc_id != proc_id && s.queue_id != queue_id);
+            services.retain(|s| s.proc_id != proc_id || s.queue_id != queue_id);
         }
     }
 
@@ -112,7 +423,7 @@ where
     pub fn remove_proc_queue_services(&mut self, proc_id: u32, queue_id: u32) {
         // This will let service with empty processors
         for service in self.table.values_mut() {
-            service.retain(|s| s.proc_id != proc_id && s.queue_id != queue_id);
+            service.retain(|s| s.proc_id != proc_id || s.queue_id != queue_id);
         }
 
         // FIXME When the API will not be unstable anymore:
@@ -121,6 +432,22 @@ where
             v.is_empty()
         });*/
     }
+
+    /// Method to remove all services from a given processor queue from the table, and return their names
+    ///
+    /// Can be call only by the main task to modify the service table
+    pub fn take_proc_queue_services(&mut self, proc_id: u32, queue_id: u32) -> Vec<String> {
+        let mut names = Vec::new();
+        for (name, services) in self.table.iter_mut() {
+            let len = services.len();
+            services.retain(|s| s.proc_id != proc_id || s.queue_id != queue_id);
+            if services.len() != len {
+                names.push(name.clone());
+            }
+        }
+
+        names
+    }
 }
 
 impl<M> fmt::Display for ServiceTable<M>
@@ -148,8 +475,15 @@ where
 {
     proc_id: u32,
     queue_id: u32,
+    group: Option<String>,
+    shutdown_rank: u32,
+    size_limit: MessageSizeLimit,
     /// Processor queue use to send transactionnal message to the processor
     pub proc_queue: mpsc::Sender<InternalMsg<M>>,
+    /// Processor control queue, if the processor split its inbox (see [`ProcParam::set_ctrl_queue`])
+    ctrl_queue: Option<mpsc::Sender<InternalMsg<M>>>,
+    /// Processor data queue, the processor queue or its transport queue (see [`ProcParam::set_transport_queue`])
+    data_queue: TransportSender<InternalMsg<M>>,
 }
 
 impl<M> ProcService<M>
@@ -165,7 +499,12 @@ where
         ProcService {
             proc_id: proc.get_proc_id(),
             queue_id,
+            group: proc.get_group().cloned(),
+            shutdown_rank: proc.get_shutdown_rank(),
+            size_limit: proc.message_size_limit().clone(),
+            data_queue: proc_queue.clone().into(),
             proc_queue,
+            ctrl_queue: None,
         }
     }
 
@@ -174,7 +513,14 @@ where
         ProcService {
             proc_id: proc.get_proc_id(),
             queue_id,
+            group: proc.get_group().cloned(),
+            shutdown_rank: proc.get_shutdown_rank(),
+            size_limit: proc.message_size_limit().clone(),
             proc_queue: proc.get_service_queue(),
+            ctrl_queue: proc.get_ctrl_queue(),
+            data_queue: proc
+                .get_transport_queue()
+                .unwrap_or_else(|| proc.get_service_queue().into()),
         }
     }
 
@@ -187,6 +533,116 @@ where
     pub fn get_queue_id(&self) -> u32 {
         self.queue_id
     }
+
+    /// Getter of the processor group
+    pub fn get_group(&self) -> Option<&String> {
+        self.group.as_ref()
+    }
+
+    /// Getter of the processor rank in the ProSA shutdown sequence
+    pub fn get_shutdown_rank(&self) -> u32 {
+        self.shutdown_rank
+    }
+
+    /// Getter of the queue that receive the control messages ([`InternalMsg::is_control`]), the control queue of the processor if it has one
+    fn control_queue(&self) -> &mpsc::Sender<InternalMsg<M>> {
+        self.ctrl_queue.as_ref().unwrap_or(&self.proc_queue)
+    }
+
+    /// Method to send a message to the processor.
+    /// Control messages ([`InternalMsg::is_control`]) are sent through the control queue of the processor if it has one, so they don't wait behind data messages.
+    /// Data messages are sent through the transport queue of the processor if it has one (see [`ProcParam::set_transport_queue`])
+    pub async fn send(&self, msg: InternalMsg<M>) -> Result<(), SendError<InternalMsg<M>>> {
+        if msg.is_control() {
+            self.control_queue().send(msg).await?;
+        } else {
+            self.data_queue.send(msg).await?;
+        }
+        Ok(())
+    }
+
+    /// Method to send a message to the processor without waiting if its queue is full (see [`ProcService::send`])
+    #[allow(clippy::result_large_err)]
+    pub fn try_send(&self, msg: InternalMsg<M>) -> Result<(), SendError<InternalMsg<M>>> {
+        if msg.is_control() {
+            self.control_queue().try_send(msg)?;
+        } else {
+            self.data_queue.try_send(msg)?;
+        }
+        Ok(())
+    }
+
+    /// Method to send a request to the processor, if its size doesn't exceed the bus limit ([`MessageSizeLimit`]).
+    ///
+    /// An oversized request is not sent, a [`ServiceError::MessageTooLarge`] is returned to the sender through its response queue instead.
+    /// The response of the request will also be checked when the processor returns it ([`RequestMsg::return_to_sender`]).
+    pub async fn send_request(
+        &self,
+        mut request: RequestMsg<M>,
+    ) -> Result<(), SendError<InternalMsg<M>>> {
+        if let Err(err) = request.get_span().in_scope(|| {
+            self.size_limit
+                .check(request.get_service(), request.get_data(), "request")
+        }) {
+            return request.return_error_to_sender(None, err).await;
+        }
+
+        request.set_size_limit(self.size_limit.clone());
+        self.data_queue.send(InternalMsg::Request(request)).await?;
+        Ok(())
+    }
+
+    /// Method to send a batch of requests to the processor in a single [`InternalMsg::RequestBatch`] message.
+    ///
+    /// Oversized requests are removed from the batch and returned as errors, like with [`ProcService::send_request`].
+    /// A batch of a single request is sent as a regular request.
+    pub async fn send_batch(
+        &self,
+        requests: Vec<RequestMsg<M>>,
+    ) -> Result<(), SendError<InternalMsg<M>>> {
+        let mut batch = Vec::with_capacity(requests.len());
+        for mut request in requests {
+            if let Err(err) = request.get_span().in_scope(|| {
+                self.size_limit
+                    .check(request.get_service(), request.get_data(), "request")
+            }) {
+                request.return_error_to_sender(None, err).await?;
+            } else {
+                request.set_size_limit(self.size_limit.clone());
+                batch.push(request);
+            }
+        }
+
+        match batch.len() {
+            0 => {}
+            1 => {
+                self.data_queue
+                    .send(InternalMsg::Request(batch.remove(0)))
+                    .await?
+            }
+            _ => {
+                self.data_queue
+                    .send(InternalMsg::RequestBatch(batch))
+                    .await?
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<M> fmt::Display for ProcService<M>
+where
+    M: Sized + Clone + Tvf,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Processor {}/{}", self.proc_id, self.queue_id)?;
+        if let Some(group) = &self.group {
+            write!(f, " of group {}", group)?;
+        }
+
+        write!(f, " (queue capacity {})", self.proc_queue.max_capacity())
+    }
 }
 
 impl<M> ProcBusParam for ProcService<M>
@@ -207,36 +663,1781 @@ where
     }
 }
 
-#[derive(Debug, Eq, Error, PartialEq)]
+/// Settings of a [`RequestBatcher`]
+///
+/// ```yaml
+/// batch:
+///   max_count: 64
+///   max_latency:
+///     secs: 0
+///     nanos: 1000000
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct BatchSettings {
+    /// Maximum number of requests of a batch, a full batch is sent right away
+    #[serde(default = "BatchSettings::default_max_count")]
+    pub max_count: usize,
+    /// Maximum duration a request wait in a batch before the batch is sent
+    #[serde(default = "BatchSettings::default_max_latency")]
+    pub max_latency: Duration,
+}
+
+impl BatchSettings {
+    fn default_max_count() -> usize {
+        64
+    }
+
+    fn default_max_latency() -> Duration {
+        Duration::from_millis(1)
+    }
+
+    /// Method to create batch settings with the maximum number of requests and latency of a batch
+    pub fn new(max_count: usize, max_latency: Duration) -> BatchSettings {
+        BatchSettings {
+            max_count,
+            max_latency,
+        }
+    }
+}
+
+impl Default for BatchSettings {
+    fn default() -> Self {
+        BatchSettings::new(Self::default_max_count(), Self::default_max_latency())
+    }
+}
+
+/// Batch of requests waiting to be sent to a processor queue
+struct PendingBatch<M>
+where
+    M: Sized + Clone + Tvf,
+{
+    service: ProcService<M>,
+    requests: Vec<RequestMsg<M>>,
+    since: Instant,
+}
+
+/// Accumulator of requests into batches, to reduce the bus overhead of high throughput flows
+///
+/// Requests are grouped by processor queue, and a batch is sent (with [`ProcService::send_batch`]) when it reaches the maximum count or when its oldest request waited the maximum latency.
+/// The latency flush has to be driven by the processor loop:
+///
+/// ```
+/// use std::time::Duration;
+/// use prosa::core::msg::{InternalMsg, RequestMsg};
+/// use prosa::core::service::{BatchSettings, RequestBatcher};
+/// use prosa_utils::msg::simple_string_tvf::SimpleStringTvf;
+/// use tokio::sync::mpsc;
+///
+/// async fn batch_loop(
+///     mut batcher: RequestBatcher<SimpleStringTvf>,
+///     mut queue: mpsc::Receiver<InternalMsg<SimpleStringTvf>>,
+/// ) {
+///     loop {
+///         tokio::select! {
+///             Some(msg) = queue.recv() => {
+///                 // Push new requests in the batcher with `batcher.push(&proc_service, request).await`
+///             }
+///             _ = batcher.wait_flush(), if !batcher.is_empty() => {
+///                 batcher.flush_expired().await.unwrap();
+///             }
+///         }
+///     }
+/// }
+/// ```
+pub struct RequestBatcher<M>
+where
+    M: Sized + Clone + Tvf,
+{
+    settings: BatchSettings,
+    batches: Vec<PendingBatch<M>>,
+}
+
+impl<M> RequestBatcher<M>
+where
+    M: Sized + Clone + Debug + Tvf + Default + 'static + std::marker::Send + std::marker::Sync,
+{
+    /// Method to create a request batcher from its settings
+    pub fn new(settings: BatchSettings) -> RequestBatcher<M> {
+        RequestBatcher {
+            settings,
+            batches: Vec::new(),
+        }
+    }
+
+    /// Getter of the batch settings
+    pub fn get_settings(&self) -> &BatchSettings {
+        &self.settings
+    }
+
+    /// Getter of the number of requests waiting in the batches
+    pub fn len(&self) -> usize {
+        self.batches.iter().map(|batch| batch.requests.len()).sum()
+    }
+
+    /// Method to know if no request is waiting in the batches
+    pub fn is_empty(&self) -> bool {
+        self.batches.is_empty()
+    }
+
+    /// Method to add a request to the batch of the processor queue. The batch is sent if it's full
+    pub async fn push(
+        &mut self,
+        service: &ProcService<M>,
+        request: RequestMsg<M>,
+    ) -> Result<(), SendError<InternalMsg<M>>> {
+        let index = if let Some(index) = self
+            .batches
+            .iter()
+            .position(|batch| batch.service == *service)
+        {
+            self.batches[index].requests.push(request);
+            index
+        } else {
+            self.batches.push(PendingBatch {
+                service: service.clone(),
+                requests: vec![request],
+                since: Instant::now(),
+            });
+            self.batches.len() - 1
+        };
+
+        if self.batches[index].requests.len() >= self.settings.max_count {
+            let batch = self.batches.swap_remove(index);
+            batch.service.send_batch(batch.requests).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Getter of the instant the oldest batch has to be sent
+    pub fn next_flush(&self) -> Option<Instant> {
+        self.batches
+            .iter()
+            .map(|batch| batch.since + self.settings.max_latency)
+            .min()
+    }
+
+    /// Method to wait until a batch has to be sent (never return if there is no batch).
+    /// This method is cancel safe, so it can be used in a `tokio::select!` with [`RequestBatcher::flush_expired`]
+    pub async fn wait_flush(&self) {
+        match self.next_flush() {
+            Some(deadline) => tokio::time::sleep_until(deadline).await,
+            None => std::future::pending().await,
+        }
+    }
+
+    /// Method to send the batches whose oldest request waited the maximum latency
+    pub async fn flush_expired(&mut self) -> Result<(), SendError<InternalMsg<M>>> {
+        let now = Instant::now();
+        let mut index = 0;
+        while index < self.batches.len() {
+            if self.batches[index].since + self.settings.max_latency <= now {
+                let batch = self.batches.swap_remove(index);
+                batch.service.send_batch(batch.requests).await?;
+            } else {
+                index += 1;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Method to send all the batches right away
+    pub async fn flush(&mut self) -> Result<(), SendError<InternalMsg<M>>> {
+        for batch in self.batches.drain(..) {
+            batch.service.send_batch(batch.requests).await?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<M> fmt::Debug for RequestBatcher<M>
+where
+    M: Sized + Clone + Tvf,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RequestBatcher")
+            .field("settings", &self.settings)
+            .field("batches", &self.batches.len())
+            .finish()
+    }
+}
+
+#[derive(Debug, Clone, Eq, Error, PartialEq)]
 /// ProSA service error when the service can't respond correctly to a request
+///
+/// A service error can be encoded into the response TVF message to be transmitted to the requester.
+/// The error is put as a buffer on the [`SERVICE_ERROR_TAG`](ServiceError::SERVICE_ERROR_TAG) field with the following sub fields:
+///
+/// | Tag | Type     | Description                                                        |
+/// |-----|----------|--------------------------------------------------------------------|
+/// | 1   | unsigned | Error code (see [`ProcError::error_code`])                         |
+/// | 2   | string   | Error reason                                                       |
+/// | 3   | string   | Service name (if any)                                              |
+/// | 4   | unsigned | Retry after (ms) for `Unavailable`, timeout (ms) for `Timeout`     |
+/// | 5   | unsigned | Protocol error code for `ProtocolError`                            |
+/// | 6   | unsigned | Message size for `MessageTooLarge`                                 |
+/// | 7   | unsigned | Message size limit for `MessageTooLarge`                           |
+///
+/// ```
+/// use std::time::Duration;
+/// use prosa::core::service::ServiceError;
+/// use prosa_utils::msg::tvf::Tvf;
+/// use prosa_utils::msg::simple_string_tvf::SimpleStringTvf;
+///
+/// let err = ServiceError::Unavailable(String::from("SRV"), Some(Duration::from_secs(1)));
+/// assert!(err.is_retryable());
+///
+/// let mut response = SimpleStringTvf::default();
+/// err.encode(&mut response);
+/// assert!(response.contains(ServiceError::SERVICE_ERROR_TAG));
+/// assert_eq!(Some(err), ServiceError::decode(&response).unwrap());
+/// ```
 pub enum ServiceError {
     /// No error on the ProSA service
     #[error("No error on the service `{0}`")]
     NoError(String),
-    /// The service is unavailable and can't be reach
-    #[error("The service `{0}` can't be reach")]
-    UnableToReachService(String),
+    /// The service is not known by ProSA
+    #[error("The service `{0}` is unknown")]
+    UnknownService(String),
+    /// The service is unavailable and can't be reach. It can be retried later (after the duration if specified)
+    #[error("The service `{0}` is unavailable")]
+    Unavailable(String, Option<Duration>),
     /// The service didn't respond in time
     #[error("The service `{0}` didn't respond before {1} ms")]
     Timeout(String, u64),
     /// The protocol is not correct on the service
-    #[error("The service `{0}` made a protocol error")]
-    ProtocolError(String),
+    #[error("Protocol error {code}: {reason}")]
+    ProtocolError {
+        /// Protocol error code
+        code: u32,
+        /// Reason of the protocol error
+        reason: String,
+    },
+    /// Internal error of the service
+    #[error("Internal service error: {0}")]
+    Internal(String),
+    /// The message exceeds the maximum message size of the bus, with the message size and the limit
+    #[error("The message size {0} exceeds the limit of {1} bytes")]
+    MessageTooLarge(usize, usize),
 }
 
-impl From<TvfError> for ServiceError {
-    fn from(err: TvfError) -> Self {
-        match err {
-            TvfError::FieldNotFound(id) => {
-                ServiceError::ProtocolError(format!("on TVF field {}", id))
+impl ServiceError {
+    /// TVF tag used to encode a service error into a response message
+    pub const SERVICE_ERROR_TAG: usize = 0xE770;
+
+    const CODE_TAG: usize = 1;
+    const REASON_TAG: usize = 2;
+    const SERVICE_TAG: usize = 3;
+    const DURATION_TAG: usize = 4;
+    const PROTOCOL_CODE_TAG: usize = 5;
+    const SIZE_TAG: usize = 6;
+    const LIMIT_TAG: usize = 7;
+
+    /// Method to know if the request can be retried later
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            ServiceError::Unavailable(_, _) | ServiceError::Timeout(_, _)
+        )
+    }
+
+    /// Getter of the kind of the error, as a metric attribute
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ServiceError::NoError(_) => "no_error",
+            ServiceError::UnknownService(_) => "unknown_service",
+            ServiceError::Unavailable(_, _) => "unavailable",
+            ServiceError::Timeout(_, _) => "timeout",
+            ServiceError::ProtocolError { .. } => "protocol_error",
+            ServiceError::Internal(_) => "internal",
+            ServiceError::MessageTooLarge(_, _) => "message_too_large",
+        }
+    }
+
+    /// Method to encode the service error into a response message
+    pub fn encode<M>(&self, msg: &mut M)
+    where
+        M: Tvf + Default + Debug + Clone,
+    {
+        let mut err_buf = M::default();
+        err_buf.put_unsigned(Self::CODE_TAG, self.error_code() as u64);
+        match self {
+            ServiceError::NoError(service) | ServiceError::UnknownService(service) => {
+                err_buf.put_string(Self::SERVICE_TAG, service.clone());
+            }
+            ServiceError::Unavailable(service, retry_after) => {
+                err_buf.put_string(Self::SERVICE_TAG, service.clone());
+                if let Some(retry_after) = retry_after {
+                    err_buf.put_unsigned(Self::DURATION_TAG, retry_after.as_millis() as u64);
+                }
+            }
+            ServiceError::Timeout(service, timeout) => {
+                err_buf.put_string(Self::SERVICE_TAG, service.clone());
+                err_buf.put_unsigned(Self::DURATION_TAG, *timeout);
+            }
+            ServiceError::ProtocolError { code, reason } => {
+                err_buf.put_unsigned(Self::PROTOCOL_CODE_TAG, *code as u64);
+                err_buf.put_string(Self::REASON_TAG, reason.clone());
             }
-            TvfError::TypeMismatch => ServiceError::ProtocolError(String::from("on TVF type")),
-            TvfError::ConvertionError(str) => {
-                ServiceError::ProtocolError(format!("on TVF convertion {}", str))
+            ServiceError::Internal(reason) => {
+                err_buf.put_string(Self::REASON_TAG, reason.clone());
             }
-            TvfError::SerializationError(str) => {
-                ServiceError::ProtocolError(format!("on TVF serialization {}", str))
+            ServiceError::MessageTooLarge(size, limit) => {
+                err_buf.put_unsigned(Self::SIZE_TAG, *size as u64);
+                err_buf.put_unsigned(Self::LIMIT_TAG, *limit as u64);
             }
         }
+
+        msg.put_buffer(Self::SERVICE_ERROR_TAG, err_buf);
+    }
+
+    /// Method to decode a service error from a response message.
+    /// Return `None` if the message doesn't contain any service error
+    pub fn decode<M>(msg: &M) -> Result<Option<ServiceError>, TvfError>
+    where
+        M: Tvf + Default + Debug + Clone,
+    {
+        if !msg.contains(Self::SERVICE_ERROR_TAG) {
+            return Ok(None);
+        }
+
+        let err_buf = msg.get_buffer(Self::SERVICE_ERROR_TAG)?;
+        let service = || {
+            err_buf
+                .get_string(Self::SERVICE_TAG)
+                .map(|s| s.into_owned())
+        };
+        let reason = || err_buf.get_string(Self::REASON_TAG).map(|s| s.into_owned());
+        match err_buf.get_unsigned(Self::CODE_TAG)? {
+            0 => Ok(Some(ServiceError::NoError(service()?))),
+            200 => Ok(Some(ServiceError::Unavailable(
+                service()?,
+                if err_buf.contains(Self::DURATION_TAG) {
+                    Some(Duration::from_millis(
+                        err_buf.get_unsigned(Self::DURATION_TAG)?,
+                    ))
+                } else {
+                    None
+                },
+            ))),
+            201 => Ok(Some(ServiceError::Timeout(
+                service()?,
+                err_buf.get_unsigned(Self::DURATION_TAG)?,
+            ))),
+            202 => Ok(Some(ServiceError::ProtocolError {
+                code: err_buf.get_unsigned(Self::PROTOCOL_CODE_TAG)? as u32,
+                reason: reason()?,
+            })),
+            203 => Ok(Some(ServiceError::UnknownService(service()?))),
+            204 => Ok(Some(ServiceError::Internal(reason()?))),
+            205 => Ok(Some(ServiceError::MessageTooLarge(
+                err_buf.get_unsigned(Self::SIZE_TAG)? as usize,
+                err_buf.get_unsigned(Self::LIMIT_TAG)? as usize,
+            ))),
+            code => Err(TvfError::ConvertionError(format!(
+                "unknown service error code {}",
+                code
+            ))),
+        }
+    }
+}
+
+impl ProcError for ServiceError {
+    fn recoverable(&self) -> bool {
+        !matches!(self, ServiceError::ProtocolError { .. })
+    }
+
+    fn recovery_duration(&self) -> Duration {
+        if let ServiceError::Unavailable(_, Some(retry_after)) = self {
+            *retry_after
+        } else {
+            Duration::ZERO
+        }
+    }
+
+    fn error_code(&self) -> u32 {
+        match self {
+            ServiceError::NoError(_) => 0,
+            ServiceError::Unavailable(_, _) => 200,
+            ServiceError::Timeout(_, _) => 201,
+            ServiceError::ProtocolError { .. } => 202,
+            ServiceError::UnknownService(_) => 203,
+            ServiceError::Internal(_) => 204,
+            ServiceError::MessageTooLarge(_, _) => 205,
+        }
+    }
+}
+
+impl From<TvfError> for ServiceError {
+    fn from(err: TvfError) -> Self {
+        match err {
+            TvfError::FieldNotFound(id) => ServiceError::ProtocolError {
+                code: 0,
+                reason: format!("on TVF field {}", id),
+            },
+            TvfError::TypeMismatch => ServiceError::ProtocolError {
+                code: 0,
+                reason: String::from("on TVF type"),
+            },
+            TvfError::ConvertionError(str) => ServiceError::ProtocolError {
+                code: 0,
+                reason: format!("on TVF convertion {}", str),
+            },
+            TvfError::SerializationError(str) => ServiceError::ProtocolError {
+                code: 0,
+                reason: format!("on TVF serialization {}", str),
+            },
+            TvfError::LossyConversion(path, tvf_type) => ServiceError::ProtocolError {
+                code: 0,
+                reason: format!("on TVF convertion of {} field {}", tvf_type, path),
+            },
+        }
+    }
+}
+
+impl From<TvfFieldError> for ServiceError {
+    fn from(err: TvfFieldError) -> Self {
+        ServiceError::ProtocolError {
+            code: 0,
+            reason: err.to_string(),
+        }
+    }
+}
+
+/// Limit of the size of the messages exchanged on the bus, with the message size metrics
+///
+/// The size of a message is given by [`Tvf::size_hint`].
+/// With a meter, sizes are recorded in the `prosa_bus_message_size` histogram, and rejected messages are counted by the `prosa_bus_message_too_large` counter (both with the `service` and `type` attributes).
+/// With a transactions window, the answered requests are counted with their latency (see [`TpsWindow`]).
+///
+/// ```
+/// use prosa::core::service::{MessageSizeLimit, ServiceError};
+/// use prosa_utils::msg::simple_string_tvf::SimpleStringTvf;
+/// use prosa_utils::msg::tvf::Tvf;
+///
+/// let mut msg = SimpleStringTvf::default();
+/// msg.put_string(1, "a message too large");
+///
+/// let limit = MessageSizeLimit::new(Some(16));
+/// assert_eq!(
+///     Err(ServiceError::MessageTooLarge(msg.size_hint(), 16)),
+///     limit.check("SERVICE", &msg, "request")
+/// );
+/// assert!(MessageSizeLimit::default().check("SERVICE", &msg, "request").is_ok());
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct MessageSizeLimit {
+    max_size: Option<usize>,
+    sizes: Option<ExemplarHistogram<u64>>,
+    rejected: Option<Counter<u64>>,
+    transactions: Option<Arc<TpsWindow>>,
+}
+
+impl MessageSizeLimit {
+    /// Method to create a message size limit (no limit if `None`)
+    pub fn new(max_size: Option<usize>) -> MessageSizeLimit {
+        MessageSizeLimit {
+            max_size,
+            sizes: None,
+            rejected: None,
+            transactions: None,
+        }
+    }
+
+    /// Setter of the meter used to record message sizes and rejected messages.
+    /// The sizes are recorded with the trace of the message as exemplar (see [`record_with_exemplar`](prosa_utils::config::telemetry::record_with_exemplar))
+    pub fn meter(mut self, meter: &Meter) -> Self {
+        let name = "prosa_bus_message_size";
+        self.sizes = Some(ExemplarHistogram::new(
+            name,
+            meter
+                .u64_histogram(name)
+                .with_description("Size of the messages sent on the bus")
+                .with_unit("By")
+                .init(),
+        ));
+        self.rejected = Some(
+            meter
+                .u64_counter("prosa_bus_message_too_large")
+                .with_description(
+                    "Number of messages rejected because they exceed the maximum message size",
+                )
+                .init(),
+        );
+        self
+    }
+
+    /// Setter of the moving window where the answered transactions are counted
+    pub fn transactions(mut self, transactions: Arc<TpsWindow>) -> Self {
+        self.transactions = Some(transactions);
+        self
+    }
+
+    /// Getter of the maximum message size (if any)
+    pub fn get_max_size(&self) -> Option<usize> {
+        self.max_size
+    }
+
+    /// Method to count a transaction answered (response or error) with its latency, if there is a transactions window
+    pub fn record_transaction(&self, latency: Duration) {
+        if let Some(transactions) = &self.transactions {
+            transactions.record(latency);
+        }
+    }
+
+    /// Method to check the size of a message of a service (`msg_type` is the kind of message, `request` or `response`).
+    /// Return a [`ServiceError::MessageTooLarge`] if the message exceeds the limit
+    pub fn check<M>(
+        &self,
+        service: &str,
+        msg: &M,
+        msg_type: &'static str,
+    ) -> Result<(), ServiceError>
+    where
+        M: Tvf,
+    {
+        if self.max_size.is_none() && self.sizes.is_none() {
+            return Ok(());
+        }
+
+        let size = msg.size_hint();
+        let attributes = || {
+            [
+                KeyValue::new("service", service.to_string()),
+                KeyValue::new("type", msg_type),
+            ]
+        };
+        if let Some(sizes) = &self.sizes {
+            sizes.record(size as u64, &attributes());
+        }
+
+        match self.max_size {
+            Some(max_size) if size > max_size => {
+                if let Some(rejected) = &self.rejected {
+                    rejected.add(1, &attributes());
+                }
+                Err(ServiceError::MessageTooLarge(size, max_size))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// State of the circuit of a service
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    /// Calls go through the service, and their failures are tracked
+    Closed,
+    /// Only a few probe calls go through to test the service
+    HalfOpen,
+    /// Calls are rejected without contacting the service
+    Open,
+}
+
+impl CircuitState {
+    /// Value of the state for the circuit gauge
+    fn gauge_value(&self) -> u64 {
+        match self {
+            CircuitState::Closed => 0,
+            CircuitState::HalfOpen => 1,
+            CircuitState::Open => 2,
+        }
+    }
+}
+
+impl fmt::Display for CircuitState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CircuitState::Closed => write!(f, "closed"),
+            CircuitState::HalfOpen => write!(f, "half-open"),
+            CircuitState::Open => write!(f, "open"),
+        }
+    }
+}
+
+/// Settings of a circuit breaker
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct CircuitBreakerSettings {
+    /// Duration of the sliding window where the outcomes of the calls are tracked
+    #[serde(default = "CircuitBreakerSettings::default_window")]
+    pub window: Duration,
+    /// Ratio of failed calls (between 0 and 1) over the window that opens the circuit
+    #[serde(default = "CircuitBreakerSettings::default_failure_ratio")]
+    pub failure_ratio: f64,
+    /// Minimum number of calls over the window before the circuit can open
+    #[serde(default = "CircuitBreakerSettings::default_min_calls")]
+    pub min_calls: u32,
+    /// Duration the circuit stays open before testing the service again
+    #[serde(default = "CircuitBreakerSettings::default_open_duration")]
+    pub open_duration: Duration,
+    /// Number of probe calls allowed when the circuit is half open. The circuit closes when all of them succeed
+    #[serde(default = "CircuitBreakerSettings::default_probes")]
+    pub probes: u32,
+}
+
+impl CircuitBreakerSettings {
+    fn default_window() -> Duration {
+        Duration::from_secs(10)
+    }
+
+    fn default_failure_ratio() -> f64 {
+        0.5
+    }
+
+    fn default_min_calls() -> u32 {
+        10
+    }
+
+    fn default_open_duration() -> Duration {
+        Duration::from_secs(5)
+    }
+
+    fn default_probes() -> u32 {
+        1
+    }
+}
+
+impl Default for CircuitBreakerSettings {
+    fn default() -> Self {
+        CircuitBreakerSettings {
+            window: Self::default_window(),
+            failure_ratio: Self::default_failure_ratio(),
+            min_calls: Self::default_min_calls(),
+            open_duration: Self::default_open_duration(),
+            probes: Self::default_probes(),
+        }
+    }
+}
+
+/// Circuit of a service, with the outcomes of its calls
+#[derive(Debug)]
+struct Circuit {
+    state: CircuitState,
+    since: Instant,
+    outcomes: VecDeque<(Instant, bool)>,
+    failures: usize,
+    probes: u32,
+    probe_successes: u32,
+}
+
+impl Circuit {
+    fn new(now: Instant) -> Circuit {
+        Circuit {
+            state: CircuitState::Closed,
+            since: now,
+            outcomes: VecDeque::new(),
+            failures: 0,
+            probes: 0,
+            probe_successes: 0,
+        }
+    }
+}
+
+/// Circuit breaker of outbound service calls, keyed by service name
+///
+/// The caller reports the outcome of its calls to the breaker.
+/// When the ratio of failed calls over the sliding window reaches the configured ratio, the circuit of the service opens: calls are rejected fast with a [`ServiceError::Unavailable`] that carries the remaining open duration.
+/// Once the open duration elapsed, the circuit is half open and lets a budget of probe calls go through.
+/// The circuit closes if all of them succeed, and opens again on the first failure.
+///
+/// State changes are logged, and exposed with the `prosa_circuit_breaker_state` gauge (0 closed, 1 half open, 2 open) if a meter is set.
+///
+/// Every method have an `_at` variant that takes the current instant, to drive the breaker with your own clock.
+///
+/// ```
+/// use prosa::core::service::{CircuitBreaker, CircuitBreakerSettings, CircuitState};
+///
+/// let breaker = CircuitBreaker::new(CircuitBreakerSettings {
+///     min_calls: 2,
+///     ..Default::default()
+/// });
+///
+/// assert!(breaker.check("SERVICE").is_ok());
+/// breaker.record("SERVICE", false);
+/// breaker.record("SERVICE", false);
+///
+/// assert_eq!(CircuitState::Open, breaker.state("SERVICE"));
+/// assert!(breaker.check("SERVICE").is_err());
+/// assert!(breaker.check("OTHER_SERVICE").is_ok());
+/// ```
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    settings: CircuitBreakerSettings,
+    circuits: Mutex<HashMap<String, Circuit>>,
+    state_gauge: Option<Gauge<u64>>,
+}
+
+impl CircuitBreaker {
+    /// Method to create a circuit breaker with its settings
+    pub fn new(settings: CircuitBreakerSettings) -> CircuitBreaker {
+        CircuitBreaker {
+            settings,
+            circuits: Mutex::new(HashMap::new()),
+            state_gauge: None,
+        }
+    }
+
+    /// Setter of the meter used to expose the state of the circuits (`prosa_circuit_breaker_state` gauge with the `service` attribute)
+    pub fn meter(mut self, meter: &Meter) -> Self {
+        self.state_gauge = Some(
+            meter
+                .u64_gauge("prosa_circuit_breaker_state")
+                .with_description(
+                    "State of the circuit breaker of a service (0 closed, 1 half open, 2 open)",
+                )
+                .init(),
+        );
+        self
+    }
+
+    /// Getter of the circuit breaker settings
+    pub fn get_settings(&self) -> &CircuitBreakerSettings {
+        &self.settings
+    }
+
+    /// Method to know if an error of a call is a failure of the service for the circuit breaker (unavailable, timeout or internal error)
+    pub fn is_failure(error: &ServiceError) -> bool {
+        error.is_retryable() || matches!(error, ServiceError::Internal(_))
+    }
+
+    /// Getter of the state of the service circuit
+    pub fn state(&self, service: &str) -> CircuitState {
+        self.circuits
+            .lock()
+            .unwrap()
+            .get(service)
+            .map(|circuit| circuit.state)
+            .unwrap_or(CircuitState::Closed)
+    }
+
+    /// Method to know if a call to the service is allowed.
+    /// Return a [`ServiceError::Unavailable`] if the circuit is open, or if the probe budget is spent
+    pub fn check(&self, service: &str) -> Result<(), ServiceError> {
+        self.check_at(service, Instant::now())
+    }
+
+    /// Same as [`CircuitBreaker::check`] at a given instant
+    pub fn check_at(&self, service: &str, now: Instant) -> Result<(), ServiceError> {
+        let mut circuits = self.circuits.lock().unwrap();
+        let Some(circuit) = circuits.get_mut(service) else {
+            return Ok(());
+        };
+
+        let reopen = circuit.since + self.settings.open_duration;
+        match circuit.state {
+            CircuitState::Closed => Ok(()),
+            CircuitState::Open if now < reopen => Err(ServiceError::Unavailable(
+                service.to_string(),
+                Some(reopen - now),
+            )),
+            CircuitState::Open => {
+                self.transition(service, circuit, CircuitState::HalfOpen, now);
+                circuit.probes = 1;
+                Ok(())
+            }
+            CircuitState::HalfOpen if circuit.probes < self.settings.probes => {
+                circuit.probes += 1;
+                Ok(())
+            }
+            CircuitState::HalfOpen if now < reopen => Err(ServiceError::Unavailable(
+                service.to_string(),
+                Some(reopen - now),
+            )),
+            CircuitState::HalfOpen => {
+                // The outcome of the probes was never reported, probe again
+                circuit.since = now;
+                circuit.probes = 1;
+                circuit.probe_successes = 0;
+                Ok(())
+            }
+        }
+    }
+
+    /// Method to report the outcome of a call to the service
+    pub fn record(&self, service: &str, success: bool) {
+        self.record_at(service, success, Instant::now())
+    }
+
+    /// Same as [`CircuitBreaker::record`] at a given instant
+    pub fn record_at(&self, service: &str, success: bool, now: Instant) {
+        let mut circuits = self.circuits.lock().unwrap();
+        let circuit = circuits
+            .entry(service.to_string())
+            .or_insert_with(|| Circuit::new(now));
+
+        match circuit.state {
+            CircuitState::Closed => {
+                circuit.outcomes.push_back((now, success));
+                if !success {
+                    circuit.failures += 1;
+                }
+
+                // Forget the outcomes out of the sliding window
+                while let Some((instant, success)) = circuit.outcomes.front() {
+                    if *instant + self.settings.window > now {
+                        break;
+                    }
+
+                    if !success {
+                        circuit.failures -= 1;
+                    }
+                    circuit.outcomes.pop_front();
+                }
+
+                let calls = circuit.outcomes.len();
+                if !success
+                    && calls >= self.settings.min_calls.max(1) as usize
+                    && circuit.failures as f64 >= self.settings.failure_ratio * calls as f64
+                {
+                    self.transition(service, circuit, CircuitState::Open, now);
+                }
+            }
+            CircuitState::HalfOpen if success => {
+                circuit.probe_successes += 1;
+                if circuit.probe_successes >= self.settings.probes {
+                    self.transition(service, circuit, CircuitState::Closed, now);
+                }
+            }
+            CircuitState::HalfOpen => self.transition(service, circuit, CircuitState::Open, now),
+            // Late outcome of a call made before the circuit opened
+            CircuitState::Open => {}
+        }
+    }
+
+    /// Method to change the state of a service circuit
+    fn transition(&self, service: &str, circuit: &mut Circuit, state: CircuitState, now: Instant) {
+        if state == CircuitState::Open {
+            warn!(target: "prosa::core::service", service, from = %circuit.state, to = %state, failures = circuit.failures, calls = circuit.outcomes.len(), "Circuit breaker opened");
+        } else {
+            info!(target: "prosa::core::service", service, from = %circuit.state, to = %state, "Circuit breaker state changed");
+        }
+
+        circuit.state = state;
+        circuit.since = now;
+        circuit.outcomes.clear();
+        circuit.failures = 0;
+        circuit.probes = 0;
+        circuit.probe_successes = 0;
+        if let Some(gauge) = &self.state_gauge {
+            gauge.record(
+                state.gauge_value(),
+                &[KeyValue::new("service", service.to_string())],
+            );
+        }
+    }
+}
+
+/// Settings of a duplicate detection cache
+///
+/// ```yaml
+/// dedup:
+///   key_path: [1, 2]
+///   window:
+///     secs: 300
+///     nanos: 0
+///   capacity: 10000
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct DedupSettings {
+    /// Tag path of the idempotency key in the requests (a string or an integer field)
+    pub key_path: Vec<usize>,
+    /// Duration a request is remembered after its registration
+    #[serde(default = "DedupSettings::default_window")]
+    pub window: Duration,
+    /// Maximum number of remembered requests. The oldest ones are forgotten first
+    #[serde(default = "DedupSettings::default_capacity")]
+    pub capacity: usize,
+}
+
+impl DedupSettings {
+    fn default_window() -> Duration {
+        Duration::from_secs(300)
+    }
+
+    fn default_capacity() -> usize {
+        10000
+    }
+
+    /// Method to create duplicate detection settings with the tag path of the idempotency key
+    pub fn new(key_path: Vec<usize>) -> DedupSettings {
+        DedupSettings {
+            key_path,
+            window: Self::default_window(),
+            capacity: Self::default_capacity(),
+        }
+    }
+}
+
+/// Outcome of a request checked by a [`DedupCache`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum DedupOutcome<M> {
+    /// First occurrence of the key in the window, the request is registered as in flight and must be executed
+    New,
+    /// A request with the same key is still being executed
+    InFlight,
+    /// A request with the same key already completed with this response
+    Completed(M),
+}
+
+/// Request remembered by a [`DedupCache`]
+#[derive(Debug)]
+struct DedupEntry<M> {
+    registered: Instant,
+    response: Option<M>,
+}
+
+/// Remembered requests of a [`DedupCache`], with their registration order
+#[derive(Debug)]
+struct DedupEntries<M> {
+    entries: HashMap<String, DedupEntry<M>>,
+    order: VecDeque<(Instant, String)>,
+}
+
+impl<M> DedupEntries<M> {
+    /// Method to remove a key if it's still the registration of the given instant (the key may have been removed, and registered again after)
+    fn remove_registered(&mut self, key: &str, registered: Instant) {
+        if self
+            .entries
+            .get(key)
+            .is_some_and(|entry| entry.registered == registered)
+        {
+            self.entries.remove(key);
+        }
+    }
+}
+
+/// Bounded and time windowed cache to detect duplicated requests with their idempotency key
+///
+/// When a partner retries a request, the duplicate is detected so the original response can be returned instead of executing the business logic again.
+/// A new key is registered as in flight, then completed with its response.
+/// Keys are forgotten once the window elapsed since their registration, or when the capacity is reached (oldest first).
+///
+/// Duplicates are counted by the `prosa_dedup_hits` counter (with the `service` and `outcome` attributes) if a meter is set.
+///
+/// Every method that depend on the time have an `_at` variant that takes the current instant, to drive the cache with your own clock.
+///
+/// ```
+/// use prosa::core::service::{DedupCache, DedupOutcome, DedupSettings};
+/// use prosa_utils::msg::simple_string_tvf::SimpleStringTvf;
+/// use prosa_utils::msg::tvf::Tvf;
+///
+/// let cache = DedupCache::<SimpleStringTvf>::new(DedupSettings::new(vec![1]));
+///
+/// let mut request = SimpleStringTvf::default();
+/// request.put_string(1, "idempotency-key");
+/// let key = cache.key(&request).unwrap();
+///
+/// assert_eq!(DedupOutcome::New, cache.check_or_register(&key));
+/// assert_eq!(DedupOutcome::InFlight, cache.check_or_register(&key));
+///
+/// let mut response = request.clone();
+/// response.put_string(2, "done");
+/// cache.complete(&key, response.clone());
+/// assert_eq!(DedupOutcome::Completed(response), cache.check_or_register(&key));
+/// ```
+#[derive(Debug)]
+pub struct DedupCache<M> {
+    settings: DedupSettings,
+    entries: Mutex<DedupEntries<M>>,
+    hits: Option<Counter<u64>>,
+}
+
+impl<M> DedupCache<M>
+where
+    M: Tvf + Default + Debug + Clone,
+{
+    /// Method to create a duplicate detection cache with its settings
+    pub fn new(settings: DedupSettings) -> DedupCache<M> {
+        DedupCache {
+            settings,
+            entries: Mutex::new(DedupEntries {
+                entries: HashMap::new(),
+                order: VecDeque::new(),
+            }),
+            hits: None,
+        }
+    }
+
+    /// Setter of the meter used to count the duplicate hits (`prosa_dedup_hits` counter)
+    pub fn meter(mut self, meter: &Meter) -> Self {
+        self.hits = Some(
+            meter
+                .u64_counter("prosa_dedup_hits")
+                .with_description("Number of duplicated requests detected by their idempotency key")
+                .init(),
+        );
+        self
+    }
+
+    /// Getter of the duplicate detection settings
+    pub fn get_settings(&self) -> &DedupSettings {
+        &self.settings
+    }
+
+    /// Method to extract the idempotency key of a request from the configured tag path.
+    /// Return `None` if the request don't have a string or an integer at this path
+    pub fn key(&self, request: &M) -> Option<String> {
+        let path = self.settings.key_path.as_slice();
+        request
+            .require_str(path)
+            .map(|key| key.into_owned())
+            .or_else(|_| request.require_unsigned(path).map(|key| key.to_string()))
+            .or_else(|_| request.require_signed(path).map(|key| key.to_string()))
+            .ok()
+    }
+
+    /// Getter of the number of remembered requests (including the expired ones that are not purged yet)
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().entries.len()
+    }
+
+    /// Method to know if no request is remembered
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Method to check if a key was already seen, and register it as in flight if not
+    pub fn check_or_register(&self, key: &str) -> DedupOutcome<M> {
+        self.check_or_register_at(key, Instant::now())
+    }
+
+    /// Same as [`DedupCache::check_or_register`] at a given instant
+    pub fn check_or_register_at(&self, key: &str, now: Instant) -> DedupOutcome<M> {
+        let mut dedup = self.entries.lock().unwrap();
+        self.purge(&mut dedup, now);
+
+        if let Some(entry) = dedup.entries.get(key) {
+            return match &entry.response {
+                Some(response) => DedupOutcome::Completed(response.clone()),
+                None => DedupOutcome::InFlight,
+            };
+        }
+
+        // Forget the oldest requests to respect the capacity
+        while dedup.entries.len() >= self.settings.capacity.max(1) {
+            let Some((registered, oldest)) = dedup.order.pop_front() else {
+                break;
+            };
+            dedup.remove_registered(&oldest, registered);
+        }
+
+        dedup.entries.insert(
+            key.to_string(),
+            DedupEntry {
+                registered: now,
+                response: None,
+            },
+        );
+        dedup.order.push_back((now, key.to_string()));
+        DedupOutcome::New
+    }
+
+    /// Method to complete an in flight request with its response. The response will be returned for the duplicates of the request
+    pub fn complete(&self, key: &str, response: M) {
+        if let Some(entry) = self.entries.lock().unwrap().entries.get_mut(key) {
+            entry.response = Some(response);
+        }
+    }
+
+    /// Method to forget a request (if it failed for example), so a retry of the request will be executed again
+    pub fn remove(&self, key: &str) {
+        self.entries.lock().unwrap().entries.remove(key);
+    }
+
+    /// Method to count a duplicate hit of a service on the meter
+    pub fn record_hit(&self, service: &str, outcome: &DedupOutcome<M>) {
+        if let Some(hits) = &self.hits {
+            let outcome = match outcome {
+                DedupOutcome::New => return,
+                DedupOutcome::InFlight => "in_flight",
+                DedupOutcome::Completed(_) => "completed",
+            };
+            hits.add(
+                1,
+                &[
+                    KeyValue::new("service", service.to_string()),
+                    KeyValue::new("outcome", outcome),
+                ],
+            );
+        }
+    }
+
+    /// Method to forget the requests registered before the window
+    fn purge(&self, dedup: &mut DedupEntries<M>, now: Instant) {
+        while let Some((registered, _)) = dedup.order.front() {
+            if *registered + self.settings.window > now {
+                break;
+            }
+
+            if let Some((registered, key)) = dedup.order.pop_front() {
+                dedup.remove_registered(&key, registered);
+            }
+        }
+    }
+}
+
+/// Counter of the service call message ids
+static SERVICE_CALL_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Builder of a request to a service, with a timeout, retries and failover
+///
+/// On a retryable error ([`ServiceError::Unavailable`] or [`ServiceError::Timeout`]), the request is sent again after the backoff (or after the retry duration given by the service if it's longer).
+/// A retry prefers a processor service that didn't fail yet.
+///
+/// Every attempt have its own response queue, so the response don't need to be correlated on the processor internal queue.
+/// A late response of a failed attempt is dropped.
+///
+/// With a deadline, requests carry it to the processors (see [`RequestMsg::with_deadline`]), attempts are shortened to not exceed it, and no retry is made once it's expired.
+///
+/// With a [`CircuitBreaker`], the outcome of every attempt is reported to it, and the call is rejected fast (without retry) while the circuit of the service is open.
+///
+/// ```
+/// use std::{sync::Arc, time::Duration};
+/// use prosa::core::service::{ServiceCall, ServiceError, ServiceTable};
+/// use prosa_utils::msg::simple_string_tvf::SimpleStringTvf;
+///
+/// async fn call(
+///     table: Arc<ServiceTable<SimpleStringTvf>>,
+///     request: SimpleStringTvf,
+/// ) -> Result<SimpleStringTvf, ServiceError> {
+///     ServiceCall::new(table, "SERVICE")
+///         .timeout(Duration::from_secs(1))
+///         .retries(2)
+///         .backoff(Duration::from_millis(100))
+///         .send(request)
+///         .await
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct ServiceCall<M>
+where
+    M: Sized + Clone + Tvf,
+{
+    table: Arc<ServiceTable<M>>,
+    service: String,
+    msg_id: Option<u64>,
+    timeout: Duration,
+    retries: u32,
+    backoff: Duration,
+    deadline: Option<SystemTime>,
+    retry_counter: Option<Counter<u64>>,
+    circuit_breaker: Option<Arc<CircuitBreaker>>,
+}
+
+impl<M> ServiceCall<M>
+where
+    M: Sized + Clone + Debug + Tvf + Default + 'static + std::marker::Send + std::marker::Sync,
+{
+    /// Default timeout of a service call attempt
+    pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// Method to create a service call on the service table (without retry by default)
+    pub fn new<S>(table: Arc<ServiceTable<M>>, service: S) -> ServiceCall<M>
+    where
+        S: Into<String>,
+    {
+        ServiceCall {
+            table,
+            service: service.into(),
+            msg_id: None,
+            timeout: Self::DEFAULT_TIMEOUT,
+            retries: 0,
+            backoff: Duration::ZERO,
+            deadline: None,
+            retry_counter: None,
+            circuit_breaker: None,
+        }
+    }
+
+    /// Setter of the request message id (a unique id is generated otherwise)
+    pub fn id(mut self, msg_id: u64) -> Self {
+        self.msg_id = Some(msg_id);
+        self
+    }
+
+    /// Setter of the timeout of each attempt
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Setter of the maximum number of retries after the first attempt
+    pub fn retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// Setter of the duration to wait before a retry
+    pub fn backoff(mut self, backoff: Duration) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// Setter of the end-to-end deadline of the request
+    pub fn deadline(mut self, deadline: SystemTime) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Setter of the meter used to count retries (`prosa_service_call_retries` counter with the `service` attribute)
+    pub fn meter(mut self, meter: &Meter) -> Self {
+        self.retry_counter = Some(
+            meter
+                .u64_counter("prosa_service_call_retries")
+                .with_description("Number of retries of service calls")
+                .init(),
+        );
+        self
+    }
+
+    /// Setter of the circuit breaker that protects the service
+    pub fn circuit_breaker(mut self, circuit_breaker: Arc<CircuitBreaker>) -> Self {
+        self.circuit_breaker = Some(circuit_breaker);
+        self
+    }
+
+    /// Method to send the request to the service and wait for its response
+    pub async fn send(&self, msg: M) -> Result<M, ServiceError> {
+        let msg_id = self
+            .msg_id
+            .unwrap_or_else(|| SERVICE_CALL_ID.fetch_add(1, Ordering::Relaxed));
+        let mut failed_services = Vec::new();
+        let mut retries = 0;
+        loop {
+            if self
+                .deadline
+                .is_some_and(|deadline| deadline <= SystemTime::now())
+            {
+                return Err(ServiceError::Timeout(
+                    self.service.clone(),
+                    self.timeout.as_millis() as u64,
+                ));
+            }
+
+            if let Some(circuit_breaker) = &self.circuit_breaker {
+                circuit_breaker.check(&self.service)?;
+            }
+
+            let (proc_service, response) =
+                self.attempt(msg_id, &failed_services, msg.clone()).await;
+            if let (Some(circuit_breaker), Some(_)) = (&self.circuit_breaker, &proc_service) {
+                circuit_breaker.record(
+                    &self.service,
+                    !matches!(&response, Err(err) if CircuitBreaker::is_failure(err)),
+                );
+            }
+            match response {
+                Err(err) if err.is_retryable() && retries < self.retries => {
+                    retries += 1;
+                    if let Some(counter) = &self.retry_counter {
+                        counter.add(1, &[KeyValue::new("service", self.service.clone())]);
+                    }
+                    if let Some(proc_service) = proc_service {
+                        failed_services.push(proc_service);
+                    }
+
+                    tokio::time::sleep(self.backoff.max(err.recovery_duration())).await;
+                }
+                response => return response,
+            }
+        }
+    }
+
+    /// Method to send one attempt of the request, return the processor service used with its response
+    async fn attempt(
+        &self,
+        msg_id: u64,
+        failed_services: &[ProcService<M>],
+        msg: M,
+    ) -> (Option<ProcService<M>>, Result<M, ServiceError>) {
+        let Some(proc_service) =
+            self.table
+                .get_proc_service_except(&self.service, msg_id, failed_services)
+        else {
+            return (
+                None,
+                Err(ServiceError::UnknownService(self.service.clone())),
+            );
+        };
+
+        let (response_queue, mut response_rx) = mpsc::channel(1);
+        let mut request = RequestMsg::new(msg_id, self.service.clone(), msg, response_queue);
+        let mut timeout = self.timeout;
+        if let Some(deadline) = self.deadline {
+            request = request.with_deadline(deadline);
+            timeout = timeout.min(request.get_remaining_time().unwrap_or_default());
+        }
+
+        if proc_service.send_request(request).await.is_err() {
+            return (
+                Some(proc_service.clone()),
+                Err(ServiceError::Unavailable(self.service.clone(), None)),
+            );
+        }
+
+        let response = match tokio::time::timeout(timeout, response_rx.recv()).await {
+            Ok(Some(InternalMsg::Response(response))) => Ok(response.get_data().clone()),
+            Ok(Some(InternalMsg::Error(error))) => Err(error.get_err().clone()),
+            Ok(_) => Err(ServiceError::Unavailable(self.service.clone(), None)),
+            Err(_) => Err(ServiceError::Timeout(
+                self.service.clone(),
+                timeout.as_millis() as u64,
+            )),
+        };
+        (Some(proc_service.clone()), response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use prosa_utils::msg::{simple_string_tvf::SimpleStringTvf, tvf::Tvf};
+
+    use super::*;
+
+    fn test_proc_service(proc_id: u32) -> ProcService<SimpleStringTvf> {
+        let proc_queue = mpsc::channel(1).0;
+        ProcService {
+            proc_id,
+            queue_id: 0,
+            group: None,
+            shutdown_rank: 0,
+            size_limit: MessageSizeLimit::new(None),
+            data_queue: proc_queue.clone().into(),
+            proc_queue,
+            ctrl_queue: None,
+        }
+    }
+
+    #[test]
+    fn service_name_validation() {
+        for name in ["PAY", "PAY.AUTH", "PAY.AUTH-V2.*", "*", "pay_1.*"] {
+            assert!(ServiceName::new(name).is_ok(), "{name} should be valid");
+        }
+
+        assert_eq!(Err(ServiceNameError::Empty), ServiceName::new(""));
+        assert_eq!(
+            Err(ServiceNameError::TooLong(
+                "A".repeat(SERVICE_NAME_MAX_LEN + 1),
+                SERVICE_NAME_MAX_LEN + 1
+            )),
+            ServiceName::new("A".repeat(SERVICE_NAME_MAX_LEN + 1))
+        );
+        assert_eq!(
+            Err(ServiceNameError::InvalidChar(String::from("PAY AUTH"), ' ')),
+            ServiceName::new("PAY AUTH")
+        );
+        for name in ["PAY..AUTH", ".PAY", "PAY."] {
+            assert_eq!(
+                Err(ServiceNameError::EmptySegment(String::from(name))),
+                ServiceName::new(name)
+            );
+        }
+        for name in ["PAY.*.AUTH", "PAY*", "PAY.A*", "**"] {
+            assert_eq!(
+                Err(ServiceNameError::MisplacedWildcard(String::from(name))),
+                ServiceName::new(name)
+            );
+        }
+
+        // Plain strings are still accepted without validation
+        assert_eq!("PAY AUTH", ServiceName::from("PAY AUTH").as_str());
+
+        // Names are validated when they are deserialized
+        assert_eq!(
+            ServiceName::from("PAY.*"),
+            serde_yaml::from_str::<ServiceName>("PAY.*").unwrap()
+        );
+        assert!(serde_yaml::from_str::<ServiceName>("PAY..*").is_err());
+
+        let wildcard = ServiceName::from("PAY.*");
+        assert!(wildcard.is_wildcard());
+        assert_eq!(Some("PAY"), wildcard.namespace());
+        assert!(wildcard.matches("PAY.AUTH"));
+        assert!(wildcard.matches("PAY.AUTH.V2"));
+        assert!(!wildcard.matches("PAY"));
+        assert!(!wildcard.matches("PAYMENT"));
+        assert!(ServiceName::from("*").matches("PAY"));
+        assert!(ServiceName::from("PAY").matches("PAY"));
+        assert_eq!(
+            vec!["PAY.AUTH.*", "PAY.*", "*"],
+            ServiceName::wildcards("PAY.AUTH.V2").collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn service_table_wildcard_precedence() {
+        let mut table = ServiceTable::default();
+        table.add_service("*", test_proc_service(1));
+        table.add_service("PAY.*", test_proc_service(2));
+        table.add_service("PAY.AUTH.*", test_proc_service(3));
+        table.add_service("PAY.AUTH", test_proc_service(4));
+
+        let proc_id = |table: &ServiceTable<SimpleStringTvf>, name: &str| {
+            table
+                .get_proc_service(name, 0)
+                .map(ProcService::get_proc_id)
+        };
+
+        // Exact service first, then the most specific wildcard
+        assert_eq!(Some(4), proc_id(&table, "PAY.AUTH"));
+        assert_eq!(Some(3), proc_id(&table, "PAY.AUTH.V2"));
+        assert_eq!(Some(2), proc_id(&table, "PAY.REFUND"));
+        assert_eq!(Some(1), proc_id(&table, "CARD"));
+        assert_eq!(Some(1), proc_id(&table, "PAYMENT.AUTH"));
+
+        // A removed exact service fallback on the wildcards
+        table.remove_service_proc("PAY.AUTH", 4);
+        assert_eq!(Some(2), proc_id(&table, "PAY.AUTH"));
+        table.remove_proc_services(1);
+        assert_eq!(None, proc_id(&table, "CARD"));
+        assert!(!table.exist_proc_service("CARD"));
+        assert!(table.exist_proc_service("PAY.AUTH.V2"));
+
+        // Without any wildcard, only the exact services are served
+        let mut table = ServiceTable::<SimpleStringTvf>::default();
+        table.add_service("PAY.AUTH", test_proc_service(1));
+        assert!(table.exist_proc_service("PAY.AUTH"));
+        assert!(!table.exist_proc_service("PAY.REFUND"));
+    }
+
+    #[test]
+    fn service_table_wildcard_fan_in() {
+        let mut table = ServiceTable::default();
+        table.add_service("PAY.*", test_proc_service(1));
+        table.add_service("PAY.*", test_proc_service(2));
+        table.add_service("PAY.*", test_proc_service(2));
+
+        // Every service of the namespace is load balanced on the processors of the wildcard
+        for service in ["PAY.AUTH", "PAY.REFUND", "PAY.AUTH.V2"] {
+            assert_eq!(2, table.count_proc_service(service));
+            assert_eq!(
+                Some(1),
+                table
+                    .get_proc_service(service, 0)
+                    .map(ProcService::get_proc_id)
+            );
+            assert_eq!(
+                Some(2),
+                table
+                    .get_proc_service(service, 1)
+                    .map(ProcService::get_proc_id)
+            );
+            assert_eq!(
+                Some(2),
+                table
+                    .get_proc_service_except(service, 0, &[test_proc_service(1)])
+                    .map(ProcService::get_proc_id)
+            );
+        }
+
+        // The wildcard registration is still an exact service, and other namespaces are not served
+        assert_eq!(2, table.count_proc_service("PAY.*"));
+        assert_eq!(0, table.count_proc_service("CARD.AUTH"));
+    }
+
+    #[test]
+    fn service_error_tvf_encoding() {
+        let errors = [
+            ServiceError::NoError(String::from("SRV")),
+            ServiceError::UnknownService(String::from("SRV")),
+            ServiceError::Unavailable(String::from("SRV"), None),
+            ServiceError::Unavailable(String::from("SRV"), Some(Duration::from_millis(1500))),
+            ServiceError::Timeout(String::from("SRV"), 200),
+            ServiceError::ProtocolError {
+                code: 42,
+                reason: String::from("bad field"),
+            },
+            ServiceError::Internal(String::from("internal failure")),
+            ServiceError::MessageTooLarge(2048, 1024),
+        ];
+
+        for err in errors {
+            let mut response = SimpleStringTvf::default();
+            response.put_string(1, "data");
+            err.encode(&mut response);
+
+            assert_eq!("data", response.get_string(1).unwrap().as_str());
+            let err_buf = response
+                .get_buffer(ServiceError::SERVICE_ERROR_TAG)
+                .unwrap();
+            assert_eq!(err.error_code() as u64, err_buf.get_unsigned(1).unwrap());
+            assert_eq!(Some(err), ServiceError::decode(&response).unwrap());
+        }
+
+        assert_eq!(
+            None,
+            ServiceError::decode(&SimpleStringTvf::default()).unwrap()
+        );
+
+        let mut wrong_response = SimpleStringTvf::default();
+        let mut err_buf = SimpleStringTvf::default();
+        err_buf.put_unsigned(1, 999);
+        wrong_response.put_buffer(ServiceError::SERVICE_ERROR_TAG, err_buf);
+        assert!(ServiceError::decode(&wrong_response).is_err());
+    }
+
+    #[test]
+    fn circuit_breaker_transitions() {
+        const SERVICE: &str = "SRV";
+        let breaker = CircuitBreaker::new(CircuitBreakerSettings {
+            window: Duration::from_secs(10),
+            failure_ratio: 0.5,
+            min_calls: 4,
+            open_duration: Duration::from_secs(5),
+            probes: 2,
+        });
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+
+        // Failures out of the sliding window are forgotten
+        breaker.record_at(SERVICE, false, at(0));
+        breaker.record_at(SERVICE, false, at(1));
+        breaker.record_at(SERVICE, true, at(11));
+        breaker.record_at(SERVICE, false, at(12));
+        assert_eq!(CircuitState::Closed, breaker.state(SERVICE));
+        assert!(breaker.check_at(SERVICE, at(12)).is_ok());
+
+        // Half of the calls failed over the window
+        breaker.record_at(SERVICE, true, at(13));
+        breaker.record_at(SERVICE, false, at(14));
+        assert_eq!(CircuitState::Open, breaker.state(SERVICE));
+        assert_eq!(
+            Err(ServiceError::Unavailable(
+                String::from(SERVICE),
+                Some(Duration::from_secs(2))
+            )),
+            breaker.check_at(SERVICE, at(17))
+        );
+        assert!(breaker.check_at("OTHER_SRV", at(17)).is_ok());
+
+        // Probe budget once the open duration elapsed, a failed probe opens the circuit again
+        assert!(breaker.check_at(SERVICE, at(19)).is_ok());
+        assert_eq!(CircuitState::HalfOpen, breaker.state(SERVICE));
+        assert!(breaker.check_at(SERVICE, at(19)).is_ok());
+        assert!(breaker.check_at(SERVICE, at(19)).is_err());
+        breaker.record_at(SERVICE, true, at(20));
+        breaker.record_at(SERVICE, false, at(20));
+        assert_eq!(CircuitState::Open, breaker.state(SERVICE));
+        assert!(breaker.check_at(SERVICE, at(24)).is_err());
+
+        // Every probes succeed, the circuit closes
+        assert!(breaker.check_at(SERVICE, at(25)).is_ok());
+        assert!(breaker.check_at(SERVICE, at(25)).is_ok());
+        breaker.record_at(SERVICE, true, at(26));
+        assert_eq!(CircuitState::HalfOpen, breaker.state(SERVICE));
+        breaker.record_at(SERVICE, true, at(26));
+        assert_eq!(CircuitState::Closed, breaker.state(SERVICE));
+
+        // Outcomes before closing are forgotten
+        breaker.record_at(SERVICE, false, at(27));
+        breaker.record_at(SERVICE, false, at(27));
+        breaker.record_at(SERVICE, false, at(27));
+        assert_eq!(CircuitState::Closed, breaker.state(SERVICE));
+        breaker.record_at(SERVICE, false, at(27));
+        assert_eq!(CircuitState::Open, breaker.state(SERVICE));
+    }
+
+    #[test]
+    fn circuit_breaker_lost_probe() {
+        const SERVICE: &str = "SRV";
+        let breaker = CircuitBreaker::new(CircuitBreakerSettings {
+            min_calls: 1,
+            ..Default::default()
+        });
+        let start = Instant::now();
+        let open_duration = breaker.get_settings().open_duration;
+
+        breaker.record_at(SERVICE, false, start);
+        assert!(breaker.check_at(SERVICE, start + open_duration).is_ok());
+        assert!(breaker.check_at(SERVICE, start + open_duration).is_err());
+
+        // The probe outcome is never reported, a new probe is allowed after the open duration
+        assert!(breaker.check_at(SERVICE, start + open_duration * 2).is_ok());
+        assert_eq!(CircuitState::HalfOpen, breaker.state(SERVICE));
+    }
+
+    #[tokio::test]
+    async fn service_call_circuit_breaker() {
+        let breaker = Arc::new(CircuitBreaker::new(CircuitBreakerSettings {
+            min_calls: 1,
+            ..Default::default()
+        }));
+        let call = ServiceCall::<SimpleStringTvf>::new(Arc::new(ServiceTable::default()), "SRV")
+            .retries(3)
+            .circuit_breaker(breaker.clone());
+
+        // No outcome to report if the service is unknown
+        assert_eq!(
+            Err(ServiceError::UnknownService(String::from("SRV"))),
+            call.send(SimpleStringTvf::default()).await
+        );
+        assert_eq!(CircuitState::Closed, breaker.state("SRV"));
+
+        // The call is rejected fast while the circuit is open
+        breaker.record("SRV", false);
+        assert!(matches!(
+            call.send(SimpleStringTvf::default()).await,
+            Err(ServiceError::Unavailable(_, Some(_)))
+        ));
+    }
+
+    #[test]
+    fn service_error_retry() {
+        assert!(!ServiceError::UnknownService(String::from("SRV")).is_retryable());
+        assert!(ServiceError::Timeout(String::from("SRV"), 200).is_retryable());
+        assert!(!ServiceError::Internal(String::from("SRV")).is_retryable());
+
+        let err = ServiceError::Unavailable(String::from("SRV"), Some(Duration::from_secs(2)));
+        assert!(err.is_retryable());
+        assert!(err.recoverable());
+        assert_eq!(Duration::from_secs(2), err.recovery_duration());
+
+        let err = ServiceError::ProtocolError {
+            code: 1,
+            reason: String::from("bad field"),
+        };
+        assert!(!err.is_retryable());
+        assert!(!err.recoverable());
+        assert_eq!(Duration::ZERO, err.recovery_duration());
+    }
+
+    #[test]
+    fn dedup_cache_outcomes() {
+        let cache = DedupCache::<SimpleStringTvf>::new(DedupSettings::new(vec![1]));
+
+        let mut request = SimpleStringTvf::default();
+        assert_eq!(None, cache.key(&request));
+        request.put_unsigned(1, 42);
+        assert_eq!(Some(String::from("42")), cache.key(&request));
+
+        assert_eq!(DedupOutcome::New, cache.check_or_register("42"));
+        assert_eq!(DedupOutcome::InFlight, cache.check_or_register("42"));
+        assert_eq!(DedupOutcome::New, cache.check_or_register("43"));
+
+        let mut response = request.clone();
+        response.put_string(2, "done");
+        cache.complete("42", response.clone());
+        assert_eq!(
+            DedupOutcome::Completed(response),
+            cache.check_or_register("42")
+        );
+        assert_eq!(DedupOutcome::InFlight, cache.check_or_register("43"));
+
+        // A forgotten request is executed again
+        cache.remove("43");
+        assert_eq!(DedupOutcome::New, cache.check_or_register("43"));
+        assert_eq!(2, cache.len());
+    }
+
+    #[test]
+    fn dedup_cache_expiry() {
+        let mut settings = DedupSettings::new(vec![1]);
+        settings.window = Duration::from_secs(10);
+        settings.capacity = 2;
+        let cache = DedupCache::<SimpleStringTvf>::new(settings);
+
+        let now = Instant::now();
+        assert_eq!(DedupOutcome::New, cache.check_or_register_at("1", now));
+        cache.complete("1", SimpleStringTvf::default());
+        assert!(matches!(
+            cache.check_or_register_at("1", now + Duration::from_secs(9)),
+            DedupOutcome::Completed(_)
+        ));
+
+        // The key is forgotten once the window elapsed
+        assert_eq!(
+            DedupOutcome::New,
+            cache.check_or_register_at("1", now + Duration::from_secs(10))
+        );
+        assert_eq!(
+            DedupOutcome::New,
+            cache.check_or_register_at("2", now + Duration::from_secs(11))
+        );
+
+        // The oldest key is forgotten to respect the capacity
+        assert_eq!(
+            DedupOutcome::New,
+            cache.check_or_register_at("3", now + Duration::from_secs(12))
+        );
+        assert_eq!(2, cache.len());
+        assert_eq!(
+            DedupOutcome::InFlight,
+            cache.check_or_register_at("2", now + Duration::from_secs(12))
+        );
+        assert_eq!(
+            DedupOutcome::New,
+            cache.check_or_register_at("1", now + Duration::from_secs(12))
+        );
+
+        // Every key expire after the window
+        cache.check_or_register_at("4", now + Duration::from_secs(30));
+        assert_eq!(1, cache.len());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn request_batcher_flush() {
+        let (proc_queue, mut proc_rx) = mpsc::channel(8);
+        let service = ProcService {
+            data_queue: proc_queue.clone().into(),
+            proc_queue,
+            ..test_proc_service(1)
+        };
+        let (response_queue, _response_rx) = mpsc::channel(8);
+        let request = |id| {
+            RequestMsg::new(
+                id,
+                String::from("SRV"),
+                SimpleStringTvf::default(),
+                response_queue.clone(),
+            )
+        };
+
+        let mut batcher = RequestBatcher::new(BatchSettings::new(3, Duration::from_millis(1)));
+        assert_eq!(None, batcher.next_flush());
+
+        // A full batch is sent right away
+        for id in 0..4 {
+            batcher.push(&service, request(id)).await.unwrap();
+        }
+        match proc_rx.try_recv() {
+            Ok(InternalMsg::RequestBatch(batch)) => assert_eq!(
+                vec![0, 1, 2],
+                batch.iter().map(|msg| msg.get_id()).collect::<Vec<u64>>()
+            ),
+            msg => panic!("Unexpected message {:?}", msg),
+        }
+        assert_eq!(1, batcher.len());
+
+        // The remaining request is sent once it waited the maximum latency
+        batcher.flush_expired().await.unwrap();
+        assert!(proc_rx.try_recv().is_err());
+        tokio::time::timeout(Duration::from_secs(1), batcher.wait_flush())
+            .await
+            .unwrap();
+        batcher.flush_expired().await.unwrap();
+        assert!(batcher.is_empty());
+        assert!(matches!(
+            proc_rx.try_recv(),
+            Ok(InternalMsg::Request(msg)) if msg.get_id() == 3
+        ));
+
+        // Without batch, the batcher never need to flush
+        assert!(
+            tokio::time::timeout(Duration::from_secs(1), batcher.wait_flush())
+                .await
+                .is_err()
+        );
+
+        batcher.push(&service, request(4)).await.unwrap();
+        batcher.push(&service, request(5)).await.unwrap();
+        batcher.flush().await.unwrap();
+        assert!(matches!(
+            proc_rx.try_recv(),
+            Ok(InternalMsg::RequestBatch(batch)) if batch.len() == 2
+        ));
     }
 }
@@ -3,14 +3,41 @@
 #![doc = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/doc_assets/settings.svg"))]
 //! </svg>
 
-use std::io::{self, Write};
+use std::{
+    collections::HashMap,
+    fmt, fs,
+    io::{self, Write},
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, Instant, SystemTime},
+};
 
+use config::{Config, File};
+use opentelemetry::KeyValue;
+use opentelemetry_sdk::Resource;
 use prosa_utils::config::observability::Observability;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::time;
+
+use super::audit::AuditSettings;
+use super::state::StateSettings;
+use super::tps::TpsSettings;
 
 /// Implement the trait [`Settings`]
 pub use prosa_macros::settings;
 
+/// Error of the configuration, re-exported for the processors macros so processors don't depend on the `config` crate
+pub use config::ConfigError;
+/// Bound of the deserializable settings, re-exported for the processors macros
+pub use serde::de::DeserializeOwned;
+
+mod report;
+mod strict;
+
+pub(crate) use report::deserialize_located;
+pub use report::{ConfigIssue, ConfigReport, ProcConfigReport};
+
 /// Running settings of a ProSA
 /// Need to be implemented by the top settings layer of a ProSA
 ///
@@ -40,15 +67,30 @@ pub use prosa_macros::settings;
 /// is equivalent to
 ///
 /// ```
-/// use prosa::core::settings::Settings;
+/// use prosa::core::audit::AuditSettings;
+/// use prosa::core::settings::{ConfigWatch, Heartbeat, Identity, Settings, Shutdown};
+/// use prosa::core::state::StateSettings;
+/// use prosa::core::tps::TpsSettings;
 /// use prosa_utils::config::observability::Observability;
 /// use serde::Serialize;
+/// use std::path::PathBuf;
 ///
 /// #[derive(Debug, Serialize)]
 /// struct MySameSettings {
 ///     test_val: String,
 ///     name: Option<String>,
 ///     observability: Observability,
+///     config_watch: Option<ConfigWatch>,
+///     heartbeat: Option<Heartbeat>,
+///     identity: Option<Identity>,
+///     max_message_size: Option<usize>,
+///     ctl_socket: Option<PathBuf>,
+///     state: Option<StateSettings>,
+///     shutdown: Option<Shutdown>,
+///     audit: Option<AuditSettings>,
+///     tps: Option<TpsSettings>,
+///     ready_routing: Option<bool>,
+///     dead_letter_service: Option<String>,
 /// }
 ///
 /// impl Settings for MySameSettings {
@@ -69,6 +111,50 @@ pub use prosa_macros::settings;
 ///     fn get_observability(&self) -> &Observability {
 ///         &self.observability
 ///     }
+///
+///     fn get_config_watch(&self) -> Option<&ConfigWatch> {
+///         self.config_watch.as_ref()
+///     }
+///
+///     fn get_heartbeat(&self) -> Option<&Heartbeat> {
+///         self.heartbeat.as_ref()
+///     }
+///
+///     fn get_identity(&self) -> Option<&Identity> {
+///         self.identity.as_ref()
+///     }
+///
+///     fn get_max_message_size(&self) -> Option<usize> {
+///         self.max_message_size
+///     }
+///
+///     fn get_ctl_socket(&self) -> Option<&PathBuf> {
+///         self.ctl_socket.as_ref()
+///     }
+///
+///     fn get_state(&self) -> Option<&StateSettings> {
+///         self.state.as_ref()
+///     }
+///
+///     fn get_shutdown(&self) -> Option<&Shutdown> {
+///         self.shutdown.as_ref()
+///     }
+///
+///     fn get_audit(&self) -> Option<&AuditSettings> {
+///         self.audit.as_ref()
+///     }
+///
+///     fn get_tps(&self) -> Option<&TpsSettings> {
+///         self.tps.as_ref()
+///     }
+///
+///     fn get_ready_routing(&self) -> bool {
+///         self.ready_routing.unwrap_or_default()
+///     }
+///
+///     fn get_dead_letter_service(&self) -> Option<&String> {
+///         self.dead_letter_service.as_ref()
+///     }
 /// }
 ///
 /// impl Default for MySameSettings {
@@ -77,6 +163,17 @@ pub use prosa_macros::settings;
 ///             test_val: "test".into(),
 ///             name: None,
 ///             observability: Observability::default(),
+///             config_watch: None,
+///             heartbeat: None,
+///             identity: None,
+///             max_message_size: None,
+///             ctl_socket: None,
+///             state: None,
+///             shutdown: None,
+///             audit: None,
+///             tps: None,
+///             ready_routing: None,
+///             dead_letter_service: None,
 ///         }
 ///     }
 /// }
@@ -84,12 +181,139 @@ pub use prosa_macros::settings;
 /// assert_eq!("test", MySameSettings::default().test_val);
 /// ```
 pub trait Settings: Serialize {
+    /// Strict mode of the settings (set with `#[settings(strict)]`), to reject unknown configuration keys
+    const STRICT: bool = false;
+
     /// Getter of the ProSA running name
     fn get_prosa_name(&self) -> String;
     /// Setter of the ProSA running name
     fn set_prosa_name(&mut self, name: String);
     /// Getter of the Observability configuration
     fn get_observability(&self) -> &Observability;
+    /// Getter of the configuration watch settings (no configuration reload by default)
+    fn get_config_watch(&self) -> Option<&ConfigWatch> {
+        None
+    }
+    /// Getter of the main task heartbeat settings (no heartbeat by default)
+    fn get_heartbeat(&self) -> Option<&Heartbeat> {
+        None
+    }
+    /// Getter of the ProSA instance identity (default identity if not overridden)
+    fn get_identity(&self) -> Option<&Identity> {
+        None
+    }
+    /// Getter of the maximum size in bytes of the messages exchanged on the bus (no limit by default)
+    ///
+    /// Oversized requests and responses are rejected with a [`ServiceError::MessageTooLarge`](crate::core::service::ServiceError::MessageTooLarge)
+    fn get_max_message_size(&self) -> Option<usize> {
+        None
+    }
+    /// Getter of the path of the control socket (no control socket by default)
+    ///
+    /// The control socket is a unix socket used to query and control the running ProSA (see [`ctl`](crate::core::ctl)). Access is granted by the socket file permissions
+    fn get_ctl_socket(&self) -> Option<&PathBuf> {
+        None
+    }
+    /// Getter of the state store settings (no state store by default)
+    ///
+    /// The state store keeps small states of the processors across restarts (see [`state`](crate::core::state))
+    fn get_state(&self) -> Option<&StateSettings> {
+        None
+    }
+    /// Getter of the shutdown settings (default shutdown timeouts if not overridden)
+    ///
+    /// Processors are stopped by ascending `shutdown_rank` (see [`ProcSettings`](crate::core::proc::ProcSettings))
+    fn get_shutdown(&self) -> Option<&Shutdown> {
+        None
+    }
+    /// Getter of the audit log settings of the main bus (no audit log by default)
+    ///
+    /// The audit log records what the main bus did with the processors and services (see [`audit`](crate::core::audit))
+    fn get_audit(&self) -> Option<&AuditSettings> {
+        None
+    }
+    /// Getter of the transactions moving window settings (no TPS statistics by default)
+    ///
+    /// The main task aggregates the transactions answered on the bus to give the current TPS and latency (see [`tps`](crate::core::tps))
+    fn get_tps(&self) -> Option<&TpsSettings> {
+        None
+    }
+    /// Getter to know if the services of a processor are routed only once it reported it's ready (`false` by default)
+    ///
+    /// With this setting, the services of a processor are withheld from the service table until it reports [`ProcLifecycle::Ready`](crate::core::proc::ProcLifecycle::Ready)
+    fn get_ready_routing(&self) -> bool {
+        false
+    }
+    /// Getter of the dead letter service name (no dead letters by default)
+    ///
+    /// The undeliverable messages and the irrecoverable failures are forwarded to this service (see [`dead_letter`](crate::core::dead_letter))
+    fn get_dead_letter_service(&self) -> Option<&String> {
+        None
+    }
+    /// Method to load the settings from a configuration
+    ///
+    /// In strict mode, the unknown keys of every settings level (main and processors settings) are collected and reported together in a [`SettingsError::UnknownKeys`], with the closest known key as suggestion.
+    ///
+    /// ```
+    /// use config::{Config, File, FileFormat};
+    /// use prosa::core::settings::{settings, Settings, SettingsError};
+    /// use serde::{Deserialize, Serialize};
+    ///
+    /// #[settings(strict)]
+    /// #[derive(Default, Debug, Deserialize, Serialize)]
+    /// struct MySettings {}
+    ///
+    /// let config = Config::builder()
+    ///     .add_source(File::from_str("name: test\nobservabilty: {}", FileFormat::Yaml))
+    ///     .build()
+    ///     .unwrap();
+    /// let error = MySettings::from_config(config).unwrap_err();
+    /// assert_eq!(
+    ///     "Unknown configuration keys: `observabilty` (did you mean `observability`?)",
+    ///     error.to_string()
+    /// );
+    /// ```
+    fn from_config(config: Config) -> Result<Self, SettingsError>
+    where
+        Self: Sized + DeserializeOwned,
+    {
+        if Self::STRICT {
+            strict::deserialize(config)
+        } else {
+            Ok(config.try_deserialize()?)
+        }
+    }
+
+    /// Method to serialize the complete settings (default values included), to dump the effective configuration
+    ///
+    /// The keys are sorted to be stable for diffing, and the [`Secret`](prosa_utils::config::secret::Secret) values are redacted.
+    /// The dump can be loaded back as a configuration file.
+    ///
+    /// ```
+    /// use prosa::core::settings::{settings, Settings, SettingsFormat};
+    /// use serde::{Deserialize, Serialize};
+    ///
+    /// #[settings]
+    /// #[derive(Default, Debug, Deserialize, Serialize)]
+    /// struct MySettings {
+    ///     zone: String,
+    /// }
+    ///
+    /// let dump = MySettings::default().dump(SettingsFormat::Toml).unwrap();
+    /// assert!(dump.contains("zone = \"\""));
+    /// ```
+    fn dump(&self, format: SettingsFormat) -> Result<String, SettingsError> {
+        let value =
+            serde_json::to_value(self).map_err(|e| SettingsError::Serialization(e.to_string()))?;
+        match format {
+            SettingsFormat::Yaml => serde_yaml::to_string(&sort_keys(value, false))
+                .map_err(|e| SettingsError::Serialization(e.to_string())),
+            // TOML doesn't have null values
+            SettingsFormat::Toml => toml::to_string(&sort_keys(value, true))
+                .map_err(|e| SettingsError::Serialization(e.to_string())),
+        }
+    }
+
     /// Method to write the configuration into a file
     fn write_config(&self, config_path: &str) -> io::Result<()> {
         let mut f = std::fs::File::create(std::path::Path::new(config_path))?;
@@ -112,6 +336,423 @@ pub trait Settings: Serialize {
     }
 }
 
+/// Unknown key found in a configuration loaded in strict mode
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownKey {
+    /// Path of the unknown key from the configuration root (`proc_name.key`)
+    pub path: String,
+    /// Closest known key at the same level, if it's close enough to be a typo
+    pub suggestion: Option<String>,
+}
+
+impl fmt::Display for UnknownKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(suggestion) = &self.suggestion {
+            write!(f, "`{}` (did you mean `{}`?)", self.path, suggestion)
+        } else {
+            write!(f, "`{}`", self.path)
+        }
+    }
+}
+
+/// Error define for the ProSA settings loading
+#[derive(Debug, Error)]
+pub enum SettingsError {
+    /// Error of the configuration (source or deserialization)
+    #[error("Configuration error: {0}")]
+    Config(#[from] ConfigError),
+    /// Error when keys of the configuration are unknown (strict mode only)
+    #[error("Unknown configuration keys: {}", .0.iter().map(|k| k.to_string()).collect::<Vec<String>>().join(", "))]
+    UnknownKeys(Vec<UnknownKey>),
+    /// Error when the settings can't be serialized
+    #[error("Settings serialization error: {0}")]
+    Serialization(String),
+}
+
+/// Serialization format of the settings
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum SettingsFormat {
+    /// YAML format (default)
+    #[default]
+    Yaml,
+    /// TOML format
+    Toml,
+}
+
+impl SettingsFormat {
+    /// Method to get the format of a configuration file from its extension (YAML if it's not `.toml`)
+    pub fn from_path(path: &str) -> SettingsFormat {
+        if path.ends_with(".toml") {
+            SettingsFormat::Toml
+        } else {
+            SettingsFormat::Yaml
+        }
+    }
+}
+
+/// Method to sort the keys of the serialized settings, and remove the null values if needed
+fn sort_keys(value: serde_json::Value, remove_nulls: bool) -> serde_json::Value {
+    match value {
+        serde_json::Value::Object(map) => {
+            let mut entries: Vec<(String, serde_json::Value)> = map
+                .into_iter()
+                .filter(|(_, v)| !(remove_nulls && v.is_null()))
+                .collect();
+            entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+            serde_json::Value::Object(
+                entries
+                    .into_iter()
+                    .map(|(k, v)| (k, sort_keys(v, remove_nulls)))
+                    .collect(),
+            )
+        }
+        serde_json::Value::Array(values) => serde_json::Value::Array(
+            values
+                .into_iter()
+                .filter(|v| !(remove_nulls && v.is_null()))
+                .map(|v| sort_keys(v, remove_nulls))
+                .collect(),
+        ),
+        value => value,
+    }
+}
+
+/// Settings to watch configuration files. When they change, the configuration is reloaded and sent to every processor
+///
+/// ```yaml
+/// config_watch:
+///   paths:
+///     - "prosa.yml"
+///     - "adaptor.yml"
+///   poll_interval: 1000
+///   debounce: 500
+/// ```
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct ConfigWatch {
+    /// Configuration files to watch
+    paths: Vec<PathBuf>,
+    /// Interval in milliseconds between two checks of the files
+    #[serde(default = "ConfigWatch::default_poll_interval")]
+    poll_interval: u64,
+    /// Duration in milliseconds without any change on the files before notifying the change (to avoid multiple notifications on successive writes)
+    #[serde(default = "ConfigWatch::default_debounce")]
+    debounce: u64,
+}
+
+impl ConfigWatch {
+    fn default_poll_interval() -> u64 {
+        1000
+    }
+
+    fn default_debounce() -> u64 {
+        500
+    }
+
+    /// Create a configuration watch on the given files with default intervals
+    pub fn new(paths: Vec<PathBuf>) -> ConfigWatch {
+        ConfigWatch {
+            paths,
+            poll_interval: ConfigWatch::default_poll_interval(),
+            debounce: ConfigWatch::default_debounce(),
+        }
+    }
+
+    /// Setter of the poll interval
+    pub fn set_poll_interval(&mut self, poll_interval: Duration) {
+        self.poll_interval = poll_interval.as_millis() as u64;
+    }
+
+    /// Setter of the debounce duration
+    pub fn set_debounce(&mut self, debounce: Duration) {
+        self.debounce = debounce.as_millis() as u64;
+    }
+
+    /// Getter of the watched configuration files
+    pub fn get_paths(&self) -> &Vec<PathBuf> {
+        &self.paths
+    }
+
+    /// Method to load the configuration from the watched files
+    pub fn load_config(&self) -> Result<Config, ConfigError> {
+        Config::builder()
+            .add_source(
+                self.paths
+                    .iter()
+                    .map(|p| File::from(p.as_path()))
+                    .collect::<Vec<_>>(),
+            )
+            .build()
+    }
+}
+
+/// Configuration reloaded from the watched files, sent to every processor with [`InternalMsg::Config`](crate::core::msg::InternalMsg::Config)
+///
+/// Processors reload their settings from it with the `reload_settings()` method generated by the `#[proc]` macro.
+#[derive(Debug, Clone)]
+pub struct ReloadedConfig(Arc<Config>);
+
+impl ReloadedConfig {
+    pub(crate) fn new(config: Config) -> ReloadedConfig {
+        ReloadedConfig(Arc::new(config))
+    }
+
+    /// Method to deserialize a section of the configuration (the settings of a processor are under its name)
+    pub fn get<T>(&self, key: &str) -> Result<T, ConfigError>
+    where
+        T: DeserializeOwned,
+    {
+        self.0.get(key)
+    }
+}
+
+/// Identity of the ProSA instance, added as resource attributes to all its telemetry (metrics, logs and traces)
+///
+/// The ProSA name is the service name, and the instance id is the hostname with the process id by default.
+///
+/// ```yaml
+/// identity:
+///   instance_id: "prosa-1"
+///   environment: "production"
+///   region: "eu-west-1"
+///   attributes:
+///     team: "payment"
+/// ```
+#[derive(Debug, Default, Deserialize, Serialize, Clone, PartialEq)]
+pub struct Identity {
+    /// Identifier of the ProSA instance
+    instance_id: Option<String>,
+    /// Environment where the ProSA is deployed
+    environment: Option<String>,
+    /// Region where the ProSA is deployed
+    region: Option<String>,
+    /// Additional attributes
+    #[serde(default)]
+    attributes: HashMap<String, String>,
+}
+
+impl Identity {
+    /// Setter of the instance id
+    pub fn set_instance_id(&mut self, instance_id: String) {
+        self.instance_id = Some(instance_id);
+    }
+
+    /// Setter of the deployment environment
+    pub fn set_environment(&mut self, environment: String) {
+        self.environment = Some(environment);
+    }
+
+    /// Setter of the deployment region
+    pub fn set_region(&mut self, region: String) {
+        self.region = Some(region);
+    }
+
+    /// Method to add an attribute to the identity
+    pub fn add_attribute(&mut self, key: String, value: String) {
+        self.attributes.insert(key, value);
+    }
+
+    /// Getter of the instance id (hostname and process id if not set)
+    pub fn get_instance_id(&self) -> String {
+        if let Some(instance_id) = &self.instance_id {
+            instance_id.clone()
+        } else {
+            let hostname = std::env::var("HOSTNAME").unwrap_or_else(|_| String::from("localhost"));
+            format!("{}-{}", hostname, std::process::id())
+        }
+    }
+
+    /// Method to build the OpenTelemetry resource of the ProSA instance
+    pub fn resource(&self, prosa_name: &str) -> Resource {
+        let mut attributes = vec![
+            KeyValue::new("service.name", prosa_name.to_string()),
+            KeyValue::new("service.instance.id", self.get_instance_id()),
+        ];
+        if let Some(environment) = &self.environment {
+            attributes.push(KeyValue::new("deployment.environment", environment.clone()));
+        }
+        if let Some(region) = &self.region {
+            attributes.push(KeyValue::new("cloud.region", region.clone()));
+        }
+        for (key, value) in &self.attributes {
+            attributes.push(KeyValue::new(key.clone(), value.clone()));
+        }
+
+        Resource::new(attributes)
+    }
+}
+
+/// Settings of the main task heartbeat, to detect processors that don't read their queue anymore
+///
+/// At every interval, the main task pings all processor queues.
+/// After `miss_threshold` consecutive pings without answer, the services of the queue are removed from the service table until the queue answers again.
+///
+/// ```yaml
+/// heartbeat:
+///   interval: 1000
+///   miss_threshold: 3
+/// ```
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct Heartbeat {
+    /// Interval in milliseconds between two pings
+    #[serde(default = "Heartbeat::default_interval")]
+    interval: u64,
+    /// Number of consecutive missed pings before evicting the processor queue
+    #[serde(default = "Heartbeat::default_miss_threshold")]
+    miss_threshold: u32,
+}
+
+impl Heartbeat {
+    fn default_interval() -> u64 {
+        1000
+    }
+
+    fn default_miss_threshold() -> u32 {
+        3
+    }
+
+    /// Create a heartbeat with the given interval and miss threshold
+    pub fn new(interval: Duration, miss_threshold: u32) -> Heartbeat {
+        Heartbeat {
+            interval: interval.as_millis() as u64,
+            miss_threshold,
+        }
+    }
+
+    /// Getter of the interval between two pings
+    pub fn get_interval(&self) -> Duration {
+        Duration::from_millis(self.interval.max(1))
+    }
+
+    /// Getter of the number of consecutive missed pings before evicting a processor queue
+    pub fn get_miss_threshold(&self) -> u32 {
+        self.miss_threshold.max(1)
+    }
+}
+
+impl Default for Heartbeat {
+    fn default() -> Self {
+        Heartbeat {
+            interval: Heartbeat::default_interval(),
+            miss_threshold: Heartbeat::default_miss_threshold(),
+        }
+    }
+}
+
+/// Settings of the ProSA shutdown sequence
+///
+/// Processors are stopped rank by rank (lowest `shutdown_rank` first).
+/// The main task waits for the processors of a rank to deregister, at most `rank_timeout` milliseconds, before stopping the next rank.
+/// The whole sequence can't last more than `timeout` milliseconds.
+///
+/// ```yaml
+/// shutdown:
+///   timeout: 10000
+///   rank_timeout: 3000
+/// ```
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct Shutdown {
+    /// Maximum duration in milliseconds of the whole shutdown sequence
+    #[serde(default = "Shutdown::default_timeout")]
+    timeout: u64,
+    /// Maximum duration in milliseconds to wait for the processors of a rank to stop
+    #[serde(default = "Shutdown::default_rank_timeout")]
+    rank_timeout: u64,
+}
+
+impl Shutdown {
+    fn default_timeout() -> u64 {
+        10000
+    }
+
+    fn default_rank_timeout() -> u64 {
+        3000
+    }
+
+    /// Create shutdown settings with the global and the per rank timeouts
+    pub fn new(timeout: Duration, rank_timeout: Duration) -> Shutdown {
+        Shutdown {
+            timeout: timeout.as_millis() as u64,
+            rank_timeout: rank_timeout.as_millis() as u64,
+        }
+    }
+
+    /// Getter of the maximum duration of the whole shutdown sequence
+    pub fn get_timeout(&self) -> Duration {
+        Duration::from_millis(self.timeout)
+    }
+
+    /// Getter of the maximum duration to wait for the processors of a rank to stop
+    pub fn get_rank_timeout(&self) -> Duration {
+        Duration::from_millis(self.rank_timeout)
+    }
+}
+
+impl Default for Shutdown {
+    fn default() -> Self {
+        Shutdown {
+            timeout: Shutdown::default_timeout(),
+            rank_timeout: Shutdown::default_rank_timeout(),
+        }
+    }
+}
+
+/// Watcher of configuration files that poll their modification
+#[derive(Debug)]
+pub(crate) struct ConfigWatcher {
+    settings: ConfigWatch,
+    interval: time::Interval,
+    files_state: Vec<Option<(SystemTime, u64)>>,
+    last_change: Option<Instant>,
+}
+
+impl ConfigWatcher {
+    pub(crate) fn new(settings: ConfigWatch) -> ConfigWatcher {
+        let mut interval = time::interval(Duration::from_millis(settings.poll_interval.max(1)));
+        interval.set_missed_tick_behavior(time::MissedTickBehavior::Delay);
+        let files_state = settings.paths.iter().map(Self::file_state).collect();
+        ConfigWatcher {
+            settings,
+            interval,
+            files_state,
+            last_change: None,
+        }
+    }
+
+    fn file_state(path: &PathBuf) -> Option<(SystemTime, u64)> {
+        fs::metadata(path)
+            .ok()
+            .and_then(|m| m.modified().ok().map(|t| (t, m.len())))
+    }
+
+    /// Method to scan files and know if one of them changed since the last scan
+    fn scan(&mut self) -> bool {
+        let mut changed = false;
+        for (path, state) in self.settings.paths.iter().zip(self.files_state.iter_mut()) {
+            let new_state = Self::file_state(path);
+            if new_state != *state {
+                *state = new_state;
+                changed = true;
+            }
+        }
+
+        changed
+    }
+
+    /// Method to wait a debounced change of the watched files (cancel safe)
+    pub(crate) async fn changed(&mut self) -> Result<Config, ConfigError> {
+        let debounce = Duration::from_millis(self.settings.debounce);
+        loop {
+            self.interval.tick().await;
+            if self.scan() {
+                self.last_change = Some(Instant::now());
+            } else if self.last_change.is_some_and(|t| t.elapsed() >= debounce) {
+                self.last_change = None;
+                return self.settings.load_config();
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -147,4 +788,363 @@ mod tests {
         assert_eq!("test", test_settings.name_test);
         assert_eq!("test2", test_settings.name_test2);
     }
+
+    #[test]
+    fn test_strict_settings() {
+        use config::FileFormat;
+        use prosa_macros::proc_settings;
+
+        #[proc_settings(strict)]
+        #[derive(Default, Debug, Deserialize, Serialize)]
+        struct TestProcSettings {
+            #[serde(default)]
+            timeout: u64,
+        }
+
+        #[settings(strict)]
+        #[derive(Default, Debug, Deserialize, Serialize)]
+        struct TestStrictSettings {
+            #[serde(default)]
+            stub: TestProcSettings,
+        }
+
+        #[settings]
+        #[derive(Default, Debug, Deserialize, Serialize)]
+        struct TestLaxSettings {
+            #[serde(default)]
+            stub: TestProcSettings,
+        }
+
+        let config = |content: &str| {
+            Config::builder()
+                .add_source(File::from_str(content, FileFormat::Yaml))
+                .build()
+                .unwrap()
+        };
+
+        let typo_config = "name: test\nobservabilty:\n  level: debug\nstub:\n  timout: 10\n  thread: 2\n  foo: bar\n";
+        match TestStrictSettings::from_config(config(typo_config)) {
+            Err(SettingsError::UnknownKeys(unknown_keys)) => assert_eq!(
+                vec![
+                    UnknownKey {
+                        path: "observabilty".into(),
+                        suggestion: Some("observability".into()),
+                    },
+                    UnknownKey {
+                        path: "stub.foo".into(),
+                        suggestion: None,
+                    },
+                    UnknownKey {
+                        path: "stub.thread".into(),
+                        suggestion: Some("threads".into()),
+                    },
+                    UnknownKey {
+                        path: "stub.timout".into(),
+                        suggestion: Some("timeout".into()),
+                    },
+                ],
+                unknown_keys
+            ),
+            other => panic!("Unexpected strict settings loading: {:?}", other),
+        }
+        assert_eq!(
+            "Unknown configuration keys: `observabilty` (did you mean `observability`?), `stub.foo`, `stub.thread` (did you mean `threads`?), `stub.timout` (did you mean `timeout`?)",
+            TestStrictSettings::from_config(config(typo_config))
+                .unwrap_err()
+                .to_string()
+        );
+
+        // Without strict main settings, the first unknown key of the strict processor settings is rejected by serde
+        assert!(matches!(
+            TestLaxSettings::from_config(config(typo_config)),
+            Err(SettingsError::Config(_))
+        ));
+        let lax_settings =
+            TestLaxSettings::from_config(config("observabilty: {}\nstub:\n  timeout: 10\n"))
+                .unwrap();
+        assert_eq!(10, lax_settings.stub.timeout);
+
+        let strict_settings = TestStrictSettings::from_config(config(
+            "name: test\nheartbeat:\n  interval: 100\nstub:\n  timeout: 10\n  threads: 2\n",
+        ))
+        .unwrap();
+        assert_eq!("test", strict_settings.get_prosa_name());
+        assert_eq!(10, strict_settings.stub.timeout);
+        assert_eq!(Some(2), strict_settings.stub.threads);
+        assert!(strict_settings.get_heartbeat().is_some());
+    }
+
+    #[test]
+    fn test_settings_dump() {
+        use config::FileFormat;
+        use prosa_macros::proc_settings;
+        use prosa_utils::config::secret::Secret;
+
+        #[proc_settings(strict)]
+        #[derive(Default, Debug, Deserialize, Serialize)]
+        struct TestProcSettings {
+            #[serde(default)]
+            timeout: u64,
+            #[serde(default)]
+            attributes: HashMap<String, String>,
+            token: Option<Secret>,
+        }
+
+        #[settings(strict)]
+        #[derive(Default, Debug, Deserialize, Serialize)]
+        struct TestDumpSettings {
+            #[serde(default)]
+            stub: TestProcSettings,
+        }
+
+        let config = Config::builder()
+            .add_source(File::from_str(
+                "name: dump\nobservability:\n  metrics:\n    prometheus:\n      endpoint: 0.0.0.0:9100\n      basic_auth:\n        username: prometheus\n        password: metrics-password\nstub:\n  timeout: 10\n  threads: 2\n  token: token-secret\n  attributes:\n    zone: eu\n    app: prosa\n    region: west\n",
+                FileFormat::Yaml,
+            ))
+            .build()
+            .unwrap();
+        let settings = TestDumpSettings::from_config(config).unwrap();
+        assert_eq!(
+            "token-secret",
+            settings.stub.token.as_ref().unwrap().expose()
+        );
+
+        for (format, extension) in [
+            (SettingsFormat::Yaml, "yml"),
+            (SettingsFormat::Toml, "toml"),
+        ] {
+            let dump = settings.dump(format).unwrap();
+            assert!(!dump.contains("token-secret"), "{}", dump);
+            assert!(!dump.contains("metrics-password"), "{}", dump);
+            assert!(dump.contains("******"), "{}", dump);
+
+            // The keys are sorted, whatever the order of the maps
+            assert_eq!(dump, settings.dump(format).unwrap());
+            let app = dump.find("app").unwrap();
+            let region = dump.find("region").unwrap();
+            let zone = dump.find("zone").unwrap();
+            assert!(app < region && region < zone, "{}", dump);
+
+            // The dump can be loaded back
+            let path = std::env::temp_dir().join(format!(
+                "prosa_test_dump_{}.{}",
+                std::process::id(),
+                extension
+            ));
+            fs::write(&path, &dump).unwrap();
+            assert_eq!(format, SettingsFormat::from_path(path.to_str().unwrap()));
+            let loaded = TestDumpSettings::from_config(
+                Config::builder()
+                    .add_source(File::from(path.as_path()))
+                    .build()
+                    .unwrap(),
+            )
+            .unwrap();
+            fs::remove_file(&path).unwrap();
+            assert_eq!("dump", loaded.get_prosa_name());
+            assert_eq!(10, loaded.stub.timeout);
+            assert_eq!(Some(2), loaded.stub.threads);
+            assert_eq!(3, loaded.stub.attributes.len());
+            assert_eq!(dump, loaded.dump(format).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_instances_settings() {
+        use config::FileFormat;
+        use prosa_macros::proc_settings;
+
+        #[proc_settings]
+        #[derive(Default, Debug, Deserialize, Serialize)]
+        struct TestProcSettings {
+            #[serde(default)]
+            endpoint: String,
+        }
+
+        // Settings generated by cargo-prosa for two instances of the same processor
+        #[settings(strict)]
+        #[derive(Default, Debug, Deserialize, Serialize)]
+        struct TestInstancesSettings {
+            #[serde(rename = "partner-a")]
+            partner_a: TestProcSettings,
+            partner_b: TestProcSettings,
+        }
+
+        let settings = TestInstancesSettings::from_config(
+            Config::builder()
+                .add_source(File::from_str(
+                    "name: test\npartner-a:\n  endpoint: a.example.com\npartner_b:\n  endpoint: b.example.com\n",
+                    FileFormat::Yaml,
+                ))
+                .build()
+                .unwrap(),
+        )
+        .unwrap();
+        assert_eq!("a.example.com", settings.partner_a.endpoint);
+        assert_eq!("b.example.com", settings.partner_b.endpoint);
+    }
+
+    #[test]
+    fn test_config_report() {
+        use crate::core::proc::ProcSettings as _;
+        use prosa_macros::proc_settings;
+
+        #[proc_settings]
+        #[derive(Default, Debug, Deserialize, Serialize)]
+        struct TestProcSettings {
+            endpoint: String,
+            #[serde(default)]
+            timeout: u64,
+        }
+
+        #[derive(Debug, Deserialize)]
+        struct TestAdaptorConfig {
+            #[allow(dead_code)]
+            response: String,
+        }
+
+        let dir = std::env::temp_dir().join("prosa_test_config_report");
+        fs::create_dir_all(&dir).unwrap();
+        let main_yml = dir.join("prosa.yml");
+        let procs_toml = dir.join("procs.toml");
+        let adaptor_yml = dir.join("adaptor.yml");
+        fs::write(
+            &main_yml,
+            format!(
+                "name: test\nproc-a:\n  endpoint: a.example.com\n  adaptor_config_path: {}\n",
+                adaptor_yml.display()
+            ),
+        )
+        .unwrap();
+        fs::write(
+            &procs_toml,
+            "[proc-b]\nendpoint = \"b.example.com\"\ntimeout = \"ten\"\n",
+        )
+        .unwrap();
+        fs::write(&adaptor_yml, "response: ok\n").unwrap();
+
+        let report = |files: &[&PathBuf]| {
+            let config = files
+                .iter()
+                .fold(Config::builder(), |builder, file| {
+                    builder.add_source(File::from(file.as_path()))
+                })
+                .build()
+                .unwrap();
+            let mut report = ConfigReport::new(files.iter().cloned().cloned());
+            report.check_proc::<TestProcSettings>(&config, "proc-a");
+            report.check_proc::<TestProcSettings>(&config, "proc-b");
+            report
+        };
+
+        // Invalid type in the TOML file of the second processor
+        let config_report = report(&[&main_yml, &procs_toml]);
+        assert!(!config_report.is_valid());
+        let proc_a = config_report.get_proc("proc-a").unwrap();
+        assert!(proc_a.is_valid());
+        assert_eq!(vec![adaptor_yml.clone()], proc_a.files);
+        let proc_b = config_report.get_proc("proc-b").unwrap();
+        assert_eq!(1, proc_b.issues.len());
+        assert_eq!(Some("proc-b.timeout"), proc_b.issues[0].key.as_deref());
+        assert_eq!(Some(&procs_toml), proc_b.issues[0].file.as_ref());
+        assert!(
+            config_report
+                .to_string()
+                .contains(&format!("`proc-b.timeout` in {}", procs_toml.display())),
+            "{}",
+            config_report
+        );
+
+        // Missing field in the YAML file of the second processor, and broken adaptor configuration of the first one
+        fs::write(&procs_toml, "[proc-b]\ntimeout = 10\n").unwrap();
+        fs::write(&adaptor_yml, "response: [ok\n").unwrap();
+        let config_report = report(&[&main_yml, &procs_toml]);
+        let proc_a = config_report.get_proc("proc-a").unwrap();
+        assert_eq!(1, proc_a.issues.len());
+        assert_eq!(None, proc_a.issues[0].key);
+        assert_eq!(Some(&adaptor_yml), proc_a.issues[0].file.as_ref());
+        let proc_b = config_report.get_proc("proc-b").unwrap();
+        assert_eq!(Some("proc-b.endpoint"), proc_b.issues[0].key.as_deref());
+        assert_eq!(Some(&procs_toml), proc_b.issues[0].file.as_ref());
+        assert_eq!("missing field `endpoint`", proc_b.issues[0].message);
+
+        // The adaptor configuration error give its key and file
+        fs::write(&adaptor_yml, "responses: ok\n").unwrap();
+        let proc_a_settings: TestProcSettings = Config::builder()
+            .add_source(File::from(main_yml.as_path()))
+            .build()
+            .unwrap()
+            .get("proc-a")
+            .unwrap();
+        let error = proc_a_settings
+            .get_adaptor_config::<TestAdaptorConfig>()
+            .unwrap_err();
+        assert_eq!(
+            format!(
+                "`response` in {} - missing field `response`",
+                adaptor_yml.display()
+            ),
+            error.to_string()
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_identity() {
+        let mut identity = Identity::default();
+        assert!(identity
+            .get_instance_id()
+            .ends_with(&format!("-{}", std::process::id())));
+
+        identity.set_instance_id("prosa-1".into());
+        identity.set_region("eu-west-1".into());
+        identity.add_attribute("team".into(), "payment".into());
+        let resource = identity.resource("prosa-test");
+        assert_eq!(
+            Some("prosa-test".into()),
+            resource.get("service.name".into())
+        );
+        assert_eq!(
+            Some("prosa-1".into()),
+            resource.get("service.instance.id".into())
+        );
+        assert_eq!(
+            Some("eu-west-1".into()),
+            resource.get("cloud.region".into())
+        );
+        assert_eq!(Some("payment".into()), resource.get("team".into()));
+        assert_eq!(None, resource.get("deployment.environment".into()));
+    }
+
+    #[tokio::test]
+    async fn test_config_watcher() {
+        let config_path = std::env::temp_dir().join("prosa_test_config_watcher.yml");
+        fs::write(&config_path, "value: first\n").unwrap();
+
+        let mut config_watch = ConfigWatch::new(vec![config_path.clone()]);
+        config_watch.set_poll_interval(Duration::from_millis(20));
+        config_watch.set_debounce(Duration::from_millis(100));
+        let mut watcher = ConfigWatcher::new(config_watch);
+
+        // No change, no notification
+        assert!(time::timeout(Duration::from_millis(200), watcher.changed())
+            .await
+            .is_err());
+
+        // Successive writes trigger only one notification
+        fs::write(&config_path, "value: second\n").unwrap();
+        fs::write(&config_path, "value: third_value\n").unwrap();
+        let config = time::timeout(Duration::from_secs(2), watcher.changed())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!("third_value", config.get_string("value").unwrap());
+        assert!(time::timeout(Duration::from_millis(300), watcher.changed())
+            .await
+            .is_err());
+
+        fs::remove_file(&config_path).unwrap();
+    }
 }
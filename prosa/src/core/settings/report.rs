@@ -0,0 +1,281 @@
+//! Report of the processors configuration, checked at startup to locate the configuration errors
+
+use std::{fmt, path::PathBuf};
+
+use config::{Config, ConfigError, File, Value};
+use glob::glob;
+use serde::de::{Deserialize, DeserializeOwned};
+
+use crate::core::proc::ProcSettings;
+
+use super::strict;
+
+/// Issue found in the configuration of a processor
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigIssue {
+    /// Full path of the key from the configuration root (`proc_name.key`), if the issue concern a key
+    pub key: Option<String>,
+    /// File that define the key (or its closest parent), if it's defined in a file
+    pub file: Option<PathBuf>,
+    /// Message of the issue
+    pub message: String,
+}
+
+impl fmt::Display for ConfigIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(key) = &self.key {
+            write!(f, "`{}` ", key)?;
+        }
+        if let Some(file) = &self.file {
+            write!(f, "in {} ", file.display())?;
+        }
+        write!(f, "- {}", self.message)
+    }
+}
+
+/// Function to deserialize a configuration loaded from files, with the key and the file in the error message if it fails
+pub(crate) fn deserialize_located<'de, C>(
+    config: Config,
+    files: &[PathBuf],
+) -> Result<C, ConfigError>
+where
+    C: Deserialize<'de>,
+{
+    strict::deserialize_value(config.try_deserialize::<Value>()?, "")
+        .map_err(|(key, e)| ConfigError::Message(ConfigReport::issue(files, key, *e).to_string()))
+}
+
+/// Configuration report of a processor instance
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProcConfigReport {
+    /// Name of the processor instance (its settings section)
+    pub name: String,
+    /// Adaptor configuration files matched by the processor's `adaptor_config_path` glob
+    pub files: Vec<PathBuf>,
+    /// Issues found in the processor configuration
+    pub issues: Vec<ConfigIssue>,
+}
+
+impl ProcConfigReport {
+    /// Method to know if the processor configuration is valid
+    pub fn is_valid(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+/// Report of the processors configuration, to show at startup which processor instance, file and key are invalid
+///
+/// ```
+/// use config::{Config, File, FileFormat};
+/// use prosa::core::proc::proc_settings;
+/// use prosa::core::settings::ConfigReport;
+/// use serde::{Deserialize, Serialize};
+///
+/// #[proc_settings]
+/// #[derive(Default, Debug, Deserialize, Serialize)]
+/// struct MyProcSettings {
+///     url: String,
+/// }
+///
+/// let config = Config::builder()
+///     .add_source(File::from_str("my-proc:\n  timeout: 10\n", FileFormat::Yaml))
+///     .build()
+///     .unwrap();
+/// let mut report = ConfigReport::new(Vec::<String>::new());
+/// report.check_proc::<MyProcSettings>(&config, "my-proc");
+///
+/// assert!(!report.is_valid());
+/// let issue = &report.get_proc("my-proc").unwrap().issues[0];
+/// assert_eq!(Some("my-proc.url"), issue.key.as_deref());
+/// assert_eq!("missing field `url`", issue.message);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConfigReport {
+    files: Vec<PathBuf>,
+    procs: Vec<ProcConfigReport>,
+}
+
+impl ConfigReport {
+    /// Method to create a report for the main configuration files (in their loading order)
+    pub fn new<P>(files: impl IntoIterator<Item = P>) -> ConfigReport
+    where
+        P: Into<PathBuf>,
+    {
+        ConfigReport {
+            files: files.into_iter().map(|f| f.into()).collect(),
+            procs: Vec::new(),
+        }
+    }
+
+    /// Function to find the last file that define a key, or its closest parent (the root is defined by every file)
+    fn locate(files: &[PathBuf], key: &str) -> Option<PathBuf> {
+        let configs: Vec<(&PathBuf, Config)> = files
+            .iter()
+            .filter_map(|file| {
+                Config::builder()
+                    .add_source(File::from(file.as_path()))
+                    .build()
+                    .ok()
+                    .map(|config| (file, config))
+            })
+            .collect();
+
+        let mut key = key;
+        while !key.is_empty() {
+            if let Some((file, _)) = configs
+                .iter()
+                .rev()
+                .find(|(_, config)| config.get::<Value>(key).is_ok())
+            {
+                return Some(file.to_path_buf());
+            }
+
+            key = &key[..key.rfind(['.', '[']).unwrap_or_default()];
+        }
+
+        configs.last().map(|(file, _)| file.to_path_buf())
+    }
+
+    /// Method to build the issue of a deserialization error, located in the configuration files
+    fn issue(files: &[PathBuf], key: String, error: ConfigError) -> ConfigIssue {
+        let message = match error {
+            ConfigError::Type {
+                unexpected,
+                expected,
+                ..
+            } => format!("invalid type: {}, expected {}", unexpected, expected),
+            e => e.to_string(),
+        };
+
+        ConfigIssue {
+            file: Self::locate(files, &key),
+            key: (!key.is_empty()).then_some(key),
+            message,
+        }
+    }
+
+    /// Method to check the settings of a processor instance, and its adaptor configuration files
+    pub fn check_proc<S>(&mut self, config: &Config, name: &str)
+    where
+        S: ProcSettings + DeserializeOwned,
+    {
+        let mut proc_report = ProcConfigReport {
+            name: name.to_string(),
+            ..Default::default()
+        };
+
+        match config.get::<Value>(name) {
+            Ok(value) => match strict::deserialize_value::<S>(value, name) {
+                Ok(settings) => {
+                    if let Some(config_path) = settings.get_adaptor_config_path() {
+                        self.check_adaptor_files(&mut proc_report, config_path);
+                    }
+                }
+                Err((key, e)) => proc_report.issues.push(Self::issue(&self.files, key, *e)),
+            },
+            Err(_) => proc_report.issues.push(ConfigIssue {
+                key: Some(name.to_string()),
+                file: None,
+                message: String::from("missing processor settings"),
+            }),
+        }
+
+        self.procs.push(proc_report);
+    }
+
+    /// Method to check that the adaptor configuration files of a processor can be read
+    fn check_adaptor_files(&self, proc_report: &mut ProcConfigReport, config_path: &str) {
+        match glob(config_path) {
+            Ok(paths) => {
+                for path in paths {
+                    match path {
+                        Ok(path) => {
+                            if let Err(e) = Config::builder()
+                                .add_source(File::from(path.as_path()))
+                                .build()
+                            {
+                                proc_report.issues.push(ConfigIssue {
+                                    key: None,
+                                    file: Some(path.clone()),
+                                    message: e.to_string(),
+                                });
+                            }
+                            proc_report.files.push(path);
+                        }
+                        Err(e) => proc_report.issues.push(ConfigIssue {
+                            key: None,
+                            file: Some(e.path().to_path_buf()),
+                            message: e.error().to_string(),
+                        }),
+                    }
+                }
+
+                if proc_report.files.is_empty() {
+                    proc_report.issues.push(ConfigIssue {
+                        key: Some(format!("{}.adaptor_config_path", proc_report.name)),
+                        file: Self::locate(
+                            &self.files,
+                            &format!("{}.adaptor_config_path", proc_report.name),
+                        ),
+                        message: format!("no adaptor configuration file match `{}`", config_path),
+                    });
+                }
+            }
+            Err(e) => proc_report.issues.push(ConfigIssue {
+                key: Some(format!("{}.adaptor_config_path", proc_report.name)),
+                file: Self::locate(
+                    &self.files,
+                    &format!("{}.adaptor_config_path", proc_report.name),
+                ),
+                message: e.to_string(),
+            }),
+        }
+    }
+
+    /// Getter of the report of a processor instance
+    pub fn get_proc(&self, name: &str) -> Option<&ProcConfigReport> {
+        self.procs.iter().find(|p| p.name == name)
+    }
+
+    /// Getter of the reports of all checked processor instances
+    pub fn get_procs(&self) -> &[ProcConfigReport] {
+        &self.procs
+    }
+
+    /// Method to know if all the processors configuration are valid
+    pub fn is_valid(&self) -> bool {
+        self.procs.iter().all(|p| p.is_valid())
+    }
+}
+
+impl fmt::Display for ConfigReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Processors configuration:")?;
+        for proc_report in &self.procs {
+            write!(f, "  {}", proc_report.name)?;
+            if !proc_report.files.is_empty() {
+                write!(
+                    f,
+                    " ({})",
+                    proc_report
+                        .files
+                        .iter()
+                        .map(|f| f.display().to_string())
+                        .collect::<Vec<String>>()
+                        .join(", ")
+                )?;
+            }
+
+            if proc_report.is_valid() {
+                writeln!(f, ": OK")?;
+            } else {
+                writeln!(f, ": INVALID")?;
+                for issue in &proc_report.issues {
+                    writeln!(f, "    {}", issue)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,410 @@
+//! Strict deserialization of the settings, that collect the unknown keys of every settings structure
+//!
+//! The same deserializer keep the path of the values, to locate the key of a deserialization error
+
+use std::cell::RefCell;
+
+use config::{Config, ConfigError, Map, Value, ValueKind};
+use serde::de::{self, DeserializeOwned, IntoDeserializer as _};
+
+use super::{SettingsError, UnknownKey};
+
+/// Function to compute the edit (Levenshtein) distance between two keys
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut prev = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let cur = row[j + 1];
+            row[j + 1] = if ca == *cb {
+                prev
+            } else {
+                1 + prev.min(cur).min(row[j])
+            };
+            prev = cur;
+        }
+    }
+
+    row[b.len()]
+}
+
+/// Function to get the closest known field of an unknown key, if it's close enough to be a typo
+fn suggest(key: &str, fields: &[&str]) -> Option<String> {
+    let max_distance = (key.chars().count() / 3).max(1);
+    fields
+        .iter()
+        .map(|field| (edit_distance(key, field), field))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, field)| field.to_string())
+}
+
+/// Function to get the path of a key from the configuration root
+fn key_path(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}.{}", path, key)
+    }
+}
+
+/// Function to deserialize settings from a configuration, collecting all its unknown keys
+pub(super) fn deserialize<S>(config: Config) -> Result<S, SettingsError>
+where
+    S: DeserializeOwned,
+{
+    let root = Value::new(None, config.try_deserialize::<Map<String, Value>>()?);
+    let ctx = Context {
+        unknown_keys: Some(RefCell::new(Vec::new())),
+        error_path: RefCell::new(None),
+    };
+    let settings = S::deserialize(StrictValue {
+        value: root,
+        path: String::new(),
+        ctx: &ctx,
+    });
+
+    // Unknown keys are reported first, because they usually are the cause of the other errors (missing field)
+    let mut unknown_keys = ctx.unknown_keys.unwrap_or_default().into_inner();
+    if unknown_keys.is_empty() {
+        Ok(settings?)
+    } else {
+        unknown_keys.sort_by(|a, b| a.path.cmp(&b.path));
+        Err(SettingsError::UnknownKeys(unknown_keys))
+    }
+}
+
+/// Function to deserialize a configuration value located at `path`, and get the full key path of the error if it fails
+///
+/// The unknown keys are not collected, so they are rejected by the structures that deny them.
+pub(super) fn deserialize_value<'de, S>(
+    value: Value,
+    path: &str,
+) -> Result<S, (String, Box<ConfigError>)>
+where
+    S: de::Deserialize<'de>,
+{
+    let ctx = Context {
+        unknown_keys: None,
+        error_path: RefCell::new(None),
+    };
+    S::deserialize(StrictValue {
+        value,
+        path: path.to_string(),
+        ctx: &ctx,
+    })
+    .map_err(|e| {
+        let error_path = ctx.error_path.into_inner().unwrap_or(path.to_string());
+        // The missing field is not in the configuration, but its path is the one to fix
+        let error_path = match e.to_string().strip_prefix("missing field `") {
+            Some(field) => key_path(&error_path, field.trim_end_matches('`')),
+            None => error_path,
+        };
+        (error_path, Box::new(e))
+    })
+}
+
+/// Context of a strict deserialization
+struct Context {
+    /// Unknown keys collected (and skipped), if they are checked
+    unknown_keys: Option<RefCell<Vec<UnknownKey>>>,
+    /// Path of the innermost value that failed to deserialize
+    error_path: RefCell<Option<String>>,
+}
+
+impl Context {
+    /// Method to keep the path of a value that failed to deserialize, if no inner value failed before
+    fn on_error(&self, path: &str, error: ConfigError) -> ConfigError {
+        self.error_path
+            .borrow_mut()
+            .get_or_insert_with(|| path.to_string());
+        error
+    }
+}
+
+/// Configuration value deserializer that check the keys of the structures
+struct StrictValue<'a> {
+    value: Value,
+    path: String,
+    ctx: &'a Context,
+}
+
+macro_rules! forward_to_value {
+    ($($method:ident),*) => {
+        $(
+            fn $method<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+            where
+                V: de::Visitor<'de>,
+            {
+                self.value.$method(visitor)
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for StrictValue<'_> {
+    type Error = ConfigError;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        match self.value.kind {
+            ValueKind::Table(_) => self.deserialize_map(visitor),
+            ValueKind::Array(_) => self.deserialize_seq(visitor),
+            _ => self.value.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        if let ValueKind::Nil = self.value.kind {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_newtype_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        match self.value.kind {
+            ValueKind::Array(values) => visitor.visit_seq(StrictSeq {
+                values: values.into_iter().enumerate(),
+                path: self.path,
+                ctx: self.ctx,
+            }),
+            _ => self.value.deserialize_seq(visitor),
+        }
+    }
+
+    fn deserialize_map<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        match self.value.kind {
+            ValueKind::Table(table) => {
+                visitor.visit_map(StrictMap::new(table, self.path, None, self.ctx))
+            }
+            _ => self.value.deserialize_map(visitor),
+        }
+    }
+
+    fn deserialize_struct<V>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        match self.value.kind {
+            ValueKind::Table(table) => {
+                visitor.visit_map(StrictMap::new(table, self.path, Some(fields), self.ctx))
+            }
+            _ => self.value.deserialize_struct(name, fields, visitor),
+        }
+    }
+
+    fn deserialize_unit_struct<V>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.value.deserialize_unit_struct(name, visitor)
+    }
+
+    fn deserialize_tuple<V>(self, len: usize, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.value.deserialize_tuple(len, visitor)
+    }
+
+    fn deserialize_tuple_struct<V>(
+        self,
+        name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.value.deserialize_tuple_struct(name, len, visitor)
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.value.deserialize_enum(name, variants, visitor)
+    }
+
+    forward_to_value!(
+        deserialize_bool,
+        deserialize_i8,
+        deserialize_i16,
+        deserialize_i32,
+        deserialize_i64,
+        deserialize_i128,
+        deserialize_u8,
+        deserialize_u16,
+        deserialize_u32,
+        deserialize_u64,
+        deserialize_u128,
+        deserialize_f32,
+        deserialize_f64,
+        deserialize_char,
+        deserialize_str,
+        deserialize_string,
+        deserialize_bytes,
+        deserialize_byte_buf,
+        deserialize_unit,
+        deserialize_identifier,
+        deserialize_ignored_any
+    );
+}
+
+/// Access to the entries of a configuration table, that skip and collect the unknown fields of a structure
+struct StrictMap<'a> {
+    entries: <Map<String, Value> as IntoIterator>::IntoIter,
+    path: String,
+    fields: Option<&'static [&'static str]>,
+    pending: Option<(String, Value)>,
+    ctx: &'a Context,
+}
+
+impl<'a> StrictMap<'a> {
+    fn new(
+        table: Map<String, Value>,
+        path: String,
+        fields: Option<&'static [&'static str]>,
+        ctx: &'a Context,
+    ) -> StrictMap<'a> {
+        StrictMap {
+            entries: table.into_iter(),
+            path,
+            fields,
+            pending: None,
+            ctx,
+        }
+    }
+}
+
+impl<'de> de::MapAccess<'de> for StrictMap<'_> {
+    type Error = ConfigError;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, Self::Error>
+    where
+        K: de::DeserializeSeed<'de>,
+    {
+        for (key, value) in self.entries.by_ref() {
+            if let (Some(fields), Some(unknown_keys)) = (self.fields, &self.ctx.unknown_keys) {
+                if !fields.contains(&key.as_str()) {
+                    unknown_keys.borrow_mut().push(UnknownKey {
+                        path: key_path(&self.path, &key),
+                        suggestion: suggest(&key, fields),
+                    });
+                    continue;
+                }
+            }
+
+            let key_value = seed
+                .deserialize(key.clone().into_deserializer())
+                .map_err(|e| self.ctx.on_error(&key_path(&self.path, &key), e))?;
+            self.pending = Some((key, value));
+            return Ok(Some(key_value));
+        }
+
+        Ok(None)
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, Self::Error>
+    where
+        V: de::DeserializeSeed<'de>,
+    {
+        let (key, value) = self
+            .pending
+            .take()
+            .ok_or_else(|| ConfigError::Message("value requested before its key".into()))?;
+        let path = key_path(&self.path, &key);
+        seed.deserialize(StrictValue {
+            value,
+            path: path.clone(),
+            ctx: self.ctx,
+        })
+        .map_err(|e| self.ctx.on_error(&path, e))
+    }
+}
+
+/// Access to the values of a configuration array, to check the structures it contains
+struct StrictSeq<'a> {
+    values: std::iter::Enumerate<std::vec::IntoIter<Value>>,
+    path: String,
+    ctx: &'a Context,
+}
+
+impl<'de> de::SeqAccess<'de> for StrictSeq<'_> {
+    type Error = ConfigError;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, Self::Error>
+    where
+        T: de::DeserializeSeed<'de>,
+    {
+        match self.values.next() {
+            Some((index, value)) => {
+                let path = format!("{}[{}]", self.path, index);
+                seed.deserialize(StrictValue {
+                    value,
+                    path: path.clone(),
+                    ctx: self.ctx,
+                })
+                .map(Some)
+                .map_err(|e| self.ctx.on_error(&path, e))
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_suggest() {
+        assert_eq!(0, edit_distance("timeout", "timeout"));
+        assert_eq!(1, edit_distance("observabilty", "observability"));
+        assert_eq!(3, edit_distance("kitten", "sitting"));
+        assert_eq!(
+            Some("observability".to_string()),
+            suggest("observabilty", &["name", "observability", "heartbeat"])
+        );
+        assert_eq!(None, suggest("foo", &["name", "observability"]));
+    }
+}
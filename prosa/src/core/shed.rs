@@ -0,0 +1,92 @@
+//! Load shedding of the processors, to answer fast when a processor falls behind
+//!
+//! With the `shed_policy` processor setting (see [`ShedPolicy`]), the receiver queue of the processor ([`ProcRxQueue`](crate::core::proc::ProcRxQueue)) evaluates every request it dequeues against the number of messages still pending in the queue.
+//! Because requests are evaluated when they are dequeued, the oldest pending requests are the ones shed, and the most recent ones are kept for the processor.
+//! A shed request is answered immediately with a [`ServiceError::Unavailable`](crate::core::service::ServiceError::Unavailable), without reaching the processor nor its adaptor.
+//! Shed requests are counted with the `prosa_proc_shed_requests` counter (with the `proc` and `service` attributes).
+//!
+//! ```yaml
+//! stub:
+//!   service_names: ["PAY"]
+//!   shed_policy:
+//!     drop_low_priority:
+//!       watermark: 100
+//!       priority_tag: 10
+//! ```
+
+use prosa_utils::msg::tvf::Tvf;
+use serde::{Deserialize, Serialize};
+
+/// Load shedding policy of a processor
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ShedPolicy {
+    /// Never shed requests (default)
+    #[default]
+    None,
+    /// Drop the oldest pending requests while the queue depth reach the watermark
+    ///
+    /// The dequeued requests are rejected until less than `watermark` messages are pending behind them, so the most recent requests are the ones processed.
+    DropOldest {
+        /// Number of pending messages from which requests are rejected
+        watermark: usize,
+    },
+    /// Drop the oldest pending low priority requests while the queue depth reach the watermark
+    DropLowPriority {
+        /// Number of pending messages from which low priority requests are rejected
+        watermark: usize,
+        /// Tag of the requests that contain their priority (unsigned, `0` if absent)
+        priority_tag: usize,
+        /// Minimum priority of the requests that are never rejected (`1` by default)
+        #[serde(default = "ShedPolicy::default_min_priority")]
+        min_priority: u64,
+    },
+}
+
+impl ShedPolicy {
+    fn default_min_priority() -> u64 {
+        1
+    }
+
+    /// Method to know if a request should be shed, given the number of messages pending behind it
+    ///
+    /// ```
+    /// use config::{Config, File, FileFormat};
+    /// use prosa::core::shed::ShedPolicy;
+    /// use prosa_utils::msg::simple_string_tvf::SimpleStringTvf;
+    /// use prosa_utils::msg::tvf::Tvf;
+    ///
+    /// let policy: ShedPolicy = Config::builder()
+    ///     .add_source(File::from_str(
+    ///         "drop_low_priority:\n  watermark: 10\n  priority_tag: 1",
+    ///         FileFormat::Yaml,
+    ///     ))
+    ///     .build()
+    ///     .and_then(|config| config.try_deserialize())
+    ///     .unwrap();
+    ///
+    /// let mut request = SimpleStringTvf::default();
+    /// assert!(!policy.should_shed(9, &request));
+    /// assert!(policy.should_shed(10, &request));
+    ///
+    /// request.put_unsigned(1, 2);
+    /// assert!(!policy.should_shed(10, &request));
+    /// ```
+    pub fn should_shed<M>(&self, depth: usize, request: &M) -> bool
+    where
+        M: Tvf,
+    {
+        match self {
+            ShedPolicy::None => false,
+            ShedPolicy::DropOldest { watermark } => depth >= *watermark,
+            ShedPolicy::DropLowPriority {
+                watermark,
+                priority_tag,
+                min_priority,
+            } => {
+                depth >= *watermark
+                    && request.get_unsigned(*priority_tag).unwrap_or_default() < *min_priority
+            }
+        }
+    }
+}
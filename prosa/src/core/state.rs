@@ -0,0 +1,820 @@
+//! Embedded key-value state store, to keep small states of processors (sequence counters, routing tables, ...) across restarts
+//!
+//! The state store is configured with the `state` setting (see [`StateSettings`]), and processors get a namespaced handle on it with [`ProcParam::state`](crate::core::proc::ProcParam::state).
+//!
+//! ```
+//! use prosa::core::proc::ProcParam;
+//! use prosa_utils::msg::tvf::Tvf;
+//!
+//! fn next_sequence<M>(proc: &ProcParam<M>) -> u64
+//! where
+//!     M: Sized + Clone + std::fmt::Debug + Tvf + Default + 'static + std::marker::Send + std::marker::Sync,
+//! {
+//!     let Ok(Some(state)) = proc.state("sequence") else {
+//!         return 0;
+//!     };
+//!
+//!     let sequence = state
+//!         .update("seq", |seq| {
+//!             let seq = seq.map(|s| u64::from_be_bytes(s[..].try_into().unwrap())).unwrap_or_default();
+//!             Some((seq + 1).to_be_bytes().to_vec().into())
+//!         })
+//!         .unwrap();
+//!     u64::from_be_bytes(sequence.unwrap()[..].try_into().unwrap())
+//! }
+//! ```
+
+use std::{
+    collections::BTreeMap,
+    fmt::Debug,
+    fs::{File, OpenOptions},
+    io::{self, BufReader, Read, Write},
+    ops::Bound,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tracing::{info, warn};
+
+/// Error of the state store
+#[derive(Debug, Error)]
+pub enum StateError {
+    /// Error on the state store file
+    #[error("State store IO error: {0}")]
+    Io(#[from] io::Error),
+    /// The key or the value is too large to be stored, with its size
+    #[error("State store entry of {0} bytes is too large")]
+    TooLarge(usize),
+    /// The namespace of a handle is too large (it must be shorter than 64 KiB), with its size
+    #[error("State namespace of {0} bytes is too large")]
+    NamespaceTooLarge(usize),
+}
+
+/// Policy to sync the state store writes on the disk
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StateSyncPolicy {
+    /// Every write is synced on the disk before returning, so it survives a system crash
+    #[default]
+    Always,
+    /// Writes are left to the system, so they survive a process crash but may be lost on a system crash
+    Never,
+}
+
+/// Settings of the state store
+///
+/// ```yaml
+/// state:
+///   path: /var/lib/my-prosa/state.log
+///   sync: always
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct StateSettings {
+    /// Path of the state store file
+    pub path: PathBuf,
+    /// Policy to sync the writes on the disk
+    #[serde(default)]
+    pub sync: StateSyncPolicy,
+    /// Size in bytes of the state store file above which it's compacted, if more than half of it is outdated
+    #[serde(default = "StateSettings::default_compaction_threshold")]
+    pub compaction_threshold: u64,
+}
+
+impl StateSettings {
+    fn default_compaction_threshold() -> u64 {
+        1024 * 1024
+    }
+
+    /// Method to create state store settings with its file path
+    pub fn new<P>(path: P) -> StateSettings
+    where
+        P: Into<PathBuf>,
+    {
+        StateSettings {
+            path: path.into(),
+            sync: StateSyncPolicy::default(),
+            compaction_threshold: Self::default_compaction_threshold(),
+        }
+    }
+}
+
+/// Trait of a key-value state store
+///
+/// Every operation is atomic per key. Operations are synchronous, so a store is meant for small states.
+pub trait StateStore: Debug + Send + Sync {
+    /// Getter of the value of a key
+    fn get(&self, key: &[u8]) -> Result<Option<Bytes>, StateError>;
+
+    /// Method to put the value of a key
+    fn put(&self, key: &[u8], value: Bytes) -> Result<(), StateError>;
+
+    /// Method to delete a key, return its previous value
+    fn delete(&self, key: &[u8]) -> Result<Option<Bytes>, StateError>;
+
+    /// Method to update atomically the value of a key from its current value.
+    /// The key is deleted if the function returns `None`. Return the new value
+    fn update(
+        &self,
+        key: &[u8],
+        f: &mut dyn FnMut(Option<&Bytes>) -> Option<Bytes>,
+    ) -> Result<Option<Bytes>, StateError>;
+
+    /// Method to get the entries whose key starts with the prefix, ordered by key
+    fn scan(&self, prefix: &[u8]) -> Result<Vec<(Bytes, Bytes)>, StateError>;
+}
+
+/// Operation of a state store log record
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LogOp {
+    Put = 1,
+    Delete = 2,
+}
+
+/// Size of a log record without its key and value: operation, key length, value length and checksum
+const RECORD_OVERHEAD: u64 = 1 + 4 + 4 + 4;
+
+/// CRC-32 (IEEE) checksum of a log record
+fn crc32(data: &[&[u8]]) -> u32 {
+    let mut crc = !0u32;
+    for byte in data.iter().flat_map(|d| d.iter()) {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xEDB8_8320 & (crc & 1).wrapping_neg());
+        }
+    }
+
+    !crc
+}
+
+/// Method to encode a log record `op | key length | value length | key | value | crc32`
+fn encode_record(op: LogOp, key: &[u8], value: &[u8]) -> Result<Vec<u8>, StateError> {
+    let key_len = u32::try_from(key.len()).map_err(|_| StateError::TooLarge(key.len()))?;
+    let value_len = u32::try_from(value.len()).map_err(|_| StateError::TooLarge(value.len()))?;
+    let header = [
+        &[op as u8][..],
+        &key_len.to_be_bytes()[..],
+        &value_len.to_be_bytes()[..],
+    ]
+    .concat();
+
+    let mut record = Vec::with_capacity(RECORD_OVERHEAD as usize + key.len() + value.len());
+    record.extend_from_slice(&header);
+    record.extend_from_slice(key);
+    record.extend_from_slice(value);
+    record.extend_from_slice(&crc32(&[&header, key, value]).to_be_bytes());
+    Ok(record)
+}
+
+/// Method to read the next log record. Return `None` at the end of the log, or if the record is incomplete or corrupted
+fn read_record<R>(reader: &mut R) -> io::Result<Option<(LogOp, Bytes, Bytes)>>
+where
+    R: Read,
+{
+    fn read_exact_or_eof<R: Read>(reader: &mut R, buf: &mut [u8]) -> io::Result<bool> {
+        match reader.read_exact(buf) {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    let mut header = [0u8; 9];
+    if !read_exact_or_eof(reader, &mut header)? {
+        return Ok(None);
+    }
+
+    let op = match header[0] {
+        1 => LogOp::Put,
+        2 => LogOp::Delete,
+        _ => return Ok(None),
+    };
+    let key_len = u32::from_be_bytes(header[1..5].try_into().unwrap()) as usize;
+    let value_len = u32::from_be_bytes(header[5..9].try_into().unwrap()) as usize;
+
+    let mut data = Vec::new();
+    if reader
+        .take((key_len + value_len + 4) as u64)
+        .read_to_end(&mut data)?
+        != key_len + value_len + 4
+    {
+        return Ok(None);
+    }
+
+    let (key, rest) = data.split_at(key_len);
+    let (value, crc) = rest.split_at(value_len);
+    if crc32(&[&header, key, value]).to_be_bytes() != crc {
+        return Ok(None);
+    }
+
+    Ok(Some((
+        op,
+        Bytes::copy_from_slice(key),
+        Bytes::copy_from_slice(value),
+    )))
+}
+
+/// Mutable state of the file state store
+#[derive(Debug)]
+struct FileStateLog {
+    file: File,
+    index: BTreeMap<Bytes, Bytes>,
+    /// Size of the log file
+    log_size: u64,
+    /// Size of the records of the live entries
+    live_size: u64,
+}
+
+/// File state store: an append-only log of the writes, with an in-memory index of the live entries
+///
+/// When the store is opened, the log is replayed up to the last complete record, so an interrupted write is discarded.
+/// The log is compacted (rewritten with only the live entries) when it exceeds the compaction threshold and more than half of it is outdated.
+#[derive(Debug)]
+pub struct FileStateStore {
+    path: PathBuf,
+    sync: StateSyncPolicy,
+    compaction_threshold: u64,
+    log: Mutex<FileStateLog>,
+}
+
+impl FileStateStore {
+    /// Method to open (or create) the state store file, and load its entries
+    pub fn open(settings: &StateSettings) -> Result<FileStateStore, StateError> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(&settings.path)?;
+
+        // Replay the log to build the index
+        let mut index: BTreeMap<Bytes, Bytes> = BTreeMap::new();
+        let mut log_size = 0;
+        let mut live_size = 0;
+        {
+            let mut reader = BufReader::new(&mut file);
+            while let Some((op, key, value)) = read_record(&mut reader)? {
+                let record_size = RECORD_OVERHEAD + key.len() as u64 + value.len() as u64;
+                log_size += record_size;
+                if let Some(previous) = index.remove(&key) {
+                    live_size -= RECORD_OVERHEAD + key.len() as u64 + previous.len() as u64;
+                }
+                if op == LogOp::Put {
+                    live_size += record_size;
+                    index.insert(key, value);
+                }
+            }
+        }
+
+        // Discard an interrupted write
+        let file_size = file.metadata()?.len();
+        if file_size > log_size {
+            warn!(target: "prosa::core::state", path = ?settings.path, "Discard {} bytes of an incomplete state store record", file_size - log_size);
+            file.set_len(log_size)?;
+            file.sync_all()?;
+        }
+
+        Ok(FileStateStore {
+            path: settings.path.clone(),
+            sync: settings.sync,
+            compaction_threshold: settings.compaction_threshold,
+            log: Mutex::new(FileStateLog {
+                file,
+                index,
+                log_size,
+                live_size,
+            }),
+        })
+    }
+
+    /// Getter of the state store file path
+    pub fn get_path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Getter of the number of entries in the store
+    pub fn len(&self) -> usize {
+        self.log.lock().unwrap().index.len()
+    }
+
+    /// Returns true if the store doesn't contain any entry
+    pub fn is_empty(&self) -> bool {
+        self.log.lock().unwrap().index.is_empty()
+    }
+
+    /// Method to compact the state store file, to keep only the live entries
+    pub fn compact(&self) -> Result<(), StateError> {
+        let mut log = self.log.lock().unwrap();
+        self.compact_log(&mut log)
+    }
+
+    /// Method to append a record to the log
+    fn append(&self, log: &mut FileStateLog, record: &[u8]) -> Result<(), StateError> {
+        if let Err(e) = log.file.write_all(record).and_then(|_| {
+            if self.sync == StateSyncPolicy::Always {
+                log.file.sync_data()
+            } else {
+                Ok(())
+            }
+        }) {
+            // Remove the partial record, so the next records are not lost on replay
+            let _ = log.file.set_len(log.log_size);
+            return Err(e.into());
+        }
+
+        log.log_size += record.len() as u64;
+        Ok(())
+    }
+
+    /// Method to write the new value of a key (delete if `None`), and compact the log if needed
+    fn write(
+        &self,
+        log: &mut FileStateLog,
+        key: &[u8],
+        value: Option<Bytes>,
+    ) -> Result<Option<Bytes>, StateError> {
+        let record = match &value {
+            Some(value) => encode_record(LogOp::Put, key, value)?,
+            None => encode_record(LogOp::Delete, key, &[])?,
+        };
+        self.append(log, &record)?;
+
+        let previous = match &value {
+            Some(value) => {
+                log.live_size += record.len() as u64;
+                log.index.insert(Bytes::copy_from_slice(key), value.clone())
+            }
+            None => log.index.remove(key),
+        };
+        if let Some(previous) = &previous {
+            log.live_size -= RECORD_OVERHEAD + key.len() as u64 + previous.len() as u64;
+        }
+
+        if log.log_size > self.compaction_threshold && log.log_size > 2 * log.live_size {
+            if let Err(e) = self.compact_log(log) {
+                warn!(target: "prosa::core::state", path = ?self.path, "Can't compact the state store: {}", e);
+            }
+        }
+
+        Ok(previous)
+    }
+
+    /// Method to rewrite the log with only the live entries. The new log replaces the current one atomically
+    fn compact_log(&self, log: &mut FileStateLog) -> Result<(), StateError> {
+        let mut compact_path = self.path.clone().into_os_string();
+        compact_path.push(".compact");
+        let compact_path = PathBuf::from(compact_path);
+
+        let mut compact_file = File::create(&compact_path)?;
+        let mut log_size = 0;
+        for (key, value) in &log.index {
+            let record = encode_record(LogOp::Put, key, value)?;
+            compact_file.write_all(&record)?;
+            log_size += record.len() as u64;
+        }
+        compact_file.sync_all()?;
+        drop(compact_file);
+
+        std::fs::rename(&compact_path, &self.path)?;
+        if let Some(dir) = self.path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            // Persist the rename
+            let _ = File::open(dir).and_then(|d| d.sync_all());
+        }
+
+        info!(target: "prosa::core::state", path = ?self.path, "State store compacted from {} to {} bytes", log.log_size, log_size);
+        log.file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .open(&self.path)?;
+        log.log_size = log_size;
+        log.live_size = log_size;
+        Ok(())
+    }
+}
+
+impl StateStore for FileStateStore {
+    fn get(&self, key: &[u8]) -> Result<Option<Bytes>, StateError> {
+        Ok(self.log.lock().unwrap().index.get(key).cloned())
+    }
+
+    fn put(&self, key: &[u8], value: Bytes) -> Result<(), StateError> {
+        let mut log = self.log.lock().unwrap();
+        self.write(&mut log, key, Some(value))?;
+        Ok(())
+    }
+
+    fn delete(&self, key: &[u8]) -> Result<Option<Bytes>, StateError> {
+        let mut log = self.log.lock().unwrap();
+        if log.index.contains_key(key) {
+            self.write(&mut log, key, None)
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn update(
+        &self,
+        key: &[u8],
+        f: &mut dyn FnMut(Option<&Bytes>) -> Option<Bytes>,
+    ) -> Result<Option<Bytes>, StateError> {
+        let mut log = self.log.lock().unwrap();
+        let current = log.index.get(key);
+        let exists = current.is_some();
+        let value = f(current);
+        if exists || value.is_some() {
+            self.write(&mut log, key, value.clone())?;
+        }
+
+        Ok(value)
+    }
+
+    fn scan(&self, prefix: &[u8]) -> Result<Vec<(Bytes, Bytes)>, StateError> {
+        Ok(self
+            .log
+            .lock()
+            .unwrap()
+            .index
+            .range::<[u8], _>((Bound::Included(prefix), Bound::Unbounded))
+            .take_while(|(key, _)| key.starts_with(prefix))
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect())
+    }
+}
+
+/// Handle on a namespace of the state store. Keys of different namespaces never collide
+///
+/// The handle is cheap to clone, and can be shared between tasks.
+///
+/// ```
+/// use std::sync::Arc;
+/// use prosa::core::state::{FileStateStore, StateHandle, StateSettings};
+///
+/// let path = std::env::temp_dir().join(format!("prosa-state-doc-{}.log", std::process::id()));
+/// let store = Arc::new(FileStateStore::open(&StateSettings::new(&path)).unwrap());
+///
+/// let routing = StateHandle::new(store.clone(), "routing").unwrap();
+/// let counters = StateHandle::new(store, "counters").unwrap();
+/// routing.put("last", "PROC_1").unwrap();
+/// counters.put("last", 42u64.to_be_bytes().to_vec()).unwrap();
+///
+/// assert_eq!(Some("PROC_1".into()), routing.get("last").unwrap());
+/// assert_eq!(vec![("last".into(), "PROC_1".into())], routing.iter().unwrap());
+/// # std::fs::remove_file(path).unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct StateHandle {
+    store: Arc<dyn StateStore>,
+    namespace: String,
+    prefix: Bytes,
+}
+
+impl StateHandle {
+    /// Method to create a handle on a namespace of the store
+    ///
+    /// Return a [`StateError::NamespaceTooLarge`] if the namespace is not shorter than 64 KiB
+    pub fn new<S>(store: Arc<dyn StateStore>, namespace: S) -> Result<StateHandle, StateError>
+    where
+        S: Into<String>,
+    {
+        let namespace = namespace.into();
+        // The namespace is length-prefixed, so no namespace is the prefix of another
+        let namespace_len = u16::try_from(namespace.len())
+            .map_err(|_| StateError::NamespaceTooLarge(namespace.len()))?;
+        let prefix = [&namespace_len.to_be_bytes()[..], namespace.as_bytes()]
+            .concat()
+            .into();
+        Ok(StateHandle {
+            store,
+            namespace,
+            prefix,
+        })
+    }
+
+    /// Getter of the namespace of the handle
+    pub fn get_namespace(&self) -> &str {
+        &self.namespace
+    }
+
+    /// Method to get the store key of a namespace key
+    fn key(&self, key: &[u8]) -> Vec<u8> {
+        [&self.prefix[..], key].concat()
+    }
+
+    /// Getter of the value of a key
+    pub fn get<K>(&self, key: K) -> Result<Option<Bytes>, StateError>
+    where
+        K: AsRef<[u8]>,
+    {
+        self.store.get(&self.key(key.as_ref()))
+    }
+
+    /// Method to put the value of a key
+    pub fn put<K, V>(&self, key: K, value: V) -> Result<(), StateError>
+    where
+        K: AsRef<[u8]>,
+        V: Into<Bytes>,
+    {
+        self.store.put(&self.key(key.as_ref()), value.into())
+    }
+
+    /// Method to delete a key, return its previous value
+    pub fn delete<K>(&self, key: K) -> Result<Option<Bytes>, StateError>
+    where
+        K: AsRef<[u8]>,
+    {
+        self.store.delete(&self.key(key.as_ref()))
+    }
+
+    /// Method to update atomically the value of a key from its current value.
+    /// The key is deleted if the function returns `None`. Return the new value
+    pub fn update<K, F>(&self, key: K, mut f: F) -> Result<Option<Bytes>, StateError>
+    where
+        K: AsRef<[u8]>,
+        F: FnMut(Option<&Bytes>) -> Option<Bytes>,
+    {
+        self.store.update(&self.key(key.as_ref()), &mut f)
+    }
+
+    /// Method to get all the entries of the namespace, ordered by key
+    pub fn iter(&self) -> Result<Vec<(Bytes, Bytes)>, StateError> {
+        Ok(self
+            .store
+            .scan(&self.prefix)?
+            .into_iter()
+            .map(|(key, value)| (key.slice(self.prefix.len()..), value))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate self as prosa;
+
+    use std::error::Error;
+
+    use prosa_macros::{settings, Adaptor};
+    use prosa_utils::msg::{simple_string_tvf::SimpleStringTvf, tvf::Tvf};
+    use serde::Serialize;
+
+    use crate::core::main::{MainProc, MainRunnable as _};
+    use crate::core::proc::ProcConfig as _;
+    use crate::core::service::ServiceError;
+    use crate::stub::{
+        adaptor::StubAdaptor,
+        proc::{StubProc, StubSettings},
+    };
+
+    use super::*;
+
+    /// Path of a state store file for a test, removed if it exists
+    fn state_path(name: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("prosa-state-{}-{}.log", name, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn file_state_store_reopen() {
+        let settings = StateSettings::new(state_path("reopen"));
+        let store = FileStateStore::open(&settings).unwrap();
+        assert!(store.is_empty());
+        store.put(b"a", Bytes::from("1")).unwrap();
+        store.put(b"b", Bytes::from("2")).unwrap();
+        store.put(b"a", Bytes::from("3")).unwrap();
+        store.put(b"c", Bytes::from("4")).unwrap();
+        assert_eq!(Some(Bytes::from("2")), store.delete(b"b").unwrap());
+        assert_eq!(None, store.delete(b"b").unwrap());
+        assert_eq!(
+            None,
+            store
+                .update(b"c", &mut |value| {
+                    assert_eq!(Some(&Bytes::from("4")), value);
+                    None
+                })
+                .unwrap()
+        );
+        store.put(b"ab", Bytes::from("5")).unwrap();
+        drop(store);
+
+        let store = FileStateStore::open(&settings).unwrap();
+        assert_eq!(2, store.len());
+        assert_eq!(Some(Bytes::from("3")), store.get(b"a").unwrap());
+        assert_eq!(None, store.get(b"b").unwrap());
+        assert_eq!(None, store.get(b"c").unwrap());
+        assert_eq!(
+            vec![
+                (Bytes::from("a"), Bytes::from("3")),
+                (Bytes::from("ab"), Bytes::from("5"))
+            ],
+            store.scan(b"a").unwrap()
+        );
+        assert!(store.scan(b"b").unwrap().is_empty());
+
+        std::fs::remove_file(store.get_path()).unwrap();
+    }
+
+    #[test]
+    fn file_state_store_interrupted_write() {
+        let settings = StateSettings::new(state_path("interrupted"));
+        let store = FileStateStore::open(&settings).unwrap();
+        store.put(b"counter", Bytes::from("1")).unwrap();
+        store.put(b"counter", Bytes::from("2")).unwrap();
+        drop(store);
+        let committed_size = std::fs::metadata(&settings.path).unwrap().len();
+
+        // Drop the store in the middle of a write
+        let record = encode_record(LogOp::Put, b"counter", b"3").unwrap();
+        let mut file = OpenOptions::new()
+            .append(true)
+            .open(&settings.path)
+            .unwrap();
+        file.write_all(&record[..record.len() - 2]).unwrap();
+        drop(file);
+
+        let store = FileStateStore::open(&settings).unwrap();
+        assert_eq!(Some(Bytes::from("2")), store.get(b"counter").unwrap());
+        assert_eq!(
+            committed_size,
+            std::fs::metadata(&settings.path).unwrap().len()
+        );
+
+        // Next writes are not lost behind the interrupted one
+        store.put(b"counter", Bytes::from("4")).unwrap();
+        drop(store);
+
+        // A corrupted record is discarded too
+        let mut record = encode_record(LogOp::Put, b"counter", b"5").unwrap();
+        record[10] ^= 0xFF;
+        let mut file = OpenOptions::new()
+            .append(true)
+            .open(&settings.path)
+            .unwrap();
+        file.write_all(&record).unwrap();
+        drop(file);
+
+        let store = FileStateStore::open(&settings).unwrap();
+        assert_eq!(Some(Bytes::from("4")), store.get(b"counter").unwrap());
+
+        std::fs::remove_file(store.get_path()).unwrap();
+    }
+
+    #[test]
+    fn file_state_store_compaction() {
+        let mut settings = StateSettings::new(state_path("compaction"));
+        settings.sync = StateSyncPolicy::Never;
+        settings.compaction_threshold = 1024;
+        let store = FileStateStore::open(&settings).unwrap();
+        store.put(b"static", Bytes::from("value")).unwrap();
+        for i in 0..1000u64 {
+            store
+                .put(b"counter", Bytes::copy_from_slice(&i.to_be_bytes()))
+                .unwrap();
+        }
+
+        let file_size = std::fs::metadata(&settings.path).unwrap().len();
+        assert!(file_size <= 1024, "file size {} not compacted", file_size);
+        drop(store);
+
+        let store = FileStateStore::open(&settings).unwrap();
+        assert_eq!(
+            Some(Bytes::copy_from_slice(&999u64.to_be_bytes())),
+            store.get(b"counter").unwrap()
+        );
+        assert_eq!(Some(Bytes::from("value")), store.get(b"static").unwrap());
+
+        store.compact().unwrap();
+        assert_eq!(
+            2 * RECORD_OVERHEAD + 7 + 8 + 6 + 5,
+            std::fs::metadata(&settings.path).unwrap().len()
+        );
+
+        std::fs::remove_file(store.get_path()).unwrap();
+    }
+
+    #[test]
+    fn state_handle_namespaces() {
+        let path = state_path("namespaces");
+        let store: Arc<dyn StateStore> =
+            Arc::new(FileStateStore::open(&StateSettings::new(&path)).unwrap());
+        let first = StateHandle::new(store.clone(), "a").unwrap();
+        let second = StateHandle::new(store.clone(), "ab").unwrap();
+        let second_clone = second.clone();
+        assert_eq!("ab", second_clone.get_namespace());
+
+        first.put("bkey", "first").unwrap();
+        second_clone.put("key", "second").unwrap();
+        assert_eq!(Some(Bytes::from("first")), first.get("bkey").unwrap());
+        assert_eq!(None, first.get("key").unwrap());
+        assert_eq!(Some(Bytes::from("second")), second.get("key").unwrap());
+        assert_eq!(
+            vec![(Bytes::from("bkey"), Bytes::from("first"))],
+            first.iter().unwrap()
+        );
+        assert_eq!(
+            vec![(Bytes::from("key"), Bytes::from("second"))],
+            second.iter().unwrap()
+        );
+
+        assert_eq!(Some(Bytes::from("second")), second.delete("key").unwrap());
+        assert!(second.iter().unwrap().is_empty());
+        assert_eq!(1, first.iter().unwrap().len());
+
+        // A namespace must be shorter than 64 KiB
+        assert!(matches!(
+            StateHandle::new(store, "n".repeat(u16::MAX as usize + 1)),
+            Err(StateError::NamespaceTooLarge(65536))
+        ));
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    /// Dummy settings
+    #[settings]
+    #[derive(Default, Debug, Serialize)]
+    struct TestSettings {}
+
+    /// Stub adaptor that responds with the number of requests it processed, kept across restarts
+    #[derive(Adaptor)]
+    struct CounterStubAdaptor {
+        state: StateHandle,
+    }
+
+    impl<M> StubAdaptor<M> for CounterStubAdaptor
+    where
+        M: 'static
+            + std::marker::Send
+            + std::marker::Sync
+            + std::marker::Sized
+            + std::clone::Clone
+            + std::fmt::Debug
+            + prosa_utils::msg::tvf::Tvf
+            + std::default::Default,
+    {
+        fn new(proc: &StubProc<M>) -> Result<Self, Box<dyn Error>> {
+            Ok(Self {
+                state: proc
+                    .get_proc_param()
+                    .state("counter_stub")?
+                    .ok_or("no state store configured")?,
+            })
+        }
+
+        fn process_request(&mut self, _service_name: &str, request: &M) -> Result<M, ServiceError> {
+            let counter = self
+                .state
+                .update("counter", |counter| {
+                    let counter = counter
+                        .map(|c| u64::from_be_bytes(c[..].try_into().unwrap()))
+                        .unwrap_or_default();
+                    Some(Bytes::copy_from_slice(&(counter + 1).to_be_bytes()))
+                })
+                .map_err(|e| ServiceError::Internal(e.to_string()))?
+                .unwrap();
+
+            let mut response = request.clone();
+            response.put_unsigned(1, u64::from_be_bytes(counter[..].try_into().unwrap()));
+            Ok(response)
+        }
+    }
+
+    #[tokio::test]
+    async fn stub_counter_restart() {
+        let path = state_path("stub");
+        let settings = TestSettings {
+            state: Some(StateSettings::new(&path)),
+            ..Default::default()
+        };
+        let process_requests = |count: u64| {
+            let (bus, _main) = MainProc::<SimpleStringTvf>::create(&settings);
+            let stub = StubProc::create(1, bus, StubSettings::new(vec![String::from("SRV")]));
+            let mut adaptor = <CounterStubAdaptor as StubAdaptor<_>>::new(&stub).unwrap();
+            (0..count)
+                .map(|_| {
+                    adaptor
+                        .process_request("SRV", &SimpleStringTvf::default())
+                        .unwrap()
+                        .get_unsigned(1)
+                        .unwrap()
+                })
+                .last()
+        };
+
+        assert_eq!(Some(3), process_requests(3));
+        // Simulated restart of the ProSA
+        assert_eq!(Some(5), process_requests(2));
+
+        // A state store that can't be opened is reported, not a panic
+        let settings = TestSettings {
+            state: Some(StateSettings::new(path.join("state.log"))),
+            ..Default::default()
+        };
+        assert!(matches!(
+            MainProc::<SimpleStringTvf>::try_create(&settings),
+            Err(StateError::Io(_))
+        ));
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,383 @@
+//! Moving window of the transactions of the bus, to know the current TPS and latency of a ProSA
+//!
+//! The window is enabled with the `tps` setting (see [`TpsSettings`]), disabled by default.
+//! Every transaction answered on the bus (response or error) is counted in the bucket of the current time, with its latency.
+//! The main task closes the buckets, and give the instantaneous TPS (last bucket), the TPS and latency percentiles of the window, and the 1/5/15 minutes TPS (exponentially weighted like the load average).
+//!
+//! The statistics are in the topology and health snapshots of the main bus, and given by the `tps` command of the control socket.
+//!
+//! ```yaml
+//! tps:
+//!   bucket: 1000
+//!   window: 60
+//! ```
+
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+
+/// Settings of the transactions moving window
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct TpsSettings {
+    /// Duration of a bucket in milliseconds
+    #[serde(default = "TpsSettings::default_bucket")]
+    bucket: u64,
+    /// Number of buckets of the window
+    #[serde(default = "TpsSettings::default_window")]
+    window: usize,
+}
+
+impl TpsSettings {
+    fn default_bucket() -> u64 {
+        1000
+    }
+
+    fn default_window() -> usize {
+        60
+    }
+
+    /// Method to create a window of `window` buckets of `bucket` duration
+    pub fn new(bucket: Duration, window: usize) -> TpsSettings {
+        TpsSettings {
+            bucket: bucket.as_millis() as u64,
+            window,
+        }
+    }
+
+    /// Getter of the duration of a bucket
+    pub fn get_bucket(&self) -> Duration {
+        Duration::from_millis(self.bucket.max(1))
+    }
+
+    /// Getter of the number of buckets of the window
+    pub fn get_window(&self) -> usize {
+        self.window.max(1)
+    }
+}
+
+impl Default for TpsSettings {
+    fn default() -> Self {
+        TpsSettings {
+            bucket: Self::default_bucket(),
+            window: Self::default_window(),
+        }
+    }
+}
+
+/// Number of sub-buckets of every power of two of the latency histogram
+const LATENCY_SUB_BITS: u32 = 2;
+/// Number of slots of the latency histogram (latencies in microseconds, 4 slots per power of two)
+const LATENCY_SLOTS: usize = ((64 - LATENCY_SUB_BITS as usize) << LATENCY_SUB_BITS) + 4;
+
+/// Function to get the latency histogram slot of a latency in microseconds
+fn latency_slot(micros: u64) -> usize {
+    if micros < 1 << LATENCY_SUB_BITS {
+        micros as usize
+    } else {
+        let exp = 63 - micros.leading_zeros();
+        let sub = (micros >> (exp - LATENCY_SUB_BITS)) & ((1 << LATENCY_SUB_BITS) - 1);
+        (((exp - LATENCY_SUB_BITS + 1) << LATENCY_SUB_BITS) as u64 + sub) as usize
+    }
+}
+
+/// Function to get the middle latency in microseconds of a latency histogram slot
+fn slot_latency(slot: usize) -> u64 {
+    if slot < 1 << LATENCY_SUB_BITS {
+        slot as u64
+    } else {
+        let exp = (slot >> LATENCY_SUB_BITS) as u32 + LATENCY_SUB_BITS - 1;
+        let sub = (slot & ((1 << LATENCY_SUB_BITS) - 1)) as u64;
+        let width = 1u64 << (exp - LATENCY_SUB_BITS);
+        (1u64 << exp) + sub * width + width / 2
+    }
+}
+
+/// Bucket of the window, reused when the window go round
+#[derive(Debug)]
+struct TpsBucket {
+    /// Number of the bucket (since the window creation) counted by this slot
+    epoch: AtomicU64,
+    count: AtomicU64,
+    latencies: [AtomicU64; LATENCY_SLOTS],
+}
+
+impl TpsBucket {
+    fn new() -> TpsBucket {
+        TpsBucket {
+            epoch: AtomicU64::new(u64::MAX),
+            count: AtomicU64::new(0),
+            latencies: std::array::from_fn(|_| AtomicU64::new(0)),
+        }
+    }
+
+    /// Method to reset the bucket if it counted a previous epoch
+    fn reset(&self, epoch: u64) {
+        let previous = self.epoch.load(Ordering::Acquire);
+        if previous != epoch
+            && self
+                .epoch
+                .compare_exchange(previous, epoch, Ordering::AcqRel, Ordering::Acquire)
+                .is_ok()
+        {
+            self.count.store(0, Ordering::Relaxed);
+            for latency in &self.latencies {
+                latency.store(0, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+/// Moving window of the transactions, shared by the bus (recording) and the main task (statistics)
+///
+/// Recording a transaction doesn't allocate: buckets are a fixed-size ring of atomic counters and latency histograms.
+///
+/// ```
+/// use std::time::Duration;
+/// use prosa::core::tps::{TpsSettings, TpsWindow};
+///
+/// let window = TpsWindow::new(&TpsSettings::new(Duration::from_millis(100), 10));
+/// window.record(Duration::from_millis(2));
+/// window.record(Duration::from_millis(4));
+///
+/// let snapshot = window.snapshot();
+/// assert_eq!(2, snapshot.transactions);
+/// assert_eq!(0.0, snapshot.tps); // The current bucket is not closed yet
+/// ```
+#[derive(Debug)]
+pub struct TpsWindow {
+    start: Instant,
+    bucket: Duration,
+    buckets: Box<[TpsBucket]>,
+    /// Last epoch included in the moving averages
+    averaged_epoch: AtomicU64,
+    /// 1, 5 and 15 minutes TPS (`f64` bits)
+    averages: [AtomicU64; 3],
+}
+
+/// Periods of the TPS moving averages in seconds
+const AVERAGE_PERIODS: [f64; 3] = [60.0, 300.0, 900.0];
+
+impl TpsWindow {
+    /// Method to create a moving window from its settings
+    pub fn new(settings: &TpsSettings) -> TpsWindow {
+        TpsWindow {
+            start: Instant::now(),
+            bucket: settings.get_bucket(),
+            // One more bucket for the current one, not closed yet
+            buckets: (0..=settings.get_window())
+                .map(|_| TpsBucket::new())
+                .collect(),
+            averaged_epoch: AtomicU64::new(0),
+            averages: std::array::from_fn(|_| AtomicU64::new(0f64.to_bits())),
+        }
+    }
+
+    /// Getter of the duration of a bucket
+    pub fn get_bucket(&self) -> Duration {
+        self.bucket
+    }
+
+    /// Epoch (bucket number since the window creation) of the current bucket
+    fn current_epoch(&self) -> u64 {
+        (self.start.elapsed().as_nanos() / self.bucket.as_nanos()) as u64
+    }
+
+    fn slot(&self, epoch: u64) -> &TpsBucket {
+        &self.buckets[(epoch % self.buckets.len() as u64) as usize]
+    }
+
+    /// Method to count a transaction answered with its latency
+    pub fn record(&self, latency: Duration) {
+        let epoch = self.current_epoch();
+        let bucket = self.slot(epoch);
+        bucket.reset(epoch);
+        bucket.count.fetch_add(1, Ordering::Relaxed);
+        bucket.latencies[latency_slot(latency.as_micros() as u64)].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Method to get the number of transactions of a closed bucket (0 if it's not in the window anymore)
+    fn closed_count(&self, epoch: u64) -> u64 {
+        let bucket = self.slot(epoch);
+        if bucket.epoch.load(Ordering::Acquire) == epoch {
+            bucket.count.load(Ordering::Relaxed)
+        } else {
+            0
+        }
+    }
+
+    /// Method to update the 1/5/15 minutes TPS with the buckets closed since the last update.
+    /// Called by the main task at every bucket
+    pub fn tick(&self) {
+        let current_epoch = self.current_epoch();
+        let bucket_secs = self.bucket.as_secs_f64();
+        let mut epoch = self.averaged_epoch.load(Ordering::Acquire);
+        while epoch < current_epoch {
+            let tps = self.closed_count(epoch) as f64 / bucket_secs;
+            for (average, period) in self.averages.iter().zip(AVERAGE_PERIODS) {
+                let alpha = 1.0 - (-bucket_secs / period).exp();
+                let value = f64::from_bits(average.load(Ordering::Relaxed));
+                average.store((value + alpha * (tps - value)).to_bits(), Ordering::Relaxed);
+            }
+            epoch += 1;
+        }
+        self.averaged_epoch.store(epoch, Ordering::Release);
+    }
+
+    /// Method to get the statistics of the window
+    pub fn snapshot(&self) -> TpsSnapshot {
+        let current_epoch = self.current_epoch();
+        let bucket_secs = self.bucket.as_secs_f64();
+        let mut latencies = [0u64; LATENCY_SLOTS];
+        let mut transactions = 0;
+        let mut window_count = 0;
+        let mut closed_buckets = 0;
+        for epoch in current_epoch.saturating_sub(self.buckets.len() as u64 - 1)..=current_epoch {
+            let bucket = self.slot(epoch);
+            if bucket.epoch.load(Ordering::Acquire) != epoch {
+                if epoch < current_epoch {
+                    closed_buckets += 1;
+                }
+                continue;
+            }
+
+            let count = bucket.count.load(Ordering::Relaxed);
+            transactions += count;
+            for (total, latency) in latencies.iter_mut().zip(bucket.latencies.iter()) {
+                *total += latency.load(Ordering::Relaxed);
+            }
+            if epoch < current_epoch {
+                window_count += count;
+                closed_buckets += 1;
+            }
+        }
+
+        let percentile = |p: f64| {
+            let rank = ((transactions as f64 * p).ceil() as u64).max(1);
+            let mut cumulated = 0;
+            for (slot, count) in latencies.iter().enumerate() {
+                cumulated += count;
+                if cumulated >= rank {
+                    return Duration::from_micros(slot_latency(slot));
+                }
+            }
+
+            Duration::ZERO
+        };
+
+        let average = |i: usize| f64::from_bits(self.averages[i].load(Ordering::Relaxed));
+        TpsSnapshot {
+            transactions,
+            tps: current_epoch
+                .checked_sub(1)
+                .map(|epoch| self.closed_count(epoch) as f64 / bucket_secs)
+                .unwrap_or_default(),
+            window_tps: if closed_buckets > 0 {
+                window_count as f64 / (closed_buckets as f64 * bucket_secs)
+            } else {
+                0.0
+            },
+            tps_1m: average(0),
+            tps_5m: average(1),
+            tps_15m: average(2),
+            latency_p50: percentile(0.5),
+            latency_p99: percentile(0.99),
+        }
+    }
+}
+
+/// Statistics of the transactions moving window
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct TpsSnapshot {
+    /// Number of transactions in the window (with the current bucket)
+    pub transactions: u64,
+    /// Instantaneous TPS (last closed bucket)
+    pub tps: f64,
+    /// TPS of the closed buckets of the window
+    pub window_tps: f64,
+    /// TPS averaged over 1 minute
+    pub tps_1m: f64,
+    /// TPS averaged over 5 minutes
+    pub tps_5m: f64,
+    /// TPS averaged over 15 minutes
+    pub tps_15m: f64,
+    /// Median latency of the transactions of the window
+    #[serde(with = "duration_micros")]
+    pub latency_p50: Duration,
+    /// 99th percentile latency of the transactions of the window
+    #[serde(with = "duration_micros")]
+    pub latency_p99: Duration,
+}
+
+/// Serialization of the latencies in microseconds
+mod duration_micros {
+    use std::time::Duration;
+
+    use serde::Serializer;
+
+    pub(super) fn serialize<S>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_u64(duration.as_micros() as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn latency_slots() {
+        for micros in [
+            0,
+            1,
+            3,
+            4,
+            5,
+            7,
+            8,
+            100,
+            1_000,
+            12_345,
+            1_000_000,
+            u64::MAX / 2,
+        ] {
+            let slot = latency_slot(micros);
+            assert!(slot < LATENCY_SLOTS);
+            let latency = slot_latency(slot);
+            // Middle of the slot, within 12.5% of the latency
+            assert!(
+                latency.abs_diff(micros) <= micros / 8 + 1,
+                "{} µs in slot {} ({} µs)",
+                micros,
+                slot,
+                latency
+            );
+        }
+        assert_eq!(LATENCY_SLOTS - 1, latency_slot(u64::MAX));
+    }
+
+    #[test]
+    fn tps_window() {
+        let window = TpsWindow::new(&TpsSettings::new(Duration::from_millis(50), 4));
+        for i in 0..100 {
+            window.record(Duration::from_micros(1000 + i * 10));
+        }
+        let snapshot = window.snapshot();
+        assert_eq!(100, snapshot.transactions);
+        let p50 = snapshot.latency_p50.as_micros();
+        assert!((1300..=1700).contains(&p50), "p50 {}", p50);
+        assert!(snapshot.latency_p99 >= snapshot.latency_p50);
+
+        // Once the buckets left the window, they're not counted anymore
+        std::thread::sleep(Duration::from_millis(300));
+        window.tick();
+        let snapshot = window.snapshot();
+        assert_eq!(0, snapshot.transactions);
+        assert_eq!(0.0, snapshot.tps);
+        assert!(snapshot.tps_1m > 0.0);
+    }
+}
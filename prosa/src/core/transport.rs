@@ -0,0 +1,312 @@
+//! Transports of the internal messages between the bus and the processors
+//!
+//! Processors receive their messages through a tokio mpsc queue ([`MpscTransport`]).
+//! For latency critical assemblies, a processor can receive its data messages (requests and responses sent through its [`ProcService`](crate::core::service::ProcService)) through a bounded lock-free queue instead, with the `lockfree` feature ([`LockFreeTransport`]).
+//! The transport is selected per processor with its `transport` setting (see [`TransportSettings`]):
+//!
+//! ```yaml
+//! stub:
+//!   service_names: ["PAY"]
+//!   transport:
+//!     lock_free:
+//!       capacity: 4096
+//! ```
+//!
+//! The control messages, and the responses sent to the processor service queue ([`ProcParam::get_service_queue`](crate::core::proc::ProcParam::get_service_queue)), still go through the mpsc queue of the processor.
+//! The processor receiver queue ([`ProcRxQueue`](crate::core::proc::ProcRxQueue)) merges both, so processors don't see any difference.
+//! Messages sent through different queues are not ordered between them.
+
+use std::{
+    fmt::{self, Debug},
+    future::Future,
+};
+
+#[cfg(feature = "lockfree")]
+use prosa_utils::queue::lockfree::{self, LockFreeQueueError};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+#[cfg(not(feature = "lockfree"))]
+use tracing::warn;
+
+#[cfg(feature = "lockfree")]
+use super::error::QueueErrorKind;
+use super::error::SendError;
+
+/// Transport of the messages to a processor, a kind of queue with its sending and receiving sides
+pub trait BusTransport<T>
+where
+    T: Send,
+{
+    /// Sending side of the transport, cloned for every sender
+    type Sender: Clone + Debug + Send + Sync;
+    /// Receiving side of the transport
+    type Receiver: Debug + Send;
+
+    /// Method to create a queue of the transport that hold at most `capacity` messages
+    fn channel(capacity: usize) -> (Self::Sender, Self::Receiver);
+
+    /// Method to send a message, waiting if the queue is full
+    fn send(sender: &Self::Sender, msg: T)
+        -> impl Future<Output = Result<(), SendError<T>>> + Send;
+
+    /// Method to send a message without waiting if the queue is full
+    #[allow(clippy::result_large_err)]
+    fn try_send(sender: &Self::Sender, msg: T) -> Result<(), SendError<T>>;
+
+    /// Method to receive the next message, `None` once every sender is dropped
+    fn recv(receiver: &mut Self::Receiver) -> impl Future<Output = Option<T>> + Send;
+
+    /// Method to receive the next message without waiting, if any
+    fn try_recv(receiver: &mut Self::Receiver) -> Option<T>;
+
+    /// Getter of the number of messages pending in the queue
+    fn len(receiver: &Self::Receiver) -> usize;
+}
+
+/// Transport over tokio mpsc queues (the default)
+#[derive(Debug, Clone, Copy)]
+pub struct MpscTransport;
+
+impl<T> BusTransport<T> for MpscTransport
+where
+    T: Send + Debug,
+{
+    type Sender = mpsc::Sender<T>;
+    type Receiver = mpsc::Receiver<T>;
+
+    fn channel(capacity: usize) -> (Self::Sender, Self::Receiver) {
+        mpsc::channel(capacity)
+    }
+
+    async fn send(sender: &Self::Sender, msg: T) -> Result<(), SendError<T>> {
+        sender.send(msg).await?;
+        Ok(())
+    }
+
+    fn try_send(sender: &Self::Sender, msg: T) -> Result<(), SendError<T>> {
+        sender.try_send(msg)?;
+        Ok(())
+    }
+
+    async fn recv(receiver: &mut Self::Receiver) -> Option<T> {
+        receiver.recv().await
+    }
+
+    fn try_recv(receiver: &mut Self::Receiver) -> Option<T> {
+        receiver.try_recv().ok()
+    }
+
+    fn len(receiver: &Self::Receiver) -> usize {
+        receiver.len()
+    }
+}
+
+/// Transport over the bounded lock-free queues of [`prosa_utils::queue::lockfree`]
+#[cfg(feature = "lockfree")]
+#[derive(Debug, Clone, Copy)]
+pub struct LockFreeTransport;
+
+#[cfg(feature = "lockfree")]
+impl<T> From<LockFreeQueueError<T>> for SendError<T> {
+    fn from(error: LockFreeQueueError<T>) -> Self {
+        match error {
+            LockFreeQueueError::Full(msg) => SendError::new(QueueErrorKind::Full, msg),
+            LockFreeQueueError::Closed(msg) => SendError::new(QueueErrorKind::Closed, msg),
+        }
+    }
+}
+
+#[cfg(feature = "lockfree")]
+impl<T> BusTransport<T> for LockFreeTransport
+where
+    T: Send + Debug,
+{
+    type Sender = lockfree::Sender<T>;
+    type Receiver = lockfree::Receiver<T>;
+
+    fn channel(capacity: usize) -> (Self::Sender, Self::Receiver) {
+        lockfree::channel(capacity)
+    }
+
+    async fn send(sender: &Self::Sender, msg: T) -> Result<(), SendError<T>> {
+        sender.send(msg).await?;
+        Ok(())
+    }
+
+    fn try_send(sender: &Self::Sender, msg: T) -> Result<(), SendError<T>> {
+        sender.try_send(msg)?;
+        Ok(())
+    }
+
+    async fn recv(receiver: &mut Self::Receiver) -> Option<T> {
+        receiver.recv().await
+    }
+
+    fn try_recv(receiver: &mut Self::Receiver) -> Option<T> {
+        receiver.try_recv()
+    }
+
+    fn len(receiver: &Self::Receiver) -> usize {
+        receiver.len()
+    }
+}
+
+/// Transport settings of a processor (`transport` processor setting)
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransportSettings {
+    /// All the messages go through the mpsc queue of the processor (default)
+    #[default]
+    Mpsc,
+    /// The data messages go through a lock-free queue (needs the `lockfree` feature, the mpsc queue is used without it)
+    LockFree {
+        /// Maximum number of messages pending in the lock-free queue
+        capacity: usize,
+    },
+}
+
+impl TransportSettings {
+    /// Method to create the data queue of a processor, if its transport use a dedicated one
+    pub fn channel<T>(&self) -> Option<(TransportSender<T>, TransportReceiver<T>)>
+    where
+        T: Send + Debug,
+    {
+        match self {
+            TransportSettings::Mpsc => None,
+            #[cfg(feature = "lockfree")]
+            TransportSettings::LockFree { capacity } => {
+                let (sender, receiver) = LockFreeTransport::channel(*capacity);
+                Some((
+                    TransportSender::LockFree(sender),
+                    TransportReceiver::LockFree(receiver),
+                ))
+            }
+            #[cfg(not(feature = "lockfree"))]
+            TransportSettings::LockFree { .. } => {
+                warn!("The lock-free transport needs the `lockfree` feature, the mpsc queue is used instead");
+                None
+            }
+        }
+    }
+}
+
+/// Sending side of a processor queue, for any of the transports
+pub enum TransportSender<T> {
+    /// Sender of a [`MpscTransport`] queue
+    Mpsc(mpsc::Sender<T>),
+    /// Sender of a [`LockFreeTransport`] queue
+    #[cfg(feature = "lockfree")]
+    LockFree(lockfree::Sender<T>),
+}
+
+impl<T> TransportSender<T>
+where
+    T: Send + Debug,
+{
+    /// Method to send a message, waiting if the queue is full
+    pub async fn send(&self, msg: T) -> Result<(), SendError<T>> {
+        match self {
+            TransportSender::Mpsc(sender) => MpscTransport::send(sender, msg).await,
+            #[cfg(feature = "lockfree")]
+            TransportSender::LockFree(sender) => LockFreeTransport::send(sender, msg).await,
+        }
+    }
+
+    /// Method to send a message without waiting if the queue is full
+    #[allow(clippy::result_large_err)]
+    pub fn try_send(&self, msg: T) -> Result<(), SendError<T>> {
+        match self {
+            TransportSender::Mpsc(sender) => MpscTransport::try_send(sender, msg),
+            #[cfg(feature = "lockfree")]
+            TransportSender::LockFree(sender) => LockFreeTransport::try_send(sender, msg),
+        }
+    }
+}
+
+impl<T> Clone for TransportSender<T> {
+    fn clone(&self) -> Self {
+        match self {
+            TransportSender::Mpsc(sender) => TransportSender::Mpsc(sender.clone()),
+            #[cfg(feature = "lockfree")]
+            TransportSender::LockFree(sender) => TransportSender::LockFree(sender.clone()),
+        }
+    }
+}
+
+impl<T> Debug for TransportSender<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TransportSender::Mpsc(_) => f.write_str("TransportSender::Mpsc"),
+            #[cfg(feature = "lockfree")]
+            TransportSender::LockFree(_) => f.write_str("TransportSender::LockFree"),
+        }
+    }
+}
+
+impl<T> From<mpsc::Sender<T>> for TransportSender<T> {
+    fn from(sender: mpsc::Sender<T>) -> Self {
+        TransportSender::Mpsc(sender)
+    }
+}
+
+/// Receiving side of a processor queue, for any of the transports
+pub enum TransportReceiver<T> {
+    /// Receiver of a [`MpscTransport`] queue
+    Mpsc(mpsc::Receiver<T>),
+    /// Receiver of a [`LockFreeTransport`] queue
+    #[cfg(feature = "lockfree")]
+    LockFree(lockfree::Receiver<T>),
+}
+
+impl<T> TransportReceiver<T>
+where
+    T: Send + Debug,
+{
+    /// Method to receive the next message, `None` once every sender is dropped
+    pub async fn recv(&mut self) -> Option<T> {
+        match self {
+            TransportReceiver::Mpsc(receiver) => MpscTransport::recv(receiver).await,
+            #[cfg(feature = "lockfree")]
+            TransportReceiver::LockFree(receiver) => LockFreeTransport::recv(receiver).await,
+        }
+    }
+
+    /// Method to receive the next message without waiting, if any
+    pub fn try_recv(&mut self) -> Option<T> {
+        match self {
+            TransportReceiver::Mpsc(receiver) => MpscTransport::try_recv(receiver),
+            #[cfg(feature = "lockfree")]
+            TransportReceiver::LockFree(receiver) => LockFreeTransport::try_recv(receiver),
+        }
+    }
+
+    /// Getter of the number of messages pending in the queue
+    pub fn len(&self) -> usize {
+        match self {
+            TransportReceiver::Mpsc(receiver) => MpscTransport::len(receiver),
+            #[cfg(feature = "lockfree")]
+            TransportReceiver::LockFree(receiver) => LockFreeTransport::len(receiver),
+        }
+    }
+
+    /// Method to know if the queue is empty
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<T> Debug for TransportReceiver<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TransportReceiver::Mpsc(_) => f.write_str("TransportReceiver::Mpsc"),
+            #[cfg(feature = "lockfree")]
+            TransportReceiver::LockFree(_) => f.write_str("TransportReceiver::LockFree"),
+        }
+    }
+}
+
+impl<T> From<mpsc::Receiver<T>> for TransportReceiver<T> {
+    fn from(receiver: mpsc::Receiver<T>) -> Self {
+        TransportReceiver::Mpsc(receiver)
+    }
+}
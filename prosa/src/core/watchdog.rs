@@ -0,0 +1,334 @@
+//! Watchdog of the processors, to detect adaptor calls that block their processor
+//!
+//! A synchronous adaptor that block (blocking IO, infinite loop) freeze its processor silently.
+//! With the `watchdog` processor setting (see [`WatchdogSettings`]), the processor record a heartbeat around each adaptor invocation, and a monitor thread check that no invocation last longer than `max_processing_time`.
+//!
+//! On a violation, the watchdog:
+//! - log an error with the processor name and the service of the message being processed
+//! - count it with the `prosa_proc_watchdog_violations` counter (with the `proc` and `service` attributes)
+//! - restart the processor group if `restart` is set (only for processors launched in a group with [`Main::run_proc`](crate::core::main::Main::run_proc)). The processor is restarted once its blocking call return
+//!
+//! ```yaml
+//! stub:
+//!   service_names: ["PAY"]
+//!   watchdog:
+//!     max_processing_time:
+//!       secs: 1
+//!       nanos: 0
+//!     restart: true
+//! ```
+
+use std::{
+    fmt::Debug,
+    future::Future,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, Weak,
+    },
+    time::{Duration, Instant},
+};
+
+use opentelemetry::{
+    metrics::{Counter, Meter},
+    KeyValue,
+};
+use prosa_utils::msg::tvf::Tvf;
+use serde::{Deserialize, Serialize};
+use tracing::{error, warn};
+
+use super::{main::Main, msg::InternalMainMsg};
+
+/// Settings of the processor watchdog
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct WatchdogSettings {
+    /// Maximum duration of an adaptor invocation before it's reported
+    #[serde(default = "WatchdogSettings::default_max_processing_time")]
+    pub max_processing_time: Duration,
+    /// Restart the processor group on a violation
+    #[serde(default)]
+    pub restart: bool,
+}
+
+impl WatchdogSettings {
+    fn default_max_processing_time() -> Duration {
+        Duration::from_secs(5)
+    }
+
+    /// Method to create watchdog settings with the maximum duration of an adaptor invocation
+    pub fn new(max_processing_time: Duration) -> WatchdogSettings {
+        WatchdogSettings {
+            max_processing_time,
+            restart: false,
+        }
+    }
+
+    /// Interval at which the monitor check the heartbeat (a quarter of the maximum processing time, between 1ms and 1s)
+    pub fn check_interval(&self) -> Duration {
+        (self.max_processing_time / 4).clamp(Duration::from_millis(1), Duration::from_secs(1))
+    }
+}
+
+impl Default for WatchdogSettings {
+    fn default() -> Self {
+        WatchdogSettings::new(Self::default_max_processing_time())
+    }
+}
+
+/// Heartbeat of the adaptor invocations
+#[derive(Debug, Default)]
+struct Heartbeat {
+    /// Start of the current invocation (`None` if the adaptor is idle)
+    since: Option<Instant>,
+    /// Service of the last processed message
+    service: String,
+    /// Sequence of the invocations, to report a violation only once
+    invocation: u64,
+}
+
+/// Reporter of the watchdog violations, shared with the monitor thread
+#[derive(Clone)]
+struct WatchdogReporter {
+    name: String,
+    max_processing_time: Duration,
+    violations: Arc<AtomicU64>,
+    counter: Option<Counter<u64>>,
+    restart: Option<Arc<dyn Fn() + Send + Sync>>,
+}
+
+impl WatchdogReporter {
+    fn report(&self, service: &str, elapsed: Duration) {
+        self.violations.fetch_add(1, Ordering::Relaxed);
+        error!(name: "proc_watchdog", target: "prosa::core::watchdog", proc_name = self.name, service = service, elapsed = ?elapsed, "The processor is processing a message for longer than {:?}", self.max_processing_time);
+        if let Some(counter) = &self.counter {
+            counter.add(
+                1,
+                &[
+                    KeyValue::new("proc", self.name.clone()),
+                    KeyValue::new("service", service.to_string()),
+                ],
+            );
+        }
+        if let Some(restart) = &self.restart {
+            restart();
+        }
+    }
+}
+
+impl Debug for WatchdogReporter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WatchdogReporter")
+            .field("name", &self.name)
+            .field("max_processing_time", &self.max_processing_time)
+            .field("violations", &self.violations)
+            .field("restart", &self.restart.is_some())
+            .finish()
+    }
+}
+
+/// Watchdog of a processor, that detect adaptor invocations longer than the maximum processing time
+///
+/// Synchronous invocations are surrounded by a [`WatchdogGuard`], and checked by a monitor thread (the processor runtime can't check them while it's blocked).
+/// The monitor thread stop once the watchdog is dropped.
+/// Asynchronous invocations are wrapped with a timeout by [`Watchdog::timeout`].
+///
+/// ```
+/// use std::time::Duration;
+/// use prosa::core::watchdog::{Watchdog, WatchdogSettings};
+///
+/// let watchdog = Watchdog::new(String::from("STUB_PROC"), WatchdogSettings::new(Duration::from_millis(20))).start();
+/// {
+///     let _guard = watchdog.guard("PAY");
+///     // Adaptor call that block its processor
+///     std::thread::sleep(Duration::from_millis(200));
+/// }
+/// assert_eq!(1, watchdog.violations());
+/// ```
+#[derive(Debug)]
+pub struct Watchdog {
+    reporter: WatchdogReporter,
+    check_interval: Duration,
+    heartbeat: Arc<Mutex<Heartbeat>>,
+}
+
+impl Watchdog {
+    /// Method to create a watchdog for a processor. The monitor is launched with [`Watchdog::start`]
+    pub fn new(name: String, settings: WatchdogSettings) -> Watchdog {
+        Watchdog {
+            reporter: WatchdogReporter {
+                name,
+                max_processing_time: settings.max_processing_time,
+                violations: Arc::new(AtomicU64::new(0)),
+                counter: None,
+                restart: None,
+            },
+            check_interval: settings.check_interval(),
+            heartbeat: Arc::new(Mutex::new(Heartbeat::default())),
+        }
+    }
+
+    /// Setter of the meter used to count the violations (`prosa_proc_watchdog_violations` counter)
+    pub fn meter(mut self, meter: &Meter) -> Self {
+        self.reporter.counter = Some(
+            meter
+                .u64_counter("prosa_proc_watchdog_violations")
+                .with_description(
+                    "Number of adaptor invocations longer than the processor max processing time",
+                )
+                .init(),
+        );
+        self
+    }
+
+    /// Setter of the processor group to restart on a violation
+    pub fn restart<M>(mut self, main: Main<M>, group: String) -> Self
+    where
+        M: Sized + Clone + Debug + Tvf + Default + 'static + std::marker::Send + std::marker::Sync,
+    {
+        let name = self.reporter.name.clone();
+        self.reporter.restart = Some(Arc::new(move || {
+            // Can't wait on the main queue from the monitor thread
+            if let Err(e) = main
+                .get_bus_queue()
+                .try_send(InternalMainMsg::RestartGroup(group.clone()))
+            {
+                warn!(name: "proc_watchdog", target: "prosa::core::watchdog", proc_name = name, "Can't restart the processor group {}: {}", group, e);
+            }
+        }));
+        self
+    }
+
+    /// Method to launch the monitor thread of the watchdog
+    pub fn start(self) -> Self {
+        let heartbeat = Arc::downgrade(&self.heartbeat);
+        let reporter = self.reporter.clone();
+        let check_interval = self.check_interval;
+        std::thread::Builder::new()
+            .name(format!("{}-watchdog", self.reporter.name))
+            .spawn(move || Self::monitor(heartbeat, reporter, check_interval))
+            .unwrap();
+        self
+    }
+
+    /// Loop of the monitor thread, until the watchdog is dropped
+    fn monitor(heartbeat: Weak<Mutex<Heartbeat>>, reporter: WatchdogReporter, interval: Duration) {
+        let mut reported = None;
+        loop {
+            std::thread::sleep(interval);
+            let Some(heartbeat) = heartbeat.upgrade() else {
+                return;
+            };
+
+            let violation = {
+                let heartbeat = heartbeat.lock().unwrap();
+                match heartbeat.since {
+                    Some(since)
+                        if reported != Some(heartbeat.invocation)
+                            && since.elapsed() > reporter.max_processing_time =>
+                    {
+                        reported = Some(heartbeat.invocation);
+                        Some((heartbeat.service.clone(), since.elapsed()))
+                    }
+                    _ => None,
+                }
+            };
+
+            if let Some((service, elapsed)) = violation {
+                reporter.report(&service, elapsed);
+            }
+        }
+    }
+
+    /// Getter of the maximum duration of an adaptor invocation
+    pub fn get_max_processing_time(&self) -> Duration {
+        self.reporter.max_processing_time
+    }
+
+    /// Getter of the number of violations detected
+    pub fn violations(&self) -> u64 {
+        self.reporter.violations.load(Ordering::Relaxed)
+    }
+
+    /// Method to record the start of a synchronous adaptor invocation for a message of a service.
+    /// The invocation end when the returned guard is dropped
+    pub fn guard(&self, service: &str) -> WatchdogGuard<'_> {
+        let mut heartbeat = self.heartbeat.lock().unwrap();
+        heartbeat.since = Some(Instant::now());
+        heartbeat.service.clear();
+        heartbeat.service.push_str(service);
+        heartbeat.invocation = heartbeat.invocation.wrapping_add(1);
+        WatchdogGuard { watchdog: self }
+    }
+
+    /// Method to run an asynchronous adaptor invocation for a message of a service, that is abandoned if it last longer than the maximum processing time
+    pub async fn timeout<F>(
+        &self,
+        service: &str,
+        future: F,
+    ) -> Result<F::Output, tokio::time::error::Elapsed>
+    where
+        F: Future,
+    {
+        let result = tokio::time::timeout(self.reporter.max_processing_time, future).await;
+        if result.is_err() {
+            self.reporter
+                .report(service, self.reporter.max_processing_time);
+        }
+        result
+    }
+}
+
+/// Guard of a synchronous adaptor invocation watched by a [`Watchdog`]
+#[derive(Debug)]
+pub struct WatchdogGuard<'a> {
+    watchdog: &'a Watchdog,
+}
+
+impl Drop for WatchdogGuard<'_> {
+    fn drop(&mut self) {
+        self.watchdog.heartbeat.lock().unwrap().since = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn watchdog_guard() {
+        let watchdog = Watchdog::new(
+            String::from("TEST_PROC"),
+            WatchdogSettings::new(Duration::from_millis(20)),
+        )
+        .start();
+
+        // Short invocations are not reported
+        for _ in 0..5 {
+            let _guard = watchdog.guard("FAST");
+        }
+        std::thread::sleep(Duration::from_millis(60));
+        assert_eq!(0, watchdog.violations());
+
+        // A long invocation is reported only once
+        {
+            let _guard = watchdog.guard("SLOW");
+            std::thread::sleep(Duration::from_millis(200));
+        }
+        assert_eq!(1, watchdog.violations());
+        assert_eq!("SLOW", watchdog.heartbeat.lock().unwrap().service);
+    }
+
+    #[tokio::test]
+    async fn watchdog_timeout() {
+        let watchdog = Watchdog::new(
+            String::from("TEST_PROC"),
+            WatchdogSettings::new(Duration::from_millis(20)),
+        );
+
+        assert_eq!(Ok(1), watchdog.timeout("FAST", async { 1 }).await);
+        assert!(watchdog
+            .timeout("SLOW", tokio::time::sleep(Duration::from_secs(1)))
+            .await
+            .is_err());
+        assert_eq!(1, watchdog.violations());
+    }
+}
@@ -5,3 +5,12 @@ pub mod pending;
 
 /// Module for speed and flow regulation
 pub mod speed;
+
+/// Module for persistent transaction journal
+pub mod journal;
+
+/// Module for debounced and coalesced event sources
+pub mod debounce;
+
+/// Module for processing statistics (counters and latency percentiles)
+pub mod stats;
@@ -0,0 +1,273 @@
+use std::{
+    collections::HashMap,
+    hash::Hash,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use tokio::{
+    sync::Notify,
+    time::{sleep_until, Instant},
+};
+
+/// Events of a key waiting for the quiet period
+#[derive(Debug)]
+struct PendingEvents<E> {
+    first_event: Instant,
+    last_event: Instant,
+    events: Vec<E>,
+}
+
+impl<E> PendingEvents<E> {
+    /// Getter of the instant when the events should be delivered
+    fn deadline(&self, quiet_period: Duration, max_delay: Option<Duration>) -> Instant {
+        let quiet_deadline = self.last_event + quiet_period;
+        if let Some(max_delay) = max_delay {
+            quiet_deadline.min(self.first_event + max_delay)
+        } else {
+            quiet_deadline
+        }
+    }
+}
+
+/// Shared state of the debouncer handles
+#[derive(Debug)]
+struct DebouncerState<K, E> {
+    pending: Mutex<HashMap<K, PendingEvents<E>>>,
+    notify: Notify,
+}
+
+/// Event source that coalesces bursts of events, and delivers them once the source is quiet
+///
+/// Events are pushed from any task (or thread) on a key.
+/// Events of a key are delivered together by [`Debouncer::recv`] when no event was pushed on the key for the quiet period, or when the first event of the batch waited for the maximum delay (if set).
+/// Keys are coalesced independently, so changes on different files can be delivered separately.
+///
+/// The debouncer is a cheap handle that can be cloned to push events from other tasks.
+///
+/// ```
+/// use std::time::Duration;
+/// use prosa::event::debounce::Debouncer;
+///
+/// async fn watch() {
+///     let debouncer = Debouncer::new(Duration::from_millis(500)).max_delay(Duration::from_secs(5));
+///
+///     let notifier = debouncer.clone();
+///     tokio::spawn(async move {
+///         notifier.push_key("prosa.yml", "modified");
+///         notifier.push_key("prosa.yml", "modified");
+///         notifier.push_key("cert.pem", "created");
+///     });
+///
+///     while let Some((file, events)) = debouncer.recv().await {
+///         println!("{} changed: {:?}", file, events);
+///     }
+/// }
+/// ```
+#[derive(Debug)]
+pub struct Debouncer<E, K = ()>
+where
+    K: Eq + Hash + Clone,
+{
+    quiet_period: Duration,
+    max_delay: Option<Duration>,
+    state: Arc<DebouncerState<K, E>>,
+}
+
+impl<E, K> Clone for Debouncer<E, K>
+where
+    K: Eq + Hash + Clone,
+{
+    fn clone(&self) -> Self {
+        Debouncer {
+            quiet_period: self.quiet_period,
+            max_delay: self.max_delay,
+            state: self.state.clone(),
+        }
+    }
+}
+
+impl<E, K> Debouncer<E, K>
+where
+    K: Eq + Hash + Clone,
+{
+    /// Method to create a debouncer that delivers events after a quiet period (without maximum delay by default)
+    pub fn new(quiet_period: Duration) -> Debouncer<E, K> {
+        Debouncer {
+            quiet_period,
+            max_delay: None,
+            state: Arc::new(DebouncerState {
+                pending: Mutex::new(HashMap::new()),
+                notify: Notify::new(),
+            }),
+        }
+    }
+
+    /// Setter of the maximum delay to deliver an event, even if events keep coming on its key
+    pub fn max_delay(mut self, max_delay: Duration) -> Self {
+        self.max_delay = Some(max_delay);
+        self
+    }
+
+    /// Getter of the quiet period
+    pub fn get_quiet_period(&self) -> Duration {
+        self.quiet_period
+    }
+
+    /// Getter of the maximum delay (if any)
+    pub fn get_max_delay(&self) -> Option<Duration> {
+        self.max_delay
+    }
+
+    /// Returns the number of keys with pending events
+    pub fn len(&self) -> usize {
+        self.state.pending.lock().unwrap().len()
+    }
+
+    /// Returns true if there is no pending event
+    pub fn is_empty(&self) -> bool {
+        self.state.pending.lock().unwrap().is_empty()
+    }
+
+    /// Method to push an event on a key. The quiet period of the key is restarted
+    pub fn push_key(&self, key: K, event: E) {
+        let now = Instant::now();
+        self.state
+            .pending
+            .lock()
+            .unwrap()
+            .entry(key)
+            .and_modify(|pending| pending.last_event = now)
+            .or_insert_with(|| PendingEvents {
+                first_event: now,
+                last_event: now,
+                events: Vec::new(),
+            })
+            .events
+            .push(event);
+        self.state.notify.notify_one();
+    }
+
+    /// Method to wait for the next coalesced batch of events, with its key (cancel safe).
+    /// Return `None` if no other handle can push events anymore
+    pub async fn recv(&self) -> Option<(K, Vec<E>)> {
+        loop {
+            let next_deadline = {
+                let mut pending = self.state.pending.lock().unwrap();
+                let next = pending
+                    .iter()
+                    .map(|(key, events)| (key, events.deadline(self.quiet_period, self.max_delay)))
+                    .min_by_key(|(_, deadline)| *deadline)
+                    .map(|(key, deadline)| (key.clone(), deadline));
+                match next {
+                    Some((key, deadline)) if deadline <= Instant::now() => {
+                        return pending.remove(&key).map(|events| (key, events.events));
+                    }
+                    Some((_, deadline)) => Some(deadline),
+                    None if Arc::strong_count(&self.state) == 1 => return None,
+                    None => None,
+                }
+            };
+
+            if let Some(deadline) = next_deadline {
+                tokio::select! {
+                    _ = self.state.notify.notified() => {},
+                    _ = sleep_until(deadline) => {},
+                }
+            } else {
+                self.state.notify.notified().await;
+            }
+        }
+    }
+}
+
+impl<E> Debouncer<E> {
+    /// Method to push an event without key
+    pub fn push(&self, event: E) {
+        self.push_key((), event)
+    }
+}
+
+impl<E, K> Drop for Debouncer<E, K>
+where
+    K: Eq + Hash + Clone,
+{
+    fn drop(&mut self) {
+        // Wake up the receiver so it can see it's the last handle
+        self.state.notify.notify_one();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const QUIET_PERIOD: Duration = Duration::from_millis(100);
+
+    #[tokio::test(start_paused = true)]
+    async fn debounce_quiet_period() {
+        let debouncer = Debouncer::new(QUIET_PERIOD);
+        let start = Instant::now();
+
+        let notifier = debouncer.clone();
+        tokio::spawn(async move {
+            for event in 0..3 {
+                notifier.push(event);
+                tokio::time::sleep(QUIET_PERIOD / 2).await;
+            }
+        });
+
+        // Delivered one quiet period after the last event
+        assert_eq!(Some(((), vec![0, 1, 2])), debouncer.recv().await);
+        assert_eq!(QUIET_PERIOD + QUIET_PERIOD, start.elapsed());
+        assert!(debouncer.is_empty());
+
+        // Every handle are dropped, no more events
+        assert_eq!(None, debouncer.recv().await);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn debounce_max_delay() {
+        const MAX_DELAY: Duration = Duration::from_millis(230);
+        let debouncer = Debouncer::new(QUIET_PERIOD).max_delay(MAX_DELAY);
+        assert_eq!(Some(MAX_DELAY), debouncer.get_max_delay());
+        let start = Instant::now();
+
+        let notifier = debouncer.clone();
+        tokio::spawn(async move {
+            for event in 0..8 {
+                notifier.push(event);
+                tokio::time::sleep(QUIET_PERIOD / 2).await;
+            }
+        });
+
+        // Events keep coming, but the batch is delivered after the maximum delay
+        assert_eq!(Some(((), (0..5).collect())), debouncer.recv().await);
+        assert_eq!(MAX_DELAY, start.elapsed());
+
+        // The following events are delivered after the quiet period of the last one
+        assert_eq!(Some(((), (5..8).collect())), debouncer.recv().await);
+        assert_eq!(QUIET_PERIOD / 2 * 7 + QUIET_PERIOD, start.elapsed());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn debounce_keys() {
+        let debouncer = Debouncer::new(QUIET_PERIOD);
+        let start = Instant::now();
+
+        let notifier = debouncer.clone();
+        tokio::spawn(async move {
+            notifier.push_key("prosa.yml", 1);
+            tokio::time::sleep(QUIET_PERIOD / 2).await;
+            notifier.push_key("cert.pem", 2);
+            tokio::time::sleep(QUIET_PERIOD / 4).await;
+            notifier.push_key("cert.pem", 3);
+        });
+
+        // Each key is delivered after its own quiet period
+        assert_eq!(Some(("prosa.yml", vec![1])), debouncer.recv().await);
+        assert_eq!(QUIET_PERIOD, start.elapsed());
+        assert_eq!(Some(("cert.pem", vec![2, 3])), debouncer.recv().await);
+        assert_eq!(QUIET_PERIOD / 4 * 3 + QUIET_PERIOD, start.elapsed());
+    }
+}
@@ -0,0 +1,774 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Debug,
+    fs::{self, File, OpenOptions},
+    io::{self, Read as _, Write as _},
+    marker::PhantomData,
+    path::{Path, PathBuf},
+};
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use chrono::{DateTime, NaiveDate};
+use prosa_utils::msg::{
+    codec::{CodecError, Compression},
+    tvf::{Tvf, TvfError, TvfType},
+};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tracing::warn;
+
+/// Error define for transaction journals
+#[derive(Debug, Error)]
+pub enum JournalError {
+    /// Error on the journal file
+    #[error("Journal IO error: {0}")]
+    Io(#[from] io::Error),
+    /// Error on a journaled TVF
+    #[error("Journal TVF error: {0}")]
+    Tvf(#[from] TvfError),
+    /// Error on a compressed journal record
+    #[error("Journal codec error: {0}")]
+    Codec(#[from] CodecError),
+    /// Error on a journal record that can't be decoded
+    #[error("Journal corrupted record: {0}")]
+    Corrupted(String),
+}
+
+/// Policy to flush the journal records on the disk
+#[derive(Debug, Default, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum JournalSync {
+    /// Every record is synchronized on the disk before returning
+    #[default]
+    Always,
+    /// Records are synchronized on the disk every N records
+    Every(u32),
+    /// Records are synchronized by the operating system
+    Never,
+}
+
+/// Settings of a transaction journal
+///
+/// ```yaml
+/// journal:
+///   path: "/var/lib/prosa/inj.journal"
+///   sync:
+///     every: 100
+///   compression:
+///     codec: zstd
+///     threshold: 512
+/// ```
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct JournalSettings {
+    /// Path of the journal file
+    path: PathBuf,
+    /// Policy to synchronize the records on the disk
+    #[serde(default)]
+    sync: JournalSync,
+    /// Compression of the records
+    #[serde(default)]
+    compression: Option<Compression>,
+}
+
+impl JournalSettings {
+    /// Create journal settings for a file, with records synchronized on every write
+    pub fn new(path: PathBuf) -> JournalSettings {
+        JournalSettings {
+            path,
+            sync: JournalSync::default(),
+            compression: None,
+        }
+    }
+
+    /// Setter of the synchronization policy
+    pub fn set_sync(&mut self, sync: JournalSync) {
+        self.sync = sync;
+    }
+
+    /// Setter of the compression of the records
+    pub fn set_compression(&mut self, compression: Compression) {
+        self.compression = Some(compression);
+    }
+
+    /// Method to open the journal described by the settings
+    pub fn open<M>(&self) -> Result<Journal<M>, JournalError>
+    where
+        M: Tvf + Default + Debug + Clone,
+    {
+        Journal::open_with_compression(&self.path, self.sync, self.compression.clone())
+    }
+}
+
+/// Record of a transaction journal
+#[derive(Debug, Clone, PartialEq)]
+pub enum JournalEntry<M> {
+    /// Request sent, with its id, its service and its data
+    Request(u64, String, M),
+    /// Response received, with its id, its service and its data
+    Response(u64, String, M),
+}
+
+impl<M> JournalEntry<M> {
+    /// Getter of the transaction id of the record
+    pub fn get_id(&self) -> u64 {
+        match self {
+            JournalEntry::Request(id, _, _) | JournalEntry::Response(id, _, _) => *id,
+        }
+    }
+
+    /// Getter of the service of the record
+    pub fn get_service(&self) -> &String {
+        match self {
+            JournalEntry::Request(_, service, _) | JournalEntry::Response(_, service, _) => service,
+        }
+    }
+}
+
+const REQUEST_RECORD: u8 = 1;
+const RESPONSE_RECORD: u8 = 2;
+/// Flag of the record kind when the rest of the record body is compressed
+const COMPRESSED_RECORD: u8 = 0x80;
+/// Size of the record header: the length and the checksum of the record body
+const RECORD_HEADER_LEN: usize = 8;
+
+/// Checksum of a record body (FNV-1a)
+fn checksum(body: &[u8]) -> u32 {
+    body.iter().fold(0x811c9dc5, |hash, byte| {
+        (hash ^ *byte as u32).wrapping_mul(0x01000193)
+    })
+}
+
+fn tvf_type_tag(tvf_type: TvfType) -> u8 {
+    match tvf_type {
+        TvfType::Buffer => 1,
+        TvfType::Unsigned => 2,
+        TvfType::Signed => 3,
+        TvfType::Byte => 4,
+        TvfType::Float => 5,
+        TvfType::String => 6,
+        TvfType::Bytes => 7,
+        TvfType::Date => 8,
+        TvfType::DateTime => 9,
+    }
+}
+
+/// Method to encode a TVF as a list of typed fields
+fn encode_tvf<M>(tvf: &M, dst: &mut BytesMut) -> Result<(), JournalError>
+where
+    M: Tvf + Default + Debug + Clone,
+{
+    let keys = tvf.keys();
+    dst.put_u32(keys.len() as u32);
+    for key in keys {
+        let tvf_type = tvf.get_type(key)?;
+        dst.put_u32(key as u32);
+        dst.put_u8(tvf_type_tag(tvf_type));
+        match tvf_type {
+            TvfType::Buffer => {
+                let mut buffer = BytesMut::new();
+                encode_tvf(&*tvf.get_buffer(key)?, &mut buffer)?;
+                dst.put_u32(buffer.len() as u32);
+                dst.put(buffer);
+            }
+            TvfType::Unsigned => dst.put_u64(tvf.get_unsigned(key)?),
+            TvfType::Signed => dst.put_i64(tvf.get_signed(key)?),
+            TvfType::Byte => dst.put_u8(tvf.get_byte(key)?),
+            TvfType::Float => dst.put_f64(tvf.get_float(key)?),
+            TvfType::String => {
+                let string = tvf.get_string(key)?;
+                dst.put_u32(string.len() as u32);
+                dst.put_slice(string.as_bytes());
+            }
+            TvfType::Bytes => {
+                let bytes = tvf.get_bytes(key)?;
+                dst.put_u32(bytes.len() as u32);
+                dst.put_slice(&bytes);
+            }
+            TvfType::Date => dst.put_i64(
+                tvf.get_date(key)?
+                    .and_hms_opt(0, 0, 0)
+                    .unwrap_or_default()
+                    .and_utc()
+                    .timestamp(),
+            ),
+            TvfType::DateTime => dst.put_i64(tvf.get_datetime(key)?.and_utc().timestamp_micros()),
+        }
+    }
+
+    Ok(())
+}
+
+/// Method to check that the buffer contains enough data to decode a value
+fn check_remaining(src: &Bytes, len: usize) -> Result<(), JournalError> {
+    if src.remaining() < len {
+        Err(JournalError::Corrupted(format!(
+            "{} bytes missing",
+            len - src.remaining()
+        )))
+    } else {
+        Ok(())
+    }
+}
+
+/// Method to decode a length prefixed value
+fn decode_chunk(src: &mut Bytes) -> Result<Bytes, JournalError> {
+    check_remaining(src, 4)?;
+    let len = src.get_u32() as usize;
+    check_remaining(src, len)?;
+    Ok(src.split_to(len))
+}
+
+/// Method to decode a TVF from its list of typed fields
+fn decode_tvf<M>(src: &mut Bytes) -> Result<M, JournalError>
+where
+    M: Tvf + Default + Debug + Clone,
+{
+    let mut tvf = M::default();
+    check_remaining(src, 4)?;
+    for _ in 0..src.get_u32() {
+        check_remaining(src, 5)?;
+        let key = src.get_u32() as usize;
+        match src.get_u8() {
+            1 => {
+                let mut buffer = decode_chunk(src)?;
+                tvf.put_buffer(key, decode_tvf(&mut buffer)?);
+            }
+            2 => {
+                check_remaining(src, 8)?;
+                tvf.put_unsigned(key, src.get_u64());
+            }
+            3 => {
+                check_remaining(src, 8)?;
+                tvf.put_signed(key, src.get_i64());
+            }
+            4 => {
+                check_remaining(src, 1)?;
+                tvf.put_byte(key, src.get_u8());
+            }
+            5 => {
+                check_remaining(src, 8)?;
+                tvf.put_float(key, src.get_f64());
+            }
+            6 => {
+                let string = decode_chunk(src)?;
+                tvf.put_string(
+                    key,
+                    String::from_utf8(string.to_vec())
+                        .map_err(|e| JournalError::Corrupted(e.to_string()))?,
+                );
+            }
+            7 => tvf.put_bytes(key, decode_chunk(src)?),
+            8 => {
+                check_remaining(src, 8)?;
+                let date = DateTime::from_timestamp(src.get_i64(), 0)
+                    .map(|d| d.date_naive())
+                    .unwrap_or(NaiveDate::MIN);
+                tvf.put_date(key, date);
+            }
+            9 => {
+                check_remaining(src, 8)?;
+                let datetime = DateTime::from_timestamp_micros(src.get_i64())
+                    .ok_or_else(|| JournalError::Corrupted(String::from("wrong datetime")))?;
+                tvf.put_datetime(key, datetime.naive_utc());
+            }
+            tag => {
+                return Err(JournalError::Corrupted(format!(
+                    "unknown field type {}",
+                    tag
+                )))
+            }
+        }
+    }
+
+    Ok(tvf)
+}
+
+/// Method to encode a journal record (header and body).
+/// With a compression, the body after the record kind is compressed and the kind is flagged with [`COMPRESSED_RECORD`]
+fn encode_record<M>(
+    kind: u8,
+    id: u64,
+    service: &str,
+    data: &M,
+    compression: Option<&Compression>,
+    dst: &mut BytesMut,
+) -> Result<(), JournalError>
+where
+    M: Tvf + Default + Debug + Clone,
+{
+    let mut content = BytesMut::new();
+    content.put_u64(id);
+    content.put_u32(service.len() as u32);
+    content.put_slice(service.as_bytes());
+    encode_tvf(data, &mut content)?;
+
+    let mut body = BytesMut::with_capacity(1 + content.len());
+    if let Some(compression) = compression {
+        body.put_u8(kind | COMPRESSED_RECORD);
+        body.put(compression.compress(&content)?);
+    } else {
+        body.put_u8(kind);
+        body.put(content);
+    }
+
+    dst.put_u32(body.len() as u32);
+    dst.put_u32(checksum(&body));
+    dst.put(body);
+    Ok(())
+}
+
+/// Method to decode the next journal record of the buffer, compressed records are decompressed within `max_size`
+fn decode_record<M>(src: &mut Bytes, max_size: usize) -> Result<JournalEntry<M>, JournalError>
+where
+    M: Tvf + Default + Debug + Clone,
+{
+    check_remaining(src, RECORD_HEADER_LEN)?;
+    let len = src.get_u32() as usize;
+    let record_checksum = src.get_u32();
+    check_remaining(src, len)?;
+    let mut body = src.split_to(len);
+    if checksum(&body) != record_checksum {
+        return Err(JournalError::Corrupted(String::from("wrong checksum")));
+    }
+
+    check_remaining(&body, 1)?;
+    let mut kind = body.get_u8();
+    if kind & COMPRESSED_RECORD != 0 {
+        kind &= !COMPRESSED_RECORD;
+        body = prosa_utils::msg::codec::decompress(&body, max_size)?;
+    }
+
+    check_remaining(&body, 8)?;
+    let id = body.get_u64();
+    let service = String::from_utf8(decode_chunk(&mut body)?.to_vec())
+        .map_err(|e| JournalError::Corrupted(e.to_string()))?;
+    let data = decode_tvf(&mut body)?;
+    match kind {
+        REQUEST_RECORD => Ok(JournalEntry::Request(id, service, data)),
+        RESPONSE_RECORD => Ok(JournalEntry::Response(id, service, data)),
+        kind => Err(JournalError::Corrupted(format!(
+            "unknown record type {}",
+            kind
+        ))),
+    }
+}
+
+/// Write-ahead log of the transactions sent and received by a processor
+///
+/// Every record is a TVF prefixed by its length and its checksum.
+/// Records can be compressed (see [`Journal::open_with_compression`]). Compressed and uncompressed records can be read whatever the compression of the journal.
+/// When the journal is opened, corrupted records at the end of the file (an interrupted write) are truncated with a warning.
+/// Requests without response are kept as unmatched, to be reported after a crash.
+///
+/// ```
+/// use prosa::event::journal::{Journal, JournalEntry, JournalSync};
+/// use prosa_utils::msg::simple_string_tvf::SimpleStringTvf;
+/// use prosa_utils::msg::tvf::Tvf;
+///
+/// let path = std::env::temp_dir().join("prosa_doc_journal.journal");
+/// # let _ = std::fs::remove_file(&path);
+/// let mut request = SimpleStringTvf::default();
+/// request.put_string(1, "request");
+///
+/// let mut journal = Journal::<SimpleStringTvf>::open(&path, JournalSync::Always).unwrap();
+/// journal.append_request(0, "SERVICE", &request).unwrap();
+/// journal.append_response(0, "SERVICE", &request).unwrap();
+/// journal.append_request(1, "SERVICE", &request).unwrap();
+/// drop(journal);
+///
+/// // After a restart, the request without response is reported
+/// let journal = Journal::<SimpleStringTvf>::open(&path, JournalSync::Always).unwrap();
+/// assert_eq!(2, journal.next_id());
+/// assert_eq!(vec![1], journal.unmatched().map(|e| e.get_id()).collect::<Vec<u64>>());
+/// # std::fs::remove_file(&path).unwrap();
+/// ```
+#[derive(Debug)]
+pub struct Journal<M>
+where
+    M: Tvf + Default + Debug + Clone,
+{
+    path: PathBuf,
+    file: File,
+    sync: JournalSync,
+    unsynced: u32,
+    compression: Option<Compression>,
+    unmatched: BTreeMap<u64, JournalEntry<M>>,
+    next_id: u64,
+}
+
+impl<M> Journal<M>
+where
+    M: Tvf + Default + Debug + Clone,
+{
+    /// Method to open (or create) a journal file, and load its unmatched requests
+    pub fn open<P>(path: P, sync: JournalSync) -> Result<Journal<M>, JournalError>
+    where
+        P: AsRef<Path>,
+    {
+        Self::open_with_compression(path, sync, None)
+    }
+
+    /// Method to open (or create) a journal file that compress its records, and load its unmatched requests
+    ///
+    /// Compressed records bigger than the maximum size of the compression are considered as corrupted.
+    pub fn open_with_compression<P>(
+        path: P,
+        sync: JournalSync,
+        compression: Option<Compression>,
+    ) -> Result<Journal<M>, JournalError>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref().to_path_buf();
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(&path)?;
+        let mut content = Vec::new();
+        file.read_to_end(&mut content)?;
+
+        let mut journal = Journal {
+            path,
+            file,
+            sync,
+            unsynced: 0,
+            compression,
+            unmatched: BTreeMap::new(),
+            next_id: 0,
+        };
+
+        let len = content.len();
+        let mut records = Bytes::from(content);
+        while records.has_remaining() {
+            let offset = len - records.remaining();
+            match decode_record::<M>(&mut records, journal.max_size()) {
+                Ok(entry) => journal.load(entry),
+                Err(e) => {
+                    warn!(
+                        "Truncate the journal {} at {} (corrupted tail of {} bytes): {}",
+                        journal.path.display(),
+                        offset,
+                        len - offset,
+                        e
+                    );
+                    journal.file.set_len(offset as u64)?;
+                    journal.file.sync_all()?;
+                    break;
+                }
+            }
+        }
+
+        Ok(journal)
+    }
+
+    /// Getter of the maximum size of the compressed records
+    fn max_size(&self) -> usize {
+        self.compression
+            .as_ref()
+            .map(|compression| compression.max_size)
+            .unwrap_or_else(|| Compression::default().max_size)
+    }
+
+    /// Method to load a record in the journal state
+    fn load(&mut self, entry: JournalEntry<M>) {
+        let id = entry.get_id();
+        if id >= self.next_id {
+            self.next_id = id + 1;
+        }
+
+        match entry {
+            JournalEntry::Request(..) => {
+                self.unmatched.insert(id, entry);
+            }
+            JournalEntry::Response(..) => {
+                self.unmatched.remove(&id);
+            }
+        }
+    }
+
+    /// Method to write a record to the journal file with the synchronization policy
+    fn append(&mut self, kind: u8, id: u64, service: &str, data: &M) -> Result<(), JournalError> {
+        let mut record = BytesMut::new();
+        encode_record(
+            kind,
+            id,
+            service,
+            data,
+            self.compression.as_ref(),
+            &mut record,
+        )?;
+        self.file.write_all(&record)?;
+
+        self.unsynced += 1;
+        match self.sync {
+            JournalSync::Always => self.file.sync_data()?,
+            JournalSync::Every(records) if self.unsynced >= records => self.file.sync_data()?,
+            _ => return Ok(()),
+        }
+        self.unsynced = 0;
+
+        Ok(())
+    }
+
+    /// Method to journal a sent request
+    pub fn append_request(&mut self, id: u64, service: &str, data: &M) -> Result<(), JournalError> {
+        self.append(REQUEST_RECORD, id, service, data)?;
+        self.load(JournalEntry::Request(id, service.to_string(), data.clone()));
+        Ok(())
+    }
+
+    /// Method to journal a received response
+    pub fn append_response(
+        &mut self,
+        id: u64,
+        service: &str,
+        data: &M,
+    ) -> Result<(), JournalError> {
+        self.append(RESPONSE_RECORD, id, service, data)?;
+        self.unmatched.remove(&id);
+        Ok(())
+    }
+
+    /// Getter of the next transaction id, to resume the sequence after the journaled transactions
+    pub fn next_id(&self) -> u64 {
+        self.next_id
+    }
+
+    /// Getter of the journaled requests without response, ordered by id
+    pub fn unmatched(&self) -> impl Iterator<Item = &JournalEntry<M>> {
+        self.unmatched.values()
+    }
+
+    /// Method to read all the records of the journal file in order
+    pub fn replay(&self) -> Result<JournalReplay<M>, JournalError> {
+        Ok(JournalReplay {
+            records: Bytes::from(fs::read(&self.path)?),
+            max_size: self.max_size(),
+            phantom: PhantomData,
+        })
+    }
+
+    /// Method to rewrite the journal file with only the unmatched requests (matched pairs are removed)
+    pub fn compact(&mut self) -> Result<(), JournalError> {
+        let mut records = BytesMut::new();
+        for entry in self.unmatched.values() {
+            if let JournalEntry::Request(id, service, data) = entry {
+                encode_record(
+                    REQUEST_RECORD,
+                    *id,
+                    service,
+                    data,
+                    self.compression.as_ref(),
+                    &mut records,
+                )?;
+            }
+        }
+
+        let mut compact_path = self.path.clone().into_os_string();
+        compact_path.push(".compact");
+        let mut compact_file = File::create(&compact_path)?;
+        compact_file.write_all(&records)?;
+        compact_file.sync_all()?;
+        fs::rename(&compact_path, &self.path)?;
+
+        self.file = OpenOptions::new().append(true).open(&self.path)?;
+        self.unsynced = 0;
+        Ok(())
+    }
+}
+
+/// Iterator over the records of a journal
+#[derive(Debug)]
+pub struct JournalReplay<M> {
+    records: Bytes,
+    max_size: usize,
+    phantom: PhantomData<M>,
+}
+
+impl<M> Iterator for JournalReplay<M>
+where
+    M: Tvf + Default + Debug + Clone,
+{
+    type Item = Result<JournalEntry<M>, JournalError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.records.has_remaining() {
+            let record = decode_record(&mut self.records, self.max_size);
+            if record.is_err() {
+                // Stop at the first corrupted record
+                self.records.clear();
+            }
+            Some(record)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDateTime;
+    use prosa_utils::msg::{codec::Codec, simple_string_tvf::SimpleStringTvf};
+
+    use super::*;
+
+    fn transaction(id: u64) -> SimpleStringTvf {
+        let mut tvf = SimpleStringTvf::default();
+        tvf.put_unsigned(1, id);
+        tvf.put_string(2, format!("transaction {}", id));
+        let mut sub_tvf = SimpleStringTvf::default();
+        sub_tvf.put_float(1, 4.2);
+        sub_tvf.put_bytes(2, Bytes::from_static(b"ProSA"));
+        tvf.put_buffer(3, sub_tvf);
+        tvf.put_datetime(
+            4,
+            NaiveDateTime::parse_from_str("2024-01-01 12:34:56", "%Y-%m-%d %H:%M:%S").unwrap(),
+        );
+        tvf
+    }
+
+    fn assert_transaction(id: u64, tvf: &SimpleStringTvf) {
+        assert_eq!(id, tvf.get_unsigned(1).unwrap());
+        assert_eq!(format!("transaction {}", id), *tvf.get_string(2).unwrap());
+        let sub_tvf = tvf.get_buffer(3).unwrap();
+        assert_eq!(4.2, sub_tvf.get_float(1).unwrap());
+        assert_eq!(Bytes::from_static(b"ProSA"), *sub_tvf.get_bytes(2).unwrap());
+        assert_eq!(
+            "2024-01-01T12:34:56",
+            tvf.get_datetime(4)
+                .unwrap()
+                .format("%Y-%m-%dT%H:%M:%S")
+                .to_string()
+        );
+    }
+
+    #[test]
+    fn journal_recovery() {
+        let path = std::env::temp_dir().join(format!("prosa_test_journal_{}", std::process::id()));
+        let _ = fs::remove_file(&path);
+
+        let mut journal = Journal::<SimpleStringTvf>::open(&path, JournalSync::Every(3)).unwrap();
+        assert_eq!(0, journal.next_id());
+        for id in 0..5 {
+            journal
+                .append_request(id, "SERVICE", &transaction(id))
+                .unwrap();
+        }
+        for id in [0, 2, 4] {
+            journal
+                .append_response(id, "SERVICE", &transaction(id))
+                .unwrap();
+        }
+
+        // Crash without compaction, during the write of a record
+        drop(journal);
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        let mut partial_record = BytesMut::new();
+        encode_record(
+            REQUEST_RECORD,
+            5,
+            "SERVICE",
+            &transaction(5),
+            None,
+            &mut partial_record,
+        )
+        .unwrap();
+        file.write_all(&partial_record[..partial_record.len() / 2])
+            .unwrap();
+        drop(file);
+
+        // The corrupted tail is truncated and the unmatched requests are reported
+        let mut journal = Journal::<SimpleStringTvf>::open(&path, JournalSync::Always).unwrap();
+        assert_eq!(5, journal.next_id());
+        let unmatched: Vec<&JournalEntry<SimpleStringTvf>> = journal.unmatched().collect();
+        assert_eq!(2, unmatched.len());
+        for (entry, id) in unmatched.into_iter().zip([1, 3]) {
+            if let JournalEntry::Request(entry_id, service, data) = entry {
+                assert_eq!(id, *entry_id);
+                assert_eq!("SERVICE", service);
+                assert_transaction(id, data);
+            } else {
+                panic!("Unexpected unmatched entry {:?}", entry);
+            }
+        }
+        let replay: Vec<JournalEntry<SimpleStringTvf>> =
+            journal.replay().unwrap().map(|r| r.unwrap()).collect();
+        assert_eq!(8, replay.len());
+        if let JournalEntry::Response(4, _, data) = &replay[7] {
+            assert_transaction(4, data);
+        } else {
+            panic!("Unexpected last entry {:?}", replay[7]);
+        }
+
+        // Matched pairs are removed by the compaction
+        journal.compact().unwrap();
+        journal
+            .append_response(1, "SERVICE", &transaction(1))
+            .unwrap();
+        drop(journal);
+        let journal = Journal::<SimpleStringTvf>::open(&path, JournalSync::Never).unwrap();
+        assert_eq!(4, journal.next_id());
+        assert_eq!(
+            vec![3],
+            journal
+                .unmatched()
+                .map(|e| e.get_id())
+                .collect::<Vec<u64>>()
+        );
+        assert_eq!(3, journal.replay().unwrap().count());
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn journal_compression() {
+        let path = std::env::temp_dir().join(format!(
+            "prosa_test_journal_compression_{}",
+            std::process::id()
+        ));
+        let raw_path =
+            std::env::temp_dir().join(format!("prosa_test_journal_raw_{}", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let _ = fs::remove_file(&raw_path);
+
+        let mut large_transaction = transaction(1);
+        large_transaction.put_string(5, "ProSA ".repeat(1024));
+
+        let mut settings = JournalSettings::new(path.clone());
+        settings.set_compression(Compression::new(Codec::Zstd, 512));
+        let mut journal = settings.open::<SimpleStringTvf>().unwrap();
+        let mut raw_journal =
+            Journal::<SimpleStringTvf>::open(&raw_path, JournalSync::Always).unwrap();
+        for journal in [&mut journal, &mut raw_journal] {
+            // Only the large transaction is bigger than the threshold
+            journal
+                .append_request(0, "SERVICE", &transaction(0))
+                .unwrap();
+            journal
+                .append_request(1, "SERVICE", &large_transaction)
+                .unwrap();
+        }
+        drop(journal);
+        drop(raw_journal);
+        assert!(fs::metadata(&path).unwrap().len() < fs::metadata(&raw_path).unwrap().len() / 2);
+
+        // Compressed records are read by a journal without compression
+        let journal = Journal::<SimpleStringTvf>::open(&path, JournalSync::Always).unwrap();
+        assert_eq!(2, journal.next_id());
+        let unmatched: Vec<&JournalEntry<SimpleStringTvf>> = journal.unmatched().collect();
+        if let [JournalEntry::Request(0, _, small), JournalEntry::Request(1, _, large)] =
+            unmatched[..]
+        {
+            assert_transaction(0, small);
+            assert_transaction(1, large);
+            assert_eq!("ProSA ".repeat(1024), *large.get_string(5).unwrap());
+        } else {
+            panic!("Unexpected unmatched entries {:?}", unmatched);
+        }
+        assert_eq!(2, journal.replay().unwrap().filter(Result::is_ok).count());
+
+        fs::remove_file(&path).unwrap();
+        fs::remove_file(&raw_path).unwrap();
+    }
+}
@@ -246,6 +246,12 @@ where
         None
     }
 
+    /// Method to pull all the pending messages at once (to fail them when their destination is lost for example)
+    pub fn drain(&mut self) -> impl Iterator<Item = T> + '_ {
+        self.timers = Default::default();
+        self.pending_messages.drain().map(|(_, msg)| msg)
+    }
+
     /// Method to wait for expired message (timeout)
     /// If there is no pending message (`is_empty` == `true`) the method return immediatelly. It doesn't block until a message is pending
     ///
@@ -316,7 +322,8 @@ mod tests {
     };
 
     use crate::core::{
-        main::{BusError, MainProc, MainRunnable},
+        error::BusError,
+        main::{MainProc, MainRunnable},
         msg::{InternalMsg, Msg, RequestMsg},
         proc::{ProcBusParam, ProcConfig},
     };
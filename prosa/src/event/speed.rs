@@ -1,8 +1,9 @@
 use core::fmt;
 use std::{cmp::Ordering, collections::VecDeque, time::Duration};
 
+use prosa_utils::time::{Clock, SystemClock};
 use tokio::sync::Notify;
-use tokio::time::{sleep, Instant};
+use tokio::time::Instant;
 
 /// Structure to define a transaction flow speed
 ///
@@ -81,9 +82,9 @@ impl Speed {
     /// Accumulate all event speeds as Duration
     ///
     /// <math><msub><mi>Σ</mi><mn>t</mn></msub></math>
-    fn accumulate_event_speeds(&self) -> Duration {
+    fn accumulate_event_speeds(&self, now: Instant) -> Duration {
         let mut duration = Duration::ZERO;
-        let mut instant = Instant::now();
+        let mut instant = now;
         for event_speed in &self.event_speeds {
             duration += instant.duration_since(*event_speed);
             instant = *event_speed;
@@ -97,7 +98,7 @@ impl Speed {
     /// <math><mfrac><mi><msub><mi>Σ</mi><mn>t</mn></msub></mi><mi><msub><mi>N</mi><mn>t</mn></msub></mi></mfrac> = mean</math>
     pub fn get_mean_duration(&self) -> Duration {
         if !self.event_speeds.is_empty() {
-            self.accumulate_event_speeds()
+            self.accumulate_event_speeds(Instant::now())
                 .div_f32(self.event_speeds.len() as f32)
         } else {
             Duration::ZERO
@@ -108,7 +109,12 @@ impl Speed {
     ///
     /// <math><mfrac><mi>1000 × <msub><mi>N</mi><mn>t</mn></msub></mi><mi><msub><mi>Σ</mi><mn>t</mn></msub></mi></mfrac> = TPS</math>
     pub fn get_speed(&self) -> f64 {
-        let sum_duration = self.accumulate_event_speeds();
+        self.get_speed_at(Instant::now())
+    }
+
+    /// Getter of the speed of transaction flow at a given instant (see [`Speed::get_speed`])
+    pub fn get_speed_at(&self, now: Instant) -> f64 {
+        let sum_duration = self.accumulate_event_speeds(now);
         if !sum_duration.is_zero() {
             (1000 * self.event_speeds.len()) as f64 / sum_duration.as_millis() as f64
         } else {
@@ -123,9 +129,19 @@ impl Speed {
     ///
     /// <math><mfrac><mi>1000 × <msub><mi>N</mi><mn>t</mn></msub></mi><mi>TPS</mi></mfrac> + overhead − <msub><mi>Σ</mi><mn>t</mn></msub> = duration</math>
     pub fn get_duration_overhead(&self, tps: f64, overhead: Option<Duration>) -> Duration {
+        self.get_duration_overhead_at(tps, overhead, Instant::now())
+    }
+
+    /// Getter of the duration time it must wait at a given instant to target the given TPS rate with an overhead (see [`Speed::get_duration_overhead`])
+    pub fn get_duration_overhead_at(
+        &self,
+        tps: f64,
+        overhead: Option<Duration>,
+        now: Instant,
+    ) -> Duration {
         let duration =
             Duration::from_millis(((1000 * self.event_speeds.len()) as f64 / tps) as u64);
-        let sum_duration = self.accumulate_event_speeds();
+        let sum_duration = self.accumulate_event_speeds(now);
         if let Some(overhead) = overhead {
             duration
                 .saturating_add(overhead)
@@ -179,6 +195,8 @@ impl fmt::Display for Speed {
 
 /// Transaction regulator use to asynchronously regulate flow to fixed TPS
 ///
+/// The regulator waits on the [`SystemClock`] by default, another [`Clock`] can be given with [`Regulator::with_clock`] (e.g. a `MockClock` for tests).
+///
 /// ```
 /// use std::time::Duration;
 /// use tokio::sync::mpsc;
@@ -198,7 +216,7 @@ impl fmt::Display for Speed {
 ///     };
 /// }
 /// ```
-pub struct Regulator {
+pub struct Regulator<C = SystemClock> {
     /// Maximum TPS speed
     max_speed: f64,
     /// Threshold time before sending the next request if the distant respond a timeout (to not overload the distant)
@@ -214,6 +232,8 @@ pub struct Regulator {
     current_concurrents_send: u32,
     /// Overhead when a timeout occur
     tick_overhead: Option<Duration>,
+    /// Clock of the regulator
+    clock: C,
 }
 
 impl Regulator {
@@ -237,9 +257,37 @@ impl Regulator {
             concurent_notify: Notify::new(),
             current_concurrents_send: 0,
             tick_overhead: None,
+            clock: SystemClock,
+        }
+    }
+}
+
+impl<C> Regulator<C>
+where
+    C: Clock,
+{
+    /// Method to use another clock for the regulator (builder style)
+    pub fn with_clock<K>(self, clock: K) -> Regulator<K>
+    where
+        K: Clock,
+    {
+        Regulator {
+            max_speed: self.max_speed,
+            timeout_threshold: self.timeout_threshold,
+            max_concurrents_send: self.max_concurrents_send,
+            speed: self.speed,
+            concurent_notify: self.concurent_notify,
+            current_concurrents_send: self.current_concurrents_send,
+            tick_overhead: self.tick_overhead,
+            clock,
         }
     }
 
+    /// Getter of the current instant of the regulator clock
+    fn now(&self) -> Instant {
+        Instant::from_std(self.clock.now())
+    }
+
     /// Method to synchronize regulator sending rate
     pub async fn tick(&mut self) {
         #[allow(clippy::while_immutable_condition)]
@@ -247,11 +295,11 @@ impl Regulator {
             self.concurent_notify.notified().await;
         }
 
-        let duration = self
-            .speed
-            .get_duration_overhead(self.max_speed, self.tick_overhead);
+        let duration =
+            self.speed
+                .get_duration_overhead_at(self.max_speed, self.tick_overhead, self.now());
         if !duration.is_zero() {
-            sleep(duration).await;
+            self.clock.sleep(duration).await;
         } else {
             self.tick_overhead.take();
         }
@@ -259,7 +307,7 @@ impl Regulator {
 
     /// Indicate that a new transaction have been sent
     pub fn notify_send_transaction(&mut self) {
-        self.speed.time();
+        self.speed.time_event(self.now());
         self.current_concurrents_send += 1;
     }
 
@@ -277,7 +325,7 @@ impl Regulator {
 
     /// Getter of the current speed of transaction flow
     pub fn get_speed(&self) -> f64 {
-        self.speed.get_speed()
+        self.speed.get_speed_at(self.now())
     }
 }
 
@@ -296,16 +344,20 @@ impl Default for Regulator {
             concurent_notify: Notify::new(),
             current_concurrents_send: 0,
             tick_overhead: None,
+            clock: SystemClock,
         }
     }
 }
 
-impl fmt::Display for Regulator {
+impl<C> fmt::Display for Regulator<C>
+where
+    C: Clock,
+{
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         writeln!(
             f,
             " - Tps                            : {} / {}",
-            self.speed.get_speed(),
+            self.get_speed(),
             self.max_speed
         )?;
         writeln!(
@@ -324,7 +376,8 @@ impl fmt::Display for Regulator {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use tokio::time::timeout;
+    use prosa_utils::time::MockClock;
+    use tokio::time::{sleep, timeout};
 
     const TPS: f64 = 25.0;
 
@@ -412,4 +465,52 @@ mod tests {
         regulator.tick().await;
         assert!(initial_time.elapsed() >= Duration::from_millis(100));
     }
+
+    #[tokio::test]
+    async fn regulator_mock_clock() {
+        let clock = MockClock::new();
+        let mut regulator =
+            Regulator::new(TPS, Duration::from_secs(3), 1, 5).with_clock(clock.clone());
+        let real_start = Instant::now();
+
+        // Tick the regulator, advancing the clock by steps once it waits
+        async fn tick(regulator: &mut Regulator<MockClock>, clock: &MockClock, steps: &[Duration]) {
+            tokio::join!(regulator.tick(), async {
+                for step in steps {
+                    while clock.waiters() == 0 {
+                        tokio::task::yield_now().await;
+                    }
+                    clock.advance(*step);
+                }
+            });
+            assert_eq!(0, clock.waiters());
+        }
+
+        for _ in 1..=5 {
+            regulator.notify_send_transaction();
+            regulator.notify_receive_transaction(Duration::from_millis(10));
+            clock.advance(Duration::from_millis(40));
+        }
+        assert_eq!(TPS, regulator.get_speed());
+
+        // At the expected rate, the regulator don't wait
+        tick(&mut regulator, &clock, &[]).await;
+        regulator.notify_send_transaction();
+        regulator.notify_receive_transaction(Duration::from_millis(10));
+
+        // The next transaction must wait 40 ms to keep the rate
+        tick(&mut regulator, &clock, &[Duration::from_millis(40)]).await;
+        regulator.notify_send_transaction();
+
+        // A slow response add an overhead of 1 second to the next transaction
+        regulator.notify_receive_transaction(Duration::from_secs(4));
+        tick(
+            &mut regulator,
+            &clock,
+            &[Duration::from_secs(1), Duration::from_millis(40)],
+        )
+        .await;
+
+        assert!(real_start.elapsed() < Duration::from_secs(1));
+    }
 }
@@ -0,0 +1,423 @@
+use std::{fmt, time::Duration};
+
+use opentelemetry::trace::TraceId;
+use prosa_utils::{
+    config::telemetry::current_trace_id,
+    msg::tvf::{Tvf, TvfError},
+};
+
+/// Number of linear sub buckets for every power of two of the recorded values (~3% precision)
+const SUB_BUCKETS: u64 = 32;
+/// Values under this limit have their own bucket
+const LINEAR_LIMIT: u64 = 2 * SUB_BUCKETS;
+/// Total number of buckets to cover the whole `u64` range
+const BUCKETS: usize = (LINEAR_LIMIT + (64 - 6) * SUB_BUCKETS) as usize;
+
+/// Name of the service that publish the statistics of a processor
+///
+/// ```
+/// use prosa::event::stats::stats_service_name;
+///
+/// assert_eq!("_stats.INJ_PROC", stats_service_name("INJ_PROC"));
+/// ```
+pub fn stats_service_name(proc_name: &str) -> String {
+    format!("_stats.{}", proc_name)
+}
+
+/// Recorder of durations in a log-linear (HDR style) histogram, to get their percentiles with a bounded memory
+///
+/// Durations are recorded with a microsecond resolution, and a relative precision of ~3%.
+/// The trace id of the maximal duration is kept as exemplar, if it was recorded in a sampled span.
+///
+/// ```
+/// use std::time::Duration;
+/// use prosa::event::stats::StatsRecorder;
+///
+/// let mut recorder = StatsRecorder::default();
+/// for ms in 1..=100 {
+///     recorder.record(Duration::from_millis(ms));
+/// }
+///
+/// let snapshot = recorder.snapshot();
+/// assert_eq!(100, snapshot.count);
+/// assert_eq!(Duration::from_millis(1), snapshot.min);
+/// assert_eq!(Duration::from_millis(100), snapshot.max);
+/// assert!((49..=51).contains(&snapshot.p50.as_millis()));
+/// assert!((97..=100).contains(&snapshot.p99.as_millis()));
+/// ```
+#[derive(Clone)]
+pub struct StatsRecorder {
+    buckets: Vec<u64>,
+    count: u64,
+    sum: u128,
+    min: u64,
+    max: u64,
+    max_trace_id: Option<TraceId>,
+}
+
+impl StatsRecorder {
+    /// Index of the bucket of a value
+    fn bucket_index(value: u64) -> usize {
+        if value < LINEAR_LIMIT {
+            value as usize
+        } else {
+            let exp = 63 - value.leading_zeros() as u64;
+            let sub_bucket = (value >> (exp - 5)) - SUB_BUCKETS;
+            (LINEAR_LIMIT + (exp - 6) * SUB_BUCKETS + sub_bucket) as usize
+        }
+    }
+
+    /// Highest value of a bucket
+    fn bucket_value(index: usize) -> u64 {
+        let index = index as u64;
+        if index < LINEAR_LIMIT {
+            index
+        } else {
+            let exp = (index - LINEAR_LIMIT) / SUB_BUCKETS + 6;
+            let sub_bucket = (index - LINEAR_LIMIT) % SUB_BUCKETS + SUB_BUCKETS;
+            (sub_bucket << (exp - 5)).saturating_add((1 << (exp - 5)) - 1)
+        }
+    }
+
+    /// Method to record a duration
+    pub fn record(&mut self, duration: Duration) {
+        let value = u64::try_from(duration.as_micros()).unwrap_or(u64::MAX);
+        self.buckets[Self::bucket_index(value)] += 1;
+        self.count += 1;
+        self.sum += value as u128;
+        self.min = self.min.min(value);
+        if value > self.max {
+            self.max = value;
+            self.max_trace_id = current_trace_id();
+        }
+    }
+
+    /// Getter of the number of recorded durations
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Getter of the duration under which `percentile` percent of the recorded durations are
+    pub fn percentile(&self, percentile: f64) -> Duration {
+        if self.count == 0 {
+            return Duration::ZERO;
+        }
+
+        let rank = ((percentile / 100.0 * self.count as f64).ceil() as u64).clamp(1, self.count);
+        let mut seen = 0;
+        for (index, count) in self.buckets.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                return Duration::from_micros(Self::bucket_value(index).clamp(self.min, self.max));
+            }
+        }
+
+        Duration::from_micros(self.max)
+    }
+
+    /// Method to get a snapshot of the recorded durations
+    pub fn snapshot(&self) -> StatsSnapshot {
+        if self.count == 0 {
+            return StatsSnapshot::default();
+        }
+
+        StatsSnapshot {
+            count: self.count,
+            min: Duration::from_micros(self.min),
+            max: Duration::from_micros(self.max),
+            mean: Duration::from_micros((self.sum / self.count as u128) as u64),
+            p50: self.percentile(50.0),
+            p90: self.percentile(90.0),
+            p99: self.percentile(99.0),
+            max_trace_id: self.max_trace_id,
+        }
+    }
+
+    /// Method to clear all the recorded durations
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+impl Default for StatsRecorder {
+    fn default() -> Self {
+        StatsRecorder {
+            buckets: vec![0; BUCKETS],
+            count: 0,
+            sum: 0,
+            min: u64::MAX,
+            max: 0,
+            max_trace_id: None,
+        }
+    }
+}
+
+impl fmt::Debug for StatsRecorder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StatsRecorder")
+            .field("snapshot", &self.snapshot())
+            .finish()
+    }
+}
+
+/// Snapshot of the durations of a [`StatsRecorder`]
+///
+/// In a TVF, the snapshot is encoded with the following unsigned fields (durations in microseconds):
+///
+/// | Id | Field |
+/// |----|-------|
+/// | 1  | count |
+/// | 2  | min   |
+/// | 3  | max   |
+/// | 4  | mean  |
+/// | 5  | p50   |
+/// | 6  | p90   |
+/// | 7  | p99   |
+///
+/// The trace id of the maximal duration (if any) is encoded as an hexadecimal string in the field 8.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct StatsSnapshot {
+    /// Number of recorded durations
+    pub count: u64,
+    /// Minimal duration
+    pub min: Duration,
+    /// Maximal duration
+    pub max: Duration,
+    /// Mean duration
+    pub mean: Duration,
+    /// Median duration
+    pub p50: Duration,
+    /// 90th percentile duration
+    pub p90: Duration,
+    /// 99th percentile duration
+    pub p99: Duration,
+    /// Trace id of the transaction with the maximal duration, if it was traced
+    pub max_trace_id: Option<TraceId>,
+}
+
+impl StatsSnapshot {
+    /// Method to encode the snapshot in a TVF
+    pub fn to_tvf<M>(&self) -> M
+    where
+        M: Tvf + Default,
+    {
+        let mut tvf = M::default();
+        tvf.put_unsigned(1, self.count);
+        for (id, duration) in [
+            (2, self.min),
+            (3, self.max),
+            (4, self.mean),
+            (5, self.p50),
+            (6, self.p90),
+            (7, self.p99),
+        ] {
+            tvf.put_unsigned(id, duration.as_micros() as u64);
+        }
+        if let Some(trace_id) = self.max_trace_id {
+            tvf.put_string(8, trace_id.to_string());
+        }
+        tvf
+    }
+
+    /// Method to decode a snapshot from a TVF
+    pub fn from_tvf<M>(tvf: &M) -> Result<StatsSnapshot, TvfError>
+    where
+        M: Tvf,
+    {
+        let duration = |id| tvf.get_unsigned(id).map(Duration::from_micros);
+        Ok(StatsSnapshot {
+            count: tvf.get_unsigned(1)?,
+            min: duration(2)?,
+            max: duration(3)?,
+            mean: duration(4)?,
+            p50: duration(5)?,
+            p90: duration(6)?,
+            p99: duration(7)?,
+            max_trace_id: tvf
+                .get_string(8)
+                .ok()
+                .and_then(|trace_id| TraceId::from_hex(&trace_id).ok()),
+        })
+    }
+}
+
+/// Processing statistics of a processor, published on its stats service (see [`stats_service_name`])
+///
+/// In a TVF, the statistics are encoded with the following fields:
+///
+/// | Id | Type     | Field                                  |
+/// |----|----------|----------------------------------------|
+/// | 1  | unsigned | sent                                   |
+/// | 2  | unsigned | received                               |
+/// | 3  | unsigned | errors                                 |
+/// | 4  | unsigned | timeouts                               |
+/// | 5  | unsigned | validation_failures                    |
+/// | 6  | unsigned | watchdog_violations                    |
+/// | 10 | buffer   | latency (see [`StatsSnapshot`])        |
+///
+/// ```
+/// use std::time::Duration;
+/// use prosa::event::stats::ProcStats;
+/// use prosa_utils::msg::simple_string_tvf::SimpleStringTvf;
+///
+/// let mut stats = ProcStats::default();
+/// stats.sent += 1;
+/// stats.received += 1;
+/// stats.latency.record(Duration::from_millis(5));
+///
+/// let tvf: SimpleStringTvf = stats.to_tvf();
+/// let summary = ProcStats::summary_from_tvf(&tvf).unwrap();
+/// assert_eq!(stats.summary(), summary);
+/// assert_eq!(1, summary.latency.count);
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct ProcStats {
+    /// Number of transactions sent (requests for an injector, responses for a stub)
+    pub sent: u64,
+    /// Number of transactions received (responses for an injector, requests for a stub)
+    pub received: u64,
+    /// Number of transactions in error
+    pub errors: u64,
+    /// Number of transactions in timeout (included in the errors)
+    pub timeouts: u64,
+    /// Number of responses that failed the adaptor validation
+    pub validation_failures: u64,
+    /// Number of adaptor invocations reported by the processor watchdog
+    pub watchdog_violations: u64,
+    /// Latency of the transactions
+    pub latency: StatsRecorder,
+}
+
+/// Summary of the processing statistics of a processor, with a snapshot of its latency
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct StatsSummary {
+    /// Number of transactions sent
+    pub sent: u64,
+    /// Number of transactions received
+    pub received: u64,
+    /// Number of transactions in error
+    pub errors: u64,
+    /// Number of transactions in timeout
+    pub timeouts: u64,
+    /// Number of responses that failed the adaptor validation
+    pub validation_failures: u64,
+    /// Number of adaptor invocations reported by the processor watchdog
+    pub watchdog_violations: u64,
+    /// Snapshot of the transactions latency
+    pub latency: StatsSnapshot,
+}
+
+impl ProcStats {
+    /// Method to get the summary of the statistics
+    pub fn summary(&self) -> StatsSummary {
+        StatsSummary {
+            sent: self.sent,
+            received: self.received,
+            errors: self.errors,
+            timeouts: self.timeouts,
+            validation_failures: self.validation_failures,
+            watchdog_violations: self.watchdog_violations,
+            latency: self.latency.snapshot(),
+        }
+    }
+
+    /// Method to encode the statistics in a TVF, as response of the stats service
+    pub fn to_tvf<M>(&self) -> M
+    where
+        M: Tvf + Default + Clone + fmt::Debug,
+    {
+        let mut tvf = M::default();
+        tvf.put_unsigned(1, self.sent);
+        tvf.put_unsigned(2, self.received);
+        tvf.put_unsigned(3, self.errors);
+        tvf.put_unsigned(4, self.timeouts);
+        tvf.put_unsigned(5, self.validation_failures);
+        tvf.put_unsigned(6, self.watchdog_violations);
+        tvf.put_buffer(10, self.latency.snapshot().to_tvf());
+        tvf
+    }
+
+    /// Method to decode the statistics summary from a stats service response
+    pub fn summary_from_tvf<M>(tvf: &M) -> Result<StatsSummary, TvfError>
+    where
+        M: Tvf + Default + Clone + fmt::Debug,
+    {
+        Ok(StatsSummary {
+            sent: tvf.get_unsigned(1)?,
+            received: tvf.get_unsigned(2)?,
+            errors: tvf.get_unsigned(3)?,
+            timeouts: tvf.get_unsigned(4)?,
+            validation_failures: tvf.get_unsigned(5)?,
+            watchdog_violations: tvf.get_unsigned(6)?,
+            latency: StatsSnapshot::from_tvf(tvf.get_buffer(10)?.as_ref())?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stats_recorder_buckets() {
+        // Every value is in a bucket whose highest value is close to it
+        for value in (0..100_000u64).chain([u32::MAX as u64, u64::MAX / 3, u64::MAX]) {
+            let index = StatsRecorder::bucket_index(value);
+            assert!(index < BUCKETS);
+            let bucket_value = StatsRecorder::bucket_value(index);
+            assert!(bucket_value >= value, "{bucket_value} < {value}");
+            assert!(
+                (bucket_value - value) as f64 <= value as f64 / SUB_BUCKETS as f64,
+                "{bucket_value} too far from {value}"
+            );
+        }
+
+        let mut recorder = StatsRecorder::default();
+        assert_eq!(StatsSnapshot::default(), recorder.snapshot());
+        recorder.record(Duration::from_micros(10));
+        for _ in 0..98 {
+            recorder.record(Duration::from_millis(10));
+        }
+        recorder.record(Duration::from_secs(10));
+
+        let snapshot = recorder.snapshot();
+        assert_eq!(100, snapshot.count);
+        assert_eq!(Duration::from_micros(10), snapshot.min);
+        assert_eq!(Duration::from_secs(10), snapshot.max);
+        assert!((10_000..10_320).contains(&snapshot.p50.as_micros()));
+        assert!((10_000..10_320).contains(&snapshot.p99.as_micros()));
+        assert_eq!(Duration::from_secs(10), recorder.percentile(100.0));
+        assert_eq!(Duration::from_micros(10), recorder.percentile(0.0));
+
+        recorder.reset();
+        assert_eq!(0, recorder.count());
+    }
+
+    #[test]
+    fn stats_recorder_exemplar() {
+        use opentelemetry::trace::TracerProvider as _;
+        use prosa_utils::msg::simple_string_tvf::SimpleStringTvf;
+        use tracing_subscriber::layer::SubscriberExt as _;
+
+        let mut recorder = StatsRecorder::default();
+        recorder.record(Duration::from_millis(1));
+        assert_eq!(None, recorder.snapshot().max_trace_id);
+
+        // The slowest traced transaction is kept as exemplar
+        let subscriber = tracing_subscriber::registry().with(
+            tracing_opentelemetry::layer()
+                .with_tracer(opentelemetry_sdk::trace::TracerProvider::default().tracer("test")),
+        );
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info_span!("prosa_test_slow")
+                .in_scope(|| recorder.record(Duration::from_millis(50)));
+            tracing::info_span!("prosa_test_fast")
+                .in_scope(|| recorder.record(Duration::from_millis(2)));
+        });
+
+        let snapshot = recorder.snapshot();
+        assert!(snapshot.max_trace_id.is_some());
+        let tvf: SimpleStringTvf = snapshot.to_tvf();
+        assert_eq!(snapshot, StatsSnapshot::from_tvf(&tvf).unwrap());
+    }
+}
@@ -13,3 +13,7 @@ pub mod proc;
 #[doc = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/doc_assets/adaptor.svg"))]
 /// </svg>
 pub mod adaptor;
+
+/// Deterministic injector to measure the bus throughput (benchmarks)
+#[cfg(feature = "bench-api")]
+pub mod bench;
@@ -1,6 +1,13 @@
 use std::error::Error;
 
-use crate::core::adaptor::Adaptor;
+use opentelemetry::KeyValue;
+use tracing::warn;
+
+use crate::core::{
+    adaptor::{Adaptor, PipelineAdaptor, TransformRegistry},
+    proc::ProcSettings as _,
+    service::ServiceError,
+};
 
 use super::proc::InjProc;
 
@@ -56,6 +63,25 @@ where
         Self: Sized;
     /// Method to build a transaction to inject
     fn build_transaction(&mut self) -> M;
+    /// Method to keep what the validation needs of an injected request until its response or its timeout (e.g. only the fields compared by [`InjAdaptor::validate_response`])
+    /// The kept request is given to [`InjAdaptor::span_attributes`] and [`InjAdaptor::validate_response`], and dead-lettered if the transaction fails
+    /// By default nothing is kept, so the requests are not validated
+    fn keep_request(&self, _request: &M) -> Option<M> {
+        None
+    }
+    /// Method to give custom attributes to the span of a transaction, given the kept request (e.g. a test scenario identifier)
+    /// The span covers the processing of the response or the error by the adaptor
+    /// These attributes are built for every transaction while the traces are enabled, so keep them cheap; only the first [`MAX_SPAN_ATTRIBUTES`](crate::core::adaptor::MAX_SPAN_ATTRIBUTES) are kept
+    /// By default no attribute is added
+    fn span_attributes(&self, _request: &M) -> Vec<KeyValue> {
+        Vec::new()
+    }
+    /// Method to validate the response of an injected request (as kept by [`InjAdaptor::keep_request`]) against the expected one
+    /// If the response is invalid, the reason is returned and counted in the `prosa_inj_validation_failures` metric
+    /// By default all responses are valid
+    fn validate_response(&self, _request: &M, _response: &M) -> Result<(), String> {
+        Ok(())
+    }
     /// Method to process transaction response of the injection (to check the return code for example)
     /// if an error is trigger, the injection and the processor will stop
     /// By default response are ignored
@@ -66,6 +92,31 @@ where
     ) -> Result<(), Box<dyn Error>> {
         Ok(())
     }
+    /// Method to process transaction response of the warm-up phase (see `warmup_count` in the inj settings)
+    /// Warm-up responses are not given to [`InjAdaptor::process_response`] and are excluded from the metrics, so they don't need to be recorded
+    /// By default warm-up responses are ignored
+    fn process_warmup_response(
+        &mut self,
+        _response: &M,
+        _service_name: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+    /// Method to process service error of the injection (the service error is decoded from the returned message if any)
+    /// if an error is trigger, the injection and the processor will stop
+    /// By default retryable errors are ignored, and other errors stop the injection
+    fn process_error(
+        &mut self,
+        _response: &M,
+        _service_name: &str,
+        err: &ServiceError,
+    ) -> Result<(), Box<dyn Error>> {
+        if err.is_retryable() {
+            Ok(())
+        } else {
+            Err(Box::new(err.clone()))
+        }
+    }
 }
 
 /// Dummy adaptor for the inj processor. Use to send a very basic message with _DUMMY_ in it.
@@ -93,3 +144,92 @@ where
         msg
     }
 }
+
+impl<A, M> InjAdaptor<M> for PipelineAdaptor<A, M>
+where
+    A: InjAdaptor<M>,
+    M: 'static
+        + std::marker::Send
+        + std::marker::Sync
+        + std::marker::Sized
+        + std::clone::Clone
+        + std::fmt::Debug
+        + prosa_utils::msg::tvf::Tvf
+        + std::default::Default,
+{
+    fn new(proc: &InjProc<M>) -> Result<Self, Box<dyn Error>> {
+        let pipeline = TransformRegistry::default().build(proc.settings.get_pipeline())?;
+        Ok(PipelineAdaptor::with_pipeline(A::new(proc)?, pipeline))
+    }
+
+    fn build_transaction(&mut self) -> M {
+        let transaction = self.adaptor.build_transaction();
+        if self.pipeline.is_empty() {
+            return transaction;
+        }
+
+        // The transaction is sent as built if the pipeline can't transform it
+        self.pipeline
+            .on_request(transaction.clone())
+            .unwrap_or_else(|e| {
+                warn!(name: "inj_adaptor", target: "prosa::inj::adaptor", "Can't transform the transaction: {}", e);
+                transaction
+            })
+    }
+
+    fn validate_response(&self, request: &M, response: &M) -> Result<(), String> {
+        let response = self
+            .pipeline
+            .on_response(response.clone())
+            .map_err(|e| e.to_string())?;
+        self.adaptor.validate_response(request, &response)
+    }
+
+    fn process_response(&mut self, response: &M, service_name: &str) -> Result<(), Box<dyn Error>> {
+        match self.pipeline.on_response(response.clone()) {
+            Ok(response) => self.adaptor.process_response(&response, service_name),
+            Err(e) => self.adaptor.process_error(response, service_name, &e),
+        }
+    }
+
+    fn process_warmup_response(
+        &mut self,
+        response: &M,
+        service_name: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        match self.pipeline.on_response(response.clone()) {
+            Ok(response) => self
+                .adaptor
+                .process_warmup_response(&response, service_name),
+            Err(e) => self.adaptor.process_error(response, service_name, &e),
+        }
+    }
+
+    fn process_error(
+        &mut self,
+        response: &M,
+        service_name: &str,
+        err: &ServiceError,
+    ) -> Result<(), Box<dyn Error>> {
+        self.adaptor.process_error(response, service_name, err)
+    }
+
+    /// The transformed request is brought back to the tag space of the inner adaptor with the response transforms, like the responses given to [`InjAdaptor::validate_response`]
+    fn keep_request(&self, request: &M) -> Option<M> {
+        if self.pipeline.is_empty() {
+            return self.adaptor.keep_request(request);
+        }
+
+        match self.pipeline.on_response(request.clone()) {
+            Ok(request) => self.adaptor.keep_request(&request),
+            Err(e) => {
+                warn!(name: "inj_adaptor", target: "prosa::inj::adaptor", "Can't keep the transaction to validate its response: {}", e);
+                None
+            }
+        }
+    }
+
+    fn span_attributes(&self, request: &M) -> Vec<KeyValue> {
+        self.adaptor.span_attributes(request)
+    }
+}
@@ -0,0 +1,213 @@
+use std::time::{Duration, Instant};
+
+use prosa_macros::proc;
+
+use crate::core::{
+    error::BusError,
+    msg::InternalMsg,
+    service::{BatchSettings, RequestBatcher},
+};
+
+extern crate self as prosa;
+
+/// Result of a benchmark injection
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct BenchReport {
+    /// Number of response received
+    pub responses: u64,
+    /// Number of error received
+    pub errors: u64,
+    /// Duration of the whole injection
+    pub elapsed: Duration,
+}
+
+impl BenchReport {
+    /// Getter of the number of transaction processed per second
+    pub fn tps(&self) -> f64 {
+        (self.responses + self.errors) as f64 / self.elapsed.as_secs_f64()
+    }
+}
+
+/// Deterministic injector processor that send a fixed count of transactions and await their completion.
+/// Unlike the [`InjProc`](crate::inj::proc::InjProc), it's not regulated by a speed, so it's use to measure the bus throughput.
+///
+/// ```
+/// use prosa::core::main::{MainProc, MainRunnable};
+/// use prosa::core::proc::{Proc, ProcConfig};
+/// use prosa::core::settings::settings;
+/// use prosa::inj::bench::BenchInjProc;
+/// use prosa::stub::adaptor::StubParotAdaptor;
+/// use prosa::stub::proc::{StubProc, StubSettings};
+/// use prosa_utils::msg::simple_string_tvf::SimpleStringTvf;
+/// use prosa_utils::msg::tvf::Tvf;
+/// use serde::Serialize;
+///
+/// #[settings]
+/// #[derive(Default, Debug, Serialize)]
+/// struct BenchSettings {}
+///
+/// let (bus, main) = MainProc::<SimpleStringTvf>::create(&BenchSettings::default());
+/// let main_task = main.run();
+///
+/// let stub_proc = StubProc::<SimpleStringTvf>::create(1, bus.clone(), StubSettings::new(vec![String::from("BENCH")]));
+/// Proc::<StubParotAdaptor>::run(stub_proc, String::from("STUB_PROC"));
+///
+/// let rt = tokio::runtime::Runtime::new().unwrap();
+/// let mut injector = BenchInjProc::<SimpleStringTvf>::create_raw(2, bus.clone());
+/// let mut transaction = SimpleStringTvf::default();
+/// transaction.put_string(1, "bench");
+/// let report = rt.block_on(async {
+///     injector.wait_service("BENCH", 1).await.unwrap();
+///     let report = injector.inject("BENCH", 100, 8, &transaction).await.unwrap();
+///     injector.stop().await.unwrap();
+///     report
+/// });
+/// assert_eq!(100, report.responses);
+///
+/// rt.block_on(bus.stop("Bench end".into())).unwrap();
+/// main_task.join().unwrap();
+/// ```
+#[proc]
+pub struct BenchInjProc {}
+
+#[proc]
+impl BenchInjProc {
+    /// Method to declare the injector on the bus and wait for the service to be available on `proc_count` processors
+    pub async fn wait_service(
+        &mut self,
+        service_name: &str,
+        proc_count: usize,
+    ) -> Result<(), BusError> {
+        self.proc.add_proc().await?;
+
+        let service_name = service_name.to_string();
+        while self.service.count_proc_service(&service_name) < proc_count.max(1) {
+            match self.internal_rx_queue.recv().await {
+                Some(InternalMsg::Service(table)) => self.service = table,
+                Some(_) => {}
+                None => {
+                    return Err(BusError::InternalQueueError(String::from(
+                        "the injector queue is closed",
+                    )))
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Method to inject `count` transactions to the service with `window` transactions in flight at most.
+    /// Return when every transaction got a response or an error
+    pub async fn inject(
+        &mut self,
+        service_name: &str,
+        count: u64,
+        window: usize,
+        transaction: &M,
+    ) -> Result<BenchReport, BusError> {
+        let service_name = service_name.to_string();
+        let mut report = BenchReport::default();
+        let mut msg_id: u64 = 0;
+        let begin = Instant::now();
+
+        while report.responses + report.errors < count {
+            // Fill the window
+            while msg_id < count && (msg_id - report.responses - report.errors) < window as u64 {
+                if let Some(service) = self.service.get_proc_service(&service_name, msg_id) {
+                    service
+                        .send_request(self.proc.new_request(
+                            msg_id,
+                            service_name.clone(),
+                            transaction.clone(),
+                        ))
+                        .await?;
+                    msg_id += 1;
+                } else {
+                    return Err(BusError::InternalQueueError(format!(
+                        "the service {} is not available",
+                        service_name
+                    )));
+                }
+            }
+
+            match self.internal_rx_queue.recv().await {
+                Some(InternalMsg::Response(_)) => report.responses += 1,
+                Some(InternalMsg::Error(_)) => report.errors += 1,
+                Some(InternalMsg::Service(table)) => self.service = table,
+                Some(_) => {}
+                None => {
+                    return Err(BusError::InternalQueueError(String::from(
+                        "the injector queue is closed",
+                    )))
+                }
+            }
+        }
+
+        report.elapsed = begin.elapsed();
+        Ok(report)
+    }
+
+    /// Method to inject `count` transactions like [`BenchInjProc::inject`], with requests sent in batches (see [`RequestBatcher`]).
+    /// Batches are sent when they are full or when their oldest request waited the batch maximum latency
+    pub async fn inject_batched(
+        &mut self,
+        service_name: &str,
+        count: u64,
+        window: usize,
+        batch_settings: BatchSettings,
+        transaction: &M,
+    ) -> Result<BenchReport, BusError> {
+        let service_name = service_name.to_string();
+        let mut batcher = RequestBatcher::new(batch_settings);
+        let mut report = BenchReport::default();
+        let mut msg_id: u64 = 0;
+        let begin = Instant::now();
+
+        while report.responses + report.errors < count {
+            // Fill the window
+            while msg_id < count && (msg_id - report.responses - report.errors) < window as u64 {
+                if let Some(service) = self.service.get_proc_service(&service_name, msg_id) {
+                    batcher
+                        .push(
+                            service,
+                            self.proc.new_request(
+                                msg_id,
+                                service_name.clone(),
+                                transaction.clone(),
+                            ),
+                        )
+                        .await?;
+                    msg_id += 1;
+                } else {
+                    return Err(BusError::InternalQueueError(format!(
+                        "the service {} is not available",
+                        service_name
+                    )));
+                }
+            }
+
+            tokio::select! {
+                msg = self.internal_rx_queue.recv() => match msg {
+                    Some(InternalMsg::Response(_)) => report.responses += 1,
+                    Some(InternalMsg::Error(_)) => report.errors += 1,
+                    Some(InternalMsg::Service(table)) => self.service = table,
+                    Some(_) => {}
+                    None => {
+                        return Err(BusError::InternalQueueError(String::from(
+                            "the injector queue is closed",
+                        )))
+                    }
+                },
+                _ = batcher.wait_flush(), if !batcher.is_empty() => batcher.flush_expired().await?,
+            }
+        }
+
+        report.elapsed = begin.elapsed();
+        Ok(report)
+    }
+
+    /// Method to remove the injector from the bus
+    pub async fn stop(&self) -> Result<(), BusError> {
+        self.proc.remove_proc().await
+    }
+}
@@ -1,29 +1,72 @@
-use std::time::Duration;
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant, SystemTime},
+};
 
-use opentelemetry::{metrics::Histogram, KeyValue};
+use opentelemetry::{
+    metrics::{Counter, Gauge},
+    KeyValue,
+};
 use prosa_macros::{proc, proc_settings};
+use prosa_utils::config::telemetry::ExemplarHistogram;
 use serde::{Deserialize, Serialize};
-use tracing::debug;
+use tracing::{debug, info, info_span, warn};
 
 use crate::{
     core::{
-        adaptor::Adaptor,
-        msg::{InternalMsg, Msg, RequestMsg},
-        proc::{Proc, ProcBusParam as _},
+        adaptor::{set_span_attributes, Adaptor, TransformSettings},
+        dead_letter::DeadLetter,
+        msg::{drop_unreturned, InternalMsg, Msg},
+        proc::{Proc, ProcBusParam as _, ProcError as _},
+        service::{ServiceError, ServiceName},
+        watchdog::Watchdog,
+    },
+    event::{
+        journal::{Journal, JournalEntry, JournalError, JournalSettings},
+        pending::Timers,
+        speed::Regulator,
+        stats::{stats_service_name, ProcStats},
     },
-    event::speed::Regulator,
 };
 
 use super::adaptor::InjAdaptor;
 
 extern crate self as prosa;
 
+/// Service targeted by the injector, with its weight in the injection distribution
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+pub struct InjTarget {
+    /// Service to inject to
+    pub service: ServiceName,
+    /// Weight of the service in the injection distribution
+    #[serde(default = "InjTarget::default_weight")]
+    pub weight: u32,
+}
+
+impl InjTarget {
+    fn default_weight() -> u32 {
+        1
+    }
+
+    /// Create a new Inj target
+    pub fn new(service: String, weight: u32) -> InjTarget {
+        InjTarget {
+            service: service.into(),
+            weight,
+        }
+    }
+}
+
 /// Inj settings for service and speed parameters
 #[proc_settings]
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct InjSettings {
-    /// Service to inject to
-    service_name: String,
+    /// Service to inject to (used if no targets are defined)
+    #[serde(default)]
+    service_name: ServiceName,
+    /// Services to inject to with their weights
+    #[serde(default)]
+    targets: Vec<InjTarget>,
     /// Max TPS speed
     #[serde(default = "InjSettings::default_max_speed")]
     max_speed: f64,
@@ -33,9 +76,37 @@ pub struct InjSettings {
     /// Max parallel transaction running at the same time
     #[serde(default = "InjSettings::default_max_concurrents_send")]
     max_concurrents_send: u32,
+    /// Maximum number of transactions in flight for a closed-loop injection (open-loop injection paced on the max speed if not set).
+    /// A new transaction is only sent once a response is received, or once an in-flight transaction timed out (after the transaction deadline or the timeout threshold)
+    #[serde(default)]
+    max_outstanding: Option<u32>,
+    /// Keep the max speed as an upper bound of the closed-loop injection rate
+    #[serde(default)]
+    closed_loop_max_speed: bool,
     /// Number of value keep to calculate the injection speed
     #[serde(default = "InjSettings::default_speed_interval")]
     speed_interval: u16,
+    /// End-to-end deadline of the injected transactions (no deadline if not set)
+    #[serde(default)]
+    transaction_deadline: Option<Duration>,
+    /// Number of warm-up transactions sent before the measured ones (excluded from the statistics)
+    #[serde(default)]
+    warmup_count: u64,
+    /// Number of measured transactions to send before the end of the injection (no limit if not set)
+    #[serde(default)]
+    max_transactions: Option<u64>,
+    /// Duration of the measured injection, after the warm-up (no limit if not set)
+    #[serde(default)]
+    max_duration: Option<Duration>,
+    /// Stop the whole ProSA once the injection is completed and all its measured transactions are settled (by a response, an error or a timeout)
+    #[serde(default)]
+    stop_prosa_on_completion: bool,
+    /// Journal of the injected transactions, to resume the injection after a restart (no journal if not set)
+    #[serde(default)]
+    journal: Option<JournalSettings>,
+    /// Transforms applied on the transactions and the responses by a [`crate::core::adaptor::PipelineAdaptor`]
+    #[serde(default)]
+    pipeline: Vec<TransformSettings>,
 }
 
 impl InjSettings {
@@ -58,7 +129,8 @@ impl InjSettings {
     /// Create a new Inj settings
     pub fn new(service_name: String) -> InjSettings {
         InjSettings {
-            service_name,
+            service_name: service_name.into(),
+            targets: Vec::new(),
             max_speed: InjSettings::default_max_speed(),
             timeout_threshold: InjSettings::default_timeout_threshold(),
             max_concurrents_send: InjSettings::default_max_concurrents_send(),
@@ -69,17 +141,103 @@ impl InjSettings {
 
     /// Setter of the service name to send the transaction to
     pub fn set_service_name(&mut self, service_name: String) {
-        self.service_name = service_name;
+        self.service_name = service_name.into();
+    }
+
+    /// Setter of the maximum speed of the injection (in TPS)
+    pub fn set_max_speed(&mut self, max_speed: f64) {
+        self.max_speed = max_speed;
+    }
+
+    /// Setter of the maximum number of transactions in flight, to inject in closed-loop
+    pub fn set_max_outstanding(&mut self, max_outstanding: u32) {
+        self.max_outstanding = Some(max_outstanding);
+    }
+
+    /// Setter to keep the max speed as an upper bound of the closed-loop injection rate
+    pub fn set_closed_loop_max_speed(&mut self, closed_loop_max_speed: bool) {
+        self.closed_loop_max_speed = closed_loop_max_speed;
+    }
+
+    /// Getter of the duration after which an in-flight transaction is considered as timed out
+    fn get_outstanding_timeout(&self) -> Duration {
+        self.transaction_deadline.unwrap_or(self.timeout_threshold)
+    }
+
+    /// Setter of the end-to-end deadline given to every injected transaction
+    pub fn set_transaction_deadline(&mut self, deadline: Duration) {
+        self.transaction_deadline = Some(deadline);
+    }
+
+    /// Setter of the number of warm-up transactions sent before the measured ones
+    pub fn set_warmup_count(&mut self, warmup_count: u64) {
+        self.warmup_count = warmup_count;
+    }
+
+    /// Setter of the number of measured transactions to send before the end of the injection
+    pub fn set_max_transactions(&mut self, max_transactions: u64) {
+        self.max_transactions = Some(max_transactions);
+    }
+
+    /// Setter of the duration of the measured injection
+    pub fn set_max_duration(&mut self, max_duration: Duration) {
+        self.max_duration = Some(max_duration);
+    }
+
+    /// Setter to stop the whole ProSA once the injection is completed
+    pub fn set_stop_prosa_on_completion(&mut self, stop_prosa_on_completion: bool) {
+        self.stop_prosa_on_completion = stop_prosa_on_completion;
+    }
+
+    /// Setter of the journal of the injected transactions
+    pub fn set_journal(&mut self, journal: JournalSettings) {
+        self.journal = Some(journal);
+    }
+
+    /// Setter of the transforms of the adaptor pipeline
+    pub fn set_pipeline(&mut self, pipeline: Vec<TransformSettings>) {
+        self.pipeline = pipeline;
+    }
+
+    /// Method to add a weighted service to inject to
+    pub fn add_target(&mut self, service: String, weight: u32) {
+        self.targets.push(InjTarget::new(service, weight));
+    }
+
+    /// Getter of the injection targets. If no targets are defined, the service name is the only target
+    pub fn get_targets(&self) -> Vec<InjTarget> {
+        if self.targets.is_empty() {
+            vec![InjTarget {
+                service: self.service_name.clone(),
+                weight: InjTarget::default_weight(),
+            }]
+        } else {
+            self.targets.clone()
+        }
     }
 
-    /// Getter of a regulator from the current settings
+    /// Getter of a regulator from the current settings.
+    /// For a closed-loop injection, the regulator is paced by the in-flight transactions (and the max speed only if it's kept as an upper bound)
     pub fn get_regulator(&self) -> Regulator {
-        Regulator::new(
-            self.max_speed,
-            self.timeout_threshold,
-            self.max_concurrents_send,
-            self.speed_interval,
-        )
+        if let Some(max_outstanding) = self.max_outstanding {
+            Regulator::new(
+                if self.closed_loop_max_speed {
+                    self.max_speed
+                } else {
+                    f64::INFINITY
+                },
+                self.timeout_threshold,
+                max_outstanding,
+                self.speed_interval,
+            )
+        } else {
+            Regulator::new(
+                self.max_speed,
+                self.timeout_threshold,
+                self.max_concurrents_send,
+                self.speed_interval,
+            )
+        }
     }
 }
 
@@ -88,14 +246,140 @@ impl Default for InjSettings {
     fn default() -> InjSettings {
         InjSettings {
             service_name: Default::default(),
+            targets: Vec::new(),
             max_speed: InjSettings::default_max_speed(),
             timeout_threshold: InjSettings::default_timeout_threshold(),
             max_concurrents_send: InjSettings::default_max_concurrents_send(),
             speed_interval: InjSettings::default_speed_interval(),
+            max_outstanding: None,
+            closed_loop_max_speed: false,
+            transaction_deadline: None,
+            warmup_count: 0,
+            max_transactions: None,
+            max_duration: None,
+            stop_prosa_on_completion: false,
+            journal: None,
+            pipeline: Vec::new(),
         }
     }
 }
 
+/// Smooth weighted round robin selection of the injection targets
+#[derive(Debug)]
+struct TargetSelector {
+    targets: Vec<(InjTarget, i64)>,
+}
+
+impl TargetSelector {
+    fn new(targets: Vec<InjTarget>) -> TargetSelector {
+        TargetSelector {
+            targets: targets
+                .into_iter()
+                .filter(|t| t.weight > 0)
+                .map(|t| (t, 0))
+                .collect(),
+        }
+    }
+
+    /// Method to know if all the targeted services are available
+    fn is_available<F>(&self, is_available: F) -> bool
+    where
+        F: Fn(&str) -> bool,
+    {
+        self.targets
+            .iter()
+            .all(|(t, _)| is_available(t.service.as_str()))
+    }
+
+    /// Method to select the next target among the available services
+    fn next<F>(&mut self, is_available: F) -> Option<String>
+    where
+        F: Fn(&str) -> bool,
+    {
+        let mut total_weight = 0;
+        let mut selected: Option<&mut (InjTarget, i64)> = None;
+        for target in self
+            .targets
+            .iter_mut()
+            .filter(|(t, _)| is_available(t.service.as_str()))
+        {
+            target.1 += target.0.weight as i64;
+            total_weight += target.0.weight as i64;
+            if selected.as_ref().is_none_or(|s| target.1 > s.1) {
+                selected = Some(target);
+            }
+        }
+
+        selected.map(|target| {
+            target.1 -= total_weight;
+            target.0.service.to_string()
+        })
+    }
+}
+
+/// Running state of the injection
+struct InjState<M>
+where
+    M: prosa_utils::msg::tvf::Tvf + std::default::Default + std::fmt::Debug + std::clone::Clone,
+{
+    regulator: Regulator,
+    selector: TargetSelector,
+    next_transaction: Option<M>,
+    /// In-flight transactions, with what the adaptor keeps of their request (see [`InjAdaptor::keep_request`])
+    pending_requests: HashMap<u64, Option<M>>,
+    /// Timeouts of the in-flight transactions, to evict the ones that never get a response
+    outstanding_timers: Timers<u64>,
+    journal: Option<Journal<M>>,
+    first_id: u64,
+    msg_id: u64,
+    /// Number of measured transactions settled by a response, an error or a timeout (warm-up transactions excluded)
+    settled: u64,
+    measure_start: Option<Instant>,
+    completed: bool,
+    stats: ProcStats,
+    watchdog: Option<Watchdog>,
+}
+
+impl<M> InjState<M>
+where
+    M: prosa_utils::msg::tvf::Tvf + std::default::Default + std::fmt::Debug + std::clone::Clone,
+{
+    /// Method to know if a transaction is part of the warm-up (transactions ids are sequential from the first id)
+    fn is_warmup(&self, id: u64, settings: &InjSettings) -> bool {
+        id.saturating_sub(self.first_id) < settings.warmup_count
+    }
+
+    /// Getter of the number of transactions sent since the start of the injection
+    fn sent(&self) -> u64 {
+        self.msg_id - self.first_id
+    }
+
+    /// Getter of the number of measured transactions sent, after the warm-up ones
+    fn measured(&self, settings: &InjSettings) -> u64 {
+        self.sent().saturating_sub(settings.warmup_count)
+    }
+
+    /// Method to know if all the transactions of the injection have been sent
+    fn is_finished(&self, settings: &InjSettings) -> bool {
+        settings
+            .max_transactions
+            .is_some_and(|max| self.measured(settings) >= max)
+            || settings
+                .max_duration
+                .zip(self.measure_start)
+                .is_some_and(|(max, start)| start.elapsed() >= max)
+    }
+}
+
+/// Meters of the inj processor
+#[derive(Debug)]
+struct InjMeters {
+    trans_duration: ExemplarHistogram<f64>,
+    validation_failures: Counter<u64>,
+    outstanding: Gauge<u64>,
+    journal_errors: Counter<u64>,
+}
+
 /// Inj processor to inject transactions
 ///
 /// ```
@@ -133,52 +417,188 @@ pub struct InjProc {}
 
 #[proc]
 impl InjProc {
+    /// Method to report a journal write error (e.g. a full disk) without stopping the injection, the transaction is only missing from the journal
+    fn journal_error(name: &str, meters: &InjMeters, error: JournalError) {
+        warn!(name: "journal_inj_proc", target: "prosa::inj::proc", proc_name = name, "Can't write the transaction in the journal: {}", error);
+        meters
+            .journal_errors
+            .add(1, &[KeyValue::new("proc", name.to_string())]);
+    }
+
     async fn process_internal<A>(
         &mut self,
         name: &str,
         msg: InternalMsg<M>,
         adaptor: &mut A,
-        regulator: &mut Regulator,
-        next_transaction: &mut Option<M>,
-        meter_trans_duration: &Histogram<f64>,
+        state: &mut InjState<M>,
+        meters: &InjMeters,
     ) -> Result<(), Box<dyn std::error::Error>>
     where
         A: Adaptor + InjAdaptor<M> + std::marker::Send + std::marker::Sync,
     {
         match msg {
+            InternalMsg::Request(msg) if *msg.get_service() == stats_service_name(name) => {
+                state.stats.watchdog_violations =
+                    state.watchdog.as_ref().map_or(0, Watchdog::violations);
+                drop_unreturned!(name, msg.return_to_sender(state.stats.to_tvf()).await)
+            }
             InternalMsg::Request(msg) => panic!(
                 "The inj processor {} receive a request {:?}",
                 self.get_proc_id(),
                 msg
             ),
+            InternalMsg::Response(msg) if !state.pending_requests.contains_key(&msg.get_id()) => {
+                debug!(name: "resp_inj_proc", target: "prosa::inj::proc", parent: msg.get_span(), proc_name = name, service = msg.get_service(), "Ignore the late response of a timed out transaction");
+            }
             InternalMsg::Response(msg) => {
                 let _enter_span = msg.enter_span();
-                meter_trans_duration.record(
-                    msg.elapsed().as_secs_f64(),
-                    &[
-                        KeyValue::new("proc", name.to_string()),
-                        KeyValue::new("service", msg.get_service().clone()),
-                    ],
-                );
+                if let Some(journal) = state.journal.as_mut() {
+                    if let Err(e) =
+                        journal.append_response(msg.get_id(), msg.get_service(), msg.get_data())
+                    {
+                        Self::journal_error(name, meters, e);
+                    }
+                }
+                if state.is_warmup(msg.get_id(), &self.settings) {
+                    debug!(name: "resp_inj_proc", target: "prosa::inj::proc", proc_name = name, service = msg.get_service(), response = format!("{:?}", msg.get_data()), "Warm-up response");
+                    state.pending_requests.remove(&msg.get_id());
+                    adaptor.process_warmup_response(msg.get_data(), msg.get_service())?;
+                    state.regulator.notify_receive_transaction(msg.elapsed());
+                    let _ = state
+                        .next_transaction
+                        .get_or_insert(adaptor.build_transaction());
+                    return self.check_completion(name, state).await;
+                }
+
+                state.settled += 1;
+                state.stats.received += 1;
+                // Recorded in the transaction span, to correlate the latency with its trace
+                msg.get_span().in_scope(|| {
+                    state.stats.latency.record(msg.elapsed());
+                    meters.trans_duration.record(
+                        msg.elapsed().as_secs_f64(),
+                        &[
+                            KeyValue::new("proc", name.to_string()),
+                            KeyValue::new("service", msg.get_service().clone()),
+                        ],
+                    );
+                });
 
                 debug!(name: "resp_inj_proc", target: "prosa::inj::proc", proc_name = name, service = msg.get_service(), response = format!("{:?}", msg.get_data()));
-                adaptor.process_response(msg.get_data(), msg.get_service())?;
+                let request = state.pending_requests.remove(&msg.get_id()).flatten();
+                let trans_span = info_span!(parent: msg.get_span(), "prosa::inj::transaction", proc_name = name, service = msg.get_service());
+                if let Some(request) = &request {
+                    set_span_attributes(&trans_span, || adaptor.span_attributes(request));
+                }
+                let watchdog_guard = state
+                    .watchdog
+                    .as_ref()
+                    .map(|watchdog| watchdog.guard(msg.get_service()));
+                trans_span.in_scope(|| {
+                    if let Some(request) = &request {
+                        if let Err(reason) = adaptor.validate_response(request, msg.get_data()) {
+                            state.stats.validation_failures += 1;
+                            meters.validation_failures.add(
+                                1,
+                                &[
+                                    KeyValue::new("proc", name.to_string()),
+                                    KeyValue::new("service", msg.get_service().clone()),
+                                ],
+                            );
+                            debug!(name: "validation_inj_proc", target: "prosa::inj::proc", proc_name = name, service = msg.get_service(), reason = reason, "Invalid response");
+                        }
+                    }
 
-                regulator.notify_receive_transaction(msg.elapsed());
+                    adaptor.process_response(msg.get_data(), msg.get_service())
+                })?;
+                drop(watchdog_guard);
+
+                state.regulator.notify_receive_transaction(msg.elapsed());
 
                 // Build the next transaction
-                let _ = next_transaction.get_or_insert(adaptor.build_transaction());
+                let _ = state
+                    .next_transaction
+                    .get_or_insert(adaptor.build_transaction());
+                self.check_completion(name, state).await?;
+            }
+            InternalMsg::Error(err) if !state.pending_requests.contains_key(&err.get_id()) => {
+                debug!(name: "err_inj_proc", target: "prosa::inj::proc", parent: err.get_span(), proc_name = name, service = err.get_service(), "Ignore the late error of a timed out transaction");
+            }
+            InternalMsg::Error(err) => {
+                let _enter_span = err.enter_span();
+                warn!(name: "err_inj_proc", target: "prosa::inj::proc", proc_name = name, service = err.get_service(), error = err.get_err().to_string());
+                if let Some(journal) = state.journal.as_mut() {
+                    if let Err(e) =
+                        journal.append_response(err.get_id(), err.get_service(), err.get_data())
+                    {
+                        Self::journal_error(name, meters, e);
+                    }
+                }
+                let request = state.pending_requests.remove(&err.get_id()).flatten();
+                let service_err =
+                    ServiceError::decode(err.get_data())?.unwrap_or_else(|| err.get_err().clone());
+                if !service_err.recoverable() {
+                    self.proc
+                        .dead_letter(
+                            DeadLetter::new(
+                                self.get_proc_id(),
+                                err.get_service().clone(),
+                                request.clone().unwrap_or_else(|| err.get_data().clone()),
+                                service_err.clone(),
+                            )
+                            .with_proc_name(name),
+                        )
+                        .await;
+                }
+                if !state.is_warmup(err.get_id(), &self.settings) {
+                    state.settled += 1;
+                    state.stats.errors += 1;
+                    if matches!(service_err, ServiceError::Timeout(..)) {
+                        state.stats.timeouts += 1;
+                    }
+                }
+                let trans_span = info_span!(parent: err.get_span(), "prosa::inj::transaction", proc_name = name, service = err.get_service());
+                if let Some(request) = &request {
+                    set_span_attributes(&trans_span, || adaptor.span_attributes(request));
+                }
+                let watchdog_guard = state
+                    .watchdog
+                    .as_ref()
+                    .map(|watchdog| watchdog.guard(err.get_service()));
+                trans_span.in_scope(|| {
+                    adaptor.process_error(err.get_data(), err.get_service(), &service_err)
+                })?;
+                drop(watchdog_guard);
+
+                state.regulator.notify_receive_transaction(err.elapsed());
+
+                // Build the next transaction
+                let _ = state
+                    .next_transaction
+                    .get_or_insert(adaptor.build_transaction());
+                self.check_completion(name, state).await?;
             }
-            InternalMsg::Error(err) => panic!(
-                "The inj processor {} receive an error {:?}",
-                self.get_proc_id(),
-                err
-            ),
             InternalMsg::Command(_) => todo!(),
-            InternalMsg::Config => todo!(),
+            // Batches are split by the processor queue
+            InternalMsg::RequestBatch(_) | InternalMsg::ResponseBatch(_) => {}
+            InternalMsg::Ping(_) => {}
+            InternalMsg::Config(config) => {
+                // Reload the injection settings to adjust the regulator and the targets
+                match self.reload_settings(&config, name) {
+                    Ok(()) => {
+                        state.regulator = self.settings.get_regulator();
+                        state.selector = TargetSelector::new(self.settings.get_targets());
+                        self.reload_adaptor(adaptor, name);
+                    }
+                    Err(e) => {
+                        warn!(name: "config_inj_proc", target: "prosa::inj::proc", proc_name = name, "Can't reload the settings: {}", e)
+                    }
+                }
+            }
             InternalMsg::Service(table) => self.service = table,
             InternalMsg::Shutdown => {
-                adaptor.terminate();
+                self.proc.set_stopping().await?;
+                adaptor.async_terminate().await;
                 self.proc.remove_proc().await?;
                 return Ok(());
             }
@@ -186,6 +606,122 @@ impl InjProc {
 
         Ok(())
     }
+
+    /// Method to end the injection once all its measured transactions are sent and settled
+    async fn check_completion(
+        &self,
+        name: &str,
+        state: &mut InjState<M>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        if !state.completed
+            && state.is_finished(&self.settings)
+            && state.settled == state.measured(&self.settings)
+        {
+            state.completed = true;
+            let measured = state.measured(&self.settings);
+            let reason = format!(
+                "Injection of {} completed: {} transactions sent after {} warm-up transactions in {:?}",
+                name,
+                measured,
+                state.sent() - measured,
+                state
+                    .measure_start
+                    .map(|start| start.elapsed())
+                    .unwrap_or_default()
+            );
+            info!(name: "end_inj_proc", target: "prosa::inj::proc", proc_name = name, "{}", reason);
+            if self.settings.stop_prosa_on_completion {
+                self.proc.stop_prosa(reason).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Method to time out an in-flight transaction without response, to evict it and send a new transaction in its place
+    async fn expire_transaction(
+        &self,
+        name: &str,
+        msg_id: u64,
+        state: &mut InjState<M>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        if state.pending_requests.remove(&msg_id).is_some() {
+            warn!(name: "timeout_inj_proc", target: "prosa::inj::proc", proc_name = name, "Transaction {} timed out without response", msg_id);
+            if !state.is_warmup(msg_id, &self.settings) {
+                state.settled += 1;
+                state.stats.errors += 1;
+                state.stats.timeouts += 1;
+            }
+
+            state
+                .regulator
+                .notify_receive_transaction(self.settings.get_outstanding_timeout());
+            self.check_completion(name, state).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Method to send a transaction to the next available target
+    async fn send_transaction<A>(
+        &self,
+        name: &str,
+        adaptor: &mut A,
+        state: &mut InjState<M>,
+        meters: &InjMeters,
+    ) -> Result<(), Box<dyn std::error::Error>>
+    where
+        A: Adaptor + InjAdaptor<M> + std::marker::Send + std::marker::Sync,
+    {
+        if state.is_finished(&self.settings) {
+            return self.check_completion(name, state).await;
+        }
+
+        if let Some(service_name) = state
+            .selector
+            .next(|service| self.service.exist_proc_service(service))
+        {
+            if let Some(service) = self.service.get_proc_service(&service_name, state.msg_id) {
+                let transaction = state
+                    .next_transaction
+                    .take()
+                    .unwrap_or_else(|| adaptor.build_transaction());
+                if let Some(journal) = state.journal.as_mut() {
+                    if let Err(e) =
+                        journal.append_request(state.msg_id, &service_name, &transaction)
+                    {
+                        Self::journal_error(name, meters, e);
+                    }
+                }
+                state
+                    .pending_requests
+                    .insert(state.msg_id, adaptor.keep_request(&transaction));
+                state
+                    .outstanding_timers
+                    .push(state.msg_id, self.settings.get_outstanding_timeout());
+                let mut trans = self
+                    .proc
+                    .new_request(state.msg_id, service_name, transaction);
+                if let Some(deadline) = self.settings.transaction_deadline {
+                    trans = trans.with_deadline(SystemTime::now() + deadline);
+                }
+
+                debug!(name: "inj_proc", target: "prosa::inj::proc", parent: trans.get_span(), proc_name = name, service = trans.get_service(), request = format!("{:?}", trans.get_data()));
+                service.send_request(trans).await?;
+
+                if !state.is_warmup(state.msg_id, &self.settings) {
+                    state.stats.sent += 1;
+                    if state.measure_start.is_none() {
+                        state.measure_start = Some(Instant::now());
+                    }
+                }
+                state.msg_id += 1;
+                state.regulator.notify_send_transaction();
+            }
+        }
+
+        Ok(())
+    }
 }
 
 #[proc]
@@ -196,74 +732,567 @@ where
     async fn internal_run(&mut self, name: String) -> Result<(), Box<dyn std::error::Error>> {
         // Initiate an adaptor for the inj processor
         let mut adaptor = A::new(self)?;
+        adaptor.async_init(&self.proc).await?;
 
         // meter
         let meter = self.proc.meter(name.clone());
-        let meter_trans_duration = meter
-            .f64_histogram("prosa_inj_request_duration")
-            .with_description("inj transaction processing duration")
-            .with_unit("seconds")
-            .init();
+        let meters = InjMeters {
+            trans_duration: ExemplarHistogram::new(
+                "prosa_inj_request_duration",
+                meter
+                    .f64_histogram("prosa_inj_request_duration")
+                    .with_description("inj transaction processing duration")
+                    .with_unit("seconds")
+                    .init(),
+            ),
+            validation_failures: meter
+                .u64_counter("prosa_inj_validation_failures")
+                .with_description("inj responses that failed the adaptor validation")
+                .init(),
+            outstanding: meter
+                .u64_gauge("prosa_inj_outstanding")
+                .with_description("inj transactions in flight")
+                .init(),
+            journal_errors: meter
+                .u64_counter("prosa_inj_journal_errors")
+                .with_description("inj transactions that couldn't be written in the journal")
+                .init(),
+        };
 
-        // Declare the processor
+        // Declare the processor, with its statistics service
         self.proc.add_proc().await?;
+        self.proc
+            .add_service_proc(vec![stats_service_name(&name)])
+            .await?;
+
+        // Open the journal to resume the transactions sequence, and report the requests without response
+        let journal = if let Some(journal_settings) = &self.settings.journal {
+            let mut journal = journal_settings.open::<M>()?;
+            for entry in journal.unmatched() {
+                if let JournalEntry::Request(id, service, data) = entry {
+                    warn!(name: "journal_inj_proc", target: "prosa::inj::proc", proc_name = name, service = service, request = format!("{:?}", data), "Transaction {} without response before the restart", id);
+                }
+            }
+            journal.compact()?;
+            Some(journal)
+        } else {
+            None
+        };
+        let first_id = journal.as_ref().map(|j| j.next_id()).unwrap_or_default();
 
         // Create a message regulator
-        let mut regulator = self.settings.get_regulator();
-        let mut next_transaction = Some(adaptor.build_transaction());
-        let mut msg_id: u64 = 0;
+        let mut state = InjState {
+            regulator: self.settings.get_regulator(),
+            selector: TargetSelector::new(self.settings.get_targets()),
+            next_transaction: Some(adaptor.build_transaction()),
+            pending_requests: HashMap::new(),
+            outstanding_timers: Timers::default(),
+            journal,
+            first_id,
+            msg_id: first_id,
+            settled: 0,
+            measure_start: None,
+            completed: false,
+            stats: ProcStats::default(),
+            watchdog: self.proc.start_watchdog(&name),
+        };
+
+        // The adaptor and the journal are initialized, the processor is ready to inject
+        self.proc.set_ready().await?;
 
         // Wait for service table
-        while !self.service.exist_proc_service(&self.settings.service_name) {
+        while !state
+            .selector
+            .is_available(|service| self.service.exist_proc_service(service))
+        {
             if let Some(msg) = self.internal_rx_queue.recv().await {
-                self.process_internal(
-                    name.as_str(),
-                    msg,
-                    &mut adaptor,
-                    &mut regulator,
-                    &mut next_transaction,
-                    &meter_trans_duration,
-                )
-                .await?;
+                self.process_internal(name.as_str(), msg, &mut adaptor, &mut state, &meters)
+                    .await?;
             }
         }
 
         // Send first transaction
-        self.service
-            .get_proc_service(&self.settings.service_name, msg_id)
-            .unwrap()
-            .proc_queue
-            .send(InternalMsg::Request(RequestMsg::new(
-                msg_id,
-                self.settings.service_name.clone(),
-                next_transaction.take().unwrap(),
-                self.proc.get_service_queue(),
-            )))
+        self.send_transaction(name.as_str(), &mut adaptor, &mut state, &meters)
             .await?;
-        msg_id += 1;
-        regulator.notify_send_transaction();
 
         loop {
+            // Once the injection is finished, only the responses are processed
+            let finished = state.is_finished(&self.settings);
             tokio::select! {
                 Some(msg) = self.internal_rx_queue.recv() => {
-                    self.process_internal(name.as_str(), msg, &mut adaptor, &mut regulator, &mut next_transaction, &meter_trans_duration).await?;
+                    self.process_internal(name.as_str(), msg, &mut adaptor, &mut state, &meters).await?;
                 }
-                _ = regulator.tick() => {
-                    if let Some(service) = self.service.get_proc_service(&self.settings.service_name, msg_id) {
-                        let trans = if let Some(transaction) = next_transaction.take() {
-                            RequestMsg::new(msg_id, self.settings.service_name.clone(), transaction, self.proc.get_service_queue())
-                        } else {
-                            RequestMsg::new(msg_id, self.settings.service_name.clone(), adaptor.build_transaction(), self.proc.get_service_queue())
-                        };
-
-                        debug!(name: "inj_proc", target: "prosa::inj::proc", parent: trans.get_span(), proc_name = name, service = self.settings.service_name, request = format!("{:?}", trans.get_data()));
-                        service.proc_queue.send(InternalMsg::Request(trans)).await?;
-
-                        msg_id += 1;
-                        regulator.notify_send_transaction();
-                    }
+                _ = state.regulator.tick(), if !finished => {
+                    self.send_transaction(name.as_str(), &mut adaptor, &mut state, &meters).await?;
+                },
+                Some(msg_id) = state.outstanding_timers.pull(), if !state.outstanding_timers.is_empty() => {
+                    self.expire_transaction(name.as_str(), msg_id, &mut state).await?;
                 },
             };
+
+            meters.outstanding.record(
+                state.pending_requests.len() as u64,
+                &[KeyValue::new("proc", name.clone())],
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        error::Error,
+        sync::atomic::{AtomicU32, Ordering},
+    };
+
+    use prosa_macros::{settings, Adaptor};
+    use prosa_utils::msg::{simple_string_tvf::SimpleStringTvf, tvf::Tvf as _};
+
+    use super::*;
+    use crate::{
+        core::{
+            adaptor::{Pipeline, PipelineAdaptor, TagRename},
+            main::{MainProc, MainRunnable as _},
+            proc::ProcConfig as _,
+        },
+        stub::{
+            adaptor::StubAdaptor,
+            proc::{StubProc, StubSettings},
+        },
+        testing::ProsaTestKit,
+    };
+
+    const ECHO_SERVICE: &str = "INJ_ECHO";
+    const ALTER_SERVICE: &str = "INJ_ALTER";
+    static ECHO_COUNTER: AtomicU32 = AtomicU32::new(0);
+    static ALTER_COUNTER: AtomicU32 = AtomicU32::new(0);
+    static VALIDATION_FAILURES: AtomicU32 = AtomicU32::new(0);
+    const BENCH_SERVICE: &str = "INJ_BENCH";
+    static BENCH_COUNTER: AtomicU32 = AtomicU32::new(0);
+    static BENCH_MEASURED: AtomicU32 = AtomicU32::new(0);
+    static BENCH_WARMUP: AtomicU32 = AtomicU32::new(0);
+    const JOURNAL_SERVICE: &str = "INJ_JOURNAL";
+    const STATS_SERVICE: &str = "INJ_STATS";
+    const CLOSED_LOOP_SERVICE: &str = "INJ_CLOSED_LOOP";
+    static STATS_COUNTER: AtomicU32 = AtomicU32::new(0);
+
+    #[settings]
+    #[derive(Default, Debug, Serialize)]
+    struct TestSettings {
+        stub: StubSettings,
+        inj: InjSettings,
+    }
+
+    #[derive(Adaptor)]
+    struct TestStubAdaptor {}
+
+    impl<M> StubAdaptor<M> for TestStubAdaptor
+    where
+        M: 'static
+            + std::marker::Send
+            + std::marker::Sync
+            + std::marker::Sized
+            + std::clone::Clone
+            + std::fmt::Debug
+            + prosa_utils::msg::tvf::Tvf
+            + std::default::Default,
+    {
+        fn new(_proc: &StubProc<M>) -> Result<Self, Box<dyn Error>> {
+            Ok(Self {})
+        }
+
+        fn process_request(&mut self, service_name: &str, request: &M) -> Result<M, ServiceError> {
+            let mut response = request.clone();
+            if service_name == ALTER_SERVICE {
+                ALTER_COUNTER.fetch_add(1, Ordering::Relaxed);
+                response.put_string(1, "ALTERED");
+            } else if service_name == BENCH_SERVICE {
+                BENCH_COUNTER.fetch_add(1, Ordering::Relaxed);
+            } else if service_name == JOURNAL_SERVICE {
+                response.put_unsigned(2, 1);
+            } else if service_name == STATS_SERVICE {
+                STATS_COUNTER.fetch_add(1, Ordering::Relaxed);
+            } else {
+                ECHO_COUNTER.fetch_add(1, Ordering::Relaxed);
+            }
+
+            Ok(response)
+        }
+    }
+
+    #[derive(Adaptor)]
+    struct TestInjAdaptor {}
+
+    impl<M> InjAdaptor<M> for TestInjAdaptor
+    where
+        M: 'static
+            + std::marker::Send
+            + std::marker::Sync
+            + std::marker::Sized
+            + std::clone::Clone
+            + std::fmt::Debug
+            + prosa_utils::msg::tvf::Tvf
+            + std::default::Default,
+    {
+        fn new(_proc: &InjProc<M>) -> Result<Self, Box<dyn Error>> {
+            Ok(Self {})
+        }
+
+        fn build_transaction(&mut self) -> M {
+            let mut msg = M::default();
+            msg.put_string(1, "INJ");
+            msg
+        }
+
+        fn keep_request(&self, request: &M) -> Option<M> {
+            Some(request.clone())
+        }
+
+        fn validate_response(&self, request: &M, response: &M) -> Result<(), String> {
+            if request.get_string(1) == response.get_string(1) {
+                Ok(())
+            } else {
+                VALIDATION_FAILURES.fetch_add(1, Ordering::Relaxed);
+                Err(format!("unexpected response {:?}", response))
+            }
+        }
+
+        fn process_response(
+            &mut self,
+            _response: &M,
+            service_name: &str,
+        ) -> Result<(), Box<dyn Error>> {
+            if service_name == BENCH_SERVICE {
+                BENCH_MEASURED.fetch_add(1, Ordering::Relaxed);
+            }
+            Ok(())
+        }
+
+        fn process_warmup_response(
+            &mut self,
+            _response: &M,
+            service_name: &str,
+        ) -> Result<(), Box<dyn Error>> {
+            if service_name == BENCH_SERVICE {
+                BENCH_WARMUP.fetch_add(1, Ordering::Relaxed);
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn inj_pipeline_validation() {
+        let mut adaptor = PipelineAdaptor::with_pipeline(
+            TestInjAdaptor {},
+            Pipeline::<SimpleStringTvf>::new()
+                .with(TagRename::new(std::collections::BTreeMap::from([(1, 10)]))),
+        );
+        let transaction = adaptor.build_transaction();
+        assert_eq!("INJ", transaction.get_string(10).unwrap().as_str());
+
+        // The echoed response is validated against the request in the tag space of the inner adaptor
+        let request = adaptor.keep_request(&transaction).unwrap();
+        assert_eq!("INJ", request.get_string(1).unwrap().as_str());
+        assert_eq!(Ok(()), adaptor.validate_response(&request, &transaction));
+    }
+
+    #[test]
+    fn target_selector() {
+        let mut settings = InjSettings::new(ECHO_SERVICE.into());
+        assert_eq!(
+            vec![InjTarget::new(ECHO_SERVICE.into(), 1)],
+            settings.get_targets()
+        );
+
+        settings.add_target(ECHO_SERVICE.into(), 3);
+        settings.add_target(ALTER_SERVICE.into(), 1);
+        let mut selector = TargetSelector::new(settings.get_targets());
+        assert!(selector.is_available(|_| true));
+        assert!(!selector.is_available(|s| s == ECHO_SERVICE));
+
+        let selected: Vec<String> = (0..8).filter_map(|_| selector.next(|_| true)).collect();
+        assert_eq!(
+            6,
+            selected.iter().filter(|s| *s == ECHO_SERVICE).count(),
+            "Wrong distribution {:?}",
+            selected
+        );
+
+        // Only available services are selected
+        assert!(
+            (0..4).all(|_| selector.next(|s| s == ALTER_SERVICE).as_deref() == Some(ALTER_SERVICE))
+        );
+        assert_eq!(None, selector.next(|_| false));
+    }
+
+    #[tokio::test]
+    async fn inj_multi_target() {
+        let mut test_settings = TestSettings {
+            stub: StubSettings::new(vec![ECHO_SERVICE.into(), ALTER_SERVICE.into()]),
+            ..Default::default()
+        };
+        test_settings.inj.add_target(ECHO_SERVICE.into(), 3);
+        test_settings.inj.add_target(ALTER_SERVICE.into(), 1);
+        test_settings.inj.max_speed = 100.0;
+
+        // Create bus and main processor
+        let (bus, main) = MainProc::<SimpleStringTvf>::create(&test_settings);
+        let main_task = main.run();
+
+        let stub_proc = StubProc::<SimpleStringTvf>::create(1, bus.clone(), test_settings.stub);
+        Proc::<TestStubAdaptor>::run(stub_proc, String::from("STUB_PROC"));
+
+        let inj_proc = InjProc::<SimpleStringTvf>::create(2, bus.clone(), test_settings.inj);
+        Proc::<TestInjAdaptor>::run(inj_proc, String::from("INJ_PROC"));
+
+        tokio::time::sleep(Duration::from_secs(2)).await;
+        bus.stop("ProSA unit test end".into()).await.unwrap();
+        main_task.join().unwrap();
+
+        let echo = ECHO_COUNTER.load(Ordering::Relaxed);
+        let alter = ALTER_COUNTER.load(Ordering::Relaxed);
+        assert!(echo + alter > 20, "Not enough transactions {echo}/{alter}");
+        assert!(
+            echo.abs_diff(alter * 3) <= 3,
+            "Distribution {echo}/{alter} is not 3:1"
+        );
+
+        // Only the altered responses fail the validation (the last one may not be received)
+        let failures = VALIDATION_FAILURES.load(Ordering::Relaxed);
+        assert!(
+            failures == alter || failures + 1 == alter,
+            "{failures} validation failures for {alter} altered responses"
+        );
+    }
+
+    #[tokio::test]
+    async fn inj_warmup_and_stop() {
+        let mut test_settings = TestSettings {
+            stub: StubSettings::new(vec![BENCH_SERVICE.into()]),
+            inj: InjSettings::new(BENCH_SERVICE.into()),
+            ..Default::default()
+        };
+        test_settings.inj.max_speed = 200.0;
+        test_settings.inj.set_warmup_count(10);
+        test_settings.inj.set_max_transactions(50);
+        test_settings.inj.set_stop_prosa_on_completion(true);
+
+        // Create bus and main processor
+        let (bus, main) = MainProc::<SimpleStringTvf>::create(&test_settings);
+        let main_task = main.run();
+
+        let stub_proc = StubProc::<SimpleStringTvf>::create(1, bus.clone(), test_settings.stub);
+        Proc::<TestStubAdaptor>::run(stub_proc, String::from("STUB_PROC"));
+
+        let inj_proc = InjProc::<SimpleStringTvf>::create(2, bus.clone(), test_settings.inj);
+        Proc::<TestInjAdaptor>::run(inj_proc, String::from("INJ_PROC"));
+
+        // The ProSA should stop by itself at the end of the injection
+        tokio::time::timeout(
+            Duration::from_secs(10),
+            tokio::task::spawn_blocking(move || main_task.join()),
+        )
+        .await
+        .expect("The ProSA didn't stop at the end of the injection")
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(60, BENCH_COUNTER.load(Ordering::Relaxed));
+        assert_eq!(10, BENCH_WARMUP.load(Ordering::Relaxed));
+        assert_eq!(50, BENCH_MEASURED.load(Ordering::Relaxed));
+    }
+
+    #[tokio::test]
+    async fn inj_stub_stats() {
+        let mut kit = ProsaTestKit::<SimpleStringTvf>::new(&TestSettings::default()).await;
+        let stub = kit.create_proc::<StubProc<_>>(StubSettings::new(vec![STATS_SERVICE.into()]));
+        kit.spawn_proc::<_, TestStubAdaptor>(stub, "STUB_PROC");
+
+        let mut inj_settings = InjSettings::new(STATS_SERVICE.into());
+        inj_settings.max_speed = 200.0;
+        inj_settings.set_warmup_count(5);
+        inj_settings.set_max_transactions(20);
+        let inj = kit.create_proc::<InjProc<_>>(inj_settings);
+        kit.spawn_proc::<_, TestInjAdaptor>(inj, "INJ_PROC");
+
+        // Wait the end of the injection
+        let inj_stats = tokio::time::timeout(Duration::from_secs(10), async {
+            loop {
+                let stats = kit
+                    .fetch_stats("INJ_PROC", Duration::from_secs(1))
+                    .await
+                    .unwrap();
+                if stats.received == 20 {
+                    return stats;
+                }
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+        })
+        .await
+        .expect("The injection didn't end");
+
+        // Warm-up transactions are excluded from the injector statistics
+        assert_eq!(20, inj_stats.sent);
+        assert_eq!(0, inj_stats.errors);
+        assert_eq!(0, inj_stats.timeouts);
+        assert_eq!(0, inj_stats.validation_failures);
+        assert_eq!(20, inj_stats.latency.count);
+        assert!(inj_stats.latency.min <= inj_stats.latency.p50);
+        assert!(inj_stats.latency.p50 <= inj_stats.latency.p99);
+        assert!(inj_stats.latency.p99 <= inj_stats.latency.max);
+
+        // The stub count all the transactions it processed
+        let stub_stats = kit
+            .fetch_stats("STUB_PROC", Duration::from_secs(1))
+            .await
+            .unwrap();
+        assert_eq!(25, STATS_COUNTER.load(Ordering::Relaxed));
+        assert_eq!(25, stub_stats.received);
+        assert_eq!(25, stub_stats.sent);
+        assert_eq!(0, stub_stats.errors);
+        assert_eq!(25, stub_stats.latency.count);
+
+        kit.stop().await;
+    }
+
+    #[tokio::test]
+    async fn inj_closed_loop() {
+        const RESPONSE_DELAY: Duration = Duration::from_millis(100);
+        const MEASURE_DURATION: Duration = Duration::from_secs(2);
+
+        // The closed-loop injection rate only depends on the response time (5 outstanding / 100 ms = 50 TPS), not on the configured speed
+        for max_speed in [5.0, 1000.0] {
+            let kit = ProsaTestKit::<SimpleStringTvf>::new(&TestSettings::default()).await;
+            let mut service = kit.fake_service(CLOSED_LOOP_SERVICE).await;
+
+            let mut inj_settings = InjSettings::new(CLOSED_LOOP_SERVICE.into());
+            inj_settings.max_speed = max_speed;
+            inj_settings.set_max_outstanding(5);
+            let inj = kit.create_proc::<InjProc<_>>(inj_settings);
+            kit.spawn_proc::<_, TestInjAdaptor>(inj, "INJ_PROC");
+
+            // Respond to every request after a delay
+            let outstanding = std::sync::Arc::new(AtomicU32::new(0));
+            let mut max_outstanding = 0;
+            let mut count = 0;
+            let mut measure_end = None;
+            loop {
+                let request = if let Some(end) = measure_end {
+                    match tokio::time::timeout_at(end, service.expect_request(MEASURE_DURATION))
+                        .await
+                    {
+                        Ok(request) => request,
+                        Err(_) => break,
+                    }
+                } else {
+                    let request = service.expect_request(Duration::from_secs(5)).await;
+                    measure_end = Some(tokio::time::Instant::now() + MEASURE_DURATION);
+                    request
+                }
+                .expect("The injector doesn't send requests");
+
+                count += 1;
+                max_outstanding =
+                    max_outstanding.max(outstanding.fetch_add(1, Ordering::Relaxed) + 1);
+                let outstanding = outstanding.clone();
+                tokio::spawn(async move {
+                    tokio::time::sleep(RESPONSE_DELAY).await;
+                    outstanding.fetch_sub(1, Ordering::Relaxed);
+                    let response = request.get_data().clone();
+                    let _ = request.return_to_sender(response).await;
+                });
+            }
+
+            assert!(
+                max_outstanding <= 5,
+                "{max_outstanding} transactions in flight for a maximum of 5"
+            );
+            let tps = count as f64 / MEASURE_DURATION.as_secs_f64();
+            assert!(
+                (40.0..=60.0).contains(&tps),
+                "Closed-loop injection at {tps} TPS instead of 50 TPS (max speed {max_speed})"
+            );
+
+            service.remove().await.unwrap();
+            kit.stop().await;
         }
     }
+
+    #[tokio::test]
+    async fn inj_journal_resume() {
+        let journal_path =
+            std::env::temp_dir().join(format!("prosa_test_inj_{}.journal", std::process::id()));
+        let _ = std::fs::remove_file(&journal_path);
+
+        // Crash of a previous injection with a request without response
+        let mut journal =
+            Journal::<SimpleStringTvf>::open(&journal_path, Default::default()).unwrap();
+        let mut request = SimpleStringTvf::default();
+        request.put_string(1, "INJ");
+        journal
+            .append_request(0, JOURNAL_SERVICE, &request)
+            .unwrap();
+        journal
+            .append_response(0, JOURNAL_SERVICE, &request)
+            .unwrap();
+        journal
+            .append_request(1, JOURNAL_SERVICE, &request)
+            .unwrap();
+        drop(journal);
+
+        let mut test_settings = TestSettings {
+            stub: StubSettings::new(vec![JOURNAL_SERVICE.into()]),
+            inj: InjSettings::new(JOURNAL_SERVICE.into()),
+            ..Default::default()
+        };
+        test_settings.inj.max_speed = 200.0;
+        test_settings.inj.set_max_transactions(20);
+        test_settings.inj.set_stop_prosa_on_completion(true);
+        test_settings
+            .inj
+            .set_journal(JournalSettings::new(journal_path.clone()));
+
+        // Create bus and main processor
+        let (bus, main) = MainProc::<SimpleStringTvf>::create(&test_settings);
+        let main_task = main.run();
+
+        let stub_proc = StubProc::<SimpleStringTvf>::create(1, bus.clone(), test_settings.stub);
+        Proc::<TestStubAdaptor>::run(stub_proc, String::from("STUB_PROC"));
+
+        let inj_proc = InjProc::<SimpleStringTvf>::create(2, bus.clone(), test_settings.inj);
+        Proc::<TestInjAdaptor>::run(inj_proc, String::from("INJ_PROC"));
+
+        tokio::time::timeout(
+            Duration::from_secs(10),
+            tokio::task::spawn_blocking(move || main_task.join()),
+        )
+        .await
+        .expect("The ProSA didn't stop at the end of the injection")
+        .unwrap()
+        .unwrap();
+
+        // The sequence is resumed after the journaled transactions, and only the crashed request stays unmatched
+        let journal = Journal::<SimpleStringTvf>::open(&journal_path, Default::default()).unwrap();
+        assert_eq!(22, journal.next_id());
+        assert_eq!(
+            vec![1],
+            journal
+                .unmatched()
+                .map(|e| e.get_id())
+                .collect::<Vec<u64>>()
+        );
+        let responses: Vec<JournalEntry<SimpleStringTvf>> = journal
+            .replay()
+            .unwrap()
+            .map(|e| e.unwrap())
+            .filter(|e| matches!(e, JournalEntry::Response(..)))
+            .collect();
+        assert_eq!(20, responses.len());
+        assert!(responses.iter().all(|e| match e {
+            JournalEntry::Response(id, _, data) => *id >= 2 && data.get_unsigned(2).is_ok(),
+            _ => false,
+        }));
+
+        std::fs::remove_file(&journal_path).unwrap();
+    }
 }
@@ -1,36 +1,123 @@
 //! Module that define IO that could be use by a ProSA processor
 use std::{
-    fmt,
+    fmt, io,
     net::{SocketAddrV4, SocketAddrV6},
     path::Path,
 };
 
+use opentelemetry::{
+    metrics::{Counter, Meter},
+    KeyValue,
+};
 pub use prosa_macros::io;
 use url::Url;
 
+pub mod bridge;
+pub mod dns;
+pub mod frame;
+pub mod http;
 pub mod listener;
+pub mod pool;
+pub mod server;
+pub mod socket;
 pub mod stream;
 
 /// Trait to define ProSA IO.
-/// Implement with the procedural macro io
+/// Implement with the procedural macro io (`#[io(length_prefixed = 4)]` to use a [`frame::LengthPrefixedCodec`])
 pub trait IO {
+    /// Frame object handled by the IO
+    type Frame;
     /// Frame error trigger when the frame operation can't be executed
     type Error;
 
     /// Method call to parse a frame
-    fn parse_frame<F>(&mut self) -> std::result::Result<Option<F>, Self::Error>;
+    fn parse_frame(&mut self) -> std::result::Result<Option<Self::Frame>, Self::Error>;
 
     /// Method to wait a complete frame
-    fn read_frame<F>(
+    fn read_frame(
         &mut self,
-    ) -> impl std::future::Future<Output = Result<Option<F>, Self::Error>> + Send;
+    ) -> impl std::future::Future<Output = Result<Option<Self::Frame>, Self::Error>> + Send;
     /// Method to write a frame and wait for completion
-    fn write_frame<F>(
+    fn write_frame(
         &mut self,
-        frame: F,
+        frame: Self::Frame,
     ) -> impl std::future::Future<Output = Result<(), Self::Error>> + Send;
 }
 
+/// Counters of the bytes exchanged by an IO (maintained by the procedural macro io)
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct IoStats {
+    read: u64,
+    written: u64,
+}
+
+impl IoStats {
+    /// Getter of the number of bytes read from the stream
+    pub fn read_bytes(&self) -> u64 {
+        self.read
+    }
+
+    /// Getter of the number of bytes written on the stream
+    pub fn written_bytes(&self) -> u64 {
+        self.written
+    }
+
+    /// Method to count bytes read from the stream
+    pub fn add_read(&mut self, len: usize) {
+        self.read += len as u64;
+    }
+
+    /// Method to count bytes written on the stream
+    pub fn add_written(&mut self, len: usize) {
+        self.written += len as u64;
+    }
+}
+
+impl fmt::Display for IoStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "read={}B written={}B", self.read, self.written)
+    }
+}
+
+/// Byte counters of an IO registered on a meter (used with `#[io(metrics)]`)
+///
+/// Counters are named after the IO: `prosa_<name>_read_bytes` and `prosa_<name>_written_bytes`.
+#[derive(Debug, Clone)]
+pub struct IoMeters {
+    read: Counter<u64>,
+    written: Counter<u64>,
+    attributes: Vec<KeyValue>,
+}
+
+impl IoMeters {
+    /// Method to register the byte counters of an IO on a meter
+    pub fn new(meter: &Meter, name: &str, attributes: Vec<KeyValue>) -> IoMeters {
+        IoMeters {
+            read: meter
+                .u64_counter(format!("prosa_{}_read_bytes", name))
+                .with_description(format!("bytes read by {}", name))
+                .with_unit("bytes")
+                .init(),
+            written: meter
+                .u64_counter(format!("prosa_{}_written_bytes", name))
+                .with_description(format!("bytes written by {}", name))
+                .with_unit("bytes")
+                .init(),
+            attributes,
+        }
+    }
+
+    /// Method to count bytes read from the stream
+    pub fn add_read(&self, len: usize) {
+        self.read.add(len as u64, &self.attributes);
+    }
+
+    /// Method to count bytes written on the stream
+    pub fn add_written(&self, len: usize) {
+        self.written.add(len as u64, &self.attributes);
+    }
+}
+
 /// Method to known if the url indicate an SSL protocol
 ///
 /// ```
@@ -49,12 +136,53 @@ pub fn url_is_ssl(url: &Url) -> bool {
     }
 }
 
-/// Internal Socket adress enum to define IPv4, IPv6 and unix socket.
+/// Method to get the path of the named pipe targeted by a `pipe://` url
+///
+/// `pipe:///name` target the local pipe `\\.\pipe\name`, and `pipe://server/name` the pipe `\\server\pipe\name` of a remote server.
+/// Return `None` if the url is not a named pipe url, or if it doesn't contain a pipe name.
+///
+/// ```
+/// use url::Url;
+/// use prosa::io::url_named_pipe;
+///
+/// assert_eq!(Some(String::from(r"\\.\pipe\prosa")), url_named_pipe(&Url::parse("pipe:///prosa").unwrap()));
+/// assert_eq!(Some(String::from(r"\\server\pipe\prosa")), url_named_pipe(&Url::parse("pipe://server/prosa").unwrap()));
+/// assert_eq!(None, url_named_pipe(&Url::parse("unix:///tmp/prosa.sock").unwrap()));
+/// ```
+pub fn url_named_pipe(url: &Url) -> Option<String> {
+    if url.scheme() != "pipe" {
+        return None;
+    }
+
+    let name = url.path().trim_start_matches('/');
+    if name.is_empty() {
+        return None;
+    }
+
+    let server = url
+        .host_str()
+        .filter(|host| !host.is_empty())
+        .unwrap_or(".");
+    Some(format!(r"\\{}\pipe\{}", server, name.replace('/', "\\")))
+}
+
+/// Error returned when an url target a stream that can't be handled on this platform (named pipe on unix, unix socket on windows, ...)
+pub(crate) fn unsupported_url(url: &Url) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!("{} is unsupported on this platform", url),
+    )
+}
+
+/// Internal Socket adress enum to define IPv4, IPv6, unix socket and named pipe.
 #[derive(Debug)]
 pub enum SocketAddr {
     #[cfg(target_family = "unix")]
     /// UNIX socket address
     Unix(tokio::net::unix::SocketAddr),
+    #[cfg(target_family = "windows")]
+    /// Named pipe path (only on windows systems)
+    NamedPipe(String),
     /// IPv4 address
     V4(SocketAddrV4),
     /// IPv6 address
@@ -68,16 +196,32 @@ impl SocketAddr {
         match self {
             #[cfg(target_family = "unix")]
             SocketAddr::Unix(_) => true,
+            #[cfg(target_family = "windows")]
+            SocketAddr::NamedPipe(path) => path.starts_with(r"\\.\"),
             SocketAddr::V4(ipv4) => ipv4.ip().is_loopback(),
             SocketAddr::V6(ipv6) => ipv6.ip().is_loopback(),
         }
     }
 
+    /// Returns the IP address associated with this socket address (`None` for Unix sockets and named pipes).
+    pub fn ip(&self) -> Option<std::net::IpAddr> {
+        match self {
+            #[cfg(target_family = "unix")]
+            SocketAddr::Unix(_) => None,
+            #[cfg(target_family = "windows")]
+            SocketAddr::NamedPipe(_) => None,
+            SocketAddr::V4(ipv4) => Some(std::net::IpAddr::V4(*ipv4.ip())),
+            SocketAddr::V6(ipv6) => Some(std::net::IpAddr::V6(*ipv6.ip())),
+        }
+    }
+
     /// Returns the port number associated with this socket address.
     pub const fn port(&self) -> u16 {
         match self {
             #[cfg(target_family = "unix")]
             SocketAddr::Unix(_) => 0u16,
+            #[cfg(target_family = "windows")]
+            SocketAddr::NamedPipe(_) => 0u16,
             SocketAddr::V4(ipv4) => ipv4.port(),
             SocketAddr::V6(ipv6) => ipv6.port(),
         }
@@ -88,6 +232,8 @@ impl SocketAddr {
         match self {
             #[cfg(target_family = "unix")]
             SocketAddr::Unix(_) => {}
+            #[cfg(target_family = "windows")]
+            SocketAddr::NamedPipe(_) => {}
             SocketAddr::V4(ipv4) => ipv4.set_port(port),
             SocketAddr::V6(ipv6) => ipv6.set_port(port),
         }
@@ -99,6 +245,8 @@ impl PartialEq for SocketAddr {
         match (self, other) {
             #[cfg(target_family = "unix")]
             (SocketAddr::Unix(s), SocketAddr::Unix(o)) => s.as_pathname() == o.as_pathname(),
+            #[cfg(target_family = "windows")]
+            (SocketAddr::NamedPipe(s), SocketAddr::NamedPipe(o)) => s == o,
             (SocketAddr::V4(s), SocketAddr::V4(o)) => s == o,
             (SocketAddr::V6(s), SocketAddr::V6(o)) => s == o,
             _ => false,
@@ -117,6 +265,8 @@ impl fmt::Display for SocketAddr {
                     .unwrap_or(Path::new("undefined"))
                     .display()
             ),
+            #[cfg(target_family = "windows")]
+            SocketAddr::NamedPipe(path) => write!(f, "{}", path),
             SocketAddr::V4(ipv4) => write!(f, "{}", ipv4),
             SocketAddr::V6(ipv6) => write!(f, "{}", ipv6),
         }
@@ -142,14 +292,19 @@ impl From<tokio::net::unix::SocketAddr> for SocketAddr {
 #[cfg(test)]
 mod tests {
     use futures_util::future;
-    use listener::{ListenerSetting, StreamListener};
+    use listener::{
+        AcceptPolicy, AcceptPolicyState, AcceptRejection, ListenerClosed, ListenerSetting,
+        MultiListener, StreamListener, StreamListenerKind,
+    };
     use openssl::ssl::SslVerifyMode;
     use prosa_utils::config::ssl::{SslConfig, Store};
-    use std::{env, os::fd::AsRawFd as _};
-    use stream::{Stream, TargetSetting};
+    #[cfg(target_family = "unix")]
+    use std::os::fd::AsRawFd as _;
+    use std::{env, time::Duration};
+    use stream::{copy_bidirectional_metrics, CopyOptions, CopySide, Stream, TargetSetting};
     use tokio::{
         fs::File,
-        io::{AsyncReadExt as _, AsyncWriteExt},
+        io::{self, AsyncReadExt as _, AsyncWriteExt},
     };
 
     use super::*;
@@ -158,7 +313,7 @@ mod tests {
     #[tokio::test]
     async fn unix_client_server() {
         let addr = "/tmp/prosa_unix_client_server_test.sock";
-        let listener = StreamListener::Unix(tokio::net::UnixListener::bind(addr).unwrap());
+        let listener = StreamListener::from(tokio::net::UnixListener::bind(addr).unwrap());
         assert!(listener.as_raw_fd() > 0);
         assert!(
             format!("{:?}", listener).contains("UnixListener"),
@@ -215,10 +370,100 @@ mod tests {
         std::fs::remove_file(addr).unwrap();
     }
 
+    #[test]
+    fn named_pipe_url() {
+        assert_eq!(
+            Some(String::from(r"\\.\pipe\prosa\test")),
+            url_named_pipe(&Url::parse("pipe:///prosa/test").unwrap())
+        );
+        assert_eq!(
+            Some(String::from(r"\\.\pipe\prosa")),
+            url_named_pipe(&Url::parse("pipe://./prosa").unwrap())
+        );
+        assert_eq!(
+            Some(String::from(r"\\server\pipe\prosa")),
+            url_named_pipe(&Url::parse("pipe://server/prosa").unwrap())
+        );
+        assert_eq!(None, url_named_pipe(&Url::parse("pipe://server").unwrap()));
+        assert_eq!(
+            None,
+            url_named_pipe(&Url::parse("tcp://localhost:4000").unwrap())
+        );
+    }
+
+    #[cfg(not(target_family = "windows"))]
+    #[tokio::test]
+    async fn named_pipe_unsupported() {
+        let url = Url::parse("pipe:///prosa_named_pipe_unsupported").unwrap();
+
+        let err = ListenerSetting::from(url.clone()).bind().await.unwrap_err();
+        assert_eq!(io::ErrorKind::Unsupported, err.kind());
+        assert_eq!(
+            "pipe:///prosa_named_pipe_unsupported is unsupported on this platform",
+            err.to_string()
+        );
+
+        let mut listener_setting = ListenerSetting::from(url.clone());
+        listener_setting.bind_all = true;
+        let err = listener_setting.bind_multi().await.unwrap_err();
+        assert_eq!(io::ErrorKind::Unsupported, err.kind());
+
+        let err = TargetSetting::from(url).connect().await.unwrap_err();
+        assert_eq!(io::ErrorKind::Unsupported, err.kind());
+    }
+
+    #[cfg(target_family = "windows")]
+    #[tokio::test]
+    async fn named_pipe_client_server() {
+        let url = Url::parse("pipe:///prosa_named_pipe_client_server").unwrap();
+        let addr = r"\\.\pipe\prosa_named_pipe_client_server";
+        let listener = ListenerSetting::from(url.clone()).bind().await.unwrap();
+        assert!(
+            format!("{:?}", listener).contains("NamedPipe"),
+            "listener `{:?}` don't contain NamedPipe",
+            listener
+        );
+        assert_eq!(format!("pipe://{}", addr), listener.to_string());
+
+        // Pipe names are unique
+        assert!(ListenerSetting::from(url.clone()).bind().await.is_err());
+
+        let server = async move {
+            for _ in 0..2 {
+                let (mut client_stream, client_addr) = listener.accept().await.unwrap();
+                assert!(client_addr.is_loopback());
+                assert_eq!(addr, client_addr.to_string());
+
+                let mut buf = [0; 5];
+                client_stream.read_exact(&mut buf).await.unwrap();
+                assert_eq!(&buf, b"ProSA");
+
+                client_stream.write_all(b"Worldline").await.unwrap();
+                client_stream.shutdown().await.unwrap();
+            }
+        };
+
+        let client = async {
+            for _ in 0..2 {
+                let mut stream = TargetSetting::from(url.clone()).connect().await.unwrap();
+                assert_eq!(format!("pipe://{}", addr), stream.to_string());
+
+                stream.write_all(b"ProSA").await.unwrap();
+
+                let mut buf = [0; 9];
+                stream.read_exact(&mut buf).await.unwrap();
+                assert_eq!(&buf, b"Worldline");
+            }
+        };
+
+        future::join(server, client).await;
+    }
+
     #[tokio::test]
     async fn tcp_client_server() {
         let addr = "localhost:41800";
         let listener = StreamListener::bind(addr).await.unwrap();
+        #[cfg(target_family = "unix")]
         assert!(listener.as_raw_fd() > 0);
         assert!(
             format!("{:?}", listener).contains("Tcp"),
@@ -248,6 +493,7 @@ mod tests {
 
         let client = async {
             let mut stream = Stream::connect_tcp(addr).await.unwrap();
+            #[cfg(target_family = "unix")]
             assert!(stream.as_raw_fd() > 0);
             assert!(
                 format!("{:?}", stream).contains("Tcp"),
@@ -260,6 +506,7 @@ mod tests {
                 stream
             );
             assert!(stream.to_string().starts_with("tcp://"));
+            assert!(stream.tls_info().is_none());
 
             stream.write_all(b"ProSA").await.unwrap();
 
@@ -273,6 +520,339 @@ mod tests {
         future::join(server, client).await;
     }
 
+    #[tokio::test]
+    async fn tcp_listener_close_accept() {
+        let listener = StreamListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        assert!(!listener.is_accept_closed());
+        assert!(listener.drain_deadline(Duration::from_secs(1)).is_none());
+
+        let mut client_stream = Stream::connect_tcp(&addr).await.unwrap();
+        let (mut server_stream, _) = listener.accept().await.unwrap();
+
+        // A pending accept is woken up by the close
+        let (pending_accept, _) = future::join(listener.accept(), async {
+            tokio::task::yield_now().await;
+            listener.close_accept();
+        })
+        .await;
+        let err = pending_accept.unwrap_err();
+        assert!(ListenerClosed::is(&err));
+        assert_eq!(io::ErrorKind::ConnectionAborted, err.kind());
+        assert!(listener.is_accept_closed());
+        assert!(listener.drain_deadline(Duration::from_secs(1)).is_some());
+
+        // Subsequent accepts return the closed error immediately
+        assert!(ListenerClosed::is(&listener.accept().await.unwrap_err()));
+        assert!(ListenerClosed::is(
+            &listener.accept_raw().await.unwrap_err()
+        ));
+
+        // The already accepted stream keep exchanging data
+        client_stream.write_all(b"ProSA").await.unwrap();
+        let mut buf = [0; 5];
+        server_stream.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"ProSA");
+
+        server_stream.write_all(b"Worldline").await.unwrap();
+        let mut buf = [0; 9];
+        client_stream.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"Worldline");
+    }
+
+    #[tokio::test]
+    async fn tcp_listener_accept_policy() {
+        let listener = StreamListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let mut listener_setting =
+            ListenerSetting::from(Url::parse(&format!("tcp://{}", addr)).unwrap());
+        listener_setting.accept_policy = Some(AcceptPolicy {
+            max_per_ip: Some(3),
+            ..Default::default()
+        });
+        let policy = listener_setting.accept_policy_state();
+
+        let mut guards = Vec::new();
+        let mut client_streams = Vec::new();
+        for _ in 0..3 {
+            client_streams.push(Stream::connect_tcp(&addr).await.unwrap());
+            let (_stream, client_addr, guard) = listener.accept_with_policy(&policy).await.unwrap();
+            assert!(client_addr.is_loopback());
+            guards.push(guard);
+        }
+        assert_eq!(3, policy.live());
+
+        // The client over the limit is closed immediately
+        let mut rejected_stream = Stream::connect_tcp(&addr).await.unwrap();
+        assert!(tokio::time::timeout(
+            Duration::from_millis(200),
+            listener.accept_with_policy(&policy)
+        )
+        .await
+        .is_err());
+        assert_eq!(1, policy.rejected());
+        let mut buf = Vec::new();
+        assert!(rejected_stream
+            .read_to_end(&mut buf)
+            .await
+            .is_ok_and(|len| len == 0));
+
+        // A dropped guard free the capacity for a new client
+        guards.pop();
+        assert_eq!(2, policy.live());
+        client_streams.push(Stream::connect_tcp(&addr).await.unwrap());
+        let (_stream, _, _guard) = listener.accept_with_policy(&policy).await.unwrap();
+        assert_eq!(3, policy.live());
+        assert_eq!(1, policy.rejected());
+    }
+
+    #[test]
+    fn accept_policy_ban_and_rate() {
+        let client_addr = SocketAddr::V4("10.0.0.1:4000".parse().unwrap());
+        let other_addr = SocketAddr::V4("10.0.0.2:4000".parse().unwrap());
+        let policy = AcceptPolicyState::new(
+            AcceptPolicy {
+                max_per_ip: Some(1),
+                max_rate: None,
+                max_violations: 2,
+                ban_duration: Some(Duration::from_secs(60)),
+            },
+            10,
+        );
+
+        let guard = policy.admit(&client_addr).unwrap();
+        assert_eq!(
+            AcceptRejection::MaxPerIp,
+            policy.admit(&client_addr).unwrap_err()
+        );
+        assert_eq!(
+            AcceptRejection::MaxPerIp,
+            policy.admit(&client_addr).unwrap_err()
+        );
+
+        // Banned after 2 violations, even once its connection is closed
+        drop(guard);
+        assert_eq!(
+            AcceptRejection::Banned,
+            policy.admit(&client_addr).unwrap_err()
+        );
+        let _other_guard = policy.admit(&other_addr).unwrap();
+        assert_eq!(3, policy.rejected());
+
+        // Accept rate and maximum number of connections
+        let policy = AcceptPolicyState::new(
+            AcceptPolicy {
+                max_rate: Some(2),
+                ..Default::default()
+            },
+            10,
+        );
+        let _guards = (0..2)
+            .map(|_| policy.admit(&client_addr).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            AcceptRejection::Rate,
+            policy.admit(&other_addr).unwrap_err()
+        );
+
+        // A saturated listener doesn't ban the rejected clients
+        let policy = AcceptPolicyState::new(
+            AcceptPolicy {
+                max_violations: 1,
+                ban_duration: Some(Duration::from_secs(60)),
+                ..Default::default()
+            },
+            1,
+        );
+        let guard = policy.admit(&client_addr).unwrap();
+        for _ in 0..3 {
+            assert_eq!(
+                AcceptRejection::MaxSocket,
+                policy.admit(&other_addr).unwrap_err()
+            );
+        }
+        drop(guard);
+        let _guard = policy.admit(&other_addr).unwrap();
+    }
+
+    #[tokio::test]
+    async fn tcp_listener_multi_addrs() {
+        // Every address resolved from localhost is bound on the same port
+        let mut listener_setting = ListenerSetting::from(Url::parse("tcp://localhost:0").unwrap());
+        listener_setting.bind_all = true;
+        let multi_listener = listener_setting.bind_multi().await.unwrap();
+        let local_addrs = multi_listener.local_addrs().unwrap();
+        assert!(!local_addrs.is_empty());
+        assert!(local_addrs
+            .iter()
+            .all(|addr| addr.port() == local_addrs[0].port()));
+
+        // Bind localhost on both families, and accept a client from each of them
+        let listener_v4 = StreamListener::bind_addr("127.0.0.1:0".parse().unwrap(), None).unwrap();
+        let port = listener_v4.local_addr().unwrap().port();
+        let Ok(listener_v6) =
+            StreamListener::bind_addr(format!("[::1]:{}", port).parse().unwrap(), None)
+        else {
+            // IPv6 is not available on the host
+            return;
+        };
+        let multi_listener = MultiListener::new(vec![listener_v4, listener_v6]);
+        assert_eq!(
+            format!("tcp://127.0.0.1:{port}, tcp://[::1]:{port}"),
+            multi_listener.to_string()
+        );
+
+        for addr in [format!("127.0.0.1:{}", port), format!("[::1]:{}", port)] {
+            let mut client_stream = Stream::connect_tcp(&addr).await.unwrap();
+            let (mut stream, client_addr) = multi_listener.accept().await.unwrap();
+            assert_eq!(client_stream.local_addr().unwrap().ip(), client_addr.ip());
+            client_stream.write_all(b"ping").await.unwrap();
+            let mut buf = [0u8; 4];
+            stream.read_exact(&mut buf).await.unwrap();
+            assert_eq!(b"ping", &buf);
+        }
+
+        multi_listener.close_accept();
+        assert!(multi_listener.is_accept_closed());
+        assert!(ListenerClosed::is(
+            &multi_listener.accept().await.unwrap_err()
+        ));
+
+        // The unspecified IPv6 address accept IPv4 clients in dual-stack
+        let mut listener_setting = ListenerSetting::from(Url::parse("tcp://[::]:0").unwrap());
+        listener_setting.bind_all = true;
+        let multi_listener = listener_setting.bind_multi().await.unwrap();
+        let port = multi_listener.local_addrs().unwrap()[0].port();
+        for addr in [format!("127.0.0.1:{}", port), format!("[::1]:{}", port)] {
+            let _client_stream = Stream::connect_tcp(&addr).await.unwrap();
+            assert!(multi_listener.accept().await.is_ok());
+        }
+    }
+
+    #[tokio::test]
+    async fn tcp_client_read_timeout() {
+        let addr = "localhost:41810";
+        let listener = StreamListener::bind(addr).await.unwrap();
+
+        let server = async move {
+            let (mut client_stream, _) = listener.accept().await.unwrap();
+
+            let mut buf = [0; 5];
+            client_stream.read_exact(&mut buf).await.unwrap();
+            assert_eq!(&buf, b"ProSA");
+
+            // Stop responding but keep the connection open
+            let _ = client_stream.read(&mut buf).await;
+        };
+
+        let mut target_settings = TargetSetting::from(Url::parse("tcp://localhost:41810").unwrap());
+        target_settings.read_timeout = Some(200);
+
+        let client = async {
+            let mut stream = target_settings.connect_timed().await.unwrap();
+            assert_eq!(Some(Duration::from_millis(200)), stream.read_timeout());
+            assert_eq!(None, stream.write_timeout());
+
+            stream.write_all(b"ProSA").await.unwrap();
+
+            let start = tokio::time::Instant::now();
+            let mut buf = [0; 9];
+            let err = stream.read_exact(&mut buf).await.unwrap_err();
+            assert_eq!(io::ErrorKind::TimedOut, err.kind());
+            assert!(start.elapsed() >= Duration::from_millis(200));
+
+            let _ = stream.shutdown().await;
+        };
+
+        future::join(server, client).await;
+    }
+
+    #[tokio::test]
+    async fn tcp_client_connect_timeout() {
+        use prosa_utils::time::{Clock as _, MockClock};
+
+        // Proxy that accept the connection but never answer
+        let listener = StreamListener::bind("localhost:41811").await.unwrap();
+        let proxy = async move {
+            let (mut client_stream, _) = listener.accept().await.unwrap();
+            let mut buf = [0; 64];
+            while client_stream.read(&mut buf).await.is_ok_and(|len| len > 0) {}
+        };
+
+        let target_settings = TargetSetting::new(
+            Url::parse("tcp://localhost:41812").unwrap(),
+            None,
+            Some(Url::parse("http://localhost:41811").unwrap()),
+        );
+        assert_eq!(5000, target_settings.connect_timeout);
+
+        // The 5 seconds connect timeout elapse without waiting for real
+        let clock = MockClock::new();
+        let client = async {
+            let start = clock.now();
+            let real_start = tokio::time::Instant::now();
+            let (connect, _) = future::join(target_settings.connect_with_clock(&clock), async {
+                while clock.waiters() == 0 {
+                    tokio::task::yield_now().await;
+                }
+                clock.advance(Duration::from_millis(5000));
+            })
+            .await;
+            let err = connect.unwrap_err();
+            assert_eq!(io::ErrorKind::TimedOut, err.kind());
+            assert_eq!(Duration::from_millis(5000), clock.now() - start);
+            assert!(real_start.elapsed() < Duration::from_secs(1));
+        };
+
+        tokio::select! {
+            _ = proxy => panic!("The proxy must not stop before the client"),
+            _ = client => {}
+        }
+    }
+
+    #[tokio::test]
+    async fn tcp_client_failover() {
+        // The target host resolve to a dead address, then to a live one
+        let dead_addr = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let live_addr = listener.local_addr().unwrap();
+        dns::DnsCache::global().insert(
+            "failover.prosa.invalid",
+            live_addr.port(),
+            vec![dead_addr, live_addr],
+            std::time::Instant::now() + Duration::from_secs(60),
+        );
+        let server = async move {
+            let (mut client_stream, _) = listener.accept().await.unwrap();
+            client_stream.write_all(b"ProSA").await.unwrap();
+        };
+
+        let mut target_settings = TargetSetting::from(
+            Url::parse(&format!(
+                "tcp://failover.prosa.invalid:{}",
+                live_addr.port()
+            ))
+            .unwrap(),
+        );
+        target_settings.connect_timeout = 1000;
+        let client = async {
+            let start = tokio::time::Instant::now();
+            let mut stream = target_settings.connect().await.unwrap();
+            assert!(start.elapsed() < Duration::from_millis(1000));
+            assert_eq!(SocketAddr::from(live_addr), stream.peer_addr().unwrap());
+
+            let mut buf = [0; 5];
+            stream.read_exact(&mut buf).await.unwrap();
+            assert_eq!(b"ProSA", &buf);
+        };
+
+        future::join(server, client).await;
+        dns::DnsCache::global().remove("failover.prosa.invalid", live_addr.port());
+    }
+
     #[tokio::test]
     async fn ssl_client_server() {
         let addr = "localhost:41443";
@@ -287,6 +867,7 @@ mod tests {
             .await
             .unwrap()
             .ssl_acceptor(ssl_acceptor, Some(ssl_config.get_ssl_timeout()));
+        #[cfg(target_family = "unix")]
         assert!(listener.as_raw_fd() > 0);
         assert!(
             format!("{:?}", listener).contains("Ssl"),
@@ -304,6 +885,14 @@ mod tests {
             let (mut client_stream, client_addr) = listener.accept().await.unwrap();
             assert!(client_addr.is_loopback());
 
+            // The client doesn't give a certificate
+            let tls_info = client_stream.tls_info().unwrap();
+            assert_eq!("TLSv1.3", tls_info.version);
+            assert!(!tls_info.cipher.is_empty());
+            assert_eq!(None, tls_info.alpn);
+            assert!(!tls_info.resumed);
+            assert_eq!(None, tls_info.peer_subject);
+
             let mut buf = [0; 5];
             client_stream.read_exact(&mut buf).await.unwrap();
             assert_eq!(&buf, b"ProSA");
@@ -318,7 +907,22 @@ mod tests {
             let mut stream = Stream::connect_ssl(&addr_url, &ssl_client_context.build())
                 .await
                 .unwrap();
+            #[cfg(target_family = "unix")]
             assert!(stream.as_raw_fd() > 0);
+
+            // The server give its self-signed certificate
+            let tls_info = stream.tls_info().unwrap();
+            assert_eq!("TLSv1.3", tls_info.version);
+            assert!(!tls_info.cipher.is_empty());
+            assert!(!tls_info.resumed);
+            assert!(
+                tls_info
+                    .peer_subject
+                    .as_ref()
+                    .is_some_and(|subject| subject.contains("CN=")),
+                "{}",
+                tls_info
+            );
             assert!(
                 format!("{:?}", stream).contains("Ssl"),
                 "stream `{:?}` don't contain Ssl",
@@ -352,6 +956,7 @@ mod tests {
             .await
             .unwrap()
             .ssl_acceptor(ssl_acceptor, Some(ssl_config.get_ssl_timeout()));
+        #[cfg(target_family = "unix")]
         assert!(listener.as_raw_fd() > 0);
         assert!(
             format!("{:?}", listener).contains("Ssl"),
@@ -384,6 +989,7 @@ mod tests {
             let mut stream = Stream::connect_ssl(&addr_url, &ssl_client_context.build())
                 .await
                 .unwrap();
+            #[cfg(target_family = "unix")]
             assert!(stream.as_raw_fd() > 0);
             assert!(
                 format!("{:?}", stream).contains("Ssl"),
@@ -431,7 +1037,7 @@ mod tests {
         assert!(listener_settings.to_string().starts_with(addr_str));
 
         let listener = listener_settings.bind().await.unwrap();
-        if let StreamListener::Ssl(_, acceptor, _) = &listener {
+        if let StreamListenerKind::Ssl(_, acceptor, _) = listener.get_kind() {
             let server_cert = acceptor.context().certificate().unwrap();
             let mut server_cert_file = File::create(temp_cert_dir.join("prosa_test_server.pem"))
                 .await
@@ -441,6 +1047,7 @@ mod tests {
                 .await
                 .unwrap();
         }
+        #[cfg(target_family = "unix")]
         assert!(listener.as_raw_fd() > 0);
         assert!(
             format!("{:?}", listener).contains("Ssl"),
@@ -476,6 +1083,7 @@ mod tests {
 
         let client = async {
             let mut stream = target_settings.connect().await.unwrap();
+            #[cfg(target_family = "unix")]
             assert!(stream.as_raw_fd() > 0);
             assert!(
                 format!("{:?}", stream).contains("Ssl"),
@@ -502,4 +1110,553 @@ mod tests {
 
         future::join(server, client).await;
     }
+
+    #[tokio::test]
+    async fn ssl_client_server_ip() {
+        // Both certificates have the same subject, so they are stored in different stores
+        let cert_dirs = [
+            env::temp_dir().join("prosa_test_ip_cert"),
+            env::temp_dir().join("prosa_test_dns_cert"),
+        ];
+
+        // Self-signed certificates carrying the IP (or the DNS name) as subject alternative name
+        let ip_listener = ListenerSetting::from(Url::parse("tls://127.0.0.1:41483").unwrap())
+            .bind()
+            .await
+            .unwrap();
+        let dns_listener = ListenerSetting::new(
+            Url::parse("tls://localhost:41493").unwrap(),
+            Some(SslConfig::default()),
+        )
+        .bind()
+        .await
+        .unwrap();
+        for (listener, cert_dir) in [
+            (&ip_listener, &cert_dirs[0]),
+            (&dns_listener, &cert_dirs[1]),
+        ] {
+            if let StreamListenerKind::Ssl(_, acceptor, _) = listener.get_kind() {
+                let cert = acceptor.context().certificate().unwrap();
+                let _ = std::fs::remove_dir_all(cert_dir);
+                std::fs::create_dir_all(cert_dir).unwrap();
+                std::fs::write(cert_dir.join("server.pem"), cert.to_pem().unwrap()).unwrap();
+            }
+        }
+
+        let server = async move {
+            // Connected with the IP, the certificate is verified against its IP SAN
+            let (mut client_stream, _) = ip_listener.accept().await.unwrap();
+            assert_eq!(None, client_stream.sni_hostname());
+            client_stream.write_all(b"ProSA").await.unwrap();
+
+            // Connected with the IP to a DNS certificate, rejected then accepted without hostname verification
+            assert!(dns_listener.accept().await.is_err());
+            let (mut client_stream, _) = dns_listener.accept().await.unwrap();
+            client_stream.write_all(b"Worldline").await.unwrap();
+        };
+
+        let client = async {
+            let mut client_ssl_config = SslConfig::default();
+            client_ssl_config
+                .set_store(Store::new(cert_dirs[0].to_str().unwrap().to_string() + "/"));
+            let target_settings = TargetSetting::new(
+                Url::parse("tls://127.0.0.1:41483").unwrap(),
+                Some(client_ssl_config),
+                None,
+            );
+            let mut stream = target_settings.connect().await.unwrap();
+            let mut buf = [0; 5];
+            stream.read_exact(&mut buf).await.unwrap();
+            assert_eq!(&buf, b"ProSA");
+            let _ = stream.shutdown().await;
+
+            // The DNS certificate doesn't match the IP
+            let mut client_ssl_config = SslConfig::default();
+            client_ssl_config
+                .set_store(Store::new(cert_dirs[1].to_str().unwrap().to_string() + "/"));
+            let dns_url = Url::parse("tls://127.0.0.1:41493").unwrap();
+            let target_settings =
+                TargetSetting::new(dns_url.clone(), Some(client_ssl_config.clone()), None);
+            assert!(target_settings.connect().await.is_err());
+
+            client_ssl_config.set_insecure_skip_hostname_verify(true);
+            let target_settings = TargetSetting::new(dns_url, Some(client_ssl_config), None);
+            let mut stream = target_settings.connect().await.unwrap();
+            let mut buf = [0; 9];
+            stream.read_exact(&mut buf).await.unwrap();
+            assert_eq!(&buf, b"Worldline");
+            let _ = stream.shutdown().await;
+        };
+
+        future::join(server, client).await;
+    }
+
+    #[tokio::test]
+    async fn ssl_session_resumption() {
+        let cert_dir = env::temp_dir().join("prosa_ssl_session_resumption");
+        let listener = ListenerSetting::from(Url::parse("tls://127.0.0.1:41503").unwrap())
+            .bind()
+            .await
+            .unwrap();
+        if let StreamListenerKind::Ssl(_, acceptor, _) = listener.get_kind() {
+            let cert = acceptor.context().certificate().unwrap();
+            let _ = std::fs::remove_dir_all(&cert_dir);
+            std::fs::create_dir_all(&cert_dir).unwrap();
+            std::fs::write(cert_dir.join("server.pem"), cert.to_pem().unwrap()).unwrap();
+        }
+
+        let server = async move {
+            for _ in 0..4 {
+                let (mut client_stream, _) = listener.accept().await.unwrap();
+                client_stream.write_all(b"ProSA").await.unwrap();
+                let _ = client_stream.shutdown().await;
+            }
+        };
+
+        let client = async {
+            let mut client_ssl_config = SslConfig::default();
+            client_ssl_config.set_store(Store::new(cert_dir.to_str().unwrap().to_string() + "/"));
+            let url = Url::parse("tls://127.0.0.1:41503").unwrap();
+
+            // Without session cache, every handshake is a full one
+            let target_settings =
+                TargetSetting::new(url.clone(), Some(client_ssl_config.clone()), None);
+            assert!(target_settings.get_ssl_session_cache().is_none());
+
+            // With a session cache, the session is resumed on reconnect
+            client_ssl_config.set_session_cache_size(Some(16));
+            let cached_target_settings = TargetSetting::new(url, Some(client_ssl_config), None);
+
+            for (target_settings, reused) in [
+                (&target_settings, false),
+                (&target_settings, false),
+                (&cached_target_settings, false),
+                (&cached_target_settings, true),
+            ] {
+                let mut stream = target_settings.connect().await.unwrap();
+                assert_eq!(reused, stream.is_session_reused());
+                assert_eq!(reused, stream.tls_info().unwrap().resumed);
+                let mut buf = [0; 5];
+                stream.read_exact(&mut buf).await.unwrap();
+                assert_eq!(&buf, b"ProSA");
+                let _ = stream.shutdown().await;
+            }
+
+            let session_cache = cached_target_settings.get_ssl_session_cache().unwrap();
+            assert_eq!(1, session_cache.len());
+            assert_eq!(1, session_cache.full_handshakes());
+            assert_eq!(1, session_cache.resumed_handshakes());
+        };
+
+        future::join(server, client).await;
+    }
+
+    #[tokio::test]
+    async fn ssl_alpn_sni() {
+        let addr_url = Url::parse("tls://localhost:41473").unwrap();
+
+        let mut listener_settings = ListenerSetting::from(addr_url.clone());
+        listener_settings.set_alpn(vec!["prosa/1".into(), "h2".into()]);
+        let listener = listener_settings.bind().await.unwrap();
+
+        let server = async move {
+            let (mut client_stream, _) = listener.accept().await.unwrap();
+            assert_eq!(Some(b"prosa/1".to_vec()), client_stream.selected_alpn());
+            assert_eq!(
+                Some(String::from("localhost")),
+                client_stream.sni_hostname()
+            );
+
+            client_stream.write_all(b"Worldline").await.unwrap();
+        };
+
+        let client = async {
+            let mut client_ssl_config = SslConfig::default();
+            client_ssl_config.set_alpn(vec!["h2".into(), "prosa/1".into()]);
+            let mut ssl_client_context = client_ssl_config.init_tls_client_context().unwrap();
+            ssl_client_context.set_verify(SslVerifyMode::NONE);
+
+            let mut stream = Stream::connect_ssl(&addr_url, &ssl_client_context.build())
+                .await
+                .unwrap();
+            assert_eq!(Some(b"prosa/1".to_vec()), stream.selected_alpn());
+
+            let mut buf = vec![];
+            stream.read_to_end(&mut buf).await.unwrap();
+            assert_eq!(buf, b"Worldline");
+
+            let _ = stream.shutdown().await;
+        };
+
+        future::join(server, client).await;
+
+        // Plain streams don't have SSL information
+        let tcp_listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let tcp_stream = Stream::connect_tcp(tcp_listener.local_addr().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(None, tcp_stream.selected_alpn());
+        assert_eq!(None, tcp_stream.sni_hostname());
+    }
+
+    #[tokio::test]
+    async fn tcp_copy_bidirectional() {
+        let backend = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let backend_addr = backend.local_addr().unwrap();
+        let front = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let front_addr = front.local_addr().unwrap();
+        let payload: Vec<u8> = (0..256 * 1024).map(|i| (i % 251) as u8).collect();
+
+        let backend_server = async move {
+            let (mut stream, _) = backend.accept().await.unwrap();
+            let mut buf = Vec::new();
+            stream.read_to_end(&mut buf).await.unwrap();
+            stream.write_all(b"Worldline").await.unwrap();
+            buf
+        };
+
+        let proxy = async move {
+            let (client_stream, _) = front.accept().await.unwrap();
+            let mut a = Stream::from(client_stream);
+            let mut b = Stream::connect_tcp(backend_addr).await.unwrap();
+            let opts = CopyOptions {
+                idle_timeout: Some(Duration::from_secs(5)),
+                ..Default::default()
+            };
+            copy_bidirectional_metrics(&mut a, &mut b, opts)
+                .await
+                .unwrap()
+        };
+
+        let client = async {
+            let mut stream = Stream::connect_tcp(front_addr).await.unwrap();
+            stream.write_all(&payload).await.unwrap();
+            stream.shutdown().await.unwrap();
+
+            let mut buf = Vec::new();
+            stream.read_to_end(&mut buf).await.unwrap();
+            buf
+        };
+
+        let (received, stats, response) = tokio::join!(backend_server, proxy, client);
+        assert_eq!(payload, received);
+        assert_eq!(b"Worldline".as_slice(), response.as_slice());
+        assert_eq!(payload.len() as u64, stats.a_to_b);
+        assert_eq!(9, stats.b_to_a);
+        assert_eq!(Some(CopySide::A), stats.first_closed);
+        assert!(stats.limit_reached.is_empty());
+        assert!(!stats.idle_timeout);
+        assert_eq!(
+            cfg!(all(target_os = "linux", feature = "splice")),
+            stats.spliced
+        );
+    }
+
+    #[tokio::test]
+    async fn tcp_copy_bidirectional_limit_idle() {
+        let backend = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let backend_addr = backend.local_addr().unwrap();
+        let front = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let front_addr = front.local_addr().unwrap();
+
+        let backend_server = async move {
+            let (mut stream, _) = backend.accept().await.unwrap();
+            let mut buf = Vec::new();
+            stream.read_to_end(&mut buf).await.unwrap();
+            (stream, buf)
+        };
+
+        let proxy = async move {
+            let (client_stream, _) = front.accept().await.unwrap();
+            let mut a = Stream::from(client_stream);
+            let mut b = Stream::connect_tcp(backend_addr).await.unwrap();
+            let opts = CopyOptions {
+                idle_timeout: Some(Duration::from_millis(200)),
+                a_to_b_limit: Some(1000),
+                ..Default::default()
+            };
+            copy_bidirectional_metrics(&mut a, &mut b, opts)
+                .await
+                .unwrap()
+        };
+
+        let client = async {
+            let mut stream = Stream::connect_tcp(front_addr).await.unwrap();
+            stream.write_all(&[0x2A; 4096]).await.unwrap();
+            stream
+        };
+
+        // Nothing is received after the limit, so the copy stop on the idle timeout
+        let ((_backend_stream, received), stats, _client_stream) =
+            tokio::join!(backend_server, proxy, client);
+        assert_eq!(1000, received.len());
+        assert_eq!(1000, stats.a_to_b);
+        assert_eq!(0, stats.b_to_a);
+        assert_eq!(None, stats.first_closed);
+        assert_eq!(vec![CopySide::A], stats.limit_reached);
+        assert!(stats.idle_timeout);
+    }
+
+    #[tokio::test]
+    async fn ssl_copy_bidirectional() {
+        let addr = "localhost:41830";
+        let addr_url = Url::parse(format!("tls://{}", addr).as_str()).unwrap();
+        let backend = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let backend_addr = backend.local_addr().unwrap();
+
+        let ssl_config = SslConfig::default();
+        let ssl_acceptor = ssl_config
+            .init_tls_server_context(addr_url.domain())
+            .unwrap()
+            .build();
+        let listener = StreamListener::bind(addr)
+            .await
+            .unwrap()
+            .ssl_acceptor(ssl_acceptor, Some(ssl_config.get_ssl_timeout()));
+        let payload: Vec<u8> = (0..64 * 1024).map(|i| (i % 251) as u8).collect();
+
+        let backend_server = async move {
+            let (mut stream, _) = backend.accept().await.unwrap();
+            let mut buf = vec![0; 64 * 1024];
+            stream.read_exact(&mut buf).await.unwrap();
+            stream.write_all(&buf).await.unwrap();
+        };
+
+        // TLS terminating proxy in front of a plain TCP server
+        let proxy = async move {
+            let (mut a, _) = listener.accept().await.unwrap();
+            let mut b = Stream::connect_tcp(backend_addr).await.unwrap();
+            copy_bidirectional_metrics(&mut a, &mut b, CopyOptions::default())
+                .await
+                .unwrap()
+        };
+
+        let client = async {
+            let mut ssl_client_context = ssl_config.init_tls_client_context().unwrap();
+            ssl_client_context.set_verify(SslVerifyMode::NONE);
+            let mut stream = Stream::connect_ssl(&addr_url, &ssl_client_context.build())
+                .await
+                .unwrap();
+
+            stream.write_all(&payload).await.unwrap();
+            let mut buf = vec![0; payload.len()];
+            stream.read_exact(&mut buf).await.unwrap();
+            assert_eq!(payload, buf);
+            stream.shutdown().await.unwrap();
+        };
+
+        let (_, stats, _) = tokio::join!(backend_server, proxy, client);
+        assert_eq!(64 * 1024, stats.a_to_b);
+        assert_eq!(64 * 1024, stats.b_to_a);
+        assert!(stats.first_closed.is_some());
+        assert!(!stats.spliced);
+    }
+
+    /// OpenSSL command line server (TLS 1.2), that request a renegotiation with its `R` command
+    struct RenegotiationServer {
+        child: tokio::process::Child,
+        output: String,
+        addr_url: Url,
+    }
+
+    impl RenegotiationServer {
+        async fn start(dir: &std::path::Path) -> RenegotiationServer {
+            let ca = prosa_utils::config::ssl::test_ca::TestCa::load_or_generate(dir).unwrap();
+            let (cert, key) = ca.issue("localhost").unwrap();
+            let (cert_path, key_path) = (dir.join("server.pem"), dir.join("server.key"));
+            std::fs::write(&cert_path, cert.to_pem().unwrap()).unwrap();
+            std::fs::write(&key_path, key.private_key_to_pem_pkcs8().unwrap()).unwrap();
+
+            let child = tokio::process::Command::new("openssl")
+                .arg("s_server")
+                .args(["-accept", "127.0.0.1:0", "-tls1_2"])
+                .arg("-cert")
+                .arg(&cert_path)
+                .arg("-key")
+                .arg(&key_path)
+                .stdin(std::process::Stdio::piped())
+                .stdout(std::process::Stdio::piped())
+                .stderr(std::process::Stdio::null())
+                .kill_on_drop(true)
+                .spawn()
+                .unwrap();
+            let mut server = RenegotiationServer {
+                child,
+                output: String::new(),
+                addr_url: Url::parse("tls://127.0.0.1").unwrap(),
+            };
+
+            // The server print the address it's bound to
+            server.wait_output("ACCEPT ").await;
+            let addr = server.wait_output("\n").await;
+            server.addr_url = Url::parse(&format!("tls://{}", addr.trim())).unwrap();
+            server
+        }
+
+        /// Method to wait for a text in the server output, and consume the output up to it
+        async fn wait_output(&mut self, text: &str) -> String {
+            let stdout = self.child.stdout.as_mut().unwrap();
+            tokio::time::timeout(Duration::from_secs(5), async {
+                while !self.output.contains(text) {
+                    let mut buf = [0u8; 1024];
+                    let len = stdout.read(&mut buf).await.unwrap();
+                    assert!(len > 0, "the openssl server ended");
+                    self.output.push_str(&String::from_utf8_lossy(&buf[..len]));
+                }
+            })
+            .await
+            .unwrap();
+            let pos = self.output.find(text).unwrap() + text.len();
+            self.output.drain(..pos).collect()
+        }
+
+        /// Method to give a command (or data to send) to the server
+        async fn input(&mut self, line: &str) {
+            let stdin = self.child.stdin.as_mut().unwrap();
+            stdin.write_all(line.as_bytes()).await.unwrap();
+            stdin.flush().await.unwrap();
+        }
+
+        /// Method to let the server read a command alone, before the next input (its command output is buffered)
+        async fn wait_command(&mut self) {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn ssl_renegotiation() {
+        let dir = env::temp_dir().join("prosa_ssl_renegotiation");
+        let mut server = RenegotiationServer::start(&dir).await;
+
+        let mut ssl_client_context = SslConfig::default().init_tls_client_context().unwrap();
+        ssl_client_context.set_verify(SslVerifyMode::NONE);
+        let mut stream = Stream::connect_ssl(&server.addr_url, &ssl_client_context.build())
+            .await
+            .unwrap();
+
+        // The renegotiation is handled transparently in the middle of the exchanges
+        let mut buf = [0u8; 5];
+        stream.write_all(b"ping").await.unwrap();
+        server.wait_output("ping").await;
+        server.input("R\n").await;
+        server.wait_command().await;
+        server.input("pong\n").await;
+        stream.read_exact(&mut buf).await.unwrap();
+        assert_eq!(b"pong\n", &buf);
+        stream.write_all(b"ping").await.unwrap();
+        server.wait_output("ping").await;
+        server.input("pong\n").await;
+        stream.read_exact(&mut buf).await.unwrap();
+        assert_eq!(b"pong\n", &buf);
+
+        // Renegotiation refused by the client
+        let mut server = RenegotiationServer::start(&dir).await;
+        let mut ssl_config = SslConfig::default();
+        ssl_config.set_renegotiation(false);
+        let mut ssl_client_context = ssl_config.init_tls_client_context().unwrap();
+        ssl_client_context.set_verify(SslVerifyMode::NONE);
+        let mut stream = Stream::connect_ssl(&server.addr_url, &ssl_client_context.build())
+            .await
+            .unwrap();
+
+        // The server abort the session when its renegotiation is refused
+        stream.write_all(b"ping").await.unwrap();
+        server.wait_output("ping").await;
+        server.input("R\n").await;
+        server.wait_command().await;
+        server.input("pong\n").await;
+        let _ = stream.read_exact(&mut buf).await;
+        let _ = stream.write_all(b"ping").await;
+        assert!(
+            tokio::time::timeout(Duration::from_secs(5), stream.read_exact(&mut buf))
+                .await
+                .unwrap()
+                .is_err()
+        );
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn ssl_shutdown_close_notify() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr_url = Url::parse(&format!("tls://{}", listener.local_addr().unwrap())).unwrap();
+        let ssl_acceptor = SslConfig::default()
+            .init_tls_server_context(None)
+            .unwrap()
+            .build();
+        let server = std::thread::spawn(move || {
+            let mut received_shutdowns = Vec::new();
+            for answer_close_notify in [true, false] {
+                let (tcp_stream, _) = listener.accept().unwrap();
+                let mut stream = ssl_acceptor.accept(tcp_stream).unwrap();
+                let mut buf = [0u8; 16];
+                assert_eq!(
+                    openssl::ssl::ErrorCode::ZERO_RETURN,
+                    stream.ssl_read(&mut buf).unwrap_err().code()
+                );
+                received_shutdowns.push(
+                    stream
+                        .get_shutdown()
+                        .contains(openssl::ssl::ShutdownState::RECEIVED),
+                );
+
+                if answer_close_notify {
+                    assert_eq!(
+                        openssl::ssl::ShutdownResult::Received,
+                        stream.shutdown().unwrap()
+                    );
+                }
+            }
+
+            received_shutdowns
+        });
+
+        let mut ssl_client_context = SslConfig::default().init_tls_client_context().unwrap();
+        ssl_client_context.set_verify(SslVerifyMode::NONE);
+        let ssl_connector = ssl_client_context.build();
+
+        // Bidirectional close_notify
+        let mut stream = Stream::connect_ssl(&addr_url, &ssl_connector)
+            .await
+            .unwrap();
+        assert!(stream.shutdown_tls(Duration::from_secs(1)).await.unwrap());
+
+        // The server close the socket without close_notify
+        let mut stream = Stream::connect_ssl(&addr_url, &ssl_connector)
+            .await
+            .unwrap();
+        assert!(!stream.shutdown_tls(Duration::from_secs(1)).await.unwrap());
+
+        assert_eq!(
+            vec![true, true],
+            tokio::task::spawn_blocking(move || server.join().unwrap())
+                .await
+                .unwrap()
+        );
+    }
+
+    #[cfg(target_family = "unix")]
+    #[tokio::test]
+    async fn tcp_inherited_listener() {
+        // Listening socket of the previous process
+        let previous_listener = StreamListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = previous_listener.local_addr().unwrap();
+        let listener_fd = previous_listener.into_raw_fd().unwrap();
+        env::set_var(listener::LISTEN_FDS_ENV, listener_fd.to_string());
+
+        // The listener inherit the socket instead of binding a new one
+        let listener_setting = ListenerSetting::from(Url::parse(&listener_fd.url).unwrap());
+        let listener = listener_setting.bind().await.unwrap();
+        assert_eq!(listener_fd.fd, listener.as_raw_fd());
+        assert_eq!(addr.to_string(), listener.local_addr().unwrap().to_string());
+        env::remove_var(listener::LISTEN_FDS_ENV);
+
+        let client_addr = addr.to_string();
+        let client = tokio::spawn(async move {
+            tokio::net::TcpStream::connect(client_addr).await.unwrap();
+        });
+        assert!(listener.accept_raw().await.is_ok());
+        client.await.unwrap();
+    }
 }
@@ -0,0 +1,483 @@
+//! Module to define a bridge processor that forward services between ProSA instances
+//!
+//! A bridge processor can export local services on a listener, and import the services of a remote ProSA through a target:
+//! - On the listener side, requests received from the remote ProSAs are sent to the exported services of the local bus, and their responses are relayed back.
+//! - On the target side, imported services are declared on the local bus while the link is up. Their requests are forwarded to the remote ProSA.
+//!   When the link is down, imported services are removed from the bus (so they are unavailable for the callers) and the processor reconnects with an exponential backoff.
+//!
+//! Both sides exchange frames prefixed by their length on 4 bytes (big endian). Every frame start with its kind and a correlation id:
+//!
+//! | Kind | Frame    | Content                                                                                 |
+//! |------|----------|-----------------------------------------------------------------------------------------|
+//! | 1    | Request  | Correlation id (u64), service name length (u16), service name, TVF message              |
+//! | 2    | Response | Correlation id (u64), TVF message                                                       |
+//! | 3    | Error    | Correlation id (u64), TVF message with the [`ServiceError`] encoded in it               |
+//!
+//! TVF messages are encoded with the [`BridgeEncoding`] configured on both sides.
+//! If a compression is configured on both sides, every frame payload is compressed (see [`codec`](prosa_utils::msg::codec)), except the ones smaller than the compression threshold.
+use std::{fmt::Debug, io};
+
+use bytes::{Buf as _, BufMut as _, Bytes, BytesMut};
+use chrono::{DateTime, Datelike as _, NaiveDate};
+use prosa_utils::msg::{
+    codec::CodecError,
+    convert,
+    simple_string_tvf::SimpleStringTvf,
+    tvf::{Tvf, TvfError, TvfType},
+};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::frame::FrameError;
+
+#[cfg(doc)]
+use crate::core::service::ServiceError;
+
+/// Definition of the bridge processor
+///
+/// <svg width="40" height="40">
+#[doc = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/doc_assets/proc.svg"))]
+/// </svg>
+pub mod proc;
+
+/// Definition of the bridge adaptor
+///
+/// <svg width="40" height="40">
+#[doc = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/doc_assets/adaptor.svg"))]
+/// </svg>
+pub mod adaptor;
+
+/// Error define for the bridge links
+#[derive(Debug, Error)]
+pub enum BridgeError {
+    /// Error on the link stream
+    #[error("Bridge IO error: {0}")]
+    Io(#[from] io::Error),
+    /// Error on a link frame
+    #[error("Bridge frame error: {0}")]
+    Frame(#[from] FrameError),
+    /// Error on a TVF message of a frame
+    #[error("Bridge TVF error: {0}")]
+    Tvf(#[from] TvfError),
+    /// Error on the compression of a frame
+    #[error("Bridge codec error: {0}")]
+    Codec(#[from] CodecError),
+    /// Error of the bridge protocol
+    #[error("Bridge protocol error: {0}")]
+    Protocol(String),
+}
+
+/// Encoding of the TVF messages exchanged by the bridge processors
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BridgeEncoding {
+    /// Binary encoding that keep the type of every field.
+    /// Every field is encoded with its tag (u32), its type (u8), and its value (sized values are prefixed by their length on a u32)
+    #[default]
+    Binary,
+    /// Text encoding of the [`SimpleStringTvf`], every value is transmitted as a string
+    SimpleString,
+}
+
+impl BridgeEncoding {
+    const BUFFER_TYPE: u8 = 1;
+    const UNSIGNED_TYPE: u8 = 2;
+    const SIGNED_TYPE: u8 = 3;
+    const BYTE_TYPE: u8 = 4;
+    const FLOAT_TYPE: u8 = 5;
+    const STRING_TYPE: u8 = 6;
+    const BYTES_TYPE: u8 = 7;
+    const DATE_TYPE: u8 = 8;
+    const DATETIME_TYPE: u8 = 9;
+
+    /// Method to encode a TVF message
+    ///
+    /// ```
+    /// use bytes::BytesMut;
+    /// use prosa::io::bridge::BridgeEncoding;
+    /// use prosa_utils::msg::tvf::Tvf;
+    /// use prosa_utils::msg::simple_string_tvf::SimpleStringTvf;
+    ///
+    /// let mut msg = SimpleStringTvf::default();
+    /// msg.put_string(1, "ProSA");
+    ///
+    /// let mut data = BytesMut::new();
+    /// BridgeEncoding::SimpleString.encode(&msg, &mut data).unwrap();
+    /// assert_eq!(b"1;5;ProSA;".as_slice(), &data[..]);
+    /// assert_eq!(msg, BridgeEncoding::SimpleString.decode::<SimpleStringTvf>(&data).unwrap());
+    /// ```
+    pub fn encode<M>(&self, msg: &M, dst: &mut BytesMut) -> Result<(), BridgeError>
+    where
+        M: Tvf + Default + Debug + Clone,
+    {
+        match self {
+            BridgeEncoding::Binary => Self::encode_binary(msg, dst),
+            BridgeEncoding::SimpleString => {
+                let msg: SimpleStringTvf = convert(msg)?;
+                dst.put_slice(msg.serialize().as_bytes());
+                Ok(())
+            }
+        }
+    }
+
+    /// Method to decode a TVF message
+    pub fn decode<M>(&self, data: &[u8]) -> Result<M, BridgeError>
+    where
+        M: Tvf + Default + Debug + Clone,
+    {
+        match self {
+            BridgeEncoding::Binary => Self::decode_binary(data),
+            BridgeEncoding::SimpleString => {
+                let data = std::str::from_utf8(data)
+                    .map_err(|e| BridgeError::Protocol(format!("invalid TVF string: {}", e)))?;
+                Ok(convert(&SimpleStringTvf::deserialize(data)?)?)
+            }
+        }
+    }
+
+    fn encode_binary<M>(msg: &M, dst: &mut BytesMut) -> Result<(), BridgeError>
+    where
+        M: Tvf + Default + Debug + Clone,
+    {
+        let mut keys = msg.keys();
+        keys.sort_unstable();
+        for key in keys {
+            let tag = u32::try_from(key)
+                .map_err(|_| BridgeError::Protocol(format!("the tag {} can't be encoded", key)))?;
+            dst.put_u32(tag);
+            match msg.get_type(key)? {
+                TvfType::Buffer => {
+                    let mut buffer = BytesMut::new();
+                    Self::encode_binary(msg.get_buffer(key)?.as_ref(), &mut buffer)?;
+                    dst.put_u8(Self::BUFFER_TYPE);
+                    Self::put_sized(dst, &buffer)?;
+                }
+                TvfType::Unsigned => {
+                    dst.put_u8(Self::UNSIGNED_TYPE);
+                    dst.put_u64(msg.get_unsigned(key)?);
+                }
+                TvfType::Signed => {
+                    dst.put_u8(Self::SIGNED_TYPE);
+                    dst.put_i64(msg.get_signed(key)?);
+                }
+                TvfType::Byte => {
+                    dst.put_u8(Self::BYTE_TYPE);
+                    dst.put_u8(msg.get_byte(key)?);
+                }
+                TvfType::Float => {
+                    dst.put_u8(Self::FLOAT_TYPE);
+                    dst.put_f64(msg.get_float(key)?);
+                }
+                TvfType::String => {
+                    dst.put_u8(Self::STRING_TYPE);
+                    Self::put_sized(dst, msg.get_string(key)?.as_bytes())?;
+                }
+                TvfType::Bytes => {
+                    dst.put_u8(Self::BYTES_TYPE);
+                    Self::put_sized(dst, msg.get_bytes(key)?.as_ref())?;
+                }
+                TvfType::Date => {
+                    dst.put_u8(Self::DATE_TYPE);
+                    dst.put_i32(msg.get_date(key)?.num_days_from_ce());
+                }
+                TvfType::DateTime => {
+                    dst.put_u8(Self::DATETIME_TYPE);
+                    dst.put_i64(msg.get_datetime(key)?.and_utc().timestamp_micros());
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn put_sized(dst: &mut BytesMut, value: &[u8]) -> Result<(), BridgeError> {
+        let len = u32::try_from(value.len()).map_err(|_| {
+            BridgeError::Protocol(format!("the value of {} bytes is too large", value.len()))
+        })?;
+        dst.put_u32(len);
+        dst.put_slice(value);
+        Ok(())
+    }
+
+    fn decode_binary<M>(mut data: &[u8]) -> Result<M, BridgeError>
+    where
+        M: Tvf + Default + Debug + Clone,
+    {
+        let mut msg = M::default();
+        while data.has_remaining() {
+            ensure_remaining(data, 5)?;
+            let key = data.get_u32() as usize;
+            match data.get_u8() {
+                Self::BUFFER_TYPE => {
+                    let buffer = get_sized(&mut data)?;
+                    msg.put_buffer(key, Self::decode_binary(buffer)?);
+                }
+                Self::UNSIGNED_TYPE => {
+                    ensure_remaining(data, 8)?;
+                    msg.put_unsigned(key, data.get_u64());
+                }
+                Self::SIGNED_TYPE => {
+                    ensure_remaining(data, 8)?;
+                    msg.put_signed(key, data.get_i64());
+                }
+                Self::BYTE_TYPE => {
+                    ensure_remaining(data, 1)?;
+                    msg.put_byte(key, data.get_u8());
+                }
+                Self::FLOAT_TYPE => {
+                    ensure_remaining(data, 8)?;
+                    msg.put_float(key, data.get_f64());
+                }
+                Self::STRING_TYPE => {
+                    let string = std::str::from_utf8(get_sized(&mut data)?).map_err(|e| {
+                        BridgeError::Protocol(format!("invalid string on tag {}: {}", key, e))
+                    })?;
+                    msg.put_string(key, string);
+                }
+                Self::BYTES_TYPE => {
+                    let bytes = get_sized(&mut data)?;
+                    msg.put_bytes(key, Bytes::copy_from_slice(bytes));
+                }
+                Self::DATE_TYPE => {
+                    ensure_remaining(data, 4)?;
+                    let date =
+                        NaiveDate::from_num_days_from_ce_opt(data.get_i32()).ok_or_else(|| {
+                            BridgeError::Protocol(format!("invalid date on tag {}", key))
+                        })?;
+                    msg.put_date(key, date);
+                }
+                Self::DATETIME_TYPE => {
+                    ensure_remaining(data, 8)?;
+                    let datetime = DateTime::from_timestamp_micros(data.get_i64())
+                        .ok_or_else(|| {
+                            BridgeError::Protocol(format!("invalid datetime on tag {}", key))
+                        })?
+                        .naive_utc();
+                    msg.put_datetime(key, datetime);
+                }
+                field_type => {
+                    return Err(BridgeError::Protocol(format!(
+                        "unknown type {} on tag {}",
+                        field_type, key
+                    )))
+                }
+            }
+        }
+
+        Ok(msg)
+    }
+}
+
+/// Method to check that enough data remain to decode a value
+fn ensure_remaining(data: &[u8], len: usize) -> Result<(), BridgeError> {
+    if data.remaining() < len {
+        Err(BridgeError::Protocol(format!(
+            "truncated frame, {} bytes missing",
+            len - data.remaining()
+        )))
+    } else {
+        Ok(())
+    }
+}
+
+/// Method to get a value prefixed by its length on a u32
+fn get_sized<'a>(data: &mut &'a [u8]) -> Result<&'a [u8], BridgeError> {
+    ensure_remaining(data, 4)?;
+    let len = data.get_u32() as usize;
+    ensure_remaining(data, len)?;
+    let (value, remaining) = data.split_at(len);
+    *data = remaining;
+    Ok(value)
+}
+
+/// Frame exchanged by the bridge processors
+///
+/// ```
+/// use bytes::BytesMut;
+/// use prosa::io::bridge::{BridgeEncoding, BridgeFrame};
+/// use prosa_utils::msg::tvf::Tvf;
+/// use prosa_utils::msg::simple_string_tvf::SimpleStringTvf;
+///
+/// let mut data = SimpleStringTvf::default();
+/// data.put_string(1, "ProSA");
+/// let request = BridgeFrame::Request { id: 42, service: String::from("SRV"), data };
+///
+/// let mut frame = BytesMut::new();
+/// request.encode(BridgeEncoding::Binary, &mut frame).unwrap();
+/// assert_eq!(request, BridgeFrame::decode(&frame, BridgeEncoding::Binary).unwrap());
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub enum BridgeFrame<M> {
+    /// Request sent to a service of the remote ProSA
+    Request {
+        /// Correlation id of the request
+        id: u64,
+        /// Name of the remote service
+        service: String,
+        /// Request message
+        data: M,
+    },
+    /// Response of the remote service
+    Response {
+        /// Correlation id of the request
+        id: u64,
+        /// Response message
+        data: M,
+    },
+    /// Error of the remote service
+    Error {
+        /// Correlation id of the request
+        id: u64,
+        /// Message with the [`ServiceError`] encoded in it
+        data: M,
+    },
+}
+
+impl<M> BridgeFrame<M>
+where
+    M: Tvf + Default + Debug + Clone,
+{
+    const REQUEST_KIND: u8 = 1;
+    const RESPONSE_KIND: u8 = 2;
+    const ERROR_KIND: u8 = 3;
+
+    /// Getter of the correlation id of the frame
+    pub fn get_id(&self) -> u64 {
+        match self {
+            BridgeFrame::Request { id, .. }
+            | BridgeFrame::Response { id, .. }
+            | BridgeFrame::Error { id, .. } => *id,
+        }
+    }
+
+    /// Method to encode the frame payload (without its length header)
+    pub fn encode(&self, encoding: BridgeEncoding, dst: &mut BytesMut) -> Result<(), BridgeError> {
+        match self {
+            BridgeFrame::Request { id, service, data } => {
+                let service_len = u16::try_from(service.len()).map_err(|_| {
+                    BridgeError::Protocol(format!("the service name `{}` is too long", service))
+                })?;
+                dst.put_u8(Self::REQUEST_KIND);
+                dst.put_u64(*id);
+                dst.put_u16(service_len);
+                dst.put_slice(service.as_bytes());
+                encoding.encode(data, dst)
+            }
+            BridgeFrame::Response { id, data } => {
+                dst.put_u8(Self::RESPONSE_KIND);
+                dst.put_u64(*id);
+                encoding.encode(data, dst)
+            }
+            BridgeFrame::Error { id, data } => {
+                dst.put_u8(Self::ERROR_KIND);
+                dst.put_u64(*id);
+                encoding.encode(data, dst)
+            }
+        }
+    }
+
+    /// Method to decode a frame payload (without its length header)
+    pub fn decode(mut frame: &[u8], encoding: BridgeEncoding) -> Result<Self, BridgeError> {
+        ensure_remaining(frame, 9)?;
+        let kind = frame.get_u8();
+        let id = frame.get_u64();
+        match kind {
+            Self::REQUEST_KIND => {
+                ensure_remaining(frame, 2)?;
+                let service_len = frame.get_u16() as usize;
+                ensure_remaining(frame, service_len)?;
+                let (service, data) = frame.split_at(service_len);
+                let service = String::from_utf8(service.to_vec())
+                    .map_err(|e| BridgeError::Protocol(format!("invalid service name: {}", e)))?;
+                Ok(BridgeFrame::Request {
+                    id,
+                    service,
+                    data: encoding.decode(data)?,
+                })
+            }
+            Self::RESPONSE_KIND => Ok(BridgeFrame::Response {
+                id,
+                data: encoding.decode(frame)?,
+            }),
+            Self::ERROR_KIND => Ok(BridgeFrame::Error {
+                id,
+                data: encoding.decode(frame)?,
+            }),
+            kind => Err(BridgeError::Protocol(format!(
+                "unknown frame kind {}",
+                kind
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDateTime;
+
+    use super::*;
+
+    #[test]
+    fn bridge_binary_encoding() {
+        let mut sub = SimpleStringTvf::default();
+        sub.put_string(1, "sub");
+        let mut msg = SimpleStringTvf::default();
+        msg.put_buffer(1, sub);
+        msg.put_unsigned(2, 42);
+        msg.put_signed(3, -42);
+        msg.put_string(4, "ProSA");
+        msg.put_bytes(5, Bytes::from_static(b"bytes"));
+        msg.put_date(6, NaiveDate::from_ymd_opt(2024, 2, 29).unwrap());
+        msg.put_datetime(
+            7,
+            NaiveDateTime::parse_from_str("2024-02-29 12:34:56.789", "%Y-%m-%d %H:%M:%S%.f")
+                .unwrap(),
+        );
+
+        let mut data = BytesMut::new();
+        BridgeEncoding::Binary.encode(&msg, &mut data).unwrap();
+        let decoded: SimpleStringTvf = BridgeEncoding::Binary.decode(&data).unwrap();
+        assert_eq!(msg, decoded);
+
+        // Truncated messages are rejected
+        assert!(matches!(
+            BridgeEncoding::Binary.decode::<SimpleStringTvf>(&data[..data.len() - 1]),
+            Err(BridgeError::Protocol(_))
+        ));
+    }
+
+    #[test]
+    fn bridge_frame() {
+        let mut data = SimpleStringTvf::default();
+        data.put_string(1, "ProSA");
+        for encoding in [BridgeEncoding::Binary, BridgeEncoding::SimpleString] {
+            for frame in [
+                BridgeFrame::Request {
+                    id: 1,
+                    service: String::from("SRV"),
+                    data: data.clone(),
+                },
+                BridgeFrame::Response {
+                    id: 2,
+                    data: data.clone(),
+                },
+                BridgeFrame::Error {
+                    id: u64::MAX,
+                    data: data.clone(),
+                },
+            ] {
+                let mut payload = BytesMut::new();
+                frame.encode(encoding, &mut payload).unwrap();
+                assert_eq!(frame, BridgeFrame::decode(&payload, encoding).unwrap());
+            }
+        }
+
+        assert!(matches!(
+            BridgeFrame::<SimpleStringTvf>::decode(
+                &[4, 0, 0, 0, 0, 0, 0, 0, 1],
+                BridgeEncoding::Binary
+            ),
+            Err(BridgeError::Protocol(_))
+        ));
+    }
+}
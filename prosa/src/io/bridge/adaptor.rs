@@ -0,0 +1,83 @@
+use std::error::Error;
+
+use crate::core::adaptor::Adaptor;
+
+use super::proc::BridgeProc;
+use super::BridgeError;
+
+extern crate self as prosa;
+
+/// Adaptator trait for the bridge processor
+///
+/// Messages are forwarded as is by the bridge, the adaptor is only notified of the link state to the target.
+/// ```
+/// use prosa::core::adaptor::Adaptor;
+/// use prosa::io::bridge::BridgeError;
+/// use prosa::io::bridge::adaptor::BridgeAdaptor;
+/// use prosa::io::bridge::proc::BridgeProc;
+///
+/// #[derive(Adaptor)]
+/// pub struct MyBridgeAdaptor { }
+///
+/// impl<M> BridgeAdaptor<M> for MyBridgeAdaptor
+/// where
+///     M: 'static
+///         + std::marker::Send
+///         + std::marker::Sync
+///         + std::marker::Sized
+///         + std::clone::Clone
+///         + std::fmt::Debug
+///         + prosa_utils::msg::tvf::Tvf
+///         + std::default::Default,
+/// {
+///     fn new(_proc: &BridgeProc<M>) -> Result<Self, Box<dyn std::error::Error>> {
+///         Ok(Self {})
+///     }
+///     fn on_link_down(&mut self, err: &BridgeError) {
+///         eprintln!("The remote ProSA is unreachable: {}", err);
+///     }
+/// }
+/// ```
+pub trait BridgeAdaptor<M>
+where
+    M: 'static
+        + std::marker::Send
+        + std::marker::Sync
+        + std::marker::Sized
+        + std::clone::Clone
+        + std::fmt::Debug
+        + prosa_utils::msg::tvf::Tvf
+        + std::default::Default,
+{
+    /// Method called when the processor spawns
+    /// This method is called only once so the processing will be thread safe
+    fn new(proc: &BridgeProc<M>) -> Result<Self, Box<dyn Error>>
+    where
+        Self: Sized;
+    /// Method called when the link to the target is up (the imported services are declared)
+    /// By default nothing is done
+    fn on_link_up(&mut self) {}
+    /// Method called when the link to the target is lost (the imported services are removed until the reconnection)
+    /// By default nothing is done
+    fn on_link_down(&mut self, _err: &BridgeError) {}
+}
+
+/// Default adaptor for the bridge processor, that doesn't do anything on link events
+#[derive(Adaptor)]
+pub struct BridgeDefaultAdaptor {}
+
+impl<M> BridgeAdaptor<M> for BridgeDefaultAdaptor
+where
+    M: 'static
+        + std::marker::Send
+        + std::marker::Sync
+        + std::marker::Sized
+        + std::clone::Clone
+        + std::fmt::Debug
+        + prosa_utils::msg::tvf::Tvf
+        + std::default::Default,
+{
+    fn new(_proc: &BridgeProc<M>) -> Result<Self, Box<dyn Error>> {
+        Ok(Self {})
+    }
+}
@@ -0,0 +1,773 @@
+use std::{io, sync::Arc, time::Duration};
+
+use bytes::{Bytes, BytesMut};
+use prosa_macros::proc_settings;
+use prosa_utils::msg::codec::Compression;
+use serde::{Deserialize, Serialize};
+use tokio::{
+    io::AsyncWriteExt as _,
+    sync::{mpsc, watch},
+    task::{JoinHandle, JoinSet},
+};
+use tracing::{debug, info, warn};
+
+use crate::core::adaptor::Adaptor;
+use crate::core::error::SendError;
+use crate::core::msg::{drop_unreturned, InternalMsg, Msg, RequestMsg};
+use crate::core::proc::{proc, Proc};
+use crate::core::service::{ServiceCall, ServiceError, ServiceTable};
+use crate::event::pending::PendingMsgs;
+use crate::io::frame::LengthPrefixedCodec;
+use crate::io::listener::{ListenerClosed, ListenerSetting, MultiListener};
+use crate::io::stream::{Stream, TargetSetting, TimedStream};
+use crate::io::SocketAddr;
+
+use super::adaptor::BridgeAdaptor;
+use super::{BridgeEncoding, BridgeError, BridgeFrame};
+
+extern crate self as prosa;
+
+/// Bridge settings for the exported services (listener side) and the imported services (target side)
+#[proc_settings]
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct BridgeSettings {
+    /// Listener where the remote ProSAs connect to call the exported services
+    #[serde(default)]
+    listener: Option<ListenerSetting>,
+    /// Local services that the remote ProSAs can call
+    #[serde(default)]
+    exported_services: Vec<String>,
+    /// Remote ProSA to connect to, to import its services
+    #[serde(default)]
+    target: Option<TargetSetting>,
+    /// Remote services declared locally while the link to the target is up
+    #[serde(default)]
+    imported_services: Vec<String>,
+    /// Encoding of the TVF messages on the links (must be the same on both sides)
+    #[serde(default)]
+    encoding: BridgeEncoding,
+    /// Compression of the frames on the links (must be the same on both sides)
+    #[serde(default)]
+    compression: Option<Compression>,
+    /// Timeout of the requests forwarded by the bridge
+    #[serde(default = "BridgeSettings::default_service_timeout")]
+    service_timeout: Duration,
+    /// Delay before reconnecting to the target, doubled after every failed attempt
+    #[serde(default = "BridgeSettings::default_reconnect_delay")]
+    reconnect_delay: Duration,
+    /// Maximum delay between two reconnection attempts
+    #[serde(default = "BridgeSettings::default_max_reconnect_delay")]
+    max_reconnect_delay: Duration,
+}
+
+impl BridgeSettings {
+    fn default_service_timeout() -> Duration {
+        Duration::from_secs(5)
+    }
+
+    fn default_reconnect_delay() -> Duration {
+        Duration::from_millis(500)
+    }
+
+    fn default_max_reconnect_delay() -> Duration {
+        Duration::from_secs(30)
+    }
+
+    /// Method to export local services to the remote ProSAs that connect to the listener
+    pub fn set_listener(&mut self, listener: ListenerSetting, exported_services: Vec<String>) {
+        self.listener = Some(listener);
+        self.exported_services = exported_services;
+    }
+
+    /// Method to import the services of a remote ProSA through the target
+    pub fn set_target(&mut self, target: TargetSetting, imported_services: Vec<String>) {
+        self.target = Some(target);
+        self.imported_services = imported_services;
+    }
+
+    /// Setter of the encoding of the TVF messages on the links
+    pub fn set_encoding(&mut self, encoding: BridgeEncoding) {
+        self.encoding = encoding;
+    }
+
+    /// Setter of the compression of the frames on the links
+    pub fn set_compression(&mut self, compression: Compression) {
+        self.compression = Some(compression);
+    }
+
+    /// Setter of the timeout of the requests forwarded by the bridge
+    pub fn set_service_timeout(&mut self, service_timeout: Duration) {
+        self.service_timeout = service_timeout;
+    }
+
+    /// Setter of the reconnection backoff to the target (initial and maximum delays)
+    pub fn set_reconnect_delay(
+        &mut self,
+        reconnect_delay: Duration,
+        max_reconnect_delay: Duration,
+    ) {
+        self.reconnect_delay = reconnect_delay;
+        self.max_reconnect_delay = max_reconnect_delay;
+    }
+
+    /// Method to encode a frame payload, compressed if the compression is set
+    fn encode_frame<M>(&self, frame: &BridgeFrame<M>) -> Result<Bytes, BridgeError>
+    where
+        M: prosa_utils::msg::tvf::Tvf + Default + std::fmt::Debug + Clone,
+    {
+        let mut payload = BytesMut::new();
+        frame.encode(self.encoding, &mut payload)?;
+        if let Some(compression) = &self.compression {
+            Ok(compression.compress(&payload)?)
+        } else {
+            Ok(payload.freeze())
+        }
+    }
+
+    /// Method to decode a frame payload, decompressed if the compression is set
+    fn decode_frame<M>(&self, payload: &[u8]) -> Result<BridgeFrame<M>, BridgeError>
+    where
+        M: prosa_utils::msg::tvf::Tvf + Default + std::fmt::Debug + Clone,
+    {
+        if let Some(compression) = &self.compression {
+            BridgeFrame::decode(&compression.decompress(payload)?, self.encoding)
+        } else {
+            BridgeFrame::decode(payload, self.encoding)
+        }
+    }
+}
+
+#[proc_settings]
+impl Default for BridgeSettings {
+    fn default() -> BridgeSettings {
+        BridgeSettings {
+            listener: None,
+            exported_services: Vec::new(),
+            target: None,
+            imported_services: Vec::new(),
+            encoding: BridgeEncoding::default(),
+            compression: None,
+            service_timeout: BridgeSettings::default_service_timeout(),
+            reconnect_delay: BridgeSettings::default_reconnect_delay(),
+            max_reconnect_delay: BridgeSettings::default_max_reconnect_delay(),
+        }
+    }
+}
+
+/// Event of the link to the target, sent to the processor
+enum LinkEvent<M> {
+    /// The link is up, with the queue of frames to send on it
+    Up(mpsc::Sender<Bytes>),
+    /// A frame is received from the target
+    Frame(BridgeFrame<M>),
+    /// The link is lost
+    Down(BridgeError),
+}
+
+/// Method to exchange frames with the target until the link is lost.
+/// Return `None` if the processor is stopped
+async fn exchange_link<M>(
+    stream: TimedStream,
+    mut frames: mpsc::Receiver<Bytes>,
+    settings: &BridgeSettings,
+    events: &mpsc::Sender<LinkEvent<M>>,
+) -> Option<BridgeError>
+where
+    M: 'static
+        + std::marker::Send
+        + std::marker::Sync
+        + std::marker::Sized
+        + std::clone::Clone
+        + std::fmt::Debug
+        + prosa_utils::msg::tvf::Tvf
+        + std::default::Default,
+{
+    let codec = LengthPrefixedCodec::<4>::default();
+    let (mut reader, mut writer) = tokio::io::split(stream);
+    let mut buffer = BytesMut::with_capacity(8192);
+    loop {
+        tokio::select! {
+            frame = codec.read(&mut reader, &mut buffer) => match frame {
+                Ok(Some(frame)) => match settings.decode_frame(&frame) {
+                    Ok(frame) => events.send(LinkEvent::Frame(frame)).await.ok()?,
+                    Err(e) => return Some(e),
+                },
+                Ok(None) => return Some(io::Error::from(io::ErrorKind::UnexpectedEof).into()),
+                Err(e) => return Some(e.into()),
+            },
+            Some(frame) = frames.recv() => {
+                if let Err(e) = codec.write(&mut writer, &frame).await {
+                    return Some(e.into());
+                }
+            }
+        }
+    }
+}
+
+/// Method to keep the link to the target up, reconnecting with an exponential backoff
+async fn run_link<M>(
+    target: TargetSetting,
+    settings: BridgeSettings,
+    events: mpsc::Sender<LinkEvent<M>>,
+) where
+    M: 'static
+        + std::marker::Send
+        + std::marker::Sync
+        + std::marker::Sized
+        + std::clone::Clone
+        + std::fmt::Debug
+        + prosa_utils::msg::tvf::Tvf
+        + std::default::Default,
+{
+    let mut delay = settings.reconnect_delay;
+    loop {
+        match target.connect_timed().await {
+            Ok(stream) => {
+                delay = settings.reconnect_delay;
+                let (frames_tx, frames_rx) = mpsc::channel(256);
+                if events.send(LinkEvent::Up(frames_tx)).await.is_err() {
+                    return;
+                }
+
+                let Some(err) = exchange_link(stream, frames_rx, &settings, &events).await else {
+                    return;
+                };
+                if events.send(LinkEvent::Down(err)).await.is_err() {
+                    return;
+                }
+            }
+            Err(e) => {
+                debug!(name: "link_bridge_proc", target: "prosa::io::bridge::proc", target_url = target.url.to_string(), "Can't connect to the target, retry in {:?}: {}", delay, e);
+            }
+        }
+
+        tokio::time::sleep(delay).await;
+        delay = (delay * 2).min(settings.max_reconnect_delay);
+    }
+}
+
+/// Method to serve a remote ProSA connected to the listener, by calling the exported services for its requests
+async fn serve_remote<M>(
+    listener: Arc<MultiListener>,
+    stream: Stream,
+    addr: SocketAddr,
+    settings: Arc<BridgeSettings>,
+    services: watch::Receiver<Arc<ServiceTable<M>>>,
+    mut shutdown: watch::Receiver<bool>,
+) where
+    M: 'static
+        + std::marker::Send
+        + std::marker::Sync
+        + std::marker::Sized
+        + std::clone::Clone
+        + std::fmt::Debug
+        + prosa_utils::msg::tvf::Tvf
+        + std::default::Default,
+{
+    let stream = match listener.handshake(stream).await {
+        Ok(stream) => stream,
+        Err(e) => {
+            debug!(name: "remote_bridge_proc", target: "prosa::io::bridge::proc", addr = addr.to_string(), "Handshake failed: {}", e);
+            return;
+        }
+    };
+    debug!(name: "remote_bridge_proc", target: "prosa::io::bridge::proc", addr = addr.to_string(), "Remote ProSA connected");
+
+    let codec = LengthPrefixedCodec::<4>::default();
+    let (mut reader, mut writer) = tokio::io::split(stream);
+    let mut buffer = BytesMut::with_capacity(8192);
+    let (frames_tx, mut frames_rx) = mpsc::channel::<Bytes>(256);
+    let reason: Option<BridgeError> = loop {
+        tokio::select! {
+            biased;
+            _ = async { let _ = shutdown.wait_for(|shutdown| *shutdown).await; } => break None,
+            Some(frame) = frames_rx.recv() => {
+                if let Err(e) = codec.write(&mut writer, &frame).await {
+                    break Some(e.into());
+                }
+            }
+            frame = codec.read(&mut reader, &mut buffer) => match frame {
+                Ok(Some(frame)) => match settings.decode_frame::<M>(&frame) {
+                    Ok(BridgeFrame::Request { id, service, data }) => {
+                        let service_call = settings
+                            .exported_services
+                            .contains(&service)
+                            .then(|| ServiceCall::new(services.borrow().clone(), service.clone()).timeout(settings.service_timeout));
+                        tokio::spawn(call_exported_service(id, service, data, service_call, settings.clone(), frames_tx.clone()));
+                    }
+                    Ok(frame) => break Some(BridgeError::Protocol(format!("unexpected frame {} from a remote ProSA", frame.get_id()))),
+                    Err(e) => break Some(e),
+                },
+                Ok(None) => break Some(io::Error::from(io::ErrorKind::UnexpectedEof).into()),
+                Err(e) => break Some(e.into()),
+            },
+        }
+    };
+
+    if let Some(reason) = reason {
+        debug!(name: "remote_bridge_proc", target: "prosa::io::bridge::proc", addr = addr.to_string(), "Remote ProSA disconnected: {}", reason);
+    } else {
+        let _ = writer.shutdown().await;
+        debug!(name: "remote_bridge_proc", target: "prosa::io::bridge::proc", addr = addr.to_string(), "Remote ProSA disconnected on shutdown");
+    }
+}
+
+/// Method to call an exported service for a remote ProSA, and queue its response frame
+async fn call_exported_service<M>(
+    id: u64,
+    service: String,
+    data: M,
+    service_call: Option<ServiceCall<M>>,
+    settings: Arc<BridgeSettings>,
+    frames: mpsc::Sender<Bytes>,
+) where
+    M: 'static
+        + std::marker::Send
+        + std::marker::Sync
+        + std::marker::Sized
+        + std::clone::Clone
+        + std::fmt::Debug
+        + prosa_utils::msg::tvf::Tvf
+        + std::default::Default,
+{
+    let response = match service_call {
+        Some(service_call) => service_call.send(data).await,
+        None => Err(ServiceError::UnknownService(service)),
+    };
+    let frame = match response {
+        Ok(data) => BridgeFrame::Response { id, data },
+        Err(err) => {
+            let mut data = M::default();
+            err.encode(&mut data);
+            BridgeFrame::Error { id, data }
+        }
+    };
+
+    match settings.encode_frame(&frame) {
+        Ok(payload) => {
+            let _ = frames.send(payload).await;
+        }
+        Err(e) => {
+            warn!(name: "remote_bridge_proc", target: "prosa::io::bridge::proc", "Can't encode the response {}: {}", id, e);
+        }
+    }
+}
+
+/// Bridge processor to forward services between ProSA instances
+///
+/// ```
+/// use prosa::core::main::{MainProc, MainRunnable};
+/// use prosa::core::proc::{proc, Proc, ProcBusParam, ProcConfig};
+/// use prosa::io::bridge::adaptor::BridgeDefaultAdaptor;
+/// use prosa::io::bridge::proc::{BridgeProc, BridgeSettings};
+/// use prosa::io::listener::ListenerSetting;
+/// use prosa::io::stream::TargetSetting;
+/// use prosa_utils::msg::simple_string_tvf::SimpleStringTvf;
+/// use prosa::core::settings::settings;
+/// use serde::Serialize;
+/// use url::Url;
+///
+/// // Main settings
+/// #[settings]
+/// #[derive(Default, Debug, Serialize)]
+/// struct Settings {}
+///
+/// // Create bus and main processor
+/// let settings = Settings::default();
+/// let (bus, main) = MainProc::<SimpleStringTvf>::create(&settings);
+///
+/// // Launch the main task
+/// let main_task = main.run();
+///
+/// // Export the local `PAYMENT` service, and import the `FRAUD` service of a remote ProSA
+/// let mut bridge_settings = BridgeSettings::default();
+/// bridge_settings.set_listener(ListenerSetting::from(Url::parse("tcp://0.0.0.0:9090").unwrap()), vec![String::from("PAYMENT")]);
+/// bridge_settings.set_target(TargetSetting::from(Url::parse("tcp://fraud-prosa:9090").unwrap()), vec![String::from("FRAUD")]);
+/// let bridge_proc = BridgeProc::<SimpleStringTvf>::create(1, bus.clone(), bridge_settings);
+/// // Proc::<BridgeDefaultAdaptor>::run(bridge_proc, String::from("BRIDGE_PROC"));
+///
+/// // Wait on main task
+/// //main_task.join().unwrap();
+/// ```
+#[proc(settings = prosa::io::bridge::proc::BridgeSettings)]
+pub struct BridgeProc {}
+
+#[proc]
+impl BridgeProc {
+    /// Method to return a service error to the requester with the error encoded in the returned message
+    async fn return_service_error(
+        msg: RequestMsg<M>,
+        err: ServiceError,
+    ) -> Result<(), SendError<InternalMsg<M>>> {
+        let mut data = msg.get_data().clone();
+        err.encode(&mut data);
+        msg.return_error_to_sender(Some(data), err).await
+    }
+}
+
+#[proc]
+impl<A> Proc<A> for BridgeProc
+where
+    A: Adaptor + BridgeAdaptor<M> + std::marker::Send + std::marker::Sync,
+{
+    async fn internal_run(&mut self, name: String) -> Result<(), Box<dyn std::error::Error>> {
+        // Initiate an adaptor for the bridge processor
+        let mut adaptor = A::new(self)?;
+        adaptor.async_init(&self.proc).await?;
+
+        // Declare the processor
+        self.proc.add_proc().await?;
+
+        let settings = Arc::new(self.settings.clone());
+        let (services_tx, services_rx) = watch::channel(self.service.clone());
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+
+        // Accept the remote ProSAs on the listener
+        let (accepted_queue, mut accepted_rx) = mpsc::channel(16);
+        let listener = if let Some(listener_setting) = &self.settings.listener {
+            let listener = Arc::new(listener_setting.bind_multi().await?);
+            info!(name: "bridge_proc", target: "prosa::io::bridge::proc", proc_name = name, listener = listener.to_string(), exported_services = format!("{:?}", self.settings.exported_services), "Listening");
+            let accept_listener = listener.clone();
+            tokio::spawn(async move {
+                loop {
+                    match accept_listener.accept_raw().await {
+                        Ok(accepted) => {
+                            if accepted_queue.send(accepted).await.is_err() {
+                                return;
+                            }
+                        }
+                        Err(e) if ListenerClosed::is(&e) => return,
+                        Err(e) => {
+                            warn!(name: "accept_bridge_proc", target: "prosa::io::bridge::proc", listener = accept_listener.to_string(), "Can't accept a remote ProSA: {}", e);
+                        }
+                    }
+                }
+            });
+            Some(listener)
+        } else {
+            drop(accepted_queue);
+            None
+        };
+        let mut remotes = JoinSet::new();
+
+        // Connect to the target
+        let (link_events_tx, mut link_events_rx) = mpsc::channel(256);
+        let link_task: Option<JoinHandle<()>> = self.settings.target.clone().map(|mut target| {
+            target.init_ssl_context();
+            tokio::spawn(run_link(target, self.settings.clone(), link_events_tx))
+        });
+        let mut link: Option<mpsc::Sender<Bytes>> = None;
+        let mut pending_msgs: PendingMsgs<RequestMsg<M>, M> = Default::default();
+        let mut msg_id: u64 = 0;
+
+        // The listener is bound, the link to the target is established in background
+        self.proc.set_ready().await?;
+
+        loop {
+            tokio::select! {
+                Some(msg) = self.internal_rx_queue.recv() => {
+                    match msg {
+                        InternalMsg::Request(msg) if msg.is_expired() => {
+                            self.internal_rx_queue.record_expired(msg.get_service());
+                            let err = msg.get_expiration_error().unwrap();
+                            drop_unreturned!(&name, Self::return_service_error(msg, err).await)
+                        }
+                        InternalMsg::Request(msg) => {
+                            let frame = BridgeFrame::Request {
+                                id: msg_id,
+                                service: msg.get_service().clone(),
+                                data: msg.get_data().clone(),
+                            };
+                            match self.settings.encode_frame(&frame) {
+                                Err(e) => {
+                                    debug!(name: "bridge_proc", target: "prosa::io::bridge::proc", parent: msg.get_span(), proc_name = name, service = msg.get_service(), "Can't encode the request: {}", e);
+                                    let err = ServiceError::ProtocolError { code: 0, reason: e.to_string() };
+                                    drop_unreturned!(&name, Self::return_service_error(msg, err).await)
+                                }
+                                Ok(payload) => if let Some(link) = link.as_ref().filter(|_| self.settings.imported_services.contains(msg.get_service())) {
+                                    if link.send(payload).await.is_ok() {
+                                        let timeout = msg.get_remaining_time().map_or(self.settings.service_timeout, |remaining| remaining.min(self.settings.service_timeout));
+                                        pending_msgs.push_with_id(msg_id, msg, timeout);
+                                        msg_id += 1;
+                                    } else {
+                                        let err = ServiceError::Unavailable(msg.get_service().clone(), None);
+                                        drop_unreturned!(&name, Self::return_service_error(msg, err).await)
+                                    }
+                                } else {
+                                    let err = ServiceError::Unavailable(msg.get_service().clone(), None);
+                                    drop_unreturned!(&name, Self::return_service_error(msg, err).await)
+                                },
+                            }
+                        }
+                        // Requests of the remote ProSA are answered by the remote tasks, a late or stray response is dropped
+                        InternalMsg::Response(msg) => {
+                            warn!(name: "bridge_proc", target: "prosa::io::bridge::proc", parent: msg.get_span(), proc_name = name, service = msg.get_service(), "Drop an unexpected response");
+                        }
+                        InternalMsg::Error(err) => {
+                            warn!(name: "bridge_proc", target: "prosa::io::bridge::proc", parent: err.get_span(), proc_name = name, service = err.get_service(), error = err.get_err().to_string(), "Drop an unexpected error");
+                        }
+                        InternalMsg::Command(command) => {
+                            debug!(name: "bridge_proc", target: "prosa::io::bridge::proc", proc_name = name, command = command, "Ignore an unsupported command");
+                        }
+                        // Batches are split by the processor queue
+                        InternalMsg::RequestBatch(_) | InternalMsg::ResponseBatch(_) => {}
+                        InternalMsg::Ping(_) => {}
+                        InternalMsg::Config(config) => {
+                            // Links are kept, other settings apply to the next requests
+                            // The adaptor keeps its configuration if the settings can't be reloaded
+                            match self.reload_settings(&config, &name) {
+                                Ok(()) => self.reload_adaptor(&mut adaptor, &name),
+                                Err(e) => {
+                                    warn!(name: "config_bridge_proc", target: "prosa::io::bridge::proc", proc_name = name, "Can't reload the settings: {}", e);
+                                }
+                            }
+                        }
+                        InternalMsg::Service(table) => {
+                            self.service = table.clone();
+                            services_tx.send_replace(table);
+                        }
+                        InternalMsg::Shutdown => {
+                            self.proc.set_stopping().await?;
+                            if let Some(link_task) = link_task {
+                                link_task.abort();
+                            }
+                            for msg in pending_msgs.drain() {
+                                let err = ServiceError::Unavailable(msg.get_service().clone(), None);
+                                drop_unreturned!(&name, Self::return_service_error(msg, err).await)
+                            }
+
+                            if let Some(listener) = &listener {
+                                listener.close_accept();
+                            }
+                            shutdown_tx.send_replace(true);
+                            while remotes.join_next().await.is_some() {}
+
+                            adaptor.async_terminate().await;
+                            self.proc.remove_proc().await?;
+                            return Ok(());
+                        }
+                    }
+                },
+                Some(event) = link_events_rx.recv() => {
+                    match event {
+                        LinkEvent::Up(frames) => {
+                            info!(name: "link_bridge_proc", target: "prosa::io::bridge::proc", proc_name = name, imported_services = format!("{:?}", self.settings.imported_services), "Link to the target is up");
+                            link = Some(frames);
+                            self.proc.add_service_proc(self.settings.imported_services.clone()).await?;
+                            adaptor.on_link_up();
+                        }
+                        LinkEvent::Frame(BridgeFrame::Response { id, data }) => {
+                            if let Some(msg) = pending_msgs.pull_msg(id) {
+                                drop_unreturned!(&name, msg.return_to_sender(data).await)
+                            }
+                        }
+                        LinkEvent::Frame(BridgeFrame::Error { id, data }) => {
+                            if let Some(msg) = pending_msgs.pull_msg(id) {
+                                let err = ServiceError::decode(&data).ok().flatten().unwrap_or_else(|| {
+                                    ServiceError::Internal(String::from("undecodable error of the remote ProSA"))
+                                });
+                                drop_unreturned!(&name, msg.return_error_to_sender(Some(data), err).await)
+                            }
+                        }
+                        LinkEvent::Frame(BridgeFrame::Request { id, service, .. }) => {
+                            warn!(name: "link_bridge_proc", target: "prosa::io::bridge::proc", proc_name = name, service = service, "The target send an unexpected request {}", id);
+                        }
+                        LinkEvent::Down(err) => {
+                            warn!(name: "link_bridge_proc", target: "prosa::io::bridge::proc", proc_name = name, "Link to the target is down: {}", err);
+                            link = None;
+                            self.proc.remove_service_proc(self.settings.imported_services.clone()).await?;
+                            for msg in pending_msgs.drain() {
+                                let err = ServiceError::Unavailable(msg.get_service().clone(), None);
+                                drop_unreturned!(&name, Self::return_service_error(msg, err).await)
+                            }
+                            adaptor.on_link_down(&err);
+                        }
+                    }
+                },
+                Some((stream, addr)) = accepted_rx.recv() => {
+                    remotes.spawn(serve_remote(
+                        listener.clone().unwrap(),
+                        stream,
+                        addr,
+                        settings.clone(),
+                        services_rx.clone(),
+                        shutdown_rx.clone(),
+                    ));
+                },
+                Some(msg) = pending_msgs.pull(), if !pending_msgs.is_empty() => {
+                    let err = ServiceError::Timeout(msg.get_service().clone(), self.settings.service_timeout.as_millis() as u64);
+                    drop_unreturned!(&name, Self::return_service_error(msg, err).await)
+                },
+                Some(_) = remotes.join_next(), if !remotes.is_empty() => {},
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        error::Error,
+        sync::atomic::{AtomicU32, Ordering},
+    };
+
+    use prosa_macros::{settings, Adaptor};
+    use prosa_utils::msg::{codec::Codec, simple_string_tvf::SimpleStringTvf};
+    use url::Url;
+
+    use super::*;
+    use crate::{
+        core::{
+            main::{MainProc, MainRunnable as _},
+            proc::ProcConfig as _,
+        },
+        inj::{
+            adaptor::InjAdaptor,
+            proc::{InjProc, InjSettings},
+        },
+        io::bridge::adaptor::BridgeDefaultAdaptor,
+        stub::{
+            adaptor::StubAdaptor,
+            proc::{StubProc, StubSettings},
+        },
+    };
+
+    const BRIDGE_SERVICE: &str = "BRIDGE_REMOTE";
+    static STUB_REQUESTS: AtomicU32 = AtomicU32::new(0);
+    static INJ_RESPONSES: AtomicU32 = AtomicU32::new(0);
+
+    #[settings]
+    #[derive(Default, Debug, Serialize)]
+    struct TestSettings {}
+
+    #[derive(Adaptor)]
+    struct CountStubAdaptor {}
+
+    impl<M> StubAdaptor<M> for CountStubAdaptor
+    where
+        M: 'static
+            + std::marker::Send
+            + std::marker::Sync
+            + std::marker::Sized
+            + std::clone::Clone
+            + std::fmt::Debug
+            + prosa_utils::msg::tvf::Tvf
+            + std::default::Default,
+    {
+        fn new(_proc: &StubProc<M>) -> Result<Self, Box<dyn Error>> {
+            Ok(Self {})
+        }
+
+        fn process_request(&mut self, _service_name: &str, request: &M) -> Result<M, ServiceError> {
+            let mut response = request.clone();
+            response.put_unsigned(2, STUB_REQUESTS.fetch_add(1, Ordering::Relaxed) as u64);
+            Ok(response)
+        }
+    }
+
+    #[derive(Adaptor)]
+    struct CountInjAdaptor {}
+
+    impl<M> InjAdaptor<M> for CountInjAdaptor
+    where
+        M: 'static
+            + std::marker::Send
+            + std::marker::Sync
+            + std::marker::Sized
+            + std::clone::Clone
+            + std::fmt::Debug
+            + prosa_utils::msg::tvf::Tvf
+            + std::default::Default,
+    {
+        fn new(_proc: &InjProc<M>) -> Result<Self, Box<dyn Error>> {
+            Ok(Self {})
+        }
+
+        fn build_transaction(&mut self) -> M {
+            let mut msg = M::default();
+            msg.put_string(1, "BRIDGED");
+            msg
+        }
+
+        fn process_response(
+            &mut self,
+            response: &M,
+            _service_name: &str,
+        ) -> Result<(), Box<dyn Error>> {
+            assert_eq!("BRIDGED", response.get_string(1)?.as_str());
+            assert!(response.contains(2));
+            INJ_RESPONSES.fetch_add(1, Ordering::Relaxed);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn bridge_inj_stub() {
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let url = Url::parse(&format!("tcp://127.0.0.1:{}", port)).unwrap();
+
+        // ProSA A with the injector, that import the remote service through its bridge
+        let (bus_a, main_a) = MainProc::<SimpleStringTvf>::create(&TestSettings::default());
+        let main_a_task = main_a.run();
+
+        // Every frame is compressed on the link
+        let compression = Compression::new(Codec::Zstd, 0);
+
+        let mut bridge_settings = BridgeSettings::default();
+        bridge_settings.set_compression(compression.clone());
+        bridge_settings.set_target(
+            TargetSetting::from(url.clone()),
+            vec![BRIDGE_SERVICE.into()],
+        );
+        bridge_settings.set_reconnect_delay(Duration::from_millis(50), Duration::from_millis(200));
+        let bridge_proc = BridgeProc::<SimpleStringTvf>::create(1, bus_a.clone(), bridge_settings);
+        Proc::<BridgeDefaultAdaptor>::run(bridge_proc, String::from("BRIDGE_PROC_A"));
+
+        let inj_proc = InjProc::<SimpleStringTvf>::create(
+            2,
+            bus_a.clone(),
+            InjSettings::new(BRIDGE_SERVICE.into()),
+        );
+        Proc::<CountInjAdaptor>::run(inj_proc, String::from("INJ_PROC"));
+
+        // Nothing is injected while the remote ProSA is unreachable
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert_eq!(0, INJ_RESPONSES.load(Ordering::Relaxed));
+
+        // ProSA B with the stub, that export its service through its bridge
+        let (bus_b, main_b) = MainProc::<SimpleStringTvf>::create(&TestSettings::default());
+        let main_b_task = main_b.run();
+
+        let stub_proc = StubProc::<SimpleStringTvf>::create(
+            1,
+            bus_b.clone(),
+            StubSettings::new(vec![BRIDGE_SERVICE.into()]),
+        );
+        Proc::<CountStubAdaptor>::run(stub_proc, String::from("STUB_PROC"));
+
+        let mut bridge_settings = BridgeSettings::default();
+        bridge_settings.set_compression(compression);
+        bridge_settings.set_listener(ListenerSetting::from(url), vec![BRIDGE_SERVICE.into()]);
+        let bridge_proc = BridgeProc::<SimpleStringTvf>::create(2, bus_b.clone(), bridge_settings);
+        Proc::<BridgeDefaultAdaptor>::run(bridge_proc, String::from("BRIDGE_PROC_B"));
+
+        // Transactions flow from the injector to the stub once the link is up
+        tokio::time::timeout(Duration::from_secs(10), async {
+            while INJ_RESPONSES.load(Ordering::Relaxed) < 5 {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+        })
+        .await
+        .expect("The transactions don't flow through the bridge");
+        assert!(STUB_REQUESTS.load(Ordering::Relaxed) >= INJ_RESPONSES.load(Ordering::Relaxed));
+
+        bus_a.stop("ProSA unit test end".into()).await.unwrap();
+        bus_b.stop("ProSA unit test end".into()).await.unwrap();
+        main_a_task.join().unwrap();
+        main_b_task.join().unwrap();
+    }
+}
@@ -0,0 +1,355 @@
+//! Module that resolve the addresses of network targets, with a cache of the resolutions
+//!
+//! A [`TargetSetting`](super::stream::TargetSetting) is resolved again at each connection, so a partner that changes its DNS records is followed on reconnect.
+//! To not hammer the resolver during reconnection storms, resolutions are kept in a [`DnsCache`] shared by all the targets: successful ones for `cache_ttl`, failed ones for `negative_cache_ttl`.
+//! The resolved addresses are then tried in order (or shuffled), each one within its attempt timeout, until a connection succeed.
+//!
+//! ```yaml
+//! target:
+//!   url: "tcp://partner.example.com:4000"
+//!   connect_timeout: 3000
+//!   resolve:
+//!     shuffle: true
+//!     attempt_timeout: 1000
+//!     cache_ttl: 30000
+//!     negative_cache_ttl: 2000
+//! ```
+
+use std::{
+    collections::HashMap,
+    hash::{BuildHasher as _, RandomState},
+    io,
+    net::{IpAddr, SocketAddr},
+    sync::{LazyLock, Mutex},
+    time::{Duration, Instant},
+};
+
+use prosa_utils::time::Clock;
+use serde::{Deserialize, Serialize};
+use tokio::net::TcpStream;
+use tracing::debug;
+
+/// Resolution and failover settings of a target
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ResolveSettings {
+    /// Shuffle the resolved addresses before trying them, to spread the connections of several clients
+    #[serde(default)]
+    pub shuffle: bool,
+    /// Timeout in milliseconds of the connection to each address.
+    /// By default the connect timeout of the target is shared between its resolved addresses
+    pub attempt_timeout: Option<u32>,
+    /// Duration in milliseconds a successful resolution is cached (`0` to resolve at each connection)
+    #[serde(default = "ResolveSettings::get_default_cache_ttl")]
+    pub cache_ttl: u32,
+    /// Duration in milliseconds a failed resolution is cached (`0` to resolve at each connection)
+    #[serde(default = "ResolveSettings::get_default_negative_cache_ttl")]
+    pub negative_cache_ttl: u32,
+}
+
+impl ResolveSettings {
+    fn get_default_cache_ttl() -> u32 {
+        5000
+    }
+
+    fn get_default_negative_cache_ttl() -> u32 {
+        1000
+    }
+
+    /// Getter of the timeout of each connection attempt, out of the target connect timeout and its number of addresses
+    pub fn get_attempt_timeout(&self, connect_timeout: Duration, addr_count: usize) -> Duration {
+        self.attempt_timeout
+            .map(|t| Duration::from_millis(t as u64))
+            .unwrap_or(connect_timeout / addr_count.max(1) as u32)
+    }
+}
+
+impl Default for ResolveSettings {
+    fn default() -> Self {
+        ResolveSettings {
+            shuffle: false,
+            attempt_timeout: None,
+            cache_ttl: Self::get_default_cache_ttl(),
+            negative_cache_ttl: Self::get_default_negative_cache_ttl(),
+        }
+    }
+}
+
+/// Cached resolution of a host
+#[derive(Debug, Clone)]
+struct DnsEntry {
+    addrs: Result<Vec<SocketAddr>, (io::ErrorKind, String)>,
+    expire: Instant,
+}
+
+/// Cache of the host resolutions, with a positive and a negative TTL
+///
+/// ```
+/// use std::time::{Duration, Instant};
+/// use prosa::io::dns::{DnsCache, ResolveSettings};
+/// use prosa_utils::time::SystemClock;
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let cache = DnsCache::default();
+/// let addr = "127.0.0.1:8080".parse().unwrap();
+/// cache.insert("partner.example.com", 8080, vec![addr], Instant::now() + Duration::from_secs(60));
+///
+/// let addrs = cache.resolve("partner.example.com", 8080, &ResolveSettings::default(), &SystemClock).await.unwrap();
+/// assert_eq!(vec![addr], addrs);
+/// # }
+/// ```
+#[derive(Debug, Default)]
+pub struct DnsCache {
+    entries: Mutex<HashMap<(String, u16), DnsEntry>>,
+}
+
+/// Cache shared by all the targets
+static GLOBAL_DNS_CACHE: LazyLock<DnsCache> = LazyLock::new(DnsCache::default);
+
+impl DnsCache {
+    /// Getter of the cache shared by all the targets
+    pub fn global() -> &'static DnsCache {
+        &GLOBAL_DNS_CACHE
+    }
+
+    /// Method to set the addresses of a host until an instant (to pin a host, or to test a target)
+    pub fn insert(&self, host: &str, port: u16, addrs: Vec<SocketAddr>, expire: Instant) {
+        self.entries.lock().unwrap().insert(
+            (host.to_string(), port),
+            DnsEntry {
+                addrs: Ok(addrs),
+                expire,
+            },
+        );
+    }
+
+    /// Method to remove the cached resolution of a host
+    pub fn remove(&self, host: &str, port: u16) {
+        self.entries
+            .lock()
+            .unwrap()
+            .remove(&(host.to_string(), port));
+    }
+
+    /// Getter of the number of cached resolutions (expired ones included)
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    /// Method to know if the cache is empty
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Method to get the cached resolution of a host, if it's not expired
+    fn get(&self, host: &str, port: u16, now: Instant) -> Option<io::Result<Vec<SocketAddr>>> {
+        let mut entries = self.entries.lock().unwrap();
+        let key = (host.to_string(), port);
+        match entries.get(&key) {
+            Some(entry) if entry.expire > now => Some(
+                entry
+                    .addrs
+                    .clone()
+                    .map_err(|(kind, msg)| io::Error::new(kind, msg)),
+            ),
+            Some(_) => {
+                entries.remove(&key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    /// Method to resolve the addresses of a host, from the cache if the resolution is still valid.
+    /// IP addresses are never cached
+    pub async fn resolve<C>(
+        &self,
+        host: &str,
+        port: u16,
+        settings: &ResolveSettings,
+        clock: &C,
+    ) -> io::Result<Vec<SocketAddr>>
+    where
+        C: Clock,
+    {
+        let ip_host = host.trim_start_matches('[').trim_end_matches(']');
+        if let Ok(ip) = ip_host.parse::<IpAddr>() {
+            return Ok(vec![SocketAddr::new(ip, port)]);
+        }
+
+        if let Some(addrs) = self.get(host, port, clock.now()) {
+            return addrs;
+        }
+
+        let addrs = match tokio::net::lookup_host((host, port)).await {
+            Ok(addrs) => {
+                let addrs: Vec<SocketAddr> = addrs.collect();
+                if addrs.is_empty() {
+                    Err(io::Error::new(
+                        io::ErrorKind::NotFound,
+                        format!("No address resolved for {}:{}", host, port),
+                    ))
+                } else {
+                    Ok(addrs)
+                }
+            }
+            Err(e) => Err(io::Error::new(
+                e.kind(),
+                format!("Can't resolve {}:{}: {}", host, port, e),
+            )),
+        };
+
+        let ttl = if addrs.is_ok() {
+            settings.cache_ttl
+        } else {
+            settings.negative_cache_ttl
+        };
+        if ttl > 0 {
+            self.entries.lock().unwrap().insert(
+                (host.to_string(), port),
+                DnsEntry {
+                    addrs: addrs
+                        .as_ref()
+                        .map(Vec::clone)
+                        .map_err(|e| (e.kind(), e.to_string())),
+                    expire: clock.now() + Duration::from_millis(ttl as u64),
+                },
+            );
+        }
+
+        addrs
+    }
+}
+
+/// Method to shuffle the resolved addresses
+pub(crate) fn shuffle(addrs: &mut [SocketAddr]) {
+    // Seeded by the random keys of the std hasher, enough to spread the connections
+    let mut state = RandomState::new().hash_one(addrs.len()) | 1;
+    for i in (1..addrs.len()).rev() {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        addrs.swap(i, (state % (i as u64 + 1)) as usize);
+    }
+}
+
+/// Method to connect a TCP stream to the first reachable address, each one within the attempt timeout
+///
+/// The error of the last attempt is returned if no address is reachable.
+pub async fn connect_addrs<C>(
+    addrs: &[SocketAddr],
+    attempt_timeout: Duration,
+    clock: &C,
+) -> io::Result<TcpStream>
+where
+    C: Clock,
+{
+    let mut last_error = io::Error::new(io::ErrorKind::InvalidInput, "No address to connect to");
+    for addr in addrs {
+        match clock
+            .timeout(attempt_timeout, TcpStream::connect(addr))
+            .await
+        {
+            Ok(Ok(stream)) => {
+                debug!("Connected to {}", addr);
+                return Ok(stream);
+            }
+            Ok(Err(e)) => {
+                debug!("Can't connect to {}: {}", addr, e);
+                last_error = e;
+            }
+            Err(_) => {
+                debug!(
+                    "Can't connect to {} within {} ms",
+                    addr,
+                    attempt_timeout.as_millis()
+                );
+                last_error = io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    format!(
+                        "Can't connect to {} within {} ms",
+                        addr,
+                        attempt_timeout.as_millis()
+                    ),
+                );
+            }
+        }
+    }
+
+    Err(last_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use prosa_utils::time::MockClock;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn dns_cache_ttl() {
+        let clock = MockClock::new();
+        let cache = DnsCache::default();
+        let settings = ResolveSettings {
+            cache_ttl: 1000,
+            negative_cache_ttl: 100,
+            ..Default::default()
+        };
+
+        // IP addresses are not cached
+        assert_eq!(
+            vec!["[::1]:80".parse::<SocketAddr>().unwrap()],
+            cache.resolve("[::1]", 80, &settings, &clock).await.unwrap()
+        );
+        assert!(cache.is_empty());
+
+        let addr: SocketAddr = "127.0.0.1:80".parse().unwrap();
+        cache.insert(
+            "prosa.invalid",
+            80,
+            vec![addr],
+            clock.now() + Duration::from_millis(1000),
+        );
+        assert_eq!(
+            vec![addr],
+            cache
+                .resolve("prosa.invalid", 80, &settings, &clock)
+                .await
+                .unwrap()
+        );
+
+        // Once expired, the host is resolved again and the failure is cached
+        clock.advance(Duration::from_millis(1000));
+        assert!(cache
+            .resolve("prosa.invalid", 80, &settings, &clock)
+            .await
+            .is_err());
+        assert_eq!(1, cache.len());
+        assert!(cache
+            .get("prosa.invalid", 80, clock.now())
+            .unwrap()
+            .is_err());
+        clock.advance(Duration::from_millis(100));
+        assert!(cache.get("prosa.invalid", 80, clock.now()).is_none());
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn dns_shuffle() {
+        let addrs: Vec<SocketAddr> = (1..=8)
+            .map(|port| SocketAddr::from(([127, 0, 0, 1], port)))
+            .collect();
+        let mut shuffled = addrs.clone();
+        shuffle(&mut shuffled);
+        shuffled.sort();
+        assert_eq!(addrs, shuffled);
+
+        assert_eq!(
+            Duration::from_millis(1250),
+            ResolveSettings::default().get_attempt_timeout(Duration::from_millis(5000), 4)
+        );
+        assert_eq!(
+            Duration::from_millis(5000),
+            ResolveSettings::default().get_attempt_timeout(Duration::from_millis(5000), 0)
+        );
+    }
+}
@@ -0,0 +1,465 @@
+//! Module that define frame codecs that could be use by a ProSA processor
+use std::{fmt, io};
+
+use bytes::{Buf as _, BufMut as _, Bytes, BytesMut};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::io::{AsyncRead, AsyncReadExt as _, AsyncWrite, AsyncWriteExt as _};
+
+use super::IO;
+
+/// Default maximum frame size (4 MiB) if the header allow it
+pub const DEFAULT_MAX_FRAME_SIZE: usize = 4 * 1024 * 1024;
+
+/// Error define for frame codecs
+#[derive(Debug, Error)]
+pub enum FrameError {
+    /// Error on the underlying stream
+    #[error("Frame IO error: {0}")]
+    Io(#[from] io::Error),
+    /// Error that indicate that a frame exceed the maximum allowed size
+    #[error("Frame of {0} bytes exceed the maximum size of {1} bytes")]
+    FrameTooLarge(usize, usize),
+    /// Error that indicate that the connection was closed in the middle of a frame
+    #[error("Connection reset by peer with {0} pending bytes")]
+    ConnectionReset(usize),
+}
+
+/// Endianness of the length header
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum Endianness {
+    /// Big endian (network order)
+    #[default]
+    Big,
+    /// Little endian
+    Little,
+}
+
+/// Codec for frames prefixed by their length on `HDR` bytes (1, 2 or 4)
+///
+/// ```
+/// use bytes::BytesMut;
+/// use prosa::io::frame::{Endianness, LengthPrefixedCodec};
+///
+/// let codec = LengthPrefixedCodec::<2>::new(Endianness::Big, 1024);
+/// let mut buffer = BytesMut::from(&[0x00, 0x05, b'P', b'r', b'o', b'S', b'A', 0x00][..]);
+/// assert_eq!(Some(b"ProSA".as_slice()), codec.parse(&mut buffer).unwrap().as_deref());
+///
+/// // Incomplete frame stay in the buffer
+/// assert_eq!(None, codec.parse(&mut buffer).unwrap());
+/// assert_eq!(1, buffer.len());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LengthPrefixedCodec<const HDR: usize> {
+    endianness: Endianness,
+    max_frame_size: usize,
+}
+
+impl<const HDR: usize> LengthPrefixedCodec<HDR> {
+    /// Size of the header checked at compile time
+    const HEADER_SIZE: usize = {
+        assert!(
+            HDR == 1 || HDR == 2 || HDR == 4,
+            "Length prefixed header size must be 1, 2 or 4 bytes"
+        );
+        HDR
+    };
+
+    /// Method to create a length prefixed codec.
+    /// The maximum frame size is bounded by the maximum length that the header can carry
+    pub fn new(endianness: Endianness, max_frame_size: usize) -> LengthPrefixedCodec<HDR> {
+        LengthPrefixedCodec {
+            endianness,
+            max_frame_size: max_frame_size.min(Self::header_max_size()),
+        }
+    }
+
+    /// Getter of the maximum length that the header can carry
+    pub const fn header_max_size() -> usize {
+        match Self::HEADER_SIZE {
+            1 => u8::MAX as usize,
+            2 => u16::MAX as usize,
+            _ => u32::MAX as usize,
+        }
+    }
+
+    /// Getter of the header endianness
+    pub fn endianness(&self) -> Endianness {
+        self.endianness
+    }
+
+    /// Getter of the maximum frame size
+    pub fn max_frame_size(&self) -> usize {
+        self.max_frame_size
+    }
+
+    /// Method to decode the frame length from the header
+    fn decode_len(&self, mut header: &[u8]) -> usize {
+        match (Self::HEADER_SIZE, self.endianness) {
+            (1, _) => header.get_u8() as usize,
+            (2, Endianness::Big) => header.get_u16() as usize,
+            (2, Endianness::Little) => header.get_u16_le() as usize,
+            (_, Endianness::Big) => header.get_u32() as usize,
+            (_, Endianness::Little) => header.get_u32_le() as usize,
+        }
+    }
+
+    /// Method to encode the frame length into a header
+    fn encode_len(&self, len: usize, dst: &mut BytesMut) {
+        match (Self::HEADER_SIZE, self.endianness) {
+            (1, _) => dst.put_u8(len as u8),
+            (2, Endianness::Big) => dst.put_u16(len as u16),
+            (2, Endianness::Little) => dst.put_u16_le(len as u16),
+            (_, Endianness::Big) => dst.put_u32(len as u32),
+            (_, Endianness::Little) => dst.put_u32_le(len as u32),
+        }
+    }
+
+    /// Method to parse a frame from the buffer.
+    /// Return `None` if the buffer doesn't contain a complete frame yet (partial data stay in the buffer)
+    pub fn parse(&self, buffer: &mut BytesMut) -> Result<Option<Bytes>, FrameError> {
+        if buffer.len() < Self::HEADER_SIZE {
+            return Ok(None);
+        }
+
+        let frame_len = self.decode_len(&buffer[..Self::HEADER_SIZE]);
+        if frame_len > self.max_frame_size {
+            return Err(FrameError::FrameTooLarge(frame_len, self.max_frame_size));
+        }
+
+        if buffer.len() < Self::HEADER_SIZE + frame_len {
+            // Reserve the missing space for the rest of the frame
+            buffer.reserve(Self::HEADER_SIZE + frame_len - buffer.len());
+            Ok(None)
+        } else {
+            buffer.advance(Self::HEADER_SIZE);
+            Ok(Some(buffer.split_to(frame_len).freeze()))
+        }
+    }
+
+    /// Method to encode a complete frame (header + payload) into the buffer
+    pub fn encode(&self, frame: &[u8], dst: &mut BytesMut) -> Result<(), FrameError> {
+        if frame.len() > self.max_frame_size {
+            return Err(FrameError::FrameTooLarge(frame.len(), self.max_frame_size));
+        }
+
+        dst.reserve(Self::HEADER_SIZE + frame.len());
+        self.encode_len(frame.len(), dst);
+        dst.put_slice(frame);
+        Ok(())
+    }
+
+    /// Method to read a complete frame from the stream.
+    /// Return `None` if the stream is closed properly (without pending data)
+    pub async fn read<S>(
+        &self,
+        stream: &mut S,
+        buffer: &mut BytesMut,
+    ) -> Result<Option<Bytes>, FrameError>
+    where
+        S: AsyncRead + Unpin,
+    {
+        loop {
+            if let Some(frame) = self.parse(buffer)? {
+                return Ok(Some(frame));
+            }
+
+            if stream.read_buf(buffer).await? == 0 {
+                return if buffer.is_empty() {
+                    Ok(None)
+                } else {
+                    Err(FrameError::ConnectionReset(buffer.len()))
+                };
+            }
+        }
+    }
+
+    /// Method to write a complete frame (header + payload) on the stream
+    pub async fn write<S>(&self, stream: &mut S, frame: &[u8]) -> Result<(), FrameError>
+    where
+        S: AsyncWrite + Unpin,
+    {
+        if frame.len() > self.max_frame_size {
+            return Err(FrameError::FrameTooLarge(frame.len(), self.max_frame_size));
+        }
+
+        let mut header = BytesMut::with_capacity(Self::HEADER_SIZE);
+        self.encode_len(frame.len(), &mut header);
+        stream.write_all(&header).await?;
+        stream.write_all(frame).await?;
+        stream.flush().await?;
+        Ok(())
+    }
+}
+
+impl<const HDR: usize> Default for LengthPrefixedCodec<HDR> {
+    fn default() -> Self {
+        Self::new(Endianness::default(), DEFAULT_MAX_FRAME_SIZE)
+    }
+}
+
+/// ProSA IO that exchange length prefixed frames over any stream
+///
+/// ```
+/// use tokio::io;
+/// use prosa::io::IO as _;
+/// use prosa::io::stream::Stream;
+/// use prosa::io::frame::{Endianness, LengthPrefixedCodec, LengthPrefixedFramer};
+///
+/// async fn exchange() -> Result<(), io::Error> {
+///     let stream: Stream = Stream::connect_tcp("localhost:8080").await?;
+///     let mut framer = LengthPrefixedFramer::<_, 2>::with_codec(stream, LengthPrefixedCodec::new(Endianness::Big, 8192));
+///
+///     if framer.write_frame("ProSA".into()).await.is_ok() {
+///         if let Ok(Some(frame)) = framer.read_frame().await {
+///             // Handle the received frame
+///         }
+///     }
+///
+///     Ok(())
+/// }
+/// ```
+pub struct LengthPrefixedFramer<S, const HDR: usize> {
+    stream: S,
+    buffer: BytesMut,
+    codec: LengthPrefixedCodec<HDR>,
+}
+
+impl<S, const HDR: usize> LengthPrefixedFramer<S, HDR> {
+    /// Method to create a framer with the default codec (big endian)
+    pub fn new(stream: S) -> LengthPrefixedFramer<S, HDR> {
+        Self::with_codec(stream, LengthPrefixedCodec::default())
+    }
+
+    /// Method to create a framer with a specific codec
+    pub fn with_codec(stream: S, codec: LengthPrefixedCodec<HDR>) -> LengthPrefixedFramer<S, HDR> {
+        LengthPrefixedFramer {
+            stream,
+            buffer: BytesMut::with_capacity(16384),
+            codec,
+        }
+    }
+
+    /// Getter of the framer codec
+    pub fn codec(&self) -> &LengthPrefixedCodec<HDR> {
+        &self.codec
+    }
+
+    /// Getter of the inner stream
+    pub fn get_ref(&self) -> &S {
+        &self.stream
+    }
+
+    /// Mutable getter of the inner stream
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.stream
+    }
+
+    /// Consume the framer to return the inner stream (pending data in the buffer are lost)
+    pub fn into_inner(self) -> S {
+        self.stream
+    }
+}
+
+impl<S, const HDR: usize> IO for LengthPrefixedFramer<S, HDR>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send,
+{
+    type Frame = Bytes;
+    type Error = FrameError;
+
+    fn parse_frame(&mut self) -> Result<Option<Bytes>, FrameError> {
+        self.codec.parse(&mut self.buffer)
+    }
+
+    async fn read_frame(&mut self) -> Result<Option<Bytes>, FrameError> {
+        self.codec.read(&mut self.stream, &mut self.buffer).await
+    }
+
+    async fn write_frame(&mut self, frame: Bytes) -> Result<(), FrameError> {
+        self.codec.write(&mut self.stream, &frame).await
+    }
+}
+
+impl<S, const HDR: usize> fmt::Debug for LengthPrefixedFramer<S, HDR>
+where
+    S: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LengthPrefixedFramer")
+            .field("stream", &self.stream)
+            .field("buffered", &self.buffer.len())
+            .field("codec", &self.codec)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use futures_util::future;
+    use tokio::net::{TcpListener, TcpStream};
+
+    use super::*;
+    use crate::io::stream::Stream;
+
+    extern crate self as prosa;
+
+    #[prosa_macros::io(length_prefixed = 4)]
+    struct TestIo {}
+
+    #[prosa_macros::io(length_prefixed = 2, metrics)]
+    struct NamedIo<S> {
+        #[io(stream)]
+        conn: S,
+        #[io(buffer)]
+        read_buffer: BytesMut,
+        frames: u32,
+    }
+
+    #[tokio::test]
+    async fn length_prefixed_round_trip() {
+        let addr = "localhost:41820";
+        let listener = TcpListener::bind(addr).await.unwrap();
+
+        let server = async move {
+            let (client_stream, _) = listener.accept().await.unwrap();
+            let mut framer = LengthPrefixedFramer::<_, 2>::new(client_stream);
+
+            let frame = framer.read_frame().await.unwrap().unwrap();
+            assert_eq!(b"ProSA".as_slice(), &frame[..]);
+            framer.write_frame(frame).await.unwrap();
+
+            // Write a frame split across multiple TCP segments
+            let stream = framer.get_mut();
+            stream.write_all(&[0x00]).await.unwrap();
+            stream.flush().await.unwrap();
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            stream.write_all(&[0x09, b'W', b'o', b'r']).await.unwrap();
+            stream.flush().await.unwrap();
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            stream.write_all(b"ldline").await.unwrap();
+            stream.flush().await.unwrap();
+
+            // Oversized frame
+            stream.write_all(&[0xFF, 0xFF]).await.unwrap();
+            stream.flush().await.unwrap();
+        };
+
+        let client = async {
+            let stream = TcpStream::connect(addr).await.unwrap();
+            let mut framer = LengthPrefixedFramer::<_, 2>::with_codec(
+                stream,
+                LengthPrefixedCodec::new(Endianness::Big, 1024),
+            );
+            assert_eq!(1024, framer.codec().max_frame_size());
+
+            framer.write_frame(Bytes::from("ProSA")).await.unwrap();
+            assert_eq!(
+                b"ProSA".as_slice(),
+                &framer.read_frame().await.unwrap().unwrap()[..]
+            );
+            assert_eq!(
+                b"Worldline".as_slice(),
+                &framer.read_frame().await.unwrap().unwrap()[..]
+            );
+            assert!(matches!(
+                framer.read_frame().await,
+                Err(FrameError::FrameTooLarge(0xFFFF, 1024))
+            ));
+            assert!(matches!(
+                framer.write_frame(Bytes::from(vec![0u8; 2048])).await,
+                Err(FrameError::FrameTooLarge(2048, 1024))
+            ));
+        };
+
+        future::join(server, client).await;
+    }
+
+    #[tokio::test]
+    async fn io_macro_length_prefixed() {
+        let addr = "localhost:41821";
+        let listener = TcpListener::bind(addr).await.unwrap();
+
+        let server = async move {
+            let (client_stream, _) = listener.accept().await.unwrap();
+            let mut test_io = TestIo::from(client_stream);
+            let frame = test_io.read_frame().await.unwrap().unwrap();
+            test_io.write_frame(frame).await.unwrap();
+            assert_eq!(9, test_io.stats().read_bytes());
+            assert_eq!(9, test_io.stats().written_bytes());
+        };
+
+        let client = async {
+            let mut test_io = TestIo::from(TcpStream::connect(addr).await.unwrap());
+            test_io.write_frame(Bytes::from("ProSA")).await.unwrap();
+            assert_eq!(
+                b"ProSA".as_slice(),
+                &test_io.read_frame().await.unwrap().unwrap()[..]
+            );
+            assert!(test_io.read_frame().await.unwrap().is_none());
+            assert_eq!(9, test_io.stats().read_bytes());
+            assert_eq!(9, test_io.stats().written_bytes());
+        };
+
+        future::join(server, client).await;
+    }
+
+    #[tokio::test]
+    async fn io_macro_named_fields() {
+        let addr = "localhost:41822";
+        let listener = TcpListener::bind(addr).await.unwrap();
+
+        let server = async move {
+            let (client_stream, client_addr) = listener.accept().await.unwrap();
+            let mut named_io = NamedIo::from((Stream::from(client_stream), client_addr));
+            named_io.register_meter(&opentelemetry::global::meter("prosa_io_test"), Vec::new());
+            assert!(
+                named_io.to_string().starts_with("tcp://"),
+                "IO `{}` don't display its stream",
+                named_io
+            );
+            assert!(
+                format!("{:?}", named_io).starts_with("NamedIo { stream: Tcp("),
+                "Wrong debug output `{:?}`",
+                named_io
+            );
+
+            while let Some(frame) = named_io.read_frame().await.unwrap() {
+                named_io.frames += 1;
+                named_io.write_frame(frame).await.unwrap();
+            }
+
+            assert_eq!(3, named_io.frames);
+            assert_eq!(22, named_io.stats().read_bytes());
+            assert_eq!(22, named_io.stats().written_bytes());
+            assert!(named_io.read_buffer.is_empty());
+        };
+
+        let client = async {
+            let mut named_io = NamedIo::from(TcpStream::connect(addr).await.unwrap());
+            assert_eq!(0, named_io.frames);
+            for frame in ["ProSA", "Worldline", "IO"] {
+                named_io.write_frame(Bytes::from(frame)).await.unwrap();
+                assert_eq!(
+                    frame.as_bytes(),
+                    &named_io.read_frame().await.unwrap().unwrap()[..]
+                );
+            }
+
+            // Bytes written directly on the stream are not counted
+            named_io.get_stream_mut().shutdown().await.unwrap();
+            assert_eq!(22, named_io.stats().read_bytes());
+            assert_eq!(22, named_io.stats().written_bytes());
+            assert!(named_io
+                .get_stream()
+                .peer_addr()
+                .unwrap()
+                .ip()
+                .is_loopback());
+        };
+
+        future::join(server, client).await;
+    }
+}
@@ -0,0 +1,696 @@
+//! Module that define a minimal HTTP/1.1 client, to call simple REST endpoints (token refresh, lookups, ...) from the adaptors
+//!
+//! The client connects with [`TargetSetting::connect`], so the proxy and SSL settings of the target apply.
+//! Its connection is kept alive between requests, and replaced when it's closed by the server.
+use std::{fmt, io, str};
+
+use bytes::{Buf as _, BufMut as _, Bytes, BytesMut};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::io::{AsyncReadExt as _, AsyncWriteExt as _};
+
+use super::{
+    pool::{PoolError, PoolOptions, PooledStream, StreamPool},
+    stream::TargetSetting,
+    url_is_ssl,
+};
+
+/// ALPN protocol of HTTP/1.1, the only one negotiated by the client
+pub const HTTP1_ALPN: &str = "http/1.1";
+
+/// Error define for the HTTP client
+#[derive(Debug, Error)]
+pub enum HttpError {
+    /// Error on the HTTP connection
+    #[error("HTTP IO error: {0}")]
+    Io(#[from] io::Error),
+    /// Error to get a connection to the target
+    #[error("HTTP connection error: {0}")]
+    Pool(#[from] PoolError),
+    /// Error that indicate that the server negotiated another protocol than HTTP/1.1 with ALPN
+    #[error("The protocol `{0}` negotiated with ALPN is not supported")]
+    UnsupportedProtocol(String),
+    /// Error that indicate that the request can't be encoded (forbidden characters in its method, path or headers)
+    #[error("Invalid HTTP request: {0}")]
+    InvalidRequest(String),
+    /// Error that indicate that the response is not a valid HTTP/1.1 response
+    #[error("Invalid HTTP response: {0}")]
+    InvalidResponse(String),
+    /// Error that indicate that the response header exceed the maximum size
+    #[error("HTTP response header exceed the maximum size of {0} bytes")]
+    HeaderTooLarge(usize),
+    /// Error that indicate that the response body exceed the maximum size
+    #[error("HTTP response body exceed the maximum size of {0} bytes")]
+    BodyTooLarge(usize),
+}
+
+/// Limits of the HTTP responses accepted by the client
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct HttpLimits {
+    /// Maximum size in bytes of the response header (status line, headers, and chunk lines)
+    #[serde(default = "HttpLimits::default_max_header_size")]
+    pub max_header_size: usize,
+    /// Maximum number of headers of the response
+    #[serde(default = "HttpLimits::default_max_headers")]
+    pub max_headers: usize,
+    /// Maximum size in bytes of the response body
+    #[serde(default = "HttpLimits::default_max_body_size")]
+    pub max_body_size: usize,
+}
+
+impl HttpLimits {
+    fn default_max_header_size() -> usize {
+        16 * 1024
+    }
+
+    fn default_max_headers() -> usize {
+        64
+    }
+
+    fn default_max_body_size() -> usize {
+        4 * 1024 * 1024
+    }
+}
+
+impl Default for HttpLimits {
+    fn default() -> Self {
+        HttpLimits {
+            max_header_size: Self::default_max_header_size(),
+            max_headers: Self::default_max_headers(),
+            max_body_size: Self::default_max_body_size(),
+        }
+    }
+}
+
+/// Response of an HTTP request
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpResponse {
+    /// Status code of the response
+    pub status: u16,
+    /// Reason phrase of the response
+    pub reason: String,
+    /// Headers of the response, in their received order
+    pub headers: Vec<(String, String)>,
+    /// Body of the response (decoded if it was chunked)
+    pub body: Bytes,
+}
+
+impl HttpResponse {
+    /// Getter of the first value of a header (the header name is case insensitive)
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Method to know if the status code is a success (2xx)
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+}
+
+impl fmt::Display for HttpResponse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "HTTP/1.1 {} {} ({} bytes)",
+            self.status,
+            self.reason,
+            self.body.len()
+        )
+    }
+}
+
+/// Header of a response
+struct ResponseHead {
+    status: u16,
+    reason: String,
+    headers: Vec<(String, String)>,
+    keep_alive: bool,
+}
+
+impl ResponseHead {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Reader of a response on a connection
+struct ResponseReader<'a> {
+    stream: &'a mut PooledStream,
+    buffer: BytesMut,
+    limits: &'a HttpLimits,
+    received: bool,
+}
+
+impl<'a> ResponseReader<'a> {
+    fn new(stream: &'a mut PooledStream, limits: &'a HttpLimits) -> ResponseReader<'a> {
+        ResponseReader {
+            stream,
+            buffer: BytesMut::with_capacity(4096),
+            limits,
+            received: false,
+        }
+    }
+
+    /// Method to read more data from the connection, return an error if the connection is closed
+    async fn fill(&mut self) -> Result<(), HttpError> {
+        if self.stream.read_buf(&mut self.buffer).await? == 0 {
+            Err(io::Error::from(io::ErrorKind::UnexpectedEof).into())
+        } else {
+            self.received = true;
+            Ok(())
+        }
+    }
+
+    /// Method to read a response header (interim responses are skipped)
+    async fn read_head(&mut self) -> Result<ResponseHead, HttpError> {
+        loop {
+            let head = self.parse_head()?;
+            match head {
+                Some(head) if (100..200).contains(&head.status) && head.status != 101 => continue,
+                Some(head) => return Ok(head),
+                None if self.buffer.len() >= self.limits.max_header_size => {
+                    return Err(HttpError::HeaderTooLarge(self.limits.max_header_size))
+                }
+                None => self.fill().await?,
+            }
+        }
+    }
+
+    fn parse_head(&mut self) -> Result<Option<ResponseHead>, HttpError> {
+        if self.buffer.is_empty() {
+            return Ok(None);
+        }
+
+        let mut headers = vec![httparse::EMPTY_HEADER; self.limits.max_headers];
+        let mut response = httparse::Response::new(&mut headers);
+        match response.parse(&self.buffer) {
+            Ok(httparse::Status::Complete(len)) => {
+                if len > self.limits.max_header_size {
+                    return Err(HttpError::HeaderTooLarge(self.limits.max_header_size));
+                }
+
+                let headers: Vec<(String, String)> = response
+                    .headers
+                    .iter()
+                    .map(|header| {
+                        (
+                            header.name.to_string(),
+                            String::from_utf8_lossy(header.value).into_owned(),
+                        )
+                    })
+                    .collect();
+                let connection = headers
+                    .iter()
+                    .find(|(name, _)| name.eq_ignore_ascii_case("connection"))
+                    .map(|(_, value)| value.to_ascii_lowercase());
+                let keep_alive = match response.version {
+                    Some(1) => connection.as_deref() != Some("close"),
+                    _ => connection.as_deref() == Some("keep-alive"),
+                };
+                let head = ResponseHead {
+                    status: response.code.unwrap_or_default(),
+                    reason: response.reason.unwrap_or_default().to_string(),
+                    headers,
+                    keep_alive,
+                };
+
+                self.buffer.advance(len);
+                Ok(Some(head))
+            }
+            Ok(httparse::Status::Partial) => Ok(None),
+            Err(e) => Err(HttpError::InvalidResponse(e.to_string())),
+        }
+    }
+
+    /// Method to read a line (without its CRLF)
+    async fn read_line(&mut self) -> Result<Bytes, HttpError> {
+        loop {
+            if let Some(pos) = self.buffer.windows(2).position(|w| w == b"\r\n") {
+                let line = self.buffer.split_to(pos).freeze();
+                self.buffer.advance(2);
+                return Ok(line);
+            } else if self.buffer.len() >= self.limits.max_header_size {
+                return Err(HttpError::HeaderTooLarge(self.limits.max_header_size));
+            }
+
+            self.fill().await?;
+        }
+    }
+
+    /// Method to read an exact number of bytes
+    async fn read_exact(&mut self, len: usize) -> Result<Bytes, HttpError> {
+        while self.buffer.len() < len {
+            self.fill().await?;
+        }
+
+        Ok(self.buffer.split_to(len).freeze())
+    }
+
+    /// Method to read a chunked body
+    async fn read_chunked(&mut self) -> Result<Bytes, HttpError> {
+        let mut body = BytesMut::new();
+        loop {
+            let line = self.read_line().await?;
+            let size = str::from_utf8(&line)
+                .ok()
+                .and_then(|line| line.split(';').next())
+                .and_then(|size| usize::from_str_radix(size.trim(), 16).ok())
+                .ok_or_else(|| {
+                    HttpError::InvalidResponse(format!(
+                        "invalid chunk size `{}`",
+                        String::from_utf8_lossy(&line)
+                    ))
+                })?;
+
+            if size == 0 {
+                // Skip the trailers
+                while !self.read_line().await?.is_empty() {}
+                return Ok(body.freeze());
+            }
+
+            if body.len() + size > self.limits.max_body_size {
+                return Err(HttpError::BodyTooLarge(self.limits.max_body_size));
+            }
+            body.put(self.read_exact(size).await?);
+            if !self.read_line().await?.is_empty() {
+                return Err(HttpError::InvalidResponse(String::from(
+                    "chunk not terminated by CRLF",
+                )));
+            }
+        }
+    }
+
+    /// Method to read a body delimited by the end of the connection
+    async fn read_to_end(&mut self) -> Result<Bytes, HttpError> {
+        loop {
+            if self.buffer.len() > self.limits.max_body_size {
+                return Err(HttpError::BodyTooLarge(self.limits.max_body_size));
+            }
+
+            match self.stream.read_buf(&mut self.buffer).await? {
+                0 => return Ok(self.buffer.split().freeze()),
+                _ => self.received = true,
+            }
+        }
+    }
+
+    /// Method to read a complete response. Return the response, and if the connection can be kept alive
+    async fn read_response(&mut self, method: &str) -> Result<(HttpResponse, bool), HttpError> {
+        let head = self.read_head().await?;
+        let mut keep_alive = head.keep_alive;
+        let body =
+            if method.eq_ignore_ascii_case("HEAD") || head.status == 204 || head.status == 304 {
+                Bytes::new()
+            } else if head
+                .header("transfer-encoding")
+                .is_some_and(|encoding| encoding.to_ascii_lowercase().contains("chunked"))
+            {
+                self.read_chunked().await?
+            } else if let Some(length) = head.header("content-length") {
+                let length: usize = length.trim().parse().map_err(|_| {
+                    HttpError::InvalidResponse(format!("invalid content length `{}`", length))
+                })?;
+                if length > self.limits.max_body_size {
+                    return Err(HttpError::BodyTooLarge(self.limits.max_body_size));
+                }
+                self.read_exact(length).await?
+            } else {
+                keep_alive = false;
+                self.read_to_end().await?
+            };
+
+        // Unexpected remaining data, the connection can't be reused
+        if !self.buffer.is_empty() {
+            keep_alive = false;
+        }
+
+        Ok((
+            HttpResponse {
+                status: head.status,
+                reason: head.reason,
+                headers: head.headers,
+                body,
+            },
+            keep_alive,
+        ))
+    }
+}
+
+/// Minimal HTTP/1.1 client over a ProSA stream
+///
+/// The client keep a single connection alive to the target (through a [`StreamPool`]), so concurrent requests are sent one after another.
+/// Over SSL, only HTTP/1.1 is proposed with ALPN.
+///
+/// ```
+/// use url::Url;
+/// use prosa::io::http::{HttpClient, HttpError};
+/// use prosa::io::stream::TargetSetting;
+///
+/// async fn refresh_token() -> Result<String, HttpError> {
+///     let target = TargetSetting::from(Url::parse("https://auth.example.com").unwrap());
+///     let client = HttpClient::new(target);
+///
+///     let response = client
+///         .request(
+///             "POST",
+///             "/oauth/token",
+///             &[("Content-Type", "application/x-www-form-urlencoded")],
+///             Some(b"grant_type=client_credentials"),
+///         )
+///         .await?;
+///     Ok(String::from_utf8_lossy(&response.body).into_owned())
+/// }
+/// ```
+#[derive(Debug)]
+pub struct HttpClient {
+    pool: StreamPool,
+    host: String,
+    limits: HttpLimits,
+}
+
+impl HttpClient {
+    /// Method to create an HTTP client to a target. The connection is opened on the first request
+    pub fn new(mut target: TargetSetting) -> HttpClient {
+        if target.ssl.is_some() || url_is_ssl(&target.url) {
+            let mut ssl = target.ssl.take().unwrap_or_default();
+            ssl.set_alpn(vec![String::from(HTTP1_ALPN)]);
+            target.ssl = Some(ssl);
+            target.init_ssl_context();
+        }
+
+        let host = match (target.url.host_str(), target.url.port()) {
+            (Some(host), Some(port)) => format!("{}:{}", host, port),
+            (Some(host), None) => host.to_string(),
+            (None, _) => String::from("localhost"),
+        };
+
+        HttpClient {
+            pool: StreamPool::new(target, PoolOptions::new(1)),
+            host,
+            limits: HttpLimits::default(),
+        }
+    }
+
+    /// Setter of the limits of the responses
+    pub fn limits(mut self, limits: HttpLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Getter of the target of the client
+    pub fn get_target(&self) -> &TargetSetting {
+        self.pool.get_target()
+    }
+
+    /// Method to send a `GET` request
+    pub async fn get(&self, path: &str) -> Result<HttpResponse, HttpError> {
+        self.request("GET", path, &[], None).await
+    }
+
+    /// Method to send a `POST` request with its content type
+    pub async fn post(
+        &self,
+        path: &str,
+        content_type: &str,
+        body: &[u8],
+    ) -> Result<HttpResponse, HttpError> {
+        self.request("POST", path, &[("Content-Type", content_type)], Some(body))
+            .await
+    }
+
+    /// Method to send a request and wait for its response.
+    ///
+    /// The `Host` and `Content-Length` headers are added if they're not given.
+    /// The method, the path and the headers can't contain line breaks, to not inject headers.
+    /// If the kept alive connection was closed by the server, an idempotent request (`GET`, `HEAD`, `PUT`, `DELETE`, `OPTIONS`, `TRACE`) is sent again on a new connection.
+    pub async fn request(
+        &self,
+        method: &str,
+        path: &str,
+        headers: &[(&str, &str)],
+        body: Option<&[u8]>,
+    ) -> Result<HttpResponse, HttpError> {
+        let request = self.encode_request(method, path, headers, body)?;
+        let idempotent = ["GET", "HEAD", "PUT", "DELETE", "OPTIONS", "TRACE"]
+            .iter()
+            .any(|m| method.eq_ignore_ascii_case(m));
+        loop {
+            let mut stream = self.pool.acquire().await?;
+            if let Some(alpn) = stream.selected_alpn() {
+                if alpn != HTTP1_ALPN.as_bytes() {
+                    stream.poison();
+                    return Err(HttpError::UnsupportedProtocol(
+                        String::from_utf8_lossy(&alpn).into_owned(),
+                    ));
+                }
+            }
+
+            let reused = stream.is_reused();
+            let result = match stream.write_all(&request).await {
+                Ok(()) => {
+                    let mut reader = ResponseReader::new(&mut stream, &self.limits);
+                    let result = reader.read_response(method).await;
+                    result.map_err(|e| (e, reader.received))
+                }
+                Err(e) => Err((e.into(), false)),
+            };
+
+            match result {
+                Ok((response, keep_alive)) => {
+                    if !keep_alive {
+                        stream.poison();
+                    }
+                    return Ok(response);
+                }
+                Err((HttpError::Io(_), false)) if reused && idempotent => {
+                    // The kept alive connection was closed by the server, the request can be safely sent again
+                    stream.poison();
+                }
+                Err((e, _)) => {
+                    stream.poison();
+                    return Err(e);
+                }
+            }
+        }
+    }
+
+    /// Method to check that a request element can't break the request framing
+    fn check_request_element(
+        kind: &str,
+        element: &str,
+        is_valid: fn(u8) -> bool,
+    ) -> Result<(), HttpError> {
+        if element.bytes().all(is_valid) {
+            Ok(())
+        } else {
+            Err(HttpError::InvalidRequest(format!(
+                "forbidden character in the {} `{}`",
+                kind,
+                element.escape_debug()
+            )))
+        }
+    }
+
+    fn encode_request(
+        &self,
+        method: &str,
+        path: &str,
+        headers: &[(&str, &str)],
+        body: Option<&[u8]>,
+    ) -> Result<BytesMut, HttpError> {
+        // Method and header names are tokens, the path and the header values can't contain control characters
+        let is_token = |c: u8| c.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&c);
+        Self::check_request_element("method", method, is_token)?;
+        Self::check_request_element("path", path, |c| c.is_ascii_graphic() || c >= 0x80)?;
+        for (name, value) in headers {
+            Self::check_request_element("header name", name, is_token)?;
+            Self::check_request_element("header value", value, |c| {
+                c == b'\t' || (c >= b' ' && c != 0x7f)
+            })?;
+        }
+
+        let mut request = BytesMut::with_capacity(256 + body.map_or(0, |b| b.len()));
+        request.put(format!("{} {} HTTP/1.1\r\n", method, path).as_bytes());
+        if !headers
+            .iter()
+            .any(|(name, _)| name.eq_ignore_ascii_case("host"))
+        {
+            request.put(format!("Host: {}\r\n", self.host).as_bytes());
+        }
+        for (name, value) in headers {
+            request.put(format!("{}: {}\r\n", name, value).as_bytes());
+        }
+        if let Some(body) = body {
+            if !headers
+                .iter()
+                .any(|(name, _)| name.eq_ignore_ascii_case("content-length"))
+            {
+                request.put(format!("Content-Length: {}\r\n", body.len()).as_bytes());
+            }
+            request.put_slice(b"\r\n");
+            request.put_slice(body);
+        } else {
+            request.put_slice(b"\r\n");
+        }
+
+        Ok(request)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        convert::Infallible,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+    };
+
+    use http_body_util::{combinators::BoxBody, BodyExt as _, Full, StreamBody};
+    use hyper::{body::Frame, server::conn::http1, service::service_fn, Request, Response};
+    use hyper_util::rt::TokioIo;
+    use tokio::{net::TcpListener, task::JoinHandle};
+    use url::Url;
+
+    use super::*;
+
+    const ADDR: &str = "127.0.0.1:41850";
+
+    async fn handle(
+        request: Request<hyper::body::Incoming>,
+    ) -> Result<Response<BoxBody<Bytes, Infallible>>, Infallible> {
+        let response = Response::builder().header("X-Method", request.method().as_str());
+        Ok(match request.uri().path() {
+            "/hello" => response
+                .body(Full::new(Bytes::from_static(b"Hello ProSA")).boxed())
+                .unwrap(),
+            "/close" => response
+                .header("Connection", "close")
+                .body(Full::new(Bytes::from_static(b"Bye")).boxed())
+                .unwrap(),
+            "/echo" => {
+                let body = request.into_body().collect().await.unwrap().to_bytes();
+                response.body(Full::new(body).boxed()).unwrap()
+            }
+            "/chunked" => {
+                let chunks = ["ProSA ", "chunked ", "response"]
+                    .map(|chunk| Ok(Frame::data(Bytes::from_static(chunk.as_bytes()))));
+                response
+                    .body(StreamBody::new(futures_util::stream::iter(chunks)).boxed())
+                    .unwrap()
+            }
+            _ => response
+                .status(404)
+                .body(Full::new(Bytes::new()).boxed())
+                .unwrap(),
+        })
+    }
+
+    /// Start an HTTP server that count its connections
+    async fn http_server(connections: Arc<AtomicUsize>) -> JoinHandle<()> {
+        let listener = TcpListener::bind(ADDR).await.unwrap();
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                connections.fetch_add(1, Ordering::Relaxed);
+                tokio::spawn(async move {
+                    let _ = http1::Builder::new()
+                        .serve_connection(TokioIo::new(stream), service_fn(handle))
+                        .await;
+                });
+            }
+        })
+    }
+
+    #[tokio::test]
+    async fn http_client() {
+        let connections = Arc::new(AtomicUsize::new(0));
+        let server = http_server(connections.clone()).await;
+        let client = HttpClient::new(TargetSetting::from(
+            Url::parse(&format!("http://{}", ADDR)).unwrap(),
+        ));
+
+        let response = client.get("/hello").await.unwrap();
+        assert!(response.is_success());
+        assert_eq!(Some("GET"), response.header("x-method"));
+        assert_eq!(Some("11"), response.header("Content-Length"));
+        assert_eq!(b"Hello ProSA".as_slice(), &response.body[..]);
+
+        let response = client
+            .post("/echo", "text/plain", b"ProSA request")
+            .await
+            .unwrap();
+        assert_eq!(Some("POST"), response.header("x-method"));
+        assert_eq!(b"ProSA request".as_slice(), &response.body[..]);
+
+        // The interim response sent for the expected continuation is skipped
+        let response = client
+            .request(
+                "POST",
+                "/echo",
+                &[("Expect", "100-continue")],
+                Some(b"ProSA continue"),
+            )
+            .await
+            .unwrap();
+        assert_eq!(200, response.status);
+        assert_eq!(b"ProSA continue".as_slice(), &response.body[..]);
+
+        // Line breaks can't be injected in the request
+        assert!(matches!(
+            client
+                .request("GET", "/hello", &[("X-Test", "a\r\nX-Injected: b")], None)
+                .await,
+            Err(HttpError::InvalidRequest(_))
+        ));
+        assert!(matches!(
+            client.get("/hello HTTP/1.1\r\nX-Injected: b").await,
+            Err(HttpError::InvalidRequest(_))
+        ));
+
+        let response = client.get("/chunked").await.unwrap();
+        assert_eq!(Some("chunked"), response.header("transfer-encoding"));
+        assert_eq!(b"ProSA chunked response".as_slice(), &response.body[..]);
+
+        let response = client.request("HEAD", "/hello", &[], None).await.unwrap();
+        assert!(response.body.is_empty());
+
+        let response = client.get("/unknown").await.unwrap();
+        assert_eq!(404, response.status);
+        assert!(!response.is_success());
+
+        // All requests are sent on the same connection
+        assert_eq!(1, connections.load(Ordering::Relaxed));
+
+        // A response too large discard the connection
+        let client = client.limits(HttpLimits {
+            max_body_size: 8,
+            ..Default::default()
+        });
+        assert!(matches!(
+            client.get("/hello").await,
+            Err(HttpError::BodyTooLarge(8))
+        ));
+        assert!(matches!(
+            client.get("/chunked").await,
+            Err(HttpError::BodyTooLarge(8))
+        ));
+        assert_eq!(2, connections.load(Ordering::Relaxed));
+
+        // The connection is replaced once the server close it
+        let response = client.get("/close").await.unwrap();
+        assert_eq!(Some("close"), response.header("connection"));
+        assert_eq!(b"Bye".as_slice(), &response.body[..]);
+        assert!(client.get("/unknown").await.is_ok());
+        assert_eq!(4, connections.load(Ordering::Relaxed));
+
+        server.abort();
+    }
+}
@@ -1,43 +1,217 @@
 //! Module that define listener IO that could be use by a ProSA processor
+//!
+//! ## Graceful close
+//!
+//! During a rolling restart, a processor may want to stop accepting new clients while finishing its in-flight streams.
+//! [`StreamListener::close_accept`] stop the accept side of the listener: a pending or a subsequent [`StreamListener::accept`] return a [`ListenerClosed`] error immediately.
+//! Streams that were already accepted are not affected, and [`StreamListener::drain_deadline`] give the instant until which they can be served.
+//!
+//! ```
+//! use std::time::Duration;
+//! use tokio::{io, task::JoinSet};
+//! use prosa::io::listener::{ListenerClosed, StreamListener};
+//!
+//! async fn serving(stream_listener: StreamListener) -> Result<(), io::Error> {
+//!     let mut streams = JoinSet::new();
+//!
+//!     loop {
+//!         match stream_listener.accept().await {
+//!             Ok((stream, addr)) => {
+//!                 streams.spawn(async move {
+//!                     // Handle the stream like any tokio stream
+//!                 });
+//!             }
+//!             Err(e) if ListenerClosed::is(&e) => break,
+//!             Err(e) => return Err(e),
+//!         }
+//!     }
+//!
+//!     // `close_accept()` was called from another task, give 30 seconds to the in-flight streams
+//!     let drain_deadline = stream_listener.drain_deadline(Duration::from_secs(30)).unwrap();
+//!     let _ = tokio::time::timeout_at(drain_deadline, async {
+//!         while streams.join_next().await.is_some() {}
+//!     })
+//!     .await;
+//!
+//!     Ok(())
+//! }
+//! ```
+//!
+//! ## Socket inheritance
+//!
+//! To restart a ProSA without dropping its listening sockets (only on unix systems), the sockets can be inherited from the previous process instead of being bound anew.
+//! [`ListenerSetting::bind`] and [`ListenerSetting::bind_multi`] first look for an inherited socket:
+//! - from the [`LISTEN_FDS_ENV`] environment variable, that map listener urls to file descriptors (`tcp://0.0.0.0:8080=3;unix:///run/prosa.sock=4`)
+//! - from the systemd socket activation convention (`LISTEN_FDS` file descriptors starting at 3, named with `LISTEN_FDNAMES`)
+//!
+//! An inherited socket is used by the listener whose url is its name in the mapping, or whose address is the local address of the socket.
+//!
+//! On the previous process side, [`StreamListener::into_raw_fd`] give a listening socket that can be inherited with its mapping ([`ListenerFd`]).
+//! Servers hand over their listeners at shutdown once [`enable_handover`] is called, so [`spawn_handover`] can start the next process with all of them:
+//!
+//! ```no_run
+//! use prosa::io::listener::{enable_handover, spawn_handover};
+//!
+//! # fn run_prosa() {}
+//! fn main() -> std::io::Result<()> {
+//!     enable_handover();
+//!     run_prosa();
+//!
+//!     // The ProSA is stopped, start the new binary with the listening sockets
+//!     let child = spawn_handover(&mut std::process::Command::new("/usr/bin/my-prosa"))?;
+//!     println!("Sockets handed over to {}", child.id());
+//!     Ok(())
+//! }
+//! ```
 use std::{
-    fmt, io,
-    net::{Ipv4Addr, SocketAddrV4},
-    os::fd::{AsFd, AsRawFd, BorrowedFd, RawFd},
+    collections::HashMap,
+    fmt,
+    future::{poll_fn, Future},
+    io,
+    net::{IpAddr, Ipv4Addr, SocketAddrV4},
     pin::Pin,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex, OnceLock,
+    },
+    task::Poll,
     time::Duration,
 };
 
+#[cfg(target_family = "unix")]
+use std::{
+    collections::HashSet,
+    os::fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd as _, IntoRawFd as _, OwnedFd, RawFd},
+};
+
 use openssl::ssl::SslAcceptor;
+use opentelemetry::{
+    metrics::{Counter, Meter},
+    KeyValue,
+};
 use prosa_utils::config::ssl::SslConfig;
 use serde::{Deserialize, Serialize};
 
 pub use prosa_macros::io;
+use thiserror::Error;
+#[cfg(target_family = "windows")]
+use tokio::net::windows::named_pipe::{NamedPipeServer, ServerOptions};
+#[cfg(target_family = "unix")]
+use tokio::net::UnixListener;
 use tokio::{
-    net::{TcpListener, ToSocketAddrs, UnixListener},
-    time::timeout,
+    net::{TcpListener, ToSocketAddrs},
+    sync::Notify,
+    time::{timeout, Instant},
 };
+use tracing::debug;
 use url::Url;
 
-use super::{stream::Stream, url_is_ssl, SocketAddr};
+#[cfg(target_family = "windows")]
+use super::url_named_pipe;
+use super::{socket::SocketOptions, stream::Stream, unsupported_url, url_is_ssl, SocketAddr};
 
-/// ProSA socket object to handle TCP/SSL server socket
-pub enum StreamListener {
+/// Error returned by an accept once the listener stopped accepting new clients with [`StreamListener::close_accept`]
+///
+/// The error is wrapped in an [`io::Error`] of kind [`io::ErrorKind::ConnectionAborted`].
+#[derive(Debug, Error, Clone, Copy, PartialEq, Eq)]
+#[error("Listener closed, no new client is accepted")]
+pub struct ListenerClosed;
+
+impl ListenerClosed {
+    /// Method to know if an IO error was returned by a closed listener
+    pub fn is(err: &io::Error) -> bool {
+        err.get_ref().is_some_and(|e| e.is::<ListenerClosed>())
+    }
+}
+
+impl From<ListenerClosed> for io::Error {
+    fn from(err: ListenerClosed) -> Self {
+        io::Error::new(io::ErrorKind::ConnectionAborted, err)
+    }
+}
+
+/// Named pipe server that create a new instance of the pipe for every client (only on windows systems)
+///
+/// ```
+/// use tokio::io;
+/// use prosa::io::listener::{NamedPipeListener, StreamListener};
+///
+/// async fn accepting() -> Result<(), io::Error> {
+///     let stream_listener: StreamListener = NamedPipeListener::bind(r"\\.\pipe\prosa")?.into();
+///
+///     loop {
+///         let (stream, addr) = stream_listener.accept().await?;
+///
+///         // Handle the stream like any tokio stream
+///     }
+///
+///     Ok(())
+/// }
+/// ```
+#[cfg(target_family = "windows")]
+#[derive(Debug)]
+pub struct NamedPipeListener {
+    path: String,
+    next_instance: tokio::sync::Mutex<NamedPipeServer>,
+}
+
+#[cfg(target_family = "windows")]
+impl NamedPipeListener {
+    /// Create the first instance of the named pipe. Fail if the pipe already exist
+    pub fn bind<P>(path: P) -> Result<NamedPipeListener, io::Error>
+    where
+        P: Into<String>,
+    {
+        let path = path.into();
+        let first_instance = ServerOptions::new()
+            .first_pipe_instance(true)
+            .create(&path)?;
+        Ok(NamedPipeListener {
+            path,
+            next_instance: tokio::sync::Mutex::new(first_instance),
+        })
+    }
+
+    /// Getter of the path of the named pipe
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Method to wait a client on the pending instance of the pipe.
+    /// A new instance is created for the next client once a client is connected
+    pub async fn accept(&self) -> Result<NamedPipeServer, io::Error> {
+        let mut next_instance = self.next_instance.lock().await;
+        next_instance.connect().await?;
+        let new_instance = ServerOptions::new().create(&self.path)?;
+        Ok(std::mem::replace(&mut *next_instance, new_instance))
+    }
+}
+
+/// Kind of ProSA server socket
+pub enum StreamListenerKind {
     #[cfg(target_family = "unix")]
     /// Unix server socket (only on unix systems)
     Unix(tokio::net::UnixListener),
+    #[cfg(target_family = "windows")]
+    /// Named pipe server (only on windows systems)
+    NamedPipe(NamedPipeListener),
     /// TCP server socket
     Tcp(TcpListener),
     /// SSL server socket
     Ssl(TcpListener, SslAcceptor, Duration),
 }
 
-impl fmt::Debug for StreamListener {
+impl fmt::Debug for StreamListenerKind {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             #[cfg(target_family = "unix")]
-            StreamListener::Unix(l) => f.debug_struct("Unix").field("listener", &l).finish(),
-            StreamListener::Tcp(l) => f.debug_struct("Tcp").field("listener", &l).finish(),
-            StreamListener::Ssl(l, a, t) => f
+            StreamListenerKind::Unix(l) => f.debug_struct("Unix").field("listener", &l).finish(),
+            #[cfg(target_family = "windows")]
+            StreamListenerKind::NamedPipe(l) => {
+                f.debug_struct("NamedPipe").field("listener", &l).finish()
+            }
+            StreamListenerKind::Tcp(l) => f.debug_struct("Tcp").field("listener", &l).finish(),
+            StreamListenerKind::Ssl(l, a, t) => f
                 .debug_struct("Ssl")
                 .field("listener", &l)
                 .field("ssl_timeout", &t)
@@ -50,10 +224,43 @@ impl fmt::Debug for StreamListener {
     }
 }
 
+/// State of the accept side of a listener
+#[derive(Debug, Default)]
+struct AcceptState {
+    /// Flag set when the listener don't accept new clients anymore
+    closed: AtomicBool,
+    /// Notify pending accepts of the close
+    notify: Notify,
+    /// Instant of the close
+    closed_at: OnceLock<Instant>,
+}
+
+/// ProSA socket object to handle TCP/SSL server socket
+pub struct StreamListener {
+    listener: StreamListenerKind,
+    accept_state: AcceptState,
+}
+
+impl fmt::Debug for StreamListener {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.listener, f)
+    }
+}
+
 impl StreamListener {
     /// Default SSL handshake timeout
     pub const DEFAULT_SSL_TIMEOUT: Duration = Duration::new(3, 0);
 
+    /// Getter of the kind of server socket
+    pub fn get_kind(&self) -> &StreamListenerKind {
+        &self.listener
+    }
+
+    /// Method to retrieve the server socket out of the listener
+    pub fn into_kind(self) -> StreamListenerKind {
+        self.listener
+    }
+
     /// Returns the local address that this listener is bound to.
     ///
     /// This can be useful, for example, when binding to port 0 to figure out
@@ -75,11 +282,17 @@ impl StreamListener {
     /// }
     /// ```
     pub fn local_addr(&self) -> Result<SocketAddr, io::Error> {
-        match self {
+        match &self.listener {
             #[cfg(target_family = "unix")]
-            StreamListener::Unix(listener) => listener.local_addr().map(|addr| addr.into()),
-            StreamListener::Tcp(listener) => listener.local_addr().map(|addr| addr.into()),
-            StreamListener::Ssl(listener, _, _) => listener.local_addr().map(|addr| addr.into()),
+            StreamListenerKind::Unix(listener) => listener.local_addr().map(|addr| addr.into()),
+            #[cfg(target_family = "windows")]
+            StreamListenerKind::NamedPipe(listener) => {
+                Ok(SocketAddr::NamedPipe(listener.path().to_string()))
+            }
+            StreamListenerKind::Tcp(listener) => listener.local_addr().map(|addr| addr.into()),
+            StreamListenerKind::Ssl(listener, _, _) => {
+                listener.local_addr().map(|addr| addr.into())
+            }
         }
     }
 
@@ -111,7 +324,57 @@ impl StreamListener {
     /// }
     /// ```
     pub async fn bind<A: ToSocketAddrs>(addr: A) -> Result<StreamListener, io::Error> {
-        Ok(StreamListener::Tcp(TcpListener::bind(addr).await?))
+        Ok(TcpListener::bind(addr).await?.into())
+    }
+
+    /// Bind a TCP listener on a single socket address
+    ///
+    /// For an IPv6 address, `only_v6` set the `IPV6_V6ONLY` option of the socket.
+    /// If it's not specified, the unspecified address `[::]` is bound in dual-stack to accept IPv4 and IPv6 clients whatever the system default.
+    ///
+    /// Must be called from a Tokio runtime.
+    ///
+    /// ```
+    /// use tokio::io;
+    /// use prosa::io::listener::StreamListener;
+    ///
+    /// async fn accepting() -> Result<(), io::Error> {
+    ///     // Accept IPv4 and IPv6 clients
+    ///     let stream_listener = StreamListener::bind_addr("[::]:10000".parse().unwrap(), None)?;
+    ///
+    ///     loop {
+    ///         let (stream, addr) = stream_listener.accept().await?;
+    ///
+    ///         // Handle the stream like any tokio stream
+    ///     }
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn bind_addr(
+        addr: std::net::SocketAddr,
+        only_v6: Option<bool>,
+    ) -> Result<StreamListener, io::Error> {
+        let socket = socket2::Socket::new(
+            socket2::Domain::for_address(addr),
+            socket2::Type::STREAM,
+            Some(socket2::Protocol::TCP),
+        )?;
+
+        #[cfg(target_family = "unix")]
+        socket.set_reuse_address(true)?;
+        if let std::net::SocketAddr::V6(addr_v6) = addr {
+            if let Some(only_v6) =
+                only_v6.or_else(|| addr_v6.ip().is_unspecified().then_some(false))
+            {
+                socket.set_only_v6(only_v6)?;
+            }
+        }
+
+        socket.set_nonblocking(true)?;
+        socket.bind(&addr.into())?;
+        socket.listen(1024)?;
+        Ok(TcpListener::from_std(socket.into())?.into())
     }
 
     #[cfg_attr(doc, aquamarine::aquamarine)]
@@ -150,23 +413,53 @@ impl StreamListener {
         ssl_acceptor: SslAcceptor,
         ssl_timeout: Option<Duration>,
     ) -> StreamListener {
-        match self {
-            StreamListener::Tcp(listener) => StreamListener::Ssl(
-                listener,
-                ssl_acceptor,
-                ssl_timeout.unwrap_or(Self::DEFAULT_SSL_TIMEOUT),
-            ),
-            StreamListener::Ssl(listener, _, _) => StreamListener::Ssl(
-                listener,
-                ssl_acceptor,
-                ssl_timeout.unwrap_or(Self::DEFAULT_SSL_TIMEOUT),
-            ),
-            _ => self,
+        let listener = match self.listener {
+            StreamListenerKind::Tcp(listener) | StreamListenerKind::Ssl(listener, _, _) => {
+                StreamListenerKind::Ssl(
+                    listener,
+                    ssl_acceptor,
+                    ssl_timeout.unwrap_or(Self::DEFAULT_SSL_TIMEOUT),
+                )
+            }
+            #[cfg(any(target_family = "unix", target_family = "windows"))]
+            listener => listener,
+        };
+
+        StreamListener {
+            listener,
+            accept_state: self.accept_state,
         }
     }
 
+    /// Method to stop accepting new clients while keeping the already accepted streams.
+    ///
+    /// A pending accept is woken up, and every subsequent accept return a [`ListenerClosed`] error immediately.
+    /// The listening socket stay open until the listener is dropped.
+    pub fn close_accept(&self) {
+        let _ = self.accept_state.closed_at.set(Instant::now());
+        self.accept_state.closed.store(true, Ordering::Release);
+        self.accept_state.notify.notify_waiters();
+    }
+
+    /// Method to know if the listener stopped accepting new clients
+    pub fn is_accept_closed(&self) -> bool {
+        self.accept_state.closed.load(Ordering::Acquire)
+    }
+
+    /// Method to get the deadline to finish the in-flight streams once the listener stopped accepting new clients.
+    ///
+    /// The deadline is the close instant plus the `drain_timeout`. Return `None` if [`StreamListener::close_accept`] was not called.
+    pub fn drain_deadline(&self, drain_timeout: Duration) -> Option<Instant> {
+        self.accept_state
+            .closed_at
+            .get()
+            .map(|closed_at| *closed_at + drain_timeout)
+    }
+
     /// Method to accept a client after a bind
     ///
+    /// Return a [`ListenerClosed`] error once [`StreamListener::close_accept`] is called.
+    ///
     /// ```
     /// use tokio::io;
     /// use prosa_utils::config::ssl::SslConfig;
@@ -187,44 +480,57 @@ impl StreamListener {
     /// }
     /// ```
     pub async fn accept(&self) -> Result<(Stream, SocketAddr), io::Error> {
-        match self {
-            #[cfg(target_family = "unix")]
-            StreamListener::Unix(l) => l.accept().await.map(|s| (Stream::Unix(s.0), s.1.into())),
-            StreamListener::Tcp(l) => l.accept().await.map(|s| (Stream::Tcp(s.0), s.1.into())),
-            StreamListener::Ssl(l, ssl_acceptor, ssl_timeout) => {
-                let ssl = openssl::ssl::Ssl::new(ssl_acceptor.context())
-                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
-                let (stream, addr) = l.accept().await?;
-                let mut stream = tokio_openssl::SslStream::new(ssl, stream)
-                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
-                if let Err(e) = timeout(*ssl_timeout, Pin::new(&mut stream).accept())
-                    .await
-                    .map_err(|_| {
-                        io::Error::new(
-                            io::ErrorKind::TimedOut,
-                            format!(
-                                "SSL timeout[{} ms] for {:?}",
-                                ssl_timeout.as_millis(),
-                                stream
-                            ),
-                        )
-                    })?
-                {
-                    if e.code() != openssl::ssl::ErrorCode::ZERO_RETURN {
-                        return Err(io::Error::new(
-                            io::ErrorKind::Other,
-                            format!("Can't accept the client: {}", e),
-                        ));
-                    }
-                }
+        let (stream, addr) = self.accept_raw().await?;
+        Ok((self.handshake(stream).await?, addr))
+    }
 
-                Ok((Stream::Ssl(stream), addr.into()))
+    /// Method to accept a client that respect the accept policy of the listener
+    ///
+    /// Clients over the limits of the policy are closed immediately, and counted as rejected by the policy.
+    /// The returned [`ConnectionGuard`] must be kept for the lifetime of the connection, its drop free the capacity of the policy.
+    ///
+    /// ```
+    /// use tokio::io;
+    /// use url::Url;
+    /// use prosa::io::listener::{AcceptPolicy, ListenerSetting, StreamListener};
+    ///
+    /// async fn accepting() -> Result<(), io::Error> {
+    ///     let mut listener_setting = ListenerSetting::new(Url::parse("tcp://0.0.0.0:10000").unwrap(), None);
+    ///     let mut accept_policy = AcceptPolicy::default();
+    ///     accept_policy.max_per_ip = Some(10);
+    ///     listener_setting.accept_policy = Some(accept_policy);
+    ///
+    ///     let stream_listener: StreamListener = listener_setting.bind().await?;
+    ///     let policy_state = listener_setting.accept_policy_state();
+    ///
+    ///     loop {
+    ///         let (stream, addr, guard) = stream_listener.accept_with_policy(&policy_state).await?;
+    ///
+    ///         tokio::spawn(async move {
+    ///             // Handle the stream like any tokio stream, and free the capacity at the end
+    ///             drop(guard);
+    ///         });
+    ///     }
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn accept_with_policy(
+        &self,
+        policy: &AcceptPolicyState,
+    ) -> Result<(Stream, SocketAddr, ConnectionGuard), io::Error> {
+        loop {
+            let (stream, addr) = self.accept_raw().await?;
+            if let Ok(guard) = policy.admit(&addr) {
+                return Ok((self.handshake(stream).await?, addr, guard));
             }
         }
     }
 
     /// Method to accept a client after a bind without SSL handshake (must be done with handshake after)
     ///
+    /// Return a [`ListenerClosed`] error once [`StreamListener::close_accept`] is called.
+    ///
     /// ```
     /// use tokio::io;
     /// use prosa_utils::config::ssl::SslConfig;
@@ -247,11 +553,35 @@ impl StreamListener {
     /// }
     /// ```
     pub async fn accept_raw(&self) -> Result<(Stream, SocketAddr), io::Error> {
-        match self {
+        // Register to the close notification before checking the flag to not miss a close in between
+        let closed = self.accept_state.notify.notified();
+        tokio::pin!(closed);
+        closed.as_mut().enable();
+        if self.is_accept_closed() {
+            return Err(ListenerClosed.into());
+        }
+
+        tokio::select! {
+            biased;
+            _ = closed => Err(ListenerClosed.into()),
+            accepted = self.accept_socket() => accepted,
+        }
+    }
+
+    async fn accept_socket(&self) -> Result<(Stream, SocketAddr), io::Error> {
+        match &self.listener {
             #[cfg(target_family = "unix")]
-            StreamListener::Unix(l) => l.accept().await.map(|s| (Stream::Unix(s.0), s.1.into())),
-            StreamListener::Tcp(l) => l.accept().await.map(|s| (Stream::Tcp(s.0), s.1.into())),
-            StreamListener::Ssl(l, _ssl_acceptor, _ssl_timeout) => {
+            StreamListenerKind::Unix(l) => {
+                l.accept().await.map(|s| (Stream::Unix(s.0), s.1.into()))
+            }
+            #[cfg(target_family = "windows")]
+            StreamListenerKind::NamedPipe(l) => l.accept().await.map(|s| {
+                (
+                    Stream::NamedPipeServer(s, l.path().to_string()),
+                    SocketAddr::NamedPipe(l.path().to_string()),
+                )
+            }),
+            StreamListenerKind::Tcp(l) | StreamListenerKind::Ssl(l, _, _) => {
                 l.accept().await.map(|s| (Stream::Tcp(s.0), s.1.into()))
             }
         }
@@ -261,7 +591,7 @@ impl StreamListener {
     pub async fn handshake(&self, stream: Stream) -> Result<Stream, io::Error> {
         match stream {
             Stream::Tcp(tcp_stream) => {
-                if let StreamListener::Ssl(_l, ssl_acceptor, ssl_timeout) = self {
+                if let StreamListenerKind::Ssl(_l, ssl_acceptor, ssl_timeout) = &self.listener {
                     let ssl = openssl::ssl::Ssl::new(ssl_acceptor.context())
                         .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
                     let mut stream = tokio_openssl::SslStream::new(ssl, tcp_stream)
@@ -280,14 +610,19 @@ impl StreamListener {
                         })?
                     {
                         if e.code() != openssl::ssl::ErrorCode::ZERO_RETURN {
-                            return Err(io::Error::new(
-                                io::ErrorKind::Other,
-                                format!("Can't accept the client: {}", e),
-                            ));
+                            return Err(io::Error::other(format!(
+                                "Can't accept the client: {}",
+                                e
+                            )));
                         }
                     }
 
-                    Ok(Stream::Ssl(stream))
+                    let peer_addr = stream.get_ref().peer_addr();
+                    let stream = Stream::Ssl(stream);
+                    if let (Ok(peer_addr), Some(tls_info)) = (peer_addr, stream.tls_info()) {
+                        debug!("SSL session established with {}: {}", peer_addr, tls_info);
+                    }
+                    Ok(stream)
                 } else {
                     Ok(Stream::Tcp(tcp_stream))
                 }
@@ -295,26 +630,80 @@ impl StreamListener {
             s => Ok(s),
         }
     }
+
+    /// Apply socket options (keepalive, buffers, linger, nodelay) on the listening socket.
+    /// Accepted sockets inherit these options from the listening socket.
+    ///
+    /// Do nothing for Unix sockets and named pipes
+    pub fn configure(&self, opts: &SocketOptions) -> Result<(), io::Error> {
+        match &self.listener {
+            #[cfg(target_family = "unix")]
+            StreamListenerKind::Unix(_) => Ok(()),
+            #[cfg(target_family = "windows")]
+            StreamListenerKind::NamedPipe(_) => Ok(()),
+            StreamListenerKind::Tcp(l) => opts.apply(l),
+            StreamListenerKind::Ssl(l, _, _) => opts.apply(l),
+        }
+    }
+
+    /// Method to get an inheritable copy of the listening socket, with its mapping for the next process (only on unix systems).
+    /// The listener keep accepting clients with its own socket
+    #[cfg(target_family = "unix")]
+    pub fn listener_fd(&self) -> Result<ListenerFd, io::Error> {
+        let socket = socket2::Socket::from(self.as_fd().try_clone_to_owned()?);
+        socket.set_cloexec(false)?;
+        Ok(ListenerFd {
+            url: self.to_string(),
+            fd: socket.into_raw_fd(),
+        })
+    }
+
+    /// Method to retrieve the listening socket, to be inherited by the next process (only on unix systems).
+    /// The file descriptor is kept open across `exec`, and is owned by the caller
+    ///
+    /// ```
+    /// use std::os::fd::{FromRawFd, OwnedFd};
+    /// use prosa::io::listener::{ListenerFd, StreamListener};
+    ///
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() -> std::io::Result<()> {
+    /// let stream_listener = StreamListener::bind("127.0.0.1:0").await?;
+    /// let addr = stream_listener.local_addr()?;
+    ///
+    /// let listener_fd: ListenerFd = stream_listener.into_raw_fd()?;
+    /// assert_eq!(format!("tcp://{}", addr), listener_fd.url);
+    /// // Mapping to give with the `PROSA_LISTEN_FDS` environment variable
+    /// assert_eq!(format!("tcp://{}={}", addr, listener_fd.fd), listener_fd.to_string());
+    /// # drop(unsafe { OwnedFd::from_raw_fd(listener_fd.fd) });
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(target_family = "unix")]
+    pub fn into_raw_fd(self) -> Result<ListenerFd, io::Error> {
+        self.listener_fd()
+    }
 }
 
+#[cfg(target_family = "unix")]
 impl AsFd for StreamListener {
     fn as_fd(&self) -> BorrowedFd<'_> {
-        match self {
+        match &self.listener {
             #[cfg(target_family = "unix")]
-            StreamListener::Unix(l) => l.as_fd(),
-            StreamListener::Tcp(l) => l.as_fd(),
-            StreamListener::Ssl(l, _, _) => l.as_fd(),
+            StreamListenerKind::Unix(l) => l.as_fd(),
+            StreamListenerKind::Tcp(l) => l.as_fd(),
+            StreamListenerKind::Ssl(l, _, _) => l.as_fd(),
         }
     }
 }
 
+#[cfg(target_family = "unix")]
 impl AsRawFd for StreamListener {
     fn as_raw_fd(&self) -> RawFd {
-        match self {
+        match &self.listener {
             #[cfg(target_family = "unix")]
-            StreamListener::Unix(l) => l.as_raw_fd(),
-            StreamListener::Tcp(l) => l.as_raw_fd(),
-            StreamListener::Ssl(l, _, _) => l.as_raw_fd(),
+            StreamListenerKind::Unix(l) => l.as_raw_fd(),
+            StreamListenerKind::Tcp(l) => l.as_raw_fd(),
+            StreamListenerKind::Ssl(l, _, _) => l.as_raw_fd(),
         }
     }
 }
@@ -327,11 +716,22 @@ impl fmt::Display for StreamListener {
                 Ipv4Addr::new(0, 0, 0, 0),
                 0,
             )));
-        match self {
+        match &self.listener {
             #[cfg(target_family = "unix")]
-            StreamListener::Unix(_) => write!(f, "unix://{}", addr),
-            StreamListener::Tcp(_) => write!(f, "tcp://{}", addr),
-            StreamListener::Ssl(_, _, _) => write!(f, "ssl://{}", addr),
+            StreamListenerKind::Unix(_) => write!(f, "unix://{}", addr),
+            #[cfg(target_family = "windows")]
+            StreamListenerKind::NamedPipe(_) => write!(f, "pipe://{}", addr),
+            StreamListenerKind::Tcp(_) => write!(f, "tcp://{}", addr),
+            StreamListenerKind::Ssl(_, _, _) => write!(f, "ssl://{}", addr),
+        }
+    }
+}
+
+impl From<StreamListenerKind> for StreamListener {
+    fn from(listener: StreamListenerKind) -> Self {
+        StreamListener {
+            listener,
+            accept_state: AcceptState::default(),
         }
     }
 }
@@ -339,13 +739,603 @@ impl fmt::Display for StreamListener {
 #[cfg(target_family = "unix")]
 impl From<tokio::net::UnixListener> for StreamListener {
     fn from(listener: tokio::net::UnixListener) -> Self {
-        StreamListener::Unix(listener)
+        StreamListenerKind::Unix(listener).into()
+    }
+}
+
+#[cfg(target_family = "windows")]
+impl From<NamedPipeListener> for StreamListener {
+    fn from(listener: NamedPipeListener) -> Self {
+        StreamListenerKind::NamedPipe(listener).into()
     }
 }
 
 impl From<TcpListener> for StreamListener {
     fn from(listener: TcpListener) -> Self {
-        StreamListener::Tcp(listener)
+        StreamListenerKind::Tcp(listener).into()
+    }
+}
+
+/// Environment variable that map listener urls to inherited file descriptors (`url=fd` separated by `;`)
+pub const LISTEN_FDS_ENV: &str = "PROSA_LISTEN_FDS";
+
+/// First file descriptor passed with the systemd socket activation convention
+#[cfg(target_family = "unix")]
+const SD_LISTEN_FDS_START: RawFd = 3;
+
+/// Inherited file descriptors already used by a listener, never reused
+#[cfg(target_family = "unix")]
+static TAKEN_FDS: Mutex<Option<HashSet<RawFd>>> = Mutex::new(None);
+
+/// Listening sockets handed over to the next process, once enabled
+#[cfg(target_family = "unix")]
+static HANDOVER_FDS: Mutex<Option<Vec<ListenerFd>>> = Mutex::new(None);
+
+/// Listening socket that can be inherited by the next process, with its url (only on unix systems)
+///
+/// Its display is the mapping expected by the [`LISTEN_FDS_ENV`] environment variable (`url=fd`).
+#[cfg(target_family = "unix")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListenerFd {
+    /// Url of the listener, with its local address (`tcp://0.0.0.0:8080` for example)
+    pub url: String,
+    /// File descriptor of the listening socket
+    pub fd: RawFd,
+}
+
+#[cfg(target_family = "unix")]
+impl ListenerFd {
+    /// Method to build the value of the [`LISTEN_FDS_ENV`] environment variable for listening sockets
+    pub fn env_value(listener_fds: &[ListenerFd]) -> String {
+        listener_fds
+            .iter()
+            .map(|l| l.to_string())
+            .collect::<Vec<String>>()
+            .join(";")
+    }
+}
+
+#[cfg(target_family = "unix")]
+impl fmt::Display for ListenerFd {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}={}", self.url, self.fd)
+    }
+}
+
+/// Method to get the file descriptors inherited from the previous process, with their name if they have one
+#[cfg(target_family = "unix")]
+fn inherited_fds() -> Vec<(Option<String>, RawFd)> {
+    let mut fds = Vec::new();
+    if let Ok(mapping) = std::env::var(LISTEN_FDS_ENV) {
+        for entry in mapping.split(';').filter(|e| !e.is_empty()) {
+            match entry.rsplit_once('=').map(|(url, fd)| (url, fd.parse())) {
+                Some((url, Ok(fd))) => fds.push((Some(url.to_string()), fd)),
+                _ => debug!(
+                    "Ignore the inherited listener `{}` of {}",
+                    entry, LISTEN_FDS_ENV
+                ),
+            }
+        }
+    }
+
+    // systemd socket activation, if the file descriptors are for this process
+    let for_this_process = std::env::var("LISTEN_PID")
+        .map(|pid| pid == std::process::id().to_string())
+        .unwrap_or(true);
+    if let Some(count) = std::env::var("LISTEN_FDS")
+        .ok()
+        .and_then(|c| c.parse::<RawFd>().ok())
+        .filter(|_| for_this_process)
+    {
+        let names = std::env::var("LISTEN_FDNAMES").unwrap_or_default();
+        let mut names = names.split(':');
+        for fd in SD_LISTEN_FDS_START..SD_LISTEN_FDS_START + count {
+            fds.push((names.next().map(String::from), fd));
+        }
+    }
+
+    fds
+}
+
+/// Method to take the inherited listening sockets of an url (its name in the mapping, or bound on one of its addresses)
+#[cfg(target_family = "unix")]
+fn take_inherited_fds(url: &Url) -> Vec<OwnedFd> {
+    let is_unix = matches!(url.scheme(), "unix" | "file");
+    let addrs = if is_unix {
+        Vec::new()
+    } else {
+        url.socket_addrs(|| url.port_or_known_default())
+            .unwrap_or_default()
+    };
+
+    let mut taken_fds = TAKEN_FDS.lock().unwrap();
+    let taken_fds = taken_fds.get_or_insert_with(HashSet::new);
+    let mut fds = Vec::new();
+    for (name, fd) in inherited_fds() {
+        if taken_fds.contains(&fd) {
+            continue;
+        }
+
+        // Only a socket can be inherited
+        let local_addr =
+            match socket2::SockRef::from(&unsafe { BorrowedFd::borrow_raw(fd) }).local_addr() {
+                Ok(local_addr) => local_addr,
+                Err(_) => continue,
+            };
+
+        let matching = if is_unix {
+            local_addr
+                .as_pathname()
+                .is_some_and(|path| path == std::path::Path::new(url.path()))
+        } else {
+            local_addr
+                .as_socket()
+                .is_some_and(|addr| addrs.contains(&addr))
+        } || name
+            .and_then(|name| Url::parse(&name).ok())
+            .is_some_and(|name| &name == url);
+        if matching {
+            taken_fds.insert(fd);
+            // The file descriptor is owned by this process, and taken only once
+            let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+            let _ = socket2::SockRef::from(&fd).set_cloexec(true);
+            fds.push(fd);
+        }
+    }
+
+    fds
+}
+
+/// Method to build a listener from an inherited listening socket
+#[cfg(target_family = "unix")]
+fn inherited_listener(url: &Url, fd: OwnedFd) -> Result<StreamListener, io::Error> {
+    if matches!(url.scheme(), "unix" | "file") {
+        let listener = std::os::unix::net::UnixListener::from(fd);
+        listener.set_nonblocking(true)?;
+        Ok(UnixListener::from_std(listener)?.into())
+    } else {
+        let listener = std::net::TcpListener::from(fd);
+        listener.set_nonblocking(true)?;
+        Ok(TcpListener::from_std(listener)?.into())
+    }
+}
+
+/// Method to hand over the listeners of the servers to the next process at their shutdown (only on unix systems)
+///
+/// The handed over listening sockets are kept open until the end of the process, to be inherited with [`spawn_handover`].
+#[cfg(target_family = "unix")]
+pub fn enable_handover() {
+    HANDOVER_FDS.lock().unwrap().get_or_insert_with(Vec::new);
+}
+
+/// Method to hand over a listener to the next process, if the handover is enabled with [`enable_handover`]. Return `true` if the listener is handed over
+#[cfg(target_family = "unix")]
+pub fn handover(listener: &StreamListener) -> Result<bool, io::Error> {
+    if let Some(handover_fds) = HANDOVER_FDS.lock().unwrap().as_mut() {
+        handover_fds.push(listener.listener_fd()?);
+        Ok(true)
+    } else {
+        Ok(false)
+    }
+}
+
+/// Getter of the listening sockets handed over to the next process
+#[cfg(target_family = "unix")]
+pub fn handover_fds() -> Vec<ListenerFd> {
+    HANDOVER_FDS.lock().unwrap().clone().unwrap_or_default()
+}
+
+/// Method to spawn the next process with the handed over listening sockets, given with the [`LISTEN_FDS_ENV`] environment variable
+#[cfg(target_family = "unix")]
+pub fn spawn_handover(
+    command: &mut std::process::Command,
+) -> Result<std::process::Child, io::Error> {
+    command
+        .env(LISTEN_FDS_ENV, ListenerFd::env_value(&handover_fds()))
+        .env_remove("LISTEN_FDS")
+        .env_remove("LISTEN_PID")
+        .env_remove("LISTEN_FDNAMES")
+        .spawn()
+}
+
+/// ProSA listener that accept clients on several bound addresses (IPv4 and IPv6 addresses of a host for example)
+///
+/// All the inner listeners share the same configuration, so the SSL handshake is the same whatever the listener that accepted the client.
+pub struct MultiListener {
+    listeners: Vec<StreamListener>,
+}
+
+impl MultiListener {
+    /// Method to create a multi listener out of bound listeners
+    pub fn new(listeners: Vec<StreamListener>) -> MultiListener {
+        MultiListener { listeners }
+    }
+
+    /// Getter of the inner listeners
+    pub fn listeners(&self) -> &[StreamListener] {
+        &self.listeners
+    }
+
+    /// Method to retrieve the inner listeners
+    pub fn into_listeners(self) -> Vec<StreamListener> {
+        self.listeners
+    }
+
+    /// Returns the local addresses that the inner listeners are bound to
+    pub fn local_addrs(&self) -> Result<Vec<SocketAddr>, io::Error> {
+        self.listeners.iter().map(|l| l.local_addr()).collect()
+    }
+
+    /// Method to accept a client on any of the inner listeners
+    pub async fn accept(&self) -> Result<(Stream, SocketAddr), io::Error> {
+        let (stream, addr) = self.accept_raw().await?;
+        Ok((self.handshake(stream).await?, addr))
+    }
+
+    /// Method to accept a client that respect the accept policy on any of the inner listeners
+    ///
+    /// The policy is shared by all the inner listeners. See [`StreamListener::accept_with_policy`]
+    pub async fn accept_with_policy(
+        &self,
+        policy: &AcceptPolicyState,
+    ) -> Result<(Stream, SocketAddr, ConnectionGuard), io::Error> {
+        loop {
+            let (stream, addr) = self.accept_raw().await?;
+            if let Ok(guard) = policy.admit(&addr) {
+                return Ok((self.handshake(stream).await?, addr, guard));
+            }
+        }
+    }
+
+    /// Method to accept a client on any of the inner listeners without SSL handshake (must be done with handshake after)
+    pub async fn accept_raw(&self) -> Result<(Stream, SocketAddr), io::Error> {
+        match self.listeners.as_slice() {
+            [] => Err(io::Error::new(
+                io::ErrorKind::NotConnected,
+                "no listener to accept from",
+            )),
+            [listener] => listener.accept_raw().await,
+            listeners => {
+                let mut accepts: Vec<_> =
+                    listeners.iter().map(|l| Box::pin(l.accept_raw())).collect();
+                poll_fn(|cx| {
+                    for accept in accepts.iter_mut() {
+                        if let Poll::Ready(accepted) = accept.as_mut().poll(cx) {
+                            return std::task::Poll::Ready(accepted);
+                        }
+                    }
+
+                    Poll::Pending
+                })
+                .await
+            }
+        }
+    }
+
+    /// Method to do an handshake with a client after an accept (Do nothing if the handshake is already done)
+    pub async fn handshake(&self, stream: Stream) -> Result<Stream, io::Error> {
+        if let Some(listener) = self.listeners.first() {
+            listener.handshake(stream).await
+        } else {
+            Ok(stream)
+        }
+    }
+
+    /// Method to stop accepting new clients on all the inner listeners. See [`StreamListener::close_accept`]
+    pub fn close_accept(&self) {
+        for listener in &self.listeners {
+            listener.close_accept();
+        }
+    }
+
+    /// Method to know if all the inner listeners stopped accepting new clients
+    pub fn is_accept_closed(&self) -> bool {
+        self.listeners.iter().all(|l| l.is_accept_closed())
+    }
+
+    /// Method to hand over all the inner listeners to the next process (see [`handover`])
+    #[cfg(target_family = "unix")]
+    pub fn handover(&self) -> Result<bool, io::Error> {
+        let mut handed_over = false;
+        for listener in &self.listeners {
+            handed_over |= handover(listener)?;
+        }
+        Ok(handed_over)
+    }
+
+    /// Method to get the deadline to finish the in-flight streams once the listeners stopped accepting new clients. See [`StreamListener::drain_deadline`]
+    pub fn drain_deadline(&self, drain_timeout: Duration) -> Option<Instant> {
+        self.listeners
+            .iter()
+            .filter_map(|l| l.drain_deadline(drain_timeout))
+            .max()
+    }
+}
+
+impl fmt::Debug for MultiListener {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(&self.listeners).finish()
+    }
+}
+
+impl fmt::Display for MultiListener {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, listener) in self.listeners.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", listener)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl From<StreamListener> for MultiListener {
+    fn from(listener: StreamListener) -> Self {
+        MultiListener::new(vec![listener])
+    }
+}
+
+/// Policy to protect a listener from abusive clients
+///
+/// The maximum number of connections is the `max_socket` of the [`ListenerSetting`].
+///
+/// ```yaml
+/// accept_policy:
+///   max_per_ip: 10
+///   max_rate: 100
+///   max_violations: 5
+///   ban_duration:
+///     secs: 60
+///     nanos: 0
+/// ```
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+pub struct AcceptPolicy {
+    /// Maximum number of live connections from the same source IP (no limit if not set)
+    #[serde(default)]
+    pub max_per_ip: Option<u32>,
+    /// Maximum number of accepted connections per second (no limit if not set)
+    #[serde(default)]
+    pub max_rate: Option<u32>,
+    /// Number of connections of a source IP rejected over `max_per_ip` before it's banned
+    #[serde(default = "AcceptPolicy::default_max_violations")]
+    pub max_violations: u32,
+    /// Duration of the ban of a source IP (no ban if not set)
+    #[serde(default)]
+    pub ban_duration: Option<Duration>,
+}
+
+impl AcceptPolicy {
+    fn default_max_violations() -> u32 {
+        5
+    }
+}
+
+impl Default for AcceptPolicy {
+    fn default() -> Self {
+        AcceptPolicy {
+            max_per_ip: None,
+            max_rate: None,
+            max_violations: Self::default_max_violations(),
+            ban_duration: None,
+        }
+    }
+}
+
+/// Reason of a connection rejected by an [`AcceptPolicy`]
+#[derive(Debug, Error, Clone, Copy, PartialEq, Eq)]
+pub enum AcceptRejection {
+    /// The listener reached its `max_socket`
+    #[error("maximum number of connections reached")]
+    MaxSocket,
+    /// The source IP reached its maximum number of connections
+    #[error("maximum number of connections per IP reached")]
+    MaxPerIp,
+    /// The listener reached its accept rate
+    #[error("accept rate exceeded")]
+    Rate,
+    /// The source IP is banned after too many violations
+    #[error("source IP banned")]
+    Banned,
+}
+
+impl AcceptRejection {
+    /// Getter of the reason as a metric attribute
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AcceptRejection::MaxSocket => "max_socket",
+            AcceptRejection::MaxPerIp => "max_per_ip",
+            AcceptRejection::Rate => "rate",
+            AcceptRejection::Banned => "banned",
+        }
+    }
+}
+
+/// Counters of a source IP
+#[derive(Debug, Default)]
+struct IpCounters {
+    live: u32,
+    violations: u32,
+    banned_until: Option<Instant>,
+}
+
+/// Live counters of an accept policy
+#[derive(Debug)]
+struct PolicyCounters {
+    total: u64,
+    per_ip: HashMap<IpAddr, IpCounters>,
+    window_start: Instant,
+    window_count: u32,
+}
+
+impl PolicyCounters {
+    /// Method to start a new accept rate window every second
+    /// Source IPs without live connection nor ban are forgotten with their violations
+    fn refresh_window(&mut self, now: Instant) {
+        if now.duration_since(self.window_start) >= Duration::from_secs(1) {
+            self.window_start = now;
+            self.window_count = 0;
+            self.per_ip
+                .retain(|_, c| c.live > 0 || c.banned_until.is_some_and(|b| b > now));
+        }
+    }
+}
+
+/// State of an [`AcceptPolicy`] shared by all the accepts of a listener
+#[derive(Debug)]
+pub struct AcceptPolicyState {
+    policy: AcceptPolicy,
+    max_socket: u64,
+    counters: Arc<Mutex<PolicyCounters>>,
+    rejected: AtomicU64,
+    rejected_meter: Option<(Counter<u64>, KeyValue)>,
+}
+
+impl AcceptPolicyState {
+    /// Create the state of an accept policy for a maximum number of connections
+    pub fn new(policy: AcceptPolicy, max_socket: u64) -> AcceptPolicyState {
+        AcceptPolicyState {
+            policy,
+            max_socket,
+            counters: Arc::new(Mutex::new(PolicyCounters {
+                total: 0,
+                per_ip: HashMap::new(),
+                window_start: Instant::now(),
+                window_count: 0,
+            })),
+            rejected: AtomicU64::new(0),
+            rejected_meter: None,
+        }
+    }
+
+    /// Method to count the rejected connections on the `prosa_listener_rejected_connections` metric of a meter
+    pub fn with_meter(mut self, meter: &Meter, listener: String) -> AcceptPolicyState {
+        self.rejected_meter = Some((
+            meter
+                .u64_counter("prosa_listener_rejected_connections")
+                .with_description("connections rejected by the accept policy of a listener")
+                .init(),
+            KeyValue::new("listener", listener),
+        ));
+        self
+    }
+
+    /// Getter of the number of rejected connections
+    pub fn rejected(&self) -> u64 {
+        self.rejected.load(Ordering::Relaxed)
+    }
+
+    /// Getter of the number of live connections
+    pub fn live(&self) -> u64 {
+        self.counters.lock().unwrap().total
+    }
+
+    /// Method to admit a new connection from an address
+    ///
+    /// Return a guard that free the capacity when dropped, or the reason of the rejection.
+    pub fn admit(&self, addr: &SocketAddr) -> Result<ConnectionGuard, AcceptRejection> {
+        let ip = addr.ip();
+        let result = self.check(ip);
+        if let Err(rejection) = result {
+            self.rejected.fetch_add(1, Ordering::Relaxed);
+            if let Some((counter, listener)) = &self.rejected_meter {
+                counter.add(
+                    1,
+                    &[
+                        listener.clone(),
+                        KeyValue::new("reason", rejection.as_str()),
+                    ],
+                );
+            }
+            debug!(name: "accept_policy", target: "prosa::io::listener", addr = addr.to_string(), "Connection rejected: {}", rejection);
+        }
+
+        result
+    }
+
+    fn check(&self, ip: Option<IpAddr>) -> Result<ConnectionGuard, AcceptRejection> {
+        let now = Instant::now();
+        let mut counters = self.counters.lock().unwrap();
+        counters.refresh_window(now);
+
+        if let Some(ip_counters) = ip.and_then(|ip| counters.per_ip.get(&ip)) {
+            if ip_counters.banned_until.is_some_and(|b| b > now) {
+                return Err(AcceptRejection::Banned);
+            }
+        }
+
+        let rejection = if counters.total >= self.max_socket {
+            Some(AcceptRejection::MaxSocket)
+        } else if self
+            .policy
+            .max_rate
+            .is_some_and(|max_rate| counters.window_count >= max_rate)
+        {
+            Some(AcceptRejection::Rate)
+        } else if let Some((ip, max_per_ip)) = ip.zip(self.policy.max_per_ip) {
+            counters
+                .per_ip
+                .get(&ip)
+                .is_some_and(|c| c.live >= max_per_ip)
+                .then_some(AcceptRejection::MaxPerIp)
+        } else {
+            None
+        };
+
+        if let Some(rejection) = rejection {
+            // Count the violation of the source IP, and ban it if needed.
+            // Only the IP limit is a violation of the client, the other rejections come from the listener load
+            if let Some(ip) = ip.filter(|_| rejection == AcceptRejection::MaxPerIp) {
+                let ip_counters = counters.per_ip.entry(ip).or_default();
+                ip_counters.violations += 1;
+                if let Some(ban_duration) = self.policy.ban_duration {
+                    if ip_counters.violations >= self.policy.max_violations {
+                        ip_counters.banned_until = Some(now + ban_duration);
+                        ip_counters.violations = 0;
+                    }
+                }
+            }
+
+            return Err(rejection);
+        }
+
+        counters.total += 1;
+        counters.window_count += 1;
+        if let Some(ip) = ip {
+            counters.per_ip.entry(ip).or_default().live += 1;
+        }
+
+        Ok(ConnectionGuard {
+            counters: self.counters.clone(),
+            ip,
+        })
+    }
+}
+
+/// Guard of a connection admitted by an [`AcceptPolicyState`]. The capacity of the connection is freed when it's dropped
+#[derive(Debug)]
+pub struct ConnectionGuard {
+    counters: Arc<Mutex<PolicyCounters>>,
+    ip: Option<IpAddr>,
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        if let Ok(mut counters) = self.counters.lock() {
+            counters.total -= 1;
+            if let Some(ip) = self.ip {
+                if let Some(ip_counters) = counters.per_ip.get_mut(&ip) {
+                    ip_counters.live -= 1;
+                    if ip_counters.live == 0
+                        && ip_counters.violations == 0
+                        && ip_counters.banned_until.is_none()
+                    {
+                        counters.per_ip.remove(&ip);
+                    }
+                }
+            }
+        }
     }
 }
 
@@ -379,6 +1369,17 @@ pub struct ListenerSetting {
     #[serde(default = "ListenerSetting::default_max_socket")]
     /// Maximum number of socket
     pub max_socket: u64,
+    /// Optional socket options applied on accepted sockets
+    pub socket: Option<SocketOptions>,
+    /// Optional policy to protect the listener from abusive clients
+    #[serde(default)]
+    pub accept_policy: Option<AcceptPolicy>,
+    /// Bind every address resolved from the url instead of the first one (`localhost` on IPv4 and IPv6 for example)
+    #[serde(default)]
+    pub bind_all: bool,
+    /// Option to accept only IPv6 clients on IPv6 addresses. By default `[::]` accept IPv4 and IPv6 clients
+    #[serde(default)]
+    pub only_v6: Option<bool>,
 }
 
 impl ListenerSetting {
@@ -407,9 +1408,13 @@ impl ListenerSetting {
             ssl,
             ssl_context: None,
             max_socket: Self::default_max_socket(),
+            socket: None,
+            accept_policy: None,
+            bind_all: false,
+            only_v6: None,
         };
 
-        target.init_ssl_context(url.domain());
+        target.init_ssl_context(url.host_str());
         target
     }
 
@@ -423,15 +1428,162 @@ impl ListenerSetting {
         }
     }
 
-    /// Method to connect a ProSA stream to the remote target using the configuration
+    /// Setter of the protocols accepted with ALPN, by order of preference.
+    /// An SSL configuration is created if the listener don't have one
+    pub fn set_alpn(&mut self, alpn: Vec<String>) {
+        self.ssl
+            .get_or_insert_with(SslConfig::default)
+            .set_alpn(alpn);
+        self.ssl_context = None;
+        let url = self.url.clone();
+        self.init_ssl_context(url.host_str());
+    }
+
+    /// Method to create the state of the accept policy, limited by the `max_socket` of the listener
+    pub fn accept_policy_state(&self) -> AcceptPolicyState {
+        AcceptPolicyState::new(
+            self.accept_policy.clone().unwrap_or_default(),
+            self.max_socket,
+        )
+    }
+
+    /// Method to bind a ProSA listener on the first usable address of the configuration
+    ///
+    /// An inherited listening socket is used instead if there is one for the url (see [socket inheritance](crate::io::listener#socket-inheritance))
     pub async fn bind(&self) -> Result<StreamListener, io::Error> {
         #[cfg(target_family = "unix")]
+        if let Some(fd) = take_inherited_fds(&self.url).into_iter().next() {
+            debug!("Inherit the listening socket {:?} for {}", fd, self.url);
+            return self.setup_inherited(fd);
+        }
+
         if self.url.scheme() == "unix" || self.url.scheme() == "file" {
-            return Ok(StreamListener::Unix(UnixListener::bind(self.url.path())?));
+            #[cfg(target_family = "unix")]
+            return Ok(UnixListener::bind(self.url.path())?.into());
+            #[cfg(not(target_family = "unix"))]
+            return Err(unsupported_url(&self.url));
+        }
+
+        if self.url.scheme() == "pipe" {
+            #[cfg(target_family = "windows")]
+            return match url_named_pipe(&self.url) {
+                Some(path) => Ok(NamedPipeListener::bind(path)?.into()),
+                None => Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("{} doesn't target a named pipe", self.url),
+                )),
+            };
+            #[cfg(not(target_family = "windows"))]
+            return Err(unsupported_url(&self.url));
+        }
+
+        let mut last_err = None;
+        for addr in self.url.socket_addrs(|| self.url.port_or_known_default())? {
+            match StreamListener::bind_addr(addr, self.only_v6) {
+                Ok(stream_listener) => return self.setup_listener(stream_listener),
+                Err(e) => last_err = Some(e),
+            }
+        }
+
+        Err(last_err.unwrap_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("could not resolve {} to any address", self.url),
+            )
+        }))
+    }
+
+    /// Method to bind ProSA listeners on every address of the configuration if `bind_all` is set, on the first usable one otherwise
+    ///
+    /// With a port 0, all the addresses are bound on the port allocated for the first one.
+    ///
+    /// ```
+    /// use tokio::io;
+    /// use url::Url;
+    /// use prosa::io::listener::{ListenerSetting, MultiListener};
+    ///
+    /// async fn accepting() -> Result<(), io::Error> {
+    ///     let mut listener_setting = ListenerSetting::new(Url::parse("tcp://localhost:10000").unwrap(), None);
+    ///     listener_setting.bind_all = true;
+    ///     let multi_listener: MultiListener = listener_setting.bind_multi().await?;
+    ///
+    ///     loop {
+    ///         // Accept clients from any of the bound addresses
+    ///         let (stream, addr) = multi_listener.accept().await?;
+    ///
+    ///         // Handle the stream like any tokio stream
+    ///     }
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn bind_multi(&self) -> Result<MultiListener, io::Error> {
+        // Unix sockets and named pipes have a single address
+        let is_local = matches!(self.url.scheme(), "unix" | "file" | "pipe");
+        if !self.bind_all || is_local {
+            return Ok(self.bind().await?.into());
+        }
+
+        #[cfg(target_family = "unix")]
+        {
+            let inherited_fds = take_inherited_fds(&self.url);
+            if !inherited_fds.is_empty() {
+                return Ok(MultiListener::new(
+                    inherited_fds
+                        .into_iter()
+                        .map(|fd| self.setup_inherited(fd))
+                        .collect::<Result<Vec<StreamListener>, io::Error>>()?,
+                ));
+            }
+        }
+
+        let mut addrs = self.url.socket_addrs(|| self.url.port_or_known_default())?;
+        addrs.dedup();
+        let mut listeners = Vec::with_capacity(addrs.len());
+        let mut bound_port = None;
+        for mut addr in addrs {
+            if let Some(port) = bound_port.filter(|_| addr.port() == 0) {
+                addr.set_port(port);
+            }
+
+            let stream_listener =
+                self.setup_listener(StreamListener::bind_addr(addr, self.only_v6)?)?;
+            if bound_port.is_none() {
+                bound_port = Some(stream_listener.local_addr()?.port());
+            }
+
+            listeners.push(stream_listener);
+        }
+
+        if listeners.is_empty() {
+            Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("could not resolve {} to any address", self.url),
+            ))
+        } else {
+            Ok(MultiListener::new(listeners))
         }
+    }
+
+    /// Method to build a listener from an inherited listening socket, with the configuration of the listener
+    #[cfg(target_family = "unix")]
+    fn setup_inherited(&self, fd: OwnedFd) -> Result<StreamListener, io::Error> {
+        let stream_listener = inherited_listener(&self.url, fd)?;
+        if matches!(self.url.scheme(), "unix" | "file") {
+            Ok(stream_listener)
+        } else {
+            self.setup_listener(stream_listener)
+        }
+    }
 
-        let addrs = self.url.socket_addrs(|| self.url.port_or_known_default())?;
-        let mut stream_listener = StreamListener::bind(&*addrs).await?;
+    /// Method to apply the socket options and the SSL configuration on a bound listener
+    fn setup_listener(
+        &self,
+        mut stream_listener: StreamListener,
+    ) -> Result<StreamListener, io::Error> {
+        if let Some(socket_options) = &self.socket {
+            stream_listener.configure(socket_options)?;
+        }
 
         if let Some(ssl_acceptor) = &self.ssl_context {
             stream_listener = stream_listener.ssl_acceptor(
@@ -439,7 +1591,8 @@ impl ListenerSetting {
                 self.ssl.as_ref().map(|c| c.get_ssl_timeout()),
             );
         } else if let Some(ssl_config) = &self.ssl {
-            if let Ok(ssl_acceptor_builder) = ssl_config.init_tls_server_context(self.url.domain())
+            if let Ok(ssl_acceptor_builder) =
+                ssl_config.init_tls_server_context(self.url.host_str())
             {
                 stream_listener = stream_listener.ssl_acceptor(
                     ssl_acceptor_builder.build(),
@@ -448,7 +1601,8 @@ impl ListenerSetting {
             }
         } else if url_is_ssl(&self.url) {
             let ssl_config = SslConfig::default();
-            if let Ok(ssl_acceptor_builder) = ssl_config.init_tls_server_context(self.url.domain())
+            if let Ok(ssl_acceptor_builder) =
+                ssl_config.init_tls_server_context(self.url.host_str())
             {
                 stream_listener = stream_listener.ssl_acceptor(
                     ssl_acceptor_builder.build(),
@@ -468,6 +1622,10 @@ impl From<Url> for ListenerSetting {
             ssl: None,
             ssl_context: None,
             max_socket: Self::default_max_socket(),
+            socket: None,
+            accept_policy: None,
+            bind_all: false,
+            only_v6: None,
         }
     }
 }
@@ -478,6 +1636,10 @@ impl fmt::Debug for ListenerSetting {
             .field("url", &self.url)
             .field("ssl", &self.ssl)
             .field("max_socket", &self.max_socket)
+            .field("socket", &self.socket)
+            .field("accept_policy", &self.accept_policy)
+            .field("bind_all", &self.bind_all)
+            .field("only_v6", &self.only_v6)
             .finish()
     }
 }
@@ -0,0 +1,625 @@
+//! Module that define a pool of persistent connections to a target, for client processors
+use std::{
+    collections::VecDeque,
+    fmt,
+    future::Future,
+    io,
+    ops::{Deref, DerefMut},
+    pin::Pin,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex, OnceLock, Weak,
+    },
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use opentelemetry::{
+    metrics::{Histogram, Meter, ObservableGauge},
+    KeyValue,
+};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadBuf},
+    sync::{OwnedSemaphorePermit, Semaphore},
+    task::JoinHandle,
+    time::Instant,
+};
+
+use super::stream::{TargetSetting, TimedStream};
+
+/// Error define for stream pools
+#[derive(Debug, Error)]
+pub enum PoolError {
+    /// Error that indicate that no connection was available before the acquire timeout
+    #[error("No connection available after {0:?}")]
+    AcquireTimeout(Duration),
+    /// Error on the connection to the target
+    #[error("Pool connection error: {0}")]
+    Io(#[from] io::Error),
+}
+
+/// Options of a stream pool
+///
+/// ```yaml
+/// pool:
+///   size: 8
+///   max_idle_time:
+///     secs: 60
+///     nanos: 0
+///   health_check_interval:
+///     secs: 10
+///     nanos: 0
+///   acquire_timeout:
+///     secs: 5
+///     nanos: 0
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct PoolOptions {
+    /// Maximum number of connections opened to the target
+    #[serde(default = "PoolOptions::default_size")]
+    pub size: usize,
+    /// Duration after which an unused connection is closed
+    #[serde(default)]
+    pub max_idle_time: Option<Duration>,
+    /// Interval of the health checks of the unused connections
+    #[serde(default)]
+    pub health_check_interval: Option<Duration>,
+    /// Maximum duration to wait for a connection (including the connection to the target)
+    #[serde(default = "PoolOptions::default_acquire_timeout")]
+    pub acquire_timeout: Duration,
+}
+
+impl PoolOptions {
+    fn default_size() -> usize {
+        8
+    }
+
+    fn default_acquire_timeout() -> Duration {
+        Duration::from_secs(5)
+    }
+
+    /// Method to create pool options with the maximum number of connections
+    pub fn new(size: usize) -> PoolOptions {
+        PoolOptions {
+            size,
+            ..Default::default()
+        }
+    }
+}
+
+impl Default for PoolOptions {
+    fn default() -> Self {
+        PoolOptions {
+            size: Self::default_size(),
+            max_idle_time: None,
+            health_check_interval: None,
+            acquire_timeout: Self::default_acquire_timeout(),
+        }
+    }
+}
+
+/// Probe used to check the health of an unused connection. The connection is closed if the probe return an error
+pub type HealthProbe = Arc<
+    dyn for<'a> Fn(&'a mut TimedStream) -> Pin<Box<dyn Future<Output = io::Result<()>> + Send + 'a>>
+        + Send
+        + Sync,
+>;
+
+/// Unused connection of the pool
+struct IdleStream {
+    stream: TimedStream,
+    since: Instant,
+}
+
+/// State of the pool shared with its connections
+struct PoolShared {
+    permits: Arc<Semaphore>,
+    idle: Mutex<VecDeque<IdleStream>>,
+    open: AtomicUsize,
+    in_use: AtomicUsize,
+}
+
+impl PoolShared {
+    /// Method to close an opened connection
+    fn close(&self, stream: TimedStream) {
+        self.open.fetch_sub(1, Ordering::AcqRel);
+        drop(stream);
+    }
+
+    /// Method to take the most recently used connection that is not expired
+    fn pop_idle(&self, max_idle_time: Option<Duration>) -> Option<TimedStream> {
+        let mut idle = self.idle.lock().unwrap();
+        while let Some(idle_stream) = idle.pop_back() {
+            if max_idle_time.is_some_and(|max| idle_stream.since.elapsed() >= max) {
+                self.close(idle_stream.stream);
+            } else {
+                return Some(idle_stream.stream);
+            }
+        }
+
+        None
+    }
+
+    /// Method to check all the unused connections: expired or unhealthy connections are closed
+    async fn check_idle(&self, max_idle_time: Option<Duration>, probe: Option<&HealthProbe>) {
+        let count = self.idle.lock().unwrap().len();
+        for _ in 0..count {
+            // Take a permit to not exceed the pool size if a connection is acquired meanwhile
+            let Ok(_permit) = self.permits.try_acquire() else {
+                return;
+            };
+            let Some(mut idle_stream) = self.idle.lock().unwrap().pop_front() else {
+                return;
+            };
+
+            if max_idle_time.is_some_and(|max| idle_stream.since.elapsed() >= max) {
+                self.close(idle_stream.stream);
+            } else if let Some(probe) = probe {
+                if probe(&mut idle_stream.stream).await.is_ok() {
+                    self.idle.lock().unwrap().push_back(idle_stream);
+                } else {
+                    self.close(idle_stream.stream);
+                }
+            } else {
+                self.idle.lock().unwrap().push_back(idle_stream);
+            }
+        }
+    }
+}
+
+/// Pool of persistent connections to a target
+///
+/// Connections are opened on demand (up to the pool size) and given back to the pool once the [`PooledStream`] is dropped.
+/// A connection is closed instead of given back if it's poisoned (explicitly, or by an IO error), if it stayed unused more than the maximum idle time, or if the health probe failed on it.
+/// Closed connections are replaced by new ones on the next acquires.
+///
+/// With a meter, the number of opened connections is exposed by the `prosa_pool_size` gauge, the number of used connections by the `prosa_pool_in_use` gauge, and the acquire durations are recorded in the `prosa_pool_acquire_duration` histogram.
+///
+/// ```
+/// use tokio::io::{AsyncReadExt as _, AsyncWriteExt as _};
+/// use url::Url;
+/// use prosa::io::pool::{PoolError, PoolOptions, StreamPool};
+/// use prosa::io::stream::TargetSetting;
+///
+/// async fn send_echo() -> Result<(), PoolError> {
+///     let target = TargetSetting::from(Url::parse("tcp://localhost:7").unwrap());
+///     let pool = StreamPool::new(target, PoolOptions::new(4)).health_check(|stream| {
+///         Box::pin(async move {
+///             stream.write_all(b"ping").await?;
+///             stream.read_exact(&mut [0u8; 4]).await.map(|_| ())
+///         })
+///     });
+///
+///     let mut stream = pool.acquire().await?;
+///     stream.write_all(b"ProSA").await?;
+///
+///     // The connection is given back to the pool when dropped
+///     Ok(())
+/// }
+/// ```
+pub struct StreamPool {
+    target: TargetSetting,
+    options: PoolOptions,
+    shared: Arc<PoolShared>,
+    probe: Option<HealthProbe>,
+    health_task: OnceLock<JoinHandle<()>>,
+    acquire_duration: Option<(Histogram<f64>, Vec<KeyValue>)>,
+    _gauges: Vec<ObservableGauge<u64>>,
+}
+
+impl StreamPool {
+    /// Method to create a pool of connections to a target. No connection is opened until it's acquired
+    pub fn new(target: TargetSetting, options: PoolOptions) -> StreamPool {
+        StreamPool {
+            target,
+            shared: Arc::new(PoolShared {
+                permits: Arc::new(Semaphore::new(options.size.max(1))),
+                idle: Mutex::new(VecDeque::new()),
+                open: AtomicUsize::new(0),
+                in_use: AtomicUsize::new(0),
+            }),
+            options,
+            probe: None,
+            health_task: OnceLock::new(),
+            acquire_duration: None,
+            _gauges: Vec::new(),
+        }
+    }
+
+    /// Setter of the probe used to check the health of the unused connections (every `health_check_interval` if set)
+    pub fn health_check<F>(mut self, probe: F) -> Self
+    where
+        F: for<'a> Fn(
+                &'a mut TimedStream,
+            ) -> Pin<Box<dyn Future<Output = io::Result<()>> + Send + 'a>>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.probe = Some(Arc::new(probe));
+        self
+    }
+
+    /// Setter of the meter used to expose the pool metrics
+    pub fn meter(mut self, meter: &Meter, attributes: Vec<KeyValue>) -> Self {
+        let weak_shared = Arc::downgrade(&self.shared);
+        let size_attributes = attributes.clone();
+        self._gauges.push(
+            meter
+                .u64_observable_gauge("prosa_pool_size")
+                .with_description("Number of connections opened by the pool")
+                .with_callback(move |observer| {
+                    if let Some(shared) = weak_shared.upgrade() {
+                        observer
+                            .observe(shared.open.load(Ordering::Acquire) as u64, &size_attributes);
+                    }
+                })
+                .init(),
+        );
+
+        let weak_shared = Arc::downgrade(&self.shared);
+        let in_use_attributes = attributes.clone();
+        self._gauges.push(
+            meter
+                .u64_observable_gauge("prosa_pool_in_use")
+                .with_description("Number of pool connections in use")
+                .with_callback(move |observer| {
+                    if let Some(shared) = weak_shared.upgrade() {
+                        observer.observe(
+                            shared.in_use.load(Ordering::Acquire) as u64,
+                            &in_use_attributes,
+                        );
+                    }
+                })
+                .init(),
+        );
+
+        self.acquire_duration = Some((
+            meter
+                .f64_histogram("prosa_pool_acquire_duration")
+                .with_description("Duration to acquire a pool connection")
+                .with_unit("seconds")
+                .init(),
+            attributes,
+        ));
+        self
+    }
+
+    /// Getter of the pool target
+    pub fn get_target(&self) -> &TargetSetting {
+        &self.target
+    }
+
+    /// Getter of the pool options
+    pub fn get_options(&self) -> &PoolOptions {
+        &self.options
+    }
+
+    /// Getter of the number of opened connections
+    pub fn size(&self) -> usize {
+        self.shared.open.load(Ordering::Acquire)
+    }
+
+    /// Getter of the number of connections in use
+    pub fn in_use(&self) -> usize {
+        self.shared.in_use.load(Ordering::Acquire)
+    }
+
+    /// Getter of the number of unused connections
+    pub fn idle(&self) -> usize {
+        self.shared.idle.lock().unwrap().len()
+    }
+
+    /// Method to acquire a connection from the pool.
+    /// Wait for a connection to be given back if all of them are in use, and open a new one if none is available
+    pub async fn acquire(&self) -> Result<PooledStream, PoolError> {
+        self.start_health_check();
+
+        let start = Instant::now();
+        let result = tokio::time::timeout(self.options.acquire_timeout, self.checkout()).await;
+        if let Some((acquire_duration, attributes)) = &self.acquire_duration {
+            acquire_duration.record(start.elapsed().as_secs_f64(), attributes);
+        }
+
+        result.unwrap_or(Err(PoolError::AcquireTimeout(self.options.acquire_timeout)))
+    }
+
+    async fn checkout(&self) -> Result<PooledStream, PoolError> {
+        let permit = self
+            .shared
+            .permits
+            .clone()
+            .acquire_owned()
+            .await
+            .map_err(io::Error::other)?;
+        let (stream, reused) = match self.shared.pop_idle(self.options.max_idle_time) {
+            Some(stream) => (stream, true),
+            None => {
+                let stream = self.target.connect_timed().await?;
+                self.shared.open.fetch_add(1, Ordering::AcqRel);
+                (stream, false)
+            }
+        };
+
+        self.shared.in_use.fetch_add(1, Ordering::AcqRel);
+        Ok(PooledStream {
+            stream: Some(stream),
+            reused,
+            poisoned: false,
+            shared: self.shared.clone(),
+            _permit: permit,
+        })
+    }
+
+    /// Method to check the unused connections now: expired connections and the ones that fail the health probe are closed
+    pub async fn check_health(&self) {
+        self.shared
+            .check_idle(self.options.max_idle_time, self.probe.as_ref())
+            .await
+    }
+
+    /// Method to start the periodic health checks if they're configured
+    fn start_health_check(&self) {
+        if let Some(interval) = self.options.health_check_interval {
+            if self.options.max_idle_time.is_some() || self.probe.is_some() {
+                self.health_task.get_or_init(|| {
+                    tokio::spawn(Self::run_health_check(
+                        Arc::downgrade(&self.shared),
+                        interval,
+                        self.options.max_idle_time,
+                        self.probe.clone(),
+                    ))
+                });
+            }
+        }
+    }
+
+    async fn run_health_check(
+        shared: Weak<PoolShared>,
+        interval: Duration,
+        max_idle_time: Option<Duration>,
+        probe: Option<HealthProbe>,
+    ) {
+        let mut interval = tokio::time::interval_at(Instant::now() + interval, interval);
+        loop {
+            interval.tick().await;
+            match shared.upgrade() {
+                Some(shared) => shared.check_idle(max_idle_time, probe.as_ref()).await,
+                None => return,
+            }
+        }
+    }
+}
+
+impl Drop for StreamPool {
+    fn drop(&mut self) {
+        if let Some(health_task) = self.health_task.get() {
+            health_task.abort();
+        }
+    }
+}
+
+impl fmt::Debug for StreamPool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StreamPool")
+            .field("target", &self.target)
+            .field("options", &self.options)
+            .field("size", &self.size())
+            .field("in_use", &self.in_use())
+            .finish()
+    }
+}
+
+/// Connection acquired from a [`StreamPool`], given back to the pool when dropped
+///
+/// The connection is closed instead if it's poisoned. A connection is poisoned automatically if an IO error occur on it.
+pub struct PooledStream {
+    stream: Option<TimedStream>,
+    reused: bool,
+    poisoned: bool,
+    shared: Arc<PoolShared>,
+    _permit: OwnedSemaphorePermit,
+}
+
+impl PooledStream {
+    /// Method to know if the connection was already used before (`false` for a new connection)
+    pub fn is_reused(&self) -> bool {
+        self.reused
+    }
+
+    /// Method to poison the connection, to close it instead of giving it back to the pool
+    pub fn poison(&mut self) {
+        self.poisoned = true;
+    }
+
+    /// Method to know if the connection is poisoned
+    pub fn is_poisoned(&self) -> bool {
+        self.poisoned
+    }
+
+    /// Method to poison the connection if an IO operation failed
+    fn check<T>(&mut self, poll: Poll<io::Result<T>>) -> Poll<io::Result<T>> {
+        if let Poll::Ready(Err(_)) = &poll {
+            self.poisoned = true;
+        }
+
+        poll
+    }
+}
+
+impl Deref for PooledStream {
+    type Target = TimedStream;
+
+    fn deref(&self) -> &Self::Target {
+        self.stream.as_ref().unwrap()
+    }
+}
+
+impl DerefMut for PooledStream {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.stream.as_mut().unwrap()
+    }
+}
+
+impl AsyncRead for PooledStream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let poll = Pin::new(&mut **self).poll_read(cx, buf);
+        self.check(poll)
+    }
+}
+
+impl AsyncWrite for PooledStream {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let poll = Pin::new(&mut **self).poll_write(cx, buf);
+        self.check(poll)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let poll = Pin::new(&mut **self).poll_flush(cx);
+        self.check(poll)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        // A connection shutted down can't be reused
+        self.poisoned = true;
+        Pin::new(&mut **self).poll_shutdown(cx)
+    }
+}
+
+impl Drop for PooledStream {
+    fn drop(&mut self) {
+        self.shared.in_use.fetch_sub(1, Ordering::AcqRel);
+        if let Some(stream) = self.stream.take() {
+            if self.poisoned {
+                self.shared.close(stream);
+            } else {
+                self.shared.idle.lock().unwrap().push_back(IdleStream {
+                    stream,
+                    since: Instant::now(),
+                });
+            }
+        }
+    }
+}
+
+impl fmt::Debug for PooledStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PooledStream")
+            .field("stream", &self.stream)
+            .field("reused", &self.reused)
+            .field("poisoned", &self.poisoned)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::{
+        io::{AsyncReadExt as _, AsyncWriteExt as _},
+        net::TcpListener,
+        task::JoinSet,
+    };
+    use url::Url;
+
+    use super::*;
+
+    const ADDR: &str = "127.0.0.1:41840";
+
+    /// Start a TCP echo server. All its connections are closed once the returned task is aborted
+    async fn echo_server() -> JoinHandle<()> {
+        let listener = TcpListener::bind(ADDR).await.unwrap();
+        tokio::spawn(async move {
+            let mut connections = JoinSet::new();
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                connections.spawn(async move {
+                    let (mut reader, mut writer) = stream.split();
+                    let _ = tokio::io::copy(&mut reader, &mut writer).await;
+                });
+            }
+        })
+    }
+
+    async fn echo(stream: &mut PooledStream, data: &[u8]) -> io::Result<()> {
+        stream.write_all(data).await?;
+        let mut buf = vec![0u8; data.len()];
+        stream.read_exact(&mut buf).await?;
+        assert_eq!(data, buf.as_slice());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn stream_pool() {
+        let server = echo_server().await;
+
+        let mut options = PoolOptions::new(2);
+        options.acquire_timeout = Duration::from_millis(200);
+        let target = TargetSetting::from(Url::parse(&format!("tcp://{}", ADDR)).unwrap());
+        let pool = StreamPool::new(target, options).health_check(|stream| {
+            Box::pin(async move {
+                stream.write_all(b"ping").await?;
+                let mut buf = [0u8; 4];
+                stream.read_exact(&mut buf).await?;
+                if &buf == b"ping" {
+                    Ok(())
+                } else {
+                    Err(io::Error::new(io::ErrorKind::InvalidData, "bad probe"))
+                }
+            })
+        });
+
+        // Exhaust the pool
+        let mut first = pool.acquire().await.unwrap();
+        let mut second = pool.acquire().await.unwrap();
+        echo(&mut first, b"first").await.unwrap();
+        echo(&mut second, b"second").await.unwrap();
+        assert_eq!(2, pool.in_use());
+        assert!(matches!(
+            pool.acquire().await,
+            Err(PoolError::AcquireTimeout(_))
+        ));
+
+        // A given back connection is reused
+        drop(first);
+        let mut third = pool.acquire().await.unwrap();
+        assert!(third.is_reused());
+        echo(&mut third, b"third").await.unwrap();
+        assert_eq!(2, pool.size());
+
+        // A poisoned connection is closed
+        third.poison();
+        drop(third);
+        drop(second);
+        assert_eq!(1, pool.size());
+        assert_eq!(0, pool.in_use());
+        pool.check_health().await;
+        assert_eq!(1, pool.idle());
+
+        // Kill the server, connections are closed by the health check
+        server.abort();
+        let _ = server.await;
+        pool.check_health().await;
+        assert_eq!(0, pool.size());
+        assert!(matches!(pool.acquire().await, Err(PoolError::Io(_))));
+
+        // Connections are replaced once the server is back
+        let server = echo_server().await;
+        let mut stream = pool.acquire().await.unwrap();
+        echo(&mut stream, b"back").await.unwrap();
+        assert_eq!(1, pool.size());
+        drop(stream);
+        assert_eq!(1, pool.idle());
+
+        server.abort();
+    }
+}
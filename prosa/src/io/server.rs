@@ -0,0 +1,109 @@
+//! Module to define a generic server processor that translate client frames into bus requests
+//!
+//! The server processor listen on every configured listener, accept clients until the `max_socket` of the listener is reached, and serve every client in its own task.
+//! Frames received from a client are converted into requests by the [`ServerAdaptor`](adaptor::ServerAdaptor), sent to the target service, and the service responses are converted back into frames for the client.
+//!
+//! On shutdown, listeners stop accepting new clients and connected clients are drained (their in-flight request is finished) before the processor ends.
+use std::{fmt, io};
+
+use thiserror::Error;
+use url::Url;
+
+use crate::core::service::ServiceError;
+
+use super::{frame::FrameError, SocketAddr};
+
+/// Definition of the server processor
+///
+/// <svg width="40" height="40">
+#[doc = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/doc_assets/proc.svg"))]
+/// </svg>
+pub mod proc;
+
+/// Definition of the server adaptor
+///
+/// <svg width="40" height="40">
+#[doc = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/doc_assets/adaptor.svg"))]
+/// </svg>
+pub mod adaptor;
+
+/// Error define for the server connections
+#[derive(Debug, Error)]
+pub enum ServerError {
+    /// Error on the client stream
+    #[error("Server IO error: {0}")]
+    Io(#[from] io::Error),
+    /// Error on a client frame
+    #[error("Server frame error: {0}")]
+    Frame(#[from] FrameError),
+    /// Error returned by the target service
+    #[error("Server service error: {0}")]
+    Service(#[from] ServiceError),
+    /// Error of the client protocol, raised by the adaptor
+    #[error("Server protocol error: {0}")]
+    Protocol(String),
+}
+
+/// Reason of a client disconnection
+#[derive(Debug)]
+pub enum DisconnectReason {
+    /// The client closed the connection
+    Closed,
+    /// The server is shutting down
+    Shutdown,
+    /// The connection is closed because of an error
+    Error(ServerError),
+}
+
+impl fmt::Display for DisconnectReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DisconnectReason::Closed => write!(f, "closed by the client"),
+            DisconnectReason::Shutdown => write!(f, "server shutdown"),
+            DisconnectReason::Error(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+/// Information of a client connection given to the adaptor hooks
+#[derive(Debug)]
+pub struct ConnectionInfo {
+    id: u64,
+    listener: Url,
+    addr: SocketAddr,
+    alpn: Option<Vec<u8>>,
+    sni_hostname: Option<String>,
+}
+
+impl ConnectionInfo {
+    /// Getter of the connection id (unique within the processor)
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Getter of the url of the listener that accepted the connection
+    pub fn listener(&self) -> &Url {
+        &self.listener
+    }
+
+    /// Getter of the client address
+    pub fn addr(&self) -> &SocketAddr {
+        &self.addr
+    }
+
+    /// Getter of the protocol negotiated with ALPN (SSL connections only)
+    pub fn selected_alpn(&self) -> Option<&[u8]> {
+        self.alpn.as_deref()
+    }
+
+    /// Getter of the hostname requested by the client with SNI (SSL connections only)
+    pub fn sni_hostname(&self) -> Option<&str> {
+        self.sni_hostname.as_deref()
+    }
+}
+
+impl fmt::Display for ConnectionInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{} {} on {}", self.id, self.addr, self.listener)
+    }
+}
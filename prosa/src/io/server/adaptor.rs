@@ -0,0 +1,118 @@
+use std::error::Error;
+
+use bytes::{Bytes, BytesMut};
+
+use crate::core::service::ServiceError;
+use crate::io::frame::{FrameError, LengthPrefixedCodec};
+
+use super::proc::ServerProc;
+use super::{ConnectionInfo, DisconnectReason, ServerError};
+
+extern crate self as prosa;
+
+/// Adaptator trait for the server processor
+///
+/// Need to define the on_frame and on_service_response methods to translate client frames into requests, and responses into frames.
+/// By default, frames are prefixed by their length on 4 bytes (big endian).
+///
+/// The adaptor is shared by all the connections of the processor, the [`ConnectionInfo`] identify the calling connection.
+/// ```
+/// use bytes::Bytes;
+/// use prosa::core::adaptor::Adaptor;
+/// use prosa::io::server::{ConnectionInfo, ServerError};
+/// use prosa::io::server::adaptor::ServerAdaptor;
+/// use prosa::io::server::proc::ServerProc;
+///
+/// #[derive(Adaptor)]
+/// pub struct MyServerAdaptor { }
+///
+/// impl<M> ServerAdaptor<M> for MyServerAdaptor
+/// where
+///     M: 'static
+///         + std::marker::Send
+///         + std::marker::Sync
+///         + std::marker::Sized
+///         + std::clone::Clone
+///         + std::fmt::Debug
+///         + prosa_utils::msg::tvf::Tvf
+///         + std::default::Default,
+/// {
+///     fn new(_proc: &ServerProc<M>) -> Result<Self, Box<dyn std::error::Error>> {
+///         Ok(Self {})
+///     }
+///     fn on_frame(&mut self, _conn: &ConnectionInfo, frame: Bytes) -> Result<Vec<M>, ServerError> {
+///         let mut request = M::default();
+///         request.put_bytes(1, frame);
+///         Ok(vec![request])
+///     }
+///     fn on_service_response(&mut self, _conn: &ConnectionInfo, response: M) -> Result<Vec<Bytes>, ServerError> {
+///         let frame = response
+///             .get_bytes(1)
+///             .map_err(|e| ServerError::Protocol(e.to_string()))?;
+///         Ok(vec![frame.into_owned()])
+///     }
+/// }
+/// ```
+pub trait ServerAdaptor<M>
+where
+    M: 'static
+        + std::marker::Send
+        + std::marker::Sync
+        + std::marker::Sized
+        + std::clone::Clone
+        + std::fmt::Debug
+        + prosa_utils::msg::tvf::Tvf
+        + std::default::Default,
+{
+    /// Method called when the processor spawns
+    /// This method is called only once so the processing will be thread safe
+    fn new(proc: &ServerProc<M>) -> Result<Self, Box<dyn Error>>
+    where
+        Self: Sized;
+    /// Method to parse a frame from the data received by a client
+    /// Return `None` if the buffer doesn't contain a complete frame yet (partial data must stay in the buffer)
+    /// By default frames are prefixed by their length on 4 bytes
+    fn parse_frame(
+        &mut self,
+        _conn: &ConnectionInfo,
+        buffer: &mut BytesMut,
+    ) -> Result<Option<Bytes>, FrameError> {
+        LengthPrefixedCodec::<4>::default().parse(buffer)
+    }
+    /// Method to encode a frame to send to a client
+    /// By default frames are prefixed by their length on 4 bytes
+    fn encode_frame(
+        &mut self,
+        _conn: &ConnectionInfo,
+        frame: Bytes,
+        dst: &mut BytesMut,
+    ) -> Result<(), FrameError> {
+        LengthPrefixedCodec::<4>::default().encode(&frame, dst)
+    }
+    /// Method called when a client is connected (after the SSL handshake)
+    /// If an error is returned, the client is disconnected
+    /// By default all clients are accepted
+    fn on_connect(&mut self, _conn: &ConnectionInfo) -> Result<(), ServerError> {
+        Ok(())
+    }
+    /// Method to translate a client frame into requests for the target service
+    fn on_frame(&mut self, conn: &ConnectionInfo, frame: Bytes) -> Result<Vec<M>, ServerError>;
+    /// Method to translate a service response into frames for the client
+    fn on_service_response(
+        &mut self,
+        conn: &ConnectionInfo,
+        response: M,
+    ) -> Result<Vec<Bytes>, ServerError>;
+    /// Method to translate a service error into frames for the client
+    /// By default the client is disconnected
+    fn on_service_error(
+        &mut self,
+        _conn: &ConnectionInfo,
+        err: ServiceError,
+    ) -> Result<Vec<Bytes>, ServerError> {
+        Err(ServerError::Service(err))
+    }
+    /// Method called when a client is disconnected
+    /// By default nothing is done
+    fn on_disconnect(&mut self, _conn: &ConnectionInfo, _reason: &DisconnectReason) {}
+}
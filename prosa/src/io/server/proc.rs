@@ -0,0 +1,755 @@
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use bytes::BytesMut;
+use prosa_macros::proc_settings;
+use prosa_utils::msg::tvf::Tvf;
+use serde::{Deserialize, Serialize};
+use tokio::{
+    io::{AsyncReadExt as _, AsyncWriteExt as _},
+    sync::{mpsc, watch, Mutex, OwnedSemaphorePermit, Semaphore},
+    task::JoinSet,
+};
+use tracing::{debug, info, warn};
+use url::Url;
+
+use crate::core::adaptor::Adaptor;
+use crate::core::msg::{drop_unreturned, InternalMsg, Msg};
+use crate::core::proc::{proc, Proc};
+use crate::core::service::{
+    CircuitBreaker, CircuitBreakerSettings, ServiceCall, ServiceError, ServiceTable,
+};
+use crate::io::frame::FrameError;
+use crate::io::listener::{
+    AcceptPolicyState, ConnectionGuard, ListenerClosed, ListenerSetting, MultiListener,
+};
+use crate::io::stream::Stream;
+use crate::io::SocketAddr;
+
+use super::adaptor::ServerAdaptor;
+use super::{ConnectionInfo, DisconnectReason, ServerError};
+
+extern crate self as prosa;
+
+/// Listener of the server processor, with the service it declares on the bus
+///
+/// The listener service respond to requests with the listener url (tag 1) and its number of connected clients (tag 2).
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ServerListener {
+    /// Listener settings (url, SSL, maximum number of clients, socket options)
+    pub listener: ListenerSetting,
+    /// Name of the service declared for the listener
+    pub service_name: String,
+}
+
+/// Server settings for listeners and the target service of the client requests
+#[proc_settings]
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ServerSettings {
+    /// Listeners of the server
+    #[serde(default)]
+    listeners: Vec<ServerListener>,
+    /// Service where the client requests are sent
+    target_service: String,
+    /// Timeout of the target service
+    #[serde(default = "ServerSettings::default_service_timeout")]
+    service_timeout: Duration,
+    /// Timeout to drain the connected clients on shutdown
+    #[serde(default = "ServerSettings::default_drain_timeout")]
+    drain_timeout: Duration,
+    /// Circuit breaker of the target service (none by default)
+    #[serde(default)]
+    circuit_breaker: Option<CircuitBreakerSettings>,
+}
+
+impl ServerSettings {
+    fn default_service_timeout() -> Duration {
+        Duration::from_secs(5)
+    }
+
+    fn default_drain_timeout() -> Duration {
+        Duration::from_secs(30)
+    }
+
+    /// Create a new Server settings
+    pub fn new(target_service: String) -> ServerSettings {
+        ServerSettings {
+            target_service,
+            ..Default::default()
+        }
+    }
+
+    /// Method to add a listener with the service declared for it
+    pub fn add_listener(&mut self, listener: ListenerSetting, service_name: String) {
+        self.listeners.push(ServerListener {
+            listener,
+            service_name,
+        });
+    }
+
+    /// Setter of the timeout of the target service
+    pub fn set_service_timeout(&mut self, service_timeout: Duration) {
+        self.service_timeout = service_timeout;
+    }
+
+    /// Setter of the timeout to drain the connected clients on shutdown
+    pub fn set_drain_timeout(&mut self, drain_timeout: Duration) {
+        self.drain_timeout = drain_timeout;
+    }
+
+    /// Setter of the circuit breaker of the target service
+    pub fn set_circuit_breaker(&mut self, circuit_breaker: CircuitBreakerSettings) {
+        self.circuit_breaker = Some(circuit_breaker);
+    }
+}
+
+#[proc_settings]
+impl Default for ServerSettings {
+    fn default() -> ServerSettings {
+        ServerSettings {
+            listeners: Vec::new(),
+            target_service: Default::default(),
+            service_timeout: ServerSettings::default_service_timeout(),
+            drain_timeout: ServerSettings::default_drain_timeout(),
+            circuit_breaker: None,
+        }
+    }
+}
+
+/// Timeout to exchange the close_notify alerts with an SSL client when it's disconnected
+const TLS_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Client accepted by a listener, waiting to be served
+struct Accepted {
+    listener: usize,
+    stream: Stream,
+    addr: SocketAddr,
+    permit: OwnedSemaphorePermit,
+    guard: Option<ConnectionGuard>,
+}
+
+/// Counter of the connected clients of a listener, decremented when the connection ends
+struct ActiveConnection(Arc<AtomicUsize>);
+
+impl ActiveConnection {
+    fn new(active: &Arc<AtomicUsize>) -> ActiveConnection {
+        active.fetch_add(1, Ordering::Relaxed);
+        ActiveConnection(active.clone())
+    }
+}
+
+impl Drop for ActiveConnection {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Context of a connection task
+struct ConnectionContext<M, A>
+where
+    M: Sized + Clone + Tvf,
+{
+    adaptor: Arc<Mutex<A>>,
+    services: watch::Receiver<Arc<ServiceTable<M>>>,
+    shutdown: watch::Receiver<bool>,
+    target_service: String,
+    service_timeout: Duration,
+    circuit_breaker: Option<Arc<CircuitBreaker>>,
+}
+
+/// Method to accept the clients of a listener, within the limit of its `max_socket` and its accept policy
+async fn accept_clients(
+    index: usize,
+    listener: Arc<MultiListener>,
+    max_socket: Arc<Semaphore>,
+    accept_policy: Option<AcceptPolicyState>,
+    accepted_queue: mpsc::Sender<Accepted>,
+) {
+    loop {
+        let Ok(permit) = max_socket.clone().acquire_owned().await else {
+            return;
+        };
+
+        match listener.accept_raw().await {
+            Ok((stream, addr)) => {
+                // Close immediately the clients rejected by the policy
+                let guard = match accept_policy.as_ref().map(|p| p.admit(&addr)).transpose() {
+                    Ok(guard) => guard,
+                    Err(_) => continue,
+                };
+                let accepted = Accepted {
+                    listener: index,
+                    stream,
+                    addr,
+                    permit,
+                    guard,
+                };
+                if accepted_queue.send(accepted).await.is_err() {
+                    return;
+                }
+            }
+            Err(e) if ListenerClosed::is(&e) => return,
+            Err(e) => {
+                warn!(name: "accept_server_proc", target: "prosa::io::server::proc", listener = listener.to_string(), "Can't accept a client: {}", e);
+            }
+        }
+    }
+}
+
+/// Method to serve a client until its disconnection
+async fn serve_client<M, A>(
+    conn: &ConnectionInfo,
+    stream: &mut Stream,
+    ctx: &mut ConnectionContext<M, A>,
+) -> Result<DisconnectReason, ServerError>
+where
+    M: 'static
+        + std::marker::Send
+        + std::marker::Sync
+        + std::marker::Sized
+        + std::clone::Clone
+        + std::fmt::Debug
+        + prosa_utils::msg::tvf::Tvf
+        + std::default::Default,
+    A: ServerAdaptor<M> + std::marker::Send,
+{
+    ctx.adaptor.lock().await.on_connect(conn)?;
+
+    let mut buffer = BytesMut::with_capacity(8192);
+    loop {
+        let frame = ctx.adaptor.lock().await.parse_frame(conn, &mut buffer)?;
+        if let Some(frame) = frame {
+            let requests = ctx.adaptor.lock().await.on_frame(conn, frame)?;
+            for request in requests {
+                let service_table = ctx.services.borrow().clone();
+                let mut service_call = ServiceCall::new(service_table, ctx.target_service.clone())
+                    .timeout(ctx.service_timeout);
+                if let Some(circuit_breaker) = &ctx.circuit_breaker {
+                    service_call = service_call.circuit_breaker(circuit_breaker.clone());
+                }
+                let response = service_call.send(request).await;
+
+                let mut adaptor = ctx.adaptor.lock().await;
+                let frames = match response {
+                    Ok(response) => adaptor.on_service_response(conn, response),
+                    Err(err) => adaptor.on_service_error(conn, err),
+                }?;
+                let mut data = BytesMut::new();
+                for frame in frames {
+                    adaptor.encode_frame(conn, frame, &mut data)?;
+                }
+                drop(adaptor);
+
+                stream.write_all(&data).await?;
+                stream.flush().await?;
+            }
+
+            continue;
+        }
+
+        tokio::select! {
+            biased;
+            _ = ctx.shutdown.wait_for(|shutdown| *shutdown) => return Ok(DisconnectReason::Shutdown),
+            read = stream.read_buf(&mut buffer) => {
+                if read? == 0 {
+                    return if buffer.is_empty() {
+                        Ok(DisconnectReason::Closed)
+                    } else {
+                        Err(FrameError::ConnectionReset(buffer.len()).into())
+                    };
+                }
+            }
+        }
+    }
+}
+
+/// Method to handle a client connection from its handshake to its disconnection
+async fn run_connection<M, A>(
+    id: u64,
+    listener: Arc<MultiListener>,
+    listener_url: Url,
+    accepted: Accepted,
+    mut ctx: ConnectionContext<M, A>,
+) where
+    M: 'static
+        + std::marker::Send
+        + std::marker::Sync
+        + std::marker::Sized
+        + std::clone::Clone
+        + std::fmt::Debug
+        + prosa_utils::msg::tvf::Tvf
+        + std::default::Default,
+    A: ServerAdaptor<M> + std::marker::Send,
+{
+    let mut stream = match listener.handshake(accepted.stream).await {
+        Ok(stream) => stream,
+        Err(e) => {
+            debug!(name: "conn_server_proc", target: "prosa::io::server::proc", listener = listener_url.to_string(), addr = accepted.addr.to_string(), "Handshake failed: {}", e);
+            return;
+        }
+    };
+
+    let conn = ConnectionInfo {
+        id,
+        listener: listener_url,
+        alpn: stream.selected_alpn(),
+        sni_hostname: stream.sni_hostname(),
+        addr: accepted.addr,
+    };
+    debug!(name: "conn_server_proc", target: "prosa::io::server::proc", connection = conn.to_string(), "Client connected");
+
+    let reason = serve_client(&conn, &mut stream, &mut ctx)
+        .await
+        .unwrap_or_else(DisconnectReason::Error);
+    let _ = stream.shutdown_tls(TLS_SHUTDOWN_TIMEOUT).await;
+    drop(stream);
+    drop(accepted.permit);
+    drop(accepted.guard);
+
+    debug!(name: "conn_server_proc", target: "prosa::io::server::proc", connection = conn.to_string(), reason = reason.to_string(), "Client disconnected");
+    ctx.adaptor.lock().await.on_disconnect(&conn, &reason);
+}
+
+/// Server processor to accept clients, and translate their frames into requests for a service
+///
+/// ```
+/// use prosa::core::main::{MainProc, MainRunnable};
+/// use prosa::core::proc::{proc, Proc, ProcBusParam, ProcConfig};
+/// use prosa::io::listener::ListenerSetting;
+/// use prosa::io::server::proc::{ServerProc, ServerSettings};
+/// use prosa_utils::msg::simple_string_tvf::SimpleStringTvf;
+/// use prosa::core::settings::settings;
+/// use serde::Serialize;
+/// use url::Url;
+///
+/// // Main settings
+/// #[settings]
+/// #[derive(Default, Debug, Serialize)]
+/// struct Settings {}
+///
+/// // Create bus and main processor
+/// let settings = Settings::default();
+/// let (bus, main) = MainProc::<SimpleStringTvf>::create(&settings);
+///
+/// // Launch the main task
+/// let main_task = main.run();
+///
+/// // Configure a server processor that send the client requests to the `BACKEND` service
+/// let mut server_settings = ServerSettings::new(String::from("BACKEND"));
+/// server_settings.add_listener(ListenerSetting::from(Url::parse("tcp://0.0.0.0:8080").unwrap()), String::from("SERVER_8080"));
+/// let server_proc = ServerProc::<SimpleStringTvf>::create(1, bus.clone(), server_settings);
+/// // Proc::<MyServerAdaptor>::run(server_proc, String::from("SERVER_PROC"));
+///
+/// // Wait on main task
+/// //main_task.join().unwrap();
+/// ```
+#[proc(settings = prosa::io::server::proc::ServerSettings)]
+pub struct ServerProc {}
+
+#[proc]
+impl<A> Proc<A> for ServerProc
+where
+    A: Adaptor + ServerAdaptor<M> + std::marker::Send + std::marker::Sync + 'static,
+{
+    async fn internal_run(&mut self, name: String) -> Result<(), Box<dyn std::error::Error>> {
+        // Initiate an adaptor for the server processor, shared by all the connections
+        let mut adaptor = A::new(self)?;
+        adaptor.async_init(&self.proc).await?;
+        let adaptor = Arc::new(Mutex::new(adaptor));
+
+        // Declare the processor
+        self.proc.add_proc().await?;
+
+        // Meter for the accept policies of the listeners and the circuit breaker
+        let meter = self.proc.meter(name.clone());
+
+        // Circuit breaker of the target service, shared by all the connections
+        let circuit_breaker = self
+            .settings
+            .circuit_breaker
+            .clone()
+            .map(|settings| Arc::new(CircuitBreaker::new(settings).meter(&meter)));
+
+        // Bind all the listeners
+        let (accepted_queue, mut accepted_rx) = mpsc::channel(64);
+        let mut listeners = Vec::with_capacity(self.settings.listeners.len());
+        for (index, server_listener) in self.settings.listeners.iter().enumerate() {
+            let listener = Arc::new(server_listener.listener.bind_multi().await?);
+            let max_socket = Arc::new(Semaphore::new(
+                server_listener
+                    .listener
+                    .max_socket
+                    .min(Semaphore::MAX_PERMITS as u64) as usize,
+            ));
+            let accept_policy = server_listener.listener.accept_policy.as_ref().map(|_| {
+                server_listener
+                    .listener
+                    .accept_policy_state()
+                    .with_meter(&meter, listener.to_string())
+            });
+            info!(name: "server_proc", target: "prosa::io::server::proc", proc_name = name, listener = listener.to_string(), service = server_listener.service_name, "Listening");
+            tokio::spawn(accept_clients(
+                index,
+                listener.clone(),
+                max_socket,
+                accept_policy,
+                accepted_queue.clone(),
+            ));
+            listeners.push((
+                listener,
+                server_listener.listener.url.clone(),
+                Arc::new(AtomicUsize::new(0)),
+            ));
+        }
+        drop(accepted_queue);
+
+        // Add a service for every listener
+        self.proc
+            .add_service_proc(
+                self.settings
+                    .listeners
+                    .iter()
+                    .map(|l| l.service_name.clone())
+                    .collect(),
+            )
+            .await?;
+
+        // The listeners are bound, the processor can accept clients
+        self.proc.set_ready().await?;
+
+        let (services_tx, services_rx) = watch::channel(self.service.clone());
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+        let mut connections = JoinSet::new();
+        let mut connection_id: u64 = 0;
+
+        loop {
+            tokio::select! {
+                Some(msg) = self.internal_rx_queue.recv() => {
+                    match msg {
+                        InternalMsg::Request(msg) => {
+                            if let Some((_, url, active)) = self
+                                .settings
+                                .listeners
+                                .iter()
+                                .position(|l| l.service_name == *msg.get_service())
+                                .and_then(|index| listeners.get(index))
+                            {
+                                let mut status = M::default();
+                                status.put_string(1, url.to_string());
+                                status.put_unsigned(2, active.load(Ordering::Relaxed) as u64);
+                                drop_unreturned!(&name, msg.return_to_sender(status).await)
+                            } else {
+                                let err = ServiceError::UnknownService(msg.get_service().clone());
+                                drop_unreturned!(&name, msg.return_error_to_sender(None, err).await)
+                            }
+                        }
+                        // The server doesn't send requests, a late or stray response is dropped
+                        InternalMsg::Response(msg) => {
+                            warn!(name: "server_proc", target: "prosa::io::server::proc", parent: msg.get_span(), proc_name = name, service = msg.get_service(), "Drop an unexpected response");
+                        }
+                        InternalMsg::Error(err) => {
+                            warn!(name: "server_proc", target: "prosa::io::server::proc", parent: err.get_span(), proc_name = name, service = err.get_service(), error = err.get_err().to_string(), "Drop an unexpected error");
+                        }
+                        InternalMsg::Command(command) => {
+                            debug!(name: "server_proc", target: "prosa::io::server::proc", proc_name = name, command = command, "Ignore an unsupported command");
+                        }
+                        // Batches are split by the processor queue
+                        InternalMsg::RequestBatch(_) | InternalMsg::ResponseBatch(_) => {}
+                        InternalMsg::Ping(_) => {}
+                        InternalMsg::Config(config) => {
+                            // Listeners and the circuit breaker are kept, other settings apply to the new connections
+                            // The adaptor keeps its configuration if the settings can't be reloaded
+                            match self.reload_settings(&config, &name) {
+                                Ok(()) => self.reload_adaptor(&mut *adaptor.lock().await, &name),
+                                Err(e) => {
+                                    warn!(name: "config_server_proc", target: "prosa::io::server::proc", proc_name = name, "Can't reload the settings: {}", e);
+                                }
+                            }
+                        }
+                        InternalMsg::Service(table) => {
+                            self.service = table.clone();
+                            services_tx.send_replace(table);
+                        }
+                        InternalMsg::Shutdown => {
+                            self.proc.set_stopping().await?;
+
+                            // Stop accepting clients (the next process may inherit the listening sockets), and drain the connected ones
+                            for (listener, _, _) in &listeners {
+                                #[cfg(target_family = "unix")]
+                                if let Err(e) = listener.handover() {
+                                    warn!(name: "server_proc", target: "prosa::io::server::proc", proc_name = name, listener = listener.to_string(), "Can't hand over the listener: {}", e);
+                                }
+                                listener.close_accept();
+                            }
+                            shutdown_tx.send_replace(true);
+                            if tokio::time::timeout(self.settings.drain_timeout, async {
+                                while connections.join_next().await.is_some() {}
+                            })
+                            .await
+                            .is_err()
+                            {
+                                warn!(name: "server_proc", target: "prosa::io::server::proc", proc_name = name, "Abort {} connections not drained in time", connections.len());
+                                connections.abort_all();
+                            }
+
+                            adaptor.lock().await.async_terminate().await;
+                            self.proc.remove_proc().await?;
+                            return Ok(());
+                        }
+                    }
+                },
+                Some(accepted) = accepted_rx.recv() => {
+                    let (listener, url, active) = &listeners[accepted.listener];
+                    let active = ActiveConnection::new(active);
+                    let ctx = ConnectionContext {
+                        adaptor: adaptor.clone(),
+                        services: services_rx.clone(),
+                        shutdown: shutdown_rx.clone(),
+                        target_service: self.settings.target_service.clone(),
+                        service_timeout: self.settings.service_timeout,
+                        circuit_breaker: circuit_breaker.clone(),
+                    };
+                    connection_id += 1;
+                    let connection = run_connection(connection_id, listener.clone(), url.clone(), accepted, ctx);
+                    connections.spawn(async move {
+                        connection.await;
+                        drop(active);
+                    });
+                },
+                Some(_) = connections.join_next(), if !connections.is_empty() => {},
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{error::Error, sync::atomic::AtomicU32};
+
+    use bytes::{Buf as _, Bytes};
+    use prosa_macros::{settings, Adaptor};
+    use prosa_utils::msg::simple_string_tvf::SimpleStringTvf;
+    use tokio::{
+        io::{AsyncBufReadExt as _, BufReader},
+        net::TcpStream,
+    };
+
+    use super::*;
+    use crate::{
+        core::{
+            main::{MainProc, MainRunnable as _},
+            proc::ProcConfig as _,
+        },
+        stub::{
+            adaptor::StubAdaptor,
+            proc::{StubProc, StubSettings},
+        },
+    };
+
+    const UPPER_SERVICE: &str = "SERVER_UPPER";
+    const LISTENER_SERVICE: &str = "SERVER_LISTENER";
+    static CONNECTS: AtomicU32 = AtomicU32::new(0);
+    static SHUTDOWN_DISCONNECTS: AtomicU32 = AtomicU32::new(0);
+
+    #[settings]
+    #[derive(Default, Debug, Serialize)]
+    struct TestSettings {
+        stub: StubSettings,
+        server: ServerSettings,
+    }
+
+    #[derive(Adaptor)]
+    struct UpperStubAdaptor {}
+
+    impl<M> StubAdaptor<M> for UpperStubAdaptor
+    where
+        M: 'static
+            + std::marker::Send
+            + std::marker::Sync
+            + std::marker::Sized
+            + std::clone::Clone
+            + std::fmt::Debug
+            + prosa_utils::msg::tvf::Tvf
+            + std::default::Default,
+    {
+        fn new(_proc: &StubProc<M>) -> Result<Self, Box<dyn Error>> {
+            Ok(Self {})
+        }
+
+        fn process_request(&mut self, _service_name: &str, request: &M) -> Result<M, ServiceError> {
+            let mut response = M::default();
+            response.put_string(1, request.get_string(1).unwrap().to_uppercase());
+            Ok(response)
+        }
+    }
+
+    /// Adaptor that exchange text lines with the clients
+    #[derive(Adaptor)]
+    struct LineAdaptor {}
+
+    impl<M> ServerAdaptor<M> for LineAdaptor
+    where
+        M: 'static
+            + std::marker::Send
+            + std::marker::Sync
+            + std::marker::Sized
+            + std::clone::Clone
+            + std::fmt::Debug
+            + prosa_utils::msg::tvf::Tvf
+            + std::default::Default,
+    {
+        fn new(_proc: &ServerProc<M>) -> Result<Self, Box<dyn Error>> {
+            Ok(Self {})
+        }
+
+        fn parse_frame(
+            &mut self,
+            _conn: &ConnectionInfo,
+            buffer: &mut BytesMut,
+        ) -> Result<Option<Bytes>, FrameError> {
+            Ok(buffer.iter().position(|b| *b == b'\n').map(|pos| {
+                let line = buffer.split_to(pos).freeze();
+                buffer.advance(1);
+                line
+            }))
+        }
+
+        fn encode_frame(
+            &mut self,
+            _conn: &ConnectionInfo,
+            frame: Bytes,
+            dst: &mut BytesMut,
+        ) -> Result<(), FrameError> {
+            dst.extend_from_slice(&frame);
+            dst.extend_from_slice(b"\n");
+            Ok(())
+        }
+
+        fn on_connect(&mut self, _conn: &ConnectionInfo) -> Result<(), ServerError> {
+            CONNECTS.fetch_add(1, Ordering::Relaxed);
+            Ok(())
+        }
+
+        fn on_frame(
+            &mut self,
+            _conn: &ConnectionInfo,
+            frame: Bytes,
+        ) -> Result<Vec<M>, ServerError> {
+            let line = String::from_utf8(frame.to_vec())
+                .map_err(|e| ServerError::Protocol(e.to_string()))?;
+            let mut request = M::default();
+            request.put_string(1, line);
+            Ok(vec![request])
+        }
+
+        fn on_service_response(
+            &mut self,
+            _conn: &ConnectionInfo,
+            response: M,
+        ) -> Result<Vec<Bytes>, ServerError> {
+            let line = response
+                .get_string(1)
+                .map_err(|e| ServerError::Protocol(e.to_string()))?;
+            Ok(vec![Bytes::from(line.into_owned())])
+        }
+
+        fn on_service_error(
+            &mut self,
+            _conn: &ConnectionInfo,
+            err: ServiceError,
+        ) -> Result<Vec<Bytes>, ServerError> {
+            Ok(vec![Bytes::from(format!("ERR {}", err))])
+        }
+
+        fn on_disconnect(&mut self, _conn: &ConnectionInfo, reason: &DisconnectReason) {
+            if matches!(reason, DisconnectReason::Shutdown) {
+                SHUTDOWN_DISCONNECTS.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn server_line_adaptor() {
+        let mut test_settings = TestSettings {
+            stub: StubSettings::new(vec![UPPER_SERVICE.into()]),
+            server: ServerSettings::new(UPPER_SERVICE.into()),
+            ..Default::default()
+        };
+        test_settings.server.add_listener(
+            ListenerSetting::from(Url::parse("tcp://localhost:41910").unwrap()),
+            LISTENER_SERVICE.into(),
+        );
+        test_settings
+            .server
+            .set_drain_timeout(Duration::from_secs(2));
+
+        // Create bus and main processor
+        let (bus, main) = MainProc::<SimpleStringTvf>::create(&test_settings);
+        let main_task = main.run();
+
+        let stub_proc = StubProc::<SimpleStringTvf>::create(1, bus.clone(), test_settings.stub);
+        Proc::<UpperStubAdaptor>::run(stub_proc, String::from("STUB_PROC"));
+
+        let server_proc =
+            ServerProc::<SimpleStringTvf>::create(2, bus.clone(), test_settings.server);
+        Proc::<LineAdaptor>::run(server_proc, String::from("SERVER_PROC"));
+
+        // Wait for the server to listen
+        let mut stream = None;
+        for _ in 0..50 {
+            if let Ok(s) = TcpStream::connect("localhost:41910").await {
+                stream = Some(s);
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        let (reader, mut writer) = stream.expect("The server is not listening").into_split();
+        let mut lines = BufReader::new(reader).lines();
+
+        // Wait for the target service to be available
+        let mut available = false;
+        for _ in 0..50 {
+            writer.write_all(b"ping\n").await.unwrap();
+            if lines.next_line().await.unwrap().as_deref() == Some("PING") {
+                available = true;
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        assert!(available, "The target service is not available");
+
+        // Several frames in one segment, and a frame split across segments
+        writer.write_all(b"hello\nwor").await.unwrap();
+        assert_eq!(Some("HELLO"), lines.next_line().await.unwrap().as_deref());
+        writer.write_all(b"ldline\n").await.unwrap();
+        assert_eq!(
+            Some("WORLDLINE"),
+            lines.next_line().await.unwrap().as_deref()
+        );
+
+        // The connected client is drained on shutdown
+        bus.stop("ProSA unit test end".into()).await.unwrap();
+        assert_eq!(
+            None,
+            tokio::time::timeout(Duration::from_secs(5), lines.next_line())
+                .await
+                .expect("The client is not disconnected on shutdown")
+                .unwrap()
+        );
+        main_task.join().unwrap();
+
+        for _ in 0..20 {
+            if SHUTDOWN_DISCONNECTS.load(Ordering::Relaxed) == 1 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        assert_eq!(1, CONNECTS.load(Ordering::Relaxed));
+        assert_eq!(1, SHUTDOWN_DISCONNECTS.load(Ordering::Relaxed));
+    }
+}
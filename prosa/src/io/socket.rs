@@ -0,0 +1,199 @@
+//! Module that define socket options that could be apply on ProSA sockets
+use std::{io, time::Duration};
+
+use serde::{Deserialize, Serialize};
+use socket2::{SockRef, TcpKeepalive};
+
+/// Socket on which options can be applied (a file descriptor on unix systems, a socket handle on windows systems)
+#[cfg(target_family = "unix")]
+pub trait AsSocketHandle: std::os::fd::AsFd {}
+
+#[cfg(target_family = "unix")]
+impl<S> AsSocketHandle for S where S: std::os::fd::AsFd {}
+
+/// Socket on which options can be applied (a file descriptor on unix systems, a socket handle on windows systems)
+#[cfg(target_family = "windows")]
+pub trait AsSocketHandle: std::os::windows::io::AsSocket {}
+
+#[cfg(target_family = "windows")]
+impl<S> AsSocketHandle for S where S: std::os::windows::io::AsSocket {}
+
+/// TCP keepalive configuration of a socket
+///
+/// Unset values keep the system defaults
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct KeepaliveOptions {
+    /// Idle time (in seconds) before sending the first keepalive probe
+    pub idle: Option<u64>,
+    /// Interval (in seconds) between keepalive probes
+    pub interval: Option<u64>,
+    /// Number of unacknowledged probes before considering the connection dead
+    pub count: Option<u32>,
+}
+
+impl KeepaliveOptions {
+    fn to_tcp_keepalive(&self) -> TcpKeepalive {
+        let mut keepalive = TcpKeepalive::new();
+        if let Some(idle) = self.idle {
+            keepalive = keepalive.with_time(Duration::from_secs(idle));
+        }
+
+        #[cfg(any(
+            target_os = "android",
+            target_os = "freebsd",
+            target_os = "ios",
+            target_os = "linux",
+            target_os = "macos",
+            target_os = "netbsd",
+            target_os = "windows",
+        ))]
+        {
+            if let Some(interval) = self.interval {
+                keepalive = keepalive.with_interval(Duration::from_secs(interval));
+            }
+
+            if let Some(count) = self.count {
+                keepalive = keepalive.with_retries(count);
+            }
+        }
+
+        keepalive
+    }
+}
+
+/// Socket options applied on TCP sockets after their connection/acceptation
+///
+/// ```
+/// use std::time::Duration;
+/// use tokio::io;
+/// use prosa::io::socket::{KeepaliveOptions, SocketOptions};
+/// use prosa::io::stream::Stream;
+///
+/// async fn connecting() -> Result<(), io::Error> {
+///     let stream: Stream = Stream::connect_tcp("worldline.com:80").await?;
+///
+///     let socket_options = SocketOptions {
+///         keepalive: Some(KeepaliveOptions {
+///             idle: Some(60),
+///             interval: Some(10),
+///             count: Some(5),
+///         }),
+///         nodelay: Some(true),
+///         ..Default::default()
+///     };
+///     stream.configure(&socket_options)?;
+///
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct SocketOptions {
+    /// TCP keepalive, enabled if set
+    pub keepalive: Option<KeepaliveOptions>,
+    /// Size of the receive buffer (SO_RCVBUF)
+    pub recv_buffer_size: Option<usize>,
+    /// Size of the send buffer (SO_SNDBUF)
+    pub send_buffer_size: Option<usize>,
+    /// Linger duration in seconds (SO_LINGER)
+    pub linger: Option<u64>,
+    /// Disable the Nagle algorithm (TCP_NODELAY)
+    pub nodelay: Option<bool>,
+}
+
+impl SocketOptions {
+    /// Method to apply the socket options on a socket
+    pub fn apply<S>(&self, socket: &S) -> Result<(), io::Error>
+    where
+        S: AsSocketHandle,
+    {
+        let socket = SockRef::from(socket);
+        if let Some(keepalive) = &self.keepalive {
+            socket.set_tcp_keepalive(&keepalive.to_tcp_keepalive())?;
+        }
+
+        if let Some(recv_buffer_size) = self.recv_buffer_size {
+            socket.set_recv_buffer_size(recv_buffer_size)?;
+        }
+
+        if let Some(send_buffer_size) = self.send_buffer_size {
+            socket.set_send_buffer_size(send_buffer_size)?;
+        }
+
+        if let Some(linger) = self.linger {
+            socket.set_linger(Some(Duration::from_secs(linger)))?;
+        }
+
+        if let Some(nodelay) = self.nodelay {
+            socket.set_tcp_nodelay(nodelay)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::net::{TcpListener, TcpStream};
+
+    use super::*;
+    use crate::io::{listener::StreamListener, stream::Stream};
+
+    #[tokio::test]
+    async fn socket_options() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let stream = TcpStream::connect(listener.local_addr().unwrap())
+            .await
+            .unwrap();
+
+        let socket_options = SocketOptions {
+            keepalive: Some(KeepaliveOptions {
+                idle: Some(60),
+                interval: Some(10),
+                count: Some(5),
+            }),
+            recv_buffer_size: Some(65536),
+            send_buffer_size: Some(65536),
+            linger: Some(2),
+            nodelay: Some(true),
+        };
+        socket_options.apply(&stream).unwrap();
+
+        let socket = SockRef::from(&stream);
+        assert!(socket.keepalive().unwrap());
+        assert!(socket.recv_buffer_size().unwrap() >= 65536);
+        assert!(socket.send_buffer_size().unwrap() >= 65536);
+        assert_eq!(Some(Duration::from_secs(2)), socket.linger().unwrap());
+        assert!(socket.tcp_nodelay().unwrap());
+
+        // Unset options keep the socket untouched
+        SocketOptions::default().apply(&stream).unwrap();
+        assert!(socket.keepalive().unwrap());
+        assert!(socket.tcp_nodelay().unwrap());
+    }
+
+    #[tokio::test]
+    async fn stream_configure() {
+        let listener = StreamListener::bind("127.0.0.1:0").await.unwrap();
+        let socket_options = SocketOptions {
+            keepalive: Some(KeepaliveOptions::default()),
+            ..Default::default()
+        };
+        listener.configure(&socket_options).unwrap();
+
+        let addr = listener.local_addr().unwrap().to_string();
+        let (client, server) = tokio::join!(Stream::connect_tcp(addr), listener.accept());
+        let client = client.unwrap();
+        let (server, _) = server.unwrap();
+
+        // Accepted socket inherit options from the listener
+        assert!(SockRef::from(&server).keepalive().unwrap());
+
+        client
+            .configure(&SocketOptions {
+                nodelay: Some(true),
+                ..Default::default()
+            })
+            .unwrap();
+        assert!(client.nodelay().unwrap());
+    }
+}
@@ -1,24 +1,50 @@
 //! Module that define stream IO that could be use by a ProSA processor
 use std::{
-    fmt, io,
-    net::{Ipv4Addr, SocketAddrV4},
-    os::fd::{AsFd, AsRawFd, BorrowedFd, RawFd},
+    cell::{Cell, RefCell},
+    fmt,
+    future::Future,
+    io,
+    net::{IpAddr, Ipv4Addr, SocketAddrV4},
+    ops::{Deref, DerefMut},
     path::Path,
     pin::Pin,
     task::{Context, Poll},
+    time::Duration,
 };
 
+#[cfg(target_family = "unix")]
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, RawFd};
+
 use openssl::ssl::{self, SslConnector};
-use prosa_utils::config::ssl::SslConfig;
+use opentelemetry::{
+    metrics::{Counter, Meter},
+    KeyValue,
+};
+use prosa_utils::config::ssl::{cert_subject, SslConfig, SslSessionCache};
+use prosa_utils::time::{Clock, SystemClock};
 use serde::{Deserialize, Serialize};
+#[cfg(target_family = "windows")]
+use tokio::net::windows::named_pipe::{ClientOptions, NamedPipeClient, NamedPipeServer};
 use tokio::{
-    io::{AsyncRead, AsyncWrite, ReadBuf},
+    io::{AsyncRead, AsyncReadExt as _, AsyncWrite, AsyncWriteExt as _, ReadBuf},
     net::{TcpStream, ToSocketAddrs},
+    time::{sleep, sleep_until, timeout, Instant, Sleep},
 };
 use tokio_openssl::SslStream;
+use tracing::{debug, warn};
 use url::Url;
 
-use super::{url_is_ssl, SocketAddr};
+#[cfg(target_family = "windows")]
+use super::url_named_pipe;
+use super::{
+    dns::{self, DnsCache, ResolveSettings},
+    socket::SocketOptions,
+    unsupported_url, url_is_ssl, SocketAddr,
+};
+
+/// Windows error returned when all the instances of a named pipe are busy
+#[cfg(target_family = "windows")]
+const ERROR_PIPE_BUSY: i32 = 231;
 
 /// ProSA socket object to handle TCP/SSL socket with or without proxy
 #[derive(Debug)]
@@ -26,9 +52,18 @@ pub enum Stream {
     #[cfg(target_family = "unix")]
     /// Unix socket (only on unix systems)
     Unix(tokio::net::UnixStream),
+    #[cfg(target_family = "windows")]
+    /// Client side of a named pipe, with its path (only on windows systems)
+    NamedPipe(NamedPipeClient, String),
+    #[cfg(target_family = "windows")]
+    /// Server side of a named pipe accepted by a listener, with its path (only on windows systems)
+    NamedPipeServer(NamedPipeServer, String),
     /// TCP socket
     Tcp(TcpStream),
     /// SSL socket
+    ///
+    /// TLS 1.2 renegotiations and TLS 1.3 key updates requested by the remote are done transparently during reads and writes (unless refused by the [`SslConfig`]).
+    /// Use [`Stream::shutdown_tls`] to close it with a close_notify exchange
     Ssl(SslStream<TcpStream>),
     /// TCP socket using Http proxy
     TcpHttpProxy(TcpStream),
@@ -59,6 +94,10 @@ impl Stream {
         match self {
             #[cfg(target_family = "unix")]
             Stream::Unix(s) => s.local_addr().map(|addr| addr.into()),
+            #[cfg(target_family = "windows")]
+            Stream::NamedPipe(_, path) | Stream::NamedPipeServer(_, path) => {
+                Ok(SocketAddr::NamedPipe(path.clone()))
+            }
             Stream::Tcp(s) => s.local_addr().map(|addr| addr.into()),
             Stream::Ssl(s) => s.get_ref().local_addr().map(|addr| addr.into()),
             Stream::TcpHttpProxy(s) => s.local_addr().map(|addr| addr.into()),
@@ -66,6 +105,24 @@ impl Stream {
         }
     }
 
+    /// Returns the remote address that this stream is connected to (the proxy address for streams through an HTTP proxy)
+    ///
+    /// For a stream connected with a [`TargetSetting`], it's the resolved address that accepted the connection.
+    pub fn peer_addr(&self) -> Result<SocketAddr, io::Error> {
+        match self {
+            #[cfg(target_family = "unix")]
+            Stream::Unix(s) => s.peer_addr().map(|addr| addr.into()),
+            #[cfg(target_family = "windows")]
+            Stream::NamedPipe(_, path) | Stream::NamedPipeServer(_, path) => {
+                Ok(SocketAddr::NamedPipe(path.clone()))
+            }
+            Stream::Tcp(s) => s.peer_addr().map(|addr| addr.into()),
+            Stream::Ssl(s) => s.get_ref().peer_addr().map(|addr| addr.into()),
+            Stream::TcpHttpProxy(s) => s.peer_addr().map(|addr| addr.into()),
+            Stream::SslHttpProxy(s) => s.get_ref().peer_addr().map(|addr| addr.into()),
+        }
+    }
+
     #[cfg(target_family = "unix")]
     #[cfg_attr(doc, aquamarine::aquamarine)]
     /// Connect a UNIX socket on a path
@@ -98,6 +155,47 @@ impl Stream {
         Ok(Stream::Unix(tokio::net::UnixStream::connect(path).await?))
     }
 
+    #[cfg(target_family = "windows")]
+    #[cfg_attr(doc, aquamarine::aquamarine)]
+    /// Connect to a named pipe with its path (`\\.\pipe\name` for a local pipe)
+    ///
+    /// If all the instances of the pipe are busy, the connection is retried until the server create a new instance.
+    ///
+    /// ```mermaid
+    /// graph LR
+    ///     client[Client]
+    ///     server[Server]
+    ///
+    ///     client -- Named pipe --> server
+    /// ```
+    ///
+    /// ```
+    /// use tokio::io;
+    /// use prosa::io::stream::Stream;
+    ///
+    /// async fn connecting() -> Result<(), io::Error> {
+    ///     let stream: Stream = Stream::connect_named_pipe(r"\\.\pipe\prosa").await?;
+    ///
+    ///     // Handle the stream like any tokio stream
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn connect_named_pipe<P>(path: P) -> Result<Stream, io::Error>
+    where
+        P: AsRef<str>,
+    {
+        loop {
+            match ClientOptions::new().open(path.as_ref()) {
+                Ok(client) => return Ok(Stream::NamedPipe(client, path.as_ref().to_string())),
+                Err(e) if e.raw_os_error() == Some(ERROR_PIPE_BUSY) => {}
+                Err(e) => return Err(e),
+            }
+
+            sleep(Duration::from_millis(50)).await;
+        }
+    }
+
     #[cfg_attr(doc, aquamarine::aquamarine)]
     /// Connect a TCP socket to a distant
     ///
@@ -130,12 +228,33 @@ impl Stream {
     }
 
     /// Method to create an SSL stream from a TCP stream
+    ///
+    /// If the host is an IP address, no SNI is sent and the remote certificate is verified against its IP subject alternative names.
+    /// The remote certificate chain is always verified, but not its host if `verify_hostname` is `false`.
+    /// If the SSL context have a session cache, the last session of the target is resumed.
     async fn create_ssl(
         tcp_stream: TcpStream,
         ssl_connector: &ssl::SslConnector,
-        domain: &str,
+        host: &str,
+        port: u16,
+        verify_hostname: bool,
     ) -> Result<SslStream<TcpStream>, io::Error> {
-        let ssl = ssl_connector.configure()?.into_ssl(domain)?;
+        let mut ssl_config = ssl_connector.configure()?;
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        let ip = host.parse::<IpAddr>().ok();
+        if let Some(ip) = ip {
+            ssl_config.set_use_server_name_indication(false);
+            ssl_config.set_verify_hostname(false);
+            if verify_hostname {
+                ssl_config.param_mut().set_ip(ip)?;
+            }
+        } else if !verify_hostname {
+            ssl_config.set_verify_hostname(false);
+        }
+
+        let mut ssl = ssl_config.into_ssl(host)?;
+        SslSessionCache::resume(&mut ssl, &format!("{}:{}", host, port))
+            .map_err(io::Error::other)?;
         let mut stream = SslStream::new(ssl, tcp_stream).unwrap();
         if let Err(e) = Pin::new(&mut stream).connect().await {
             if e.code() != ssl::ErrorCode::ZERO_RETURN {
@@ -145,6 +264,7 @@ impl Stream {
                 ));
             }
         }
+        SslSessionCache::handshake_done(stream.ssl());
 
         Ok(stream)
     }
@@ -178,19 +298,33 @@ impl Stream {
     ///     Ok(())
     /// }
     /// ```
+    ///
+    /// The URL host can be a domain name or an IP address (`tls://127.0.0.1:8443`).
+    /// For an IP address, the remote certificate must carry it as IP subject alternative name.
     pub async fn connect_ssl(
         url: &Url,
         ssl_context: &ssl::SslConnector,
+    ) -> Result<Stream, io::Error> {
+        Self::connect_ssl_verify(url, ssl_context, true).await
+    }
+
+    /// Method to connect an SSL socket to a distant, optionally without verifying the remote certificate host
+    async fn connect_ssl_verify(
+        url: &Url,
+        ssl_context: &ssl::SslConnector,
+        verify_hostname: bool,
     ) -> Result<Stream, io::Error> {
         let addrs = url.socket_addrs(|| url.port_or_known_default())?;
         Ok(Stream::Ssl(
             Self::create_ssl(
                 TcpStream::connect(&*addrs).await?,
                 ssl_context,
-                url.domain().ok_or(io::Error::new(
+                url.host_str().ok_or(io::Error::new(
                     io::ErrorKind::InvalidInput,
-                    format!("Can't retrieve domain name from url `{}`", url),
+                    format!("Can't retrieve host from url `{}`", url),
                 ))?,
+                url.port_or_known_default().unwrap_or_default(),
+                verify_hostname,
             )
             .await?,
         ))
@@ -305,22 +439,159 @@ impl Stream {
         port: u16,
         ssl_connector: &ssl::SslConnector,
         proxy: &Url,
+    ) -> Result<Stream, io::Error> {
+        Self::connect_ssl_with_http_proxy_verify(host, port, ssl_connector, proxy, true).await
+    }
+
+    /// Method to connect an SSL socket through an HTTP proxy, optionally without verifying the remote certificate host
+    async fn connect_ssl_with_http_proxy_verify(
+        host: &str,
+        port: u16,
+        ssl_connector: &ssl::SslConnector,
+        proxy: &Url,
+        verify_hostname: bool,
     ) -> Result<Stream, io::Error> {
         Ok(Stream::SslHttpProxy(
             Self::create_ssl(
                 Self::connect_http_proxy(host, port, proxy).await?,
                 ssl_connector,
                 host,
+                port,
+                verify_hostname,
             )
             .await?,
         ))
     }
 
+    /// Getter of the SSL session of the stream (`None` if the stream is not an SSL one)
+    fn ssl(&self) -> Option<&ssl::SslRef> {
+        match self {
+            Stream::Ssl(s) | Stream::SslHttpProxy(s) => Some(s.ssl()),
+            _ => None,
+        }
+    }
+
+    /// Getter of the protocol negotiated with ALPN during the SSL handshake
+    ///
+    /// ```
+    /// use prosa::io::stream::Stream;
+    ///
+    /// fn protocol_handler(stream: &Stream) -> &'static str {
+    ///     match stream.selected_alpn().as_deref() {
+    ///         Some(b"h2") => "http2",
+    ///         Some(b"http/1.1") => "http1",
+    ///         _ => "raw",
+    ///     }
+    /// }
+    /// ```
+    pub fn selected_alpn(&self) -> Option<Vec<u8>> {
+        self.ssl()
+            .and_then(|ssl| ssl.selected_alpn_protocol())
+            .map(|alpn| alpn.to_vec())
+    }
+
+    /// Method to know if the SSL session was resumed during the handshake (`false` for a full handshake or a stream that is not an SSL one)
+    pub fn is_session_reused(&self) -> bool {
+        self.ssl().is_some_and(|ssl| ssl.session_reused())
+    }
+
+    /// Getter of the parameters negotiated during the SSL handshake (`None` if the stream is not an SSL one)
+    ///
+    /// ```
+    /// use prosa::io::stream::Stream;
+    ///
+    /// fn log_connection(stream: &Stream) {
+    ///     if let Some(tls_info) = stream.tls_info() {
+    ///         println!("Connected with {} {}", tls_info.version, tls_info.cipher);
+    ///     }
+    /// }
+    /// ```
+    pub fn tls_info(&self) -> Option<TlsInfo> {
+        self.ssl().map(|ssl| TlsInfo {
+            version: ssl.version_str().to_string(),
+            cipher: ssl
+                .current_cipher()
+                .map(|cipher| cipher.name().to_string())
+                .unwrap_or_default(),
+            alpn: ssl
+                .selected_alpn_protocol()
+                .map(|alpn| String::from_utf8_lossy(alpn).into_owned()),
+            resumed: ssl.session_reused(),
+            peer_subject: ssl.peer_certificate().map(|cert| cert_subject(&cert)),
+        })
+    }
+
+    /// Getter of the hostname requested by the client with SNI during the SSL handshake
+    pub fn sni_hostname(&self) -> Option<String> {
+        self.ssl()
+            .and_then(|ssl| ssl.servername(ssl::NameType::HOST_NAME))
+            .map(String::from)
+    }
+
+    /// Method to gracefully close the stream.
+    /// For an SSL stream, a close_notify alert is sent and the data of the remote are discarded until its own close_notify (bidirectional shutdown) or the `timeout`.
+    /// The write side of the socket is then closed. The socket itself is closed when the stream is dropped.
+    ///
+    /// Return `true` if the close_notify alerts were exchanged, `false` if the remote didn't answer it in time or if the stream is not an SSL one.
+    /// OpenSSL 3 reports a socket closed without close_notify as a read error, older versions can't tell it apart from a close_notify
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use tokio::io;
+    /// use prosa::io::stream::Stream;
+    ///
+    /// async fn disconnect(mut stream: Stream) -> Result<(), io::Error> {
+    ///     if !stream.shutdown_tls(Duration::from_secs(1)).await? {
+    ///         println!("The remote didn't close its SSL session");
+    ///     }
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn shutdown_tls(&mut self, shutdown_timeout: Duration) -> Result<bool, io::Error> {
+        let (Stream::Ssl(ssl_stream) | Stream::SslHttpProxy(ssl_stream)) = self else {
+            self.shutdown().await?;
+            return Ok(false);
+        };
+
+        let bidirectional_shutdown = async {
+            // Send the close_notify alert
+            ssl_stream.shutdown().await?;
+
+            // Discard the data of the remote until its close_notify (another error means that the remote closed the socket without it)
+            let mut buf = [0u8; 4096];
+            loop {
+                match Pin::new(&mut *ssl_stream).peek(&mut buf).await {
+                    Ok(len) if len > 0 => {
+                        ssl_stream.read_exact(&mut buf[..len]).await?;
+                    }
+                    Ok(_) => return Ok(false),
+                    Err(e) => {
+                        return Ok::<bool, io::Error>(e.code() == ssl::ErrorCode::ZERO_RETURN)
+                    }
+                }
+            }
+        };
+
+        match timeout(shutdown_timeout, bidirectional_shutdown).await {
+            Ok(res) => res,
+            Err(_) => {
+                debug!(
+                    "The remote didn't answer the close_notify in {} ms",
+                    shutdown_timeout.as_millis()
+                );
+                Ok(false)
+            }
+        }
+    }
+
     /// Sets the value of the TCP_NODELAY option on the ProSA socket
     pub fn set_nodelay(&self, nodelay: bool) -> Result<(), io::Error> {
         match self {
             #[cfg(target_family = "unix")]
             Stream::Unix(_) => Ok(()),
+            #[cfg(target_family = "windows")]
+            Stream::NamedPipe(..) | Stream::NamedPipeServer(..) => Ok(()),
             Stream::Tcp(s) => s.set_nodelay(nodelay),
             Stream::Ssl(s) => s.get_ref().set_nodelay(nodelay),
             Stream::TcpHttpProxy(s) => s.set_nodelay(nodelay),
@@ -333,6 +604,8 @@ impl Stream {
         match self {
             #[cfg(target_family = "unix")]
             Stream::Unix(_) => Ok(true),
+            #[cfg(target_family = "windows")]
+            Stream::NamedPipe(..) | Stream::NamedPipeServer(..) => Ok(true),
             Stream::Tcp(s) => s.nodelay(),
             Stream::Ssl(s) => s.get_ref().nodelay(),
             Stream::TcpHttpProxy(s) => s.nodelay(),
@@ -345,6 +618,8 @@ impl Stream {
         match self {
             #[cfg(target_family = "unix")]
             Stream::Unix(_) => Ok(()),
+            #[cfg(target_family = "windows")]
+            Stream::NamedPipe(..) | Stream::NamedPipeServer(..) => Ok(()),
             Stream::Tcp(s) => s.set_ttl(ttl),
             Stream::Ssl(s) => s.get_ref().set_ttl(ttl),
             Stream::TcpHttpProxy(s) => s.set_ttl(ttl),
@@ -357,14 +632,33 @@ impl Stream {
         match self {
             #[cfg(target_family = "unix")]
             Stream::Unix(_) => Ok(0),
+            #[cfg(target_family = "windows")]
+            Stream::NamedPipe(..) | Stream::NamedPipeServer(..) => Ok(0),
             Stream::Tcp(s) => s.ttl(),
             Stream::Ssl(s) => s.get_ref().ttl(),
             Stream::TcpHttpProxy(s) => s.ttl(),
             Stream::SslHttpProxy(s) => s.get_ref().ttl(),
         }
     }
+
+    /// Apply socket options (keepalive, buffers, linger, nodelay) on the ProSA socket
+    ///
+    /// Do nothing for Unix sockets and named pipes
+    pub fn configure(&self, opts: &SocketOptions) -> Result<(), io::Error> {
+        match self {
+            #[cfg(target_family = "unix")]
+            Stream::Unix(_) => Ok(()),
+            #[cfg(target_family = "windows")]
+            Stream::NamedPipe(..) | Stream::NamedPipeServer(..) => Ok(()),
+            Stream::Tcp(s) => opts.apply(s),
+            Stream::Ssl(s) => opts.apply(s.get_ref()),
+            Stream::TcpHttpProxy(s) => opts.apply(s),
+            Stream::SslHttpProxy(s) => opts.apply(s.get_ref()),
+        }
+    }
 }
 
+#[cfg(target_family = "unix")]
 impl AsFd for Stream {
     fn as_fd(&self) -> BorrowedFd<'_> {
         match self {
@@ -378,6 +672,7 @@ impl AsFd for Stream {
     }
 }
 
+#[cfg(target_family = "unix")]
 impl AsRawFd for Stream {
     fn as_raw_fd(&self) -> RawFd {
         match self {
@@ -403,6 +698,16 @@ impl AsyncRead for Stream {
                 let stream = Pin::new(s);
                 stream.poll_read(cx, buf)
             }
+            #[cfg(target_family = "windows")]
+            Stream::NamedPipe(s, _) => {
+                let stream = Pin::new(s);
+                stream.poll_read(cx, buf)
+            }
+            #[cfg(target_family = "windows")]
+            Stream::NamedPipeServer(s, _) => {
+                let stream = Pin::new(s);
+                stream.poll_read(cx, buf)
+            }
             Stream::Tcp(s) => {
                 let stream = Pin::new(s);
                 stream.poll_read(cx, buf)
@@ -435,6 +740,16 @@ impl AsyncWrite for Stream {
                 let stream = Pin::new(s);
                 stream.poll_write(cx, buf)
             }
+            #[cfg(target_family = "windows")]
+            Stream::NamedPipe(s, _) => {
+                let stream = Pin::new(s);
+                stream.poll_write(cx, buf)
+            }
+            #[cfg(target_family = "windows")]
+            Stream::NamedPipeServer(s, _) => {
+                let stream = Pin::new(s);
+                stream.poll_write(cx, buf)
+            }
             Stream::Tcp(s) => {
                 let stream = Pin::new(s);
                 stream.poll_write(cx, buf)
@@ -465,6 +780,16 @@ impl AsyncWrite for Stream {
                 let stream = Pin::new(s);
                 stream.poll_write_vectored(cx, bufs)
             }
+            #[cfg(target_family = "windows")]
+            Stream::NamedPipe(s, _) => {
+                let stream = Pin::new(s);
+                stream.poll_write_vectored(cx, bufs)
+            }
+            #[cfg(target_family = "windows")]
+            Stream::NamedPipeServer(s, _) => {
+                let stream = Pin::new(s);
+                stream.poll_write_vectored(cx, bufs)
+            }
             Stream::Tcp(s) => {
                 let stream = Pin::new(s);
                 stream.poll_write_vectored(cx, bufs)
@@ -488,6 +813,10 @@ impl AsyncWrite for Stream {
         match self {
             #[cfg(target_family = "unix")]
             Stream::Unix(s) => s.is_write_vectored(),
+            #[cfg(target_family = "windows")]
+            Stream::NamedPipe(s, _) => s.is_write_vectored(),
+            #[cfg(target_family = "windows")]
+            Stream::NamedPipeServer(s, _) => s.is_write_vectored(),
             Stream::Tcp(s) => s.is_write_vectored(),
             Stream::Ssl(s) => s.is_write_vectored(),
             Stream::TcpHttpProxy(s) => s.is_write_vectored(),
@@ -503,6 +832,16 @@ impl AsyncWrite for Stream {
                 let stream = Pin::new(s);
                 stream.poll_flush(cx)
             }
+            #[cfg(target_family = "windows")]
+            Stream::NamedPipe(s, _) => {
+                let stream = Pin::new(s);
+                stream.poll_flush(cx)
+            }
+            #[cfg(target_family = "windows")]
+            Stream::NamedPipeServer(s, _) => {
+                let stream = Pin::new(s);
+                stream.poll_flush(cx)
+            }
             Stream::Tcp(s) => {
                 let stream = Pin::new(s);
                 stream.poll_flush(cx)
@@ -529,6 +868,16 @@ impl AsyncWrite for Stream {
                 let stream = Pin::new(s);
                 stream.poll_shutdown(cx)
             }
+            #[cfg(target_family = "windows")]
+            Stream::NamedPipe(s, _) => {
+                let stream = Pin::new(s);
+                stream.poll_shutdown(cx)
+            }
+            #[cfg(target_family = "windows")]
+            Stream::NamedPipeServer(s, _) => {
+                let stream = Pin::new(s);
+                stream.poll_shutdown(cx)
+            }
             Stream::Tcp(s) => {
                 let stream = Pin::new(s);
                 stream.poll_shutdown(cx)
@@ -560,6 +909,8 @@ impl fmt::Display for Stream {
         match self {
             #[cfg(target_family = "unix")]
             Stream::Unix(_) => write!(f, "unix://{}", addr),
+            #[cfg(target_family = "windows")]
+            Stream::NamedPipe(..) | Stream::NamedPipeServer(..) => write!(f, "pipe://{}", addr),
             Stream::Tcp(_) => write!(f, "tcp://{}", addr),
             Stream::Ssl(_) => write!(f, "ssl://{}", addr),
             Stream::TcpHttpProxy(_) => write!(f, "tcp+http_proxy://{}", addr),
@@ -569,6 +920,79 @@ impl fmt::Display for Stream {
 }
 
 #[cfg(target_family = "unix")]
+/// Parameters negotiated during the SSL handshake of a stream (see [`Stream::tls_info`])
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TlsInfo {
+    /// Protocol version (`TLSv1.3`, `TLSv1.2`, ...)
+    pub version: String,
+    /// Name of the cipher suite
+    pub cipher: String,
+    /// Protocol negotiated with ALPN, if any
+    pub alpn: Option<String>,
+    /// The session was resumed (no full handshake)
+    pub resumed: bool,
+    /// Subject of the remote certificate, if the remote gave one
+    pub peer_subject: Option<String>,
+}
+
+impl fmt::Display for TlsInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.version, self.cipher)?;
+        if let Some(alpn) = &self.alpn {
+            write!(f, " alpn={}", alpn)?;
+        }
+        if self.resumed {
+            write!(f, " resumed")?;
+        }
+        if let Some(peer_subject) = &self.peer_subject {
+            write!(f, " peer=[{}]", peer_subject)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Counter of the SSL sessions established by a processor, by protocol version and cipher suite (`prosa_tls_sessions`)
+///
+/// ```
+/// use opentelemetry::KeyValue;
+/// use prosa::io::stream::{Stream, TlsMeters};
+///
+/// fn count_session(meter: &opentelemetry::metrics::Meter, stream: &Stream) {
+///     let tls_meters = TlsMeters::new(meter, vec![KeyValue::new("proc", "server")]);
+///     if let Some(tls_info) = stream.tls_info() {
+///         tls_meters.record(&tls_info);
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct TlsMeters {
+    sessions: Counter<u64>,
+    attributes: Vec<KeyValue>,
+}
+
+impl TlsMeters {
+    /// Method to register the SSL sessions counter on a meter
+    pub fn new(meter: &Meter, attributes: Vec<KeyValue>) -> TlsMeters {
+        TlsMeters {
+            sessions: meter
+                .u64_counter("prosa_tls_sessions")
+                .with_description("SSL sessions established, by version and cipher suite")
+                .init(),
+            attributes,
+        }
+    }
+
+    /// Method to count an established SSL session
+    pub fn record(&self, tls_info: &TlsInfo) {
+        let mut attributes = self.attributes.clone();
+        attributes.push(KeyValue::new("version", tls_info.version.clone()));
+        attributes.push(KeyValue::new("cipher", tls_info.cipher.clone()));
+        attributes.push(KeyValue::new("resumed", tls_info.resumed));
+        self.sessions.add(1, &attributes);
+    }
+}
+
 impl From<tokio::net::UnixStream> for Stream {
     fn from(stream: tokio::net::UnixStream) -> Self {
         Stream::Unix(stream)
@@ -581,17 +1005,581 @@ impl From<TcpStream> for Stream {
     }
 }
 
+/// Side of a bidirectional copy between two streams (see [`copy_bidirectional_metrics`])
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CopySide {
+    /// First stream of the copy
+    A,
+    /// Second stream of the copy
+    B,
+}
+
+/// Options of a bidirectional copy between two streams (see [`copy_bidirectional_metrics`])
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CopyOptions {
+    /// Duration without any transferred byte (in both directions) after which the copy is stopped
+    pub idle_timeout: Option<Duration>,
+    /// Maximum number of bytes copied from the stream A to the stream B
+    pub a_to_b_limit: Option<u64>,
+    /// Maximum number of bytes copied from the stream B to the stream A
+    pub b_to_a_limit: Option<u64>,
+    /// Size of the buffer of each direction when the bytes are copied through userspace
+    pub buffer_size: usize,
+}
+
+impl Default for CopyOptions {
+    fn default() -> Self {
+        CopyOptions {
+            idle_timeout: None,
+            a_to_b_limit: None,
+            b_to_a_limit: None,
+            buffer_size: 8 * 1024,
+        }
+    }
+}
+
+/// Statistics of a bidirectional copy between two streams (see [`copy_bidirectional_metrics`])
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct CopyStats {
+    /// Number of bytes copied from the stream A to the stream B
+    pub a_to_b: u64,
+    /// Number of bytes copied from the stream B to the stream A
+    pub b_to_a: u64,
+    /// Side that closed its stream first (`None` if no side closed its stream)
+    pub first_closed: Option<CopySide>,
+    /// Sides that reached their byte limit (`A` if the A to B limit is reached)
+    pub limit_reached: Vec<CopySide>,
+    /// The copy was stopped by the idle timeout
+    pub idle_timeout: bool,
+    /// The bytes were copied with `splice(2)`, without going through userspace
+    pub spliced: bool,
+}
+
+/// State of the copy shared by both directions
+#[derive(Default)]
+struct CopyState {
+    a_to_b: Cell<u64>,
+    b_to_a: Cell<u64>,
+    first_closed: Cell<Option<CopySide>>,
+    limit_reached: RefCell<Vec<CopySide>>,
+    last_activity: Cell<Option<Instant>>,
+    spliced: Cell<bool>,
+}
+
+impl CopyState {
+    fn copied(&self, side: CopySide) -> &Cell<u64> {
+        match side {
+            CopySide::A => &self.a_to_b,
+            CopySide::B => &self.b_to_a,
+        }
+    }
+
+    fn progress(&self, side: CopySide, len: usize) {
+        let copied = self.copied(side);
+        copied.set(copied.get() + len as u64);
+        self.last_activity.set(Some(Instant::now()));
+    }
+
+    fn limited(&self, side: CopySide) {
+        self.limit_reached.borrow_mut().push(side);
+    }
+
+    fn closed(&self, side: CopySide) {
+        if self.first_closed.get().is_none() {
+            self.first_closed.set(Some(side));
+        }
+    }
+
+    /// Number of bytes that can still be read from a side, according to its limit
+    fn remaining(&self, side: CopySide, limit: Option<u64>, len: usize) -> usize {
+        limit.map_or(len, |limit| {
+            limit
+                .saturating_sub(self.copied(side).get())
+                .min(len as u64) as usize
+        })
+    }
+
+    /// Wait until no byte are transferred during the idle timeout
+    async fn idle(&self, idle_timeout: Option<Duration>) {
+        if let Some(idle_timeout) = idle_timeout {
+            loop {
+                let deadline = self.last_activity.get().unwrap_or_else(Instant::now) + idle_timeout;
+                sleep_until(deadline).await;
+                if self
+                    .last_activity
+                    .get()
+                    .is_none_or(|last_activity| last_activity + idle_timeout <= Instant::now())
+                {
+                    return;
+                }
+            }
+        } else {
+            std::future::pending().await
+        }
+    }
+}
+
+/// Method to copy the bytes of a direction through a userspace buffer
+async fn copy_buffered<R, W>(
+    reader: &mut R,
+    writer: &mut W,
+    side: CopySide,
+    limit: Option<u64>,
+    buffer_size: usize,
+    state: &CopyState,
+) -> Result<(), io::Error>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let mut buf = vec![0; buffer_size.max(1)];
+    loop {
+        let len = state.remaining(side, limit, buf.len());
+        if len == 0 {
+            state.limited(side);
+            return writer.shutdown().await;
+        }
+
+        let read = reader.read(&mut buf[..len]).await?;
+        if read == 0 {
+            state.closed(side);
+            return writer.shutdown().await;
+        }
+
+        writer.write_all(&buf[..read]).await?;
+        state.progress(side, read);
+    }
+}
+
+/// Zero-copy transfer between two TCP sockets with `splice(2)` through a pipe
+#[cfg(all(target_os = "linux", feature = "splice"))]
+mod splice {
+    use std::{
+        io,
+        net::Shutdown,
+        os::fd::{AsRawFd, FromRawFd, OwnedFd},
+    };
+
+    use socket2::SockRef;
+    use tokio::{io::Interest, net::TcpStream};
+
+    use super::{CopySide, CopyState};
+
+    /// Number of bytes moved at most by a `splice(2)` call (default capacity of a pipe)
+    const SPLICE_LEN: usize = 64 * 1024;
+
+    fn pipe() -> Result<(OwnedFd, OwnedFd), io::Error> {
+        let mut fds = [0; 2];
+        // SAFETY: the pipe file descriptors are written in the 2 integers array
+        if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_NONBLOCK | libc::O_CLOEXEC) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        // SAFETY: the pipe file descriptors are new and owned by nobody else
+        Ok(unsafe { (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) })
+    }
+
+    fn splice(fd_in: i32, fd_out: i32, len: usize) -> Result<usize, io::Error> {
+        // SAFETY: both file descriptors are valid during the call, and no offsets are given
+        let spliced = unsafe {
+            libc::splice(
+                fd_in,
+                std::ptr::null_mut(),
+                fd_out,
+                std::ptr::null_mut(),
+                len,
+                libc::SPLICE_F_MOVE | libc::SPLICE_F_NONBLOCK,
+            )
+        };
+        if spliced < 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(spliced as usize)
+        }
+    }
+
+    /// Method to move the bytes of a direction from a socket to the other through a pipe
+    pub(super) async fn copy(
+        reader: &TcpStream,
+        writer: &TcpStream,
+        side: CopySide,
+        limit: Option<u64>,
+        state: &CopyState,
+    ) -> Result<(), io::Error> {
+        let (pipe_read, pipe_write) = pipe()?;
+        loop {
+            let len = state.remaining(side, limit, SPLICE_LEN);
+            if len == 0 {
+                state.limited(side);
+                return SockRef::from(writer).shutdown(Shutdown::Write);
+            }
+
+            let read = loop {
+                reader.readable().await?;
+                match reader.try_io(Interest::READABLE, || {
+                    splice(reader.as_raw_fd(), pipe_write.as_raw_fd(), len)
+                }) {
+                    Err(e) if e.kind() == io::ErrorKind::WouldBlock => continue,
+                    read => break read?,
+                }
+            };
+            if read == 0 {
+                state.closed(side);
+                return SockRef::from(writer).shutdown(Shutdown::Write);
+            }
+
+            let mut pending = read;
+            while pending > 0 {
+                writer.writable().await?;
+                match writer.try_io(Interest::WRITABLE, || {
+                    splice(pipe_read.as_raw_fd(), writer.as_raw_fd(), pending)
+                }) {
+                    Err(e) if e.kind() == io::ErrorKind::WouldBlock => continue,
+                    written => {
+                        let written = written?;
+                        pending -= written;
+                        state.progress(side, written);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Method to copy the bytes between two streams in both directions concurrently, until both sides closed their stream
+///
+/// When a side close its stream (or reach its byte limit), the writing of the other stream is shut down and the opposite direction continue.
+/// The copy is stopped if no byte is transferred during the idle timeout.
+/// On Linux with the `splice` feature, bytes between two TCP streams are moved with `splice(2)` without going through userspace. Other streams use buffered copies.
+///
+/// ```
+/// use std::time::Duration;
+/// use tokio::io;
+/// use prosa::io::stream::{copy_bidirectional_metrics, CopyOptions, Stream};
+///
+/// async fn proxying(mut client: Stream) -> Result<(), io::Error> {
+///     let mut server = Stream::connect_tcp("worldline.com:80").await?;
+///
+///     let opts = CopyOptions {
+///         idle_timeout: Some(Duration::from_secs(60)),
+///         ..Default::default()
+///     };
+///     let stats = copy_bidirectional_metrics(&mut client, &mut server, opts).await?;
+///     println!("{} bytes sent, {} bytes received", stats.a_to_b, stats.b_to_a);
+///
+///     Ok(())
+/// }
+/// ```
+pub async fn copy_bidirectional_metrics(
+    a: &mut Stream,
+    b: &mut Stream,
+    opts: CopyOptions,
+) -> Result<CopyStats, io::Error> {
+    let state = CopyState::default();
+
+    let directions = async {
+        #[cfg(all(target_os = "linux", feature = "splice"))]
+        if let (Stream::Tcp(a), Stream::Tcp(b)) = (&*a, &*b) {
+            state.spliced.set(true);
+            return tokio::try_join!(
+                splice::copy(a, b, CopySide::A, opts.a_to_b_limit, &state),
+                splice::copy(b, a, CopySide::B, opts.b_to_a_limit, &state)
+            );
+        }
+
+        let (mut a_reader, mut a_writer) = tokio::io::split(a);
+        let (mut b_reader, mut b_writer) = tokio::io::split(b);
+        tokio::try_join!(
+            copy_buffered(
+                &mut a_reader,
+                &mut b_writer,
+                CopySide::A,
+                opts.a_to_b_limit,
+                opts.buffer_size,
+                &state
+            ),
+            copy_buffered(
+                &mut b_reader,
+                &mut a_writer,
+                CopySide::B,
+                opts.b_to_a_limit,
+                opts.buffer_size,
+                &state
+            )
+        )
+    };
+
+    let idle_timeout = tokio::select! {
+        ends = directions => {
+            ends?;
+            false
+        }
+        _ = state.idle(opts.idle_timeout) => true,
+    };
+
+    Ok(CopyStats {
+        a_to_b: state.a_to_b.get(),
+        b_to_a: state.b_to_a.get(),
+        first_closed: state.first_closed.get(),
+        limit_reached: state.limit_reached.take(),
+        idle_timeout,
+        spliced: state.spliced.get(),
+    })
+}
+
+/// ProSA stream wrapper that detect idle read/write on a [`Stream`]
+///
+/// If no progress is made on a pending read (or write) during the configured duration, the operation return an [`io::ErrorKind::TimedOut`] error.
+/// The idle timer is reset every time the stream make progress.
+///
+/// ```
+/// use std::time::Duration;
+/// use tokio::io::{self, AsyncReadExt};
+/// use prosa::io::stream::{Stream, TimedStream};
+///
+/// async fn reading() -> Result<(), io::Error> {
+///     let stream: Stream = Stream::connect_tcp("worldline.com:80").await?;
+///     let mut timed_stream = TimedStream::new(stream, Some(Duration::from_secs(30)), None);
+///
+///     let mut buf = [0; 5];
+///     match timed_stream.read_exact(&mut buf).await {
+///         Err(e) if e.kind() == io::ErrorKind::TimedOut => {
+///             // Nothing receive since 30 seconds, the connection is considered as dead
+///         }
+///         _ => {}
+///     }
+///
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug)]
+pub struct TimedStream {
+    stream: Stream,
+    read_timeout: Option<Duration>,
+    write_timeout: Option<Duration>,
+    read_timer: Option<Pin<Box<Sleep>>>,
+    write_timer: Option<Pin<Box<Sleep>>>,
+}
+
+impl TimedStream {
+    /// Method to create a timed stream with optional read/write idle timeouts
+    pub fn new(
+        stream: Stream,
+        read_timeout: Option<Duration>,
+        write_timeout: Option<Duration>,
+    ) -> TimedStream {
+        TimedStream {
+            stream,
+            read_timeout,
+            write_timeout,
+            read_timer: None,
+            write_timer: None,
+        }
+    }
+
+    /// Getter of the read idle timeout
+    pub fn read_timeout(&self) -> Option<Duration> {
+        self.read_timeout
+    }
+
+    /// Setter of the read idle timeout
+    pub fn set_read_timeout(&mut self, read_timeout: Option<Duration>) {
+        self.read_timeout = read_timeout;
+        self.read_timer = None;
+    }
+
+    /// Getter of the write idle timeout
+    pub fn write_timeout(&self) -> Option<Duration> {
+        self.write_timeout
+    }
+
+    /// Setter of the write idle timeout
+    pub fn set_write_timeout(&mut self, write_timeout: Option<Duration>) {
+        self.write_timeout = write_timeout;
+        self.write_timer = None;
+    }
+
+    /// Getter of the inner stream
+    pub fn get_ref(&self) -> &Stream {
+        &self.stream
+    }
+
+    /// Mutable getter of the inner stream
+    pub fn get_mut(&mut self) -> &mut Stream {
+        &mut self.stream
+    }
+
+    /// Consume the timed stream to return the inner stream
+    pub fn into_inner(self) -> Stream {
+        self.stream
+    }
+
+    /// Method to poll an idle timer if the operation is pending.
+    /// Reset the timer if the operation made progress.
+    fn poll_idle<T>(
+        cx: &mut Context<'_>,
+        poll: Poll<io::Result<T>>,
+        timeout: Option<Duration>,
+        timer: &mut Option<Pin<Box<Sleep>>>,
+        operation: &str,
+    ) -> Poll<io::Result<T>> {
+        match (poll, timeout) {
+            (Poll::Pending, Some(timeout)) => {
+                let idle_timer = timer.get_or_insert_with(|| Box::pin(sleep(timeout)));
+                if idle_timer.as_mut().poll(cx).is_ready() {
+                    *timer = None;
+                    Poll::Ready(Err(io::Error::new(
+                        io::ErrorKind::TimedOut,
+                        format!("No {} activity since {:?}", operation, timeout),
+                    )))
+                } else {
+                    Poll::Pending
+                }
+            }
+            (Poll::Pending, None) => Poll::Pending,
+            (ready, _) => {
+                *timer = None;
+                ready
+            }
+        }
+    }
+}
+
+impl Deref for TimedStream {
+    type Target = Stream;
+
+    fn deref(&self) -> &Self::Target {
+        &self.stream
+    }
+}
+
+impl DerefMut for TimedStream {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.stream
+    }
+}
+
+#[cfg(target_family = "unix")]
+impl AsFd for TimedStream {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.stream.as_fd()
+    }
+}
+
+#[cfg(target_family = "unix")]
+impl AsRawFd for TimedStream {
+    fn as_raw_fd(&self) -> RawFd {
+        self.stream.as_raw_fd()
+    }
+}
+
+impl AsyncRead for TimedStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let timed_stream = self.get_mut();
+        let poll = Pin::new(&mut timed_stream.stream).poll_read(cx, buf);
+        Self::poll_idle(
+            cx,
+            poll,
+            timed_stream.read_timeout,
+            &mut timed_stream.read_timer,
+            "read",
+        )
+    }
+}
+
+impl AsyncWrite for TimedStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let timed_stream = self.get_mut();
+        let poll = Pin::new(&mut timed_stream.stream).poll_write(cx, buf);
+        Self::poll_idle(
+            cx,
+            poll,
+            timed_stream.write_timeout,
+            &mut timed_stream.write_timer,
+            "write",
+        )
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        let timed_stream = self.get_mut();
+        let poll = Pin::new(&mut timed_stream.stream).poll_write_vectored(cx, bufs);
+        Self::poll_idle(
+            cx,
+            poll,
+            timed_stream.write_timeout,
+            &mut timed_stream.write_timer,
+            "write",
+        )
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.stream.is_write_vectored()
+    }
+
+    #[inline]
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let timed_stream = self.get_mut();
+        let poll = Pin::new(&mut timed_stream.stream).poll_flush(cx);
+        Self::poll_idle(
+            cx,
+            poll,
+            timed_stream.write_timeout,
+            &mut timed_stream.write_timer,
+            "write",
+        )
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let timed_stream = self.get_mut();
+        let poll = Pin::new(&mut timed_stream.stream).poll_shutdown(cx);
+        Self::poll_idle(
+            cx,
+            poll,
+            timed_stream.write_timeout,
+            &mut timed_stream.write_timer,
+            "write",
+        )
+    }
+}
+
+impl fmt::Display for TimedStream {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.stream)
+    }
+}
+
+impl From<Stream> for TimedStream {
+    fn from(stream: Stream) -> Self {
+        TimedStream::new(stream, None, None)
+    }
+}
+
 /// Configuration struct of an network target
 ///
 /// ```
 /// use tokio::io;
 /// use url::Url;
-/// use prosa::io::stream::{TargetSetting, Stream};
+/// use prosa::io::stream::{TargetSetting, Stream, TimedStream};
 ///
 /// async fn connecting() -> Result<(), io::Error> {
 ///     let wl_target = TargetSetting::new(Url::parse("https://worldline.com").unwrap(), None, None);
 ///     let stream: Stream = wl_target.connect().await?;
 ///
+///     // Or with the configured read/write idle timeouts
+///     let timed_stream: TimedStream = wl_target.connect_timed().await?;
+///
 ///     // Handle the stream like any tokio stream
 ///
 ///     Ok(())
@@ -612,6 +1600,15 @@ pub struct TargetSetting {
     #[serde(default = "TargetSetting::get_default_connect_timeout")]
     /// Timeout for socket connection in milliseconds
     pub connect_timeout: u32,
+    /// Optional read idle timeout in milliseconds
+    pub read_timeout: Option<u32>,
+    /// Optional write idle timeout in milliseconds
+    pub write_timeout: Option<u32>,
+    /// Optional socket options applied after the connection
+    pub socket: Option<SocketOptions>,
+    #[serde(default)]
+    /// Resolution of the target addresses, and failover between them
+    pub resolve: ResolveSettings,
 }
 
 impl TargetSetting {
@@ -627,6 +1624,10 @@ impl TargetSetting {
             proxy,
             ssl_context: None,
             connect_timeout: Self::get_default_connect_timeout(),
+            read_timeout: None,
+            write_timeout: None,
+            socket: None,
+            resolve: ResolveSettings::default(),
         };
 
         target.init_ssl_context();
@@ -643,11 +1644,119 @@ impl TargetSetting {
         }
     }
 
-    /// Method to connect a ProSA stream to the remote target using the configuration
+    /// Getter of the SSL session cache of the target, to know its resumed and full handshakes.
+    /// Available if the SSL context is initialized with a `session_cache_size`
+    pub fn get_ssl_session_cache(&self) -> Option<&SslSessionCache> {
+        self.ssl_context
+            .as_ref()
+            .and_then(|ssl_context| SslSessionCache::from_context(ssl_context.context()))
+    }
+
+    /// Method to connect a ProSA stream to the remote target using the configuration.
+    /// The connection must be established within the `connect_timeout`.
+    ///
+    /// The target host is resolved at each connection (through the [`DnsCache`]), and its addresses are tried in order until one accept the connection (see [`ResolveSettings`]).
+    /// The address of the connection is given by [`Stream::peer_addr`]
     pub async fn connect(&self) -> Result<Stream, io::Error> {
-        #[cfg(target_family = "unix")]
+        self.connect_with_clock(&SystemClock).await
+    }
+
+    /// Method to connect a ProSA stream to the remote target, measuring the `connect_timeout` with the given clock (see [`TargetSetting::connect`])
+    pub async fn connect_with_clock<C>(&self, clock: &C) -> Result<Stream, io::Error>
+    where
+        C: Clock,
+    {
+        let connect_timeout = Duration::from_millis(self.connect_timeout as u64);
+        let stream = clock
+            .timeout(connect_timeout, self.connect_stream(connect_timeout, clock))
+            .await
+            .map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    format!(
+                        "Can't connect to {} within {} ms",
+                        self.url, self.connect_timeout
+                    ),
+                )
+            })??;
+        if let Some(socket_options) = &self.socket {
+            stream.configure(socket_options)?;
+        }
+
+        Ok(stream)
+    }
+
+    /// Method to connect a ProSA stream to the remote target (see [`TargetSetting::connect`]), wrapped with the configured `read_timeout`/`write_timeout` idle timeouts
+    pub async fn connect_timed(&self) -> Result<TimedStream, io::Error> {
+        self.connect_timed_with_clock(&SystemClock).await
+    }
+
+    /// Method to connect a ProSA stream wrapped with the configured idle timeouts, measuring the `connect_timeout` with the given clock (see [`TargetSetting::connect_timed`])
+    pub async fn connect_timed_with_clock<C>(&self, clock: &C) -> Result<TimedStream, io::Error>
+    where
+        C: Clock,
+    {
+        Ok(TimedStream::new(
+            self.connect_with_clock(clock).await?,
+            self.read_timeout.map(|t| Duration::from_millis(t as u64)),
+            self.write_timeout.map(|t| Duration::from_millis(t as u64)),
+        ))
+    }
+
+    /// Method to connect a TCP stream to the first reachable address of the target host
+    async fn connect_tcp_stream<C>(
+        &self,
+        host: &str,
+        connect_timeout: Duration,
+        clock: &C,
+    ) -> Result<TcpStream, io::Error>
+    where
+        C: Clock,
+    {
+        let port = self.url.port_or_known_default().ok_or(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Can't retrieve port from url `{}`", self.url),
+        ))?;
+        let mut addrs = DnsCache::global()
+            .resolve(host, port, &self.resolve, clock)
+            .await?;
+        if self.resolve.shuffle {
+            dns::shuffle(&mut addrs);
+        }
+
+        let attempt_timeout = self
+            .resolve
+            .get_attempt_timeout(connect_timeout, addrs.len());
+        dns::connect_addrs(&addrs, attempt_timeout, clock).await
+    }
+
+    /// Method to connect the raw ProSA stream to the remote target
+    async fn connect_stream<C>(
+        &self,
+        connect_timeout: Duration,
+        clock: &C,
+    ) -> Result<Stream, io::Error>
+    where
+        C: Clock,
+    {
         if self.url.scheme() == "unix" || self.url.scheme() == "file" {
+            #[cfg(target_family = "unix")]
             return Stream::connect_unix(self.url.path()).await;
+            #[cfg(not(target_family = "unix"))]
+            return Err(unsupported_url(&self.url));
+        }
+
+        if self.url.scheme() == "pipe" {
+            #[cfg(target_family = "windows")]
+            return match url_named_pipe(&self.url) {
+                Some(path) => Stream::connect_named_pipe(path).await,
+                None => Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("{} doesn't target a named pipe", self.url),
+                )),
+            };
+            #[cfg(not(target_family = "windows"))]
+            return Err(unsupported_url(&self.url));
         }
 
         let ssl_context = if self.ssl_context.is_some() {
@@ -669,13 +1778,25 @@ impl TargetSetting {
             None
         };
 
+        let verify_hostname = !self
+            .ssl
+            .as_ref()
+            .is_some_and(|c| c.is_insecure_skip_hostname_verify());
+        if ssl_context.is_some() && !verify_hostname {
+            warn!(
+                "The certificate host of the target {} is not verified (`insecure_skip_hostname_verify`), don't use it in production",
+                self.url
+            );
+        }
+
         if let Some(proxy_url) = &self.proxy {
             if let Some(ssl_cx) = ssl_context {
-                Stream::connect_ssl_with_http_proxy(
+                Stream::connect_ssl_with_http_proxy_verify(
                     self.url.host_str().unwrap_or_default(),
                     self.url.port_or_known_default().unwrap_or_default(),
                     &ssl_cx,
                     proxy_url,
+                    verify_hostname,
                 )
                 .await
             } else {
@@ -686,11 +1807,28 @@ impl TargetSetting {
                 )
                 .await
             }
-        } else if let Some(ssl_cx) = ssl_context {
-            Stream::connect_ssl(&self.url, &ssl_cx).await
         } else {
-            let addrs = self.url.socket_addrs(|| self.url.port_or_known_default())?;
-            Stream::connect_tcp(&*addrs).await
+            let host = self.url.host_str().ok_or(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Can't retrieve host from url `{}`", self.url),
+            ))?;
+            let tcp_stream = self
+                .connect_tcp_stream(host, connect_timeout, clock)
+                .await?;
+            if let Some(ssl_cx) = ssl_context {
+                Ok(Stream::Ssl(
+                    Stream::create_ssl(
+                        tcp_stream,
+                        &ssl_cx,
+                        host,
+                        self.url.port_or_known_default().unwrap_or_default(),
+                        verify_hostname,
+                    )
+                    .await?,
+                ))
+            } else {
+                Ok(Stream::Tcp(tcp_stream))
+            }
         }
     }
 }
@@ -703,6 +1841,10 @@ impl From<Url> for TargetSetting {
             proxy: None,
             ssl_context: None,
             connect_timeout: Self::get_default_connect_timeout(),
+            read_timeout: None,
+            write_timeout: None,
+            socket: None,
+            resolve: ResolveSettings::default(),
         }
     }
 }
@@ -713,6 +1855,10 @@ impl fmt::Debug for TargetSetting {
             .field("url", &self.url)
             .field("ssl", &self.ssl)
             .field("connect_timeout", &self.connect_timeout)
+            .field("read_timeout", &self.read_timeout)
+            .field("write_timeout", &self.write_timeout)
+            .field("socket", &self.socket)
+            .field("resolve", &self.resolve)
             .finish()
     }
 }
@@ -5,6 +5,8 @@
 #![doc = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/doc_assets/header_badges.md"))]
 //!
 //! ProSA base library that define standard modules and include procedural macros
+//!
+//! To embed ProSA in an existing binary (without cargo-prosa), use the [`ProsaBuilder`](core::builder::ProsaBuilder).
 #![warn(missing_docs)]
 #![deny(unreachable_pub)]
 
@@ -17,35 +19,47 @@ pub mod io;
 pub mod inj;
 pub mod stub;
 
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+
 #[cfg(test)]
 mod tests {
     use std::{
+        collections::BTreeMap,
         error::Error,
-        sync::atomic::{AtomicU32, Ordering},
-        time,
+        io::{Read as _, Write as _},
+        net::{TcpListener, TcpStream},
+        sync::Mutex,
+        time::{self, Duration},
     };
 
     extern crate self as prosa;
 
     use prosa::core::{
+        builder::{BuilderError, ProsaBuilder},
+        error::BusError,
         main::{MainProc, MainRunnable as _},
-        proc::{Proc, ProcConfig as _},
+        msg::InternalMsg,
+        proc::{Proc, ProcBusParam as _, ProcConfig as _},
+        service::{ServiceCall, ServiceError},
+        tps::TpsSettings,
     };
     use prosa::inj::{
         adaptor::InjDummyAdaptor,
         proc::{InjProc, InjSettings},
     };
     use prosa::stub::{
-        adaptor::StubAdaptor,
+        adaptor::{StubAdaptor, StubParotAdaptor},
         proc::{StubProc, StubSettings},
     };
-    use prosa_macros::{settings, Adaptor};
-    use prosa_utils::msg::simple_string_tvf::SimpleStringTvf;
+    use prosa_macros::{proc, settings, Adaptor};
+    use prosa_utils::msg::{simple_string_tvf::SimpleStringTvf, tvf::Tvf as _};
     use serde::Serialize;
 
     const SERVICE_TEST: &str = "PROSA_TEST";
     const WAIT_TIME: time::Duration = time::Duration::from_secs(5);
-    static COUNTER: AtomicU32 = AtomicU32::new(0);
+    /// Number of requests processed by the test stub adaptor, by service
+    static COUNTERS: Mutex<BTreeMap<String, u32>> = Mutex::new(BTreeMap::new());
 
     /// Dummy settings
     #[settings]
@@ -56,17 +70,46 @@ mod tests {
     }
 
     impl TestSettings {
-        fn new(service_name: &str) -> Self {
+        fn new(service_name: &str, prometheus_endpoint: &str) -> Self {
             let stub = StubSettings::new(vec![service_name.into()]);
             let inj = InjSettings::new(service_name.into());
+            let observability = serde_yaml::from_str(&format!(
+                "metrics:\n  prometheus:\n    endpoint: {}",
+                prometheus_endpoint
+            ))
+            .unwrap();
             TestSettings {
                 stub,
                 inj,
+                observability,
                 ..Default::default()
             }
         }
     }
 
+    /// Scrape the prometheus metrics exposed by ProSA
+    fn scrape_metrics(prometheus_endpoint: &str) -> String {
+        http_get(prometheus_endpoint, "/metrics", None)
+    }
+
+    /// Send an HTTP GET request and return the whole response
+    fn http_get(endpoint: &str, path: &str, authorization: Option<&str>) -> String {
+        let mut stream = TcpStream::connect(endpoint).unwrap();
+        write!(
+            stream,
+            "GET {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n",
+            path, endpoint
+        )
+        .unwrap();
+        if let Some(authorization) = authorization {
+            write!(stream, "Authorization: {}\r\n", authorization).unwrap();
+        }
+        write!(stream, "\r\n").unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
     #[derive(Adaptor)]
     struct TestStubAdaptor {
         msg_count: u32,
@@ -87,45 +130,703 @@ mod tests {
             Ok(Self { msg_count: 0 })
         }
 
-        fn process_request(&mut self, _service_name: &str, request: &M) -> M {
+        fn process_request(&mut self, service_name: &str, request: &M) -> Result<M, ServiceError> {
             assert!(!request.is_empty());
             self.msg_count += 1;
-            COUNTER.fetch_add(1, Ordering::Relaxed);
-            request.clone()
+            *COUNTERS
+                .lock()
+                .unwrap()
+                .entry(service_name.to_string())
+                .or_default() += 1;
+            Ok(request.clone())
         }
     }
 
+    /// Run a ProSA with an injector processor sending transactions to a stub processor for a service
+    async fn run_prosa(service_name: &str, stub_settings: Option<StubSettings>) {
+        let prometheus_endpoint = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .to_string();
+        let mut test_settings = TestSettings::new(service_name, &prometheus_endpoint);
+        if let Some(stub_settings) = stub_settings {
+            test_settings.stub = stub_settings;
+        }
+        let stub_settings = test_settings.stub.clone();
+        let inj_settings = test_settings.inj.clone();
+
+        // Run the main task with a stub processor and an inj processor
+        let prosa = ProsaBuilder::<SimpleStringTvf>::new()
+            .settings(test_settings)
+            .with_proc::<StubProc<SimpleStringTvf>, TestStubAdaptor>("STUB_PROC", stub_settings)
+            .with_proc::<InjProc<SimpleStringTvf>, InjDummyAdaptor>("INJ_PROC", inj_settings)
+            .build_and_run()
+            .unwrap();
+        assert_eq!(Some(1), prosa.get_proc_id("STUB_PROC"));
+        assert_eq!(Some(2), prosa.get_proc_id("INJ_PROC"));
+
+        // Wait before stopping prosa
+        std::thread::sleep(WAIT_TIME);
+
+        // Check the processors standard metrics
+        let metrics = scrape_metrics(&prometheus_endpoint);
+        for proc_name in ["STUB_PROC", "INJ_PROC"] {
+            for metric in [
+                "prosa_proc_queue_depth",
+                "prosa_proc_messages_total",
+                "prosa_proc_handling_duration_seconds_count",
+            ] {
+                assert!(
+                    metrics
+                        .lines()
+                        .any(|l| l.starts_with(metric) && l.contains(proc_name)),
+                    "Missing metric {} for {}:\n{}",
+                    metric,
+                    proc_name,
+                    metrics
+                );
+            }
+        }
+        assert!(metrics
+            .lines()
+            .any(|l| l.starts_with("prosa_proc_messages_total")
+                && l.contains("STUB_PROC")
+                && l.contains("type=\"request\"")));
+
+        prosa.stop("ProSA unit test end".into()).await.unwrap();
+
+        // Wait on main task to end
+        prosa.join().unwrap();
+
+        // Check exchanges messages
+        let nb_trans = COUNTERS
+            .lock()
+            .unwrap()
+            .get(service_name)
+            .copied()
+            .unwrap_or_default() as u64;
+        let estimated_trans = WAIT_TIME.as_secs() * 5;
+        assert!(nb_trans > (estimated_trans - 2) && nb_trans < (estimated_trans + 2));
+        // Should have a coherent number of transaction with the regulator
+    }
+
     /// Test a ProSA with an injector processor sending transactions to a stub processor
-    #[allow(clippy::needless_return)]
     #[tokio::test]
     async fn prosa() {
-        let test_settings = TestSettings::new(SERVICE_TEST);
+        run_prosa(SERVICE_TEST, None).await;
+    }
+
+    /// Test a ProSA with an injector processor sending transactions to a stub processor through a lock-free queue
+    #[cfg(feature = "lockfree")]
+    #[tokio::test]
+    async fn prosa_lockfree() {
+        use prosa::core::transport::TransportSettings;
+
+        let mut stub_settings = StubSettings::new(vec![String::from("PROSA_LOCKFREE")]);
+        stub_settings.set_transport(TransportSettings::LockFree { capacity: 64 });
+        run_prosa("PROSA_LOCKFREE", Some(stub_settings)).await;
+    }
+
+    #[test]
+    fn prosa_builder_errors() {
+        assert_eq!(
+            Some(BuilderError::MissingSettings),
+            ProsaBuilder::<SimpleStringTvf>::new().build_and_run().err()
+        );
+
+        let stub_settings = StubSettings::new(vec![String::from("PROSA_BUILDER")]);
+        assert_eq!(
+            Some(BuilderError::DuplicateProcName(String::from("STUB_PROC"))),
+            ProsaBuilder::<SimpleStringTvf>::new()
+                .settings(TestSettings::default())
+                .with_proc::<StubProc<SimpleStringTvf>, StubParotAdaptor>(
+                    "STUB_PROC",
+                    stub_settings.clone()
+                )
+                .with_proc::<StubProc<SimpleStringTvf>, StubParotAdaptor>(
+                    "STUB_PROC",
+                    stub_settings
+                )
+                .build_and_run()
+                .err()
+        );
+
+        let builder = || {
+            ProsaBuilder::<SimpleStringTvf>::new()
+                .settings(TestSettings::default())
+                .with_proc::<StubProc<SimpleStringTvf>, StubParotAdaptor>(
+                    "STUB_A",
+                    StubSettings::new(vec![String::from("PROSA_BUILDER_A")]),
+                )
+                .with_proc::<StubProc<SimpleStringTvf>, StubParotAdaptor>(
+                    "STUB_B",
+                    StubSettings::new(vec![String::from("PROSA_BUILDER_B")]),
+                )
+        };
+        assert_eq!(
+            Some(BuilderError::UnknownDependency(
+                String::from("STUB_A"),
+                String::from("STUB_C")
+            )),
+            builder()
+                .depends_on("STUB_A", ["STUB_C"])
+                .build_and_run()
+                .err()
+        );
+        assert_eq!(
+            Some(BuilderError::DependencyCycle(vec![
+                String::from("STUB_A"),
+                String::from("STUB_B")
+            ])),
+            builder()
+                .depends_on("STUB_A", ["STUB_B"])
+                .depends_on("STUB_B", ["STUB_A"])
+                .build_and_run()
+                .err()
+        );
+    }
+
+    /// Processor ids in the order their starter adaptor is created
+    static STARTED_PROCS: Mutex<Vec<u32>> = Mutex::new(Vec::new());
+
+    /// Adaptor that record the start of its processor
+    #[derive(Adaptor)]
+    struct TestStarterAdaptor {}
+
+    impl StubAdaptor<SimpleStringTvf> for TestStarterAdaptor {
+        fn new(proc: &StubProc<SimpleStringTvf>) -> Result<Self, Box<dyn Error>> {
+            STARTED_PROCS.lock().unwrap().push(proc.get_proc_id());
+            Ok(Self {})
+        }
+
+        fn process_request(
+            &mut self,
+            _service_name: &str,
+            request: &SimpleStringTvf,
+        ) -> Result<SimpleStringTvf, ServiceError> {
+            Ok(request.clone())
+        }
+    }
+
+    /// Test that processors with a chain of dependencies start in order, once their dependency is ready
+    #[tokio::test]
+    async fn prosa_startup_order() {
+        let prosa = ProsaBuilder::<SimpleStringTvf>::new()
+            .settings(TestSettings::default())
+            .with_proc::<StubProc<SimpleStringTvf>, TestStarterAdaptor>(
+                "CONNECTOR",
+                StubSettings::new(vec![String::from("PROSA_CONNECTOR")]),
+            )
+            .with_proc::<StubProc<SimpleStringTvf>, TestStarterAdaptor>(
+                "ROUTER",
+                StubSettings::new(vec![String::from("PROSA_ROUTER")]),
+            )
+            .with_proc::<StubProc<SimpleStringTvf>, TestStarterAdaptor>(
+                "BACKEND",
+                StubSettings::new(vec![String::from("PROSA_BACKEND")]),
+            )
+            .depends_on("CONNECTOR", ["ROUTER"])
+            .depends_on("ROUTER", ["BACKEND"])
+            .start()
+            .await
+            .unwrap();
+        assert_eq!(Some(1), prosa.get_proc_id("CONNECTOR"));
+        assert_eq!(Some(3), prosa.get_proc_id("BACKEND"));
+        assert!(prosa
+            .main()
+            .wait_proc_ready(1, Duration::from_secs(5))
+            .await
+            .unwrap());
+        assert_eq!(vec![3, 2, 1], *STARTED_PROCS.lock().unwrap());
+
+        prosa
+            .stop("ProSA startup order test end".into())
+            .await
+            .unwrap();
+        prosa.join().unwrap();
+    }
+
+    /// Adaptor that is unavailable for its first request
+    #[derive(Adaptor)]
+    struct TestRetryAdaptor {
+        msg_count: u32,
+    }
+
+    impl StubAdaptor<SimpleStringTvf> for TestRetryAdaptor {
+        fn new(_proc: &StubProc<SimpleStringTvf>) -> Result<Self, Box<dyn Error>> {
+            Ok(Self { msg_count: 0 })
+        }
+
+        fn process_request(
+            &mut self,
+            service_name: &str,
+            request: &SimpleStringTvf,
+        ) -> Result<SimpleStringTvf, ServiceError> {
+            self.msg_count += 1;
+            if self.msg_count == 1 {
+                Err(ServiceError::Unavailable(service_name.into(), None))
+            } else {
+                Ok(request.clone())
+            }
+        }
+    }
+
+    /// Adaptor that is always unavailable
+    #[derive(Adaptor)]
+    struct TestUnavailableAdaptor {}
+
+    impl StubAdaptor<SimpleStringTvf> for TestUnavailableAdaptor {
+        fn new(_proc: &StubProc<SimpleStringTvf>) -> Result<Self, Box<dyn Error>> {
+            Ok(Self {})
+        }
+
+        fn process_request(
+            &mut self,
+            service_name: &str,
+            _request: &SimpleStringTvf,
+        ) -> Result<SimpleStringTvf, ServiceError> {
+            Err(ServiceError::Unavailable(service_name.into(), None))
+        }
+    }
+
+    #[proc]
+    struct TestCallerProc {}
+
+    #[proc]
+    impl TestCallerProc<SimpleStringTvf> {
+        /// Call the service for every request id with a retry, and collect the responses
+        async fn call(
+            &mut self,
+            service_name: &str,
+            nb_procs: usize,
+            msg_ids: Vec<u64>,
+        ) -> Result<Vec<Result<SimpleStringTvf, ServiceError>>, BusError> {
+            let requests = msg_ids
+                .into_iter()
+                .map(|msg_id| {
+                    let mut request = SimpleStringTvf::default();
+                    request.put_unsigned(1, msg_id);
+                    (msg_id, request)
+                })
+                .collect();
+            self.call_requests(service_name, nb_procs, requests).await
+        }
+
+        /// Call the service for every request with a retry, and collect the responses
+        async fn call_requests(
+            &mut self,
+            service_name: &str,
+            nb_procs: usize,
+            requests: Vec<(u64, SimpleStringTvf)>,
+        ) -> Result<Vec<Result<SimpleStringTvf, ServiceError>>, BusError> {
+            self.proc.add_proc().await?;
+
+            // Wait for all processors of the service
+            while self.service.count_proc_service(service_name) < nb_procs {
+                if let Some(InternalMsg::Service(table)) = self.internal_rx_queue.recv().await {
+                    self.service = table;
+                }
+            }
+
+            let meter = self.proc.meter("prosa_service_call");
+            let mut responses = Vec::with_capacity(requests.len());
+            for (msg_id, request) in requests {
+                responses.push(
+                    ServiceCall::new(self.service.clone(), service_name)
+                        .id(msg_id)
+                        .timeout(Duration::from_secs(1))
+                        .retries(1)
+                        .meter(&meter)
+                        .send(request)
+                        .await,
+                );
+            }
+
+            self.proc.remove_proc().await?;
+            Ok(responses)
+        }
+    }
+
+    /// Get the value of a counter for a service from the scraped prometheus metrics
+    fn scraped_counter(metrics: &str, metric: &str, service_name: &str) -> Option<u64> {
+        metrics
+            .lines()
+            .find(|l| l.starts_with(metric) && l.contains(&format!("service=\"{}\"", service_name)))
+            .and_then(|l| l.rsplit(' ').next())
+            .and_then(|v| v.parse().ok())
+    }
+
+    /// Test service calls retried on the same processor, or on an other processor serving the service
+    #[tokio::test]
+    async fn prosa_service_call_retry() {
+        let prometheus_endpoint = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .to_string();
+        let test_settings = TestSettings::new("PROSA_RETRY", &prometheus_endpoint);
+
+        let (bus, main) = MainProc::<SimpleStringTvf>::create(&test_settings);
+        let main_task = main.run();
+
+        // Stub unavailable for the first request
+        let retry_proc = StubProc::<SimpleStringTvf>::create(1, bus.clone(), test_settings.stub);
+        Proc::<TestRetryAdaptor>::run(retry_proc, String::from("RETRY_PROC"));
+
+        let mut caller = TestCallerProc::<SimpleStringTvf>::create_raw(2, bus.clone());
+        let responses = caller.call("PROSA_RETRY", 1, vec![0]).await.unwrap();
+        assert_eq!(0, responses[0].as_ref().unwrap().get_unsigned(1).unwrap());
+
+        // Two stubs serving the same service, one is always unavailable
+        let failover_settings = StubSettings::new(vec![String::from("PROSA_FAILOVER")]);
+        let unavailable_proc =
+            StubProc::<SimpleStringTvf>::create(3, bus.clone(), failover_settings.clone());
+        Proc::<TestUnavailableAdaptor>::run(unavailable_proc, String::from("UNAVAILABLE_PROC"));
+        let available_proc = StubProc::<SimpleStringTvf>::create(4, bus.clone(), failover_settings);
+        Proc::<StubParotAdaptor>::run(available_proc, String::from("AVAILABLE_PROC"));
+
+        let mut caller = TestCallerProc::<SimpleStringTvf>::create_raw(5, bus.clone());
+        let responses = caller
+            .call("PROSA_FAILOVER", 2, vec![0, 1, 2, 3])
+            .await
+            .unwrap();
+        for (msg_id, response) in responses.into_iter().enumerate() {
+            assert_eq!(
+                msg_id as u64,
+                response.unwrap().get_unsigned(1).unwrap(),
+                "the request should have been failed over"
+            );
+        }
+
+        // Half of the requests are sent first to the unavailable processor
+        let metrics = scrape_metrics(&prometheus_endpoint);
+        assert_eq!(
+            Some(1),
+            scraped_counter(&metrics, "prosa_service_call_retries_total", "PROSA_RETRY"),
+            "{}",
+            metrics
+        );
+        assert_eq!(
+            Some(2),
+            scraped_counter(
+                &metrics,
+                "prosa_service_call_retries_total",
+                "PROSA_FAILOVER"
+            ),
+            "{}",
+            metrics
+        );
+
+        bus.stop("ProSA service call test end".into())
+            .await
+            .unwrap();
+        main_task.join().unwrap();
+    }
+
+    #[derive(Adaptor)]
+    struct TestCollectorAdaptor {
+        requests: prometheus::IntCounter,
+    }
+
+    impl<M> StubAdaptor<M> for TestCollectorAdaptor
+    where
+        M: 'static
+            + std::marker::Send
+            + std::marker::Sync
+            + std::marker::Sized
+            + std::clone::Clone
+            + std::fmt::Debug
+            + prosa_utils::msg::tvf::Tvf
+            + std::default::Default,
+    {
+        fn new(proc: &StubProc<M>) -> Result<Self, Box<dyn Error>> {
+            // Register a custom collector from the processor runtime
+            let requests = prometheus::IntCounter::new(
+                "prosa_test_collector_requests",
+                "Requests counted by a custom collector",
+            )?;
+            proc.get_proc_param()
+                .prometheus_registry()
+                .ok_or("no prometheus registry")?
+                .register(Box::new(requests.clone()))?;
+            requests.inc();
+            Ok(Self { requests })
+        }
+
+        fn process_request(&mut self, _service_name: &str, request: &M) -> Result<M, ServiceError> {
+            self.requests.inc();
+            Ok(request.clone())
+        }
+    }
+
+    /// Test a custom prometheus collector registered by an adaptor, exposed on a protected path
+    #[tokio::test]
+    async fn prosa_prometheus_collector() {
+        let prometheus_endpoint = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .to_string();
+        let mut test_settings = TestSettings::new("PROSA_COLLECTOR", &prometheus_endpoint);
+        test_settings.observability = serde_yaml::from_str(&format!(
+            "metrics:\n  prometheus:\n    endpoint: {}\n    path: /prosa/metrics\n    basic_auth:\n      username: prosa\n      password: secret",
+            prometheus_endpoint
+        ))
+        .unwrap();
+
+        let (bus, main) = MainProc::<SimpleStringTvf>::create(&test_settings);
+        let main_task = main.run();
+        let stub_proc = StubProc::<SimpleStringTvf>::create(1, bus.clone(), test_settings.stub);
+        Proc::<TestCollectorAdaptor>::run(stub_proc, String::from("COLLECTOR_PROC"));
+
+        let authorization = "Basic cHJvc2E6c2VjcmV0";
+        let start = time::Instant::now();
+        let metrics = loop {
+            let metrics = http_get(&prometheus_endpoint, "/prosa/metrics", Some(authorization));
+            if metrics.contains("prosa_test_collector_requests 1") || start.elapsed() > WAIT_TIME {
+                break metrics;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        };
+        assert!(metrics.starts_with("HTTP/1.1 200"), "{}", metrics);
+        assert!(
+            metrics
+                .lines()
+                .any(|l| l == "prosa_test_collector_requests 1"),
+            "{}",
+            metrics
+        );
+
+        // The metrics are only served on the configured path, to authenticated scrapers
+        let response = http_get(&prometheus_endpoint, "/prosa/metrics", None);
+        assert!(response.starts_with("HTTP/1.1 401"), "{}", response);
+        assert!(response.contains("WWW-Authenticate: Basic"), "{}", response);
+        let response = http_get(
+            &prometheus_endpoint,
+            "/prosa/metrics",
+            Some("Basic cHJvc2E6"),
+        );
+        assert!(response.starts_with("HTTP/1.1 401"), "{}", response);
+        let response = http_get(&prometheus_endpoint, "/metrics", Some(authorization));
+        assert!(response.starts_with("HTTP/1.1 404"), "{}", response);
+
+        bus.stop("ProSA collector test end".into()).await.unwrap();
+        main_task.join().unwrap();
+    }
+
+    /// Test the runtime metrics exported for a multi-thread processor runtime
+    #[cfg(feature = "runtime-metrics")]
+    #[tokio::test]
+    async fn prosa_runtime_metrics() {
+        let prometheus_endpoint = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .to_string();
+        let mut test_settings = TestSettings::new("PROSA_RUNTIME_METRICS", &prometheus_endpoint);
+        test_settings.stub = serde_yaml::from_str(
+            "service_names: [PROSA_RUNTIME_METRICS]\nthreads: 2\nruntime_metrics_interval:\n  secs: 0\n  nanos: 50000000",
+        )
+        .unwrap();
 
-        // Create bus and main processor
         let (bus, main) = MainProc::<SimpleStringTvf>::create(&test_settings);
+        let main_task = main.run();
+        let stub_proc = StubProc::<SimpleStringTvf>::create(1, bus.clone(), test_settings.stub);
+        Proc::<StubParotAdaptor>::run(stub_proc, String::from("RUNTIME_PROC"));
+
+        let gauges = [
+            "prosa_proc_runtime_workers",
+            "prosa_proc_runtime_alive_tasks",
+            "prosa_proc_runtime_injection_queue_depth",
+        ];
+        let gauge_line = |metrics: &str, gauge: &str| {
+            metrics
+                .lines()
+                .find(|l| l.starts_with(gauge) && l.contains("proc=\"RUNTIME_PROC\""))
+                .map(str::to_string)
+        };
+        let start = time::Instant::now();
+        let metrics = loop {
+            let metrics = scrape_metrics(&prometheus_endpoint);
+            if gauges.iter().all(|g| gauge_line(&metrics, g).is_some())
+                || start.elapsed() > WAIT_TIME
+            {
+                break metrics;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        };
+        for gauge in gauges {
+            assert!(gauge_line(&metrics, gauge).is_some(), "{}", metrics);
+        }
+        assert!(
+            gauge_line(&metrics, "prosa_proc_runtime_workers").is_some_and(|l| l.ends_with(" 2")),
+            "{}",
+            metrics
+        );
+
+        bus.stop("ProSA runtime metrics test end".into())
+            .await
+            .unwrap();
+        main_task.join().unwrap();
+    }
 
-        // Launch the main task
+    /// Adaptor that pads the response with the size requested in the field 2 of the request
+    #[derive(Adaptor)]
+    struct TestPaddingAdaptor {}
+
+    impl StubAdaptor<SimpleStringTvf> for TestPaddingAdaptor {
+        fn new(_proc: &StubProc<SimpleStringTvf>) -> Result<Self, Box<dyn Error>> {
+            Ok(Self {})
+        }
+
+        fn process_request(
+            &mut self,
+            _service_name: &str,
+            request: &SimpleStringTvf,
+        ) -> Result<SimpleStringTvf, ServiceError> {
+            let mut response = request.clone();
+            let padding = request.get_unsigned(2)? as usize;
+            response.put_string(3, "x".repeat(padding));
+            Ok(response)
+        }
+    }
+
+    /// Test the rejection of requests and responses that exceed the maximum message size
+    #[tokio::test]
+    async fn prosa_max_message_size() {
+        const MAX_MESSAGE_SIZE: usize = 128;
+        let prometheus_endpoint = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .to_string();
+        let mut test_settings = TestSettings::new("PROSA_SIZE", &prometheus_endpoint);
+        test_settings.max_message_size = Some(MAX_MESSAGE_SIZE);
+
+        let (bus, main) = MainProc::<SimpleStringTvf>::create(&test_settings);
         let main_task = main.run();
 
-        // Launch a stub processor
         let stub_proc = StubProc::<SimpleStringTvf>::create(1, bus.clone(), test_settings.stub);
-        Proc::<TestStubAdaptor>::run(stub_proc, String::from("STUB_PROC"));
+        Proc::<TestPaddingAdaptor>::run(stub_proc, String::from("PADDING_PROC"));
 
-        // Launch an inj processor
-        let inj_proc = InjProc::<SimpleStringTvf>::create(2, bus.clone(), test_settings.inj);
-        Proc::<InjDummyAdaptor>::run(inj_proc, String::from("INJ_PROC"));
+        let build_request = |msg_id: u64, request_padding: usize, response_padding: u64| {
+            let mut request = SimpleStringTvf::default();
+            request.put_unsigned(1, msg_id);
+            request.put_unsigned(2, response_padding);
+            request.put_string(4, "x".repeat(request_padding));
+            (msg_id, request)
+        };
+        let oversized_request = build_request(1, MAX_MESSAGE_SIZE, 0);
+        let oversized_size = oversized_request.1.size_hint();
+        let mut caller = TestCallerProc::<SimpleStringTvf>::create_raw(2, bus.clone());
+        let mut responses = caller
+            .call_requests(
+                "PROSA_SIZE",
+                1,
+                vec![
+                    build_request(0, 0, 0),
+                    oversized_request,
+                    build_request(2, 0, MAX_MESSAGE_SIZE as u64),
+                ],
+            )
+            .await
+            .unwrap()
+            .into_iter();
 
-        // Wait before stopping prosa
-        std::thread::sleep(WAIT_TIME);
-        bus.stop("ProSA unit test end".into()).await.unwrap();
+        // Only the request and the response within the limit are exchanged
+        assert_eq!(
+            0,
+            responses.next().unwrap().unwrap().get_unsigned(1).unwrap()
+        );
+        assert_eq!(
+            Some(Err(ServiceError::MessageTooLarge(
+                oversized_size,
+                MAX_MESSAGE_SIZE
+            ))),
+            responses.next()
+        );
+        assert!(matches!(
+            responses.next(),
+            Some(Err(ServiceError::MessageTooLarge(size, MAX_MESSAGE_SIZE))) if size > MAX_MESSAGE_SIZE
+        ));
 
-        // Wait on main task to end
+        // One request and one response are rejected
+        let metrics = scrape_metrics(&prometheus_endpoint);
+        for msg_type in ["request", "response"] {
+            assert_eq!(
+                Some(1),
+                metrics
+                    .lines()
+                    .find(|l| l.starts_with("prosa_bus_message_too_large_total")
+                        && l.contains("service=\"PROSA_SIZE\"")
+                        && l.contains(&format!("type=\"{}\"", msg_type)))
+                    .and_then(|l| l.rsplit(' ').next())
+                    .and_then(|v| v.parse::<u64>().ok()),
+                "{}",
+                metrics
+            );
+        }
+        assert!(
+            metrics.contains("prosa_bus_message_size_bytes_count"),
+            "{}",
+            metrics
+        );
+
+        bus.stop("ProSA max message size test end".into())
+            .await
+            .unwrap();
         main_task.join().unwrap();
+    }
 
-        // Check exchanges messages
-        let nb_trans = COUNTER.load(Ordering::Relaxed) as u64;
-        let estimated_trans = WAIT_TIME.as_secs() * 5;
-        assert!(nb_trans > (estimated_trans - 2) && nb_trans < (estimated_trans + 2));
-        // Should have a coherent number of transaction with the regulator
+    /// Test the TPS of the transactions window with an injector sending a known rate of transactions to a stub processor
+    #[tokio::test]
+    async fn prosa_tps() {
+        const INJ_SPEED: f64 = 40.0;
+        let mut inj_settings = InjSettings::new(String::from("PROSA_TPS"));
+        inj_settings.set_max_speed(INJ_SPEED);
+        let test_settings = TestSettings {
+            tps: Some(TpsSettings::new(Duration::from_millis(250), 8)),
+            ..Default::default()
+        };
+
+        let prosa = ProsaBuilder::<SimpleStringTvf>::new()
+            .settings(test_settings)
+            .with_proc::<StubProc<SimpleStringTvf>, StubParotAdaptor>(
+                "STUB_PROC",
+                StubSettings::new(vec![String::from("PROSA_TPS")]),
+            )
+            .with_proc::<InjProc<SimpleStringTvf>, InjDummyAdaptor>("INJ_PROC", inj_settings)
+            .build_and_run()
+            .unwrap();
+
+        // Let the injector reach its speed, and fill the window
+        tokio::time::sleep(Duration::from_secs(4)).await;
+        let tps = prosa.main().tps().unwrap();
+        assert!(
+            (tps.window_tps - INJ_SPEED).abs() < INJ_SPEED * 0.2,
+            "window TPS {:?}",
+            tps
+        );
+        assert!(tps.transactions > 0);
+        assert!(
+            tps.tps_1m > 0.0 && tps.tps_1m < INJ_SPEED * 1.2,
+            "{:?}",
+            tps
+        );
+        assert!(tps.latency_p99 >= tps.latency_p50);
+        assert_eq!(
+            Some(tps.transactions > 0),
+            prosa
+                .main()
+                .topology()
+                .await
+                .unwrap()
+                .tps
+                .map(|t| t.transactions > 0)
+        );
+
+        prosa.stop("ProSA TPS test end".into()).await.unwrap();
+        prosa.join().unwrap();
     }
 }
@@ -13,3 +13,9 @@ pub mod proc;
 #[doc = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/doc_assets/adaptor.svg"))]
 /// </svg>
 pub mod adaptor;
+
+/// Definition of the stub fixtures use to record/replay traffic
+pub mod fixture;
+
+/// Definition of the stub sessions use to keep a state between requests
+pub mod session;
@@ -1,12 +1,26 @@
-use std::error::Error;
+use std::{
+    error::Error,
+    fs::{File, OpenOptions},
+    io::Write as _,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
-use crate::core::{adaptor::Adaptor, proc::ProcConfig};
+use crate::core::{
+    adaptor::{Adaptor, AdaptorConfigReload, AdaptorError, PipelineAdaptor, TransformRegistry},
+    dead_letter,
+    proc::{ProcConfig, ProcSettings as _},
+    service::ServiceError,
+};
 
 use super::proc::StubProc;
+use super::session::SessionStore;
 
 extern crate self as prosa;
 
-use opentelemetry::metrics::Meter;
+use opentelemetry::{metrics::Meter, KeyValue};
+use prosa_utils::dict::{serialize::DictSerializer, DictError, Dictionary};
+use serde::{Deserialize, Serialize};
 
 /// Adaptator trait for the stub processor
 ///
@@ -15,6 +29,7 @@ use opentelemetry::metrics::Meter;
 /// use prosa::stub::proc::StubProc;
 /// use prosa::core::adaptor::Adaptor;
 /// use prosa::stub::adaptor::StubAdaptor;
+/// use prosa::core::service::ServiceError;
 ///
 /// #[derive(Adaptor)]
 /// pub struct MyStubAdaptor { }
@@ -33,10 +48,14 @@ use opentelemetry::metrics::Meter;
 ///     fn new(_proc: &StubProc<M>) -> Result<Self, Box<dyn std::error::Error>> {
 ///         Ok(Self {})
 ///     }
-///     fn process_request(&mut self, service_name: &str, request: &M) -> M {
+///     fn process_request(&mut self, service_name: &str, request: &M) -> Result<M, ServiceError> {
+///         if request.is_empty() {
+///             return Err(ServiceError::ProtocolError { code: 1, reason: String::from("empty request") });
+///         }
+///
 ///         let mut msg = request.clone();
 ///         msg.put_string(1, format!("test service {}", service_name));
-///         msg
+///         Ok(msg)
 ///     }
 /// }
 /// ```
@@ -57,7 +76,40 @@ where
     where
         Self: Sized;
     /// Method to process incomming requests
-    fn process_request(&mut self, service_name: &str, request: &M) -> M;
+    /// If a service error is returned, it'll be encoded in the response (see [`ServiceError::encode`]) and sent back as an error to the requester
+    fn process_request(&mut self, service_name: &str, request: &M) -> Result<M, ServiceError>;
+    /// Method to process incomming requests of stateful protocols, with the session store of the stub processor (configured with the `session` stub setting)
+    /// Use it to keep a state between the requests of a session (a token given by a first request, and checked by the next ones for example)
+    /// By default the state is not used and the request is given to [`StubAdaptor::process_request`]
+    fn process_request_with_state(
+        &mut self,
+        service_name: &str,
+        request: &M,
+        _state: &SessionStore<M>,
+    ) -> Result<M, ServiceError> {
+        self.process_request(service_name, request)
+    }
+    /// Method to process a batch of incomming requests, when the stub processor consumes batches (`batch` stub setting).
+    /// Override it to handle the requests at once (vectorized processing); it must return one result per request, in the same order.
+    /// By default every request is given to [`StubAdaptor::process_request_with_state`]
+    fn process_batch(
+        &mut self,
+        requests: &[(&str, &M)],
+        state: &SessionStore<M>,
+    ) -> Vec<Result<M, ServiceError>> {
+        requests
+            .iter()
+            .map(|(service_name, request)| {
+                self.process_request_with_state(service_name, request, state)
+            })
+            .collect()
+    }
+    /// Method to give custom attributes to the span of the transaction processing the request (e.g. a merchant or a card scheme)
+    /// These attributes are built for every request while the traces are enabled, so keep them cheap; only the first [`MAX_SPAN_ATTRIBUTES`](crate::core::adaptor::MAX_SPAN_ATTRIBUTES) are kept
+    /// By default no attribute is added
+    fn span_attributes(&self, _request: &M) -> Vec<KeyValue> {
+        Vec::new()
+    }
 }
 
 /// Parot adaptor for the stub processor. Use to respond to a request with the same message
@@ -84,7 +136,149 @@ where
         })
     }
 
-    fn process_request(&mut self, _service_name: &str, request: &M) -> M {
-        request.clone()
+    fn process_request(&mut self, _service_name: &str, request: &M) -> Result<M, ServiceError> {
+        Ok(request.clone())
+    }
+}
+
+/// Configuration of the [`DeadLetterFileAdaptor`] (adaptor configuration of its stub)
+///
+/// ```yaml
+/// path: "/var/log/prosa/dead_letters.jsonl"
+/// dictionary: "/etc/prosa/message.csv"
+/// ```
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct DeadLetterFileConfig {
+    /// Path of the JSON-lines file where the dead letters are appended
+    pub path: PathBuf,
+    /// Dictionary file (CSV, or XML with the `.xml` extension) of the original payloads.
+    /// Without it, only the dead letter metadata are written
+    #[serde(default)]
+    pub dictionary: Option<PathBuf>,
+}
+
+impl DeadLetterFileConfig {
+    /// Method to load the dictionary of the original payloads (if any)
+    fn load_dictionary(&self) -> Result<Option<Arc<Dictionary>>, DictError> {
+        self.dictionary
+            .as_deref()
+            .map(|path| {
+                if path.extension().is_some_and(|ext| ext == "xml") {
+                    Dictionary::from_xml_file(path)
+                } else {
+                    Dictionary::from_csv_file(path)
+                }
+            })
+            .transpose()
+    }
+}
+
+/// Dead letter adaptor for the stub processor, to serve the dead letter service (see [`dead_letter`](crate::core::dead_letter))
+///
+/// Every [`DeadLetter`](crate::core::dead_letter::DeadLetter) is appended as a JSON line to the configured file (see [`DeadLetterFileConfig`]), written with the [`DictSerializer`] of the dead letter dictionary ([`dead_letter::dictionary`]).
+/// Its requests are answered with an empty message once they are written.
+#[derive(Adaptor)]
+#[adaptor(reload = DeadLetterFileConfig)]
+pub struct DeadLetterFileAdaptor {
+    dictionary: Dictionary,
+    file: File,
+}
+
+impl DeadLetterFileAdaptor {
+    /// Method to open the dead letter file of a configuration
+    fn open(config: &DeadLetterFileConfig) -> Result<(Dictionary, File), AdaptorError> {
+        let dictionary = config.load_dictionary().map_err(|e| {
+            AdaptorError::InvalidConfig(format!("can't load the payload dictionary: {}", e))
+        })?;
+        let file = Self::open_file(&config.path).map_err(|e| {
+            AdaptorError::InvalidConfig(format!(
+                "can't open the dead letter file {}: {}",
+                config.path.display(),
+                e
+            ))
+        })?;
+        Ok((dead_letter::dictionary(dictionary), file))
+    }
+
+    fn open_file(path: &Path) -> std::io::Result<File> {
+        OpenOptions::new().create(true).append(true).open(path)
+    }
+}
+
+impl AdaptorConfigReload<DeadLetterFileConfig> for DeadLetterFileAdaptor {
+    fn reload(&mut self, config: DeadLetterFileConfig) -> Result<(), AdaptorError> {
+        (self.dictionary, self.file) = Self::open(&config)?;
+        Ok(())
+    }
+}
+
+impl<M> StubAdaptor<M> for DeadLetterFileAdaptor
+where
+    M: 'static
+        + std::marker::Send
+        + std::marker::Sync
+        + std::marker::Sized
+        + std::clone::Clone
+        + std::fmt::Debug
+        + prosa_utils::msg::tvf::Tvf
+        + std::default::Default,
+{
+    fn new(proc: &StubProc<M>) -> Result<Self, Box<dyn Error>> {
+        let config: DeadLetterFileConfig = proc.settings.get_adaptor_config()?;
+        let (dictionary, file) = Self::open(&config)?;
+        Ok(Self { dictionary, file })
+    }
+
+    fn process_request(&mut self, _service_name: &str, request: &M) -> Result<M, ServiceError> {
+        let mut line = serde_json::to_vec(&DictSerializer::new(&self.dictionary, request))
+            .map_err(|e| {
+                ServiceError::Internal(format!("can't serialize the dead letter: {}", e))
+            })?;
+        line.push(b'\n');
+        self.file
+            .write_all(&line)
+            .map_err(|e| ServiceError::Internal(format!("can't write the dead letter: {}", e)))?;
+        Ok(M::default())
+    }
+}
+
+impl<A, M> StubAdaptor<M> for PipelineAdaptor<A, M>
+where
+    A: StubAdaptor<M>,
+    M: 'static
+        + std::marker::Send
+        + std::marker::Sync
+        + std::marker::Sized
+        + std::clone::Clone
+        + std::fmt::Debug
+        + prosa_utils::msg::tvf::Tvf
+        + std::default::Default,
+{
+    fn new(proc: &StubProc<M>) -> Result<Self, Box<dyn Error>> {
+        let pipeline = TransformRegistry::default().build(proc.settings.get_pipeline())?;
+        Ok(PipelineAdaptor::with_pipeline(A::new(proc)?, pipeline))
+    }
+
+    fn process_request(&mut self, service_name: &str, request: &M) -> Result<M, ServiceError> {
+        let request = self.pipeline.on_request(request.clone())?;
+        let response = self.adaptor.process_request(service_name, &request)?;
+        self.pipeline.on_response(response)
+    }
+
+    fn process_request_with_state(
+        &mut self,
+        service_name: &str,
+        request: &M,
+        state: &SessionStore<M>,
+    ) -> Result<M, ServiceError> {
+        let request = self.pipeline.on_request(request.clone())?;
+        let response = self
+            .adaptor
+            .process_request_with_state(service_name, &request, state)?;
+        self.pipeline.on_response(response)
+    }
+
+    fn span_attributes(&self, request: &M) -> Vec<KeyValue> {
+        self.adaptor.span_attributes(request)
     }
 }
@@ -0,0 +1,309 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Debug,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use bytes::Bytes;
+use chrono::{NaiveDate, NaiveDateTime};
+use prosa_utils::msg::tvf::{Tvf, TvfType};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Error define for stub fixtures
+#[derive(Debug, Error)]
+pub enum FixtureError {
+    /// Error on a fixture file
+    #[error("Fixture IO error: {0}")]
+    Io(#[from] io::Error),
+    /// Error on the fixture serialization
+    #[error("Fixture serialization error: {0}")]
+    Serialization(#[from] serde_json::Error),
+}
+
+/// Value of a recorded TVF field, with its type
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FixtureValue {
+    /// Unsigned field
+    Unsigned(u64),
+    /// Signed field
+    Signed(i64),
+    /// Byte field
+    Byte(u8),
+    /// Float field
+    Float(f64),
+    /// String field
+    String(String),
+    /// Bytes field
+    Bytes(Vec<u8>),
+    /// Sub buffer field
+    Buffer(TvfFixture),
+    /// Date field
+    Date(NaiveDate),
+    /// Datetime field
+    DateTime(NaiveDateTime),
+}
+
+/// Raw representation of a TVF that can be serialized (JSON) into a fixture file
+///
+/// ```
+/// use prosa_utils::msg::tvf::Tvf;
+/// use prosa_utils::msg::simple_string_tvf::SimpleStringTvf;
+/// use prosa::stub::fixture::TvfFixture;
+///
+/// let mut tvf = SimpleStringTvf::default();
+/// tvf.put_string(1, "ProSA");
+/// tvf.put_unsigned(2, 42);
+///
+/// let fixture = TvfFixture::from_tvf(&tvf);
+/// assert_eq!(tvf, fixture.to_tvf::<SimpleStringTvf>());
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize)]
+pub struct TvfFixture(BTreeMap<usize, FixtureValue>);
+
+impl TvfFixture {
+    /// Method to create a fixture from a TVF, keeping the type of its fields (see [`Tvf::get_type`])
+    pub fn from_tvf<M>(tvf: &M) -> TvfFixture
+    where
+        M: Tvf + Default + Debug + Clone,
+    {
+        let mut fields = BTreeMap::new();
+        for key in tvf.keys() {
+            let value = match tvf.get_type(key) {
+                Ok(TvfType::Buffer) => tvf
+                    .get_buffer(key)
+                    .map(|buffer| FixtureValue::Buffer(TvfFixture::from_tvf(&*buffer))),
+                Ok(TvfType::Unsigned) => tvf.get_unsigned(key).map(FixtureValue::Unsigned),
+                Ok(TvfType::Signed) => tvf.get_signed(key).map(FixtureValue::Signed),
+                Ok(TvfType::Byte) => tvf.get_byte(key).map(FixtureValue::Byte),
+                Ok(TvfType::Float) => tvf.get_float(key).map(FixtureValue::Float),
+                Ok(TvfType::String) => tvf
+                    .get_string(key)
+                    .map(|value| FixtureValue::String(value.into_owned())),
+                Ok(TvfType::Bytes) => tvf
+                    .get_bytes(key)
+                    .map(|bytes| FixtureValue::Bytes(bytes.to_vec())),
+                Ok(TvfType::Date) => tvf.get_date(key).map(FixtureValue::Date),
+                Ok(TvfType::DateTime) => tvf.get_datetime(key).map(FixtureValue::DateTime),
+                Err(e) => Err(e),
+            };
+            if let Ok(value) = value {
+                fields.insert(key, value);
+            }
+        }
+
+        TvfFixture(fields)
+    }
+
+    /// Method to build a TVF from the fixture
+    pub fn to_tvf<M>(&self) -> M
+    where
+        M: Tvf + Default + Debug + Clone,
+    {
+        let mut tvf = M::default();
+        for (key, value) in &self.0 {
+            match value {
+                FixtureValue::Unsigned(value) => tvf.put_unsigned(*key, *value),
+                FixtureValue::Signed(value) => tvf.put_signed(*key, *value),
+                FixtureValue::Byte(value) => tvf.put_byte(*key, *value),
+                FixtureValue::Float(value) => tvf.put_float(*key, *value),
+                FixtureValue::String(value) => tvf.put_string(*key, value.clone()),
+                FixtureValue::Bytes(bytes) => tvf.put_bytes(*key, Bytes::from(bytes.clone())),
+                FixtureValue::Buffer(buffer) => tvf.put_buffer(*key, buffer.to_tvf()),
+                FixtureValue::Date(date) => tvf.put_date(*key, *date),
+                FixtureValue::DateTime(datetime) => tvf.put_datetime(*key, *datetime),
+            }
+        }
+
+        tvf
+    }
+
+    /// Getter of a recorded field
+    pub fn get(&self, key: usize) -> Option<&FixtureValue> {
+        self.0.get(&key)
+    }
+}
+
+/// Recorded request/response pair of a service
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct StubFixture {
+    /// Name of the recorded service
+    pub service: String,
+    /// Request received by the service
+    pub request: TvfFixture,
+    /// Response returned by the service
+    pub response: TvfFixture,
+}
+
+/// Strategy to match an incomming request with a recorded one
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub enum MatchStrategy {
+    /// The whole request must be identical
+    #[default]
+    Full,
+    /// Only the listed tags must be identical
+    Tags(Vec<usize>),
+}
+
+impl MatchStrategy {
+    /// Method to know if a request match a recorded one
+    pub fn is_matching(&self, request: &TvfFixture, recorded: &TvfFixture) -> bool {
+        match self {
+            MatchStrategy::Full => request == recorded,
+            MatchStrategy::Tags(tags) => tags.iter().all(|t| request.get(*t) == recorded.get(*t)),
+        }
+    }
+}
+
+/// Fallback when a replayed request doesn't match any recorded one
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum ReplayFallback {
+    /// Return a service error
+    #[default]
+    Error,
+    /// Respond with the stub adaptor
+    Adaptor,
+}
+
+/// Recorder that write request/response pairs into a fixture directory
+#[derive(Debug)]
+pub struct FixtureRecorder {
+    output_dir: PathBuf,
+    count: usize,
+}
+
+impl FixtureRecorder {
+    /// Method to create a recorder in the output directory (created if missing)
+    pub fn new<P>(output_dir: P) -> Result<FixtureRecorder, FixtureError>
+    where
+        P: AsRef<Path>,
+    {
+        fs::create_dir_all(&output_dir)?;
+        let count = fs::read_dir(&output_dir)?
+            .filter(|e| {
+                e.as_ref()
+                    .is_ok_and(|e| e.path().extension().is_some_and(|ext| ext == "json"))
+            })
+            .count();
+        Ok(FixtureRecorder {
+            output_dir: output_dir.as_ref().to_path_buf(),
+            count,
+        })
+    }
+
+    /// Method to get a file name from a service name that can't escape the output directory
+    fn file_stem(service: &str) -> String {
+        service
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-') {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect::<String>()
+            .replace("..", "_")
+    }
+
+    /// Method to record a request/response pair. Return the path of the written fixture
+    ///
+    /// The service name is sanitized to build the fixture file name, so the fixture is always written in the output directory.
+    /// Every character outside `[A-Za-z0-9._-]` and every `..` sequence are replaced by `_`.
+    ///
+    /// ```
+    /// use prosa_utils::msg::tvf::Tvf;
+    /// use prosa_utils::msg::simple_string_tvf::SimpleStringTvf;
+    /// use prosa::stub::fixture::FixtureRecorder;
+    ///
+    /// let output_dir = std::env::temp_dir().join("prosa_fixture_recorder_doc");
+    /// let _ = std::fs::remove_dir_all(&output_dir);
+    /// let mut recorder = FixtureRecorder::new(&output_dir).unwrap();
+    ///
+    /// let mut request = SimpleStringTvf::default();
+    /// request.put_string(1, "ProSA");
+    /// let path = recorder.record("PAY./../../x", &request, &request).unwrap();
+    /// assert_eq!(output_dir.join("PAY._____x_000001.json"), path);
+    /// # std::fs::remove_dir_all(&output_dir).unwrap();
+    /// ```
+    pub fn record<M>(
+        &mut self,
+        service: &str,
+        request: &M,
+        response: &M,
+    ) -> Result<PathBuf, FixtureError>
+    where
+        M: Tvf + Default + Debug + Clone,
+    {
+        let fixture = StubFixture {
+            service: service.to_string(),
+            request: TvfFixture::from_tvf(request),
+            response: TvfFixture::from_tvf(response),
+        };
+
+        let file_stem = Self::file_stem(service);
+        let path = loop {
+            self.count += 1;
+            let path = self
+                .output_dir
+                .join(format!("{}_{:06}.json", file_stem, self.count));
+            if !path.exists() {
+                break path;
+            }
+        };
+
+        fs::write(&path, serde_json::to_vec_pretty(&fixture)?)?;
+        Ok(path)
+    }
+}
+
+/// Store of recorded fixtures use to replay responses
+#[derive(Debug, Default)]
+pub struct FixtureStore {
+    fixtures: Vec<StubFixture>,
+}
+
+impl FixtureStore {
+    /// Method to load all JSON fixtures of a directory
+    pub fn load<P>(input_dir: P) -> Result<FixtureStore, FixtureError>
+    where
+        P: AsRef<Path>,
+    {
+        let mut paths: Vec<PathBuf> = fs::read_dir(input_dir)?
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| p.extension().is_some_and(|ext| ext == "json"))
+            .collect();
+        paths.sort();
+
+        let mut fixtures = Vec::with_capacity(paths.len());
+        for path in paths {
+            fixtures.push(serde_json::from_slice(&fs::read(path)?)?);
+        }
+
+        Ok(FixtureStore { fixtures })
+    }
+
+    /// Returns the number of loaded fixtures
+    pub fn len(&self) -> usize {
+        self.fixtures.len()
+    }
+
+    /// Returns true if there is no loaded fixture
+    pub fn is_empty(&self) -> bool {
+        self.fixtures.is_empty()
+    }
+
+    /// Method to find the recorded response of a request
+    pub fn find<M>(&self, service: &str, request: &M, match_strategy: &MatchStrategy) -> Option<M>
+    where
+        M: Tvf + Default + Debug + Clone,
+    {
+        let request = TvfFixture::from_tvf(request);
+        self.fixtures
+            .iter()
+            .find(|f| f.service == service && match_strategy.is_matching(&request, &f.request))
+            .map(|f| f.response.to_tvf())
+    }
+}
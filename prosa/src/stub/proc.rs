@@ -1,34 +1,161 @@
+use std::{path::PathBuf, time::Duration};
+
+use opentelemetry::KeyValue;
 use prosa_macros::proc_settings;
 use serde::{Deserialize, Serialize};
-use tracing::debug;
+use tracing::{debug, info_span, warn};
 
-use crate::core::adaptor::Adaptor;
-use crate::core::msg::{InternalMsg, Msg};
-use crate::core::proc::{proc, Proc, ProcBusParam};
+use crate::core::adaptor::{set_span_attributes, Adaptor, TransformSettings};
+use crate::core::dead_letter::DeadLetter;
+use crate::core::error::SendError;
+use crate::core::msg::{drop_unreturned, InternalMsg, Msg, RequestMsg};
+use crate::core::proc::{proc, Proc, ProcBusParam, ProcError as _};
+use crate::core::service::{DedupCache, DedupOutcome, DedupSettings, ServiceError, ServiceName};
+use crate::core::transport::TransportSettings;
+use crate::core::watchdog::Watchdog;
+use crate::event::pending::PendingMsgs;
+use crate::event::stats::{stats_service_name, ProcStats};
 
 use super::adaptor::StubAdaptor;
+use super::fixture::{FixtureRecorder, FixtureStore, MatchStrategy, ReplayFallback};
+use super::session::{SessionSettings, SessionStore};
 
 extern crate self as prosa;
 
+/// Mode of the stub processor
+#[derive(Default, Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[serde(tag = "type")]
+pub enum StubMode {
+    /// Respond with the stub adaptor
+    #[default]
+    Respond,
+    /// Forward requests to a target service and record request/response pairs into fixture files
+    Record {
+        /// Service where requests are forwarded
+        target_service: String,
+        /// Directory where fixtures are written
+        output_dir: PathBuf,
+        /// Timeout of the target service
+        #[serde(default = "StubMode::default_record_timeout")]
+        timeout: Duration,
+    },
+    /// Respond with previously recorded fixtures
+    Replay {
+        /// Directory where fixtures are read
+        input_dir: PathBuf,
+        /// Strategy to match requests with recorded ones
+        #[serde(default)]
+        match_strategy: MatchStrategy,
+        /// Behavior when no recorded request match
+        #[serde(default)]
+        fallback: ReplayFallback,
+    },
+}
+
+impl StubMode {
+    fn default_record_timeout() -> Duration {
+        Duration::new(10, 0)
+    }
+
+    /// Create a record mode that forward requests to a target service
+    pub fn record(target_service: String, output_dir: PathBuf) -> StubMode {
+        StubMode::Record {
+            target_service,
+            output_dir,
+            timeout: Self::default_record_timeout(),
+        }
+    }
+
+    /// Create a replay mode that respond with recorded fixtures
+    pub fn replay(input_dir: PathBuf, match_strategy: MatchStrategy) -> StubMode {
+        StubMode::Replay {
+            input_dir,
+            match_strategy,
+            fallback: ReplayFallback::default(),
+        }
+    }
+}
+
 /// Stub settings to list all services tu stub
 #[proc_settings]
 #[derive(Default, Debug, Deserialize, Serialize, Clone)]
 pub struct StubSettings {
-    service_names: Vec<String>,
+    /// Services (or wildcards like `PAY.*`) served by the stub
+    service_names: Vec<ServiceName>,
+    /// Mode of the stub (respond, record or replay)
+    #[serde(default)]
+    mode: StubMode,
+    /// Transforms applied on the requests and the responses by a [`crate::core::adaptor::PipelineAdaptor`]
+    #[serde(default)]
+    pipeline: Vec<TransformSettings>,
+    /// Duplicate detection of the requests. If set, repeated requests within the window get the cached response
+    #[serde(default)]
+    dedup: Option<DedupSettings>,
+    /// Sessions given to the adaptor to keep a state between requests
+    #[serde(default)]
+    session: Option<SessionSettings>,
+    /// Consume the batches of requests as a whole with [`StubAdaptor::process_batch`] (only in respond mode without duplicate detection)
+    #[serde(default)]
+    batch: bool,
 }
 
 impl StubSettings {
     /// Create a new Stub settings
     pub fn new(service_names: Vec<String>) -> StubSettings {
         StubSettings {
-            service_names,
+            service_names: service_names.into_iter().map(ServiceName::from).collect(),
             ..Default::default()
         }
     }
 
     /// Method to add service name
     pub fn add_service_name(&mut self, service_name: String) {
-        self.service_names.push(service_name);
+        self.service_names.push(service_name.into());
+    }
+
+    /// Setter of the stub mode
+    pub fn set_mode(&mut self, mode: StubMode) {
+        self.mode = mode;
+    }
+
+    /// Setter of the transforms of the adaptor pipeline
+    pub fn set_pipeline(&mut self, pipeline: Vec<TransformSettings>) {
+        self.pipeline = pipeline;
+    }
+
+    /// Getter of the duplicate detection settings
+    pub fn get_dedup(&self) -> Option<&DedupSettings> {
+        self.dedup.as_ref()
+    }
+
+    /// Setter of the duplicate detection settings
+    pub fn set_dedup(&mut self, dedup: DedupSettings) {
+        self.dedup = Some(dedup);
+    }
+
+    /// Getter of the session settings
+    pub fn get_session(&self) -> Option<&SessionSettings> {
+        self.session.as_ref()
+    }
+
+    /// Setter of the session settings
+    pub fn set_session(&mut self, session: SessionSettings) {
+        self.session = Some(session);
+    }
+
+    /// Getter of the batch consumption flag
+    pub fn is_batch(&self) -> bool {
+        self.batch
+    }
+
+    /// Setter of the batch consumption flag
+    pub fn set_batch(&mut self, batch: bool) {
+        self.batch = batch;
+    }
+
+    /// Setter of the transport of the stub data messages
+    pub fn set_transport(&mut self, transport: TransportSettings) {
+        self.transport = Some(transport);
     }
 }
 
@@ -67,6 +194,135 @@ impl StubSettings {
 #[proc(settings = prosa::stub::proc::StubSettings)]
 pub struct StubProc {}
 
+#[proc]
+impl StubProc {
+    /// Method to return a service error to the requester with the error encoded in the returned message
+    async fn return_service_error(
+        msg: RequestMsg<M>,
+        err: ServiceError,
+        stats: &mut ProcStats,
+    ) -> Result<(), SendError<InternalMsg<M>>> {
+        stats.errors += 1;
+        if matches!(err, ServiceError::Timeout(..)) {
+            stats.timeouts += 1;
+        }
+
+        let mut data = msg.get_data().clone();
+        err.encode(&mut data);
+        msg.return_error_to_sender(Some(data), err).await
+    }
+
+    /// Method to respond to a request, and keep the response for its duplicates
+    async fn respond(
+        msg: RequestMsg<M>,
+        response: M,
+        dedup: Option<&DedupCache<M>>,
+        stats: &mut ProcStats,
+    ) -> Result<(), SendError<InternalMsg<M>>> {
+        if let Some(dedup) = dedup {
+            if let Some(key) = dedup.key(msg.get_data()) {
+                dedup.complete(&key, response.clone());
+            }
+        }
+
+        stats.sent += 1;
+        msg.get_span()
+            .in_scope(|| stats.latency.record(msg.elapsed()));
+
+        msg.return_to_sender(response).await
+    }
+
+    /// Method to forward a request that can't be processed to the dead letter service (see [`dead_letter`](crate::core::dead_letter))
+    async fn dead_letter(&self, name: &str, msg: &RequestMsg<M>, err: &ServiceError) {
+        self.proc
+            .dead_letter(
+                DeadLetter::new(
+                    self.get_proc_id(),
+                    msg.get_service().clone(),
+                    msg.get_data().clone(),
+                    err.clone(),
+                )
+                .with_proc_name(name),
+            )
+            .await;
+    }
+
+    /// Method to return a service error to the requester, and forget the request so its retries can be executed again.
+    /// Irrecoverable errors are also forwarded to the dead letter service (see [`dead_letter`](crate::core::dead_letter))
+    async fn fail(
+        &self,
+        name: &str,
+        msg: RequestMsg<M>,
+        err: ServiceError,
+        dedup: Option<&DedupCache<M>>,
+        stats: &mut ProcStats,
+    ) -> Result<(), SendError<InternalMsg<M>>> {
+        if let Some(dedup) = dedup {
+            if let Some(key) = dedup.key(msg.get_data()) {
+                dedup.remove(&key);
+            }
+        }
+
+        if !err.recoverable() {
+            self.dead_letter(name, &msg, &err).await;
+        }
+
+        Self::return_service_error(msg, err, stats).await
+    }
+
+    /// Method to process a request with the adaptor, within a transaction span that carry the adaptor span attributes
+    fn process_adaptor_request<A>(
+        adaptor: &mut A,
+        name: &str,
+        msg: &RequestMsg<M>,
+        sessions: &SessionStore<M>,
+        watchdog: Option<&Watchdog>,
+    ) -> Result<M, ServiceError>
+    where
+        A: StubAdaptor<M>,
+    {
+        let _watchdog_guard = watchdog.map(|watchdog| watchdog.guard(msg.get_service()));
+        let trans_span = info_span!(parent: msg.get_span(), "prosa::stub::transaction", proc_name = name, stub_service = msg.get_service());
+        set_span_attributes(&trans_span, || adaptor.span_attributes(msg.get_data()));
+        trans_span.in_scope(|| {
+            adaptor.process_request_with_state(msg.get_service(), msg.get_data(), sessions)
+        })
+    }
+
+    /// Method to process a batch of requests with the adaptor, within a batch span.
+    /// Return one result per request
+    fn process_adaptor_batch<A>(
+        adaptor: &mut A,
+        name: &str,
+        msgs: &[RequestMsg<M>],
+        sessions: &SessionStore<M>,
+        watchdog: Option<&Watchdog>,
+    ) -> Vec<Result<M, ServiceError>>
+    where
+        A: StubAdaptor<M>,
+    {
+        let _watchdog_guard =
+            watchdog.and_then(|watchdog| msgs.first().map(|msg| watchdog.guard(msg.get_service())));
+        let batch_span = info_span!(
+            "prosa::stub::batch",
+            proc_name = name,
+            batch_size = msgs.len()
+        );
+        let requests: Vec<(&str, &M)> = msgs
+            .iter()
+            .map(|msg| (msg.get_service().as_str(), msg.get_data()))
+            .collect();
+        let mut results = batch_span.in_scope(|| adaptor.process_batch(&requests, sessions));
+        results.resize_with(msgs.len(), || {
+            Err(ServiceError::ProtocolError {
+                code: 0,
+                reason: String::from("no result of the adaptor for the batched request"),
+            })
+        });
+        results
+    }
+}
+
 #[proc]
 impl<A> Proc<A> for StubProc
 where
@@ -75,43 +331,1456 @@ where
     async fn internal_run(&mut self, name: String) -> Result<(), Box<dyn std::error::Error>> {
         // Initiate an adaptor for the stub processor
         let mut adaptor = A::new(self)?;
+        adaptor.async_init(&self.proc).await?;
+
+        // Prepare the record/replay objects
+        let mode = self.settings.mode.clone();
+        let mut recorder = if let StubMode::Record { output_dir, .. } = &mode {
+            Some(FixtureRecorder::new(output_dir)?)
+        } else {
+            None
+        };
+        let fixtures = if let StubMode::Replay { input_dir, .. } = &mode {
+            FixtureStore::load(input_dir)?
+        } else {
+            FixtureStore::default()
+        };
+        let dedup = self
+            .settings
+            .dedup
+            .clone()
+            .map(|settings| DedupCache::new(settings).meter(&self.proc.meter(name.clone())));
+        let sessions = SessionStore::new(self.settings.session.clone().unwrap_or_default()).meter(
+            &self.proc.meter(name.clone()),
+            vec![KeyValue::new("proc", name.clone())],
+        );
+        let mut session_purge = tokio::time::interval(sessions.get_settings().ttl);
+        let mut pending_msgs: PendingMsgs<RequestMsg<M>, M> = Default::default();
+        let mut msg_id: u64 = 0;
+        let stats_service = stats_service_name(&name);
+        let mut stats = ProcStats::default();
+        let watchdog = self.proc.start_watchdog(&name);
+
+        // Consume the batches of requests if they can be processed together
+        if self.settings.batch {
+            if matches!(mode, StubMode::Respond) && dedup.is_none() {
+                self.internal_rx_queue.set_batch_consumer(true);
+            } else {
+                warn!(name: "stub_proc", target: "prosa::stub::proc", proc_name = name, "Batches of requests are only consumed in respond mode without duplicate detection");
+            }
+        }
 
         // Declare the processor
         self.proc.add_proc().await?;
 
-        // Add all service to listen
+        // Add all service to listen, with the statistics service
         self.proc
-            .add_service_proc(self.settings.service_names.clone())
+            .add_service_proc(
+                self.settings
+                    .service_names
+                    .iter()
+                    .map(ServiceName::to_string)
+                    .chain([stats_service.clone()])
+                    .collect(),
+            )
             .await?;
 
+        // The adaptor is initialized, the processor can serve its services
+        self.proc.set_ready().await?;
+
         loop {
-            if let Some(msg) = self.internal_rx_queue.recv().await {
-                match msg {
-                    InternalMsg::Request(msg) => {
-                        let resp_data = adaptor.process_request(msg.get_service(), msg.get_data());
-                        debug!(name: "stub_proc", target: "prosa::stub::proc", parent: msg.get_span(), proc_name = name, stub_service = msg.get_service(), stub_req = format!("{:?}", msg.get_data()).to_string(), stub_resp = format!("{:?}", resp_data));
-                        msg.return_to_sender(resp_data).await.unwrap()
+            tokio::select! {
+                Some(msg) = self.internal_rx_queue.recv() => {
+                    match msg {
+                        InternalMsg::Request(msg) if *msg.get_service() == stats_service => {
+                            stats.watchdog_violations = watchdog.as_ref().map_or(0, Watchdog::violations);
+                            drop_unreturned!(&name, msg.return_to_sender(stats.to_tvf()).await)
+                        }
+                        InternalMsg::Request(msg) if msg.is_expired() => {
+                            debug!(name: "stub_proc", target: "prosa::stub::proc", parent: msg.get_span(), proc_name = name, stub_service = msg.get_service(), "Drop an expired request");
+                            self.internal_rx_queue.record_expired(msg.get_service());
+                            stats.received += 1;
+                            let err = msg.get_expiration_error().unwrap();
+                            drop_unreturned!(&name, Self::return_service_error(msg, err, &mut stats).await)
+                        }
+                        InternalMsg::Request(msg) => {
+                            stats.received += 1;
+                            let dedup_key = dedup.as_ref().and_then(|dedup| dedup.key(msg.get_data()));
+                            if let (Some(dedup), Some(key)) = (dedup.as_ref(), dedup_key) {
+                                let outcome = dedup.check_or_register(&key);
+                                dedup.record_hit(msg.get_service(), &outcome);
+                                match outcome {
+                                    DedupOutcome::New => {}
+                                    DedupOutcome::InFlight => {
+                                        debug!(name: "stub_proc_dedup", target: "prosa::stub::proc", parent: msg.get_span(), proc_name = name, stub_service = msg.get_service(), dedup_key = key, "Reject a duplicated request that is still in flight");
+                                        let service = msg.get_service().clone();
+                                        drop_unreturned!(&name, Self::return_service_error(msg, ServiceError::Unavailable(service, None), &mut stats).await);
+                                        continue;
+                                    }
+                                    DedupOutcome::Completed(resp_data) => {
+                                        debug!(name: "stub_proc_dedup", target: "prosa::stub::proc", parent: msg.get_span(), proc_name = name, stub_service = msg.get_service(), dedup_key = key, "Respond to a duplicated request with the cached response");
+                                        stats.sent += 1;
+                                        msg.get_span().in_scope(|| stats.latency.record(msg.elapsed()));
+                                        drop_unreturned!(&name, msg.return_to_sender(resp_data).await);
+                                        continue;
+                                    }
+                                }
+                            }
+
+                            match &mode {
+                                StubMode::Respond => match Self::process_adaptor_request(&mut adaptor, &name, &msg, &sessions, watchdog.as_ref()) {
+                                    Ok(resp_data) => {
+                                        debug!(name: "stub_proc", target: "prosa::stub::proc", parent: msg.get_span(), proc_name = name, stub_service = msg.get_service(), stub_req = format!("{:?}", msg.get_data()).to_string(), stub_resp = format!("{:?}", resp_data));
+                                        drop_unreturned!(&name, Self::respond(msg, resp_data, dedup.as_ref(), &mut stats).await)
+                                    }
+                                    Err(err) => drop_unreturned!(&name, self.fail(&name, msg, err, dedup.as_ref(), &mut stats).await),
+                                },
+                                StubMode::Record { target_service, timeout, .. } => {
+                                    if let Some(service) = self.service.get_proc_service(target_service, msg_id) {
+                                        debug!(name: "stub_proc_record", target: "prosa::stub::proc", parent: msg.get_span(), proc_name = name, stub_service = msg.get_service(), target_service = target_service, stub_req = format!("{:?}", msg.get_data()));
+                                        let mut request = self.proc.new_request(msg_id, target_service.clone(), msg.get_data().clone());
+                                        if let Some(deadline) = msg.get_deadline() {
+                                            request = request.with_deadline(deadline);
+                                        }
+                                        service.send_request(request).await?;
+                                        let timeout = msg.get_remaining_time().map_or(*timeout, |remaining| remaining.min(*timeout));
+                                        pending_msgs.push_with_id(msg_id, msg, timeout);
+                                        msg_id += 1;
+                                    } else {
+                                        // The request can't be forwarded, so it's also kept by the dead letter service
+                                        let err = ServiceError::Unavailable(target_service.clone(), None);
+                                        self.dead_letter(&name, &msg, &err).await;
+                                        drop_unreturned!(&name, self.fail(&name, msg, err, dedup.as_ref(), &mut stats).await)
+                                    }
+                                }
+                                StubMode::Replay { match_strategy, fallback, .. } => {
+                                    if let Some(resp_data) = fixtures.find(msg.get_service(), msg.get_data(), match_strategy) {
+                                        debug!(name: "stub_proc_replay", target: "prosa::stub::proc", parent: msg.get_span(), proc_name = name, stub_service = msg.get_service(), stub_req = format!("{:?}", msg.get_data()).to_string(), stub_resp = format!("{:?}", resp_data));
+                                        drop_unreturned!(&name, Self::respond(msg, resp_data, dedup.as_ref(), &mut stats).await)
+                                    } else if *fallback == ReplayFallback::Adaptor {
+                                        match Self::process_adaptor_request(&mut adaptor, &name, &msg, &sessions, watchdog.as_ref()) {
+                                            Ok(resp_data) => drop_unreturned!(&name, Self::respond(msg, resp_data, dedup.as_ref(), &mut stats).await),
+                                            Err(err) => drop_unreturned!(&name, self.fail(&name, msg, err, dedup.as_ref(), &mut stats).await),
+                                        }
+                                    } else {
+                                        warn!(name: "stub_proc_replay", target: "prosa::stub::proc", parent: msg.get_span(), proc_name = name, stub_service = msg.get_service(), stub_req = format!("{:?}", msg.get_data()), "No recorded fixture match the request");
+                                        let reason = format!("no recorded fixture match the request on service `{}`", msg.get_service());
+                                        drop_unreturned!(&name, self.fail(&name, msg, ServiceError::ProtocolError { code: 0, reason }, dedup.as_ref(), &mut stats).await)
+                                    }
+                                }
+                            }
+                        }
+                        InternalMsg::RequestBatch(batch) => {
+                            let mut msgs = Vec::with_capacity(batch.len());
+                            for msg in batch {
+                                if *msg.get_service() == stats_service {
+                                    stats.watchdog_violations = watchdog.as_ref().map_or(0, Watchdog::violations);
+                                    drop_unreturned!(&name, msg.return_to_sender(stats.to_tvf()).await)
+                                } else if msg.is_expired() {
+                                    debug!(name: "stub_proc", target: "prosa::stub::proc", parent: msg.get_span(), proc_name = name, stub_service = msg.get_service(), "Drop an expired request");
+                                    self.internal_rx_queue.record_expired(msg.get_service());
+                                    stats.received += 1;
+                                    let err = msg.get_expiration_error().unwrap();
+                                    drop_unreturned!(&name, Self::return_service_error(msg, err, &mut stats).await)
+                                } else {
+                                    stats.received += 1;
+                                    msgs.push(msg);
+                                }
+                            }
+
+                            let results = Self::process_adaptor_batch(&mut adaptor, &name, &msgs, &sessions, watchdog.as_ref());
+                            let mut responses = Vec::with_capacity(msgs.len());
+                            for (msg, result) in msgs.into_iter().zip(results) {
+                                match result {
+                                    Ok(resp_data) => {
+                                        stats.sent += 1;
+                                        msg.get_span().in_scope(|| stats.latency.record(msg.elapsed()));
+                                        responses.push((msg, resp_data));
+                                    }
+                                    Err(err) => drop_unreturned!(&name, Self::return_service_error(msg, err, &mut stats).await),
+                                }
+                            }
+                            drop_unreturned!(&name, RequestMsg::return_batch_to_senders(responses).await)
+                        }
+                        // Batches of responses are split by the processor queue (batches are only consumed in respond mode)
+                        InternalMsg::ResponseBatch(_) => {}
+                        InternalMsg::Response(resp) => {
+                            if let (Some(msg), Some(recorder)) = (pending_msgs.pull_msg(resp.get_id()), recorder.as_mut()) {
+                                // Answer the client before recording, a recording failure only misses the fixture
+                                let (service, request, span) = (msg.get_service().clone(), msg.get_data().clone(), msg.get_span().clone());
+                                drop_unreturned!(&name, Self::respond(msg, resp.get_data().clone(), dedup.as_ref(), &mut stats).await);
+                                match recorder.record(&service, &request, resp.get_data()) {
+                                    Ok(fixture_path) => debug!(name: "stub_proc_record", target: "prosa::stub::proc", parent: &span, proc_name = name, stub_service = service, stub_resp = format!("{:?}", resp.get_data()), fixture = fixture_path.to_str()),
+                                    Err(e) => warn!(name: "stub_proc_record", target: "prosa::stub::proc", parent: &span, proc_name = name, stub_service = service, "Can't record the fixture: {}", e),
+                                }
+                            } else if recorder.is_none() {
+                                panic!(
+                                    "The stub processor {} receive a response {:?}",
+                                    self.get_proc_id(),
+                                    resp
+                                )
+                            }
+                        }
+                        InternalMsg::Error(err) => {
+                            if let Some(msg) = pending_msgs.pull_msg(err.get_id()) {
+                                if let Some((dedup, key)) = dedup.as_ref().and_then(|dedup| dedup.key(msg.get_data()).map(|key| (dedup, key))) {
+                                    dedup.remove(&key);
+                                }
+                                stats.errors += 1;
+                                if matches!(err.get_err(), ServiceError::Timeout(..)) {
+                                    stats.timeouts += 1;
+                                }
+                                drop_unreturned!(&name, msg.return_error_to_sender(Some(err.get_data().clone()), err.get_err().clone()).await)
+                            } else if recorder.is_none() {
+                                panic!(
+                                    "The stub processor {} receive an error {:?}",
+                                    self.get_proc_id(),
+                                    err
+                                )
+                            }
+                        }
+                        InternalMsg::Command(_) => todo!(),
+                        InternalMsg::Ping(_) => {}
+                        InternalMsg::Config(config) => {
+                            // Services and mode are kept, but the adaptor can reload its configuration
+                            // The adaptor keeps its configuration if the settings can't be reloaded
+                            match self.reload_settings(&config, &name) {
+                                Ok(()) => self.reload_adaptor(&mut adaptor, &name),
+                                Err(e) => {
+                                    warn!(name: "config_stub_proc", target: "prosa::stub::proc", proc_name = name, "Can't reload the settings: {}", e);
+                                }
+                            }
+                        }
+                        InternalMsg::Service(table) => self.service = table,
+                        InternalMsg::Shutdown => {
+                            self.proc.set_stopping().await?;
+                            if let Some(watchdog) = &watchdog {
+                                let _ = watchdog.timeout(&name, adaptor.async_terminate()).await;
+                            } else {
+                                adaptor.async_terminate().await;
+                            }
+                            self.proc.remove_proc().await?;
+                            return Ok(());
+                        }
                     }
-                    InternalMsg::Response(msg) => panic!(
-                        "The stub processor {} receive a response {:?}",
-                        self.get_proc_id(),
-                        msg
-                    ),
-                    InternalMsg::Error(err) => panic!(
-                        "The stub processor {} receive an error {:?}",
-                        self.get_proc_id(),
-                        err
-                    ),
-                    InternalMsg::Command(_) => todo!(),
-                    InternalMsg::Config => todo!(),
-                    InternalMsg::Service(table) => self.service = table,
-                    InternalMsg::Shutdown => {
-                        adaptor.terminate();
-                        self.proc.remove_proc().await?;
-                        return Ok(());
+                },
+                _ = session_purge.tick() => sessions.purge(),
+                Some(msg) = pending_msgs.pull(), if !pending_msgs.is_empty() => {
+                    if let StubMode::Record { target_service, timeout, .. } = &mode {
+                        drop_unreturned!(&name, self.fail(&name, msg, ServiceError::Timeout(target_service.clone(), timeout.as_millis() as u64), dedup.as_ref(), &mut stats).await)
                     }
+                },
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate self as prosa;
+
+    use std::{
+        env,
+        error::Error,
+        fs,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc, Mutex,
+        },
+        time::{Duration, SystemTime},
+    };
+
+    use futures_util::future::BoxFuture;
+    use opentelemetry::{trace::TracerProvider as _, Key, KeyValue, Value};
+    use opentelemetry_sdk::{
+        export::trace::{ExportResult, SpanData, SpanExporter},
+        trace::TracerProvider,
+    };
+    use prosa_macros::{proc, settings, Adaptor};
+    use prosa_utils::msg::{
+        simple_string_tvf::SimpleStringTvf,
+        tvf::{Tvf, TvfExt as _},
+    };
+    use serde::{Deserialize, Serialize};
+    use tracing_subscriber::layer::SubscriberExt as _;
+
+    use crate::core::{
+        adaptor::{AdaptorConfigReload, AdaptorError, MAX_SPAN_ATTRIBUTES},
+        error::BusError,
+        main::{MainProc, MainRunnable as _},
+        msg::{InternalMsg, Msg, RequestMsg},
+        proc::{Proc, ProcConfig as _, ProcParam, ProcSettings as _},
+        service::{DedupSettings, ServiceCall, ServiceError},
+        settings::ConfigWatch,
+        shed::ShedPolicy,
+        watchdog::WatchdogSettings,
+    };
+    use crate::stub::adaptor::{DeadLetterFileAdaptor, StubAdaptor, StubParotAdaptor};
+    use crate::stub::fixture::MatchStrategy;
+    use crate::stub::session::{SessionSettings, SessionStore};
+    use crate::testing::ProsaTestKit;
+
+    use super::{StubMode, StubProc, StubSettings};
+
+    const WAIT_TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// Dummy settings
+    #[settings]
+    #[derive(Default, Debug, Serialize)]
+    struct TestSettings {}
+
+    /// Adaptor of the original target that tag its responses
+    #[derive(Adaptor)]
+    struct TestTargetAdaptor {}
+
+    impl StubAdaptor<SimpleStringTvf> for TestTargetAdaptor {
+        fn new(_proc: &StubProc<SimpleStringTvf>) -> Result<Self, Box<dyn Error>> {
+            Ok(Self {})
+        }
+
+        fn process_request(
+            &mut self,
+            _service_name: &str,
+            request: &SimpleStringTvf,
+        ) -> Result<SimpleStringTvf, ServiceError> {
+            if request.require_str(1)?.as_str() == "error" {
+                return Err(ServiceError::Internal(String::from("target error")));
+            }
+
+            let mut response = request.clone();
+            response.put_string(2, "target");
+            Ok(response)
+        }
+    }
+
+    static CONFIG_RELOADS: AtomicUsize = AtomicUsize::new(0);
+
+    #[derive(Deserialize)]
+    struct TestAdaptorConfig {
+        value: String,
+    }
+
+    /// Adaptor that respond with a value of its configuration
+    #[derive(Adaptor)]
+    #[adaptor(reload = TestAdaptorConfig)]
+    struct TestConfigAdaptor {
+        value: String,
+    }
+
+    impl StubAdaptor<SimpleStringTvf> for TestConfigAdaptor {
+        fn new(proc: &StubProc<SimpleStringTvf>) -> Result<Self, Box<dyn Error>> {
+            let config: TestAdaptorConfig = proc.settings.get_adaptor_config()?;
+            Ok(Self {
+                value: config.value,
+            })
+        }
+
+        fn process_request(
+            &mut self,
+            _service_name: &str,
+            request: &SimpleStringTvf,
+        ) -> Result<SimpleStringTvf, ServiceError> {
+            let mut response = request.clone();
+            response.put_string(2, self.value.clone());
+            Ok(response)
+        }
+    }
+
+    impl AdaptorConfigReload<TestAdaptorConfig> for TestConfigAdaptor {
+        fn reload(&mut self, config: TestAdaptorConfig) -> Result<(), AdaptorError> {
+            self.value = config.value;
+            CONFIG_RELOADS.fetch_add(1, Ordering::Relaxed);
+            Ok(())
+        }
+    }
+
+    static EXPIRED_ADAPTOR_REQUESTS: AtomicUsize = AtomicUsize::new(0);
+
+    /// Adaptor that count the requests it process
+    #[derive(Adaptor)]
+    struct TestExpiredAdaptor {}
+
+    impl StubAdaptor<SimpleStringTvf> for TestExpiredAdaptor {
+        fn new(_proc: &StubProc<SimpleStringTvf>) -> Result<Self, Box<dyn Error>> {
+            Ok(Self {})
+        }
+
+        fn process_request(
+            &mut self,
+            _service_name: &str,
+            request: &SimpleStringTvf,
+        ) -> Result<SimpleStringTvf, ServiceError> {
+            EXPIRED_ADAPTOR_REQUESTS.fetch_add(1, Ordering::Relaxed);
+            Ok(request.clone())
+        }
+    }
+
+    static SHED_ADAPTOR_REQUESTS: AtomicUsize = AtomicUsize::new(0);
+
+    /// Slow adaptor that count the requests it process
+    #[derive(Adaptor)]
+    struct TestShedAdaptor {}
+
+    impl StubAdaptor<SimpleStringTvf> for TestShedAdaptor {
+        fn new(_proc: &StubProc<SimpleStringTvf>) -> Result<Self, Box<dyn Error>> {
+            Ok(Self {})
+        }
+
+        fn process_request(
+            &mut self,
+            _service_name: &str,
+            request: &SimpleStringTvf,
+        ) -> Result<SimpleStringTvf, ServiceError> {
+            SHED_ADAPTOR_REQUESTS.fetch_add(1, Ordering::Relaxed);
+            std::thread::sleep(Duration::from_millis(20));
+            Ok(request.clone())
+        }
+    }
+
+    static DEDUP_ADAPTOR_REQUESTS: AtomicUsize = AtomicUsize::new(0);
+
+    /// Adaptor that respond with the number of requests it process, and fail on `error` requests
+    #[derive(Adaptor)]
+    struct TestDedupAdaptor {}
+
+    impl StubAdaptor<SimpleStringTvf> for TestDedupAdaptor {
+        fn new(_proc: &StubProc<SimpleStringTvf>) -> Result<Self, Box<dyn Error>> {
+            Ok(Self {})
+        }
+
+        fn process_request(
+            &mut self,
+            _service_name: &str,
+            request: &SimpleStringTvf,
+        ) -> Result<SimpleStringTvf, ServiceError> {
+            let count = DEDUP_ADAPTOR_REQUESTS.fetch_add(1, Ordering::Relaxed) + 1;
+            if request.require_str(1)?.as_str() == "error" {
+                return Err(ServiceError::Internal(String::from("dedup error")));
+            }
+
+            let mut response = request.clone();
+            response.put_unsigned(2, count as u64);
+            Ok(response)
+        }
+    }
+
+    /// Adaptor that open a session with a token, and check the token of the next requests of the session
+    #[derive(Adaptor)]
+    struct TestSessionAdaptor {}
+
+    impl StubAdaptor<SimpleStringTvf> for TestSessionAdaptor {
+        fn new(_proc: &StubProc<SimpleStringTvf>) -> Result<Self, Box<dyn Error>> {
+            Ok(Self {})
+        }
+
+        fn process_request(
+            &mut self,
+            _service_name: &str,
+            _request: &SimpleStringTvf,
+        ) -> Result<SimpleStringTvf, ServiceError> {
+            Err(ServiceError::Internal(String::from("stateless call")))
+        }
+
+        fn process_request_with_state(
+            &mut self,
+            _service_name: &str,
+            request: &SimpleStringTvf,
+            state: &SessionStore<SimpleStringTvf>,
+        ) -> Result<SimpleStringTvf, ServiceError> {
+            let session = state.key(request).unwrap();
+            let mut response = request.clone();
+            if request.require_str(2)?.as_str() == "open" {
+                let token = format!("token-{}", session);
+                state.put(&session, token.clone());
+                response.put_string(3, token);
+            } else if state.get::<String>(&session).as_deref()
+                != Some(request.require_str(3)?.as_str())
+            {
+                return Err(ServiceError::ProtocolError {
+                    code: 1,
+                    reason: String::from("invalid session token"),
+                });
+            }
+
+            Ok(response)
+        }
+    }
+
+    /// Runtime flavor, number of workers and task thread name seen by the [`TestRuntimeAdaptor`]
+    static PROC_RUNTIME: Mutex<Option<(tokio::runtime::RuntimeFlavor, usize, Option<String>)>> =
+        Mutex::new(None);
+
+    /// Adaptor that record the runtime of its processor
+    #[derive(Adaptor)]
+    struct TestRuntimeAdaptor {}
+
+    impl StubAdaptor<SimpleStringTvf> for TestRuntimeAdaptor {
+        fn new(_proc: &StubProc<SimpleStringTvf>) -> Result<Self, Box<dyn Error>> {
+            let runtime = tokio::runtime::Handle::current();
+            let flavor = runtime.runtime_flavor();
+            let num_workers = runtime.metrics().num_workers();
+            runtime.spawn(async move {
+                let thread_name = std::thread::current().name().map(String::from);
+                *PROC_RUNTIME.lock().unwrap() = Some((flavor, num_workers, thread_name));
+            });
+            Ok(Self {})
+        }
+
+        fn process_request(
+            &mut self,
+            _service_name: &str,
+            request: &SimpleStringTvf,
+        ) -> Result<SimpleStringTvf, ServiceError> {
+            Ok(request.clone())
+        }
+    }
+
+    /// Adaptor that put the request value and more attributes than allowed on its transaction spans
+    #[derive(Adaptor)]
+    struct TestSpanAdaptor {}
+
+    impl StubAdaptor<SimpleStringTvf> for TestSpanAdaptor {
+        fn new(_proc: &StubProc<SimpleStringTvf>) -> Result<Self, Box<dyn Error>> {
+            Ok(Self {})
+        }
+
+        fn process_request(
+            &mut self,
+            _service_name: &str,
+            request: &SimpleStringTvf,
+        ) -> Result<SimpleStringTvf, ServiceError> {
+            Ok(request.clone())
+        }
+
+        fn span_attributes(&self, request: &SimpleStringTvf) -> Vec<KeyValue> {
+            let value = request
+                .get_string(1)
+                .map(|v| v.to_string())
+                .unwrap_or_default();
+            (0..MAX_SPAN_ATTRIBUTES + 4)
+                .map(|i| KeyValue::new(format!("test.attr{}", i), value.clone()))
+                .collect()
+        }
+    }
+
+    /// Span exporter that keep the exported spans in memory
+    #[derive(Debug, Clone, Default)]
+    struct TestSpanExporter {
+        spans: Arc<Mutex<Vec<SpanData>>>,
+    }
+
+    impl SpanExporter for TestSpanExporter {
+        fn export(&mut self, batch: Vec<SpanData>) -> BoxFuture<'static, ExportResult> {
+            self.spans.lock().unwrap().extend(batch);
+            Box::pin(std::future::ready(Ok(())))
+        }
+    }
+
+    #[proc]
+    struct TestClientProc {}
+
+    #[proc]
+    impl TestClientProc<SimpleStringTvf> {
+        /// Send requests one by one to the service and collect responses (Err with the returned data for errors)
+        async fn exchange(
+            &mut self,
+            wait_services: &[&str],
+            service_name: &str,
+            requests: Vec<SimpleStringTvf>,
+        ) -> Result<Vec<Result<SimpleStringTvf, SimpleStringTvf>>, BusError> {
+            self.exchange_with_deadline(wait_services, service_name, requests, None)
+                .await
+        }
+
+        /// Same as `exchange` with a deadline given to every request
+        async fn exchange_with_deadline(
+            &mut self,
+            wait_services: &[&str],
+            service_name: &str,
+            requests: Vec<SimpleStringTvf>,
+            deadline: Option<SystemTime>,
+        ) -> Result<Vec<Result<SimpleStringTvf, SimpleStringTvf>>, BusError> {
+            self.proc.add_proc().await?;
+
+            // Wait for all needed services
+            while !wait_services
+                .iter()
+                .all(|s| self.service.exist_proc_service(s))
+            {
+                if let Some(InternalMsg::Service(table)) = self.internal_rx_queue.recv().await {
+                    self.service = table;
+                }
+            }
+
+            let service_name = service_name.to_string();
+            let mut responses = Vec::with_capacity(requests.len());
+            for (msg_id, request) in requests.into_iter().enumerate() {
+                let mut request = RequestMsg::new(
+                    msg_id as u64,
+                    service_name.clone(),
+                    request,
+                    self.proc.get_service_queue(),
+                );
+                if let Some(deadline) = deadline {
+                    request = request.with_deadline(deadline);
+                }
+
+                self.service
+                    .get_proc_service(&service_name, msg_id as u64)
+                    .unwrap()
+                    .proc_queue
+                    .send(InternalMsg::Request(request))
+                    .await?;
+
+                loop {
+                    match self.internal_rx_queue.recv().await {
+                        Some(InternalMsg::Response(resp)) => {
+                            responses.push(Ok(resp.get_data().clone()));
+                            break;
+                        }
+                        Some(InternalMsg::Error(err)) => {
+                            responses.push(Err(err.get_data().clone()));
+                            break;
+                        }
+                        Some(InternalMsg::Service(table)) => self.service = table,
+                        msg => panic!("Unexpected message {:?}", msg),
+                    }
+                }
+            }
+
+            self.proc.remove_proc().await?;
+            Ok(responses)
+        }
+    }
+
+    #[proc]
+    impl TestClientProc<SimpleStringTvf> {
+        /// Send all the requests at once to the service, and collect the responses with their latency
+        #[allow(clippy::type_complexity)]
+        async fn flood(
+            &mut self,
+            service_name: &str,
+            count: u64,
+        ) -> Result<Vec<(Result<SimpleStringTvf, ServiceError>, Duration)>, BusError> {
+            self.proc.add_proc().await?;
+            while !self.service.exist_proc_service(service_name) {
+                if let Some(InternalMsg::Service(table)) = self.internal_rx_queue.recv().await {
+                    self.service = table;
+                }
+            }
+
+            let begin = std::time::Instant::now();
+            let proc_service = self.service.get_proc_service(service_name, 0).unwrap();
+            for msg_id in 0..count {
+                proc_service
+                    .proc_queue
+                    .send(InternalMsg::Request(RequestMsg::new(
+                        msg_id,
+                        service_name.to_string(),
+                        test_request("flood"),
+                        self.proc.get_service_queue(),
+                    )))
+                    .await?;
+            }
+
+            let mut responses = Vec::with_capacity(count as usize);
+            while responses.len() < count as usize {
+                match self.internal_rx_queue.recv().await {
+                    Some(InternalMsg::Response(resp)) => {
+                        responses.push((Ok(resp.get_data().clone()), begin.elapsed()))
+                    }
+                    Some(InternalMsg::Error(err)) => {
+                        responses.push((Err(err.get_err().clone()), begin.elapsed()))
+                    }
+                    Some(InternalMsg::Service(table)) => self.service = table,
+                    msg => panic!("Unexpected message {:?}", msg),
                 }
             }
+
+            self.proc.remove_proc().await?;
+            Ok(responses)
         }
     }
+
+    fn test_request(value: &str) -> SimpleStringTvf {
+        let mut request = SimpleStringTvf::default();
+        request.put_string(1, value);
+        request
+    }
+
+    #[tokio::test]
+    async fn stub_record_replay() {
+        let fixture_dir = env::temp_dir().join("prosa_stub_record_replay");
+        let _ = fs::remove_dir_all(&fixture_dir);
+
+        // Record transactions through a passthrough stub
+        let (bus, main) = MainProc::<SimpleStringTvf>::create(&TestSettings::default());
+        let main_task = main.run();
+
+        let target_proc = StubProc::<SimpleStringTvf>::create(
+            1,
+            bus.clone(),
+            StubSettings::new(vec![String::from("TARGET")]),
+        );
+        Proc::<TestTargetAdaptor>::run(target_proc, String::from("TARGET_PROC"));
+
+        let mut record_settings = StubSettings::new(vec![String::from("RECORD")]);
+        record_settings.set_mode(StubMode::record(
+            String::from("TARGET"),
+            fixture_dir.clone(),
+        ));
+        let record_proc = StubProc::<SimpleStringTvf>::create(2, bus.clone(), record_settings);
+        Proc::<StubParotAdaptor>::run(record_proc, String::from("RECORD_PROC"));
+
+        let mut client = TestClientProc::<SimpleStringTvf>::create_raw(3, bus.clone());
+        let responses = client
+            .exchange(
+                &["TARGET", "RECORD"],
+                "RECORD",
+                vec![test_request("first"), test_request("second")],
+            )
+            .await
+            .unwrap();
+        for (response, value) in responses.iter().zip(["first", "second"]) {
+            let response = response.as_ref().unwrap();
+            assert_eq!(value, response.get_string(1).unwrap().as_str());
+            assert_eq!("target", response.get_string(2).unwrap().as_str());
+        }
+
+        bus.stop("Record end".into()).await.unwrap();
+        main_task.join().unwrap();
+        assert_eq!(2, fs::read_dir(&fixture_dir).unwrap().count());
+
+        // Replay recorded transactions without the original target
+        let (bus, main) = MainProc::<SimpleStringTvf>::create(&TestSettings::default());
+        let main_task = main.run();
+
+        let mut replay_settings = StubSettings::new(vec![String::from("RECORD")]);
+        replay_settings.set_mode(StubMode::replay(
+            fixture_dir.clone(),
+            MatchStrategy::Tags(vec![1]),
+        ));
+        let replay_proc = StubProc::<SimpleStringTvf>::create(1, bus.clone(), replay_settings);
+        Proc::<StubParotAdaptor>::run(replay_proc, String::from("REPLAY_PROC"));
+
+        let mut client = TestClientProc::<SimpleStringTvf>::create_raw(2, bus.clone());
+        let responses = client
+            .exchange(
+                &["RECORD"],
+                "RECORD",
+                vec![
+                    test_request("second"),
+                    test_request("first"),
+                    test_request("unknown"),
+                ],
+            )
+            .await
+            .unwrap();
+        assert_eq!(3, responses.len());
+        for (response, value) in responses.iter().zip(["second", "first"]) {
+            let response = response.as_ref().unwrap();
+            assert_eq!(value, response.get_string(1).unwrap().as_str());
+            assert_eq!("target", response.get_string(2).unwrap().as_str());
+        }
+        assert_eq!(
+            Some(ServiceError::ProtocolError {
+                code: 0,
+                reason: String::from("no recorded fixture match the request on service `RECORD`")
+            }),
+            ServiceError::decode(responses[2].as_ref().unwrap_err()).unwrap()
+        );
+
+        bus.stop("Replay end".into()).await.unwrap();
+        main_task.join().unwrap();
+        fs::remove_dir_all(&fixture_dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn stub_adaptor_service_error() {
+        let mut kit = ProsaTestKit::<SimpleStringTvf>::new(&TestSettings::default()).await;
+        let target_proc =
+            kit.create_proc::<StubProc<_>>(StubSettings::new(vec![String::from("TARGET")]));
+        kit.spawn_proc::<_, TestTargetAdaptor>(target_proc, "TARGET_PROC");
+
+        kit.send_request_as("TARGET", test_request("error"))
+            .await
+            .unwrap();
+        let error = kit
+            .expect_response(WAIT_TIMEOUT)
+            .await
+            .unwrap()
+            .unwrap_err();
+        assert_eq!("error", error.get_data().get_string(1).unwrap().as_str());
+        assert_eq!(
+            Some(ServiceError::Internal(String::from("target error"))),
+            ServiceError::decode(error.get_data()).unwrap()
+        );
+
+        kit.send_request_as("TARGET", test_request("ok"))
+            .await
+            .unwrap();
+        let response = kit.expect_response(WAIT_TIMEOUT).await.unwrap().unwrap();
+        assert_eq!(None, ServiceError::decode(response.get_data()).unwrap());
+        assert!(kit.drain().is_empty());
+
+        kit.stop().await;
+    }
+
+    #[tokio::test]
+    async fn stub_adaptor_config_reload() {
+        let adaptor_config_path = env::temp_dir().join("prosa_stub_adaptor_config_reload.yml");
+        let proc_config_path = env::temp_dir().join("prosa_stub_adaptor_config_reload_proc.yml");
+        fs::write(&adaptor_config_path, "value: first\n").unwrap();
+        fs::write(
+            &proc_config_path,
+            format!(
+                "config_proc:\n  service_names: [CONFIG]\n  adaptor_config_path: {:?}\n",
+                adaptor_config_path
+            ),
+        )
+        .unwrap();
+
+        let mut config_watch =
+            ConfigWatch::new(vec![proc_config_path.clone(), adaptor_config_path.clone()]);
+        config_watch.set_poll_interval(Duration::from_millis(20));
+        config_watch.set_debounce(Duration::from_millis(100));
+        let settings = TestSettings {
+            config_watch: Some(config_watch),
+            ..Default::default()
+        };
+        let (bus, main) = MainProc::<SimpleStringTvf>::create(&settings);
+        let main_task = main.run();
+
+        let mut stub_settings = StubSettings::new(vec![String::from("CONFIG")]);
+        stub_settings.adaptor_config_path = Some(adaptor_config_path.to_str().unwrap().into());
+        let stub_proc = StubProc::<SimpleStringTvf>::create(1, bus.clone(), stub_settings);
+        Proc::<TestConfigAdaptor>::run(stub_proc, String::from("config_proc"));
+
+        let mut client = TestClientProc::<SimpleStringTvf>::create_raw(2, bus.clone());
+        let responses = client
+            .exchange(&["CONFIG"], "CONFIG", vec![test_request("before")])
+            .await
+            .unwrap();
+        let response = responses[0].as_ref().unwrap();
+        assert_eq!("first", response.get_string(2).unwrap().as_str());
+
+        // Rewrite the adaptor configuration several times in a row
+        fs::write(&adaptor_config_path, "value: second\n").unwrap();
+        fs::write(&adaptor_config_path, "value: third\n").unwrap();
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while CONFIG_RELOADS.load(Ordering::Relaxed) == 0 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert_eq!(1, CONFIG_RELOADS.load(Ordering::Relaxed));
+
+        let mut client = TestClientProc::<SimpleStringTvf>::create_raw(3, bus.clone());
+        let responses = client
+            .exchange(&["CONFIG"], "CONFIG", vec![test_request("after")])
+            .await
+            .unwrap();
+        let response = responses[0].as_ref().unwrap();
+        assert_eq!("third", response.get_string(2).unwrap().as_str());
+
+        bus.stop("Config reload end".into()).await.unwrap();
+        main_task.join().unwrap();
+        fs::remove_file(&adaptor_config_path).unwrap();
+        fs::remove_file(&proc_config_path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn stub_dead_letter() {
+        let dead_letter_path = env::temp_dir().join("prosa_stub_dead_letter.jsonl");
+        let dictionary_path = env::temp_dir().join("prosa_stub_dead_letter.csv");
+        let adaptor_config_path = env::temp_dir().join("prosa_stub_dead_letter.yml");
+        let _ = fs::remove_file(&dead_letter_path);
+        fs::write(
+            &dictionary_path,
+            "dictionary,tag,label,type,repeatable,reference\nmessage,1,value,string,,\n",
+        )
+        .unwrap();
+        fs::write(
+            &adaptor_config_path,
+            format!(
+                "path: {:?}\ndictionary: {:?}\n",
+                dead_letter_path, dictionary_path
+            ),
+        )
+        .unwrap();
+
+        let settings = TestSettings {
+            dead_letter_service: Some(String::from("DEAD_LETTER")),
+            ..Default::default()
+        };
+        let (bus, main) = MainProc::<SimpleStringTvf>::create(&settings);
+        let main_task = main.run();
+
+        let mut stub_settings = StubSettings::new(vec![String::from("DEAD_LETTER")]);
+        stub_settings.adaptor_config_path = Some(adaptor_config_path.to_str().unwrap().into());
+        let stub_proc = StubProc::<SimpleStringTvf>::create(1, bus.clone(), stub_settings);
+        Proc::<DeadLetterFileAdaptor>::run(stub_proc, String::from("DEAD_LETTER_PROC"));
+        tokio::time::timeout(WAIT_TIMEOUT, async {
+            while !bus.get_service_table().exist_proc_service("DEAD_LETTER") {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+
+        // Send a request to a service that doesn't exist
+        let (client_queue, mut client_rx_queue) = tokio::sync::mpsc::channel(16);
+        let client = ProcParam::new(2, client_queue, bus.clone());
+        client.add_proc().await.unwrap();
+        client.send_after(
+            Duration::ZERO,
+            1,
+            String::from("UNKNOWN"),
+            test_request("lost"),
+        );
+        let err = tokio::time::timeout(WAIT_TIMEOUT, async {
+            loop {
+                match client_rx_queue.recv().await {
+                    Some(InternalMsg::Error(err)) => return err,
+                    Some(InternalMsg::Service(_)) => continue,
+                    msg => panic!("Unexpected message {:?}", msg),
+                }
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!(
+            &ServiceError::UnknownService(String::from("UNKNOWN")),
+            err.get_err()
+        );
+
+        // The dead letter adaptor received the wrapped request
+        let line = tokio::time::timeout(WAIT_TIMEOUT, async {
+            loop {
+                if let Some(line) = fs::read_to_string(&dead_letter_path)
+                    .ok()
+                    .and_then(|content| content.lines().next().map(String::from))
+                {
+                    return line;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+        let dead_letter: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!("unknown_service", dead_letter["reason"]);
+        assert_eq!("The service `UNKNOWN` is unknown", dead_letter["error"]);
+        assert_eq!("UNKNOWN", dead_letter["service"]);
+        assert_eq!(2, dead_letter["proc_id"]);
+        assert_eq!("lost", dead_letter["payload"]["value"]);
+        assert!(dead_letter["timestamp"].is_string());
+
+        // A request that a recording stub can't forward is also a dead letter
+        let mut record_settings = StubSettings::new(vec![String::from("FORWARD")]);
+        record_settings.set_mode(StubMode::record(
+            String::from("MISSING"),
+            env::temp_dir().join("prosa_stub_dead_letter_record"),
+        ));
+        let record_proc = StubProc::<SimpleStringTvf>::create(3, bus.clone(), record_settings);
+        Proc::<StubParotAdaptor>::run(record_proc, String::from("FORWARD_PROC"));
+        let mut client = TestClientProc::<SimpleStringTvf>::create_raw(4, bus.clone());
+        let responses = client
+            .exchange(&["FORWARD"], "FORWARD", vec![test_request("unforwarded")])
+            .await
+            .unwrap();
+        assert_eq!(
+            Some(ServiceError::Unavailable(String::from("MISSING"), None)),
+            ServiceError::decode(responses[0].as_ref().unwrap_err()).unwrap()
+        );
+        let line = tokio::time::timeout(WAIT_TIMEOUT, async {
+            loop {
+                if let Some(line) = fs::read_to_string(&dead_letter_path)
+                    .ok()
+                    .and_then(|content| content.lines().nth(1).map(String::from))
+                {
+                    return line;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+        let dead_letter: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!("unavailable", dead_letter["reason"]);
+        assert_eq!("FORWARD", dead_letter["service"]);
+        assert_eq!(3, dead_letter["proc_id"]);
+        assert_eq!("unforwarded", dead_letter["payload"]["value"]);
+
+        bus.stop("Dead letter end".into()).await.unwrap();
+        main_task.join().unwrap();
+        fs::remove_file(&dead_letter_path).unwrap();
+        fs::remove_file(&dictionary_path).unwrap();
+        fs::remove_file(&adaptor_config_path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn stub_settings_threads() {
+        let (bus, main) = MainProc::<SimpleStringTvf>::create(&TestSettings::default());
+        let main_task = main.run();
+
+        let mut stub_settings = StubSettings::new(vec![String::from("THREADS")]);
+        stub_settings.threads = Some(2);
+        let stub_proc = StubProc::<SimpleStringTvf>::create(1, bus.clone(), stub_settings);
+        assert_eq!(Some(2), stub_proc.get_proc_param().get_threads());
+        Proc::<TestRuntimeAdaptor>::run(stub_proc, String::from("THREADS_PROC"));
+
+        let (flavor, num_workers, thread_name) =
+            tokio::time::timeout(Duration::from_secs(5), async {
+                loop {
+                    if let Some(runtime) = PROC_RUNTIME.lock().unwrap().take() {
+                        return runtime;
+                    }
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                }
+            })
+            .await
+            .unwrap();
+        assert_eq!(tokio::runtime::RuntimeFlavor::MultiThread, flavor);
+        assert_eq!(2, num_workers);
+        assert_eq!(Some(String::from("THREADS_PROC")), thread_name);
+
+        bus.stop("Threads end".into()).await.unwrap();
+        main_task.join().unwrap();
+    }
+
+    #[tokio::test]
+    async fn stub_shed_requests() {
+        const REQUESTS: u64 = 40;
+        const WATERMARK: usize = 5;
+        let (bus, main) = MainProc::<SimpleStringTvf>::create(&TestSettings::default());
+        let main_task = main.run();
+
+        let mut stub_settings = StubSettings::new(vec![String::from("SHED")]);
+        stub_settings.shed_policy = Some(ShedPolicy::DropOldest {
+            watermark: WATERMARK,
+        });
+        let stub_proc = StubProc::<SimpleStringTvf>::create(1, bus.clone(), stub_settings);
+        Proc::<TestShedAdaptor>::run(stub_proc, String::from("SHED_PROC"));
+
+        let mut client = TestClientProc::<SimpleStringTvf>::create_raw(2, bus.clone());
+        let responses = tokio::time::timeout(WAIT_TIMEOUT, client.flood("SHED", REQUESTS))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(REQUESTS as usize, responses.len());
+
+        let (processed, shed): (Vec<_>, Vec<_>) =
+            responses.into_iter().partition(|(resp, _)| resp.is_ok());
+        assert!(
+            processed.len() <= WATERMARK + 2,
+            "{} requests processed",
+            processed.len()
+        );
+        assert_eq!(
+            processed.len(),
+            SHED_ADAPTOR_REQUESTS.load(Ordering::Relaxed)
+        );
+        for (resp, _) in &shed {
+            assert!(
+                matches!(resp, Err(ServiceError::Unavailable(service, None)) if service == "SHED")
+            );
+        }
+
+        // Shed requests are answered without waiting for the slow adaptor
+        let last_shed = shed.iter().map(|(_, latency)| *latency).max().unwrap();
+        let last_processed = processed.iter().map(|(_, latency)| *latency).max().unwrap();
+        assert!(last_shed < last_processed);
+
+        bus.stop("Shed end".into()).await.unwrap();
+        main_task.join().unwrap();
+    }
+
+    #[tokio::test]
+    async fn stub_expired_request() {
+        let (bus, main) = MainProc::<SimpleStringTvf>::create(&TestSettings::default());
+        let main_task = main.run();
+
+        let stub_proc = StubProc::<SimpleStringTvf>::create(
+            1,
+            bus.clone(),
+            StubSettings::new(vec![String::from("EXPIRED")]),
+        );
+        Proc::<TestExpiredAdaptor>::run(stub_proc, String::from("EXPIRED_PROC"));
+
+        let mut client = TestClientProc::<SimpleStringTvf>::create_raw(2, bus.clone());
+        let responses = client
+            .exchange_with_deadline(
+                &["EXPIRED"],
+                "EXPIRED",
+                vec![test_request("expired")],
+                Some(SystemTime::now() - Duration::from_secs(1)),
+            )
+            .await
+            .unwrap();
+        assert!(matches!(
+            ServiceError::decode(responses[0].as_ref().unwrap_err()).unwrap(),
+            Some(ServiceError::Timeout(service, _)) if service == "EXPIRED"
+        ));
+        assert_eq!(0, EXPIRED_ADAPTOR_REQUESTS.load(Ordering::Relaxed));
+
+        let mut client = TestClientProc::<SimpleStringTvf>::create_raw(3, bus.clone());
+        let responses = client
+            .exchange_with_deadline(
+                &["EXPIRED"],
+                "EXPIRED",
+                vec![test_request("valid")],
+                Some(SystemTime::now() + Duration::from_secs(5)),
+            )
+            .await
+            .unwrap();
+        assert!(responses[0].is_ok());
+        assert_eq!(1, EXPIRED_ADAPTOR_REQUESTS.load(Ordering::Relaxed));
+
+        bus.stop("Expired end".into()).await.unwrap();
+        main_task.join().unwrap();
+    }
+
+    #[tokio::test]
+    async fn stub_dedup_requests() {
+        let (bus, main) = MainProc::<SimpleStringTvf>::create(&TestSettings::default());
+        let main_task = main.run();
+
+        let mut stub_settings = StubSettings::new(vec![String::from("DEDUP")]);
+        stub_settings.set_dedup(DedupSettings::new(vec![1]));
+        let stub_proc = StubProc::<SimpleStringTvf>::create(1, bus.clone(), stub_settings);
+        Proc::<TestDedupAdaptor>::run(stub_proc, String::from("DEDUP_PROC"));
+
+        let mut client = TestClientProc::<SimpleStringTvf>::create_raw(2, bus.clone());
+        let responses = client
+            .exchange(
+                &["DEDUP"],
+                "DEDUP",
+                vec![
+                    test_request("first"),
+                    test_request("first"),
+                    test_request("second"),
+                    test_request("error"),
+                    test_request("error"),
+                ],
+            )
+            .await
+            .unwrap();
+
+        // The duplicate get the cached response of the first request
+        assert_eq!(1, responses[0].as_ref().unwrap().get_unsigned(2).unwrap());
+        assert_eq!(1, responses[1].as_ref().unwrap().get_unsigned(2).unwrap());
+        assert_eq!(2, responses[2].as_ref().unwrap().get_unsigned(2).unwrap());
+
+        // A failed request is executed again when retried
+        assert!(responses[3].is_err());
+        assert!(responses[4].is_err());
+        assert_eq!(4, DEDUP_ADAPTOR_REQUESTS.load(Ordering::Relaxed));
+
+        bus.stop("Dedup end".into()).await.unwrap();
+        main_task.join().unwrap();
+    }
+
+    #[tokio::test]
+    async fn stub_session_flow() {
+        let (bus, main) = MainProc::<SimpleStringTvf>::create(&TestSettings::default());
+        let main_task = main.run();
+
+        let mut session_settings = SessionSettings::new(vec![1]);
+        session_settings.ttl = Duration::from_millis(200);
+        let mut stub_settings = StubSettings::new(vec![String::from("SESSION")]);
+        stub_settings.set_session(session_settings);
+        let stub_proc = StubProc::<SimpleStringTvf>::create(1, bus.clone(), stub_settings);
+        Proc::<TestSessionAdaptor>::run(stub_proc, String::from("SESSION_PROC"));
+
+        let session_request = |session: &str, step: &str, token: Option<&str>| {
+            let mut request = test_request(session);
+            request.put_string(2, step);
+            if let Some(token) = token {
+                request.put_string(3, token);
+            }
+            request
+        };
+
+        let mut client = TestClientProc::<SimpleStringTvf>::create_raw(2, bus.clone());
+        let responses = client
+            .exchange(
+                &["SESSION"],
+                "SESSION",
+                vec![
+                    session_request("s1", "open", None),
+                    session_request("s1", "use", Some("token-s1")),
+                    session_request("s2", "use", Some("token-s2")),
+                ],
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            "token-s1",
+            responses[0]
+                .as_ref()
+                .unwrap()
+                .get_string(3)
+                .unwrap()
+                .as_str()
+        );
+        assert!(responses[1].is_ok());
+
+        // The token is absent for a session that was never opened
+        assert!(matches!(
+            ServiceError::decode(responses[2].as_ref().unwrap_err()).unwrap(),
+            Some(ServiceError::ProtocolError { code: 1, .. })
+        ));
+
+        // The token expired after the session TTL
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        let mut client = TestClientProc::<SimpleStringTvf>::create_raw(3, bus.clone());
+        let responses = client
+            .exchange(
+                &["SESSION"],
+                "SESSION",
+                vec![session_request("s1", "use", Some("token-s1"))],
+            )
+            .await
+            .unwrap();
+        assert!(responses[0].is_err());
+
+        bus.stop("Session end".into()).await.unwrap();
+        main_task.join().unwrap();
+    }
+
+    #[tokio::test]
+    async fn stub_span_attributes() {
+        // Processors run on their own threads, so the subscriber need to be global
+        let exporter = TestSpanExporter::default();
+        let provider = TracerProvider::builder()
+            .with_simple_exporter(exporter.clone())
+            .build();
+        let subscriber = tracing_subscriber::registry().with(
+            tracing_opentelemetry::layer().with_tracer(provider.tracer("stub_span_attributes")),
+        );
+        tracing::subscriber::set_global_default(subscriber).unwrap();
+
+        let (bus, main) = MainProc::<SimpleStringTvf>::create(&TestSettings::default());
+        let main_task = main.run();
+
+        let stub_proc = StubProc::<SimpleStringTvf>::create(
+            1,
+            bus.clone(),
+            StubSettings::new(vec![String::from("SPAN")]),
+        );
+        Proc::<TestSpanAdaptor>::run(stub_proc, String::from("SPAN_PROC"));
+
+        let mut client = TestClientProc::<SimpleStringTvf>::create_raw(2, bus.clone());
+        let responses = client
+            .exchange(&["SPAN"], "SPAN", vec![test_request("merchant")])
+            .await
+            .unwrap();
+        assert!(responses[0].is_ok());
+
+        bus.stop("Span end".into()).await.unwrap();
+        main_task.join().unwrap();
+
+        let spans = exporter.spans.lock().unwrap();
+        let trans_span = spans
+            .iter()
+            .find(|span| span.name == "prosa::stub::transaction")
+            .expect("the transaction span should be exported");
+        let attribute = |key: String| {
+            trans_span
+                .attributes
+                .iter()
+                .find(|kv| kv.key == Key::from(key.clone()))
+                .map(|kv| kv.value.clone())
+        };
+        for i in 0..MAX_SPAN_ATTRIBUTES {
+            assert_eq!(
+                Some(Value::from("merchant")),
+                attribute(format!("test.attr{}", i))
+            );
+        }
+        assert_eq!(None, attribute(format!("test.attr{}", MAX_SPAN_ATTRIBUTES)));
+        assert_eq!(
+            Some(Value::from("SPAN")),
+            attribute(String::from("stub_service"))
+        );
+
+        // The transaction span is a child of the message span
+        let msg_span = spans
+            .iter()
+            .find(|span| span.span_context.span_id() == trans_span.parent_span_id)
+            .expect("the message span should be exported");
+        assert_eq!("prosa::Msg", msg_span.name);
+    }
+
+    static BATCH_SIZES: Mutex<Vec<usize>> = Mutex::new(Vec::new());
+
+    /// Adaptor that process the batches of requests at once
+    #[derive(Adaptor)]
+    struct TestBatchAdaptor {}
+
+    impl StubAdaptor<SimpleStringTvf> for TestBatchAdaptor {
+        fn new(_proc: &StubProc<SimpleStringTvf>) -> Result<Self, Box<dyn Error>> {
+            Ok(Self {})
+        }
+
+        fn process_request(
+            &mut self,
+            _service_name: &str,
+            request: &SimpleStringTvf,
+        ) -> Result<SimpleStringTvf, ServiceError> {
+            if request.require_str(1)?.as_str() == "error" {
+                return Err(ServiceError::Internal(String::from("batch error")));
+            }
+
+            Ok(request.clone())
+        }
+
+        fn process_batch(
+            &mut self,
+            requests: &[(&str, &SimpleStringTvf)],
+            _state: &SessionStore<SimpleStringTvf>,
+        ) -> Vec<Result<SimpleStringTvf, ServiceError>> {
+            BATCH_SIZES.lock().unwrap().push(requests.len());
+            requests
+                .iter()
+                .map(|(service_name, request)| self.process_request(service_name, request))
+                .collect()
+        }
+    }
+
+    #[tokio::test]
+    async fn stub_request_batch() {
+        let mut kit = ProsaTestKit::<SimpleStringTvf>::new(&TestSettings::default()).await;
+        let mut settings = StubSettings::new(vec![String::from("BATCH")]);
+        settings.set_batch(true);
+        let stub = kit.create_proc::<StubProc<_>>(settings);
+        kit.spawn_proc::<_, TestBatchAdaptor>(stub, "BATCH_PROC");
+        let stub =
+            kit.create_proc::<StubProc<_>>(StubSettings::new(vec![String::from("UNBATCHED")]));
+        kit.spawn_proc::<_, TestBatchAdaptor>(stub, "UNBATCHED_PROC");
+
+        // The batch is processed at once, the error is returned alone and the responses together
+        let requests = vec![
+            test_request("first"),
+            test_request("error"),
+            test_request("second"),
+        ];
+        let msg_ids = kit.send_batch_as("BATCH", requests.clone()).await.unwrap();
+        assert_eq!(3, msg_ids.len());
+        let error = kit
+            .expect_response(WAIT_TIMEOUT)
+            .await
+            .unwrap()
+            .unwrap_err();
+        assert_eq!(msg_ids[1], error.get_id());
+        assert_eq!(
+            &ServiceError::Internal(String::from("batch error")),
+            error.get_err()
+        );
+        for (msg_id, request) in [(msg_ids[0], &requests[0]), (msg_ids[2], &requests[2])] {
+            let response = kit.expect_response(WAIT_TIMEOUT).await.unwrap().unwrap();
+            assert_eq!(msg_id, response.get_id());
+            assert_eq!(request, response.get_data());
+        }
+        assert_eq!(vec![3], *BATCH_SIZES.lock().unwrap());
+
+        let stats = kit.fetch_stats("BATCH_PROC", WAIT_TIMEOUT).await.unwrap();
+        assert_eq!(3, stats.received);
+        assert_eq!(2, stats.sent);
+        assert_eq!(1, stats.errors);
+
+        // Without batch consumption, the batch is split and processed request by request
+        kit.send_batch_as(
+            "UNBATCHED",
+            vec![test_request("first"), test_request("second")],
+        )
+        .await
+        .unwrap();
+        for _ in 0..2 {
+            assert!(kit.expect_response(WAIT_TIMEOUT).await.unwrap().is_ok());
+        }
+        assert_eq!(vec![3], *BATCH_SIZES.lock().unwrap());
+
+        kit.stop().await;
+    }
+
+    /// Adaptor that block its processor on the `slow` requests
+    #[derive(Adaptor)]
+    struct TestSlowAdaptor {}
+
+    impl StubAdaptor<SimpleStringTvf> for TestSlowAdaptor {
+        fn new(_proc: &StubProc<SimpleStringTvf>) -> Result<Self, Box<dyn Error>> {
+            Ok(Self {})
+        }
+
+        fn process_request(
+            &mut self,
+            _service_name: &str,
+            request: &SimpleStringTvf,
+        ) -> Result<SimpleStringTvf, ServiceError> {
+            if request.require_str(1)?.as_str() == "slow" {
+                std::thread::sleep(Duration::from_millis(300));
+            }
+
+            Ok(request.clone())
+        }
+    }
+
+    #[tokio::test]
+    async fn stub_watchdog() {
+        let mut kit = ProsaTestKit::<SimpleStringTvf>::new(&TestSettings::default()).await;
+        let mut settings = StubSettings::new(vec![String::from("SLOW")]);
+        settings.watchdog = Some(WatchdogSettings::new(Duration::from_millis(50)));
+        let stub = kit.create_proc::<StubProc<_>>(settings);
+        kit.spawn_proc::<_, TestSlowAdaptor>(stub, "SLOW_PROC");
+
+        // Fast requests are not reported
+        kit.send_request_as("SLOW", test_request("fast"))
+            .await
+            .unwrap();
+        assert!(kit.expect_response(WAIT_TIMEOUT).await.unwrap().is_ok());
+        let stats = kit.fetch_stats("SLOW_PROC", WAIT_TIMEOUT).await.unwrap();
+        assert_eq!(0, stats.watchdog_violations);
+
+        // The blocking request is detected, and still responded once the adaptor return
+        kit.send_request_as("SLOW", test_request("slow"))
+            .await
+            .unwrap();
+        assert!(kit.expect_response(WAIT_TIMEOUT).await.unwrap().is_ok());
+        let stats = kit.fetch_stats("SLOW_PROC", WAIT_TIMEOUT).await.unwrap();
+        assert_eq!(1, stats.watchdog_violations);
+        assert_eq!(2, stats.sent);
+
+        kit.stop().await;
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn stub_late_response() {
+        let kit = ProsaTestKit::<SimpleStringTvf>::new(&TestSettings::default()).await;
+        let stub = kit.create_proc::<StubProc<_>>(StubSettings::new(vec![String::from("LATE")]));
+        let stub_task = kit.spawn_proc::<_, TestSlowAdaptor>(stub, "LATE_PROC");
+        assert!(kit.wait_service("LATE", WAIT_TIMEOUT).await);
+
+        // The caller times out before the stub answers, so the stub can't return its late response
+        let call = ServiceCall::new(kit.main().get_service_table(), "LATE")
+            .timeout(Duration::from_millis(50));
+        assert!(matches!(
+            call.send(test_request("slow")).await,
+            Err(ServiceError::Timeout(..))
+        ));
+
+        // The late response is dropped, and the stub keeps serving
+        let call = ServiceCall::new(kit.main().get_service_table(), "LATE").timeout(WAIT_TIMEOUT);
+        assert_eq!(
+            test_request("fast"),
+            call.send(test_request("fast")).await.unwrap()
+        );
+        assert!(!stub_task.is_finished());
+
+        kit.stop().await;
+        assert_eq!(Ok(()), stub_task.await.unwrap());
+    }
 }
@@ -0,0 +1,310 @@
+use std::{
+    any::Any,
+    collections::HashMap,
+    fmt,
+    hash::{DefaultHasher, Hash, Hasher},
+    marker::PhantomData,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use opentelemetry::{
+    metrics::{Meter, ObservableGauge},
+    KeyValue,
+};
+use prosa_utils::msg::tvf::{Tvf, TvfExt as _};
+use serde::{Deserialize, Serialize};
+
+/// Settings of the stub sessions, to keep a state between the requests of a same session
+///
+/// ```yaml
+/// session:
+///   key_path: [1]
+///   ttl:
+///     secs: 300
+///     nanos: 0
+///   shards: 16
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct SessionSettings {
+    /// Tag path of the session key in the requests (a string or an integer field)
+    #[serde(default)]
+    pub key_path: Vec<usize>,
+    /// Duration a session is kept after its last update
+    #[serde(default = "SessionSettings::default_ttl")]
+    pub ttl: Duration,
+    /// Number of shards of the store, to limit the lock contention
+    #[serde(default = "SessionSettings::default_shards")]
+    pub shards: usize,
+}
+
+impl SessionSettings {
+    fn default_ttl() -> Duration {
+        Duration::from_secs(300)
+    }
+
+    fn default_shards() -> usize {
+        16
+    }
+
+    /// Method to create session settings with the tag path of the session key
+    pub fn new(key_path: Vec<usize>) -> SessionSettings {
+        SessionSettings {
+            key_path,
+            ..Default::default()
+        }
+    }
+}
+
+impl Default for SessionSettings {
+    fn default() -> Self {
+        SessionSettings {
+            key_path: Vec::new(),
+            ttl: Self::default_ttl(),
+            shards: Self::default_shards(),
+        }
+    }
+}
+
+/// State of a session, with its expiration
+struct SessionEntry {
+    value: Box<dyn Any + Send + Sync>,
+    expire: Instant,
+}
+
+type SessionShard = Mutex<HashMap<String, SessionEntry>>;
+
+/// Session store given by the stub processor to its adaptor, to keep a typed state per session key
+///
+/// The store is a sharded concurrent map. A session expire once the TTL elapsed since its last update (expired sessions are purged periodically by the stub processor).
+/// The number of sessions is exposed by the `prosa_stub_sessions` gauge if a meter is set.
+///
+/// Every method that depend on the time have an `_at` variant that takes the current instant, to drive the store with your own clock.
+///
+/// ```
+/// use prosa::stub::session::{SessionSettings, SessionStore};
+/// use prosa_utils::msg::simple_string_tvf::SimpleStringTvf;
+/// use prosa_utils::msg::tvf::Tvf;
+///
+/// let sessions = SessionStore::<SimpleStringTvf>::new(SessionSettings::new(vec![1]));
+///
+/// let mut request = SimpleStringTvf::default();
+/// request.put_string(1, "session-1");
+/// let key = sessions.key(&request).unwrap();
+///
+/// sessions.put(&key, String::from("token"));
+/// assert_eq!(Some(String::from("token")), sessions.get::<String>(&key));
+/// assert_eq!(None, sessions.get::<u64>(&key));
+///
+/// assert_eq!(Some(String::from("token")), sessions.remove::<String>(&key));
+/// assert!(sessions.is_empty());
+/// ```
+pub struct SessionStore<M> {
+    settings: SessionSettings,
+    shards: Arc<Vec<SessionShard>>,
+    _size_gauge: Option<ObservableGauge<u64>>,
+    phantom: PhantomData<fn(&M)>,
+}
+
+impl<M> SessionStore<M>
+where
+    M: Tvf + Default + fmt::Debug + Clone,
+{
+    /// Method to create a session store with its settings
+    pub fn new(settings: SessionSettings) -> SessionStore<M> {
+        let shards = (0..settings.shards.max(1))
+            .map(|_| Mutex::new(HashMap::new()))
+            .collect();
+        SessionStore {
+            settings,
+            shards: Arc::new(shards),
+            _size_gauge: None,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Setter of the meter used to expose the number of sessions (`prosa_stub_sessions` gauge)
+    pub fn meter(mut self, meter: &Meter, attributes: Vec<KeyValue>) -> Self {
+        let weak_shards = Arc::downgrade(&self.shards);
+        self._size_gauge = Some(
+            meter
+                .u64_observable_gauge("prosa_stub_sessions")
+                .with_description("Number of sessions kept by the stub")
+                .with_callback(move |observer| {
+                    if let Some(shards) = weak_shards.upgrade() {
+                        observer.observe(Self::shards_len(&shards) as u64, &attributes);
+                    }
+                })
+                .init(),
+        );
+        self
+    }
+
+    /// Getter of the session settings
+    pub fn get_settings(&self) -> &SessionSettings {
+        &self.settings
+    }
+
+    /// Method to extract the session key of a request from the configured tag path.
+    /// Return `None` if the request don't have a string or an integer at this path
+    pub fn key(&self, request: &M) -> Option<String> {
+        let path = self.settings.key_path.as_slice();
+        if path.is_empty() {
+            return None;
+        }
+
+        request
+            .require_str(path)
+            .map(|key| key.into_owned())
+            .or_else(|_| request.require_unsigned(path).map(|key| key.to_string()))
+            .or_else(|_| request.require_signed(path).map(|key| key.to_string()))
+            .ok()
+    }
+
+    /// Method to get the shard of a session key
+    fn shard(&self, key: &str) -> &SessionShard {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        &self.shards[hasher.finish() as usize % self.shards.len()]
+    }
+
+    fn shards_len(shards: &[SessionShard]) -> usize {
+        shards.iter().map(|shard| shard.lock().unwrap().len()).sum()
+    }
+
+    /// Getter of the number of sessions (including the expired ones that are not purged yet)
+    pub fn len(&self) -> usize {
+        Self::shards_len(&self.shards)
+    }
+
+    /// Method to know if the store don't have any session
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Getter of the state of a session. Return `None` if the session doesn't exist, is expired, or doesn't have a state of this type
+    pub fn get<T>(&self, key: &str) -> Option<T>
+    where
+        T: Clone + 'static,
+    {
+        self.get_at(key, Instant::now())
+    }
+
+    /// Same as [`SessionStore::get`] at a given instant
+    pub fn get_at<T>(&self, key: &str, now: Instant) -> Option<T>
+    where
+        T: Clone + 'static,
+    {
+        let mut shard = self.shard(key).lock().unwrap();
+        if shard.get(key).is_some_and(|entry| entry.expire <= now) {
+            shard.remove(key);
+            return None;
+        }
+
+        shard
+            .get(key)
+            .and_then(|entry| entry.value.downcast_ref::<T>())
+            .cloned()
+    }
+
+    /// Setter of the state of a session. The session TTL start again
+    pub fn put<T>(&self, key: &str, value: T)
+    where
+        T: Any + Send + Sync,
+    {
+        self.put_at(key, value, Instant::now())
+    }
+
+    /// Same as [`SessionStore::put`] at a given instant
+    pub fn put_at<T>(&self, key: &str, value: T, now: Instant)
+    where
+        T: Any + Send + Sync,
+    {
+        self.shard(key).lock().unwrap().insert(
+            key.to_string(),
+            SessionEntry {
+                value: Box::new(value),
+                expire: now + self.settings.ttl,
+            },
+        );
+    }
+
+    /// Method to remove a session. Return its state if it has this type
+    pub fn remove<T>(&self, key: &str) -> Option<T>
+    where
+        T: 'static,
+    {
+        self.shard(key)
+            .lock()
+            .unwrap()
+            .remove(key)
+            .and_then(|entry| entry.value.downcast::<T>().ok())
+            .map(|value| *value)
+    }
+
+    /// Method to remove the expired sessions
+    pub fn purge(&self) {
+        self.purge_at(Instant::now())
+    }
+
+    /// Same as [`SessionStore::purge`] at a given instant
+    pub fn purge_at(&self, now: Instant) {
+        for shard in self.shards.iter() {
+            shard.lock().unwrap().retain(|_, entry| entry.expire > now);
+        }
+    }
+}
+
+impl<M> fmt::Debug for SessionStore<M> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SessionStore")
+            .field("settings", &self.settings)
+            .field("shards", &self.shards.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use prosa_utils::msg::simple_string_tvf::SimpleStringTvf;
+
+    use super::*;
+
+    #[test]
+    fn session_store_expiry() {
+        let mut settings = SessionSettings::new(vec![1]);
+        settings.ttl = Duration::from_secs(10);
+        settings.shards = 4;
+        let sessions = SessionStore::<SimpleStringTvf>::new(settings);
+
+        let mut request = SimpleStringTvf::default();
+        assert_eq!(None, sessions.key(&request));
+        request.put_unsigned(1, 42);
+        assert_eq!(Some(String::from("42")), sessions.key(&request));
+
+        let now = Instant::now();
+        sessions.put_at("42", 1u64, now);
+        sessions.put_at("43", 2u64, now + Duration::from_secs(5));
+        assert_eq!(
+            Some(1u64),
+            sessions.get_at("42", now + Duration::from_secs(9))
+        );
+
+        // The session expire once its TTL elapsed
+        assert_eq!(
+            None,
+            sessions.get_at::<u64>("42", now + Duration::from_secs(10))
+        );
+        assert_eq!(1, sessions.len());
+
+        // An update refresh the TTL
+        sessions.put_at("43", 3u64, now + Duration::from_secs(12));
+        sessions.purge_at(now + Duration::from_secs(20));
+        assert_eq!(
+            Some(3u64),
+            sessions.get_at("43", now + Duration::from_secs(20))
+        );
+        sessions.purge_at(now + Duration::from_secs(22));
+        assert!(sessions.is_empty());
+    }
+}
@@ -0,0 +1,503 @@
+//! Test harness to write processor unit tests without a full ProSA assembly
+//!
+//! The [`ProsaTestKit`] run a real main task on the current runtime, so the processors under test and the test itself share the tokio clock.
+//! With a paused clock (`#[tokio::test(start_paused = true)]`), timers of the processors can be driven with [`ProsaTestKit::advance`].
+//!
+//! ```
+//! use std::time::Duration;
+//! use prosa::core::{msg::Msg, settings::Settings};
+//! use prosa::stub::{adaptor::StubParotAdaptor, proc::{StubProc, StubSettings}};
+//! use prosa::testing::ProsaTestKit;
+//! use prosa_utils::msg::{simple_string_tvf::SimpleStringTvf, tvf::Tvf};
+//!
+//! async fn stub_echo<S: Settings>(settings: &S) {
+//!     let mut kit = ProsaTestKit::<SimpleStringTvf>::new(settings).await;
+//!     let stub = kit.create_proc::<StubProc<_>>(StubSettings::new(vec![String::from("ECHO")]));
+//!     kit.spawn_proc::<_, StubParotAdaptor>(stub, "STUB");
+//!
+//!     let mut request = SimpleStringTvf::default();
+//!     request.put_string(1, "ProSA");
+//!     kit.send_request_as("ECHO", request.clone()).await.unwrap();
+//!     let response = kit.expect_response(Duration::from_secs(1)).await.unwrap().unwrap();
+//!     assert_eq!(&request, response.get_data());
+//!
+//!     kit.stop().await;
+//! }
+//! ```
+use std::{
+    collections::VecDeque,
+    error::Error,
+    fmt::Debug,
+    sync::atomic::{AtomicU32, Ordering},
+    time::Duration,
+};
+
+use prosa_utils::msg::tvf::Tvf;
+use tokio::{sync::mpsc, task::JoinHandle};
+
+use crate::core::{
+    adaptor::Adaptor,
+    error::BusError,
+    main::{Main, MainProc, MainRunnable as _},
+    msg::{ErrorMsg, InternalMsg, Msg as _, RequestMsg, ResponseMsg},
+    proc::{Proc, ProcConfig, ProcParam},
+    service::ServiceError,
+    settings::Settings,
+};
+use crate::event::stats::{stats_service_name, ProcStats, StatsSummary};
+
+/// Processor id used by the test kit to send its requests
+pub const TEST_KIT_PROC_ID: u32 = u32::MAX;
+
+/// Default duration the test kit wait for a service to be available
+pub const TEST_KIT_SERVICE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Method to receive the next message of a queue that is not a service table update, within a timeout
+async fn recv_msg<M>(
+    queue: &mut mpsc::Receiver<InternalMsg<M>>,
+    timeout: Duration,
+) -> Option<InternalMsg<M>>
+where
+    M: Sized + Clone + Tvf,
+{
+    tokio::time::timeout(timeout, async {
+        loop {
+            match queue.recv().await {
+                Some(InternalMsg::Service(_)) => continue,
+                msg => return msg,
+            }
+        }
+    })
+    .await
+    .ok()
+    .flatten()
+}
+
+/// Method to take the pending messages of a queue that are not service table updates
+fn drain_msgs<M>(queue: &mut mpsc::Receiver<InternalMsg<M>>) -> Vec<InternalMsg<M>>
+where
+    M: Sized + Clone + Tvf,
+{
+    let mut msgs = Vec::new();
+    while let Ok(msg) = queue.try_recv() {
+        if !matches!(msg, InternalMsg::Service(_)) {
+            msgs.push(msg);
+        }
+    }
+
+    msgs
+}
+
+/// Test kit that run a ProSA main task on the current runtime, to test processors without a full assembly
+///
+/// The test kit is itself declared as a processor ([`TEST_KIT_PROC_ID`]) to send requests to the processors under test and receive their responses.
+/// Services used by the processors under test can be faked with [`ProsaTestKit::fake_service`].
+pub struct ProsaTestKit<M>
+where
+    M: Sized + Clone + Tvf,
+{
+    main: Main<M>,
+    main_task: JoinHandle<Result<(), BusError>>,
+    proc: ProcParam<M>,
+    queue: mpsc::Receiver<InternalMsg<M>>,
+    responses: VecDeque<ResponseMsg<M>>,
+    next_proc_id: AtomicU32,
+    next_msg_id: u64,
+}
+
+impl<M> ProsaTestKit<M>
+where
+    M: Sized + Clone + Debug + Tvf + Default + 'static + std::marker::Send + std::marker::Sync,
+{
+    /// Method to create a test kit with the ProSA settings, and run its main task on the current runtime
+    pub async fn new<S: Settings>(settings: &S) -> ProsaTestKit<M> {
+        let (main, main_proc) = MainProc::<M>::create(settings);
+        let main_task = main_proc.spawn();
+        let (queue_tx, queue) = mpsc::channel(2048);
+        let proc = ProcParam::new(TEST_KIT_PROC_ID, queue_tx, main.clone());
+        proc.add_proc()
+            .await
+            .expect("The test kit can't declare its processor");
+
+        ProsaTestKit {
+            main,
+            main_task,
+            proc,
+            queue,
+            responses: VecDeque::new(),
+            next_proc_id: AtomicU32::new(1),
+            next_msg_id: 0,
+        }
+    }
+
+    /// Getter of the main bus, to create processors
+    pub fn main(&self) -> &Main<M> {
+        &self.main
+    }
+
+    /// Method to allocate a new processor id
+    pub fn next_proc_id(&self) -> u32 {
+        self.next_proc_id.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// Method to create a processor to test from its settings, with a new processor id
+    pub fn create_proc<P>(&self, settings: P::Settings) -> P
+    where
+        P: ProcConfig<M>,
+    {
+        P::create(self.next_proc_id(), self.main.clone(), settings)
+    }
+
+    /// Method to run a processor with its adaptor on the current runtime.
+    /// Return the task of the processor, that give its error as a string
+    pub fn spawn_proc<P, A>(&self, mut proc: P, name: &str) -> JoinHandle<Result<(), String>>
+    where
+        P: Proc<A> + std::marker::Send + 'static,
+        A: Adaptor,
+    {
+        let name = name.to_string();
+        tokio::spawn(async move {
+            let _panic_guard = proc.get_panic_guard();
+            proc.internal_run(name)
+                .await
+                .map_err(|e: Box<dyn Error>| e.to_string())
+        })
+    }
+
+    /// Method to wait until a service is available on the main bus.
+    /// Return `false` if the service is still unavailable after the timeout
+    pub async fn wait_service(&self, name: &str, timeout: Duration) -> bool {
+        tokio::time::timeout(timeout, async {
+            while !self.main.get_service_table().exist_proc_service(name) {
+                tokio::time::sleep(Duration::from_millis(1)).await;
+            }
+        })
+        .await
+        .is_ok()
+    }
+
+    /// Method to declare a fake service that record its requests, for the test to reply to them
+    pub async fn fake_service(&self, name: &str) -> FakeService<M> {
+        let (queue_tx, queue) = mpsc::channel(2048);
+        let proc = ProcParam::new(self.next_proc_id(), queue_tx, self.main.clone());
+        proc.add_proc()
+            .await
+            .expect("The test kit can't declare the fake service processor");
+        proc.add_service_proc(vec![name.to_string()])
+            .await
+            .expect("The test kit can't declare the fake service");
+        assert!(
+            self.wait_service(name, TEST_KIT_SERVICE_TIMEOUT).await,
+            "The fake service {} is not available",
+            name
+        );
+
+        FakeService {
+            name: name.to_string(),
+            proc,
+            queue,
+            requests: Vec::new(),
+        }
+    }
+
+    /// Method to send a request to a service as the test kit processor, once the service is available.
+    /// Return the id of the sent request, its response can be retrieved with [`ProsaTestKit::expect_response`]
+    pub async fn send_request_as(&mut self, service: &str, msg: M) -> Result<u64, ServiceError> {
+        if !self.wait_service(service, TEST_KIT_SERVICE_TIMEOUT).await {
+            return Err(ServiceError::UnknownService(service.to_string()));
+        }
+
+        self.next_msg_id += 1;
+        let msg_id = self.next_msg_id;
+        let request = self.proc.new_request(msg_id, service.to_string(), msg);
+        self.main
+            .get_service_table()
+            .get_proc_service(service, msg_id)
+            .ok_or_else(|| ServiceError::UnknownService(service.to_string()))?
+            .send_request(request)
+            .await
+            .map_err(|_| ServiceError::Unavailable(service.to_string(), None))?;
+        Ok(msg_id)
+    }
+
+    /// Method to send a batch of requests to a service as the test kit processor, once the service is available.
+    /// Return the ids of the sent requests, their responses can be retrieved with [`ProsaTestKit::expect_response`]
+    pub async fn send_batch_as(
+        &mut self,
+        service: &str,
+        msgs: Vec<M>,
+    ) -> Result<Vec<u64>, ServiceError> {
+        if !self.wait_service(service, TEST_KIT_SERVICE_TIMEOUT).await {
+            return Err(ServiceError::UnknownService(service.to_string()));
+        }
+
+        let first_msg_id = self.next_msg_id + 1;
+        let requests: Vec<RequestMsg<M>> = msgs
+            .into_iter()
+            .map(|msg| {
+                self.next_msg_id += 1;
+                self.proc
+                    .new_request(self.next_msg_id, service.to_string(), msg)
+            })
+            .collect();
+        let msg_ids = (first_msg_id..=self.next_msg_id).collect();
+        self.main
+            .get_service_table()
+            .get_proc_service(service, first_msg_id)
+            .ok_or_else(|| ServiceError::UnknownService(service.to_string()))?
+            .send_batch(requests)
+            .await
+            .map_err(|_| ServiceError::Unavailable(service.to_string(), None))?;
+        Ok(msg_ids)
+    }
+
+    /// Method to wait the next response (or error) received by the test kit processor.
+    /// Batches of responses are returned one response at a time.
+    /// Return `None` if nothing is received within the timeout
+    pub async fn expect_response(
+        &mut self,
+        timeout: Duration,
+    ) -> Option<Result<ResponseMsg<M>, ErrorMsg<M>>> {
+        if let Some(response) = self.responses.pop_front() {
+            return Some(Ok(response));
+        }
+
+        match recv_msg(&mut self.queue, timeout).await? {
+            InternalMsg::Response(response) => Some(Ok(response)),
+            InternalMsg::ResponseBatch(batch) => {
+                self.responses.extend(batch);
+                self.responses.pop_front().map(Ok)
+            }
+            InternalMsg::Error(error) => Some(Err(error)),
+            msg => panic!("Unexpected message received by the test kit: {:?}", msg),
+        }
+    }
+
+    /// Method to fetch the processing statistics of a processor from its statistics service (see [`stats_service_name`]).
+    /// Return `None` if the processor doesn't respond within the timeout
+    pub async fn fetch_stats(
+        &mut self,
+        proc_name: &str,
+        timeout: Duration,
+    ) -> Option<StatsSummary> {
+        self.send_request_as(&stats_service_name(proc_name), M::default())
+            .await
+            .ok()?;
+        match self.expect_response(timeout).await? {
+            Ok(response) => ProcStats::summary_from_tvf(response.get_data()).ok(),
+            Err(_) => None,
+        }
+    }
+
+    /// Method to take all the messages received by the test kit processor and not consumed yet (except the service table updates)
+    pub fn drain(&mut self) -> Vec<InternalMsg<M>> {
+        drain_msgs(&mut self.queue)
+    }
+
+    /// Method to let the tasks of the current runtime process their pending messages
+    pub async fn settle(&self) {
+        for _ in 0..64 {
+            tokio::task::yield_now().await;
+        }
+    }
+
+    /// Method to advance the paused tokio clock, and let the tasks process the expired timers
+    ///
+    /// The clock must be paused (`#[tokio::test(start_paused = true)]` or [`tokio::time::pause`]), otherwise this method panics.
+    pub async fn advance(&self, duration: Duration) {
+        tokio::time::advance(duration).await;
+        self.settle().await;
+    }
+
+    /// Method to stop the ProSA main task (and the processors under test), and wait for it
+    pub async fn stop(self) {
+        let _ = self.proc.remove_proc().await;
+        self.main
+            .stop(String::from("ProSA test kit end"))
+            .await
+            .expect("The test kit can't stop the main task");
+        self.main_task
+            .await
+            .expect("The main task panicked")
+            .expect("The main task failed");
+    }
+}
+
+/// Fake service declared by the [`ProsaTestKit`], that record the requests it receives for the test to reply to them
+pub struct FakeService<M>
+where
+    M: Sized + Clone + Tvf,
+{
+    name: String,
+    proc: ProcParam<M>,
+    queue: mpsc::Receiver<InternalMsg<M>>,
+    requests: Vec<M>,
+}
+
+impl<M> FakeService<M>
+where
+    M: Sized + Clone + Debug + Tvf + Default + 'static + std::marker::Send + std::marker::Sync,
+{
+    /// Getter of the fake service name
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Getter of the data of all the requests received by the fake service
+    pub fn requests(&self) -> &[M] {
+        &self.requests
+    }
+
+    /// Method to wait the next request received by the fake service, to reply to it with [`RequestMsg::return_to_sender`] or [`RequestMsg::return_error_to_sender`].
+    /// Return `None` if nothing is received within the timeout
+    pub async fn expect_request(&mut self, timeout: Duration) -> Option<RequestMsg<M>> {
+        match recv_msg(&mut self.queue, timeout).await? {
+            InternalMsg::Request(request) => {
+                self.requests.push(request.get_data().clone());
+                Some(request)
+            }
+            msg => panic!(
+                "Unexpected message received by the fake service {}: {:?}",
+                self.name, msg
+            ),
+        }
+    }
+
+    /// Method to wait the next request received by the fake service and reply to it with the result of the reply function.
+    /// Return `false` if nothing is received within the timeout
+    pub async fn reply<F>(&mut self, timeout: Duration, reply: F) -> bool
+    where
+        F: FnOnce(&M) -> Result<M, ServiceError>,
+    {
+        if let Some(request) = self.expect_request(timeout).await {
+            let _ = match reply(request.get_data()) {
+                Ok(response) => request.return_to_sender(response).await,
+                Err(err) => request.return_error_to_sender(None, err).await,
+            };
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Method to take all the requests received by the fake service and not consumed yet
+    pub fn drain(&mut self) -> Vec<RequestMsg<M>> {
+        drain_msgs(&mut self.queue)
+            .into_iter()
+            .filter_map(|msg| match msg {
+                InternalMsg::Request(request) => {
+                    self.requests.push(request.get_data().clone());
+                    Some(request)
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Method to remove the fake service from the main bus
+    pub async fn remove(self) -> Result<(), BusError> {
+        self.proc.remove_proc().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate self as prosa;
+
+    use std::{env, fs};
+
+    use prosa_macros::settings;
+    use prosa_utils::msg::simple_string_tvf::SimpleStringTvf;
+    use serde::Serialize;
+
+    use crate::stub::{
+        adaptor::StubParotAdaptor,
+        proc::{StubMode, StubProc, StubSettings},
+    };
+
+    use super::*;
+
+    const WAIT_TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// Dummy settings
+    #[settings]
+    #[derive(Default, Debug, Serialize)]
+    struct TestSettings {}
+
+    fn test_msg(value: &str) -> SimpleStringTvf {
+        let mut msg = SimpleStringTvf::default();
+        msg.put_string(1, value);
+        msg
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_kit_fake_service() {
+        let fixture_dir = env::temp_dir().join("prosa_test_kit_fake_service");
+        let _ = fs::remove_dir_all(&fixture_dir);
+
+        let mut kit = ProsaTestKit::<SimpleStringTvf>::new(&TestSettings::default()).await;
+        let mut target = kit.fake_service("TARGET").await;
+        assert_eq!("TARGET", target.name());
+
+        // Stub under test that forward its requests to the fake service
+        let mut settings = StubSettings::new(vec![String::from("RECORD")]);
+        settings.set_mode(StubMode::Record {
+            target_service: String::from("TARGET"),
+            output_dir: fixture_dir.clone(),
+            timeout: Duration::from_secs(2),
+        });
+        let stub = kit.create_proc::<StubProc<_>>(settings);
+        kit.spawn_proc::<_, StubParotAdaptor>(stub, "RECORD_PROC");
+
+        // The fake service reply to the forwarded request
+        let msg_id = kit
+            .send_request_as("RECORD", test_msg("first"))
+            .await
+            .unwrap();
+        assert!(
+            target
+                .reply(WAIT_TIMEOUT, |request| {
+                    let mut response = request.clone();
+                    response.put_string(2, "target");
+                    Ok(response)
+                })
+                .await
+        );
+        let response = kit.expect_response(WAIT_TIMEOUT).await.unwrap().unwrap();
+        assert_eq!(msg_id, response.get_id());
+        assert_eq!(
+            "target",
+            response.get_data().get_string(2).unwrap().as_str()
+        );
+
+        // Without reply, the stub time out once the clock reach its timeout
+        kit.send_request_as("RECORD", test_msg("second"))
+            .await
+            .unwrap();
+        let _pending_request = target.expect_request(WAIT_TIMEOUT).await.unwrap();
+        kit.advance(Duration::from_secs(1)).await;
+        assert!(kit.drain().is_empty());
+        kit.advance(Duration::from_secs(1)).await;
+        match kit.drain().as_slice() {
+            [InternalMsg::Error(error)] => assert_eq!(
+                &ServiceError::Timeout(String::from("TARGET"), 2000),
+                error.get_err()
+            ),
+            msgs => panic!("Unexpected messages {:?}", msgs),
+        }
+
+        assert_eq!(
+            vec![test_msg("first"), test_msg("second")],
+            target.requests()
+        );
+        assert!(target.drain().is_empty());
+        assert_eq!(
+            Err(ServiceError::UnknownService(String::from("UNKNOWN"))),
+            kit.send_request_as("UNKNOWN", test_msg("unknown")).await
+        );
+
+        target.remove().await.unwrap();
+        kit.stop().await;
+        fs::remove_dir_all(&fixture_dir).unwrap();
+    }
+}
@@ -1,12 +1,161 @@
 use quote::quote;
 
+/// Lifecycle hooks requested with the `#[adaptor(...)]` attribute
+#[derive(Default)]
+struct AdaptorParams {
+    async_init: bool,
+    async_terminate: bool,
+    reload: Option<syn::Type>,
+}
+
+impl AdaptorParams {
+    fn parse_attrs(&mut self, attrs: &[syn::Attribute]) -> syn::parse::Result<()> {
+        for attr in attrs.iter().filter(|a| a.path().is_ident("adaptor")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("async_init") {
+                    self.async_init = true;
+                    Ok(())
+                } else if meta.path.is_ident("async_terminate") {
+                    self.async_terminate = true;
+                    Ok(())
+                } else if meta.path.is_ident("reload") {
+                    self.reload = Some(meta.value()?.parse()?);
+                    Ok(())
+                } else {
+                    Err(meta.error(
+                        "unknown adaptor args, expected `async_init`, `async_terminate` or `reload = ConfigType`",
+                    ))
+                }
+            })?;
+        }
+
+        Ok(())
+    }
+}
+
 /// Implementation of the ProSA Adaptor Derive macro
 pub(crate) fn adaptor_impl(ast: syn::DeriveInput) -> syn::parse::Result<proc_macro2::TokenStream> {
     let name = &ast.ident;
     let generics = &ast.generics;
+
+    let mut params = AdaptorParams::default();
+    params.parse_attrs(&ast.attrs)?;
+
+    let async_init = if params.async_init {
+        quote! {
+            async fn async_init(&mut self, proc: &(dyn prosa::core::proc::ProcBusParam + std::marker::Sync)) -> std::result::Result<(), std::boxed::Box<dyn std::error::Error>> {
+                self.on_init(proc).await
+            }
+        }
+    } else {
+        proc_macro2::TokenStream::new()
+    };
+
+    let async_terminate = if params.async_terminate {
+        quote! {
+            async fn async_terminate(&mut self) {
+                self.on_terminate().await
+            }
+        }
+    } else {
+        proc_macro2::TokenStream::new()
+    };
+
+    let reload = if let Some(config) = &params.reload {
+        quote! {
+            fn reload_config<S>(&mut self, settings: &S) -> std::result::Result<bool, prosa::core::adaptor::AdaptorError>
+            where
+                S: prosa::core::proc::ProcSettings,
+            {
+                let config: #config = settings.get_adaptor_config()?;
+                prosa::core::adaptor::AdaptorConfigReload::<#config>::reload(self, config)?;
+                Ok(true)
+            }
+        }
+    } else {
+        proc_macro2::TokenStream::new()
+    };
+
     Ok(quote! {
         impl #generics prosa::core::adaptor::Adaptor for #name #generics {
             fn terminate(&mut self) {}
+            #async_init
+            #async_terminate
+            #reload
         }
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expand(item: proc_macro2::TokenStream) -> syn::parse::Result<syn::ItemImpl> {
+        syn::parse2(adaptor_impl(syn::parse2(item).unwrap())?)
+    }
+
+    fn impl_fns(item_impl: &syn::ItemImpl) -> Vec<String> {
+        item_impl
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                syn::ImplItem::Fn(item_fn) => Some(item_fn.sig.ident.to_string()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn adaptor_default() {
+        let item_impl = expand(quote! { struct TestAdaptor<M> { msg: M } }).unwrap();
+        assert_eq!(vec!["terminate"], impl_fns(&item_impl));
+        assert_eq!(1, item_impl.generics.params.len());
+    }
+
+    #[test]
+    fn adaptor_lifecycle() {
+        let item_impl = expand(quote! {
+            #[derive(Debug)]
+            #[adaptor(async_init, async_terminate)]
+            struct TestAdaptor {}
+        })
+        .unwrap();
+        assert_eq!(
+            vec!["terminate", "async_init", "async_terminate"],
+            impl_fns(&item_impl)
+        );
+
+        // The lifecycle overrides call the adaptor hooks
+        let item_impl = quote::ToTokens::to_token_stream(&item_impl).to_string();
+        assert!(item_impl.contains("self . on_init (proc) . await"));
+        assert!(item_impl.contains("self . on_terminate () . await"));
+
+        let item_impl = expand(quote! {
+            #[adaptor(async_terminate)]
+            #[adaptor(reload = TestConfig)]
+            struct TestAdaptor {}
+        })
+        .unwrap();
+        assert_eq!(
+            vec!["terminate", "async_terminate", "reload_config"],
+            impl_fns(&item_impl)
+        );
+    }
+
+    #[test]
+    fn adaptor_unknown_args() {
+        let err = expand(quote! {
+            #[adaptor(foo)]
+            struct TestAdaptor {}
+        })
+        .unwrap_err();
+        assert!(err.to_string().contains("unknown adaptor args"));
+
+        // The reload config type is required
+        assert!(expand(quote! {
+            #[adaptor(reload)]
+            struct TestAdaptor {}
+        })
+        .is_err());
+    }
+}
@@ -1,15 +1,95 @@
 use quote::quote;
-use syn::parse::Parser;
 use syn::spanned::Spanned;
+use syn::Token;
+use syn::{parse::Parser, punctuated::Punctuated};
 
 use crate::add_angle_bracketed;
 
+#[derive(Debug, Default)]
+struct IoParams {
+    length_prefixed: Option<syn::LitInt>,
+    metrics: bool,
+}
+
+impl IoParams {
+    fn parse_attr_args(
+        &mut self,
+        args: &Punctuated<syn::Meta, Token![,]>,
+    ) -> syn::parse::Result<()> {
+        for meta in args {
+            if let syn::Meta::NameValue(v) = meta {
+                if !v.path.segments.is_empty() {
+                    let name = &v.path.segments.first().unwrap().ident;
+                    if name == "length_prefixed" {
+                        if let syn::Expr::Lit(syn::ExprLit {
+                            lit: syn::Lit::Int(i),
+                            ..
+                        }) = &v.value
+                        {
+                            if matches!(i.base10_parse::<usize>()?, 1 | 2 | 4) {
+                                self.length_prefixed = Some(i.clone());
+                            } else {
+                                return Err(syn::Error::new(
+                                    i.span(),
+                                    "expected 1, 2 or 4 for io args length_prefixed",
+                                ));
+                            }
+                        } else {
+                            return Err(syn::Error::new(
+                                v.value.span(),
+                                "expected int value for io args length_prefixed (header size)",
+                            ));
+                        }
+                    } else {
+                        return Err(syn::Error::new(
+                            name.span(),
+                            format!("unknown io args value {}", name),
+                        ));
+                    }
+                } else {
+                    return Err(syn::Error::new(v.span(), "no key define for the value"));
+                }
+            } else if let syn::Meta::Path(path) = meta {
+                if path.is_ident("metrics") {
+                    self.metrics = true;
+                } else {
+                    return Err(syn::Error::new(path.span(), "unknown io args"));
+                }
+            } else {
+                return Err(syn::Error::new(meta.span(), "unexpected io expression"));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Fields of the struct used by the IO (stream and buffer can be renamed with `#[io(stream)]` and `#[io(buffer)]`)
+struct IoFields {
+    stream: syn::Ident,
+    buffer: syn::Ident,
+    io_type: syn::Ident,
+    derive_debug: bool,
+}
+
+impl IoFields {
+    /// Method to know if the field is handled by the IO (not initialized with its default value)
+    fn is_io_field(&self, ident: &syn::Ident) -> bool {
+        *ident == self.stream
+            || *ident == self.buffer
+            || ident == "addr"
+            || ident == "socket_id"
+            || ident == "io_stats"
+            || ident == "io_meters"
+    }
+}
+
 /// Add the Generic type IO to specify the net object
-fn add_io_generic(generics: &mut syn::Generics) -> syn::parse::Result<()> {
+fn add_io_generic(generics: &mut syn::Generics, io_type: &syn::Ident) -> syn::parse::Result<()> {
     // Check if a generic IO is already present
     let io_predicate = generics.params.iter().position(|p| {
         if let syn::GenericParam::Type(tp) = p {
-            tp.ident == "IO"
+            tp.ident == *io_type
         } else {
             false
         }
@@ -17,17 +97,18 @@ fn add_io_generic(generics: &mut syn::Generics) -> syn::parse::Result<()> {
 
     // Add the IO generic if it's not present
     if io_predicate.is_none() {
-        let ident = syn::Ident::new("IO", proc_macro2::Span::call_site());
         generics
             .params
-            .push(syn::GenericParam::Type(syn::TypeParam::from(ident)));
+            .push(syn::GenericParam::Type(syn::TypeParam::from(
+                io_type.clone(),
+            )));
     }
 
     // Add a where clause to specify IO type
     generics.make_where_clause();
     if let Some(ref mut where_clause) = generics.where_clause {
         let io_where: syn::WherePredicate = syn::parse2(
-            quote! { IO: 'static + tokio::io::AsyncReadExt + tokio::io::AsyncWriteExt + std::marker::Unpin + std::marker::Send },
+            quote! { #io_type: 'static + tokio::io::AsyncReadExt + tokio::io::AsyncWriteExt + std::marker::Unpin + std::marker::Send },
         )?;
         where_clause.predicates.push(io_where);
     } else {
@@ -40,15 +121,120 @@ fn add_io_generic(generics: &mut syn::Generics) -> syn::parse::Result<()> {
     Ok(())
 }
 
-fn generate_struct(mut item_struct: syn::ItemStruct) -> syn::parse::Result<syn::ItemStruct> {
-    // Add mandatory fields
+/// Add a predicate to the where clause of the generics
+fn with_predicate(
+    generics: &syn::Generics,
+    predicate: proc_macro2::TokenStream,
+) -> syn::parse::Result<syn::Generics> {
+    let mut generics = generics.clone();
+    generics
+        .make_where_clause()
+        .predicates
+        .push(syn::parse2(predicate)?);
+    Ok(generics)
+}
+
+/// Take the `#[io(stream)]` or `#[io(buffer)]` attribute of a field
+fn take_field_attr(field: &mut syn::Field) -> syn::parse::Result<Option<syn::Ident>> {
+    let mut io_attr = None;
+    let mut attrs = Vec::with_capacity(field.attrs.len());
+    for attr in field.attrs.drain(..) {
+        if attr.path().is_ident("io") {
+            let ident: syn::Ident = attr.parse_args()?;
+            if ident != "stream" && ident != "buffer" {
+                return Err(syn::Error::new(
+                    ident.span(),
+                    format!(
+                        "unknown io field attribute {}, expected stream or buffer",
+                        ident
+                    ),
+                ));
+            }
+            io_attr = Some(ident);
+        } else {
+            attrs.push(attr);
+        }
+    }
+
+    field.attrs = attrs;
+    Ok(io_attr)
+}
+
+/// Check if the struct derive Debug
+fn has_derive_debug(attrs: &[syn::Attribute]) -> bool {
+    attrs.iter().any(|attr| {
+        attr.path().is_ident("derive")
+            && attr
+                .parse_args_with(Punctuated::<syn::Path, Token![,]>::parse_terminated)
+                .is_ok_and(|paths| {
+                    paths
+                        .iter()
+                        .any(|p| p.segments.last().is_some_and(|s| s.ident == "Debug"))
+                })
+    })
+}
+
+/// Convert the struct name to snake case to name its metrics
+fn to_snake_case(name: &str) -> String {
+    let mut snake_name = String::with_capacity(name.len() + 4);
+    for (i, c) in name.chars().enumerate() {
+        if c.is_uppercase() {
+            if i > 0 {
+                snake_name.push('_');
+            }
+            snake_name.extend(c.to_lowercase());
+        } else {
+            snake_name.push(c);
+        }
+    }
+    snake_name
+}
+
+fn generate_struct(
+    mut item_struct: syn::ItemStruct,
+    io_params: &IoParams,
+) -> syn::parse::Result<(syn::ItemStruct, IoFields)> {
+    let mut stream = None;
+    let mut buffer = None;
+    let mut io_type = syn::Ident::new("IO", proc_macro2::Span::call_site());
+    let derive_debug = has_derive_debug(&item_struct.attrs);
+
     if let syn::Fields::Named(ref mut fields) = item_struct.fields {
+        // Find the stream and buffer fields declared by the struct
+        for field in fields.named.iter_mut() {
+            let io_attr = take_field_attr(field)?;
+            if let Some(ident) = &field.ident {
+                if io_attr.as_ref().is_some_and(|a| a == "stream")
+                    || (io_attr.is_none() && ident == "stream")
+                {
+                    stream = Some(ident.clone());
+
+                    // The stream type can be a generic of the struct
+                    if let syn::Type::Path(type_path) = &field.ty {
+                        if let Some(type_ident) = type_path.path.get_ident() {
+                            if item_struct.generics.params.iter().any(|p| {
+                                matches!(p, syn::GenericParam::Type(tp) if tp.ident == *type_ident)
+                            }) {
+                                io_type = type_ident.clone();
+                            }
+                        }
+                    }
+                } else if io_attr.as_ref().is_some_and(|a| a == "buffer")
+                    || (io_attr.is_none() && ident == "buffer")
+                {
+                    buffer = Some(ident.clone());
+                }
+            }
+        }
+
         // Add the stream field to handle a net object
-        fields.named.push(
-            syn::Field::parse_named
-                .parse2(quote! { stream: IO })
-                .unwrap(),
-        );
+        if stream.is_none() {
+            fields.named.push(
+                syn::Field::parse_named
+                    .parse2(quote! { stream: #io_type })
+                    .unwrap(),
+            );
+        }
         // Add the Address field that is the remote address
         fields.named.push(
             syn::Field::parse_named
@@ -56,87 +242,332 @@ fn generate_struct(mut item_struct: syn::ItemStruct) -> syn::parse::Result<syn::
                 .unwrap(),
         );
         // Add the buffer object to read from the net object
+        if buffer.is_none() {
+            fields.named.push(
+                syn::Field::parse_named
+                    .parse2(quote! { buffer: bytes::BytesMut })
+                    .unwrap(),
+            );
+        }
+
+        // Add the socket id information
         fields.named.push(
             syn::Field::parse_named
-                .parse2(quote! { buffer: bytes::BytesMut })
+                .parse2(quote! { socket_id: u32 })
                 .unwrap(),
         );
 
-        // Add the socket id information
+        // Add the byte counters
         fields.named.push(
             syn::Field::parse_named
-                .parse2(quote! { socket_id: u32 })
+                .parse2(quote! { io_stats: prosa::io::IoStats })
                 .unwrap(),
         );
+        if io_params.metrics {
+            fields.named.push(
+                syn::Field::parse_named
+                    .parse2(quote! { io_meters: std::option::Option<prosa::io::IoMeters> })
+                    .unwrap(),
+            );
+        }
+    } else {
+        return Err(syn::Error::new(
+            item_struct.fields.span(),
+            "expected a struct with named fields",
+        ));
     }
 
     // Add the Generic type IO to specify the net object
-    add_io_generic(&mut item_struct.generics)?;
+    add_io_generic(&mut item_struct.generics, &io_type)?;
 
-    Ok(item_struct)
+    let io_fields = IoFields {
+        stream: stream.unwrap_or_else(|| syn::Ident::new("stream", proc_macro2::Span::call_site())),
+        buffer: buffer.unwrap_or_else(|| syn::Ident::new("buffer", proc_macro2::Span::call_site())),
+        io_type,
+        derive_debug,
+    };
+    Ok((item_struct, io_fields))
 }
 
 fn generate_struct_impl(
     item_struct: &syn::ItemStruct,
+    io_fields: &IoFields,
+    io_params: &IoParams,
 ) -> syn::parse::Result<proc_macro2::TokenStream> {
     let item_ident = &item_struct.ident;
-    let item_generics = &item_struct.generics;
-    let item_other_fields = if let syn::Fields::Named(syn::FieldsNamed { named, .. }) =
-        &item_struct.fields
-    {
-        let token_other_fields = named.iter().filter_map(|f| {
-            if let Some(ident) = &f.ident {
-                if ident != "stream" && ident != "addr" && ident != "buffer" && ident != "socket_id"
-                {
-                    return Some(quote! { #ident: std::default::Default::default() });
+    let io_type = &io_fields.io_type;
+    let item_other_fields =
+        if let syn::Fields::Named(syn::FieldsNamed { named, .. }) = &item_struct.fields {
+            let token_other_fields = named.iter().filter_map(|f| {
+                if let Some(ident) = &f.ident {
+                    if !io_fields.is_io_field(ident) {
+                        return Some(quote! { #ident: std::default::Default::default() });
+                    }
                 }
-            }
 
-            None
-        });
+                None
+            });
 
-        quote! { #(#token_other_fields,)* }
-    } else {
-        proc_macro2::TokenStream::new()
-    };
+            quote! { #(#token_other_fields,)* }
+        } else {
+            proc_macro2::TokenStream::new()
+        };
+    let io_meters_field = io_params
+        .metrics
+        .then(|| quote! { io_meters: std::option::Option::None, });
+
+    // The socket id is the file descriptor of the stream on unix systems, there is no equivalent for all the streams on other systems
+    let unix_generics = with_predicate(
+        &item_struct.generics,
+        quote! { #io_type: std::os::fd::AsRawFd },
+    )?;
+    let from_unix = generate_struct_impl_from(
+        item_ident,
+        quote! { #[cfg(target_family = "unix")] },
+        &unix_generics,
+        io_fields,
+        &io_meters_field,
+        &item_other_fields,
+        quote! { std::os::fd::AsRawFd::as_raw_fd(&stream) as u32 },
+    );
+    let from_other = generate_struct_impl_from(
+        item_ident,
+        quote! { #[cfg(not(target_family = "unix"))] },
+        &item_struct.generics,
+        io_fields,
+        &io_meters_field,
+        &item_other_fields,
+        quote! { 0u32 },
+    );
 
     Ok(quote! {
-        impl #item_generics std::convert::From<IO> for #item_ident #item_generics
-        where
-            IO: 'static + tokio::io::AsyncReadExt + tokio::io::AsyncWriteExt + std::os::fd::AsRawFd + std::marker::Unpin + std::marker::Send
+        #from_unix
+        #from_other
+    })
+}
+
+/// Generate the conversions of a stream (with or without its address) into the IO
+fn generate_struct_impl_from(
+    item_ident: &syn::Ident,
+    cfg: proc_macro2::TokenStream,
+    from_generics: &syn::Generics,
+    io_fields: &IoFields,
+    io_meters_field: &Option<proc_macro2::TokenStream>,
+    item_other_fields: &proc_macro2::TokenStream,
+    socket_id: proc_macro2::TokenStream,
+) -> proc_macro2::TokenStream {
+    let IoFields {
+        stream,
+        buffer,
+        io_type,
+        ..
+    } = io_fields;
+    let (from_impl_generics, ty_generics, from_where_clause) = from_generics.split_for_impl();
+
+    quote! {
+        #cfg
+        impl #from_impl_generics std::convert::From<#io_type> for #item_ident #ty_generics #from_where_clause
         {
-            fn from(stream: IO) -> Self {
-                let socket_id = stream.as_raw_fd() as u32;
+            fn from(stream: #io_type) -> Self {
+                let socket_id = #socket_id;
                 #item_ident {
-                    stream,
+                    #stream: stream,
                     addr: None,
-                    buffer: bytes::BytesMut::with_capacity(16384),
+                    #buffer: bytes::BytesMut::with_capacity(16384),
                     socket_id,
+                    io_stats: std::default::Default::default(),
+                    #io_meters_field
                     #item_other_fields
                 }
             }
         }
-        impl #item_generics std::convert::From<(IO, std::net::SocketAddr)> for #item_ident #item_generics
-        where
-            IO: 'static + tokio::io::AsyncReadExt + tokio::io::AsyncWriteExt + std::os::fd::AsRawFd + std::marker::Unpin + std::marker::Send
+        #cfg
+        impl #from_impl_generics std::convert::From<(#io_type, std::net::SocketAddr)> for #item_ident #ty_generics #from_where_clause
         {
-            fn from(socket: (IO, std::net::SocketAddr)) -> Self {
+            fn from(socket: (#io_type, std::net::SocketAddr)) -> Self {
                 let (stream, addr) = socket;
-                let socket_id = stream.as_raw_fd() as u32;
+                let socket_id = #socket_id;
                 #item_ident {
-                    stream,
+                    #stream: stream,
                     addr: Some(addr),
-                    buffer: bytes::BytesMut::with_capacity(16384),
+                    #buffer: bytes::BytesMut::with_capacity(16384),
                     socket_id,
+                    io_stats: std::default::Default::default(),
+                    #io_meters_field
                     #item_other_fields
                 }
             }
         }
+    }
+}
+
+/// Generate the stream accessors, the byte counters and the formatting of the IO
+fn generate_struct_impl_stream(
+    item_struct: &syn::ItemStruct,
+    io_fields: &IoFields,
+    io_params: &IoParams,
+) -> syn::parse::Result<proc_macro2::TokenStream> {
+    let item_ident = &item_struct.ident;
+    let IoFields {
+        stream,
+        buffer,
+        io_type,
+        derive_debug,
+    } = io_fields;
+    let (impl_generics, ty_generics, where_clause) = item_struct.generics.split_for_impl();
+
+    let (meter_read, meter_written, meter_register) = if io_params.metrics {
+        let metric_name = to_snake_case(&item_ident.to_string());
+        (
+            quote! {
+                if let Some(io_meters) = &self.io_meters {
+                    io_meters.add_read(len);
+                }
+            },
+            quote! {
+                if let Some(io_meters) = &self.io_meters {
+                    io_meters.add_written(data.len());
+                }
+            },
+            quote! {
+                /// Method to register the byte counters of the IO on a meter (`prosa_<io_name>_read_bytes` and `prosa_<io_name>_written_bytes`)
+                pub fn register_meter(&mut self, meter: &opentelemetry::metrics::Meter, attributes: std::vec::Vec<opentelemetry::KeyValue>) {
+                    self.io_meters = Some(prosa::io::IoMeters::new(meter, #metric_name, attributes));
+                }
+            },
+        )
+    } else {
+        Default::default()
+    };
+
+    let display_generics = with_predicate(
+        &item_struct.generics,
+        quote! { #io_type: std::fmt::Display },
+    )?;
+    let (display_impl_generics, _, display_where_clause) = display_generics.split_for_impl();
+    let display_impl = quote! {
+        impl #display_impl_generics std::fmt::Display for #item_ident #ty_generics #display_where_clause {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                std::fmt::Display::fmt(&self.#stream, f)
+            }
+        }
+    };
+
+    let debug_impl = if *derive_debug {
+        proc_macro2::TokenStream::new()
+    } else {
+        let debug_generics =
+            with_predicate(&item_struct.generics, quote! { #io_type: std::fmt::Debug })?;
+        let (debug_impl_generics, _, debug_where_clause) = debug_generics.split_for_impl();
+        let item_name = item_ident.to_string();
+        quote! {
+            impl #debug_impl_generics std::fmt::Debug for #item_ident #ty_generics #debug_where_clause {
+                fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                    f.debug_struct(#item_name)
+                        .field("stream", &self.#stream)
+                        .field("addr", &self.addr)
+                        .field("socket_id", &self.socket_id)
+                        .field("buffered", &self.#buffer.len())
+                        .field("stats", &self.io_stats)
+                        .finish_non_exhaustive()
+                }
+            }
+        }
+    };
+
+    Ok(quote! {
+        impl #impl_generics #item_ident #ty_generics #where_clause {
+            /// Getter of the underlying stream
+            pub fn get_stream(&self) -> &#io_type {
+                &self.#stream
+            }
+
+            /// Getter of the mutable underlying stream (bytes exchanged directly on it are not counted)
+            pub fn get_stream_mut(&mut self) -> &mut #io_type {
+                &mut self.#stream
+            }
+
+            /// Getter of the counters of bytes exchanged through the IO
+            pub fn stats(&self) -> prosa::io::IoStats {
+                self.io_stats
+            }
+
+            /// Method to read available data from the stream into the buffer, and count the read bytes
+            /// Return 0 if the stream is closed
+            pub async fn read_buf(&mut self) -> std::io::Result<usize> {
+                let len = tokio::io::AsyncReadExt::read_buf(&mut self.#stream, &mut self.#buffer).await?;
+                self.io_stats.add_read(len);
+                #meter_read
+                Ok(len)
+            }
+
+            /// Method to write all the data on the stream, and count the written bytes
+            pub async fn write_all(&mut self, data: &[u8]) -> std::io::Result<()> {
+                tokio::io::AsyncWriteExt::write_all(&mut self.#stream, data).await?;
+                tokio::io::AsyncWriteExt::flush(&mut self.#stream).await?;
+                self.io_stats.add_written(data.len());
+                #meter_written
+                Ok(())
+            }
+
+            #meter_register
+        }
+
+        #display_impl
+        #debug_impl
     })
 }
 
+fn generate_struct_impl_io(
+    item_struct: &syn::ItemStruct,
+    io_fields: &IoFields,
+    header_size: &syn::LitInt,
+) -> proc_macro2::TokenStream {
+    let item_ident = &item_struct.ident;
+    let buffer = &io_fields.buffer;
+    let (impl_generics, ty_generics, where_clause) = item_struct.generics.split_for_impl();
+
+    quote! {
+        impl #impl_generics prosa::io::IO for #item_ident #ty_generics #where_clause {
+            type Frame = bytes::Bytes;
+            type Error = prosa::io::frame::FrameError;
+
+            fn parse_frame(&mut self) -> std::result::Result<std::option::Option<Self::Frame>, Self::Error> {
+                prosa::io::frame::LengthPrefixedCodec::<#header_size>::default().parse(&mut self.#buffer)
+            }
+
+            async fn read_frame(&mut self) -> std::result::Result<std::option::Option<Self::Frame>, Self::Error> {
+                let codec = prosa::io::frame::LengthPrefixedCodec::<#header_size>::default();
+                loop {
+                    if let Some(frame) = codec.parse(&mut self.#buffer)? {
+                        return Ok(Some(frame));
+                    }
+
+                    if self.read_buf().await? == 0 {
+                        return if self.#buffer.is_empty() {
+                            Ok(None)
+                        } else {
+                            Err(prosa::io::frame::FrameError::ConnectionReset(self.#buffer.len()))
+                        };
+                    }
+                }
+            }
+
+            async fn write_frame(&mut self, frame: Self::Frame) -> std::result::Result<(), Self::Error> {
+                let mut data = bytes::BytesMut::with_capacity(#header_size + frame.len());
+                prosa::io::frame::LengthPrefixedCodec::<#header_size>::default().encode(&frame, &mut data)?;
+                self.write_all(&data).await?;
+                Ok(())
+            }
+        }
+    }
+}
+
 fn add_struct_impl(mut item_impl: syn::ItemImpl) -> syn::parse::Result<syn::ItemImpl> {
-    add_io_generic(&mut item_impl.generics)?;
+    add_io_generic(
+        &mut item_impl.generics,
+        &syn::Ident::new("IO", proc_macro2::Span::call_site()),
+    )?;
 
     // Add IO template if missing
     if let syn::Type::Path(syn::TypePath {
@@ -170,14 +601,27 @@ fn add_struct_impl(mut item_impl: syn::ItemImpl) -> syn::parse::Result<syn::Item
 }
 
 /// Implementation of the procedural prosa_io macro
-pub(crate) fn io_impl(item: syn::Item) -> syn::parse::Result<proc_macro2::TokenStream> {
+pub(crate) fn io_impl(
+    args: &Punctuated<syn::Meta, Token![,]>,
+    item: syn::Item,
+) -> syn::parse::Result<proc_macro2::TokenStream> {
+    let mut io_params = IoParams::default();
+    io_params.parse_attr_args(args)?;
+
     match item {
         syn::Item::Struct(item_struct) => {
-            let struct_output = generate_struct(item_struct)?;
-            let struct_impl = generate_struct_impl(&struct_output)?;
+            let (struct_output, io_fields) = generate_struct(item_struct, &io_params)?;
+            let struct_impl = generate_struct_impl(&struct_output, &io_fields, &io_params)?;
+            let struct_impl_stream =
+                generate_struct_impl_stream(&struct_output, &io_fields, &io_params)?;
+            let struct_impl_io = io_params.length_prefixed.as_ref().map(|header_size| {
+                generate_struct_impl_io(&struct_output, &io_fields, header_size)
+            });
             Ok(quote! {
                 #struct_output
                 #struct_impl
+                #struct_impl_stream
+                #struct_impl_io
             })
         }
         syn::Item::Impl(item_impl) => {
@@ -192,3 +636,127 @@ pub(crate) fn io_impl(item: syn::Item) -> syn::parse::Result<proc_macro2::TokenS
         )),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expand(args: proc_macro2::TokenStream, item: proc_macro2::TokenStream) -> syn::File {
+        let args = Punctuated::<syn::Meta, Token![,]>::parse_terminated
+            .parse2(args)
+            .unwrap();
+        syn::parse2(io_impl(&args, syn::parse2(item).unwrap()).unwrap()).unwrap()
+    }
+
+    fn struct_fields(file: &syn::File) -> Vec<String> {
+        if let Some(syn::Item::Struct(item_struct)) = file.items.first() {
+            item_struct
+                .fields
+                .iter()
+                .map(|f| f.ident.as_ref().unwrap().to_string())
+                .collect()
+        } else {
+            panic!("The first generated item is not the struct");
+        }
+    }
+
+    fn impl_traits(file: &syn::File) -> Vec<String> {
+        file.items
+            .iter()
+            .filter_map(|item| {
+                if let syn::Item::Impl(item_impl) = item {
+                    item_impl
+                        .trait_
+                        .as_ref()
+                        .map(|(_, path, _)| path.segments.last().unwrap().ident.to_string())
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn io_default_fields() {
+        let file = expand(quote! {}, quote! { struct TestIo { id: u32 } });
+        assert_eq!(
+            vec!["id", "stream", "addr", "buffer", "socket_id", "io_stats"],
+            struct_fields(&file)
+        );
+        // Conversions are generated for unix and for the other platforms
+        assert_eq!(
+            vec!["From", "From", "From", "From", "Display", "Debug"],
+            impl_traits(&file)
+        );
+        let from_cfgs: Vec<String> = file
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                syn::Item::Impl(item_impl) => item_impl.attrs.first(),
+                _ => None,
+            })
+            .map(|attr| quote::ToTokens::to_token_stream(&attr.meta).to_string())
+            .collect();
+        assert_eq!(
+            vec![
+                "cfg (target_family = \"unix\")",
+                "cfg (target_family = \"unix\")",
+                "cfg (not (target_family = \"unix\"))",
+                "cfg (not (target_family = \"unix\"))"
+            ],
+            from_cfgs
+        );
+        assert_eq!(
+            "test_io",
+            to_snake_case("TestIo"),
+            "metrics are named after the struct"
+        );
+    }
+
+    #[test]
+    fn io_named_fields() {
+        let file = expand(
+            quote! { length_prefixed = 2, metrics },
+            quote! {
+                #[derive(Debug)]
+                struct TestIo<S> {
+                    #[io(stream)]
+                    conn: S,
+                    #[io(buffer)]
+                    read_buffer: bytes::BytesMut,
+                }
+            },
+        );
+        assert_eq!(
+            vec![
+                "conn",
+                "read_buffer",
+                "addr",
+                "socket_id",
+                "io_stats",
+                "io_meters"
+            ],
+            struct_fields(&file)
+        );
+        assert_eq!(
+            vec!["From", "From", "From", "From", "Display", "IO"],
+            impl_traits(&file)
+        );
+
+        // The stream generic is kept and the io attributes are removed
+        if let Some(syn::Item::Struct(item_struct)) = file.items.first() {
+            assert_eq!(1, item_struct.generics.params.len());
+            assert!(item_struct.fields.iter().all(|f| f.attrs.is_empty()));
+        }
+
+        let args = Punctuated::<syn::Meta, Token![,]>::parse_terminated
+            .parse2(quote! {})
+            .unwrap();
+        let err = io_impl(
+            &args,
+            syn::parse2(quote! { struct TestIo { #[io(unknown)] conn: IO } }).unwrap(),
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("unknown io field attribute"));
+    }
+}
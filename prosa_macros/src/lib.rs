@@ -47,7 +47,10 @@ fn add_angle_bracketed(
 }
 
 /// Derive macro to define a generic ProSA Adaptor.
-#[proc_macro_derive(Adaptor)]
+///
+/// Use the `#[adaptor(async_init, async_terminate)]` attribute to call the adaptor `on_init(&mut self, proc)` and `on_terminate(&mut self)` async methods during the processor lifecycle.
+/// Use the `#[adaptor(reload = MyConfig)]` attribute to reload the adaptor configuration with its `AdaptorConfigReload<MyConfig>` implementation when the processor configuration is reloaded.
+#[proc_macro_derive(Adaptor, attributes(adaptor))]
 pub fn adaptor(input: TokenStream) -> TokenStream {
     adaptor::adaptor_impl(parse_macro_input!(input as syn::DeriveInput))
         .unwrap_or_else(|e| e.to_compile_error())
@@ -55,6 +58,8 @@ pub fn adaptor(input: TokenStream) -> TokenStream {
 }
 
 /// Procedural macro to help building an ProSA Processor
+///
+/// Use the `#[proc(queue_size = 2048)]` argument to size the processor internal queue, and `#[proc(ctrl_queue_size = 64)]` to receive the control messages (service table, configuration, shutdown, ...) on a dedicated queue that is always drained before the data messages.
 #[proc_macro_attribute]
 pub fn proc(args: TokenStream, input: TokenStream) -> TokenStream {
     let args = match Punctuated::<syn::Meta, Token![,]>::parse_terminated.parse2(args.into()) {
@@ -68,19 +73,33 @@ pub fn proc(args: TokenStream, input: TokenStream) -> TokenStream {
 }
 
 /// Procedural macro to help building an ProSA Settings
+///
+/// With the `strict` argument, unknown configuration keys are rejected (`#[serde(deny_unknown_fields)]`) and reported together by `Settings::from_config()`
 #[proc_macro_attribute]
 pub fn settings(args: TokenStream, input: TokenStream) -> TokenStream {
-    assert!(args.is_empty());
-    settings::settings_impl(parse_macro_input!(input as syn::Item))
+    let args = match Punctuated::<syn::Meta, Token![,]>::parse_terminated.parse2(args.into()) {
+        Ok(args) => args,
+        Err(e) => return e.to_compile_error().into(),
+    };
+
+    settings::settings_impl(&args, parse_macro_input!(input as syn::Item))
         .unwrap_or_else(|e| e.to_compile_error())
         .into()
 }
 
 /// Procedural macro to help building an ProSA Processor Settings
+///
+/// With the `strict` argument, unknown configuration keys are rejected (`#[serde(deny_unknown_fields)]`)
+///
+/// A `pipeline: Vec<TransformSettings>` field is given by `ProcSettings::get_pipeline()`, to reload the adaptor pipeline
 #[proc_macro_attribute]
 pub fn proc_settings(args: TokenStream, input: TokenStream) -> TokenStream {
-    assert!(args.is_empty());
-    settings::proc_settings_impl(parse_macro_input!(input as syn::Item))
+    let args = match Punctuated::<syn::Meta, Token![,]>::parse_terminated.parse2(args.into()) {
+        Ok(args) => args,
+        Err(e) => return e.to_compile_error().into(),
+    };
+
+    settings::proc_settings_impl(&args, parse_macro_input!(input as syn::Item))
         .unwrap_or_else(|e| e.to_compile_error())
         .into()
 }
@@ -93,10 +112,27 @@ pub fn proc_settings(args: TokenStream, input: TokenStream) -> TokenStream {
 ///     buffer: bytes::BytesMut,
 /// }
 /// ```
+///
+/// The stream and buffer fields are added if they are not declared. Other field names can be used with the `#[io(stream)]` and `#[io(buffer)]` field attributes.
+/// The stream type is the struct generic `IO`, or the struct generic used by the stream field.
+///
+/// The macro generate:
+/// - `From<IO>` and `From<(IO, SocketAddr)>` to build the struct from a stream
+/// - `get_stream()` and `get_stream_mut()` accessors to the underlying stream
+/// - `read_buf()` and `write_all()` methods that count the exchanged bytes, exposed through `stats()` (`prosa::io::IoStats`)
+/// - `fmt::Display` delegating to the stream display (`tcp://addr`), and `fmt::Debug` if the struct doesn't derive it
+///
+/// With the `length_prefixed = <1|2|4>` argument, the `prosa::io::IO` trait is implemented with a `prosa::io::frame::LengthPrefixedCodec` (big endian header)
+///
+/// With the `metrics` argument, a `register_meter(meter, attributes)` method register the byte counters on an OpenTelemetry meter (`prosa_<struct_name>_read_bytes` and `prosa_<struct_name>_written_bytes`)
 #[proc_macro_attribute]
 pub fn io(args: TokenStream, input: TokenStream) -> TokenStream {
-    assert!(args.is_empty());
-    io::io_impl(parse_macro_input!(input as syn::Item))
+    let args = match Punctuated::<syn::Meta, Token![,]>::parse_terminated.parse2(args.into()) {
+        Ok(args) => args,
+        Err(e) => return e.to_compile_error().into(),
+    };
+
+    io::io_impl(&args, parse_macro_input!(input as syn::Item))
         .unwrap_or_else(|e| e.to_compile_error())
         .into()
 }
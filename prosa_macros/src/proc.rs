@@ -10,6 +10,7 @@ use crate::add_angle_bracketed;
 struct ProcParams {
     settings: Option<syn::Path>,
     queue_size: syn::LitInt,
+    ctrl_queue_size: Option<syn::LitInt>,
 }
 
 impl ProcParams {
@@ -43,6 +44,19 @@ impl ProcParams {
                                 "expected int value for task args queue_size (2048 by default)",
                             ));
                         }
+                    } else if name == "ctrl_queue_size" {
+                        if let syn::Expr::Lit(syn::ExprLit {
+                            lit: syn::Lit::Int(i),
+                            ..
+                        }) = &v.value
+                        {
+                            self.ctrl_queue_size = Some(i.clone());
+                        } else {
+                            return Err(syn::Error::new(
+                                v.value.span(),
+                                "expected int value for task args ctrl_queue_size (no control queue by default)",
+                            ));
+                        }
                     } else {
                         return Err(syn::Error::new(
                             name.span(),
@@ -66,6 +80,7 @@ impl std::default::Default for ProcParams {
         Self {
             settings: None,
             queue_size: syn::LitInt::new("2048", Span::call_site()),
+            ctrl_queue_size: None,
         }
     }
 }
@@ -129,7 +144,7 @@ fn generate_struct(
         // Add the receiver queue for processor messaging
         fields.named.push(
             syn::Field::parse_named
-                .parse2(quote! { internal_rx_queue: tokio::sync::mpsc::Receiver<prosa::core::msg::InternalMsg<M>> })
+                .parse2(quote! { internal_rx_queue: prosa::core::proc::ProcRxQueue<M> })
                 .unwrap(),
         );
 
@@ -178,13 +193,69 @@ fn generate_struct_impl_config(
     let item_ident = &item_struct.ident;
     let item_generics = &item_struct.generics;
     let queue_size = &args.queue_size;
+    // Split the processor inbox with a control queue if asked
+    let (ctrl_queue_quote, ctrl_rx_queue_quote) = if let Some(ctrl_queue_size) =
+        &args.ctrl_queue_size
+    {
+        (
+            quote! {
+                let (internal_ctrl_tx_queue, internal_ctrl_rx_queue) = tokio::sync::mpsc::channel(#ctrl_queue_size);
+                proc.set_ctrl_queue(std::option::Option::Some(internal_ctrl_tx_queue));
+            },
+            quote! {
+                let mut internal_rx_queue = prosa::core::proc::ProcRxQueue::new_proc(internal_rx_queue, &proc);
+                internal_rx_queue.set_ctrl_queue(internal_ctrl_rx_queue);
+            },
+        )
+    } else {
+        (
+            TokenStream::new(),
+            quote! {
+                let internal_rx_queue = prosa::core::proc::ProcRxQueue::new_proc(internal_rx_queue, &proc);
+            },
+        )
+    };
 
-    let (settings, settings_quote) = if let Some(settings) = &args.settings {
-        (settings.clone(), quote! { settings, })
+    let (settings, settings_quote, threads_quote, transport_rx_queue_quote) = if let Some(
+        settings,
+    ) = &args.settings
+    {
+        (
+            settings.clone(),
+            quote! { settings, },
+            quote! {
+                proc.set_threads(prosa::core::proc::ProcSettings::get_proc_threads(&settings));
+                proc.set_shutdown_rank(prosa::core::proc::ProcSettings::get_shutdown_rank(&settings).unwrap_or_default());
+                proc.set_watchdog(prosa::core::proc::ProcSettings::get_watchdog(&settings).cloned());
+                proc.set_shed_policy(prosa::core::proc::ProcSettings::get_shed_policy(&settings).cloned());
+                proc.set_runtime_metrics_interval(prosa::core::proc::ProcSettings::get_runtime_metrics_interval(&settings));
+                let transport_rx_queue = prosa::core::proc::ProcSettings::get_transport(&settings)
+                    .and_then(|transport| transport.channel())
+                    .map(|(transport_tx_queue, transport_rx_queue)| {
+                        proc.set_transport_queue(std::option::Option::Some(transport_tx_queue));
+                        transport_rx_queue
+                    });
+            },
+            quote! {
+                let internal_rx_queue = match transport_rx_queue {
+                    std::option::Option::Some(transport_rx_queue) => {
+                        let mut internal_rx_queue = internal_rx_queue;
+                        internal_rx_queue.set_transport_queue(transport_rx_queue);
+                        internal_rx_queue
+                    }
+                    std::option::Option::None => internal_rx_queue,
+                };
+            },
+        )
     } else {
         let setting_string_path: syn::Path = syn::parse2(quote! { std::string::String })?;
 
-        (setting_string_path, TokenStream::new())
+        (
+            setting_string_path,
+            TokenStream::new(),
+            TokenStream::new(),
+            TokenStream::new(),
+        )
     };
 
     Ok(quote! {
@@ -197,7 +268,12 @@ fn generate_struct_impl_config(
 
             fn create(proc_id: u32, main: prosa::core::main::Main<M>, settings: Self::Settings) -> Self {
                 let (internal_tx_queue, internal_rx_queue) = tokio::sync::mpsc::channel(#queue_size);
-                let proc = prosa::core::proc::ProcParam::new(proc_id, internal_tx_queue, main);
+                #[allow(unused_mut)]
+                let mut proc = prosa::core::proc::ProcParam::new(proc_id, internal_tx_queue, main);
+                #threads_quote
+                #ctrl_queue_quote
+                #ctrl_rx_queue_quote
+                #transport_rx_queue_quote
                 #item_ident {
                     proc,
                     service: std::default::Default::default(),
@@ -213,6 +289,44 @@ fn generate_struct_impl_config(
     })
 }
 
+fn generate_struct_impl_reload(
+    item_struct: &syn::ItemStruct,
+    args: &ProcParams,
+) -> syn::parse::Result<proc_macro2::TokenStream> {
+    let item_ident = &item_struct.ident;
+    let item_generics = &item_struct.generics;
+
+    if let Some(settings) = &args.settings {
+        Ok(quote! {
+            impl #item_generics #item_ident #item_generics
+            where
+                M: 'static + std::marker::Send + std::marker::Sync + std::marker::Sized + std::clone::Clone + std::fmt::Debug + prosa_utils::msg::tvf::Tvf + std::default::Default,
+            {
+                /// Method to reload the processor settings from its section (processor name) of a reloaded configuration.
+                /// The adaptor configuration can be reloaded after with `ProcSettings::get_adaptor_config()`
+                pub fn reload_settings(&mut self, config: &prosa::core::settings::ReloadedConfig, name: &str) -> std::result::Result<(), prosa::core::settings::ConfigError>
+                where
+                    #settings: prosa::core::settings::DeserializeOwned,
+                {
+                    self.settings = config.get(&name.replace('-', "_"))?;
+                    Ok(())
+                }
+
+                /// Method to reload the adaptor configuration from the processor settings, for adaptors that implement `AdaptorConfigReload`.
+                /// The outcome is logged, and the adaptor keeps its previous configuration if the reload fails.
+                pub fn reload_adaptor<A>(&self, adaptor: &mut A, name: &str)
+                where
+                    A: prosa::core::adaptor::Adaptor,
+                {
+                    prosa::core::adaptor::reload_adaptor_config(adaptor, &self.settings, name)
+                }
+            }
+        })
+    } else {
+        Ok(TokenStream::new())
+    }
+}
+
 fn add_struct_impl(mut item_impl: syn::ItemImpl) -> syn::parse::Result<syn::ItemImpl> {
     // Add IO template if missing
     if let syn::Type::Path(syn::TypePath {
@@ -253,6 +367,87 @@ fn add_struct_impl(mut item_impl: syn::ItemImpl) -> syn::parse::Result<syn::Item
     Ok(item_impl)
 }
 
+/// Know if the impl is a `Proc` trait implementation
+fn is_proc_impl(item_impl: &syn::ItemImpl) -> bool {
+    item_impl
+        .trait_
+        .as_ref()
+        .and_then(|(_, path, _)| path.segments.last())
+        .is_some_and(|s| s.ident == "Proc")
+}
+
+/// Add the getters of the processor settings threads number and runtime metrics to a `Proc` implementation
+fn add_proc_settings_threads(item_impl: &mut syn::ItemImpl) -> syn::parse::Result<()> {
+    if !is_proc_impl(item_impl) {
+        return Ok(());
+    }
+
+    let is_defined = |item_impl: &syn::ItemImpl, name: &str| {
+        item_impl
+            .items
+            .iter()
+            .any(|item| matches!(item, syn::ImplItem::Fn(item_fn) if item_fn.sig.ident == name))
+    };
+    if !is_defined(item_impl, "get_settings_threads") {
+        item_impl.items.push(syn::parse2(quote! {
+            fn get_settings_threads(&self) -> std::option::Option<usize> {
+                self.proc.get_threads()
+            }
+        })?);
+    }
+    if !is_defined(item_impl, "get_panic_guard") {
+        item_impl.items.push(syn::parse2(quote! {
+            fn get_panic_guard(&self) -> std::option::Option<prosa::core::main::ProcPanicGuard> {
+                std::option::Option::Some(self.proc.panic_guard())
+            }
+        })?);
+    }
+    if !is_defined(item_impl, "get_crash_counter") {
+        item_impl.items.push(syn::parse2(quote! {
+            fn get_crash_counter(&self, name: &str) -> std::option::Option<prosa::core::proc::ProcCrashCounter> {
+                std::option::Option::Some(self.proc.crash_counter(name))
+            }
+        })?);
+    }
+    if !is_defined(item_impl, "get_runtime_metrics_sampler") {
+        item_impl.items.push(syn::parse2(quote! {
+            fn get_runtime_metrics_sampler(&self, name: &str) -> std::option::Option<prosa::core::proc::RuntimeMetricsSampler> {
+                self.proc.runtime_metrics_sampler(name)
+            }
+        })?);
+    }
+
+    Ok(())
+}
+
+/// Add the initialization of the processor standard metrics at the start of the `internal_run` method of a `Proc` implementation
+fn add_proc_metrics(item_impl: &mut syn::ItemImpl) -> syn::parse::Result<()> {
+    if !is_proc_impl(item_impl) {
+        return Ok(());
+    }
+
+    for item in &mut item_impl.items {
+        if let syn::ImplItem::Fn(item_fn) = item {
+            if item_fn.sig.ident == "internal_run" {
+                // Get the processor name argument
+                if let Some(syn::FnArg::Typed(pat_type)) = item_fn.sig.inputs.iter().nth(1) {
+                    if let syn::Pat::Ident(pat_ident) = &*pat_type.pat {
+                        let name = &pat_ident.ident;
+                        item_fn.block.stmts.insert(
+                            0,
+                            syn::parse2(quote! {
+                                self.internal_rx_queue.init_metrics(&self.proc, &#name);
+                            })?,
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
 /// Implementation of the procedural prosa_proc macro
 pub(crate) fn proc_impl(
     args: &Punctuated<syn::Meta, Token![,]>,
@@ -266,14 +461,18 @@ pub(crate) fn proc_impl(
             let struct_output = generate_struct(item_struct, &proc_args)?;
             let struct_impl_bus_param = generate_struct_impl_bus_param(&struct_output)?;
             let struct_impl_config = generate_struct_impl_config(&struct_output, &proc_args)?;
+            let struct_impl_reload = generate_struct_impl_reload(&struct_output, &proc_args)?;
             Ok(quote! {
                 #struct_output
                 #struct_impl_bus_param
                 #struct_impl_config
+                #struct_impl_reload
             })
         }
         syn::Item::Impl(item_impl) => {
-            let impl_output = add_struct_impl(item_impl)?;
+            let mut impl_output = add_struct_impl(item_impl)?;
+            add_proc_metrics(&mut impl_output)?;
+            add_proc_settings_threads(&mut impl_output)?;
             Ok(quote! {
                 #impl_output
             })
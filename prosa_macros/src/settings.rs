@@ -1,9 +1,32 @@
 use quote::{quote, ToTokens};
 use syn::{
     parse::{Parse, Parser},
-    ItemImpl,
+    punctuated::Punctuated,
+    ItemImpl, Token,
 };
 
+/// Function to know if the settings are strict (`strict` argument)
+fn is_strict(args: &Punctuated<syn::Meta, Token![,]>) -> syn::parse::Result<bool> {
+    let mut strict = false;
+    for arg in args {
+        match arg {
+            syn::Meta::Path(path) if path.is_ident("strict") => strict = true,
+            _ => return Err(syn::Error::new_spanned(arg, "unknown settings argument")),
+        }
+    }
+
+    Ok(strict)
+}
+
+/// Function to reject unknown fields when the settings are strict
+fn add_strict_attr(item_struct: &mut syn::ItemStruct) {
+    item_struct.attrs.extend(
+        syn::Attribute::parse_outer
+            .parse2(quote! { #[serde(deny_unknown_fields)] })
+            .unwrap(),
+    );
+}
+
 /// Function to add default member to Default trait impl
 fn add_default_member<F>(mut item_impl: ItemImpl, func: F) -> syn::parse::Result<ItemImpl>
 where
@@ -37,14 +60,14 @@ where
                             }
                         }
                         // Direct Expr return (Self {..})
-                        syn::Stmt::Expr(syn::Expr::Struct(expr), _) => {
-                            if expr.path.is_ident(self_ident) {
-                                if !expr.fields.trailing_punct() {
-                                    expr.fields.push_punct(syn::token::Comma::default());
-                                }
-
-                                func(expr);
+                        syn::Stmt::Expr(syn::Expr::Struct(expr), _)
+                            if expr.path.is_ident(self_ident) =>
+                        {
+                            if !expr.fields.trailing_punct() {
+                                expr.fields.push_punct(syn::token::Comma::default());
                             }
+
+                            func(expr);
                         }
                         _ => {}
                     }
@@ -87,6 +110,50 @@ fn generate_proc_settings_struct(
                 .parse2(quote! { adaptor_config_path: std::option::Option<std::string::String> })
                 .unwrap(),
         );
+
+        // Processor runtime threads number
+        fields.named.push(
+            syn::Field::parse_named
+                .parse2(quote! { threads: std::option::Option<usize> })
+                .unwrap(),
+        );
+
+        // Processor shutdown rank
+        fields.named.push(
+            syn::Field::parse_named
+                .parse2(quote! { shutdown_rank: std::option::Option<u32> })
+                .unwrap(),
+        );
+
+        // Processor watchdog
+        fields.named.push(
+            syn::Field::parse_named
+                .parse2(quote! { watchdog: std::option::Option<prosa::core::watchdog::WatchdogSettings> })
+                .unwrap(),
+        );
+
+        // Processor load shedding policy
+        fields.named.push(
+            syn::Field::parse_named
+                .parse2(quote! { shed_policy: std::option::Option<prosa::core::shed::ShedPolicy> })
+                .unwrap(),
+        );
+
+        // Processor runtime metrics sampling interval
+        fields.named.push(
+            syn::Field::parse_named
+                .parse2(
+                    quote! { runtime_metrics_interval: std::option::Option<std::time::Duration> },
+                )
+                .unwrap(),
+        );
+
+        // Processor transport
+        fields.named.push(
+            syn::Field::parse_named
+                .parse2(quote! { transport: std::option::Option<prosa::core::transport::TransportSettings> })
+                .unwrap(),
+        );
     }
 
     Ok(item_struct)
@@ -97,19 +164,69 @@ fn generate_struct_impl_proc_settings(
 ) -> syn::parse::Result<proc_macro2::TokenStream> {
     let item_ident = &item_struct.ident;
 
+    // Give the adaptor pipeline of the processors that declare one
+    let pipeline_getter = if let syn::Fields::Named(fields) = &item_struct.fields {
+        fields
+            .named
+            .iter()
+            .any(|field| field.ident.as_ref().is_some_and(|i| i == "pipeline"))
+            .then(|| {
+                quote! {
+                    fn get_pipeline(&self) -> &[prosa::core::adaptor::TransformSettings] {
+                        &self.pipeline
+                    }
+                }
+            })
+    } else {
+        None
+    };
+
     Ok(quote! {
         impl prosa::core::proc::ProcSettings for #item_ident {
             fn get_adaptor_config_path(&self) -> std::option::Option<&std::string::String> {
                 self.adaptor_config_path.as_ref()
             }
+
+            fn get_proc_threads(&self) -> std::option::Option<usize> {
+                self.threads
+            }
+
+            fn get_shutdown_rank(&self) -> std::option::Option<u32> {
+                self.shutdown_rank
+            }
+
+            fn get_watchdog(&self) -> std::option::Option<&prosa::core::watchdog::WatchdogSettings> {
+                self.watchdog.as_ref()
+            }
+
+            fn get_shed_policy(&self) -> std::option::Option<&prosa::core::shed::ShedPolicy> {
+                self.shed_policy.as_ref()
+            }
+
+            fn get_runtime_metrics_interval(&self) -> std::option::Option<std::time::Duration> {
+                self.runtime_metrics_interval
+            }
+
+            fn get_transport(&self) -> std::option::Option<&prosa::core::transport::TransportSettings> {
+                self.transport.as_ref()
+            }
+
+            #pipeline_getter
         }
     })
 }
 
 /// Implementation of the procedural proc_settings macro
-pub(crate) fn proc_settings_impl(item: syn::Item) -> syn::parse::Result<proc_macro2::TokenStream> {
+pub(crate) fn proc_settings_impl(
+    args: &Punctuated<syn::Meta, Token![,]>,
+    item: syn::Item,
+) -> syn::parse::Result<proc_macro2::TokenStream> {
+    let strict = is_strict(args)?;
     match item {
-        syn::Item::Struct(item_struct) => {
+        syn::Item::Struct(mut item_struct) => {
+            if strict {
+                add_strict_attr(&mut item_struct);
+            }
             let struct_output = generate_proc_settings_struct(item_struct)?;
             let struct_impl_proc_settings = generate_struct_impl_proc_settings(&struct_output)?;
             Ok(quote! {
@@ -124,6 +241,48 @@ pub(crate) fn proc_settings_impl(item: syn::Item) -> syn::parse::Result<proc_mac
                     .unwrap(),
             );
             x.fields.push_punct(syn::token::Comma::default());
+
+            x.fields.push_value(
+                syn::FieldValue::parse
+                    .parse2(quote! { threads: None })
+                    .unwrap(),
+            );
+            x.fields.push_punct(syn::token::Comma::default());
+
+            x.fields.push_value(
+                syn::FieldValue::parse
+                    .parse2(quote! { shutdown_rank: None })
+                    .unwrap(),
+            );
+            x.fields.push_punct(syn::token::Comma::default());
+
+            x.fields.push_value(
+                syn::FieldValue::parse
+                    .parse2(quote! { watchdog: None })
+                    .unwrap(),
+            );
+            x.fields.push_punct(syn::token::Comma::default());
+
+            x.fields.push_value(
+                syn::FieldValue::parse
+                    .parse2(quote! { shed_policy: None })
+                    .unwrap(),
+            );
+            x.fields.push_punct(syn::token::Comma::default());
+
+            x.fields.push_value(
+                syn::FieldValue::parse
+                    .parse2(quote! { runtime_metrics_interval: None })
+                    .unwrap(),
+            );
+            x.fields.push_punct(syn::token::Comma::default());
+
+            x.fields.push_value(
+                syn::FieldValue::parse
+                    .parse2(quote! { transport: None })
+                    .unwrap(),
+            );
+            x.fields.push_punct(syn::token::Comma::default());
         })?
         .into_token_stream()),
         _ => Err(syn::Error::new(
@@ -153,6 +312,105 @@ fn generate_settings_struct(
                 observability: prosa_utils::config::observability::Observability })
                 .unwrap(),
         );
+
+        // ProSA configuration watch setting
+        fields.named.push(
+            syn::Field::parse_named
+                .parse2(quote! {
+                #[serde(default)]
+                config_watch: std::option::Option<prosa::core::settings::ConfigWatch> })
+                .unwrap(),
+        );
+
+        // ProSA main heartbeat setting
+        fields.named.push(
+            syn::Field::parse_named
+                .parse2(quote! {
+                #[serde(default)]
+                heartbeat: std::option::Option<prosa::core::settings::Heartbeat> })
+                .unwrap(),
+        );
+
+        // ProSA instance identity setting
+        fields.named.push(
+            syn::Field::parse_named
+                .parse2(quote! {
+                #[serde(default)]
+                identity: std::option::Option<prosa::core::settings::Identity> })
+                .unwrap(),
+        );
+
+        // ProSA maximum message size setting
+        fields.named.push(
+            syn::Field::parse_named
+                .parse2(quote! {
+                #[serde(default)]
+                max_message_size: std::option::Option<usize> })
+                .unwrap(),
+        );
+
+        // ProSA control socket setting
+        fields.named.push(
+            syn::Field::parse_named
+                .parse2(quote! {
+                #[serde(default)]
+                ctl_socket: std::option::Option<std::path::PathBuf> })
+                .unwrap(),
+        );
+
+        // ProSA state store setting
+        fields.named.push(
+            syn::Field::parse_named
+                .parse2(quote! {
+                #[serde(default)]
+                state: std::option::Option<prosa::core::state::StateSettings> })
+                .unwrap(),
+        );
+
+        // ProSA shutdown setting
+        fields.named.push(
+            syn::Field::parse_named
+                .parse2(quote! {
+                #[serde(default)]
+                shutdown: std::option::Option<prosa::core::settings::Shutdown> })
+                .unwrap(),
+        );
+
+        // ProSA audit log setting
+        fields.named.push(
+            syn::Field::parse_named
+                .parse2(quote! {
+                #[serde(default)]
+                audit: std::option::Option<prosa::core::audit::AuditSettings> })
+                .unwrap(),
+        );
+
+        // ProSA transactions moving window setting
+        fields.named.push(
+            syn::Field::parse_named
+                .parse2(quote! {
+                #[serde(default)]
+                tps: std::option::Option<prosa::core::tps::TpsSettings> })
+                .unwrap(),
+        );
+
+        // ProSA routing only to ready processors setting
+        fields.named.push(
+            syn::Field::parse_named
+                .parse2(quote! {
+                #[serde(default)]
+                ready_routing: std::option::Option<bool> })
+                .unwrap(),
+        );
+
+        // ProSA dead letter service setting
+        fields.named.push(
+            syn::Field::parse_named
+                .parse2(quote! {
+                #[serde(default)]
+                dead_letter_service: std::option::Option<std::string::String> })
+                .unwrap(),
+        );
     }
 
     Ok(item_struct)
@@ -160,11 +418,19 @@ fn generate_settings_struct(
 
 fn generate_struct_impl_settings(
     item_struct: &syn::ItemStruct,
+    strict: bool,
 ) -> syn::parse::Result<proc_macro2::TokenStream> {
     let item_ident = &item_struct.ident;
+    let strict_const = if strict {
+        quote! { const STRICT: bool = true; }
+    } else {
+        quote! {}
+    };
 
     Ok(quote! {
         impl prosa::core::settings::Settings for #item_ident {
+            #strict_const
+
             fn get_prosa_name(&self) -> String {
                 if let Some(name) = &self.name {
                     name.clone()
@@ -182,16 +448,67 @@ fn generate_struct_impl_settings(
             fn get_observability(&self) -> &prosa_utils::config::observability::Observability {
                 &self.observability
             }
+
+            fn get_config_watch(&self) -> std::option::Option<&prosa::core::settings::ConfigWatch> {
+                self.config_watch.as_ref()
+            }
+
+            fn get_heartbeat(&self) -> std::option::Option<&prosa::core::settings::Heartbeat> {
+                self.heartbeat.as_ref()
+            }
+
+            fn get_identity(&self) -> std::option::Option<&prosa::core::settings::Identity> {
+                self.identity.as_ref()
+            }
+
+            fn get_max_message_size(&self) -> std::option::Option<usize> {
+                self.max_message_size
+            }
+
+            fn get_ctl_socket(&self) -> std::option::Option<&std::path::PathBuf> {
+                self.ctl_socket.as_ref()
+            }
+
+            fn get_state(&self) -> std::option::Option<&prosa::core::state::StateSettings> {
+                self.state.as_ref()
+            }
+
+            fn get_shutdown(&self) -> std::option::Option<&prosa::core::settings::Shutdown> {
+                self.shutdown.as_ref()
+            }
+
+            fn get_audit(&self) -> std::option::Option<&prosa::core::audit::AuditSettings> {
+                self.audit.as_ref()
+            }
+
+            fn get_tps(&self) -> std::option::Option<&prosa::core::tps::TpsSettings> {
+                self.tps.as_ref()
+            }
+
+            fn get_ready_routing(&self) -> bool {
+                self.ready_routing.unwrap_or_default()
+            }
+
+            fn get_dead_letter_service(&self) -> std::option::Option<&std::string::String> {
+                self.dead_letter_service.as_ref()
+            }
         }
     })
 }
 
 /// Implementation of the procedural proc macro
-pub(crate) fn settings_impl(item: syn::Item) -> syn::parse::Result<proc_macro2::TokenStream> {
+pub(crate) fn settings_impl(
+    args: &Punctuated<syn::Meta, Token![,]>,
+    item: syn::Item,
+) -> syn::parse::Result<proc_macro2::TokenStream> {
+    let strict = is_strict(args)?;
     match item {
-        syn::Item::Struct(item_struct) => {
+        syn::Item::Struct(mut item_struct) => {
+            if strict {
+                add_strict_attr(&mut item_struct);
+            }
             let struct_output = generate_settings_struct(item_struct)?;
-            let struct_impl_settings = generate_struct_impl_settings(&struct_output)?;
+            let struct_impl_settings = generate_struct_impl_settings(&struct_output, strict)?;
             Ok(quote! {
                 #struct_output
                 #struct_impl_settings
@@ -211,6 +528,83 @@ pub(crate) fn settings_impl(item: syn::Item) -> syn::parse::Result<proc_macro2::
                     .unwrap(),
             );
             x.fields.push_punct(syn::token::Comma::default());
+
+            x.fields.push_value(
+                syn::FieldValue::parse
+                    .parse2(quote! { config_watch: None })
+                    .unwrap(),
+            );
+            x.fields.push_punct(syn::token::Comma::default());
+
+            x.fields.push_value(
+                syn::FieldValue::parse
+                    .parse2(quote! { heartbeat: None })
+                    .unwrap(),
+            );
+            x.fields.push_punct(syn::token::Comma::default());
+
+            x.fields.push_value(
+                syn::FieldValue::parse
+                    .parse2(quote! { identity: None })
+                    .unwrap(),
+            );
+            x.fields.push_punct(syn::token::Comma::default());
+
+            x.fields.push_value(
+                syn::FieldValue::parse
+                    .parse2(quote! { max_message_size: None })
+                    .unwrap(),
+            );
+            x.fields.push_punct(syn::token::Comma::default());
+
+            x.fields.push_value(
+                syn::FieldValue::parse
+                    .parse2(quote! { ctl_socket: None })
+                    .unwrap(),
+            );
+            x.fields.push_punct(syn::token::Comma::default());
+
+            x.fields.push_value(
+                syn::FieldValue::parse
+                    .parse2(quote! { state: None })
+                    .unwrap(),
+            );
+            x.fields.push_punct(syn::token::Comma::default());
+
+            x.fields.push_value(
+                syn::FieldValue::parse
+                    .parse2(quote! { shutdown: None })
+                    .unwrap(),
+            );
+            x.fields.push_punct(syn::token::Comma::default());
+
+            x.fields.push_value(
+                syn::FieldValue::parse
+                    .parse2(quote! { audit: None })
+                    .unwrap(),
+            );
+            x.fields.push_punct(syn::token::Comma::default());
+
+            x.fields.push_value(
+                syn::FieldValue::parse
+                    .parse2(quote! { tps: None })
+                    .unwrap(),
+            );
+            x.fields.push_punct(syn::token::Comma::default());
+
+            x.fields.push_value(
+                syn::FieldValue::parse
+                    .parse2(quote! { ready_routing: None })
+                    .unwrap(),
+            );
+            x.fields.push_punct(syn::token::Comma::default());
+
+            x.fields.push_value(
+                syn::FieldValue::parse
+                    .parse2(quote! { dead_letter_service: None })
+                    .unwrap(),
+            );
+            x.fields.push_punct(syn::token::Comma::default());
         })?
         .into_token_stream()),
         _ => Err(syn::Error::new(
@@ -0,0 +1,37 @@
+//! Fuzz the dictionary deserializer with arbitrary JSON messages on the test dictionary
+
+#![no_main]
+
+use std::sync::LazyLock;
+
+use libfuzzer_sys::fuzz_target;
+use prosa_utils::{
+    dict::{deserialize::DictDeserializer, Dictionary},
+    msg::{simple_string_tvf::SimpleStringTvf, tvf::Tvf as _},
+};
+use serde::de::DeserializeSeed as _;
+
+const MAX_DEPTH: usize = 4;
+const MAX_SIZE: usize = 256;
+
+static DICTIONARY: LazyLock<Dictionary> = LazyLock::new(|| {
+    Dictionary::from_csv_reader(include_str!("../../test_assets/dict_message.csv").as_bytes())
+        .unwrap()
+});
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(tvf) = DictDeserializer::<SimpleStringTvf>::new(&DICTIONARY)
+        .max_depth(MAX_DEPTH)
+        .max_size(MAX_SIZE)
+        .deserialize(&mut serde_json::Deserializer::from_slice(data))
+    else {
+        return;
+    };
+
+    // Only the dictionary fields are read, and the message stay proportional to the input
+    assert!(tvf.len() <= DICTIONARY.len());
+    assert!(tvf.size_hint() <= 8 * data.len() + 64);
+    if let Ok(tags) = tvf.get_buffer(4) {
+        assert!(tags.len() <= MAX_SIZE);
+    }
+});
@@ -0,0 +1,37 @@
+//! Fuzz the simple string TVF parsing, and check that its serialization round trip
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use prosa_utils::msg::{simple_string_tvf::SimpleStringTvf, tvf::Tvf as _};
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(serial) = std::str::from_utf8(data) else {
+        return;
+    };
+    let Ok(tvf) = SimpleStringTvf::deserialize(serial) else {
+        return;
+    };
+
+    // Fields are never bigger than the input
+    assert!(tvf.size_hint() <= serial.len());
+
+    // Getters must not panic whatever the field content
+    for tag in tvf.keys() {
+        let _ = tvf.get_type(tag);
+        let _ = tvf.get_buffer(tag);
+        let _ = tvf.get_unsigned(tag);
+        let _ = tvf.get_signed(tag);
+        let _ = tvf.get_byte(tag);
+        let _ = tvf.get_float(tag);
+        let _ = tvf.get_bytes(tag);
+        let _ = tvf.get_date(tag);
+        let _ = tvf.get_datetime(tag);
+    }
+    let _ = tvf.entries().count();
+
+    // Serialization round trip
+    let serial = tvf.serialize();
+    assert_eq!(serial.len(), tvf.size_hint());
+    assert_eq!(Ok(tvf), SimpleStringTvf::deserialize(&serial));
+});
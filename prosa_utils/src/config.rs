@@ -12,6 +12,8 @@ use std::{
 use serde::Serialize;
 use thiserror::Error;
 
+pub mod secret;
+
 // Feature openssl or rusttls,...
 #[cfg(feature = "config-openssl")]
 pub mod ssl;
@@ -24,6 +26,10 @@ pub mod observability;
 #[cfg(feature = "config-observability")]
 pub mod tracing;
 
+// Feature telemetry utilities
+#[cfg(feature = "config-observability")]
+pub mod telemetry;
+
 /// Error define for configuration object
 #[derive(Debug, Error)]
 pub enum ConfigError {
@@ -36,10 +42,17 @@ pub enum ConfigError {
     /// Error on a file read
     #[error("The file `{0}` can't be read `{1}`")]
     IoFile(String, io::Error),
+    /// Error on a secret resolution (never contain the secret value)
+    #[error("The secret `{0}` can't be resolved `{1}`")]
+    Secret(String, String),
     #[cfg(feature = "config-openssl")]
     /// SSL error
     #[error("Openssl error `{0}`")]
     Ssl(#[from] openssl::error::ErrorStack),
+    #[cfg(feature = "config-openssl")]
+    /// PKCS#11 error, on the load of a private key from an HSM
+    #[error("PKCS#11 error `{0}`")]
+    Pkcs11(#[from] ssl::pkcs11::Pkcs11Error),
 }
 
 /// Method to get the country name from the OS
@@ -12,6 +12,7 @@ use opentelemetry_sdk::{
     },
     runtime,
     trace::{Tracer, TracerProvider},
+    Resource,
 };
 use serde::{Deserialize, Serialize};
 use std::{env, net::AddrParseError, time::Duration};
@@ -19,8 +20,16 @@ use tracing_subscriber::{filter, prelude::*};
 use tracing_subscriber::{layer::SubscriberExt, util::TryInitError};
 use url::Url;
 
+#[cfg(feature = "config-observability-prometheus")]
+use super::secret::Secret;
 use super::tracing::{TelemetryFilter, TelemetryLevel};
 
+/// Registry of the prometheus exporter of a meter provider, if any
+#[cfg(feature = "config-observability-prometheus")]
+type PrometheusRegistry = Option<prometheus::Registry>;
+#[cfg(not(feature = "config-observability-prometheus"))]
+type PrometheusRegistry = ();
+
 /// Configuration struct of an **O**pen **T**e**l**emetry **P**rotocol Exporter
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub(crate) struct OTLPExporterCfg {
@@ -70,16 +79,89 @@ impl Default for OTLPExporterCfg {
     }
 }
 
+#[cfg(feature = "config-observability-prometheus")]
+/// Basic authentication required to scrape the prometheus metrics
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+pub struct PrometheusBasicAuth {
+    /// User name
+    pub username: String,
+    /// Password of the user
+    pub password: Secret,
+}
+
+#[cfg(feature = "config-observability-prometheus")]
+impl PrometheusBasicAuth {
+    /// Value of the `Authorization` header expected from the scrapers
+    fn authorization(&self) -> String {
+        use base64::Engine as _;
+        format!(
+            "Basic {}",
+            base64::engine::general_purpose::STANDARD.encode(format!(
+                "{}:{}",
+                self.username,
+                self.password.expose()
+            ))
+        )
+    }
+}
+
 #[cfg(feature = "config-observability-prometheus")]
 /// Configuration struct of a prometheus metric exporter
+///
+/// The metrics are served over HTTP on the `path` of the `endpoint` (`/metrics` by default), other paths are not found.
+/// If a basic authentication is configured, scrapers without the credentials are unauthorized.
+///
+/// With `exemplars`, the trace ids of the latency histograms are kept (see [`telemetry`](crate::config::telemetry)) and exposed in the OpenMetrics format to the scrapers that accept it (`Accept: application/openmetrics-text`).
+/// Other scrapers still get the plain prometheus format.
+///
+/// ```yaml
+/// prometheus:
+///   endpoint: 0.0.0.0:9100
+///   path: /prosa/metrics
+///   exemplars: true
+///   basic_auth:
+///     username: prometheus
+///     password:
+///       env: PROMETHEUS_PASSWORD
+/// ```
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct PrometheusExporterCfg {
     endpoint: String,
+    #[serde(default = "PrometheusExporterCfg::get_default_path")]
+    path: String,
+    #[serde(default)]
+    basic_auth: Option<PrometheusBasicAuth>,
+    #[serde(default)]
+    exemplars: bool,
 }
 
 #[cfg(feature = "config-observability-prometheus")]
 impl PrometheusExporterCfg {
+    fn get_default_path() -> String {
+        String::from("/metrics")
+    }
+
+    /// Setter of the HTTP path of the metrics
+    pub fn set_path<T>(&mut self, path: T)
+    where
+        T: Into<String>,
+    {
+        self.path = path.into();
+    }
+
+    /// Setter of the basic authentication required to scrape the metrics
+    pub fn set_basic_auth(&mut self, basic_auth: Option<PrometheusBasicAuth>) {
+        self.basic_auth = basic_auth;
+    }
+
+    /// Setter to expose the exemplars of the histograms (in the OpenMetrics format)
+    pub fn set_exemplars(&mut self, exemplars: bool) {
+        self.exemplars = exemplars;
+    }
+
     /// Instantiate a builder to build a prometheus exporter
+    ///
+    /// The builded exporter doesn't support the basic authentication, use [`PrometheusExporterCfg::start`] instead
     pub fn builder(&self) -> Result<prometheus_exporter::Builder, MetricsError> {
         Ok(prometheus_exporter::Builder::new(
             self.endpoint
@@ -87,6 +169,106 @@ impl PrometheusExporterCfg {
                 .map_err(|e: AddrParseError| MetricsError::Config(e.to_string()))?,
         ))
     }
+
+    /// Method to start the HTTP server that expose the metrics of the registry.
+    /// The server run on its own thread
+    pub fn start(&self, registry: prometheus::Registry) -> Result<(), MetricsError> {
+        let addr: std::net::SocketAddr = self
+            .endpoint
+            .parse()
+            .map_err(|e: AddrParseError| MetricsError::Config(e.to_string()))?;
+        let server =
+            tiny_http::Server::http(addr).map_err(|e| MetricsError::Other(e.to_string()))?;
+        let path = format!("/{}", self.path.trim_matches('/'));
+        let authorization = self.basic_auth.as_ref().map(|a| a.authorization());
+        let exemplars = self.exemplars;
+        if exemplars {
+            super::telemetry::set_exemplars_enabled(true);
+        }
+
+        std::thread::Builder::new()
+            .name(String::from("prosa-prometheus"))
+            .spawn(move || {
+                for request in server.incoming_requests() {
+                    let response = Self::respond(
+                        &request,
+                        &path,
+                        authorization.as_deref(),
+                        exemplars,
+                        &registry,
+                    );
+                    let _ = request.respond(response);
+                }
+            })
+            .map_err(|e| MetricsError::Other(e.to_string()))?;
+        Ok(())
+    }
+
+    /// Method to build the response of a scrape request
+    fn respond(
+        request: &tiny_http::Request,
+        path: &str,
+        authorization: Option<&str>,
+        exemplars: bool,
+        registry: &prometheus::Registry,
+    ) -> tiny_http::Response<std::io::Cursor<Vec<u8>>> {
+        use prometheus::Encoder as _;
+
+        let request_path = request.url().split('?').next().unwrap_or_default();
+        if request_path != path {
+            return tiny_http::Response::from_string("Not found").with_status_code(404);
+        }
+
+        if let Some(authorization) = authorization {
+            if !request
+                .headers()
+                .iter()
+                .any(|h| h.field.equiv("Authorization") && h.value.as_str() == authorization)
+            {
+                let mut response =
+                    tiny_http::Response::from_string("Unauthorized").with_status_code(401);
+                if let Ok(header) =
+                    tiny_http::Header::from_bytes("WWW-Authenticate", "Basic realm=\"prosa\"")
+                {
+                    response.add_header(header);
+                }
+                return response;
+            }
+        }
+
+        // Exemplars are only supported by the OpenMetrics format, if the scraper accept it
+        if exemplars
+            && request.headers().iter().any(|h| {
+                h.field.equiv("Accept") && h.value.as_str().contains("application/openmetrics-text")
+            })
+        {
+            let mut response = tiny_http::Response::from_string(
+                super::telemetry::encode_openmetrics(&registry.gather()),
+            );
+            if let Ok(header) = tiny_http::Header::from_bytes(
+                "Content-Type",
+                super::telemetry::OPENMETRICS_CONTENT_TYPE,
+            ) {
+                response.add_header(header);
+            }
+            return response;
+        }
+
+        let encoder = prometheus::TextEncoder::new();
+        let mut buffer = Vec::new();
+        match encoder.encode(&registry.gather(), &mut buffer) {
+            Ok(()) => {
+                let mut response = tiny_http::Response::from_data(buffer);
+                if let Ok(header) =
+                    tiny_http::Header::from_bytes("Content-Type", encoder.format_type())
+                {
+                    response.add_header(header);
+                }
+                response
+            }
+            Err(e) => tiny_http::Response::from_string(e.to_string()).with_status_code(500),
+        }
+    }
 }
 
 #[cfg(feature = "config-observability-prometheus")]
@@ -100,6 +282,9 @@ impl Default for PrometheusExporterCfg {
 
         PrometheusExporterCfg {
             endpoint: format!("0.0.0.0:{}", port),
+            path: Self::get_default_path(),
+            basic_auth: None,
+            exemplars: false,
         }
     }
 }
@@ -132,9 +317,18 @@ impl TelemetryMetrics {
         }
     }
 
-    /// Build a meter provider based on the self configuration
-    fn build_provider(&self) -> Result<SdkMeterProvider, MetricsError> {
-        let mut meter_provider = SdkMeterProvider::builder();
+    /// Build a meter provider based on the self configuration, with the registry of its prometheus exporter
+    fn build_provider(
+        &self,
+        resource: &Resource,
+    ) -> Result<(SdkMeterProvider, PrometheusRegistry), MetricsError> {
+        #[cfg_attr(
+            not(feature = "config-observability-prometheus"),
+            allow(unused_mut, clippy::let_unit_value)
+        )]
+        let mut prometheus_registry = PrometheusRegistry::default();
+        let mut meter_provider =
+            SdkMeterProvider::builder().with_resource(Resource::default().merge(resource));
         if let Some(s) = &self.otlp {
             let c = ExportConfig::from(s.clone());
             let agregator = Box::new(DefaultAggregationSelector::new());
@@ -154,20 +348,25 @@ impl TelemetryMetrics {
             let registry = prometheus::Registry::new();
 
             // configure OpenTelemetry to use this registry
-            let exporter = opentelemetry_prometheus::exporter()
+            let mut exporter = opentelemetry_prometheus::exporter()
                 .with_registry(registry.clone())
-                .without_target_info()
-                .without_scope_info()
-                .build()
-                .unwrap();
+                .without_target_info();
+            if resource.is_empty() {
+                exporter = exporter.without_scope_info();
+            } else {
+                // Label every metric with the resource attributes (only done by the exporter with the scope labels)
+                exporter = exporter.with_resource_selector(
+                    opentelemetry_prometheus::ResourceSelector::KeyAllowList(
+                        resource.iter().map(|(key, _)| key.clone()).collect(),
+                    ),
+                );
+            }
+            let exporter = exporter.build().unwrap();
 
             meter_provider = meter_provider.with_reader(exporter);
 
-            let mut prom_builder = prom.builder()?;
-            prom_builder.with_registry(registry);
-            prom_builder
-                .start()
-                .map_err(|e| MetricsError::Other(e.to_string()))?;
+            prom.start(registry.clone())?;
+            prometheus_registry = Some(registry);
         }
 
         if self.stdout.is_some() {
@@ -176,7 +375,7 @@ impl TelemetryMetrics {
             meter_provider = meter_provider.with_reader(reader);
         }
 
-        Ok(meter_provider.build())
+        Ok((meter_provider.build(), prometheus_registry))
     }
 }
 
@@ -220,8 +419,9 @@ impl TelemetryData {
     }
 
     /// Build a logger provider based on the self configuration
-    fn build_logger_provider(&self) -> Result<LoggerProvider, LogError> {
-        let mut logs_provider = LoggerProvider::builder();
+    fn build_logger_provider(&self, resource: &Resource) -> Result<LoggerProvider, LogError> {
+        let mut logs_provider =
+            LoggerProvider::builder().with_resource(Resource::default().merge(resource));
         if let Some(s) = &self.otlp {
             let c = ExportConfig::from(s.clone());
             let exporter = opentelemetry_otlp::new_exporter()
@@ -236,8 +436,11 @@ impl TelemetryData {
     }
 
     /// Build a tracer provider based on the self configuration
-    fn build_tracer_provider(&self) -> Result<TracerProvider, TraceError> {
-        let mut trace_provider = TracerProvider::builder();
+    fn build_tracer_provider(&self, resource: &Resource) -> Result<TracerProvider, TraceError> {
+        let mut trace_provider = TracerProvider::builder().with_config(
+            opentelemetry_sdk::trace::Config::default()
+                .with_resource(Resource::default().merge(resource)),
+        );
         if let Some(s) = &self.otlp {
             let c = ExportConfig::from(s.clone());
             let exporter = opentelemetry_otlp::new_exporter()
@@ -252,8 +455,11 @@ impl TelemetryData {
     }
 
     /// Build a tracer provider based on the self configuration
-    fn build_tracer(&self) -> Result<Tracer, TraceError> {
-        let mut trace_provider = TracerProvider::builder();
+    fn build_tracer(&self, resource: &Resource) -> Result<Tracer, TraceError> {
+        let mut trace_provider = TracerProvider::builder().with_config(
+            opentelemetry_sdk::trace::Config::default()
+                .with_resource(Resource::default().merge(resource)),
+        );
         if let Some(s) = &self.otlp {
             let c = ExportConfig::from(s.clone());
             let exporter = opentelemetry_otlp::new_exporter()
@@ -309,6 +515,9 @@ pub struct Observability {
     logs: Option<TelemetryData>,
     /// Traces settings of a ProSA
     traces: Option<TelemetryData>,
+    /// Resource attributes added to all the telemetry (identity of the ProSA instance)
+    #[serde(skip)]
+    resource: Option<Resource>,
 }
 
 impl Observability {
@@ -319,9 +528,22 @@ impl Observability {
             metrics: Some(TelemetryMetrics::default()),
             logs: Some(TelemetryData::default()),
             traces: Some(TelemetryData::default()),
+            resource: None,
         }
     }
 
+    /// Setter of the resource attributes added to all the telemetry of the built providers (to identify the ProSA instance)
+    ///
+    /// For prometheus, those attributes are added as labels to every metric.
+    pub fn set_resource(&mut self, resource: Resource) {
+        self.resource = Some(resource);
+    }
+
+    /// Getter of the resource attributes added to all the telemetry (empty if not set)
+    fn get_resource(&self) -> Resource {
+        self.resource.clone().unwrap_or_else(Resource::empty)
+    }
+
     /// Getter of the log level (max value)
     pub fn get_logger_level(&self) -> TelemetryLevel {
         if let Some(logs) = &self.logs {
@@ -339,16 +561,36 @@ impl Observability {
     /// Meter provider builder
     pub fn build_meter_provider(&self) -> SdkMeterProvider {
         if let Some(settings) = &self.metrics {
-            settings.build_provider().unwrap_or_default()
+            settings
+                .build_provider(&self.get_resource())
+                .map(|(meter_provider, _)| meter_provider)
+                .unwrap_or_default()
         } else {
             SdkMeterProvider::default()
         }
     }
 
+    /// Meter provider builder that also return the registry of the prometheus exporter (if configured)
+    ///
+    /// Custom collectors can be registered in the registry to be exposed with the ProSA metrics.
+    /// The registry is thread safe, and can be cloned to register collectors from any thread.
+    #[cfg(feature = "config-observability-prometheus")]
+    pub fn build_meter_provider_with_registry(
+        &self,
+    ) -> (SdkMeterProvider, Option<prometheus::Registry>) {
+        if let Some(settings) = &self.metrics {
+            settings
+                .build_provider(&self.get_resource())
+                .unwrap_or_default()
+        } else {
+            (SdkMeterProvider::default(), None)
+        }
+    }
+
     /// Logger provider builder
     pub fn build_logger_provider(&self) -> LoggerProvider {
         if let Some(settings) = &self.logs {
-            match settings.build_logger_provider() {
+            match settings.build_logger_provider(&self.get_resource()) {
                 Ok(m) => m,
                 Err(_) => LoggerProvider::builder().build(),
             }
@@ -370,7 +612,9 @@ impl Observability {
     /// ```
     pub fn build_tracer_provider(&self) -> TracerProvider {
         if let Some(settings) = &self.traces {
-            settings.build_tracer_provider().unwrap_or_default()
+            settings
+                .build_tracer_provider(&self.get_resource())
+                .unwrap_or_default()
         } else {
             TracerProvider::default()
         }
@@ -388,7 +632,7 @@ impl Observability {
     /// ```
     pub fn build_tracer(&self) -> Tracer {
         if let Some(settings) = &self.traces {
-            match settings.build_tracer() {
+            match settings.build_tracer(&self.get_resource()) {
                 Ok(m) => m,
                 Err(_) => TracerProvider::default().tracer(OTLPExporterCfg::DEFAULT_TRACER_NAME),
             }
@@ -452,6 +696,64 @@ impl Default for Observability {
                     level: Some(TelemetryLevel::DEBUG),
                 }),
             }),
+            resource: None,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "config-observability-prometheus")]
+    #[test]
+    fn prometheus_resource_labels() {
+        use opentelemetry::{metrics::MeterProvider as _, KeyValue};
+        use std::io::{Read as _, Write as _};
+
+        let endpoint = "127.0.0.1:41920";
+        let mut observability = Observability {
+            level: TelemetryLevel::default(),
+            metrics: Some(TelemetryMetrics {
+                otlp: None,
+                prometheus: Some(PrometheusExporterCfg {
+                    endpoint: endpoint.into(),
+                    ..Default::default()
+                }),
+                stdout: None,
+            }),
+            logs: None,
+            traces: None,
+            resource: None,
+        };
+        observability.set_resource(Resource::new([
+            KeyValue::new("service.name", "prosa-test"),
+            KeyValue::new("service.instance.id", "prosa-test-1"),
+        ]));
+
+        let meter_provider = observability.build_meter_provider();
+        meter_provider
+            .meter("prosa_test")
+            .u64_counter("prosa_test_resource")
+            .init()
+            .add(1, &[KeyValue::new("type", "test")]);
+
+        let mut stream = std::net::TcpStream::connect(endpoint).unwrap();
+        stream
+            .write_all(b"GET /metrics HTTP/1.0\r\nHost: localhost\r\n\r\n")
+            .unwrap();
+        let mut metrics = String::new();
+        stream.read_to_string(&mut metrics).unwrap();
+
+        let metric = metrics
+            .lines()
+            .find(|l| l.starts_with("prosa_test_resource"))
+            .expect("The metric is not exported");
+        assert!(metric.contains("service_name=\"prosa-test\""), "{metric}");
+        assert!(
+            metric.contains("service_instance_id=\"prosa-test-1\""),
+            "{metric}"
+        );
+        assert!(metric.contains("type=\"test\""), "{metric}");
+    }
+}
@@ -0,0 +1,211 @@
+//! Module to handle secrets of configurations
+//!
+//! Sensitive values (passphrases, credentials, ...) should not be written in plaintext configuration files.
+//! A [`Secret`] can reference an environment variable or a file (like `/run/secrets/x`) that is resolved when the configuration is loaded.
+
+use std::{env, fmt, fs};
+
+use serde::{Deserialize, Serialize};
+
+use super::ConfigError;
+
+/// Redacted representation of a secret value
+const REDACTED: &str = "******";
+
+/// Reference form of a secret, as written in configuration files
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(untagged)]
+enum SecretRef {
+    /// Secret read from an environment variable
+    Env { env: String },
+    /// Secret read from a file
+    File { file: String },
+    /// Secret written directly in the configuration
+    Literal(String),
+}
+
+impl fmt::Display for SecretRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SecretRef::Env { env } => write!(f, "env:{}", env),
+            SecretRef::File { file } => write!(f, "file:{}", file),
+            SecretRef::Literal(_) => write!(f, "{}", REDACTED),
+        }
+    }
+}
+
+/// Sensitive string value of a configuration
+///
+/// The secret can be written in the configuration as:
+/// - a literal: `passphrase = "value"`
+/// - an environment variable: `passphrase = { env = "VAR" }`
+/// - a file (with trailing line break removed): `passphrase = { file = "/run/secrets/x" }`
+///
+/// The secret is resolved when the configuration is loaded.
+/// Its value is never displayed by `Debug` or serialized, the reference form is serialized instead (literals are redacted).
+///
+/// ```
+/// use prosa_utils::config::secret::Secret;
+///
+/// let secret: Secret = serde_yaml::from_str("{ env: PATH }").unwrap();
+/// assert_eq!(std::env::var("PATH").unwrap(), secret.expose());
+/// assert_eq!("Secret(env:PATH)", format!("{:?}", secret));
+/// ```
+#[derive(Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(try_from = "SecretRef", into = "SecretRef")]
+pub struct Secret {
+    reference: SecretRef,
+    value: String,
+}
+
+impl Secret {
+    /// Method to create a secret from a literal value
+    pub fn new<T>(value: T) -> Secret
+    where
+        T: Into<String>,
+    {
+        let value = value.into();
+        Secret {
+            reference: SecretRef::Literal(value.clone()),
+            value,
+        }
+    }
+
+    /// Method to create a secret from an environment variable
+    pub fn from_env<T>(var: T) -> Result<Secret, ConfigError>
+    where
+        T: Into<String>,
+    {
+        Secret::try_from(SecretRef::Env { env: var.into() })
+    }
+
+    /// Method to create a secret from a file
+    pub fn from_file<T>(path: T) -> Result<Secret, ConfigError>
+    where
+        T: Into<String>,
+    {
+        Secret::try_from(SecretRef::File { file: path.into() })
+    }
+
+    /// Getter of the secret value
+    pub fn expose(&self) -> &str {
+        self.value.as_str()
+    }
+}
+
+impl From<String> for Secret {
+    fn from(value: String) -> Self {
+        Secret::new(value)
+    }
+}
+
+impl From<&str> for Secret {
+    fn from(value: &str) -> Self {
+        Secret::new(value)
+    }
+}
+
+impl TryFrom<SecretRef> for Secret {
+    type Error = ConfigError;
+
+    fn try_from(reference: SecretRef) -> Result<Self, Self::Error> {
+        let value = match &reference {
+            SecretRef::Env { env } => env::var(env)
+                .map_err(|e| ConfigError::Secret(reference.to_string(), e.to_string()))?,
+            SecretRef::File { file } => fs::read_to_string(file)
+                .map_err(|e| ConfigError::Secret(reference.to_string(), e.to_string()))?
+                .trim_end_matches(['\r', '\n'])
+                .to_string(),
+            SecretRef::Literal(value) => value.clone(),
+        };
+
+        Ok(Secret { reference, value })
+    }
+}
+
+impl From<Secret> for SecretRef {
+    fn from(secret: Secret) -> Self {
+        match secret.reference {
+            SecretRef::Literal(_) => SecretRef::Literal(REDACTED.into()),
+            reference => reference,
+        }
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Secret({})", self.reference)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Deserialize, Serialize)]
+    struct TestConfig {
+        secret: Secret,
+    }
+
+    #[test]
+    fn secret_literal() {
+        let config: TestConfig = toml::from_str("secret = \"my_value\"").unwrap();
+        assert_eq!("my_value", config.secret.expose());
+        assert_eq!(Secret::new("my_value"), config.secret);
+        assert!(!format!("{:?}", config).contains("my_value"));
+
+        let serialized = toml::to_string(&config).unwrap();
+        assert!(!serialized.contains("my_value"));
+        assert_eq!(format!("secret = \"{}\"\n", REDACTED), serialized);
+    }
+
+    #[test]
+    fn secret_env() {
+        env::set_var("PROSA_TEST_SECRET_ENV", "env_value");
+        let config: TestConfig =
+            toml::from_str("secret = { env = \"PROSA_TEST_SECRET_ENV\" }").unwrap();
+        assert_eq!("env_value", config.secret.expose());
+        assert_eq!(
+            "Secret(env:PROSA_TEST_SECRET_ENV)",
+            format!("{:?}", config.secret)
+        );
+
+        // Round-trip serialization keep the reference
+        let serialized = toml::to_string(&config).unwrap();
+        assert!(!serialized.contains("env_value"));
+        let config: TestConfig = toml::from_str(&serialized).unwrap();
+        assert_eq!("env_value", config.secret.expose());
+
+        let err = toml::from_str::<TestConfig>("secret = { env = \"PROSA_TEST_SECRET_MISSING\" }")
+            .unwrap_err();
+        assert!(err.to_string().contains("env:PROSA_TEST_SECRET_MISSING"));
+    }
+
+    #[test]
+    fn secret_file() {
+        let secret_path = env::temp_dir().join("prosa_test_secret_file");
+        fs::write(&secret_path, "file_value\n").unwrap();
+        let secret_path = secret_path.to_str().unwrap();
+
+        let config: TestConfig =
+            serde_yaml::from_str(&format!("secret:\n  file: {}", secret_path)).unwrap();
+        assert_eq!("file_value", config.secret.expose());
+        assert_eq!(
+            format!("Secret(file:{})", secret_path),
+            format!("{:?}", config.secret)
+        );
+
+        // Round-trip serialization keep the reference
+        let serialized = serde_yaml::to_string(&config).unwrap();
+        assert!(!serialized.contains("file_value"));
+        let config: TestConfig = serde_yaml::from_str(&serialized).unwrap();
+        assert_eq!("file_value", config.secret.expose());
+        assert_eq!(
+            Secret::from_file(secret_path).unwrap().expose(),
+            config.secret.expose()
+        );
+
+        let err = Secret::from_file("/prosa/missing/secret").unwrap_err();
+        assert!(err.to_string().contains("file:/prosa/missing/secret"));
+    }
+}
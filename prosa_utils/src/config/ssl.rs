@@ -6,22 +6,44 @@ use openssl::{
     asn1::{Asn1Integer, Asn1Time},
     bn::{BigNum, MsbOption},
     ec::{Asn1Flag, EcGroup, EcKey},
+    ex_data::Index,
     hash::MessageDigest,
     nid::Nid,
+    pkcs7::Pkcs7,
     pkey::PKey,
-    ssl::{AlpnError, SslContextBuilder, SslFiletype, SslMethod, SslVerifyMode},
-    x509::{extension::SubjectAlternativeName, X509NameBuilder, X509},
+    ssl::{
+        AlpnError, Ssl, SslContext, SslContextBuilder, SslContextRef, SslFiletype, SslMethod,
+        SslOptions, SslRef, SslSession, SslSessionCacheMode, SslVerifyMode, SslVersion,
+    },
+    x509::{
+        extension::SubjectAlternativeName,
+        store::{X509Lookup, X509StoreBuilder},
+        verify::X509VerifyFlags,
+        X509NameBuilder, X509,
+    },
 };
 use serde::{Deserialize, Serialize};
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     ffi::OsStr,
     fmt, fs,
+    net::IpAddr,
     ops::DerefMut,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, OnceLock,
+    },
+    thread,
     time::{self, Duration},
 };
 
-use super::{os_country, ConfigError};
+use super::{os_country, secret::Secret, ConfigError};
+
+pub mod pkcs11;
+use pkcs11::Pkcs11Config;
+pub mod test_ca;
+use test_ca::{test_ca_store, TestCa};
 
 /// SSL configuration object for store certificates
 #[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
@@ -30,30 +52,79 @@ pub struct Store {
 }
 
 impl Store {
-    /// Method to read a certificate from its path
-    fn get_certificate(
-        path: &std::path::PathBuf,
-    ) -> Result<Option<openssl::x509::X509>, ConfigError> {
-        if path.is_file() {
-            match &path.extension().and_then(OsStr::to_str) {
-                Some("pem") => match fs::read(path) {
-                    Ok(pem_file) => Ok(Some(openssl::x509::X509::from_pem(&pem_file)?)),
-                    Err(io) => Err(ConfigError::IoFile(
-                        path.to_str().unwrap_or_default().into(),
-                        io,
-                    )),
-                },
-                Some("der") => match fs::read(path) {
-                    Ok(der_file) => Ok(Some(openssl::x509::X509::from_der(&der_file)?)),
-                    Err(io) => Err(ConfigError::IoFile(
-                        path.to_str().unwrap_or_default().into(),
-                        io,
-                    )),
-                },
-                _ => Ok(None),
+    /// Maximum depth of the linked directories followed by the store (to not loop on symbolic links)
+    const MAX_DEPTH: usize = 4;
+
+    /// Method to read the certificates of a file from its path:
+    /// - PEM files (`.pem`, `.crt`) with all their certificates (CA bundles)
+    /// - DER files (`.der`) with a single certificate
+    /// - PKCS#7 bundles (`.p7b`, `.p7c`) in PEM or DER
+    fn get_certificates(path: &Path) -> Result<Vec<openssl::x509::X509>, ConfigError> {
+        let extension = path.extension().and_then(OsStr::to_str);
+        if !path.is_file() || !matches!(extension, Some("pem" | "crt" | "der" | "p7b" | "p7c")) {
+            return Ok(Vec::new());
+        }
+
+        let file = fs::read(path)
+            .map_err(|io| ConfigError::IoFile(path.to_str().unwrap_or_default().into(), io))?;
+        match extension {
+            Some("der") => Ok(vec![openssl::x509::X509::from_der(&file)?]),
+            Some("p7b" | "p7c") => {
+                let pkcs7 = if file.starts_with(b"-----BEGIN") {
+                    Pkcs7::from_pem(&file)?
+                } else {
+                    Pkcs7::from_der(&file)?
+                };
+                Ok(pkcs7
+                    .signed()
+                    .and_then(|signed| signed.certificates())
+                    .map(|certs| certs.iter().map(|cert| cert.to_owned()).collect())
+                    .unwrap_or_default())
+            }
+            _ => Ok(openssl::x509::X509::stack_from_pem(&file)?),
+        }
+    }
+
+    /// Method to walk a path of the store to list its files.
+    /// Symbolic links to directories are followed up to [`Store::MAX_DEPTH`], and each directory is walked only once
+    fn walk_files(
+        path: PathBuf,
+        depth: usize,
+        visited: &mut HashSet<PathBuf>,
+        files: &mut Vec<PathBuf>,
+    ) {
+        if path.is_dir() {
+            if path.is_symlink()
+                && depth < Self::MAX_DEPTH
+                && fs::canonicalize(&path).is_ok_and(|dir| visited.insert(dir))
+            {
+                if let Ok(entries) = fs::read_dir(&path) {
+                    let mut entries: Vec<PathBuf> =
+                        entries.flatten().map(|entry| entry.path()).collect();
+                    entries.sort();
+                    for entry in entries {
+                        Self::walk_files(entry, depth + 1, visited, files);
+                    }
+                }
             }
         } else {
-            Ok(None)
+            files.push(path);
+        }
+    }
+
+    /// Method to get all the files of the store
+    fn get_files(&self) -> Result<Vec<PathBuf>, ConfigError> {
+        match glob(&(self.path.clone() + "*")) {
+            Ok(paths) => {
+                let mut files = Vec::new();
+                let mut visited = HashSet::new();
+                for path in paths.flatten() {
+                    Self::walk_files(path, 0, &mut visited, &mut files);
+                }
+
+                Ok(files)
+            }
+            Err(e) => Err(ConfigError::WrongPath(self.path.clone(), e)),
         }
     }
 
@@ -72,22 +143,25 @@ impl Store {
     /// let openssl_store: openssl::x509::store::X509Store = store.get_store().unwrap();
     /// ```
     pub fn get_store(&self) -> Result<openssl::x509::store::X509Store, ConfigError> {
-        match glob(&(self.path.clone() + "*")) {
-            Ok(certs) => {
-                let mut store = openssl::x509::store::X509StoreBuilder::new()?;
-                for cert_path in certs.flatten() {
-                    if let Some(cert) = Self::get_certificate(&cert_path)? {
-                        store.add_cert(cert)?;
-                    }
-                }
+        Ok(self.get_store_builder()?.build())
+    }
 
-                Ok(store.build())
+    /// Method to get an OpenSSL cert store builder filled with all the store certificates
+    fn get_store_builder(&self) -> Result<X509StoreBuilder, ConfigError> {
+        let mut store = X509StoreBuilder::new()?;
+        for cert_path in self.get_files()? {
+            for cert in Self::get_certificates(&cert_path)? {
+                store.add_cert(cert)?;
             }
-            Err(e) => Err(ConfigError::WrongPath(self.path.clone(), e)),
         }
+
+        Ok(store)
     }
 
-    /// Method to get all OpenSSL certificate with their names as key
+    /// Method to get all OpenSSL certificate with their names as key.
+    ///
+    /// Certificates are named with their file path (without extension).
+    /// Certificates of a bundle are named with their file path followed by their common name, and the serial number is appended to the names that are already taken.
     ///
     /// ```
     /// use prosa_utils::config::ssl::Store;
@@ -99,27 +173,35 @@ impl Store {
     /// assert!(certs_map.is_empty());
     /// ```
     pub fn get_certs(&self) -> Result<HashMap<String, openssl::x509::X509>, ConfigError> {
-        match glob(&(self.path.clone() + "*")) {
-            Ok(certs) => {
-                let mut certs_map = HashMap::new();
-                for cert_path in certs.flatten() {
-                    if let Some(cert_path_name) = cert_path.to_str() {
-                        if let Some(cert_name) = cert_path_name.strip_suffix(".pem") {
-                            if let Some(cert) = Self::get_certificate(&cert_path)? {
-                                certs_map.insert(cert_name.into(), cert);
-                            }
-                        } else if let Some(cert_name) = cert_path_name.strip_suffix(".der") {
-                            if let Some(cert) = Self::get_certificate(&cert_path)? {
-                                certs_map.insert(cert_name.into(), cert);
-                            }
-                        }
-                    }
-                }
+        let mut certs_map = HashMap::new();
+        for cert_path in self.get_files()? {
+            let mut certs = Self::get_certificates(&cert_path)?;
+            let file_name = cert_path.with_extension("").to_string_lossy().to_string();
+            if certs.len() == 1 {
+                certs_map.insert(file_name, certs.remove(0));
+                continue;
+            }
 
-                Ok(certs_map)
+            for cert in certs {
+                let common_name = cert
+                    .subject_name()
+                    .entries_by_nid(Nid::COMMONNAME)
+                    .next()
+                    .and_then(|entry| entry.data().to_string().ok())
+                    .unwrap_or_default();
+                let mut cert_name = format!("{}/{}", file_name, common_name);
+                if certs_map.contains_key(&cert_name) {
+                    cert_name = format!(
+                        "{}#{}",
+                        cert_name,
+                        cert.serial_number().to_bn()?.to_hex_str()?
+                    );
+                }
+                certs_map.insert(cert_name, cert);
             }
-            Err(e) => Err(ConfigError::WrongPath(self.path.clone(), e)),
         }
+
+        Ok(certs_map)
     }
 }
 
@@ -153,6 +235,395 @@ impl fmt::Display for Store {
     }
 }
 
+/// OCSP response stapled by a server, refreshed from its file by a background thread at every refresh interval
+///
+/// The TLS status callback only reads the cached response, so the handshake never waits on the file system.
+#[derive(Debug)]
+struct OcspStaple {
+    response: Mutex<Arc<[u8]>>,
+}
+
+impl OcspStaple {
+    /// Method to load the OCSP response from its file, and refresh it in the background as long as the staple is used
+    fn load(path: String, refresh: Duration) -> Result<Arc<OcspStaple>, ConfigError> {
+        let response = match fs::read(&path) {
+            Ok(response) => response,
+            Err(io) => return Err(ConfigError::IoFile(path, io)),
+        };
+        let ocsp_staple = Arc::new(OcspStaple {
+            response: Mutex::new(response.into()),
+        });
+
+        let weak_staple = Arc::downgrade(&ocsp_staple);
+        let refresh_path = path.clone();
+        thread::Builder::new()
+            .name(String::from("ocsp_staple"))
+            .spawn(move || loop {
+                thread::sleep(refresh);
+                let Some(ocsp_staple) = weak_staple.upgrade() else {
+                    break;
+                };
+
+                // If the file can't be read anymore, the last known response is kept
+                match fs::read(&refresh_path) {
+                    Ok(response) => {
+                        if let Ok(mut cached_response) = ocsp_staple.response.lock() {
+                            *cached_response = response.into();
+                        }
+                    }
+                    Err(e) => log::warn!(
+                        "Can't refresh the OCSP staple {}, the last response is kept: {}",
+                        refresh_path,
+                        e
+                    ),
+                }
+            })
+            .map_err(|io| ConfigError::IoFile(path, io))?;
+
+        Ok(ocsp_staple)
+    }
+
+    /// Getter of the cached OCSP response to staple
+    fn get_response(&self) -> Option<Arc<[u8]>> {
+        self.response.lock().ok().map(|response| response.clone())
+    }
+}
+
+/// Version of the TLS protocol
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum TlsVersion {
+    /// TLS 1.2
+    #[serde(rename = "TLS1.2")]
+    Tls12,
+    /// TLS 1.3
+    #[serde(rename = "TLS1.3")]
+    Tls13,
+}
+
+impl From<TlsVersion> for SslVersion {
+    fn from(version: TlsVersion) -> Self {
+        match version {
+            TlsVersion::Tls12 => SslVersion::TLS1_2,
+            TlsVersion::Tls13 => SslVersion::TLS1_3,
+        }
+    }
+}
+
+/// In-memory cache of the client SSL sessions, keyed by target, to resume them on reconnect instead of doing a full handshake
+///
+/// The cache is attached to the client SSL context when the `session_cache_size` is set (see [`SslConfig::set_session_cache_size`]).
+/// The client set the target of its SSL session with [`SslSessionCache::resume`] before the handshake, and count it with [`SslSessionCache::handshake_done`] once it's done.
+#[derive(Clone)]
+pub struct SslSessionCache {
+    capacity: usize,
+    sessions: Arc<Mutex<HashMap<String, SslSession>>>,
+    resumed: Arc<AtomicU64>,
+    full: Arc<AtomicU64>,
+}
+
+impl SslSessionCache {
+    fn new(capacity: usize) -> SslSessionCache {
+        SslSessionCache {
+            capacity,
+            sessions: Arc::new(Mutex::new(HashMap::with_capacity(capacity))),
+            resumed: Arc::new(AtomicU64::new(0)),
+            full: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Index of the session cache in the SSL context
+    fn context_index() -> Result<Index<SslContext, SslSessionCache>, ConfigError> {
+        static INDEX: OnceLock<Index<SslContext, SslSessionCache>> = OnceLock::new();
+        if let Some(index) = INDEX.get() {
+            Ok(*index)
+        } else {
+            let index = SslContext::new_ex_index()?;
+            Ok(*INDEX.get_or_init(|| index))
+        }
+    }
+
+    /// Index of the session target in the SSL session
+    fn target_index() -> Result<Index<Ssl, String>, ConfigError> {
+        static INDEX: OnceLock<Index<Ssl, String>> = OnceLock::new();
+        if let Some(index) = INDEX.get() {
+            Ok(*index)
+        } else {
+            let index = Ssl::new_ex_index()?;
+            Ok(*INDEX.get_or_init(|| index))
+        }
+    }
+
+    /// Method to attach the cache to a client SSL context, to store its new sessions
+    fn attach(self, context_builder: &mut SslContextBuilder) -> Result<(), ConfigError> {
+        context_builder.set_session_cache_mode(
+            SslSessionCacheMode::CLIENT | SslSessionCacheMode::NO_INTERNAL_STORE,
+        );
+
+        let target_index = Self::target_index()?;
+        let cache = self.clone();
+        context_builder.set_new_session_callback(move |ssl, session| {
+            if let Some(target) = ssl.ex_data(target_index) {
+                cache.insert(target.clone(), session);
+            }
+        });
+        context_builder.set_ex_data(Self::context_index()?, self);
+        Ok(())
+    }
+
+    fn insert(&self, target: String, session: SslSession) {
+        let mut sessions = self.sessions.lock().unwrap();
+        if sessions.len() >= self.capacity && !sessions.contains_key(&target) {
+            if let Some(evicted) = sessions.keys().next().cloned() {
+                sessions.remove(&evicted);
+            }
+        }
+
+        sessions.insert(target, session);
+    }
+
+    /// Getter of the session cache attached to a client SSL context
+    pub fn from_context(context: &SslContextRef) -> Option<&SslSessionCache> {
+        Self::context_index()
+            .ok()
+            .and_then(|index| context.ex_data(index))
+    }
+
+    /// Method to set the target (`host:port`) of an SSL session before its handshake.
+    /// The last session of the target is resumed if the SSL context have a session cache, otherwise it does nothing.
+    pub fn resume(ssl: &mut SslRef, target: &str) -> Result<(), ConfigError> {
+        let session = if let Some(cache) = Self::from_context(ssl.ssl_context()) {
+            cache.sessions.lock().unwrap().get(target).cloned()
+        } else {
+            return Ok(());
+        };
+
+        ssl.set_ex_data(Self::target_index()?, target.to_string());
+        if let Some(session) = session {
+            // SAFETY: the session comes from the cache attached to the SSL context, so it was created by the same context
+            unsafe { ssl.set_session(&session)? };
+        }
+
+        Ok(())
+    }
+
+    /// Method to count a finished handshake (resumed or full) in the session cache of its SSL context
+    pub fn handshake_done(ssl: &SslRef) {
+        if let Some(cache) = Self::from_context(ssl.ssl_context()) {
+            if ssl.session_reused() {
+                cache.resumed.fetch_add(1, Ordering::Relaxed);
+            } else {
+                cache.full.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Getter of the number of targets that have a session to resume
+    pub fn len(&self) -> usize {
+        self.sessions.lock().unwrap().len()
+    }
+
+    /// Method to know if there is no session to resume
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Getter of the number of resumed handshakes
+    pub fn resumed_handshakes(&self) -> u64 {
+        self.resumed.load(Ordering::Relaxed)
+    }
+
+    /// Getter of the number of full handshakes
+    pub fn full_handshakes(&self) -> u64 {
+        self.full.load(Ordering::Relaxed)
+    }
+}
+
+impl fmt::Debug for SslSessionCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SslSessionCache")
+            .field("capacity", &self.capacity)
+            .field("len", &self.len())
+            .field("resumed", &self.resumed_handshakes())
+            .field("full", &self.full_handshakes())
+            .finish()
+    }
+}
+
+/// Hook called when a client certificate is rejected by the [`ClientAuth`] rules, with the rejection reason (use to count rejected handshakes)
+#[derive(Clone)]
+pub struct ClientRejectHook(Arc<dyn Fn(&str) + Send + Sync>);
+
+impl fmt::Debug for ClientRejectHook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ClientRejectHook")
+    }
+}
+
+impl PartialEq for ClientRejectHook {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+/// Client identities allowed to connect to a server
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize)]
+pub struct ClientAllow {
+    /// SHA-256 fingerprints of the allowed client certificates (hexadecimal, with or without `:` separators)
+    #[serde(default)]
+    pub fingerprints: Vec<String>,
+    /// Patterns of DNS subject alternative names of the allowed client certificates (`*` match a single label, ex: `*.prosa.io`)
+    #[serde(default)]
+    pub san_dns: Vec<String>,
+}
+
+impl ClientAllow {
+    /// Method to know if no rule is defined (every client with a valid certificate is allowed)
+    pub fn is_empty(&self) -> bool {
+        self.fingerprints.is_empty() && self.san_dns.is_empty()
+    }
+
+    /// Method to know if a DNS name match a SAN pattern
+    fn san_match(pattern: &str, name: &str) -> bool {
+        if let Some(suffix) = pattern.strip_prefix("*.") {
+            name.len() > suffix.len() + 1
+                && name[name.len() - suffix.len()..].eq_ignore_ascii_case(suffix)
+                && name.as_bytes()[name.len() - suffix.len() - 1] == b'.'
+                && !name[..name.len() - suffix.len() - 1].contains('.')
+        } else {
+            pattern.eq_ignore_ascii_case(name)
+        }
+    }
+
+    /// Method to check a client certificate against the rules. Return the rejection reason if it's not allowed
+    fn check(&self, cert: &openssl::x509::X509Ref) -> Result<(), String> {
+        if self.is_empty() {
+            return Ok(());
+        }
+
+        let fingerprint = cert
+            .digest(MessageDigest::sha256())
+            .map(hex::encode)
+            .map_err(|e| e.to_string())?;
+        if self
+            .fingerprints
+            .iter()
+            .any(|f| f.replace(':', "").eq_ignore_ascii_case(&fingerprint))
+        {
+            return Ok(());
+        }
+
+        let dns_names: Vec<String> = cert
+            .subject_alt_names()
+            .map(|names| {
+                names
+                    .iter()
+                    .filter_map(|n| n.dnsname().map(String::from))
+                    .collect()
+            })
+            .unwrap_or_default();
+        if dns_names.iter().any(|name| {
+            self.san_dns
+                .iter()
+                .any(|pattern| Self::san_match(pattern, name))
+        }) {
+            return Ok(());
+        }
+
+        Err(format!(
+            "the certificate (sha256 {}, SAN DNS {:?}) is not allowed",
+            fingerprint, dns_names
+        ))
+    }
+}
+
+/// Mutual TLS authentication of clients by a server
+///
+/// ```yaml
+/// client_auth:
+///   required: true
+///   allow:
+///     fingerprints:
+///       - "5E:0F:...:A2"
+///     san_dns:
+///       - "*.client.prosa.io"
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize)]
+pub struct ClientAuth {
+    /// If the client must present a certificate
+    #[serde(default)]
+    pub required: bool,
+    /// Client identities allowed to connect (every client with a valid certificate if empty)
+    #[serde(default)]
+    pub allow: ClientAllow,
+    #[serde(skip)]
+    reject_hook: Option<ClientRejectHook>,
+}
+
+/// Method to get the subject of a certificate as a string (`CN=client, O=Worldline`)
+pub fn cert_subject(cert: &openssl::x509::X509Ref) -> String {
+    cert.subject_name()
+        .entries()
+        .map(|entry| {
+            format!(
+                "{}={}",
+                entry.object().nid().short_name().unwrap_or("?"),
+                entry.data().to_string().unwrap_or_default()
+            )
+        })
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+impl ClientAuth {
+    /// Method to create a client authentication with its rules
+    pub fn new(required: bool, allow: ClientAllow) -> ClientAuth {
+        ClientAuth {
+            required,
+            allow,
+            reject_hook: None,
+        }
+    }
+
+    /// Setter of the hook called when a client certificate is rejected
+    pub fn set_reject_hook<F>(&mut self, hook: F)
+    where
+        F: Fn(&str) + Send + Sync + 'static,
+    {
+        self.reject_hook = Some(ClientRejectHook(Arc::new(hook)));
+    }
+
+    /// Method to install the client verification on a server context.
+    /// The allow rules are checked on the client certificate once its chain is validated
+    fn set_verify(&self, context_builder: &mut SslContextBuilder) {
+        let mode = if self.required {
+            SslVerifyMode::PEER | SslVerifyMode::FAIL_IF_NO_PEER_CERT
+        } else {
+            SslVerifyMode::PEER
+        };
+
+        let allow = self.allow.clone();
+        let reject_hook = self.reject_hook.clone();
+        context_builder.set_verify_callback(mode, move |preverify_ok, x509_ctx| {
+            if !preverify_ok || x509_ctx.error_depth() != 0 {
+                return preverify_ok;
+            }
+
+            if let Some(cert) = x509_ctx.current_cert() {
+                if let Err(reason) = allow.check(cert) {
+                    let reason = format!("Reject the client `{}`: {}", cert_subject(cert), reason);
+                    log::warn!(target: "prosa_utils::config::ssl", "{}", reason);
+                    if let Some(reject_hook) = &reject_hook {
+                        (reject_hook.0)(&reason);
+                    }
+                    return false;
+                }
+            }
+
+            true
+        });
+    }
+}
+
 /// SSL configuration for socket
 ///
 /// Client SSL socket
@@ -228,8 +699,10 @@ pub struct SslConfig {
     cert: Option<String>,
     /// private key
     key: Option<String>,
+    /// private key stored in an HSM, used with the `cert` certificate (need the `pkcs11` feature)
+    pkcs11: Option<Pkcs11Config>,
     /// passphrase for private key or pkcs12
-    passphrase: Option<String>,
+    passphrase: Option<Secret>,
     #[serde(default)]
     /// ALPN list send by the client, or order of ALPN accepted by the server
     alpn: Vec<String>,
@@ -241,6 +714,44 @@ pub struct SslConfig {
     #[serde(default = "SslConfig::default_ssl_timeout")]
     /// SSL operation timeout
     ssl_timeout: u64,
+    /// Certificate revocation lists (path or glob of PEM/DER files) use to check the remote certificate chain
+    crl: Option<String>,
+    #[serde(default)]
+    /// Enable OCSP stapling (only for server)
+    ocsp_stapling: bool,
+    /// Path of the DER OCSP response stapled by the server
+    ocsp_staple: Option<String>,
+    #[serde(skip_serializing)]
+    #[serde(default = "SslConfig::default_ocsp_refresh")]
+    /// Refresh interval of the OCSP staple in seconds
+    ocsp_refresh: u64,
+    /// Mutual TLS authentication of clients (only for server)
+    client_auth: Option<ClientAuth>,
+    #[serde(default)]
+    /// Don't verify that the server certificate matches the target host (only for client).
+    /// Must only be used for lab environments
+    insecure_skip_hostname_verify: bool,
+    /// Size of the SSL session cache.
+    /// For a server, it's the number of sessions kept by OpenSSL (`0` to disable the cache).
+    /// For a client, it's the number of targets whose session is kept to be resumed on reconnect (no resumption if not set)
+    session_cache_size: Option<usize>,
+    #[serde(default = "SslConfig::default_session_tickets")]
+    /// Enable the session tickets (stateless session resumption)
+    session_tickets: bool,
+    #[serde(default = "SslConfig::default_renegotiation")]
+    /// Accept the TLS 1.2 renegotiations of the remote. Can be disabled if the security policy require it.
+    /// TLS 1.3 key updates are always accepted
+    renegotiation: bool,
+    /// Minimal TLS version (`TLS1.2` or `TLS1.3`)
+    min_version: Option<TlsVersion>,
+    /// Maximal TLS version (`TLS1.2` or `TLS1.3`)
+    max_version: Option<TlsVersion>,
+    /// TLS 1.3 cipher suites, in the OpenSSL format (`TLS_AES_256_GCM_SHA384:TLS_CHACHA20_POLY1305_SHA256`)
+    ciphersuites: Option<String>,
+    /// TLS 1.2 cipher list, in the OpenSSL format (`ECDHE-ECDSA-AES256-GCM-SHA384:ECDHE-RSA-AES256-GCM-SHA384`)
+    cipher_list: Option<String>,
+    /// Directory of a certificate authority generated for test environments (see [`SslConfig::new_test_ca`])
+    test_ca: Option<String>,
 }
 
 impl SslConfig {
@@ -252,6 +763,18 @@ impl SslConfig {
         3000
     }
 
+    fn default_ocsp_refresh() -> u64 {
+        3600
+    }
+
+    fn default_session_tickets() -> bool {
+        true
+    }
+
+    fn default_renegotiation() -> bool {
+        true
+    }
+
     /// Method to create an ssl configuration from a pkcs12 manually
     /// Should be use with config instead of building it manually
     pub fn new_pkcs12(pkcs12_path: String) -> SslConfig {
@@ -260,10 +783,25 @@ impl SslConfig {
             pkcs12: Some(pkcs12_path),
             cert: None,
             key: None,
+            pkcs11: None,
             passphrase: None,
             alpn: Vec::default(),
             modern_security: Self::default_modern_security(),
             ssl_timeout: Self::default_ssl_timeout(),
+            crl: None,
+            ocsp_stapling: false,
+            ocsp_staple: None,
+            ocsp_refresh: Self::default_ocsp_refresh(),
+            client_auth: None,
+            insecure_skip_hostname_verify: false,
+            session_cache_size: None,
+            session_tickets: Self::default_session_tickets(),
+            renegotiation: Self::default_renegotiation(),
+            min_version: None,
+            max_version: None,
+            ciphersuites: None,
+            cipher_list: None,
+            test_ca: None,
         }
     }
 
@@ -279,10 +817,45 @@ impl SslConfig {
             pkcs12: None,
             cert: Some(cert_path),
             key: Some(key_path),
-            passphrase,
+            pkcs11: None,
+            passphrase: passphrase.map(Secret::from),
             alpn: Vec::default(),
             modern_security: Self::default_modern_security(),
             ssl_timeout: Self::default_ssl_timeout(),
+            crl: None,
+            ocsp_stapling: false,
+            ocsp_staple: None,
+            ocsp_refresh: Self::default_ocsp_refresh(),
+            client_auth: None,
+            insecure_skip_hostname_verify: false,
+            session_cache_size: None,
+            session_tickets: Self::default_session_tickets(),
+            renegotiation: Self::default_renegotiation(),
+            min_version: None,
+            max_version: None,
+            ciphersuites: None,
+            cipher_list: None,
+            test_ca: None,
+        }
+    }
+
+    /// Method to create an ssl configuration for test environments, with a certificate authority generated (or loaded if present) in a directory.
+    /// Servers get a certificate issued by this authority for their host, and clients trust this authority if they don't have a store.
+    /// Other clients only need the certificate authority in their store (see [`test_ca_store`])
+    ///
+    /// ```
+    /// use prosa_utils::config::ssl::SslConfig;
+    ///
+    /// let dir = std::env::temp_dir().join(format!("prosa_ssl_test_ca_doc_{}", std::process::id()));
+    /// let server_config = SslConfig::new_test_ca(dir.to_string_lossy().into_owned());
+    /// let ssl_acceptor = server_config.init_tls_server_context(Some("localhost")).unwrap().build();
+    /// assert!(ssl_acceptor.context().certificate().is_some());
+    /// # std::fs::remove_dir_all(dir).unwrap();
+    /// ```
+    pub fn new_test_ca(dir: String) -> SslConfig {
+        SslConfig {
+            test_ca: Some(dir),
+            ..Default::default()
         }
     }
 
@@ -301,6 +874,140 @@ impl SslConfig {
         self.alpn = alpn;
     }
 
+    /// Setter of the private key stored in an HSM, used with the `cert` certificate (need the `pkcs11` feature)
+    pub fn set_pkcs11(&mut self, cert_path: String, pkcs11: Pkcs11Config) {
+        self.cert = Some(cert_path);
+        self.pkcs11 = Some(pkcs11);
+    }
+
+    /// Setter of the passphrase for private key or pkcs12
+    pub fn set_passphrase(&mut self, passphrase: Secret) {
+        self.passphrase = Some(passphrase);
+    }
+
+    /// Setter of the certificate revocation lists (path or glob of PEM/DER files)
+    pub fn set_crl(&mut self, crl: String) {
+        self.crl = Some(crl);
+    }
+
+    /// Setter of the OCSP response (DER file) stapled by the server. Enable the OCSP stapling.
+    /// The response is reloaded from its file at every `refresh` interval (1 hour by default)
+    pub fn set_ocsp_staple(&mut self, ocsp_staple: String, refresh: Option<Duration>) {
+        self.ocsp_stapling = true;
+        self.ocsp_staple = Some(ocsp_staple);
+        if let Some(refresh) = refresh {
+            self.ocsp_refresh = refresh.as_secs();
+        }
+    }
+
+    /// Setter of the mutual TLS authentication of clients (only for server)
+    pub fn set_client_auth(&mut self, client_auth: ClientAuth) {
+        self.client_auth = Some(client_auth);
+    }
+
+    /// Getter of the flag to skip the verification of the server certificate hostname (only for client)
+    pub fn is_insecure_skip_hostname_verify(&self) -> bool {
+        self.insecure_skip_hostname_verify
+    }
+
+    /// Setter of the flag to skip the verification of the server certificate hostname (only for client).
+    /// The certificate chain is still verified, but it can be issued for any host. Must only be used for lab environments
+    pub fn set_insecure_skip_hostname_verify(&mut self, insecure_skip_hostname_verify: bool) {
+        self.insecure_skip_hostname_verify = insecure_skip_hostname_verify;
+    }
+
+    /// Getter of the size of the SSL session cache
+    pub fn get_session_cache_size(&self) -> Option<usize> {
+        self.session_cache_size
+    }
+
+    /// Setter of the size of the SSL session cache.
+    /// For a client, sessions are resumed on reconnect to the same target only if the size is set
+    pub fn set_session_cache_size(&mut self, session_cache_size: Option<usize>) {
+        self.session_cache_size = session_cache_size;
+    }
+
+    /// Setter to enable or disable the session tickets (enabled by default)
+    pub fn set_session_tickets(&mut self, session_tickets: bool) {
+        self.session_tickets = session_tickets;
+    }
+
+    /// Setter to accept or refuse the TLS 1.2 renegotiations (accepted by default)
+    pub fn set_renegotiation(&mut self, renegotiation: bool) {
+        self.renegotiation = renegotiation;
+    }
+
+    /// Setter of the minimal and maximal TLS versions
+    pub fn set_tls_versions(
+        &mut self,
+        min_version: Option<TlsVersion>,
+        max_version: Option<TlsVersion>,
+    ) {
+        self.min_version = min_version;
+        self.max_version = max_version;
+    }
+
+    /// Setter of the TLS 1.3 cipher suites, in the OpenSSL format
+    pub fn set_ciphersuites(&mut self, ciphersuites: String) {
+        self.ciphersuites = Some(ciphersuites);
+    }
+
+    /// Setter of the TLS 1.2 cipher list, in the OpenSSL format
+    pub fn set_cipher_list(&mut self, cipher_list: String) {
+        self.cipher_list = Some(cipher_list);
+    }
+
+    /// Getter of the mutual TLS authentication of clients, to set its reject hook for example
+    pub fn get_client_auth_mut(&mut self) -> Option<&mut ClientAuth> {
+        self.client_auth.as_mut()
+    }
+
+    /// Method to load the certificate revocation lists into a cert store
+    fn load_crl(&self, store: &mut X509StoreBuilder) -> Result<(), ConfigError> {
+        if let Some(crl) = &self.crl {
+            let crl_paths = glob(crl).map_err(|e| ConfigError::WrongPath(crl.clone(), e))?;
+            let lookup = store.add_lookup(X509Lookup::file())?;
+            let mut loaded_crl = 0;
+            for crl_path in crl_paths {
+                let crl_path = crl_path.map_err(|e| {
+                    ConfigError::WrongValue(
+                        "crl".into(),
+                        format!("{} - {}", e.path().to_string_lossy(), e.error()),
+                    )
+                })?;
+                if !crl_path.is_file() {
+                    continue;
+                }
+
+                let file_type = if crl_path.extension().and_then(OsStr::to_str) == Some("der") {
+                    SslFiletype::ASN1
+                } else {
+                    SslFiletype::PEM
+                };
+
+                lookup.load_crl_file(&crl_path, file_type).map_err(|e| {
+                    ConfigError::WrongValue(
+                        "crl".into(),
+                        format!("{} - {}", crl_path.to_string_lossy(), e),
+                    )
+                })?;
+                loaded_crl += 1;
+            }
+
+            // Without any CRL, every remote certificate would be rejected
+            if loaded_crl == 0 {
+                return Err(ConfigError::WrongValue(
+                    "crl".into(),
+                    format!("{} - no CRL file found", crl),
+                ));
+            }
+
+            store.set_flags(X509VerifyFlags::CRL_CHECK | X509VerifyFlags::CRL_CHECK_ALL)?;
+        }
+
+        Ok(())
+    }
+
     /// Method to init an SSL context for a socket
     pub(crate) fn init_tls_context<B>(
         &self,
@@ -315,7 +1022,7 @@ impl SslConfig {
             match fs::read(pkcs12_path) {
                 Ok(pkcs12_file) => {
                     let pkcs12 = openssl::pkcs12::Pkcs12::from_der(pkcs12_file.as_ref())?
-                        .parse2(self.passphrase.as_ref().unwrap_or(&String::from("")))?;
+                        .parse2(self.passphrase.as_ref().map_or("", |p| p.expose()))?;
 
                     if let Some(pkey) = pkcs12.pkey {
                         context_builder.set_private_key(&pkey)?;
@@ -333,6 +1040,10 @@ impl SslConfig {
                 }
                 Err(io) => return Err(ConfigError::IoFile(pkcs12_path.to_string(), io)),
             }
+        } else if let (Some(cert_path), Some(pkcs11)) = (&self.cert, &self.pkcs11) {
+            context_builder.set_certificate_file(cert_path, SslFiletype::PEM)?;
+            let pkey = pkcs11.load_private_key()?;
+            context_builder.set_private_key(&pkey)?;
         } else if let (Some(cert_path), Some(key_path)) = (&self.cert, &self.key) {
             context_builder.set_certificate_file(cert_path, SslFiletype::PEM)?;
 
@@ -343,7 +1054,7 @@ impl SslConfig {
                     } else if let Some(passphrase) = &self.passphrase {
                         PKey::private_key_from_pem_passphrase(
                             key_file.as_slice(),
-                            passphrase.as_bytes(),
+                            passphrase.expose().as_bytes(),
                         )?
                     } else {
                         PKey::private_key_from_pem(key_file.as_slice())?
@@ -353,6 +1064,12 @@ impl SslConfig {
                 }
                 Err(io) => return Err(ConfigError::IoFile(key_path.to_string(), io)),
             }
+        } else if let (true, Some(ca_dir)) = (is_server, &self.test_ca) {
+            let ca = TestCa::load_or_generate(ca_dir)?;
+            let (cert, pkey) = ca.issue(domain.unwrap_or("localhost"))?;
+            context_builder.set_private_key(&pkey)?;
+            context_builder.set_certificate(&cert)?;
+            context_builder.add_extra_chain_cert(ca.get_cert().clone())?;
         } else if is_server {
             let mut group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1)?;
             group.set_asn1_flag(Asn1Flag::NAMED_CURVE);
@@ -383,12 +1100,17 @@ impl SslConfig {
             cert.set_subject_name(&x509_name)?;
             cert.set_issuer_name(&x509_name)?;
 
-            // Add DNS subject alternative name if needed to check the certificate
-            if let Some(dns) = domain {
+            // Add DNS (or IP for an IP address host) subject alternative name if needed to check the certificate
+            if let Some(host) = domain {
                 let mut subject_alternative_name = SubjectAlternativeName::new();
-                let x509_extension = subject_alternative_name
-                    .dns(dns)
-                    .build(&cert.x509v3_context(None, None))?;
+                let host = host.trim_start_matches('[').trim_end_matches(']');
+                if host.parse::<IpAddr>().is_ok() {
+                    subject_alternative_name.ip(host);
+                } else {
+                    subject_alternative_name.dns(host);
+                }
+                let x509_extension =
+                    subject_alternative_name.build(&cert.x509v3_context(None, None))?;
                 cert.append_extension2(&x509_extension)?;
             }
 
@@ -397,17 +1119,32 @@ impl SslConfig {
             context_builder.set_certificate(&cert.build())?;
         }
 
-        if let Some(store) = &self.store {
-            context_builder.set_cert_store(store.get_store()?);
+        // Clients of a test environment trust its certificate authority
+        let store = match (&self.store, &self.test_ca) {
+            (None, Some(ca_dir)) if !is_server || self.client_auth.is_some() => {
+                Some(test_ca_store(ca_dir)?)
+            }
+            (store, _) => store.clone(),
+        };
+        if let Some(store) = &store {
+            let mut store_builder = store.get_store_builder()?;
+            self.load_crl(&mut store_builder)?;
+            context_builder.set_cert_store(store_builder.build());
             if is_server {
                 context_builder.set_verify(SslVerifyMode::PEER);
             }
-        } else if !is_server {
-            context_builder.set_cert_store(Store::default().get_store()?);
+        } else if !is_server || self.client_auth.is_some() {
+            let mut store_builder = Store::default().get_store_builder()?;
+            self.load_crl(&mut store_builder)?;
+            context_builder.set_cert_store(store_builder.build());
         } else {
             context_builder.set_verify(SslVerifyMode::NONE);
         }
 
+        if let (true, Some(client_auth)) = (is_server, &self.client_auth) {
+            client_auth.set_verify(&mut context_builder);
+        }
+
         if !self.alpn.is_empty() {
             if is_server {
                 let alpn_list = self.alpn.clone();
@@ -445,6 +1182,52 @@ impl SslConfig {
             }
         }
 
+        if let Some(min_version) = self.min_version {
+            context_builder.set_min_proto_version(Some(min_version.into()))?;
+        }
+        if let Some(max_version) = self.max_version {
+            context_builder.set_max_proto_version(Some(max_version.into()))?;
+        }
+        if let Some(ciphersuites) = &self.ciphersuites {
+            context_builder.set_ciphersuites(ciphersuites)?;
+        }
+        if let Some(cipher_list) = &self.cipher_list {
+            context_builder.set_cipher_list(cipher_list)?;
+        }
+
+        if !self.session_tickets {
+            context_builder.set_options(SslOptions::NO_TICKET);
+        }
+        if !self.renegotiation {
+            context_builder.set_options(SslOptions::NO_RENEGOTIATION);
+        }
+        match self.session_cache_size {
+            Some(0) if is_server => {
+                context_builder.set_session_cache_mode(SslSessionCacheMode::OFF);
+            }
+            Some(size) if is_server => {
+                context_builder.set_session_cache_size(i32::try_from(size).unwrap_or(i32::MAX));
+            }
+            Some(size) if size > 0 => SslSessionCache::new(size).attach(&mut context_builder)?,
+            _ => {}
+        }
+
+        if is_server && self.ocsp_stapling {
+            let ocsp_staple = self
+                .ocsp_staple
+                .as_ref()
+                .ok_or(ConfigError::WrongValue("ocsp_staple".into(), "None".into()))?;
+            let ocsp_staple =
+                OcspStaple::load(ocsp_staple.clone(), Duration::from_secs(self.ocsp_refresh))?;
+            context_builder.set_status_callback(move |ssl| match ocsp_staple.get_response() {
+                Some(response) if !response.is_empty() => {
+                    ssl.set_ocsp_status(&response)?;
+                    Ok(true)
+                }
+                _ => Ok(false),
+            })?;
+        }
+
         Ok(context_builder)
     }
 
@@ -471,6 +1254,8 @@ impl SslConfig {
 
     /// Method to init an SSL context for a server socket
     ///
+    /// Without certificate configured, a self-signed one is generated with the `domain` (DNS name or IP address) as subject alternative name.
+    ///
     /// ```
     /// use prosa_utils::config::ssl::SslConfig;
     ///
@@ -499,10 +1284,25 @@ impl Default for SslConfig {
             pkcs12: None,
             cert: None,
             key: None,
+            pkcs11: None,
             passphrase: None,
             alpn: Vec::default(),
             modern_security: Self::default_modern_security(),
             ssl_timeout: Self::default_ssl_timeout(),
+            crl: None,
+            ocsp_stapling: false,
+            ocsp_staple: None,
+            ocsp_refresh: Self::default_ocsp_refresh(),
+            client_auth: None,
+            insecure_skip_hostname_verify: false,
+            session_cache_size: None,
+            session_tickets: Self::default_session_tickets(),
+            renegotiation: Self::default_renegotiation(),
+            min_version: None,
+            max_version: None,
+            ciphersuites: None,
+            cipher_list: None,
+            test_ca: None,
         }
     }
 }
@@ -510,6 +1310,124 @@ impl Default for SslConfig {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use openssl::{
+        asn1::{Asn1Object, Asn1OctetString},
+        ssl::{Ssl, StatusType},
+        x509::{
+            extension::{AuthorityKeyIdentifier, BasicConstraints, SubjectKeyIdentifier},
+            X509CrlBuilder, X509Extension, X509Revoked,
+        },
+    };
+    use std::pin::Pin;
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio_openssl::SslStream;
+
+    fn generate_cert(
+        cn: &str,
+        serial: u32,
+        issuer: Option<(&X509, &PKey<openssl::pkey::Private>)>,
+    ) -> (X509, PKey<openssl::pkey::Private>) {
+        let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+        let pkey = PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap();
+
+        let mut x509_name = X509NameBuilder::new().unwrap();
+        x509_name.append_entry_by_text("CN", cn).unwrap();
+        let x509_name = x509_name.build();
+
+        let mut cert = X509::builder().unwrap();
+        cert.set_version(2).unwrap();
+        cert.set_pubkey(&pkey).unwrap();
+        cert.set_serial_number(&BigNum::from_u32(serial).unwrap().to_asn1_integer().unwrap())
+            .unwrap();
+        cert.set_not_before(&Asn1Time::days_from_now(0).unwrap())
+            .unwrap();
+        cert.set_not_after(&Asn1Time::days_from_now(1).unwrap())
+            .unwrap();
+        cert.set_subject_name(&x509_name).unwrap();
+        if let Some((issuer_cert, issuer_key)) = issuer {
+            cert.set_issuer_name(issuer_cert.subject_name()).unwrap();
+            cert.sign(issuer_key, MessageDigest::sha256()).unwrap();
+        } else {
+            cert.set_issuer_name(&x509_name).unwrap();
+            cert.append_extension(BasicConstraints::new().critical().ca().build().unwrap())
+                .unwrap();
+            let skid = SubjectKeyIdentifier::new()
+                .build(&cert.x509v3_context(None, None))
+                .unwrap();
+            cert.append_extension(skid).unwrap();
+            cert.sign(&pkey, MessageDigest::sha256()).unwrap();
+        }
+
+        (cert.build(), pkey)
+    }
+
+    fn generate_crl(ca: &X509, ca_key: &PKey<openssl::pkey::Private>, serial: u8) -> Vec<u8> {
+        let mut crl = X509CrlBuilder::new().unwrap();
+        crl.set_issuer_name(ca.subject_name()).unwrap();
+        crl.set_last_update(&Asn1Time::days_from_now(0).unwrap())
+            .unwrap();
+        crl.set_next_update(&Asn1Time::days_from_now(1).unwrap())
+            .unwrap();
+
+        let akid = AuthorityKeyIdentifier::new()
+            .keyid(true)
+            .build(&X509::builder().unwrap().x509v3_context(Some(ca), None))
+            .unwrap();
+        crl.append_extension(akid).unwrap();
+        let crl_number = X509Extension::new_from_der(
+            &Asn1Object::from_str("2.5.29.20").unwrap(),
+            false,
+            &Asn1OctetString::new_from_bytes(&[0x02, 0x01, 0x01]).unwrap(),
+        )
+        .unwrap();
+        crl.append_extension(crl_number).unwrap();
+
+        // SEQUENCE { INTEGER serial, UTCTime revocationDate }
+        let mut revoked_der = vec![0x30, 0x12, 0x02, 0x01, serial, 0x17, 0x0d];
+        revoked_der.extend_from_slice(b"240101000000Z");
+        crl.add_revoked(X509Revoked::from_der(&revoked_der).unwrap())
+            .unwrap();
+
+        crl.sign(ca_key, MessageDigest::sha256()).unwrap();
+        crl.build().unwrap().to_pem().unwrap()
+    }
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("prosa_ssl_{}_{}", name, std::process::id()));
+        fs::create_dir_all(dir.join("store")).unwrap();
+        fs::create_dir_all(dir.join("crl")).unwrap();
+        dir
+    }
+
+    async fn mtls_handshake(
+        server_config: &SslConfig,
+        client_config: &SslConfig,
+    ) -> Result<(), openssl::ssl::Error> {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let acceptor = server_config.init_tls_server_context(None).unwrap().build();
+        let mut connector = client_config.init_tls_client_context().unwrap();
+        connector.set_verify(SslVerifyMode::NONE);
+        let connector = connector.build();
+
+        let server = async {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut stream = SslStream::new(Ssl::new(acceptor.context()).unwrap(), stream).unwrap();
+            Pin::new(&mut stream).accept().await
+        };
+        let client = async {
+            let stream = TcpStream::connect(addr).await.unwrap();
+            let ssl = connector
+                .configure()
+                .unwrap()
+                .into_ssl("localhost")
+                .unwrap();
+            let mut stream = SslStream::new(ssl, stream).unwrap();
+            let _ = Pin::new(&mut stream).connect().await;
+        };
+
+        tokio::join!(server, client).0
+    }
 
     #[test]
     fn test_tls_server_context() {
@@ -520,4 +1438,364 @@ mod tests {
         assert!(ssl_acceptor.context().private_key().is_some());
         assert!(ssl_acceptor.context().certificate().is_some());
     }
+
+    #[test]
+    fn test_tls_handshake_settings() {
+        let ssl_config: SslConfig = serde_yaml::from_str(
+            "min_version: TLS1.2\nmax_version: TLS1.3\nsession_cache_size: 128\nsession_tickets: false\nrenegotiation: false\nciphersuites: TLS_AES_256_GCM_SHA384\ncipher_list: ECDHE-ECDSA-AES256-GCM-SHA384",
+        )
+        .unwrap();
+        assert_eq!(Some(128), ssl_config.get_session_cache_size());
+
+        let ssl_acceptor_builder = ssl_config.init_tls_server_context(None).unwrap();
+        assert!(ssl_acceptor_builder
+            .options()
+            .contains(SslOptions::NO_TICKET | SslOptions::NO_RENEGOTIATION));
+        let ssl_acceptor = ssl_acceptor_builder.build();
+        assert_eq!(128, ssl_acceptor.context().session_cache_size());
+        assert!(SslSessionCache::from_context(ssl_acceptor.context()).is_none());
+
+        let ssl_connector = ssl_config.init_tls_client_context().unwrap().build();
+        assert!(SslSessionCache::from_context(ssl_connector.context())
+            .is_some_and(|cache| cache.is_empty()));
+
+        let mut ssl_config = SslConfig::default();
+        ssl_config.set_cipher_list(String::from("UNKNOWN-CIPHER"));
+        assert!(ssl_config.init_tls_client_context().is_err());
+    }
+
+    #[test]
+    fn test_store_bundles() {
+        let dir = test_dir("bundles");
+        let store_dir = dir.join("store");
+        let store = Store::new(store_dir.to_string_lossy().to_string() + "/");
+        let (ca1, ca1_key) = generate_cert("ProSA CA", 1, None);
+        let (ca2, _) = generate_cert("ProSA CA", 2, None);
+        let (ca3, _) = generate_cert("ProSA other CA", 3, None);
+
+        // PEM bundle with all the CAs
+        let bundle: Vec<u8> = [&ca1, &ca2, &ca3]
+            .iter()
+            .flat_map(|ca| ca.to_pem().unwrap())
+            .collect();
+        fs::write(store_dir.join("ca-bundle.crt"), bundle).unwrap();
+        assert_eq!(3, store.get_store().unwrap().all_certificates().len());
+        let certs = store.get_certs().unwrap();
+        let bundle_name = store_dir.join("ca-bundle").to_string_lossy().to_string();
+        assert_eq!(3, certs.len());
+        assert_eq!(
+            Some(&ca1.to_der().unwrap()),
+            certs
+                .get(&format!("{}/ProSA CA", bundle_name))
+                .map(|cert| cert.to_der().unwrap())
+                .as_ref()
+        );
+        assert!(certs.contains_key(&format!("{}/ProSA CA#02", bundle_name)));
+        assert!(certs.contains_key(&format!("{}/ProSA other CA", bundle_name)));
+        fs::remove_file(store_dir.join("ca-bundle.crt")).unwrap();
+
+        // PKCS#7 bundles in a linked directory, with a symbolic link loop
+        let mut extra_certs = openssl::stack::Stack::new().unwrap();
+        extra_certs.push(ca2).unwrap();
+        extra_certs.push(ca3).unwrap();
+        let pkcs7 = Pkcs7::sign(
+            &ca1,
+            &ca1_key,
+            &extra_certs,
+            b"",
+            openssl::pkcs7::Pkcs7Flags::DETACHED,
+        )
+        .unwrap();
+        let sub_dir = dir.join("pkcs7");
+        fs::create_dir_all(&sub_dir).unwrap();
+        #[cfg(target_family = "unix")]
+        {
+            std::os::unix::fs::symlink(&sub_dir, store_dir.join("pkcs7")).unwrap();
+            std::os::unix::fs::symlink(&store_dir, sub_dir.join("loop")).unwrap();
+        }
+        #[cfg(not(target_family = "unix"))]
+        let sub_dir = store_dir.clone();
+        for (file_name, pkcs7_file) in [
+            ("ca-bundle.p7b", pkcs7.to_der().unwrap()),
+            ("ca-bundle.p7c", pkcs7.to_pem().unwrap()),
+        ] {
+            fs::write(sub_dir.join(file_name), pkcs7_file).unwrap();
+            assert_eq!(3, store.get_store().unwrap().all_certificates().len());
+            assert_eq!(3, store.get_certs().unwrap().len());
+            fs::remove_file(sub_dir.join(file_name)).unwrap();
+        }
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_tls_crl() {
+        let dir = test_dir("crl");
+        let (ca, ca_key) = generate_cert("ProSA CA", 1, None);
+        fs::write(dir.join("store/ca.pem"), ca.to_pem().unwrap()).unwrap();
+        fs::write(dir.join("crl/ca.pem"), generate_crl(&ca, &ca_key, 3)).unwrap();
+
+        let mut server_config = SslConfig::default();
+        server_config.set_store(Store::new(dir.join("store/").to_string_lossy().into()));
+        server_config.set_crl(dir.join("crl/*.pem").to_string_lossy().into());
+
+        // Client certificate that is not revoked
+        let (cert, key) = generate_cert("valid", 2, Some((&ca, &ca_key)));
+        fs::write(dir.join("valid.pem"), cert.to_pem().unwrap()).unwrap();
+        fs::write(
+            dir.join("valid.key"),
+            key.private_key_to_pem_pkcs8().unwrap(),
+        )
+        .unwrap();
+        let client_config = SslConfig::new_cert_key(
+            dir.join("valid.pem").to_string_lossy().into(),
+            dir.join("valid.key").to_string_lossy().into(),
+            None,
+        );
+        assert!(mtls_handshake(&server_config, &client_config).await.is_ok());
+
+        // Client certificate revoked by the CRL
+        let (cert, key) = generate_cert("revoked", 3, Some((&ca, &ca_key)));
+        fs::write(dir.join("revoked.pem"), cert.to_pem().unwrap()).unwrap();
+        fs::write(
+            dir.join("revoked.key"),
+            key.private_key_to_pem_pkcs8().unwrap(),
+        )
+        .unwrap();
+        let client_config = SslConfig::new_cert_key(
+            dir.join("revoked.pem").to_string_lossy().into(),
+            dir.join("revoked.key").to_string_lossy().into(),
+            None,
+        );
+        assert!(mtls_handshake(&server_config, &client_config)
+            .await
+            .is_err());
+
+        // Malformed CRL
+        fs::write(dir.join("crl/malformed.pem"), b"not a CRL").unwrap();
+        assert!(matches!(
+            server_config.init_tls_server_context(None),
+            Err(ConfigError::WrongValue(_, _))
+        ));
+
+        // No CRL matched by the glob
+        server_config.set_crl(dir.join("crl/*.der").to_string_lossy().into());
+        assert!(matches!(
+            server_config.init_tls_server_context(None),
+            Err(ConfigError::WrongValue(param, _)) if param == "crl"
+        ));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_ocsp_staple_refresh() {
+        use std::time::Instant;
+
+        let dir = test_dir("ocsp_refresh");
+        let path = dir.join("ocsp.der");
+        fs::write(&path, b"first response").unwrap();
+
+        let ocsp_staple =
+            OcspStaple::load(path.to_string_lossy().into(), Duration::from_millis(10)).unwrap();
+        assert_eq!(b"first response"[..], *ocsp_staple.get_response().unwrap());
+
+        // The response is refreshed in the background
+        fs::write(&path, b"second response").unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while *ocsp_staple.get_response().unwrap() != b"second response"[..] {
+            assert!(
+                Instant::now() < deadline,
+                "The OCSP staple is not refreshed"
+            );
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        // The last response is kept if the file can't be read anymore
+        fs::remove_dir_all(dir).unwrap();
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(b"second response"[..], *ocsp_staple.get_response().unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_tls_ocsp_stapling() {
+        let dir = test_dir("ocsp");
+        let ocsp_response = b"ProSA OCSP response";
+        fs::write(dir.join("ocsp.der"), ocsp_response).unwrap();
+
+        let mut server_config = SslConfig::default();
+        server_config.set_ocsp_staple(dir.join("ocsp.der").to_string_lossy().into(), None);
+        let acceptor = server_config.init_tls_server_context(None).unwrap().build();
+
+        let mut connector = SslConfig::default().init_tls_client_context().unwrap();
+        connector.set_verify(SslVerifyMode::NONE);
+        let stapled = Arc::new(Mutex::new(None));
+        let client_stapled = stapled.clone();
+        connector
+            .set_status_callback(move |ssl| {
+                *client_stapled.lock().unwrap() = ssl.ocsp_status().map(|s| s.to_vec());
+                Ok(true)
+            })
+            .unwrap();
+        let connector = connector.build();
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = async {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut stream = SslStream::new(Ssl::new(acceptor.context()).unwrap(), stream).unwrap();
+            Pin::new(&mut stream).accept().await
+        };
+        let client = async {
+            let stream = TcpStream::connect(addr).await.unwrap();
+            let mut ssl = connector
+                .configure()
+                .unwrap()
+                .into_ssl("localhost")
+                .unwrap();
+            ssl.set_status_type(StatusType::OCSP).unwrap();
+            let mut stream = SslStream::new(ssl, stream).unwrap();
+            Pin::new(&mut stream).connect().await
+        };
+
+        let (server, client) = tokio::join!(server, client);
+        assert!(server.is_ok());
+        assert!(client.is_ok());
+        assert_eq!(Some(ocsp_response.to_vec()), *stapled.lock().unwrap());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_client_allow_san_match() {
+        assert!(ClientAllow::san_match("client.prosa.io", "CLIENT.prosa.io"));
+        assert!(ClientAllow::san_match("*.prosa.io", "client.prosa.io"));
+        assert!(!ClientAllow::san_match("*.prosa.io", "prosa.io"));
+        assert!(!ClientAllow::san_match("*.prosa.io", ".prosa.io"));
+        assert!(!ClientAllow::san_match("*.prosa.io", "sub.client.prosa.io"));
+        assert!(!ClientAllow::san_match("*.prosa.io", "clientprosa.io"));
+    }
+
+    #[tokio::test]
+    async fn test_tls_client_auth() {
+        let dir = test_dir("client_auth");
+        let (ca, ca_key) = generate_cert("ProSA CA", 1, None);
+        fs::write(dir.join("store/ca.pem"), ca.to_pem().unwrap()).unwrap();
+
+        let client_config = |name: &str, serial: u32| {
+            let (cert, key) = generate_cert(name, serial, Some((&ca, &ca_key)));
+            let cert_path = dir.join(format!("{}.pem", name));
+            let key_path = dir.join(format!("{}.key", name));
+            fs::write(&cert_path, cert.to_pem().unwrap()).unwrap();
+            fs::write(&key_path, key.private_key_to_pem_pkcs8().unwrap()).unwrap();
+            let fingerprint = cert.digest(MessageDigest::sha256()).unwrap();
+            (
+                SslConfig::new_cert_key(
+                    cert_path.to_string_lossy().into(),
+                    key_path.to_string_lossy().into(),
+                    None,
+                ),
+                fingerprint
+                    .iter()
+                    .map(|b| format!("{:02X}", b))
+                    .collect::<Vec<String>>()
+                    .join(":"),
+            )
+        };
+        let (allowed_config, allowed_fingerprint) = client_config("allowed", 2);
+        let (denied_config, _) = client_config("denied", 3);
+
+        let rejected = Arc::new(Mutex::new(Vec::new()));
+        let hook_rejected = rejected.clone();
+        let mut client_auth = ClientAuth::new(
+            true,
+            ClientAllow {
+                fingerprints: vec![allowed_fingerprint],
+                san_dns: vec![String::from("*.prosa.io")],
+            },
+        );
+        client_auth.set_reject_hook(move |reason| {
+            hook_rejected.lock().unwrap().push(reason.to_string());
+        });
+        let mut server_config = SslConfig::default();
+        server_config.set_store(Store::new(dir.join("store/").to_string_lossy().into()));
+        server_config.set_client_auth(client_auth);
+
+        assert!(mtls_handshake(&server_config, &allowed_config)
+            .await
+            .is_ok());
+        assert!(rejected.lock().unwrap().is_empty());
+
+        assert!(mtls_handshake(&server_config, &denied_config)
+            .await
+            .is_err());
+        {
+            let rejected = rejected.lock().unwrap();
+            assert_eq!(1, rejected.len());
+            assert!(
+                rejected[0].starts_with("Reject the client `CN=denied`"),
+                "{}",
+                rejected[0]
+            );
+        }
+
+        // A certificate is required
+        assert!(mtls_handshake(&server_config, &SslConfig::default())
+            .await
+            .is_err());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_tls_test_ca() {
+        let dir = test_dir("test_ca").join("ca");
+        let server_config = SslConfig::new_test_ca(dir.to_string_lossy().into_owned());
+
+        // Two listeners with certificates issued by the same certificate authority
+        let mut listeners = Vec::new();
+        for host in ["localhost", "127.0.0.1"] {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let acceptor = server_config
+                .init_tls_server_context(Some(host))
+                .unwrap()
+                .build();
+            listeners.push((host, listener, acceptor));
+        }
+
+        // The client only trust the certificate authority
+        let mut client_config = SslConfig::default();
+        client_config.set_store(test_ca::test_ca_store(&dir).unwrap());
+        let connector = client_config.init_tls_client_context().unwrap().build();
+
+        for (host, listener, acceptor) in listeners {
+            let addr = listener.local_addr().unwrap();
+            let server = async {
+                let (stream, _) = listener.accept().await.unwrap();
+                let mut stream =
+                    SslStream::new(Ssl::new(acceptor.context()).unwrap(), stream).unwrap();
+                Pin::new(&mut stream).accept().await
+            };
+            let client = async {
+                let stream = TcpStream::connect(addr).await.unwrap();
+                let ssl = connector.configure().unwrap().into_ssl(host).unwrap();
+                let mut stream = SslStream::new(ssl, stream).unwrap();
+                Pin::new(&mut stream).connect().await
+            };
+
+            let (server, client) = tokio::join!(server, client);
+            assert!(server.is_ok(), "{host}: {server:?}");
+            assert!(client.is_ok(), "{host}: {client:?}");
+        }
+
+        // The certificates are persisted, and the CA is reused
+        let ca = TestCa::load_or_generate(&dir).unwrap();
+        for host in ["localhost", "127.0.0.1"] {
+            let cert =
+                X509::from_pem(&fs::read(dir.join(format!("server_{host}.pem"))).unwrap()).unwrap();
+            assert!(cert.verify(&ca.get_cert().public_key().unwrap()).unwrap());
+        }
+
+        fs::remove_dir_all(dir.parent().unwrap()).unwrap();
+    }
 }
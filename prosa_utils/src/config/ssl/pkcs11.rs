@@ -0,0 +1,554 @@
+//! Definition of PKCS#11 private keys, stored in an HSM
+//!
+//! The private key is loaded through the OpenSSL `pkcs11` provider ([pkcs11-provider](https://github.com/latchset/pkcs11-provider)) with the `pkcs11` feature.
+//! The key never leave the HSM, OpenSSL delegate every private key operation to the PKCS#11 module.
+//!
+//! The `pkcs11` feature need OpenSSL 3, and the `pkcs11` provider installed in the OpenSSL modules directory.
+//! A single PKCS#11 module can be used by a process (it's given to the provider with its `pkcs11-module-path` configuration parameter).
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::config::{secret::Secret, ConfigError};
+
+/// Error define for PKCS#11 private keys
+#[derive(Debug, Error, PartialEq)]
+pub enum Pkcs11Error {
+    /// Error on the load of the PKCS#11 module (or of the OpenSSL provider)
+    #[error("The PKCS#11 module `{0}` can't be loaded `{1}`")]
+    ModuleLoad(String, String),
+    /// Error on the login to the token (wrong PIN, locked PIN, ...)
+    #[error("The login to the PKCS#11 token `{0}` failed `{1}`")]
+    Login(String, String),
+    /// Error on the lookup of the private key
+    #[error("The PKCS#11 private key `{0}` can't be found `{1}`")]
+    KeyNotFound(String, String),
+}
+
+impl Pkcs11Error {
+    /// PKCS#11 return values and functions that indicate a login failure
+    const LOGIN_REASONS: [&'static str; 5] = [
+        "C_Login",
+        "CKR_PIN_",
+        "CKR_USER_",
+        "CKR_TOKEN_NOT_PRESENT",
+        "Login failed",
+    ];
+
+    /// Method to map an OpenSSL failure of the key lookup to a login or a key lookup error
+    pub fn from_key_lookup(config: &Pkcs11Config, reason: String) -> Pkcs11Error {
+        if Self::LOGIN_REASONS
+            .iter()
+            .any(|login_reason| reason.contains(login_reason))
+        {
+            Pkcs11Error::Login(config.token_desc(), reason)
+        } else {
+            Pkcs11Error::KeyNotFound(config.to_string(), reason)
+        }
+    }
+}
+
+/// PKCS#11 configuration of a private key stored in an HSM
+///
+/// ```
+/// use prosa_utils::config::ssl::pkcs11::Pkcs11Config;
+///
+/// let pkcs11: Pkcs11Config = serde_yaml::from_str("
+/// module: /usr/lib/softhsm/libsofthsm2.so
+/// token: prosa
+/// key_label: server
+/// pin:
+///   env: PATH
+/// ").unwrap();
+///
+/// assert_eq!("pkcs11:token=prosa;object=server;type=private", pkcs11.to_string());
+/// ```
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Pkcs11Config {
+    /// Path of the PKCS#11 module (shared library of the HSM)
+    module: String,
+    /// Slot id of the token
+    slot: Option<u64>,
+    /// Label of the token
+    token: Option<String>,
+    /// Label of the private key
+    key_label: Option<String>,
+    /// Id of the private key (in hexadecimal)
+    key_id: Option<String>,
+    /// PIN of the token user
+    pin: Option<Secret>,
+}
+
+impl Pkcs11Config {
+    /// Method to create a PKCS#11 configuration of a private key with its label
+    /// Should be use with config instead of building it manually
+    pub fn new(module: String, key_label: String) -> Pkcs11Config {
+        Pkcs11Config {
+            module,
+            slot: None,
+            token: None,
+            key_label: Some(key_label),
+            key_id: None,
+            pin: None,
+        }
+    }
+
+    /// Getter of the PKCS#11 module path
+    pub fn get_module(&self) -> &str {
+        &self.module
+    }
+
+    /// Setter of the slot id of the token
+    pub fn set_slot(&mut self, slot: u64) {
+        self.slot = Some(slot);
+    }
+
+    /// Setter of the token label
+    pub fn set_token(&mut self, token: String) {
+        self.token = Some(token);
+    }
+
+    /// Setter of the private key id (in hexadecimal)
+    pub fn set_key_id(&mut self, key_id: String) {
+        self.key_id = Some(key_id);
+    }
+
+    /// Setter of the PIN of the token user
+    pub fn set_pin(&mut self, pin: Secret) {
+        self.pin = Some(pin);
+    }
+
+    /// Method to percent-encode a PKCS#11 URI attribute value (RFC 7512)
+    fn encode_attribute(value: &[u8]) -> String {
+        let mut encoded = String::with_capacity(value.len());
+        for byte in value {
+            if byte.is_ascii_alphanumeric() || b"-._~".contains(byte) {
+                encoded.push(*byte as char);
+            } else {
+                encoded.push_str(&format!("%{:02X}", byte));
+            }
+        }
+
+        encoded
+    }
+
+    /// Description of the token for the error messages
+    fn token_desc(&self) -> String {
+        match (&self.token, self.slot) {
+            (Some(token), _) => token.clone(),
+            (None, Some(slot)) => format!("slot {}", slot),
+            (None, None) => self.module.clone(),
+        }
+    }
+
+    /// Method to get the PKCS#11 URI of the private key, without the PIN
+    pub fn key_uri(&self) -> Result<String, ConfigError> {
+        if self.key_label.is_none() && self.key_id.is_none() {
+            return Err(ConfigError::WrongValue(
+                "pkcs11".into(),
+                "a `key_label` or a `key_id` is needed to find the private key".into(),
+            ));
+        }
+
+        let mut attributes = Vec::with_capacity(4);
+        if let Some(token) = &self.token {
+            attributes.push(format!(
+                "token={}",
+                Self::encode_attribute(token.as_bytes())
+            ));
+        }
+        if let Some(slot) = self.slot {
+            attributes.push(format!("slot-id={}", slot));
+        }
+        if let Some(key_label) = &self.key_label {
+            attributes.push(format!(
+                "object={}",
+                Self::encode_attribute(key_label.as_bytes())
+            ));
+        }
+        if let Some(key_id) = &self.key_id {
+            let id = hex::decode(key_id.trim_start_matches("0x"))
+                .map_err(|e| ConfigError::WrongValue("pkcs11.key_id".into(), e.to_string()))?;
+            attributes.push(format!("id={}", Self::encode_attribute(&id)));
+        }
+        attributes.push(String::from("type=private"));
+
+        Ok(format!("pkcs11:{}", attributes.join(";")))
+    }
+
+    /// Method to load the private key from the HSM through the OpenSSL `pkcs11` provider
+    #[cfg(feature = "pkcs11")]
+    pub fn load_private_key(
+        &self,
+    ) -> Result<openssl::pkey::PKey<openssl::pkey::Private>, ConfigError> {
+        let mut uri = self.key_uri()?;
+        if let Some(pin) = &self.pin {
+            uri.push_str("?pin-value=");
+            uri.push_str(&Self::encode_attribute(pin.expose().as_bytes()));
+        }
+
+        provider::load_module(&self.module)?;
+        provider::load_private_key(&uri)
+            .map_err(|reason| Pkcs11Error::from_key_lookup(self, reason).into())
+    }
+
+    /// Method to load the private key from the HSM, which is not possible without the `pkcs11` feature
+    #[cfg(not(feature = "pkcs11"))]
+    pub fn load_private_key(
+        &self,
+    ) -> Result<openssl::pkey::PKey<openssl::pkey::Private>, ConfigError> {
+        Err(Pkcs11Error::ModuleLoad(
+            self.module.clone(),
+            "ProSA is built without the `pkcs11` feature".into(),
+        )
+        .into())
+    }
+}
+
+impl fmt::Display for Pkcs11Config {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.key_uri() {
+            Ok(uri) => write!(f, "{}", uri),
+            Err(_) => write!(f, "pkcs11:{}", self.module),
+        }
+    }
+}
+
+/// Binding of the OpenSSL provider and store API that are not exposed by the `openssl` crate
+#[cfg(feature = "pkcs11")]
+mod provider {
+    use std::{
+        ffi::{c_char, c_int, c_long, c_uint, c_void, CString},
+        ptr,
+        sync::Mutex,
+    };
+
+    use foreign_types::ForeignType as _;
+    use openssl::{
+        error::ErrorStack,
+        pkey::{PKey, Private},
+    };
+    use openssl_sys as ffi;
+
+    use super::Pkcs11Error;
+
+    /// Name of the OpenSSL provider for PKCS#11
+    const PROVIDER_NAME: &str = "pkcs11";
+    /// Configuration parameter of the provider that contain the PKCS#11 module path
+    const MODULE_PARAM: &str = "pkcs11-module-path";
+    /// Type of a store object that contain a private key
+    const OSSL_STORE_INFO_PKEY: c_int = 4;
+
+    #[allow(non_camel_case_types)]
+    enum OSSL_STORE_CTX {}
+    #[allow(non_camel_case_types)]
+    enum OSSL_STORE_INFO {}
+
+    extern "C" {
+        fn NCONF_load_bio(conf: *mut ffi::CONF, bp: *mut ffi::BIO, eline: *mut c_long) -> c_int;
+        fn OSSL_PROVIDER_available(libctx: *mut ffi::OSSL_LIB_CTX, name: *const c_char) -> c_int;
+        fn CONF_modules_load(cnf: *const ffi::CONF, appname: *const c_char, flags: c_uint)
+            -> c_int;
+        fn OSSL_STORE_open(
+            uri: *const c_char,
+            ui_method: *const c_void,
+            ui_data: *mut c_void,
+            post_process: *const c_void,
+            post_process_data: *mut c_void,
+        ) -> *mut OSSL_STORE_CTX;
+        fn OSSL_STORE_load(ctx: *mut OSSL_STORE_CTX) -> *mut OSSL_STORE_INFO;
+        fn OSSL_STORE_eof(ctx: *mut OSSL_STORE_CTX) -> c_int;
+        fn OSSL_STORE_error(ctx: *mut OSSL_STORE_CTX) -> c_int;
+        fn OSSL_STORE_close(ctx: *mut OSSL_STORE_CTX) -> c_int;
+        fn OSSL_STORE_INFO_get_type(info: *const OSSL_STORE_INFO) -> c_int;
+        fn OSSL_STORE_INFO_get1_PKEY(info: *const OSSL_STORE_INFO) -> *mut ffi::EVP_PKEY;
+        fn OSSL_STORE_INFO_free(info: *mut OSSL_STORE_INFO);
+    }
+
+    /// Path of the PKCS#11 module of the loaded provider
+    static PROVIDER_MODULE: Mutex<Option<String>> = Mutex::new(None);
+
+    /// Method to quote a value of an OpenSSL configuration
+    fn quote_conf_value(value: &str) -> String {
+        let mut quoted = String::with_capacity(value.len() + 2);
+        quoted.push('"');
+        for c in value.chars() {
+            if matches!(c, '"' | '\\' | '$') {
+                quoted.push('\\');
+            }
+            quoted.push(c);
+        }
+        quoted.push('"');
+        quoted
+    }
+
+    /// Method to build the OpenSSL configuration that activate the `pkcs11` provider with its PKCS#11 module (and keep the default provider)
+    fn provider_conf(module: &str) -> String {
+        format!(
+            "openssl_conf = prosa_pkcs11_init\n\
+             [prosa_pkcs11_init]\n\
+             providers = prosa_pkcs11_providers\n\
+             [prosa_pkcs11_providers]\n\
+             default = prosa_default_provider\n\
+             {PROVIDER_NAME} = prosa_pkcs11_provider\n\
+             [prosa_default_provider]\n\
+             activate = 1\n\
+             [prosa_pkcs11_provider]\n\
+             {MODULE_PARAM} = {}\n\
+             activate = 1\n",
+            quote_conf_value(module)
+        )
+    }
+
+    /// Method to load the `pkcs11` provider with its PKCS#11 module, once for the process
+    ///
+    /// The module path is given to the provider through its configuration parameters, so the environment of the process is never modified.
+    pub(super) fn load_module(module: &str) -> Result<(), Pkcs11Error> {
+        let mut provider_module = PROVIDER_MODULE.lock().unwrap();
+        match provider_module.as_ref() {
+            Some(loaded_module) if loaded_module == module => Ok(()),
+            Some(loaded_module) => Err(Pkcs11Error::ModuleLoad(
+                module.to_string(),
+                format!("the PKCS#11 module `{}` is already loaded", loaded_module),
+            )),
+            None => {
+                // The OpenSSL initialization load the configuration modules, including the providers one
+                ffi::init();
+                let conf_str = provider_conf(module);
+                let load_error =
+                    || Pkcs11Error::ModuleLoad(module.to_string(), ErrorStack::get().to_string());
+                unsafe {
+                    let conf = ffi::NCONF_new(ptr::null_mut());
+                    if conf.is_null() {
+                        return Err(load_error());
+                    }
+
+                    let bio = ffi::BIO_new_mem_buf(
+                        conf_str.as_ptr() as *const c_void,
+                        conf_str.len() as c_int,
+                    );
+                    let mut eline: c_long = 0;
+                    let loaded = !bio.is_null()
+                        && NCONF_load_bio(conf, bio, &mut eline) > 0
+                        && CONF_modules_load(conf, ptr::null(), 0) > 0;
+                    if !bio.is_null() {
+                        ffi::BIO_free_all(bio);
+                    }
+                    ffi::NCONF_free(conf);
+
+                    // The configuration may be loaded without activating the provider
+                    let provider_name = CString::new(PROVIDER_NAME).unwrap();
+                    if !loaded
+                        || OSSL_PROVIDER_available(ptr::null_mut(), provider_name.as_ptr()) != 1
+                    {
+                        return Err(load_error());
+                    }
+                }
+
+                *provider_module = Some(module.to_string());
+                Ok(())
+            }
+        }
+    }
+
+    /// Method to load the first private key of a PKCS#11 URI. Return the OpenSSL errors if the key can't be found
+    pub(super) fn load_private_key(uri: &str) -> Result<PKey<Private>, String> {
+        let uri = CString::new(uri).map_err(|e| e.to_string())?;
+        unsafe {
+            let store = OSSL_STORE_open(
+                uri.as_ptr(),
+                ptr::null(),
+                ptr::null_mut(),
+                ptr::null(),
+                ptr::null_mut(),
+            );
+            if store.is_null() {
+                return Err(ErrorStack::get().to_string());
+            }
+
+            let mut pkey = None;
+            let mut store_error = false;
+            while pkey.is_none() && OSSL_STORE_eof(store) == 0 {
+                let info = OSSL_STORE_load(store);
+                if info.is_null() {
+                    // Without an object, the store reached its end or failed
+                    if OSSL_STORE_error(store) != 0 {
+                        store_error = true;
+                        break;
+                    }
+                    continue;
+                }
+
+                if OSSL_STORE_INFO_get_type(info) == OSSL_STORE_INFO_PKEY {
+                    let key = OSSL_STORE_INFO_get1_PKEY(info);
+                    if !key.is_null() {
+                        pkey = Some(PKey::from_ptr(key));
+                    }
+                }
+                OSSL_STORE_INFO_free(info);
+            }
+            OSSL_STORE_close(store);
+
+            let errors = ErrorStack::get();
+            pkey.ok_or_else(|| {
+                if !errors.errors().is_empty() {
+                    errors.to_string()
+                } else if store_error {
+                    String::from("the PKCS#11 store failed to load the objects of the URI")
+                } else {
+                    String::from("no private key match the URI")
+                }
+            })
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn provider_conf_quoting() {
+            assert_eq!(
+                r#""/usr/lib/libhsm.so""#,
+                quote_conf_value("/usr/lib/libhsm.so")
+            );
+            assert_eq!(
+                r#""C:\\hsm\"\$HOME\".dll""#,
+                quote_conf_value(r#"C:\hsm"$HOME".dll"#)
+            );
+            assert!(provider_conf("/usr/lib/libhsm.so")
+                .contains("pkcs11-module-path = \"/usr/lib/libhsm.so\"\n"));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "pkcs11")]
+    fn softhsm_config() -> Option<Pkcs11Config> {
+        let module = std::env::var("PROSA_TEST_PKCS11_MODULE").ok().or_else(|| {
+            [
+                "/usr/lib/softhsm/libsofthsm2.so",
+                "/usr/lib64/softhsm/libsofthsm2.so",
+                "/usr/lib/x86_64-linux-gnu/softhsm/libsofthsm2.so",
+            ]
+            .into_iter()
+            .find(|path| std::path::Path::new(path).exists())
+            .map(String::from)
+        })?;
+
+        let mut pkcs11 = Pkcs11Config::new(
+            module,
+            std::env::var("PROSA_TEST_PKCS11_KEY").unwrap_or("prosa".into()),
+        );
+        pkcs11.set_token(std::env::var("PROSA_TEST_PKCS11_TOKEN").unwrap_or("prosa".into()));
+        pkcs11.set_pin(Secret::new(
+            std::env::var("PROSA_TEST_PKCS11_PIN").unwrap_or("1234".into()),
+        ));
+        Some(pkcs11)
+    }
+
+    #[test]
+    fn pkcs11_config() {
+        let pkcs11: Pkcs11Config = serde_yaml::from_str(
+            "
+module: /usr/lib/softhsm/libsofthsm2.so
+slot: 3
+key_id: 0a1B
+pin: '1234'
+",
+        )
+        .unwrap();
+        assert_eq!("/usr/lib/softhsm/libsofthsm2.so", pkcs11.get_module());
+        assert_eq!(
+            "pkcs11:slot-id=3;id=%0A%1B;type=private",
+            pkcs11.key_uri().unwrap()
+        );
+
+        let mut pkcs11 = Pkcs11Config::new("libhsm.so".into(), "my key/1".into());
+        pkcs11.set_token("ProSA token".into());
+        assert_eq!(
+            "pkcs11:token=ProSA%20token;object=my%20key%2F1;type=private",
+            pkcs11.key_uri().unwrap()
+        );
+
+        // The key must be identified
+        let pkcs11: Pkcs11Config = serde_yaml::from_str("module: libhsm.so").unwrap();
+        assert!(matches!(
+            pkcs11.key_uri(),
+            Err(ConfigError::WrongValue(param, _)) if param == "pkcs11"
+        ));
+        let mut pkcs11 = pkcs11;
+        pkcs11.set_key_id("xyz".into());
+        assert!(matches!(
+            pkcs11.key_uri(),
+            Err(ConfigError::WrongValue(param, _)) if param == "pkcs11.key_id"
+        ));
+    }
+
+    #[test]
+    fn pkcs11_error_mapping() {
+        let mut pkcs11 = Pkcs11Config::new("libhsm.so".into(), "server".into());
+        pkcs11.set_slot(1);
+
+        assert_eq!(
+            Pkcs11Error::Login(
+                "slot 1".into(),
+                "error:40000101:pkcs11:C_Login:CKR_PIN_INCORRECT".into()
+            ),
+            Pkcs11Error::from_key_lookup(
+                &pkcs11,
+                "error:40000101:pkcs11:C_Login:CKR_PIN_INCORRECT".into()
+            )
+        );
+        assert_eq!(
+            Pkcs11Error::KeyNotFound(
+                "pkcs11:slot-id=1;object=server;type=private".into(),
+                "no private key match the URI".into()
+            ),
+            Pkcs11Error::from_key_lookup(&pkcs11, "no private key match the URI".into())
+        );
+
+        let err = ConfigError::from(Pkcs11Error::ModuleLoad(
+            "libhsm.so".into(),
+            "not found".into(),
+        ));
+        assert_eq!(
+            "PKCS#11 error `The PKCS#11 module `libhsm.so` can't be loaded `not found``",
+            err.to_string()
+        );
+    }
+
+    #[cfg(not(feature = "pkcs11"))]
+    #[test]
+    fn pkcs11_without_feature() {
+        let pkcs11 = Pkcs11Config::new("libhsm.so".into(), "server".into());
+        assert!(pkcs11.load_private_key().is_err());
+    }
+
+    /// Load a key from SoftHSM2 (skipped if SoftHSM2 is not installed).
+    /// The token and the key can be created with:
+    /// `softhsm2-util --init-token --free --label prosa --pin 1234 --so-pin 1234`
+    /// `pkcs11-tool --module <module> --token-label prosa --login --pin 1234 --keypairgen --key-type EC:prime256v1 --label prosa`
+    #[cfg(feature = "pkcs11")]
+    #[test]
+    fn pkcs11_softhsm() {
+        let Some(pkcs11) = softhsm_config() else {
+            eprintln!("SoftHSM2 is not installed, skip the PKCS#11 test");
+            return;
+        };
+
+        match pkcs11.load_private_key() {
+            Ok(pkey) => assert!(pkey.bits() > 0),
+            Err(e) => eprintln!("SoftHSM2 token is not ready, skip the PKCS#11 test: {}", e),
+        }
+
+        // An other module can't be loaded
+        let other = Pkcs11Config::new("/other/libhsm.so".into(), "server".into());
+        assert!(other.load_private_key().is_err());
+    }
+}
@@ -0,0 +1,358 @@
+//! Certificate authority generated for test environments
+//!
+//! The certificate authority key and certificate are generated (or loaded if present) in a directory.
+//! Every listener get a certificate issued by this authority for its host, so the clients only need to trust the authority (see [`test_ca_store`]).
+//!
+//! Keys are written with owner only permissions, and certificates are regenerated when they're close to their expiry.
+//! It must only be used for test environments.
+
+use std::{
+    fs,
+    io::Write as _,
+    net::IpAddr,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time,
+};
+
+use openssl::{
+    asn1::{Asn1Integer, Asn1Time},
+    bn::{BigNum, MsbOption},
+    ec::{Asn1Flag, EcGroup, EcKey},
+    hash::MessageDigest,
+    nid::Nid,
+    pkey::{PKey, Private},
+    x509::{
+        extension::{
+            AuthorityKeyIdentifier, BasicConstraints, ExtendedKeyUsage, KeyUsage,
+            SubjectAlternativeName, SubjectKeyIdentifier,
+        },
+        X509Builder, X509NameBuilder, X509,
+    },
+};
+
+use super::Store;
+use crate::config::{os_country, ConfigError};
+
+/// File name of the certificate authority certificate
+pub const CA_CERT_FILE: &str = "ca.pem";
+/// File name of the certificate authority private key
+pub const CA_KEY_FILE: &str = "ca.key";
+
+/// Validity of the certificate authority (4 years)
+const CA_VALIDITY_DAYS: u32 = 1461;
+/// Validity of the issued certificates
+const CERT_VALIDITY_DAYS: u32 = 397;
+/// Certificates are regenerated when they expire in less than this number of days
+const RENEWAL_DAYS: u32 = 30;
+
+/// Lock to avoid concurrent generations of the certificate authority by listeners of the same process
+static TEST_CA_LOCK: Mutex<()> = Mutex::new(());
+
+/// Certificate authority of a test environment, that issue the listeners certificates
+///
+/// ```
+/// use prosa_utils::config::ssl::test_ca::TestCa;
+///
+/// let dir = std::env::temp_dir().join(format!("prosa_test_ca_doc_{}", std::process::id()));
+/// let ca = TestCa::load_or_generate(&dir).unwrap();
+/// let (cert, _key) = ca.issue("localhost").unwrap();
+/// assert!(cert.verify(&ca.get_cert().public_key().unwrap()).unwrap());
+///
+/// // The certificate authority is reused
+/// let same_ca = TestCa::load_or_generate(&dir).unwrap();
+/// assert_eq!(ca.get_cert().to_der().unwrap(), same_ca.get_cert().to_der().unwrap());
+/// # std::fs::remove_dir_all(dir).unwrap();
+/// ```
+#[derive(Debug)]
+pub struct TestCa {
+    dir: PathBuf,
+    cert: X509,
+    key: PKey<Private>,
+}
+
+impl TestCa {
+    /// Method to load the certificate authority from its directory, or to generate it if it's missing or close to its expiry
+    pub fn load_or_generate<P>(dir: P) -> Result<TestCa, ConfigError>
+    where
+        P: AsRef<Path>,
+    {
+        let dir = dir.as_ref().to_path_buf();
+        let _lock = TEST_CA_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        create_private_dir(&dir)?;
+
+        let cert_path = dir.join(CA_CERT_FILE);
+        let key_path = dir.join(CA_KEY_FILE);
+        if let Some((cert, key)) = load_cert_key(&cert_path, &key_path)? {
+            if !is_expiring(&cert)? {
+                return Ok(TestCa { dir, cert, key });
+            }
+        }
+
+        let (cert, key) = Self::generate(CA_VALIDITY_DAYS)?;
+        write_private_file(&key_path, &key.private_key_to_pem_pkcs8()?)?;
+        write_private_file(&cert_path, &cert.to_pem()?)?;
+        Ok(TestCa { dir, cert, key })
+    }
+
+    /// Method to generate a self-signed certificate authority
+    fn generate(validity_days: u32) -> Result<(X509, PKey<Private>), ConfigError> {
+        let key = generate_key()?;
+        let mut x509_name = X509NameBuilder::new()?;
+        if let Some(cn) = os_country() {
+            x509_name.append_entry_by_text("C", cn.as_str())?;
+        }
+        x509_name.append_entry_by_text("CN", "ProSA test CA")?;
+        let x509_name = x509_name.build();
+
+        let mut cert = cert_builder(&key, validity_days)?;
+        cert.set_subject_name(&x509_name)?;
+        cert.set_issuer_name(&x509_name)?;
+        cert.append_extension(BasicConstraints::new().critical().ca().pathlen(0).build()?)?;
+        cert.append_extension(
+            KeyUsage::new()
+                .critical()
+                .key_cert_sign()
+                .crl_sign()
+                .build()?,
+        )?;
+        let skid = SubjectKeyIdentifier::new().build(&cert.x509v3_context(None, None))?;
+        cert.append_extension(skid)?;
+        cert.sign(&key, MessageDigest::sha256())?;
+
+        Ok((cert.build(), key))
+    }
+
+    /// Getter of the certificate authority certificate
+    pub fn get_cert(&self) -> &X509 {
+        &self.cert
+    }
+
+    /// Getter of a store that contain only the certificate authority, for the clients
+    pub fn get_store(&self) -> Store {
+        Store::new(self.dir.join(CA_CERT_FILE).to_string_lossy().into_owned())
+    }
+
+    /// Method to get a certificate for a host (DNS name or IP address), issued by the certificate authority.
+    /// The certificate is persisted in the directory, and reissued if it's close to its expiry or if the certificate authority changed
+    pub fn issue(&self, host: &str) -> Result<(X509, PKey<Private>), ConfigError> {
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        let file_name: String = host
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '.' || c == '-' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        let cert_path = self.dir.join(format!("server_{}.pem", file_name));
+        let key_path = self.dir.join(format!("server_{}.key", file_name));
+
+        let ca_pubkey = self.cert.public_key()?;
+        let _lock = TEST_CA_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some((cert, key)) = load_cert_key(&cert_path, &key_path)? {
+            if !is_expiring(&cert)? && cert.verify(&ca_pubkey)? {
+                return Ok((cert, key));
+            }
+        }
+
+        let (cert, key) = self.generate_cert(host)?;
+        write_private_file(&key_path, &key.private_key_to_pem_pkcs8()?)?;
+        write_private_file(&cert_path, &cert.to_pem()?)?;
+        Ok((cert, key))
+    }
+
+    /// Method to generate a certificate for a host, signed by the certificate authority
+    fn generate_cert(&self, host: &str) -> Result<(X509, PKey<Private>), ConfigError> {
+        let key = generate_key()?;
+        let mut x509_name = X509NameBuilder::new()?;
+        x509_name.append_entry_by_text("CN", host)?;
+        let x509_name = x509_name.build();
+
+        let mut cert = cert_builder(&key, CERT_VALIDITY_DAYS)?;
+        cert.set_subject_name(&x509_name)?;
+        cert.set_issuer_name(self.cert.subject_name())?;
+        cert.append_extension(BasicConstraints::new().critical().build()?)?;
+        cert.append_extension(
+            KeyUsage::new()
+                .critical()
+                .digital_signature()
+                .key_encipherment()
+                .build()?,
+        )?;
+        cert.append_extension(
+            ExtendedKeyUsage::new()
+                .server_auth()
+                .client_auth()
+                .build()?,
+        )?;
+        let skid =
+            SubjectKeyIdentifier::new().build(&cert.x509v3_context(Some(&self.cert), None))?;
+        cert.append_extension(skid)?;
+        let akid = AuthorityKeyIdentifier::new()
+            .keyid(true)
+            .build(&cert.x509v3_context(Some(&self.cert), None))?;
+        cert.append_extension(akid)?;
+
+        let mut subject_alternative_name = SubjectAlternativeName::new();
+        if host.parse::<IpAddr>().is_ok() {
+            subject_alternative_name.ip(host);
+        } else {
+            subject_alternative_name.dns(host);
+        }
+        let san = subject_alternative_name.build(&cert.x509v3_context(Some(&self.cert), None))?;
+        cert.append_extension(san)?;
+
+        cert.sign(&self.key, MessageDigest::sha256())?;
+        Ok((cert.build(), key))
+    }
+}
+
+/// Method to get a store that trust the certificate authority of a test environment (generated if needed), for the client configurations
+///
+/// ```
+/// use prosa_utils::config::ssl::{test_ca::test_ca_store, SslConfig};
+///
+/// let dir = std::env::temp_dir().join(format!("prosa_test_ca_store_doc_{}", std::process::id()));
+/// let mut client_config = SslConfig::default();
+/// client_config.set_store(test_ca_store(&dir).unwrap());
+/// assert_eq!(1, test_ca_store(&dir).unwrap().get_certs().unwrap().len());
+/// # std::fs::remove_dir_all(dir).unwrap();
+/// ```
+pub fn test_ca_store<P>(dir: P) -> Result<Store, ConfigError>
+where
+    P: AsRef<Path>,
+{
+    Ok(TestCa::load_or_generate(dir)?.get_store())
+}
+
+/// Method to generate an EC P-256 private key
+fn generate_key() -> Result<PKey<Private>, ConfigError> {
+    let mut group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1)?;
+    group.set_asn1_flag(Asn1Flag::NAMED_CURVE);
+    Ok(PKey::from_ec_key(EcKey::generate(&group)?)?)
+}
+
+/// Method to prepare a certificate builder with a random serial and a validity from now
+fn cert_builder(key: &PKey<Private>, validity_days: u32) -> Result<X509Builder, ConfigError> {
+    let mut cert = X509::builder()?;
+    cert.set_version(2)?;
+    cert.set_pubkey(key)?;
+
+    let mut serial_bn = BigNum::new()?;
+    serial_bn.pseudo_rand(64, MsbOption::MAYBE_ZERO, true)?;
+    let serial_number = Asn1Integer::from_bn(&serial_bn)?;
+    cert.set_serial_number(&serial_number)?;
+
+    let begin_valid_time =
+        Asn1Time::from_unix(time::UNIX_EPOCH.elapsed().unwrap().as_secs() as i64 - 360)?;
+    cert.set_not_before(&begin_valid_time)?;
+    let end_valid_time = Asn1Time::days_from_now(validity_days)?;
+    cert.set_not_after(&end_valid_time)?;
+    Ok(cert)
+}
+
+/// Method to know if a certificate expire in less than [`RENEWAL_DAYS`]
+fn is_expiring(cert: &X509) -> Result<bool, ConfigError> {
+    Ok(cert.not_after() < Asn1Time::days_from_now(RENEWAL_DAYS)?)
+}
+
+/// Method to load a PEM certificate and its PEM private key, if both files exist
+fn load_cert_key(
+    cert_path: &Path,
+    key_path: &Path,
+) -> Result<Option<(X509, PKey<Private>)>, ConfigError> {
+    if !cert_path.is_file() || !key_path.is_file() {
+        return Ok(None);
+    }
+
+    let cert = fs::read(cert_path)
+        .map_err(|io| ConfigError::IoFile(cert_path.to_string_lossy().into_owned(), io))?;
+    let key = fs::read(key_path)
+        .map_err(|io| ConfigError::IoFile(key_path.to_string_lossy().into_owned(), io))?;
+    Ok(Some((
+        X509::from_pem(&cert)?,
+        PKey::private_key_from_pem(&key)?,
+    )))
+}
+
+/// Method to create a directory only accessible by its owner
+fn create_private_dir(dir: &Path) -> Result<(), ConfigError> {
+    let mut builder = fs::DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(target_family = "unix")]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+    builder
+        .create(dir)
+        .map_err(|io| ConfigError::IoFile(dir.to_string_lossy().into_owned(), io))
+}
+
+/// Method to write a file only readable by its owner
+fn write_private_file(path: &Path, data: &[u8]) -> Result<(), ConfigError> {
+    let io_error = |io| ConfigError::IoFile(path.to_string_lossy().into_owned(), io);
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(target_family = "unix")]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options.open(path).map_err(io_error)?;
+
+    // The mode is only applied on creation
+    #[cfg(target_family = "unix")]
+    file.set_permissions(std::os::unix::fs::PermissionsExt::from_mode(0o600))
+        .map_err(io_error)?;
+    file.write_all(data).map_err(io_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ca_renewal() {
+        let dir =
+            std::env::temp_dir().join(format!("prosa_test_ca_renewal_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+
+        // A certificate authority close to its expiry is regenerated, with its issued certificates
+        create_private_dir(&dir).unwrap();
+        let (expiring_cert, expiring_key) = TestCa::generate(1).unwrap();
+        write_private_file(
+            &dir.join(CA_KEY_FILE),
+            &expiring_key.private_key_to_pem_pkcs8().unwrap(),
+        )
+        .unwrap();
+        write_private_file(&dir.join(CA_CERT_FILE), &expiring_cert.to_pem().unwrap()).unwrap();
+        let expiring_ca = TestCa {
+            dir: dir.clone(),
+            cert: expiring_cert.clone(),
+            key: expiring_key,
+        };
+        let (expiring_server_cert, _) = expiring_ca.issue("127.0.0.1").unwrap();
+
+        let ca = TestCa::load_or_generate(&dir).unwrap();
+        assert_ne!(
+            expiring_cert.to_der().unwrap(),
+            ca.get_cert().to_der().unwrap()
+        );
+        let (server_cert, _) = ca.issue("127.0.0.1").unwrap();
+        assert_ne!(
+            expiring_server_cert.to_der().unwrap(),
+            server_cert.to_der().unwrap()
+        );
+        assert!(server_cert
+            .verify(&ca.get_cert().public_key().unwrap())
+            .unwrap());
+
+        // Private keys are only readable by their owner
+        #[cfg(target_family = "unix")]
+        for file in [CA_KEY_FILE, "server_127.0.0.1.key"] {
+            use std::os::unix::fs::PermissionsExt as _;
+            let mode = fs::metadata(dir.join(file)).unwrap().permissions().mode();
+            assert_eq!(0o600, mode & 0o777, "{file}");
+        }
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,516 @@
+//! Telemetry utilities, to correlate the metrics with the traces
+//!
+//! When a latency histogram spikes, an exemplar let you jump to a trace of an offending transaction.
+//! Histograms recorded with [`record_with_exemplar`] keep, for each set of attributes, the last value recorded while a sampled span was active with its trace id.
+//!
+//! Exemplars are only kept once enabled (done by the prometheus exporter when its `exemplars` option is set, see [`PrometheusExporterCfg`](crate::config::observability::PrometheusExporterCfg)).
+//! They are exposed in the OpenMetrics format (see [`encode_openmetrics`]) to the scrapers that accept it:
+//!
+//! ```text
+//! prosa_inj_request_duration_seconds_bucket{proc="INJ",service="PAY",le="0.05"} 12 # {trace_id="4bf92f3577b34da6a3ce929d0e0e4736",span_id="00f067aa0ba902b7"} 0.043 1700000000.123
+//! ```
+
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex, OnceLock,
+    },
+    time::SystemTime,
+};
+
+use opentelemetry::{
+    metrics::Histogram,
+    trace::{SpanId, TraceContextExt as _, TraceId},
+    KeyValue,
+};
+use tracing_opentelemetry::OpenTelemetrySpanExt as _;
+
+/// Attributes of a series, with their sanitized prometheus label names
+type SeriesLabels = Vec<(String, String)>;
+
+/// Enable the capture of the exemplars
+static EXEMPLARS_ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Last exemplars of every series, by metric name
+static EXEMPLARS: OnceLock<Mutex<HashMap<String, HashMap<SeriesLabels, Exemplar>>>> =
+    OnceLock::new();
+
+/// Method to enable (or disable) the capture of the exemplars by [`record_with_exemplar`]
+pub fn set_exemplars_enabled(enabled: bool) {
+    EXEMPLARS_ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Method to know if the exemplars are captured
+pub fn exemplars_enabled() -> bool {
+    EXEMPLARS_ENABLED.load(Ordering::Relaxed)
+}
+
+/// Getter of the trace and span ids of the current span, if it's sampled
+pub fn current_span_ids() -> Option<(TraceId, SpanId)> {
+    let context = tracing::Span::current().context();
+    let span = context.span();
+    let span_context = span.span_context();
+    if span_context.is_valid() && span_context.is_sampled() {
+        Some((span_context.trace_id(), span_context.span_id()))
+    } else {
+        None
+    }
+}
+
+/// Getter of the trace id of the current span, if it's sampled
+pub fn current_trace_id() -> Option<TraceId> {
+    current_span_ids().map(|(trace_id, _)| trace_id)
+}
+
+/// Sanitize a name to be a valid prometheus name
+fn sanitize(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+/// Exemplar of a histogram: a recorded value with the trace that recorded it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Exemplar {
+    /// Trace id of the span active when the value was recorded
+    pub trace_id: TraceId,
+    /// Span id of the span active when the value was recorded
+    pub span_id: SpanId,
+    /// Recorded value
+    pub value: f64,
+    /// Time of the record
+    pub timestamp: SystemTime,
+}
+
+impl Exemplar {
+    /// Method to get the last exemplar of a metric for a set of attributes
+    pub fn get(name: &str, attributes: &[KeyValue]) -> Option<Exemplar> {
+        EXEMPLARS
+            .get()?
+            .lock()
+            .unwrap()
+            .get(name)?
+            .get(&Self::series_labels(attributes))
+            .copied()
+    }
+
+    /// Labels of a series, sorted by label names
+    fn series_labels(attributes: &[KeyValue]) -> SeriesLabels {
+        let mut labels: SeriesLabels = attributes
+            .iter()
+            .map(|kv| (sanitize(kv.key.as_str()), kv.value.to_string()))
+            .collect();
+        labels.sort();
+        labels
+    }
+
+    /// Method to keep the exemplar of a series
+    fn store(name: &str, attributes: &[KeyValue], exemplar: Exemplar) {
+        EXEMPLARS
+            .get_or_init(Default::default)
+            .lock()
+            .unwrap()
+            .entry(name.to_string())
+            .or_default()
+            .insert(Self::series_labels(attributes), exemplar);
+    }
+
+    /// Method to find the exemplar of a histogram series exported with `family` name and `labels`
+    #[cfg(feature = "config-observability-prometheus")]
+    fn find(family: &str, labels: &[(&str, &str)]) -> Option<Exemplar> {
+        let exemplars = EXEMPLARS.get()?.lock().unwrap();
+        exemplars
+            .iter()
+            // The exporter can add a unit suffix to the metric name
+            .filter(|(name, _)| {
+                family
+                    .strip_prefix(sanitize(name).as_str())
+                    .is_some_and(|suffix| suffix.is_empty() || suffix.starts_with('_'))
+            })
+            .flat_map(|(_, series)| series.iter())
+            .find(|(series_labels, _)| {
+                series_labels
+                    .iter()
+                    .all(|(key, value)| labels.iter().any(|(k, v)| k == key && v == value))
+            })
+            .map(|(_, exemplar)| *exemplar)
+    }
+}
+
+/// Value of a histogram that can be kept in an exemplar
+pub trait ExemplarValue: Copy {
+    /// Value of the exemplar
+    fn exemplar_value(self) -> f64;
+}
+
+impl ExemplarValue for f64 {
+    fn exemplar_value(self) -> f64 {
+        self
+    }
+}
+
+impl ExemplarValue for u64 {
+    fn exemplar_value(self) -> f64 {
+        self as f64
+    }
+}
+
+/// Histogram that keeps exemplars of its records, with the name it's exported with
+///
+/// ```
+/// use opentelemetry::{global, KeyValue};
+/// use prosa_utils::config::telemetry::{record_with_exemplar, ExemplarHistogram};
+///
+/// let name = "prosa_example_duration";
+/// let histogram = ExemplarHistogram::new(
+///     name,
+///     global::meter("example").f64_histogram(name).with_unit("s").init(),
+/// );
+/// record_with_exemplar(&histogram, 0.042, &[KeyValue::new("service", "PAY")]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct ExemplarHistogram<T> {
+    name: Cow<'static, str>,
+    histogram: Histogram<T>,
+}
+
+impl<T> ExemplarHistogram<T>
+where
+    T: ExemplarValue,
+{
+    /// Method to create an histogram that keeps exemplars, from an histogram and its name
+    pub fn new<N>(name: N, histogram: Histogram<T>) -> ExemplarHistogram<T>
+    where
+        N: Into<Cow<'static, str>>,
+    {
+        ExemplarHistogram {
+            name: name.into(),
+            histogram,
+        }
+    }
+
+    /// Getter of the name of the histogram
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    /// Method to record a value, with an exemplar if a span is active (see [`record_with_exemplar`])
+    pub fn record(&self, value: T, attributes: &[KeyValue]) {
+        record_with_exemplar(self, value, attributes)
+    }
+}
+
+/// Method to record a value in an histogram, and keep it as exemplar of its series with the trace id of the current span (if exemplars are enabled and a sampled span is active)
+pub fn record_with_exemplar<T>(histogram: &ExemplarHistogram<T>, value: T, attributes: &[KeyValue])
+where
+    T: ExemplarValue,
+{
+    histogram.histogram.record(value, attributes);
+    if exemplars_enabled() {
+        if let Some((trace_id, span_id)) = current_span_ids() {
+            Exemplar::store(
+                &histogram.name,
+                attributes,
+                Exemplar {
+                    trace_id,
+                    span_id,
+                    value: value.exemplar_value(),
+                    timestamp: SystemTime::now(),
+                },
+            );
+        }
+    }
+}
+
+/// Content type of the OpenMetrics text format
+#[cfg(feature = "config-observability-prometheus")]
+pub const OPENMETRICS_CONTENT_TYPE: &str =
+    "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+/// Format a value as an OpenMetrics number
+#[cfg(feature = "config-observability-prometheus")]
+fn openmetrics_number(value: f64) -> String {
+    if value.is_nan() {
+        String::from("NaN")
+    } else if value == f64::INFINITY {
+        String::from("+Inf")
+    } else if value == f64::NEG_INFINITY {
+        String::from("-Inf")
+    } else {
+        value.to_string()
+    }
+}
+
+/// Escape a label value or a help text
+#[cfg(feature = "config-observability-prometheus")]
+fn openmetrics_escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Write a sample line in the OpenMetrics format
+#[cfg(feature = "config-observability-prometheus")]
+fn write_sample(
+    out: &mut String,
+    name: &str,
+    labels: &[(&str, &str)],
+    extra_label: Option<(&str, String)>,
+    value: String,
+) {
+    out.push_str(name);
+    if !labels.is_empty() || extra_label.is_some() {
+        let labels: Vec<String> = labels
+            .iter()
+            .map(|(k, v)| format!("{}=\"{}\"", k, openmetrics_escape(v)))
+            .chain(extra_label.map(|(k, v)| format!("{}=\"{}\"", k, v)))
+            .collect();
+        out.push('{');
+        out.push_str(&labels.join(","));
+        out.push('}');
+    }
+    out.push(' ');
+    out.push_str(&value);
+}
+
+/// Method to encode metric families in the OpenMetrics text format, with the exemplars of their histograms
+///
+/// The exemplar of a series is attached to the bucket that contains its value.
+#[cfg(feature = "config-observability-prometheus")]
+pub fn encode_openmetrics(families: &[prometheus::proto::MetricFamily]) -> String {
+    use prometheus::proto::MetricType;
+
+    let mut out = String::new();
+    for family in families {
+        let name = family.get_name();
+        let (family_name, metric_type) = match family.get_field_type() {
+            MetricType::COUNTER => (name.strip_suffix("_total").unwrap_or(name), "counter"),
+            MetricType::GAUGE => (name, "gauge"),
+            MetricType::HISTOGRAM => (name, "histogram"),
+            MetricType::SUMMARY => (name, "summary"),
+            MetricType::UNTYPED => (name, "unknown"),
+        };
+
+        if !family.get_help().is_empty() {
+            out.push_str(&format!(
+                "# HELP {} {}\n",
+                family_name,
+                openmetrics_escape(family.get_help())
+            ));
+        }
+        out.push_str(&format!("# TYPE {} {}\n", family_name, metric_type));
+
+        for metric in family.get_metric() {
+            let labels: Vec<(&str, &str)> = metric
+                .get_label()
+                .iter()
+                .map(|l| (l.get_name(), l.get_value()))
+                .collect();
+            match family.get_field_type() {
+                MetricType::COUNTER => {
+                    write_sample(
+                        &mut out,
+                        &format!("{}_total", family_name),
+                        &labels,
+                        None,
+                        openmetrics_number(metric.get_counter().get_value()),
+                    );
+                    out.push('\n');
+                }
+                MetricType::GAUGE => {
+                    write_sample(
+                        &mut out,
+                        family_name,
+                        &labels,
+                        None,
+                        openmetrics_number(metric.get_gauge().get_value()),
+                    );
+                    out.push('\n');
+                }
+                MetricType::UNTYPED => {
+                    write_sample(
+                        &mut out,
+                        family_name,
+                        &labels,
+                        None,
+                        openmetrics_number(metric.get_untyped().get_value()),
+                    );
+                    out.push('\n');
+                }
+                MetricType::SUMMARY => {
+                    let summary = metric.get_summary();
+                    for quantile in summary.get_quantile() {
+                        write_sample(
+                            &mut out,
+                            family_name,
+                            &labels,
+                            Some(("quantile", openmetrics_number(quantile.get_quantile()))),
+                            openmetrics_number(quantile.get_value()),
+                        );
+                        out.push('\n');
+                    }
+                    write_sample(
+                        &mut out,
+                        &format!("{}_sum", family_name),
+                        &labels,
+                        None,
+                        openmetrics_number(summary.get_sample_sum()),
+                    );
+                    out.push('\n');
+                    write_sample(
+                        &mut out,
+                        &format!("{}_count", family_name),
+                        &labels,
+                        None,
+                        summary.get_sample_count().to_string(),
+                    );
+                    out.push('\n');
+                }
+                MetricType::HISTOGRAM => {
+                    let histogram = metric.get_histogram();
+                    let mut exemplar = Exemplar::find(family_name, &labels);
+                    let mut buckets: Vec<(f64, u64)> = histogram
+                        .get_bucket()
+                        .iter()
+                        .map(|b| (b.get_upper_bound(), b.get_cumulative_count()))
+                        .collect();
+                    if buckets.last().is_none_or(|(b, _)| *b != f64::INFINITY) {
+                        buckets.push((f64::INFINITY, histogram.get_sample_count()));
+                    }
+
+                    let bucket_name = format!("{}_bucket", family_name);
+                    for (upper_bound, count) in buckets {
+                        write_sample(
+                            &mut out,
+                            &bucket_name,
+                            &labels,
+                            Some(("le", openmetrics_number(upper_bound))),
+                            count.to_string(),
+                        );
+                        if let Some(e) = exemplar.filter(|e| e.value <= upper_bound) {
+                            let timestamp = e
+                                .timestamp
+                                .duration_since(SystemTime::UNIX_EPOCH)
+                                .unwrap_or_default();
+                            out.push_str(&format!(
+                                " # {{trace_id=\"{}\",span_id=\"{}\"}} {} {}.{:03}",
+                                e.trace_id,
+                                e.span_id,
+                                openmetrics_number(e.value),
+                                timestamp.as_secs(),
+                                timestamp.subsec_millis()
+                            ));
+                            exemplar = None;
+                        }
+                        out.push('\n');
+                    }
+                    write_sample(
+                        &mut out,
+                        &format!("{}_sum", family_name),
+                        &labels,
+                        None,
+                        openmetrics_number(histogram.get_sample_sum()),
+                    );
+                    out.push('\n');
+                    write_sample(
+                        &mut out,
+                        &format!("{}_count", family_name),
+                        &labels,
+                        None,
+                        histogram.get_sample_count().to_string(),
+                    );
+                    out.push('\n');
+                }
+            }
+        }
+    }
+    out.push_str("# EOF\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "config-observability-prometheus")]
+    #[test]
+    fn openmetrics_exemplars() {
+        use opentelemetry::{metrics::MeterProvider as _, trace::TracerProvider as _};
+        use opentelemetry_sdk::{metrics::SdkMeterProvider, trace::TracerProvider};
+        use prometheus::Encoder as _;
+        use tracing_subscriber::layer::SubscriberExt as _;
+
+        let registry = prometheus::Registry::new();
+        let exporter = opentelemetry_prometheus::exporter()
+            .with_registry(registry.clone())
+            .without_target_info()
+            .without_scope_info()
+            .build()
+            .unwrap();
+        let meter_provider = SdkMeterProvider::builder().with_reader(exporter).build();
+        let name = "prosa_test_exemplar_duration";
+        let histogram = ExemplarHistogram::new(
+            name,
+            meter_provider
+                .meter("prosa_test")
+                .f64_histogram(name)
+                .with_unit("s")
+                .init(),
+        );
+
+        let subscriber = tracing_subscriber::registry().with(
+            tracing_opentelemetry::layer().with_tracer(TracerProvider::default().tracer("test")),
+        );
+        let traced = [KeyValue::new("service", "TRACED")];
+        let untraced = [KeyValue::new("service", "UNTRACED")];
+        let disabled = [KeyValue::new("service", "DISABLED")];
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("prosa_test_span");
+            let _enter = span.enter();
+
+            // Exemplars are not kept until enabled
+            histogram.record(0.2, &disabled);
+            assert_eq!(None, Exemplar::get(name, &disabled));
+
+            set_exemplars_enabled(true);
+            record_with_exemplar(&histogram, 0.042, &traced);
+        });
+        // Without an active span, no exemplar is kept
+        histogram.record(0.3, &untraced);
+
+        let exemplar = Exemplar::get(name, &traced).expect("The exemplar is not kept");
+        assert_eq!(0.042, exemplar.value);
+        assert_eq!(None, Exemplar::get(name, &untraced));
+
+        let openmetrics = encode_openmetrics(&registry.gather());
+        assert!(openmetrics.ends_with("# EOF\n"), "{openmetrics}");
+        let exemplar_lines: Vec<&str> = openmetrics
+            .lines()
+            .filter(|l| l.contains(" # {trace_id="))
+            .collect();
+        assert_eq!(1, exemplar_lines.len(), "{openmetrics}");
+        assert!(
+            exemplar_lines[0].starts_with(&format!("{}_seconds_bucket{{service=\"TRACED\"", name)),
+            "{openmetrics}"
+        );
+        assert!(
+            exemplar_lines[0].contains(&format!("trace_id=\"{}\"", exemplar.trace_id)),
+            "{openmetrics}"
+        );
+        assert!(exemplar_lines[0].contains("le=\"5\""), "{openmetrics}");
+
+        // The plain prometheus format doesn't have exemplars
+        let mut plain = Vec::new();
+        prometheus::TextEncoder::new()
+            .encode(&registry.gather(), &mut plain)
+            .unwrap();
+        let plain = String::from_utf8(plain).unwrap();
+        assert!(plain.contains("service=\"TRACED\""), "{plain}");
+        assert!(!plain.contains("trace_id"), "{plain}");
+        assert!(!plain.contains("# EOF"), "{plain}");
+    }
+}
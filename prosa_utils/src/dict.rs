@@ -0,0 +1,465 @@
+//! Module to define dictionaries of TVF fields
+//!
+//! A [`Dictionary`] give a label and a type to the tags of a TVF message.
+//! It's used to read messages with labeled fields (like JSON) into TVF with the [`deserialize::DictDeserializer`], and to write them back with the [`serialize::DictSerializer`].
+//!
+//! Dictionaries can be built in code, or loaded from CSV or XML files.
+//! Nested dictionaries (used by [`EntryType::Node`] fields) are declared in the same file and referenced by their name.
+//!
+//! CSV files have a header line, and one line per field. The first dictionary of the file is the root dictionary:
+//! ```csv
+//! dictionary,tag,label,type,repeatable,reference
+//! message,1,mti,string,,
+//! message,2,card,node,,card
+//! card,1,pan,string,,
+//! ```
+//!
+//! XML files declare their root dictionary with the `root` attribute:
+//! ```xml
+//! <dictionaries root="message">
+//!   <dictionary name="message">
+//!     <entry tag="1" label="mti" type="string"/>
+//!     <entry tag="2" label="card" type="node" reference="card"/>
+//!   </dictionary>
+//!   <dictionary name="card">
+//!     <entry tag="1" label="pan" type="string"/>
+//!   </dictionary>
+//! </dictionaries>
+//! ```
+//!
+//! Field types are `unsigned`, `signed`, `byte`, `float`, `string`, `bytes` (hexadecimal), `date`, `datetime` and `node`.
+
+use std::{
+    collections::HashMap,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, OnceLock},
+    time::SystemTime,
+};
+
+use thiserror::Error;
+
+pub mod deserialize;
+mod load;
+pub mod serialize;
+
+/// Position in a dictionary file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DictPosition {
+    /// Line of a CSV file
+    Line(u64),
+    /// Position (row and column) of an XML element
+    Element {
+        /// Row of the element
+        row: u32,
+        /// Column of the element
+        col: u32,
+    },
+}
+
+impl fmt::Display for DictPosition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DictPosition::Line(line) => write!(f, "line {}", line),
+            DictPosition::Element { row, col } => write!(f, "{}:{}", row, col),
+        }
+    }
+}
+
+/// Error define for dictionaries
+#[derive(Debug, Error)]
+pub enum DictError {
+    /// Error when the dictionary file can't be read
+    #[error("Can't read the dictionary: {0}")]
+    Io(#[from] io::Error),
+    /// Error on the dictionary file content
+    #[error("Invalid dictionary at {position}: {reason}")]
+    Invalid {
+        /// Position of the error in the file
+        position: DictPosition,
+        /// Reason of the error
+        reason: String,
+    },
+    /// Error when an entry can't be added to a dictionary
+    #[error("Invalid dictionary entry: {0}")]
+    Entry(String),
+}
+
+/// Type of a dictionary entry
+#[derive(Debug, Clone, PartialEq)]
+pub enum EntryType {
+    /// Unsigned integer
+    Unsigned,
+    /// Signed integer
+    Signed,
+    /// Single byte
+    Byte,
+    /// Float
+    Float,
+    /// String
+    String,
+    /// Bytes (hexadecimal in labeled messages)
+    Bytes,
+    /// Date (`%Y-%m-%d`)
+    Date,
+    /// Date and time (`%Y-%m-%dT%H:%M:%S%.f` with an optional offset, see [`deserialize::parse_datetime`])
+    DateTime,
+    /// Sub buffer described by its own dictionary
+    Node(Arc<Dictionary>),
+}
+
+impl fmt::Display for EntryType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EntryType::Unsigned => write!(f, "unsigned"),
+            EntryType::Signed => write!(f, "signed"),
+            EntryType::Byte => write!(f, "byte"),
+            EntryType::Float => write!(f, "float"),
+            EntryType::String => write!(f, "string"),
+            EntryType::Bytes => write!(f, "bytes"),
+            EntryType::Date => write!(f, "date"),
+            EntryType::DateTime => write!(f, "datetime"),
+            EntryType::Node(dictionary) => write!(f, "node({})", dictionary.get_name()),
+        }
+    }
+}
+
+/// Entry of a dictionary that describe a TVF field
+///
+/// A repeatable field is stored as a sub buffer where values are put on tags starting from 1.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DictEntry {
+    /// Tag of the field
+    pub tag: usize,
+    /// Label of the field
+    pub label: String,
+    /// Type of the field
+    pub entry_type: EntryType,
+    /// If the field can have several values
+    pub repeatable: bool,
+}
+
+impl DictEntry {
+    /// Method to create a dictionary entry
+    pub fn new<T>(tag: usize, label: T, entry_type: EntryType, repeatable: bool) -> DictEntry
+    where
+        T: Into<String>,
+    {
+        DictEntry {
+            tag,
+            label: label.into(),
+            entry_type,
+            repeatable,
+        }
+    }
+}
+
+/// Cache of dictionaries loaded from files, with the modification time of their file
+type DictCache = HashMap<PathBuf, (SystemTime, Arc<Dictionary>)>;
+
+/// Dictionary that give labels and types to TVF tags
+///
+/// ```
+/// use std::sync::Arc;
+/// use prosa_utils::dict::{Dictionary, DictEntry, EntryType};
+///
+/// let mut card = Dictionary::new("card");
+/// card.add_entry(DictEntry::new(1, "pan", EntryType::String, false)).unwrap();
+///
+/// let mut message = Dictionary::new("message");
+/// message.add_entry(DictEntry::new(1, "amount", EntryType::Unsigned, false)).unwrap();
+/// message.add_entry(DictEntry::new(2, "card", EntryType::Node(Arc::new(card)), false)).unwrap();
+///
+/// assert_eq!(2, message.get_by_label("card").unwrap().tag);
+/// assert_eq!("amount", message.get_by_tag(1).unwrap().label);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Dictionary {
+    name: String,
+    entries: Vec<DictEntry>,
+    labels: HashMap<String, usize>,
+    tags: HashMap<usize, usize>,
+}
+
+impl Dictionary {
+    /// Method to create an empty dictionary
+    pub fn new<T>(name: T) -> Dictionary
+    where
+        T: Into<String>,
+    {
+        Dictionary {
+            name: name.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Getter of the dictionary name
+    pub fn get_name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    /// Getter of the number of entries
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Getter to know if the dictionary don't have entries
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Getter of the dictionary entries (in insertion order)
+    pub fn entries(&self) -> std::slice::Iter<'_, DictEntry> {
+        self.entries.iter()
+    }
+
+    /// Method to add an entry to the dictionary. Tags and labels must be unique
+    pub fn add_entry(&mut self, entry: DictEntry) -> Result<(), DictError> {
+        if self.tags.contains_key(&entry.tag) {
+            return Err(DictError::Entry(format!(
+                "tag {} is already defined in the dictionary `{}`",
+                entry.tag, self.name
+            )));
+        }
+
+        if self.labels.contains_key(&entry.label) {
+            return Err(DictError::Entry(format!(
+                "label `{}` is already defined in the dictionary `{}`",
+                entry.label, self.name
+            )));
+        }
+
+        let index = self.entries.len();
+        self.tags.insert(entry.tag, index);
+        self.labels.insert(entry.label.clone(), index);
+        self.entries.push(entry);
+        Ok(())
+    }
+
+    /// Getter of an entry from its label
+    pub fn get_by_label(&self, label: &str) -> Option<&DictEntry> {
+        self.labels.get(label).map(|i| &self.entries[*i])
+    }
+
+    /// Getter of an entry from its tag
+    pub fn get_by_tag(&self, tag: usize) -> Option<&DictEntry> {
+        self.tags.get(&tag).map(|i| &self.entries[*i])
+    }
+
+    /// Method to load a dictionary from a CSV file
+    ///
+    /// Dictionaries are cached by path and modification time, so the file is only parsed again when it changes.
+    pub fn from_csv_file<P>(path: P) -> Result<Arc<Dictionary>, DictError>
+    where
+        P: AsRef<Path>,
+    {
+        Self::load_cached(path.as_ref(), Dictionary::from_csv_reader)
+    }
+
+    /// Method to load a dictionary from an XML file
+    ///
+    /// Dictionaries are cached by path and modification time, so the file is only parsed again when it changes.
+    pub fn from_xml_file<P>(path: P) -> Result<Arc<Dictionary>, DictError>
+    where
+        P: AsRef<Path>,
+    {
+        Self::load_cached(path.as_ref(), Dictionary::from_xml_reader)
+    }
+
+    /// Method to load a dictionary file, or to get it from the cache if the file didn't change
+    fn load_cached<F>(path: &Path, loader: F) -> Result<Arc<Dictionary>, DictError>
+    where
+        F: FnOnce(fs::File) -> Result<Dictionary, DictError>,
+    {
+        static DICT_CACHE: OnceLock<Mutex<DictCache>> = OnceLock::new();
+
+        let file = fs::File::open(path)?;
+        let modified = file.metadata()?.modified()?;
+        let cache = DICT_CACHE.get_or_init(Default::default);
+        if let Some((cache_modified, dictionary)) = cache.lock().unwrap().get(path) {
+            if *cache_modified == modified {
+                return Ok(dictionary.clone());
+            }
+        }
+
+        let dictionary = Arc::new(loader(file)?);
+        cache
+            .lock()
+            .unwrap()
+            .insert(path.to_path_buf(), (modified, dictionary.clone()));
+        Ok(dictionary)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use serde::de::DeserializeSeed as _;
+
+    use super::*;
+    use crate::msg::{simple_string_tvf::SimpleStringTvf, tvf::Tvf as _};
+    use deserialize::DictDeserializer;
+
+    const SAMPLE_CSV: &str = include_str!("../test_assets/dict_message.csv");
+    const SAMPLE_XML: &str = include_str!("../test_assets/dict_message.xml");
+
+    #[test]
+    fn dict_csv_xml_round_trip() {
+        let csv_dict = Dictionary::from_csv_reader(SAMPLE_CSV.as_bytes()).unwrap();
+        let xml_dict = Dictionary::from_xml_reader(SAMPLE_XML.as_bytes()).unwrap();
+        assert_eq!(csv_dict, xml_dict);
+        assert_eq!("message", csv_dict.get_name());
+        assert_eq!(5, csv_dict.len());
+
+        let card_entry = csv_dict.get_by_label("card").unwrap();
+        if let EntryType::Node(card) = &card_entry.entry_type {
+            assert_eq!("card", card.get_name());
+            assert_eq!(EntryType::Date, card.get_by_tag(2).unwrap().entry_type);
+        } else {
+            panic!("card should be a node instead of {}", card_entry.entry_type);
+        }
+        assert!(csv_dict.get_by_tag(4).unwrap().repeatable);
+
+        let json = r#"{
+            "mti": "0100",
+            "amount": 1250,
+            "card": { "pan": "4111111111111111", "expiry": "2027-12-31", "cvv_data": "a1b2" },
+            "tags": ["first", "second"],
+            "transmission": "2024-06-05T15:02:00"
+        }"#;
+        for dictionary in [&csv_dict, &xml_dict] {
+            let tvf: SimpleStringTvf = DictDeserializer::new(dictionary)
+                .deserialize(&mut serde_json::Deserializer::from_str(json))
+                .unwrap();
+            assert_eq!("0100", tvf.get_string(1).unwrap().as_str());
+            assert_eq!(1250, tvf.get_unsigned(2).unwrap());
+
+            let card = tvf.get_buffer(3).unwrap();
+            assert_eq!("4111111111111111", card.get_string(1).unwrap().as_str());
+            assert_eq!(
+                chrono::NaiveDate::from_ymd_opt(2027, 12, 31).unwrap(),
+                card.get_date(2).unwrap()
+            );
+            assert_eq!(&[0xa1, 0xb2][..], card.get_bytes(3).unwrap().as_ref());
+
+            let tags = tvf.get_buffer(4).unwrap();
+            assert_eq!("first", tags.get_string(1).unwrap().as_str());
+            assert_eq!("second", tags.get_string(2).unwrap().as_str());
+
+            assert_eq!(
+                chrono::NaiveDate::from_ymd_opt(2024, 6, 5)
+                    .unwrap()
+                    .and_hms_opt(15, 2, 0)
+                    .unwrap(),
+                tvf.get_datetime(5).unwrap()
+            );
+        }
+
+        let err = DictDeserializer::<SimpleStringTvf>::new(&csv_dict)
+            .deserialize(&mut serde_json::Deserializer::from_str(r#"{"unknown": 1}"#))
+            .unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("unknown field `unknown` in the dictionary `message`"));
+    }
+
+    #[test]
+    fn dict_load_errors() {
+        let csv = "dictionary,tag,label,type,repeatable,reference
+message,1,mti,string,,
+message,2,card,node,,card
+";
+        match Dictionary::from_csv_reader(csv.as_bytes()) {
+            Err(DictError::Invalid { position, reason }) => {
+                assert_eq!(DictPosition::Line(3), position);
+                assert_eq!("unknown dictionary reference `card`", reason);
+            }
+            res => panic!("Unexpected result {:?}", res),
+        }
+
+        let csv = "dictionary,tag,label,type,repeatable,reference
+message,1,mti,string,,
+message,1,amount,unsigned,,
+";
+        assert!(matches!(
+            Dictionary::from_csv_reader(csv.as_bytes()),
+            Err(DictError::Invalid {
+                position: DictPosition::Line(3),
+                ..
+            })
+        ));
+
+        let csv = "dictionary,tag,label,type,repeatable,reference
+message,one,mti,string,,
+";
+        assert!(matches!(
+            Dictionary::from_csv_reader(csv.as_bytes()),
+            Err(DictError::Invalid {
+                position: DictPosition::Line(2),
+                ..
+            })
+        ));
+
+        let xml = r#"<dictionaries root="message">
+  <dictionary name="message">
+    <entry tag="1" label="mti" type="text"/>
+  </dictionary>
+</dictionaries>"#;
+        match Dictionary::from_xml_reader(xml.as_bytes()) {
+            Err(DictError::Invalid { position, reason }) => {
+                assert_eq!(DictPosition::Element { row: 3, col: 5 }, position);
+                assert_eq!("unknown type `text`", reason);
+            }
+            res => panic!("Unexpected result {:?}", res),
+        }
+
+        let xml = r#"<dictionaries root="message">
+  <dictionary name="message">
+    <entry tag="1" label="self" type="node" reference="message"/>
+  </dictionary>
+</dictionaries>"#;
+        match Dictionary::from_xml_reader(xml.as_bytes()) {
+            Err(DictError::Invalid { position, reason }) => {
+                assert_eq!(DictPosition::Element { row: 3, col: 5 }, position);
+                assert_eq!("cyclic dictionary reference `message`", reason);
+            }
+            res => panic!("Unexpected result {:?}", res),
+        }
+
+        assert!(matches!(
+            Dictionary::from_xml_reader("<dictionaries>".as_bytes()),
+            Err(DictError::Invalid {
+                position: DictPosition::Element { row: 1, .. },
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn dict_file_cache() {
+        let dict_path = env::temp_dir().join("prosa_test_dict_cache.csv");
+        fs::write(&dict_path, SAMPLE_CSV).unwrap();
+
+        let dictionary = Dictionary::from_csv_file(&dict_path).unwrap();
+        assert!(Arc::ptr_eq(
+            &dictionary,
+            &Dictionary::from_csv_file(&dict_path).unwrap()
+        ));
+
+        // A modified file is loaded again
+        let file = fs::File::options().append(true).open(&dict_path).unwrap();
+        io::Write::write_all(&mut &file, b"message,6,extra,string,,\n").unwrap();
+        file.set_modified(SystemTime::now() + std::time::Duration::from_secs(1))
+            .unwrap();
+        let reloaded_dictionary = Dictionary::from_csv_file(&dict_path).unwrap();
+        assert!(!Arc::ptr_eq(&dictionary, &reloaded_dictionary));
+        assert_eq!("extra", reloaded_dictionary.get_by_tag(6).unwrap().label);
+
+        fs::remove_file(&dict_path).unwrap();
+        assert!(matches!(
+            Dictionary::from_csv_file(&dict_path),
+            Err(DictError::Io(_))
+        ));
+    }
+}
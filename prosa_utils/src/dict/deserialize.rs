@@ -0,0 +1,532 @@
+//! Deserialization of labeled messages (like JSON) into TVF with a dictionary
+//!
+//! ```
+//! use serde::de::DeserializeSeed;
+//! use prosa_utils::dict::{Dictionary, DictEntry, EntryType, deserialize::DictDeserializer};
+//! use prosa_utils::msg::{simple_string_tvf::SimpleStringTvf, tvf::Tvf};
+//!
+//! let mut dictionary = Dictionary::new("message");
+//! dictionary.add_entry(DictEntry::new(1, "name", EntryType::String, false)).unwrap();
+//! dictionary.add_entry(DictEntry::new(2, "values", EntryType::Unsigned, true)).unwrap();
+//!
+//! let json = r#"{ "name": "ProSA", "values": [1, 2] }"#;
+//! let tvf: SimpleStringTvf = DictDeserializer::new(&dictionary)
+//!     .deserialize(&mut serde_json::Deserializer::from_str(json))
+//!     .unwrap();
+//! assert_eq!("ProSA", tvf.get_string(1).unwrap().as_str());
+//! assert_eq!(2, tvf.get_buffer(2).unwrap().get_unsigned(2).unwrap());
+//! ```
+//!
+//! Messages can come from untrusted partners, so the deserializer bound the nesting of the sub buffers and the size of the values before allocating them (see [`DictDeserializer::max_depth`] and [`DictDeserializer::max_size`]).
+
+use std::{
+    fmt::{self, Debug},
+    marker::PhantomData,
+};
+
+use bytes::Bytes;
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+use serde::de::{self, DeserializeSeed, Deserializer, MapAccess, SeqAccess, Visitor};
+use serde::Deserialize as _;
+
+use super::{Dictionary, EntryType};
+use crate::msg::tvf::Tvf;
+
+/// Default maximum depth of the sub buffers read by the [`DictDeserializer`]
+pub const DEFAULT_MAX_DEPTH: usize = 32;
+/// Default maximum size of the values read by the [`DictDeserializer`]
+pub const DEFAULT_MAX_SIZE: usize = 1 << 20;
+
+/// Format of dictionary dates
+pub const DATE_FORMAT: &str = "%Y-%m-%d";
+/// Formats of dictionary datetimes (an offset can be added to them, see [`parse_datetime`])
+pub const DATETIME_FORMATS: [&str; 2] = ["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%d %H:%M:%S%.f"];
+
+/// Method to parse a dictionary datetime
+///
+/// The date and the time can be separated by a `T` or a space, and seconds can have a fractional part.
+/// A trailing `Z` or `±hh:mm` offset is accepted, the datetime is then converted to naive UTC.
+///
+/// ```
+/// use chrono::NaiveDate;
+/// use prosa_utils::dict::deserialize::parse_datetime;
+///
+/// let datetime = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap().and_hms_opt(12, 0, 0).unwrap();
+/// assert_eq!(Ok(datetime), parse_datetime("2024-01-01 12:00:00.000"));
+/// assert_eq!(Ok(datetime), parse_datetime("2024-01-01T14:00:00+02:00"));
+/// ```
+pub fn parse_datetime(value: &str) -> Result<NaiveDateTime, String> {
+    let parse_naive = |value: &str| {
+        DATETIME_FORMATS
+            .iter()
+            .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+    };
+
+    let datetime = if let Some(utc_value) = value.strip_suffix('Z') {
+        parse_naive(utc_value)
+    } else {
+        parse_naive(value).or_else(|| {
+            DATETIME_FORMATS.iter().find_map(|format| {
+                DateTime::parse_from_str(value, &format!("{}%:z", format))
+                    .ok()
+                    .map(|datetime| datetime.naive_utc())
+            })
+        })
+    };
+
+    datetime.ok_or_else(|| {
+        format!(
+            "invalid datetime `{}`, expected formats are `{}` with an optional `Z` or `%:z` offset",
+            value,
+            DATETIME_FORMATS.join("`, `")
+        )
+    })
+}
+
+/// Limits of the deserialization, with the depth of the buffer being read
+#[derive(Debug, Clone, Copy)]
+struct Limits {
+    max_depth: usize,
+    max_size: usize,
+    depth: usize,
+}
+
+impl Limits {
+    /// Method to get the limits of a sub buffer
+    fn nested<E: de::Error>(self) -> Result<Limits, E> {
+        if self.depth < self.max_depth {
+            Ok(Limits {
+                depth: self.depth + 1,
+                ..self
+            })
+        } else {
+            Err(de::Error::custom(format!(
+                "sub buffers exceed the maximum depth of {}",
+                self.max_depth
+            )))
+        }
+    }
+
+    /// Method to check the size of a value
+    fn check_size<E: de::Error>(&self, size: usize) -> Result<(), E> {
+        if size <= self.max_size {
+            Ok(())
+        } else {
+            Err(de::Error::custom(format!(
+                "value of size {} exceed the maximum size of {}",
+                size, self.max_size
+            )))
+        }
+    }
+}
+
+/// Deserializer of a labeled map into a TVF, using a dictionary to get the tag and the type of every field
+///
+/// Unknown labels are rejected. Repeatable fields are read from sequences into sub buffers where values start at tag 1.
+///
+/// The nesting of sub buffers (nodes and repeatable fields) is bounded by `max_depth`.
+/// The length of string values (hexadecimal strings for bytes) and the number of values of a repeatable field are bounded by `max_size`.
+///
+/// ```
+/// use serde::de::DeserializeSeed;
+/// use prosa_utils::dict::{Dictionary, DictEntry, EntryType, deserialize::DictDeserializer};
+/// use prosa_utils::msg::simple_string_tvf::SimpleStringTvf;
+///
+/// let mut dictionary = Dictionary::new("message");
+/// dictionary.add_entry(DictEntry::new(1, "name", EntryType::String, false)).unwrap();
+///
+/// let json = r#"{ "name": "a too long name" }"#;
+/// let err = DictDeserializer::<SimpleStringTvf>::new(&dictionary)
+///     .max_size(8)
+///     .deserialize(&mut serde_json::Deserializer::from_str(json))
+///     .unwrap_err();
+/// assert!(err.to_string().starts_with("value of size 15 exceed the maximum size of 8"));
+/// ```
+pub struct DictDeserializer<'d, T> {
+    dictionary: &'d Dictionary,
+    limits: Limits,
+    tvf: PhantomData<T>,
+}
+
+impl<'d, T> DictDeserializer<'d, T> {
+    /// Method to create a deserializer for a dictionary, with the default limits ([`DEFAULT_MAX_DEPTH`] and [`DEFAULT_MAX_SIZE`])
+    pub fn new(dictionary: &'d Dictionary) -> DictDeserializer<'d, T> {
+        DictDeserializer {
+            dictionary,
+            limits: Limits {
+                max_depth: DEFAULT_MAX_DEPTH,
+                max_size: DEFAULT_MAX_SIZE,
+                depth: 0,
+            },
+            tvf: PhantomData,
+        }
+    }
+
+    /// Setter of the maximum depth of the sub buffers
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.limits.max_depth = max_depth;
+        self
+    }
+
+    /// Setter of the maximum size of the values (length of the strings, and number of values of repeatable fields)
+    pub fn max_size(mut self, max_size: usize) -> Self {
+        self.limits.max_size = max_size;
+        self
+    }
+
+    /// Method to create the deserializer of a sub buffer
+    fn nested(dictionary: &'d Dictionary, limits: Limits) -> DictDeserializer<'d, T> {
+        DictDeserializer {
+            dictionary,
+            limits,
+            tvf: PhantomData,
+        }
+    }
+}
+
+impl<'de, T> DeserializeSeed<'de> for DictDeserializer<'_, T>
+where
+    T: Tvf + Default + Debug + Clone,
+{
+    type Value = T;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<T, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de, T> Visitor<'de> for DictDeserializer<'_, T>
+where
+    T: Tvf + Default + Debug + Clone,
+{
+    type Value = T;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            formatter,
+            "a map of the `{}` dictionary fields",
+            self.dictionary.get_name()
+        )
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<T, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let mut tvf = T::default();
+        while let Some(label) = map.next_key::<String>()? {
+            let entry = self.dictionary.get_by_label(&label).ok_or_else(|| {
+                de::Error::custom(format!(
+                    "unknown field `{}` in the dictionary `{}`",
+                    label,
+                    self.dictionary.get_name()
+                ))
+            })?;
+
+            if entry.repeatable {
+                let values = map.next_value_seed(RepeatedSeed {
+                    entry_type: &entry.entry_type,
+                    limits: self.limits.nested()?,
+                    tvf: PhantomData::<T>,
+                })?;
+                tvf.put_buffer(entry.tag, values);
+            } else {
+                map.next_value_seed(FieldSeed {
+                    tag: entry.tag,
+                    entry_type: &entry.entry_type,
+                    limits: self.limits,
+                    tvf: &mut tvf,
+                })?;
+            }
+        }
+
+        Ok(tvf)
+    }
+}
+
+/// Seed to deserialize a string, with its size checked before its allocation
+struct BoundedString {
+    limits: Limits,
+}
+
+impl<'de> DeserializeSeed<'de> for BoundedString {
+    type Value = String;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<String, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_str(self)
+    }
+}
+
+impl Visitor<'_> for BoundedString {
+    type Value = String;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            formatter,
+            "a string of at most {} bytes",
+            self.limits.max_size
+        )
+    }
+
+    fn visit_str<E>(self, value: &str) -> Result<String, E>
+    where
+        E: de::Error,
+    {
+        self.limits.check_size(value.len())?;
+        Ok(value.to_string())
+    }
+
+    fn visit_string<E>(self, value: String) -> Result<String, E>
+    where
+        E: de::Error,
+    {
+        self.limits.check_size(value.len())?;
+        Ok(value)
+    }
+}
+
+/// Seed to deserialize a field value into a TVF
+struct FieldSeed<'a, 'd, T> {
+    tag: usize,
+    entry_type: &'d EntryType,
+    limits: Limits,
+    tvf: &'a mut T,
+}
+
+impl<'de, T> DeserializeSeed<'de> for FieldSeed<'_, '_, T>
+where
+    T: Tvf + Default + Debug + Clone,
+{
+    type Value = ();
+
+    fn deserialize<D>(self, deserializer: D) -> Result<(), D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        match self.entry_type {
+            EntryType::Unsigned => self
+                .tvf
+                .put_unsigned(self.tag, u64::deserialize(deserializer)?),
+            EntryType::Signed => self
+                .tvf
+                .put_signed(self.tag, i64::deserialize(deserializer)?),
+            EntryType::Byte => self.tvf.put_byte(self.tag, u8::deserialize(deserializer)?),
+            EntryType::Float => self
+                .tvf
+                .put_float(self.tag, f64::deserialize(deserializer)?),
+            EntryType::String => self.tvf.put_string(
+                self.tag,
+                BoundedString {
+                    limits: self.limits,
+                }
+                .deserialize(deserializer)?,
+            ),
+            EntryType::Bytes => {
+                let value = BoundedString {
+                    limits: self.limits,
+                }
+                .deserialize(deserializer)?;
+                let bytes = hex::decode(&value).map_err(|e| {
+                    de::Error::custom(format!("invalid hexadecimal bytes `{}`: {}", value, e))
+                })?;
+                self.tvf.put_bytes(self.tag, Bytes::from(bytes));
+            }
+            EntryType::Date => {
+                let value = String::deserialize(deserializer)?;
+                let date = NaiveDate::parse_from_str(&value, DATE_FORMAT)
+                    .map_err(|e| de::Error::custom(format!("invalid date `{}`: {}", value, e)))?;
+                self.tvf.put_date(self.tag, date);
+            }
+            EntryType::DateTime => {
+                let value = String::deserialize(deserializer)?;
+                let datetime = parse_datetime(&value).map_err(de::Error::custom)?;
+                self.tvf.put_datetime(self.tag, datetime);
+            }
+            EntryType::Node(dictionary) => {
+                let buffer = DictDeserializer::<T>::nested(dictionary, self.limits.nested()?)
+                    .deserialize(deserializer)?;
+                self.tvf.put_buffer(self.tag, buffer);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Seed to deserialize the values of a repeatable field into a sub buffer
+struct RepeatedSeed<'d, T> {
+    entry_type: &'d EntryType,
+    limits: Limits,
+    tvf: PhantomData<T>,
+}
+
+impl<'de, T> DeserializeSeed<'de> for RepeatedSeed<'_, T>
+where
+    T: Tvf + Default + Debug + Clone,
+{
+    type Value = T;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<T, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de, T> Visitor<'de> for RepeatedSeed<'_, T>
+where
+    T: Tvf + Default + Debug + Clone,
+{
+    type Value = T;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "a sequence of {} values", self.entry_type)
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<T, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let mut tvf = T::default();
+        let mut tag = 1;
+        while seq
+            .next_element_seed(FieldSeed {
+                tag,
+                entry_type: self.entry_type,
+                limits: self.limits,
+                tvf: &mut tvf,
+            })?
+            .is_some()
+        {
+            self.limits.check_size(tag)?;
+            tag += 1;
+        }
+
+        Ok(tvf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{dict::DictEntry, msg::simple_string_tvf::SimpleStringTvf};
+
+    #[test]
+    fn dict_parse_datetime() {
+        let datetime = NaiveDate::from_ymd_opt(2024, 6, 5)
+            .unwrap()
+            .and_hms_opt(15, 2, 0)
+            .unwrap();
+        let datetime_ms = NaiveDate::from_ymd_opt(2024, 6, 5)
+            .unwrap()
+            .and_hms_milli_opt(15, 2, 0, 123)
+            .unwrap();
+
+        for (expected, value) in [
+            (datetime, "2024-06-05T15:02:00"),
+            (datetime, "2024-06-05 15:02:00"),
+            (datetime, "2024-06-05T15:02:00Z"),
+            (datetime, "2024-06-05 15:02:00Z"),
+            (datetime, "2024-06-05T17:02:00+02:00"),
+            (datetime, "2024-06-05 10:32:00-04:30"),
+            (datetime, "2024-06-05T15:02:00+00:00"),
+            (datetime_ms, "2024-06-05T15:02:00.123"),
+            (datetime_ms, "2024-06-05 15:02:00.123"),
+            (datetime_ms, "2024-06-05T15:02:00.123Z"),
+            (datetime_ms, "2024-06-05 15:02:00.123Z"),
+            (datetime_ms, "2024-06-05T16:02:00.123+01:00"),
+            (datetime_ms, "2024-06-05 13:02:00.123-02:00"),
+        ] {
+            assert_eq!(Ok(expected), parse_datetime(value), "{}", value);
+        }
+
+        // Offsets can change the date
+        assert_eq!(
+            Ok(NaiveDate::from_ymd_opt(2024, 6, 4)
+                .unwrap()
+                .and_hms_opt(23, 30, 0)
+                .unwrap()),
+            parse_datetime("2024-06-05T01:30:00+02:00")
+        );
+    }
+
+    #[test]
+    fn dict_parse_datetime_invalid() {
+        for value in [
+            "",
+            "2024-06-05",
+            "2024-06-05T15:02",
+            "2024-06-05_15:02:00",
+            "2024-06-05T15:02:00+02",
+            "2024-06-05T15:02:00ZZ",
+            "2024-13-05T15:02:00",
+            "05/06/2024 15:02:00",
+        ] {
+            let err = parse_datetime(value).unwrap_err();
+            assert_eq!(
+                format!(
+                    "invalid datetime `{}`, expected formats are `%Y-%m-%dT%H:%M:%S%.f`, `%Y-%m-%d %H:%M:%S%.f` with an optional `Z` or `%:z` offset",
+                    value
+                ),
+                err
+            );
+        }
+    }
+
+    #[test]
+    fn dict_deserialize_limits() {
+        let mut card = Dictionary::new("card");
+        card.add_entry(DictEntry::new(1, "pan", EntryType::String, false))
+            .unwrap();
+        card.add_entry(DictEntry::new(2, "cvv_data", EntryType::Bytes, false))
+            .unwrap();
+        let mut dictionary = Dictionary::new("message");
+        dictionary
+            .add_entry(DictEntry::new(
+                1,
+                "card",
+                EntryType::Node(Arc::new(card)),
+                false,
+            ))
+            .unwrap();
+        dictionary
+            .add_entry(DictEntry::new(2, "tags", EntryType::String, true))
+            .unwrap();
+
+        let deserialize = |json: &str, max_depth: usize, max_size: usize| {
+            DictDeserializer::<SimpleStringTvf>::new(&dictionary)
+                .max_depth(max_depth)
+                .max_size(max_size)
+                .deserialize(&mut serde_json::Deserializer::from_str(json))
+                .map_err(|e| e.to_string())
+        };
+
+        let json = r#"{ "card": { "pan": "4111", "cvv_data": "a1b2" }, "tags": ["a", "b"] }"#;
+        assert!(deserialize(json, 1, 4).is_ok());
+        assert!(deserialize(json, 0, 4)
+            .unwrap_err()
+            .starts_with("sub buffers exceed the maximum depth of 0"));
+        assert!(deserialize(json, 1, 3)
+            .unwrap_err()
+            .starts_with("value of size 4 exceed the maximum size of 3"));
+
+        // Repeatable fields are bounded in number of values
+        assert!(deserialize(r#"{ "tags": ["a", "b", "c"] }"#, 1, 2)
+            .unwrap_err()
+            .starts_with("value of size 3 exceed the maximum size of 2"));
+
+        // Escaped strings are checked too
+        assert!(deserialize(r#"{ "tags": ["\u0041\u0042\u0043"] }"#, 1, 2)
+            .unwrap_err()
+            .starts_with("value of size 3 exceed the maximum size of 2"));
+    }
+}
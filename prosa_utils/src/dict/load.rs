@@ -0,0 +1,278 @@
+//! Loaders of dictionaries from CSV and XML files
+
+use std::{collections::HashMap, io, sync::Arc};
+
+use serde::Deserialize;
+
+use super::{DictEntry, DictError, DictPosition, Dictionary, EntryType};
+
+/// Entry read from a dictionary file, before the resolution of its reference
+struct RawEntry {
+    position: DictPosition,
+    tag: usize,
+    label: String,
+    entry_type: String,
+    repeatable: bool,
+    reference: Option<String>,
+}
+
+/// Line of a CSV dictionary file
+#[derive(Deserialize)]
+struct CsvEntry {
+    dictionary: String,
+    tag: usize,
+    label: String,
+    #[serde(rename = "type")]
+    entry_type: String,
+    repeatable: Option<bool>,
+    reference: Option<String>,
+}
+
+/// Dictionaries read from a file, indexed by their name
+#[derive(Default)]
+struct RawDictionaries {
+    dictionaries: HashMap<String, Vec<RawEntry>>,
+    resolved: HashMap<String, Arc<Dictionary>>,
+}
+
+impl RawDictionaries {
+    fn push(&mut self, dictionary: &str, entry: RawEntry) {
+        self.dictionaries
+            .entry(dictionary.to_string())
+            .or_default()
+            .push(entry);
+    }
+
+    /// Method to build a dictionary with its referenced sub dictionaries
+    fn resolve(
+        &mut self,
+        name: &str,
+        position: DictPosition,
+        resolving: &mut Vec<String>,
+    ) -> Result<Arc<Dictionary>, DictError> {
+        if let Some(dictionary) = self.resolved.get(name) {
+            return Ok(dictionary.clone());
+        }
+
+        if resolving.iter().any(|n| n == name) {
+            return Err(DictError::Invalid {
+                position,
+                reason: format!("cyclic dictionary reference `{}`", name),
+            });
+        }
+
+        let entries = self
+            .dictionaries
+            .remove(name)
+            .ok_or_else(|| DictError::Invalid {
+                position,
+                reason: format!("unknown dictionary reference `{}`", name),
+            })?;
+
+        resolving.push(name.to_string());
+        let mut dictionary = Dictionary::new(name);
+        for raw_entry in entries {
+            let entry_type = match raw_entry.entry_type.to_lowercase().as_str() {
+                "unsigned" => EntryType::Unsigned,
+                "signed" => EntryType::Signed,
+                "byte" => EntryType::Byte,
+                "float" => EntryType::Float,
+                "string" => EntryType::String,
+                "bytes" => EntryType::Bytes,
+                "date" => EntryType::Date,
+                "datetime" => EntryType::DateTime,
+                "node" => {
+                    let reference =
+                        raw_entry
+                            .reference
+                            .as_deref()
+                            .ok_or_else(|| DictError::Invalid {
+                                position: raw_entry.position,
+                                reason: format!(
+                                    "node `{}` don't have a dictionary reference",
+                                    raw_entry.label
+                                ),
+                            })?;
+                    EntryType::Node(self.resolve(reference, raw_entry.position, resolving)?)
+                }
+                entry_type => {
+                    return Err(DictError::Invalid {
+                        position: raw_entry.position,
+                        reason: format!("unknown type `{}`", entry_type),
+                    })
+                }
+            };
+
+            dictionary
+                .add_entry(DictEntry::new(
+                    raw_entry.tag,
+                    raw_entry.label,
+                    entry_type,
+                    raw_entry.repeatable,
+                ))
+                .map_err(|e| DictError::Invalid {
+                    position: raw_entry.position,
+                    reason: e.to_string(),
+                })?;
+        }
+        resolving.pop();
+
+        let dictionary = Arc::new(dictionary);
+        self.resolved.insert(name.to_string(), dictionary.clone());
+        Ok(dictionary)
+    }
+
+    /// Method to build the root dictionary
+    fn into_dictionary(
+        mut self,
+        root: &str,
+        position: DictPosition,
+    ) -> Result<Dictionary, DictError> {
+        let dictionary = self.resolve(root, position, &mut Vec::new())?;
+        // Drop the other references to get the root dictionary back
+        self.resolved.clear();
+        Ok(Arc::try_unwrap(dictionary).unwrap_or_else(|d| (*d).clone()))
+    }
+}
+
+/// Getter of the position of an XML node
+fn xml_position(node: &roxmltree::Node) -> DictPosition {
+    let pos = node.document().text_pos_at(node.range().start);
+    DictPosition::Element {
+        row: pos.row,
+        col: pos.col,
+    }
+}
+
+/// Getter of a mandatory attribute of an XML node
+fn xml_attribute<'a>(
+    node: &roxmltree::Node<'a, '_>,
+    attribute: &str,
+) -> Result<&'a str, DictError> {
+    node.attribute(attribute).ok_or_else(|| DictError::Invalid {
+        position: xml_position(node),
+        reason: format!(
+            "missing `{}` attribute on `{}`",
+            attribute,
+            node.tag_name().name()
+        ),
+    })
+}
+
+impl Dictionary {
+    /// Method to load a dictionary from a CSV content
+    ///
+    /// The first dictionary of the content is the root dictionary. Other dictionaries are referenced by node entries.
+    pub fn from_csv_reader<R>(reader: R) -> Result<Dictionary, DictError>
+    where
+        R: io::Read,
+    {
+        let csv_position =
+            |e: &csv::Error| DictPosition::Line(e.position().map(|p| p.line()).unwrap_or_default());
+
+        let mut csv_reader = csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .comment(Some(b'#'))
+            .from_reader(reader);
+        let headers = csv_reader
+            .headers()
+            .map_err(|e| DictError::Invalid {
+                position: csv_position(&e),
+                reason: e.to_string(),
+            })?
+            .clone();
+
+        let mut root = None;
+        let mut raw_dictionaries = RawDictionaries::default();
+        for record in csv_reader.records() {
+            let record = record.map_err(|e| DictError::Invalid {
+                position: csv_position(&e),
+                reason: e.to_string(),
+            })?;
+            let position =
+                DictPosition::Line(record.position().map(|p| p.line()).unwrap_or_default());
+            let entry: CsvEntry =
+                record
+                    .deserialize(Some(&headers))
+                    .map_err(|e| DictError::Invalid {
+                        position,
+                        reason: e.to_string(),
+                    })?;
+
+            if root.is_none() {
+                root = Some((entry.dictionary.clone(), position));
+            }
+
+            raw_dictionaries.push(
+                &entry.dictionary,
+                RawEntry {
+                    position,
+                    tag: entry.tag,
+                    label: entry.label,
+                    entry_type: entry.entry_type,
+                    repeatable: entry.repeatable.unwrap_or_default(),
+                    reference: entry.reference.filter(|r| !r.is_empty()),
+                },
+            );
+        }
+
+        let (root, position) = root.ok_or_else(|| DictError::Invalid {
+            position: DictPosition::Line(1),
+            reason: String::from("no dictionary entry"),
+        })?;
+        raw_dictionaries.into_dictionary(&root, position)
+    }
+
+    /// Method to load a dictionary from an XML content
+    ///
+    /// The root dictionary is given by the `root` attribute of the `dictionaries` element. Other dictionaries are referenced by node entries.
+    pub fn from_xml_reader<R>(mut reader: R) -> Result<Dictionary, DictError>
+    where
+        R: io::Read,
+    {
+        let mut xml = String::new();
+        reader.read_to_string(&mut xml)?;
+        let document = roxmltree::Document::parse(&xml).map_err(|e| {
+            let pos = e.pos();
+            DictError::Invalid {
+                position: DictPosition::Element {
+                    row: pos.row,
+                    col: pos.col,
+                },
+                reason: e.to_string(),
+            }
+        })?;
+
+        let root_element = document.root_element();
+        let root = xml_attribute(&root_element, "root")?;
+        let mut raw_dictionaries = RawDictionaries::default();
+        for dictionary in root_element
+            .children()
+            .filter(|n| n.has_tag_name("dictionary"))
+        {
+            let name = xml_attribute(&dictionary, "name")?;
+            for entry in dictionary.children().filter(|n| n.has_tag_name("entry")) {
+                let position = xml_position(&entry);
+                let tag = xml_attribute(&entry, "tag")?;
+                raw_dictionaries.push(
+                    name,
+                    RawEntry {
+                        position,
+                        tag: tag.parse().map_err(|_| DictError::Invalid {
+                            position,
+                            reason: format!("invalid tag `{}`", tag),
+                        })?,
+                        label: xml_attribute(&entry, "label")?.to_string(),
+                        entry_type: xml_attribute(&entry, "type")?.to_string(),
+                        repeatable: entry
+                            .attribute("repeatable")
+                            .is_some_and(|r| r.eq_ignore_ascii_case("true")),
+                        reference: entry.attribute("reference").map(str::to_string),
+                    },
+                );
+            }
+        }
+
+        raw_dictionaries.into_dictionary(root, xml_position(&root_element))
+    }
+}
@@ -0,0 +1,276 @@
+//! Serialization of TVF into labeled messages (like JSON) with a dictionary
+//!
+//! ```
+//! use prosa_utils::dict::{Dictionary, DictEntry, EntryType, serialize::DictSerializer};
+//! use prosa_utils::msg::{simple_string_tvf::SimpleStringTvf, tvf::Tvf};
+//!
+//! let mut dictionary = Dictionary::new("message");
+//! dictionary.add_entry(DictEntry::new(1, "name", EntryType::String, false)).unwrap();
+//! dictionary.add_entry(DictEntry::new(2, "values", EntryType::Unsigned, true)).unwrap();
+//!
+//! let mut values = SimpleStringTvf::default();
+//! values.put_unsigned(1, 1);
+//! values.put_unsigned(2, 2);
+//! let mut tvf = SimpleStringTvf::default();
+//! tvf.put_string(1, "ProSA");
+//! tvf.put_buffer(2, values);
+//!
+//! let json = serde_json::to_string(&DictSerializer::new(&dictionary, &tvf)).unwrap();
+//! assert_eq!(r#"{"name":"ProSA","values":[1,2]}"#, json);
+//! ```
+//!
+//! It's the reverse of the [`DictDeserializer`](super::deserialize::DictDeserializer), so a serialized message can be read back with the same dictionary.
+
+use std::fmt::Debug;
+
+use serde::ser::{self, Serialize, SerializeMap, SerializeSeq, Serializer};
+
+use super::{
+    deserialize::{DATETIME_FORMATS, DATE_FORMAT},
+    Dictionary, EntryType,
+};
+use crate::msg::tvf::Tvf;
+
+/// Serializer of a TVF into a labeled map, using a dictionary to get the label and the type of every field
+///
+/// Fields are serialized in the order of the dictionary entries, and the tags that are not in the dictionary are skipped.
+/// Repeatable fields are written as sequences of the sub buffer values, from tag 1 to the first missing tag.
+/// A field that doesn't have the type of its dictionary entry is an error.
+///
+/// ```
+/// use prosa_utils::dict::{Dictionary, DictEntry, EntryType, serialize::DictSerializer};
+/// use prosa_utils::msg::{simple_string_tvf::SimpleStringTvf, tvf::Tvf};
+///
+/// let mut dictionary = Dictionary::new("message");
+/// dictionary.add_entry(DictEntry::new(1, "amount", EntryType::Unsigned, false)).unwrap();
+///
+/// let mut tvf = SimpleStringTvf::default();
+/// tvf.put_string(1, "not a number");
+/// tvf.put_string(2, "unknown");
+/// assert!(serde_json::to_string(&DictSerializer::new(&dictionary, &tvf)).is_err());
+///
+/// tvf.put_unsigned(1, 42);
+/// assert_eq!(
+///     r#"{"amount":42}"#,
+///     serde_json::to_string(&DictSerializer::new(&dictionary, &tvf)).unwrap()
+/// );
+/// ```
+pub struct DictSerializer<'d, 't, T> {
+    dictionary: &'d Dictionary,
+    tvf: &'t T,
+}
+
+impl<'d, 't, T> DictSerializer<'d, 't, T> {
+    /// Method to create a serializer of a TVF for a dictionary
+    pub fn new(dictionary: &'d Dictionary, tvf: &'t T) -> DictSerializer<'d, 't, T> {
+        DictSerializer { dictionary, tvf }
+    }
+}
+
+impl<T> Serialize for DictSerializer<'_, '_, T>
+where
+    T: Tvf + Default + Debug + Clone,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut map = serializer.serialize_map(None)?;
+        for entry in self
+            .dictionary
+            .entries()
+            .filter(|entry| self.tvf.contains(entry.tag))
+        {
+            if entry.repeatable {
+                let values = self.tvf.get_buffer(entry.tag).map_err(|e| {
+                    ser::Error::custom(format!("repeatable field `{}`: {}", entry.label, e))
+                })?;
+                map.serialize_entry(
+                    &entry.label,
+                    &RepeatedSerializer {
+                        entry_type: &entry.entry_type,
+                        tvf: values.as_ref(),
+                    },
+                )?;
+            } else {
+                map.serialize_entry(
+                    &entry.label,
+                    &FieldSerializer {
+                        tag: entry.tag,
+                        entry_type: &entry.entry_type,
+                        tvf: self.tvf,
+                    },
+                )?;
+            }
+        }
+
+        map.end()
+    }
+}
+
+/// Serializer of a field value of a TVF
+struct FieldSerializer<'d, 't, T> {
+    tag: usize,
+    entry_type: &'d EntryType,
+    tvf: &'t T,
+}
+
+impl<T> Serialize for FieldSerializer<'_, '_, T>
+where
+    T: Tvf + Default + Debug + Clone,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let field_error = |e| {
+            ser::Error::custom(format!(
+                "field {} of type {}: {}",
+                self.tag, self.entry_type, e
+            ))
+        };
+
+        match self.entry_type {
+            EntryType::Unsigned => {
+                serializer.serialize_u64(self.tvf.get_unsigned(self.tag).map_err(field_error)?)
+            }
+            EntryType::Signed => {
+                serializer.serialize_i64(self.tvf.get_signed(self.tag).map_err(field_error)?)
+            }
+            EntryType::Byte => {
+                serializer.serialize_u8(self.tvf.get_byte(self.tag).map_err(field_error)?)
+            }
+            EntryType::Float => {
+                serializer.serialize_f64(self.tvf.get_float(self.tag).map_err(field_error)?)
+            }
+            EntryType::String => {
+                serializer.serialize_str(&self.tvf.get_string(self.tag).map_err(field_error)?)
+            }
+            EntryType::Bytes => serializer.serialize_str(&hex::encode(
+                self.tvf.get_bytes(self.tag).map_err(field_error)?.as_ref(),
+            )),
+            EntryType::Date => serializer.collect_str(
+                &self
+                    .tvf
+                    .get_date(self.tag)
+                    .map_err(field_error)?
+                    .format(DATE_FORMAT),
+            ),
+            EntryType::DateTime => serializer.collect_str(
+                &self
+                    .tvf
+                    .get_datetime(self.tag)
+                    .map_err(field_error)?
+                    .format(DATETIME_FORMATS[0]),
+            ),
+            EntryType::Node(dictionary) => {
+                let buffer = self.tvf.get_buffer(self.tag).map_err(field_error)?;
+                DictSerializer::new(dictionary, buffer.as_ref()).serialize(serializer)
+            }
+        }
+    }
+}
+
+/// Serializer of the values of a repeatable field, from its sub buffer
+struct RepeatedSerializer<'d, 't, T> {
+    entry_type: &'d EntryType,
+    tvf: &'t T,
+}
+
+impl<T> Serialize for RepeatedSerializer<'_, '_, T>
+where
+    T: Tvf + Default + Debug + Clone,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut seq = serializer.serialize_seq(None)?;
+        let mut tag = 1;
+        while self.tvf.contains(tag) {
+            seq.serialize_element(&FieldSerializer {
+                tag,
+                entry_type: self.entry_type,
+                tvf: self.tvf,
+            })?;
+            tag += 1;
+        }
+
+        seq.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use bytes::Bytes;
+    use chrono::NaiveDate;
+    use serde::de::DeserializeSeed as _;
+
+    use super::*;
+    use crate::{
+        dict::{deserialize::DictDeserializer, DictEntry},
+        msg::simple_string_tvf::SimpleStringTvf,
+    };
+
+    #[test]
+    fn dict_serialize_round_trip() {
+        let mut card = Dictionary::new("card");
+        card.add_entry(DictEntry::new(1, "pan", EntryType::String, false))
+            .unwrap();
+        card.add_entry(DictEntry::new(2, "cvv_data", EntryType::Bytes, false))
+            .unwrap();
+        let mut dictionary = Dictionary::new("message");
+        for entry in [
+            DictEntry::new(1, "amount", EntryType::Unsigned, false),
+            DictEntry::new(2, "balance", EntryType::Signed, false),
+            DictEntry::new(3, "kind", EntryType::Byte, false),
+            DictEntry::new(4, "rate", EntryType::Float, false),
+            DictEntry::new(5, "date", EntryType::Date, false),
+            DictEntry::new(6, "datetime", EntryType::DateTime, false),
+            DictEntry::new(7, "card", EntryType::Node(Arc::new(card)), false),
+            DictEntry::new(8, "tags", EntryType::String, true),
+        ] {
+            dictionary.add_entry(entry).unwrap();
+        }
+
+        let mut card = SimpleStringTvf::default();
+        card.put_string(1, "4111");
+        card.put_bytes(2, Bytes::from_static(&[0xa1, 0xb2]));
+        let mut tags = SimpleStringTvf::default();
+        tags.put_string(1, "a");
+        tags.put_string(2, "b");
+        let mut tvf = SimpleStringTvf::default();
+        tvf.put_unsigned(1, 42);
+        tvf.put_signed(2, -42);
+        tvf.put_byte(3, 7);
+        tvf.put_float(4, 0.5);
+        tvf.put_date(5, NaiveDate::from_ymd_opt(2024, 6, 5).unwrap());
+        tvf.put_datetime(
+            6,
+            NaiveDate::from_ymd_opt(2024, 6, 5)
+                .unwrap()
+                .and_hms_opt(15, 2, 0)
+                .unwrap(),
+        );
+        tvf.put_buffer(7, card);
+        tvf.put_buffer(8, tags);
+        tvf.put_string(9, "not in the dictionary");
+
+        let json = serde_json::to_string(&DictSerializer::new(&dictionary, &tvf)).unwrap();
+        assert_eq!(
+            r#"{"amount":42,"balance":-42,"kind":7,"rate":0.5,"date":"2024-06-05","datetime":"2024-06-05T15:02:00","card":{"pan":"4111","cvv_data":"a1b2"},"tags":["a","b"]}"#,
+            json
+        );
+
+        let read: SimpleStringTvf = DictDeserializer::new(&dictionary)
+            .deserialize(&mut serde_json::Deserializer::from_str(&json))
+            .unwrap();
+        tvf.remove(9);
+        assert_eq!(
+            serde_json::to_value(DictSerializer::new(&dictionary, &tvf)).unwrap(),
+            serde_json::to_value(DictSerializer::new(&dictionary, &read)).unwrap()
+        );
+    }
+}
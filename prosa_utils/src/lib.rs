@@ -9,5 +9,18 @@
 #![warn(missing_docs)]
 pub mod msg;
 
+// Re-exported for the macros, so callers don't need their own tracing dependency
+#[doc(hidden)]
+pub use tracing;
+
+#[cfg(feature = "dict")]
+pub mod dict;
+
+#[cfg(feature = "queue")]
+pub mod queue;
+
+#[cfg(feature = "time")]
+pub mod time;
+
 #[cfg(feature = "config")]
 pub mod config;
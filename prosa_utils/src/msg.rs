@@ -1,4 +1,339 @@
 //! Module for ProSA internal messaging object
 
+use std::fmt::Debug;
+
+use tvf::{Tvf, TvfError, TvfType};
+
+pub mod codec;
+pub mod diff;
+pub mod mask;
 pub mod simple_string_tvf;
 pub mod tvf;
+
+/// Method to convert a TVF into an other TVF implementation.
+///
+/// All fields are copied with their type, sub buffers are converted recursively.
+/// If the destination TVF can't represent a value, a [`TvfError::LossyConversion`] error is returned with the tag path of the field (ex: `3.1`).
+///
+/// ```
+/// use prosa_utils::msg::convert;
+/// use prosa_utils::msg::tvf::Tvf;
+/// use prosa_utils::msg::simple_string_tvf::SimpleStringTvf;
+///
+/// let mut tvf = SimpleStringTvf::default();
+/// tvf.put_string(1, "ProSA");
+///
+/// let converted: SimpleStringTvf = convert(&tvf).unwrap();
+/// assert_eq!(tvf, converted);
+/// ```
+pub fn convert<Src, Dst>(src: &Src) -> Result<Dst, TvfError>
+where
+    Src: Tvf + Default + Debug + Clone,
+    Dst: Tvf + Default + Debug + Clone,
+{
+    convert_buffer("", src)
+}
+
+fn convert_buffer<Src, Dst>(parent_path: &str, src: &Src) -> Result<Dst, TvfError>
+where
+    Src: Tvf + Default + Debug + Clone,
+    Dst: Tvf + Default + Debug + Clone,
+{
+    let mut dst = Dst::default();
+    let mut keys = src.keys();
+    keys.sort_unstable();
+
+    for id in keys {
+        let path = if parent_path.is_empty() {
+            id.to_string()
+        } else {
+            format!("{}.{}", parent_path, id)
+        };
+
+        /// Macro to copy a value and check that the destination can read it back
+        macro_rules! convert_value {
+            ( $get:ident, $put:ident, $tvf_type:expr ) => {{
+                let value = src.$get(id)?;
+                dst.$put(id, value.clone());
+                if dst.$get(id).ok().as_ref() != Some(&value) {
+                    return Err(TvfError::LossyConversion(path, $tvf_type));
+                }
+            }};
+        }
+
+        match src.get_type(id)? {
+            TvfType::Buffer => {
+                let buffer = convert_buffer(&path, src.get_buffer(id)?.as_ref())?;
+                dst.put_buffer(id, buffer);
+            }
+            TvfType::Unsigned => convert_value!(get_unsigned, put_unsigned, TvfType::Unsigned),
+            TvfType::Signed => convert_value!(get_signed, put_signed, TvfType::Signed),
+            TvfType::Byte => convert_value!(get_byte, put_byte, TvfType::Byte),
+            TvfType::Float => {
+                let value = src.get_float(id)?;
+                dst.put_float(id, value);
+                if !dst
+                    .get_float(id)
+                    .is_ok_and(|v| v == value || (v.is_nan() && value.is_nan()))
+                {
+                    return Err(TvfError::LossyConversion(path, TvfType::Float));
+                }
+            }
+            TvfType::String => {
+                let value = src.get_string(id)?.into_owned();
+                dst.put_string(id, value.clone());
+                if dst.get_string(id).ok().as_deref() != Some(&value) {
+                    return Err(TvfError::LossyConversion(path, TvfType::String));
+                }
+            }
+            TvfType::Bytes => {
+                let value = src.get_bytes(id)?.into_owned();
+                dst.put_bytes(id, value.clone());
+                if dst.get_bytes(id).ok().as_deref() != Some(&value) {
+                    return Err(TvfError::LossyConversion(path, TvfType::Bytes));
+                }
+            }
+            TvfType::Date => convert_value!(get_date, put_date, TvfType::Date),
+            TvfType::DateTime => convert_value!(get_datetime, put_datetime, TvfType::DateTime),
+        }
+    }
+
+    Ok(dst)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{borrow::Cow, collections::HashMap};
+
+    use bytes::Bytes;
+    use chrono::{NaiveDate, NaiveDateTime};
+
+    use super::*;
+    use simple_string_tvf::SimpleStringTvf;
+
+    /// Value of a typed TVF field
+    #[derive(Debug, Clone, PartialEq)]
+    enum TypedValue {
+        Buffer(TypedTvf),
+        Unsigned(u64),
+        Signed(i64),
+        Byte(u8),
+        Float(f64),
+        String(String),
+        Bytes(Bytes),
+        Date(NaiveDate),
+        DateTime(NaiveDateTime),
+    }
+
+    /// TVF that keep the type of its fields
+    #[derive(Debug, Default, Clone, PartialEq)]
+    struct TypedTvf {
+        fields: HashMap<usize, TypedValue>,
+    }
+
+    /// Macro to get a typed value from the TypedTvf
+    macro_rules! typed_get {
+        ( $self:ident, $id:ident, $variant:ident ) => {
+            match $self.fields.get(&$id) {
+                Some(TypedValue::$variant(value)) => Ok(value.clone()),
+                Some(_) => Err(TvfError::TypeMismatch),
+                None => Err(TvfError::FieldNotFound($id)),
+            }
+        };
+    }
+
+    impl Tvf for TypedTvf {
+        fn is_empty(&self) -> bool {
+            self.fields.is_empty()
+        }
+
+        fn len(&self) -> usize {
+            self.fields.len()
+        }
+
+        fn contains(&self, id: usize) -> bool {
+            self.fields.contains_key(&id)
+        }
+
+        fn remove(&mut self, id: usize) {
+            self.fields.remove(&id);
+        }
+
+        fn into_keys(self) -> Vec<usize> {
+            self.fields.into_keys().collect()
+        }
+
+        fn keys(&self) -> Vec<usize> {
+            self.fields.keys().cloned().collect()
+        }
+
+        fn get_type(&self, id: usize) -> Result<TvfType, TvfError> {
+            match self.fields.get(&id) {
+                Some(TypedValue::Buffer(_)) => Ok(TvfType::Buffer),
+                Some(TypedValue::Unsigned(_)) => Ok(TvfType::Unsigned),
+                Some(TypedValue::Signed(_)) => Ok(TvfType::Signed),
+                Some(TypedValue::Byte(_)) => Ok(TvfType::Byte),
+                Some(TypedValue::Float(_)) => Ok(TvfType::Float),
+                Some(TypedValue::String(_)) => Ok(TvfType::String),
+                Some(TypedValue::Bytes(_)) => Ok(TvfType::Bytes),
+                Some(TypedValue::Date(_)) => Ok(TvfType::Date),
+                Some(TypedValue::DateTime(_)) => Ok(TvfType::DateTime),
+                None => Err(TvfError::FieldNotFound(id)),
+            }
+        }
+
+        fn get_buffer(&self, id: usize) -> Result<Cow<'_, Self>, TvfError> {
+            typed_get!(self, id, Buffer).map(Cow::Owned)
+        }
+
+        fn get_unsigned(&self, id: usize) -> Result<u64, TvfError> {
+            typed_get!(self, id, Unsigned)
+        }
+
+        fn get_signed(&self, id: usize) -> Result<i64, TvfError> {
+            typed_get!(self, id, Signed)
+        }
+
+        fn get_byte(&self, id: usize) -> Result<u8, TvfError> {
+            typed_get!(self, id, Byte)
+        }
+
+        fn get_float(&self, id: usize) -> Result<f64, TvfError> {
+            typed_get!(self, id, Float)
+        }
+
+        fn get_string(&self, id: usize) -> Result<Cow<'_, String>, TvfError> {
+            typed_get!(self, id, String).map(Cow::Owned)
+        }
+
+        fn get_bytes(&self, id: usize) -> Result<Cow<'_, Bytes>, TvfError> {
+            typed_get!(self, id, Bytes).map(Cow::Owned)
+        }
+
+        fn get_date(&self, id: usize) -> Result<NaiveDate, TvfError> {
+            typed_get!(self, id, Date)
+        }
+
+        fn get_datetime(&self, id: usize) -> Result<NaiveDateTime, TvfError> {
+            typed_get!(self, id, DateTime)
+        }
+
+        fn put_buffer(&mut self, id: usize, buffer: Self) {
+            self.fields.insert(id, TypedValue::Buffer(buffer));
+        }
+
+        fn put_unsigned(&mut self, id: usize, unsigned: u64) {
+            self.fields.insert(id, TypedValue::Unsigned(unsigned));
+        }
+
+        fn put_signed(&mut self, id: usize, signed: i64) {
+            self.fields.insert(id, TypedValue::Signed(signed));
+        }
+
+        fn put_byte(&mut self, id: usize, byte: u8) {
+            self.fields.insert(id, TypedValue::Byte(byte));
+        }
+
+        fn put_float(&mut self, id: usize, float: f64) {
+            self.fields.insert(id, TypedValue::Float(float));
+        }
+
+        fn put_string<T: Into<String>>(&mut self, id: usize, string: T) {
+            self.fields.insert(id, TypedValue::String(string.into()));
+        }
+
+        fn put_bytes(&mut self, id: usize, buffer: Bytes) {
+            self.fields.insert(id, TypedValue::Bytes(buffer));
+        }
+
+        fn put_date(&mut self, id: usize, date: NaiveDate) {
+            self.fields.insert(id, TypedValue::Date(date));
+        }
+
+        fn put_datetime(&mut self, id: usize, datetime: NaiveDateTime) {
+            self.fields.insert(id, TypedValue::DateTime(datetime));
+        }
+    }
+
+    fn test_datetime(nanos: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2024, 6, 5)
+            .unwrap()
+            .and_hms_nano_opt(15, 2, 0, nanos)
+            .unwrap()
+    }
+
+    #[test]
+    fn convert_simple_string_round_trip() {
+        let mut sub_tvf = SimpleStringTvf::default();
+        sub_tvf.put_string(1, "sub");
+        sub_tvf.put_unsigned(2, 42);
+
+        let mut tvf = SimpleStringTvf::default();
+        tvf.put_string(1, "ProSA");
+        tvf.put_float(2, 6.56);
+        tvf.put_bytes(3, Bytes::from_static(b"\xaa\xbb"));
+        tvf.put_buffer(4, sub_tvf);
+
+        let typed_tvf: TypedTvf = convert(&tvf).unwrap();
+        assert_eq!(Ok(TvfType::String), typed_tvf.get_type(1));
+        assert_eq!(Ok(TvfType::Buffer), typed_tvf.get_type(4));
+        assert_eq!(
+            "42",
+            typed_tvf
+                .get_buffer(4)
+                .unwrap()
+                .get_string(2)
+                .unwrap()
+                .as_str()
+        );
+
+        // Serialized sub buffers are not ordered, so compare them field by field
+        let simple_tvf: SimpleStringTvf = convert(&typed_tvf).unwrap();
+        assert!(diff::tvf_diff(&tvf, &simple_tvf).is_empty());
+    }
+
+    #[test]
+    fn convert_typed_round_trip() {
+        let mut sub_tvf = TypedTvf::default();
+        sub_tvf.put_signed(1, -1);
+        sub_tvf.put_date(2, NaiveDate::from_ymd_opt(2024, 6, 5).unwrap());
+
+        let mut tvf = TypedTvf::default();
+        tvf.put_unsigned(1, 42);
+        tvf.put_byte(2, 32);
+        tvf.put_float(3, 154.5);
+        tvf.put_string(4, "Hello world!");
+        tvf.put_datetime(5, test_datetime(0));
+        tvf.put_buffer(6, sub_tvf);
+
+        let simple_tvf: SimpleStringTvf = convert(&tvf).unwrap();
+        assert_eq!(42, simple_tvf.get_unsigned(1).unwrap());
+        assert_eq!(32, simple_tvf.get_byte(2).unwrap());
+        assert_eq!(154.5, simple_tvf.get_float(3).unwrap());
+        assert_eq!(test_datetime(0), simple_tvf.get_datetime(5).unwrap());
+        assert_eq!(-1, simple_tvf.get_buffer(6).unwrap().get_signed(1).unwrap());
+
+        let typed_tvf: TypedTvf = convert(&tvf).unwrap();
+        assert_eq!(tvf, typed_tvf);
+    }
+
+    #[test]
+    fn convert_lossy() {
+        // SimpleStringTvf datetimes don't keep sub seconds
+        let mut sub_tvf = TypedTvf::default();
+        sub_tvf.put_datetime(1, test_datetime(500_000_000));
+        let mut tvf = TypedTvf::default();
+        tvf.put_string(1, "ProSA");
+        tvf.put_buffer(3, sub_tvf);
+
+        let err = convert::<TypedTvf, SimpleStringTvf>(&tvf).unwrap_err();
+        assert_eq!(
+            TvfError::LossyConversion(String::from("3.1"), TvfType::DateTime),
+            err
+        );
+        assert_eq!(
+            "The field `3.1` can't be converted without loss of datetime",
+            err.to_string()
+        );
+    }
+}
@@ -0,0 +1,531 @@
+//! Compression of TVF messages
+//!
+//! Large messages crossing a bridge or stored in a journal can be compressed with a [`Codec`].
+//! Every compressed payload start with a small header (codec id on 1 byte, uncompressed size on 4 bytes big endian) so it's self described:
+//! the decoder doesn't need to know the codec, and check the declared size against a maximum before allocating anything (to prevent zip bombs).
+//!
+//! | Codec  | Id | Feature    |
+//! |--------|----|------------|
+//! | `none` | 0  |            |
+//! | `zstd` | 1  | `msg-zstd` |
+//! | `lz4`  | 2  | `msg-lz4`  |
+//!
+//! ```
+//! use prosa_utils::msg::codec::{self, Codec};
+//! use prosa_utils::msg::simple_string_tvf::SimpleStringTvf;
+//! use prosa_utils::msg::tvf::Tvf;
+//!
+//! let mut msg = SimpleStringTvf::default();
+//! msg.put_string(1, "ProSA");
+//!
+//! let data = codec::encode(&msg, Codec::None).unwrap();
+//! assert_eq!(msg, codec::decode::<SimpleStringTvf>(&data, 1024).unwrap());
+//! ```
+
+use std::fmt::{self, Debug};
+
+use bytes::{Buf as _, BufMut as _, Bytes, BytesMut};
+use chrono::{DateTime, Datelike as _, NaiveDate};
+use thiserror::Error;
+
+use super::tvf::{Tvf, TvfError, TvfType};
+
+/// Size of the header of a compressed payload: codec id (u8) and uncompressed size (u32)
+pub const HEADER_LEN: usize = 5;
+
+/// Error define for the message codecs
+#[derive(Debug, Error, PartialEq)]
+pub enum CodecError {
+    /// Error on a TVF message
+    #[error("Codec TVF error: {0}")]
+    Tvf(#[from] TvfError),
+    /// Error when the codec of a payload is unknown or not compiled in
+    #[error("Codec `{0}` is not supported")]
+    Unsupported(u8),
+    /// Error when the declared uncompressed size exceed the maximum size
+    #[error("Codec uncompressed size of {0} bytes exceed the maximum of {1} bytes")]
+    TooLarge(usize, usize),
+    /// Error when a payload can't be compressed or decompressed
+    #[error("Codec corrupted payload: {0}")]
+    Corrupted(String),
+}
+
+/// Compression codec of the messages
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "config",
+    derive(serde::Deserialize, serde::Serialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum Codec {
+    /// No compression, the payload is only prefixed by the header
+    #[default]
+    None,
+    /// [Zstandard](https://facebook.github.io/zstd/) compression
+    #[cfg(feature = "msg-zstd")]
+    Zstd,
+    /// [LZ4](https://lz4.org/) block compression
+    #[cfg(feature = "msg-lz4")]
+    Lz4,
+}
+
+impl Codec {
+    const NONE_ID: u8 = 0;
+    #[cfg(feature = "msg-zstd")]
+    const ZSTD_ID: u8 = 1;
+    #[cfg(feature = "msg-lz4")]
+    const LZ4_ID: u8 = 2;
+
+    /// Getter of the codec id written in the header
+    pub fn id(&self) -> u8 {
+        match self {
+            Codec::None => Self::NONE_ID,
+            #[cfg(feature = "msg-zstd")]
+            Codec::Zstd => Self::ZSTD_ID,
+            #[cfg(feature = "msg-lz4")]
+            Codec::Lz4 => Self::LZ4_ID,
+        }
+    }
+
+    /// Method to get the codec from its id
+    pub fn from_id(id: u8) -> Result<Codec, CodecError> {
+        match id {
+            Self::NONE_ID => Ok(Codec::None),
+            #[cfg(feature = "msg-zstd")]
+            Self::ZSTD_ID => Ok(Codec::Zstd),
+            #[cfg(feature = "msg-lz4")]
+            Self::LZ4_ID => Ok(Codec::Lz4),
+            id => Err(CodecError::Unsupported(id)),
+        }
+    }
+}
+
+impl fmt::Display for Codec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Codec::None => write!(f, "none"),
+            #[cfg(feature = "msg-zstd")]
+            Codec::Zstd => write!(f, "zstd"),
+            #[cfg(feature = "msg-lz4")]
+            Codec::Lz4 => write!(f, "lz4"),
+        }
+    }
+}
+
+/// Method to compress data with a codec, the compressed payload is prefixed by its header
+pub fn compress(data: &[u8], codec: Codec) -> Result<Bytes, CodecError> {
+    let size = u32::try_from(data.len()).map_err(|_| {
+        CodecError::Corrupted(format!("the data of {} bytes is too large", data.len()))
+    })?;
+
+    let mut dst = BytesMut::with_capacity(HEADER_LEN + data.len());
+    dst.put_u8(codec.id());
+    dst.put_u32(size);
+    match codec {
+        Codec::None => dst.put_slice(data),
+        #[cfg(feature = "msg-zstd")]
+        Codec::Zstd => dst.put_slice(
+            &zstd::bulk::compress(data, zstd::DEFAULT_COMPRESSION_LEVEL)
+                .map_err(|e| CodecError::Corrupted(e.to_string()))?,
+        ),
+        #[cfg(feature = "msg-lz4")]
+        Codec::Lz4 => dst.put_slice(&lz4_flex::block::compress(data)),
+    }
+
+    Ok(dst.freeze())
+}
+
+/// Method to decompress a payload prefixed by its header.
+/// The declared uncompressed size is checked against `max_size` before decompressing
+pub fn decompress(mut payload: &[u8], max_size: usize) -> Result<Bytes, CodecError> {
+    if payload.len() < HEADER_LEN {
+        return Err(CodecError::Corrupted(format!(
+            "truncated header of {} bytes",
+            payload.len()
+        )));
+    }
+
+    let codec = Codec::from_id(payload.get_u8())?;
+    let size = payload.get_u32() as usize;
+    if size > max_size {
+        return Err(CodecError::TooLarge(size, max_size));
+    }
+
+    let data = match codec {
+        Codec::None => Bytes::copy_from_slice(payload),
+        #[cfg(feature = "msg-zstd")]
+        Codec::Zstd => Bytes::from(
+            zstd::bulk::decompress(payload, size)
+                .map_err(|e| CodecError::Corrupted(e.to_string()))?,
+        ),
+        #[cfg(feature = "msg-lz4")]
+        Codec::Lz4 => Bytes::from(
+            lz4_flex::block::decompress(payload, size)
+                .map_err(|e| CodecError::Corrupted(e.to_string()))?,
+        ),
+    };
+
+    if data.len() != size {
+        return Err(CodecError::Corrupted(format!(
+            "{} bytes decompressed instead of {}",
+            data.len(),
+            size
+        )));
+    }
+
+    Ok(data)
+}
+
+/// Method to encode a TVF message with its field types, and compress it with a codec
+pub fn encode<T>(msg: &T, codec: Codec) -> Result<Bytes, CodecError>
+where
+    T: Tvf + Default + Debug + Clone,
+{
+    let mut data = BytesMut::new();
+    write_tvf(msg, &mut data)?;
+    compress(&data, codec)
+}
+
+/// Method to decompress and decode a TVF message encoded by [`encode`]
+pub fn decode<T>(payload: &[u8], max_size: usize) -> Result<T, CodecError>
+where
+    T: Tvf + Default + Debug + Clone,
+{
+    read_tvf(&decompress(payload, max_size)?)
+}
+
+const BUFFER_TYPE: u8 = 1;
+const UNSIGNED_TYPE: u8 = 2;
+const SIGNED_TYPE: u8 = 3;
+const BYTE_TYPE: u8 = 4;
+const FLOAT_TYPE: u8 = 5;
+const STRING_TYPE: u8 = 6;
+const BYTES_TYPE: u8 = 7;
+const DATE_TYPE: u8 = 8;
+const DATETIME_TYPE: u8 = 9;
+
+/// Method to write a TVF as a list of fields: tag (u32), type (u8), and value (sized values are prefixed by their length on a u32)
+fn write_tvf<T>(msg: &T, dst: &mut BytesMut) -> Result<(), CodecError>
+where
+    T: Tvf + Default + Debug + Clone,
+{
+    let mut keys = msg.keys();
+    keys.sort_unstable();
+    for key in keys {
+        let tag = u32::try_from(key)
+            .map_err(|_| CodecError::Corrupted(format!("the tag {} can't be encoded", key)))?;
+        dst.put_u32(tag);
+        match msg.get_type(key)? {
+            TvfType::Buffer => {
+                let mut buffer = BytesMut::new();
+                write_tvf(msg.get_buffer(key)?.as_ref(), &mut buffer)?;
+                dst.put_u8(BUFFER_TYPE);
+                put_sized(dst, &buffer)?;
+            }
+            TvfType::Unsigned => {
+                dst.put_u8(UNSIGNED_TYPE);
+                dst.put_u64(msg.get_unsigned(key)?);
+            }
+            TvfType::Signed => {
+                dst.put_u8(SIGNED_TYPE);
+                dst.put_i64(msg.get_signed(key)?);
+            }
+            TvfType::Byte => {
+                dst.put_u8(BYTE_TYPE);
+                dst.put_u8(msg.get_byte(key)?);
+            }
+            TvfType::Float => {
+                dst.put_u8(FLOAT_TYPE);
+                dst.put_f64(msg.get_float(key)?);
+            }
+            TvfType::String => {
+                dst.put_u8(STRING_TYPE);
+                put_sized(dst, msg.get_string(key)?.as_bytes())?;
+            }
+            TvfType::Bytes => {
+                dst.put_u8(BYTES_TYPE);
+                put_sized(dst, msg.get_bytes(key)?.as_ref())?;
+            }
+            TvfType::Date => {
+                dst.put_u8(DATE_TYPE);
+                dst.put_i32(msg.get_date(key)?.num_days_from_ce());
+            }
+            TvfType::DateTime => {
+                dst.put_u8(DATETIME_TYPE);
+                dst.put_i64(msg.get_datetime(key)?.and_utc().timestamp_micros());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn put_sized(dst: &mut BytesMut, value: &[u8]) -> Result<(), CodecError> {
+    let len = u32::try_from(value.len()).map_err(|_| {
+        CodecError::Corrupted(format!("the value of {} bytes is too large", value.len()))
+    })?;
+    dst.put_u32(len);
+    dst.put_slice(value);
+    Ok(())
+}
+
+/// Method to check that enough data remain to read a value
+fn ensure_remaining(data: &[u8], len: usize) -> Result<(), CodecError> {
+    if data.len() < len {
+        Err(CodecError::Corrupted(format!(
+            "truncated message, {} bytes missing",
+            len - data.len()
+        )))
+    } else {
+        Ok(())
+    }
+}
+
+/// Method to get a value prefixed by its length on a u32
+fn get_sized<'a>(data: &mut &'a [u8]) -> Result<&'a [u8], CodecError> {
+    ensure_remaining(data, 4)?;
+    let len = data.get_u32() as usize;
+    ensure_remaining(data, len)?;
+    let (value, remaining) = data.split_at(len);
+    *data = remaining;
+    Ok(value)
+}
+
+/// Method to read a TVF written by [`write_tvf`]
+fn read_tvf<T>(mut data: &[u8]) -> Result<T, CodecError>
+where
+    T: Tvf + Default + Debug + Clone,
+{
+    let mut msg = T::default();
+    while !data.is_empty() {
+        ensure_remaining(data, 5)?;
+        let key = data.get_u32() as usize;
+        match data.get_u8() {
+            BUFFER_TYPE => {
+                let buffer = get_sized(&mut data)?;
+                msg.put_buffer(key, read_tvf(buffer)?);
+            }
+            UNSIGNED_TYPE => {
+                ensure_remaining(data, 8)?;
+                msg.put_unsigned(key, data.get_u64());
+            }
+            SIGNED_TYPE => {
+                ensure_remaining(data, 8)?;
+                msg.put_signed(key, data.get_i64());
+            }
+            BYTE_TYPE => {
+                ensure_remaining(data, 1)?;
+                msg.put_byte(key, data.get_u8());
+            }
+            FLOAT_TYPE => {
+                ensure_remaining(data, 8)?;
+                msg.put_float(key, data.get_f64());
+            }
+            STRING_TYPE => {
+                let string = std::str::from_utf8(get_sized(&mut data)?).map_err(|e| {
+                    CodecError::Corrupted(format!("invalid string on tag {}: {}", key, e))
+                })?;
+                msg.put_string(key, string);
+            }
+            BYTES_TYPE => {
+                let bytes = get_sized(&mut data)?;
+                msg.put_bytes(key, Bytes::copy_from_slice(bytes));
+            }
+            DATE_TYPE => {
+                ensure_remaining(data, 4)?;
+                let date = NaiveDate::from_num_days_from_ce_opt(data.get_i32())
+                    .ok_or_else(|| CodecError::Corrupted(format!("invalid date on tag {}", key)))?;
+                msg.put_date(key, date);
+            }
+            DATETIME_TYPE => {
+                ensure_remaining(data, 8)?;
+                let datetime = DateTime::from_timestamp_micros(data.get_i64())
+                    .ok_or_else(|| {
+                        CodecError::Corrupted(format!("invalid datetime on tag {}", key))
+                    })?
+                    .naive_utc();
+                msg.put_datetime(key, datetime);
+            }
+            field_type => {
+                return Err(CodecError::Corrupted(format!(
+                    "unknown type {} on tag {}",
+                    field_type, key
+                )))
+            }
+        }
+    }
+
+    Ok(msg)
+}
+
+/// Compression settings of the payloads, with a threshold below which they're not compressed
+///
+/// ```yaml
+/// compression:
+///   codec: zstd
+///   threshold: 1024
+///   max_size: 16777216
+/// ```
+///
+/// ```
+/// use prosa_utils::msg::codec::{Codec, Compression};
+///
+/// let compression = Compression::new(Codec::None, 16);
+/// let payload = compression.compress(b"small").unwrap();
+/// assert_eq!(b"small".as_slice(), &compression.decompress(&payload).unwrap()[..]);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "config", derive(serde::Deserialize, serde::Serialize))]
+pub struct Compression {
+    /// Codec used to compress the payloads
+    #[cfg_attr(feature = "config", serde(default))]
+    pub codec: Codec,
+    /// Payloads smaller than this size (in bytes) are not compressed
+    #[cfg_attr(feature = "config", serde(default = "Compression::default_threshold"))]
+    pub threshold: usize,
+    /// Maximum uncompressed size (in bytes) accepted when decompressing
+    #[cfg_attr(feature = "config", serde(default = "Compression::default_max_size"))]
+    pub max_size: usize,
+}
+
+impl Compression {
+    fn default_threshold() -> usize {
+        1024
+    }
+
+    fn default_max_size() -> usize {
+        16 * 1024 * 1024
+    }
+
+    /// Method to create compression settings with a codec and a threshold
+    pub fn new(codec: Codec, threshold: usize) -> Compression {
+        Compression {
+            codec,
+            threshold,
+            max_size: Self::default_max_size(),
+        }
+    }
+
+    /// Method to compress a payload with the codec, or without compression if it's smaller than the threshold
+    pub fn compress(&self, data: &[u8]) -> Result<Bytes, CodecError> {
+        if data.len() < self.threshold {
+            compress(data, Codec::None)
+        } else {
+            compress(data, self.codec)
+        }
+    }
+
+    /// Method to decompress a payload, within the maximum size
+    pub fn decompress(&self, payload: &[u8]) -> Result<Bytes, CodecError> {
+        decompress(payload, self.max_size)
+    }
+}
+
+impl Default for Compression {
+    fn default() -> Self {
+        Compression::new(Codec::default(), Self::default_threshold())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDateTime;
+
+    use super::*;
+    use crate::msg::simple_string_tvf::SimpleStringTvf;
+
+    fn codecs() -> Vec<Codec> {
+        vec![
+            Codec::None,
+            #[cfg(feature = "msg-zstd")]
+            Codec::Zstd,
+            #[cfg(feature = "msg-lz4")]
+            Codec::Lz4,
+        ]
+    }
+
+    fn large_msg() -> SimpleStringTvf {
+        let mut sub = SimpleStringTvf::default();
+        sub.put_string(1, "sub");
+        let mut msg = SimpleStringTvf::default();
+        msg.put_buffer(1, sub);
+        msg.put_unsigned(2, 42);
+        msg.put_bytes(3, Bytes::from(vec![0xab; 4096]));
+        msg.put_date(4, NaiveDate::from_ymd_opt(2024, 2, 29).unwrap());
+        msg.put_datetime(
+            5,
+            NaiveDateTime::parse_from_str("2024-02-29 12:34:56", "%Y-%m-%d %H:%M:%S").unwrap(),
+        );
+        msg.put_string(6, "ProSA ".repeat(512));
+        msg
+    }
+
+    #[test]
+    fn codec_round_trip() {
+        let msg = large_msg();
+        for codec in codecs() {
+            let payload = encode(&msg, codec).unwrap();
+            assert_eq!(codec.id(), payload[0], "{}", codec);
+            assert_eq!(Ok(codec), Codec::from_id(payload[0]));
+            assert_eq!(Ok(msg.clone()), decode(&payload, 1 << 20), "{}", codec);
+            if codec != Codec::None {
+                assert!(
+                    payload.len() < 4096,
+                    "{} payload of {}",
+                    codec,
+                    payload.len()
+                );
+            }
+
+            // Truncated payloads are rejected
+            assert!(decode::<SimpleStringTvf>(&payload[..payload.len() - 1], 1 << 20).is_err());
+        }
+
+        assert_eq!(
+            Err(CodecError::Unsupported(42)),
+            decompress(&[42, 0, 0, 0, 0], 1024)
+        );
+    }
+
+    #[test]
+    fn codec_threshold() {
+        let data = vec![0u8; 2048];
+        for codec in codecs() {
+            let compression = Compression::new(codec, 1024);
+            let small = compression.compress(&data[..512]).unwrap();
+            assert_eq!(Codec::None.id(), small[0]);
+            assert_eq!(HEADER_LEN + 512, small.len());
+            assert_eq!(&data[..512], &compression.decompress(&small).unwrap()[..]);
+
+            let large = compression.compress(&data).unwrap();
+            assert_eq!(codec.id(), large[0]);
+            assert_eq!(&data[..], &compression.decompress(&large).unwrap()[..]);
+        }
+    }
+
+    #[test]
+    fn codec_max_size() {
+        let data = vec![0u8; 4096];
+        for codec in codecs() {
+            let payload = compress(&data, codec).unwrap();
+            assert_eq!(
+                Err(CodecError::TooLarge(4096, 1024)),
+                decompress(&payload, 1024)
+            );
+            assert_eq!(4096, decompress(&payload, 4096).unwrap().len());
+
+            // A declared size that lie about the content is rejected
+            let mut forged = BytesMut::from(&payload[..]);
+            forged[1..HEADER_LEN].copy_from_slice(&(u32::MAX).to_be_bytes());
+            assert_eq!(
+                Err(CodecError::TooLarge(u32::MAX as usize, 1 << 20)),
+                decompress(&forged, 1 << 20)
+            );
+            forged[1..HEADER_LEN].copy_from_slice(&2048u32.to_be_bytes());
+            assert!(matches!(
+                decompress(&forged, 1 << 20),
+                Err(CodecError::Corrupted(_))
+            ));
+        }
+    }
+}
@@ -0,0 +1,370 @@
+//! Module to compare TVF[^tvfnote] messages field by field
+//!
+//! Useful to write readable assertions in processor tests.
+//!
+//! [^tvfnote]: **T**ag **V**alue **F**ormat
+
+use std::{collections::HashSet, fmt};
+
+use bytes::Bytes;
+
+use super::tvf::Tvf;
+
+/// Kind of difference between two TVF fields
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TvfDifferenceKind {
+    /// The field is expected but not present
+    Missing,
+    /// The field is present but not expected
+    Unexpected,
+    /// The field is present on both sides but with a different type (buffer, string or bytes)
+    TypeMismatch,
+    /// The field is present on both sides with a different value
+    ValueMismatch,
+}
+
+impl fmt::Display for TvfDifferenceKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TvfDifferenceKind::Missing => write!(f, "missing"),
+            TvfDifferenceKind::Unexpected => write!(f, "unexpected"),
+            TvfDifferenceKind::TypeMismatch => write!(f, "type mismatch"),
+            TvfDifferenceKind::ValueMismatch => write!(f, "value mismatch"),
+        }
+    }
+}
+
+/// Difference of a field between an expected and an actual TVF
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TvfDifference {
+    /// Tag path of the field (tags of nested buffers separated by `.`, ex: `3.1`)
+    pub path: String,
+    /// Kind of the difference
+    pub kind: TvfDifferenceKind,
+    /// Expected value rendered as string (if present)
+    pub expected: Option<String>,
+    /// Actual value rendered as string (if present)
+    pub actual: Option<String>,
+}
+
+impl fmt::Display for TvfDifference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}] {}", self.path, self.kind)?;
+        if let Some(expected) = &self.expected {
+            write!(f, "\n    expected: {}", expected)?;
+        }
+        if let Some(actual) = &self.actual {
+            write!(f, "\n    actual:   {}", actual)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Value of a TVF field, as compared by the diff
+enum FieldValue<T> {
+    Buffer(T),
+    String(String),
+    Bytes(Bytes),
+}
+
+impl<T> FieldValue<T>
+where
+    T: Tvf + Default + fmt::Debug + Clone,
+{
+    fn get(tvf: &T, id: usize) -> Option<FieldValue<T>> {
+        if let Some(buffer) = tvf.get_buffer(id).ok().filter(|b| !b.is_empty()) {
+            Some(FieldValue::Buffer(buffer.into_owned()))
+        } else if let Ok(string) = tvf.get_string(id) {
+            Some(FieldValue::String(string.into_owned()))
+        } else {
+            tvf.get_bytes(id)
+                .ok()
+                .map(|bytes| FieldValue::Bytes(bytes.into_owned()))
+        }
+    }
+}
+
+impl<T> fmt::Display for FieldValue<T>
+where
+    T: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FieldValue::Buffer(buffer) => write!(f, "{:?}", buffer),
+            FieldValue::String(string) => write!(f, "{:?}", string),
+            FieldValue::Bytes(bytes) => write!(f, "0x{}", hex::encode(bytes)),
+        }
+    }
+}
+
+/// TVF comparator, with a set of ignored tag paths
+///
+/// Nested buffers are compared recursively. Lists (buffers indexed by position) are compared by index.
+///
+/// ```
+/// use prosa_utils::msg::tvf::Tvf;
+/// use prosa_utils::msg::simple_string_tvf::SimpleStringTvf;
+/// use prosa_utils::msg::diff::{TvfDiff, TvfDifferenceKind};
+///
+/// let mut expected = SimpleStringTvf::default();
+/// expected.put_string(1, "ProSA");
+/// expected.put_string(2, "2024-01-01T00:00:00");
+///
+/// let mut actual = SimpleStringTvf::default();
+/// actual.put_string(1, "Worldline");
+/// actual.put_string(2, "2024-06-01T12:00:00");
+///
+/// let differences = TvfDiff::default().ignore("2").diff(&expected, &actual);
+/// assert_eq!(1, differences.len());
+/// assert_eq!("1", differences[0].path);
+/// assert_eq!(TvfDifferenceKind::ValueMismatch, differences[0].kind);
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct TvfDiff {
+    ignored_paths: HashSet<String>,
+}
+
+impl TvfDiff {
+    /// Method to ignore a tag path (and all its sub fields) from the comparison
+    pub fn ignore<P>(mut self, path: P) -> Self
+    where
+        P: Into<String>,
+    {
+        self.ignored_paths.insert(path.into());
+        self
+    }
+
+    /// Method to compare an expected TVF with an actual one
+    pub fn diff<T>(&self, expected: &T, actual: &T) -> Vec<TvfDifference>
+    where
+        T: Tvf + Default + fmt::Debug + Clone,
+    {
+        let mut differences = Vec::new();
+        self.diff_buffer("", expected, actual, &mut differences);
+        differences
+    }
+
+    fn diff_buffer<T>(
+        &self,
+        parent_path: &str,
+        expected: &T,
+        actual: &T,
+        differences: &mut Vec<TvfDifference>,
+    ) where
+        T: Tvf + Default + fmt::Debug + Clone,
+    {
+        let mut keys = expected.keys();
+        keys.extend(actual.keys().into_iter().filter(|k| !expected.contains(*k)));
+        keys.sort_unstable();
+
+        for key in keys {
+            let path = if parent_path.is_empty() {
+                key.to_string()
+            } else {
+                format!("{}.{}", parent_path, key)
+            };
+
+            if self.ignored_paths.contains(&path) {
+                continue;
+            }
+
+            match (FieldValue::get(expected, key), FieldValue::get(actual, key)) {
+                (Some(FieldValue::Buffer(expected)), Some(FieldValue::Buffer(actual))) => {
+                    self.diff_buffer(&path, &expected, &actual, differences)
+                }
+                (Some(FieldValue::String(e)), Some(FieldValue::String(a))) if e == a => {}
+                (Some(FieldValue::Bytes(e)), Some(FieldValue::Bytes(a))) if e == a => {}
+                (Some(expected), Some(actual)) => {
+                    let kind =
+                        if std::mem::discriminant(&expected) == std::mem::discriminant(&actual) {
+                            TvfDifferenceKind::ValueMismatch
+                        } else {
+                            TvfDifferenceKind::TypeMismatch
+                        };
+                    differences.push(TvfDifference {
+                        path,
+                        kind,
+                        expected: Some(expected.to_string()),
+                        actual: Some(actual.to_string()),
+                    });
+                }
+                (expected, actual) => differences.push(TvfDifference {
+                    path,
+                    kind: if expected.is_some() {
+                        TvfDifferenceKind::Missing
+                    } else {
+                        TvfDifferenceKind::Unexpected
+                    },
+                    expected: expected.map(|v| v.to_string()),
+                    actual: actual.map(|v| v.to_string()),
+                }),
+            }
+        }
+    }
+}
+
+/// Method to compare an expected TVF with an actual one.
+/// Return all differences between them (empty if they are equal)
+pub fn tvf_diff<T>(expected: &T, actual: &T) -> Vec<TvfDifference>
+where
+    T: Tvf + Default + fmt::Debug + Clone,
+{
+    TvfDiff::default().diff(expected, actual)
+}
+
+/// Method to render a report of TVF differences
+pub fn tvf_diff_report(differences: &[TvfDifference]) -> String {
+    let mut report = format!("TVF are not equal ({} differences):", differences.len());
+    for difference in differences {
+        report.push_str(&format!("\n  {}", difference));
+    }
+
+    report
+}
+
+/// Assert that two TVF are equal, panic with a report of all differences otherwise.
+/// Tag paths to ignore can be given after the TVFs.
+///
+/// ```
+/// use prosa_utils::assert_tvf_eq;
+/// use prosa_utils::msg::tvf::Tvf;
+/// use prosa_utils::msg::simple_string_tvf::SimpleStringTvf;
+///
+/// let mut expected = SimpleStringTvf::default();
+/// expected.put_string(1, "ProSA");
+/// let mut actual = expected.clone();
+/// assert_tvf_eq!(expected, actual);
+///
+/// actual.put_string(2, "timestamp");
+/// assert_tvf_eq!(expected, actual, "2");
+/// ```
+#[macro_export]
+macro_rules! assert_tvf_eq {
+    ( $expected:expr, $actual:expr $(, $ignore:expr )* $(,)? ) => {
+        let differences = $crate::msg::diff::TvfDiff::default()
+            $( .ignore($ignore) )*
+            .diff(&$expected, &$actual);
+        if !differences.is_empty() {
+            panic!("{}", $crate::msg::diff::tvf_diff_report(&differences));
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::msg::simple_string_tvf::SimpleStringTvf;
+
+    fn test_tvf() -> SimpleStringTvf {
+        let mut sub_tvf = SimpleStringTvf::default();
+        sub_tvf.put_string(1, "first");
+        sub_tvf.put_string(2, "second");
+
+        let mut tvf = SimpleStringTvf::default();
+        tvf.put_string(1, "ProSA");
+        tvf.put_unsigned(2, 42);
+        tvf.put_buffer(3, sub_tvf);
+        tvf
+    }
+
+    #[test]
+    fn tvf_diff_equal() {
+        assert!(tvf_diff(&test_tvf(), &test_tvf()).is_empty());
+        assert_tvf_eq!(test_tvf(), test_tvf());
+    }
+
+    #[test]
+    fn tvf_diff_kinds() {
+        let expected = test_tvf();
+        let mut actual = test_tvf();
+        actual.remove(1);
+        actual.put_string(2, "43");
+        actual.put_string(4, "unexpected");
+
+        let mut sub_tvf = SimpleStringTvf::default();
+        sub_tvf.put_string(1, "first");
+        sub_tvf.put_string(2, "other");
+        sub_tvf.put_string(3, "third");
+        actual.put_buffer(3, sub_tvf);
+
+        let differences = tvf_diff(&expected, &actual);
+        assert_eq!(
+            vec![
+                TvfDifference {
+                    path: String::from("1"),
+                    kind: TvfDifferenceKind::Missing,
+                    expected: Some(String::from("\"ProSA\"")),
+                    actual: None,
+                },
+                TvfDifference {
+                    path: String::from("2"),
+                    kind: TvfDifferenceKind::ValueMismatch,
+                    expected: Some(String::from("\"42\"")),
+                    actual: Some(String::from("\"43\"")),
+                },
+                TvfDifference {
+                    path: String::from("3.2"),
+                    kind: TvfDifferenceKind::ValueMismatch,
+                    expected: Some(String::from("\"second\"")),
+                    actual: Some(String::from("\"other\"")),
+                },
+                TvfDifference {
+                    path: String::from("3.3"),
+                    kind: TvfDifferenceKind::Unexpected,
+                    expected: None,
+                    actual: Some(String::from("\"third\"")),
+                },
+                TvfDifference {
+                    path: String::from("4"),
+                    kind: TvfDifferenceKind::Unexpected,
+                    expected: None,
+                    actual: Some(String::from("\"unexpected\"")),
+                },
+            ],
+            differences
+        );
+
+        // Type mismatch between a buffer and a string
+        let mut actual = test_tvf();
+        actual.put_string(3, "not a buffer");
+        let differences = tvf_diff(&expected, &actual);
+        assert_eq!(1, differences.len());
+        assert_eq!("3", differences[0].path);
+        assert_eq!(TvfDifferenceKind::TypeMismatch, differences[0].kind);
+        assert_eq!(
+            Some(String::from("\"not a buffer\"")),
+            differences[0].actual
+        );
+    }
+
+    #[test]
+    fn tvf_diff_ignore() {
+        let expected = test_tvf();
+        let mut actual = test_tvf();
+        actual.put_string(1, "Worldline");
+        let mut sub_tvf = SimpleStringTvf::default();
+        sub_tvf.put_string(1, "first");
+        sub_tvf.put_string(2, "other");
+        actual.put_buffer(3, sub_tvf);
+
+        let differences = TvfDiff::default().ignore("1").diff(&expected, &actual);
+        assert_eq!(1, differences.len());
+        assert_eq!("3.2", differences[0].path);
+
+        assert!(TvfDiff::default()
+            .ignore("1")
+            .ignore("3.2")
+            .diff(&expected, &actual)
+            .is_empty());
+        assert_tvf_eq!(expected, actual, "1", "3");
+    }
+
+    #[test]
+    #[should_panic(expected = "[1] value mismatch")]
+    fn tvf_assert_report() {
+        let mut actual = test_tvf();
+        actual.put_string(1, "Worldline");
+        assert_tvf_eq!(test_tvf(), actual);
+    }
+}
@@ -0,0 +1,516 @@
+//! Module to mask sensitive TVF[^tvfnote] fields before they reach the logs
+//!
+//! A [`MaskPolicy`] list the tag paths of the sensitive fields (tags of nested buffers separated by `.`, ex: `2.1`) with their [`MaskStrategy`].
+//! A `*` segment match any tag, to mask a field in every element of a repeated field (ex: `3.*.1`).
+//! When a path target a buffer, all the fields of the buffer are masked (useful for repeated fields).
+//!
+//! With the `config` feature, a policy can be loaded from the configuration:
+//! ```yaml
+//! mask:
+//!   hash_key: { env: MASK_HASH_KEY }
+//!   "2": pan
+//!   "3.*.1": full
+//!   "card.cvv": hash
+//! ```
+//!
+//! The `hash_key` is the secret key of the [`MaskStrategy::Hash`] strategy (see [`Secret`](crate::config::secret::Secret) for its forms).
+//!
+//! Paths can use the labels of a [`Dictionary`](crate::dict::Dictionary) once the policy is attached to it with [`MaskPolicy::with_dictionary`].
+//!
+//! [^tvfnote]: **T**ag **V**alue **F**ormat
+
+use std::{collections::BTreeMap, fmt};
+
+use hmac::{Hmac, Mac as _};
+use sha2::Sha256;
+
+use super::tvf::{Tvf, TvfTypedRef};
+
+/// Secret key of the [`MaskStrategy::Hash`] strategy. Its value is never displayed
+#[derive(Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "config",
+    derive(serde::Deserialize),
+    serde(from = "crate::config::secret::Secret")
+)]
+pub struct MaskKey(Vec<u8>);
+
+impl MaskKey {
+    /// Method to create a hash key from its secret value
+    pub fn new<T>(key: T) -> MaskKey
+    where
+        T: Into<Vec<u8>>,
+    {
+        MaskKey(key.into())
+    }
+}
+
+#[cfg(feature = "config")]
+impl From<crate::config::secret::Secret> for MaskKey {
+    fn from(secret: crate::config::secret::Secret) -> Self {
+        MaskKey(secret.expose().as_bytes().to_vec())
+    }
+}
+
+impl fmt::Debug for MaskKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "MaskKey(******)")
+    }
+}
+
+/// Strategy to mask a field value
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "config",
+    derive(serde::Deserialize, serde::Serialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum MaskStrategy {
+    /// Replace the whole value
+    Full,
+    /// Keep the 6 first and the 4 last characters of the value (PAN masking). Short values are fully masked
+    Pan,
+    /// Replace the value by its keyed hash (HMAC-SHA256), to correlate values across logs without revealing them.
+    /// Without a hash key, the value is fully masked
+    Hash,
+}
+
+impl MaskStrategy {
+    /// Mask applied on a fully masked value
+    pub const FULL_MASK: &'static str = "***";
+
+    /// Method to mask a value with the strategy, with the key of the hash strategy
+    ///
+    /// ```
+    /// use prosa_utils::msg::mask::{MaskKey, MaskStrategy};
+    ///
+    /// let key = MaskKey::new("secret");
+    /// assert_eq!("***", MaskStrategy::Full.mask("4970100000001234", None));
+    /// assert_eq!("497010******1234", MaskStrategy::Pan.mask("4970100000001234", None));
+    /// assert_eq!("*****", MaskStrategy::Pan.mask("12345", None));
+    /// assert!(MaskStrategy::Hash.mask("4970100000001234", Some(&key)).starts_with("hash:"));
+    /// assert_eq!("***", MaskStrategy::Hash.mask("4970100000001234", None));
+    /// ```
+    pub fn mask(&self, value: &str, hash_key: Option<&MaskKey>) -> String {
+        match self {
+            MaskStrategy::Full => String::from(Self::FULL_MASK),
+            MaskStrategy::Pan => {
+                let chars: Vec<char> = value.chars().collect();
+                if chars.len() > 10 {
+                    let mut masked: String = chars[..6].iter().collect();
+                    masked.extend(std::iter::repeat_n('*', chars.len() - 10));
+                    masked.extend(&chars[chars.len() - 4..]);
+                    masked
+                } else {
+                    "*".repeat(chars.len())
+                }
+            }
+            MaskStrategy::Hash => match hash_key {
+                Some(MaskKey(key)) => {
+                    let mut mac = Hmac::<Sha256>::new_from_slice(key)
+                        .expect("HMAC can take a key of any size");
+                    mac.update(value.as_bytes());
+                    // The truncated HMAC is enough to correlate values in the logs
+                    format!("hash:{}", hex::encode(&mac.finalize().into_bytes()[..8]))
+                }
+                None => String::from(Self::FULL_MASK),
+            },
+        }
+    }
+}
+
+impl fmt::Display for MaskStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MaskStrategy::Full => write!(f, "full"),
+            MaskStrategy::Pan => write!(f, "pan"),
+            MaskStrategy::Hash => write!(f, "hash"),
+        }
+    }
+}
+
+/// Policy that list the sensitive fields of a TVF with their masking strategy
+///
+/// ```
+/// use prosa_utils::msg::mask::{masked_display, MaskPolicy, MaskStrategy};
+/// use prosa_utils::msg::simple_string_tvf::SimpleStringTvf;
+/// use prosa_utils::msg::tvf::Tvf;
+///
+/// let mut card = SimpleStringTvf::default();
+/// card.put_string(1, "4970100000001234");
+/// card.put_string(2, "123");
+/// let mut tvf = SimpleStringTvf::default();
+/// tvf.put_unsigned(1, 100);
+/// tvf.put_buffer(2, card);
+///
+/// let policy = MaskPolicy::default()
+///     .mask("2.1", MaskStrategy::Pan)
+///     .mask("2.2", MaskStrategy::Full);
+/// assert_eq!(
+///     r#"{1: "100", 2: {1: "497010******1234", 2: "***"}}"#,
+///     masked_display(&tvf, &policy)
+/// );
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "config", derive(serde::Deserialize, serde::Serialize))]
+pub struct MaskPolicy {
+    #[cfg_attr(feature = "config", serde(default, skip_serializing))]
+    hash_key: Option<MaskKey>,
+    #[cfg_attr(feature = "config", serde(flatten))]
+    paths: BTreeMap<String, MaskStrategy>,
+}
+
+impl MaskPolicy {
+    /// Method to mask a field path with a strategy
+    pub fn mask<P>(mut self, path: P, strategy: MaskStrategy) -> Self
+    where
+        P: Into<String>,
+    {
+        self.paths.insert(path.into(), strategy);
+        self
+    }
+
+    /// Method to set the secret key of the hash strategy
+    pub fn with_hash_key(mut self, hash_key: MaskKey) -> Self {
+        self.hash_key = Some(hash_key);
+        self
+    }
+
+    /// Getter of the secret key of the hash strategy
+    pub fn get_hash_key(&self) -> Option<&MaskKey> {
+        self.hash_key.as_ref()
+    }
+
+    /// Getter to know if the policy don't mask any field
+    pub fn is_empty(&self) -> bool {
+        self.paths.is_empty()
+    }
+
+    /// Getter of the masked paths with their strategy
+    pub fn paths(&self) -> impl Iterator<Item = (&String, &MaskStrategy)> {
+        self.paths.iter()
+    }
+
+    /// Getter of the strategy to apply on a field path (tags separated by `.`)
+    pub fn get_strategy(&self, path: &[usize]) -> Option<MaskStrategy> {
+        self.paths.iter().find_map(|(pattern, strategy)| {
+            let mut segments = pattern.split('.');
+            let matched = path.iter().all(|tag| {
+                segments
+                    .next()
+                    .is_some_and(|s| s == "*" || s.parse::<usize>().is_ok_and(|t| t == *tag))
+            }) && segments.next().is_none();
+            matched.then_some(*strategy)
+        })
+    }
+
+    /// Method to attach the policy to a dictionary: labels of the paths are replaced by their tags
+    ///
+    /// For a repeatable field, the index segment can be omitted (all its elements are masked).
+    ///
+    /// ```
+    /// use std::sync::Arc;
+    /// use prosa_utils::dict::{Dictionary, DictEntry, EntryType};
+    /// use prosa_utils::msg::mask::{MaskPolicy, MaskStrategy};
+    ///
+    /// let mut card = Dictionary::new("card");
+    /// card.add_entry(DictEntry::new(1, "pan", EntryType::String, false)).unwrap();
+    /// let mut message = Dictionary::new("message");
+    /// message.add_entry(DictEntry::new(3, "cards", EntryType::Node(Arc::new(card)), true)).unwrap();
+    ///
+    /// let policy = MaskPolicy::default()
+    ///     .mask("cards.pan", MaskStrategy::Pan)
+    ///     .with_dictionary(&message)
+    ///     .unwrap();
+    /// assert_eq!(Some(MaskStrategy::Pan), policy.get_strategy(&[3, 2, 1]));
+    /// ```
+    #[cfg(feature = "dict")]
+    pub fn with_dictionary(
+        self,
+        dictionary: &crate::dict::Dictionary,
+    ) -> Result<MaskPolicy, crate::dict::DictError> {
+        use crate::dict::{DictError, EntryType};
+
+        let mut paths = BTreeMap::new();
+        for (path, strategy) in self.paths {
+            let mut tags = Vec::new();
+            let mut current = Some(dictionary);
+            let mut expect_index = false;
+            let mut segments = path.split('.').peekable();
+            while let Some(segment) = segments.next() {
+                if segment == "*" || segment.parse::<usize>().is_ok() {
+                    // An index of a repeatable field keep its dictionary, other tags can't be resolved anymore
+                    tags.push(segment.to_string());
+                    if !expect_index {
+                        current = None;
+                    }
+                    expect_index = false;
+                    continue;
+                }
+
+                let entry = current
+                    .and_then(|d| d.get_by_label(segment))
+                    .ok_or_else(|| {
+                        DictError::Entry(format!(
+                            "unknown label `{}` in mask path `{}`",
+                            segment, path
+                        ))
+                    })?;
+                tags.push(entry.tag.to_string());
+                current = if let EntryType::Node(node) = &entry.entry_type {
+                    Some(node.as_ref())
+                } else {
+                    None
+                };
+
+                // Elements of repeatable fields are indexed from 1
+                expect_index = entry.repeatable;
+                if expect_index
+                    && segments
+                        .peek()
+                        .is_some_and(|s| *s != "*" && s.parse::<usize>().is_err())
+                {
+                    tags.push(String::from("*"));
+                    expect_index = false;
+                }
+            }
+
+            paths.insert(tags.join("."), strategy);
+        }
+
+        Ok(MaskPolicy {
+            hash_key: self.hash_key,
+            paths,
+        })
+    }
+}
+
+/// Method to render a TVF with its sensitive fields masked
+///
+/// Fields are rendered with their tag, ordered by tag: `{1: "value", 2: {1: "sub value"}}`.
+/// Bytes are rendered in hexadecimal, and masked from their hexadecimal representation.
+pub fn masked_display<T>(tvf: &T, policy: &MaskPolicy) -> String
+where
+    T: Tvf + Default + fmt::Debug + Clone,
+{
+    let mut output = String::new();
+    write_buffer(&mut output, tvf, policy, &mut Vec::new(), None);
+    output
+}
+
+fn write_buffer<T>(
+    output: &mut String,
+    tvf: &T,
+    policy: &MaskPolicy,
+    path: &mut Vec<usize>,
+    parent_strategy: Option<MaskStrategy>,
+) where
+    T: Tvf + Default + fmt::Debug + Clone,
+{
+    let mut entries: Vec<_> = tvf.entries().collect();
+    entries.sort_unstable_by_key(|(id, _)| *id);
+
+    output.push('{');
+    for (i, (id, value)) in entries.into_iter().enumerate() {
+        if i > 0 {
+            output.push_str(", ");
+        }
+        output.push_str(&id.to_string());
+        output.push_str(": ");
+
+        path.push(id);
+        let strategy = parent_strategy.or_else(|| policy.get_strategy(path));
+        match value {
+            TvfTypedRef::Buffer(buffer) => {
+                write_buffer(output, buffer.as_ref(), policy, path, strategy)
+            }
+            value => {
+                let is_bytes = matches!(value, TvfTypedRef::Bytes(_));
+                let value = value.to_string();
+                let value = strategy
+                    .map(|s| s.mask(&value, policy.get_hash_key()))
+                    .unwrap_or(value);
+                if is_bytes && strategy.is_none() {
+                    output.push_str(&value);
+                } else {
+                    output.push_str(&format!("{:?}", value));
+                }
+            }
+        }
+        path.pop();
+    }
+    output.push('}');
+}
+
+/// Macro to log a TVF with a [`tracing`](https://docs.rs/tracing) event, with its sensitive fields masked by a [`MaskPolicy`]
+///
+/// ```
+/// use prosa_utils::log_tvf;
+/// use prosa_utils::msg::mask::{MaskPolicy, MaskStrategy};
+/// use prosa_utils::msg::simple_string_tvf::SimpleStringTvf;
+/// use prosa_utils::msg::tvf::Tvf;
+///
+/// let policy = MaskPolicy::default().mask("1", MaskStrategy::Pan);
+/// let mut request = SimpleStringTvf::default();
+/// request.put_string(1, "4970100000001234");
+///
+/// log_tvf!(prosa_utils::tracing::Level::INFO, &policy, &request);
+/// log_tvf!(prosa_utils::tracing::Level::DEBUG, &policy, &request, "Request received from {}", "client");
+/// ```
+#[macro_export]
+macro_rules! log_tvf {
+    ($level:expr, $policy:expr, $msg:expr) => {
+        $crate::tracing::event!(
+            $level,
+            tvf = $crate::msg::mask::masked_display($msg, $policy)
+        )
+    };
+    ($level:expr, $policy:expr, $msg:expr, $($arg:tt)+) => {
+        $crate::tracing::event!(
+            $level,
+            tvf = $crate::msg::mask::masked_display($msg, $policy),
+            $($arg)+
+        )
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+
+    use super::*;
+    use crate::msg::simple_string_tvf::SimpleStringTvf;
+
+    const PAN: &str = "4970100000001234";
+
+    fn card(pan: &str) -> SimpleStringTvf {
+        let mut card = SimpleStringTvf::default();
+        card.put_string(1, pan);
+        card.put_string(2, "123");
+        card
+    }
+
+    fn message() -> SimpleStringTvf {
+        let mut cards = SimpleStringTvf::default();
+        cards.put_buffer(1, card(PAN));
+        cards.put_buffer(2, card("5130100000009876"));
+
+        let mut secrets = SimpleStringTvf::default();
+        secrets.put_string(1, "key1");
+        secrets.put_string(2, "key2");
+
+        let mut tvf = SimpleStringTvf::default();
+        tvf.put_string(1, PAN);
+        tvf.put_unsigned(2, 100);
+        tvf.put_buffer(3, cards);
+        tvf.put_buffer(4, secrets);
+        tvf.put_bytes(5, Bytes::from_static(b"\x01\x02"));
+        tvf
+    }
+
+    #[test]
+    fn mask_strategies() {
+        let key = MaskKey::new("secret");
+        let policy = MaskPolicy::default()
+            .with_hash_key(key.clone())
+            .mask("1", MaskStrategy::Pan)
+            .mask("3.*.1", MaskStrategy::Pan)
+            .mask("3.2.2", MaskStrategy::Hash)
+            .mask("4", MaskStrategy::Full);
+        assert_eq!(Some(MaskStrategy::Pan), policy.get_strategy(&[3, 8, 1]));
+        assert_eq!(None, policy.get_strategy(&[3, 8]));
+        assert_eq!(None, policy.get_strategy(&[3, 8, 1, 1]));
+
+        let masked = masked_display(&message(), &policy);
+        assert_eq!(
+            format!(
+                r#"{{1: "497010******1234", 2: "100", 3: {{1: {{1: "497010******1234", 2: "123"}}, 2: {{1: "513010******9876", 2: "{}"}}}}, 4: {{1: "***", 2: "***"}}, 5: "0102"}}"#,
+                MaskStrategy::Hash.mask("123", Some(&key))
+            ),
+            masked
+        );
+        assert!(!masked.contains(PAN));
+
+        // Nothing is masked without policy
+        assert!(masked_display(&message(), &MaskPolicy::default()).contains(PAN));
+        assert_eq!(
+            MaskStrategy::Hash.mask(PAN, Some(&key)),
+            MaskStrategy::Hash.mask(PAN, Some(&key)),
+            "hash must be stable"
+        );
+        assert_ne!(
+            MaskStrategy::Hash.mask(PAN, Some(&key)),
+            MaskStrategy::Hash.mask("123", Some(&key))
+        );
+
+        // The hash depends on the key, and isn't computed without key
+        assert_ne!(
+            MaskStrategy::Hash.mask(PAN, Some(&key)),
+            MaskStrategy::Hash.mask(PAN, Some(&MaskKey::new("other")))
+        );
+        assert_eq!("***", MaskStrategy::Hash.mask(PAN, None));
+        assert_eq!("MaskKey(******)", format!("{:?}", key));
+    }
+
+    #[cfg(feature = "dict")]
+    #[test]
+    fn mask_dictionary_labels() {
+        use crate::dict::{DictEntry, Dictionary, EntryType};
+        use std::sync::Arc;
+
+        let mut card = Dictionary::new("card");
+        card.add_entry(DictEntry::new(1, "pan", EntryType::String, false))
+            .unwrap();
+        card.add_entry(DictEntry::new(2, "cvv", EntryType::String, false))
+            .unwrap();
+        let mut message_dict = Dictionary::new("message");
+        message_dict
+            .add_entry(DictEntry::new(1, "pan", EntryType::String, false))
+            .unwrap();
+        message_dict
+            .add_entry(DictEntry::new(
+                3,
+                "cards",
+                EntryType::Node(Arc::new(card)),
+                true,
+            ))
+            .unwrap();
+
+        let policy = MaskPolicy::default()
+            .mask("pan", MaskStrategy::Full)
+            .mask("cards.pan", MaskStrategy::Pan)
+            .mask("cards.2.cvv", MaskStrategy::Full)
+            .with_dictionary(&message_dict)
+            .unwrap();
+        assert_eq!(
+            vec!["1", "3.*.1", "3.2.2"],
+            policy.paths().map(|(p, _)| p.as_str()).collect::<Vec<_>>()
+        );
+
+        let masked = masked_display(&message(), &policy);
+        assert!(masked.starts_with(
+            r#"{1: "***", 2: "100", 3: {1: {1: "497010******1234", 2: "123"}, 2: {1: "513010******9876", 2: "***"}}"#
+        ), "{}", masked);
+
+        assert!(MaskPolicy::default()
+            .mask("cards.unknown", MaskStrategy::Full)
+            .with_dictionary(&message_dict)
+            .is_err());
+    }
+
+    #[cfg(feature = "config")]
+    #[test]
+    fn mask_policy_config() {
+        let policy: MaskPolicy =
+            serde_yaml::from_str("hash_key: secret\n\"1\": pan\n\"3.*.1\": full\n\"4\": hash\n")
+                .unwrap();
+        assert_eq!(
+            MaskPolicy::default()
+                .with_hash_key(MaskKey::new("secret"))
+                .mask("1", MaskStrategy::Pan)
+                .mask("3.*.1", MaskStrategy::Full)
+                .mask("4", MaskStrategy::Hash),
+            policy
+        );
+    }
+}
@@ -3,7 +3,7 @@
 use bytes::Bytes;
 use chrono::{NaiveDate, NaiveDateTime};
 
-use crate::msg::tvf::{Tvf, TvfError};
+use crate::msg::tvf::{Tvf, TvfError, TvfType, TvfTypedRef};
 use std::{borrow::Cow, collections::hash_map::HashMap};
 
 /// Struct that define a simple string TVF
@@ -67,6 +67,75 @@ impl Tvf for SimpleStringTvf {
         self.fields.keys().cloned().collect()
     }
 
+    fn tags(&self) -> impl Iterator<Item = usize> + '_ {
+        self.fields.keys().copied()
+    }
+
+    /// Iterate over the fields of the TVF. All fields are borrowed strings, except serialized sub buffers
+    fn entries(&self) -> impl Iterator<Item = (usize, TvfTypedRef<'_, SimpleStringTvf>)> {
+        self.fields.iter().map(|(id, str_value)| {
+            let value = match SimpleStringTvf::deserialize(str_value) {
+                Ok(buffer) if !buffer.is_empty() => TvfTypedRef::Buffer(Cow::Owned(buffer)),
+                _ => TvfTypedRef::String(Cow::Borrowed(str_value)),
+            };
+            (*id, value)
+        })
+    }
+
+    /// Get the size of the serialized TVF
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use prosa_utils::msg::tvf::Tvf;
+    /// use prosa_utils::msg::simple_string_tvf::SimpleStringTvf;
+    ///
+    /// let mut tvf: SimpleStringTvf = Default::default();
+    /// tvf.put_string(1, String::from("first_val"));
+    /// tvf.put_string(20, String::from("second_val"));
+    ///
+    /// assert_eq!(tvf.serialize().len(), tvf.size_hint());
+    /// ```
+    fn size_hint(&self) -> usize {
+        // Each field is serialized as `key;len;value;`
+        fn digits(n: usize) -> usize {
+            n.checked_ilog10().unwrap_or(0) as usize + 1
+        }
+
+        self.fields
+            .iter()
+            .map(|(k, v)| digits(*k) + digits(v.len()) + v.len() + 3)
+            .sum()
+    }
+
+    /// Get the type of a field. All fields are strings, except serialized sub buffers
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use prosa_utils::msg::tvf::{Tvf, TvfType};
+    /// use prosa_utils::msg::simple_string_tvf::SimpleStringTvf;
+    ///
+    /// let mut tvf: SimpleStringTvf = Default::default();
+    /// tvf.put_unsigned(1, 42);
+    /// tvf.put_buffer(2, tvf.clone());
+    ///
+    /// assert_eq!(Ok(TvfType::String), tvf.get_type(1));
+    /// assert_eq!(Ok(TvfType::Buffer), tvf.get_type(2));
+    /// ```
+    fn get_type(&self, id: usize) -> Result<TvfType, TvfError> {
+        match self.fields.get(&id) {
+            Some(str_value) => {
+                if SimpleStringTvf::deserialize(str_value).is_ok_and(|b| !b.is_empty()) {
+                    Ok(TvfType::Buffer)
+                } else {
+                    Ok(TvfType::String)
+                }
+            }
+            None => Err(TvfError::FieldNotFound(id)),
+        }
+    }
+
     fn get_buffer(&self, id: usize) -> Result<Cow<'_, SimpleStringTvf>, TvfError> {
         match self.fields.get(&id) {
             Some(str_value) => Ok(Cow::Owned(SimpleStringTvf::deserialize(str_value)?)),
@@ -97,7 +166,7 @@ impl Tvf for SimpleStringTvf {
     fn get_byte(&self, id: usize) -> Result<u8, TvfError> {
         match self.fields.get(&id) {
             Some(str_value) => match hex::decode(str_value) {
-                Ok(bytes) => Ok(bytes[0]),
+                Ok(bytes) => bytes.first().copied().ok_or(TvfError::TypeMismatch),
                 Err(_) => Err(TvfError::TypeMismatch),
             },
             None => Err(TvfError::FieldNotFound(id)),
@@ -222,6 +291,19 @@ impl SimpleStringTvf {
         out_str
     }
 
+    /// Serialize this TVF to String with its fields ordered by their id, to have a canonical representation of sub buffers in the serde representation
+    #[cfg(feature = "config")]
+    fn serialize_sorted(&self) -> String {
+        let mut keys = self.keys();
+        keys.sort_unstable();
+        keys.into_iter()
+            .map(|k| {
+                let v = &self.fields[&k];
+                format!("{};{};{};", k, v.len(), v)
+            })
+            .collect()
+    }
+
     /// Load a TVF from String
     pub fn deserialize(serial: &str) -> Result<SimpleStringTvf, TvfError> {
         let mut buffer: SimpleStringTvf = Default::default();
@@ -236,8 +318,11 @@ impl SimpleStringTvf {
                 let len = l
                     .parse::<usize>()
                     .map_err(|e| TvfError::SerializationError(e.to_string()))?;
-                buffer.fields.insert(key, String::from(&rest[0..len]));
-                if rest.chars().nth(len) != Some(';') {
+                let value = rest.get(0..len).ok_or(TvfError::SerializationError(
+                    "Field length out of bound".into(),
+                ))?;
+                buffer.fields.insert(key, String::from(value));
+                if rest.as_bytes().get(len) != Some(&b';') {
                     return Err(TvfError::SerializationError(
                         "Bad field termination char".into(),
                     ));
@@ -252,6 +337,164 @@ impl SimpleStringTvf {
     }
 }
 
+/// Typed value of a field, for the serde representation of [`SimpleStringTvf`]
+#[cfg(feature = "config")]
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "t", content = "v", rename_all = "lowercase")]
+enum SimpleStringValue {
+    Buf(SimpleStringTvf),
+    Uint(u64),
+    Int(i64),
+    Byte(u8),
+    Float(f64),
+    Str(String),
+    Bytes(String),
+    Date(String),
+    DateTime(String),
+}
+
+#[cfg(feature = "config")]
+impl SimpleStringValue {
+    /// Method to get the typed value of a field, from its type (see [`Tvf::get_type`]).
+    /// Sub buffers are kept as nested maps only when they can be serialized back identically (fields ordered by their id)
+    fn from_field(tvf: &SimpleStringTvf, id: usize) -> Result<SimpleStringValue, TvfError> {
+        let value = tvf.fields.get(&id).ok_or(TvfError::FieldNotFound(id))?;
+        Ok(match tvf.get_type(id)? {
+            TvfType::Buffer => match tvf.get_buffer(id) {
+                Ok(buffer) if buffer.serialize_sorted() == *value => {
+                    SimpleStringValue::Buf(buffer.into_owned())
+                }
+                _ => SimpleStringValue::Str(value.clone()),
+            },
+            TvfType::Unsigned => SimpleStringValue::Uint(tvf.get_unsigned(id)?),
+            TvfType::Signed => SimpleStringValue::Int(tvf.get_signed(id)?),
+            TvfType::Byte => SimpleStringValue::Byte(tvf.get_byte(id)?),
+            TvfType::Float => SimpleStringValue::Float(tvf.get_float(id)?),
+            TvfType::String => SimpleStringValue::Str(value.clone()),
+            TvfType::Bytes => SimpleStringValue::Bytes(value.clone()),
+            TvfType::Date => SimpleStringValue::Date(value.clone()),
+            TvfType::DateTime => SimpleStringValue::DateTime(value.clone()),
+        })
+    }
+
+    /// Method to put the typed value in the TVF
+    fn put(self, tvf: &mut SimpleStringTvf, id: usize) -> Result<(), TvfError> {
+        match self {
+            SimpleStringValue::Buf(buffer) => tvf.put_string(id, buffer.serialize_sorted()),
+            SimpleStringValue::Uint(unsigned) => tvf.put_unsigned(id, unsigned),
+            SimpleStringValue::Int(signed) => tvf.put_signed(id, signed),
+            SimpleStringValue::Byte(byte) => tvf.put_byte(id, byte),
+            SimpleStringValue::Float(float) => tvf.put_float(id, float),
+            SimpleStringValue::Str(string) => tvf.put_string(id, string),
+            SimpleStringValue::Bytes(bytes) => tvf.put_bytes(
+                id,
+                hex::decode(bytes)
+                    .map_err(|e| TvfError::ConvertionError(e.to_string()))?
+                    .into(),
+            ),
+            SimpleStringValue::Date(date) => tvf.put_date(
+                id,
+                NaiveDate::parse_from_str(&date, SIMPLE_DATE_FMT)
+                    .map_err(|e| TvfError::ConvertionError(e.to_string()))?,
+            ),
+            SimpleStringValue::DateTime(datetime) => tvf.put_datetime(
+                id,
+                NaiveDateTime::parse_from_str(&datetime, SIMPLE_DATETIME_FMT)
+                    .map_err(|e| TvfError::ConvertionError(e.to_string()))?,
+            ),
+        }
+
+        Ok(())
+    }
+}
+
+/// Serialize the raw representation of the TVF: a map of field id to typed value `{ "t": <type>, "v": <value> }`
+///
+/// Every field is serialized with its type (see [`Tvf::get_type`]). The simple string TVF doesn't keep the type of its fields, so they are strings (`str`), except sub buffers (`buf`) that are nested maps.
+/// This representation differs from the labeled maps handled by the [dictionary deserializer](crate::dict::deserialize::DictDeserializer).
+///
+/// ```
+/// use prosa_utils::msg::tvf::Tvf;
+/// use prosa_utils::msg::simple_string_tvf::SimpleStringTvf;
+///
+/// let mut tvf = SimpleStringTvf::default();
+/// tvf.put_unsigned(1, 42);
+/// let mut sub_buffer = SimpleStringTvf::default();
+/// sub_buffer.put_string(1, "val");
+/// tvf.put_buffer(2, sub_buffer);
+///
+/// assert_eq!(
+///     r#"{"1":{"t":"str","v":"42"},"2":{"t":"buf","v":{"1":{"t":"str","v":"val"}}}}"#,
+///     serde_json::to_string(&tvf).unwrap()
+/// );
+/// ```
+#[cfg(feature = "config")]
+impl serde::Serialize for SimpleStringTvf {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        use serde::ser::{Error as _, SerializeMap as _};
+
+        let mut keys = self.keys();
+        keys.sort_unstable();
+        let mut map = serializer.serialize_map(Some(keys.len()))?;
+        for key in keys {
+            let value = SimpleStringValue::from_field(self, key).map_err(S::Error::custom)?;
+            map.serialize_entry(&key, &value)?;
+        }
+        map.end()
+    }
+}
+
+/// Deserialize the raw representation of the TVF: a map of field id to typed value `{ "t": <type>, "v": <value> }`
+///
+/// Every [`TvfType`] can be used as type, to write fixtures easily:
+///
+/// | Type       | `t`        | `v`                           |
+/// |------------|------------|-------------------------------|
+/// | `Buffer`   | `buf`      | Nested map                    |
+/// | `Unsigned` | `uint`     | Unsigned integer              |
+/// | `Signed`   | `int`      | Signed integer                |
+/// | `Byte`     | `byte`     | Unsigned integer (0-255)      |
+/// | `Float`    | `float`    | Float                         |
+/// | `String`   | `str`      | String                        |
+/// | `Bytes`    | `bytes`    | Hexadecimal string            |
+/// | `Date`     | `date`     | `%Y-%m-%d` string             |
+/// | `DateTime` | `datetime` | `%Y-%m-%dT%H:%M:%S` string    |
+///
+/// ```
+/// use prosa_utils::msg::tvf::Tvf;
+/// use prosa_utils::msg::simple_string_tvf::SimpleStringTvf;
+///
+/// let tvf: SimpleStringTvf = serde_json::from_str(r#"{
+///     "1": { "t": "uint", "v": 42 },
+///     "2": { "t": "bytes", "v": "cafe" },
+///     "3": { "t": "date", "v": "2024-06-01" }
+/// }"#).unwrap();
+///
+/// assert_eq!(Ok(42), tvf.get_unsigned(1));
+/// assert_eq!(&[0xca, 0xfe][..], tvf.get_bytes(2).unwrap().as_ref());
+/// assert_eq!("2024-06-01", tvf.get_date(3).unwrap().to_string());
+/// ```
+#[cfg(feature = "config")]
+impl<'de> serde::Deserialize<'de> for SimpleStringTvf {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        use serde::de::Error as _;
+
+        let values = HashMap::<usize, SimpleStringValue>::deserialize(deserializer)?;
+        let mut tvf = SimpleStringTvf::default();
+        for (id, value) in values {
+            value.put(&mut tvf, id).map_err(D::Error::custom)?;
+        }
+
+        Ok(tvf)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::msg::tvf::TvfFilter;
@@ -375,6 +618,103 @@ mod tests {
             )),
             SimpleStringTvf::deserialize("1;2;to,")
         );
+
+        // An empty field is not a byte
+        assert_eq!(
+            Err(TvfError::TypeMismatch),
+            SimpleStringTvf::deserialize("1;0;;").unwrap().get_byte(1)
+        );
+    }
+
+    #[cfg(feature = "config")]
+    #[test]
+    fn test_simple_tvf_serde() {
+        let mut simple_tvf: SimpleStringTvf = Default::default();
+        test_tvf(&mut simple_tvf);
+        let mut empty_buffer = SimpleStringTvf::default();
+        simple_tvf.put_buffer(20, empty_buffer.clone());
+        empty_buffer.put_string(1, "1;1;a;");
+        simple_tvf.put_string(21, "2;1;b;1;1;a;");
+        simple_tvf.put_buffer(22, empty_buffer);
+
+        let json = serde_json::to_string(&simple_tvf).unwrap();
+        assert_eq!(
+            simple_tvf,
+            serde_json::from_str::<SimpleStringTvf>(&json).unwrap()
+        );
+        let yaml = serde_yaml::to_string(&simple_tvf).unwrap();
+        assert_eq!(
+            simple_tvf,
+            serde_yaml::from_str::<SimpleStringTvf>(&yaml).unwrap()
+        );
+
+        // Not canonical sub buffer kept as string
+        let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!("str", json["21"]["t"]);
+        assert_eq!("buf", json["22"]["t"]);
+        assert_eq!("buf", json["22"]["v"]["1"]["t"]);
+        assert_eq!("str", json["22"]["v"]["1"]["v"]["1"]["t"]);
+        assert_eq!("", json["20"]["v"]);
+
+        // Typed values
+        let typed_tvf: SimpleStringTvf = serde_yaml::from_str(
+            r#"
+1: { t: buf, v: { 1: { t: str, v: "val" } } }
+2: { t: uint, v: 42 }
+3: { t: int, v: -42 }
+4: { t: byte, v: 255 }
+5: { t: float, v: 1.5 }
+6: { t: str, v: "val" }
+7: { t: bytes, v: "0102" }
+8: { t: date, v: "2024-06-01" }
+9: { t: datetime, v: "2024-06-01T12:30:00" }
+"#,
+        )
+        .unwrap();
+        assert_eq!(TvfType::Buffer, typed_tvf.get_type(1).unwrap());
+        assert_eq!(
+            "val",
+            typed_tvf
+                .get_buffer(1)
+                .unwrap()
+                .get_string(1)
+                .unwrap()
+                .as_str()
+        );
+        assert_eq!(Ok(42), typed_tvf.get_unsigned(2));
+        assert_eq!(Ok(-42), typed_tvf.get_signed(3));
+        assert_eq!(Ok(255), typed_tvf.get_byte(4));
+        assert_eq!(Ok(1.5), typed_tvf.get_float(5));
+        assert_eq!("val", typed_tvf.get_string(6).unwrap().as_str());
+        assert_eq!(&[1, 2][..], typed_tvf.get_bytes(7).unwrap().as_ref());
+        assert_eq!(
+            NaiveDate::from_ymd_opt(2024, 6, 1).unwrap(),
+            typed_tvf.get_date(8).unwrap()
+        );
+        assert_eq!(
+            NaiveDate::from_ymd_opt(2024, 6, 1)
+                .unwrap()
+                .and_hms_opt(12, 30, 0)
+                .unwrap(),
+            typed_tvf.get_datetime(9).unwrap()
+        );
+        assert_eq!(
+            typed_tvf,
+            serde_json::from_value::<SimpleStringTvf>(serde_json::to_value(&typed_tvf).unwrap())
+                .unwrap()
+        );
+
+        // Bad typed values
+        assert!(
+            serde_json::from_str::<SimpleStringTvf>(r#"{"1":{"t":"date","v":"01/06/2024"}}"#)
+                .is_err()
+        );
+        assert!(
+            serde_json::from_str::<SimpleStringTvf>(r#"{"1":{"t":"bytes","v":"xyz"}}"#).is_err()
+        );
+        assert!(
+            serde_json::from_str::<SimpleStringTvf>(r#"{"1":{"t":"time","v":"12:00"}}"#).is_err()
+        );
     }
 
     enum TvfTestFilter {}
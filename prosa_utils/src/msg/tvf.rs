@@ -10,7 +10,7 @@
 use bytes::Bytes;
 use chrono::{NaiveDate, NaiveDateTime};
 use std::borrow::Cow;
-use std::fmt::Debug;
+use std::fmt::{self, Debug};
 use thiserror::Error;
 
 /// Error define for TVF object
@@ -29,6 +29,48 @@ pub enum TvfError {
     /// Error encountered during serialization or deserializarion process
     #[error("Serialization error: {0}")]
     SerializationError(String),
+    /// Error that indicate a field (with its tag path) can't be represented by the destination TVF of a conversion
+    #[error("The field `{0}` can't be converted without loss of {1}")]
+    LossyConversion(String, TvfType),
+}
+
+/// Type of a TVF field
+#[derive(Debug, Clone, Copy, Eq, PartialOrd, PartialEq)]
+pub enum TvfType {
+    /// Sub buffer field
+    Buffer,
+    /// Unsigned field
+    Unsigned,
+    /// Signed field
+    Signed,
+    /// Byte field
+    Byte,
+    /// Float field
+    Float,
+    /// String field
+    String,
+    /// Bytes field
+    Bytes,
+    /// Date field
+    Date,
+    /// Datetime field
+    DateTime,
+}
+
+impl fmt::Display for TvfType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TvfType::Buffer => write!(f, "buffer"),
+            TvfType::Unsigned => write!(f, "unsigned"),
+            TvfType::Signed => write!(f, "signed"),
+            TvfType::Byte => write!(f, "byte"),
+            TvfType::Float => write!(f, "float"),
+            TvfType::String => write!(f, "string"),
+            TvfType::Bytes => write!(f, "bytes"),
+            TvfType::Date => write!(f, "date"),
+            TvfType::DateTime => write!(f, "datetime"),
+        }
+    }
 }
 
 /// Trait that define a TVF[^tvfnote]
@@ -65,6 +107,74 @@ pub trait Tvf {
     /// Get all the keys for this TVF
     fn keys(&self) -> Vec<usize>;
 
+    /// Iterate over the tags of the TVF fields (not recursive), in no particular order
+    fn tags(&self) -> impl Iterator<Item = usize> + '_ {
+        self.keys().into_iter()
+    }
+
+    /// Iterate over the fields of the TVF (not recursive) with their typed values, in no particular order.
+    /// By default the values are retrieved with [`Tvf::get_type`] and the getters, implementations that can borrow their values should override it.
+    ///
+    /// ```
+    /// use prosa_utils::msg::tvf::{Tvf, TvfType};
+    /// use prosa_utils::msg::simple_string_tvf::SimpleStringTvf;
+    ///
+    /// let mut tvf = SimpleStringTvf::default();
+    /// tvf.put_string(1, "ProSA");
+    /// tvf.put_buffer(2, tvf.clone());
+    ///
+    /// let mut types: Vec<(usize, TvfType)> = tvf
+    ///     .entries()
+    ///     .map(|(id, value)| (id, value.get_type()))
+    ///     .collect();
+    /// types.sort_unstable_by_key(|(id, _)| *id);
+    /// assert_eq!(vec![(1, TvfType::String), (2, TvfType::Buffer)], types);
+    /// ```
+    fn entries(&self) -> impl Iterator<Item = (usize, TvfTypedRef<'_, Self>)>
+    where
+        Self: Tvf + Default + Debug + Clone,
+    {
+        self.tags()
+            .filter_map(move |id| typed_field(self, id).ok().map(|value| (id, value)))
+    }
+
+    /// Approximate size in bytes of the TVF once serialized.
+    /// Used to enforce a message size limit, return `0` if the size is unknown (default implementation)
+    fn size_hint(&self) -> usize {
+        0
+    }
+
+    /// Get the type of a field.
+    /// By default the type is guessed with getters (buffer, string, bytes, then numbers and dates), implementations that know their field types should override it.
+    fn get_type(&self, id: usize) -> Result<TvfType, TvfError>
+    where
+        Self: Tvf + Default + Debug + Clone,
+    {
+        if !self.contains(id) {
+            Err(TvfError::FieldNotFound(id))
+        } else if self.get_buffer(id).is_ok_and(|b| !b.is_empty()) {
+            Ok(TvfType::Buffer)
+        } else if self.get_string(id).is_ok() {
+            Ok(TvfType::String)
+        } else if self.get_bytes(id).is_ok() {
+            Ok(TvfType::Bytes)
+        } else if self.get_unsigned(id).is_ok() {
+            Ok(TvfType::Unsigned)
+        } else if self.get_signed(id).is_ok() {
+            Ok(TvfType::Signed)
+        } else if self.get_byte(id).is_ok() {
+            Ok(TvfType::Byte)
+        } else if self.get_float(id).is_ok() {
+            Ok(TvfType::Float)
+        } else if self.get_date(id).is_ok() {
+            Ok(TvfType::Date)
+        } else if self.get_datetime(id).is_ok() {
+            Ok(TvfType::DateTime)
+        } else {
+            Err(TvfError::TypeMismatch)
+        }
+    }
+
     /// Get a sub buffer from a TVF
     fn get_buffer(&self, id: usize) -> Result<Cow<'_, Self>, TvfError>
     where
@@ -110,6 +220,92 @@ pub trait Tvf {
     fn put_datetime(&mut self, id: usize, datetime: NaiveDateTime);
 }
 
+/// Typed value of a TVF field, borrowed from the TVF when possible
+///
+/// Given by [`Tvf::entries`] to walk through a TVF without knowing its concrete type.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TvfTypedRef<'a, T>
+where
+    T: Clone,
+{
+    /// Sub buffer field
+    Buffer(Cow<'a, T>),
+    /// Unsigned field
+    Unsigned(u64),
+    /// Signed field
+    Signed(i64),
+    /// Byte field
+    Byte(u8),
+    /// Float field
+    Float(f64),
+    /// String field
+    String(Cow<'a, String>),
+    /// Bytes field
+    Bytes(Cow<'a, Bytes>),
+    /// Date field
+    Date(NaiveDate),
+    /// Datetime field
+    DateTime(NaiveDateTime),
+}
+
+impl<T> TvfTypedRef<'_, T>
+where
+    T: Clone,
+{
+    /// Getter of the type of the field
+    pub fn get_type(&self) -> TvfType {
+        match self {
+            TvfTypedRef::Buffer(_) => TvfType::Buffer,
+            TvfTypedRef::Unsigned(_) => TvfType::Unsigned,
+            TvfTypedRef::Signed(_) => TvfType::Signed,
+            TvfTypedRef::Byte(_) => TvfType::Byte,
+            TvfTypedRef::Float(_) => TvfType::Float,
+            TvfTypedRef::String(_) => TvfType::String,
+            TvfTypedRef::Bytes(_) => TvfType::Bytes,
+            TvfTypedRef::Date(_) => TvfType::Date,
+            TvfTypedRef::DateTime(_) => TvfType::DateTime,
+        }
+    }
+}
+
+/// Render the value of the field. Bytes are rendered in hexadecimal (`0x0102`), and buffers with their debug representation
+impl<T> fmt::Display for TvfTypedRef<'_, T>
+where
+    T: Clone + Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TvfTypedRef::Buffer(buffer) => write!(f, "{:?}", buffer),
+            TvfTypedRef::Unsigned(unsigned) => write!(f, "{}", unsigned),
+            TvfTypedRef::Signed(signed) => write!(f, "{}", signed),
+            TvfTypedRef::Byte(byte) => write!(f, "{}", byte),
+            TvfTypedRef::Float(float) => write!(f, "{}", float),
+            TvfTypedRef::String(string) => write!(f, "{}", string),
+            TvfTypedRef::Bytes(bytes) => write!(f, "0x{}", hex::encode(bytes.as_ref())),
+            TvfTypedRef::Date(date) => write!(f, "{}", date),
+            TvfTypedRef::DateTime(datetime) => write!(f, "{}", datetime),
+        }
+    }
+}
+
+/// Method to get the typed value of a field from its type
+fn typed_field<T>(tvf: &T, id: usize) -> Result<TvfTypedRef<'_, T>, TvfError>
+where
+    T: Tvf + Default + Debug + Clone,
+{
+    Ok(match tvf.get_type(id)? {
+        TvfType::Buffer => TvfTypedRef::Buffer(tvf.get_buffer(id)?),
+        TvfType::Unsigned => TvfTypedRef::Unsigned(tvf.get_unsigned(id)?),
+        TvfType::Signed => TvfTypedRef::Signed(tvf.get_signed(id)?),
+        TvfType::Byte => TvfTypedRef::Byte(tvf.get_byte(id)?),
+        TvfType::Float => TvfTypedRef::Float(tvf.get_float(id)?),
+        TvfType::String => TvfTypedRef::String(tvf.get_string(id)?),
+        TvfType::Bytes => TvfTypedRef::Bytes(tvf.get_bytes(id)?),
+        TvfType::Date => TvfTypedRef::Date(tvf.get_date(id)?),
+        TvfType::DateTime => TvfTypedRef::DateTime(tvf.get_datetime(id)?),
+    })
+}
+
 /// Trait to define a TVF[^tvfnote] filter.
 /// Useful to filter sensitive data.
 ///
@@ -158,3 +354,478 @@ pub trait TvfFilter {
         tvf
     }
 }
+
+/// Error of a typed access to a TVF[^tvfnote] field, with the tag path of the field (tags of nested buffers separated by `.`, ex: `3.1`)
+///
+/// [^tvfnote]: **T**ag **V**alue **F**ormat
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum TvfFieldError {
+    /// The field is not present
+    Missing {
+        /// Tag path of the field
+        path: String,
+        /// Type expected for the field
+        expected: TvfType,
+    },
+    /// The field is present but can't be retrieve with the expected type
+    WrongType {
+        /// Tag path of the field
+        path: String,
+        /// Type expected for the field
+        expected: TvfType,
+        /// Type of the field (if it can be determined)
+        found: Option<TvfType>,
+    },
+}
+
+impl TvfFieldError {
+    /// Method to create the error of a field from the TVF that should contain it (the last tag of the path)
+    fn new<T>(tvf: &T, path: &[usize], expected: TvfType) -> TvfFieldError
+    where
+        T: Tvf + Default + Debug + Clone,
+    {
+        let path_str = path
+            .iter()
+            .map(|tag| tag.to_string())
+            .collect::<Vec<_>>()
+            .join(".");
+        match path.last() {
+            Some(tag) if tvf.contains(*tag) => TvfFieldError::WrongType {
+                path: path_str,
+                expected,
+                found: tvf.get_type(*tag).ok(),
+            },
+            _ => TvfFieldError::Missing {
+                path: path_str,
+                expected,
+            },
+        }
+    }
+
+    /// Getter of the tag path of the field
+    pub fn path(&self) -> &str {
+        match self {
+            TvfFieldError::Missing { path, .. } | TvfFieldError::WrongType { path, .. } => path,
+        }
+    }
+
+    /// Getter of the type expected for the field
+    pub fn expected(&self) -> TvfType {
+        match self {
+            TvfFieldError::Missing { expected, .. } | TvfFieldError::WrongType { expected, .. } => {
+                *expected
+            }
+        }
+    }
+
+    /// Getter of the type of the field, `None` if the field is missing or if its type can't be determined
+    pub fn found(&self) -> Option<TvfType> {
+        match self {
+            TvfFieldError::Missing { .. } => None,
+            TvfFieldError::WrongType { found, .. } => *found,
+        }
+    }
+
+    /// Method to know if the field is missing
+    pub fn is_missing(&self) -> bool {
+        matches!(self, TvfFieldError::Missing { .. })
+    }
+}
+
+impl fmt::Display for TvfFieldError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TvfFieldError::Missing { path, expected } => {
+                write!(f, "The field `{}` is missing (expected {})", path, expected)
+            }
+            TvfFieldError::WrongType {
+                path,
+                expected,
+                found: Some(found),
+            } => write!(
+                f,
+                "The field `{}` is a {} instead of a {}",
+                path, found, expected
+            ),
+            TvfFieldError::WrongType {
+                path,
+                expected,
+                found: None,
+            } => write!(f, "The field `{}` is not a {}", path, expected),
+        }
+    }
+}
+
+impl std::error::Error for TvfFieldError {}
+
+/// Tag path of a TVF field: a single tag, or the tags of the nested buffers followed by the tag of the field
+pub trait TvfPath {
+    /// Getter of the tags of the path
+    fn tags(&self) -> &[usize];
+}
+
+impl TvfPath for usize {
+    fn tags(&self) -> &[usize] {
+        std::slice::from_ref(self)
+    }
+}
+
+impl<const N: usize> TvfPath for [usize; N] {
+    fn tags(&self) -> &[usize] {
+        self
+    }
+}
+
+impl<const N: usize> TvfPath for &[usize; N] {
+    fn tags(&self) -> &[usize] {
+        *self
+    }
+}
+
+impl TvfPath for &[usize] {
+    fn tags(&self) -> &[usize] {
+        self
+    }
+}
+
+impl TvfPath for Vec<usize> {
+    fn tags(&self) -> &[usize] {
+        self
+    }
+}
+
+/// Macro to implement a `require_*` method that return a copied value
+macro_rules! require_value {
+    ( $self:ident, $path:ident, $getter:ident, $tvf_type:expr ) => {{
+        let tags = $path.tags();
+        let (parent, tag) = $self.get_parent(tags, $tvf_type)?;
+        parent
+            .$getter(tag)
+            .map_err(|_| TvfFieldError::new(parent.as_ref(), tags, $tvf_type))
+    }};
+}
+
+/// Macro to implement a `require_*` method that return a borrowed value (owned if it's read from a nested buffer)
+macro_rules! require_ref {
+    ( $self:ident, $path:ident, $getter:ident, $tvf_type:expr ) => {{
+        let tags = $path.tags();
+        match $self.get_parent(tags, $tvf_type)? {
+            (Cow::Borrowed(parent), tag) => parent
+                .$getter(tag)
+                .map_err(|_| TvfFieldError::new(parent, tags, $tvf_type)),
+            (Cow::Owned(parent), tag) => parent
+                .$getter(tag)
+                .map(|value| Cow::Owned(value.into_owned()))
+                .map_err(|_| TvfFieldError::new(&parent, tags, $tvf_type)),
+        }
+    }};
+}
+
+/// Extension of the [`Tvf`] trait with typed getters that report the tag path, the expected and the found type of the field in error.
+/// Implemented for every TVF.
+///
+/// Every getter take a [`TvfPath`]: a tag, or the tags of the nested buffers followed by the tag of the field.
+///
+/// ```
+/// use prosa_utils::msg::tvf::{Tvf, TvfExt, TvfType};
+/// use prosa_utils::msg::simple_string_tvf::SimpleStringTvf;
+///
+/// let mut tvf = SimpleStringTvf::default();
+/// tvf.put_string(1, "ProSA");
+/// tvf.insert_path(&[2, 1], |buffer, tag| buffer.put_unsigned(tag, 42)).unwrap();
+///
+/// assert_eq!("ProSA", tvf.require_str(1).unwrap().as_str());
+/// assert_eq!(42, tvf.require_unsigned([2, 1]).unwrap());
+///
+/// let err = tvf.require_unsigned(1).unwrap_err();
+/// assert_eq!(Some(TvfType::String), err.found());
+/// assert_eq!("The field `1` is a string instead of a unsigned", err.to_string());
+///
+/// let err = tvf.require_str([2, 3]).unwrap_err();
+/// assert!(err.is_missing());
+/// assert_eq!("2.3", err.path());
+/// ```
+pub trait TvfExt: Tvf + Default + Debug + Clone {
+    /// Method to get the nested buffer at the tag path (every tag of the path is a buffer).
+    /// An empty path return the TVF itself
+    fn get_path(&self, path: &[usize]) -> Result<Cow<'_, Self>, TvfFieldError> {
+        let mut buffer = Cow::Borrowed(self);
+        for (depth, tag) in path.iter().enumerate() {
+            buffer = match buffer {
+                Cow::Borrowed(parent) => parent
+                    .get_buffer(*tag)
+                    .map_err(|_| TvfFieldError::new(parent, &path[..=depth], TvfType::Buffer))?,
+                Cow::Owned(parent) => parent
+                    .get_buffer(*tag)
+                    .map(|buffer| Cow::Owned(buffer.into_owned()))
+                    .map_err(|_| TvfFieldError::new(&parent, &path[..=depth], TvfType::Buffer))?,
+            };
+        }
+
+        Ok(buffer)
+    }
+
+    /// Method to get the buffer that contain the field of the tag path, with the tag of the field
+    fn get_parent(
+        &self,
+        path: &[usize],
+        expected: TvfType,
+    ) -> Result<(Cow<'_, Self>, usize), TvfFieldError> {
+        if let Some((tag, parent_path)) = path.split_last() {
+            Ok((self.get_path(parent_path)?, *tag))
+        } else {
+            Err(TvfFieldError::Missing {
+                path: String::new(),
+                expected,
+            })
+        }
+    }
+
+    /// Method to know if the TVF contain a field at the tag path
+    fn contains_path(&self, path: &[usize]) -> bool {
+        path.split_last().is_some_and(|(tag, parent_path)| {
+            self.get_path(parent_path)
+                .is_ok_and(|parent| parent.contains(*tag))
+        })
+    }
+
+    /// Method to put a field at the tag path, creating the intermediate buffers if needed.
+    /// The `put` function is called with the buffer that must contain the field and the tag of the field
+    fn insert_path<F>(&mut self, path: &[usize], put: F) -> Result<(), TvfFieldError>
+    where
+        F: FnOnce(&mut Self, usize),
+    {
+        insert_at(self, path, 0, put)
+    }
+
+    /// Get a sub buffer
+    fn require_buffer<P: TvfPath>(&self, path: P) -> Result<Cow<'_, Self>, TvfFieldError> {
+        self.get_path(path.tags())
+    }
+
+    /// Get an unsigned value
+    fn require_unsigned<P: TvfPath>(&self, path: P) -> Result<u64, TvfFieldError> {
+        require_value!(self, path, get_unsigned, TvfType::Unsigned)
+    }
+
+    /// Get a signed value
+    fn require_signed<P: TvfPath>(&self, path: P) -> Result<i64, TvfFieldError> {
+        require_value!(self, path, get_signed, TvfType::Signed)
+    }
+
+    /// Get a byte value
+    fn require_byte<P: TvfPath>(&self, path: P) -> Result<u8, TvfFieldError> {
+        require_value!(self, path, get_byte, TvfType::Byte)
+    }
+
+    /// Get a float value
+    fn require_float<P: TvfPath>(&self, path: P) -> Result<f64, TvfFieldError> {
+        require_value!(self, path, get_float, TvfType::Float)
+    }
+
+    /// Get a string value
+    fn require_str<P: TvfPath>(&self, path: P) -> Result<Cow<'_, String>, TvfFieldError> {
+        require_ref!(self, path, get_string, TvfType::String)
+    }
+
+    /// Get a buffer of bytes
+    fn require_bytes<P: TvfPath>(&self, path: P) -> Result<Cow<'_, Bytes>, TvfFieldError> {
+        require_ref!(self, path, get_bytes, TvfType::Bytes)
+    }
+
+    /// Get a date
+    fn require_date<P: TvfPath>(&self, path: P) -> Result<NaiveDate, TvfFieldError> {
+        require_value!(self, path, get_date, TvfType::Date)
+    }
+
+    /// Get a datetime (considered to be UTC)
+    fn require_datetime<P: TvfPath>(&self, path: P) -> Result<NaiveDateTime, TvfFieldError> {
+        require_value!(self, path, get_datetime, TvfType::DateTime)
+    }
+}
+
+impl<T> TvfExt for T where T: Tvf + Default + Debug + Clone {}
+
+/// Method to put a field at a tag path from the depth of the TVF, creating the intermediate buffers if needed
+fn insert_at<T, F>(tvf: &mut T, path: &[usize], depth: usize, put: F) -> Result<(), TvfFieldError>
+where
+    T: Tvf + Default + Debug + Clone,
+    F: FnOnce(&mut T, usize),
+{
+    match &path[depth..] {
+        [] => Err(TvfFieldError::Missing {
+            path: String::new(),
+            expected: TvfType::Buffer,
+        }),
+        [tag] => {
+            put(tvf, *tag);
+            Ok(())
+        }
+        [tag, ..] => {
+            let mut buffer = if tvf.contains(*tag) {
+                tvf.get_buffer(*tag)
+                    .map_err(|_| TvfFieldError::new(tvf, &path[..=depth], TvfType::Buffer))?
+                    .into_owned()
+            } else {
+                T::default()
+            };
+            insert_at(&mut buffer, path, depth + 1, put)?;
+            tvf.put_buffer(*tag, buffer);
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::simple_string_tvf::SimpleStringTvf;
+    use super::*;
+
+    /// Visit recursively all fields of a TVF, with their tag path
+    fn visit<T>(tvf: &T, path: &str, visited: &mut Vec<(String, TvfType)>)
+    where
+        T: Tvf + Default + Debug + Clone,
+    {
+        for (id, value) in tvf.entries() {
+            let field_path = if path.is_empty() {
+                id.to_string()
+            } else {
+                format!("{}.{}", path, id)
+            };
+            visited.push((field_path.clone(), value.get_type()));
+            if let TvfTypedRef::Buffer(buffer) = value {
+                visit(buffer.as_ref(), &field_path, visited);
+            }
+        }
+    }
+
+    #[test]
+    fn test_tvf_entries() {
+        let mut sub_sub_tvf = SimpleStringTvf::default();
+        sub_sub_tvf.put_string(1, "deep");
+
+        let mut sub_tvf = SimpleStringTvf::default();
+        sub_tvf.put_unsigned(1, 42);
+        sub_tvf.put_buffer(2, sub_sub_tvf);
+
+        let mut tvf = SimpleStringTvf::default();
+        tvf.put_string(1, "ProSA");
+        tvf.put_buffer(3, sub_tvf);
+        tvf.put_string(10, "");
+
+        let mut tags: Vec<usize> = tvf.tags().collect();
+        tags.sort_unstable();
+        assert_eq!(vec![1, 3, 10], tags);
+
+        let mut visited = Vec::new();
+        visit(&tvf, "", &mut visited);
+        visited.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(
+            vec![
+                (String::from("1"), TvfType::String),
+                (String::from("10"), TvfType::String),
+                (String::from("3"), TvfType::Buffer),
+                (String::from("3.1"), TvfType::String),
+                (String::from("3.2"), TvfType::Buffer),
+                (String::from("3.2.1"), TvfType::String),
+            ],
+            visited
+        );
+
+        let (_, value) = tvf.entries().find(|(id, _)| *id == 1).unwrap();
+        assert!(matches!(value, TvfTypedRef::String(Cow::Borrowed(_))));
+        assert_eq!("ProSA", value.to_string());
+    }
+
+    #[test]
+    fn test_tvf_ext_missing() {
+        let mut tvf = SimpleStringTvf::default();
+        tvf.put_string(1, "ProSA");
+
+        let err = tvf.require_unsigned(12).unwrap_err();
+        assert!(err.is_missing());
+        assert_eq!(
+            TvfFieldError::Missing {
+                path: String::from("12"),
+                expected: TvfType::Unsigned
+            },
+            err
+        );
+        assert_eq!(
+            "The field `12` is missing (expected unsigned)",
+            err.to_string()
+        );
+
+        // Missing intermediate buffer
+        let err = tvf.require_str([3, 1, 2]).unwrap_err();
+        assert_eq!("3", err.path());
+        assert_eq!(TvfType::Buffer, err.expected());
+        assert!(!tvf.contains_path(&[3, 1, 2]));
+        assert!(tvf.contains_path(&[1]));
+        assert!(!tvf.contains_path(&[]));
+    }
+
+    #[test]
+    fn test_tvf_ext_wrong_type() {
+        let mut tvf = SimpleStringTvf::default();
+        tvf.put_string(12, "ProSA");
+        tvf.put_unsigned(13, 42);
+
+        let err = tvf.require_unsigned(12).unwrap_err();
+        assert!(!err.is_missing());
+        assert_eq!(
+            TvfFieldError::WrongType {
+                path: String::from("12"),
+                expected: TvfType::Unsigned,
+                found: Some(TvfType::String)
+            },
+            err
+        );
+        assert_eq!(
+            "The field `12` is a string instead of a unsigned",
+            err.to_string()
+        );
+        assert_eq!(42, tvf.require_unsigned(13).unwrap());
+        assert_eq!(42, tvf.require_signed(13).unwrap());
+    }
+
+    #[test]
+    fn test_tvf_ext_deep_path() {
+        let mut tvf = SimpleStringTvf::default();
+        tvf.insert_path(&[1, 2, 3], |buffer, tag| buffer.put_string(tag, "deep"))
+            .unwrap();
+        tvf.insert_path(&[1, 2, 4], |buffer, tag| buffer.put_unsigned(tag, 42))
+            .unwrap();
+        tvf.insert_path(&[1, 5], |buffer, tag| {
+            buffer.put_bytes(tag, Bytes::from_static(b"bytes"))
+        })
+        .unwrap();
+
+        assert_eq!("deep", tvf.require_str([1, 2, 3]).unwrap().as_str());
+        let path: &[usize] = &[1, 2, 4];
+        assert_eq!(42, tvf.require_unsigned(path).unwrap());
+        assert_eq!(
+            b"bytes".as_slice(),
+            tvf.require_bytes(vec![1, 5]).unwrap().as_ref()
+        );
+        assert_eq!(2, tvf.get_path(&[1]).unwrap().len());
+        assert_eq!(
+            "deep",
+            tvf.get_path(&[1, 2])
+                .unwrap()
+                .require_str(3)
+                .unwrap()
+                .as_str()
+        );
+        assert!(tvf.contains_path(&[1, 2, 4]));
+
+        let err = tvf.require_float([1, 2, 6]).unwrap_err();
+        assert!(err.is_missing());
+        assert_eq!("1.2.6", err.path());
+        assert_eq!(
+            "The field `1.2.6` is missing (expected float)",
+            err.to_string()
+        );
+        assert!(tvf.insert_path(&[], |_, _| {}).is_err());
+    }
+}
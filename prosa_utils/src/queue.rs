@@ -0,0 +1,5 @@
+//! Module for queues used to dispatch messages between tasks
+
+pub mod keyed;
+
+pub mod lockfree;
@@ -0,0 +1,412 @@
+//! Queue that keep the order of the items of a same key, while items of different keys can be consumed in parallel
+//!
+//! Processors that multiplex many sessions over one queue can use it to process every session in order with several workers.
+//! A worker claims the pending items of a key with a [`KeyedGuard`], and no other worker can consume this key until the guard is released.
+//!
+//! ```
+//! use prosa_utils::queue::keyed::KeyedQueue;
+//!
+//! # #[tokio::main(flavor = "current_thread")]
+//! # async fn main() {
+//! let queue = KeyedQueue::new();
+//! queue.push("session_1", 1).unwrap();
+//! queue.push("session_2", 1).unwrap();
+//! queue.push("session_1", 2).unwrap();
+//!
+//! // The first worker get all the items of the first session, in order
+//! let session_1 = queue.consume().await.unwrap();
+//! assert_eq!("session_1", *session_1.key());
+//!
+//! // An other worker can process the second session at the same time
+//! let mut session_2 = queue.try_consume().unwrap();
+//! assert_eq!(Some(1), session_2.next());
+//!
+//! assert_eq!(vec![1, 2], session_1.collect::<Vec<_>>());
+//! # }
+//! ```
+
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt,
+    hash::Hash,
+    pin::pin,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use thiserror::Error;
+use tokio::sync::Notify;
+
+/// Error define for keyed queues
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum KeyedQueueError<T> {
+    /// Error when an item is pushed to a closed queue. The item is given back
+    #[error("The keyed queue is closed")]
+    Closed(T),
+}
+
+/// Pending items of a key
+struct KeyRun<T> {
+    items: VecDeque<T>,
+    claimed: bool,
+}
+
+impl<T> Default for KeyRun<T> {
+    fn default() -> Self {
+        KeyRun {
+            items: VecDeque::new(),
+            claimed: false,
+        }
+    }
+}
+
+/// Shared state of the queue
+///
+/// Every key of `ready` have pending items and is not claimed by a worker.
+struct KeyedState<K, T> {
+    runs: HashMap<K, KeyRun<T>>,
+    ready: VecDeque<K>,
+    len: usize,
+    closed: bool,
+}
+
+struct KeyedInner<K, T> {
+    state: Mutex<KeyedState<K, T>>,
+    notify: Notify,
+}
+
+/// Multi producer multi consumer queue with a per key ordering guarantee
+///
+/// The queue can be cloned to be shared between producers and workers.
+pub struct KeyedQueue<K, T> {
+    inner: Arc<KeyedInner<K, T>>,
+}
+
+impl<K, T> KeyedQueue<K, T>
+where
+    K: Eq + Hash + Clone,
+{
+    /// Method to create an empty keyed queue
+    pub fn new() -> KeyedQueue<K, T> {
+        KeyedQueue {
+            inner: Arc::new(KeyedInner {
+                state: Mutex::new(KeyedState {
+                    runs: HashMap::new(),
+                    ready: VecDeque::new(),
+                    len: 0,
+                    closed: false,
+                }),
+                notify: Notify::new(),
+            }),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, KeyedState<K, T>> {
+        self.inner
+            .state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Method to push an item after the pending items of its key
+    pub fn push(&self, key: K, item: T) -> Result<(), KeyedQueueError<T>> {
+        let mut guard = self.lock();
+        let state = &mut *guard;
+        if state.closed {
+            return Err(KeyedQueueError::Closed(item));
+        }
+
+        let run = state.runs.entry(key.clone()).or_default();
+        run.items.push_back(item);
+        let newly_ready = !run.claimed && run.items.len() == 1;
+        if newly_ready {
+            state.ready.push_back(key);
+        }
+        state.len += 1;
+        drop(guard);
+
+        if newly_ready {
+            self.inner.notify.notify_one();
+        }
+
+        Ok(())
+    }
+
+    /// Method to claim the pending items of a key, if any key is available
+    pub fn try_consume(&self) -> Option<KeyedGuard<K, T>> {
+        let mut guard = self.lock();
+        let state = &mut *guard;
+        let key = state.ready.pop_front()?;
+        if let Some(run) = state.runs.get_mut(&key) {
+            run.claimed = true;
+        }
+
+        Some(KeyedGuard {
+            queue: self.clone(),
+            key,
+        })
+    }
+
+    /// Method to wait for a key with pending items and claim them
+    ///
+    /// Return `None` once the queue is closed and all its items are consumed
+    pub async fn consume(&self) -> Option<KeyedGuard<K, T>> {
+        loop {
+            let mut notified = pin!(self.inner.notify.notified());
+            notified.as_mut().enable();
+
+            if let Some(guard) = self.try_consume() {
+                return Some(guard);
+            }
+
+            {
+                let state = self.lock();
+                if state.closed && state.len == 0 {
+                    return None;
+                }
+            }
+
+            notified.await;
+        }
+    }
+
+    /// Method to close the queue. No more items can be pushed, but pending items can still be consumed
+    pub fn close(&self) {
+        self.lock().closed = true;
+        self.inner.notify.notify_waiters();
+    }
+
+    /// Method to know if the queue is closed
+    pub fn is_closed(&self) -> bool {
+        self.lock().closed
+    }
+
+    /// Getter of the number of pending items (claimed or not)
+    pub fn len(&self) -> usize {
+        self.lock().len
+    }
+
+    /// Method to know if the queue don't have any pending item
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<K, T> Default for KeyedQueue<K, T>
+where
+    K: Eq + Hash + Clone,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, T> Clone for KeyedQueue<K, T> {
+    fn clone(&self) -> Self {
+        KeyedQueue {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<K, T> fmt::Debug for KeyedQueue<K, T>
+where
+    K: Eq + Hash + Clone,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.lock();
+        f.debug_struct("KeyedQueue")
+            .field("keys", &state.runs.len())
+            .field("len", &state.len)
+            .field("closed", &state.closed)
+            .finish()
+    }
+}
+
+/// Claim of a key of a [`KeyedQueue`]
+///
+/// Iterate over the guard to get the pending items of the key in order (including the ones pushed while the key is claimed).
+/// When the guard is dropped, the key is released and its remaining items can be claimed by an other worker.
+pub struct KeyedGuard<K, T>
+where
+    K: Eq + Hash + Clone,
+{
+    queue: KeyedQueue<K, T>,
+    key: K,
+}
+
+impl<K, T> KeyedGuard<K, T>
+where
+    K: Eq + Hash + Clone,
+{
+    /// Getter of the claimed key
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+}
+
+impl<K, T> Iterator for KeyedGuard<K, T>
+where
+    K: Eq + Hash + Clone,
+{
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        let mut guard = self.queue.lock();
+        let state = &mut *guard;
+        let item = state.runs.get_mut(&self.key)?.items.pop_front();
+        if item.is_some() {
+            state.len -= 1;
+        }
+
+        item
+    }
+}
+
+impl<K, T> Drop for KeyedGuard<K, T>
+where
+    K: Eq + Hash + Clone,
+{
+    fn drop(&mut self) {
+        let mut guard = self.queue.lock();
+        let state = &mut *guard;
+        let mut ready = false;
+        if let Some(run) = state.runs.get_mut(&self.key) {
+            if run.items.is_empty() {
+                state.runs.remove(&self.key);
+            } else {
+                run.claimed = false;
+                state.ready.push_back(self.key.clone());
+                ready = true;
+            }
+        }
+        let drained = state.closed && state.len == 0;
+        drop(guard);
+
+        if ready {
+            self.queue.inner.notify.notify_one();
+        } else if drained {
+            // Wake up the workers waiting for the end of the queue
+            self.queue.inner.notify.notify_waiters();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, time::Duration};
+
+    use super::*;
+
+    #[test]
+    fn keyed_queue_claim() {
+        let queue = KeyedQueue::new();
+        queue.push('a', 1).unwrap();
+        queue.push('a', 2).unwrap();
+        queue.push('b', 1).unwrap();
+        assert_eq!(3, queue.len());
+
+        let mut guard_a = queue.try_consume().unwrap();
+        assert_eq!('a', *guard_a.key());
+        let guard_b = queue.try_consume().unwrap();
+        assert_eq!('b', *guard_b.key());
+
+        // A claimed key can't be consumed by an other worker, even with new items
+        queue.push('a', 3).unwrap();
+        assert!(queue.try_consume().is_none());
+        assert_eq!(Some(1), guard_a.next());
+
+        // Remaining items are available once the key is released
+        drop(guard_a);
+        assert_eq!(
+            vec![2, 3],
+            queue.try_consume().unwrap().collect::<Vec<i32>>()
+        );
+        assert_eq!(vec![1], guard_b.collect::<Vec<i32>>());
+        assert!(queue.is_empty());
+        assert!(queue.try_consume().is_none());
+
+        queue.close();
+        assert_eq!(Err(KeyedQueueError::Closed(4)), queue.push('a', 4));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn keyed_queue_stress() {
+        const PRODUCERS: usize = 8;
+        const WORKERS: usize = 8;
+        const ITEMS: usize = 2000;
+        const KEYS: usize = 32;
+
+        let queue = KeyedQueue::<usize, (usize, usize)>::new();
+        let in_flight = Arc::new(Mutex::new(HashSet::new()));
+        let consumed = Arc::new(Mutex::new(HashMap::<usize, Vec<(usize, usize)>>::new()));
+
+        let mut workers = Vec::with_capacity(WORKERS);
+        for _ in 0..WORKERS {
+            let queue = queue.clone();
+            let in_flight = in_flight.clone();
+            let consumed = consumed.clone();
+            workers.push(tokio::spawn(async move {
+                while let Some(mut guard) = queue.consume().await {
+                    let key = *guard.key();
+                    assert!(
+                        in_flight.lock().unwrap().insert(key),
+                        "key {key} consumed by two workers"
+                    );
+
+                    for item in &mut guard {
+                        consumed.lock().unwrap().entry(key).or_default().push(item);
+                        if item.1 % 7 == 0 {
+                            tokio::task::yield_now().await;
+                        }
+                    }
+
+                    in_flight.lock().unwrap().remove(&key);
+                }
+            }));
+        }
+
+        let mut producers = Vec::with_capacity(PRODUCERS);
+        for producer in 0..PRODUCERS {
+            let queue = queue.clone();
+            producers.push(tokio::spawn(async move {
+                for seq in 0..ITEMS {
+                    queue
+                        .push((producer * 7 + seq) % KEYS, (producer, seq))
+                        .unwrap();
+                    if seq % 13 == 0 {
+                        tokio::task::yield_now().await;
+                    }
+                }
+            }));
+        }
+
+        for producer in producers {
+            producer.await.unwrap();
+        }
+        queue.close();
+        tokio::time::timeout(Duration::from_secs(30), async {
+            for worker in workers {
+                worker.await.unwrap();
+            }
+        })
+        .await
+        .expect("Workers didn't end after the queue close");
+
+        // Every item is consumed once, in the order of its producer for each key
+        let consumed = consumed.lock().unwrap();
+        assert_eq!(
+            PRODUCERS * ITEMS,
+            consumed.values().map(|items| items.len()).sum::<usize>()
+        );
+        for (key, items) in consumed.iter() {
+            let mut last_seq = HashMap::new();
+            for (producer, seq) in items {
+                assert_eq!(*key, (producer * 7 + seq) % KEYS);
+                if let Some(last) = last_seq.insert(producer, seq) {
+                    assert!(last < seq, "key {key}: {producer}/{seq} after {last}");
+                }
+            }
+        }
+        assert!(queue.is_empty());
+    }
+}
@@ -0,0 +1,460 @@
+//! Bounded lock-free queue, to exchange items between tasks without lock on the hot path
+//!
+//! The [`LockFreeQueue`] is a fixed capacity ring buffer where producers and consumers only synchronize with atomic operations.
+//! The [`channel`] adds an async notification layer over it, so a consumer can wait for items and producers can wait for room, like a tokio mpsc channel.
+//!
+//! ```
+//! use prosa_utils::queue::lockfree::{self, LockFreeQueueError};
+//!
+//! # #[tokio::main(flavor = "current_thread")]
+//! # async fn main() {
+//! let (tx, mut rx) = lockfree::channel(2);
+//! tx.send(1).await.unwrap();
+//! tx.try_send(2).unwrap();
+//! assert_eq!(Err(LockFreeQueueError::Full(3)), tx.try_send(3));
+//!
+//! assert_eq!(Some(1), rx.recv().await);
+//! assert_eq!(Some(2), rx.try_recv());
+//!
+//! // Once every sender is dropped, the remaining items are received then the channel is closed
+//! tx.send(3).await.unwrap();
+//! drop(tx);
+//! assert_eq!(Some(3), rx.recv().await);
+//! assert_eq!(None, rx.recv().await);
+//! # }
+//! ```
+
+use std::{
+    cell::UnsafeCell,
+    fmt,
+    mem::MaybeUninit,
+    ops::Deref,
+    pin::pin,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use thiserror::Error;
+use tokio::sync::Notify;
+
+/// Error define for lock-free queues
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum LockFreeQueueError<T> {
+    /// Error when an item is pushed to a full queue. The item is given back
+    #[error("The lock-free queue is full")]
+    Full(T),
+    /// Error when an item is pushed to a queue without receiver. The item is given back
+    #[error("The lock-free queue is closed")]
+    Closed(T),
+}
+
+/// Value aligned on its own cache line, so the atomics of the producers and the consumers don't invalidate each other
+#[repr(align(64))]
+struct CachePadded<T>(T);
+
+impl<T> Deref for CachePadded<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+/// Slot of the ring buffer
+///
+/// Its sequence tells the position that can use it: it's equal to twice the enqueue position when the slot is free, and to twice the dequeue position + 1 when the slot holds an item.
+/// Doubling the positions keeps both states distinct even with a single slot.
+struct Slot<T> {
+    sequence: AtomicUsize,
+    value: UnsafeCell<MaybeUninit<T>>,
+}
+
+/// Bounded multi producer multi consumer queue, based on atomic sequences on a ring buffer
+pub struct LockFreeQueue<T> {
+    buffer: Box<[Slot<T>]>,
+    enqueue_pos: CachePadded<AtomicUsize>,
+    dequeue_pos: CachePadded<AtomicUsize>,
+}
+
+// Items are moved between threads through the slots, whose access is guarded by their sequence
+unsafe impl<T: Send> Send for LockFreeQueue<T> {}
+unsafe impl<T: Send> Sync for LockFreeQueue<T> {}
+
+impl<T> LockFreeQueue<T> {
+    /// Method to create an empty queue that can hold `capacity` items
+    ///
+    /// # Panics
+    ///
+    /// Panics if the capacity is 0
+    pub fn new(capacity: usize) -> LockFreeQueue<T> {
+        assert!(
+            capacity > 0,
+            "the lock-free queue capacity must be positive"
+        );
+        LockFreeQueue {
+            buffer: (0..capacity)
+                .map(|i| Slot {
+                    sequence: AtomicUsize::new(i.wrapping_mul(2)),
+                    value: UnsafeCell::new(MaybeUninit::uninit()),
+                })
+                .collect(),
+            enqueue_pos: CachePadded(AtomicUsize::new(0)),
+            dequeue_pos: CachePadded(AtomicUsize::new(0)),
+        }
+    }
+
+    /// Method to push an item at the end of the queue. The item is given back if the queue is full
+    pub fn push(&self, item: T) -> Result<(), T> {
+        let mut pos = self.enqueue_pos.load(Ordering::Relaxed);
+        loop {
+            let slot = &self.buffer[pos % self.buffer.len()];
+            let sequence = slot.sequence.load(Ordering::Acquire);
+            match (sequence as isize).wrapping_sub(pos.wrapping_mul(2) as isize) {
+                0 => match self.enqueue_pos.compare_exchange_weak(
+                    pos,
+                    pos.wrapping_add(1),
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => {
+                        // The position is reserved, no other producer or consumer can access the slot until its sequence is released
+                        unsafe { (*slot.value.get()).write(item) };
+                        slot.sequence
+                            .store(pos.wrapping_mul(2).wrapping_add(1), Ordering::Release);
+                        return Ok(());
+                    }
+                    Err(current) => pos = current,
+                },
+                // The slot still holds the item of the previous turn
+                diff if diff < 0 => return Err(item),
+                // An other producer took the position
+                _ => pos = self.enqueue_pos.load(Ordering::Relaxed),
+            }
+        }
+    }
+
+    /// Method to pop the first item of the queue, if any
+    pub fn pop(&self) -> Option<T> {
+        let mut pos = self.dequeue_pos.load(Ordering::Relaxed);
+        loop {
+            let slot = &self.buffer[pos % self.buffer.len()];
+            let sequence = slot.sequence.load(Ordering::Acquire);
+            match (sequence as isize).wrapping_sub(pos.wrapping_mul(2).wrapping_add(1) as isize) {
+                0 => match self.dequeue_pos.compare_exchange_weak(
+                    pos,
+                    pos.wrapping_add(1),
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => {
+                        // The position is reserved and the item was released by its producer
+                        let item = unsafe { (*slot.value.get()).assume_init_read() };
+                        slot.sequence.store(
+                            pos.wrapping_add(self.buffer.len()).wrapping_mul(2),
+                            Ordering::Release,
+                        );
+                        return Some(item);
+                    }
+                    Err(current) => pos = current,
+                },
+                // The slot is not filled yet
+                diff if diff < 0 => return None,
+                // An other consumer took the position
+                _ => pos = self.dequeue_pos.load(Ordering::Relaxed),
+            }
+        }
+    }
+
+    /// Getter of the number of items in the queue (approximate if the queue is used concurrently)
+    pub fn len(&self) -> usize {
+        let dequeue_pos = self.dequeue_pos.load(Ordering::Relaxed);
+        let enqueue_pos = self.enqueue_pos.load(Ordering::Relaxed);
+        enqueue_pos.wrapping_sub(dequeue_pos).min(self.buffer.len())
+    }
+
+    /// Method to know if the queue is empty (approximate if the queue is used concurrently)
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Getter of the maximum number of items of the queue
+    pub fn capacity(&self) -> usize {
+        self.buffer.len()
+    }
+}
+
+impl<T> Drop for LockFreeQueue<T> {
+    fn drop(&mut self) {
+        while self.pop().is_some() {}
+    }
+}
+
+impl<T> fmt::Debug for LockFreeQueue<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LockFreeQueue")
+            .field("len", &self.len())
+            .field("capacity", &self.capacity())
+            .finish()
+    }
+}
+
+/// Shared state of a lock-free channel
+struct Chan<T> {
+    queue: LockFreeQueue<T>,
+    /// Notified when an item is pushed, or when the last sender is dropped
+    recv_notify: Notify,
+    /// Notified when an item is popped, or when the receiver is dropped
+    send_notify: Notify,
+    senders: AtomicUsize,
+    closed: AtomicBool,
+}
+
+/// Method to create a bounded lock-free channel, with a [`LockFreeQueue`] of `capacity` items
+///
+/// # Panics
+///
+/// Panics if the capacity is 0
+pub fn channel<T>(capacity: usize) -> (Sender<T>, Receiver<T>) {
+    let chan = Arc::new(Chan {
+        queue: LockFreeQueue::new(capacity),
+        recv_notify: Notify::new(),
+        send_notify: Notify::new(),
+        senders: AtomicUsize::new(1),
+        closed: AtomicBool::new(false),
+    });
+
+    (Sender { chan: chan.clone() }, Receiver { chan })
+}
+
+/// Sending side of a lock-free channel, that can be cloned to have several producers
+pub struct Sender<T> {
+    chan: Arc<Chan<T>>,
+}
+
+impl<T> Sender<T> {
+    /// Method to send an item, waiting for room if the queue is full
+    pub async fn send(&self, mut item: T) -> Result<(), LockFreeQueueError<T>> {
+        loop {
+            // Register for the room notification before trying, to not miss a pop in between
+            let mut notified = pin!(self.chan.send_notify.notified());
+            notified.as_mut().enable();
+            match self.try_send(item) {
+                Err(LockFreeQueueError::Full(full_item)) => item = full_item,
+                res => return res,
+            }
+
+            notified.await;
+        }
+    }
+
+    /// Method to send an item without waiting if the queue is full
+    pub fn try_send(&self, item: T) -> Result<(), LockFreeQueueError<T>> {
+        if self.chan.closed.load(Ordering::Acquire) {
+            return Err(LockFreeQueueError::Closed(item));
+        }
+
+        self.chan
+            .queue
+            .push(item)
+            .map_err(LockFreeQueueError::Full)?;
+        self.chan.recv_notify.notify_one();
+        Ok(())
+    }
+
+    /// Method to know if the receiver of the channel is dropped
+    pub fn is_closed(&self) -> bool {
+        self.chan.closed.load(Ordering::Acquire)
+    }
+
+    /// Getter of the number of items pending in the channel
+    pub fn len(&self) -> usize {
+        self.chan.queue.len()
+    }
+
+    /// Method to know if the channel is empty
+    pub fn is_empty(&self) -> bool {
+        self.chan.queue.is_empty()
+    }
+
+    /// Getter of the maximum number of items of the channel
+    pub fn capacity(&self) -> usize {
+        self.chan.queue.capacity()
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        self.chan.senders.fetch_add(1, Ordering::Relaxed);
+        Sender {
+            chan: self.chan.clone(),
+        }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        if self.chan.senders.fetch_sub(1, Ordering::AcqRel) == 1 {
+            // Wake the receiver so it sees the channel closed
+            self.chan.recv_notify.notify_one();
+        }
+    }
+}
+
+impl<T> fmt::Debug for Sender<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Sender")
+            .field("queue", &self.chan.queue)
+            .field("closed", &self.is_closed())
+            .finish()
+    }
+}
+
+/// Receiving side of a lock-free channel
+pub struct Receiver<T> {
+    chan: Arc<Chan<T>>,
+}
+
+impl<T> Receiver<T> {
+    /// Method to receive the next item, waiting for one if the queue is empty.
+    /// Return `None` once every sender is dropped and the queue is empty
+    pub async fn recv(&mut self) -> Option<T> {
+        loop {
+            if let Some(item) = self.try_recv() {
+                return Some(item);
+            }
+
+            if self.chan.senders.load(Ordering::Acquire) == 0 {
+                // An item may have been pushed by the last sender before its drop
+                return self.try_recv();
+            }
+
+            // A push done since the try is kept as a notify permit, so it can't be missed
+            self.chan.recv_notify.notified().await;
+        }
+    }
+
+    /// Method to receive the next item without waiting if the queue is empty
+    pub fn try_recv(&mut self) -> Option<T> {
+        let item = self.chan.queue.pop()?;
+        self.chan.send_notify.notify_one();
+        Some(item)
+    }
+
+    /// Getter of the number of items pending in the channel
+    pub fn len(&self) -> usize {
+        self.chan.queue.len()
+    }
+
+    /// Method to know if the channel is empty
+    pub fn is_empty(&self) -> bool {
+        self.chan.queue.is_empty()
+    }
+
+    /// Getter of the maximum number of items of the channel
+    pub fn capacity(&self) -> usize {
+        self.chan.queue.capacity()
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        self.chan.closed.store(true, Ordering::Release);
+        self.chan.send_notify.notify_waiters();
+    }
+}
+
+impl<T> fmt::Debug for Receiver<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Receiver")
+            .field("queue", &self.chan.queue)
+            .field("senders", &self.chan.senders.load(Ordering::Relaxed))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, time::Duration};
+
+    use super::*;
+
+    #[test]
+    fn lockfree_queue_wrap() {
+        let queue = LockFreeQueue::new(3);
+        for turn in 0..10 {
+            assert_eq!(Ok(()), queue.push(turn * 2));
+            assert_eq!(Ok(()), queue.push(turn * 2 + 1));
+            assert_eq!(2, queue.len());
+            assert_eq!(Some(turn * 2), queue.pop());
+            assert_eq!(Some(turn * 2 + 1), queue.pop());
+            assert_eq!(None, queue.pop());
+        }
+
+        for i in 0..3 {
+            queue.push(i).unwrap();
+        }
+        assert_eq!(Err(3), queue.push(3));
+        assert_eq!(3, queue.len());
+
+        // Remaining items are dropped with the queue
+        let item = Arc::new(());
+        let queue = LockFreeQueue::new(2);
+        queue.push(item.clone()).unwrap();
+        drop(queue);
+        assert_eq!(1, Arc::strong_count(&item));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn lockfree_channel_producers() {
+        const PRODUCERS: usize = 4;
+        const ITEMS: usize = 10_000;
+
+        // A small capacity make producers wait for room
+        let (tx, mut rx) = channel(8);
+        let producers: Vec<_> = (0..PRODUCERS)
+            .map(|producer| {
+                let tx = tx.clone();
+                tokio::spawn(async move {
+                    for i in 0..ITEMS {
+                        tx.send((producer, i)).await.unwrap();
+                    }
+                })
+            })
+            .collect();
+        drop(tx);
+
+        // Items of a producer are received in order
+        let mut next: HashMap<usize, usize> = HashMap::new();
+        let mut count = 0;
+        while let Some((producer, i)) = rx.recv().await {
+            let expected = next.entry(producer).or_default();
+            assert_eq!(*expected, i);
+            *expected += 1;
+            count += 1;
+        }
+        assert_eq!(PRODUCERS * ITEMS, count);
+
+        for producer in producers {
+            producer.await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn lockfree_channel_closed() {
+        let (tx, rx) = channel(1);
+        tx.send(1).await.unwrap();
+
+        // A sender waiting for room is released when the receiver is dropped
+        let waiting_tx = tx.clone();
+        let waiting = tokio::spawn(async move { waiting_tx.send(2).await });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert!(!waiting.is_finished());
+
+        drop(rx);
+        assert_eq!(Err(LockFreeQueueError::Closed(2)), waiting.await.unwrap());
+        assert!(tx.is_closed());
+        assert_eq!(Err(LockFreeQueueError::Closed(3)), tx.try_send(3));
+    }
+}
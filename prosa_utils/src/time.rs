@@ -0,0 +1,276 @@
+//! Time provider, to control the time of the components that wait or measure durations
+//!
+//! Components that rely on the time (timeouts, regulators, caches with TTL, ...) take a [`Clock`] instead of calling `Instant::now()` or `tokio::time::sleep()` directly.
+//! They use the [`SystemClock`] by default, and their tests use a [`MockClock`] that only move forward when it's advanced, so they run instantly.
+//!
+//! ```
+//! use std::time::Duration;
+//! use prosa_utils::time::{Clock, Elapsed, MockClock};
+//!
+//! # #[tokio::main(flavor = "current_thread")]
+//! # async fn main() {
+//! let clock = MockClock::new();
+//! let start = clock.now();
+//!
+//! // Wait a never ending future for 30 seconds, without sleeping for real
+//! let timeout = tokio::spawn({
+//!     let clock = clock.clone();
+//!     async move { clock.timeout(Duration::from_secs(30), std::future::pending::<()>()).await }
+//! });
+//! while clock.waiters() == 0 {
+//!     tokio::task::yield_now().await;
+//! }
+//!
+//! clock.advance(Duration::from_secs(30));
+//! assert_eq!(Err(Elapsed), timeout.await.unwrap());
+//! assert_eq!(Duration::from_secs(30), clock.now() - start);
+//! # }
+//! ```
+
+use std::{
+    collections::HashMap,
+    fmt,
+    future::{poll_fn, Future},
+    pin::{pin, Pin},
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Waker},
+    time::{Duration, Instant},
+};
+
+use thiserror::Error;
+
+/// Error returned when a [`Clock::timeout`] deadline elapsed before the end of its future
+#[derive(Debug, Error, Clone, Copy, PartialEq, Eq)]
+#[error("deadline has elapsed")]
+pub struct Elapsed;
+
+/// Provider of the time, cheap to clone
+pub trait Clock: Clone + fmt::Debug + Send + Sync + 'static {
+    /// Getter of the current instant of the clock
+    fn now(&self) -> Instant;
+
+    /// Method to wait until the duration has elapsed on the clock
+    fn sleep(&self, duration: Duration) -> impl Future<Output = ()> + Send + 'static;
+
+    /// Method to wait the end of a future, at most for the duration on the clock
+    fn timeout<F>(
+        &self,
+        duration: Duration,
+        future: F,
+    ) -> impl Future<Output = Result<F::Output, Elapsed>> + Send
+    where
+        F: Future + Send,
+    {
+        let sleep = self.sleep(duration);
+        async move {
+            let mut future = pin!(future);
+            let mut sleep = pin!(sleep);
+            poll_fn(|cx| {
+                if let Poll::Ready(output) = future.as_mut().poll(cx) {
+                    Poll::Ready(Ok(output))
+                } else {
+                    sleep.as_mut().poll(cx).map(|_| Err(Elapsed))
+                }
+            })
+            .await
+        }
+    }
+}
+
+/// Clock of the system, backed by the tokio timer (so it follows the tokio paused time)
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        tokio::time::Instant::now().into_std()
+    }
+
+    fn sleep(&self, duration: Duration) -> impl Future<Output = ()> + Send + 'static {
+        tokio::time::sleep(duration)
+    }
+
+    async fn timeout<F>(&self, duration: Duration, future: F) -> Result<F::Output, Elapsed>
+    where
+        F: Future + Send,
+    {
+        tokio::time::timeout(duration, future)
+            .await
+            .map_err(|_| Elapsed)
+    }
+}
+
+/// Shared state of a mock clock
+#[derive(Debug)]
+struct MockState {
+    now: Instant,
+    next_sleep_id: u64,
+    /// Deadline and waker of the pending sleeps, by sleep id
+    waiters: HashMap<u64, (Instant, Waker)>,
+}
+
+/// Clock that only move forward when it's advanced, to test time related components without waiting
+///
+/// Its clones share the same time. Futures that sleep on it are registered as waiters, and are woken once the clock is advanced after their deadline.
+#[derive(Debug, Clone)]
+pub struct MockClock {
+    state: Arc<Mutex<MockState>>,
+}
+
+impl MockClock {
+    /// Method to create a mock clock, starting at the current instant
+    pub fn new() -> MockClock {
+        MockClock {
+            state: Arc::new(Mutex::new(MockState {
+                now: Instant::now(),
+                next_sleep_id: 0,
+                waiters: HashMap::new(),
+            })),
+        }
+    }
+
+    /// Method to move the clock forward, and wake the waiters whose deadline is reached
+    pub fn advance(&self, duration: Duration) {
+        let ready: Vec<_> = {
+            let mut state = self.state.lock().unwrap();
+            state.now += duration;
+            let now = state.now;
+            state
+                .waiters
+                .extract_if(|_, (deadline, _)| *deadline <= now)
+                .collect()
+        };
+
+        for (_, (_, waker)) in ready {
+            waker.wake();
+        }
+    }
+
+    /// Getter of the number of waiters registered on the clock (sleeps that wait for the clock to advance)
+    pub fn waiters(&self) -> usize {
+        self.state.lock().unwrap().waiters.len()
+    }
+}
+
+impl Default for MockClock {
+    fn default() -> Self {
+        MockClock::new()
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> Instant {
+        self.state.lock().unwrap().now
+    }
+
+    fn sleep(&self, duration: Duration) -> impl Future<Output = ()> + Send + 'static {
+        let mut state = self.state.lock().unwrap();
+        state.next_sleep_id += 1;
+        MockSleep {
+            state: self.state.clone(),
+            id: state.next_sleep_id,
+            deadline: state.now + duration,
+        }
+    }
+}
+
+/// Sleep of a [`MockClock`], ready once the clock reach its deadline
+struct MockSleep {
+    state: Arc<Mutex<MockState>>,
+    id: u64,
+    deadline: Instant,
+}
+
+impl Future for MockSleep {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.state.lock().unwrap();
+        if state.now >= self.deadline {
+            return Poll::Ready(());
+        }
+
+        // Register the waiter, or update its waker if the sleep is polled again
+        match state.waiters.get_mut(&self.id) {
+            Some((_, waker)) => waker.clone_from(cx.waker()),
+            None => {
+                state
+                    .waiters
+                    .insert(self.id, (self.deadline, cx.waker().clone()));
+            }
+        }
+
+        Poll::Pending
+    }
+}
+
+impl Drop for MockSleep {
+    fn drop(&mut self) {
+        // A dropped sleep (e.g. a completed timeout) doesn't wait anymore
+        if let Ok(mut state) = self.state.lock() {
+            state.waiters.remove(&self.id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn mock_clock_timeout() {
+        let clock = MockClock::new();
+        let start = clock.now();
+
+        // A future that end before the deadline is not timed out, and its sleep is unregistered
+        assert_eq!(
+            Ok(42),
+            clock.timeout(Duration::from_secs(3600), async { 42 }).await
+        );
+        assert_eq!(0, clock.waiters());
+
+        // An hour timeout is elapsed without sleeping for real
+        let real_start = Instant::now();
+        let timeout = tokio::spawn({
+            let clock = clock.clone();
+            async move {
+                clock
+                    .timeout(Duration::from_secs(3600), std::future::pending::<()>())
+                    .await
+            }
+        });
+        while clock.waiters() == 0 {
+            tokio::task::yield_now().await;
+        }
+
+        clock.advance(Duration::from_secs(1800));
+        tokio::task::yield_now().await;
+        assert!(!timeout.is_finished());
+        assert_eq!(1, clock.waiters());
+
+        clock.advance(Duration::from_secs(1800));
+        assert_eq!(Err(Elapsed), timeout.await.unwrap());
+        assert_eq!(0, clock.waiters());
+        assert_eq!(Duration::from_secs(3600), clock.now() - start);
+        assert!(real_start.elapsed() < Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn system_clock() {
+        let clock = SystemClock;
+        let start = clock.now();
+        clock.sleep(Duration::from_millis(10)).await;
+        assert!(clock.now() - start >= Duration::from_millis(10));
+
+        assert_eq!(
+            Err(Elapsed),
+            clock
+                .timeout(Duration::from_millis(10), std::future::pending::<()>())
+                .await
+        );
+        assert_eq!(
+            Ok(()),
+            clock.timeout(Duration::from_millis(10), async {}).await
+        );
+    }
+}